@@ -15,6 +15,12 @@ if isa == 'arm':
     cross = 'arm-buildroot-linux-musleabi-'
     crts0 = ['crt0.o', 'crtbegin.o']
     crtsn = ['crtend.o']
+elif isa == 'aarch64':
+    rustisa = isa
+    rustabi = 'musl'
+    cross = 'aarch64-buildroot-linux-musl-'
+    crts0 = ['crt0.o', 'crtbegin.o']
+    crtsn = ['crtend.o']
 elif isa == 'riscv':
     rustisa = 'riscv64'
     rustabi = 'musl'
@@ -82,6 +88,9 @@ class M3Env(Env):
             self['ASFLAGS'] += ['-msoft-float', '-mno-sse']
             self['CFLAGS'] += ['-msoft-float', '-mno-sse']
             self['CXXFLAGS'] += ['-msoft-float', '-mno-sse']
+        elif self['ISA'] == 'aarch64':
+            self['CFLAGS'] += ['-mgeneral-regs-only']
+            self['CXXFLAGS'] += ['-mgeneral-regs-only']
         elif self['ISA'] == 'riscv':
             self['ASFLAGS'] += ['-mabi=lp64']
             self['CFLAGS'] += ['-march=rv64imac', '-mabi=lp64']
@@ -181,6 +190,12 @@ class M3Env(Env):
             self['CRGFLAGS'] += ['--features', 'base/bench']
         if self['BUILD'] == 'coverage' and self['ISA'] == 'riscv':
             self['CRGFLAGS'] += ['--features', 'base/coverage']
+        if os.environ.get('M3_TRACE', '0') == '1':
+            self['CRGFLAGS'] += ['--features', 'base/trace']
+        if os.environ.get('M3_HEAPCHECK', '0') == '1':
+            self['CRGFLAGS'] += ['--features', 'heap/canaries']
+        if os.environ.get('M3_HEAPLEAKS', '0') == '1':
+            self['CRGFLAGS'] += ['--features', 'heap/leaks']
         self['CRGFLAGS'] += ['--features', 'base/' + self['TGT']]
 
     def rust_deps(self):
@@ -387,6 +402,11 @@ elif isa == 'arm':
     env['CXXFLAGS'] += ['-march=armv7-a']
     env['LINKFLAGS'] += ['-march=armv7-a']
     env['ASFLAGS'] += ['-march=armv7-a']
+elif isa == 'aarch64':
+    env['CFLAGS'] += ['-march=armv8-a']
+    env['CXXFLAGS'] += ['-march=armv8-a']
+    env['LINKFLAGS'] += ['-march=armv8-a']
+    env['ASFLAGS'] += ['-march=armv8-a']
 elif isa == 'riscv':
     env['CFLAGS'] += ['-march=rv64imafdc', '-mabi=lp64d']
     env['CXXFLAGS'] += ['-march=rv64imafdc', '-mabi=lp64d']
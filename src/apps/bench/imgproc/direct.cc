@@ -19,9 +19,7 @@
 #include <base/stream/Serial.h>
 #include <base/time/Instant.h>
 
-#include <m3/Syscalls.h>
-#include <m3/accel/StreamAccel.h>
-#include <m3/pipe/IndirectPipe.h>
+#include <m3/accel/StreamChain.h>
 #include <m3/stream/Standard.h>
 #include <m3/vfs/VFS.h>
 
@@ -30,7 +28,6 @@
 using namespace m3;
 
 static constexpr bool VERBOSE = 1;
-static constexpr size_t PIPE_SHM_SIZE = 512 * 1024;
 
 static const char *names[] = {
     "FFT",
@@ -38,119 +35,29 @@ static const char *names[] = {
     "IFFT",
 };
 
-class DirectChain {
-public:
-    static const size_t ACCEL_COUNT = 3;
-
-    explicit DirectChain(Pipes &pipesrv, size_t id, FileRef<GenericFile> &in,
-                         FileRef<GenericFile> &out, Mode _mode)
-        : mode(_mode),
-          acts(),
-          accels(),
-          pipes(),
-          mems() {
-        // create activities
-        for(size_t i = 0; i < ACCEL_COUNT; ++i) {
-            OStringStream name;
-            format_to(name, "{}{}"_cf, names[i], id);
-
-            if(VERBOSE)
-                println("Creating Activity {}"_cf, name.str());
-
-            tiles[i] = Tile::get("copy");
-            acts[i] = std::make_unique<ChildActivity>(tiles[i], name.str());
-
-            accels[i] = std::make_unique<StreamAccel>(acts[i], ACCEL_TIMES[i]);
-
-            if(mode == Mode::DIR_SIMPLE && i + 1 < ACCEL_COUNT) {
-                mems[i] =
-                    std::make_unique<MemCap>(MemCap::create_global(PIPE_SHM_SIZE, MemCap::RW));
-                pipes[i] = std::make_unique<IndirectPipe>(pipesrv, *mems[i], PIPE_SHM_SIZE);
-            }
-        }
+static const size_t ACCEL_COUNT = ARRAY_SIZE(names);
 
-        if(VERBOSE)
-            println("Connecting input and output..."_cf);
-
-        // connect input/output
-        accels[0]->connect_input(&*in);
-        accels[ACCEL_COUNT - 1]->connect_output(&*out);
-        for(size_t i = 0; i < ACCEL_COUNT; ++i) {
-            if(i > 0) {
-                if(mode == Mode::DIR_SIMPLE) {
-                    auto &rd = pipes[i - 1]->reader();
-                    accels[i]->connect_input(&rd);
-                }
-                else
-                    accels[i]->connect_input(accels[i - 1].get());
-            }
-            if(i + 1 < ACCEL_COUNT) {
-                if(mode == Mode::DIR_SIMPLE) {
-                    auto &wr = pipes[i]->writer();
-                    accels[i]->connect_output(&wr);
-                }
-                else
-                    accels[i]->connect_output(accels[i + 1].get());
-            }
-        }
-    }
-
-    void start() {
-        for(size_t i = 0; i < ACCEL_COUNT; ++i) {
-            acts[i]->start();
-            running[i] = true;
-        }
-    }
+static std::unique_ptr<StreamChain> create_chain(Pipes &pipes, size_t id, FileRef<GenericFile> &in,
+                                                 FileRef<GenericFile> &out, Mode mode) {
+    auto chain = mode == Mode::DIR_SIMPLE ? std::make_unique<StreamChain>(*in, *out, pipes)
+                                          : std::make_unique<StreamChain>(*in, *out);
 
-    void add_running(capsel_t *sels, size_t *count) {
-        for(size_t i = 0; i < ACCEL_COUNT; ++i) {
-            if(running[i])
-                sels[(*count)++] = acts[i]->sel();
-        }
-    }
-    void terminated(capsel_t act, int exitcode) {
-        for(size_t i = 0; i < ACCEL_COUNT; ++i) {
-            if(running[i] && acts[i]->sel() == act) {
-                if(exitcode != 0)
-                    eprintln("chain{} terminated with exit code {}"_cf, i, exitcode);
-                if(mode == Mode::DIR_SIMPLE) {
-                    if(pipes[i])
-                        pipes[i]->close_writer();
-                    if(i > 0 && pipes[i - 1])
-                        pipes[i - 1]->close_reader();
-                }
-                running[i] = false;
-                break;
-            }
-        }
-    }
+    for(size_t i = 0; i < ACCEL_COUNT; ++i) {
+        OStringStream name;
+        format_to(name, "{}{}"_cf, names[i], id);
 
-private:
-    Mode mode;
-    Reference<Tile> tiles[ACCEL_COUNT];
-    std::unique_ptr<ChildActivity> acts[ACCEL_COUNT];
-    std::unique_ptr<StreamAccel> accels[ACCEL_COUNT];
-    std::unique_ptr<IndirectPipe> pipes[ACCEL_COUNT];
-    std::unique_ptr<MemCap> mems[ACCEL_COUNT];
-    bool running[ACCEL_COUNT];
-};
-
-static void wait_for(std::unique_ptr<DirectChain> *chains, size_t num) {
-    for(size_t rem = num * DirectChain::ACCEL_COUNT; rem > 0; --rem) {
-        size_t count = 0;
-        capsel_t sels[num * DirectChain::ACCEL_COUNT];
-        for(size_t i = 0; i < num; ++i)
-            chains[i]->add_running(sels, &count);
+        if(VERBOSE)
+            println("Creating Activity {}"_cf, name.str());
 
-        const auto [exitcode, act] = Syscalls::activity_wait(sels, rem, 0);
-        for(size_t i = 0; i < num; ++i)
-            chains[i]->terminated(act, exitcode);
+        chain->append("copy", name.str(), ACCEL_TIMES[i]);
     }
+    return chain;
 }
 
 CycleDuration chain_direct(const char *in, size_t num, Mode mode) {
     Pipes pipes("pipes");
-    std::unique_ptr<DirectChain> chains[num];
+    std::unique_ptr<StreamChain> chains[num];
+    StreamChain *chain_ptrs[num];
     FileRef<GenericFile> infds[num];
     FileRef<GenericFile> outfds[num];
 
@@ -162,7 +69,8 @@ CycleDuration chain_direct(const char *in, size_t num, Mode mode) {
         infds[i] = VFS::open(in, FILE_R | FILE_NEWSESS);
         outfds[i] = VFS::open(outpath.str(), FILE_W | FILE_TRUNC | FILE_CREATE | FILE_NEWSESS);
 
-        chains[i] = std::make_unique<DirectChain>(pipes, i, infds[i], outfds[i], mode);
+        chains[i] = create_chain(pipes, i, infds[i], outfds[i], mode);
+        chain_ptrs[i] = chains[i].get();
     }
 
     if(VERBOSE)
@@ -173,15 +81,15 @@ CycleDuration chain_direct(const char *in, size_t num, Mode mode) {
     if(mode == Mode::DIR) {
         for(size_t i = 0; i < num; ++i)
             chains[i]->start();
-        wait_for(chains, num);
+        StreamChain::wait_all(chain_ptrs, num);
     }
     else {
         for(size_t i = 0; i < num / 2; ++i)
             chains[i]->start();
-        wait_for(chains, num / 2);
+        StreamChain::wait_all(chain_ptrs, num / 2);
         for(size_t i = num / 2; i < num; ++i)
             chains[i]->start();
-        wait_for(chains + num / 2, num / 2);
+        StreamChain::wait_all(chain_ptrs + num / 2, num / 2);
     }
 
     auto end = CycleInstant::now();
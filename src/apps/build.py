@@ -9,11 +9,14 @@ dirs = [
     'dosattack',
     'evilcompute',
     'faulter',
+    'fbdemo',
     'filterchain',
     'hashmuxtests',
     'hello',
+    'httpserver',
     'info',
     'libctest',
+    'logctl',
     'msgchan',
     'netechoserver',
     'noop',
@@ -21,6 +24,7 @@ dirs = [
     'ping',
     'queue',
     'resmngtest',
+    'rusage',
     'rusthello',
     'rustnettests',
     'ruststandalone',
@@ -22,12 +22,16 @@ use m3::errors::Error;
 use m3::test::{DefaultWvTester, WvTester};
 use m3::{println, wv_run_suite};
 
+mod tcache;
 mod tdisk;
+mod tpart;
 
 #[no_mangle]
 pub fn main() -> Result<(), Error> {
     let mut tester = DefaultWvTester::default();
     wv_run_suite!(tester, tdisk::run);
+    wv_run_suite!(tester, tcache::run);
+    wv_run_suite!(tester, tpart::run);
     println!("{}", tester);
     Ok(())
 }
@@ -0,0 +1,148 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use m3::client::{Disk, DiskBlockNo, DiskBlockRange};
+use m3::col::Vec;
+use m3::com::{MemCap, MemGate, Perm};
+use m3::errors::Error;
+use m3::mem::GlobOff;
+use m3::test::WvTester;
+use m3::{vec, wv_assert, wv_assert_eq, wv_assert_ok, wv_run_test};
+
+// the granularity of the disk server's cache
+const BLOCK_SIZE: usize = 4096;
+
+pub fn run(t: &mut dyn WvTester) {
+    wv_run_test!(t, shared_blocks);
+    wv_run_test!(t, write_through);
+    wv_run_test!(t, eviction);
+}
+
+/// A disk session with a buffer for a single block
+struct Client {
+    disk: Disk,
+    mem: MemGate,
+    _cap: MemCap,
+}
+
+impl Client {
+    fn new(name: &str) -> Result<Self, Error> {
+        let disk = Disk::new(name)?;
+        let mem = MemGate::new(BLOCK_SIZE as GlobOff, Perm::RW)?;
+        let cap = mem.derive_cap(0, BLOCK_SIZE as GlobOff, Perm::RW)?;
+        disk.delegate_mem(&cap, DiskBlockRange::new(0))?;
+        Ok(Self {
+            disk,
+            mem,
+            _cap: cap,
+        })
+    }
+
+    fn read(&self, bno: DiskBlockNo) -> Result<Vec<u8>, Error> {
+        self.disk
+            .read(0, DiskBlockRange::new(bno), BLOCK_SIZE, None)?;
+        self.mem.read_into_vec(BLOCK_SIZE, 0)
+    }
+
+    fn write(&self, bno: DiskBlockNo, data: &[u8]) -> Result<(), Error> {
+        self.mem.write(data, 0)?;
+        self.disk
+            .write(0, DiskBlockRange::new(bno), BLOCK_SIZE, None)
+    }
+}
+
+fn shared_blocks(t: &mut dyn WvTester) {
+    let c1 = wv_assert_ok!(Client::new("disk"));
+
+    let before = wv_assert_ok!(c1.disk.cache_stats());
+    let data1 = wv_assert_ok!(c1.read(1));
+    let after = wv_assert_ok!(c1.disk.cache_stats());
+    // the block might have been cached before by the file system
+    wv_assert_eq!(
+        t,
+        after.hits + after.misses,
+        before.hits + before.misses + 1
+    );
+    wv_assert!(t, after.used >= 1 && after.used <= after.capacity);
+
+    {
+        let c2 = wv_assert_ok!(Client::new("disk-clone"));
+        let data2 = wv_assert_ok!(c2.read(1));
+        wv_assert_eq!(t, data1, data2);
+
+        // the second session uses the cached block of the first one
+        let shared = wv_assert_ok!(c2.disk.cache_stats());
+        wv_assert_eq!(t, shared.hits, after.hits + 1);
+        wv_assert_eq!(t, shared.misses, after.misses);
+        wv_assert!(t, shared.shared >= 1);
+    }
+
+    // closing a session does not drop its blocks from the cache
+    let closed = wv_assert_ok!(c1.disk.cache_stats());
+    wv_assert_eq!(t, closed.used, after.used);
+    let data3 = wv_assert_ok!(c1.read(1));
+    wv_assert_eq!(t, data1, data3);
+    wv_assert_eq!(
+        t,
+        wv_assert_ok!(c1.disk.cache_stats()).hits,
+        closed.hits + 1
+    );
+}
+
+fn write_through(t: &mut dyn WvTester) {
+    let c1 = wv_assert_ok!(Client::new("disk"));
+    let c2 = wv_assert_ok!(Client::new("disk-clone"));
+
+    // write the same content back to not disturb the file system
+    let data = wv_assert_ok!(c1.read(1));
+    let before = wv_assert_ok!(c1.disk.cache_stats());
+    wv_assert_ok!(c1.write(1, &data));
+
+    // by default, modified blocks are written back immediately
+    let after = wv_assert_ok!(c1.disk.cache_stats());
+    wv_assert_eq!(t, after.writebacks, before.writebacks + 1);
+    wv_assert_eq!(t, after.dirty, 0);
+
+    // the other session sees the written content
+    wv_assert_eq!(t, wv_assert_ok!(c2.read(1)), data);
+
+    // nothing left to write back
+    wv_assert_ok!(c2.disk.flush());
+    let flushed = wv_assert_ok!(c2.disk.cache_stats());
+    wv_assert_eq!(t, flushed.writebacks, after.writebacks);
+}
+
+fn eviction(t: &mut dyn WvTester) {
+    let c = wv_assert_ok!(Client::new("disk"));
+
+    let before = wv_assert_ok!(c.disk.cache_stats());
+    let mut first = vec![];
+    for bno in 0..before.capacity + 1 {
+        let data = wv_assert_ok!(c.read(bno as DiskBlockNo));
+        if bno == 0 {
+            first = data;
+        }
+    }
+
+    // the cache is full and at least one block had to be replaced
+    let after = wv_assert_ok!(c.disk.cache_stats());
+    wv_assert_eq!(t, after.used, after.capacity);
+    wv_assert!(t, after.evictions > before.evictions);
+
+    // the least recently used block has been evicted, but is loaded again on access
+    wv_assert_eq!(t, wv_assert_ok!(c.read(0)), first);
+    let reload = wv_assert_ok!(c.disk.cache_stats());
+    wv_assert_eq!(t, reload.misses, after.misses + 1);
+}
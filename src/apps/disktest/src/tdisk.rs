@@ -26,6 +26,7 @@ pub fn run(t: &mut dyn WvTester) {
     wv_run_test!(t, pat_file);
     wv_run_test!(t, write_file);
     wv_run_test!(t, list_dir);
+    wv_run_test!(t, large_file);
 }
 
 fn text_files(t: &mut dyn WvTester) {
@@ -85,6 +86,34 @@ fn list_dir(t: &mut dyn WvTester) {
     wv_assert_eq!(t, vec[3], "test.txt");
 }
 
+fn large_file(t: &mut dyn WvTester) {
+    // large enough to span multiple transfer chunks of the block device
+    const SIZE: usize = 256 * 1024;
+
+    let mut buf = vec![0u8; 8 * 1024];
+    for (i, b) in buf.iter_mut().enumerate() {
+        *b = (i & 0xFF) as u8;
+    }
+
+    {
+        let mut file = wv_assert_ok!(VFS::open(
+            "/largefile",
+            OpenFlags::W | OpenFlags::CREATE | OpenFlags::TRUNC
+        ));
+        for _ in 0..SIZE / buf.len() {
+            wv_assert_ok!(file.write_all(&buf));
+        }
+        wv_assert_ok!(file.sync());
+    }
+
+    {
+        let file = wv_assert_ok!(VFS::open("/largefile", OpenFlags::R));
+        wv_assert_eq!(t, _validate_pattern_content(t, file, &mut buf), SIZE);
+    }
+
+    wv_assert_ok!(VFS::unlink("/largefile"));
+}
+
 fn _validate_pattern_content(
     t: &mut dyn WvTester,
     mut file: FileRef<GenericFile>,
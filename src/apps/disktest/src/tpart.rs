@@ -0,0 +1,49 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use m3::client::Disk;
+use m3::errors::Code;
+use m3::test::WvTester;
+use m3::{wv_assert, wv_assert_eq, wv_assert_err, wv_assert_ok, wv_run_test};
+
+pub fn run(t: &mut dyn WvTester) {
+    wv_run_test!(t, part_info);
+}
+
+fn part_info(t: &mut dyn WvTester) {
+    let disk = wv_assert_ok!(Disk::new("disk"));
+
+    // the file system uses partition 0, which therefore has to exist
+    let count = wv_assert_ok!(disk.partition_count());
+    wv_assert!(t, count >= 1);
+    wv_assert_eq!(t, wv_assert_ok!(disk.partition_info(0)).id, 0);
+
+    let mut last_id = None;
+    for i in 0..count {
+        let info = wv_assert_ok!(disk.partition_info(i));
+        wv_assert!(t, info.sectors > 0);
+        wv_assert!(t, !info.ty.is_empty());
+        // partitions are reported in the order of their ids
+        wv_assert!(t, last_id.map(|id| id < info.id).unwrap_or(true));
+        last_id = Some(info.id);
+
+        // without partition table, the whole disk is used
+        if info.ty == "whole" {
+            wv_assert_eq!(t, info.start, 0);
+        }
+    }
+
+    wv_assert_err!(t, disk.partition_info(count), Code::NotFound);
+}
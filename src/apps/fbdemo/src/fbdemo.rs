@@ -0,0 +1,73 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+#![no_std]
+
+use m3::client::{FbRect, Framebuffer};
+use m3::col::Vec;
+use m3::errors::Error;
+use m3::io::Write;
+use m3::mem::GlobOff;
+use m3::tiles::OwnActivity;
+use m3::time::TimeDuration;
+use m3::vec;
+
+use gfx::{rgb, Color, TextConsole};
+
+fn draw_gradient(fb: &Framebuffer) -> Result<(), Error> {
+    let info = *fb.info();
+    let mem = fb.get_mem()?;
+
+    let mut row: Vec<Color> = vec![0; info.width as usize];
+    for y in 0..info.height {
+        for (x, px) in row.iter_mut().enumerate() {
+            let r = (x * 255 / info.width as usize) as u8;
+            let g = (y * 255 / info.height) as u8;
+            *px = rgb(r, g, 0x80);
+        }
+        mem.write(&row, info.offset(0, y) as GlobOff)?;
+    }
+
+    fb.flush(FbRect::new(0, 0, info.width, info.height))
+}
+
+#[no_mangle]
+pub fn main() -> Result<(), Error> {
+    let fb = Framebuffer::new("fb")?;
+
+    draw_gradient(&fb)?;
+    OwnActivity::sleep_for(TimeDuration::from_secs(1))?;
+
+    let info = *fb.info();
+    let mut con = TextConsole::new(fb)?;
+    con.set_colors(rgb(0xFF, 0xFF, 0xFF), rgb(0x20, 0x20, 0x60));
+    con.clear();
+
+    let (cols, rows) = con.size();
+    writeln!(
+        con,
+        "Hello from M3!\nFramebuffer: {}x{}, console: {}x{}\n",
+        info.width, info.height, cols, rows
+    )?;
+    con.flush()?;
+
+    for i in 0..rows * 2 {
+        writeln!(con, "line {}", i)?;
+        con.flush()?;
+        OwnActivity::sleep_for(TimeDuration::from_millis(50))?;
+    }
+
+    Ok(())
+}
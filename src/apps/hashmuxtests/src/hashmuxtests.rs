@@ -18,16 +18,26 @@
 
 #![no_std]
 
+use m3::env;
 use m3::errors::Error;
 use m3::test::{DefaultWvTester, WvTester};
 use m3::{println, wv_run_suite};
 
+mod tcrypto;
 mod thash;
+mod ttls;
 
 #[no_mangle]
 pub fn main() -> Result<(), Error> {
     let mut tester = DefaultWvTester::default();
-    wv_run_suite!(tester, thash::run);
+    // the crypto service needs the accelerator as well, so that it runs in a separate setup
+    if env::args().nth(1) == Some("crypto") {
+        wv_run_suite!(tester, tcrypto::run);
+    }
+    else {
+        wv_run_suite!(tester, thash::run);
+        wv_run_suite!(tester, ttls::run);
+    }
     println!("{}", tester);
     Ok(())
 }
@@ -0,0 +1,317 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use cshake::kmac;
+use hex_literal::hex;
+
+use m3::client::{CryptoSession, KeyId, AEAD_TAG_SIZE};
+use m3::com::{MemCap, MemGate, Perm};
+use m3::crypto::HashAlgorithm;
+use m3::errors::{Code, Error};
+use m3::mem::GlobOff;
+use m3::println;
+use m3::test::WvTester;
+use m3::{wv_assert_eq, wv_assert_err, wv_assert_ok, wv_run_test};
+
+use crate::thash::{CSHAKE_NIST_SAMPLES, KMAC_NIST_SAMPLES};
+
+const MEM_SIZE: usize = 0x4000;
+
+const KEY: [u8; 32] = hex!(
+    "
+    40 41 42 43 44 45 46 47 48 49 4A 4B 4C 4D 4E 4F
+    50 51 52 53 54 55 56 57 58 59 5A 5B 5C 5D 5E 5F
+    "
+);
+
+pub fn run(t: &mut dyn WvTester) {
+    wv_run_test!(t, hash);
+    wv_run_test!(t, cshake_nist);
+    wv_run_test!(t, kmac_nist);
+    wv_run_test!(t, keys);
+    wv_run_test!(t, aead_roundtrip);
+    wv_run_test!(t, aead_tampered);
+}
+
+/// A crypto session together with the memory that is used for all operations
+struct Setup {
+    sess: CryptoSession,
+    mem: MemGate,
+    _cmem: MemCap,
+}
+
+impl Setup {
+    fn new(name: &str) -> Self {
+        let sess = wv_assert_ok!(CryptoSession::new(name));
+        let mem = wv_assert_ok!(MemGate::new(MEM_SIZE as GlobOff, Perm::RW));
+        let cmem = wv_assert_ok!(mem.derive_cap(0, MEM_SIZE as GlobOff, Perm::RW));
+        wv_assert_ok!(sess.ep().configure(cmem.sel()));
+        Self {
+            sess,
+            mem,
+            _cmem: cmem,
+        }
+    }
+
+    fn import_key(&self, key: &[u8]) -> KeyId {
+        wv_assert_ok!(self.mem.write(key, 0));
+        let id = wv_assert_ok!(self.sess.import_key(0..key.len()));
+        // the service has its own copy now
+        wv_assert_ok!(self.mem.write(&[0u8; 64][..key.len()], 0));
+        id
+    }
+}
+
+/// Ignores the result of tests that need cSHAKE, which is not supported by all backends
+fn supported<T>(res: Result<T, Error>) -> Option<T> {
+    match res {
+        Err(e) if e.code() == Code::NotSup => {
+            println!("Ignoring test -- cSHAKE not supported");
+            None
+        },
+        res => Some(wv_assert_ok!(res)),
+    }
+}
+
+fn hash(t: &mut dyn WvTester) {
+    let s = Setup::new("crypto");
+    let mut buf = [0u8; 32];
+
+    wv_assert_ok!(s.sess.hash(&HashAlgorithm::SHA3_256, 0..0, 0x100..0x120));
+    wv_assert_ok!(s.mem.read(&mut buf, 0x100));
+    wv_assert_eq!(
+        t,
+        &buf,
+        &hex!("a7ffc6f8bf1ed76651c14756a061d662f580ff4de43b49fa82d80a4b80f8434a")
+    );
+
+    // cSHAKE without function name and customization string is equal to SHAKE
+    for algo in [&HashAlgorithm::SHAKE128, &HashAlgorithm::CSHAKE128] {
+        wv_assert_ok!(s.sess.hash(algo, 0..0, 0x100..0x120));
+        wv_assert_ok!(s.mem.read(&mut buf, 0x100));
+        wv_assert_eq!(
+            t,
+            &buf,
+            &hex!("7f9c2ba4e88f827d616045507605853ed73b8093f6efbc88eb1a6eacfa66ef26")
+        );
+    }
+
+    // fixed-size hashes need an output region of exactly the hash size
+    wv_assert_err!(
+        t,
+        s.sess.hash(&HashAlgorithm::SHA3_256, 0..0, 0x100..0x110),
+        Code::InvArgs
+    );
+    // SHA-3 and SHAKE do not support customization
+    wv_assert_err!(
+        t,
+        s.sess
+            .cshake(&HashAlgorithm::SHAKE128, "", "foo", 0..0, 0x100..0x120),
+        Code::InvArgs
+    );
+}
+
+fn cshake_nist(t: &mut dyn WvTester) {
+    let s = Setup::new("crypto");
+    let mut buf = [0u8; HashAlgorithm::MAX_OUTPUT_BYTES];
+
+    for test in &CSHAKE_NIST_SAMPLES {
+        wv_assert_ok!(s.mem.write(test.data, 0));
+        let out = 0x1000..0x1000 + test.expected.len();
+        let res = s
+            .sess
+            .cshake(test.algo, test.n, test.s, 0..test.data.len(), out.clone());
+        if supported(res).is_none() {
+            return;
+        }
+
+        wv_assert_ok!(s.mem.read(&mut buf[..out.len()], out.start as GlobOff));
+        wv_assert_eq!(t, &buf[..out.len()], test.expected);
+    }
+}
+
+fn kmac_nist(t: &mut dyn WvTester) {
+    let s = Setup::new("crypto");
+    let mut buf = [0u8; HashAlgorithm::MAX_OUTPUT_BYTES];
+
+    // the service determines the output length by the output region; thus, no KMACXOF
+    for test in KMAC_NIST_SAMPLES
+        .iter()
+        .filter(|t| t.output_length != kmac::XOF_OUTPUT_LENGTH)
+    {
+        let key = s.import_key(test.key);
+
+        wv_assert_ok!(s.mem.write(test.data, 0x100));
+        let out = 0x1000..0x1000 + test.output_length / 8;
+        let res = s.sess.kmac(
+            key,
+            test.algo,
+            test.s,
+            0x100..0x100 + test.data.len(),
+            out.clone(),
+        );
+        if supported(res).is_none() {
+            return;
+        }
+
+        wv_assert_ok!(s.mem.read(&mut buf[..out.len()], out.start as GlobOff));
+        wv_assert_eq!(t, &buf[..out.len()], test.expected);
+        wv_assert_ok!(s.sess.delete_key(key));
+    }
+}
+
+fn keys(t: &mut dyn WvTester) {
+    let s1 = Setup::new("crypto");
+    let s2 = Setup::new("crypto2");
+
+    // too short keys are refused
+    wv_assert_err!(t, s1.sess.import_key(0..8), Code::InvArgs);
+
+    let key = s1.import_key(&KEY);
+    wv_assert_ok!(s1.mem.write(b"test", 0x100));
+
+    // the key is only usable in the session it has been imported into
+    let res = s2.sess.kmac(
+        key,
+        &HashAlgorithm::CSHAKE256,
+        "",
+        0x100..0x104,
+        0x200..0x220,
+    );
+    wv_assert_err!(t, res, Code::InvArgs);
+
+    // deleted keys cannot be used anymore
+    wv_assert_ok!(s1.sess.delete_key(key));
+    let res = s1.sess.kmac(
+        key,
+        &HashAlgorithm::CSHAKE256,
+        "",
+        0x100..0x104,
+        0x200..0x220,
+    );
+    wv_assert_err!(t, res, Code::InvArgs);
+    wv_assert_err!(t, s1.sess.delete_key(key), Code::InvArgs);
+}
+
+const PLAINTEXT: &[u8] = b"The quick brown fox jumps over the lazy dog, again and again.";
+const AD: &[u8] = b"header";
+
+// memory layout for the AEAD tests
+const AD_OFF: usize = 0x100;
+const PT_OFF: usize = 0x200;
+const CT_OFF: usize = 0x1000;
+const OUT_OFF: usize = 0x2000;
+
+fn aead_seal(s: &Setup) -> Option<(KeyId, usize)> {
+    let key = s.import_key(&KEY);
+    wv_assert_ok!(s.mem.write(AD, AD_OFF as GlobOff));
+    wv_assert_ok!(s.mem.write(PLAINTEXT, PT_OFF as GlobOff));
+
+    let res = s.sess.seal(
+        key,
+        1,
+        AD_OFF..AD_OFF + AD.len(),
+        PT_OFF..PT_OFF + PLAINTEXT.len(),
+        CT_OFF,
+    );
+    supported(res).map(|len| (key, len))
+}
+
+fn aead_roundtrip(t: &mut dyn WvTester) {
+    let s = Setup::new("crypto");
+    let (key, len) = match aead_seal(&s) {
+        Some(res) => res,
+        None => return,
+    };
+    wv_assert_eq!(t, len, PLAINTEXT.len() + AEAD_TAG_SIZE);
+
+    let mut buf = [0u8; PLAINTEXT.len()];
+    wv_assert_ok!(s.mem.read(&mut buf, CT_OFF as GlobOff));
+    wv_assert_eq!(t, buf != PLAINTEXT, true);
+
+    let res = s.sess.open(
+        key,
+        1,
+        AD_OFF..AD_OFF + AD.len(),
+        CT_OFF..CT_OFF + len,
+        OUT_OFF,
+    );
+    wv_assert_eq!(t, res, Ok(PLAINTEXT.len()));
+    wv_assert_ok!(s.mem.read(&mut buf, OUT_OFF as GlobOff));
+    wv_assert_eq!(t, &buf, PLAINTEXT);
+
+    // a different nonce produces a different ciphertext
+    let res = s.sess.seal(
+        key,
+        2,
+        AD_OFF..AD_OFF + AD.len(),
+        PT_OFF..PT_OFF + PLAINTEXT.len(),
+        OUT_OFF,
+    );
+    wv_assert_eq!(t, res, Ok(len));
+    let mut ct1 = [0u8; PLAINTEXT.len()];
+    let mut ct2 = [0u8; PLAINTEXT.len()];
+    wv_assert_ok!(s.mem.read(&mut ct1, CT_OFF as GlobOff));
+    wv_assert_ok!(s.mem.read(&mut ct2, OUT_OFF as GlobOff));
+    wv_assert_eq!(t, ct1 != ct2, true);
+}
+
+fn aead_tampered(t: &mut dyn WvTester) {
+    let s = Setup::new("crypto");
+    let (key, len) = match aead_seal(&s) {
+        Some(res) => res,
+        None => return,
+    };
+    wv_assert_ok!(s.mem.write(&[0u8; PLAINTEXT.len()], OUT_OFF as GlobOff));
+
+    // wrong nonce
+    let res = s.sess.open(
+        key,
+        2,
+        AD_OFF..AD_OFF + AD.len(),
+        CT_OFF..CT_OFF + len,
+        OUT_OFF,
+    );
+    wv_assert_err!(t, res, Code::InvChecksum);
+
+    // wrong associated data
+    let res = s.sess.open(
+        key,
+        1,
+        AD_OFF..AD_OFF + AD.len() - 1,
+        CT_OFF..CT_OFF + len,
+        OUT_OFF,
+    );
+    wv_assert_err!(t, res, Code::InvChecksum);
+
+    // modified ciphertext
+    let mut byte = [0u8; 1];
+    wv_assert_ok!(s.mem.read(&mut byte, CT_OFF as GlobOff));
+    byte[0] ^= 1;
+    wv_assert_ok!(s.mem.write(&byte, CT_OFF as GlobOff));
+    let res = s.sess.open(
+        key,
+        1,
+        AD_OFF..AD_OFF + AD.len(),
+        CT_OFF..CT_OFF + len,
+        OUT_OFF,
+    );
+    wv_assert_err!(t, res, Code::InvChecksum);
+
+    // nothing has been written on failures
+    let mut buf = [0u8; PLAINTEXT.len()];
+    wv_assert_ok!(s.mem.read(&mut buf, OUT_OFF as GlobOff));
+    wv_assert_eq!(t, buf.iter().all(|b| *b == 0), true);
+}
@@ -481,26 +481,26 @@ fn shake_and_hash_pipe(t: &mut dyn WvTester) {
     wv_assert_eq!(t, closure.wait(), Ok(Code::Success));
 }
 
-struct CSHAKETest {
-    algo: &'static HashAlgorithm,
-    data: &'static [u8],
-    n: &'static str,
-    s: &'static str,
-    expected: &'static [u8],
+pub(crate) struct CSHAKETest {
+    pub algo: &'static HashAlgorithm,
+    pub data: &'static [u8],
+    pub n: &'static str,
+    pub s: &'static str,
+    pub expected: &'static [u8],
 }
 
-struct KMACTest {
-    algo: &'static HashAlgorithm,
-    key: &'static [u8],
-    data: &'static [u8],
-    output_length: usize,
-    s: &'static str,
-    expected: &'static [u8],
+pub(crate) struct KMACTest {
+    pub algo: &'static HashAlgorithm,
+    pub key: &'static [u8],
+    pub data: &'static [u8],
+    pub output_length: usize,
+    pub s: &'static str,
+    pub expected: &'static [u8],
 }
 
 /// NIST test vectors for cSHAKE
 /// https://csrc.nist.gov/CSRC/media/Projects/Cryptographic-Standards-and-Guidelines/documents/examples/cSHAKE_samples.pdf
-const CSHAKE_NIST_SAMPLES: [CSHAKETest; 4] = [
+pub(crate) const CSHAKE_NIST_SAMPLES: [CSHAKETest; 4] = [
     CSHAKETest {
         algo: &HashAlgorithm::CSHAKE128,
         data: &hex!("00 01 02 03"),
@@ -590,7 +590,7 @@ const CSHAKE_NIST_SAMPLES: [CSHAKETest; 4] = [
 /// NIST test vectors for KMAC / KMACXOF
 /// https://csrc.nist.gov/CSRC/media/Projects/Cryptographic-Standards-and-Guidelines/documents/examples/KMAC_samples.pdf
 /// https://csrc.nist.gov/CSRC/media/Projects/Cryptographic-Standards-and-Guidelines/documents/examples/KMACXOF_samples.pdf
-const KMAC_NIST_SAMPLES: [KMACTest; 12] = [
+pub(crate) const KMAC_NIST_SAMPLES: [KMACTest; 12] = [
     // KMAC
     KMACTest {
         algo: &HashAlgorithm::CSHAKE128,
@@ -0,0 +1,99 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use hex_literal::hex;
+
+use m3::errors::Code;
+use m3::test::WvTester;
+use m3::{vec, wv_assert_eq, wv_assert_err, wv_assert_ok, wv_run_test};
+
+use tls::{CertVerifier, PinnedCertVerifier, TlsConfig, TlsConnector};
+
+// a self-signed certificate for "m3.test" with a P-256 key
+const CERT: [u8; 404] = hex!(
+    "3082019030820135a003020102021406cf49c5d086f5b653201d1f6eef4fc756e6815e300a06082a"
+    "8648ce3d04030230123110300e06035504030c076d332e746573743020170d323631303136323335"
+    "3534305a180f32313236303932323233353534305a30123110300e06035504030c076d332e746573"
+    "743059301306072a8648ce3d020106082a8648ce3d03010703420004ad428e12afa0f50f6adde550"
+    "0690c80bd93290c59e420ff0c55adbe88d3075d9deef106a17885fc79c3f82956d4644568917ab09"
+    "e5ec522872f0ea6b5defd16da3673065301d0603551d0e0416041451e05d3bce1e20fc735c513253"
+    "c316ba4fb769aa301f0603551d2304183016801451e05d3bce1e20fc735c513253c316ba4fb769aa"
+    "30120603551d11040b300982076d332e74657374300f0603551d130101ff040530030101ff300a06"
+    "082a8648ce3d0403020349003046022100f09d5e6955e4a29f70d5326b4c183bd9f599bc845dcc52"
+    "c25c45d2aad2263a0e022100e39f36f9593b9c2ed9eeb26d55da347c655c2d67714e2ecb4e10acc2"
+    "d02a4c7b"
+);
+
+// the SHA3-256 hash of CERT
+const CERT_FP: [u8; 32] = hex!("7d27b7c76aa4746b742587d8e3335834d3b1cbc2f26808f15232fb718e294e4a");
+
+pub fn run(t: &mut dyn WvTester) {
+    wv_run_test!(t, fingerprint);
+    wv_run_test!(t, pinned_certs);
+    wv_run_test!(t, root_certs);
+    wv_run_test!(t, custom_verifier);
+}
+
+fn fingerprint(t: &mut dyn WvTester) {
+    let verifier = wv_assert_ok!(PinnedCertVerifier::new("hash"));
+    wv_assert_eq!(t, wv_assert_ok!(verifier.fingerprint(&CERT)), CERT_FP);
+    // the fingerprint does not depend on previous certificates
+    wv_assert_eq!(t, wv_assert_ok!(verifier.fingerprint(&CERT)), CERT_FP);
+
+    let large = vec![0u8; 16 * 1024 + 1];
+    wv_assert_err!(t, verifier.fingerprint(&large), Code::OutOfBounds);
+}
+
+fn pinned_certs(t: &mut dyn WvTester) {
+    let mut verifier = wv_assert_ok!(PinnedCertVerifier::new("hash"));
+    wv_assert_err!(t, verifier.verify("m3.test", &CERT, &[]), Code::NoPerm);
+
+    verifier.pin(CERT_FP);
+    wv_assert_ok!(verifier.verify("m3.test", &CERT, &[]));
+    // the name is not checked for pinned certificates
+    wv_assert_ok!(verifier.verify("other.test", &CERT, &[]));
+
+    // any modification of the certificate is detected
+    let mut modified = CERT;
+    modified[200] ^= 1;
+    wv_assert_err!(t, verifier.verify("m3.test", &modified, &[]), Code::NoPerm);
+}
+
+fn root_certs(t: &mut dyn WvTester) {
+    // without root certificates, no server can be verified
+    wv_assert_err!(
+        t,
+        TlsConnector::new(TlsConfig::new()).map(|_| ()),
+        Code::InvArgs
+    );
+
+    wv_assert_err!(
+        t,
+        TlsConfig::new().root_cert(&CERT[0..100]).map(|_| ()),
+        Code::NoPerm
+    );
+
+    let config = wv_assert_ok!(TlsConfig::new().root_cert(&CERT));
+    wv_assert_ok!(TlsConnector::new(config.unix_time(1_700_000_000)));
+}
+
+fn custom_verifier(_t: &mut dyn WvTester) {
+    let mut verifier = wv_assert_ok!(PinnedCertVerifier::new("hash"));
+    verifier.pin(CERT_FP);
+
+    // the verifier replaces the root certificates
+    let config = TlsConfig::new().verifier(verifier).alpn(b"http/1.1");
+    wv_assert_ok!(TlsConnector::new(config));
+}
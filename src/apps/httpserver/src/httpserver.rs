@@ -0,0 +1,165 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+#![no_std]
+
+use http::{HttpConnection, Method, Request, Response};
+
+use m3::client::Network;
+use m3::col::String;
+use m3::errors::{Code, Error};
+use m3::format;
+use m3::io::{Read, Write};
+use m3::net::{Port, Socket, StreamSocket, StreamSocketArgs, TcpSocket};
+use m3::rc::Rc;
+use m3::vfs::{FileRef, OpenFlags, VFS};
+use m3::{env, println, vec};
+
+/// The maximum size of request bodies that we accept (and ignore)
+const MAX_BODY: usize = 64 * 1024;
+
+fn socket_args(net: &Rc<Network>) -> StreamSocketArgs {
+    StreamSocketArgs::new(net.clone())
+        .send_buffer(64 * 1024)
+        .recv_buffer(64 * 1024)
+}
+
+fn content_type(path: &str) -> &'static str {
+    match path.rsplit_once('.').map(|(_, ext)| ext) {
+        Some("html") | Some("htm") => "text/html",
+        Some("txt") => "text/plain",
+        Some("css") => "text/css",
+        Some("js") => "text/javascript",
+        Some("json") => "application/json",
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        _ => "application/octet-stream",
+    }
+}
+
+fn error_code(e: &Error) -> u16 {
+    match e.code() {
+        Code::NoSuchFile | Code::NotFound => 404,
+        Code::NoPerm => 403,
+        _ => 500,
+    }
+}
+
+fn send_error(conn: &mut HttpConnection<FileRef<TcpSocket>>, status: u16) -> Result<(), Error> {
+    let resp = Response::new(status).header("Content-Type", "text/plain");
+    let body = format!("{} {}\n", status, resp.reason());
+    conn.send_response(&resp, body.as_bytes())
+}
+
+fn send_file(
+    conn: &mut HttpConnection<FileRef<TcpSocket>>,
+    root: &str,
+    req: &Request,
+) -> Result<(), Error> {
+    // don't allow to escape from the root directory
+    if req.path().split('/').any(|c| c == "..") {
+        return send_error(conn, 403);
+    }
+
+    let mut path = String::from(root);
+    path.push_str(req.path());
+    let mut info = match VFS::stat(&path) {
+        Ok(info) => info,
+        Err(e) => return send_error(conn, error_code(&e)),
+    };
+    if info.mode.is_dir() {
+        if !path.ends_with('/') {
+            path.push('/');
+        }
+        path.push_str("index.html");
+        info = match VFS::stat(&path) {
+            Ok(info) => info,
+            Err(e) => return send_error(conn, error_code(&e)),
+        };
+    }
+
+    let mut file = match VFS::open(&path, OpenFlags::R) {
+        Ok(file) => file,
+        Err(e) => return send_error(conn, error_code(&e)),
+    };
+
+    let resp = Response::new(200)
+        .header("Content-Type", content_type(&path))
+        .header("Content-Length", &format!("{}", info.size));
+    conn.send_response(&resp, &[])?;
+    if *req.method() == Method::Head {
+        return Ok(());
+    }
+
+    // send the file contents directly as the body
+    let mut buf = vec![0u8; 4096];
+    loop {
+        let amount = file.read(&mut buf)?;
+        if amount == 0 {
+            break Ok(());
+        }
+        conn.get_mut().write_all(&buf[0..amount])?;
+    }
+}
+
+fn handle_client(socket: FileRef<TcpSocket>, root: &str) -> Result<(), Error> {
+    let mut conn = HttpConnection::new(socket);
+    while let Some(req) = conn.read_request()? {
+        // the body is not used, but needs to be received to get to the next request
+        conn.read_request_body(&req, MAX_BODY)?;
+
+        match req.method() {
+            Method::Get | Method::Head => send_file(&mut conn, root, &req)?,
+            _ => send_error(&mut conn, 405)?,
+        }
+
+        if !req.keep_alive() {
+            break;
+        }
+    }
+    conn.get_mut().close()
+}
+
+#[no_mangle]
+pub fn main() -> Result<(), Error> {
+    let args = env::args().collect::<m3::col::Vec<_>>();
+    if args.len() < 2 {
+        println!("Usage: {} <root> [<port>]", args[0]);
+        return Err(Error::new(Code::InvArgs));
+    }
+
+    let root = args[1].trim_end_matches('/');
+    let port = match args.get(2) {
+        Some(p) => p.parse::<Port>().map_err(|_| Error::new(Code::InvArgs))?,
+        None => 80,
+    };
+
+    let net = Network::new("net").expect("connecting to net failed");
+    let listener = TcpSocket::new(socket_args(&net)).expect("creating TCP socket failed");
+    listener
+        .borrow_as()
+        .listen_with_backlog(port, 4)
+        .expect("listen failed");
+
+    println!("Serving {} on port {}", root, port);
+
+    loop {
+        let socket = listener.borrow_as().accept_socket(socket_args(&net))?;
+        let remote = socket.remote_endpoint();
+        if let Err(e) = handle_client(socket, root) {
+            println!("Connection with {:?} failed: {}", remote, e);
+        }
+    }
+}
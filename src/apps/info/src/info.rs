@@ -27,14 +27,14 @@ pub fn main() -> Result<(), Error> {
         .get_activity_count()
         .expect("Unable to get Activity count");
     println!(
-        "{:2} | {:5} | {:>10} | {:>22} | {:>14} | {:>14} | {:>12} | Name",
-        "ID", "Tile", "Endpoints", "Time", "UserMem", "KernelMem", "Pagetables"
+        "{:2} | {:5} | {:>10} | {:>22} | {:>10} | {:>14} | {:>14} | {:>12} | Name",
+        "ID", "Tile", "Endpoints", "Time", "CPU", "UserMem", "KernelMem", "Pagetables"
     );
     for i in 0..num {
         match Activity::own().resmng().unwrap().get_activity_info(i) {
             Ok(act) => {
                 println!(
-                    "{:2} | {:5} | {:2}:{:3}/{:3} | {:4}:{:6}us/{:6}us | {:2}:{:4}M/{:4}M | {:2}:{:4}M/{:4}M | {:4}:{:3}/{:3} | {:0l$}{}",
+                    "{:2} | {:5} | {:2}:{:3}/{:3} | {:4}:{:6}us/{:6}us | {:8}us | {:2}:{:4}M/{:4}M | {:2}:{:4}M/{:4}M | {:4}:{:3}/{:3} | {:0l$}{}",
                     act.id,
                     act.tile,
                     act.eps.id(),
@@ -43,6 +43,7 @@ pub fn main() -> Result<(), Error> {
                     act.time.id(),
                     act.time.remaining().as_micros(),
                     act.time.total().as_micros(),
+                    act.cpu_time.as_micros(),
                     act.umem.id(),
                     act.umem.remaining() / (1024 * 1024),
                     act.umem.total() / (1024 * 1024),
@@ -0,0 +1,2 @@
+def build(gen, env):
+    env.m3_rust_exe(gen, out='logctl')
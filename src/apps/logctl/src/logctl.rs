@@ -0,0 +1,75 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+#![no_std]
+
+use m3::env;
+use m3::errors::{Code, Error};
+use m3::io::log::LogFormat;
+use m3::io::LogFlags;
+use m3::println;
+use m3::tiles::{Activity, OwnActivity};
+
+fn usage() -> ! {
+    println!(
+        "Usage: {} <name> <flags> [text|json]",
+        env::args().next().unwrap()
+    );
+    println!();
+    println!("Changes the log flags of all running activities with given name. The flags are");
+    println!("given as a comma-separated list (e.g., Info,Error,ResMngChild). Optionally, the");
+    println!("log format can be changed as well (default: text).");
+    OwnActivity::exit_with(Code::InvArgs);
+}
+
+#[no_mangle]
+pub fn main() -> Result<(), Error> {
+    let mut args = env::args().skip(1);
+    let (name, flags) = match (args.next(), args.next()) {
+        (Some(name), Some(flags)) => (name, flags),
+        _ => usage(),
+    };
+
+    let flags = flags
+        .split(',')
+        .map(|f| f.parse::<LogFlags>())
+        .collect::<Result<LogFlags, _>>()
+        .unwrap_or_else(|_| {
+            println!("Invalid log flags '{}'", flags);
+            usage();
+        });
+
+    let format = match args.next() {
+        None | Some("text") => LogFormat::Text,
+        Some("json") => LogFormat::Json,
+        Some(f) => {
+            println!("Invalid log format '{}'", f);
+            usage();
+        },
+    };
+
+    Activity::own()
+        .resmng()
+        .unwrap()
+        .set_log(name, flags, format)
+        .map_err(|e| {
+            println!(
+                "Unable to change log settings of '{}': {:?}",
+                name,
+                e.code()
+            );
+            e
+        })
+}
@@ -83,8 +83,8 @@ fn send_echo(
         ttl,
         protocol: IP_PROTO_ICMP,
         checksum: 0,
-        src: src.0.to_be(),
-        dst: dest.0.to_be(),
+        src: src.as_v4().unwrap().to_be(),
+        dst: dest.as_v4().unwrap().to_be(),
     };
     ip.checksum = (!net::data_checksum(util::object_to_bytes(&ip))).to_be();
     // copy to buffer
@@ -281,6 +281,12 @@ pub fn main() -> Result<(), Error> {
     let dest_ip = dns
         .get_addr(net, &settings.dest, TimeDuration::from_secs(3))
         .unwrap_or_else(|_| panic!("Unable to resolve name '{}'", settings.dest));
+    if !dest_ip.is_v4() {
+        panic!(
+            "Only IPv4 is supported, but '{}' is {}",
+            settings.dest, dest_ip
+        );
+    }
 
     let total = mem::size_of::<IPv4Header>() + mem::size_of::<ICMP>() + settings.nbytes;
     let mut buf = vec![0u8; total];
@@ -30,7 +30,7 @@ use m3::wv_run_suite;
 
 #[no_mangle]
 pub fn main() -> Result<(), Error> {
-    let mut tester = DefaultWvTester::default();
+    let mut tester = DefaultWvTester::from_env();
     wv_run_suite!(tester, tchilds::run);
     wv_run_suite!(tester, tmemory::run);
     wv_run_suite!(tester, tparse::run);
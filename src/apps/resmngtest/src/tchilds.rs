@@ -15,13 +15,13 @@
 
 use m3::col::ToString;
 use m3::errors::Code;
-use m3::kif::{Perm, TileDesc, TileISA, TileType};
+use m3::kif::{service::Version, Perm, TileDesc, TileISA, TileType};
 use m3::mem::GlobOff;
 use m3::test::{DefaultWvTester, WvTester};
 use m3::tiles::{Activity, Tile};
-use m3::{wv_assert_eq, wv_assert_err, wv_assert_ok, wv_assert_some, wv_run_test};
+use m3::{wv_assert, wv_assert_eq, wv_assert_err, wv_assert_ok, wv_assert_some, wv_run_test};
 
-use resmng::childs::Child;
+use resmng::childs::{Child, ChildManager, Id};
 use resmng::resources::Resources;
 use resmng::subsys::Subsystem;
 
@@ -72,8 +72,9 @@ fn basics(t: &mut dyn WvTester) {
             services(&mut t, child, &mut res);
             memories(&mut t, child, &mut res);
             tiles(&mut t, child, &mut res);
+            usage(&mut t, &mut childmng, cid, &mut res);
 
-            let sel = child.activity_sel();
+            let sel = wv_assert_some!(childmng.child_by_id(cid)).activity_sel();
             childmng.kill_child_async(&reqs, &mut res, sel, Code::Success);
 
             wv_assert_eq!(t, childmng.children(), 0);
@@ -86,12 +87,37 @@ fn basics(t: &mut dyn WvTester) {
 fn services(t: &mut dyn WvTester, child: &mut dyn Child, res: &mut Resources) {
     wv_assert_err!(
         t,
-        child.reg_service(res, 123, 124, "other".to_string(), 16),
+        child.reg_service(res, 123, 124, "other".to_string(), Version::default(), 16),
         Code::InvArgs
     );
-    wv_assert_ok!(child.reg_service(res, 123, 124, "test".to_string(), 16));
+    wv_assert_ok!(child.reg_service(res, 123, 124, "test".to_string(), Version::new(1, 2), 16));
     wv_assert_eq!(t, child.res().services().len(), 1);
 
+    let services = res.services_mut();
+    wv_assert_ok!(services.get_mut_by_version("test", None));
+    wv_assert_ok!(services.get_mut_by_version("test", Some(Version::new(1, 1))));
+    wv_assert_err!(
+        t,
+        services
+            .get_mut_by_version("test", Some(Version::new(1, 3)))
+            .map(|_| ()),
+        Code::VersionMismatch
+    );
+    wv_assert_err!(
+        t,
+        services
+            .get_mut_by_version("test", Some(Version::new(2, 0)))
+            .map(|_| ()),
+        Code::VersionMismatch
+    );
+    wv_assert_err!(
+        t,
+        services
+            .get_mut_by_version("other", Some(Version::new(1, 0)))
+            .map(|_| ()),
+        Code::InvArgs
+    );
+
     wv_assert_err!(t, child.unreg_service(res, 124), Code::InvArgs);
     wv_assert_ok!(child.unreg_service(res, 123));
     wv_assert_eq!(t, child.res().services().len(), 0);
@@ -113,6 +139,16 @@ fn memories(t: &mut dyn WvTester, child: &mut dyn Child, _res: &mut Resources) {
 
     wv_assert_eq!(t, child.res().memories().len(), 0);
     wv_assert_eq!(t, child.mem().quota(), QUOTA);
+
+    // change the quota at runtime
+    wv_assert_ok!(child.mem().set_total(QUOTA / 2));
+    wv_assert_eq!(t, child.mem().quota(), QUOTA / 2);
+    wv_assert_ok!(child.alloc_mem(123, 4 * 1024, Perm::RW));
+    wv_assert_err!(t, child.mem().set_total(0), Code::NoSpace);
+    wv_assert_ok!(child.mem().set_total(QUOTA));
+    wv_assert_eq!(t, child.mem().quota(), QUOTA - (4 * 1024));
+    wv_assert_ok!(child.free_mem(123));
+    wv_assert_eq!(t, child.mem().quota(), QUOTA);
 }
 
 fn tiles(t: &mut dyn WvTester, child: &mut dyn Child, res: &mut Resources) {
@@ -126,11 +162,21 @@ fn tiles(t: &mut dyn WvTester, child: &mut dyn Child, res: &mut Resources) {
             starter,
             123,
             TileDesc::new(TileType::Mem, TileISA::None, 0),
-            false
+            false,
+            None,
+            None
         ),
         Code::InvArgs
     );
-    wv_assert_ok!(child.alloc_tile(res, starter, 123, Activity::own().tile_desc(), false));
+    wv_assert_ok!(child.alloc_tile(
+        res,
+        starter,
+        123,
+        Activity::own().tile_desc(),
+        false,
+        None,
+        None
+    ));
 
     wv_assert_eq!(t, child.res().tiles().len(), 1);
 
@@ -139,3 +185,45 @@ fn tiles(t: &mut dyn WvTester, child: &mut dyn Child, res: &mut Resources) {
 
     wv_assert_eq!(t, child.res().tiles().len(), 0);
 }
+
+fn usage(t: &mut dyn WvTester, childmng: &mut ChildManager, cid: Id, res: &mut Resources) {
+    const QUOTA: usize = 32 * 1024 * 1024;
+
+    // the child has no permission to get information about the other childs
+    wv_assert_err!(t, childmng.get_usage(cid, 0, 0), Code::NoPerm);
+
+    {
+        let child = wv_assert_some!(childmng.child_by_id_mut(cid));
+        wv_assert_ok!(child.alloc_mem(130, 8 * 1024, Perm::RW));
+        wv_assert_ok!(child.reg_service(res, 123, 124, "test".to_string(), Version::default(), 16));
+    }
+
+    let usages = wv_assert_ok!(childmng.usage());
+    wv_assert_eq!(t, usages.len(), 1);
+    {
+        let child = wv_assert_some!(childmng.child_by_id(cid));
+        let usage = &usages[0];
+        wv_assert_eq!(t, usage.id, child.activity_id());
+        wv_assert_eq!(t, &usage.name, child.name());
+        wv_assert!(t, !usage.daemon);
+        wv_assert!(t, !usage.foreign);
+        wv_assert_eq!(t, usage.tile, child.child_tile().tile_id());
+        wv_assert_eq!(t, usage.umem.total(), QUOTA);
+        wv_assert_eq!(t, usage.umem.remaining(), QUOTA - 8 * 1024);
+        wv_assert_eq!(t, usage.umem_allocated, 8 * 1024);
+        wv_assert_eq!(t, usage.tiles, 0);
+        wv_assert_eq!(t, usage.services, 1);
+        wv_assert_eq!(t, usage.sessions, 0);
+    }
+
+    {
+        let child = wv_assert_some!(childmng.child_by_id_mut(cid));
+        wv_assert_ok!(child.unreg_service(res, 123));
+        wv_assert_ok!(child.free_mem(130));
+    }
+
+    let usages = wv_assert_ok!(childmng.usage());
+    wv_assert_eq!(t, usages[0].umem.remaining(), QUOTA);
+    wv_assert_eq!(t, usages[0].umem_allocated, 0);
+    wv_assert_eq!(t, usages[0].services, 0);
+}
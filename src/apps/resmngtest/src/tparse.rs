@@ -13,25 +13,36 @@
  * General Public License version 2 for more details.
  */
 
-use m3::col::ToString;
+use m3::col::{String, ToString};
 use m3::errors::Code;
 use m3::kif::Perm;
+use m3::mem::GlobAddr;
 use m3::test::WvTester;
 use m3::time::TimeDuration;
-use m3::{wv_assert_eq, wv_assert_err, wv_assert_ok, wv_run_test};
+use m3::util::random::LinearCongruentialGenerator;
+use m3::{format, wv_assert, wv_assert_eq, wv_assert_err, wv_assert_ok, wv_run_test};
 
 use resmng::config::{
-    AppConfig, DualName, ModDesc, MountDesc, RGateDesc, SGateDesc, SemDesc, ServiceDesc,
-    SessCrtDesc, SessionDesc, TileDesc, TileType,
+    AppConfig, DeviceDesc, DualName, EnvDesc, ModDesc, MountDesc, PhysMemDesc, RGateDesc,
+    RestartPolicy, SGateDesc, SemDesc, ServiceDesc, SessCrtDesc, SessionDesc, TileDesc, TileType,
+    WatchdogAction, WatchdogDesc,
 };
 
 pub fn run(t: &mut dyn WvTester) {
     wv_run_test!(t, errors);
+    wv_run_test!(t, error_positions);
+    wv_run_test!(t, limits);
+    wv_run_test!(t, fuzz);
     wv_run_test!(t, app_short);
     wv_run_test!(t, app_long);
     wv_run_test!(t, app_args);
+    wv_run_test!(t, app_restart);
     wv_run_test!(t, app_mounts);
+    wv_run_test!(t, app_env);
+    wv_run_test!(t, app_watchdog);
     wv_run_test!(t, app_mods);
+    wv_run_test!(t, app_physmems);
+    wv_run_test!(t, app_devices);
     wv_run_test!(t, app_services);
     wv_run_test!(t, app_sesscrts);
     wv_run_test!(t, app_sessions);
@@ -73,6 +84,132 @@ fn errors(t: &mut dyn WvTester) {
     );
 }
 
+fn error_positions(t: &mut dyn WvTester) {
+    let err = AppConfig::parse("<app args=\"foo\">\n  <foo/>\n</app>").unwrap_err();
+    wv_assert_eq!(t, err.code(), Code::InvArgs);
+    wv_assert_eq!(t, err.msg(), "2:3: <foo>: unknown element");
+
+    let err = AppConfig::parse("<app args=\"foo\"\n     bar=\"1\"/>").unwrap_err();
+    wv_assert_eq!(t, err.msg(), "2:6: <app>: unknown attribute 'bar'");
+
+    let err = AppConfig::parse("<app args=\"foo\" daemon=\"x\"/>").unwrap_err();
+    wv_assert_eq!(
+        t,
+        err.msg(),
+        "1:17: <app>: invalid value for attribute 'daemon'"
+    );
+
+    let err = AppConfig::parse("<app args=\"foo\">\n</dom>").unwrap_err();
+    wv_assert_eq!(t, err.msg(), "2:1: expected </app>, found </dom>");
+
+    let err = AppConfig::parse("<app args=\"foo").unwrap_err();
+    wv_assert_eq!(t, err.msg(), "1:6: unterminated value of attribute 'args'");
+}
+
+fn limits(t: &mut dyn WvTester) {
+    // nesting depth
+    let mut cfg = String::new();
+    for _ in 0..64 {
+        cfg.push_str("<app args=\"foo\">");
+    }
+    for _ in 0..64 {
+        cfg.push_str("</app>");
+    }
+    wv_assert_err!(t, AppConfig::parse(&cfg), Code::InvArgs);
+
+    // number of elements
+    let mut cfg = "<app args=\"foo\">".to_string();
+    for _ in 0..2048 {
+        cfg.push_str("<serial/>");
+    }
+    cfg.push_str("</app>");
+    wv_assert_err!(t, AppConfig::parse(&cfg), Code::InvArgs);
+
+    // attribute sizes
+    let cfg = format!("<app args=\"{}\"/>", "a".repeat(2048));
+    wv_assert_err!(t, AppConfig::parse(&cfg), Code::InvArgs);
+    let cfg = format!("<app {}=\"foo\"/>", "a".repeat(128));
+    wv_assert_err!(t, AppConfig::parse(&cfg), Code::InvArgs);
+
+    // the config range is given in bytes, also for non-ASCII characters
+    let cfg_str = "<app args=\"föö\"><app args=\"bär\"/></app>";
+    let cfg = wv_assert_ok!(AppConfig::parse(cfg_str));
+    let child = &cfg.domains()[0].apps()[0];
+    wv_assert_eq!(t, child.name(), "bär");
+    let (start, end) = child.cfg_range();
+    wv_assert_eq!(t, &cfg_str[start..end], "<app args=\"bär\"/>");
+}
+
+fn fuzz(t: &mut dyn WvTester) {
+    // mutations of a config that uses most elements; the parser has to reject or accept them
+    // without panicking
+    const SEED: &str = "<app args=\"root test\" usermem=\"4M\" daemon=\"1\">
+        <app args=\"foo\" restart=\"always\" backoff=\"10ms\">
+            <mod name=\"mod1\" perm=\"rw\"/>
+            <mount fs=\"m3fs\" path=\"/\"/>
+            <env name=\"PATH\" value=\"/bin\"/>
+            <sess lname=\"lserv\" gname=\"gserv\" dep=\"false\"/>
+            <tiles type=\"core|perf\" count=\"4\"/>
+            <rgate name=\"rg\" msgsize=\"128\" slots=\"4\"/>
+            <sgate name=\"rg\" credits=\"2\"/>
+            <sem name=\"sem\"/>
+            <serial/>
+        </app>
+        <dom tile=\"perf\"><app args=\"b\u{e4}r\"><serv name=\"srv\"/></app></dom>
+    </app>";
+    const TOKENS: &[&str] = &[
+        "<",
+        ">",
+        "/",
+        "=",
+        "\"",
+        " ",
+        "\n",
+        "\u{f6}",
+        "\0",
+        "<app>",
+        "</app>",
+        "<dom>",
+        "</dom>",
+        "<app args=\"x\"/>",
+        " args=\"y\"",
+        "=\"",
+    ];
+
+    wv_assert_ok!(AppConfig::parse(SEED));
+
+    let mut rng = LinearCongruentialGenerator::new(0x1234);
+    let mut invalid = 0;
+    for _ in 0..256 {
+        let mut cfg = SEED.to_string();
+        for _ in 0..1 + rng.get() % 4 {
+            let mut pos = rng.get() as usize % (cfg.len() + 1);
+            while !cfg.is_char_boundary(pos) {
+                pos -= 1;
+            }
+            let mut end = (pos + 1 + rng.get() as usize % 8).min(cfg.len());
+            while !cfg.is_char_boundary(end) {
+                end -= 1;
+            }
+
+            match rng.get() % 4 {
+                0 => cfg.replace_range(pos..end, ""),
+                1 => cfg.insert_str(pos, TOKENS[rng.get() as usize % TOKENS.len()]),
+                2 => {
+                    let part = cfg[pos..end].to_string();
+                    cfg.insert_str(end, &part);
+                },
+                _ => cfg.truncate(pos),
+            }
+        }
+
+        if AppConfig::parse(&cfg).is_err() {
+            invalid += 1;
+        }
+    }
+    wv_assert!(t, invalid > 0);
+}
+
 fn app_short(t: &mut dyn WvTester) {
     let cfg_str = "<app args=\"foo\"/>";
     let cfg = wv_assert_ok!(AppConfig::parse(cfg_str));
@@ -125,11 +262,34 @@ fn app_args(t: &mut dyn WvTester) {
         AppConfig::parse("<app args=\"foo\" getinfo=\"a\"/>"),
         Code::InvArgs
     );
+    wv_assert_err!(
+        t,
+        AppConfig::parse("<app args=\"foo\" overcommit=\"b\"/>"),
+        Code::InvArgs
+    );
+    wv_assert_err!(
+        t,
+        AppConfig::parse("<app args=\"foo\" tilectrl=\"c\"/>"),
+        Code::InvArgs
+    );
+    wv_assert_err!(
+        t,
+        AppConfig::parse("<app args=\"foo\" appctrl=\"d\"/>"),
+        Code::InvArgs
+    );
+    wv_assert_err!(
+        t,
+        AppConfig::parse("<app args=\"foo\" logctl=\"d\"/>"),
+        Code::InvArgs
+    );
 
     let cfg_str = "<app args=\"foo test 22\" daemon=\"1\"
                         usermem=\"4M\" kernmem=\"32M\"
                         time=\"4ms\" pagetables=\"18\"
-                        eps=\"64\" getinfo=\"1\"/>";
+                        eps=\"64\" getinfo=\"1\" overcommit=\"0\"
+                        tilectrl=\"1\" appctrl=\"1\" logctl=\"1\"
+                        coredump=\"/tmp/foo.core\"
+                        gdbstub=\"tcp:1234\"/>";
     let cfg = wv_assert_ok!(AppConfig::parse(cfg_str));
     wv_assert_eq!(t, cfg.name(), "foo");
     wv_assert_eq!(t, cfg.args(), &["foo", "test", "22"]);
@@ -141,6 +301,45 @@ fn app_args(t: &mut dyn WvTester) {
     wv_assert_eq!(t, cfg.page_tables(), Some(18));
     wv_assert_eq!(t, cfg.eps(), Some(64));
     wv_assert_eq!(t, cfg.can_get_info(), true);
+    wv_assert_eq!(t, cfg.overcommit(), false);
+    wv_assert_eq!(t, cfg.can_ctrl_tiles(), true);
+    wv_assert_eq!(t, cfg.can_ctrl_apps(), true);
+    wv_assert_eq!(t, cfg.can_ctrl_log(), true);
+    wv_assert_eq!(t, cfg.coredump(), Some("/tmp/foo.core"));
+    wv_assert_eq!(t, cfg.gdbstub(), Some("tcp:1234"));
+}
+
+fn app_restart(t: &mut dyn WvTester) {
+    wv_assert_err!(
+        t,
+        AppConfig::parse("<app args=\"foo\" restart=\"sometimes\"/>"),
+        Code::InvArgs
+    );
+    wv_assert_err!(
+        t,
+        AppConfig::parse("<app args=\"foo\" max-restarts=\"x\"/>"),
+        Code::InvArgs
+    );
+    wv_assert_err!(
+        t,
+        AppConfig::parse("<app args=\"foo\" backoff=\"10\"/>"),
+        Code::InvArgs
+    );
+
+    let cfg = wv_assert_ok!(AppConfig::parse("<app args=\"foo\"/>"));
+    wv_assert_eq!(t, cfg.restart(), RestartPolicy::Never);
+    wv_assert_eq!(t, cfg.max_restarts(), None);
+    wv_assert_eq!(t, cfg.backoff(), TimeDuration::ZERO);
+
+    let cfg_str = "<app args=\"foo\" restart=\"on-failure\"
+                        max-restarts=\"3\" backoff=\"50ms\"/>";
+    let cfg = wv_assert_ok!(AppConfig::parse(cfg_str));
+    wv_assert_eq!(t, cfg.restart(), RestartPolicy::OnFailure);
+    wv_assert_eq!(t, cfg.max_restarts(), Some(3));
+    wv_assert_eq!(t, cfg.backoff(), TimeDuration::from_millis(50));
+
+    let cfg = wv_assert_ok!(AppConfig::parse("<app args=\"foo\" restart=\"always\"/>"));
+    wv_assert_eq!(t, cfg.restart(), RestartPolicy::Always);
 }
 
 fn app_mounts(t: &mut dyn WvTester) {
@@ -176,6 +375,90 @@ fn app_mounts(t: &mut dyn WvTester) {
     }
 }
 
+fn app_env(t: &mut dyn WvTester) {
+    wv_assert_err!(
+        t,
+        AppConfig::parse("<app args=\"foo\"><env /></app>"),
+        Code::InvArgs
+    );
+    wv_assert_err!(
+        t,
+        AppConfig::parse("<app args=\"foo\"><env name=\"a=b\" value=\"c\"/></app>"),
+        Code::InvArgs
+    );
+    wv_assert_err!(
+        t,
+        AppConfig::parse("<app args=\"foo\"><env name=\"a\" foo=\"c\"/></app>"),
+        Code::InvArgs
+    );
+
+    {
+        let cfg = wv_assert_ok!(AppConfig::parse(
+            "<app args=\"foo\"><env name=\"A\" value=\"1\"/><env name=\"B\"/></app>"
+        ));
+        wv_assert_eq!(t, cfg.env(), &[
+            EnvDesc::new("A".to_string(), "1".to_string()),
+            EnvDesc::new("B".to_string(), "".to_string())
+        ]);
+    }
+}
+
+fn app_watchdog(t: &mut dyn WvTester) {
+    wv_assert_err!(
+        t,
+        AppConfig::parse("<app args=\"foo\"><watchdog /></app>"),
+        Code::InvArgs
+    );
+    wv_assert_err!(
+        t,
+        AppConfig::parse("<app args=\"foo\"><watchdog timeout=\"0\"/></app>"),
+        Code::InvArgs
+    );
+    wv_assert_err!(
+        t,
+        AppConfig::parse("<app args=\"foo\"><watchdog timeout=\"1s\" action=\"foo\"/></app>"),
+        Code::InvArgs
+    );
+
+    {
+        let cfg = wv_assert_ok!(AppConfig::parse("<app args=\"foo\"/>"));
+        wv_assert_eq!(t, cfg.watchdog(), None);
+    }
+
+    {
+        let cfg = wv_assert_ok!(AppConfig::parse(
+            "<app args=\"foo\"><watchdog timeout=\"200ms\"/></app>"
+        ));
+        wv_assert_eq!(
+            t,
+            cfg.watchdog(),
+            Some(&WatchdogDesc::new(
+                TimeDuration::from_millis(200),
+                WatchdogAction::Log
+            ))
+        );
+        wv_assert_eq!(
+            t,
+            cfg.watchdog().unwrap().interval(),
+            TimeDuration::from_millis(100)
+        );
+    }
+
+    {
+        let cfg = wv_assert_ok!(AppConfig::parse(
+            "<app args=\"foo\"><watchdog timeout=\"2s\" action=\"kill\"/></app>"
+        ));
+        wv_assert_eq!(
+            t,
+            cfg.watchdog(),
+            Some(&WatchdogDesc::new(
+                TimeDuration::from_secs(2),
+                WatchdogAction::Kill
+            ))
+        );
+    }
+}
+
 fn app_mods(t: &mut dyn WvTester) {
     wv_assert_err!(
         t,
@@ -226,6 +509,77 @@ fn app_mods(t: &mut dyn WvTester) {
     ]);
 }
 
+fn app_physmems(t: &mut dyn WvTester) {
+    wv_assert_err!(
+        t,
+        AppConfig::parse("<app args=\"foo\"><physmem/></app>"),
+        Code::InvArgs
+    );
+    wv_assert_err!(
+        t,
+        AppConfig::parse("<app args=\"foo\"><physmem addr=\"0x1000\" size=\"4K\"/></app>"),
+        Code::InvArgs
+    );
+    wv_assert_err!(
+        t,
+        AppConfig::parse("<app args=\"foo\"><physmem name=\"a\" size=\"4K\"/></app>"),
+        Code::InvArgs
+    );
+    wv_assert_err!(
+        t,
+        AppConfig::parse("<app args=\"foo\"><physmem name=\"a\" addr=\"0x1000\"/></app>"),
+        Code::InvArgs
+    );
+    wv_assert_err!(
+        t,
+        AppConfig::parse("<app args=\"foo\"><physmem name=\"a\" addr=\"0xg\" size=\"4K\"/></app>"),
+        Code::InvArgs
+    );
+
+    let cfg_str = "<app args=\"foo\">
+        <physmem name=\"uart\" addr=\"0x1000\" size=\"4K\"/>
+        <physmem name=\"rom\" addr=\"8192\" size=\"0x100\" perm=\"r\"/>
+    </app>";
+    let cfg = wv_assert_ok!(AppConfig::parse(cfg_str));
+    wv_assert_eq!(t, cfg.physmems(), &[
+        PhysMemDesc::new("uart".to_string(), GlobAddr::new(0x1000), 0x1000, Perm::RW),
+        PhysMemDesc::new("rom".to_string(), GlobAddr::new(0x2000), 0x100, Perm::R)
+    ]);
+}
+
+fn app_devices(t: &mut dyn WvTester) {
+    wv_assert_err!(
+        t,
+        AppConfig::parse("<app args=\"foo\"><dev name=\"nic\"/></app>"),
+        Code::InvArgs
+    );
+    wv_assert_err!(
+        t,
+        AppConfig::parse("<app args=\"foo\"><dev compatible=\"intel,e1000\"/></app>"),
+        Code::InvArgs
+    );
+    wv_assert_err!(
+        t,
+        AppConfig::parse("<app args=\"foo\"><dev name=\"a=b\" compatible=\"x\"/></app>"),
+        Code::InvArgs
+    );
+    wv_assert_err!(
+        t,
+        AppConfig::parse("<app args=\"foo\"><dev name=\"a\" compatible=\"x\" index=\"y\"/></app>"),
+        Code::InvArgs
+    );
+
+    let cfg_str = "<app args=\"foo\">
+        <dev name=\"nic\" compatible=\"intel,e1000\"/>
+        <dev name=\"uart1\" compatible=\"ns16550a\" index=\"1\"/>
+    </app>";
+    let cfg = wv_assert_ok!(AppConfig::parse(cfg_str));
+    wv_assert_eq!(t, cfg.devices(), &[
+        DeviceDesc::new("nic".to_string(), "intel,e1000".to_string(), 0),
+        DeviceDesc::new("uart1".to_string(), "ns16550a".to_string(), 1)
+    ]);
+}
+
 fn app_services(t: &mut dyn WvTester) {
     wv_assert_err!(
         t,
@@ -236,11 +590,13 @@ fn app_services(t: &mut dyn WvTester) {
     let cfg_str = "<app args=\"foo\">
         <serv name=\"service\"/>
         <serv lname=\"lserv\" gname=\"gserv\"/>
+        <serv name=\"fs2\" alias=\"fs\"/>
     </app>";
     let cfg = wv_assert_ok!(AppConfig::parse(cfg_str));
     wv_assert_eq!(t, cfg.services(), &[
         ServiceDesc::new(DualName::new_simple("service".to_string())),
-        ServiceDesc::new(DualName::new("lserv".to_string(), "gserv".to_string()))
+        ServiceDesc::new(DualName::new("lserv".to_string(), "gserv".to_string())),
+        ServiceDesc::new(DualName::new_simple("fs2".to_string())).with_alias("fs".to_string())
     ]);
 }
 
@@ -19,7 +19,7 @@ use m3::rc::Rc;
 use m3::tcu::TileId;
 use m3::test::WvTester;
 use m3::tiles::Tile;
-use m3::{wv_assert_eq, wv_assert_err, wv_assert_ok, wv_run_test};
+use m3::{wv_assert, wv_assert_eq, wv_assert_err, wv_assert_ok, wv_run_test};
 
 use resmng::resources::tiles::TileManager;
 
@@ -45,7 +45,9 @@ fn create_tiles() -> TileManager {
 
 pub fn run(t: &mut dyn WvTester) {
     wv_run_test!(t, find);
+    wv_run_test!(t, find_placed);
     wv_run_test!(t, usage);
+    wv_run_test!(t, offline);
 }
 
 fn find(t: &mut dyn WvTester) {
@@ -75,6 +77,67 @@ fn find(t: &mut dyn WvTester) {
     wv_assert_eq!(t, arm.tile_id(), TileId::new(0, 2));
 }
 
+fn find_placed(t: &mut dyn WvTester) {
+    let mut mng = TileManager::default();
+    for (i, id) in [TileId::new(0, 1), TileId::new(0, 6), TileId::new(1, 4)]
+        .iter()
+        .enumerate()
+    {
+        mng.add(Rc::new(Tile::new_bind_with(
+            *id,
+            TileDesc::new(TileType::Comp, TileISA::RISCV, 0),
+            64 + i as u64,
+        )));
+    }
+
+    let desc = TileDesc::new(TileType::Comp, TileISA::RISCV, 0);
+
+    // specific tile
+    let tile = wv_assert_ok!(mng.find_with(desc, Some(TileId::new(0, 6)), None));
+    wv_assert_eq!(t, tile.tile_id(), TileId::new(0, 6));
+    wv_assert_err!(
+        t,
+        mng.find_with(desc, Some(TileId::new(0, 2)), None),
+        Code::NotFound
+    );
+    wv_assert_err!(
+        t,
+        mng.find_with(
+            TileDesc::new(TileType::Comp, TileISA::ARM, 0),
+            Some(TileId::new(0, 6)),
+            None
+        ),
+        Code::NotFound
+    );
+
+    // closest tile
+    let tile = wv_assert_ok!(mng.find_with(desc, None, Some(TileId::new(0, 5))));
+    wv_assert_eq!(t, tile.tile_id(), TileId::new(0, 6));
+    let tile = wv_assert_ok!(mng.find_with(desc, None, Some(TileId::new(1, 0))));
+    wv_assert_eq!(t, tile.tile_id(), TileId::new(1, 4));
+
+    // without a free tile on the same chip, the closest tile on another chip is chosen
+    mng.add_user(&tile);
+    let tile = wv_assert_ok!(mng.find_with(desc, None, Some(TileId::new(1, 0))));
+    wv_assert_eq!(t, tile.tile_id(), TileId::new(0, 1));
+
+    wv_assert_eq!(
+        t,
+        TileManager::distance(TileId::new(0, 3), TileId::new(0, 1)),
+        2
+    );
+    wv_assert_eq!(
+        t,
+        TileManager::distance(TileId::new(0, 1), TileId::new(0, 3)),
+        2
+    );
+    wv_assert!(
+        t,
+        TileManager::distance(TileId::new(0, 1), TileId::new(1, 1))
+            > TileManager::distance(TileId::new(0, 0), TileId::new(0, 255))
+    );
+}
+
 fn usage(t: &mut dyn WvTester) {
     let mng = create_tiles();
 
@@ -101,3 +164,31 @@ fn usage(t: &mut dyn WvTester) {
 
     wv_assert_ok!(mng.find(TileDesc::new(TileType::Comp, TileISA::RISCV, 0)));
 }
+
+fn offline(t: &mut dyn WvTester) {
+    let mng = create_tiles();
+    let desc = TileDesc::new(TileType::Comp, TileISA::RISCV, 0);
+
+    // unknown tiles cannot be controlled
+    wv_assert_err!(
+        t,
+        mng.set_online(TileId::new(0, 7), false, desc),
+        Code::NotFound
+    );
+    // tiles are online by default
+    wv_assert_err!(
+        t,
+        mng.set_online(TileId::new(0, 1), true, desc),
+        Code::InvState
+    );
+
+    // tiles in use cannot be taken offline
+    let riscv = wv_assert_ok!(mng.find(desc));
+    mng.add_user(&riscv);
+    wv_assert_err!(
+        t,
+        mng.set_online(TileId::new(0, 1), false, desc),
+        Code::InvState
+    );
+    mng.remove_user(&riscv);
+}
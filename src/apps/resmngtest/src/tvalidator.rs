@@ -13,8 +13,9 @@
  * General Public License version 2 for more details.
  */
 
+use m3::com::MemCap;
 use m3::errors::Code;
-use m3::kif::{boot, TileAttr, TileDesc, TileISA, TileType};
+use m3::kif::{boot, TileAttr, TileDesc, TileISA, TileType, FIRST_FREE_SEL};
 use m3::mem::GlobAddr;
 use m3::rc::Rc;
 use m3::tcu::TileId;
@@ -24,6 +25,7 @@ use m3::test::WvTester;
 use m3::{wv_assert_err, wv_assert_ok, wv_run_test};
 
 use resmng::config::{validator, AppConfig};
+use resmng::resources::memory::MemMod;
 use resmng::resources::Resources;
 
 pub fn run(t: &mut dyn WvTester) {
@@ -31,6 +33,8 @@ pub fn run(t: &mut dyn WvTester) {
     wv_run_test!(t, gates);
     wv_run_test!(t, tiles);
     wv_run_test!(t, mods);
+    wv_run_test!(t, restarts);
+    wv_run_test!(t, physmems);
 }
 
 fn services(t: &mut dyn WvTester) {
@@ -233,3 +237,85 @@ fn mods(t: &mut dyn WvTester) {
         wv_assert_ok!(validator::validate(&cfg, &res));
     }
 }
+
+fn restarts(t: &mut dyn WvTester) {
+    let res = Resources::default();
+
+    {
+        let cfg_str = "<app args=\"ourself\">
+            <app args=\"foo\" restart=\"always\">
+                <app args=\"bar\"/>
+            </app>
+        </app>";
+        let cfg = wv_assert_ok!(AppConfig::parse(cfg_str));
+        wv_assert_err!(t, validator::validate(&cfg, &res), Code::NotSup);
+    }
+
+    {
+        let cfg_str = "<app args=\"ourself\">
+            <app args=\"foo\">
+                <app args=\"bar\" restart=\"on-failure\"/>
+            </app>
+        </app>";
+        let cfg = wv_assert_ok!(AppConfig::parse(cfg_str));
+        wv_assert_ok!(validator::validate(&cfg, &res));
+    }
+}
+
+fn physmems(t: &mut dyn WvTester) {
+    let mut res = Resources::default();
+    // the validator does not use the capability
+    res.memory_mut().add(Rc::new(MemMod::new(
+        MemCap::new_bind(FIRST_FREE_SEL),
+        GlobAddr::new(0x10000),
+        0x10000,
+        true,
+    )));
+
+    {
+        let cfg_str = "<app args=\"ourself\">
+            <app args=\"foo\">
+                <physmem name=\"dev\" addr=\"0x8000\" size=\"0x1000\"/>
+            </app>
+        </app>";
+        let cfg = wv_assert_ok!(AppConfig::parse(cfg_str));
+        wv_assert_err!(t, validator::validate(&cfg, &res), Code::NotFound);
+    }
+
+    {
+        let cfg_str = "<app args=\"ourself\">
+            <app args=\"foo\">
+                <physmem name=\"dev\" addr=\"0x1F000\" size=\"0x2000\"/>
+            </app>
+        </app>";
+        let cfg = wv_assert_ok!(AppConfig::parse(cfg_str));
+        wv_assert_err!(t, validator::validate(&cfg, &res), Code::NotFound);
+    }
+
+    {
+        let cfg_str = "<app args=\"ourself\">
+            <app args=\"foo\">
+                <physmem name=\"dev\" addr=\"0x10000\" size=\"0x2000\"/>
+            </app>
+            <app args=\"bar\">
+                <physmem name=\"dev\" addr=\"0x11000\" size=\"0x1000\" perm=\"r\"/>
+            </app>
+        </app>";
+        let cfg = wv_assert_ok!(AppConfig::parse(cfg_str));
+        wv_assert_err!(t, validator::validate(&cfg, &res), Code::Exists);
+    }
+
+    {
+        let cfg_str = "<app args=\"ourself\">
+            <app args=\"foo\">
+                <physmem name=\"dev\" addr=\"0x10000\" size=\"0x2000\" perm=\"r\"/>
+            </app>
+            <app args=\"bar\">
+                <physmem name=\"dev\" addr=\"0x11000\" size=\"0x1000\" perm=\"r\"/>
+                <physmem name=\"dev2\" addr=\"0x12000\" size=\"4K\"/>
+            </app>
+        </app>";
+        let cfg = wv_assert_ok!(AppConfig::parse(cfg_str));
+        wv_assert_ok!(validator::validate(&cfg, &res));
+    }
+}
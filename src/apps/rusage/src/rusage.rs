@@ -0,0 +1,67 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+#![no_std]
+
+use m3::errors::Error;
+use m3::println;
+use m3::tiles::Activity;
+
+const MIB: usize = 1024 * 1024;
+
+#[no_mangle]
+pub fn main() -> Result<(), Error> {
+    let usages = Activity::own()
+        .resmng()
+        .unwrap()
+        .get_usage()
+        .expect("Unable to get resource usage");
+
+    println!(
+        "{:2} | {:5} | {:>10} | {:>18} | {:>10} | {:>9} | {:>9} | {:>10} | {:>14} | {:>3} | {:>3} | {:>3} | Name",
+        "ID", "Tile", "Endpoints", "Time", "CPU", "UserMem", "Allocated", "Committed", "KernelMem", "Til", "Srv", "Ses"
+    );
+    for u in usages {
+        println!(
+            "{:2} | {:5} | {:2}:{:3}/{:3} | {:6}us/{:8}us | {:8}us | {:4}M/{:3}M | {:8}M | {:9}M | {:2}:{:4}M/{:4}M | {:3} | {:3} | {:3} | {}{}",
+            u.id,
+            u.tile,
+            u.eps.id(),
+            u.eps.remaining(),
+            u.eps.total(),
+            u.time.remaining().as_micros(),
+            u.time.total().as_micros(),
+            u.cpu_time.as_micros(),
+            u.umem.remaining() / MIB,
+            u.umem.total() / MIB,
+            u.umem_allocated / MIB,
+            u.umem_committed / MIB,
+            u.kmem.id(),
+            u.kmem.remaining() / MIB,
+            u.kmem.total() / MIB,
+            u.tiles,
+            u.services,
+            u.sessions,
+            u.name,
+            match (u.daemon, u.foreign) {
+                (_, true) => " (foreign)",
+                (true, false) => " (daemon)",
+                (false, false) => "",
+            },
+        );
+    }
+
+    Ok(())
+}
@@ -26,6 +26,8 @@ use m3::net::IpAddr;
 use m3::test::{DefaultWvTester, WvTester};
 use m3::{println, wv_run_suite};
 
+mod tcompat;
+mod tdns;
 mod traw;
 mod ttcp;
 mod tudp;
@@ -54,7 +56,9 @@ pub fn main() -> Result<(), Error> {
     let mut tester = DefaultWvTester::default();
     wv_run_suite!(tester, traw::run);
     wv_run_suite!(tester, tudp::run);
+    wv_run_suite!(tester, tdns::run);
     wv_run_suite!(tester, ttcp::run);
+    wv_run_suite!(tester, tcompat::run);
     println!("{}", tester);
     Ok(())
 }
@@ -0,0 +1,249 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use core::mem;
+
+use m3::com::Semaphore;
+use m3::compat::errno::{Errno, __m3c_errno};
+use m3::compat::socket::{
+    self, PollFd, SockAddr, SockAddrIn, __m3c_bsd_bind, __m3c_bsd_close, __m3c_bsd_socket, AF_INET,
+    AF_INET6, IPPROTO_TCP, MSG_DONTWAIT, POLLIN, POLLOUT, SOCK_DGRAM, SOCK_NONBLOCK, SOCK_RAW,
+    SOCK_STREAM,
+};
+use m3::errors::{Code, Error};
+use m3::net::Endpoint;
+use m3::test::WvTester;
+use m3::{wv_assert, wv_assert_eq, wv_assert_ok, wv_run_test};
+
+pub fn run(t: &mut dyn WvTester) {
+    wv_run_test!(t, errno);
+    wv_run_test!(t, errors);
+    wv_run_test!(t, server_errors);
+    wv_run_test!(t, c_interface);
+    wv_run_test!(t, udp);
+    wv_run_test!(t, tcp);
+}
+
+fn errno(t: &mut dyn WvTester) {
+    let mapping = [
+        (Code::NoPerm, Errno::EACCES),
+        (Code::InvArgs, Errno::EINVAL),
+        (Code::InvState, Errno::EINVAL),
+        (Code::OutOfMem, Errno::ENOMEM),
+        (Code::NoSuchFile, Errno::ENOENT),
+        (Code::NotSup, Errno::EOPNOTSUPP),
+        (Code::NoSpace, Errno::ENOSPC),
+        (Code::Exists, Errno::EEXIST),
+        (Code::IsDir, Errno::EISDIR),
+        (Code::IsNoDir, Errno::ENOTDIR),
+        (Code::Timeout, Errno::ETIMEDOUT),
+        (Code::BadFd, Errno::EBADF),
+        (Code::WouldBlock, Errno::EAGAIN),
+        (Code::NoCredits, Errno::EAGAIN),
+        (Code::InProgress, Errno::EINPROGRESS),
+        (Code::AlreadyInProgress, Errno::EALREADY),
+        (Code::NotConnected, Errno::ENOTCONN),
+        (Code::IsConnected, Errno::EISCONN),
+        (Code::SocketClosed, Errno::EPIPE),
+        (Code::ConnectionFailed, Errno::ECONNREFUSED),
+        (Code::ConnClosed, Errno::ECONNRESET),
+        // codes without counterpart are reported as I/O errors
+        (Code::EndOfFile, Errno::EIO),
+        (Code::RecvGone, Errno::EIO),
+    ];
+    for (code, errno) in mapping {
+        wv_assert_eq!(t, Errno::from(code), errno);
+        wv_assert_eq!(t, Errno::from(Error::new(code)), errno);
+    }
+
+    // the numbers are the ones of Linux
+    wv_assert_eq!(t, Errno::EAGAIN.0, 11);
+    wv_assert_eq!(t, Errno::ECONNREFUSED.0, 111);
+    wv_assert_eq!(t, unsafe { __m3c_errno(Code::NoPerm) }, 13);
+    wv_assert_eq!(t, unsafe { __m3c_errno(Code::NotConnected) }, 107);
+}
+
+fn errors(t: &mut dyn WvTester) {
+    wv_assert_ok!(socket::init("net0"));
+
+    wv_assert_eq!(
+        t,
+        socket::socket(1, SOCK_STREAM, 0),
+        Err(Errno::EAFNOSUPPORT)
+    );
+    wv_assert_eq!(t, socket::socket(AF_INET, 42, 0), Err(Errno::EINVAL));
+    wv_assert_eq!(
+        t,
+        socket::socket(AF_INET, SOCK_DGRAM, IPPROTO_TCP),
+        Err(Errno::EPROTONOSUPPORT)
+    );
+    wv_assert_eq!(t, socket::close(1000), Err(Errno::EBADF));
+
+    let udp = wv_assert_ok!(socket::socket(AF_INET, SOCK_DGRAM, 0));
+    wv_assert_eq!(t, socket::listen(udp, 1), Err(Errno::EOPNOTSUPP));
+    wv_assert_eq!(t, socket::send(udp, &[0], 0), Err(Errno::EDESTADDRREQ));
+    // MSG_OOB is not supported
+    wv_assert_eq!(t, socket::recv(udp, &mut [0], 1), Err(Errno::EOPNOTSUPP));
+    wv_assert_ok!(socket::close(udp));
+
+    let tcp = wv_assert_ok!(socket::socket(AF_INET6, SOCK_STREAM, 0));
+    wv_assert_eq!(t, socket::listen(tcp, 1), Err(Errno::EDESTADDRREQ));
+    wv_assert_eq!(t, socket::recv(tcp, &mut [0], 0), Err(Errno::ENOTCONN));
+    wv_assert_ok!(socket::close(tcp));
+}
+
+fn server_errors(t: &mut dyn WvTester) {
+    wv_assert_ok!(socket::init("net0"));
+
+    // the session neither allows raw sockets nor the port 3000
+    wv_assert_eq!(t, socket::socket(AF_INET, SOCK_RAW, 0), Err(Errno::EACCES));
+
+    let udp = wv_assert_ok!(socket::socket(AF_INET, SOCK_DGRAM, 0));
+    let ep = Endpoint::new(crate::NET0_IP.get(), 3000);
+    wv_assert_eq!(t, socket::bind(udp, ep), Err(Errno::EACCES));
+    wv_assert_ok!(socket::close(udp));
+
+    let tcp1 = wv_assert_ok!(socket::socket(AF_INET, SOCK_STREAM, 0));
+    wv_assert_eq!(t, socket::send(tcp1, &[0], 0), Err(Errno::ENOTCONN));
+    // the port is only checked by the server on listen
+    wv_assert_ok!(socket::bind(tcp1, ep));
+    wv_assert_eq!(t, socket::bind(tcp1, ep), Err(Errno::EINVAL));
+    wv_assert_eq!(t, socket::listen(tcp1, 1), Err(Errno::EACCES));
+
+    // the session is limited to two sockets
+    let tcp2 = wv_assert_ok!(socket::socket(AF_INET, SOCK_STREAM, 0));
+    wv_assert_eq!(
+        t,
+        socket::socket(AF_INET, SOCK_STREAM, 0),
+        Err(Errno::ENOSPC)
+    );
+
+    wv_assert_ok!(socket::close(tcp2));
+    wv_assert_ok!(socket::close(tcp1));
+    wv_assert_eq!(t, socket::close(tcp1), Err(Errno::EBADF));
+}
+
+fn c_interface(t: &mut dyn WvTester) {
+    wv_assert_ok!(socket::init("net0"));
+
+    // errors are returned as negated error numbers
+    wv_assert_eq!(
+        t,
+        unsafe { __m3c_bsd_socket(1, SOCK_STREAM, 0) },
+        -Errno::EAFNOSUPPORT.0 as isize
+    );
+    wv_assert_eq!(
+        t,
+        unsafe { __m3c_bsd_close(1000) },
+        -Errno::EBADF.0 as isize
+    );
+
+    let fd = unsafe { __m3c_bsd_socket(AF_INET, SOCK_DGRAM, 0) };
+    wv_assert!(t, fd >= 0);
+    let fd = fd as i32;
+
+    // the port is given in network byte order; the address is ignored
+    let sin = SockAddrIn {
+        sin_family: AF_INET as u16,
+        sin_port: 2001u16.to_be(),
+        ..Default::default()
+    };
+    let addr = &sin as *const SockAddrIn as *const SockAddr;
+    let len = mem::size_of::<SockAddrIn>() as u32;
+
+    wv_assert_eq!(
+        t,
+        unsafe { __m3c_bsd_bind(fd, core::ptr::null(), len) },
+        -Errno::EINVAL.0 as isize
+    );
+    wv_assert_eq!(
+        t,
+        unsafe { __m3c_bsd_bind(fd, addr, len - 1) },
+        -Errno::EINVAL.0 as isize
+    );
+    let unix = SockAddr {
+        sa_family: 1,
+        sa_data: [0; 14],
+    };
+    wv_assert_eq!(
+        t,
+        unsafe { __m3c_bsd_bind(fd, &unix, mem::size_of::<SockAddr>() as u32) },
+        -Errno::EAFNOSUPPORT.0 as isize
+    );
+    wv_assert_eq!(t, unsafe { __m3c_bsd_bind(fd, addr, len) }, 0);
+
+    wv_assert_eq!(t, unsafe { __m3c_bsd_close(fd) }, 0);
+}
+
+fn udp(t: &mut dyn WvTester) {
+    wv_assert_ok!(socket::init("net0"));
+
+    let fd = wv_assert_ok!(socket::socket(AF_INET, SOCK_DGRAM | SOCK_NONBLOCK, 0));
+    wv_assert_ok!(socket::bind(fd, Endpoint::new(crate::NET0_IP.get(), 2000)));
+
+    let mut buf = [0u8; 16];
+    wv_assert_eq!(t, socket::recv(fd, &mut buf, 0), Err(Errno::EAGAIN));
+
+    let dest = Endpoint::new(crate::DST_IP.get(), 1337);
+    wv_assert_eq!(t, socket::sendto(fd, b"compat", 0, Some(dest)), Ok(6));
+
+    // use a high timeout to workaround the high ARP-request delay with the loopback device
+    let mut fds = [PollFd {
+        fd,
+        events: POLLIN,
+        revents: 0,
+    }];
+    wv_assert_eq!(t, socket::poll(&mut fds, 6000), 1);
+    wv_assert_eq!(t, fds[0].revents, POLLIN);
+
+    let (amount, src) = wv_assert_ok!(socket::recvfrom(fd, &mut buf, 0));
+    wv_assert_eq!(t, &buf[0..amount], b"compat");
+    wv_assert_eq!(t, src, Some(dest));
+
+    wv_assert_ok!(socket::close(fd));
+}
+
+fn tcp(t: &mut dyn WvTester) {
+    wv_assert_ok!(socket::init("net0"));
+
+    let fd = wv_assert_ok!(socket::socket(AF_INET, SOCK_STREAM, IPPROTO_TCP));
+
+    wv_assert_ok!(Semaphore::attach("net-tcp").unwrap().down());
+
+    let dest = Endpoint::new(crate::DST_IP.get(), 1338);
+    wv_assert_ok!(socket::connect(fd, dest));
+
+    let mut fds = [PollFd {
+        fd,
+        events: POLLOUT,
+        revents: 0,
+    }];
+    wv_assert_eq!(t, socket::poll(&mut fds, 0), 1);
+    wv_assert_eq!(t, fds[0].revents, POLLOUT);
+
+    let send_buf = [0x42u8; 32];
+    wv_assert_eq!(t, socket::send(fd, &send_buf, 0), Ok(send_buf.len()));
+
+    let mut recv_buf = [0u8; 32];
+    let mut received = 0;
+    while received < recv_buf.len() {
+        fds[0].events = POLLIN;
+        wv_assert_eq!(t, socket::poll(&mut fds, -1), 1);
+        received += wv_assert_ok!(socket::recv(fd, &mut recv_buf[received..], MSG_DONTWAIT));
+    }
+    wv_assert!(t, recv_buf == send_buf);
+
+    wv_assert_ok!(socket::close(fd));
+}
@@ -0,0 +1,259 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use m3::client::Network;
+use m3::col::Vec;
+use m3::errors::Code;
+use m3::net::{DGramSocket, DgramSocketArgs, DnsQuery, Endpoint, IpAddr, Socket, UdpSocket, DNS};
+use m3::rc::Rc;
+use m3::test::WvTester;
+use m3::time::TimeDuration;
+use m3::vfs::{File, FileEvent, FileRef, FileWaiter};
+use m3::{wv_assert, wv_assert_eq, wv_assert_err, wv_assert_ok, wv_run_test};
+
+// use a higher timeout than the smoltcp-internal timeout to workaround the ARP-request delay
+const TIMEOUT: TimeDuration = TimeDuration::from_secs(6);
+
+const RCODE_SERVER_FAILURE: u16 = 2;
+const RCODE_NAME_ERROR: u16 = 3;
+
+pub fn run(t: &mut dyn WvTester) {
+    wv_run_test!(t, resolve_and_cache);
+    wv_run_test!(t, resolve_v6);
+    wv_run_test!(t, cache_expiry);
+    wv_run_test!(t, negative_cache);
+    wv_run_test!(t, server_failure);
+    wv_run_test!(t, failover);
+}
+
+/// A nameserver on the loopback interface that answers the queries of the test
+struct NameServer {
+    sock: FileRef<UdpSocket>,
+    buf: [u8; 512],
+}
+
+impl NameServer {
+    fn new(net: Rc<Network>) -> Self {
+        let mut sock = wv_assert_ok!(UdpSocket::new(
+            DgramSocketArgs::new(net)
+                .send_buffer(2, 1024)
+                .recv_buffer(2, 1024)
+        ));
+        wv_assert_ok!(sock.set_blocking(false));
+        wv_assert_ok!(sock.bind(53));
+        Self {
+            sock,
+            buf: [0; 512],
+        }
+    }
+
+    fn has_request(&self) -> bool {
+        self.sock.has_data()
+    }
+
+    /// Waits for the request of the given query, while the resolver asks the nameservers in order
+    fn receive(
+        &mut self,
+        t: &mut dyn WvTester,
+        dns: &mut DNS,
+        query: &mut DnsQuery,
+    ) -> (usize, Endpoint) {
+        loop {
+            // the query is not finished before we responded
+            wv_assert_eq!(t, wv_assert_ok!(dns.poll(query)), None);
+
+            let mut waiter = FileWaiter::default();
+            waiter.add(self.sock.fd(), FileEvent::INPUT);
+            waiter.wait_for(query.remaining());
+
+            if self.sock.has_data() {
+                break wv_assert_ok!(self.sock.recv_from(&mut self.buf));
+            }
+        }
+    }
+
+    /// Answers the request of the given query with `rcode` and the address `data`, if given
+    fn respond(
+        &mut self,
+        t: &mut dyn WvTester,
+        dns: &mut DNS,
+        query: &mut DnsQuery,
+        rcode: u16,
+        data: Option<(&[u8], u32)>,
+    ) {
+        let (len, src) = self.receive(t, dns, query);
+        let req = &self.buf[0..len];
+
+        let mut resp = Vec::new();
+        // the transaction id of the request
+        resp.extend_from_slice(&req[0..2]);
+        // response with recursion desired and available
+        resp.extend_from_slice(&(0x8180 | rcode).to_be_bytes());
+        resp.extend_from_slice(&1u16.to_be_bytes());
+        resp.extend_from_slice(&(data.is_some() as u16).to_be_bytes());
+        resp.extend_from_slice(&[0, 0, 0, 0]);
+        // repeat the question
+        resp.extend_from_slice(&req[12..]);
+        if let Some((addr, ttl)) = data {
+            // refer to the name in the question and use the requested type and class
+            resp.extend_from_slice(&[0xC0, 0x0C]);
+            resp.extend_from_slice(&req[len - 4..]);
+            resp.extend_from_slice(&ttl.to_be_bytes());
+            resp.extend_from_slice(&(addr.len() as u16).to_be_bytes());
+            resp.extend_from_slice(addr);
+        }
+
+        wv_assert_ok!(self.sock.send_to(&resp, src));
+    }
+}
+
+fn setup() -> (Rc<Network>, NameServer, DNS) {
+    let net = wv_assert_ok!(Network::new("dns"));
+    let ns = NameServer::new(net.clone());
+    let mut dns = DNS::default();
+    dns.add_nameserver(IpAddr::new(127, 0, 0, 1));
+    (net, ns, dns)
+}
+
+fn resolve_and_cache(t: &mut dyn WvTester) {
+    let (net, mut ns, mut dns) = setup();
+
+    let mut query = wv_assert_ok!(dns.resolve_async(net.clone(), "Host.M3", TIMEOUT));
+    wv_assert!(t, !query.is_done());
+    wv_assert!(t, query.fd().is_some());
+    ns.respond(t, &mut dns, &mut query, 0, Some((&[10, 0, 0, 1], 60)));
+    wv_assert_eq!(
+        t,
+        wv_assert_ok!(dns.wait(&mut query)),
+        IpAddr::new(10, 0, 0, 1)
+    );
+    wv_assert!(t, query.is_done());
+
+    // names are case-insensitive and the answer is cached
+    let mut query = wv_assert_ok!(dns.resolve_async(net.clone(), "host.m3", TIMEOUT));
+    wv_assert!(t, query.is_done());
+    wv_assert_eq!(t, query.fd(), None);
+    wv_assert_eq!(
+        t,
+        wv_assert_ok!(dns.poll(&mut query)),
+        Some(IpAddr::new(10, 0, 0, 1))
+    );
+    wv_assert_eq!(
+        t,
+        wv_assert_ok!(dns.get_addr(net.clone(), "host.m3", TIMEOUT)),
+        IpAddr::new(10, 0, 0, 1)
+    );
+    wv_assert!(t, !ns.has_request());
+
+    // after flushing the cache, the nameserver is asked again
+    dns.flush_cache();
+    let mut query = wv_assert_ok!(dns.resolve_async(net, "host.m3", TIMEOUT));
+    ns.respond(t, &mut dns, &mut query, 0, Some((&[10, 0, 0, 2], 60)));
+    wv_assert_eq!(
+        t,
+        wv_assert_ok!(dns.wait(&mut query)),
+        IpAddr::new(10, 0, 0, 2)
+    );
+}
+
+fn resolve_v6(t: &mut dyn WvTester) {
+    let (net, mut ns, mut dns) = setup();
+
+    let octets = [0xfd, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+    let mut query = wv_assert_ok!(dns.resolve_v6_async(net.clone(), "host.m3", TIMEOUT));
+    ns.respond(t, &mut dns, &mut query, 0, Some((&octets, 60)));
+    wv_assert_eq!(
+        t,
+        wv_assert_ok!(dns.wait(&mut query)),
+        IpAddr::from_octets(octets)
+    );
+
+    // the IPv4 address is cached separately
+    let query = wv_assert_ok!(dns.resolve_async(net, "host.m3", TIMEOUT));
+    wv_assert!(t, !query.is_done());
+}
+
+fn cache_expiry(t: &mut dyn WvTester) {
+    let (net, mut ns, mut dns) = setup();
+
+    // a TTL of zero means that the answer must not be cached
+    let mut query = wv_assert_ok!(dns.resolve_async(net.clone(), "host.m3", TIMEOUT));
+    ns.respond(t, &mut dns, &mut query, 0, Some((&[10, 0, 0, 3], 0)));
+    wv_assert_eq!(
+        t,
+        wv_assert_ok!(dns.wait(&mut query)),
+        IpAddr::new(10, 0, 0, 3)
+    );
+
+    let mut query = wv_assert_ok!(dns.resolve_async(net, "host.m3", TIMEOUT));
+    wv_assert!(t, !query.is_done());
+    ns.respond(t, &mut dns, &mut query, 0, Some((&[10, 0, 0, 4], 0)));
+    wv_assert_eq!(
+        t,
+        wv_assert_ok!(dns.wait(&mut query)),
+        IpAddr::new(10, 0, 0, 4)
+    );
+}
+
+fn negative_cache(t: &mut dyn WvTester) {
+    let (net, mut ns, mut dns) = setup();
+
+    let mut query = wv_assert_ok!(dns.resolve_async(net.clone(), "unknown.m3", TIMEOUT));
+    ns.respond(t, &mut dns, &mut query, RCODE_NAME_ERROR, None);
+    wv_assert_err!(t, dns.wait(&mut query), Code::NotFound);
+
+    // the failed lookup is remembered
+    let mut query = wv_assert_ok!(dns.resolve_async(net.clone(), "unknown.m3", TIMEOUT));
+    wv_assert!(t, query.is_done());
+    wv_assert_err!(t, dns.poll(&mut query), Code::NotFound);
+    wv_assert_err!(t, dns.resolve(net, "unknown.m3", TIMEOUT), Code::NotFound);
+    wv_assert!(t, !ns.has_request());
+}
+
+fn server_failure(t: &mut dyn WvTester) {
+    let (net, mut ns, mut dns) = setup();
+
+    // if no nameserver can answer the query, we give up
+    let mut query = wv_assert_ok!(dns.resolve_async(net.clone(), "host.m3", TIMEOUT));
+    ns.respond(t, &mut dns, &mut query, RCODE_SERVER_FAILURE, None);
+    wv_assert_err!(t, dns.wait(&mut query), Code::Timeout);
+
+    // failures are not cached
+    let query = wv_assert_ok!(dns.resolve_async(net, "host.m3", TIMEOUT));
+    wv_assert!(t, !query.is_done());
+}
+
+fn failover(t: &mut dyn WvTester) {
+    let net = wv_assert_ok!(Network::new("dns"));
+    let mut ns = NameServer::new(net.clone());
+
+    // nobody answers on 127.0.0.2, so that the resolver moves on to 127.0.0.1 after the timeout.
+    // the previous tests have resolved the link address of 127.0.0.1 already, so that a short
+    // timeout suffices.
+    let mut dns = DNS::default();
+    dns.add_nameserver(IpAddr::new(127, 0, 0, 2));
+    dns.add_nameserver(IpAddr::new(127, 0, 0, 1));
+    dns.add_nameserver(IpAddr::new(127, 0, 0, 1));
+    wv_assert_eq!(t, dns.nameservers().len(), 2);
+
+    let timeout = TimeDuration::from_secs(1);
+    let mut query = wv_assert_ok!(dns.resolve_async(net, "host.m3", timeout));
+    ns.respond(t, &mut dns, &mut query, 0, Some((&[10, 0, 0, 5], 60)));
+    wv_assert_eq!(
+        t,
+        wv_assert_ok!(dns.wait(&mut query)),
+        IpAddr::new(10, 0, 0, 5)
+    );
+}
@@ -17,16 +17,19 @@ use m3::cap::Selector;
 use m3::client::Network;
 use m3::com::Semaphore;
 use m3::errors::Code;
-use m3::net::{Endpoint, IpAddr, Socket, State, StreamSocket, StreamSocketArgs, TcpSocket};
+use m3::net::{
+    Endpoint, IpAddr, Socket, SocketOpt, State, StreamSocket, StreamSocketArgs, TcpSocket,
+};
 use m3::test::{DefaultWvTester, WvTester};
 use m3::tiles::{Activity, ActivityArgs, ChildActivity, RunningActivity, Tile};
 use m3::vec::Vec;
 use m3::vfs::{File, FileEvent, FileWaiter};
-use m3::{vec, wv_assert_eq, wv_assert_err, wv_assert_ok, wv_run_test};
+use m3::{vec, wv_assert, wv_assert_eq, wv_assert_err, wv_assert_ok, wv_run_test};
 
 pub fn run(t: &mut dyn WvTester) {
     wv_run_test!(t, basics);
     wv_run_test!(t, unreachable);
+    wv_run_test!(t, options);
     wv_run_test!(t, nonblocking_client);
     wv_run_test!(t, nonblocking_server);
     wv_run_test!(t, open_close);
@@ -95,6 +98,36 @@ fn unreachable(t: &mut dyn WvTester) {
     );
 }
 
+fn options(t: &mut dyn WvTester) {
+    let net = wv_assert_ok!(Network::new("net0"));
+
+    let mut socket = wv_assert_ok!(TcpSocket::new(StreamSocketArgs::new(net)));
+
+    // Nagle's algorithm is disabled by default
+    wv_assert_eq!(t, socket.get_option(SocketOpt::NoDelay), Ok(1));
+    wv_assert_ok!(socket.set_option(SocketOpt::NoDelay, 0));
+    wv_assert_eq!(t, socket.get_option(SocketOpt::NoDelay), Ok(0));
+    wv_assert_err!(t, socket.set_option(SocketOpt::NoDelay, 2), Code::InvArgs);
+
+    wv_assert_eq!(t, socket.get_option(SocketOpt::KeepAlive), Ok(0));
+    wv_assert_ok!(socket.set_option(SocketOpt::KeepAlive, 1000));
+    wv_assert_eq!(t, socket.get_option(SocketOpt::KeepAlive), Ok(1000));
+
+    wv_assert_ok!(socket.set_option(SocketOpt::Ttl, 32));
+    wv_assert_eq!(t, socket.get_option(SocketOpt::Ttl), Ok(32));
+
+    // the options stay in effect for the connection
+    wv_assert_ok!(Semaphore::attach("net-tcp").unwrap().down());
+    wv_assert_ok!(socket.connect(Endpoint::new(crate::DST_IP.get(), 1338)));
+
+    let mut buf = [0u8; 32];
+    wv_assert_eq!(t, socket.send(&buf), Ok(buf.len()));
+    wv_assert_ok!(socket.recv(&mut buf));
+    wv_assert_eq!(t, socket.get_option(SocketOpt::NoDelay), Ok(0));
+
+    wv_assert_ok!(socket.abort());
+}
+
 fn nonblocking_client(t: &mut dyn WvTester) {
     let net = wv_assert_ok!(Network::new("net0"));
 
@@ -121,8 +154,10 @@ fn nonblocking_client(t: &mut dyn WvTester) {
             socket.connect(Endpoint::new(crate::DST_IP.get(), 1338)),
             Code::AlreadyInProgress
         );
-        in_waiter.wait();
+        // the socket becomes writable as soon as the connect completed
+        out_waiter.wait();
     }
+    wv_assert!(t, socket.borrow_as().take_error().is_none());
 
     let mut buf = [0u8; 32];
 
@@ -16,7 +16,9 @@
 use m3::client::Network;
 use m3::com::Semaphore;
 use m3::errors::{Code, Error};
-use m3::net::{DGramSocket, DgramSocketArgs, Endpoint, Socket, State, UdpSocket, MTU};
+use m3::net::{
+    DGramSocket, DgramSocketArgs, Endpoint, IpAddr, Socket, SocketOpt, State, UdpSocket, MTU,
+};
 use m3::test::WvTester;
 use m3::time::TimeDuration;
 use m3::vfs::{File, FileEvent, FileRef, FileWaiter};
@@ -30,7 +32,10 @@ pub fn run(t: &mut dyn WvTester) {
 
     wv_run_test!(t, basics);
     wv_run_test!(t, connect);
+    wv_run_test!(t, options);
+    wv_run_test!(t, multicast);
     wv_run_test!(t, data);
+    wv_run_test!(t, loopback);
 }
 
 fn basics(t: &mut dyn WvTester) {
@@ -64,6 +69,70 @@ fn connect(t: &mut dyn WvTester) {
     wv_assert_eq!(t, socket.state(), State::Bound);
 }
 
+fn options(t: &mut dyn WvTester) {
+    let net = wv_assert_ok!(Network::new("net0"));
+
+    let args = || {
+        DgramSocketArgs::new(net.clone())
+            .send_buffer(2, 1024)
+            .recv_buffer(2, 1024)
+    };
+
+    {
+        let mut socket1 = wv_assert_ok!(UdpSocket::new(args()));
+        let mut socket2 = wv_assert_ok!(UdpSocket::new(args()));
+
+        wv_assert_eq!(t, socket1.get_option(SocketOpt::Ttl), Ok(0));
+        wv_assert_ok!(socket1.set_option(SocketOpt::Ttl, 16));
+        wv_assert_eq!(t, socket1.get_option(SocketOpt::Ttl), Ok(16));
+        wv_assert_err!(t, socket1.set_option(SocketOpt::Ttl, 256), Code::InvArgs);
+        wv_assert_err!(t, socket1.set_option(SocketOpt::NoDelay, 1), Code::NotSup);
+        wv_assert_err!(t, socket1.get_option(SocketOpt::KeepAlive), Code::NotSup);
+
+        // without the option, the port cannot be shared
+        wv_assert_ok!(socket1.bind(2000));
+        wv_assert_ok!(socket2.set_option(SocketOpt::ReuseAddr, 1));
+        wv_assert_err!(t, socket2.bind(2000), Code::Exists);
+    }
+
+    {
+        let mut socket1 = wv_assert_ok!(UdpSocket::new(args()));
+        let mut socket2 = wv_assert_ok!(UdpSocket::new(args()));
+
+        wv_assert_err!(
+            t,
+            socket1.set_option(SocketOpt::ReuseAddr, 2),
+            Code::InvArgs
+        );
+        wv_assert_ok!(socket1.set_option(SocketOpt::ReuseAddr, 1));
+        wv_assert_ok!(socket2.set_option(SocketOpt::ReuseAddr, 1));
+        wv_assert_eq!(t, socket2.get_option(SocketOpt::ReuseAddr), Ok(1));
+        wv_assert_ok!(socket1.bind(2000));
+        wv_assert_ok!(socket2.bind(2000));
+    }
+}
+
+fn multicast(t: &mut dyn WvTester) {
+    let net = wv_assert_ok!(Network::new("net0"));
+
+    let mut socket = wv_assert_ok!(UdpSocket::new(DgramSocketArgs::new(net)));
+    wv_assert_ok!(socket.bind(2000));
+
+    let group = IpAddr::new(224, 0, 0, 251);
+    wv_assert_ok!(socket.borrow_as().join_multicast(group));
+    wv_assert_err!(t, socket.borrow_as().join_multicast(group), Code::Exists);
+    wv_assert_err!(
+        t,
+        socket.borrow_as().join_multicast(crate::NET0_IP.get()),
+        Code::InvArgs
+    );
+    let group6 = IpAddr::new_v6([0xff02, 0, 0, 0, 0, 0, 0, 0xfb]);
+    wv_assert_err!(t, socket.borrow_as().join_multicast(group6), Code::NotSup);
+
+    wv_assert_ok!(socket.borrow_as().leave_multicast(group));
+    wv_assert_err!(t, socket.borrow_as().leave_multicast(group), Code::NotFound);
+}
+
 fn send_recv(
     waiter: &mut FileWaiter,
     socket: &mut FileRef<UdpSocket>,
@@ -144,3 +213,62 @@ fn data(t: &mut dyn WvTester) {
         }
     }
 }
+
+fn recv_within(
+    socket: &mut FileRef<UdpSocket>,
+    recv_buf: &mut [u8],
+    timeout: TimeDuration,
+) -> Result<(usize, Endpoint), Error> {
+    let mut waiter = FileWaiter::default();
+    waiter.add(socket.fd(), FileEvent::INPUT);
+    waiter.wait_for(timeout);
+
+    if socket.has_data() {
+        socket.recv_from(recv_buf)
+    }
+    else {
+        Err(Error::new(Code::Timeout))
+    }
+}
+
+fn loopback(t: &mut dyn WvTester) {
+    let net = wv_assert_ok!(Network::new("net0"));
+
+    let args = || {
+        DgramSocketArgs::new(net.clone())
+            .send_buffer(2, 1024)
+            .recv_buffer(2, 1024)
+    };
+
+    let mut server = wv_assert_ok!(UdpSocket::new(args()));
+    let mut client = wv_assert_ok!(UdpSocket::new(args()));
+    wv_assert_ok!(server.set_blocking(false));
+    wv_assert_ok!(client.set_blocking(false));
+    wv_assert_ok!(server.bind(2000));
+    wv_assert_ok!(client.bind(2001));
+
+    // localhost is reachable via the loopback interface, regardless of the other interfaces
+    let server_ep = Endpoint::new(IpAddr::new(127, 0, 0, 1), 2000);
+    let client_ep = Endpoint::new(IpAddr::new(127, 0, 0, 1), 2001);
+
+    let mut recv_buf = [0u8; 16];
+
+    // use a higher timeout than the smoltcp-internal timeout to workaround the ARP-request delay
+    wv_assert_ok!(client.send_to(b"ping", server_ep));
+    let (size, src) = wv_assert_ok!(recv_within(
+        &mut server,
+        &mut recv_buf,
+        TimeDuration::from_secs(6)
+    ));
+    wv_assert_eq!(t, &recv_buf[0..size], b"ping");
+    wv_assert_eq!(t, src, client_ep);
+
+    wv_assert_ok!(server.send_to(b"pong", src));
+    let (size, src) = wv_assert_ok!(recv_within(
+        &mut client,
+        &mut recv_buf,
+        TimeDuration::from_secs(6)
+    ));
+    wv_assert_eq!(t, &recv_buf[0..size], b"pong");
+    wv_assert_eq!(t, src, server_ep);
+}
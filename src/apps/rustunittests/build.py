@@ -1,2 +1,2 @@
 def build(gen, env):
-    env.m3_rust_exe(gen, out='rustunittests')
+    env.m3_rust_exe(gen, out='rustunittests', libs=['thread'])
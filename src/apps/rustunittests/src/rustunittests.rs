@@ -18,6 +18,7 @@
 
 #![no_std]
 
+use m3::env;
 use m3::errors::Error;
 use m3::test::{DefaultWvTester, WvTester};
 use m3::{println, wv_run_suite};
@@ -27,51 +28,98 @@ mod tboxlist;
 mod tbufio;
 mod tdir;
 mod tdlist;
+mod tdtb;
 mod tenvvars;
+mod tfb;
 mod tfilemux;
 mod tfloat;
 mod tgenfile;
+mod theap;
+mod thttp;
+mod tkvstore;
+mod tlocalsock;
 mod tm3fs;
+mod tmath;
 mod tmemmap;
 mod tmgate;
+mod tmpsc;
 mod tnonblock;
 mod tpaging;
+mod tpci;
 mod tpipe;
+mod tpmu;
+mod tpty;
 mod trgate;
 mod tsems;
 mod tserialize;
 mod tserver;
 mod tsgate;
+mod tshm;
+mod tsqueue;
 mod tsrvmsgs;
+mod tsync;
 mod tsyscalls;
+mod tsystime;
+mod tthread;
+mod ttimer;
 mod ttreap;
+mod tusb;
+mod tvirtio;
 
 #[no_mangle]
 pub fn main() -> Result<(), Error> {
-    let mut tester = DefaultWvTester::default();
-    wv_run_suite!(tester, tboxlist::run);
-    wv_run_suite!(tester, tbufio::run);
-    wv_run_suite!(tester, tserialize::run);
-    wv_run_suite!(tester, tdir::run);
-    wv_run_suite!(tester, tdlist::run);
-    wv_run_suite!(tester, tenvvars::run);
-    wv_run_suite!(tester, tfilemux::run);
-    wv_run_suite!(tester, tfloat::run);
-    wv_run_suite!(tester, tgenfile::run);
-    wv_run_suite!(tester, tm3fs::run);
-    wv_run_suite!(tester, tmemmap::run);
-    wv_run_suite!(tester, tmgate::run);
-    wv_run_suite!(tester, tnonblock::run);
-    wv_run_suite!(tester, tpaging::run);
-    wv_run_suite!(tester, tpipe::run);
-    wv_run_suite!(tester, trgate::run);
-    wv_run_suite!(tester, tsgate::run);
-    wv_run_suite!(tester, tsems::run);
-    wv_run_suite!(tester, tserver::run);
-    wv_run_suite!(tester, tsrvmsgs::run);
-    wv_run_suite!(tester, tsyscalls::run);
-    wv_run_suite!(tester, ttreap::run);
-    wv_run_suite!(tester, tactivity::run);
+    let mut tester = DefaultWvTester::from_env();
+    // the service queue tests need a kernel with a small queue limit, so that they run in a
+    // separate setup
+    if env::args().nth(1) == Some("tsqueue") {
+        wv_run_suite!(tester, tsqueue::run);
+    }
+    // the framebuffer tests need the fb service, which is only started in a separate setup
+    else if env::args().nth(1) == Some("tfb") {
+        wv_run_suite!(tester, tfb::run);
+    }
+    else {
+        wv_run_suite!(tester, tboxlist::run);
+        wv_run_suite!(tester, tbufio::run);
+        wv_run_suite!(tester, tserialize::run);
+        wv_run_suite!(tester, tdir::run);
+        wv_run_suite!(tester, tdlist::run);
+        wv_run_suite!(tester, tdtb::run);
+        wv_run_suite!(tester, tenvvars::run);
+        wv_run_suite!(tester, tfilemux::run);
+        wv_run_suite!(tester, tfloat::run);
+        wv_run_suite!(tester, tgenfile::run);
+        wv_run_suite!(tester, theap::run);
+        wv_run_suite!(tester, thttp::run);
+        wv_run_suite!(tester, tkvstore::run);
+        wv_run_suite!(tester, tlocalsock::run);
+        wv_run_suite!(tester, tm3fs::run);
+        wv_run_suite!(tester, tmath::run);
+        wv_run_suite!(tester, tmemmap::run);
+        wv_run_suite!(tester, tmgate::run);
+        wv_run_suite!(tester, tmpsc::run);
+        wv_run_suite!(tester, tnonblock::run);
+        wv_run_suite!(tester, tpaging::run);
+        wv_run_suite!(tester, tpci::run);
+        wv_run_suite!(tester, tpipe::run);
+        wv_run_suite!(tester, tpmu::run);
+        wv_run_suite!(tester, tpty::run);
+        wv_run_suite!(tester, trgate::run);
+        wv_run_suite!(tester, tsgate::run);
+        wv_run_suite!(tester, tshm::run);
+        wv_run_suite!(tester, tsems::run);
+        wv_run_suite!(tester, tserver::run);
+        wv_run_suite!(tester, tsrvmsgs::run);
+        wv_run_suite!(tester, tsync::run);
+        wv_run_suite!(tester, tsyscalls::run);
+        wv_run_suite!(tester, tsystime::run);
+        wv_run_suite!(tester, tthread::run);
+        wv_run_suite!(tester, ttimer::run);
+        wv_run_suite!(tester, ttreap::run);
+        wv_run_suite!(tester, tusb::run);
+        wv_run_suite!(tester, tvirtio::run);
+        wv_run_suite!(tester, tactivity::run);
+    }
     println!("{}", tester);
     Ok(())
 }
@@ -17,21 +17,32 @@
  */
 
 use m3::cap::Selector;
+use m3::cell::StaticCell;
+use m3::cfg;
 use m3::com::{recv_msg, RecvCap, RecvGate, SGateArgs, SendCap, SendGate};
+use m3::elf;
 use m3::env;
 use m3::errors::{Code, Error};
+use m3::io::Write;
+use m3::mem;
 use m3::test::{DefaultWvTester, WvTester};
-use m3::tiles::{Activity, ActivityArgs, ChildActivity, OwnActivity, RunningActivity, Tile};
+use m3::tiles::{Activity, ActivityArgs, ChildActivity, Gang, OwnActivity, RunningActivity, Tile};
 use m3::time::TimeDuration;
-use m3::util::math;
+use m3::util::{self, math};
+use m3::vfs::{OpenFlags, VFS};
 
-use m3::{send_vmsg, wv_assert_eq, wv_assert_ok, wv_run_test};
+use m3::{send_vmsg, wv_assert, wv_assert_eq, wv_assert_err, wv_assert_ok, wv_run_test};
 
 pub fn run(t: &mut dyn WvTester) {
     wv_run_test!(t, run_stop);
     wv_run_test!(t, run_arguments);
     wv_run_test!(t, run_send_receive);
+    wv_run_test!(t, run_clone);
+    wv_run_test!(t, stack_overflow);
+    wv_run_test!(t, cpu_time);
+    wv_run_test!(t, gang);
     wv_run_test!(t, exec_fail);
+    wv_run_test!(t, exec_missing_lib);
     wv_run_test!(t, exec_hello);
     wv_run_test!(t, exec_rust_hello);
 }
@@ -135,6 +146,108 @@ fn run_send_receive(t: &mut dyn WvTester) {
     wv_assert_eq!(t, act.wait(), Ok(Code::NoFreeTile));
 }
 
+fn run_clone(t: &mut dyn WvTester) {
+    static VALUE: StaticCell<u64> = StaticCell::new(0);
+
+    let tile = wv_assert_ok!(Tile::get("compat|own"));
+    let act = wv_assert_ok!(ChildActivity::new_with(tile, ActivityArgs::new("test")));
+    if act.pager().is_none() {
+        m3::println!("Skipping clone test without pager");
+        return;
+    }
+
+    VALUE.set(42);
+
+    let act = wv_assert_ok!(act.run_clone(|| {
+        let mut t = DefaultWvTester::default();
+        // we see the state of the parent at the time of the clone
+        wv_assert_eq!(t, VALUE.get(), 42);
+        VALUE.set(23);
+        wv_assert_eq!(t, VALUE.get(), 23);
+        Ok(())
+    }));
+
+    wv_assert_eq!(t, act.wait(), Ok(Code::Success));
+    // the write of the child went to its own copy
+    wv_assert_eq!(t, VALUE.get(), 42);
+}
+
+fn stack_overflow(t: &mut dyn WvTester) {
+    let tile = wv_assert_ok!(Tile::get("compat|own"));
+    let act = wv_assert_ok!(ChildActivity::new_with(tile, ActivityArgs::new("test")));
+    if act.pager().is_none() {
+        m3::println!("Skipping stack overflow test without pager");
+        return;
+    }
+
+    fn recurse(depth: u64) -> u64 {
+        // use some stack space in each frame to reach the guard area quickly
+        let frame = core::hint::black_box([depth; 64]);
+        if depth == u64::MAX {
+            return frame[0];
+        }
+        frame[1] + recurse(depth + 1)
+    }
+
+    let act = wv_assert_ok!(act.run(|| {
+        core::hint::black_box(recurse(0));
+        Ok(())
+    }));
+
+    wv_assert_eq!(t, act.wait(), Ok(Code::StackOverflow));
+}
+
+fn cpu_time(t: &mut dyn WvTester) {
+    if !Activity::own().tile_desc().supports_tilemux() {
+        m3::println!("Skipping CPU time test without TileMux");
+        return;
+    }
+
+    let before = wv_assert_ok!(Activity::own().cpu_time());
+    // do something to consume CPU time
+    let mut n = 0u64;
+    for i in 0..10000 {
+        n = core::hint::black_box(n + i);
+    }
+    let after = wv_assert_ok!(Activity::own().cpu_time());
+    wv_assert!(t, after > before);
+}
+
+fn gang(t: &mut dyn WvTester) {
+    let tile = wv_assert_ok!(Tile::get("compat|own"));
+    let gang = wv_assert_ok!(Gang::new(2));
+
+    let act1 = wv_assert_ok!(ChildActivity::new_with(
+        tile.clone(),
+        ActivityArgs::new("gang1")
+    ));
+    let act2 = wv_assert_ok!(ChildActivity::new_with(
+        tile.clone(),
+        ActivityArgs::new("gang2")
+    ));
+    let act3 = wv_assert_ok!(ChildActivity::new_with(tile, ActivityArgs::new("gang3")));
+
+    wv_assert_ok!(gang.join(&act1));
+    // joining twice or joining a full gang fails
+    wv_assert_err!(t, gang.join(&act1), Code::Exists);
+    wv_assert_ok!(gang.join(&act2));
+    wv_assert_err!(t, gang.join(&act3), Code::NoSpace);
+
+    // leaving makes room for others
+    wv_assert_ok!(gang.leave(&act2));
+    wv_assert_err!(t, gang.leave(&act2), Code::NotFound);
+    wv_assert_ok!(gang.join(&act3));
+
+    // the first member is not started until the second one is started as well
+    let act1 = wv_assert_ok!(act1.run(|| Ok(())));
+    let act3 = wv_assert_ok!(act3.run(|| Ok(())));
+    wv_assert_eq!(t, act1.wait(), Ok(Code::Success));
+    wv_assert_eq!(t, act3.wait(), Ok(Code::Success));
+
+    // the gang has been started, so that nobody can join anymore
+    wv_assert_err!(t, gang.join(&act2), Code::InvState);
+}
+
 fn exec_fail(_t: &mut dyn WvTester) {
     let tile = wv_assert_ok!(Tile::get("compat|own"));
     // file too small
@@ -155,6 +268,79 @@ fn exec_fail(_t: &mut dyn WvTester) {
     }
 }
 
+fn exec_missing_lib(t: &mut dyn WvTester) {
+    // a program that needs a shared object, which does not exist
+    const BASE: usize = 0x2000_0000;
+    const STRTAB: &[u8] = b"\0libmissing.so\0";
+
+    let ph_off = mem::size_of::<elf::ElfHeader>();
+    let dyn_off = ph_off + 2 * mem::size_of::<elf::ProgramHeader>();
+    let str_off = dyn_off + 3 * mem::size_of::<elf::Dyn>();
+    let size = str_off + STRTAB.len();
+
+    let mut hdr = elf::ElfHeader {
+        ty: elf::ElfType::Exec.into(),
+        entry: BASE,
+        ph_off,
+        eh_size: ph_off as u16,
+        ph_entry_size: mem::size_of::<elf::ProgramHeader>() as u16,
+        ph_num: 2,
+        ..Default::default()
+    };
+    hdr.ident[..4].copy_from_slice(b"\x7FELF");
+
+    // one segment that contains the whole file and the dynamic section within it
+    let load = elf::ProgramHeader {
+        ty: elf::PHType::Load.into(),
+        flags: elf::PHFlags::R.bits(),
+        offset: 0,
+        virt_addr: BASE,
+        file_size: size as _,
+        mem_size: size as _,
+        align: cfg::PAGE_SIZE as _,
+        ..Default::default()
+    };
+    let dynamic = elf::ProgramHeader {
+        ty: elf::PHType::Dynamic.into(),
+        flags: elf::PHFlags::R.bits(),
+        offset: dyn_off as _,
+        virt_addr: BASE + dyn_off,
+        file_size: (str_off - dyn_off) as _,
+        mem_size: (str_off - dyn_off) as _,
+        ..Default::default()
+    };
+    let dyns = [
+        elf::Dyn {
+            tag: elf::DynTag::Needed.into(),
+            val: 1,
+        },
+        elf::Dyn {
+            tag: elf::DynTag::StrTab.into(),
+            val: BASE + str_off,
+        },
+        elf::Dyn::default(),
+    ];
+
+    {
+        let mut file = wv_assert_ok!(VFS::open(
+            "/dynprog",
+            OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::W
+        ));
+        wv_assert_ok!(file.write_all(util::object_to_bytes(&hdr)));
+        wv_assert_ok!(file.write_all(util::object_to_bytes(&load)));
+        wv_assert_ok!(file.write_all(util::object_to_bytes(&dynamic)));
+        wv_assert_ok!(file.write_all(util::object_to_bytes(&dyns)));
+        wv_assert_ok!(file.write_all(STRTAB));
+    }
+
+    let tile = wv_assert_ok!(Tile::get("compat|own"));
+    let mut act = wv_assert_ok!(ChildActivity::new_with(tile, ActivityArgs::new("test")));
+    act.set_env("LD_LIBRARY_PATH", "/nonexistent:/lib");
+    wv_assert_err!(t, act.exec(&["/dynprog"]).map(|_| ()), Code::NotFound);
+
+    wv_assert_ok!(VFS::unlink("/dynprog"));
+}
+
 fn exec_hello(t: &mut dyn WvTester) {
     let tile = wv_assert_ok!(Tile::get("compat|own"));
     let act = wv_assert_ok!(ChildActivity::new_with(tile, ActivityArgs::new("test")));
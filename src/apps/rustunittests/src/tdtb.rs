@@ -0,0 +1,217 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use dtb::DeviceTree;
+
+use m3::col::{ToString, Vec};
+use m3::devinfo::DeviceInfo;
+use m3::errors::Code;
+use m3::test::WvTester;
+use m3::{vec, wv_assert_eq, wv_assert_err, wv_assert_ok, wv_assert_some, wv_run_test};
+
+pub fn run(t: &mut dyn WvTester) {
+    wv_run_test!(t, invalid);
+    wv_run_test!(t, devices);
+    wv_run_test!(t, devinfo);
+}
+
+/// A minimal builder for flattened device trees
+#[derive(Default)]
+struct Builder {
+    structs: Vec<u8>,
+    strings: Vec<u8>,
+}
+
+impl Builder {
+    fn word(&mut self, val: u32) {
+        self.structs.extend_from_slice(&val.to_be_bytes());
+    }
+
+    fn pad(&mut self) {
+        while self.structs.len() % 4 != 0 {
+            self.structs.push(0);
+        }
+    }
+
+    fn begin(&mut self, name: &str) -> &mut Self {
+        self.word(0x1);
+        self.structs.extend_from_slice(name.as_bytes());
+        self.structs.push(0);
+        self.pad();
+        self
+    }
+
+    fn prop(&mut self, name: &str, value: &[u8]) -> &mut Self {
+        let name_off = self.strings.len() as u32;
+        self.strings.extend_from_slice(name.as_bytes());
+        self.strings.push(0);
+
+        self.word(0x3);
+        self.word(value.len() as u32);
+        self.word(name_off);
+        self.structs.extend_from_slice(value);
+        self.pad();
+        self
+    }
+
+    fn prop_cells(&mut self, name: &str, cells: &[u32]) -> &mut Self {
+        let value = cells
+            .iter()
+            .flat_map(|c| c.to_be_bytes())
+            .collect::<Vec<u8>>();
+        self.prop(name, &value)
+    }
+
+    fn end(&mut self) -> &mut Self {
+        self.word(0x2);
+        self
+    }
+
+    fn finish(&mut self) -> Vec<u8> {
+        self.word(0x9);
+
+        let off_struct = 40 + 16;
+        let off_strings = off_struct + self.structs.len();
+        let total = off_strings + self.strings.len();
+
+        let mut res = Vec::new();
+        for val in [
+            0xd00d_feed,
+            total as u32,
+            off_struct as u32,
+            off_strings as u32,
+            40,
+            17,
+            16,
+            0,
+            self.strings.len() as u32,
+            self.structs.len() as u32,
+        ] {
+            res.extend_from_slice(&u32::to_be_bytes(val));
+        }
+        // empty memory reservation block
+        res.extend_from_slice(&[0; 16]);
+        res.extend_from_slice(&self.structs);
+        res.extend_from_slice(&self.strings);
+        res
+    }
+}
+
+fn invalid(t: &mut dyn WvTester) {
+    wv_assert_err!(t, DeviceTree::new(&[]), Code::InvArgs);
+    wv_assert_err!(t, DeviceTree::new(&[0; 64]), Code::InvArgs);
+
+    // truncated
+    let dtb = Builder::default().begin("").end().finish();
+    wv_assert_ok!(DeviceTree::new(&dtb));
+    wv_assert_err!(t, DeviceTree::new(&dtb[..dtb.len() - 8]), Code::InvArgs);
+
+    // unbalanced nodes
+    let dtb = Builder::default().begin("").finish();
+    wv_assert_err!(t, DeviceTree::new(&dtb), Code::InvArgs);
+    let dtb = Builder::default().begin("").end().end().finish();
+    wv_assert_err!(t, DeviceTree::new(&dtb), Code::InvArgs);
+}
+
+fn devices(t: &mut dyn WvTester) {
+    let dtb = Builder::default()
+        .begin("")
+        .prop_cells("#address-cells", &[2])
+        .prop_cells("#size-cells", &[2])
+        .begin("intc")
+        .prop_cells("phandle", &[1])
+        .prop_cells("#interrupt-cells", &[2])
+        .end()
+        .begin("soc")
+        .prop_cells("#address-cells", &[1])
+        .prop_cells("#size-cells", &[1])
+        .prop_cells("interrupt-parent", &[1])
+        .begin("serial@1000")
+        .prop("compatible", b"vendor,uart\0ns16550a\0")
+        .prop_cells("reg", &[0x1000, 0x100])
+        .prop_cells("interrupts", &[10, 4])
+        .end()
+        .begin("serial@2000")
+        .prop("compatible", b"ns16550a\0")
+        .prop("status", b"disabled\0")
+        .prop_cells("reg", &[0x2000, 0x100])
+        .end()
+        .begin("serial@3000")
+        .prop("compatible", b"ns16550a\0")
+        .prop_cells("reg", &[0x3000, 0x100, 0x4000, 0x10])
+        .prop_cells("interrupts", &[11, 4, 12, 4])
+        .prop_cells("m3,msi-address", &[0x0, 0x5000])
+        .end()
+        .end()
+        .begin("nic@100000000")
+        .prop("compatible", b"intel,e1000\0")
+        .prop_cells("reg", &[0x1, 0x0, 0x0, 0x2_0000])
+        .prop_cells("m3,msi-address", &[0x0, 0xFEE0_0000])
+        .end()
+        .end()
+        .finish();
+
+    let tree = wv_assert_ok!(DeviceTree::new(&dtb));
+    wv_assert_eq!(t, tree.nodes().len(), 7);
+    wv_assert_eq!(t, tree.find_phandle(1), Some(1));
+
+    // disabled nodes are skipped
+    let uart0 = wv_assert_some!(tree.find_compatible("ns16550a", 0));
+    let uart1 = wv_assert_some!(tree.find_compatible("ns16550a", 1));
+    wv_assert_eq!(t, tree.find_compatible("ns16550a", 2), None);
+    wv_assert_eq!(t, tree.node(uart0).map(|n| n.name()), Some("serial@1000"));
+    wv_assert_eq!(t, tree.node(uart1).map(|n| n.name()), Some("serial@3000"));
+
+    wv_assert_eq!(t, wv_assert_ok!(tree.reg(uart0)), vec![(0x1000, 0x100)]);
+    wv_assert_eq!(t, wv_assert_ok!(tree.interrupts(uart0)), vec![10]);
+    wv_assert_eq!(t, wv_assert_ok!(tree.reg(uart1)), vec![
+        (0x3000, 0x100),
+        (0x4000, 0x10)
+    ]);
+    wv_assert_eq!(t, wv_assert_ok!(tree.interrupts(uart1)), vec![11, 12]);
+
+    let nic = wv_assert_some!(tree.find_compatible("intel,e1000", 0));
+    wv_assert_eq!(t, wv_assert_ok!(tree.reg(nic)), vec![(
+        0x1_0000_0000,
+        0x2_0000
+    )]);
+    wv_assert_eq!(t, wv_assert_ok!(tree.interrupts(nic)), vec![]);
+
+    // the MSI address uses the address cells of the parent
+    wv_assert_eq!(t, wv_assert_ok!(tree.msi_address(nic)), Some(0xFEE0_0000));
+    wv_assert_eq!(t, wv_assert_ok!(tree.msi_address(uart0)), None);
+    wv_assert_err!(t, tree.msi_address(uart1), Code::InvArgs);
+}
+
+fn devinfo(t: &mut dyn WvTester) {
+    let info = DeviceInfo::new(vec![(0x4000, 0x1000), (0xF00_4000, 0x10_0000)], vec![3, 7]);
+    let s = info.to_string();
+    wv_assert_eq!(t, s, "reg=0x4000+0x1000,0xf004000+0x100000 irq=3,7");
+    wv_assert_eq!(t, wv_assert_ok!(s.parse::<DeviceInfo>()), info);
+
+    let empty = DeviceInfo::default();
+    wv_assert_eq!(t, empty.to_string(), "reg= irq=");
+    wv_assert_eq!(t, wv_assert_ok!("reg= irq=".parse::<DeviceInfo>()), empty);
+
+    let msi = DeviceInfo::new(vec![(0x4000, 0x1000)], vec![]).with_msi(0xFEE0_0000);
+    let s = msi.to_string();
+    wv_assert_eq!(t, s, "reg=0x4000+0x1000 irq= msi=0xfee00000");
+    wv_assert_eq!(t, wv_assert_ok!(s.parse::<DeviceInfo>()), msi);
+    wv_assert_eq!(t, msi.msi(), Some(0xFEE0_0000));
+    wv_assert_eq!(t, info.msi(), None);
+
+    wv_assert_err!(t, "reg=0x1000".parse::<DeviceInfo>(), Code::InvArgs);
+    wv_assert_err!(t, "foo=bar".parse::<DeviceInfo>(), Code::InvArgs);
+}
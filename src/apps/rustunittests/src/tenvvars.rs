@@ -28,6 +28,7 @@ pub fn run(t: &mut dyn WvTester) {
     wv_run_test!(t, basics);
     wv_run_test!(t, multi);
     wv_run_test!(t, to_child);
+    wv_run_test!(t, child_env);
 
     if let Some(log) = log {
         env::set_var("LOG", log);
@@ -114,3 +115,36 @@ fn to_child(t: &mut dyn WvTester) {
     env::remove_var("V1");
     wv_assert_eq!(t, env::vars().len(), 0);
 }
+
+fn child_env(t: &mut dyn WvTester) {
+    env::set_var("V1", "val1");
+    env::set_var("V2", "val2");
+
+    let mut act = wv_assert_ok!(ChildActivity::new_with(
+        wv_assert_ok!(Tile::get("compat|own")),
+        ActivityArgs::new("child")
+    ));
+
+    // changes to the child's environment do not affect ours
+    act.set_env("V1", "new");
+    act.remove_env("V2");
+    act.set_env("V3", "val3");
+    wv_assert_eq!(t, env::vars().len(), 2);
+    wv_assert_eq!(t, env::var("V1"), Some("val1".to_string()));
+
+    let run = wv_assert_ok!(act.run(|| {
+        let mut t = DefaultWvTester::default();
+        let vars = env::vars();
+        let mut it = vars.iter();
+        wv_assert_eq!(t, it.next(), Some(&("V1".to_string(), "new".to_string())));
+        wv_assert_eq!(t, it.next(), Some(&("V3".to_string(), "val3".to_string())));
+        wv_assert_eq!(t, it.next(), None);
+        Ok(())
+    }));
+
+    wv_assert_eq!(t, run.wait(), Ok(Code::Success));
+
+    env::remove_var("V2");
+    env::remove_var("V1");
+    wv_assert_eq!(t, env::vars().len(), 0);
+}
@@ -0,0 +1,214 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use gfx::font::{self, GLYPH_HEIGHT};
+use gfx::{Color, TextConsole, CELL_HEIGHT, CELL_WIDTH};
+
+use m3::client::{FbInfo, FbRect, Framebuffer, FB_BYTES_PER_PIXEL};
+use m3::com::MemGate;
+use m3::errors::Code;
+use m3::io::Write;
+use m3::mem::GlobOff;
+use m3::test::WvTester;
+use m3::{wv_assert, wv_assert_eq, wv_assert_err, wv_assert_ok, wv_run_test};
+
+// the resolution of the headless display in boot/rust-unittests-fb.xml; the width is intentionally
+// not a multiple of the cell width
+const WIDTH: u32 = 100;
+const HEIGHT: u32 = 40;
+
+pub fn run(t: &mut dyn WvTester) {
+    wv_run_test!(t, rects);
+    wv_run_test!(t, glyphs);
+    wv_run_test!(t, info);
+    wv_run_test!(t, shared_mem);
+    wv_run_test!(t, flush);
+    wv_run_test!(t, console_render);
+    wv_run_test!(t, console_scroll);
+}
+
+fn rects(t: &mut dyn WvTester) {
+    let empty = FbRect::default();
+    wv_assert!(t, empty.is_empty());
+    wv_assert!(t, FbRect::new(4, 4, 0, 10).is_empty());
+
+    let a = FbRect::new(10, 20, 5, 5);
+    let b = FbRect::new(0, 22, 4, 10);
+    wv_assert_eq!(t, a.union(&b), FbRect::new(0, 20, 15, 12));
+    wv_assert_eq!(t, b.union(&a), FbRect::new(0, 20, 15, 12));
+    // empty rectangles do not extend the union
+    wv_assert_eq!(t, a.union(&empty), a);
+    wv_assert_eq!(t, empty.union(&b), b);
+
+    let info = FbInfo {
+        width: 10,
+        height: 5,
+        stride: 48,
+    };
+    wv_assert_eq!(t, info.size(), 48 * 5);
+    wv_assert_eq!(t, info.offset(0, 0), 0);
+    wv_assert_eq!(t, info.offset(3, 2), 2 * 48 + 3 * FB_BYTES_PER_PIXEL);
+}
+
+fn glyphs(t: &mut dyn WvTester) {
+    wv_assert!(t, font::glyph(b' ').iter().all(|r| *r == 0));
+    wv_assert!(t, font::glyph(b'A').iter().any(|r| *r != 0));
+    // characters without glyph are shown as '?'
+    wv_assert_eq!(t, font::glyph(0x80), font::glyph(b'?'));
+    wv_assert_eq!(t, font::glyph(b'\n'), font::glyph(b'?'));
+}
+
+fn info(t: &mut dyn WvTester) {
+    let fb = wv_assert_ok!(Framebuffer::new("fb"));
+    let info = *fb.info();
+    wv_assert_eq!(t, info.width, WIDTH);
+    wv_assert_eq!(t, info.height, HEIGHT);
+    wv_assert!(
+        t,
+        info.stride as usize >= WIDTH as usize * FB_BYTES_PER_PIXEL
+    );
+
+    let mem = wv_assert_ok!(fb.get_mem());
+    let (_, size) = wv_assert_ok!(mem.region());
+    wv_assert_eq!(t, size as usize, info.size());
+}
+
+fn shared_mem(t: &mut dyn WvTester) {
+    let fb1 = wv_assert_ok!(Framebuffer::new("fb"));
+    let fb2 = wv_assert_ok!(Framebuffer::new("fb-clone"));
+    wv_assert_eq!(t, fb1.info(), fb2.info());
+
+    // all clients use the same pixel buffer
+    let mem1 = wv_assert_ok!(fb1.get_mem());
+    let mem2 = wv_assert_ok!(fb2.get_mem());
+    let off = fb1.info().offset(WIDTH - 1, HEIGHT - 1) as GlobOff;
+    wv_assert_ok!(mem1.write_obj(&0x00FF_8000u32, off));
+    wv_assert_eq!(t, wv_assert_ok!(mem2.read_obj::<u32>(off)), 0x00FF_8000);
+}
+
+fn flush(t: &mut dyn WvTester) {
+    let fb = wv_assert_ok!(Framebuffer::new("fb"));
+
+    wv_assert_ok!(fb.flush(FbRect::new(0, 0, WIDTH, HEIGHT)));
+    wv_assert_ok!(fb.flush(FbRect::new(WIDTH - 1, HEIGHT - 1, 1, 1)));
+    // empty rectangles are fine as long as they are within the display
+    wv_assert_ok!(fb.flush(FbRect::new(WIDTH, HEIGHT, 0, 0)));
+
+    wv_assert_err!(
+        t,
+        fb.flush(FbRect::new(0, 0, WIDTH + 1, HEIGHT)),
+        Code::InvArgs
+    );
+    wv_assert_err!(t, fb.flush(FbRect::new(0, 1, WIDTH, HEIGHT)), Code::InvArgs);
+    wv_assert_err!(t, fb.flush(FbRect::new(u32::MAX, 0, 2, 1)), Code::InvArgs);
+}
+
+fn pixel(mem: &MemGate, info: &FbInfo, x: usize, y: usize) -> Color {
+    let off = info.offset(x as u32, y as u32) as GlobOff;
+    wv_assert_ok!(mem.read_obj::<Color>(off))
+}
+
+/// Checks that the cell at given column and row shows the character `c`
+fn check_cell(
+    t: &mut dyn WvTester,
+    mem: &MemGate,
+    info: &FbInfo,
+    (col, row): (usize, usize),
+    c: u8,
+    (fg, bg): (Color, Color),
+) {
+    let glyph = font::glyph(c);
+    for py in 0..CELL_HEIGHT {
+        for px in 0..CELL_WIDTH {
+            let exp = if (glyph[py / 2] >> px) & 1 != 0 {
+                fg
+            }
+            else {
+                bg
+            };
+            let x = col * CELL_WIDTH + px;
+            let y = row * CELL_HEIGHT + py;
+            wv_assert_eq!(t, pixel(mem, info, x, y), exp);
+        }
+    }
+}
+
+fn console_render(t: &mut dyn WvTester) {
+    let fb = wv_assert_ok!(Framebuffer::new("fb"));
+    let info = *fb.info();
+    let mem = wv_assert_ok!(fb.get_mem());
+    let mut con = wv_assert_ok!(TextConsole::new(fb));
+
+    let cols = WIDTH as usize / CELL_WIDTH;
+    let rows = HEIGHT as usize / CELL_HEIGHT;
+    wv_assert_eq!(t, con.size(), (cols, rows));
+    wv_assert_eq!(t, GLYPH_HEIGHT * 2, CELL_HEIGHT);
+
+    let colors = (gfx::rgb(0xFF, 0xFF, 0), gfx::rgb(0, 0, 0x80));
+    con.set_colors(colors.0, colors.1);
+    wv_assert_ok!(write!(con, "Hi\tx\x08y\r!"));
+    wv_assert_eq!(t, con.cursor(), (1, 0));
+    wv_assert_ok!(con.flush());
+
+    check_cell(t, &mem, &info, (0, 0), b'!', colors);
+    check_cell(t, &mem, &info, (1, 0), b'i', colors);
+    check_cell(t, &mem, &info, (2, 0), b' ', colors);
+    check_cell(t, &mem, &info, (8, 0), b'y', colors);
+    check_cell(t, &mem, &info, (0, 1), b' ', colors);
+
+    // the pixels right of the last column have the background color
+    for y in 0..rows * CELL_HEIGHT {
+        for x in cols * CELL_WIDTH..WIDTH as usize {
+            wv_assert_eq!(t, pixel(&mem, &info, x, y), colors.1);
+        }
+    }
+
+    // the cursor is clamped to the console
+    con.set_cursor(100, 100);
+    wv_assert_eq!(t, con.cursor(), (cols - 1, rows - 1));
+    con.clear();
+    wv_assert_eq!(t, con.cursor(), (0, 0));
+    wv_assert_ok!(con.flush());
+    check_cell(t, &mem, &info, (0, 0), b' ', colors);
+}
+
+fn console_scroll(t: &mut dyn WvTester) {
+    let fb = wv_assert_ok!(Framebuffer::new("fb"));
+    let info = *fb.info();
+    let mem = wv_assert_ok!(fb.get_mem());
+    let mut con = wv_assert_ok!(TextConsole::new(fb));
+    let colors = (gfx::rgb(0xC0, 0xC0, 0xC0), gfx::rgb(0, 0, 0));
+
+    let (cols, rows) = con.size();
+    wv_assert_eq!(t, rows, 2);
+
+    // text is wrapped at the end of a row
+    for _ in 0..cols {
+        con.put(b'a');
+    }
+    wv_assert_eq!(t, con.cursor(), (cols, 0));
+    con.put(b'b');
+    wv_assert_eq!(t, con.cursor(), (1, 1));
+
+    // the console scrolls up if the cursor moves past the last row
+    wv_assert_ok!(write!(con, "\nc"));
+    wv_assert_eq!(t, con.cursor(), (1, 1));
+    wv_assert_ok!(con.flush());
+
+    check_cell(t, &mem, &info, (0, 0), b'b', colors);
+    check_cell(t, &mem, &info, (1, 0), b' ', colors);
+    check_cell(t, &mem, &info, (0, 1), b'c', colors);
+    check_cell(t, &mem, &info, (cols - 1, 1), b' ', colors);
+}
@@ -21,7 +21,7 @@ use m3::errors::Code;
 use m3::io::{Read, Write};
 use m3::test::WvTester;
 use m3::vfs::{File, FileRef, GenericFile, OpenFlags, Seek, SeekMode, VFS};
-use m3::{vec, wv_assert_eq, wv_assert_err, wv_assert_ok, wv_run_test};
+use m3::{vec, wv_assert, wv_assert_eq, wv_assert_err, wv_assert_ok, wv_run_test};
 
 pub fn run(t: &mut dyn WvTester) {
     wv_run_test!(t, permissions);
@@ -30,14 +30,17 @@ pub fn run(t: &mut dyn WvTester) {
     wv_run_test!(t, read_file_at_once);
     wv_run_test!(t, read_file_in_small_steps);
     wv_run_test!(t, read_file_in_large_steps);
+    wv_run_test!(t, read_file_pipelined);
     wv_run_test!(t, write_and_read_file);
     wv_run_test!(t, write_then_read_file);
     wv_run_test!(t, write_fmt);
     wv_run_test!(t, extend_small_file);
     wv_run_test!(t, overwrite_beginning);
+    wv_run_test!(t, overwrite_pipelined);
     wv_run_test!(t, truncate);
     wv_run_test!(t, append);
     wv_run_test!(t, append_read);
+    wv_run_test!(t, sparse);
 }
 
 fn permissions(t: &mut dyn WvTester) {
@@ -120,6 +123,29 @@ fn read_file_in_large_steps(t: &mut dyn WvTester) {
     );
 }
 
+fn read_file_pipelined(t: &mut dyn WvTester) {
+    let filename = "/pat.bin";
+
+    let mut file = wv_assert_ok!(VFS::open(
+        filename,
+        OpenFlags::R | OpenFlags::NEW_SESS | OpenFlags::PIPELINE
+    ));
+    let mut buf = vec![0u8; 4 * 1024];
+
+    wv_assert_eq!(
+        t,
+        _validate_pattern_content(t, &mut file, &mut buf),
+        64 * 1024
+    );
+
+    // seeking discards the chunk the server has prepared
+    wv_assert_eq!(t, file.seek(1024 + 3, SeekMode::Set), Ok(1024 + 3));
+    wv_assert_ok!(file.read_exact(&mut buf[0..16]));
+    for (i, b) in buf.iter().take(16).enumerate() {
+        wv_assert_eq!(t, *b, (i + 3) as u8);
+    }
+}
+
 fn write_and_read_file(t: &mut dyn WvTester) {
     let content = "Foobar, a test and more and more and more!";
     let filename = "/mat.txt";
@@ -214,6 +240,32 @@ fn overwrite_beginning(t: &mut dyn WvTester) {
     _validate_pattern_file(t, "/test.txt", 1024 * 33);
 }
 
+fn overwrite_pipelined(t: &mut dyn WvTester) {
+    let filename = "/pipelined.txt";
+
+    {
+        let mut file = wv_assert_ok!(VFS::open(filename, OpenFlags::W | OpenFlags::CREATE));
+        let zeros = vec![0u8; 1024];
+        for _ in 0..32 {
+            wv_assert_eq!(t, file.write_all(&zeros), Ok(()));
+        }
+    }
+
+    {
+        let mut file = wv_assert_ok!(VFS::open(
+            filename,
+            OpenFlags::W | OpenFlags::NEW_SESS | OpenFlags::PIPELINE
+        ));
+        let buf = _get_pat_vector(1024);
+        for _ in 0..32 {
+            wv_assert_eq!(t, file.write_all(&buf), Ok(()));
+        }
+    }
+
+    _validate_pattern_file(t, filename, 1024 * 32);
+    wv_assert_ok!(VFS::unlink(filename));
+}
+
 fn truncate(t: &mut dyn WvTester) {
     {
         let mut file = wv_assert_ok!(VFS::open("/test.txt", OpenFlags::W | OpenFlags::TRUNC));
@@ -276,6 +328,60 @@ fn append_read(t: &mut dyn WvTester) {
     _validate_pattern_file(t, "/test.txt", 1024 * 4);
 }
 
+fn sparse(t: &mut dyn WvTester) {
+    let hole = 256 * 1024;
+
+    {
+        let mut file = wv_assert_ok!(VFS::open(
+            "/sparse.txt",
+            OpenFlags::RW | OpenFlags::TRUNC | OpenFlags::CREATE
+        ));
+
+        let pat = _get_pat_vector(1024);
+        wv_assert_eq!(t, file.write_all(&pat[0..1024]), Ok(()));
+
+        // seek beyond the end and write there
+        wv_assert_eq!(t, file.seek(hole, SeekMode::Set), Ok(hole));
+        wv_assert_eq!(t, file.write_all(&pat[0..1024]), Ok(()));
+    }
+
+    {
+        let mut file = wv_assert_ok!(VFS::open("/sparse.txt", OpenFlags::R));
+
+        let info = wv_assert_ok!(file.stat());
+        wv_assert_eq!(t, { info.size }, hole + 1024);
+        // the hole does not occupy any blocks
+        wv_assert!(
+            t,
+            (info.blocks as usize) < info.size / info.blocksize as usize
+        );
+
+        // the hole reads as zeros
+        let mut buf = [0xFFu8; 1024];
+        wv_assert_eq!(t, file.seek(hole / 2, SeekMode::Set), Ok(hole / 2));
+        wv_assert_ok!(file.read_exact(&mut buf));
+        wv_assert!(t, buf.iter().all(|b| *b == 0));
+
+        // the data behind the hole is still there
+        wv_assert_eq!(t, file.seek(hole, SeekMode::Set), Ok(hole));
+        wv_assert_ok!(file.read_exact(&mut buf));
+        wv_assert_eq!(t, &buf[..], &_get_pat_vector(1024)[..]);
+    }
+
+    {
+        // growing the file via truncate leaves a hole as well
+        let mut file = wv_assert_ok!(VFS::open("/sparse.txt", OpenFlags::RW));
+        let blocks = wv_assert_ok!(file.stat()).blocks;
+        wv_assert_ok!(file.truncate(hole * 2));
+
+        let info = wv_assert_ok!(file.stat());
+        wv_assert_eq!(t, { info.size }, hole * 2);
+        wv_assert_eq!(t, { info.blocks }, blocks);
+    }
+
+    wv_assert_ok!(VFS::unlink("/sparse.txt"));
+}
+
 fn _get_pat_vector(size: usize) -> Vec<u8> {
     let mut buf = Vec::with_capacity(size);
     for i in 0..1024 {
@@ -0,0 +1,142 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use m3::col::Vec;
+use m3::errors::{Code, Error};
+use m3::heap;
+use m3::println;
+use m3::test::WvTester;
+use m3::tiles::{ActivityArgs, ChildActivity, RunningActivity, Tile};
+use m3::{vec, wv_assert, wv_assert_eq, wv_assert_ok, wv_run_test};
+
+pub fn run(t: &mut dyn WvTester) {
+    wv_run_test!(t, stats);
+    wv_run_test!(t, stats_realloc);
+    wv_run_test!(t, poison);
+    wv_run_test!(t, realloc_moves);
+    wv_run_test!(t, overflow);
+    wv_run_test!(t, underflow);
+}
+
+fn stats(t: &mut dyn WvTester) {
+    let before = heap::stats();
+
+    let vec = Vec::<u8>::with_capacity(100);
+    let during = heap::stats();
+    wv_assert_eq!(t, during.allocs, before.allocs + 1);
+    wv_assert_eq!(t, during.cur_bytes, before.cur_bytes + 100);
+    wv_assert!(t, during.peak_bytes >= during.cur_bytes);
+    // 100 is in [2^6, 2^7)
+    wv_assert_eq!(t, during.histogram[6], before.histogram[6] + 1);
+    wv_assert_eq!(t, during.live_allocs(), before.live_allocs() + 1);
+
+    drop(vec);
+    let after = heap::stats();
+    wv_assert_eq!(t, after.frees, before.frees + 1);
+    wv_assert_eq!(t, after.cur_bytes, before.cur_bytes);
+    wv_assert_eq!(t, after.live_allocs(), before.live_allocs());
+}
+
+fn stats_realloc(t: &mut dyn WvTester) {
+    let mut vec = Vec::<u8>::with_capacity(16);
+    let before = heap::stats();
+
+    vec.reserve_exact(64);
+    let after = heap::stats();
+    wv_assert_eq!(t, after.reallocs, before.reallocs + 1);
+    wv_assert_eq!(t, after.allocs, before.allocs);
+    wv_assert_eq!(t, after.cur_bytes, before.cur_bytes + 48);
+}
+
+fn has_canaries() -> bool {
+    if !heap::has_canaries() {
+        println!("Heap canaries are disabled (build with M3_HEAPCHECK=1); skipping test");
+    }
+    heap::has_canaries()
+}
+
+fn poison(t: &mut dyn WvTester) {
+    if !has_canaries() {
+        return;
+    }
+
+    // uninitialized memory is poisoned
+    let vec = Vec::<u8>::with_capacity(64);
+    // safety: the capacity is allocated; we only inspect the bytes
+    let bytes = unsafe { core::slice::from_raw_parts(vec.as_ptr(), vec.capacity()) };
+    wv_assert!(t, bytes.iter().all(|b| *b == heap::ALLOC_POISON));
+
+    // zeroed memory is not
+    let zeroed = vec![0u8; 64];
+    wv_assert_eq!(t, zeroed.capacity(), 64);
+    wv_assert!(t, zeroed.iter().all(|b| *b == 0));
+}
+
+fn realloc_moves(t: &mut dyn WvTester) {
+    if !has_canaries() {
+        return;
+    }
+
+    let mut vec = Vec::<u8>::with_capacity(16);
+    vec.extend_from_slice(&[1, 2, 3, 4]);
+    let old = vec.as_ptr();
+
+    // the allocation is always moved, but keeps its content
+    vec.reserve_exact(64);
+    wv_assert!(t, vec.as_ptr() != old);
+    wv_assert_eq!(t, vec, [1, 2, 3, 4]);
+    // the new part is poisoned
+    // safety: the capacity is allocated; we only inspect the bytes
+    let bytes = unsafe { core::slice::from_raw_parts(vec.as_ptr(), vec.capacity()) };
+    wv_assert!(t, bytes[4..].iter().all(|b| *b == heap::ALLOC_POISON));
+}
+
+fn corrupt(func: fn() -> Result<(), Error>) -> Code {
+    let tile = wv_assert_ok!(Tile::get("compat|own"));
+    let act = wv_assert_ok!(ChildActivity::new_with(tile, ActivityArgs::new("corrupt")));
+    let act = wv_assert_ok!(act.run(func));
+    wv_assert_ok!(act.wait())
+}
+
+fn overflow(t: &mut dyn WvTester) {
+    if !has_canaries() {
+        return;
+    }
+
+    // the corruption is detected on free and lets the child panic
+    let code = corrupt(|| {
+        let mut vec = Vec::<u8>::with_capacity(16);
+        // safety: not safe; we corrupt the trailer on purpose
+        unsafe { vec.as_mut_ptr().add(16).write(0) };
+        drop(vec);
+        Ok(())
+    });
+    wv_assert!(t, code != Code::Success);
+}
+
+fn underflow(t: &mut dyn WvTester) {
+    if !has_canaries() {
+        return;
+    }
+
+    let code = corrupt(|| {
+        let mut vec = Vec::<u8>::with_capacity(16);
+        // safety: not safe; we corrupt the header on purpose
+        unsafe { vec.as_mut_ptr().sub(1).write(0) };
+        drop(vec);
+        Ok(())
+    });
+    wv_assert!(t, code != Code::Success);
+}
@@ -0,0 +1,153 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use http::{write_chunk, write_last_chunk, ChunkedDecoder, Method, Request, Response, Version};
+
+use m3::col::{ToString, Vec};
+use m3::errors::{Code, Error};
+use m3::io::Write;
+use m3::test::WvTester;
+use m3::{wv_assert, wv_assert_eq, wv_assert_err, wv_assert_ok, wv_assert_some, wv_run_test};
+
+pub fn run(t: &mut dyn WvTester) {
+    wv_run_test!(t, request);
+    wv_run_test!(t, response);
+    wv_run_test!(t, keep_alive);
+    wv_run_test!(t, chunked);
+}
+
+struct Sink(Vec<u8>);
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        self.0.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+}
+
+fn request(t: &mut dyn WvTester) {
+    let msg = b"GET /dir/file.txt?a=1 HTTP/1.1\r\nHost: example\r\ncontent-length: 4\r\n\r\nbody";
+
+    // incomplete heads are not parsed yet
+    wv_assert_eq!(t, Request::parse(&msg[..20]), Ok(None));
+    wv_assert_eq!(t, Request::parse(&msg[..64]), Ok(None));
+
+    let (req, len) = wv_assert_some!(wv_assert_ok!(Request::parse(msg)));
+    wv_assert_eq!(t, &msg[len..], b"body");
+    wv_assert_eq!(t, *req.method(), Method::Get);
+    wv_assert_eq!(t, req.target(), "/dir/file.txt?a=1");
+    wv_assert_eq!(t, req.path(), "/dir/file.txt");
+    wv_assert_eq!(t, req.query(), Some("a=1"));
+    wv_assert_eq!(t, req.version(), Version::Http11);
+    wv_assert_eq!(t, req.headers().get("HOST"), Some("example"));
+    wv_assert_eq!(t, req.headers().content_length(), Ok(Some(4)));
+
+    // bare line feeds are accepted as well
+    let (req, _) = wv_assert_some!(wv_assert_ok!(Request::parse(b"FOO / HTTP/1.0\n\n")));
+    wv_assert_eq!(t, *req.method(), Method::Other("FOO".to_string()));
+    wv_assert_eq!(t, req.version(), Version::Http10);
+
+    wv_assert_err!(t, Request::parse(b"GET /\r\n\r\n"), Code::InvArgs);
+    wv_assert_err!(t, Request::parse(b"GET / HTTP/2\r\n\r\n"), Code::NotSup);
+    wv_assert_err!(
+        t,
+        Request::parse(b"GET / HTTP/1.1\r\nNo colon\r\n\r\n"),
+        Code::InvArgs
+    );
+
+    // requests are written in the same format
+    let req = Request::new(Method::Post, "/upload").header("Host", "example");
+    wv_assert_eq!(
+        t,
+        req.to_string(),
+        "POST /upload HTTP/1.1\r\nHost: example\r\n"
+    );
+}
+
+fn response(t: &mut dyn WvTester) {
+    let msg = b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n";
+    let (resp, len) = wv_assert_some!(wv_assert_ok!(Response::parse(msg)));
+    wv_assert_eq!(t, len, msg.len());
+    wv_assert_eq!(t, resp.status(), 404);
+    wv_assert_eq!(t, resp.reason(), "Not Found");
+    wv_assert!(t, resp.has_body());
+    wv_assert!(t, !Response::new(204).has_body());
+
+    wv_assert_err!(
+        t,
+        Response::parse(b"HTTP/1.1 2000 OK\r\n\r\n"),
+        Code::InvArgs
+    );
+    let msg = b"HTTP/1.1 200 OK\r\nContent-Length: 1\r\nContent-Length: 2\r\n\r\n";
+    let (resp, _) = wv_assert_some!(wv_assert_ok!(Response::parse(msg)));
+    wv_assert_err!(t, resp.headers().content_length(), Code::InvArgs);
+
+    let resp = Response::new(200).header("Content-Type", "text/plain");
+    wv_assert_eq!(
+        t,
+        resp.to_string(),
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\n"
+    );
+}
+
+fn keep_alive(t: &mut dyn WvTester) {
+    let parse = |msg: &[u8]| Request::parse(msg).unwrap().unwrap().0;
+
+    wv_assert!(t, parse(b"GET / HTTP/1.1\r\n\r\n").keep_alive());
+    wv_assert!(
+        t,
+        !parse(b"GET / HTTP/1.1\r\nConnection: Close\r\n\r\n").keep_alive()
+    );
+    wv_assert!(t, !parse(b"GET / HTTP/1.0\r\n\r\n").keep_alive());
+    wv_assert!(
+        t,
+        parse(b"GET / HTTP/1.0\r\nConnection: keep-alive\r\n\r\n").keep_alive()
+    );
+}
+
+fn chunked(t: &mut dyn WvTester) {
+    let mut sink = Sink(Vec::new());
+    wv_assert_ok!(write_chunk(&mut sink, b"Hello, "));
+    wv_assert_ok!(write_chunk(&mut sink, b""));
+    wv_assert_ok!(write_chunk(&mut sink, b"World!"));
+    wv_assert_ok!(write_last_chunk(&mut sink));
+    wv_assert_eq!(t, sink.0, b"7\r\nHello, \r\n6\r\nWorld!\r\n0\r\n\r\n");
+
+    // decode byte by byte, followed by the next message
+    let mut input = sink.0.clone();
+    input.extend_from_slice(b"GET");
+    let mut dec = ChunkedDecoder::new();
+    let mut body = Vec::new();
+    let mut pos = 0;
+    while !dec.is_done() && pos < input.len() {
+        pos += wv_assert_ok!(dec.decode(&input[pos..pos + 1], &mut body));
+    }
+    wv_assert!(t, dec.is_done());
+    wv_assert_eq!(t, body, b"Hello, World!");
+    wv_assert_eq!(t, &input[pos..], b"GET");
+
+    // extensions and trailers are ignored
+    let mut dec = ChunkedDecoder::new();
+    let mut body = Vec::new();
+    let input = b"A;name=val\r\n0123456789\r\n0\r\nExpires: never\r\n\r\n";
+    wv_assert_eq!(t, dec.decode(input, &mut body), Ok(input.len()));
+    wv_assert!(t, dec.is_done());
+    wv_assert_eq!(t, body, b"0123456789");
+
+    let mut dec = ChunkedDecoder::new();
+    wv_assert_err!(t, dec.decode(b"xyz\r\n", &mut body), Code::InvArgs);
+    let mut dec = ChunkedDecoder::new();
+    wv_assert_err!(t, dec.decode(b"2\r\nabc", &mut body), Code::InvArgs);
+}
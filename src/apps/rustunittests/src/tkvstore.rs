@@ -0,0 +1,139 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use m3::client::{KvStore, KV_MAX_KEY_LEN, KV_MAX_VALUE_LEN};
+use m3::col::{String, Vec};
+use m3::errors::Code;
+use m3::test::WvTester;
+use m3::{format, vec};
+use m3::{wv_assert_eq, wv_assert_err, wv_assert_ok, wv_run_test};
+
+pub fn run(t: &mut dyn WvTester) {
+    wv_run_test!(t, put_get);
+    wv_run_test!(t, delete);
+    wv_run_test!(t, scan);
+    wv_run_test!(t, limits);
+    wv_run_test!(t, many_updates);
+}
+
+fn put_get(t: &mut dyn WvTester) {
+    let kv = wv_assert_ok!(KvStore::new("kvstore"));
+
+    wv_assert_ok!(kv.put("pg/foo", b"bar"));
+    wv_assert_eq!(t, kv.get("pg/foo"), Ok(b"bar".to_vec()));
+
+    // overwrite with a longer and a shorter value
+    wv_assert_ok!(kv.put("pg/foo", b"a much longer value"));
+    wv_assert_eq!(t, kv.get("pg/foo"), Ok(b"a much longer value".to_vec()));
+    wv_assert_ok!(kv.put("pg/foo", b""));
+    wv_assert_eq!(t, kv.get("pg/foo"), Ok(Vec::new()));
+
+    // values are visible in other sessions as well
+    let kv2 = wv_assert_ok!(KvStore::new("kvstore"));
+    wv_assert_eq!(t, kv2.get("pg/foo"), Ok(Vec::new()));
+
+    wv_assert_err!(t, kv.get("pg/none"), Code::NotFound);
+
+    wv_assert_ok!(kv.delete("pg/foo"));
+}
+
+fn delete(t: &mut dyn WvTester) {
+    let kv = wv_assert_ok!(KvStore::new("kvstore"));
+
+    wv_assert_ok!(kv.put("del/a", b"1"));
+    wv_assert_ok!(kv.delete("del/a"));
+    wv_assert_err!(t, kv.get("del/a"), Code::NotFound);
+    wv_assert_err!(t, kv.delete("del/a"), Code::NotFound);
+
+    // the key can be used again afterwards
+    wv_assert_ok!(kv.put("del/a", b"2"));
+    wv_assert_eq!(t, kv.get("del/a"), Ok(b"2".to_vec()));
+    wv_assert_ok!(kv.delete("del/a"));
+}
+
+fn scan(t: &mut dyn WvTester) {
+    let kv = wv_assert_ok!(KvStore::new("kvstore"));
+
+    for k in ["scan/b", "scan/a", "scan/c/x", "scana", "sca"] {
+        wv_assert_ok!(kv.put(k, k.as_bytes()));
+    }
+
+    wv_assert_eq!(
+        t,
+        kv.scan("scan/"),
+        Ok(vec![
+            String::from("scan/a"),
+            String::from("scan/b"),
+            String::from("scan/c/x")
+        ])
+    );
+    wv_assert_eq!(t, kv.scan("scan/c"), Ok(vec![String::from("scan/c/x")]));
+    wv_assert_eq!(t, kv.scan("scan/d"), Ok(Vec::new()));
+
+    for k in ["scan/b", "scan/a", "scan/c/x", "scana", "sca"] {
+        wv_assert_ok!(kv.delete(k));
+    }
+    wv_assert_eq!(t, kv.scan("sca"), Ok(Vec::new()));
+
+    // more keys than fit into a single reply
+    let keys = (0..100)
+        .map(|i| format!("scan/{:03}/{}", i, "x".repeat(64)))
+        .collect::<Vec<_>>();
+    for k in &keys {
+        wv_assert_ok!(kv.put(k, b""));
+    }
+    wv_assert_eq!(t, kv.scan("scan/"), Ok(keys.clone()));
+    for k in &keys {
+        wv_assert_ok!(kv.delete(k));
+    }
+}
+
+fn limits(t: &mut dyn WvTester) {
+    let kv = wv_assert_ok!(KvStore::new("kvstore"));
+
+    wv_assert_err!(t, kv.put("", b"x"), Code::InvArgs);
+
+    let long_key = "k".repeat(KV_MAX_KEY_LEN + 1);
+    wv_assert_err!(t, kv.put(&long_key, b"x"), Code::InvArgs);
+    let max_key = "k".repeat(KV_MAX_KEY_LEN);
+    wv_assert_ok!(kv.put(&max_key, b"x"));
+    wv_assert_ok!(kv.delete(&max_key));
+
+    let big = vec![0xAAu8; KV_MAX_VALUE_LEN + 1];
+    wv_assert_err!(t, kv.put("lim/big", &big), Code::InvArgs);
+    wv_assert_ok!(kv.put("lim/big", &big[0..KV_MAX_VALUE_LEN]));
+    wv_assert_eq!(t, kv.get("lim/big"), Ok(big[0..KV_MAX_VALUE_LEN].to_vec()));
+    wv_assert_ok!(kv.delete("lim/big"));
+}
+
+fn many_updates(t: &mut dyn WvTester) {
+    let kv = wv_assert_ok!(KvStore::new("kvstore"));
+
+    // overwrite the same keys often enough to trigger a compaction of the log
+    let value = vec![0x55u8; 1024];
+    for i in 0..200 {
+        wv_assert_ok!(kv.put(&format!("upd/{}", i % 4), &value[0..512 + i]));
+    }
+    for i in 196..200 {
+        wv_assert_eq!(
+            t,
+            kv.get(&format!("upd/{}", i % 4)),
+            Ok(value[0..512 + i].to_vec())
+        );
+    }
+    for i in 0..4 {
+        wv_assert_ok!(kv.delete(&format!("upd/{}", i)));
+    }
+}
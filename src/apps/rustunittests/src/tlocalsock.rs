@@ -0,0 +1,154 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use m3::cap::Selector;
+use m3::col::Vec;
+use m3::com::{LocalSocket, MAX_LOCAL_DATA};
+use m3::errors::Code;
+use m3::io::{Read, Write};
+use m3::kif::{CapRngDesc, CapType};
+use m3::test::{DefaultWvTester, WvTester};
+use m3::tiles::{Activity, ActivityArgs, ChildActivity, RunningActivity, Tile};
+use m3::vfs::{File, FileEvent};
+use m3::{wv_assert, wv_assert_eq, wv_assert_err, wv_assert_ok, wv_run_test};
+
+pub fn run(t: &mut dyn WvTester) {
+    wv_run_test!(t, basics);
+    wv_run_test!(t, large_data);
+    wv_run_test!(t, non_blocking);
+    wv_run_test!(t, close);
+    wv_run_test!(t, child);
+}
+
+fn basics(t: &mut dyn WvTester) {
+    let (mut a, mut b) = wv_assert_ok!(LocalSocket::pair());
+
+    wv_assert_eq!(t, a.write(b"ping"), Ok(4));
+    let mut buf = [0u8; 16];
+    wv_assert_eq!(t, b.read(&mut buf), Ok(4));
+    wv_assert_eq!(t, &buf[0..4], b"ping");
+
+    // the other direction works the same and data can be read in pieces
+    wv_assert_eq!(t, b.write(b"pong"), Ok(4));
+    wv_assert_eq!(t, a.read(&mut buf[0..3]), Ok(3));
+    wv_assert_eq!(t, a.read(&mut buf[3..]), Ok(1));
+    wv_assert_eq!(t, &buf[0..4], b"pong");
+}
+
+fn large_data(t: &mut dyn WvTester) {
+    let (mut a, mut b) = wv_assert_ok!(LocalSocket::pair());
+
+    // more than fits into a single message, but less than the available credits allow
+    let data = (0..MAX_LOCAL_DATA * 2 + 10)
+        .map(|i| i as u8)
+        .collect::<Vec<u8>>();
+    let mut pos = 0;
+    while pos < data.len() {
+        pos += wv_assert_ok!(a.write(&data[pos..]));
+    }
+
+    let mut recv = Vec::new();
+    let mut buf = [0u8; 100];
+    while recv.len() < data.len() {
+        let amount = wv_assert_ok!(b.read(&mut buf));
+        recv.extend_from_slice(&buf[0..amount]);
+    }
+    wv_assert_eq!(t, recv, data);
+}
+
+fn non_blocking(t: &mut dyn WvTester) {
+    let (mut a, mut b) = wv_assert_ok!(LocalSocket::pair());
+    wv_assert_ok!(a.set_blocking(false));
+    wv_assert_ok!(b.set_blocking(false));
+
+    let mut buf = [0u8; 16];
+    wv_assert_err!(t, b.read(&mut buf), Code::WouldBlock);
+    wv_assert!(t, !b.check_events(FileEvent::INPUT));
+
+    // fill all message slots except the one that is reserved for the close
+    let data = [0u8; MAX_LOCAL_DATA];
+    let mut sent = 0;
+    while a.check_events(FileEvent::OUTPUT) {
+        wv_assert_eq!(t, a.write(&data), Ok(MAX_LOCAL_DATA));
+        sent += 1;
+    }
+    wv_assert!(t, sent > 0);
+    wv_assert_err!(t, a.write(&data), Code::WouldBlock);
+
+    // reading a complete message returns the credit
+    wv_assert!(t, b.check_events(FileEvent::INPUT));
+    let mut buf = [0u8; MAX_LOCAL_DATA];
+    wv_assert_eq!(t, b.read(&mut buf), Ok(MAX_LOCAL_DATA));
+    wv_assert_eq!(t, a.write(&data), Ok(MAX_LOCAL_DATA));
+}
+
+fn close(t: &mut dyn WvTester) {
+    let (mut a, mut b) = wv_assert_ok!(LocalSocket::pair());
+
+    wv_assert_eq!(t, a.write(b"bye"), Ok(3));
+    wv_assert_ok!(a.borrow_as().close());
+    wv_assert_err!(t, a.write(b"more"), Code::SocketClosed);
+
+    // the data before the close is still received, followed by EOF
+    let mut buf = [0u8; 16];
+    wv_assert_eq!(t, b.read(&mut buf), Ok(3));
+    wv_assert_eq!(t, b.read(&mut buf), Ok(0));
+    wv_assert_eq!(t, b.read(&mut buf), Ok(0));
+    wv_assert!(t, b.check_events(FileEvent::INPUT));
+
+    // the other direction is still open
+    wv_assert_eq!(t, b.write(b"ack"), Ok(3));
+    wv_assert_eq!(t, a.read(&mut buf), Ok(3));
+    wv_assert_eq!(t, &buf[0..3], b"ack");
+}
+
+fn child(t: &mut dyn WvTester) {
+    let (mut sock, caps) = wv_assert_ok!(LocalSocket::new());
+
+    let tile = wv_assert_ok!(Tile::get("compat|own"));
+    let mut act = wv_assert_ok!(ChildActivity::new_with(tile, ActivityArgs::new("child")));
+    wv_assert_ok!(act.delegate(CapRngDesc::new(CapType::Object, caps, 2)));
+
+    let mut dst = act.data_sink();
+    dst.push(caps);
+
+    let act = wv_assert_ok!(act.run(|| {
+        let mut t = DefaultWvTester::default();
+        let mut src = Activity::own().data_source();
+        let caps: Selector = src.pop().unwrap();
+
+        // echo everything back until the parent closes its side
+        let mut sock = wv_assert_ok!(LocalSocket::new_bind(caps));
+        let mut buf = [0u8; 64];
+        loop {
+            let amount = wv_assert_ok!(sock.read(&mut buf));
+            if amount == 0 {
+                break;
+            }
+            wv_assert_eq!(t, sock.write(&buf[0..amount]), Ok(amount));
+        }
+        Ok(())
+    }));
+
+    let mut buf = [0u8; 64];
+    for msg in [&b"Hello"[..], &b"World!"[..]] {
+        wv_assert_eq!(t, sock.write(msg), Ok(msg.len()));
+        wv_assert_eq!(t, sock.read(&mut buf), Ok(msg.len()));
+        wv_assert_eq!(t, &buf[0..msg.len()], msg);
+    }
+    wv_assert_ok!(sock.borrow_as().close());
+
+    wv_assert_eq!(t, act.wait(), Ok(Code::Success));
+}
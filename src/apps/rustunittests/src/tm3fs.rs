@@ -16,18 +16,24 @@
  * General Public License version 2 for more details.
  */
 
-use m3::col::ToString;
+use m3::client::{WatchEvent, M3FS};
+use m3::col::{String, ToString, Vec};
 use m3::errors::Code;
-use m3::io::Write;
+use m3::io::{Read, Write};
 use m3::test::WvTester;
 use m3::vfs::{FileMode, OpenFlags, VFS};
-use m3::{wv_assert_eq, wv_assert_err, wv_assert_ok, wv_run_test};
+use m3::{wv_assert, wv_assert_eq, wv_assert_err, wv_assert_ok, wv_run_test};
 
 pub fn run(t: &mut dyn WvTester) {
     wv_run_test!(t, paths);
     wv_run_test!(t, mkdir_rmdir);
     wv_run_test!(t, link_unlink);
     wv_run_test!(t, rename);
+    wv_run_test!(t, times);
+    wv_run_test!(t, perms);
+    wv_run_test!(t, tmpfs);
+    wv_run_test!(t, overlay);
+    wv_run_test!(t, watch);
 }
 
 fn setup() {
@@ -252,3 +258,254 @@ fn rename(t: &mut dyn WvTester) {
 
     teardown();
 }
+
+fn times(t: &mut dyn WvTester) {
+    setup();
+
+    wv_assert_err!(
+        t,
+        VFS::utime("/example/non-existing", 1000, 2000),
+        Code::NoSuchFile
+    );
+
+    // set times explicitly
+    wv_assert_ok!(VFS::utime("/example/myfile", 1000, 2000));
+    let info = wv_assert_ok!(VFS::stat("/example/myfile"));
+    wv_assert_eq!(t, info.lastaccess, 1000);
+    wv_assert_eq!(t, info.lastmod, 2000);
+
+    // writing updates the modification and change time
+    {
+        let mut file = wv_assert_ok!(VFS::open("/example/myfile", OpenFlags::W));
+        wv_assert_ok!(write!(file, "more text\n"));
+    }
+    let info = wv_assert_ok!(VFS::stat("/example/myfile"));
+    wv_assert_eq!(t, info.lastaccess, 1000);
+    wv_assert_eq!(t, info.lastmod, info.lastchange);
+
+    // renaming only updates the change time
+    wv_assert_ok!(VFS::utime("/example/myfile", 1000, 2000));
+    wv_assert_ok!(VFS::rename("/example/myfile", "/example/myfile2"));
+    let info = wv_assert_ok!(VFS::stat("/example/myfile2"));
+    wv_assert_eq!(t, info.lastmod, 2000);
+    wv_assert_ok!(VFS::rename("/example/myfile2", "/example/myfile"));
+
+    teardown();
+}
+
+fn perms(t: &mut dyn WvTester) {
+    setup();
+
+    // the session "m3fs-user" has the identity uid=1000 gid=100; all others belong to root
+    wv_assert_ok!(VFS::mount("/user/", "m3fs", "m3fs-user"));
+
+    // others can read, but not write files of root
+    wv_assert_ok!(VFS::open("/user/example/myfile", OpenFlags::R));
+    wv_assert_err!(
+        t,
+        VFS::open("/user/example/myfile", OpenFlags::W),
+        Code::NoPerm
+    );
+    wv_assert_err!(
+        t,
+        VFS::utime("/user/example/myfile", 1000, 2000),
+        Code::NoPerm
+    );
+
+    // others cannot change directories of root
+    wv_assert_err!(
+        t,
+        VFS::open("/user/example/new", OpenFlags::W | OpenFlags::CREATE),
+        Code::NoPerm
+    );
+    wv_assert_err!(
+        t,
+        VFS::mkdir("/user/example/dir", FileMode::from_bits(0o755).unwrap()),
+        Code::NoPerm
+    );
+    wv_assert_err!(t, VFS::unlink("/user/example/myfile"), Code::NoPerm);
+    wv_assert_err!(
+        t,
+        VFS::rename("/user/example/myfile", "/user/example/other"),
+        Code::NoPerm
+    );
+
+    // but they can in a world-writable directory and own the files they create
+    wv_assert_ok!(VFS::mkdir(
+        "/example/shared",
+        FileMode::from_bits(0o777).unwrap()
+    ));
+    {
+        let mut file = wv_assert_ok!(VFS::open(
+            "/user/example/shared/file",
+            OpenFlags::W | OpenFlags::CREATE
+        ));
+        wv_assert_ok!(write!(file, "text\n"));
+    }
+    let info = wv_assert_ok!(VFS::stat("/example/shared/file"));
+    wv_assert_eq!(t, info.uid, 1000);
+    wv_assert_eq!(t, info.gid, 100);
+    wv_assert_ok!(VFS::utime("/user/example/shared/file", 1000, 2000));
+
+    // root is treated as the owner
+    wv_assert_ok!(VFS::open("/example/shared/file", OpenFlags::RW));
+
+    wv_assert_ok!(VFS::unlink("/user/example/shared/file"));
+    wv_assert_ok!(VFS::rmdir("/example/shared"));
+    wv_assert_ok!(VFS::unmount("/user/"));
+
+    teardown();
+}
+
+fn tmpfs(t: &mut dyn WvTester) {
+    // "m3fs-tmp" starts with an empty file system in memory
+    wv_assert_ok!(VFS::mount("/tmp/", "m3fs", "m3fs-tmp"));
+
+    let info = wv_assert_ok!(VFS::stat("/tmp"));
+    wv_assert!(t, info.mode.is_dir());
+    wv_assert_eq!(t, info.mode & FileMode::PERM, FileMode::PERM);
+    let names = wv_assert_ok!(VFS::read_dir("/tmp"))
+        .map(|e| e.file_name().to_string())
+        .collect::<Vec<_>>();
+    wv_assert_eq!(t, names, [".", ".."]);
+
+    {
+        let mut file = wv_assert_ok!(VFS::open("/tmp/scratch", OpenFlags::W | OpenFlags::CREATE));
+        wv_assert_ok!(write!(file, "temporary\n"));
+    }
+    let mut file = wv_assert_ok!(VFS::open("/tmp/scratch", OpenFlags::R));
+    wv_assert_eq!(t, wv_assert_ok!(file.read_to_string()), "temporary\n");
+    drop(file);
+    wv_assert_ok!(VFS::unlink("/tmp/scratch"));
+
+    wv_assert_ok!(VFS::unmount("/tmp/"));
+}
+
+fn overlay(t: &mut dyn WvTester) {
+    setup();
+
+    fn entries(path: &str) -> Vec<String> {
+        let mut names = wv_assert_ok!(VFS::read_dir(path))
+            .map(|e| e.file_name().to_string())
+            .filter(|n| n != "." && n != "..")
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    // "m3fs-tmp" is the writable upper layer, whereas our root file system is the lower layer
+    wv_assert_ok!(VFS::mount_overlay("/ovl/", "m3fs-tmp", "m3fs-clone"));
+
+    // files of the lower layer are visible
+    let mut file = wv_assert_ok!(VFS::open("/ovl/example/myfile", OpenFlags::R));
+    wv_assert_eq!(t, wv_assert_ok!(file.read_to_string()), "text\n");
+    drop(file);
+
+    // writes are performed on a copy in the upper layer
+    {
+        let mut file = wv_assert_ok!(VFS::open("/ovl/example/myfile", OpenFlags::W));
+        wv_assert_ok!(write!(file, "new!\n"));
+    }
+    let mut file = wv_assert_ok!(VFS::open("/ovl/example/myfile", OpenFlags::R));
+    wv_assert_eq!(t, wv_assert_ok!(file.read_to_string()), "new!\n");
+    let mut file = wv_assert_ok!(VFS::open("/example/myfile", OpenFlags::R));
+    wv_assert_eq!(t, wv_assert_ok!(file.read_to_string()), "text\n");
+    drop(file);
+
+    // new files only exist in the overlay and directories are merged
+    wv_assert_ok!(VFS::open(
+        "/ovl/example/other",
+        OpenFlags::W | OpenFlags::CREATE
+    ));
+    wv_assert_err!(t, VFS::stat("/example/other"), Code::NoSuchFile);
+    wv_assert_eq!(t, entries("/ovl/example"), ["myfile", "other"]);
+
+    // removed files of the lower layer are hidden, but still exist there
+    wv_assert_ok!(VFS::unlink("/ovl/example/myfile"));
+    wv_assert_err!(t, VFS::stat("/ovl/example/myfile"), Code::NoSuchFile);
+    wv_assert_ok!(VFS::stat("/example/myfile"));
+    wv_assert_eq!(t, entries("/ovl/example"), ["other"]);
+
+    // directories need to be empty in all layers
+    wv_assert_err!(t, VFS::rmdir("/ovl/example"), Code::DirNotEmpty);
+    wv_assert_ok!(VFS::unlink("/ovl/example/other"));
+    wv_assert_ok!(VFS::rmdir("/ovl/example"));
+    wv_assert_err!(t, VFS::stat("/ovl/example"), Code::NoSuchFile);
+    wv_assert_ok!(VFS::stat("/example"));
+
+    // a recreated directory does not contain the entries of the lower layer
+    wv_assert_ok!(VFS::mkdir(
+        "/ovl/example",
+        FileMode::from_bits(0o755).unwrap()
+    ));
+    wv_assert_eq!(t, entries("/ovl/example"), Vec::<String>::new());
+    wv_assert_err!(t, VFS::stat("/ovl/example/myfile"), Code::NoSuchFile);
+    wv_assert_ok!(VFS::rmdir("/ovl/example"));
+
+    wv_assert_ok!(VFS::unmount("/ovl/"));
+
+    teardown();
+}
+
+fn watch(t: &mut dyn WvTester) {
+    setup();
+
+    let m3fs = wv_assert_ok!(M3FS::new(1, "m3fs-clone"));
+    let m3fs = m3fs.borrow();
+    let m3fs = m3fs.as_any().downcast_ref::<M3FS>().unwrap();
+
+    // only existing paths can be watched
+    wv_assert_err!(
+        t,
+        m3fs.watch("/example/foo", WatchEvent::all())
+            .map(|w| w.id()),
+        Code::NoSuchFile
+    );
+
+    let watch = wv_assert_ok!(m3fs.watch("/example", WatchEvent::all()));
+    wv_assert!(t, matches!(watch.try_receive(), Ok(None)));
+
+    {
+        let mut file = wv_assert_ok!(VFS::open(
+            "/example/newfile",
+            OpenFlags::W | OpenFlags::CREATE
+        ));
+        wv_assert_ok!(write!(file, "foo"));
+    }
+    wv_assert_eq!(
+        t,
+        watch.receive(),
+        Ok((WatchEvent::CREATE, "newfile".to_string()))
+    );
+    wv_assert_eq!(
+        t,
+        watch.receive(),
+        Ok((WatchEvent::MODIFY, "newfile".to_string()))
+    );
+
+    wv_assert_ok!(VFS::rename("/example/newfile", "/example/renamed"));
+    wv_assert_eq!(
+        t,
+        watch.receive(),
+        Ok((WatchEvent::RENAME, "newfile".to_string()))
+    );
+    wv_assert_eq!(
+        t,
+        watch.receive(),
+        Ok((WatchEvent::RENAME, "renamed".to_string()))
+    );
+
+    // changes outside of the watched directory are not reported
+    wv_assert_ok!(VFS::mkdir("/other", FileMode::from_bits(0o755).unwrap()));
+    wv_assert_ok!(VFS::rmdir("/other"));
+
+    wv_assert_ok!(VFS::unlink("/example/renamed"));
+    wv_assert_eq!(
+        t,
+        watch.receive(),
+        Ok((WatchEvent::DELETE, "renamed".to_string()))
+    );
+    wv_assert!(t, matches!(watch.try_receive(), Ok(None)));
+
+    teardown();
+}
@@ -0,0 +1,113 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use m3::math::{self, Float, FloatConst};
+use m3::test::WvTester;
+use m3::{wv_assert, wv_assert_eq, wv_run_test};
+
+pub fn run(t: &mut dyn WvTester) {
+    wv_run_test!(t, trigonometry);
+    wv_run_test!(t, exponential);
+    wv_run_test!(t, float_trait);
+    wv_run_test!(t, conversions);
+    wv_run_test!(t, comparisons);
+}
+
+const EPSILON: f64 = 1e-9;
+
+fn approx_eq(a: f64, b: f64) -> bool {
+    math::fabs(a - b) < EPSILON
+}
+
+fn trigonometry(t: &mut dyn WvTester) {
+    wv_assert!(t, approx_eq(math::sin(0.0), 0.0));
+    wv_assert!(t, approx_eq(math::sin(f64::PI() / 2.0), 1.0));
+    wv_assert!(t, approx_eq(math::cos(f64::PI()), -1.0));
+    wv_assert!(t, approx_eq(math::tan(f64::FRAC_PI_4()), 1.0));
+    wv_assert!(t, approx_eq(math::atan2(1.0, 1.0), f64::FRAC_PI_4()));
+
+    let x = 0.7;
+    let (s, c) = (math::sin(x), math::cos(x));
+    wv_assert!(t, approx_eq(s * s + c * c, 1.0));
+    wv_assert!(t, math::fabsf(math::sinf(x as f32) - s as f32) < 1e-6);
+}
+
+fn exponential(t: &mut dyn WvTester) {
+    wv_assert!(t, approx_eq(math::exp(0.0), 1.0));
+    wv_assert!(t, approx_eq(math::exp(1.0), f64::E()));
+    wv_assert!(t, approx_eq(math::log(f64::E()), 1.0));
+    wv_assert!(t, approx_eq(math::pow(2.0, 10.0), 1024.0));
+    wv_assert!(t, approx_eq(math::pow(9.0, 0.5), 3.0));
+    wv_assert!(t, approx_eq(math::sqrt(2.0) * math::sqrt(2.0), 2.0));
+    wv_assert!(t, math::sqrt(-1.0).is_nan());
+}
+
+fn float_trait(t: &mut dyn WvTester) {
+    wv_assert!(t, approx_eq(Float::sin(f64::FRAC_PI_2()), 1.0));
+    wv_assert!(t, approx_eq(Float::powi(3.0f64, 3), 27.0));
+    wv_assert!(t, approx_eq(Float::sqrt(16.0f64), 4.0));
+    wv_assert_eq!(t, Float::floor(2.7f64), 2.0);
+    wv_assert_eq!(t, Float::ceil(2.1f64), 3.0);
+    wv_assert_eq!(t, Float::round(-2.5f64), -3.0);
+    wv_assert_eq!(t, Float::trunc(-2.7f32), -2.0);
+}
+
+fn conversions(t: &mut dyn WvTester) {
+    // use black_box to prevent constant folding so that the conversions happen at runtime (via the
+    // soft-float helpers on ARM)
+    let d = core::hint::black_box(-1234.75f64);
+    wv_assert_eq!(t, d as i32, -1234);
+    wv_assert_eq!(t, d as i64, -1234);
+    wv_assert_eq!(t, -d as u32, 1234);
+    wv_assert_eq!(t, -d as u64, 1234);
+    wv_assert_eq!(t, d as f32, -1234.75f32);
+
+    let f = core::hint::black_box(3.5f32);
+    wv_assert_eq!(t, f as i32, 3);
+    wv_assert_eq!(t, f as u64, 3);
+    wv_assert_eq!(t, f as f64, 3.5f64);
+
+    let i = core::hint::black_box(-42i32);
+    wv_assert_eq!(t, i as f64, -42.0);
+    wv_assert_eq!(t, i as f32, -42.0);
+    let u = core::hint::black_box(u64::MAX);
+    wv_assert_eq!(t, u as f64, 18446744073709551616.0);
+}
+
+// the comparisons with NaN are intended to test the unordered cases of the helpers
+#[allow(clippy::eq_op, clippy::neg_cmp_op_on_partial_ord)]
+fn comparisons(t: &mut dyn WvTester) {
+    let a = core::hint::black_box(1.5f64);
+    let b = core::hint::black_box(2.5f64);
+    let nan = core::hint::black_box(f64::NAN);
+    wv_assert!(t, a < b);
+    wv_assert!(t, a <= b);
+    wv_assert!(t, b > a);
+    wv_assert!(t, b >= a);
+    wv_assert!(t, a != b);
+    wv_assert!(
+        t,
+        !(nan < a) && !(nan <= a) && !(nan > a) && !(nan >= a) && nan != nan
+    );
+    wv_assert_eq!(t, b - a, 1.0);
+    wv_assert_eq!(t, a * b, 3.75);
+    wv_assert_eq!(t, b / 0.5, 5.0);
+
+    let x = core::hint::black_box(1.5f32);
+    let nanf = core::hint::black_box(f32::NAN);
+    wv_assert!(t, (1.5..2.0).contains(&x));
+    wv_assert!(t, nanf.is_nan());
+    wv_assert_eq!(t, x + x, 3.0);
+}
@@ -23,6 +23,8 @@ use m3::{wv_assert_eq, wv_assert_err, wv_run_test};
 
 pub fn run(t: &mut dyn WvTester) {
     wv_run_test!(t, basics);
+    wv_run_test!(t, best_fit);
+    wv_run_test!(t, largest);
 }
 
 fn basics(t: &mut dyn WvTester) {
@@ -43,3 +45,55 @@ fn basics(t: &mut dyn WvTester) {
 
     wv_assert_eq!(t, m.size(), (0x1000, 1));
 }
+
+fn best_fit(t: &mut dyn WvTester) {
+    let mut m = MemMap::new(0, 0x1000);
+
+    // create a large hole at 0x0 and a small hole at 0x500
+    wv_assert_eq!(t, m.allocate(0x400, 0x10), Ok(0x0));
+    wv_assert_eq!(t, m.allocate(0x100, 0x10), Ok(0x400));
+    wv_assert_eq!(t, m.allocate(0x100, 0x10), Ok(0x500));
+    wv_assert_eq!(t, m.allocate(0xA00, 0x10), Ok(0x600));
+    m.free(0x0, 0x400);
+    m.free(0x500, 0x100);
+
+    // first fit takes the large hole, best fit the small one
+    wv_assert_eq!(t, m.allocate_best_fit(0x80, 0x10), Ok(0x500));
+    wv_assert_eq!(t, m.allocate_best_fit(0x80, 0x10), Ok(0x580));
+    wv_assert_eq!(t, m.allocate_best_fit(0x80, 0x10), Ok(0x0));
+    wv_assert_eq!(t, m.allocate(0x80, 0x10), Ok(0x80));
+
+    // alignment is considered for the fit
+    wv_assert_eq!(t, m.allocate_best_fit(0x100, 0x200), Ok(0x200));
+    wv_assert_err!(t, m.allocate_best_fit(0x400, 0x10), Code::OutOfMem);
+
+    m.free(0x0, 0x100);
+    m.free(0x200, 0x100);
+    m.free(0x500, 0x100);
+    m.free(0x400, 0x100);
+    m.free(0x600, 0xA00);
+
+    wv_assert_eq!(t, m.size(), (0x1000, 1));
+}
+
+fn largest(t: &mut dyn WvTester) {
+    let mut m = MemMap::new(0, 0x1000);
+    wv_assert_eq!(t, m.largest_contiguous(), Some(0x1000));
+
+    wv_assert_eq!(t, m.allocate(0x800, 0x10), Ok(0x0));
+    wv_assert_eq!(t, m.largest_contiguous(), Some(0x800));
+    wv_assert_eq!(t, m.allocate(0x100, 0x10), Ok(0x800));
+    wv_assert_eq!(t, m.largest_contiguous(), Some(0x700));
+
+    // freeing a smaller area does not change the largest one
+    m.free(0x0, 0x400);
+    wv_assert_eq!(t, m.largest_contiguous(), Some(0x700));
+    // merging areas does
+    m.free(0x400, 0x400);
+    wv_assert_eq!(t, m.largest_contiguous(), Some(0x800));
+    m.free(0x800, 0x100);
+    wv_assert_eq!(t, m.largest_contiguous(), Some(0x1000));
+
+    wv_assert_eq!(t, m.allocate(0x1000, 0x10), Ok(0x0));
+    wv_assert_eq!(t, m.largest_contiguous(), None);
+}
@@ -19,6 +19,7 @@
 use m3::cap::Selector;
 use m3::cfg;
 use m3::client::MapFlags;
+use m3::col::Vec;
 use m3::com::{MGateArgs, MemGate, Perm, Semaphore};
 use m3::errors::Code;
 use m3::mem::{GlobOff, VirtAddr};
@@ -34,6 +35,8 @@ pub fn run(t: &mut dyn WvTester) {
     wv_run_test!(t, derive);
     wv_run_test!(t, read_write);
     wv_run_test!(t, read_write_object);
+    wv_run_test!(t, read_write_vectored);
+    wv_run_test!(t, copy);
     wv_run_test!(t, remote_access);
 }
 
@@ -104,6 +107,49 @@ fn read_write_object(t: &mut dyn WvTester) {
     wv_assert_eq!(t, refobj, obj);
 }
 
+fn read_write_vectored(t: &mut dyn WvTester) {
+    let mgate = wv_assert_ok!(MemGate::new(0x2000, Perm::RW));
+    let refdata = [0u8, 1, 2, 3, 4, 5, 6, 7];
+
+    // write across a page boundary in the memory region
+    wv_assert_ok!(mgate.write_v(&[&refdata[0..3], &[], &refdata[3..8]], 0xFFC));
+
+    let mut data = [0u8; 8];
+    wv_assert_ok!(mgate.read(&mut data, 0xFFC));
+    wv_assert_eq!(t, data, refdata);
+
+    let mut first = [0u8; 5];
+    let mut second = [0u8; 3];
+    wv_assert_ok!(mgate.read_v(&mut [&mut first, &mut second], 0xFFC));
+    wv_assert_eq!(t, &first, &refdata[0..5]);
+    wv_assert_eq!(t, &second, &refdata[5..8]);
+}
+
+fn copy(t: &mut dyn WvTester) {
+    let src = wv_assert_ok!(MemGate::new(0x4000, Perm::RW));
+    let dst = wv_assert_ok!(MemGate::new(0x4000, Perm::RW));
+
+    let refdata = (0..0x3000).map(|i| i as u8).collect::<Vec<u8>>();
+    wv_assert_ok!(src.write(&refdata, 0x10));
+
+    // more than a single chunk at unaligned offsets
+    wv_assert_ok!(src.copy_to(&dst, 0x2FF0, 0x20, 0x7));
+    let data = wv_assert_ok!(dst.read_into_vec::<u8>(0x2FF0, 0x7));
+    wv_assert_eq!(t, &data[..], &refdata[0x10..0x3000]);
+
+    // non-overlapping copy within the same region
+    wv_assert_ok!(src.copy_to(&src, 0x8, 0x10, 0x3800));
+    let data = wv_assert_ok!(src.read_into_vec::<u8>(0x8, 0x3800));
+    wv_assert_eq!(t, &data[..], &refdata[0..8]);
+
+    // copying nothing is fine
+    wv_assert_ok!(src.copy_to(&dst, 0, 0, 0));
+
+    let rdst = wv_assert_ok!(dst.derive(0, 0x1000, Perm::R));
+    wv_assert_err!(t, src.copy_to(&rdst, 0x10, 0, 0), Code::NoPerm);
+    wv_assert_err!(t, src.copy_to(&dst, 0x10, 0x3FF8, 0), Code::OutOfBounds);
+}
+
 fn remote_access(t: &mut dyn WvTester) {
     static mut _OBJ: u64 = 0;
     let sem1 = wv_assert_ok!(Semaphore::create(0));
@@ -0,0 +1,103 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use m3::col::{FixedMpscQueue, MpscQueue, String};
+use m3::test::WvTester;
+use m3::{wv_assert, wv_assert_eq, wv_run_test};
+
+pub fn run(t: &mut dyn WvTester) {
+    wv_run_test!(t, heap_order);
+    wv_run_test!(t, heap_interleaved);
+    wv_run_test!(t, fixed_order);
+    wv_run_test!(t, fixed_full);
+    wv_run_test!(t, fixed_wrap);
+    wv_run_test!(t, drop_remaining);
+}
+
+fn heap_order(t: &mut dyn WvTester) {
+    let q = MpscQueue::new();
+    wv_assert!(t, q.is_empty());
+    wv_assert_eq!(t, q.pop(), None);
+
+    for i in 0..10 {
+        q.push(i);
+    }
+    wv_assert_eq!(t, q.len(), 10);
+    for i in 0..10 {
+        wv_assert_eq!(t, q.pop(), Some(i));
+    }
+    wv_assert_eq!(t, q.pop(), None);
+    wv_assert!(t, q.is_empty());
+}
+
+fn heap_interleaved(t: &mut dyn WvTester) {
+    let q = MpscQueue::new();
+    q.push(1);
+    q.push(2);
+    wv_assert_eq!(t, q.pop(), Some(1));
+    // pushed while the consumer has already taken over the older elements
+    q.push(3);
+    wv_assert_eq!(t, q.pop(), Some(2));
+    wv_assert_eq!(t, q.pop(), Some(3));
+    wv_assert_eq!(t, q.pop(), None);
+}
+
+fn fixed_order(t: &mut dyn WvTester) {
+    let q: FixedMpscQueue<u32, 8> = FixedMpscQueue::new();
+    wv_assert_eq!(t, q.capacity(), 8);
+    wv_assert_eq!(t, q.pop(), None);
+
+    for i in 0..5 {
+        wv_assert_eq!(t, q.push(i), Ok(()));
+    }
+    wv_assert_eq!(t, q.len(), 5);
+    for i in 0..5 {
+        wv_assert_eq!(t, q.pop(), Some(i));
+    }
+    wv_assert!(t, q.is_empty());
+}
+
+fn fixed_full(t: &mut dyn WvTester) {
+    let q: FixedMpscQueue<u32, 4> = FixedMpscQueue::new();
+    for i in 0..4 {
+        wv_assert_eq!(t, q.push(i), Ok(()));
+    }
+    wv_assert_eq!(t, q.push(4), Err(4));
+    wv_assert_eq!(t, q.pop(), Some(0));
+    wv_assert_eq!(t, q.push(4), Ok(()));
+    wv_assert_eq!(t, q.len(), 4);
+}
+
+fn fixed_wrap(t: &mut dyn WvTester) {
+    let q: FixedMpscQueue<u32, 2> = FixedMpscQueue::new();
+    for i in 0..100 {
+        wv_assert_eq!(t, q.push(i), Ok(()));
+        wv_assert_eq!(t, q.pop(), Some(i));
+    }
+    wv_assert_eq!(t, q.pop(), None);
+}
+
+fn drop_remaining(t: &mut dyn WvTester) {
+    let before = m3::heap::stats();
+    {
+        let q = MpscQueue::new();
+        q.push(String::from("foo"));
+        q.push(String::from("bar"));
+        let f: FixedMpscQueue<String, 2> = FixedMpscQueue::new();
+        wv_assert_eq!(t, f.push(String::from("baz")), Ok(()));
+    }
+    // the remaining elements have been dropped
+    wv_assert_eq!(t, m3::heap::stats().cur_bytes, before.cur_bytes);
+}
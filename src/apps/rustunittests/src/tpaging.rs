@@ -13,15 +13,23 @@
  * General Public License version 2 for more details.
  */
 
-use m3::com::MemGate;
+use m3::cfg;
+use m3::client::MapFlags;
+use m3::com::{MGateArgs, MemGate};
+use m3::errors::Code;
+use m3::io::{Read, Write};
 use m3::kif::Perm;
 use m3::mem::{GlobOff, VirtAddr};
 use m3::test::WvTester;
 use m3::tiles::Activity;
-use m3::{wv_assert_ok, wv_run_test};
+use m3::vfs::{FileMapping, OpenFlags, Seek, SeekMode, VFS};
+use m3::{vec, wv_assert_eq, wv_assert_err, wv_assert_ok, wv_run_test};
 
 pub fn run(t: &mut dyn WvTester) {
     wv_run_test!(t, large_pages);
+    wv_run_test!(t, large_page_mgate);
+    wv_run_test!(t, lazy_anon);
+    wv_run_test!(t, map_file);
 }
 
 fn large_pages(_t: &mut dyn WvTester) {
@@ -42,3 +50,117 @@ fn large_pages(_t: &mut dyn WvTester) {
         m3::println!("Skipping paging test without pager");
     }
 }
+
+fn large_page_mgate(t: &mut dyn WvTester) {
+    let mem = wv_assert_ok!(MemGate::new_with(
+        MGateArgs::new(3 * cfg::PAGE_SIZE as GlobOff, Perm::RW).large_pages(true)
+    ));
+
+    // the memory is rounded up to a large page and aligned accordingly
+    let (addr, size) = wv_assert_ok!(mem.region());
+    wv_assert_eq!(t, size, cfg::LPAGE_SIZE as GlobOff);
+    wv_assert_eq!(t, addr.offset() % cfg::LPAGE_SIZE as GlobOff, 0);
+
+    if let Some(pager) = Activity::own().pager() {
+        const VIRT: VirtAddr = VirtAddr::new(0x3200_0000);
+        wv_assert_ok!(pager.map_mem(VIRT, mem.sel(), cfg::LPAGE_SIZE, Perm::RW));
+
+        // write via the mapping and read it via the gate
+        let off = cfg::LPAGE_SIZE - cfg::PAGE_SIZE;
+        unsafe {
+            (VIRT + off).as_mut_ptr::<u64>().write_volatile(0xDEAD_BEEF);
+        }
+        wv_assert_eq!(
+            t,
+            wv_assert_ok!(mem.read_obj::<u64>(off as GlobOff)),
+            0xDEAD_BEEF
+        );
+
+        wv_assert_ok!(pager.unmap(VIRT));
+    }
+}
+
+fn lazy_anon(t: &mut dyn WvTester) {
+    if let Some(pager) = Activity::own().pager() {
+        // by default, we can map more memory than we have, because it's only allocated on touch
+        const VIRT: VirtAddr = VirtAddr::new(0x4000_0000);
+        const MAP_SIZE: usize = 256 * 1024 * 1024;
+        wv_assert_ok!(pager.map_anon(VIRT, MAP_SIZE, Perm::RW, MapFlags::NOLPAGE));
+
+        // touch a few pages; they are zero-filled on first touch
+        for off in [0, MAP_SIZE / 2, MAP_SIZE - cfg::PAGE_SIZE] {
+            let ptr = (VIRT + off).as_mut_ptr::<u64>();
+            unsafe {
+                wv_assert_eq!(t, ptr.read_volatile(), 0);
+                ptr.write_volatile(off as u64);
+                wv_assert_eq!(t, ptr.read_volatile(), off as u64);
+            }
+        }
+
+        wv_assert_ok!(pager.unmap(VIRT));
+    }
+    else {
+        m3::println!("Skipping paging test without pager");
+    }
+}
+
+fn map_file(t: &mut dyn WvTester) {
+    if Activity::own().pager().is_none() {
+        m3::println!("Skipping file mapping test without pager");
+        return;
+    }
+
+    const VIRT: VirtAddr = VirtAddr::new(0x3100_0000);
+
+    {
+        let mut file = wv_assert_ok!(VFS::open(
+            "/mapped",
+            OpenFlags::W | OpenFlags::CREATE | OpenFlags::TRUNC
+        ));
+        let content = vec![b'a'; cfg::PAGE_SIZE];
+        wv_assert_ok!(file.write_all(&content));
+    }
+
+    // files without their own session cannot be mapped
+    {
+        let file = wv_assert_ok!(VFS::open("/mapped", OpenFlags::RW));
+        wv_assert_err!(
+            t,
+            FileMapping::new(file, VIRT, 0, cfg::PAGE_SIZE, Perm::RW, MapFlags::SHARED),
+            Code::NotSup
+        );
+    }
+
+    {
+        let file = wv_assert_ok!(VFS::open("/mapped", OpenFlags::RW | OpenFlags::NEW_SESS));
+        let mut map = wv_assert_ok!(FileMapping::new(
+            file,
+            VIRT,
+            0,
+            cfg::PAGE_SIZE,
+            Perm::RW,
+            MapFlags::SHARED
+        ));
+
+        // safety: the mapping is readable and writable and nobody truncates the file
+        let data = unsafe { map.as_mut_slice() };
+        wv_assert_eq!(t, data[0], b'a');
+        wv_assert_eq!(t, data[cfg::PAGE_SIZE - 1], b'a');
+        data[0] = b'b';
+        data[cfg::PAGE_SIZE - 1] = b'c';
+        wv_assert_ok!(map.sync());
+    }
+
+    // the changes are visible via the file as well
+    {
+        let mut file = wv_assert_ok!(VFS::open("/mapped", OpenFlags::R));
+        let mut buf = [0u8; 1];
+        wv_assert_ok!(file.read_exact(&mut buf));
+        wv_assert_eq!(t, buf[0], b'b');
+        wv_assert_ok!(file.seek(cfg::PAGE_SIZE - 1, SeekMode::Set));
+        wv_assert_ok!(file.read_exact(&mut buf));
+        wv_assert_eq!(t, buf[0], b'c');
+    }
+
+    wv_assert_ok!(VFS::unlink("/mapped"));
+}
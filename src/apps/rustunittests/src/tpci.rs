@@ -0,0 +1,56 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use m3::test::WvTester;
+use m3::{wv_assert_eq, wv_run_test};
+
+use pci::Interrupt;
+
+pub fn run(t: &mut dyn WvTester) {
+    wv_run_test!(t, msix_payload);
+    wv_run_test!(t, legacy_payload);
+}
+
+fn msix_payload(t: &mut dyn WvTester) {
+    for vec in [0, 1, 7, 2047] {
+        let data = Interrupt::msix_data(vec);
+        wv_assert_eq!(
+            t,
+            Interrupt::from_payload(Some(data as u64)),
+            Interrupt::MsiX(vec)
+        );
+    }
+}
+
+fn legacy_payload(t: &mut dyn WvTester) {
+    // legacy interrupts are never mistaken for MSI-X vectors, regardless of their payload
+    wv_assert_eq!(t, Interrupt::from_payload(None), Interrupt::Legacy);
+    wv_assert_eq!(t, Interrupt::from_payload(Some(0)), Interrupt::Legacy);
+    wv_assert_eq!(t, Interrupt::from_payload(Some(1)), Interrupt::Legacy);
+    wv_assert_eq!(t, Interrupt::from_payload(Some(0xFFFF)), Interrupt::Legacy);
+
+    // the tag has to match exactly
+    let data = Interrupt::msix_data(3) as u64;
+    wv_assert_eq!(
+        t,
+        Interrupt::from_payload(Some(data ^ 0x1_0000)),
+        Interrupt::Legacy
+    );
+    wv_assert_eq!(
+        t,
+        Interrupt::from_payload(Some(data | (1 << 32))),
+        Interrupt::Legacy
+    );
+}
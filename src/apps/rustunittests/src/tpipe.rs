@@ -17,15 +17,18 @@
  */
 
 use m3::client::Pipes;
-use m3::col::String;
+use m3::col::{String, Vec};
 use m3::com::MemGate;
-use m3::errors::Code;
+use m3::errors::{Code, Error};
 use m3::io::{self, Read, Write};
 use m3::kif;
 use m3::test::{DefaultWvTester, WvTester};
 use m3::tiles::{ActivityArgs, ChildActivity, RunningActivity, Tile};
-use m3::vfs::{BufReader, IndirectPipe};
-use m3::{println, wv_assert_eq, wv_assert_ok, wv_run_test};
+use m3::vfs::{BufReader, File, FileEvent, FileWaiter, IndirectPipe};
+use m3::{println, wv_assert, wv_assert_eq, wv_assert_ok, wv_run_test};
+
+const RECORD_SIZE: usize = 100;
+const RECORD_COUNT: usize = 50;
 
 pub fn run(t: &mut dyn WvTester) {
     wv_run_test!(t, child_to_parent);
@@ -34,6 +37,8 @@ pub fn run(t: &mut dyn WvTester) {
     wv_run_test!(t, exec_child_to_child);
     wv_run_test!(t, writer_quit);
     wv_run_test!(t, reader_quit);
+    wv_run_test!(t, multiple_writers);
+    wv_run_test!(t, wait_for_eof);
 }
 
 fn child_to_parent(t: &mut dyn WvTester) {
@@ -215,3 +220,78 @@ fn reader_quit(t: &mut dyn WvTester) {
 
     wv_assert_eq!(t, act.wait(), Ok(Code::Success));
 }
+
+fn write_records(c: u8) -> Result<(), Error> {
+    let record = [c; RECORD_SIZE];
+    // write to the file directly to prevent that the buffering splits the records
+    let mut out = io::stdout();
+    for _ in 0..RECORD_COUNT {
+        out.get_mut().write_all(&record)?;
+    }
+    Ok(())
+}
+
+fn multiple_writers(t: &mut dyn WvTester) {
+    let pipeserv = wv_assert_ok!(Pipes::new("pipes"));
+    let pipe_mem = wv_assert_ok!(MemGate::new(0x10000, kif::Perm::RW));
+    let pipe = wv_assert_ok!(IndirectPipe::new(&pipeserv, pipe_mem));
+
+    let tile1 = wv_assert_ok!(Tile::get("compat|own"));
+    let tile2 = wv_assert_ok!(Tile::get("compat|own"));
+    let mut writer1 = wv_assert_ok!(ChildActivity::new_with(tile1, ActivityArgs::new("writer1")));
+    let mut writer2 = wv_assert_ok!(ChildActivity::new_with(tile2, ActivityArgs::new("writer2")));
+    writer1.add_file(io::STDOUT_FILENO, pipe.writer().unwrap().fd());
+    writer2.add_file(io::STDOUT_FILENO, pipe.writer().unwrap().fd());
+
+    let act1 = wv_assert_ok!(writer1.run(|| write_records(b'a')));
+    let act2 = wv_assert_ok!(writer2.run(|| write_records(b'b')));
+
+    pipe.close_writer();
+
+    let mut input = pipe.reader().unwrap();
+    let mut data = Vec::new();
+    wv_assert_eq!(
+        t,
+        input.read_to_end(&mut data),
+        Ok(2 * RECORD_COUNT * RECORD_SIZE)
+    );
+
+    // the records of both writers may be arbitrarily ordered, but never interleaved
+    for rec in data.chunks(RECORD_SIZE) {
+        wv_assert!(t, rec.iter().all(|b| *b == rec[0]));
+    }
+
+    pipe.close_reader();
+
+    wv_assert_eq!(t, act1.wait(), Ok(Code::Success));
+    wv_assert_eq!(t, act2.wait(), Ok(Code::Success));
+}
+
+fn wait_for_eof(t: &mut dyn WvTester) {
+    let pipeserv = wv_assert_ok!(Pipes::new("pipes"));
+    let pipe_mem = wv_assert_ok!(MemGate::new(0x10000, kif::Perm::RW));
+    let pipe = wv_assert_ok!(IndirectPipe::new(&pipeserv, pipe_mem));
+
+    let mut input = pipe.reader().unwrap();
+    wv_assert_ok!(input.set_blocking(false));
+
+    let mut output = pipe.writer().unwrap();
+    wv_assert_eq!(t, output.write(b"test"), Ok(4));
+    wv_assert_ok!(output.flush());
+    pipe.close_writer();
+
+    let mut waiter = FileWaiter::default();
+    waiter.add(input.fd(), FileEvent::INPUT);
+
+    // first, we should get the data
+    waiter.wait();
+    let mut buf = [0u8; 8];
+    wv_assert_eq!(t, input.read(&mut buf), Ok(4));
+    wv_assert_eq!(t, &buf[0..4], b"test");
+
+    // afterwards, the reader has to be woken up again to notice the EOF
+    waiter.wait();
+    wv_assert_eq!(t, input.read(&mut buf), Ok(0));
+
+    pipe.close_reader();
+}
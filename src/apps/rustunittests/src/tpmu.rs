@@ -0,0 +1,117 @@
+/*
+ * Copyright (C) 2020-2022 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use m3::errors::Code;
+use m3::test::WvTester;
+use m3::time::{PmuInstant, Profiler, TimeDuration, TimeInstant};
+use m3::tmif::{self, PmuCounter};
+use m3::{println, wv_assert, wv_assert_eq, wv_assert_err, wv_assert_ok, wv_run_test};
+
+pub fn run(t: &mut dyn WvTester) {
+    wv_run_test!(t, config);
+    wv_run_test!(t, counting);
+    wv_run_test!(t, profiler);
+}
+
+fn supported() -> bool {
+    match tmif::pmu_config(&[PmuCounter::Cycles]) {
+        Err(e) if e.code() == Code::NotSup => {
+            println!("No performance counters; skipping test");
+            false
+        },
+        res => {
+            tmif::pmu_config(&[]).ok();
+            res.is_ok()
+        },
+    }
+}
+
+fn config(t: &mut dyn WvTester) {
+    if !supported() {
+        return;
+    }
+
+    wv_assert_ok!(tmif::pmu_config(&[PmuCounter::Cycles]));
+    let vals = wv_assert_ok!(tmif::pmu_read());
+    wv_assert!(t, vals.get(PmuCounter::Cycles).is_some());
+    wv_assert_eq!(t, vals.get(PmuCounter::CacheMisses), None);
+
+    // all counters disabled
+    wv_assert_ok!(tmif::pmu_config(&[]));
+    let vals = wv_assert_ok!(tmif::pmu_read());
+    wv_assert_eq!(t, vals.enabled, 0);
+    wv_assert_eq!(t, vals.get(PmuCounter::Cycles), None);
+
+    // unsupported counters are refused without changing the configuration
+    if tmif::pmu_config(&[PmuCounter::CacheMisses]).is_err() {
+        wv_assert_err!(
+            t,
+            tmif::pmu_config(&[PmuCounter::Cycles, PmuCounter::CacheMisses]),
+            Code::NotSup
+        );
+        let vals = wv_assert_ok!(tmif::pmu_read());
+        wv_assert_eq!(t, vals.enabled, 0);
+    }
+}
+
+fn counting(t: &mut dyn WvTester) {
+    if !supported() {
+        return;
+    }
+
+    wv_assert!(t, PmuInstant::enable());
+
+    let start = PmuInstant::now();
+    // yield in between to check that the counters survive context switches
+    let end_time = TimeInstant::now() + TimeDuration::from_micros(100);
+    while TimeInstant::now() < end_time {
+        wv_assert_ok!(tmif::switch_activity());
+    }
+    let end = PmuInstant::now();
+
+    let diff = end.duration_since(start);
+    wv_assert!(t, !diff.is_empty());
+    wv_assert!(t, diff.get(PmuCounter::Cycles).unwrap() > 0);
+    if let Some(instrs) = diff.get(PmuCounter::Instrs) {
+        wv_assert!(t, instrs > 0);
+    }
+
+    // the counters restart at zero after reconfiguration
+    wv_assert!(t, PmuInstant::enable());
+    let restarted = PmuInstant::now();
+    wv_assert!(
+        t,
+        restarted.get(PmuCounter::Cycles).unwrap() < end.get(PmuCounter::Cycles).unwrap()
+    );
+
+    PmuInstant::disable();
+    wv_assert!(t, PmuInstant::now().duration_since(start).is_empty());
+}
+
+fn profiler(t: &mut dyn WvTester) {
+    if !supported() {
+        return;
+    }
+
+    let prof = Profiler::default().repeats(4).warmup(1).pmu(true);
+    let res = prof.run::<TimeInstant, _>(|| {
+        tmif::noop().ok();
+    });
+    wv_assert_eq!(t, res.runs(), 4);
+    wv_assert!(t, res.pmu_avg().get(PmuCounter::Cycles).is_some());
+    println!("noop: {}", res);
+
+    PmuInstant::disable();
+}
@@ -0,0 +1,79 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use m3::client::Pty;
+use m3::io::{Read, Write};
+use m3::test::WvTester;
+use m3::vfs::{File, LocalFlags, WinSize};
+use m3::{wv_assert_eq, wv_assert_ok, wv_run_test};
+
+pub fn run(t: &mut dyn WvTester) {
+    wv_run_test!(t, cooked);
+    wv_run_test!(t, raw);
+    wv_run_test!(t, winsize);
+}
+
+fn cooked(t: &mut dyn WvTester) {
+    let pty = wv_assert_ok!(Pty::new("pty"));
+    let (mut master, mut slave) = wv_assert_ok!(pty.create_pair());
+
+    // the erase character removes the last character of the current line
+    wv_assert_ok!(master.write_all(b"abx\x7fc\r"));
+    wv_assert_ok!(master.flush());
+
+    let mut buf = [0u8; 16];
+    wv_assert_eq!(t, slave.read(&mut buf), Ok(4));
+    wv_assert_eq!(t, &buf[0..4], b"abc\n");
+
+    // the echo contains the erase sequence and the translated newline
+    wv_assert_eq!(t, master.read(&mut buf), Ok(9));
+    wv_assert_eq!(t, &buf[0..9], b"abx\x08 \x08c\r\n");
+
+    // output of the slave gets translated as well
+    wv_assert_ok!(slave.write_all(b"hi\n"));
+    wv_assert_ok!(slave.flush());
+    wv_assert_eq!(t, master.read(&mut buf), Ok(4));
+    wv_assert_eq!(t, &buf[0..4], b"hi\r\n");
+}
+
+fn raw(t: &mut dyn WvTester) {
+    let pty = wv_assert_ok!(Pty::new("pty"));
+    let (mut master, mut slave) = wv_assert_ok!(pty.create_pair());
+
+    let mut termios = wv_assert_ok!(slave.get_termios());
+    termios.lflags.remove(LocalFlags::ICANON | LocalFlags::ECHO);
+    wv_assert_ok!(slave.set_termios(&termios));
+    wv_assert_eq!(t, master.get_termios(), Ok(termios));
+
+    // without canonical mode, characters are passed on immediately and without echo
+    wv_assert_ok!(master.write_all(b"x"));
+    wv_assert_ok!(master.flush());
+
+    let mut buf = [0u8; 16];
+    wv_assert_eq!(t, slave.read(&mut buf), Ok(1));
+    wv_assert_eq!(t, buf[0], b'x');
+}
+
+fn winsize(t: &mut dyn WvTester) {
+    let pty = wv_assert_ok!(Pty::new("pty"));
+    let (mut master, slave) = wv_assert_ok!(pty.create_pair());
+
+    let size = WinSize {
+        rows: 40,
+        cols: 120,
+    };
+    wv_assert_ok!(master.set_winsize(size));
+    wv_assert_eq!(t, slave.get_winsize(), Ok(size));
+}
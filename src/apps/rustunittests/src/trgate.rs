@@ -24,6 +24,7 @@ use m3::{wv_assert_err, wv_run_test};
 pub fn run(t: &mut dyn WvTester) {
     wv_run_test!(t, create);
     wv_run_test!(t, destroy);
+    wv_run_test!(t, gate_set);
 }
 
 fn create(t: &mut dyn WvTester) {
@@ -93,3 +94,44 @@ fn destroy(t: &mut dyn WvTester) {
 
     wv_assert_eq!(t, act.wait(), Ok(Code::Success));
 }
+
+fn gate_set(t: &mut dyn WvTester) {
+    use m3::com::{GateSet, SGateArgs, SendGate};
+    use m3::time::TimeDuration;
+    use m3::{send_vmsg, wv_assert_eq, wv_assert_ok};
+
+    let rg1 = wv_assert_ok!(RecvGate::new_with(
+        RGateArgs::default().order(6).msg_order(6)
+    ));
+    let rg2 = wv_assert_ok!(RecvGate::new_with(
+        RGateArgs::default().order(6).msg_order(6)
+    ));
+    let sg1 = wv_assert_ok!(SendGate::new_with(SGateArgs::new(&rg1).credits(2)));
+    let sg2 = wv_assert_ok!(SendGate::new_with(SGateArgs::new(&rg2).credits(2)));
+
+    let mut set = GateSet::new();
+    wv_assert_err!(t, set.wait(), Code::InvArgs);
+    wv_assert_eq!(t, set.add(&rg1), 0);
+    wv_assert_eq!(t, set.add(&rg2), 1);
+
+    // nothing to receive yet
+    wv_assert_eq!(t, set.ready(), None);
+    wv_assert_err!(t, set.wait_for(TimeDuration::from_millis(1)), Code::Timeout);
+
+    wv_assert_ok!(send_vmsg!(&sg2, RecvGate::def(), 2));
+    wv_assert_eq!(t, set.wait(), Ok(1));
+    let msg = wv_assert_ok!(rg2.fetch());
+    wv_assert_ok!(rg2.ack_msg(msg));
+
+    // both gates are ready; they are reported in turns
+    wv_assert_ok!(send_vmsg!(&sg1, RecvGate::def(), 1));
+    wv_assert_ok!(send_vmsg!(&sg2, RecvGate::def(), 2));
+    wv_assert_eq!(t, set.wait(), Ok(0));
+    wv_assert_eq!(t, set.wait(), Ok(1));
+
+    for rg in [&rg1, &rg2] {
+        let msg = wv_assert_ok!(rg.fetch());
+        wv_assert_ok!(rg.ack_msg(msg));
+    }
+    wv_assert_eq!(t, set.ready(), None);
+}
@@ -14,15 +14,22 @@
  */
 
 use m3::cap::Selector;
+use m3::col::Vec;
 use m3::com::Semaphore;
+use m3::errors::Code;
 use m3::io::{Read, Write};
+use m3::kif::{syscalls, CapRngDesc, CapType};
 use m3::test::{DefaultWvTester, WvTester};
 use m3::tiles::{Activity, ChildActivity, RunningActivity, Tile};
+use m3::time::{TimeDuration, TimeInstant};
 use m3::vfs::{OpenFlags, VFS};
-use m3::{wv_assert_eq, wv_assert_ok, wv_run_test};
+use m3::{wv_assert, wv_assert_eq, wv_assert_err, wv_assert_ok, wv_run_test};
 
 pub fn run(t: &mut dyn WvTester) {
     wv_run_test!(t, taking_turns);
+    wv_run_test!(t, counting);
+    wv_run_test!(t, timed_wait);
+    wv_run_test!(t, bulk_delegate);
 }
 
 fn get_counter(filename: &str) -> u32 {
@@ -84,3 +91,89 @@ fn taking_turns(t: &mut dyn WvTester) {
 
     wv_assert_ok!(act.wait());
 }
+
+fn counting(t: &mut dyn WvTester) {
+    let sem = wv_assert_ok!(Semaphore::create(2));
+    wv_assert_eq!(t, sem.value(), Ok(2));
+
+    wv_assert_eq!(t, sem.try_down(), Ok(true));
+    wv_assert_eq!(t, sem.try_down(), Ok(true));
+    wv_assert_eq!(t, sem.try_down(), Ok(false));
+    wv_assert_eq!(t, sem.value(), Ok(0));
+
+    wv_assert_ok!(sem.up());
+    wv_assert_ok!(sem.up());
+    wv_assert_ok!(sem.up());
+    wv_assert_eq!(t, sem.value(), Ok(3));
+    wv_assert_ok!(sem.down());
+    wv_assert_eq!(t, sem.value(), Ok(2));
+}
+
+fn timed_wait(t: &mut dyn WvTester) {
+    let sem = wv_assert_ok!(Semaphore::create(1));
+
+    // available values are taken immediately
+    wv_assert_ok!(sem.down_for(TimeDuration::from_millis(1)));
+    wv_assert_err!(t, sem.down_for(TimeDuration::ZERO), Code::Timeout);
+
+    let timeout = TimeDuration::from_millis(2);
+    let start = TimeInstant::now();
+    wv_assert_err!(t, sem.down_for(timeout), Code::Timeout);
+    wv_assert!(t, start.elapsed() >= timeout);
+    wv_assert_eq!(t, sem.value(), Ok(0));
+
+    // an up by another activity ends the wait before the timeout
+    let tile = wv_assert_ok!(Tile::get("compat|own"));
+    let mut child = wv_assert_ok!(ChildActivity::new(tile, "child"));
+    wv_assert_ok!(child.delegate_obj(sem.sel()));
+
+    let mut dst = child.data_sink();
+    dst.push(sem.sel());
+
+    let act = wv_assert_ok!(child.run(|| {
+        let mut src = Activity::own().data_source();
+        let sem = Semaphore::bind(src.pop().unwrap());
+        wv_assert_ok!(sem.up());
+        Ok(())
+    }));
+
+    wv_assert_ok!(sem.down_for(TimeDuration::from_secs(10)));
+    wv_assert_ok!(act.wait());
+}
+
+fn bulk_delegate(_t: &mut dyn WvTester) {
+    // use more semaphores than fit into a single exchange syscall
+    let count = syscalls::MAX_EXCHG_DESCS + 4;
+    let sems = (0..count)
+        .map(|_| wv_assert_ok!(Semaphore::create(0)))
+        .collect::<Vec<_>>();
+
+    let tile = wv_assert_ok!(Tile::get("compat|own"));
+    let mut child = wv_assert_ok!(ChildActivity::new(tile, "child"));
+    let descs = sems
+        .iter()
+        .map(|s| syscalls::ExchangeDesc::new(CapRngDesc::new(CapType::Object, s.sel(), 1), s.sel()))
+        .collect::<Vec<_>>();
+    wv_assert_ok!(child.delegate_bulk(&descs));
+
+    let mut dst = child.data_sink();
+    dst.push(count);
+    for s in &sems {
+        dst.push(s.sel());
+    }
+
+    let act = wv_assert_ok!(child.run(|| {
+        let mut src = Activity::own().data_source();
+        let count: usize = src.pop().unwrap();
+        for _ in 0..count {
+            let sem = Semaphore::bind(src.pop().unwrap());
+            wv_assert_ok!(sem.up());
+        }
+        Ok(())
+    }));
+
+    for s in &sems {
+        wv_assert_ok!(s.down());
+    }
+    wv_assert_ok!(act.wait());
+}
@@ -28,17 +28,45 @@ use m3::kif::{self, CapRngDesc, CapType};
 use m3::mem::MsgBuf;
 use m3::server::{
     server_loop, CapExchange, ClientManager, ExcType, RequestHandler, RequestSession, Server,
-    ServerSession, SessId,
+    ServerSession, SessId, WaitPolicy,
 };
 use m3::syscalls;
 use m3::test::{DefaultWvTester, WvTester};
 use m3::tiles::{Activity, ActivityArgs, ChildActivity, OwnActivity, RunningActivity, Tile};
+use m3::time::{TimeDuration, TimeInstant};
 use m3::{send_vmsg, wv_assert_eq, wv_assert_err, wv_assert_ok, wv_run_test};
 
 pub fn run(t: &mut dyn WvTester) {
     wv_run_test!(t, testnoresp);
     wv_run_test!(t, testcliexit);
     wv_run_test!(t, testcaps);
+    wv_run_test!(t, wait_policy);
+}
+
+fn wait_policy(t: &mut dyn WvTester) {
+    // without spinning, the poll function is never called
+    let mut calls = 0;
+    let res = WaitPolicy::sleep().spin(|| {
+        calls += 1;
+        Some(())
+    });
+    wv_assert_eq!(t, res, None);
+    wv_assert_eq!(t, calls, 0);
+
+    // spinning stops as soon as the poll function reports success
+    let mut calls = 0;
+    let res = WaitPolicy::spin_then_sleep(TimeDuration::from_secs(1)).spin(|| {
+        calls += 1;
+        (calls == 3).then_some(calls)
+    });
+    wv_assert_eq!(t, res, Some(3));
+
+    // or after the spin time has passed
+    let spin = TimeDuration::from_micros(100);
+    let start = TimeInstant::now();
+    let res = WaitPolicy::spin_then_sleep(spin).spin(|| None::<()>);
+    wv_assert_eq!(t, res, None);
+    wv_assert_eq!(t, TimeInstant::now().duration_since(start) >= spin, true);
 }
 
 struct CrashSession {
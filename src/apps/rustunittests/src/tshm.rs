@@ -0,0 +1,100 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use m3::cfg;
+use m3::client::Shm;
+use m3::com::Perm;
+use m3::errors::Code;
+use m3::mem::GlobOff;
+use m3::test::WvTester;
+use m3::{wv_assert_eq, wv_assert_err, wv_assert_ok, wv_run_test};
+
+pub fn run(t: &mut dyn WvTester) {
+    wv_run_test!(t, share);
+    wv_run_test!(t, names);
+    wv_run_test!(t, quota);
+    wv_run_test!(t, remove);
+}
+
+const PAGE: GlobOff = cfg::PAGE_SIZE as GlobOff;
+
+fn share(t: &mut dyn WvTester) {
+    let prod = wv_assert_ok!(Shm::new("shm"));
+    let cons = wv_assert_ok!(Shm::new("shm-clone"));
+
+    let wmem = wv_assert_ok!(prod.create("share", 2 * PAGE, Perm::RW));
+    wv_assert_ok!(wmem.write_obj(&0xDEAD_BEEFu64, PAGE));
+
+    // the other session can open the object by name with fewer permissions
+    let rmem = wv_assert_ok!(cons.open("share", Perm::R));
+    wv_assert_eq!(t, wv_assert_ok!(rmem.region()).1, 2 * PAGE);
+    wv_assert_eq!(t, rmem.read_obj::<u64>(PAGE), Ok(0xDEAD_BEEF));
+    wv_assert_err!(t, rmem.write_obj(&0u64, 0), Code::NoPerm);
+
+    wv_assert_ok!(prod.remove("share"));
+}
+
+fn names(t: &mut dyn WvTester) {
+    let sess = wv_assert_ok!(Shm::new("shm"));
+
+    wv_assert_err!(t, sess.open("nonexisting", Perm::R), Code::NotFound);
+    wv_assert_err!(t, sess.create("", PAGE, Perm::RW), Code::InvArgs);
+    wv_assert_err!(
+        t,
+        sess.create("a-very-long-name-that-exceeds-the-limit", PAGE, Perm::RW),
+        Code::InvArgs
+    );
+    wv_assert_err!(t, sess.create("empty", 0, Perm::RW), Code::InvArgs);
+    wv_assert_err!(t, sess.create("noperm", PAGE, Perm::empty()), Code::InvArgs);
+
+    let _mem = wv_assert_ok!(sess.create("name", PAGE, Perm::RW));
+    wv_assert_err!(t, sess.create("name", PAGE, Perm::RW), Code::Exists);
+    wv_assert_ok!(sess.remove("name"));
+    wv_assert_err!(t, sess.remove("name"), Code::NotFound);
+}
+
+fn quota(t: &mut dyn WvTester) {
+    let sess = wv_assert_ok!(Shm::new("shm-clone"));
+    let (total, used) = wv_assert_ok!(sess.quota());
+    wv_assert_eq!(t, total, 16 * 1024);
+    wv_assert_eq!(t, used, 0);
+
+    // sizes are rounded up to pages
+    let _mem = wv_assert_ok!(sess.create("quota1", 1, Perm::RW));
+    wv_assert_eq!(t, sess.quota(), Ok((total, PAGE)));
+
+    wv_assert_err!(t, sess.create("quota2", total, Perm::RW), Code::NoSpace);
+    wv_assert_eq!(t, sess.quota(), Ok((total, PAGE)));
+
+    wv_assert_ok!(sess.remove("quota1"));
+    wv_assert_eq!(t, sess.quota(), Ok((total, 0)));
+}
+
+fn remove(t: &mut dyn WvTester) {
+    let owner = wv_assert_ok!(Shm::new("shm"));
+    let other = wv_assert_ok!(Shm::new("shm-clone"));
+
+    let _mem = wv_assert_ok!(owner.create("remove", PAGE, Perm::RW));
+    let omem = wv_assert_ok!(other.open("remove", Perm::RW));
+    wv_assert_ok!(omem.write_obj(&1u64, 0));
+
+    // only the creator can remove the object
+    wv_assert_err!(t, other.remove("remove"), Code::NoPerm);
+    wv_assert_ok!(owner.remove("remove"));
+
+    // afterwards, the object is gone and all memory gates are invalid
+    wv_assert_err!(t, other.open("remove", Perm::R), Code::NotFound);
+    wv_assert_err!(t, omem.write_obj(&1u64, 0), Code::NoMEP);
+}
@@ -0,0 +1,127 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! Tests the backpressure of the kernel's service queues. These tests expect a kernel that has
+//! been started with `-q:len 1 -q:err` (see boot/rust-unittests-squeue.xml).
+
+use m3::cap::Selector;
+use m3::client::ClientSession;
+use m3::col::Vec;
+use m3::errors::{Code, Error};
+use m3::server::{
+    CapExchange, ClientManager, ExcType, RequestHandler, RequestSession, Server, ServerSession,
+    SessId,
+};
+use m3::syscalls;
+use m3::test::WvTester;
+use m3::tiles::{Activity, ActivityArgs, ChildActivity, OwnActivity, RunningActivity, Tile};
+use m3::{wv_assert, wv_assert_eq, wv_assert_ok, wv_run_test};
+
+use crate::tserver::open_sess;
+
+const CLIENTS: usize = 3;
+
+pub fn run(t: &mut dyn WvTester) {
+    wv_run_test!(t, full_queue);
+}
+
+struct HangSession {
+    _serv: ServerSession,
+}
+
+impl RequestSession for HangSession {
+    fn new(_serv: ServerSession, _arg: &str) -> Result<Self, Error>
+    where
+        Self: Sized,
+    {
+        Ok(Self { _serv })
+    }
+}
+
+impl HangSession {
+    fn hang(
+        _cli: &mut ClientManager<Self>,
+        _crt: usize,
+        _sid: SessId,
+        _xchg: &mut CapExchange<'_>,
+    ) -> Result<(), Error> {
+        // never respond so that all further requests stay in the kernel's queue
+        loop {
+            OwnActivity::sleep().ok();
+        }
+    }
+}
+
+fn server_main() -> Result<(), Error> {
+    let mut hdl = wv_assert_ok!(RequestHandler::new());
+    let mut srv = wv_assert_ok!(Server::new("test", &mut hdl));
+
+    hdl.reg_cap_handler(0usize, ExcType::Obt(1), HangSession::hang);
+
+    wv_assert_ok!(hdl.run(&mut srv));
+
+    Ok(())
+}
+
+fn client_main() -> Result<(), Error> {
+    let mut src = Activity::own().data_source();
+    let sess_sel: Selector = src.pop().unwrap();
+
+    let sess = ClientSession::new_bind(sess_sel);
+    sess.obtain(1, |is| is.push(0), |_| Ok(())).map(|_| ())
+}
+
+fn full_queue(t: &mut dyn WvTester) {
+    let server_tile = wv_assert_ok!(Tile::get("compat|own"));
+    let serv = wv_assert_ok!(ChildActivity::new_with(
+        server_tile,
+        ActivityArgs::new("server")
+    ));
+    let sact = wv_assert_ok!(serv.run(server_main));
+
+    // the session is opened before the server hangs and shared with all clients
+    let sess = open_sess("test");
+
+    let mut clients = Vec::new();
+    for _ in 0..CLIENTS {
+        let tile = wv_assert_ok!(Tile::get("compat|own"));
+        let mut client = wv_assert_ok!(ChildActivity::new_with(tile, ActivityArgs::new("client")));
+        wv_assert_ok!(client.delegate_obj(sess.sel()));
+
+        let mut dst = client.data_sink();
+        dst.push(sess.sel());
+
+        clients.push(wv_assert_ok!(client.run(client_main)));
+    }
+
+    // the first request is in flight and the second one is queued. since the queue is limited to
+    // one message, the third request (whichever client sends it) fails immediately.
+    let sels = clients
+        .iter()
+        .map(|c| c.activity().sel())
+        .collect::<Vec<_>>();
+    let (sel, code) = wv_assert_ok!(syscalls::activity_wait(&sels, 0));
+    wv_assert_eq!(t, code, Code::NoSpace);
+    clients.retain(|c| c.activity().sel() != sel);
+    wv_assert_eq!(t, clients.len(), CLIENTS - 1);
+
+    // destroy the server to let the other requests fail as well
+    drop(sact);
+
+    for c in clients {
+        let code = wv_assert_ok!(c.wait());
+        wv_assert!(t, code != Code::Success && code != Code::NoSpace);
+    }
+}
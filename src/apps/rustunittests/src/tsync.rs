@@ -0,0 +1,163 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use core::sync::atomic::AtomicU32;
+
+use m3::cap::Selector;
+use m3::cell::SpinLock;
+use m3::cfg;
+use m3::com::MemGate;
+use m3::errors::{Code, Error};
+use m3::kif::Perm;
+use m3::mem::{GlobOff, VirtAddr};
+use m3::sync::{Condvar, Mutex};
+use m3::test::WvTester;
+use m3::tiles::{Activity, ActivityArgs, ChildActivity, RunningActivity, Tile};
+use m3::time::TimeDuration;
+use m3::tmif;
+use m3::{println, wv_assert, wv_assert_eq, wv_assert_err, wv_assert_ok, wv_run_test};
+
+pub fn run(t: &mut dyn WvTester) {
+    wv_run_test!(t, futex);
+    wv_run_test!(t, mutex);
+    wv_run_test!(t, condvar_timeout);
+    wv_run_test!(t, shared_mutex);
+    wv_run_test!(t, spinlock);
+}
+
+fn futex(t: &mut dyn WvTester) {
+    let word = AtomicU32::new(1);
+    let addr = VirtAddr::from(word.as_ptr() as *const u32);
+
+    // the value differs, so that we don't block
+    wv_assert_err!(t, tmif::futex_wait(addr, 0, None), Code::WouldBlock);
+    // nobody wakes us up, so that we run into the timeout
+    wv_assert_err!(
+        t,
+        tmif::futex_wait(addr, 1, Some(TimeDuration::from_micros(100))),
+        Code::Timeout
+    );
+    // waking up without waiters is fine
+    wv_assert_ok!(tmif::futex_wake(addr, 1));
+
+    // unaligned words are not supported
+    let unaligned = VirtAddr::from(word.as_ptr() as usize + 1);
+    wv_assert_err!(t, tmif::futex_wait(unaligned, 1, None), Code::InvArgs);
+}
+
+fn mutex(t: &mut dyn WvTester) {
+    let mut m = Mutex::new(1);
+
+    {
+        let mut guard = m.lock();
+        *guard += 1;
+        wv_assert!(t, m.is_locked());
+        wv_assert!(t, m.try_lock().is_none());
+    }
+
+    wv_assert!(t, !m.is_locked());
+    wv_assert_eq!(t, m.try_lock().map(|g| *g), Some(2));
+    *m.get_mut() = 3;
+    wv_assert_eq!(t, m.into_inner(), 3);
+}
+
+fn condvar_timeout(t: &mut dyn WvTester) {
+    let m = Mutex::new(false);
+    let cv = Condvar::new();
+
+    let guard = m.lock();
+    let (guard, timed_out) = cv.wait_timeout(guard, TimeDuration::from_micros(100));
+    wv_assert!(t, timed_out);
+    wv_assert!(t, !*guard);
+    drop(guard);
+
+    // notifying without waiters has no effect
+    cv.notify_one();
+    cv.notify_all();
+    wv_assert!(t, !m.is_locked());
+}
+
+fn shared_mutex(t: &mut dyn WvTester) {
+    const PARENT_VIRT: VirtAddr = VirtAddr::new(0x3200_0000);
+    const CHILD_VIRT: VirtAddr = VirtAddr::new(0x3300_0000);
+    const ITERATIONS: u64 = 1000;
+
+    let pager = match Activity::own().pager() {
+        Some(pager) => pager,
+        None => {
+            println!("Skipping shared mutex test without pager");
+            return;
+        },
+    };
+    // the futex-based primitives only work between activities on the same tile
+    let tile = match Tile::get("own") {
+        Ok(tile) => tile,
+        Err(_) => {
+            println!("Skipping shared mutex test without multiplexed tile");
+            return;
+        },
+    };
+
+    let mem = wv_assert_ok!(MemGate::new(cfg::PAGE_SIZE as GlobOff, Perm::RW));
+    wv_assert_ok!(pager.map_mem(PARENT_VIRT, mem.sel(), cfg::PAGE_SIZE, Perm::RW));
+    let counter = unsafe {
+        let ptr = PARENT_VIRT.as_mut_ptr::<Mutex<u64>>();
+        ptr.write(Mutex::new(0));
+        &*ptr
+    };
+
+    let mut act = wv_assert_ok!(ChildActivity::new_with(tile, ActivityArgs::new("sync")));
+    wv_assert_ok!(act.delegate_obj(mem.sel()));
+    let mut dst = act.data_sink();
+    dst.push(mem.sel());
+
+    // the child maps the same memory at a different address
+    let act = wv_assert_ok!(act.run(|| {
+        let mut src = Activity::own().data_source();
+        let mem_sel: Selector = src.pop()?;
+        let pager = Activity::own()
+            .pager()
+            .ok_or_else(|| Error::new(Code::NotSup))?;
+        pager.map_mem(CHILD_VIRT, mem_sel, cfg::PAGE_SIZE, Perm::RW)?;
+
+        let counter = unsafe { &*CHILD_VIRT.as_ptr::<Mutex<u64>>() };
+        for _ in 0..ITERATIONS {
+            *counter.lock() += 1;
+        }
+        Ok(())
+    }));
+
+    for _ in 0..ITERATIONS {
+        *counter.lock() += 1;
+    }
+
+    wv_assert_eq!(t, act.wait(), Ok(Code::Success));
+    wv_assert_eq!(t, *counter.lock(), 2 * ITERATIONS);
+
+    wv_assert_ok!(pager.unmap(PARENT_VIRT));
+}
+
+fn spinlock(t: &mut dyn WvTester) {
+    let lock = SpinLock::new(1);
+    wv_assert!(t, !lock.is_locked());
+    {
+        let mut guard = lock.lock();
+        *guard += 1;
+        wv_assert!(t, lock.is_locked());
+        wv_assert!(t, lock.try_lock().is_none());
+    }
+    wv_assert!(t, !lock.is_locked());
+    wv_assert_eq!(t, lock.try_lock().map(|g| *g), Some(2));
+}
@@ -19,18 +19,21 @@
 use m3::cap::{SelSpace, Selector};
 use m3::cfg::{self, PAGE_SIZE};
 use m3::client::M3FS;
-use m3::com::{EpMng, GateCap, MemCap, MemGate, RecvCap, RecvGate, SendCap};
+use m3::com::{EpMng, GateCap, MemCap, MemGate, RecvCap, RecvGate, Semaphore, SendCap};
 use m3::cpu::{CPUOps, CPU};
 use m3::errors::{Code, Error};
-use m3::kif::syscalls::{ActivityOp, SemOp};
+use m3::kif::syscalls::{
+    ActivityOp, MGateRegion, MGateRegionReply, MuxType, Noop, Operation, SemOp,
+};
 use m3::kif::{CapRngDesc, CapType, Perm, INVALID_SEL, SEL_ACT, SEL_KMEM, SEL_TILE};
 use m3::mem::{GlobOff, VirtAddr};
 use m3::server::{CapExchange, Handler, Server, ServerSession, SessId, SessionContainer};
 use m3::syscalls;
-use m3::tcu::{EpId, FIRST_USER_EP, INVALID_EP};
+use m3::tcu::{EpId, Message, FIRST_USER_EP, INVALID_EP};
 use m3::test::WvTester;
-use m3::tiles::{Activity, ActivityArgs, ChildActivity, Tile};
+use m3::tiles::{Activity, ActivityArgs, ChildActivity, RunningActivity, Tile};
 use m3::time::TimeDuration;
+use m3::tmif;
 use m3::util::math;
 use m3::{wv_assert, wv_assert_eq, wv_assert_err, wv_assert_ok, wv_run_test};
 
@@ -57,11 +60,14 @@ pub fn run(t: &mut dyn WvTester) {
     wv_run_test!(t, tile_quota);
     wv_run_test!(t, tile_set_quota);
     wv_run_test!(t, sem_ctrl);
+    wv_run_test!(t, get_stats);
 
     wv_run_test!(t, delegate);
     wv_run_test!(t, obtain);
     wv_run_test!(t, exchange);
     wv_run_test!(t, revoke);
+
+    wv_run_test!(t, async_calls);
 }
 
 fn create_srv(t: &mut dyn WvTester) {
@@ -725,19 +731,19 @@ fn derive_tile(t: &mut dyn WvTester) {
     // invalid dest selector
     wv_assert_err!(
         t,
-        syscalls::derive_tile(tile.sel(), SEL_ACT, Some(1), None, None),
+        syscalls::derive_tile(tile.sel(), SEL_ACT, Some(1), None, None, None),
         Code::InvArgs
     );
     // invalid ep count
     wv_assert_err!(
         t,
-        syscalls::derive_tile(tile.sel(), sel, Some(oquote_eps + 1), None, None),
+        syscalls::derive_tile(tile.sel(), sel, Some(oquote_eps + 1), None, None, None),
         Code::NoSpace
     );
     // invalid tile sel
     wv_assert_err!(
         t,
-        syscalls::derive_tile(SEL_ACT, sel, Some(1), None, None),
+        syscalls::derive_tile(SEL_ACT, sel, Some(1), None, None, None),
         Code::InvArgs
     );
 
@@ -754,26 +760,49 @@ fn derive_tile(t: &mut dyn WvTester) {
         wv_assert_eq!(t, nquota, oquote_eps);
     }
 
-    // transfer time
+    // restrict interrupts; a derived tile object cannot permit more than its parent
+    {
+        let tile2 = wv_assert_ok!(tile.derive_with_irqs(None, None, None, Some(0)));
+        wv_assert_err!(
+            t,
+            tile2
+                .derive_with_irqs(None, None, None, Some(1 << 2))
+                .map(|_| ()),
+            Code::NoPerm
+        );
+        wv_assert_ok!(tile2.derive_with_irqs(None, None, None, Some(0)));
+
+        // activities on the restricted tile object cannot claim interrupts
+        if wv_assert_ok!(tile2.mux_type()) == MuxType::TileMux {
+            let act = wv_assert_ok!(ChildActivity::new(tile2, "test"));
+            let act = wv_assert_ok!(act.run(|| tmif::claim_irq(2, FIRST_USER_EP)));
+            wv_assert_eq!(t, act.wait(), Ok(Code::NoPerm));
+        }
+    }
+
+    // share time; the derived quota is part of the budget of the parent
     if oquota.time().total().as_nanos() > 100 {
         {
             let tile2 = wv_assert_ok!(tile.derive(None, Some(TimeDuration::from_nanos(100)), None));
             let quota2 = wv_assert_ok!(tile2.quota()).time().total();
             let nquota = wv_assert_ok!(tile.quota()).time().total();
             wv_assert_eq!(t, quota2, TimeDuration::from_nanos(100));
-            wv_assert_eq!(
-                t,
-                nquota,
-                oquota.time().total() - TimeDuration::from_nanos(100)
-            );
+            wv_assert_eq!(t, nquota, oquota.time().total());
         }
         let nquota = wv_assert_ok!(tile.quota()).time().total();
         wv_assert_eq!(t, nquota, oquota.time().total());
     }
     else {
-        m3::println!("Skipping time transfer test due to insufficient time");
+        m3::println!("Skipping time sharing test due to insufficient time");
     }
 
+    // the derived quota cannot exceed the budget of the parent
+    wv_assert_err!(
+        t,
+        tile.derive(None, Some(oquota.time().total() * 2), None),
+        Code::NoSpace
+    );
+
     {
         let _act = wv_assert_ok!(ChildActivity::new(tile.clone(), "test"));
         // activity is still using the Tile
@@ -956,6 +985,25 @@ fn sem_ctrl(t: &mut dyn WvTester) {
     );
 }
 
+fn get_stats(t: &mut dyn WvTester) {
+    // invalid selector
+    wv_assert_err!(t, syscalls::get_stats(SEL_KMEM), Code::InvArgs);
+    wv_assert_err!(t, syscalls::get_stats(INVALID_SEL), Code::InvArgs);
+
+    let before = wv_assert_ok!(syscalls::get_stats(SEL_ACT));
+    wv_assert!(t, before.obj_caps > 0);
+    wv_assert!(t, before.syscalls >= before.act_syscalls);
+    wv_assert!(t, before.mem_free <= before.mem_total);
+    wv_assert!(t, before.mem_largest <= before.mem_free);
+
+    // new capabilities and system calls are accounted
+    let _sem = wv_assert_ok!(Semaphore::create(0));
+    let after = wv_assert_ok!(syscalls::get_stats(SEL_ACT));
+    wv_assert_eq!(t, after.obj_caps, before.obj_caps + 1);
+    wv_assert!(t, after.act_syscalls >= before.act_syscalls + 2);
+    wv_assert!(t, after.syscalls >= before.syscalls + 2);
+}
+
 fn activity_ctrl(t: &mut dyn WvTester) {
     wv_assert_err!(
         t,
@@ -1087,3 +1135,50 @@ fn revoke(t: &mut dyn WvTester) {
         Code::InvArgs
     );
 }
+
+fn wait_async_reply<F: FnOnce(u64, &'static Message)>(func: F) {
+    let mut func = Some(func);
+    while !syscalls::fetch_async_reply(|ev, msg| func.take().unwrap()(ev, msg)) {}
+}
+
+fn async_calls(t: &mut dyn WvTester) {
+    let ticket: syscalls::AsyncTicket<()> =
+        wv_assert_ok!(syscalls::send_async(Operation::Noop, &Noop {}, 1));
+    wv_assert_eq!(t, ticket.event(), 1);
+
+    // only one system call can be outstanding at a time
+    wv_assert_err!(t, syscalls::noop(), Code::NoCredits);
+    wv_assert_err!(
+        t,
+        syscalls::send_async::<_, ()>(Operation::Noop, &Noop {}, 2).map(|t| t.event()),
+        Code::NoCredits
+    );
+
+    let mut res = None;
+    wait_async_reply(|ev, msg| {
+        wv_assert_eq!(t, ev, 1);
+        res = Some(ticket.finish_result(Some(msg)));
+    });
+    wv_assert_ok!(res.unwrap());
+
+    // synchronous system calls work again
+    wv_assert_ok!(syscalls::noop());
+
+    // errors are reported on completion
+    let ticket: syscalls::AsyncTicket<MGateRegionReply> = wv_assert_ok!(syscalls::send_async(
+        Operation::MGateRegion,
+        &MGateRegion { mgate: SEL_ACT },
+        3
+    ));
+    let mut res = None;
+    wait_async_reply(|_, msg| {
+        res = Some(ticket.finish(Some(msg)).map(|r| r.size));
+    });
+    wv_assert_err!(t, res.unwrap(), Code::InvArgs);
+
+    // an aborted system call fails
+    let ticket: syscalls::AsyncTicket<()> =
+        wv_assert_ok!(syscalls::send_async(Operation::Noop, &Noop {}, 4));
+    wait_async_reply(|_, _| {});
+    wv_assert_err!(t, ticket.finish_result(None), Code::RecvGone);
+}
@@ -0,0 +1,59 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use m3::col::ToString;
+use m3::test::WvTester;
+use m3::time::{SystemTime, TimeDuration};
+use m3::{wv_assert, wv_assert_eq, wv_run_test};
+
+pub fn run(t: &mut dyn WvTester) {
+    wv_run_test!(t, datetime);
+    wv_run_test!(t, arith);
+}
+
+fn datetime(t: &mut dyn WvTester) {
+    wv_assert_eq!(
+        t,
+        SystemTime::UNIX_EPOCH.to_datetime(),
+        (1970, 1, 1, 0, 0, 0)
+    );
+    wv_assert_eq!(
+        t,
+        SystemTime::UNIX_EPOCH.to_string(),
+        "1970-01-01T00:00:00.000Z"
+    );
+
+    // leap day in a year divisible by 400
+    let leap = SystemTime::from_unix_secs(951_782_400);
+    wv_assert_eq!(t, leap.to_datetime(), (2000, 2, 29, 0, 0, 0));
+
+    let time = SystemTime::from_unix_secs(1_706_704_496) + TimeDuration::from_millis(789);
+    wv_assert_eq!(t, time.to_string(), "2024-01-31T12:34:56.789Z");
+    wv_assert_eq!(t, time.as_unix_secs(), 1_706_704_496);
+}
+
+fn arith(t: &mut dyn WvTester) {
+    let start = SystemTime::from_unix_secs(100);
+    let end = start + TimeDuration::from_secs(5);
+    wv_assert_eq!(t, end.as_unix_secs(), 105);
+    wv_assert_eq!(t, end.duration_since(start), TimeDuration::from_secs(5));
+    wv_assert_eq!(t, start.checked_duration_since(end), None);
+    wv_assert_eq!(t, start.duration_since(end), TimeDuration::ZERO);
+    wv_assert!(t, start < end);
+
+    let mut time = end;
+    time -= TimeDuration::from_secs(5);
+    wv_assert_eq!(t, time, start);
+}
@@ -0,0 +1,321 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use m3::cell::StaticRefCell;
+use m3::col::Vec;
+use m3::errors::Code;
+use m3::heap;
+use m3::mem::VirtAddr;
+use m3::test::WvTester;
+use m3::tiles::OwnActivity;
+use m3::time::{TimeDuration, TimeInstant};
+use m3::{vec, wv_assert, wv_assert_eq, wv_assert_err, wv_assert_ok, wv_run_test};
+
+use thread::{Event, Priority};
+
+// the priorities of the workers in the priorities test and the order in which they ran
+const PRIOS: [Priority; 4] = [0, thread::MAX_PRIO, 2, 2];
+static ORDER: StaticRefCell<Vec<usize>> = StaticRefCell::new(Vec::new());
+
+struct CancelArgs {
+    event: Event,
+    pending: bool,
+    expired: usize,
+    woken: usize,
+}
+
+pub fn run(t: &mut dyn WvTester) {
+    thread::init();
+
+    wv_run_test!(t, timeout);
+    wv_run_test!(t, expired_timeout);
+    wv_run_test!(t, notify_before_timeout);
+    wv_run_test!(t, cancel_infinite_timeout);
+    wv_run_test!(t, grow_and_shrink_pool);
+    wv_run_test!(t, keep_blocked_threads);
+    wv_run_test!(t, keep_yielded_threads);
+    wv_run_test!(t, keep_main_thread);
+    wv_run_test!(t, reuse_stacks);
+    wv_run_test!(t, priorities);
+}
+
+// new threads only run if the current thread blocks. thus, the tests block the main thread and let
+// helper threads wake it up again. afterwards, the helpers park and are destroyed via
+// thread::shrink_pool.
+
+fn spawn(func: fn(usize), arg: usize) {
+    thread::add_thread(VirtAddr::from(func as *const ()), arg);
+}
+
+fn idle() {
+    loop {
+        thread::park();
+    }
+}
+
+fn timeout_driver(_arg: usize) {
+    // drive the timeouts like a server loop until the main thread is ready again
+    loop {
+        OwnActivity::sleep_for(thread::next_timeout().unwrap_or(TimeDuration::MAX)).ok();
+        thread::check_timeouts();
+        thread::park();
+    }
+}
+
+fn notifier(event: usize) {
+    thread::notify(event as Event, None);
+    idle();
+}
+
+fn canceller(args: &mut CancelArgs) {
+    args.pending = thread::next_timeout().is_some();
+    args.expired = thread::check_timeouts();
+    args.woken = thread::cancel(args.event);
+    idle();
+}
+
+fn blocker(event: usize) {
+    // let the main thread continue and wait until it wakes us up again
+    thread::notify(event as Event, None);
+    thread::wait_for(event as Event + 1);
+    thread::stop();
+}
+
+fn yielder(event: usize) {
+    // let the main thread continue in the middle of our work
+    thread::notify(event as Event, None);
+    thread::try_yield();
+    thread::notify(event as Event + 1, None);
+    idle();
+}
+
+fn remover(shrunk: &mut usize) {
+    // let the main thread continue until it wakes us up and parks itself
+    thread::notify(0x1040, None);
+    thread::wait_for(0x1041);
+
+    // the main thread is the only parked thread and cannot be destroyed
+    *shrunk = thread::shrink_pool(1);
+    // therefore, we are destroyed on park instead, which lets the main thread continue
+    thread::remove_thread();
+    idle();
+}
+
+fn prio_worker(idx: usize) {
+    thread::set_priority(PRIOS[idx]);
+    thread::notify(0x1020, None);
+
+    thread::wait_for(0x1021);
+    ORDER.borrow_mut().push(idx);
+    // the last worker wakes up the main thread
+    if ORDER.borrow().len() == PRIOS.len() {
+        thread::notify(0x1022, None);
+    }
+    thread::stop();
+}
+
+fn timeout(t: &mut dyn WvTester) {
+    spawn(timeout_driver, 0);
+
+    let timeout = TimeDuration::from_millis(1);
+    let start = TimeInstant::now();
+    wv_assert_err!(t, thread::wait_for_timeout(0x1000, timeout), Code::Timeout);
+    wv_assert!(t, start.elapsed() >= timeout);
+    wv_assert_eq!(t, thread::blocked_count(), 0);
+    wv_assert_eq!(t, thread::next_timeout(), None);
+
+    wv_assert_eq!(t, thread::shrink_pool(1), 1);
+}
+
+fn expired_timeout(t: &mut dyn WvTester) {
+    // the timeout is detected when blocking, so that we continue immediately
+    wv_assert_err!(
+        t,
+        thread::wait_for_timeout(0x1001, TimeDuration::ZERO),
+        Code::Timeout
+    );
+    wv_assert_eq!(t, thread::blocked_count(), 0);
+}
+
+fn notify_before_timeout(t: &mut dyn WvTester) {
+    spawn(notifier, 0x1002);
+
+    wv_assert_ok!(thread::wait_for_timeout(
+        0x1002,
+        TimeDuration::from_secs(10)
+    ));
+    wv_assert_eq!(t, thread::next_timeout(), None);
+
+    wv_assert_eq!(t, thread::shrink_pool(1), 1);
+}
+
+fn cancel_infinite_timeout(t: &mut dyn WvTester) {
+    let mut args = CancelArgs {
+        event: 0x1003,
+        pending: false,
+        expired: 0,
+        woken: 0,
+    };
+    thread::add_thread(
+        VirtAddr::from(canceller as *const ()),
+        &mut args as *mut _ as usize,
+    );
+
+    // the deadline saturates instead of overflowing
+    wv_assert_err!(
+        t,
+        thread::wait_for_timeout(args.event, TimeDuration::MAX),
+        Code::Abort
+    );
+    wv_assert!(t, args.pending);
+    wv_assert_eq!(t, args.expired, 0);
+    wv_assert_eq!(t, args.woken, 1);
+    wv_assert_eq!(t, thread::cancel(args.event), 0);
+
+    wv_assert_eq!(t, thread::shrink_pool(1), 1);
+}
+
+fn grow_and_shrink_pool(t: &mut dyn WvTester) {
+    wv_assert_eq!(t, thread::thread_count(), 0);
+
+    thread::spawn_pool(VirtAddr::from(idle as *const ()), 0, 3);
+    wv_assert_eq!(t, thread::idle_count(), 3);
+    wv_assert_eq!(t, thread::thread_count(), 3);
+
+    thread::spawn_pool_with_stack(
+        VirtAddr::from(idle as *const ()),
+        0,
+        2,
+        thread::MIN_STACK_SIZE,
+    );
+    wv_assert_eq!(t, thread::idle_count(), 5);
+
+    // only existing threads can be destroyed
+    wv_assert_eq!(t, thread::shrink_pool(2), 2);
+    wv_assert_eq!(t, thread::idle_count(), 3);
+    wv_assert_eq!(t, thread::shrink_pool(10), 3);
+    wv_assert_eq!(t, thread::thread_count(), 0);
+    wv_assert_eq!(t, thread::shrink_pool(1), 0);
+}
+
+fn keep_blocked_threads(t: &mut dyn WvTester) {
+    spawn(blocker, 0x1010);
+
+    // let the blocker run until it waits for its event
+    thread::wait_for(0x1010);
+    wv_assert_eq!(t, thread::blocked_count(), 1);
+    wv_assert_eq!(t, thread::idle_count(), 0);
+
+    // blocked threads are never destroyed
+    wv_assert_eq!(t, thread::shrink_pool(1), 0);
+    wv_assert_eq!(t, thread::blocked_count(), 1);
+
+    // let the blocker continue; it stops itself and becomes a zombie, which makes us run again
+    thread::notify(0x1011, None);
+    thread::try_yield();
+    wv_assert_eq!(t, thread::thread_count(), 0);
+
+    // the zombie is destroyed with the next thread operation
+    let before = heap::stats();
+    wv_assert_eq!(t, thread::shrink_pool(1), 0);
+    wv_assert!(t, heap::stats().frees > before.frees);
+}
+
+fn keep_yielded_threads(t: &mut dyn WvTester) {
+    spawn(yielder, 0x1030);
+
+    // the yielder wakes us up and yields before it has finished its work
+    thread::wait_for(0x1030);
+    wv_assert_eq!(t, thread::sleeping_count(), 1);
+    wv_assert_eq!(t, thread::idle_count(), 0);
+    wv_assert_eq!(t, thread::shrink_pool(1), 0);
+
+    // let the yielder finish its work, after which it parks
+    thread::wait_for(0x1031);
+    wv_assert_eq!(t, thread::sleeping_count(), 0);
+    wv_assert_eq!(t, thread::idle_count(), 1);
+    wv_assert_eq!(t, thread::shrink_pool(1), 1);
+}
+
+fn keep_main_thread(t: &mut dyn WvTester) {
+    let mut shrunk = 1;
+    thread::add_thread(
+        VirtAddr::from(remover as *const ()),
+        &mut shrunk as *mut _ as usize,
+    );
+
+    // let the remover run until it waits for us
+    thread::wait_for(0x1040);
+    wv_assert_eq!(t, thread::blocked_count(), 1);
+
+    // wake it up and park, so that it runs while we are idle
+    thread::notify(0x1041, None);
+    thread::park();
+    wv_assert_eq!(t, shrunk, 0);
+    wv_assert_eq!(t, thread::thread_count(), 0);
+}
+
+fn reuse_stacks(t: &mut dyn WvTester) {
+    // stacks are allocated as a whole, so that their size determines the histogram bucket
+    let bucket = |size: usize| (size.ilog2() as usize).min(heap::SIZE_CLASSES - 1);
+    let def_bucket = bucket(thread::DEF_STACK_SIZE);
+    let min_bucket = bucket(thread::MIN_STACK_SIZE);
+
+    // make sure that there is at least one cached stack of the default size
+    thread::add_thread(VirtAddr::from(idle as *const ()), 0);
+    wv_assert_eq!(t, thread::shrink_pool(1), 1);
+
+    let before = heap::stats();
+    thread::add_thread(VirtAddr::from(idle as *const ()), 0);
+    wv_assert_eq!(
+        t,
+        heap::stats().histogram[def_bucket],
+        before.histogram[def_bucket]
+    );
+
+    // stacks of other sizes are not reused
+    let before = heap::stats();
+    thread::add_thread_with_stack(VirtAddr::from(idle as *const ()), 0, thread::MIN_STACK_SIZE);
+    wv_assert_eq!(
+        t,
+        heap::stats().histogram[min_bucket],
+        before.histogram[min_bucket] + 1
+    );
+
+    wv_assert_eq!(t, thread::shrink_pool(2), 2);
+}
+
+fn priorities(t: &mut dyn WvTester) {
+    wv_assert_eq!(t, thread::cur().priority(), thread::DEF_PRIO);
+    thread::set_priority(thread::MAX_PRIO);
+    wv_assert_eq!(t, thread::cur().priority(), thread::MAX_PRIO);
+    thread::set_priority(thread::DEF_PRIO);
+
+    for i in 0..PRIOS.len() {
+        spawn(prio_worker, i);
+        // let the worker set its priority and wait for work
+        thread::wait_for(0x1020);
+    }
+    wv_assert_eq!(t, thread::blocked_count(), PRIOS.len());
+
+    // wake up all workers at once and let them run until the last one wakes us up
+    thread::notify(0x1021, None);
+    wv_assert_eq!(t, thread::ready_count(), PRIOS.len());
+    thread::wait_for(0x1022);
+
+    // higher priorities run first and threads with the same priority in FIFO order
+    wv_assert_eq!(t, *ORDER.borrow(), vec![1, 2, 3, 0]);
+    wv_assert_eq!(t, thread::thread_count(), 0);
+}
@@ -0,0 +1,146 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use m3::cell::Cell;
+use m3::col::Vec;
+use m3::rc::Rc;
+use m3::test::WvTester;
+use m3::time::TimeDuration;
+use m3::timer::{self, TimerWheel};
+use m3::{wv_assert, wv_assert_eq, wv_run_test};
+
+pub fn run(t: &mut dyn WvTester) {
+    wv_run_test!(t, wheel_order);
+    wv_run_test!(t, wheel_cascade);
+    wv_run_test!(t, wheel_cancel);
+    wv_run_test!(t, oneshot);
+    wv_run_test!(t, periodic);
+    wv_run_test!(t, wakeup);
+}
+
+fn expired(wheel: &mut TimerWheel<u64>) -> Vec<u64> {
+    let mut res = Vec::new();
+    while let Some((_, item)) = wheel.pop_expired() {
+        res.push(item);
+    }
+    res
+}
+
+fn wheel_order(t: &mut dyn WvTester) {
+    let mut wheel = TimerWheel::new();
+    for e in [5, 3, 40, 1] {
+        wheel.insert(e, e);
+    }
+    wv_assert_eq!(t, wheel.len(), 4);
+    wv_assert_eq!(t, wheel.next_expiry(), Some(1));
+
+    wheel.advance(4);
+    wv_assert_eq!(t, expired(&mut wheel), [1, 3]);
+    wv_assert_eq!(t, wheel.next_expiry(), Some(5));
+
+    wheel.advance(100);
+    wv_assert_eq!(t, expired(&mut wheel), [5, 40]);
+    wv_assert!(t, wheel.is_empty());
+    wv_assert_eq!(t, wheel.next_expiry(), None);
+}
+
+fn wheel_cascade(t: &mut dyn WvTester) {
+    let mut wheel = TimerWheel::new();
+    // one item per level and one beyond the range of all levels
+    for e in [70, 5000, 300_000, 20_000_000, 100_000_000] {
+        wheel.insert(e, e);
+    }
+
+    // the next expiry is never later than the actual one
+    wv_assert!(t, wheel.next_expiry().unwrap() <= 70);
+
+    for e in [70, 5000, 300_000, 20_000_000, 100_000_000] {
+        wheel.advance(e - 1);
+        wv_assert!(t, expired(&mut wheel).is_empty());
+        wheel.advance(e);
+        wv_assert_eq!(t, expired(&mut wheel), [e]);
+    }
+    wv_assert!(t, wheel.is_empty());
+}
+
+fn wheel_cancel(t: &mut dyn WvTester) {
+    let mut wheel = TimerWheel::new();
+    let a = wheel.insert(10, 10);
+    let b = wheel.insert(10_000, 10_000);
+    wheel.insert(20, 20);
+
+    wv_assert_eq!(t, wheel.cancel(a), Some(10));
+    wv_assert_eq!(t, wheel.cancel(a), None);
+    wv_assert_eq!(t, wheel.cancel(b), Some(10_000));
+
+    wheel.advance(20_000);
+    wv_assert_eq!(t, expired(&mut wheel), [20]);
+}
+
+fn oneshot(t: &mut dyn WvTester) {
+    let calls = Rc::new(Cell::new(0));
+    let calls2 = calls.clone();
+    let id = timer::add(TimeDuration::ZERO, move || calls2.set(calls2.get() + 1));
+    let calls3 = calls.clone();
+    let cancelled = timer::add(TimeDuration::ZERO, move || calls3.set(calls3.get() + 10));
+    wv_assert!(t, timer::cancel(cancelled));
+
+    wv_assert_eq!(t, timer::next_timeout(), Some(TimeDuration::ZERO));
+    wv_assert_eq!(t, timer::run(), 1);
+    wv_assert_eq!(t, calls.get(), 1);
+    // the timer is gone after it has been called
+    wv_assert!(t, !timer::cancel(id));
+    wv_assert_eq!(t, timer::run(), 0);
+}
+
+fn periodic(t: &mut dyn WvTester) {
+    let calls = Rc::new(Cell::new(0));
+    let calls2 = calls.clone();
+    let id = Rc::new(Cell::new(0));
+    let id2 = id.clone();
+    let cancelled = Rc::new(Cell::new(false));
+    let cancelled2 = cancelled.clone();
+    id.set(timer::add_periodic(timer::RESOLUTION, move || {
+        calls2.set(calls2.get() + 1);
+        // cancel ourself after the third call
+        if calls2.get() == 3 {
+            cancelled2.set(timer::cancel(id2.get()));
+        }
+    }));
+
+    while calls.get() < 3 {
+        timer::sleep_for(TimeDuration::from_millis(10)).unwrap();
+    }
+    wv_assert_eq!(t, calls.get(), 3);
+    wv_assert!(t, cancelled.get());
+    wv_assert!(t, !timer::cancel(id.get()));
+    wv_assert_eq!(t, timer::next_timeout(), None);
+}
+
+fn wakeup(t: &mut dyn WvTester) {
+    let id = timer::add_wakeup(TimeDuration::from_millis(2));
+    wv_assert!(t, timer::is_pending(id));
+    wv_assert!(
+        t,
+        timer::remaining(id).unwrap() <= TimeDuration::from_millis(2)
+    );
+
+    // sleep_for removes the timer once it has expired
+    while timer::remaining(id).is_some() {
+        timer::sleep_for(TimeDuration::from_millis(10)).unwrap();
+    }
+    wv_assert!(t, !timer::is_pending(id));
+    wv_assert!(t, !timer::cancel(id));
+}
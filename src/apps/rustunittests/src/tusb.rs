@@ -0,0 +1,220 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use m3::com::MemGate;
+use m3::errors::Code;
+use m3::kif::Perm;
+use m3::mem::GlobOff;
+use m3::test::WvTester;
+use m3::{wv_assert, wv_assert_eq, wv_assert_err, wv_assert_ok, wv_run_test};
+
+use usb::desc::{ConfigDesc, DeviceDesc, Direction, EndpointDesc, SetupPacket, DESC_CONFIG};
+use usb::ring::*;
+
+pub fn run(t: &mut dyn WvTester) {
+    wv_run_test!(t, setup_packets);
+    wv_run_test!(t, device_desc);
+    wv_run_test!(t, config_desc);
+    wv_run_test!(t, config_desc_errors);
+    wv_run_test!(t, transfer_ring);
+    wv_run_test!(t, event_ring);
+}
+
+// the device descriptor of a USB flash drive
+const DEVICE: [u8; 18] = [
+    18, 1, 0x00, 0x02, 0, 0, 0, 64, 0x81, 0x07, 0x81, 0x55, 0x00, 0x01, 1, 2, 3, 1,
+];
+
+// a mass-storage configuration with a class-specific descriptor between the endpoints
+#[rustfmt::skip]
+const CONFIG: [u8; 37] = [
+    // configuration
+    9, 2, 37, 0, 1, 1, 0, 0x80, 50,
+    // interface
+    9, 4, 0, 0, 2, 0x08, 0x06, 0x50, 0,
+    // bulk-in endpoint 1
+    7, 5, 0x81, 0x02, 0x00, 0x02, 0,
+    // class-specific
+    5, 0x24, 0, 0, 0,
+    // bulk-out endpoint 2 with additional transactions
+    7, 5, 0x02, 0x02, 0x00, 0x1A, 0,
+];
+
+fn setup_packets(t: &mut dyn WvTester) {
+    let get = SetupPacket::get_descriptor(DESC_CONFIG, 0, 9);
+    wv_assert_eq!(t, get.direction(), Direction::In);
+    wv_assert_eq!(t, get.to_raw(), 0x0009_0000_0200_0680);
+
+    let set = SetupPacket::set_configuration(1);
+    wv_assert_eq!(t, set.direction(), Direction::Out);
+    wv_assert_eq!(t, set.to_raw(), 0x0000_0000_0001_0900);
+
+    let clear = SetupPacket::clear_halt(0x81);
+    wv_assert_eq!(t, clear.direction(), Direction::Out);
+    wv_assert_eq!(t, clear.to_raw(), 0x0000_0081_0000_0102);
+}
+
+fn device_desc(t: &mut dyn WvTester) {
+    let dev = wv_assert_ok!(DeviceDesc::parse(&DEVICE));
+    wv_assert_eq!(t, dev.usb_version, 0x0200);
+    wv_assert_eq!(t, dev.class, 0);
+    wv_assert_eq!(t, dev.max_packet_size0, 64);
+    wv_assert_eq!(t, dev.vendor, 0x0781);
+    wv_assert_eq!(t, dev.product, 0x5581);
+    wv_assert_eq!(t, dev.num_configs, 1);
+
+    wv_assert_err!(t, DeviceDesc::parse(&DEVICE[0..17]), Code::InvArgs);
+    let mut wrong = DEVICE;
+    wrong[1] = DESC_CONFIG;
+    wv_assert_err!(t, DeviceDesc::parse(&wrong), Code::InvArgs);
+}
+
+fn check_ep(t: &mut dyn WvTester, ep: &EndpointDesc, num: u8, dir: Direction, mps: u16) {
+    wv_assert_eq!(t, ep.number(), num);
+    wv_assert_eq!(t, ep.direction(), dir);
+    wv_assert_eq!(t, ep.transfer_type(), EndpointDesc::TYPE_BULK);
+    wv_assert_eq!(t, ep.max_packet_size, mps);
+}
+
+fn config_desc(t: &mut dyn WvTester) {
+    wv_assert_eq!(
+        t,
+        wv_assert_ok!(ConfigDesc::total_length(&CONFIG[0..9])),
+        37
+    );
+
+    let cfg = wv_assert_ok!(ConfigDesc::parse(&CONFIG));
+    wv_assert_eq!(t, cfg.value, 1);
+    wv_assert_eq!(t, cfg.attributes, 0x80);
+    wv_assert_eq!(t, cfg.interfaces.len(), 1);
+
+    let intf = &cfg.interfaces[0];
+    wv_assert_eq!(t, intf.class, 0x08);
+    wv_assert_eq!(t, intf.subclass, 0x06);
+    wv_assert_eq!(t, intf.protocol, 0x50);
+    // the class-specific descriptor is skipped
+    wv_assert_eq!(t, intf.endpoints.len(), 2);
+    check_ep(t, &intf.endpoints[0], 1, Direction::In, 512);
+    // the bits for additional transactions are not part of the packet size
+    check_ep(t, &intf.endpoints[1], 2, Direction::Out, 512);
+
+    // descriptors beyond the given bytes are ignored
+    let cfg = wv_assert_ok!(ConfigDesc::parse(&CONFIG[0..30]));
+    wv_assert_eq!(t, cfg.interfaces[0].endpoints.len(), 1);
+}
+
+fn config_desc_errors(t: &mut dyn WvTester) {
+    wv_assert_err!(t, ConfigDesc::total_length(&CONFIG[0..8]), Code::InvArgs);
+    wv_assert_err!(t, ConfigDesc::total_length(&DEVICE), Code::InvArgs);
+
+    // endpoint without interface
+    let mut no_intf = CONFIG;
+    no_intf[10] = 0x24;
+    wv_assert_err!(t, ConfigDesc::parse(&no_intf), Code::InvArgs);
+
+    // descriptor that is too short
+    let mut short = CONFIG;
+    short[25] = 1;
+    wv_assert_err!(t, ConfigDesc::parse(&short), Code::InvArgs);
+
+    // descriptor that exceeds the total length
+    let mut long = CONFIG;
+    long[30] = 8;
+    wv_assert_err!(t, ConfigDesc::parse(&long), Code::InvArgs);
+}
+
+fn read_trb(mem: &MemGate, addr: GlobOff) -> Trb {
+    wv_assert_ok!(mem.read_obj::<Trb>(addr))
+}
+
+fn transfer_ring(t: &mut dyn WvTester) {
+    let mem = wv_assert_ok!(MemGate::new(0x100, Perm::RW));
+    let mut ring = wv_assert_ok!(Ring::new(&mem, 0x40, 4));
+    wv_assert_eq!(t, ring.addr(), 0x40);
+    wv_assert!(t, ring.cycle());
+
+    // the link TRB is not handed to the controller yet
+    let link = read_trb(&mem, 0x70);
+    wv_assert_eq!(t, link.ty(), TRB_LINK);
+    wv_assert_eq!(t, link.param, 0x40);
+    wv_assert!(t, !link.cycle());
+
+    let trb = Trb::new(TRB_NORMAL, 0x1000, 8, TRB_IOC);
+    wv_assert_eq!(t, wv_assert_ok!(ring.push(&mem, trb)), 0x40);
+    let first = read_trb(&mem, 0x40);
+    wv_assert_eq!(t, first.ty(), TRB_NORMAL);
+    wv_assert_eq!(t, first.param, 0x1000);
+    wv_assert_eq!(t, first.status, 8);
+    wv_assert!(t, first.cycle());
+    wv_assert!(t, (first.control & TRB_IOC) != 0);
+
+    wv_assert_eq!(t, wv_assert_ok!(ring.push(&mem, trb)), 0x50);
+    wv_assert_eq!(t, wv_assert_ok!(ring.push(&mem, trb)), 0x60);
+
+    // the ring wrapped around and handed the link TRB to the controller
+    wv_assert_eq!(t, ring.enqueue_addr(), 0x40);
+    wv_assert!(t, !ring.cycle());
+    let link = read_trb(&mem, 0x70);
+    wv_assert_eq!(t, link.ty(), TRB_LINK);
+    wv_assert!(t, link.cycle());
+    wv_assert!(t, (link.control & TRB_TOGGLE_CYCLE) != 0);
+
+    // TRBs of the next round have the cycle bit cleared, regardless of the given TRB
+    let trb = Trb::new(TRB_NORMAL, 0x2000, 16, TRB_CYCLE);
+    wv_assert_eq!(t, wv_assert_ok!(ring.push(&mem, trb)), 0x40);
+    let first = read_trb(&mem, 0x40);
+    wv_assert_eq!(t, first.param, 0x2000);
+    wv_assert!(t, !first.cycle());
+}
+
+fn event_ring(t: &mut dyn WvTester) {
+    let mem = wv_assert_ok!(MemGate::new(0x100, Perm::RW));
+    let mut ring = wv_assert_ok!(EventRing::new(&mem, 0, 2));
+    wv_assert!(t, wv_assert_ok!(ring.pop(&mem)).is_none());
+
+    // the controller completes a command on slot 5
+    let status = (CC_SUCCESS as u32) << 24;
+    let cmd = Trb::new(TRB_CMD_COMPLETION, 0x40, status, 5 << 24 | TRB_CYCLE);
+    wv_assert_ok!(mem.write_obj(&cmd, 0));
+
+    let ev = wv_assert_ok!(ring.pop(&mem)).unwrap();
+    wv_assert_eq!(t, ev.ty(), TRB_CMD_COMPLETION);
+    wv_assert_eq!(t, ev.param, 0x40);
+    wv_assert_eq!(t, ev.completion_code(), CC_SUCCESS);
+    wv_assert_eq!(t, ev.slot(), 5);
+    wv_assert_eq!(t, ring.dequeue_addr(), TRB_SIZE as GlobOff);
+    wv_assert!(t, wv_assert_ok!(ring.pop(&mem)).is_none());
+
+    // a short transfer on endpoint 4 of slot 3
+    let status = (CC_SHORT_PACKET as u32) << 24 | 0x123;
+    let xfer = Trb::new(TRB_TRANSFER_EVENT, 0, status, 3 << 24 | 4 << 16 | TRB_CYCLE);
+    wv_assert_ok!(mem.write_obj(&xfer, TRB_SIZE as GlobOff));
+
+    let ev = wv_assert_ok!(ring.pop(&mem)).unwrap();
+    wv_assert_eq!(t, ev.ty(), TRB_TRANSFER_EVENT);
+    wv_assert_eq!(t, ev.completion_code(), CC_SHORT_PACKET);
+    wv_assert_eq!(t, ev.residual(), 0x123);
+    wv_assert_eq!(t, ev.slot(), 3);
+    wv_assert_eq!(t, ev.endpoint(), 4);
+
+    // after the wrap around, the old event is not consumed again
+    wv_assert_eq!(t, ring.dequeue_addr(), 0);
+    wv_assert!(t, wv_assert_ok!(ring.pop(&mem)).is_none());
+
+    let cmd = Trb::new(TRB_CMD_COMPLETION, 0x50, status, 5 << 24);
+    wv_assert_ok!(mem.write_obj(&cmd, 0));
+    let ev = wv_assert_ok!(ring.pop(&mem)).unwrap();
+    wv_assert_eq!(t, ev.param, 0x50);
+}
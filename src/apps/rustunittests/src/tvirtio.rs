@@ -0,0 +1,179 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use m3::com::MemGate;
+use m3::errors::Code;
+use m3::kif::Perm;
+use m3::mem::GlobOff;
+use m3::test::WvTester;
+use m3::{wv_assert, wv_assert_eq, wv_assert_err, wv_assert_ok, wv_assert_some, wv_run_test};
+
+use virtio::{Buffer, VirtQueue};
+
+// a queue with 4 descriptors: the descriptor table and the available ring fit into the first
+// page and the used ring starts at the second page
+const SIZE: u16 = 4;
+const AVAIL_OFF: GlobOff = SIZE as GlobOff * 16;
+const USED_OFF: GlobOff = 0x1000;
+
+pub fn run(t: &mut dyn WvTester) {
+    wv_run_test!(t, mem_size);
+    wv_run_test!(t, unaligned);
+    wv_run_test!(t, add_chain);
+    wv_run_test!(t, add_errors);
+    wv_run_test!(t, pop_used);
+    wv_run_test!(t, wrap_around);
+}
+
+fn create() -> (MemGate, VirtQueue) {
+    let mem = wv_assert_ok!(MemGate::new(VirtQueue::mem_size(SIZE) as GlobOff, Perm::RW));
+    let queue = wv_assert_ok!(VirtQueue::new(&mem, 0, 0, SIZE));
+    (mem, queue)
+}
+
+/// Lets the device use the chain `head` and write `len` bytes into it
+fn use_chain(mem: &MemGate, head: u16, len: u32) {
+    let idx: u16 = wv_assert_ok!(mem.read_obj(USED_OFF + 2));
+    let slot = (idx % SIZE) as GlobOff;
+    wv_assert_ok!(mem.write_obj(&(head as u32), USED_OFF + 4 + slot * 8));
+    wv_assert_ok!(mem.write_obj(&len, USED_OFF + 4 + slot * 8 + 4));
+    wv_assert_ok!(mem.write_obj(&idx.wrapping_add(1), USED_OFF + 2));
+}
+
+/// Returns the address, length, flags, and next index of descriptor `idx`
+fn descriptor(mem: &MemGate, idx: u16) -> (u64, u32, u16, u16) {
+    let off = idx as GlobOff * 16;
+    (
+        wv_assert_ok!(mem.read_obj(off)),
+        wv_assert_ok!(mem.read_obj(off + 8)),
+        wv_assert_ok!(mem.read_obj(off + 12)),
+        wv_assert_ok!(mem.read_obj(off + 14)),
+    )
+}
+
+fn mem_size(t: &mut dyn WvTester) {
+    wv_assert_eq!(t, VirtQueue::mem_size(SIZE), 0x2000);
+    // 4096 bytes of descriptors and 518 bytes of available ring; 2054 bytes of used ring
+    wv_assert_eq!(t, VirtQueue::mem_size(256), 0x3000);
+}
+
+fn unaligned(t: &mut dyn WvTester) {
+    let mem = wv_assert_ok!(MemGate::new(0x4000, Perm::RW));
+    wv_assert_err!(
+        t,
+        VirtQueue::new(&mem, 0, 0x800, SIZE).map(|_| ()),
+        Code::InvArgs
+    );
+    let queue = wv_assert_ok!(VirtQueue::new(&mem, 1, 0x1000, SIZE));
+    wv_assert_eq!(t, queue.index(), 1);
+    wv_assert_eq!(t, queue.size(), SIZE);
+    wv_assert_eq!(t, queue.num_free(), SIZE as usize);
+}
+
+fn add_chain(t: &mut dyn WvTester) {
+    let (mem, mut queue) = create();
+
+    let head = wv_assert_ok!(queue.add(&mem, &[
+        Buffer::new(0x4000, 16, false),
+        Buffer::new(0x5000, 512, true),
+    ]));
+    wv_assert_eq!(t, head, 0);
+    wv_assert_eq!(t, queue.num_free(), 2);
+
+    // the buffers are linked and the last one is not
+    wv_assert_eq!(t, descriptor(&mem, 0), (0x4000, 16, 1, 1));
+    wv_assert_eq!(t, descriptor(&mem, 1), (0x5000, 512, 2, 0));
+
+    // the chain has been published in the available ring
+    wv_assert_eq!(t, wv_assert_ok!(mem.read_obj::<u16>(AVAIL_OFF + 2)), 1);
+    wv_assert_eq!(t, wv_assert_ok!(mem.read_obj::<u16>(AVAIL_OFF + 4)), 0);
+
+    // the next chain starts with the next free descriptor
+    let head = wv_assert_ok!(queue.add(&mem, &[Buffer::new(0x6000, 8, true)]));
+    wv_assert_eq!(t, head, 2);
+    wv_assert_eq!(t, wv_assert_ok!(mem.read_obj::<u16>(AVAIL_OFF + 2)), 2);
+    wv_assert_eq!(t, wv_assert_ok!(mem.read_obj::<u16>(AVAIL_OFF + 6)), 2);
+}
+
+fn add_errors(t: &mut dyn WvTester) {
+    let (mem, mut queue) = create();
+
+    wv_assert_err!(t, queue.add(&mem, &[]), Code::InvArgs);
+
+    let bufs = [Buffer::new(0x4000, 8, false); SIZE as usize + 1];
+    wv_assert_err!(t, queue.add(&mem, &bufs), Code::NoSpace);
+    wv_assert_eq!(t, queue.num_free(), SIZE as usize);
+
+    // all descriptors can be used by a single chain
+    wv_assert_ok!(queue.add(&mem, &bufs[0..SIZE as usize]));
+    wv_assert_eq!(t, queue.num_free(), 0);
+    wv_assert_err!(t, queue.add(&mem, &bufs[0..1]), Code::NoSpace);
+}
+
+fn pop_used(t: &mut dyn WvTester) {
+    let (mem, mut queue) = create();
+
+    let first = wv_assert_ok!(queue.add(&mem, &[
+        Buffer::new(0x4000, 16, false),
+        Buffer::new(0x5000, 512, true),
+    ]));
+    let second = wv_assert_ok!(queue.add(&mem, &[Buffer::new(0x6000, 8, true)]));
+    wv_assert!(t, !wv_assert_ok!(queue.has_used(&mem)));
+    wv_assert_eq!(t, wv_assert_ok!(queue.pop_used(&mem)), None);
+
+    // the device may finish the chains in a different order
+    use_chain(&mem, second, 8);
+    use_chain(&mem, first, 300);
+    wv_assert!(t, wv_assert_ok!(queue.has_used(&mem)));
+
+    wv_assert_eq!(
+        t,
+        wv_assert_some!(wv_assert_ok!(queue.pop_used(&mem))),
+        (second, 8)
+    );
+    wv_assert_eq!(t, queue.num_free(), 2);
+    wv_assert_eq!(
+        t,
+        wv_assert_some!(wv_assert_ok!(queue.pop_used(&mem))),
+        (first, 300)
+    );
+    wv_assert_eq!(t, queue.num_free(), SIZE as usize);
+    wv_assert_eq!(t, wv_assert_ok!(queue.pop_used(&mem)), None);
+
+    // the freed descriptors are reused
+    let bufs = [Buffer::new(0x4000, 8, false); SIZE as usize];
+    wv_assert_eq!(t, wv_assert_ok!(queue.add(&mem, &bufs)), first);
+    wv_assert_eq!(t, queue.num_free(), 0);
+}
+
+fn wrap_around(t: &mut dyn WvTester) {
+    let (mem, mut queue) = create();
+
+    // use the rings several times to wrap around the ring slots
+    for i in 0..SIZE as u32 * 3 {
+        let head = wv_assert_ok!(queue.add(&mem, &[
+            Buffer::new(0x4000, 64, false),
+            Buffer::new(0x5000, 64, true),
+        ]));
+        use_chain(&mem, head, i);
+        wv_assert_eq!(t, wv_assert_ok!(queue.pop_used(&mem)), Some((head, i)));
+        wv_assert_eq!(t, queue.num_free(), SIZE as usize);
+    }
+    wv_assert_eq!(
+        t,
+        wv_assert_ok!(mem.read_obj::<u16>(AVAIL_OFF + 2)),
+        SIZE * 3
+    );
+}
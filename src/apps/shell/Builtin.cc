@@ -22,18 +22,27 @@
 #include <m3/vfs/VFS.h>
 
 #include <errno.h>
+#include <stdlib.h>
 #include <unistd.h>
 
+#include "Jobs.h"
+
 using namespace m3;
 
+static int execute_bg(char **args, int);
 static int execute_cd(char **args, int);
 static int execute_echo(char **args, int outfd);
 static int execute_export(char **args, int outfd);
+static int execute_fg(char **args, int);
+static int execute_jobs(char **args, int outfd);
 
 Builtin::Command Builtin::commands[] = {
+    {"bg",     execute_bg    },
     {"cd",     execute_cd    },
     {"echo",   execute_echo  },
     {"export", execute_export},
+    {"fg",     execute_fg    },
+    {"jobs",   execute_jobs  },
     {nullptr,  nullptr       },
 };
 
@@ -45,6 +54,56 @@ bool Builtin::is_builtin(const char *name) {
     return false;
 }
 
+static Job *get_job(char **args) {
+    size_t id = 0;
+    if(args[1]) {
+        // allow both "fg 1" and "fg %1"
+        const char *arg = args[1][0] == '%' ? args[1] + 1 : args[1];
+        char *end;
+        id = strtoul(arg, &end, 10);
+        if(*arg == '\0' || *end != '\0' || id == 0) {
+            eprintln("Invalid job '{}'"_cf, args[1]);
+            return nullptr;
+        }
+    }
+
+    Job *job = Jobs::get(id);
+    if(!job)
+        eprintln("{}: no such job"_cf, args[0]);
+    return job;
+}
+
+static int execute_bg(char **args, int) {
+    Job *job = get_job(args);
+    if(!job)
+        return 1;
+
+    Jobs::background(job);
+    return 0;
+}
+
+static int execute_fg(char **args, int) {
+    Job *job = get_job(args);
+    if(!job)
+        return 1;
+
+    println("{}"_cf, job->name());
+    Jobs::foreground(job);
+    return 0;
+}
+
+static int execute_jobs(char **, int outfd) {
+    try {
+        FStream fout(outfd);
+        Jobs::print(fout);
+    }
+    catch(const Exception &e) {
+        eprintln("jobs failed: {}"_cf, e.what());
+        return 1;
+    }
+    return 0;
+}
+
 static int execute_cd(char **args, int) {
     if(!args[1]) {
         eprintln("Usage: {} <path>"_cf, args[0]);
@@ -0,0 +1,433 @@
+/*
+ * Copyright (C) 2015-2018 Nils Asmussen <nils@os.inf.tu-dresden.de>
+ * Economic rights: Technische Universitaet Dresden (Germany)
+ *
+ * Copyright (C) 2019-2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+#include "Jobs.h"
+
+#include <base/stream/OStringStream.h>
+
+#include <m3/Syscalls.h>
+#include <m3/stream/FStream.h>
+#include <m3/stream/Standard.h>
+#include <m3/vfs/VFS.h>
+
+#include "Builtin.h"
+#include "Vars.h"
+
+using namespace m3;
+
+static const CycleDuration ACOMP_TIME = CycleDuration::from_raw(4096);
+
+static const size_t PIPE_SHM_SIZE = 512 * 1024;
+
+static const uint MIN_EPS = 16;
+static const TimeDuration MIN_TIME = TimeDuration::from_micros(100);
+static const size_t MIN_PTS = 16;
+
+bool Jobs::_have_vterm = false;
+size_t Jobs::_next_id = 1;
+Job *Jobs::_fg = nullptr;
+std::vector<std::unique_ptr<Job>> Jobs::_jobs;
+
+static std::unique_ptr<char *[]> build_args(const Parser::Command &cmd) {
+    std::unique_ptr<char *[]> res(new char *[cmd.args()->size() + 1]);
+    for(size_t i = 0; i < cmd.args()->size(); ++i)
+        res[i] = (char *)expr_value(*cmd.args()->get(i));
+    res[cmd.args()->size()] = nullptr;
+    return res;
+}
+
+static const char *get_pe_name(const std::unique_ptr<Parser::VarList> &vars, const char *path) {
+    FStream f(path, FILE_R | FILE_X);
+    if(f.bad())
+        return "";
+
+    // accelerator description file?
+    if(f.read() == '@' && f.read() == '=') {
+        static char line[128];
+        f.getline(line, sizeof(line));
+        return line;
+    }
+
+    for(auto var = vars->cbegin(); var != vars->cend(); ++var) {
+        if((*var)->name() == "TILE")
+            return expr_value(*(*var)->value());
+    }
+    // prefer a different tile to prevent that we run out of EPs or similar
+    return "core|own";
+}
+
+Job::Job(std::unique_ptr<Parser::CmdList> &&cmds)
+    : _id(),
+      _name(),
+      _state(RUNNING),
+      _cmds(std::move(cmds)),
+      _pipes(),
+      _mems(),
+      _accels(),
+      _tiles(),
+      _acts(),
+      _infile(),
+      _outfile(),
+      _errfile(),
+      _clones(),
+      _group() {
+    OStringStream os;
+    for(size_t i = 0; i < _cmds->size(); ++i) {
+        auto &args = _cmds->get(i)->args();
+        if(i > 0)
+            format_to(os, " | "_cf);
+        for(size_t a = 0; a < args->size(); ++a)
+            format_to(os, "{}{}"_cf, a > 0 ? " " : "", expr_value(*args->get(a)));
+    }
+    _name = os.str();
+}
+
+const char *Job::cmd_name(size_t i) const {
+    return expr_value(*_cmds->get(i)->args()->get(0));
+}
+
+void Job::start(Pipes &pipesrv, VTerm *vterm) {
+    bool builtin[MAX_CMDS];
+
+    if(_cmds->size() > MAX_CMDS)
+        throw MessageException("Too many commands in pipeline");
+
+    // get tile types
+    for(size_t i = 0; i < _cmds->size(); ++i) {
+        auto &cmd = _cmds->get(i);
+        if(cmd->args()->size() == 0) {
+            eprintln("Command has no arguments"_cf);
+            return;
+        }
+
+        const char *cmd_name = expr_value(*cmd->args()->get(0));
+        builtin[i] = Builtin::is_builtin(cmd_name);
+        if(i > 0 && builtin[i]) {
+            eprintln("Builtin command cannot read from pipe"_cf);
+            return;
+        }
+        if(!builtin[i]) {
+            const char *tile_name = get_pe_name(cmd->vars(), cmd_name);
+            _tiles[i] = Tile::get(tile_name);
+        }
+    }
+
+    for(size_t i = 0; i < _cmds->size(); ++i) {
+        auto &cmd = _cmds->get(i);
+
+        Vars vars;
+        for(auto var = cmd->vars()->cbegin(); var != cmd->vars()->cend(); ++var)
+            vars.set((*var)->name().c_str(), expr_value(*(*var)->value()));
+
+        if(!builtin[i]) {
+            // if we share our tile with this child activity, give it separate quotas to ensure
+            // that we get our share (we don't trust the child apps)
+            if(_tiles[i]->sel() == Activity::own().tile()->sel()) {
+                const auto [eps, time, pts] = _tiles[i]->quota();
+                if(eps.left > MIN_EPS && pts.left > MIN_PTS) {
+                    _tiles[i] = _tiles[i]->derive(Some(eps.left - MIN_EPS),
+                                                  Some(time.total - MIN_TIME),
+                                                  Some(pts.left - MIN_PTS));
+                }
+                else
+                    _tiles[i] = Tile::get("core");
+            }
+
+            _acts[i] = std::make_unique<ChildActivity>(_tiles[i], cmd_name(i));
+            _group.add(_acts[i].get());
+        }
+
+        // I/O redirection is only supported at the beginning and end
+        if((i + 1 < _cmds->size() && cmd->redirections()->std_out()) ||
+           (i > 0 && cmd->redirections()->std_in())) {
+            throw MessageException("Invalid I/O redirection");
+        }
+
+        fd_t infd = STDIN_FD;
+        if(i == 0) {
+            if(cmd->redirections()->std_in())
+                _infile =
+                    VFS::open(expr_value(*cmd->redirections()->std_in()), FILE_R | FILE_NEWSESS);
+            else if(vterm)
+                _infile = vterm->create_channel(true);
+            if(_infile.is_valid())
+                infd = _infile->fd();
+        }
+        else if((builtin[i - 1] || _tiles[i - 1]->desc().is_programmable()) ||
+                (builtin[i] || _tiles[i]->desc().is_programmable()))
+            infd = _pipes[i - 1]->reader().fd();
+
+        if(_acts[i] && infd != STDIN_FD)
+            _acts[i]->add_file(STDIN_FD, infd);
+
+        fd_t outfd = STDOUT_FD;
+        if(i + 1 == _cmds->size()) {
+            if(cmd->redirections()->std_out())
+                _outfile = VFS::open(expr_value(*cmd->redirections()->std_out()),
+                                     FILE_W | FILE_CREATE | FILE_TRUNC | FILE_NEWSESS);
+            else if(vterm)
+                _outfile = vterm->create_channel(false);
+            if(_outfile.is_valid())
+                outfd = _outfile->fd();
+        }
+        else if((builtin[i] || _tiles[i]->desc().is_programmable()) ||
+                (builtin[i + 1] || _tiles[i + 1]->desc().is_programmable())) {
+            _mems[i] = std::make_unique<MemCap>(MemCap::create_global(PIPE_SHM_SIZE, MemCap::RW));
+            _pipes[i] = std::make_unique<IndirectPipe>(pipesrv, *_mems[i], PIPE_SHM_SIZE);
+            outfd = _pipes[i]->writer().fd();
+        }
+
+        if(_acts[i] && outfd != STDOUT_FD)
+            _acts[i]->add_file(STDOUT_FD, outfd);
+
+        std::unique_ptr<char *[]> args = build_args(*cmd);
+
+        if(builtin[i]) {
+            Builtin::execute(args.get(), outfd);
+            // close stdout pipe to send EOF
+            if(_pipes[i])
+                _pipes[i]->close_writer();
+        }
+        else if(_tiles[i]->desc().is_programmable()) {
+            if(vterm)
+                _errfile = vterm->create_channel(false);
+            if(_errfile.is_valid())
+                _acts[i]->add_file(STDERR_FD, _errfile->fd());
+
+            _acts[i]->add_mount("/", "/");
+
+            _acts[i]->exec(static_cast<int>(cmd->args()->size()),
+                           const_cast<const char **>(args.get()), vars.get());
+        }
+        else
+            _accels[i] = std::make_unique<StreamAccel>(_acts[i], ACOMP_TIME);
+
+        if(i > 0 && _pipes[i - 1]) {
+            if(_acts[i] && _acts[i]->tile_desc().is_programmable())
+                _pipes[i - 1]->close_reader();
+            if(_acts[i - 1] && _acts[i - 1]->tile_desc().is_programmable())
+                _pipes[i - 1]->close_writer();
+        }
+    }
+
+    // connect input/output of accelerators
+    size_t c = 0;
+    for(size_t i = 0; i < _cmds->size(); ++i) {
+        if(_accels[i]) {
+            fd_t our_in_fd = _acts[i]->get_file(STDIN_FD);
+            if(our_in_fd != FileTable::MAX_FDS) {
+                auto our_in = Activity::own().files()->get(our_in_fd);
+                auto ain = our_in->clone();
+                _accels[i]->connect_input(static_cast<GenericFile *>(&*ain));
+                _clones[c++] = std::move(ain);
+            }
+            else if(_accels[i - 1])
+                _accels[i]->connect_input(_accels[i - 1].get());
+
+            fd_t our_out_fd = _acts[i]->get_file(STDOUT_FD);
+            if(our_out_fd != FileTable::MAX_FDS) {
+                auto our_out = Activity::own().files()->get(our_out_fd);
+                auto aout = our_out->clone();
+                _accels[i]->connect_output(static_cast<GenericFile *>(&*aout));
+                _clones[c++] = std::move(aout);
+            }
+            else if(_accels[i + 1])
+                _accels[i]->connect_output(_accels[i + 1].get());
+        }
+    }
+
+    // start accelerator activities
+    for(size_t i = 0; i < _cmds->size(); ++i) {
+        if(_accels[i])
+            _acts[i]->start();
+    }
+}
+
+size_t Job::find(capsel_t act) const {
+    for(size_t i = 0; i < _cmds->size(); ++i) {
+        if(_acts[i] && _acts[i]->sel() == act)
+            return i;
+    }
+    return MAX_CMDS;
+}
+
+void Job::remove(size_t i) {
+    if(!_acts[i]->tile_desc().is_programmable()) {
+        if(_pipes[i])
+            _pipes[i]->close_writer();
+        if(i > 0 && _pipes[i - 1])
+            _pipes[i - 1]->close_reader();
+    }
+    _group.remove(_acts[i]->sel());
+    delete _acts[i].release();
+}
+
+void Job::exited(size_t i, int exitcode) {
+    if(exitcode != 0) {
+        eprintln("{} terminated with exit code {}"_cf, cmd_name(i),
+                 static_cast<Errors::Code>(exitcode));
+    }
+    remove(i);
+}
+
+void Job::interrupt() {
+    for(size_t i = 0; i < _cmds->size(); ++i) {
+        if(_acts[i]) {
+            eprintln("{} terminated by signal"_cf, cmd_name(i));
+            remove(i);
+        }
+    }
+}
+
+Job *Jobs::add(std::unique_ptr<Job> &&job) {
+    if(job->finished())
+        return nullptr;
+
+    if(_jobs.size() == MAX_JOBS)
+        throw MessageException("Too many jobs");
+
+    if(_jobs.empty())
+        _next_id = 1;
+    job->_id = _next_id++;
+    _jobs.push_back(std::move(job));
+    return _jobs.back().get();
+}
+
+Job *Jobs::get(size_t id) {
+    if(_jobs.empty())
+        return nullptr;
+    if(id == 0)
+        return _jobs.back().get();
+
+    for(auto &job : _jobs) {
+        if(job->id() == id)
+            return job.get();
+    }
+    return nullptr;
+}
+
+void Jobs::foreground(Job *job) {
+    if(job->_state == Job::STOPPED) {
+        job->_group.resume();
+        job->_state = Job::RUNNING;
+    }
+
+    if(_have_vterm) {
+        // fetch the signals first to ensure we don't have one from last time
+        cin.file()->fetch_signal();
+        cin.file()->fetch_suspend();
+    }
+
+    _fg = job;
+    while(!job->finished()) {
+        wait_async();
+
+        while(true) {
+            if(handle_upcall())
+                break;
+            // terminal signals are only delivered to the foreground job
+            else if(_have_vterm && cin.file()->fetch_signal()) {
+                job->interrupt();
+                break;
+            }
+            else if(_have_vterm && cin.file()->fetch_suspend()) {
+                job->_group.suspend();
+                job->_state = Job::STOPPED;
+                eprintln("\n[{}]+ Stopped {}"_cf, job->id(), job->name());
+                _fg = nullptr;
+                // keep waiting for the background jobs
+                wait_async();
+                return;
+            }
+
+            OwnActivity::sleep();
+        }
+    }
+    _fg = nullptr;
+
+    remove(job);
+    wait_async();
+}
+
+void Jobs::background(Job *job) {
+    if(job->_state == Job::STOPPED) {
+        job->_group.resume();
+        job->_state = Job::RUNNING;
+        println("[{}]+ {} &"_cf, job->id(), job->name());
+    }
+}
+
+void Jobs::reap() {
+    if(_jobs.empty())
+        return;
+
+    while(handle_upcall())
+        wait_async();
+}
+
+void Jobs::print(OStream &os) {
+    for(auto &job : _jobs) {
+        bool cur = job.get() == _jobs.back().get();
+        format_to(os, "[{}]{} {:<8} {}\n"_cf, job->id(), cur ? "+" : " ",
+                  job->state() == Job::RUNNING ? "Running" : "Stopped", job->name());
+    }
+}
+
+void Jobs::wait_async() {
+    capsel_t sels[MAX_JOBS * MAX_CMDS];
+    size_t count = 0;
+    for(auto &job : _jobs)
+        count += job->_group.sels(sels + count);
+
+    // a wait with zero activities cancels a previous wait
+    Syscalls::activity_wait(sels, count, 1);
+}
+
+bool Jobs::handle_upcall() {
+    const TCU::Message *msg = RecvGate::upcall().fetch();
+    if(!msg)
+        return false;
+
+    GateIStream is(RecvGate::upcall(), msg);
+    auto upcall = reinterpret_cast<const KIF::Upcall::ActivityWait *>(msg->data);
+    capsel_t act = upcall->act_sel;
+    int exitcode = static_cast<int>(upcall->exitcode);
+    reply_vmsg(is, 0);
+
+    for(auto &job : _jobs) {
+        size_t idx = job->find(act);
+        if(idx != MAX_CMDS) {
+            job->exited(idx, exitcode);
+            // report finished background jobs; the foreground job is removed by our caller
+            if(job->finished() && job.get() != _fg) {
+                println("[{}]  Done {}"_cf, job->id(), job->name());
+                remove(job.get());
+            }
+            break;
+        }
+    }
+    return true;
+}
+
+void Jobs::remove(Job *job) {
+    _jobs.erase(std::remove_if(_jobs.begin(), _jobs.end(),
+                               [job](const std::unique_ptr<Job> &j) {
+                                   return j.get() == job;
+                               }),
+                _jobs.end());
+}
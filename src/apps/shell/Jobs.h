@@ -0,0 +1,161 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+#pragma once
+
+#include <base/Common.h>
+
+#include <m3/accel/StreamAccel.h>
+#include <m3/pipe/IndirectPipe.h>
+#include <m3/session/VTerm.h>
+#include <m3/tiles/ActivityGroup.h>
+#include <m3/tiles/ChildActivity.h>
+#include <m3/tiles/Tile.h>
+
+#include <memory>
+#include <string>
+#include <vector>
+
+#include "Parser.h"
+
+static constexpr size_t MAX_CMDS = 8; // TODO get rid of this limit
+
+/**
+ * A job is a pipeline of commands. All activities of the pipeline form an ActivityGroup so that
+ * they are suspended, resumed, and interrupted together.
+ */
+class Job {
+    friend class Jobs;
+
+public:
+    enum State {
+        RUNNING,
+        STOPPED,
+    };
+
+    explicit Job(std::unique_ptr<Parser::CmdList> &&cmds);
+
+    /**
+     * Creates the activities, pipes, etc. for the pipeline and starts all commands.
+     *
+     * @param pipesrv the pipe service
+     * @param vterm the virtual terminal (might be null)
+     */
+    void start(m3::Pipes &pipesrv, m3::VTerm *vterm);
+
+    size_t id() const {
+        return _id;
+    }
+    const char *name() const {
+        return _name.c_str();
+    }
+    State state() const {
+        return _state;
+    }
+
+    /**
+     * @return true if all activities of this job are finished
+     */
+    bool finished() const {
+        return _group.empty();
+    }
+
+private:
+    const char *cmd_name(size_t i) const;
+    size_t find(capsel_t act) const;
+    void remove(size_t i);
+    void exited(size_t i, int exitcode);
+    void interrupt();
+
+    size_t _id;
+    std::string _name;
+    State _state;
+    std::unique_ptr<Parser::CmdList> _cmds;
+    std::unique_ptr<m3::IndirectPipe> _pipes[MAX_CMDS];
+    std::unique_ptr<m3::MemCap> _mems[MAX_CMDS];
+    // destroy the activities first to prevent errors due to destroyed communication channels
+    std::unique_ptr<m3::StreamAccel> _accels[MAX_CMDS];
+    m3::Reference<m3::Tile> _tiles[MAX_CMDS];
+    std::unique_ptr<m3::ChildActivity> _acts[MAX_CMDS];
+    m3::FileRef<m3::File> _infile;
+    m3::FileRef<m3::File> _outfile;
+    m3::FileRef<m3::File> _errfile;
+    m3::FileRef<m3::File> _clones[MAX_CMDS * 2];
+    m3::ActivityGroup _group;
+};
+
+/**
+ * The job control of the shell.
+ *
+ * There is at most one foreground job that receives the signals of the terminal (^C and ^Z). All
+ * other jobs are either stopped or run in the background. Background jobs that finished are
+ * reported before the next prompt.
+ */
+class Jobs {
+    // ActivityWait syscalls support at most 32 activities
+    static constexpr size_t MAX_JOBS = 4;
+
+public:
+    /**
+     * Sets whether the shell runs on a terminal that delivers signals
+     */
+    static void set_terminal(bool have_vterm) {
+        _have_vterm = have_vterm;
+    }
+
+    /**
+     * Adds the given job to the list of jobs, if it has running activities. Otherwise, the job is
+     * destroyed.
+     *
+     * @return the job, if it has been added
+     */
+    static Job *add(std::unique_ptr<Job> &&job);
+
+    /**
+     * @param id the job id (0 refers to the most recent job)
+     * @return the job with given id or nullptr
+     */
+    static Job *get(size_t id);
+
+    /**
+     * Continues the given job in the foreground and waits until it is finished or stopped.
+     */
+    static void foreground(Job *job);
+
+    /**
+     * Continues the given job in the background.
+     */
+    static void background(Job *job);
+
+    /**
+     * Reports and removes all finished background jobs without blocking.
+     */
+    static void reap();
+
+    /**
+     * Prints all jobs to the given output stream.
+     */
+    static void print(m3::OStream &os);
+
+private:
+    static void wait_async();
+    static bool handle_upcall();
+    static void remove(Job *job);
+
+    static bool _have_vterm;
+    static size_t _next_id;
+    static Job *_fg;
+    static std::vector<std::unique_ptr<Job>> _jobs;
+};
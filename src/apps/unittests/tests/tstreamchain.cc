@@ -0,0 +1,129 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+#include <base/Common.h>
+
+#include <m3/Test.h>
+#include <m3/accel/StreamChain.h>
+#include <m3/session/Pipes.h>
+#include <m3/stream/Standard.h>
+#include <m3/tiles/Tile.h>
+#include <m3/vfs/VFS.h>
+
+#include "../unittests.h"
+
+using namespace m3;
+
+static const char *in_file = "/pat.bin";
+static const char *out_file = "/streamchain.out";
+
+static char buf1[1024];
+static char buf2[1024];
+
+static bool have_accels() {
+    // the tests need rot13 accelerators, which only exist on gem5
+    try {
+        Tile::get("rot13");
+        return true;
+    }
+    catch(const Exception &) {
+        println("No rot13 accelerators available; skipping test"_cf);
+        return false;
+    }
+}
+
+static void compare_files(const char *a, const char *b) {
+    auto fa = VFS::open(a, FILE_R);
+    auto fb = VFS::open(b, FILE_R);
+
+    size_t total = 0;
+    size_t count;
+    while((count = fa->read(buf1, sizeof(buf1)).unwrap()) > 0) {
+        WVASSERTEQ(fb->read(buf2, count).unwrap(), count);
+        WVASSERTEQ(memcmp(buf1, buf2, count), 0);
+        total += count;
+    }
+    WVASSERT(total > 0);
+    WVASSERTEQ(fb->read(buf2, sizeof(buf2)).unwrap(), 0u);
+}
+
+static void run_chain(Pipes *pipes) {
+    {
+        auto in = VFS::open(in_file, FILE_R | FILE_NEWSESS);
+        auto out = VFS::open(out_file, FILE_W | FILE_TRUNC | FILE_CREATE | FILE_NEWSESS);
+
+        auto chain = pipes ? std::make_unique<StreamChain>(*in, *out, *pipes)
+                           : std::make_unique<StreamChain>(*in, *out);
+        // applying rot13 twice yields the original data
+        chain->append("rot13", "first", CycleDuration::from_raw(1000));
+        chain->append("rot13", "second", CycleDuration::from_raw(1000));
+        WVASSERTEQ(chain->size(), 2u);
+        WVASSERTEQ(chain->running(), 0u);
+
+        chain->start();
+        WVASSERTEQ(chain->running(), 2u);
+
+        // the chain cannot be changed or started again afterwards
+        WVASSERTERR(Errors::INV_STATE, [&chain] {
+            chain->append("rot13", "third", CycleDuration::from_raw(1000));
+        });
+        WVASSERTERR(Errors::INV_STATE, [&chain] {
+            chain->start();
+        });
+
+        chain->wait();
+        WVASSERTEQ(chain->running(), 0u);
+    }
+
+    compare_files(in_file, out_file);
+    VFS::unlink(out_file);
+}
+
+static void empty_chain() {
+    auto in = VFS::open(in_file, FILE_R);
+    auto out = VFS::open(out_file, FILE_W | FILE_TRUNC | FILE_CREATE);
+
+    StreamChain chain(*in, *out);
+    WVASSERTEQ(chain.size(), 0u);
+    WVASSERTERR(Errors::INV_STATE, [&chain] {
+        chain.start();
+    });
+
+    // waiting for an empty chain returns immediately
+    chain.wait();
+
+    VFS::unlink(out_file);
+}
+
+static void direct_chain() {
+    if(!have_accels())
+        return;
+
+    run_chain(nullptr);
+}
+
+static void indirect_chain() {
+    if(!have_accels())
+        return;
+
+    Pipes pipes("pipes");
+    run_chain(&pipes);
+}
+
+void tstreamchain() {
+    RUN_TEST(empty_chain);
+    RUN_TEST(direct_chain);
+    RUN_TEST(indirect_chain);
+}
@@ -37,3 +37,4 @@ void tstream();
 void tpipe();
 void tstring();
 void tsgate();
+void tstreamchain();
@@ -70,6 +70,14 @@ public:
     uint64_t data_addr;
     uint64_t data_len;
 
+    uint64_t cloned;
+
+    uint64_t dl_objs_addr;
+    uint64_t dl_objs_len;
+
+    // the log settings (flags and format), changeable at runtime by the resource manager
+    uint64_t log_ctrl[3];
+
     void format(OStream &os, const FormatSpecs &) const {
         format_to(os, "platform     : {}\n"_cf, platform);
         format_to(os, "tile_id      : {}\n"_cf, tile_id);
@@ -99,6 +107,11 @@ public:
         format_to(os, "fds_len      : {:p}\n"_cf, fds_len);
         format_to(os, "data_addr    : {}\n"_cf, data_addr);
         format_to(os, "data_len     : {:p}\n"_cf, data_len);
+        format_to(os, "cloned       : {}\n"_cf, cloned);
+        format_to(os, "dl_objs_addr : {:p}\n"_cf, dl_objs_addr);
+        format_to(os, "dl_objs_len  : {}\n"_cf, dl_objs_len);
+        format_to(os, "log_flags    : {:#x}:{:#x}\n"_cf, log_ctrl[1], log_ctrl[0]);
+        format_to(os, "log_format   : {}\n"_cf, log_ctrl[2]);
     }
 } PACKED;
 
@@ -96,6 +96,11 @@ struct Errors {
         SOCKET_CLOSED,
         CONNECTION_FAILED,
         CONN_CLOSED,
+        // services
+        VERSION_MISMATCH,
+        DEADLOCK,
+        // activities
+        STACK_OVERFLOW,
     };
 
     /**
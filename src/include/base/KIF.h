@@ -166,12 +166,14 @@ struct KIF {
             CREATE_MAP,
             CREATE_ACT,
             CREATE_SEM,
+            CREATE_GANG,
             ALLOC_EPS,
 
             // capability operations
             ACTIVATE,
             ACT_CTRL,
             ACT_WAIT,
+            ACT_TIME,
             DERIVE_MEM,
             DERIVE_KMEM,
             DERIVE_TILE,
@@ -186,7 +188,9 @@ struct KIF {
             TILE_MEM,
             TILE_INFO,
             TILE_RESET,
+            TILE_CTRL,
             SEM_CTRL,
+            GANG_CTRL,
 
             // capability exchange
             EXCHANGE_SESS,
@@ -195,6 +199,7 @@ struct KIF {
 
             // misc
             RESET_STATS,
+            GET_STATS,
             NOOP,
 
             COUNT
@@ -204,11 +209,21 @@ struct KIF {
             VCTRL_INIT,
             VCTRL_START,
             VCTRL_STOP,
+            VCTRL_SUSPEND,
+            VCTRL_RESUME,
         };
 
         enum SemOp {
             SCTRL_UP,
             SCTRL_DOWN,
+            SCTRL_TRY_DOWN,
+            SCTRL_DOWN_FOR,
+            SCTRL_VALUE,
+        };
+
+        enum GangOp {
+            GCTRL_JOIN,
+            GCTRL_LEAVE,
         };
 
         struct CreateSrv : public DefaultRequest {
@@ -275,6 +290,11 @@ struct KIF {
             xfer_t value;
         } PACKED;
 
+        struct CreateGang : public DefaultRequest {
+            xfer_t dst_sel;
+            xfer_t size;
+        } PACKED;
+
         struct AllocEP : public DefaultRequest {
             xfer_t dst_sel;
             xfer_t act_sel;
@@ -310,6 +330,14 @@ struct KIF {
             xfer_t exitcode;
         } PACKED;
 
+        struct ActivityTime : public DefaultRequest {
+            xfer_t act_sel;
+        } PACKED;
+
+        struct ActivityTimeReply : public DefaultReply {
+            xfer_t nanos;
+        } PACKED;
+
         struct DeriveMem : public DefaultRequest {
             xfer_t act_sel;
             xfer_t dst_sel;
@@ -331,6 +359,7 @@ struct KIF {
             xfer_t eps;
             xfer_t time;
             xfer_t pts;
+            xfer_t irqs;
         } PACKED;
 
         struct DeriveSrv : public DefaultRequest {
@@ -410,6 +439,17 @@ struct KIF {
             xfer_t ep_count;
         } PACKED;
 
+        enum TileOp {
+            TCTRL_OFFLINE,
+            TCTRL_ONLINE,
+        };
+
+        struct TileCtrl : public DefaultRequest {
+            xfer_t tile_sel;
+            xfer_t op;
+            xfer_t desc;
+        } PACKED;
+
         enum MuxType {
             NONE,
             TILE_MUX,
@@ -435,13 +475,29 @@ struct KIF {
         struct SemCtrl : public DefaultRequest {
             xfer_t sem_sel;
             xfer_t op;
+            xfer_t timeout;
         } PACKED;
 
-        struct Exchange : public DefaultRequest {
+        struct SemCtrlReply : public DefaultReply {
+            xfer_t value;
+        } PACKED;
+
+        struct GangCtrl : public DefaultRequest {
+            xfer_t gang_sel;
             xfer_t act_sel;
+            xfer_t op;
+        } PACKED;
+
+        struct ExchangeDesc {
             xfer_t own_caps[2];
             xfer_t other_sel;
+        } PACKED;
+
+        struct Exchange : public DefaultRequest {
+            xfer_t act_sel;
             xfer_t obtain;
+            xfer_t desc_count;
+            ExchangeDesc descs[16];
         } PACKED;
 
         struct ExchangeSess : public DefaultRequest {
@@ -465,6 +521,23 @@ struct KIF {
         struct ResetStats : public DefaultRequest {
         } PACKED;
 
+        struct GetStats : public DefaultRequest {
+            xfer_t act_sel;
+        } PACKED;
+
+        struct GetStatsReply : public DefaultReply {
+            xfer_t obj_caps;
+            xfer_t map_caps;
+            xfer_t act_syscalls;
+            xfer_t syscalls;
+            xfer_t pending_msgs;
+            xfer_t delayed_queues;
+            xfer_t mem_total;
+            xfer_t mem_free;
+            xfer_t mem_largest;
+            xfer_t mem_fragments;
+        } PACKED;
+
         struct Noop : public DefaultRequest {
         } PACKED;
     };
@@ -479,7 +552,8 @@ struct KIF {
             OBTAIN,
             DELEGATE,
             CLOSE,
-            SHUTDOWN
+            SHUTDOWN,
+            REVOKED,
         };
 
         struct Open : public DefaultRequest {
@@ -513,6 +587,7 @@ struct KIF {
 
         struct ExchangeReply : public DefaultReply {
             ExchangeData data;
+            xfer_t notify_revoke;
         } PACKED;
 
         struct Close : public DefaultRequest {
@@ -521,6 +596,11 @@ struct KIF {
 
         struct Shutdown : public DefaultRequest {
         } PACKED;
+
+        struct Revoked : public DefaultRequest {
+            xfer_t sess;
+            xfer_t sel;
+        } PACKED;
     };
 
     /**
@@ -37,6 +37,33 @@ enum Operation : word_t {
     FLUSH_INV,
     INIT_TLS,
     NOOP,
+    FUTEX_WAIT,
+    FUTEX_WAKE,
+    REG_CRASH,
+    RESUME,
+    PMU_CONFIG,
+    PMU_READ,
+    CLAIM_IRQ,
+    MASK_IRQ,
+};
+
+/**
+ * The performance counters that TileMux virtualizes per activity
+ */
+enum PmuCounter : word_t {
+    CYCLES,
+    INSTRS,
+    CACHE_MISSES,
+};
+
+static constexpr size_t PMU_COUNTERS = 3;
+
+/**
+ * The values of the performance counters, as written by TileMux
+ */
+struct PmuValues {
+    word_t enabled;
+    uint64_t values[PMU_COUNTERS];
 };
 
 }
@@ -49,6 +76,8 @@ enum Operation : word_t {
 #        include "arch/x86_64/TMABI.h"
 #    elif defined(__arm__)
 #        include "arch/arm/TMABI.h"
+#    elif defined(__aarch64__)
+#        include "arch/aarch64/TMABI.h"
 #    elif defined(__riscv)
 #        include "arch/riscv/TMABI.h"
 #    else
@@ -92,6 +121,22 @@ struct TMIF {
     static Errors::Code init_tls(uintptr_t) {
         return Errors::NOT_SUP;
     }
+
+    static Errors::Code futex_wait(uintptr_t, uint32_t, TimeDuration) {
+        return Errors::NOT_SUP;
+    }
+
+    static Errors::Code futex_wake(uintptr_t, size_t) {
+        return Errors::NOT_SUP;
+    }
+
+    static Errors::Code pmu_config(word_t) {
+        return Errors::NOT_SUP;
+    }
+
+    static Errors::Code pmu_read(PmuValues *) {
+        return Errors::NOT_SUP;
+    }
 };
 
 #else
@@ -124,6 +169,28 @@ struct TMIF {
     static Errors::Code init_tls(uintptr_t virt) {
         return TMABI::call2(Operation::INIT_TLS, virt, 0);
     }
+
+    static Errors::Code futex_wait(uintptr_t addr, uint32_t expected, TimeDuration timeout) {
+        return TMABI::call3(Operation::FUTEX_WAIT, addr, expected, timeout.as_nanos());
+    }
+
+    static Errors::Code futex_wake(uintptr_t addr, size_t count) {
+        return TMABI::call2(Operation::FUTEX_WAKE, addr, count);
+    }
+
+    /**
+     * Enables the performance counters in <mask> (bit i = PmuCounter i) and resets them
+     */
+    static Errors::Code pmu_config(word_t mask) {
+        return TMABI::call1(Operation::PMU_CONFIG, mask);
+    }
+
+    /**
+     * Reads the performance counters of the current activity into <vals>
+     */
+    static Errors::Code pmu_read(PmuValues *vals) {
+        return TMABI::call1(Operation::PMU_READ, reinterpret_cast<word_t>(vals));
+    }
 };
 
 #endif
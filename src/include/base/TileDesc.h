@@ -49,6 +49,7 @@ enum class TileISA {
     IDE_DEV = 7,
     NIC_DEV = 8,
     SERIAL_DEV = 9,
+    USB_DEV = 10,
 };
 
 enum TileAttr {
@@ -59,6 +60,7 @@ enum TileAttr {
     IMEM = 1 << 4,
     IEPS = 1 << 5,
     KECACC = 1 << 6,
+    VEC = 1 << 7,
 };
 
 /**
@@ -121,7 +123,7 @@ struct TileDesc {
      */
     bool is_device() const {
         return isa() == TileISA::NIC_DEV || isa() == TileISA::IDE_DEV ||
-               isa() == TileISA::SERIAL_DEV;
+               isa() == TileISA::SERIAL_DEV || isa() == TileISA::USB_DEV;
     }
 
     /**
@@ -163,6 +165,13 @@ struct TileDesc {
         return (attr() & TileAttr::IEPS) != 0;
     }
 
+    /**
+     * @return true if the tile has a core with vector extension
+     */
+    bool has_vector() const {
+        return (attr() & TileAttr::VEC) != 0;
+    }
+
     /**
      * @return the starting address and size of the standard receive buffer space
      */
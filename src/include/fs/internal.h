@@ -68,8 +68,10 @@ using blockno_t = uint32_t;
 using time_t = uint32_t;
 
 enum {
-    INODE_DIR_COUNT = 3,
+    INODE_DIR_COUNT = 2,
     MAX_BLOCK_SIZE = 4096,
+    // version 0 had no journal; later versions only append fields after the checksum
+    FS_VERSION = 1,
 };
 
 constexpr inodeno_t INVALID_INO = static_cast<inodeno_t>(-1);
@@ -89,6 +91,8 @@ enum {
     FILE_CREATE = 32,
     FILE_NODATA = 64,
     FILE_NEWSESS = 128,
+    FILE_PIPELINE = 256,
+    FILE_ATOMIC = 512,
 };
 
 #if !defined(__tools__)
@@ -107,10 +111,14 @@ struct FileInfo {
     inodeno_t inode;
     mode_t mode;
     uint32_t links;
+    uint16_t uid;
+    uint16_t gid;
     size_t size;
     time_t lastaccess;
     time_t lastmod;
+    time_t lastchange;
     uint32_t blocksize;
+    uint32_t blocks;
     // for debugging
     uint32_t extents;
     blockno_t firstblock;
@@ -123,9 +131,12 @@ struct alignas(8) INode {
     uint16_t links;
     time_t lastaccess;
     time_t lastmod;
+    time_t lastchange;
     uint32_t extents;
     inodeno_t inode;
     mode_t mode;
+    uint16_t uid;
+    uint16_t gid;
     uint64_t size;
     Extent direct[INODE_DIR_COUNT];
     blockno_t indirect;
@@ -159,9 +170,12 @@ struct SuperBlock {
     blockno_t inode_blocks() const {
         return (total_inodes * sizeof(INode) + blocksize - 1) / blocksize;
     }
-    blockno_t first_data_block() const {
+    blockno_t first_journal_block() const {
         return first_inode_block() + inode_blocks();
     }
+    blockno_t first_data_block() const {
+        return first_journal_block() + journal_blocks;
+    }
     unsigned extents_per_block() const {
         return blocksize / sizeof(Extent);
     }
@@ -169,10 +183,33 @@ struct SuperBlock {
         return blocksize / sizeof(INode);
     }
     uint32_t get_checksum() const {
+        return get_checksum_v0() + version * 19 + journal_blocks * 23;
+    }
+    uint32_t get_checksum_v0() const {
         return 1 + blocksize * 2 + total_inodes * 3 + total_blocks * 5 + free_inodes * 7 +
                free_blocks * 11 + first_free_inode * 13 + first_free_block * 17;
     }
 
+    /**
+     * Converts the superblock of a file system with version 0 to the current version. Since these
+     * file systems have no space reserved for the journal, they are used without journal.
+     *
+     * @return true if the file system has the current version afterwards
+     */
+    bool migrate() {
+        // version 0 ends with the checksum; the following bytes are undefined
+        if(checksum != get_checksum() && checksum == get_checksum_v0()) {
+            version = 0;
+            journal_blocks = 0;
+        }
+        if(version == 0) {
+            version = FS_VERSION;
+            journal_blocks = 0;
+            checksum = get_checksum();
+        }
+        return version == FS_VERSION;
+    }
+
     uint32_t blocksize;
     uint32_t total_inodes;
     uint32_t total_blocks;
@@ -181,6 +218,9 @@ struct SuperBlock {
     uint32_t first_free_inode;
     uint32_t first_free_block;
     uint32_t checksum;
+    // the following fields have been added in version 1
+    uint32_t version;
+    uint32_t journal_blocks;
 } __attribute__((packed));
 
 class Bitmap {
@@ -278,40 +318,49 @@ static __attribute__((unused)) m3::INode read_inode(m3::inodeno_t ino) {
     return inode;
 }
 
-static __attribute__((unused)) m3::blockno_t get_block_no_rec(const m3::INode &ino,
-                                                              m3::blockno_t indirect, size_t &no,
-                                                              int layer) {
-    m3::blockno_t res = 0;
+static __attribute__((unused)) bool find_block_no_rec(const m3::INode &ino,
+                                                      m3::blockno_t indirect, size_t &no,
+                                                      int layer, m3::blockno_t &res) {
     std::unique_ptr<m3::Extent[]> extents(new m3::Extent[sb.extents_per_block()]);
     read_from_block(extents.get(), sb.blocksize, indirect);
     for(size_t i = 0; i < sb.extents_per_block(); ++i) {
         if(layer > 0) {
-            res = get_block_no_rec(ino, extents[i].start, no, layer - 1);
-            if(res)
-                break;
+            if(extents[i].start && find_block_no_rec(ino, extents[i].start, no, layer - 1, res))
+                return true;
         }
         else {
             if(extents[i].length > no) {
-                res = extents[i].start + no;
-                break;
+                // extents starting at block 0 are holes
+                res = extents[i].start ? extents[i].start + no : 0;
+                return true;
             }
             no -= extents[i].length;
         }
     }
-    return res;
+    return false;
 }
 
-static __attribute__((unused)) m3::blockno_t get_block_no(const m3::INode &ino, size_t no) {
+// returns true if the <no>th block is covered by the extents; <res> is 0 for holes
+static __attribute__((unused)) bool find_block_no(const m3::INode &ino, size_t no,
+                                                  m3::blockno_t &res) {
     for(size_t i = 0; i < m3::INODE_DIR_COUNT; ++i) {
-        if(ino.direct[i].length > no)
-            return ino.direct[i].start + no;
+        if(ino.direct[i].length > no) {
+            res = ino.direct[i].start ? ino.direct[i].start + no : 0;
+            return true;
+        }
         no -= ino.direct[i].length;
     }
 
-    m3::blockno_t res = get_block_no_rec(ino, ino.indirect, no, 0);
-    if(res)
+    if(ino.indirect && find_block_no_rec(ino, ino.indirect, no, 0, res))
+        return true;
+    return ino.dindirect && find_block_no_rec(ino, ino.dindirect, no, 1, res);
+}
+
+static __attribute__((unused)) m3::blockno_t get_block_no(const m3::INode &ino, size_t no) {
+    m3::blockno_t res;
+    if(find_block_no(ino, no, res))
         return res;
-    return get_block_no_rec(ino, ino.dindirect, no, 1);
+    return 0;
 }
 
 static __attribute__((unused)) unsigned first_free(m3::Bitmap &bm, unsigned total) {
@@ -0,0 +1,180 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+#pragma once
+
+#include <base/time/Duration.h>
+
+#include <m3/accel/StreamAccel.h>
+#include <m3/pipe/IndirectPipe.h>
+#include <m3/session/Pipes.h>
+#include <m3/tiles/ChildActivity.h>
+#include <m3/vfs/GenericFile.h>
+
+#include <memory>
+#include <vector>
+
+namespace m3 {
+
+/**
+ * A pipeline of stream accelerators that reads from a source file and writes to a sink file.
+ *
+ * The chain is built by appending accelerators in the order in which the data should flow through
+ * them. On start, the first accelerator is connected to the source, the last to the sink, and all
+ * others to their neighbors. Neighbors are either connected directly, so that each accelerator
+ * writes into the buffer of the next one and uses a send gate with a single credit to hand over
+ * the data (credit-based flow control), or indirectly via a pipe per connection.
+ *
+ * Example:
+ *   StreamChain chain(*in, *out);
+ *   chain.append("copy", "first", CycleDuration::from_raw(1000));
+ *   chain.append("rot13", "second", CycleDuration::from_raw(1000));
+ *   chain.start();
+ *   chain.wait();
+ */
+class StreamChain {
+    struct Stage {
+        explicit Stage(const Reference<Tile> &tile, const std::string_view &name,
+                       CycleDuration comptime);
+
+        Reference<Tile> tile;
+        std::unique_ptr<ChildActivity> act;
+        std::unique_ptr<StreamAccel> accel;
+        // the pipe to the next stage, if connected indirectly
+        std::unique_ptr<MemCap> pipe_mem;
+        std::unique_ptr<IndirectPipe> pipe;
+        bool running;
+    };
+
+public:
+    static const size_t DEF_PIPE_SIZE = 512 * 1024;
+
+    /**
+     * Creates an empty chain that connects the accelerators directly with each other.
+     *
+     * @param in the source of the data
+     * @param out the sink of the data
+     */
+    explicit StreamChain(GenericFile &in, GenericFile &out) noexcept
+        : StreamChain(in, out, nullptr, 0) {
+    }
+
+    /**
+     * Creates an empty chain that connects the accelerators via pipes of the given pipe service.
+     *
+     * @param in the source of the data
+     * @param out the sink of the data
+     * @param pipes the pipe service
+     * @param pipe_size the size of the shared memory for each pipe
+     */
+    explicit StreamChain(GenericFile &in, GenericFile &out, Pipes &pipes,
+                         size_t pipe_size = DEF_PIPE_SIZE) noexcept
+        : StreamChain(in, out, &pipes, pipe_size) {
+    }
+
+    StreamChain(const StreamChain &) = delete;
+    StreamChain &operator=(const StreamChain &) = delete;
+
+    /**
+     * @return the number of accelerators in this chain
+     */
+    size_t size() const noexcept {
+        return _stages.size();
+    }
+
+    /**
+     * @return the number of accelerators that have not terminated yet
+     */
+    size_t running() const noexcept;
+
+    /**
+     * Appends an accelerator on a tile with given description (see Tile::get) to the chain.
+     *
+     * @param tile the description of the tile (e.g., "copy")
+     * @param name the name of the activity
+     * @param comptime the computation time per KiB
+     */
+    void append(const char *tile, const std::string_view &name, CycleDuration comptime);
+
+    /**
+     * Appends an accelerator on the given tile to the chain.
+     *
+     * @param tile the tile
+     * @param name the name of the activity
+     * @param comptime the computation time per KiB
+     */
+    void append(const Reference<Tile> &tile, const std::string_view &name, CycleDuration comptime);
+
+    /**
+     * Connects all accelerators and starts them. Afterwards, no accelerators can be appended.
+     */
+    void start();
+
+    /**
+     * Adds the selectors of all running accelerators to the given array.
+     *
+     * @param sels the array of selectors
+     * @param count the number of selectors in the array (will be incremented)
+     */
+    void add_running(capsel_t *sels, size_t *count) const noexcept;
+
+    /**
+     * Notifies the chain that the given activity has terminated. If the accelerators are connected
+     * via pipes, the corresponding ends of the pipes are closed so that its neighbors notice the
+     * end of the stream.
+     *
+     * @param act the selector of the activity
+     * @param exitcode the exit code of the activity
+     * @return true if the activity belonged to this chain
+     */
+    bool terminated(capsel_t act, int exitcode);
+
+    /**
+     * Waits until all accelerators of this chain have terminated.
+     */
+    void wait() {
+        StreamChain *chain = this;
+        wait_all(&chain, 1);
+    }
+
+    /**
+     * Waits until all accelerators of the given chains have terminated.
+     *
+     * @param chains the chains
+     * @param num the number of chains
+     */
+    static void wait_all(StreamChain *const *chains, size_t num);
+
+private:
+    explicit StreamChain(GenericFile &in, GenericFile &out, Pipes *pipes, size_t pipe_size) noexcept
+        : _in(in),
+          _out(out),
+          _pipes(pipes),
+          _pipe_size(pipe_size),
+          _started(),
+          _stages() {
+    }
+
+    void connect();
+
+    GenericFile &_in;
+    GenericFile &_out;
+    Pipes *_pipes;
+    size_t _pipe_size;
+    bool _started;
+    std::vector<std::unique_ptr<Stage>> _stages;
+};
+
+}
@@ -39,6 +39,10 @@ struct File {
         GET_PATH,
         GET_TMODE,
         SET_TMODE,
+        GET_TERMIOS,
+        SET_TERMIOS,
+        GET_WIN_SIZE,
+        SET_WIN_SIZE,
         SET_DEST,
         ENABLE_NOTIFY,
         REQ_NOTIFY,
@@ -59,6 +63,8 @@ struct FileSystem {
         OPEN_PRIV,
         CLOSE_PRIV,
         CLONE_META,
+        SET_NEXT_DEST,
+        UTIME,
     };
 };
 
@@ -70,6 +76,13 @@ struct Pipe {
     };
 };
 
+struct Pty {
+    enum Operation {
+        OPEN_PAIR = File::REQ_NOTIFY + 1,
+        OPEN_SLAVE,
+    };
+};
+
 struct Net {
     enum Operation {
         BIND,
@@ -79,6 +92,11 @@ struct Net {
         CREATE,
         GET_IP,
         GET_NAMESRV,
+        ACCEPT,
+        SET_OPT,
+        GET_OPT,
+        JOIN_MULTICAST,
+        LEAVE_MULTICAST,
     };
 };
 
@@ -33,6 +33,21 @@ enum class SocketType {
     RAW     // IP
 };
 
+/**
+ * The options that can be set for sockets. All options have integer values; boolean options use 0
+ * and 1.
+ */
+enum class SocketOpt {
+    // The time-to-live (hop limit) of outgoing packets (0 = default of the net server)
+    TTL,
+    // Whether the local port can be shared with other sockets that enable this option as well
+    REUSE_ADDR,
+    // Whether Nagle's algorithm is disabled (TCP only; disabled by default)
+    NO_DELAY,
+    // The interval in milliseconds for keep-alive packets on idle connections (TCP only; 0 = off)
+    KEEP_ALIVE,
+};
+
 class IpAddr {
 public:
     explicit IpAddr() noexcept : _addr(0) {
@@ -52,6 +67,23 @@ public:
         _addr = addr;
     }
 
+    /**
+     * Creates an IpAddr from the representation used in the protocol with the net server. The
+     * address is transferred as an IPv6 address, using the IPv4-mapped form for IPv4 addresses.
+     * As only IPv4 is supported here, the upper part is ignored.
+     */
+    static IpAddr from_raw(const uint64_t raw[2]) noexcept {
+        return IpAddr(static_cast<uint32_t>(raw[1]));
+    }
+
+    /**
+     * Stores the representation used in the protocol with the net server into <raw>.
+     */
+    void to_raw(uint64_t raw[2]) const noexcept {
+        raw[0] = 0;
+        raw[1] = 0xFFFF00000000 | _addr;
+    }
+
     void format(OStream &os, const FormatSpecs &) const {
         format_to(os, "IPv4[{}.{}.{}.{}]"_cf, (_addr >> 24) & 0xFF, (_addr >> 16) & 0xFF,
                   (_addr >> 8) & 0xFF, (_addr >> 0) & 0xFF);
@@ -195,6 +195,23 @@ public:
      */
     virtual Option<size_t> recv(void *dst, size_t amount) = 0;
 
+    /**
+     * Sets the option <opt> of this socket to <value>.
+     *
+     * Throws an exception with NOT_SUP if the option is not supported for this type of socket and
+     * INV_ARGS if the value is out of range.
+     *
+     * @param opt the option
+     * @param value the new value
+     */
+    void set_option(SocketOpt opt, uint64_t value);
+
+    /**
+     * @param opt the option
+     * @return the current value of the option <opt>
+     */
+    uint64_t get_option(SocketOpt opt);
+
 protected:
     explicit Socket(int sd, capsel_t caps, Network &nm);
 
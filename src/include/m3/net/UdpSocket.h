@@ -93,6 +93,23 @@ public:
      */
     void bind(port_t port);
 
+    /**
+     * Joins the IPv4 multicast group <addr>.
+     *
+     * Afterwards, this socket receives the datagrams that are sent to the group and to the port
+     * this socket is bound to.
+     *
+     * @param addr the multicast group
+     */
+    void join_multicast(const IpAddr &addr);
+
+    /**
+     * Leaves the multicast group <addr>, which has been joined via join_multicast before.
+     *
+     * @param addr the multicast group
+     */
+    void leave_multicast(const IpAddr &addr);
+
     /**
      * Connects this socket to the given remote endpoint.
      *
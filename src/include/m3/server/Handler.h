@@ -33,7 +33,8 @@ public:
         : _in(in),
           _out(out),
           _is(in.args),
-          _os(out.args) {
+          _os(out.args),
+          _notify_revoke(false) {
     }
 
     ExchangeIStream &in_args() {
@@ -50,11 +51,23 @@ public:
         crd.to_raw(_out.caps);
     }
 
+    /**
+     * Requests a notification (see Handler::revoked) for each delegated capability once it gets
+     * revoked by someone else.
+     */
+    void notify_revoke() {
+        _notify_revoke = true;
+    }
+    bool notify_revoke_requested() const {
+        return _notify_revoke;
+    }
+
 private:
     const KIF::Service::ExchangeData &_in;
     KIF::Service::ExchangeData &_out;
     ExchangeIStream _is;
     ExchangeOStream _os;
+    bool _notify_revoke;
 };
 
 template<class SESS>
@@ -75,6 +88,8 @@ public:
     virtual Errors::Code close(SESS *sess, size_t crt) = 0;
     virtual void shutdown() {
     }
+    virtual void revoked(SESS *, size_t, capsel_t) {
+    }
 };
 
 }
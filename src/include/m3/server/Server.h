@@ -99,6 +99,7 @@ private:
         _ctrl_handler[KIF::Service::DELEGATE] = &Server::handle_delegate;
         _ctrl_handler[KIF::Service::CLOSE] = &Server::handle_close;
         _ctrl_handler[KIF::Service::SHUTDOWN] = &Server::handle_shutdown;
+        _ctrl_handler[KIF::Service::REVOKED] = &Server::handle_revoked;
     }
 
     void handle_message(GateIStream &is) {
@@ -197,6 +198,7 @@ private:
         reply.error = _handler->obtain(sess, crt, xchg);
 
         reply.data.args.bytes = xchg.out_args().total();
+        reply.notify_revoke = false;
         is.reply(reply_buf);
     }
 
@@ -216,6 +218,7 @@ private:
         reply.error = _handler->delegate(sess, crt, xchg);
 
         reply.data.args.bytes = xchg.out_args().total();
+        reply.notify_revoke = xchg.notify_revoke_requested();
         is.reply(reply_buf);
     }
 
@@ -236,6 +239,19 @@ private:
         reply_error(is, res);
     }
 
+    void handle_revoked(GateIStream &is) {
+        auto *req = reinterpret_cast<const KIF::Service::Revoked *>(is.message().data);
+        label_t crt = is.message().label;
+
+        LOG(LogFlags::LibServ, "{:#x}: revoked(sel={})"_cf, (word_t)req->sess, req->sel);
+
+        typename HDL::session_type *sess =
+            reinterpret_cast<typename HDL::session_type *>(req->sess);
+        _handler->revoked(sess, crt, req->sel);
+
+        reply_error(is, Errors::SUCCESS);
+    }
+
     void handle_shutdown(GateIStream &is) {
         LOG(LogFlags::LibServ, "shutdown()"_cf, 0);
 
@@ -257,7 +273,7 @@ private:
 
 protected:
     std::unique_ptr<HDL> _handler;
-    handler_func _ctrl_handler[KIF::Service::SHUTDOWN + 1];
+    handler_func _ctrl_handler[KIF::Service::REVOKED + 1];
     std::unique_ptr<Creator> _creators[MAX_CREATORS];
     RecvGate _rgate;
 };
@@ -95,24 +95,27 @@ private:
 
 template<>
 struct OStreamSize<FileInfo> {
-    static const size_t value = 10 * sizeof(xfer_t);
+    static const size_t value = 14 * sizeof(xfer_t);
 };
 
 static inline Unmarshaller &operator>>(Unmarshaller &u, FileInfo &info) noexcept {
-    u >> info.devno >> info.inode >> info.mode >> info.links >> info.size >> info.lastaccess >>
-        info.lastmod >> info.blocksize >> info.extents >> info.firstblock;
+    u >> info.devno >> info.inode >> info.mode >> info.links >> info.uid >> info.gid >> info.size >>
+        info.lastaccess >> info.lastmod >> info.lastchange >> info.blocksize >> info.blocks >>
+        info.extents >> info.firstblock;
     return u;
 }
 
 static inline GateIStream &operator>>(GateIStream &is, FileInfo &info) noexcept {
-    is >> info.devno >> info.inode >> info.mode >> info.links >> info.size >> info.lastaccess >>
-        info.lastmod >> info.blocksize >> info.extents >> info.firstblock;
+    is >> info.devno >> info.inode >> info.mode >> info.links >> info.uid >> info.gid >>
+        info.size >> info.lastaccess >> info.lastmod >> info.lastchange >> info.blocksize >>
+        info.blocks >> info.extents >> info.firstblock;
     return is;
 }
 
 static inline Marshaller &operator<<(Marshaller &m, const FileInfo &info) noexcept {
-    m << info.devno << info.inode << info.mode << info.links << info.size << info.lastaccess
-      << info.lastmod << info.blocksize << info.extents << info.firstblock;
+    m << info.devno << info.inode << info.mode << info.links << info.uid << info.gid << info.size
+      << info.lastaccess << info.lastmod << info.lastchange << info.blocksize << info.blocks
+      << info.extents << info.firstblock;
     return m;
 }
 
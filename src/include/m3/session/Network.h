@@ -66,6 +66,10 @@ private:
     IpAddr listen(int32_t sd, port_t port);
     Endpoint connect_socket(int32_t sd, Endpoint remote_ep);
     void abort(int32_t sd, bool remove);
+    void set_option(int32_t sd, SocketOpt opt, uint64_t value);
+    uint64_t get_option(int32_t sd, SocketOpt opt);
+    void join_multicast(int32_t sd, const IpAddr &addr);
+    void leave_multicast(int32_t sd, const IpAddr &addr);
 
     SendGate _sgate;
 };
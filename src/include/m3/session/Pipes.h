@@ -47,7 +47,7 @@ public:
             os << opcodes::Pipe::OPEN_CHAN << read;
             args.bytes = os.total();
             KIF::CapRngDesc desc = obtain(2, &args);
-            flags |= FILE_NEWSESS | (read ? FILE_R : FILE_W);
+            flags |= FILE_NEWSESS | (read ? FILE_R : FILE_W | FILE_ATOMIC);
             auto file = std::unique_ptr<GenericFile>(
                 new GenericFile(flags, desc.start(), static_cast<size_t>(-1)));
             return Activity::own().files()->alloc(std::move(file));
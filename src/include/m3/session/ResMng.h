@@ -72,9 +72,10 @@ public:
     std::unique_ptr<ResMngChild> clone(ChildActivity &act, capsel_t sgate_sel,
                                        const std::string_view &name);
 
-    void reg_service(capsel_t dst, capsel_t sgate, const std::string_view &name, size_t sessions) {
-        GateIStream reply =
-            send_receive_vmsg(_sgate, opcodes::ResMng::REG_SERV, dst, sgate, name, sessions);
+    void reg_service(capsel_t dst, capsel_t sgate, const std::string_view &name, size_t sessions,
+                     uint16_t major = 0, uint16_t minor = 0) {
+        GateIStream reply = send_receive_vmsg(_sgate, opcodes::ResMng::REG_SERV, dst, sgate, name,
+                                              sessions, major, minor);
         retrieve_result(opcodes::ResMng::REG_SERV, reply);
     }
 
@@ -104,8 +105,10 @@ public:
     }
 
     TileDesc alloc_tile(capsel_t sel, const TileDesc &desc, bool init) {
-        GateIStream reply =
-            send_receive_vmsg(_sgate, opcodes::ResMng::ALLOC_TILE, sel, desc.value(), init);
+        // no specific tile id and no tile to be close to
+        const xfer_t none = static_cast<xfer_t>(-1);
+        GateIStream reply = send_receive_vmsg(_sgate, opcodes::ResMng::ALLOC_TILE, sel,
+                                              desc.value(), init, none, none);
         retrieve_result(opcodes::ResMng::ALLOC_TILE, reply);
         TileDesc::value_t res;
         TileId::raw_t tileid;
@@ -0,0 +1,123 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+#pragma once
+
+#include <m3/tiles/ChildActivity.h>
+
+#include <algorithm>
+#include <vector>
+
+namespace m3 {
+
+/**
+ * A group of child activities that is controlled as a unit, similar to a process group on UNIX.
+ *
+ * For example, a shell puts all activities of a pipeline into one group to suspend, resume, or
+ * stop them together. The group does not own the activities, but only refers to them. Therefore,
+ * activities need to be removed from the group before they are destroyed.
+ */
+class ActivityGroup {
+public:
+    explicit ActivityGroup() noexcept : _acts(), _suspended() {
+    }
+
+    /**
+     * @return the number of activities in this group
+     */
+    size_t size() const noexcept {
+        return _acts.size();
+    }
+
+    /**
+     * @return true if this group has no activities
+     */
+    bool empty() const noexcept {
+        return _acts.empty();
+    }
+
+    /**
+     * @return true if the group is currently suspended
+     */
+    bool suspended() const noexcept {
+        return _suspended;
+    }
+
+    /**
+     * @param act the selector of the activity
+     * @return true if the activity with given selector is part of this group
+     */
+    bool contains(capsel_t act) const noexcept {
+        return std::any_of(_acts.begin(), _acts.end(), [act](const ChildActivity *a) {
+            return a->sel() == act;
+        });
+    }
+
+    /**
+     * Adds the given activity to this group
+     *
+     * @param act the activity
+     */
+    void add(ChildActivity *act) {
+        _acts.push_back(act);
+    }
+
+    /**
+     * Removes the activity with given selector from this group
+     *
+     * @param act the selector of the activity
+     */
+    void remove(capsel_t act) {
+        _acts.erase(std::remove_if(_acts.begin(), _acts.end(),
+                                   [act](const ChildActivity *a) {
+                                       return a->sel() == act;
+                                   }),
+                    _acts.end());
+    }
+
+    /**
+     * Stores the selectors of all activities in this group into <sels>.
+     *
+     * @param sels the array of selectors (needs to have room for size() selectors)
+     * @return the number of selectors
+     */
+    size_t sels(capsel_t *sels) const noexcept {
+        for(size_t i = 0; i < _acts.size(); ++i)
+            sels[i] = _acts[i]->sel();
+        return _acts.size();
+    }
+
+    /**
+     * Suspends all activities in this group. Activities on tiles without TileMux (e.g.,
+     * accelerators) cannot be suspended and keep running.
+     */
+    void suspend();
+
+    /**
+     * Resumes all activities in this group that have been suspended before.
+     */
+    void resume();
+
+    /**
+     * Stops all activities in this group.
+     */
+    void stop();
+
+private:
+    std::vector<ChildActivity *> _acts;
+    bool _suspended;
+};
+
+}
@@ -186,6 +186,17 @@ public:
      */
     void stop();
 
+    /**
+     * Suspends the activity, i.e., it is no longer scheduled until it is resumed. This requires
+     * that the activity runs on a tile with TileMux.
+     */
+    void suspend();
+
+    /**
+     * Resumes the previously suspended activity.
+     */
+    void resume();
+
     /**
      * Waits until the currently executing program on this activity is finished
      *
@@ -97,7 +97,7 @@ public:
      * one
      *
      * @param eps the number of EPs to transfer (None = share the quota)
-     * @param time the time slice length to transfer (None = share the quota)
+     * @param time the time slice length within the budget of this tile (None = share the quota)
      * @param pts the number of page tables to transfer (None = share the quota)
      * @return the new tile object
      */
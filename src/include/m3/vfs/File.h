@@ -59,6 +59,7 @@ public:
         INPUT = 1,
         OUTPUT = 2,
         SIGNAL = 4,
+        SUSPEND = 8,
     };
 
     static constexpr size_t NOTIFY_MSG_SIZE = 64;
@@ -327,6 +328,16 @@ public:
         throw Exception(Errors::NOT_SUP);
     }
 
+    /**
+     * Tries to fetch a suspend request (e.g., due to ^Z) from the file, if any. This works like
+     * fetch_signal, but for the SUSPEND event.
+     *
+     * @return true if a suspend request was found
+     */
+    virtual bool fetch_suspend() {
+        throw Exception(Errors::NOT_SUP);
+    }
+
     /**
      * Checks whether any of the given events has arrived.
      *
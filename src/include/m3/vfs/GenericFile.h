@@ -72,6 +72,7 @@ public:
     virtual void set_tmode(TMode mode) override;
 
     virtual bool fetch_signal() override;
+    virtual bool fetch_suspend() override;
 
     virtual char type() const noexcept override {
         return 'F';
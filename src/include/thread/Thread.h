@@ -27,6 +27,8 @@
 #    include <thread/isa/x86_64/Thread.h>
 #elif defined(__arm__)
 #    include <thread/isa/arm/Thread.h>
+#elif defined(__aarch64__)
+#    include <thread/isa/aarch64/Thread.h>
 #elif defined(__riscv)
 #    include <thread/isa/riscv/Thread.h>
 #else
@@ -37,7 +39,7 @@ namespace m3 {
 
 typedef void (*thread_func)(void *);
 
-void thread_init(thread_func func, void *arg, Regs *regs, word_t *stack);
+void thread_init(thread_func func, void *arg, Regs *regs, word_t *stack, size_t stack_words);
 
 class ThreadManager;
 
@@ -45,17 +47,43 @@ class Thread : public SListItem {
     friend class ThreadManager;
 
     static constexpr size_t T_STACK_WORDS = m3::T_STACK_WORDS;
-    static constexpr size_t T_STACK_SZ = T_STACK_WORDS * sizeof(word_t);
     static constexpr size_t MAX_MSG_SIZE = 1024;
 
+    // the value and number of words at the bottom of each stack to detect stack overflows
+    static constexpr word_t STACK_CANARY = 0x5AFE57AC;
+    static constexpr size_t STACK_CANARY_WORDS = 4;
+
 public:
-    explicit Thread(thread_func func, void *arg);
+    static constexpr size_t T_STACK_SZ = T_STACK_WORDS * sizeof(word_t);
+    static constexpr size_t MIN_STACK_SZ = 4096;
+
+    /**
+     * Creates a new thread that executes <func> with argument <arg> on a stack of <stack_size>
+     * bytes. The thread is idle until it is chosen by the ThreadManager.
+     *
+     * Threads have no guard pages, because their stacks are allocated on the heap. Instead, the
+     * bottom of each stack contains a canary, which is checked whenever the ThreadManager switches
+     * away from a thread. An overflow therefore results in a panic.
+     *
+     * @param func the function to execute
+     * @param arg the argument for the function
+     * @param stack_size the size of the stack (at least MIN_STACK_SZ)
+     */
+    explicit Thread(thread_func func, void *arg, size_t stack_size = T_STACK_SZ);
     ~Thread();
 
 private:
-    explicit Thread() : _id(_next_id++), _regs(), _stack(), _event(0), _content(false) {
+    explicit Thread()
+        : _id(_next_id++),
+          _regs(),
+          _stack(),
+          _stack_size(),
+          _event(0),
+          _content(false) {
     }
 
+    void check_stack() const;
+
     void subscribe(event_t event) {
         _event = event;
     }
@@ -76,6 +104,9 @@ public:
     const Regs &regs() const {
         return _regs;
     }
+    size_t stack_size() const {
+        return _stack_size;
+    }
     inline bool trigger_event(event_t event) const {
         return _event == event;
     }
@@ -87,6 +118,7 @@ private:
     int _id;
     Regs _regs;
     word_t *_stack;
+    size_t _stack_size;
     event_t _event;
     bool _content;
     unsigned char _msg[MAX_MSG_SIZE];
@@ -123,6 +123,7 @@ private:
     }
 
     void switch_to(Thread *t) {
+        _current->check_stack();
         LOG(LogFlags::LibThread, "Switching from {} to {}"_cf, _current->id(), t->id());
         auto old = _current;
         _current = t;
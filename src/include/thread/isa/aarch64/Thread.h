@@ -0,0 +1,43 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+#pragma once
+
+#include <base/Types.h>
+
+namespace m3 {
+
+struct Regs {
+    word_t x0;
+    word_t x19;
+    word_t x20;
+    word_t x21;
+    word_t x22;
+    word_t x23;
+    word_t x24;
+    word_t x25;
+    word_t x26;
+    word_t x27;
+    word_t x28;
+    word_t x29; // frame pointer
+    word_t x30; // link register
+    word_t sp;
+} PACKED;
+
+enum {
+    T_STACK_WORDS = 4096
+};
+
+}
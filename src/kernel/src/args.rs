@@ -18,9 +18,13 @@ use base::cfg;
 use base::col::Vec;
 use base::env;
 
+use crate::com::{QueuePolicy, DEF_QUEUE_LIMIT};
+
 pub struct Args {
     pub kmem: usize,
     pub root_eps: usize,
+    pub squeue_limit: usize,
+    pub squeue_policy: QueuePolicy,
 }
 
 impl Default for Args {
@@ -28,6 +32,8 @@ impl Default for Args {
         Self {
             kmem: 64 * 1024 * 1024,
             root_eps: cfg::DEF_EP_COUNT,
+            squeue_limit: DEF_QUEUE_LIMIT,
+            squeue_policy: QueuePolicy::Block,
         }
     }
 }
@@ -65,6 +71,12 @@ pub fn parse() {
             }
             args.root_eps = ep_count;
         }
+        else if argv[i] == "-q:len" {
+            args.squeue_limit = get_size_arg(&argv, &mut i);
+        }
+        else if argv[i] == "-q:err" {
+            args.squeue_policy = QueuePolicy::Error;
+        }
         i += 1;
     }
 
@@ -73,8 +85,11 @@ pub fn parse() {
 
 fn usage() -> ! {
     panic!(
-        "\nUsage: {} [-m <kmem>]
-          -m: the kernel memory size (> FIXED_KMEM)",
+        "\nUsage: {} [-m <kmem>] [-r:eps <count>] [-q:len <count>] [-q:err]
+          -m: the kernel memory size (> FIXED_KMEM)
+          -r:eps: the number of endpoints for root
+          -q:len: the max. number of queued messages per service (default: 16, 0 = unlimited)
+          -q:err: let requests to services with full queues fail instead of blocking",
         env::args().next().unwrap()
     );
 }
@@ -21,13 +21,13 @@ use base::io::LogFlags;
 use base::kif::{CapRngDesc, CapSel, SEL_ACT, SEL_KMEM, SEL_TILE};
 use base::log;
 use base::mem::{size_of, GlobOff, VirtAddr};
-use base::rc::Rc;
+use base::rc::{Rc, SRc};
 use base::tcu::ActId;
 use core::cmp;
 use core::fmt;
 use core::ptr::NonNull;
 
-use crate::cap::{EPObject, GateEP, KObject};
+use crate::cap::{EPObject, GateEP, KObject, SessObject};
 use crate::ktcu;
 use crate::tiles::{tilemng, Activity, ActivityMng, INVAL_ID};
 
@@ -101,6 +101,10 @@ impl CapTable {
         self.act = Some(act_ptr);
     }
 
+    pub fn count(&self) -> usize {
+        self.caps.len()
+    }
+
     pub fn is_empty(&self) -> bool {
         self.caps.is_empty()
     }
@@ -192,6 +196,7 @@ impl CapTable {
         let mut nc: Capability = (*cap).clone();
         nc.sels = SelRange::new(sel);
         nc.derived = true;
+        nc.notify = None;
 
         let nc = self.do_insert(nc);
         log!(LogFlags::KernCaps, "Cloning cap {:?}", nc);
@@ -277,6 +282,8 @@ pub struct Capability {
     next: Option<NonNull<Capability>>,
     prev: Option<NonNull<Capability>>,
     derived: bool,
+    // the session whose server is notified when this capability is revoked
+    notify: Option<SRc<SessObject>>,
 }
 
 impl Capability {
@@ -299,6 +306,7 @@ impl Capability {
             next: None,
             prev: None,
             derived: false,
+            notify: None,
         }
     }
 
@@ -318,6 +326,10 @@ impl Capability {
         &self.obj
     }
 
+    pub fn set_revoke_notify(&mut self, sess: SRc<SessObject>) {
+        self.notify = Some(sess);
+    }
+
     pub fn has_parent(&self) -> bool {
         self.parent.is_some()
     }
@@ -472,6 +484,7 @@ impl Capability {
     fn release_async(mut self, revoker: ActId) {
         log!(LogFlags::KernCaps, "Freeing cap {:?}", self);
 
+        let notify = self.notify.take();
         let act = self.activity();
         let sel = self.sel();
         if !self.derived {
@@ -484,6 +497,10 @@ impl Capability {
             act.kmem().free(act, sel, Capability::size());
         }
 
+        if let Some(sess) = notify {
+            sess.revoked_async(sel, revoker);
+        }
+
         match self.obj {
             KObject::Activity(ref v) => {
                 // remove activity if we revoked the root capability and if it's not the own activity
@@ -561,6 +578,9 @@ impl Capability {
             KObject::Sem(ref s) => {
                 s.revoke();
             },
+
+            // members keep the gang alive as long as they exist
+            KObject::Gang(_) => {},
         }
     }
 }
@@ -16,6 +16,7 @@
 use base::build_vmsg;
 use base::cell::{Cell, Ref, RefCell, RefMut, StaticCell};
 use base::cfg;
+use base::col::Vec;
 use base::env;
 use base::errors::{Code, Error};
 use base::io::LogFlags;
@@ -24,6 +25,7 @@ use base::log;
 use base::mem::{size_of, GlobAddr, GlobOff, MsgBuf, PhysAddr, VirtAddr};
 use base::rc::{Rc, SRc, Weak};
 use base::tcu::{ActId, EpId, Label, TileId};
+use base::time::{TimeDuration, TimeInstant};
 
 use core::fmt;
 use core::ptr;
@@ -48,6 +50,7 @@ pub enum KObject {
     KMem(SRc<KMemObject>),
     Tile(SRc<TileObject>),
     EP(Rc<EPObject>),
+    Gang(SRc<GangObject>),
 }
 
 const fn kobj_size<T>() -> usize {
@@ -66,7 +69,7 @@ const fn kobj_size<T>() -> usize {
     }
 }
 
-static KOBJ_SIZES: [usize; 11] = [
+static KOBJ_SIZES: [usize; 12] = [
     kobj_size::<SGateObject>(),
     kobj_size::<RGateObject>(),
     kobj_size::<MGateObject>(),
@@ -79,6 +82,7 @@ static KOBJ_SIZES: [usize; 11] = [
     // assume pessimistically that each TileObject has its own EPQuota
     kobj_size::<TileObject>() + kobj_size::<EPQuota>(),
     kobj_size::<EPObject>(),
+    kobj_size::<GangObject>(),
 ];
 
 impl KObject {
@@ -112,6 +116,7 @@ impl fmt::Debug for KObject {
             KObject::KMem(k) => write!(f, "{:?}", k),
             KObject::Tile(p) => write!(f, "{:?}", p),
             KObject::EP(e) => write!(f, "{:?}", e),
+            KObject::Gang(g) => write!(f, "{:?}", g),
         }
     }
 }
@@ -474,6 +479,35 @@ impl SessObject {
                 .unwrap();
         }
     }
+
+    pub fn revoked_async(&self, sel: kif::CapSel, revoker: ActId) {
+        let serv_act = self.srv.service().activity();
+        // don't notify the server if it revoked the capability itself or is already gone
+        if serv_act.id() == revoker || serv_act.state() == State::DEAD {
+            return;
+        }
+
+        log!(
+            LogFlags::KernServ,
+            "Sending revoked(sess={:#x}, sel={}) to service {} with creator {}",
+            self.ident(),
+            sel,
+            self.srv.service().name(),
+            self.creator,
+        );
+
+        let mut smsg = MsgBuf::borrow_def();
+        build_vmsg!(smsg, service::Request::Revoked {
+            sid: self.ident,
+            sel
+        });
+
+        // the notification is only a hint; ignore failures if the server is not reachable anymore
+        self.srv
+            .service()
+            .send_receive_async(self.creator as Label, smsg)
+            .ok();
+    }
 }
 
 impl fmt::Debug for SessObject {
@@ -501,20 +535,54 @@ impl SemObject {
         })
     }
 
-    pub fn down_async(sem: &SRc<Self>) -> Result<(), Error> {
+    pub fn value(&self) -> u32 {
+        self.counter.get()
+    }
+
+    /// Waits until the counter is non-zero and decrements it
+    ///
+    /// If `timeout` is given, the wait is aborted with `Code::Timeout` after the given time.
+    pub fn down_async(sem: &SRc<Self>, timeout: Option<TimeDuration>) -> Result<(), Error> {
+        let deadline = timeout.map(|t| TimeInstant::now() + t);
         while unsafe { ptr::read_volatile(sem.counter.as_ptr()) } == 0 {
+            let remaining = match deadline {
+                Some(end) => match end.checked_duration_since(TimeInstant::now()) {
+                    Some(rem) if rem > TimeDuration::ZERO => Some(rem),
+                    _ => return Err(Error::new(Code::Timeout)),
+                },
+                None => None,
+            };
+
             sem.waiters.set(sem.waiters.get() + 1);
             let event = sem.get_event();
-            thread::wait_for(event);
+            let res = match remaining {
+                Some(rem) => thread::wait_for_timeout(event, rem),
+                None => {
+                    thread::wait_for(event);
+                    Ok(())
+                },
+            };
             if unsafe { ptr::read_volatile(sem.waiters.as_ptr()) } == -1 {
                 return Err(Error::new(Code::RecvGone));
             }
             sem.waiters.set(sem.waiters.get() - 1);
+            res?;
         }
         sem.counter.set(sem.counter.get() - 1);
         Ok(())
     }
 
+    /// Decrements the counter if it is non-zero and returns whether it was decremented
+    pub fn try_down(&self) -> bool {
+        match self.counter.get() {
+            0 => false,
+            c => {
+                self.counter.set(c - 1);
+                true
+            },
+        }
+    }
+
     pub fn up(&self) {
         if self.waiters.get() > 0 {
             thread::notify(self.get_event(), None);
@@ -545,6 +613,106 @@ impl fmt::Debug for SemObject {
     }
 }
 
+pub struct GangObject {
+    size: u32,
+    // the members and whether they have requested to be started
+    members: RefCell<Vec<(Weak<Activity>, bool)>>,
+    started: Cell<bool>,
+}
+
+impl GangObject {
+    pub fn new(size: u32) -> SRc<Self> {
+        SRc::new(Self {
+            size,
+            members: RefCell::from(Vec::new()),
+            started: Cell::from(false),
+        })
+    }
+
+    pub fn started(&self) -> bool {
+        self.started.get()
+    }
+
+    /// Adds the not yet started activity `act` to the gang
+    pub fn join(gang: &SRc<Self>, act: &Rc<Activity>) -> Result<(), Error> {
+        if gang.started.get() || act.state() != State::INIT {
+            return Err(Error::new(Code::InvState));
+        }
+        if act.gang().is_some() {
+            return Err(Error::new(Code::Exists));
+        }
+
+        let mut members = gang.members.borrow_mut();
+        // activities that have been destroyed before the gang was started are no members anymore
+        members.retain(|(m, _)| m.upgrade().is_some());
+        if members.len() >= gang.size as usize {
+            return Err(Error::new(Code::NoSpace));
+        }
+
+        members.push((Rc::downgrade(act), false));
+        act.set_gang(Some(gang.clone()));
+        Ok(())
+    }
+
+    /// Removes the not yet started activity `act` from the gang
+    pub fn leave(&self, act: &Activity) -> Result<(), Error> {
+        if self.started.get() {
+            return Err(Error::new(Code::InvState));
+        }
+
+        let mut members = self.members.borrow_mut();
+        let pos = members
+            .iter()
+            .position(|(m, _)| m.upgrade().map(|m| m.id()) == Some(act.id()))
+            .ok_or_else(|| Error::new(Code::NotFound))?;
+        members.remove(pos);
+        act.set_gang(None);
+        Ok(())
+    }
+
+    /// Records that `act` should be started.
+    ///
+    /// Returns all members that need to be started now, which is either nobody (if not all members
+    /// have joined and requested to be started yet) or all members.
+    pub fn request_start(&self, act: &Activity) -> Vec<Rc<Activity>> {
+        let mut members = self.members.borrow_mut();
+        members.retain(|(m, _)| m.upgrade().is_some());
+        for (m, start) in members.iter_mut() {
+            if m.upgrade().map(|m| m.id()) == Some(act.id()) {
+                *start = true;
+            }
+        }
+
+        if members.len() != self.size as usize || !members.iter().all(|(_, start)| *start) {
+            return Vec::new();
+        }
+
+        self.started.set(true);
+        members.iter().filter_map(|(m, _)| m.upgrade()).collect()
+    }
+
+    /// Returns all members of the gang that still exist
+    pub fn members(&self) -> Vec<Rc<Activity>> {
+        self.members
+            .borrow()
+            .iter()
+            .filter_map(|(m, _)| m.upgrade())
+            .collect()
+    }
+}
+
+impl fmt::Debug for GangObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Gang[size={}, members={}, started={}]",
+            self.size,
+            self.members.borrow().len(),
+            self.started.get()
+        )
+    }
+}
+
 pub struct EPQuota {
     id: QuotaId,
     total: Cell<usize>,
@@ -583,6 +751,7 @@ pub struct TileObject {
     ep_quota: Rc<EPQuota>,
     time_quota: QuotaId,
     pt_quota: QuotaId,
+    irqs: u32,
     derived: bool,
 }
 
@@ -592,6 +761,7 @@ impl TileObject {
         ep_quota: Rc<EPQuota>,
         time_quota: QuotaId,
         pt_quota: QuotaId,
+        irqs: u32,
         derived: bool,
     ) -> SRc<Self> {
         let res = SRc::new(Self {
@@ -600,17 +770,19 @@ impl TileObject {
             ep_quota: ep_quota.clone(),
             time_quota,
             pt_quota,
+            irqs,
             derived,
         });
         log!(
             LogFlags::KernTiles,
-            "Tile[{}, {:#x}]: {} new TileObject with EPs={}, time={}, pts={}",
+            "Tile[{}, {:#x}]: {} new TileObject with EPs={}, time={}, pts={}, irqs={:#x}",
             tile,
             &*res as *const _ as usize,
             if derived { "derived" } else { "created" },
             ep_quota.total(),
             time_quota,
             pt_quota,
+            irqs,
         );
         res
     }
@@ -639,6 +811,11 @@ impl TileObject {
         self.pt_quota
     }
 
+    /// Returns the mask of external interrupts that activities on this tile may use
+    pub fn irqs(&self) -> u32 {
+        self.irqs
+    }
+
     pub fn has_quota(&self, eps: usize) -> bool {
         self.ep_quota.left() >= eps
     }
@@ -27,9 +27,26 @@ use crate::ktcu;
 
 pub const MAX_PENDING_MSGS: usize = 4;
 
+/// The default number of messages that can be queued per service (see [`SendQueue::set_limit`])
+pub const DEF_QUEUE_LIMIT: usize = 16;
+
+/// Determines what happens if a client wants to send a message to a full queue
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum QueuePolicy {
+    /// Block the client until there is space in the queue again
+    Block,
+    /// Let the request fail with [`Code::NoSpace`]
+    Error,
+}
+
 static PENDING_QUEUES: LazyStaticRefCell<VecDeque<*mut SendQueue>> = LazyStaticRefCell::default();
 static PENDING_MSGS: StaticCell<usize> = StaticCell::new(0);
 
+/// Returns the number of messages that wait for a reply and the number of delayed queues
+pub fn queue_stats() -> (usize, usize) {
+    (PENDING_MSGS.get(), PENDING_QUEUES.borrow().len())
+}
+
 fn delay_queue(queue: &mut SendQueue) {
     if !queue.pending {
         queue.pending = true;
@@ -42,17 +59,29 @@ fn delay_queue(queue: &mut SendQueue) {
     }
 }
 
-fn resume_queue() {
-    if let Some(q) = PENDING_QUEUES.borrow_mut().pop_front() {
+fn resume_queue() -> bool {
+    let q = PENDING_QUEUES.borrow_mut().pop_front();
+    if let Some(q) = q {
         // safety: as soon as a queue is aborted/dropped, we remove it from the PENDING_QUEUES.
         // thus, whenever a queue is found here, it is still alive (and has messages pending) and
         // therefore safe to access
         unsafe {
             log!(LogFlags::KernSQueue, "SendQueue[{:?}]: resuming", (*q).id());
             (*q).pending = false;
-            (*q).queue.send_pending();
+            if (*q).queue.send_pending() {
+                (*q).notify_space();
+            }
         }
+        true
     }
+    else {
+        false
+    }
+}
+
+fn resume_queues() {
+    // resume the delayed queues in round-robin order as long as there are free slots
+    while PENDING_MSGS.get() < MAX_PENDING_MSGS && resume_queue() {}
 }
 
 fn remove_queue(queue: &mut SendQueue) {
@@ -119,6 +148,7 @@ impl MsgSender<MetaData> for KTCUSender {
 
 pub struct SendQueue {
     queue: MsgQueue<KTCUSender, MetaData>,
+    limit: usize,
     aborted: bool,
     pending: bool,
 }
@@ -147,6 +177,7 @@ impl SendQueue {
                 rpl_lbl: 0,
                 cur_event: None,
             }),
+            limit: 0,
             aborted: false,
             pending: false,
         });
@@ -158,6 +189,35 @@ impl SendQueue {
         self.queue.sender().id
     }
 
+    /// Limits the number of queued messages to `limit` (0 = unlimited)
+    ///
+    /// The limit is not enforced by [`SendQueue::send`], but needs to be checked by the callers
+    /// via [`SendQueue::is_full`] to apply backpressure to clients. Messages that are sent by the
+    /// kernel itself (e.g., to close sessions) are therefore always accepted.
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+    }
+
+    /// Returns the number of messages that are queued, but not sent yet
+    pub fn queued(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Returns true if the limit of queued messages has been reached
+    pub fn is_full(&self) -> bool {
+        // an aborted queue rejects all messages anyway
+        !self.aborted && self.limit > 0 && self.queued() >= self.limit
+    }
+
+    /// Returns the event that is notified whenever a queued message has been sent
+    pub fn space_event(&self) -> thread::Event {
+        0x4000_0000_0000_0000 | (self as *const Self as thread::Event)
+    }
+
+    fn notify_space(&self) {
+        thread::notify(self.space_event(), None);
+    }
+
     pub fn send(
         &mut self,
         rep: tcu::EpId,
@@ -210,9 +270,11 @@ impl SendQueue {
         // now that we've copied the message, we can mark it read
         ktcu::ack_msg(ktcu::KSRV_EP, msg);
 
-        if !self.queue.send_pending() {
-            resume_queue();
+        // to be fair, let the other delayed queues send first before we send our next message
+        if !self.queue.is_empty() {
+            delay_queue(self);
         }
+        resume_queues();
     }
 
     pub fn abort(&mut self) {
@@ -223,9 +285,11 @@ impl SendQueue {
             thread::notify(ev, None);
             // we were waiting for a message and won't receive it
             PENDING_MSGS.set(PENDING_MSGS.get() - 1);
-            resume_queue();
+            resume_queues();
         }
         self.aborted = true;
+        // wake up all clients that wait for space; they will notice that we're aborted
+        self.notify_space();
     }
 }
 
@@ -16,14 +16,17 @@
 use base::boxed::Box;
 use base::cell::RefCell;
 use base::col::String;
-use base::errors::Error;
+use base::errors::{Code, Error};
+use base::io::LogFlags;
+use base::log;
 use base::mem::{MsgBuf, MsgBufRef};
 use base::rc::{Rc, SRc, Weak};
 use base::tcu;
 use core::fmt;
 
+use crate::args;
 use crate::cap::RGateObject;
-use crate::com::{QueueId, SendQueue};
+use crate::com::{QueueId, QueuePolicy, SendQueue};
 use crate::tiles::Activity;
 
 pub struct Service {
@@ -35,11 +38,13 @@ pub struct Service {
 
 impl Service {
     pub fn new(act: &Rc<Activity>, name: String, rgate: SRc<RGateObject>) -> SRc<Self> {
+        let mut queue = SendQueue::new(QueueId::Serv(act.id()), act.tile_id());
+        queue.set_limit(args::get().squeue_limit);
         SRc::new(Service {
             act: Rc::downgrade(act),
             name,
             rgate,
-            queue: RefCell::from(SendQueue::new(QueueId::Serv(act.id()), act.tile_id())),
+            queue: RefCell::from(queue),
         })
     }
 
@@ -51,6 +56,34 @@ impl Service {
         &self.name
     }
 
+    /// Waits until the queue of this service accepts another request of a client
+    ///
+    /// If the queue is full, the calling thread is blocked or the call fails with
+    /// [`Code::NoSpace`], depending on the queue policy of the kernel. This has to be called before
+    /// a client request is sent to the service to prevent that clients let the queue grow
+    /// unboundedly.
+    pub fn wait_for_space_async(&self) -> Result<(), Error> {
+        loop {
+            let event = {
+                let queue = self.queue.borrow();
+                if !queue.is_full() {
+                    return Ok(());
+                }
+                if args::get().squeue_policy == QueuePolicy::Error {
+                    return Err(Error::new(Code::NoSpace));
+                }
+                queue.space_event()
+            };
+
+            log!(
+                LogFlags::KernSQueue,
+                "Service {}: queue full, waiting for space",
+                self.name
+            );
+            thread::wait_for(event);
+        }
+    }
+
     pub fn send(&self, lbl: tcu::Label, msg: &MsgBuf) -> Result<thread::Event, Error> {
         let (_, rep) = self.rgate.location().unwrap();
         self.queue.borrow_mut().send(rep, lbl, msg)
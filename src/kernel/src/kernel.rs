@@ -39,6 +39,7 @@ use base::log;
 use base::machine;
 use base::mem::VirtAddr;
 use base::tcu;
+use base::trace;
 use base::util::math;
 
 use core::ptr;
@@ -62,6 +63,7 @@ pub extern "C" fn abort() -> ! {
 #[no_mangle]
 pub extern "C" fn exit(_code: i32) -> ! {
     log!(LogFlags::Info, "Shutting down");
+    trace::flush();
     machine::write_coverage(0);
     machine::shutdown();
 }
@@ -193,10 +195,13 @@ fn workloop() -> ! {
     }
 
     while ActivityMng::count() > 0 {
-        if env::boot().platform != env::Platform::Hw {
+        // we cannot sleep with a timeout here; thus, poll as long as threads wait with a timeout
+        if env::boot().platform != env::Platform::Hw && thread::next_timeout().is_none() {
             tcu::TCU::sleep().unwrap();
         }
 
+        thread::check_timeouts();
+
         if let Some(msg) = ktcu::fetch_msg(ktcu::KSYS_EP) {
             syscalls::handle_async(msg);
         }
@@ -213,7 +218,9 @@ fn workloop() -> ! {
             crate::tiles::TileMux::handle_call_async(crate::tiles::tilemng::tilemux(tile), msg);
         }
 
-        thread::try_yield();
+        // we are done with our work; park, so that the threads of removed activities can be
+        // destroyed
+        thread::park();
     }
 
     // do the tile deinit just once
@@ -88,6 +88,14 @@ impl MainMemory {
                 return Ok(Allocation::new(gaddr, size));
             }
         }
+
+        log!(
+            LogFlags::KernMem,
+            "Unable to allocate {:#x} bytes (largest: {:#x}, fragments: {})",
+            size,
+            self.largest_contiguous(mtype).unwrap_or(0),
+            self.fragments(mtype)
+        );
         Err(Error::new(Code::OutOfMem))
     }
 
@@ -118,6 +126,14 @@ impl MainMemory {
         max
     }
 
+    /// Returns the total number of free areas in all modules of given type
+    pub fn fragments(&self, mtype: MemType) -> usize {
+        self.mods
+            .iter()
+            .filter(|m| m.mem_type() == mtype)
+            .fold(0, |total, m| total + m.fragments())
+    }
+
     pub fn capacity(&self) -> GlobOff {
         self.mods.iter().fold(0, |total, m| total + m.capacity())
     }
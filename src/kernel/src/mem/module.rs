@@ -57,6 +57,11 @@ impl MemMod {
         self.map.largest_contiguous()
     }
 
+    /// Returns the number of free areas, which grows with the fragmentation of the module
+    pub fn fragments(&self) -> usize {
+        self.map.size().1
+    }
+
     pub fn capacity(&self) -> GlobOff {
         self.size
     }
@@ -66,7 +71,10 @@ impl MemMod {
     }
 
     pub fn allocate(&mut self, size: GlobOff, align: GlobOff) -> Result<GlobAddr, Error> {
-        self.map.allocate(size, align).map(|addr| self.gaddr + addr)
+        // use best fit to keep large areas available for large activity allocations
+        self.map
+            .allocate_best_fit(size, align)
+            .map(|addr| self.gaddr + addr)
     }
 
     pub fn free(&mut self, addr: GlobAddr, size: GlobOff) -> bool {
@@ -84,11 +92,13 @@ impl fmt::Debug for MemMod {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "MemMod[type: {:?}, addr: {}, size: {} MiB, available: {} MiB, map: {:?}]",
+            "MemMod[type: {:?}, addr: {}, size: {} MiB, available: {} MiB, largest: {} MiB, fragments: {}, map: {:?}]",
             self.ty,
             self.gaddr,
             self.capacity() / (1024 * 1024),
             self.available() / (1024 * 1024),
+            self.largest_contiguous().unwrap_or(0) / (1024 * 1024),
+            self.fragments(),
             self.map
         )
     }
@@ -13,7 +13,7 @@
  * General Public License version 2 for more details.
  */
 
-use base::cell::LazyReadOnlyCell;
+use base::cell::{LazyReadOnlyCell, StaticRefCell};
 use base::cfg;
 use base::col::Vec;
 use base::env;
@@ -57,6 +57,11 @@ impl KEnv {
 
 static KENV: LazyReadOnlyCell<KEnv> = LazyReadOnlyCell::default();
 
+// the tiles that have been reconfigured at runtime with their new description
+static RECONF_TILES: StaticRefCell<Vec<(TileId, TileDesc)>> = StaticRefCell::new(Vec::new());
+// the tiles that are currently offline
+static OFFLINE_TILES: StaticRefCell<Vec<TileId>> = StaticRefCell::new(Vec::new());
+
 fn get() -> &'static KEnv {
     KENV.get()
 }
@@ -91,9 +96,31 @@ pub fn user_tiles() -> impl Iterator<Item = TileId> {
 }
 
 pub fn tile_desc(id: TileId) -> TileDesc {
+    if let Some((_, desc)) = RECONF_TILES.borrow().iter().find(|(t, _)| *t == id) {
+        return *desc;
+    }
     get().tiles[id.chip() as usize][id.tile() as usize].desc
 }
 
+/// Changes the description of the given tile (e.g., after reconfiguring an FPGA-based tile)
+pub fn set_tile_desc(id: TileId, desc: TileDesc) {
+    let mut reconf = RECONF_TILES.borrow_mut();
+    reconf.retain(|(t, _)| *t != id);
+    reconf.push((id, desc));
+}
+
+pub fn is_online(id: TileId) -> bool {
+    !OFFLINE_TILES.borrow().contains(&id)
+}
+
+pub fn set_online(id: TileId, online: bool) {
+    let mut offline = OFFLINE_TILES.borrow_mut();
+    offline.retain(|t| *t != id);
+    if !online {
+        offline.push(id);
+    }
+}
+
 pub fn is_shared(id: TileId) -> bool {
     tile_desc(id).is_programmable()
 }
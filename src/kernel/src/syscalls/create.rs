@@ -24,8 +24,8 @@ use base::tcu;
 
 use crate::cap::{Capability, KObject, SelRange};
 use crate::cap::{
-    EPCategory, EPObject, MGateObject, MapObject, RGateObject, SGateObject, SemObject, ServObject,
-    SessObject,
+    EPCategory, EPObject, GangObject, MGateObject, MapObject, RGateObject, SGateObject, SemObject,
+    ServObject, SessObject,
 };
 use crate::com::Service;
 use crate::mem;
@@ -279,6 +279,9 @@ pub fn create_activity_async(
     }
 
     let tile = get_kobj!(act, r.tile, Tile);
+    if !platform::is_online(tile.tile()) {
+        sysc_err!(Code::InvState, "Tile {} is offline", tile.tile());
+    }
     if !tile.has_quota(tcu::STD_EPS_COUNT) {
         sysc_err!(
             Code::InvArgs,
@@ -361,6 +364,24 @@ pub fn create_sem(act: &Rc<Activity>, msg: &'static tcu::Message) -> Result<(),
     Ok(())
 }
 
+pub fn create_gang(act: &Rc<Activity>, msg: &'static tcu::Message) -> Result<(), VerboseError> {
+    let r: syscalls::CreateGang = get_request(msg)?;
+    sysc_log!(act, "create_gang(dst={}, size={})", r.dst, r.size);
+
+    if !act.obj_caps().borrow().unused(r.dst) {
+        sysc_err!(Code::InvArgs, "Selector {} already in use", r.dst);
+    }
+    if r.size == 0 {
+        sysc_err!(Code::InvArgs, "Gang size must be non-zero");
+    }
+
+    let cap = Capability::new(r.dst, KObject::Gang(GangObject::new(r.size)));
+    try_kmem_quota!(act.obj_caps().borrow_mut().insert(cap));
+
+    reply_success(msg);
+    Ok(())
+}
+
 #[inline(never)]
 pub fn create_map_async(
     act: &Rc<Activity>,
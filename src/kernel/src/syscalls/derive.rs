@@ -39,12 +39,13 @@ pub fn derive_tile_async(
     let r: syscalls::DeriveTile = get_request(msg)?;
     sysc_log!(
         act,
-        "derive_tile(tile={}, dst={}, eps={:?}, time={:?}, pts={:?})",
+        "derive_tile(tile={}, dst={}, eps={:?}, time={:?}, pts={:?}, irqs={:?})",
         r.tile,
         r.dst,
         r.eps,
         r.time,
         r.pts,
+        r.irqs,
     );
 
     if !act.obj_caps().borrow().unused(r.dst) {
@@ -53,6 +54,18 @@ pub fn derive_tile_async(
 
     let tile = get_kobj!(act, r.tile, Tile);
 
+    let irqs = match r.irqs {
+        Some(irqs) if (irqs & !tile.irqs()) != 0 => {
+            sysc_err!(
+                Code::NoPerm,
+                "Interrupts {:#x} not permitted",
+                irqs & !tile.irqs()
+            )
+        },
+        Some(irqs) => irqs,
+        None => tile.irqs(),
+    };
+
     let ep_quota = if let Some(eps) = r.eps {
         if !tile.has_quota(eps) {
             sysc_err!(Code::NoSpace, "Insufficient EPs");
@@ -89,7 +102,14 @@ pub fn derive_tile_async(
 
     let cap = Capability::new(
         r.dst,
-        KObject::Tile(TileObject::new(tile.tile(), ep_quota, time_id, pt_id, true)),
+        KObject::Tile(TileObject::new(
+            tile.tile(),
+            ep_quota,
+            time_id,
+            pt_id,
+            irqs,
+            true,
+        )),
     );
     // TODO we will leak the quota object in TileMux if this fails
     try_kmem_quota!(act.obj_caps().borrow_mut().insert_as_child(cap, r.tile));
@@ -189,6 +209,15 @@ pub fn derive_srv_async(
 
     let srvcap = get_kobj!(act, r.srv, Serv);
 
+    // apply backpressure if the service is flooded with requests
+    if let Err(e) = srvcap.service().wait_for_space_async() {
+        sysc_err!(
+            e.code(),
+            "Service {} is overloaded",
+            srvcap.service().name()
+        );
+    }
+
     // everything worked, send the reply
     reply_success(msg);
 
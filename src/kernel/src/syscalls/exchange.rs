@@ -85,19 +85,26 @@ fn do_exchange(
 #[inline(never)]
 pub fn exchange(act: &Rc<Activity>, msg: &'static tcu::Message) -> Result<(), VerboseError> {
     let r: syscalls::Exchange = get_request(msg)?;
-    let other_crd = CapRngDesc::new(r.own.cap_type(), r.other, r.own.count());
 
     sysc_log!(
         act,
-        "exchange(act={}, own={}, other={}, obtain={})",
+        "exchange(act={}, descs={}, obtain={})",
         r.act,
-        r.own,
-        other_crd,
+        r.desc_count,
         r.obtain
     );
 
+    if r.desc_count == 0 || r.desc_count > syscalls::MAX_EXCHG_DESCS {
+        sysc_err!(Code::InvArgs, "Invalid descriptor count {}", r.desc_count);
+    }
+
     let actcap = get_kobj!(act, r.act, Activity).upgrade().unwrap();
-    do_exchange(act, &actcap, &r.own, &other_crd, r.obtain)?;
+    for desc in &r.descs[0..r.desc_count] {
+        let other_crd = CapRngDesc::new(desc.own.cap_type(), desc.other, desc.own.count());
+        sysc_log!(act, "exchange-desc(own={}, other={})", desc.own, other_crd);
+
+        do_exchange(act, &actcap, &desc.own, &other_crd, r.obtain)?;
+    }
 
     reply_success(msg);
     Ok(())
@@ -122,6 +129,15 @@ pub fn exchange_over_sess_async(
     let actcap = get_kobj!(act, r.act, Activity).upgrade().unwrap();
     let sess = get_kobj!(act, r.sess, Sess);
 
+    // apply backpressure if the service is flooded with requests
+    if let Err(e) = sess.service().service().wait_for_space_async() {
+        sysc_err!(
+            e.code(),
+            "Service {} is overloaded",
+            sess.service().service().name()
+        );
+    }
+
     let mut smsg = MsgBuf::borrow_def();
     let data = service::ExchangeData {
         caps: r.crd,
@@ -178,13 +194,19 @@ pub fn exchange_over_sess_async(
         reply.data.caps
     );
 
-    do_exchange(
-        &actcap,
-        &serv.service().activity(),
-        &r.crd,
-        &reply.data.caps,
-        r.obtain,
-    )?;
+    let serv_act = serv.service().activity();
+    do_exchange(&actcap, &serv_act, &r.crd, &reply.data.caps, r.obtain)?;
+
+    // let the server know when the delegated capabilities are revoked, if desired
+    if !r.obtain && reply.notify_revoke {
+        let srv_crd = reply.data.caps;
+        let mut srv_caps = serv_act.obj_caps().borrow_mut();
+        for sel in srv_crd.start()..srv_crd.start() + srv_crd.count() {
+            if let Some(cap) = srv_caps.get_mut(sel) {
+                cap.set_revoke_notify(sess.clone());
+            }
+        }
+    }
 
     let mut kreply = MsgBuf::borrow_def();
     build_vmsg!(kreply, Code::Success, syscalls::ExchangeSessReply {
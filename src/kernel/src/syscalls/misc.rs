@@ -16,18 +16,21 @@
 use base::build_vmsg;
 use base::cfg;
 use base::col::ToString;
-use base::errors::{Code, VerboseError};
+use base::errors::{Code, Error, VerboseError};
 use base::kif::{self, syscalls};
 use base::mem::{GlobOff, MsgBuf, PhysAddr, PhysAddrRaw};
 use base::rc::Rc;
 use base::tcu;
+use base::time::TimeDuration;
 
 use crate::cap::{Capability, KObject};
-use crate::cap::{EPCategory, EPObject, SemObject};
+use crate::cap::{EPCategory, EPObject, GangObject, SemObject};
+use crate::com;
 use crate::ktcu;
+use crate::mem::{self, MemType};
 use crate::platform;
-use crate::syscalls::{get_request, reply_success, send_reply};
-use crate::tiles::{tilemng, Activity};
+use crate::syscalls::{get_request, reply_result, reply_success, send_reply};
+use crate::tiles::{tilemng, Activity, TileMux};
 
 #[inline(never)]
 pub fn alloc_ep(act: &Rc<Activity>, msg: &'static tcu::Message) -> Result<(), VerboseError> {
@@ -388,22 +391,83 @@ pub fn activate_async(act: &Rc<Activity>, msg: &'static tcu::Message) -> Result<
 #[inline(never)]
 pub fn sem_ctrl_async(act: &Rc<Activity>, msg: &'static tcu::Message) -> Result<(), VerboseError> {
     let r: syscalls::SemCtrl = get_request(msg)?;
-    sysc_log!(act, "sem_ctrl(sem={}, op={:?})", r.sem, r.op);
+    sysc_log!(
+        act,
+        "sem_ctrl(sem={}, op={:?}, timeout={})",
+        r.sem,
+        r.op,
+        r.timeout
+    );
 
     let sem = get_kobj!(act, r.sem, Sem);
 
-    match r.op {
+    let res = match r.op {
         kif::syscalls::SemOp::Up => {
             sem.up();
+            Ok(())
         },
 
-        kif::syscalls::SemOp::Down => {
-            let res = SemObject::down_async(&sem);
+        kif::syscalls::SemOp::Down | kif::syscalls::SemOp::DownFor => {
+            let timeout = if r.op == kif::syscalls::SemOp::DownFor {
+                Some(TimeDuration::from_nanos(r.timeout))
+            }
+            else {
+                None
+            };
+            let res = SemObject::down_async(&sem, timeout);
             sysc_log!(act, "sem_ctrl-cont(res={:?})", res);
-            if let Err(e) = res {
-                sysc_err!(e.code(), "Semaphore operation failed");
+            res
+        },
+
+        kif::syscalls::SemOp::TryDown => {
+            if sem.try_down() {
+                Ok(())
+            }
+            else {
+                Err(Error::new(Code::WouldBlock))
             }
         },
+
+        kif::syscalls::SemOp::Value => Ok(()),
+    };
+
+    match res {
+        Ok(_) => {
+            let mut reply = MsgBuf::borrow_def();
+            build_vmsg!(reply, Code::Success, kif::syscalls::SemCtrlReply {
+                value: sem.value(),
+            });
+            send_reply(msg, &reply);
+        },
+        // an unsuccessful try or timeout is not an error of the system call
+        Err(e) if e.code() == Code::WouldBlock || e.code() == Code::Timeout => {
+            reply_result(msg, e.code());
+        },
+        Err(e) => sysc_err!(e.code(), "Semaphore operation failed"),
+    }
+
+    Ok(())
+}
+
+pub fn gang_ctrl(act: &Rc<Activity>, msg: &'static tcu::Message) -> Result<(), VerboseError> {
+    let r: syscalls::GangCtrl = get_request(msg)?;
+    sysc_log!(
+        act,
+        "gang_ctrl(gang={}, act={}, op={:?})",
+        r.gang,
+        r.act,
+        r.op
+    );
+
+    let gang = get_kobj!(act, r.gang, Gang);
+    let actcap = get_kobj!(act, r.act, Activity).upgrade().unwrap();
+
+    let res = match r.op {
+        kif::syscalls::GangOp::Join => GangObject::join(&gang, &actcap),
+        kif::syscalls::GangOp::Leave => gang.leave(&actcap),
+    };
+    if let Err(e) = res {
+        sysc_err!(e.code(), "Unable to {:?} gang", r.op);
     }
 
     reply_success(msg);
@@ -440,11 +504,33 @@ pub fn activity_ctrl_async(
         kif::syscalls::ActivityOp::Stop => {
             let is_self = r.act == kif::SEL_ACT;
             actcap.stop_app_async(Code::from(r.arg as u32), is_self, act.id());
+
+            // the members of a started gang are stopped together as well
+            if !is_self {
+                if let Some(gang) = actcap.gang().filter(|g| g.started()) {
+                    for m in gang.members() {
+                        if m.id() != actcap.id() && m.id() != act.id() {
+                            m.stop_app_async(Code::Unspecified, false, act.id());
+                        }
+                    }
+                }
+            }
             if is_self {
                 ktcu::ack_msg(ktcu::KSYS_EP, msg);
                 return Ok(());
             }
         },
+
+        kif::syscalls::ActivityOp::Suspend | kif::syscalls::ActivityOp::Resume => {
+            if Rc::ptr_eq(act, &actcap) {
+                sysc_err!(Code::InvArgs, "Activity can't suspend or resume itself");
+            }
+
+            let suspend = r.op == kif::syscalls::ActivityOp::Suspend;
+            if let Err(e) = actcap.suspend_app_async(suspend) {
+                sysc_err!(e.code(), "Unable to {:?} Activity", r.op);
+            }
+        },
     };
 
     reply_success(msg);
@@ -485,6 +571,35 @@ pub fn activity_wait_async(
     Ok(())
 }
 
+#[inline(never)]
+pub fn activity_time_async(
+    act: &Rc<Activity>,
+    msg: &'static tcu::Message,
+) -> Result<(), VerboseError> {
+    let r: syscalls::ActivityTime = get_request(msg)?;
+    sysc_log!(act, "activity_time(act={})", r.act);
+
+    let actcap = get_kobj!(act, r.act, Activity).upgrade().unwrap();
+
+    let tile = actcap.tile_id();
+    if !platform::tile_desc(tile).supports_tilemux() || !tilemng::tilemux(tile).is_initialized() {
+        sysc_err!(Code::NotSup, "CPU time is only available with TileMux");
+    }
+
+    let time = match TileMux::activity_time_async(tilemng::tilemux(tile), actcap.id()) {
+        Ok(time) => time,
+        Err(e) => sysc_err!(e.code(), "Unable to get CPU time"),
+    };
+
+    let mut reply = MsgBuf::borrow_def();
+    build_vmsg!(reply, Code::Success, kif::syscalls::ActivityTimeReply {
+        nanos: time.as_nanos() as u64,
+    });
+    send_reply(msg, &reply);
+
+    Ok(())
+}
+
 pub fn reset_stats(act: &Rc<Activity>, msg: &'static tcu::Message) -> Result<(), VerboseError> {
     sysc_log!(act, "reset_stats()",);
 
@@ -497,6 +612,41 @@ pub fn reset_stats(act: &Rc<Activity>, msg: &'static tcu::Message) -> Result<(),
     Ok(())
 }
 
+pub fn get_stats(act: &Rc<Activity>, msg: &'static tcu::Message) -> Result<(), VerboseError> {
+    let r: syscalls::GetStats = get_request(msg)?;
+    sysc_log!(act, "get_stats(act={})", r.act);
+
+    let actcap = get_kobj!(act, r.act, Activity).upgrade().unwrap();
+
+    let (pending_msgs, delayed_queues) = com::queue_stats();
+    let (mem_total, mem_free, mem_largest, mem_fragments) = {
+        let mem = mem::borrow_mut();
+        (
+            mem.capacity(),
+            mem.available(),
+            mem.largest_contiguous(MemType::USER).unwrap_or(0),
+            mem.fragments(MemType::USER),
+        )
+    };
+
+    let mut reply = MsgBuf::borrow_def();
+    build_vmsg!(reply, Code::Success, kif::syscalls::GetStatsReply {
+        obj_caps: actcap.obj_caps().borrow().count(),
+        map_caps: actcap.map_caps().borrow().count(),
+        act_syscalls: actcap.syscalls(),
+        syscalls: crate::syscalls::count(),
+        pending_msgs,
+        delayed_queues,
+        mem_total,
+        mem_free,
+        mem_largest,
+        mem_fragments,
+    });
+    send_reply(msg, &reply);
+
+    Ok(())
+}
+
 pub fn noop(act: &Rc<Activity>, msg: &'static tcu::Message) -> Result<(), VerboseError> {
     sysc_log!(act, "noop()",);
 
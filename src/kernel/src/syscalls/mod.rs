@@ -14,6 +14,7 @@
  */
 
 use base::build_vmsg;
+use base::cell::StaticCell;
 use base::errors::{Code, Error};
 use base::io::LogFlags;
 use base::kif;
@@ -22,6 +23,7 @@ use base::mem;
 use base::rc::Rc;
 use base::serialize::{Deserialize, M3Deserializer};
 use base::tcu;
+use base::trace;
 
 use core::convert::TryFrom;
 
@@ -88,6 +90,13 @@ macro_rules! get_kobj_ref {
     }};
 }
 
+static SYSCALLS: StaticCell<u64> = StaticCell::new(0);
+
+/// Returns the number of system calls that have been received so far
+pub fn count() -> u64 {
+    SYSCALLS.get()
+}
+
 mod create;
 mod derive;
 mod exchange;
@@ -116,9 +125,12 @@ fn get_request<R: Deserialize<'static>>(msg: &'static tcu::Message) -> Result<R,
 
 pub fn handle_async(msg: &'static tcu::Message) {
     let act: Rc<Activity> = ActivityMng::activity(msg.header.label() as tcu::ActId).unwrap();
+    act.count_syscall();
+    SYSCALLS.set(SYSCALLS.get() + 1);
 
     use kif::syscalls::Operation;
     let opcode = msg.as_words()[0];
+    trace!(Begin, trace::EventId::Syscall, act.id(), opcode);
     let res = match opcode {
         o if o == Operation::CreateMGate.into() => create::create_mgate(&act, msg),
         o if o == Operation::CreateRGate.into() => create::create_rgate(&act, msg),
@@ -127,6 +139,7 @@ pub fn handle_async(msg: &'static tcu::Message) {
         o if o == Operation::CreateSess.into() => create::create_sess(&act, msg),
         o if o == Operation::CreateAct.into() => create::create_activity_async(&act, msg),
         o if o == Operation::CreateSem.into() => create::create_sem(&act, msg),
+        o if o == Operation::CreateGang.into() => create::create_gang(&act, msg),
         o if o == Operation::CreateMap.into() => create::create_map_async(&act, msg),
 
         o if o == Operation::DeriveTile.into() => derive::derive_tile_async(&act, msg),
@@ -149,12 +162,16 @@ pub fn handle_async(msg: &'static tcu::Message) {
         o if o == Operation::TileReset.into() => tile::tile_reset_async(&act, msg),
         o if o == Operation::TileInfo.into() => tile::tile_info_async(&act, msg),
         o if o == Operation::TileMem.into() => tile::tile_mem(&act, msg),
+        o if o == Operation::TileCtrl.into() => tile::tile_ctrl(&act, msg),
         o if o == Operation::GetSess.into() => misc::get_sess(&act, msg),
         o if o == Operation::SemCtrl.into() => misc::sem_ctrl_async(&act, msg),
+        o if o == Operation::GangCtrl.into() => misc::gang_ctrl(&act, msg),
         o if o == Operation::ActCtrl.into() => misc::activity_ctrl_async(&act, msg),
         o if o == Operation::ActWait.into() => misc::activity_wait_async(&act, msg),
+        o if o == Operation::ActTime.into() => misc::activity_time_async(&act, msg),
 
         o if o == Operation::ResetStats.into() => misc::reset_stats(&act, msg),
+        o if o == Operation::GetStats.into() => misc::get_stats(&act, msg),
         o if o == Operation::Noop.into() => misc::noop(&act, msg),
 
         _ => panic!("Unexpected operation: {}", opcode),
@@ -174,4 +191,5 @@ pub fn handle_async(msg: &'static tcu::Message) {
 
         reply_result(msg, e.code());
     }
+    trace!(End, trace::EventId::Syscall, act.id());
 }
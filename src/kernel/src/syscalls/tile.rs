@@ -218,6 +218,10 @@ pub fn tile_reset_async(
     }
 
     let tile_id = tile.tile();
+    if r.mux_mem != kif::INVALID_SEL && !platform::is_online(tile_id) {
+        sysc_err!(Code::InvState, "Tile {} is offline", tile_id);
+    }
+
     let mux_mem = if r.mux_mem == kif::INVALID_SEL {
         None
     }
@@ -247,6 +251,56 @@ pub fn tile_reset_async(
     Ok(())
 }
 
+pub fn tile_ctrl(act: &Rc<Activity>, msg: &'static tcu::Message) -> Result<(), VerboseError> {
+    let r: syscalls::TileCtrl = get_request(msg)?;
+    sysc_log!(
+        act,
+        "tile_ctrl(tile={}, op={:?}, desc={:?})",
+        r.tile,
+        r.op,
+        r.desc
+    );
+
+    let act_caps = act.obj_caps().borrow();
+    let tile = get_kobj_ref!(act_caps, r.tile, Tile);
+    if tile.derived() {
+        sysc_err!(
+            Code::NoPerm,
+            "Cannot control tiles via derived tile objects"
+        );
+    }
+
+    let tile_id = tile.tile();
+    let tilemux = tilemng::tilemux(tile_id);
+    if tilemux.has_activities() || tilemux.is_initialized() {
+        sysc_err!(Code::InvState, "Tile {} is still in use", tile_id);
+    }
+
+    match r.op {
+        kif::syscalls::TileOp::Offline => platform::set_online(tile_id, false),
+
+        kif::syscalls::TileOp::Online => {
+            if platform::is_online(tile_id) {
+                sysc_err!(Code::InvState, "Tile {} is not offline", tile_id);
+            }
+            // memory tiles are managed by the kernel and cannot be added at runtime
+            if r.desc.tile_type() == kif::TileType::Mem {
+                sysc_err!(
+                    Code::InvArgs,
+                    "Cannot turn tile {} into memory tile",
+                    tile_id
+                );
+            }
+
+            platform::set_tile_desc(tile_id, r.desc);
+            platform::set_online(tile_id, true);
+        },
+    }
+
+    reply_success(msg);
+    Ok(())
+}
+
 #[inline(never)]
 pub fn tile_info_async(act: &Rc<Activity>, msg: &'static tcu::Message) -> Result<(), VerboseError> {
     let r: syscalls::TileInfo = get_request(msg)?;
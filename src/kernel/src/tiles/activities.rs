@@ -28,7 +28,7 @@ use base::tcu::{ActId, EpId, TileId, STD_EPS_COUNT, UPCALL_REP_OFF};
 use bitflags::bitflags;
 use core::fmt;
 
-use crate::cap::{CapTable, Capability, EPObject, KMemObject, KObject, TileObject};
+use crate::cap::{CapTable, Capability, EPObject, GangObject, KMemObject, KObject, TileObject};
 use crate::com::{QueueId, SendQueue};
 use crate::ktcu;
 use crate::platform;
@@ -71,15 +71,18 @@ pub struct Activity {
     kmem: SRc<KMemObject>,
 
     state: Cell<State>,
+    suspended: Cell<bool>,
     exit_code: Cell<Option<Code>>,
     first_sel: Cell<CapSel>,
 
     obj_caps: RefCell<CapTable>,
     map_caps: RefCell<CapTable>,
+    syscalls: Cell<u64>,
 
     eps: RefCell<Vec<Rc<EPObject>>>,
     rbuf_phys: Cell<PhysAddr>,
     upcalls: RefCell<Box<SendQueue>>,
+    gang: RefCell<Option<SRc<GangObject>>>,
 }
 
 impl Activity {
@@ -98,13 +101,16 @@ impl Activity {
             eps_start,
             kmem,
             state: Cell::from(State::INIT),
+            suspended: Cell::from(false),
             exit_code: Cell::from(None),
             first_sel: Cell::from(kif::FIRST_FREE_SEL),
             obj_caps: RefCell::from(CapTable::default()),
             map_caps: RefCell::from(CapTable::default()),
+            syscalls: Cell::from(0),
             eps: RefCell::from(Vec::new()),
             rbuf_phys: Cell::from(PhysAddr::default()),
             upcalls: RefCell::from(SendQueue::new(QueueId::Activity(id), tile.tile())),
+            gang: RefCell::from(None),
             tile,
         });
 
@@ -278,10 +284,26 @@ impl Activity {
         &self.map_caps
     }
 
+    pub fn syscalls(&self) -> u64 {
+        self.syscalls.get()
+    }
+
+    pub fn count_syscall(&self) {
+        self.syscalls.set(self.syscalls.get() + 1);
+    }
+
     pub fn state(&self) -> State {
         self.state.get()
     }
 
+    pub fn gang(&self) -> Option<SRc<GangObject>> {
+        self.gang.borrow().clone()
+    }
+
+    pub fn set_gang(&self, gang: Option<SRc<GangObject>>) {
+        self.gang.replace(gang);
+    }
+
     pub fn is_root(&self) -> bool {
         self.flags.contains(ActivityFlags::IS_ROOT)
     }
@@ -447,10 +469,48 @@ impl Activity {
             return Ok(());
         }
 
+        // members of a gang are started together as soon as all members requested to be started
+        if let Some(gang) = self.gang() {
+            for act in gang.request_start(self) {
+                act.do_start_async()?;
+            }
+            return Ok(());
+        }
+
+        self.do_start_async()
+    }
+
+    fn do_start_async(&self) -> Result<(), Error> {
+        if self.state.get() != State::INIT {
+            return Ok(());
+        }
+
         self.state.set(State::RUNNING);
         ActivityMng::start_activity_async(self)
     }
 
+    pub fn suspend_app_async(&self, suspend: bool) -> Result<(), Error> {
+        // only started activities can be suspended; everything else is not scheduled anyway
+        if self.state.get() != State::RUNNING {
+            return Err(Error::new(Code::InvState));
+        }
+        if self.suspended.get() == suspend {
+            return Ok(());
+        }
+
+        log!(
+            LogFlags::KernActs,
+            "{} Activity {} [id={}]",
+            if suspend { "Suspending" } else { "Resuming" },
+            self.name(),
+            self.id()
+        );
+
+        ActivityMng::suspend_activity_async(self, suspend)?;
+        self.suspended.set(suspend);
+        Ok(())
+    }
+
     pub fn stop_app_async(&self, exit_code: Code, is_self: bool, revoker: ActId) {
         if self.state.get() == State::DEAD {
             return;
@@ -122,6 +122,7 @@ impl ActivityMng {
                 act.tile().time_quota_id(),
                 act.tile().pt_quota_id(),
                 act.eps_start(),
+                act.tile().irqs(),
             )?;
         }
 
@@ -152,6 +153,21 @@ impl ActivityMng {
         Ok(())
     }
 
+    pub fn suspend_activity_async(act: &Activity, suspend: bool) -> Result<(), Error> {
+        // without TileMux, there is nobody that could hold back the activity
+        if !platform::tile_desc(act.tile_id()).supports_tilemux() {
+            return Err(Error::new(Code::NotSup));
+        }
+
+        let op = if suspend {
+            kif::tilemux::ActivityOp::Suspend
+        }
+        else {
+            kif::tilemux::ActivityOp::Resume
+        };
+        TileMux::activity_ctrl_async(tilemng::tilemux(act.tile_id()), act.id(), op)
+    }
+
     pub fn start_root_async() -> Result<(), Error> {
         // TODO temporary
         let isa = platform::tile_desc(platform::kernel_tile()).isa();
@@ -26,6 +26,7 @@ use base::mem::{size_of, GlobAddr, GlobOff, MsgBuf, VirtAddr};
 use base::quota;
 use base::rc::{Rc, SRc, Weak};
 use base::tcu::{self, ActId, EpId, TileId};
+use base::time::TimeDuration;
 
 use core::cmp;
 use core::convert::TryFrom;
@@ -153,6 +154,8 @@ impl TileMux {
             EPQuota::new(0),
             kif::tilemux::DEF_QUOTA_ID,
             kif::tilemux::DEF_QUOTA_ID,
+            // the tile's owner may use all interrupts
+            !0,
             false,
         );
 
@@ -579,6 +582,7 @@ impl TileMux {
         time_quota: quota::Id,
         pt_quota: quota::Id,
         eps_start: EpId,
+        irqs: u32,
     ) -> Result<(), Error> {
         let mut buf = MsgBuf::borrow_def();
         let msg = kif::tilemux::ActInit {
@@ -586,6 +590,7 @@ impl TileMux {
             time_quota,
             pt_quota,
             eps_start,
+            irqs,
         };
         build_vmsg!(buf, kif::tilemux::Sidecalls::ActInit, &msg);
 
@@ -611,6 +616,18 @@ impl TileMux {
         .map(|_| ())
     }
 
+    pub fn activity_time_async(
+        tilemux: RefMut<'_, Self>,
+        act: ActId,
+    ) -> Result<TimeDuration, Error> {
+        let mut buf = MsgBuf::borrow_def();
+        let msg = kif::tilemux::ActTime { act_id: act as u64 };
+        build_vmsg!(buf, kif::tilemux::Sidecalls::ActTime, &msg);
+
+        Self::send_receive_sidecall_async::<kif::tilemux::ActTime>(tilemux, None, buf, &msg, true)
+            .map(|r| TimeDuration::from_nanos(r.val1))
+    }
+
     pub fn derive_quota_async(
         tilemux: RefMut<'_, Self>,
         parent_time: quota::Id,
@@ -95,6 +95,13 @@ static const char *errmsgs[] = {
     "Socket is closed",
     "Connection failed",
     "Connection closed gracefully",
+
+    /* Services */
+    "Service version mismatch",
+    "Request would cause a deadlock",
+
+    /* Activities */
+    "Stack overflow",
 };
 
 const char *Errors::to_string(Code code) {
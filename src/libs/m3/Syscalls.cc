@@ -260,6 +260,7 @@ void Syscalls::derive_tile(capsel_t tile, capsel_t dst, Option<uint> eps, Option
     else
         req.time = static_cast<uint64_t>(-1);
     req.pts = pts.unwrap_or(static_cast<size_t>(-1));
+    req.irqs = static_cast<xfer_t>(-1);
     send_receive_throw(req_buf);
 }
 
@@ -407,6 +408,7 @@ void Syscalls::sem_ctrl(capsel_t sel, KIF::Syscall::SemOp op) {
     req.opcode = KIF::Syscall::SEM_CTRL;
     req.sem_sel = sel;
     req.op = op;
+    req.timeout = 0;
     send_receive_throw(req_buf);
 }
 
@@ -415,9 +417,10 @@ void Syscalls::exchange(capsel_t act, const KIF::CapRngDesc &own, capsel_t other
     auto &req = req_buf.cast<KIF::Syscall::Exchange>();
     req.opcode = KIF::Syscall::EXCHANGE;
     req.act_sel = act;
-    own.to_raw(req.own_caps);
-    req.other_sel = other;
     req.obtain = obtain;
+    req.desc_count = 1;
+    own.to_raw(req.descs[0].own_caps);
+    req.descs[0].other_sel = other;
     send_receive_throw(req_buf);
 }
 
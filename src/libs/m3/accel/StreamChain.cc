@@ -0,0 +1,136 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+#include <m3/Exception.h>
+#include <m3/Syscalls.h>
+#include <m3/accel/StreamChain.h>
+#include <m3/stream/Standard.h>
+#include <m3/tiles/Tile.h>
+
+namespace m3 {
+
+StreamChain::Stage::Stage(const Reference<Tile> &tile, const std::string_view &name,
+                          CycleDuration comptime)
+    : tile(tile),
+      act(std::make_unique<ChildActivity>(tile, name)),
+      // the accelerator refers to the activity, which lives as long as the stage
+      accel(std::make_unique<StreamAccel>(act, comptime)),
+      pipe_mem(),
+      pipe(),
+      running() {
+}
+
+size_t StreamChain::running() const noexcept {
+    size_t count = 0;
+    for(auto &s : _stages) {
+        if(s->running)
+            count++;
+    }
+    return count;
+}
+
+void StreamChain::append(const char *tile, const std::string_view &name, CycleDuration comptime) {
+    append(Tile::get(tile), name, comptime);
+}
+
+void StreamChain::append(const Reference<Tile> &tile, const std::string_view &name,
+                         CycleDuration comptime) {
+    if(_started)
+        throw Exception(Errors::INV_STATE);
+    _stages.push_back(std::make_unique<Stage>(tile, name, comptime));
+}
+
+void StreamChain::connect() {
+    auto num = _stages.size();
+
+    _stages[0]->accel->connect_input(&_in);
+    _stages[num - 1]->accel->connect_output(&_out);
+
+    for(size_t i = 0; i + 1 < num; ++i) {
+        auto &cur = _stages[i];
+        auto &next = _stages[i + 1];
+        if(_pipes) {
+            cur->pipe_mem = std::make_unique<MemCap>(MemCap::create_global(_pipe_size, MemCap::RW));
+            cur->pipe = std::make_unique<IndirectPipe>(*_pipes, *cur->pipe_mem, _pipe_size);
+            cur->accel->connect_output(&cur->pipe->writer());
+            next->accel->connect_input(&cur->pipe->reader());
+        }
+        else {
+            cur->accel->connect_output(next->accel.get());
+            next->accel->connect_input(cur->accel.get());
+        }
+    }
+}
+
+void StreamChain::start() {
+    if(_started || _stages.empty())
+        throw Exception(Errors::INV_STATE);
+
+    connect();
+    _started = true;
+
+    for(auto &s : _stages) {
+        s->act->start();
+        s->running = true;
+    }
+}
+
+void StreamChain::add_running(capsel_t *sels, size_t *count) const noexcept {
+    for(auto &s : _stages) {
+        if(s->running)
+            sels[(*count)++] = s->act->sel();
+    }
+}
+
+bool StreamChain::terminated(capsel_t act, int exitcode) {
+    for(size_t i = 0; i < _stages.size(); ++i) {
+        auto &s = _stages[i];
+        if(s->running && s->act->sel() == act) {
+            if(exitcode != 0)
+                eprintln("accelerator {} terminated with exit code {}"_cf, i, exitcode);
+            // let the neighbors know that this accelerator will neither read nor write anymore
+            if(s->pipe)
+                s->pipe->close_writer();
+            if(i > 0 && _stages[i - 1]->pipe)
+                _stages[i - 1]->pipe->close_reader();
+            s->running = false;
+            return true;
+        }
+    }
+    return false;
+}
+
+void StreamChain::wait_all(StreamChain *const *chains, size_t num) {
+    size_t total = 0;
+    for(size_t i = 0; i < num; ++i)
+        total += chains[i]->running();
+    if(total == 0)
+        return;
+
+    capsel_t sels[total];
+    for(size_t rem = total; rem > 0; --rem) {
+        size_t count = 0;
+        for(size_t i = 0; i < num; ++i)
+            chains[i]->add_running(sels, &count);
+
+        const auto [exitcode, act] = Syscalls::activity_wait(sels, count, 0);
+        for(size_t i = 0; i < num; ++i) {
+            if(chains[i]->terminated(act, exitcode))
+                break;
+        }
+    }
+}
+
+}
@@ -63,6 +63,14 @@ void Socket::tear_down() noexcept {
     }
 }
 
+void Socket::set_option(SocketOpt opt, uint64_t value) {
+    _net.set_option(sd(), opt, value);
+}
+
+uint64_t Socket::get_option(SocketOpt opt) {
+    return _net.get_option(sd(), opt);
+}
+
 void Socket::disconnect() {
     _state = Closed;
     _local_ep = Endpoint();
@@ -88,15 +96,15 @@ void Socket::process_message(const NetEventChannel::ControlMessage &message,
 void Socket::handle_data(NetEventChannel::DataMessage const &msg, NetEventChannel::Event &event) {
     log_net(NetLogEvent::RecvPacket, _sd, msg.size);
     LOG(LogFlags::LibNet, "socket {}: received data with {}b from {}:{}"_cf, _sd, msg.size,
-        IpAddr(msg.addr), msg.port);
+        IpAddr::from_raw(msg.addr), msg.port);
     _recv_queue.append(new DataQueue::Item(&msg, std::move(event)));
 }
 
 void Socket::handle_connected(NetEventChannel::ConnectedMessage const &msg) {
     log_net(NetLogEvent::RecvConnected, _sd, msg.port);
-    LOG(LogFlags::LibNet, "socket {}: connected to {}:{}"_cf, _sd, IpAddr(msg.addr), msg.port);
+    LOG(LogFlags::LibNet, "socket {}: connected to {}:{}"_cf, _sd, IpAddr::from_raw(msg.addr), msg.port);
     _state = Connected;
-    _remote_ep.addr = IpAddr(msg.addr);
+    _remote_ep.addr = IpAddr::from_raw(msg.addr);
     _remote_ep.port = msg.port;
 }
 
@@ -48,6 +48,14 @@ void UdpSocket::bind(port_t port) {
     _state = State::Bound;
 }
 
+void UdpSocket::join_multicast(const IpAddr &addr) {
+    _net.join_multicast(sd(), addr);
+}
+
+void UdpSocket::leave_multicast(const IpAddr &addr) {
+    _net.leave_multicast(sd(), addr);
+}
+
 bool UdpSocket::connect(const Endpoint &ep) {
     if(ep == Endpoint::unspecified())
         throw Exception(Errors::INV_ARGS);
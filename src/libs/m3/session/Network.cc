@@ -50,43 +50,44 @@ int32_t Network::create(SocketType type, uint8_t protocol, const SocketArgs &arg
 IpAddr Network::ip_addr() {
     GateIStream reply = send_receive_vmsg(_sgate, opcodes::Net::GET_IP);
     reply.pull_result();
-    uint32_t addr;
-    reply >> addr;
-    return IpAddr(addr);
+    uint64_t addr[2];
+    reply >> addr[0] >> addr[1];
+    return IpAddr::from_raw(addr);
 }
 
 IpAddr Network::get_nameserver() {
     GateIStream reply = send_receive_vmsg(_sgate, opcodes::Net::GET_NAMESRV);
     reply.pull_result();
-    uint32_t addr;
-    reply >> addr;
-    return IpAddr(addr);
+    uint64_t addr[2];
+    reply >> addr[0] >> addr[1];
+    return IpAddr::from_raw(addr);
 }
 
 std::pair<IpAddr, port_t> Network::bind(int32_t sd, port_t port) {
     GateIStream reply = send_receive_vmsg(_sgate, opcodes::Net::BIND, sd, port);
     reply.pull_result();
-    uint32_t addr;
-    reply >> addr >> port;
-    return std::make_pair(IpAddr(addr), port);
+    uint64_t addr[2];
+    reply >> addr[0] >> addr[1] >> port;
+    return std::make_pair(IpAddr::from_raw(addr), port);
 }
 
 IpAddr Network::listen(int32_t sd, port_t port) {
-    GateIStream reply = send_receive_vmsg(_sgate, opcodes::Net::LISTEN, sd, port);
+    GateIStream reply = send_receive_vmsg(_sgate, opcodes::Net::LISTEN, sd, port, size_t(0));
     reply.pull_result();
-    uint32_t addr;
-    reply >> addr;
-    return IpAddr(addr);
+    uint64_t addr[2];
+    reply >> addr[0] >> addr[1];
+    return IpAddr::from_raw(addr);
 }
 
 Endpoint Network::connect_socket(int32_t sd, Endpoint remote_ep) {
+    uint64_t addr[2];
+    remote_ep.addr.to_raw(addr);
     GateIStream reply =
-        send_receive_vmsg(_sgate, opcodes::Net::CONNECT, sd, remote_ep.addr.addr(), remote_ep.port);
+        send_receive_vmsg(_sgate, opcodes::Net::CONNECT, sd, addr[0], addr[1], remote_ep.port);
     reply.pull_result();
-    uint32_t addr;
     port_t port;
-    reply >> addr >> port;
-    return Endpoint(IpAddr(addr), port);
+    reply >> addr[0] >> addr[1] >> port;
+    return Endpoint(IpAddr::from_raw(addr), port);
 }
 
 void Network::abort(int32_t sd, bool remove) {
@@ -94,4 +95,35 @@ void Network::abort(int32_t sd, bool remove) {
     reply.pull_result();
 }
 
+void Network::set_option(int32_t sd, SocketOpt opt, uint64_t value) {
+    GateIStream reply = send_receive_vmsg(_sgate, opcodes::Net::SET_OPT, sd,
+                                          static_cast<uint64_t>(opt), value);
+    reply.pull_result();
+}
+
+uint64_t Network::get_option(int32_t sd, SocketOpt opt) {
+    GateIStream reply =
+        send_receive_vmsg(_sgate, opcodes::Net::GET_OPT, sd, static_cast<uint64_t>(opt));
+    reply.pull_result();
+    uint64_t value;
+    reply >> value;
+    return value;
+}
+
+void Network::join_multicast(int32_t sd, const IpAddr &addr) {
+    uint64_t raw[2];
+    addr.to_raw(raw);
+    GateIStream reply =
+        send_receive_vmsg(_sgate, opcodes::Net::JOIN_MULTICAST, sd, raw[0], raw[1]);
+    reply.pull_result();
+}
+
+void Network::leave_multicast(int32_t sd, const IpAddr &addr) {
+    uint64_t raw[2];
+    addr.to_raw(raw);
+    GateIStream reply =
+        send_receive_vmsg(_sgate, opcodes::Net::LEAVE_MULTICAST, sd, raw[0], raw[1]);
+    reply.pull_result();
+}
+
 } // namespace m3
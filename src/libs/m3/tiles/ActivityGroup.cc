@@ -0,0 +1,62 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+#include <base/Errors.h>
+
+#include <m3/Exception.h>
+#include <m3/tiles/ActivityGroup.h>
+
+namespace m3 {
+
+static void ctrl(ChildActivity *act, void (ChildActivity::*func)()) {
+    try {
+        (act->*func)();
+    }
+    catch(const Exception &e) {
+        // the activity might have exited in the meantime, which is fine
+        if(e.code() != Errors::INV_STATE)
+            throw;
+    }
+}
+
+void ActivityGroup::suspend() {
+    if(_suspended)
+        return;
+
+    for(auto act : _acts) {
+        if(act->tile_desc().supports_tilemux())
+            ctrl(act, &ChildActivity::suspend);
+    }
+    _suspended = true;
+}
+
+void ActivityGroup::resume() {
+    if(!_suspended)
+        return;
+
+    for(auto act : _acts) {
+        if(act->tile_desc().supports_tilemux())
+            ctrl(act, &ChildActivity::resume);
+    }
+    _suspended = false;
+}
+
+void ActivityGroup::stop() {
+    for(auto act : _acts)
+        act->stop();
+    _suspended = false;
+}
+
+}
@@ -111,6 +111,14 @@ void ChildActivity::stop() {
     Syscalls::activity_ctrl(sel(), KIF::Syscall::VCTRL_STOP, 0);
 }
 
+void ChildActivity::suspend() {
+    Syscalls::activity_ctrl(sel(), KIF::Syscall::VCTRL_SUSPEND, 0);
+}
+
+void ChildActivity::resume() {
+    Syscalls::activity_ctrl(sel(), KIF::Syscall::VCTRL_RESUME, 0);
+}
+
 int ChildActivity::wait_async(event_t event) {
     const capsel_t sels[] = {sel()};
     return Syscalls::activity_wait(sels, 1, event).first;
@@ -203,6 +211,10 @@ void ChildActivity::do_exec(int argc, const char *const *argv, const char *const
     senv.pager_sgate = _pager ? _pager->child_sgate() : 0;
 
     senv.lambda = func_addr;
+    senv.cloned = 0;
+    // we don't support dynamically linked programs here
+    senv.dl_objs_addr = 0;
+    senv.dl_objs_len = 0;
 
     /* add mounts, fds, caps and eps */
     /* align it because we cannot necessarily read e.g. integers from unaligned addresses */
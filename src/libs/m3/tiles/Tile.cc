@@ -46,6 +46,8 @@ static TileDesc desc_with_properties(TileDesc desc, const char *props) {
             res = TileDesc(res.type(), res.isa(), 0, res.attr() | TileAttr::NIC);
         else if(strcmp(prop, "serial") == 0)
             res = TileDesc(res.type(), res.isa(), 0, res.attr() | TileAttr::SERIAL);
+        else if(strcmp(prop, "vec") == 0)
+            res = TileDesc(res.type(), res.isa(), 0, res.attr() | TileAttr::VEC);
         else if(strcmp(prop, "kecacc") == 0)
             res = TileDesc(res.type(), res.isa(), 0, res.attr() | TileAttr::KECACC);
         else if(strcmp(prop, "indir") == 0)
@@ -60,6 +62,8 @@ static TileDesc desc_with_properties(TileDesc desc, const char *props) {
             res = TileDesc(TileType::COMP, TileISA::NIC_DEV, 0, TileAttr::IMEM);
         else if(strcmp(prop, "serdev") == 0)
             res = TileDesc(TileType::COMP, TileISA::SERIAL_DEV, 0, TileAttr::IMEM);
+        else if(strcmp(prop, "usbdev") == 0)
+            res = TileDesc(TileType::COMP, TileISA::USB_DEV, 0, TileAttr::IMEM);
         prop = strtok(NULL, "+");
     }
     return res;
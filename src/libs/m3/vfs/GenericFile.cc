@@ -247,6 +247,11 @@ Option<size_t> GenericFile::write(const void *buffer, size_t count) {
 
     LOG(LogFlags::LibFS, "GenFile[{}]::write({}, pos={})"_cf, _fd, count, _goff + _pos);
 
+    // start with a new chunk if the write does not fit into the rest of the current one, but into a
+    // complete one. This way, the server can ensure that it's not interleaved with others.
+    if((flags() & FILE_ATOMIC) && count > _len - _pos && count <= _len)
+        commit();
+
     if(_pos == _len) {
         if(!_blocking && !receive_notify(Event::OUTPUT, true))
             return None;
@@ -368,6 +373,13 @@ bool GenericFile::fetch_signal() {
     return receive_notify(Event::SIGNAL, true);
 }
 
+bool GenericFile::fetch_suspend() {
+    if(!_notify_rgate)
+        enable_notifications();
+
+    return receive_notify(Event::SUSPEND, true);
+}
+
 void GenericFile::map(Reference<Pager> &pager, goff_t *virt, size_t fileoff, size_t len, int prot,
                       int flags) const {
     pager->map_ds(virt, len, prot, flags, _sess, fileoff);
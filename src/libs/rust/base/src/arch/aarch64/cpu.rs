@@ -0,0 +1,125 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use core::arch::asm;
+
+use crate::arch::CPUOps;
+use crate::mem::VirtAddr;
+
+/// Reads the value of the given system register, e.g., "sctlr_el1"
+#[macro_export]
+macro_rules! read_csr {
+    ($reg_name:tt) => {{
+        let res: usize;
+        unsafe {
+            core::arch::asm!(
+                concat!("mrs {0}, ", $reg_name),
+                out(reg) res,
+                options(nomem, nostack)
+            )
+        };
+        res
+    }};
+}
+
+/// Writes `$val` to the given system register, e.g., "sctlr_el1"
+#[macro_export]
+macro_rules! write_csr {
+    ($reg_name:tt, $val:expr) => {{
+        unsafe {
+            let val: usize = $val;
+            core::arch::asm!(
+                concat!("msr ", $reg_name, ", {0}"),
+                "isb",
+                in(reg) val,
+                options(nomem, nostack)
+            )
+        };
+    }};
+}
+
+pub struct AArch64CPU {}
+
+impl CPUOps for AArch64CPU {
+    unsafe fn read8b(addr: *const u64) -> u64 {
+        let res: u64;
+        asm!(
+            "ldr {0}, [{1}]",
+            lateout(reg) res,
+            in(reg) addr,
+            options(nostack),
+        );
+        res
+    }
+
+    unsafe fn write8b(addr: *mut u64, val: u64) {
+        asm!(
+            "str {0}, [{1}]",
+            in(reg) val,
+            in(reg) addr,
+            options(nostack),
+        );
+    }
+
+    #[inline(always)]
+    fn stack_pointer() -> VirtAddr {
+        let sp: usize;
+        unsafe {
+            asm!(
+                "mov {0}, sp",
+                out(reg) sp,
+                options(nomem, nostack),
+            )
+        }
+        VirtAddr::from(sp)
+    }
+
+    #[inline(always)]
+    fn base_pointer() -> VirtAddr {
+        let fp: usize;
+        unsafe {
+            asm!(
+                "mov {0}, x29",
+                out(reg) fp,
+                options(nomem, nostack),
+            )
+        }
+        VirtAddr::from(fp)
+    }
+
+    unsafe fn backtrace_step(bp: VirtAddr, func: &mut VirtAddr) -> VirtAddr {
+        // the frame record consists of the previous frame pointer and the link register
+        let bp_ptr = bp.as_ptr::<usize>();
+        *func = VirtAddr::from(*bp_ptr.offset(1));
+        VirtAddr::from(*bp_ptr)
+    }
+
+    fn elapsed_cycles() -> u64 {
+        let cnt: u64;
+        unsafe {
+            asm!(
+                "mrs {0}, cntvct_el0",
+                out(reg) cnt,
+                options(nomem, nostack),
+            )
+        };
+        cnt
+    }
+
+    fn gem5_debug(_msg: u64) -> u64 {
+        // TODO there is no custom instruction for aarch64 yet
+        0
+    }
+}
@@ -0,0 +1,76 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use core::arch::asm;
+
+use crate::arch::TMABIOps;
+use crate::errors::Error;
+use crate::tmif::Operation;
+
+pub struct AArch64TMABI {}
+
+impl TMABIOps for AArch64TMABI {
+    fn call1(op: Operation, arg1: usize) -> Result<(), Error> {
+        Self::call2(op, arg1, 0)
+    }
+
+    fn call2(op: Operation, arg1: usize, arg2: usize) -> Result<(), Error> {
+        let mut res = op.into();
+        unsafe {
+            asm!(
+                "svc #0",
+                inout("x0") res,
+                in("x1") arg1,
+                in("x2") arg2,
+            );
+        }
+        crate::tmif::get_result(res)
+    }
+
+    fn call3(op: Operation, arg1: usize, arg2: usize, arg3: usize) -> Result<(), Error> {
+        let mut res = op.into();
+        unsafe {
+            asm!(
+                "svc #0",
+                inout("x0") res,
+                in("x1") arg1,
+                in("x2") arg2,
+                in("x3") arg3,
+            );
+        }
+        crate::tmif::get_result(res)
+    }
+
+    fn call4(
+        op: Operation,
+        arg1: usize,
+        arg2: usize,
+        arg3: usize,
+        arg4: usize,
+    ) -> Result<(), Error> {
+        let mut res = op.into();
+        unsafe {
+            asm!(
+                "svc #0",
+                inout("x0") res,
+                in("x1") arg1,
+                in("x2") arg2,
+                in("x3") arg3,
+                in("x4") arg4,
+            );
+        }
+        crate::tmif::get_result(res)
+    }
+}
@@ -99,6 +99,14 @@ cfg_if! {
         #[cfg(not(feature = "linux"))]
         pub type TMABI = crate::arch::isa::tmabi::ARMTMABI;
     }
+    else if #[cfg(target_arch = "aarch64")] {
+        #[path = "aarch64/mod.rs"]
+        mod isa;
+
+        pub type CPU = crate::arch::isa::cpu::AArch64CPU;
+        #[cfg(not(feature = "linux"))]
+        pub type TMABI = crate::arch::isa::tmabi::AArch64TMABI;
+    }
     else {
         #[path = "riscv/mod.rs"]
         mod isa;
@@ -145,4 +145,13 @@ impl<T: Sized> LazyReadOnlyCell<T> {
     pub unsafe fn reset(&self, val: T) -> Option<T> {
         mem::replace(&mut *self.inner.get(), Some(val))
     }
+
+    /// Removes the inner value and returns the old value.
+    ///
+    /// # Safety
+    ///
+    /// The caller needs to make sure that there are no references left to the old value.
+    pub unsafe fn unset(&self) -> Option<T> {
+        (*self.inner.get()).take()
+    }
 }
@@ -19,11 +19,13 @@
 //! Shareable mutable containers
 
 mod lazy;
+mod spin;
 mod stat;
 mod statref;
 mod statunsafe;
 
 pub use self::lazy::{LazyReadOnlyCell, LazyStaticCell, LazyStaticRefCell};
+pub use self::spin::{SpinLock, SpinLockGuard};
 pub use self::stat::StaticCell;
 pub use self::statref::StaticRefCell;
 pub use self::statunsafe::{LazyStaticUnsafeCell, StaticUnsafeCell};
@@ -0,0 +1,107 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::hint;
+use core::marker::Sync;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// A cell that protects a static object by a spinlock to allow accesses from multiple cores.
+///
+/// In contrast to [`StaticRefCell`](super::StaticRefCell), the `SpinLock` waits until the object is
+/// available instead of panicking. Therefore, recursive locking results in a deadlock. The lock does
+/// not disable interrupts; the caller needs to make sure that the lock is not acquired by an
+/// interrupt handler that interrupted the owner of the lock.
+pub struct SpinLock<T: Sized> {
+    locked: AtomicBool,
+    inner: UnsafeCell<T>,
+}
+
+unsafe impl<T: Sized> Sync for SpinLock<T> {
+}
+
+impl<T: Sized> SpinLock<T> {
+    /// Creates a new unlocked spinlock with given value
+    pub const fn new(val: T) -> Self {
+        SpinLock {
+            locked: AtomicBool::new(false),
+            inner: UnsafeCell::new(val),
+        }
+    }
+
+    /// Acquires the lock, spinning until it is available, and returns a guard to access the value
+    pub fn lock(&self) -> SpinLockGuard<'_, T> {
+        loop {
+            if let Some(guard) = self.try_lock() {
+                break guard;
+            }
+            // wait until the lock is released before trying again to not bounce the cacheline
+            while self.locked.load(Ordering::Relaxed) {
+                hint::spin_loop();
+            }
+        }
+    }
+
+    /// Acquires the lock if it is available and returns a guard to access the value
+    pub fn try_lock(&self) -> Option<SpinLockGuard<'_, T>> {
+        self.locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| SpinLockGuard { lock: self })
+    }
+
+    /// Returns true if the lock is currently held
+    pub fn is_locked(&self) -> bool {
+        self.locked.load(Ordering::Relaxed)
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for SpinLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.try_lock() {
+            Some(guard) => guard.fmt(f),
+            None => write!(f, "SpinLock[locked]"),
+        }
+    }
+}
+
+/// Provides access to the value of a [`SpinLock`] and releases the lock when dropped
+pub struct SpinLockGuard<'l, T: Sized> {
+    lock: &'l SpinLock<T>,
+}
+
+impl<T: Sized> Deref for SpinLockGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // safety: we hold the lock
+        unsafe { &*self.lock.inner.get() }
+    }
+}
+
+impl<T: Sized> DerefMut for SpinLockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // safety: we hold the lock
+        unsafe { &mut *self.lock.inner.get() }
+    }
+}
+
+impl<T: Sized> Drop for SpinLockGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.locked.store(false, Ordering::Release);
+    }
+}
@@ -67,6 +67,8 @@ pub const ENV_START: VirtAddr = VirtAddr::new((MEM_OFFSET + 0x1F_E000) as VirtAd
 pub const ENV_SIZE: usize = PAGE_SIZE;
 
 pub const STACK_SIZE: usize = 0x20000;
+/// The size of the area below the stack that stays unmapped to detect stack overflows
+pub const STACK_GUARD_SIZE: usize = 4 * PAGE_SIZE;
 
 pub const FIXED_KMEM: usize = 2 * 1024 * 1024;
 pub const FIXED_ROOT_MEM: usize = MOD_HEAP_SIZE + FIXED_TILEMUX_MEM + 2 * 1024 * 1024;
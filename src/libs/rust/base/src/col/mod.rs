@@ -21,12 +21,14 @@
 mod bitarray;
 mod boxlist;
 mod dlist;
+mod mpsc;
 mod ringbuf;
 mod treap;
 
 pub use self::bitarray::BitArray;
 pub use self::boxlist::{BoxItem, BoxList, BoxListIter, BoxListIterMut, BoxRef};
 pub use self::dlist::{DList, DListIter, DListIterMut};
+pub use self::mpsc::{FixedMpscQueue, MpscQueue};
 pub use self::ringbuf::VarRingBuf;
 pub use self::treap::Treap;
 
@@ -0,0 +1,320 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! Lock-free queues with multiple producers and a single consumer
+//!
+//! Producers only need a shared reference to the queue and never block each other. At most one
+//! consumer can be active at a time; a concurrent call to `pop` (e.g., from an interrupt handler
+//! that interrupted the consumer) does not block, but simply returns `None`.
+
+use core::cell::UnsafeCell;
+use core::cmp;
+use core::fmt;
+use core::mem::MaybeUninit;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering};
+
+use crate::boxed::Box;
+
+/// Ensures that only one consumer is active at a time
+struct ConsumerLock<'l>(&'l AtomicBool);
+
+impl<'l> ConsumerLock<'l> {
+    fn try_lock(flag: &'l AtomicBool) -> Option<Self> {
+        match flag.swap(true, Ordering::Acquire) {
+            true => None,
+            false => Some(Self(flag)),
+        }
+    }
+}
+
+impl Drop for ConsumerLock<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+struct Node<T> {
+    val: T,
+    next: *mut Node<T>,
+}
+
+/// A heap-backed MPSC queue without capacity limit
+///
+/// Every pushed element is allocated on the heap. Therefore, this queue cannot be used in contexts
+/// that must not allocate memory (e.g., interrupt handlers). Use [`FixedMpscQueue`] in these
+/// cases.
+pub struct MpscQueue<T> {
+    // elements pushed by the producers in LIFO order
+    incoming: AtomicPtr<Node<T>>,
+    // elements taken over by the consumer in FIFO order
+    outgoing: UnsafeCell<*mut Node<T>>,
+    consuming: AtomicBool,
+    len: AtomicUsize,
+}
+
+// safety: all shared state is either accessed atomically or only by the single consumer
+unsafe impl<T: Send> Send for MpscQueue<T> {
+}
+unsafe impl<T: Send> Sync for MpscQueue<T> {
+}
+
+impl<T> MpscQueue<T> {
+    /// Creates a new and empty queue
+    pub const fn new() -> Self {
+        Self {
+            incoming: AtomicPtr::new(ptr::null_mut()),
+            outgoing: UnsafeCell::new(ptr::null_mut()),
+            consuming: AtomicBool::new(false),
+            len: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the number of elements in the queue
+    ///
+    /// As producers can push elements concurrently, the value might be outdated immediately.
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    /// Returns true if the queue contains no elements
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Appends `val` to the end of the queue
+    pub fn push(&self, val: T) {
+        let node = Box::into_raw(Box::new(Node {
+            val,
+            next: ptr::null_mut(),
+        }));
+
+        // count the element first so that a concurrent pop never underflows the counter
+        self.len.fetch_add(1, Ordering::Relaxed);
+
+        let mut head = self.incoming.load(Ordering::Relaxed);
+        loop {
+            // safety: the node is not visible to others until the exchange below succeeded
+            unsafe { (*node).next = head };
+            match self.incoming.compare_exchange_weak(
+                head,
+                node,
+                Ordering::Release,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => break,
+                Err(cur) => head = cur,
+            }
+        }
+    }
+
+    /// Removes the first element from the queue and returns it
+    ///
+    /// Returns `None` if the queue is empty or another consumer is currently active.
+    pub fn pop(&self) -> Option<T> {
+        let _lock = ConsumerLock::try_lock(&self.consuming)?;
+
+        // safety: only the consumer holding the lock accesses `outgoing` and the nodes in it
+        unsafe {
+            let out = &mut *self.outgoing.get();
+            if out.is_null() {
+                // take over all incoming elements at once and reverse them to get FIFO order
+                let mut cur = self.incoming.swap(ptr::null_mut(), Ordering::Acquire);
+                while !cur.is_null() {
+                    let next = (*cur).next;
+                    (*cur).next = *out;
+                    *out = cur;
+                    cur = next;
+                }
+            }
+
+            if out.is_null() {
+                return None;
+            }
+
+            let node = Box::from_raw(*out);
+            *out = node.next;
+            self.len.fetch_sub(1, Ordering::Relaxed);
+            Some(node.val)
+        }
+    }
+}
+
+impl<T> Default for MpscQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for MpscQueue<T> {
+    fn drop(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
+impl<T> fmt::Debug for MpscQueue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "MpscQueue[len={}]", self.len())
+    }
+}
+
+struct Slot<T> {
+    // the position this slot is ready for, relative to the slot index (see `FixedMpscQueue::seq`)
+    seq: AtomicUsize,
+    val: UnsafeCell<MaybeUninit<T>>,
+}
+
+/// A MPSC queue with a fixed capacity of `N` elements
+///
+/// The elements are stored inline, so that the queue never allocates memory and can be used in
+/// statics and interrupt handlers. `N` needs to be a power of two.
+pub struct FixedMpscQueue<T, const N: usize> {
+    slots: [Slot<T>; N],
+    // the next position to write to
+    tail: AtomicUsize,
+    // the next position to read from
+    head: AtomicUsize,
+    consuming: AtomicBool,
+}
+
+// safety: all shared state is either accessed atomically or only by the single consumer
+unsafe impl<T: Send, const N: usize> Send for FixedMpscQueue<T, N> {
+}
+unsafe impl<T: Send, const N: usize> Sync for FixedMpscQueue<T, N> {
+}
+
+impl<T, const N: usize> FixedMpscQueue<T, N> {
+    // only used to initialize the slots array
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY_SLOT: Slot<T> = Slot {
+        seq: AtomicUsize::new(0),
+        val: UnsafeCell::new(MaybeUninit::uninit()),
+    };
+
+    /// Creates a new and empty queue
+    pub const fn new() -> Self {
+        assert!(N.is_power_of_two());
+        Self {
+            slots: [Self::EMPTY_SLOT; N],
+            tail: AtomicUsize::new(0),
+            head: AtomicUsize::new(0),
+            consuming: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns the capacity of the queue
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Returns the number of elements in the queue
+    ///
+    /// As producers can push elements concurrently, the value might be outdated immediately.
+    pub fn len(&self) -> usize {
+        let head = self.head.load(Ordering::Relaxed);
+        self.tail.load(Ordering::Relaxed).wrapping_sub(head).min(N)
+    }
+
+    /// Returns true if the queue contains no elements
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Each slot has a sequence number that tells whether it is ready for a producer at position
+    // `seq` or for the consumer at position `seq - 1`. To be able to initialize all slots with the
+    // same value, the sequence numbers are stored relative to the slot index.
+    fn seq(&self, idx: usize) -> usize {
+        self.slots[idx]
+            .seq
+            .load(Ordering::Acquire)
+            .wrapping_add(idx)
+    }
+
+    fn set_seq(&self, idx: usize, seq: usize) {
+        self.slots[idx]
+            .seq
+            .store(seq.wrapping_sub(idx), Ordering::Release);
+    }
+
+    /// Appends `val` to the end of the queue
+    ///
+    /// Returns `Err(val)` if the queue is full.
+    pub fn push(&self, val: T) -> Result<(), T> {
+        let mut pos = self.tail.load(Ordering::Relaxed);
+        loop {
+            let idx = pos % N;
+            let diff = self.seq(idx).wrapping_sub(pos) as isize;
+            match diff.cmp(&0) {
+                cmp::Ordering::Equal => match self.tail.compare_exchange_weak(
+                    pos,
+                    pos.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        // safety: we own the slot until we publish it via the sequence number
+                        unsafe { (*self.slots[idx].val.get()).write(val) };
+                        self.set_seq(idx, pos.wrapping_add(1));
+                        return Ok(());
+                    },
+                    Err(cur) => pos = cur,
+                },
+                // the slot still holds the element from the previous round
+                cmp::Ordering::Less => return Err(val),
+                // another producer was faster
+                cmp::Ordering::Greater => pos = self.tail.load(Ordering::Relaxed),
+            }
+        }
+    }
+
+    /// Removes the first element from the queue and returns it
+    ///
+    /// Returns `None` if the queue is empty or another consumer is currently active. Note that an
+    /// element might not be visible yet if its producer is still writing it.
+    pub fn pop(&self) -> Option<T> {
+        let _lock = ConsumerLock::try_lock(&self.consuming)?;
+
+        let pos = self.head.load(Ordering::Relaxed);
+        let idx = pos % N;
+        if self.seq(idx) != pos.wrapping_add(1) {
+            return None;
+        }
+
+        // safety: the producer has published the slot and only we as the consumer read it
+        let val = unsafe { (*self.slots[idx].val.get()).assume_init_read() };
+        self.head.store(pos.wrapping_add(1), Ordering::Relaxed);
+        // the slot is ready for the producer in the next round
+        self.set_seq(idx, pos.wrapping_add(N));
+        Some(val)
+    }
+}
+
+impl<T, const N: usize> Default for FixedMpscQueue<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> Drop for FixedMpscQueue<T, N> {
+    fn drop(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
+impl<T, const N: usize> fmt::Debug for FixedMpscQueue<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "FixedMpscQueue[len={}, cap={}]", self.len(), N)
+    }
+}
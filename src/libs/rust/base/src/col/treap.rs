@@ -78,6 +78,20 @@ impl<K: Copy + Ord, V> Treap<K, V> {
         self.root.is_none()
     }
 
+    /// Returns the number of elements in the treap
+    ///
+    /// Note that the number is not stored, but determined by walking through the whole tree.
+    pub fn len(&self) -> usize {
+        self.root.map(Self::count_rec).unwrap_or(0)
+    }
+
+    fn count_rec(node: NonNull<Node<K, V>>) -> usize {
+        unsafe {
+            1 + (*node.as_ptr()).left.map(Self::count_rec).unwrap_or(0)
+                + (*node.as_ptr()).right.map(Self::count_rec).unwrap_or(0)
+        }
+    }
+
     /// Removes all elements from the treap
     pub fn clear(&mut self) {
         if let Some(r) = self.root.take() {
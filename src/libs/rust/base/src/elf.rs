@@ -26,13 +26,146 @@ use crate::kif;
 
 const EI_NIDENT: usize = 16;
 
+/// The ELF file types
+#[derive(Copy, Clone, Default, Debug, Eq, PartialEq, IntoPrimitive, TryFromPrimitive)]
+#[repr(u16)]
+pub enum ElfType {
+    /// Executable file
+    #[default]
+    Exec = 2,
+    /// Shared object file
+    Dyn  = 3,
+    /// Core file
+    Core = 4,
+}
+
+/// The machine types
+#[derive(Copy, Clone, Debug, Eq, PartialEq, IntoPrimitive, TryFromPrimitive)]
+#[repr(u16)]
+pub enum Machine {
+    /// ARM (32-bit)
+    Arm    = 40,
+    /// x86-64
+    X86_64 = 62,
+    /// RISC-V
+    RiscV  = 243,
+}
+
 /// The program header entry types
 #[derive(Copy, Clone, Default, Debug, Eq, PartialEq, IntoPrimitive, TryFromPrimitive)]
 #[repr(u32)]
 pub enum PHType {
     /// Load segment
     #[default]
-    Load = 1,
+    Load    = 1,
+    /// Dynamic linking information
+    Dynamic = 2,
+    /// Path to the program interpreter
+    Interp  = 3,
+    /// Auxiliary information (e.g., the registers in core files)
+    Note    = 4,
+}
+
+/// The tags of the entries in the dynamic section
+#[derive(Copy, Clone, Default, Debug, Eq, PartialEq, IntoPrimitive, TryFromPrimitive)]
+#[repr(usize)]
+pub enum DynTag {
+    /// Marks the end of the dynamic section
+    #[default]
+    Null        = 0,
+    /// String table offset of the name of a needed library
+    Needed      = 1,
+    /// Size of the PLT relocations
+    PltRelSz    = 2,
+    /// Address of the PLT and/or GOT
+    PltGot      = 3,
+    /// Address of the symbol hash table
+    Hash        = 4,
+    /// Address of the string table
+    StrTab      = 5,
+    /// Address of the symbol table
+    SymTab      = 6,
+    /// Address of the relocations with addend
+    Rela        = 7,
+    /// Size of the relocations with addend
+    RelaSz      = 8,
+    /// Size of a relocation with addend
+    RelaEnt     = 9,
+    /// Size of the string table
+    StrSz       = 10,
+    /// Size of a symbol table entry
+    SymEnt      = 11,
+    /// Address of the initialization function
+    Init        = 12,
+    /// Address of the termination function
+    Fini        = 13,
+    /// Address of the relocations without addend
+    Rel         = 17,
+    /// Type of the PLT relocations
+    PltRel      = 20,
+    /// Relocations might modify non-writable segments
+    TextRel     = 22,
+    /// Address of the PLT relocations
+    JmpRel      = 23,
+    /// All relocations need to be processed before the program is started
+    BindNow     = 24,
+    /// Address of the array of initialization functions
+    InitArray   = 25,
+    /// Address of the array of termination functions
+    FiniArray   = 26,
+    /// Size of the array of initialization functions
+    InitArraySz = 27,
+    /// Size of the array of termination functions
+    FiniArraySz = 28,
+    /// Flags for the object
+    Flags       = 30,
+    /// Address of the GNU-style symbol hash table
+    GnuHash     = 0x6fff_fef5,
+}
+
+/// The flag in the [`DynTag::Flags`] entry that requests non-lazy binding
+pub const DF_BIND_NOW: usize = 0x8;
+
+/// The section index of undefined symbols
+pub const SHN_UNDEF: u16 = 0;
+
+/// The note type for the process status (including the registers) in core files
+pub const NT_PRSTATUS: u32 = 1;
+/// The note type for the process information in core files
+pub const NT_PRPSINFO: u32 = 3;
+
+/// The symbol bindings
+#[derive(Copy, Clone, Default, Debug, Eq, PartialEq, IntoPrimitive, TryFromPrimitive)]
+#[repr(u8)]
+pub enum SymBind {
+    /// Local symbol
+    #[default]
+    Local  = 0,
+    /// Global symbol
+    Global = 1,
+    /// Weak symbol
+    Weak   = 2,
+}
+
+/// The symbol types
+#[derive(Copy, Clone, Default, Debug, Eq, PartialEq, IntoPrimitive, TryFromPrimitive)]
+#[repr(u8)]
+pub enum SymType {
+    /// Unspecified type
+    #[default]
+    NoType  = 0,
+    /// Data object
+    Object  = 1,
+    /// Function
+    Func    = 2,
+    /// Section
+    Section = 3,
+    /// Source file
+    File    = 4,
+    /// Common data object
+    Common  = 5,
+    /// Thread-local data object
+    Tls     = 6,
 }
 
 bitflags! {
@@ -143,6 +276,129 @@ pub type ProgramHeader = ProgramHeader64;
 #[cfg(target_pointer_width = "32")]
 pub type ProgramHeader = ProgramHeader32;
 
+/// Header of an entry in a note segment
+///
+/// The header is followed by the name and the descriptor, both padded to 4 bytes.
+#[derive(Default, Debug, Copy, Clone)]
+#[repr(C)]
+pub struct NoteHeader {
+    /// The length of the name including the null byte
+    pub name_size: u32,
+    /// The length of the descriptor
+    pub desc_size: u32,
+    /// The note type (e.g., [`NT_PRSTATUS`])
+    pub ty: u32,
+}
+
+/// Entry in the dynamic section
+#[derive(Default, Debug, Copy, Clone)]
+#[repr(C)]
+pub struct Dyn {
+    /// The tag (see [`DynTag`])
+    pub tag: usize,
+    /// The value or address, depending on the tag
+    pub val: usize,
+}
+
+/// Relocation with addend
+#[derive(Default, Debug, Copy, Clone)]
+#[repr(C)]
+pub struct Rela {
+    /// The address to relocate
+    pub offset: usize,
+    /// The symbol index and relocation type
+    pub info: usize,
+    /// The addend
+    pub addend: isize,
+}
+
+impl Rela {
+    /// Returns the index of the symbol this relocation refers to
+    #[cfg(target_pointer_width = "64")]
+    pub fn sym(&self) -> usize {
+        self.info >> 32
+    }
+
+    /// Returns the type of the relocation
+    #[cfg(target_pointer_width = "64")]
+    pub fn ty(&self) -> u32 {
+        self.info as u32
+    }
+
+    /// Returns the index of the symbol this relocation refers to
+    #[cfg(target_pointer_width = "32")]
+    pub fn sym(&self) -> usize {
+        self.info >> 8
+    }
+
+    /// Returns the type of the relocation
+    #[cfg(target_pointer_width = "32")]
+    pub fn ty(&self) -> u32 {
+        (self.info & 0xFF) as u32
+    }
+}
+
+/// Symbol table entry for 32-bit ELF files
+#[derive(Default, Debug, Copy, Clone)]
+#[repr(C)]
+#[cfg(target_pointer_width = "32")]
+pub struct Sym32 {
+    /// String table offset of the name
+    pub name: u32,
+    /// Value of the symbol
+    pub value: usize,
+    /// Size of the symbol
+    pub size: u32,
+    /// Binding and type of the symbol
+    pub info: u8,
+    /// Visibility of the symbol
+    pub other: u8,
+    /// Index of the section the symbol is defined in
+    pub shndx: u16,
+}
+#[cfg(target_pointer_width = "32")]
+const _: () = assert!(crate::mem::size_of::<Sym32>() == 16);
+
+/// Symbol table entry for 64-bit ELF files
+#[derive(Default, Debug, Copy, Clone)]
+#[repr(C)]
+#[cfg(target_pointer_width = "64")]
+pub struct Sym64 {
+    /// String table offset of the name
+    pub name: u32,
+    /// Binding and type of the symbol
+    pub info: u8,
+    /// Visibility of the symbol
+    pub other: u8,
+    /// Index of the section the symbol is defined in
+    pub shndx: u16,
+    /// Value of the symbol
+    pub value: usize,
+    /// Size of the symbol
+    pub size: u64,
+}
+#[cfg(target_pointer_width = "64")]
+const _: () = assert!(crate::mem::size_of::<Sym64>() == 24);
+
+/// Symbol table entry (64-bit)
+#[cfg(target_pointer_width = "64")]
+pub type Sym = Sym64;
+/// Symbol table entry (32-bit)
+#[cfg(target_pointer_width = "32")]
+pub type Sym = Sym32;
+
+impl Sym {
+    /// Returns the binding of the symbol
+    pub fn bind(&self) -> Option<SymBind> {
+        SymBind::try_from(self.info >> 4).ok()
+    }
+
+    /// Returns the type of the symbol
+    pub fn ty(&self) -> Option<SymType> {
+        SymType::try_from(self.info & 0xF).ok()
+    }
+}
+
 impl From<PHFlags> for kif::Perm {
     fn from(flags: PHFlags) -> Self {
         let mut prot = kif::Perm::empty();
@@ -30,6 +30,7 @@ use crate::cell::LazyStaticRefCell;
 use crate::cfg;
 use crate::col::{String, ToString, Vec};
 use crate::format;
+use crate::io::log::LogCtrl;
 use crate::kif::TileDesc;
 use crate::mem::VirtAddr;
 use crate::tcu::TileId;
@@ -95,6 +96,13 @@ pub struct BaseEnv {
 
     pub data_addr: u64,
     pub data_len: u64,
+
+    pub cloned: u64,
+
+    pub dl_objs_addr: u64,
+    pub dl_objs_len: u64,
+
+    pub log_ctrl: LogCtrl,
 }
 
 /// Collects the strings and pointers for the given slice of arguments to pass to a program.
@@ -96,6 +96,12 @@ pub enum Code {
     InvChecksum,
     SocketClosed,
     ConnectionFailed,
+    ConnClosed,
+    // services
+    VersionMismatch,
+    Deadlock,
+    // activities
+    StackOverflow,
 }
 
 impl Default for Code {
@@ -260,7 +266,7 @@ impl From<u32> for Error {
 
 impl From<u32> for Code {
     fn from(error: u32) -> Self {
-        assert!(error <= Code::ConnectionFailed as u32);
+        assert!(error <= Code::StackOverflow as u32);
         // safety: assuming that the assert above doesn't fail, the conversion is safe
         // TODO better way?
         unsafe { intrinsics::transmute(error) }
@@ -19,20 +19,84 @@
 //! Contains the logger
 
 use core::cmp;
+use core::fmt;
+use core::ptr;
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
 
 use crate::cell::{RefMut, StaticCell, StaticRefCell};
 use crate::env;
 use crate::errors::Error;
 use crate::io::{LogFlags, Serial, Write};
+use crate::serialize::{Deserialize, Serialize};
 use crate::tcu::{TileId, TCU};
+use crate::time::SystemTime;
 
 const MAX_LINE_LEN: usize = 180;
 const SUFFIX: &[u8] = b"\x1B[0m";
 
+// the end of a JSON record and the end of a record that is continued in the next line
+const RECORD_END: &[u8] = b"\"}";
+const RECORD_MORE: &[u8] = b"\",\"more\":true}";
+// the maximum number of bytes for one character within a JSON string (\u00XX)
+const MAX_ESC_LEN: usize = 6;
+
 static LOG_READY: StaticCell<bool> = StaticCell::new(false);
-static LOG_FLAGS: StaticCell<LogFlags> = StaticCell::new(LogFlags::empty());
+static DEF_CTRL: StaticCell<LogCtrl> =
+    StaticCell::new(LogCtrl::new(LogFlags::empty(), LogFormat::Text));
+static LOG_CTRL: StaticCell<*mut LogCtrl> = StaticCell::new(ptr::null_mut());
 static LOG: StaticRefCell<Log> = StaticRefCell::new(Log::new());
 
+/// The format of log messages
+///
+/// In the JSON format, each line written via the `log` macro contains a record of the form
+/// `{"flag":"<flag>","msg":"<msg>"}` after the usual prefix with tile, component, and time. If the
+/// wall-clock time is known (see [`SystemTime::is_synced`]), the record additionally contains the
+/// field `"time":"<time>"` with the time in ISO 8601 format.
+/// Messages that do not fit into a single line are split into multiple records, all but the last
+/// having the additional field `"more":true`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, IntoPrimitive, TryFromPrimitive)]
+#[repr(u64)]
+pub enum LogFormat {
+    /// Human-readable text (default)
+    #[default]
+    Text,
+    /// Machine-readable JSON records
+    Json,
+}
+
+/// The log settings of a component
+///
+/// The settings are kept in this structure so that they can be moved to memory that is accessible
+/// by others (see [`place_ctrl`]), which allows to change the settings of a running component.
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
+#[repr(C)]
+pub struct LogCtrl {
+    flags: [u64; 2],
+    format: u64,
+}
+
+impl LogCtrl {
+    /// Creates new log settings with given flags and format
+    pub const fn new(flags: LogFlags, format: LogFormat) -> Self {
+        let bits = flags.bits();
+        Self {
+            flags: [bits as u64, (bits >> 64) as u64],
+            format: format as u64,
+        }
+    }
+
+    /// Returns the log flags
+    pub fn flags(&self) -> LogFlags {
+        LogFlags::from_bits_truncate(self.flags[0] as u128 | (self.flags[1] as u128) << 64)
+    }
+
+    /// Returns the log format
+    pub fn format(&self) -> LogFormat {
+        LogFormat::try_from(self.format).unwrap_or_default()
+    }
+}
+
 /// A buffered logger that writes to the serial line
 pub struct Log {
     serial: Serial,
@@ -40,6 +104,8 @@ pub struct Log {
     pos: usize,
     time_pos: usize,
     start_pos: usize,
+    // the name of the flag, if a JSON record is currently written
+    record: Option<&'static str>,
 }
 
 /// The color used for log entries. Chosen based on tile ID by default.
@@ -91,6 +157,69 @@ impl Log {
             pos: 0,
             time_pos: 0,
             start_pos: 0,
+            record: None,
+        }
+    }
+
+    /// Starts a JSON record for a message with given flag
+    pub(crate) fn begin_record(&mut self, flag: LogFlags) {
+        self.record = Some(
+            flag.iter_names()
+                .next()
+                .map(|(n, _)| n)
+                .unwrap_or("Unknown"),
+        );
+    }
+
+    /// Ends the current JSON record
+    pub(crate) fn end_record(&mut self) {
+        // terminate the record in case the message was not terminated by a newline
+        if self.pos != self.start_pos {
+            self.put_raw(RECORD_END);
+            self.put_char(b'\n');
+        }
+        self.record = None;
+    }
+
+    fn put_raw(&mut self, bytes: &[u8]) {
+        self.buf[self.pos..self.pos + bytes.len()].copy_from_slice(bytes);
+        self.pos += bytes.len();
+    }
+
+    fn put_record_char(&mut self, c: u8) {
+        if self.pos == self.start_pos {
+            self.put_raw(b"{\"flag\":\"");
+            self.put_raw(self.record.unwrap().as_bytes());
+            if SystemTime::is_synced() {
+                fmt::Write::write_fmt(
+                    &mut RawWriter(self),
+                    format_args!("\",\"time\":\"{}", SystemTime::now()),
+                )
+                .unwrap();
+            }
+            self.put_raw(b"\",\"msg\":\"");
+        }
+
+        match c {
+            b'\n' => {
+                self.put_raw(RECORD_END);
+                self.put_char(b'\n');
+                return;
+            },
+            b'"' => self.put_raw(b"\\\""),
+            b'\\' => self.put_raw(b"\\\\"),
+            c if c < 0x20 => {
+                const HEX: &[u8] = b"0123456789abcdef";
+                self.put_raw(b"\\u00");
+                self.put_raw(&[HEX[(c >> 4) as usize], HEX[(c & 0xF) as usize]]);
+            },
+            c => self.put_raw(&[c]),
+        }
+
+        // continue the message in a new record if the next character might not fit
+        if self.pos + MAX_ESC_LEN + RECORD_MORE.len() + SUFFIX.len() + 1 >= MAX_LINE_LEN {
+            self.put_raw(RECORD_MORE);
+            self.put_char(b'\n');
         }
     }
 
@@ -158,14 +287,75 @@ impl Write for Log {
     }
 
     fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
-        self.write_bytes(buf);
+        if self.record.is_some() {
+            for b in buf {
+                self.put_record_char(*b);
+            }
+        }
+        else {
+            self.write_bytes(buf);
+        }
         Ok(buf.len())
     }
 }
 
+// writes formatted strings into the log buffer without escaping
+struct RawWriter<'l>(&'l mut Log);
+
+impl fmt::Write for RawWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.put_raw(s.as_bytes());
+        Ok(())
+    }
+}
+
+fn ctrl() -> *mut LogCtrl {
+    match LOG_CTRL.get() {
+        c if c.is_null() => DEF_CTRL.as_ptr(),
+        c => c,
+    }
+}
+
+fn read_ctrl() -> LogCtrl {
+    // the settings might be changed by others at any time
+    unsafe { ctrl().read_volatile() }
+}
+
+fn write_ctrl(ctrl: LogCtrl) {
+    unsafe { self::ctrl().write_volatile(ctrl) }
+}
+
 /// Returns the currently set logging flags
 pub fn flags() -> LogFlags {
-    LOG_FLAGS.get()
+    read_ctrl().flags()
+}
+
+/// Sets the logging flags to `flags`
+pub fn set_flags(flags: LogFlags) {
+    write_ctrl(LogCtrl::new(flags, format()));
+}
+
+/// Returns the currently used log format
+pub fn format() -> LogFormat {
+    read_ctrl().format()
+}
+
+/// Sets the log format to `format`
+pub fn set_format(format: LogFormat) {
+    write_ctrl(LogCtrl::new(flags(), format));
+}
+
+/// Moves the log settings to `ctrl` and uses them from now on
+///
+/// This allows other components to change the log settings at runtime by writing to the given
+/// location, as done by resource managers for their children via the environment.
+///
+/// # Safety
+///
+/// The caller needs to ensure that `ctrl` is valid, properly aligned, and stays valid forever.
+pub unsafe fn place_ctrl(ctrl: *mut LogCtrl) {
+    ctrl.write_volatile(read_ctrl());
+    LOG_CTRL.set(ctrl);
 }
 
 /// Initializes the logger
@@ -182,6 +372,13 @@ pub fn init(tile_id: TileId, name: &str, color: LogColor) {
                     .unwrap_or_else(|_| panic!("Unable to decode log-flag '{}'", flag))
             })
             .collect();
-        LOG_FLAGS.set(flags);
+        set_flags(flags);
+    }
+    if let Some(fmt) = env::boot_var("LOGFMT") {
+        set_format(match fmt {
+            "text" => LogFormat::Text,
+            "json" => LogFormat::Json,
+            _ => panic!("Unable to decode log format '{}'", fmt),
+        });
     }
 }
@@ -31,6 +31,9 @@ bitflags! {
     /// Additionally, there are per-component flags such as `KernEPs`, `ResMngChild`, or `PgReqs`
     /// that control the logging of certain aspects within a specific component.
     ///
+    /// The flags and the output format (see `LOGFMT`) of running activities can be changed via the
+    /// resource manager (see `logctl`), provided that the requester has the `logctl` permission.
+    ///
     /// Note however that the log flags are hard coded to `Info` and `Error` in bench mode
     /// (`M3_BUILD=bench`)!
     #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -65,9 +68,15 @@ bitflags! {
         const LibHeap       = 1 << (Self::__lib_start.bits() + 8);
         /// libraries: data channel
         const LibDataChan   = 1 << (Self::__lib_start.bits() + 9);
+        /// libraries: virtio device and queue operations
+        const LibVirtio     = 1 << (Self::__lib_start.bits() + 10);
+        /// libraries: TLS handshakes and records
+        const LibTls        = 1 << (Self::__lib_start.bits() + 11);
+        /// libraries: USB host controller and device operations
+        const LibUsb        = 1 << (Self::__lib_start.bits() + 12);
 
         #[doc(hidden)]
-        const __kern_start = Self::__lib_start.bits() + 10;
+        const __kern_start = Self::__lib_start.bits() + 13;
 
         /// Kernel: endpoint configurations for user tiles
         const KernEPs       = 1 << (Self::__kern_start.bits() + 0);
@@ -229,14 +238,88 @@ bitflags! {
         const DiskCtrl      = 1 << (Self::__disk_start.bits() + 3);
         /// disk: more verbose output
         const DiskDbg       = 1 << (Self::__disk_start.bits() + 4);
+        /// disk: block cache operations
+        const DiskCache     = 1 << (Self::__disk_start.bits() + 5);
 
         #[doc(hidden)]
-        const __pipe_start = Self::__disk_start.bits() + 5;
+        const __pipe_start = Self::__disk_start.bits() + 6;
 
         /// pipe: requests
         const PipeReqs      = 1 << (Self::__pipe_start.bits() + 0);
         /// pipe: data transfers / state changes
         const PipeData      = 1 << (Self::__pipe_start.bits() + 1);
+
+        #[doc(hidden)]
+        const __shm_start = Self::__pipe_start.bits() + 2;
+
+        /// shm: requests
+        const ShmReqs       = 1 << (Self::__shm_start.bits() + 0);
+
+        #[doc(hidden)]
+        const __pty_start = Self::__shm_start.bits() + 1;
+
+        /// pty: requests
+        const PtyReqs       = 1 << (Self::__pty_start.bits() + 0);
+        /// pty: input/output operations
+        const PtyInOut      = 1 << (Self::__pty_start.bits() + 1);
+        /// pty: sent events
+        const PtyEvents     = 1 << (Self::__pty_start.bits() + 2);
+
+        #[doc(hidden)]
+        const __clock_start = Self::__pty_start.bits() + 3;
+
+        /// clock: requests
+        const ClockReqs     = 1 << (Self::__clock_start.bits() + 0);
+        /// clock: time synchronization
+        const ClockSync     = 1 << (Self::__clock_start.bits() + 1);
+
+        #[doc(hidden)]
+        const __hostfs_start = Self::__clock_start.bits() + 2;
+
+        /// hostfs: requests
+        const HostFSReqs    = 1 << (Self::__hostfs_start.bits() + 0);
+        /// hostfs: 9P messages exchanged with the host
+        const HostFS9P      = 1 << (Self::__hostfs_start.bits() + 1);
+
+        #[doc(hidden)]
+        const __fatfs_start = Self::__hostfs_start.bits() + 2;
+
+        /// fatfs: requests
+        const FatFSReqs     = 1 << (Self::__fatfs_start.bits() + 0);
+        /// fatfs: accesses of the device
+        const FatFSDev      = 1 << (Self::__fatfs_start.bits() + 1);
+
+        #[doc(hidden)]
+        const __crypto_start = Self::__fatfs_start.bits() + 2;
+
+        /// crypto: requests
+        const CryptoReqs    = 1 << (Self::__crypto_start.bits() + 0);
+        /// crypto: key management
+        const CryptoKeys    = 1 << (Self::__crypto_start.bits() + 1);
+
+        #[doc(hidden)]
+        const __kv_start = Self::__crypto_start.bits() + 2;
+
+        /// kvstore: requests
+        const KVReqs        = 1 << (Self::__kv_start.bits() + 0);
+        /// kvstore: log operations (replay, appends, compaction)
+        const KVLog         = 1 << (Self::__kv_start.bits() + 1);
+
+        #[doc(hidden)]
+        const __fb_start = Self::__kv_start.bits() + 2;
+
+        /// fb: requests
+        const FbReqs        = 1 << (Self::__fb_start.bits() + 0);
+        /// fb: device operations
+        const FbDev         = 1 << (Self::__fb_start.bits() + 1);
+
+        #[doc(hidden)]
+        const __audio_start = Self::__fb_start.bits() + 2;
+
+        /// audio: requests
+        const AudioReqs     = 1 << (Self::__audio_start.bits() + 0);
+        /// audio: device operations
+        const AudioDev      = 1 << (Self::__audio_start.bits() + 1);
     }
 }
 
@@ -27,7 +27,7 @@ pub use self::logflags::LogFlags;
 pub use self::rdwr::{read_object, Read, Write};
 pub use self::serial::Serial;
 
-use crate::io::log::{Log, LogColor};
+use crate::io::log::{Log, LogColor, LogFormat};
 use crate::tcu::TileId;
 
 use core::fmt;
@@ -52,8 +52,9 @@ macro_rules! log {
     );
 
     (@log_impl $flag:expr, $($args:tt)*)      => ({
-        if $crate::util::unlikely($crate::io::should_log($flag)) {
-            $crate::io::log_str(format_args!($($args)*));
+        let flag = $flag;
+        if $crate::util::unlikely($crate::io::should_log(flag)) {
+            $crate::io::log_msg(flag, format_args!($($args)*));
         }
     });
 }
@@ -80,6 +81,22 @@ pub fn log_str(fmt: fmt::Arguments<'_>) {
     }
 }
 
+/// Helper for the log macro that writes the message for given flag in the current log format
+#[cold]
+#[inline(never)]
+pub fn log_msg(flag: LogFlags, fmt: fmt::Arguments<'_>) {
+    if let Some(mut l) = Log::get() {
+        if log::format() == LogFormat::Json {
+            l.begin_record(flag);
+            l.write_fmt(fmt).unwrap();
+            l.end_record();
+        }
+        else {
+            l.write_fmt(fmt).unwrap();
+        }
+    }
+}
+
 /// Writes the given byte array to the log, showing `addr` as a prefix
 ///
 /// # Safety
@@ -17,7 +17,7 @@
 
 use core::fmt;
 
-use crate::kif;
+use crate::kif::{self, service};
 use crate::mem::GlobAddr;
 use crate::tcu::TileId;
 use crate::util;
@@ -179,16 +179,18 @@ impl fmt::Debug for Mem {
 #[derive(Default, Copy, Clone)]
 pub struct Service {
     sessions: u32,
+    version: service::Version,
     name: [u8; MAX_SERVNAME_LEN],
 }
-const _: () = assert!(crate::mem::size_of::<Service>() == 4 + MAX_SERVNAME_LEN);
+const _: () = assert!(crate::mem::size_of::<Service>() == 8 + MAX_SERVNAME_LEN);
 
 impl Service {
     /// Creates a new service
-    pub fn new(name: &str, sessions: u32) -> Self {
+    pub fn new(name: &str, version: service::Version, sessions: u32) -> Self {
         assert!(name.len() < MAX_SERVNAME_LEN);
         let mut m = Self {
             sessions,
+            version,
             name: [0; MAX_SERVNAME_LEN],
         };
         m.name[..name.len()].copy_from_slice(name.as_bytes());
@@ -200,6 +202,11 @@ impl Service {
         self.sessions
     }
 
+    /// Returns the protocol version of the service
+    pub fn version(&self) -> service::Version {
+        self.version
+    }
+
     /// Returns the name of the service
     pub fn name(&self) -> &str {
         util::cstr_slice_to_str(&self.name)
@@ -208,6 +215,6 @@ impl Service {
 
 impl fmt::Debug for Service {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
-        write!(f, "Serv[name: {}]", self.name(),)
+        write!(f, "Serv[name: {}, version: {}]", self.name(), self.version)
     }
 }
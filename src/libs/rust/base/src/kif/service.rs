@@ -18,10 +18,67 @@
 
 //! The service interface
 
+use core::fmt;
+
 use super::syscalls::ExchangeArgs;
+use crate::errors::{Code, Error};
 use crate::kif::{CapRngDesc, CapSel};
 use crate::serialize::{Deserialize, Serialize};
 
+/// The version of a service protocol
+///
+/// A service version is compatible to a requested version if both have the same major version and
+/// the minor version of the service is at least the requested minor version. The version 0.0
+/// denotes an unversioned service.
+#[derive(Copy, Clone, Default, Debug, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
+#[repr(C)]
+pub struct Version {
+    pub major: u16,
+    pub minor: u16,
+}
+
+impl Version {
+    /// Creates a new version with given major and minor version
+    pub const fn new(major: u16, minor: u16) -> Self {
+        Self { major, minor }
+    }
+
+    /// Parses a version in the form "major" or "major.minor"
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        let mut parts = s.splitn(2, '.');
+        let major = parts
+            .next()
+            .and_then(|m| m.parse::<u16>().ok())
+            .ok_or_else(|| Error::new(Code::InvArgs))?;
+        let minor = match parts.next() {
+            Some(m) => m.parse::<u16>().map_err(|_| Error::new(Code::InvArgs))?,
+            None => 0,
+        };
+        Ok(Self::new(major, minor))
+    }
+
+    /// Splits the given service name in the form "name@version" into the name and the version
+    ///
+    /// If `name` does not contain a version, `None` is returned for the version.
+    pub fn split_name(name: &str) -> Result<(&str, Option<Self>), Error> {
+        match name.split_once('@') {
+            Some((name, ver)) => Ok((name, Some(Self::parse(ver)?))),
+            None => Ok((name, None)),
+        }
+    }
+
+    /// Returns true if a service with this version can serve clients that requested `req`
+    pub fn is_compatible(&self, req: &Version) -> bool {
+        self.major == req.major && self.minor >= req.minor
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
 /// The data part of the delegate/obtain request messages
 #[derive(Default, Serialize, Deserialize)]
 #[repr(C)]
@@ -39,6 +96,7 @@ pub enum Request<'s> {
     Delegate { sid: u64, data: ExchangeData },
     Close { sid: u64 },
     Shutdown,
+    Revoked { sid: u64, sel: CapSel },
 }
 
 /// The open reply message
@@ -62,4 +120,7 @@ pub struct DeriveCreatorReply {
 #[repr(C)]
 pub struct ExchangeReply {
     pub data: ExchangeData,
+    /// Whether the server wants to receive a [`Request::Revoked`] for each delegated capability as
+    /// soon as it gets revoked
+    pub notify_revoke: bool,
 }
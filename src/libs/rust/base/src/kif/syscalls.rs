@@ -31,6 +31,9 @@ use crate::tcu::{ActId, EpId, Label, TileId};
 /// The maximum number of arguments for the exchange syscalls
 pub const MAX_EXCHG_ARGS: usize = 8;
 
+/// The maximum number of capability ranges that can be exchanged with one exchange syscall
+pub const MAX_EXCHG_DESCS: usize = 16;
+
 /// The maximum number of activities one can wait for
 pub const MAX_WAIT_ACTS: usize = 32;
 
@@ -47,12 +50,14 @@ pub enum Operation {
     CreateMap,
     CreateAct,
     CreateSem,
+    CreateGang,
     AllocEP,
 
     // Capability operations
     Activate,
     ActCtrl,
     ActWait,
+    ActTime,
     DeriveMem,
     DeriveKMem,
     DeriveTile,
@@ -67,7 +72,9 @@ pub enum Operation {
     TileMem,
     TileInfo,
     TileReset,
+    TileCtrl,
     SemCtrl,
+    GangCtrl,
 
     // Capability exchange
     ExchangeSess,
@@ -76,6 +83,7 @@ pub enum Operation {
 
     // Misc
     ResetStats,
+    GetStats,
     Noop,
 }
 
@@ -152,6 +160,14 @@ pub struct CreateSem {
     pub value: u32,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+#[repr(C)]
+pub struct CreateGang {
+    pub dst: CapSel,
+    /// The number of activities that need to join the gang before it can be started
+    pub size: u32,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[repr(C)]
 pub struct AllocEP {
@@ -176,6 +192,8 @@ pub struct Activate {
 pub enum ActivityOp {
     Start = 1,
     Stop,
+    Suspend,
+    Resume,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -194,6 +212,12 @@ pub struct ActivityWait {
     pub acts: [CapSel; MAX_WAIT_ACTS],
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+#[repr(C)]
+pub struct ActivityTime {
+    pub act: CapSel,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[repr(C)]
 pub struct DeriveMem {
@@ -221,6 +245,7 @@ pub struct DeriveTile {
     pub eps: Option<usize>,
     pub time: Option<u64>,
     pub pts: Option<usize>,
+    pub irqs: Option<u32>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -332,12 +357,39 @@ pub struct TileReset {
     pub ep_count: Option<usize>,
 }
 
+/// The operations for the `tile_ctrl` system call
+#[derive(Copy, Clone, Debug, Eq, PartialEq, IntoPrimitive, Serialize_repr, Deserialize_repr)]
+#[repr(u64)]
+pub enum TileOp {
+    /// Takes the tile offline so that it can be reconfigured
+    Offline,
+    /// Brings an offline tile online with a new description
+    Online,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[repr(C)]
+pub struct TileCtrl {
+    pub tile: CapSel,
+    pub op: TileOp,
+    /// The new description of the tile (only used for [`TileOp::Online`])
+    pub desc: TileDesc,
+}
+
 /// The operations for the `sem_ctrl` system call
 #[derive(Copy, Clone, Debug, Eq, PartialEq, IntoPrimitive, Serialize_repr, Deserialize_repr)]
 #[repr(u64)]
 pub enum SemOp {
+    /// Increments the value and wakes up a waiter, if any
     Up,
+    /// Waits until the value is non-zero and decrements it
     Down,
+    /// Decrements the value if it is non-zero, but does not wait
+    TryDown,
+    /// Like `Down`, but gives up after the given timeout
+    DownFor,
+    /// Only retrieves the current value
+    Value,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -345,6 +397,26 @@ pub enum SemOp {
 pub struct SemCtrl {
     pub sem: CapSel,
     pub op: SemOp,
+    /// The timeout in nanoseconds (only used for `DownFor`)
+    pub timeout: u64,
+}
+
+/// The operations for the `gang_ctrl` system call
+#[derive(Copy, Clone, Debug, Eq, PartialEq, IntoPrimitive, Serialize_repr, Deserialize_repr)]
+#[repr(u64)]
+pub enum GangOp {
+    /// Adds a not yet started activity to the gang
+    Join,
+    /// Removes a not yet started activity from the gang
+    Leave,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[repr(C)]
+pub struct GangCtrl {
+    pub gang: CapSel,
+    pub act: CapSel,
+    pub op: GangOp,
 }
 
 #[repr(C)]
@@ -364,13 +436,28 @@ pub struct ExchangeSess {
     pub obtain: bool,
 }
 
+/// A single capability range to exchange: `own` in the caller's capability space and
+/// `other`..`other`+`own.count()` in the other activity's capability space
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
+#[repr(C)]
+pub struct ExchangeDesc {
+    pub own: CapRngDesc,
+    pub other: CapSel,
+}
+
+impl ExchangeDesc {
+    pub fn new(own: CapRngDesc, other: CapSel) -> Self {
+        Self { own, other }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[repr(C)]
 pub struct Exchange {
     pub act: CapSel,
-    pub own: CapRngDesc,
-    pub other: CapSel,
     pub obtain: bool,
+    pub desc_count: usize,
+    pub descs: [ExchangeDesc; MAX_EXCHG_DESCS],
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -385,6 +472,12 @@ pub struct Revoke {
 #[repr(C)]
 pub struct ResetStats {}
 
+#[derive(Debug, Serialize, Deserialize)]
+#[repr(C)]
+pub struct GetStats {
+    pub act: CapSel,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[repr(C)]
 pub struct Noop {}
@@ -412,6 +505,21 @@ pub struct ActivityWaitReply {
     pub exitcode: Code,
 }
 
+/// The activity time reply message
+#[derive(Debug, Serialize, Deserialize)]
+#[repr(C)]
+pub struct ActivityTimeReply {
+    pub nanos: u64,
+}
+
+/// The semaphore control reply message
+#[derive(Debug, Serialize, Deserialize)]
+#[repr(C)]
+pub struct SemCtrlReply {
+    /// The value of the semaphore after the operation
+    pub value: u32,
+}
+
 /// The kernel gate region reply message
 #[derive(Debug, Serialize, Deserialize)]
 #[repr(C)]
@@ -458,3 +566,29 @@ pub struct TileQuotaReply {
 pub struct ExchangeSessReply {
     pub args: ExchangeArgs,
 }
+
+/// The get stats reply message
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[repr(C)]
+pub struct GetStatsReply {
+    /// The number of object capabilities of the activity
+    pub obj_caps: usize,
+    /// The number of mapping capabilities of the activity
+    pub map_caps: usize,
+    /// The number of system calls the activity has performed
+    pub act_syscalls: u64,
+    /// The number of system calls all activities have performed
+    pub syscalls: u64,
+    /// The number of messages the kernel has sent and waits for a reply
+    pub pending_msgs: usize,
+    /// The number of send queues that wait until they can send their messages
+    pub delayed_queues: usize,
+    /// The total amount of user memory in bytes
+    pub mem_total: u64,
+    /// The amount of free user memory in bytes
+    pub mem_free: u64,
+    /// The size of the largest contiguous free user memory region in bytes
+    pub mem_largest: u64,
+    /// The number of free user memory regions, indicating the fragmentation
+    pub mem_fragments: usize,
+}
@@ -61,6 +61,8 @@ pub enum TileISA {
     NICDev,
     /// Dummy ISA to represent the serial input device
     SerialDev,
+    /// Dummy ISA to represent the USB host controller
+    USBDev,
 }
 
 bitflags! {
@@ -84,6 +86,8 @@ bitflags! {
         const IEPS          = 1 << 5;
         /// Contains a Keccak Accelerator (KecAcc)
         const KECACC        = 1 << 6;
+        /// Contains a core with vector extension
+        const VEC           = 1 << 7;
     }
 }
 
@@ -169,6 +173,7 @@ impl TileDesc {
         self.isa() == TileISA::NICDev
             || self.isa() == TileISA::IDEDev
             || self.isa() == TileISA::SerialDev
+            || self.isa() == TileISA::USBDev
     }
 
     /// Return if the tile supports activities
@@ -191,6 +196,11 @@ impl TileDesc {
         self.attr().contains(TileAttr::IEPS)
     }
 
+    /// Returns whether the tile has a core with vector extension
+    pub fn has_vector(self) -> bool {
+        self.attr().contains(TileAttr::VEC)
+    }
+
     /// Returns whether the tile supports virtual memory
     pub fn has_virtmem(self) -> bool {
         // all non-device tiles without internal memory have currently VM support
@@ -238,6 +248,14 @@ impl TileDesc {
                         res.attr() | TileAttr::SERIAL,
                     )
                 },
+                "vec" => {
+                    res = TileDesc::new_with_attr(
+                        res.tile_type(),
+                        res.isa(),
+                        0,
+                        res.attr() | TileAttr::VEC,
+                    )
+                },
                 "kecacc" => {
                     res = TileDesc::new_with_attr(
                         res.tile_type(),
@@ -295,6 +313,14 @@ impl TileDesc {
                         TileAttr::empty(),
                     )
                 },
+                "usbdev" => {
+                    res = TileDesc::new_with_attr(
+                        TileType::Comp,
+                        TileISA::USBDev,
+                        0,
+                        TileAttr::empty(),
+                    )
+                },
 
                 _ => {},
             }
@@ -329,6 +355,20 @@ impl TileDesc {
         (self.rbuf_base() - cfg::STACK_SIZE, cfg::STACK_SIZE)
     }
 
+    /// Returns the starting address and size of the guard area below the stack, if any.
+    ///
+    /// The guard area is never mapped so that accesses to it can be detected as stack overflows.
+    /// This requires virtual memory; without virtual memory, there is no guard area.
+    pub fn stack_guard(self) -> Option<(VirtAddr, usize)> {
+        if self.has_virtmem() {
+            let (stack_addr, _) = self.stack_space();
+            Some((stack_addr - cfg::STACK_GUARD_SIZE, cfg::STACK_GUARD_SIZE))
+        }
+        else {
+            None
+        }
+    }
+
     fn rbuf_base(self) -> VirtAddr {
         if self.has_virtmem() {
             cfg::RBUF_STD_ADDR
@@ -44,6 +44,7 @@ pub enum Sidecalls {
     Info,
     ActInit,
     ActCtrl,
+    ActTime,
     Map,
     Translate,
     RemMsgs,
@@ -67,6 +68,8 @@ pub struct Info {}
 pub enum ActivityOp {
     Start,
     Stop,
+    Suspend,
+    Resume,
 }
 
 /// The activity init sidecall
@@ -77,6 +80,7 @@ pub struct ActInit {
     pub time_quota: QuotaId,
     pub pt_quota: QuotaId,
     pub eps_start: EpId,
+    pub irqs: u32,
 }
 
 /// The activity control sidecall
@@ -87,6 +91,13 @@ pub struct ActivityCtrl {
     pub act_op: ActivityOp,
 }
 
+/// The activity time sidecall
+#[derive(Debug, Serialize, Deserialize)]
+#[repr(C)]
+pub struct ActTime {
+    pub act_id: u64,
+}
+
 /// The map sidecall
 #[derive(Debug, Serialize, Deserialize)]
 #[repr(C)]
@@ -69,6 +69,7 @@ pub mod errors;
 pub mod kif;
 pub mod libc;
 pub mod machine;
+pub mod math;
 pub mod mem;
 pub mod msgqueue;
 pub mod quota;
@@ -77,6 +78,7 @@ pub mod serialize;
 pub mod tcu;
 pub mod time;
 pub mod tmif;
+pub mod trace;
 
 #[cfg(feature = "coverage")]
 pub use minicov;
@@ -0,0 +1,27 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! Mathematical functions for `f32` and `f64`
+//!
+//! Without the standard library, `f32` and `f64` lack methods like `sin`, `exp`, or `powf`. This
+//! module provides them based on the `libm` crate, a Rust port of musl's libm, in two flavors:
+//! as free functions like [`sin`] (`f64`) and [`sinf`] (`f32`) and as methods of the [`Float`]
+//! trait, so that `x.sin()` works as usual once the trait is imported.
+//!
+//! The functions are implemented in software and do not depend on an FPU. On ARM, the soft-float
+//! helpers for the basic arithmetic are provided by the `lang` crate.
+
+pub use libm::*;
+pub use num_traits::float::{Float, FloatConst};
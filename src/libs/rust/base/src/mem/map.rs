@@ -21,7 +21,7 @@ use core::ops;
 
 use num_traits::PrimInt;
 
-use crate::col::DList;
+use crate::col::{DList, DListIterMut};
 use crate::errors::{Code, Error};
 use crate::util::math;
 
@@ -45,6 +45,7 @@ impl<T: PrimInt + fmt::LowerHex> fmt::Debug for Area<T> {
 /// The memory map, allowing allocs and frees of memory areas
 pub struct MemMap<T: PrimInt> {
     areas: DList<Area<T>>,
+    largest: T,
 }
 
 impl<T: PrimInt + ops::AddAssign + ops::SubAssign> MemMap<T> {
@@ -52,10 +53,15 @@ impl<T: PrimInt + ops::AddAssign + ops::SubAssign> MemMap<T> {
     pub fn new(addr: T, size: T) -> Self {
         let mut areas = DList::new();
         areas.push_back(Area::new(addr, size));
-        MemMap { areas }
+        MemMap {
+            areas,
+            largest: size,
+        }
     }
 
     /// Allocates a region of `size` bytes, aligned by `align`.
+    ///
+    /// The region is taken from the first area with sufficient space (first fit).
     pub fn allocate(&mut self, size: T, align: T) -> Result<T, Error> {
         // find an area with sufficient space
         let mut it = self.areas.iter_mut();
@@ -63,8 +69,7 @@ impl<T: PrimInt + ops::AddAssign + ops::SubAssign> MemMap<T> {
             match it.next() {
                 None => break None,
                 Some(a) => {
-                    let diff = math::round_up(a.addr, align) - a.addr;
-                    if a.size > diff && a.size - diff >= size {
+                    if Self::remaining(a, size, align).is_some() {
                         break Some(a);
                     }
                 },
@@ -74,27 +79,91 @@ impl<T: PrimInt + ops::AddAssign + ops::SubAssign> MemMap<T> {
         match a {
             None => Err(Error::new(Code::OutOfMem)),
             Some(a) => {
-                // if we need to do some alignment, create a new area in front of a
-                let diff = math::round_up(a.addr, align) - a.addr;
-                if diff != T::zero() {
-                    it.insert_before(Area::new(a.addr, diff));
-                    a.addr += diff;
-                    a.size -= diff;
+                let (res, update) = Self::take(&mut it, a, size, align, self.largest);
+                if update {
+                    self.update_largest();
                 }
+                Ok(res)
+            },
+        }
+    }
 
-                // take it from the front
-                let res = a.addr;
-                a.size -= size;
-                a.addr += size;
-
-                // if the area is empty now, remove it
-                if a.size == T::zero() {
-                    it.remove();
+    /// Allocates a region of `size` bytes, aligned by `align`.
+    ///
+    /// In contrast to [`MemMap::allocate`], the region is taken from the area that leaves the least
+    /// space behind (best fit). This keeps large areas intact and thereby reduces the
+    /// fragmentation in case of many allocations and frees of different sizes. However, it always
+    /// needs to walk over all areas.
+    pub fn allocate_best_fit(&mut self, size: T, align: T) -> Result<T, Error> {
+        // find the area with the least remaining space
+        let mut best: Option<(usize, T)> = None;
+        for (i, a) in self.areas.iter().enumerate() {
+            if let Some(left) = Self::remaining(a, size, align) {
+                if best.map(|(_, b)| left < b).unwrap_or(true) {
+                    best = Some((i, left));
+                    // we can't do better than a perfect fit
+                    if left == T::zero() {
+                        break;
+                    }
                 }
+            }
+        }
 
-                Ok(res)
-            },
+        let (idx, _) = best.ok_or_else(|| Error::new(Code::OutOfMem))?;
+        let mut it = self.areas.iter_mut();
+        let a = it.nth(idx).unwrap();
+        let (res, update) = Self::take(&mut it, a, size, align, self.largest);
+        if update {
+            self.update_largest();
         }
+        Ok(res)
+    }
+
+    /// Returns the space that is left in `a` after allocating `size` bytes aligned by `align` or
+    /// None if `a` is not large enough.
+    fn remaining(a: &Area<T>, size: T, align: T) -> Option<T> {
+        let diff = math::round_up(a.addr, align) - a.addr;
+        if a.size > diff && a.size - diff >= size {
+            Some(a.size - diff - size)
+        }
+        else {
+            None
+        }
+    }
+
+    /// Takes `size` bytes aligned by `align` from `a`, which is the current element of `it`.
+    ///
+    /// Returns the address of the region and whether the largest area needs to be determined
+    /// again.
+    fn take(
+        it: &mut DListIterMut<'_, Area<T>>,
+        a: &mut Area<T>,
+        size: T,
+        align: T,
+        largest: T,
+    ) -> (T, bool) {
+        // if we took space from the largest area, it might not be the largest anymore
+        let update = a.size == largest;
+
+        // if we need to do some alignment, create a new area in front of a
+        let diff = math::round_up(a.addr, align) - a.addr;
+        if diff != T::zero() {
+            it.insert_before(Area::new(a.addr, diff));
+            a.addr += diff;
+            a.size -= diff;
+        }
+
+        // take it from the front
+        let res = a.addr;
+        a.size -= size;
+        a.addr += size;
+
+        // if the area is empty now, remove it
+        if a.size == T::zero() {
+            it.remove();
+        }
+
+        (res, update)
     }
 
     /// Free's the given memory region defined by `addr` and `size`.
@@ -112,7 +181,7 @@ impl<T: PrimInt + ops::AddAssign + ops::SubAssign> MemMap<T> {
             }
         };
 
-        let res = {
+        let (res, merged) = {
             let p: Option<&mut Area<T>> = it.peek_prev();
             match (p, n) {
                 // merge with prev and next
@@ -120,23 +189,23 @@ impl<T: PrimInt + ops::AddAssign + ops::SubAssign> MemMap<T> {
                     if p.addr + p.size == addr && addr + size == n.addr =>
                 {
                     p.size += size + n.size;
-                    1
+                    (1, p.size)
                 },
 
                 // merge with prev
                 (Some(ref mut p), _) if p.addr + p.size == addr => {
                     p.size += size;
-                    0
+                    (0, p.size)
                 },
 
                 // merge with next
                 (_, Some(ref mut n)) if addr + size == n.addr => {
                     n.addr -= size;
                     n.size += size;
-                    0
+                    (0, n.size)
                 },
 
-                (_, _) => 2,
+                (_, _) => (2, size),
             }
         };
 
@@ -146,14 +215,30 @@ impl<T: PrimInt + ops::AddAssign + ops::SubAssign> MemMap<T> {
         else if res == 2 {
             it.insert_before(Area::new(addr, size));
         }
+
+        // frees can only make the largest area larger
+        if merged > self.largest {
+            self.largest = merged;
+        }
     }
 
     /// Returns the size of the largest contiguous free space
+    ///
+    /// The size is tracked during allocations and frees and can therefore be obtained cheaply.
     pub fn largest_contiguous(&self) -> Option<T> {
-        self.areas
+        match self.areas.len() {
+            0 => None,
+            _ => Some(self.largest),
+        }
+    }
+
+    fn update_largest(&mut self) {
+        self.largest = self
+            .areas
             .iter()
-            .max_by(|a, b| a.size.cmp(&b.size))
             .map(|a| a.size)
+            .max()
+            .unwrap_or_else(T::zero);
     }
 
     /// Returns a pair of the remaining space and the number of areas.
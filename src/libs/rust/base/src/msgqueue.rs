@@ -15,7 +15,7 @@
 
 //! Contains the message queue types
 
-use crate::col::{DList, Vec};
+use crate::col::{MpscQueue, Vec};
 use crate::errors::Error;
 use crate::mem::MsgBuf;
 
@@ -49,7 +49,7 @@ pub trait MsgSender<M> {
 /// [`MsgSender`] given as the type argument `S`. Additionally, the custom meta data `M` will be
 /// passed to every sent message.
 pub struct MsgQueue<S: MsgSender<M>, M> {
-    queue: DList<PendingMsg<M>>,
+    queue: MpscQueue<PendingMsg<M>>,
     sender: S,
 }
 
@@ -57,7 +57,7 @@ impl<S: MsgSender<M>, M> MsgQueue<S, M> {
     /// Creates a new message queue with given sender
     pub const fn new(sender: S) -> Self {
         Self {
-            queue: DList::new(),
+            queue: MpscQueue::new(),
             sender,
         }
     }
@@ -72,6 +72,16 @@ impl<S: MsgSender<M>, M> MsgQueue<S, M> {
         &mut self.sender
     }
 
+    /// Returns the number of messages that are queued for a later retry
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Returns true if no messages are queued
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
     /// Sends the given message with given meta data.
     ///
     /// If sending is currently possible, it happens immediately. Otherwise, the message is queued
@@ -83,7 +93,7 @@ impl<S: MsgSender<M>, M> MsgQueue<S, M> {
 
         // copy message to heap
         let msg = msg.bytes().to_vec();
-        self.queue.push_back(PendingMsg { msg, meta });
+        self.queue.push(PendingMsg { msg, meta });
         Ok(false)
     }
 
@@ -92,7 +102,7 @@ impl<S: MsgSender<M>, M> MsgQueue<S, M> {
     /// Returns true if any message was sent
     pub fn send_pending(&mut self) -> bool {
         loop {
-            match self.queue.pop_front() {
+            match self.queue.pop() {
                 None => break false,
 
                 Some(e) => {
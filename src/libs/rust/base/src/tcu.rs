@@ -542,6 +542,7 @@ impl TCU {
         reply_lbl: Label,
         reply_ep: EpId,
     ) -> Result<(), Error> {
+        crate::trace!(Instant, crate::trace::EventId::MsgSend, 0, ep, len);
         let msg_addr = VirtAddr::from(msg);
         Self::write_data(msg_addr, len);
         if reply_lbl != 0 {
@@ -616,6 +617,29 @@ impl TCU {
         )
     }
 
+    /// Uses the TCU read command to read from the memory region denoted by the endpoint at offset
+    /// `off` into the given segments. The segments are filled back-to-back, that is, the first
+    /// segment receives the data at `off`, the second one the data directly following it, etc.
+    pub fn read_v(ep: EpId, bufs: &mut [&mut [u8]], mut off: GlobOff) -> Result<(), Error> {
+        let mut res = Ok(());
+        for buf in bufs.iter_mut() {
+            res = Self::perform_transfer(
+                ep,
+                VirtAddr::from(buf.as_mut_ptr()),
+                buf.len(),
+                off,
+                CmdOpCode::Read,
+            );
+            if res.is_err() {
+                break;
+            }
+            off += buf.len() as GlobOff;
+        }
+        // see read
+        atomic::fence(atomic::Ordering::SeqCst);
+        res
+    }
+
     /// Reads `mem::size_of::<T>()` bytes via the TCU read command from the memory region
     /// denoted by the endpoint at offset `off` and returns the data as an object of `T`.
     pub fn read_obj<T>(ep: EpId, off: GlobOff) -> Result<T, Error> {
@@ -639,6 +663,24 @@ impl TCU {
         Self::write(ep, data.as_ptr() as *const u8, mem::size_of_val(data), off)
     }
 
+    /// Writes the given segments back-to-back to offset `off` in the memory region denoted by the
+    /// endpoint.
+    pub fn write_v(ep: EpId, bufs: &[&[u8]], mut off: GlobOff) -> Result<(), Error> {
+        // see write
+        atomic::fence(atomic::Ordering::SeqCst);
+        for buf in bufs {
+            Self::perform_transfer(
+                ep,
+                VirtAddr::from(buf.as_ptr()),
+                buf.len(),
+                off,
+                CmdOpCode::Write,
+            )?;
+            off += buf.len() as GlobOff;
+        }
+        Ok(())
+    }
+
     /// Writes `obj` to offset `off` in the memory region denoted by the endpoint.
     pub fn write_obj<T>(ep: EpId, obj: &T, off: GlobOff) -> Result<(), Error> {
         Self::write(ep, obj as *const T as *const u8, mem::size_of::<T>(), off)
@@ -702,6 +744,7 @@ impl TCU {
         Self::get_error().ok()?;
         let msg = Self::read_unpriv_reg(UnprivReg::Arg1);
         if msg != !0 {
+            crate::trace!(Instant, crate::trace::EventId::MsgRecv, 0, ep, msg);
             Some(msg as usize)
         }
         else {
@@ -76,6 +76,13 @@ impl TimeInstant {
     pub fn elapsed(&self) -> TimeDuration {
         TimeDuration::from_nanos(Self::now().0 - self.0)
     }
+
+    /// Returns the instant `duration` after this one or the latest representable instant in case of
+    /// an overflow.
+    pub fn saturating_add(&self, duration: TimeDuration) -> Self {
+        let nanos = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+        Self(self.0.saturating_add(nanos))
+    }
 }
 
 impl Add<TimeDuration> for TimeInstant {
@@ -20,9 +20,13 @@
 
 mod duration;
 mod instant;
+mod pmu;
 mod profile;
+mod system;
 
 pub use self::duration::{CycleDuration, Duration};
 pub use self::instant::{CycleInstant, Instant, TimeInstant};
+pub use self::pmu::{PmuDuration, PmuInstant};
 pub use self::profile::{Profiler, Results, Runner};
+pub use self::system::{SystemTime, BOOT_TIME_VAR};
 pub use core::time::Duration as TimeDuration;
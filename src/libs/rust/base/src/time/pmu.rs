@@ -0,0 +1,145 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! Contains measurements based on the performance counters
+
+use core::fmt;
+
+use crate::tmif::{self, PmuCounter, PmuValues, PMU_COUNTERS};
+
+/// A snapshot of the performance counters of the current activity
+///
+/// The counters need to be enabled first, e.g., via [`PmuInstant::enable`]. If no counters are
+/// enabled or the platform does not support them, all snapshots are empty.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct PmuInstant(PmuValues);
+
+impl PmuInstant {
+    /// Enables as many of the counters cycles, instructions, and cache misses as supported and
+    /// returns true if at least one counter has been enabled
+    pub fn enable() -> bool {
+        let all = [
+            PmuCounter::Cycles,
+            PmuCounter::Instrs,
+            PmuCounter::CacheMisses,
+        ];
+        (1..=all.len())
+            .rev()
+            .any(|n| tmif::pmu_config(&all[0..n]).is_ok())
+    }
+
+    /// Disables all counters
+    pub fn disable() {
+        tmif::pmu_config(&[]).ok();
+    }
+
+    /// Returns a snapshot of the current counter values
+    pub fn now() -> Self {
+        Self(tmif::pmu_read().unwrap_or_default())
+    }
+
+    /// Returns the value of the given counter or `None` if the counter is not enabled
+    pub fn get(&self, counter: PmuCounter) -> Option<u64> {
+        self.0.get(counter)
+    }
+
+    /// Returns the events that occurred from another snapshot to this one
+    pub fn duration_since(&self, earlier: Self) -> PmuDuration {
+        let mut res = PmuDuration {
+            enabled: self.0.enabled & earlier.0.enabled,
+            ..Default::default()
+        };
+        for i in 0..PMU_COUNTERS {
+            res.values[i] = self.0.values[i].saturating_sub(earlier.0.values[i]);
+        }
+        res
+    }
+}
+
+/// The number of events between two [`PmuInstant`]s
+#[derive(Copy, Clone, Default)]
+pub struct PmuDuration {
+    enabled: usize,
+    values: [u64; PMU_COUNTERS],
+}
+
+impl PmuDuration {
+    /// Returns true if no counters are enabled
+    pub fn is_empty(&self) -> bool {
+        self.enabled == 0
+    }
+
+    /// Returns the number of events for the given counter or `None` if the counter is not enabled
+    pub fn get(&self, counter: PmuCounter) -> Option<u64> {
+        match self.enabled & PmuCounter::mask(&[counter]) {
+            0 => None,
+            _ => Some(self.values[counter as usize]),
+        }
+    }
+
+    /// Returns the instructions per cycle, if both counters are enabled
+    pub fn ipc(&self) -> Option<f32> {
+        let cycles = self.get(PmuCounter::Cycles)?;
+        let instrs = self.get(PmuCounter::Instrs)?;
+        match cycles {
+            0 => None,
+            c => Some(instrs as f32 / c as f32),
+        }
+    }
+
+    pub(crate) fn add(&mut self, other: &PmuDuration) {
+        self.enabled = if self.is_empty() {
+            other.enabled
+        }
+        else {
+            self.enabled & other.enabled
+        };
+        for i in 0..PMU_COUNTERS {
+            self.values[i] += other.values[i];
+        }
+    }
+
+    pub(crate) fn div(&self, runs: u64) -> PmuDuration {
+        let mut res = *self;
+        if runs > 0 {
+            for v in &mut res.values {
+                *v /= runs;
+            }
+        }
+        res
+    }
+}
+
+impl fmt::Debug for PmuDuration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut sep = "";
+        if let Some(cycles) = self.get(PmuCounter::Cycles) {
+            write!(f, "{}{} cycles", sep, cycles)?;
+            sep = ", ";
+        }
+        if let Some(instrs) = self.get(PmuCounter::Instrs) {
+            write!(f, "{}{} instrs", sep, instrs)?;
+            sep = ", ";
+        }
+        if let Some(ipc) = self.ipc() {
+            write!(f, "{}IPC {:.2}", sep, ipc)?;
+            sep = ", ";
+        }
+        if let Some(misses) = self.get(PmuCounter::CacheMisses) {
+            write!(f, "{}{} cache misses", sep, misses)?;
+        }
+        Ok(())
+    }
+}
@@ -21,13 +21,15 @@
 use core::fmt;
 
 use crate::col::Vec;
-use crate::time::{Duration, Instant};
+use crate::time::{Duration, Instant, PmuDuration, PmuInstant};
 use crate::util::math;
 
 /// A container for the measured execution times
 #[derive(Clone)]
 pub struct Results<T: Duration> {
     times: Vec<T>,
+    // the sum of the performance counter events of all runs
+    pmu: PmuDuration,
 }
 
 impl<T: Duration> Results<T> {
@@ -35,6 +37,7 @@ impl<T: Duration> Results<T> {
     pub fn new(runs: usize) -> Self {
         Results {
             times: Vec::with_capacity(runs),
+            pmu: PmuDuration::default(),
         }
     }
 
@@ -48,6 +51,18 @@ impl<T: Duration> Results<T> {
         self.times.push(time);
     }
 
+    /// Adds the given performance counter events of one run
+    pub fn push_pmu(&mut self, events: &PmuDuration) {
+        self.pmu.add(events);
+    }
+
+    /// Returns the average number of performance counter events per run
+    ///
+    /// The result is empty if the performance counters have not been measured.
+    pub fn pmu_avg(&self) -> PmuDuration {
+        self.pmu.div(self.times.len() as u64)
+    }
+
     /// Returns the number of runs
     pub fn runs(&self) -> usize {
         self.times.len()
@@ -97,7 +112,11 @@ impl<T: Duration> fmt::Display for Results<T> {
             self.avg(),
             self.stddev(),
             self.runs(),
-        )
+        )?;
+        if !self.pmu.is_empty() {
+            write!(f, " [{:?}]", self.pmu_avg())?;
+        }
+        Ok(())
     }
 }
 
@@ -134,9 +153,19 @@ impl<T: Duration> fmt::Display for Results<T> {
 /// let mut prof = profile::Profiler::default().repeats(10).warmup(2);
 /// println!("{}", prof.runner::<CycleInstant, _>(&mut Tester::default()));
 /// ```
+///
+/// With performance counters (e.g., to report the IPC and cache misses):
+///
+/// ```
+/// use base::profile;
+///
+/// let mut prof = profile::Profiler::default().pmu(true);
+/// println!("{}", prof.run::<CycleInstant, _>(|| /* my benchmark */));
+/// ```
 pub struct Profiler {
     repeats: u64,
     warmup: u64,
+    pmu: bool,
 }
 
 /// A runner is used to run the benchmarks and allows to perform pre- and post-actions.
@@ -166,17 +195,30 @@ impl Profiler {
         self
     }
 
+    /// Sets whether the performance counters should be measured as well
+    ///
+    /// The counters are enabled (see [`PmuInstant::enable`]) at the start of each benchmark. If
+    /// they are not supported, the results simply do not contain them.
+    pub fn pmu(mut self, pmu: bool) -> Self {
+        self.pmu = pmu;
+        self
+    }
+
     /// Runs `func` as benchmark and returns the result
     #[inline(always)]
     pub fn run<T: Instant, F: FnMut()>(&self, mut func: F) -> Results<T::Duration> {
         let mut res = Results::new((self.warmup + self.repeats) as usize);
+        let pmu = self.pmu && PmuInstant::enable();
         for i in 0..self.warmup + self.repeats {
+            let pmu_start = pmu.then(PmuInstant::now).unwrap_or_default();
             let start = T::now();
             func();
             let end = T::now();
+            let pmu_end = pmu.then(PmuInstant::now).unwrap_or_default();
 
             if i >= self.warmup {
                 res.push(end.duration_since(start));
+                res.push_pmu(&pmu_end.duration_since(pmu_start));
             }
         }
         res
@@ -186,17 +228,21 @@ impl Profiler {
     #[inline(always)]
     pub fn runner<T: Instant, R: Runner>(&self, runner: &mut R) -> Results<T::Duration> {
         let mut res = Results::new((self.warmup + self.repeats) as usize);
+        let pmu = self.pmu && PmuInstant::enable();
         for i in 0..self.warmup + self.repeats {
             runner.pre();
 
+            let pmu_start = pmu.then(PmuInstant::now).unwrap_or_default();
             let start = T::now();
             runner.run();
             let end = T::now();
+            let pmu_end = pmu.then(PmuInstant::now).unwrap_or_default();
 
             runner.post();
 
             if i >= self.warmup {
                 res.push(end.duration_since(start));
+                res.push_pmu(&pmu_end.duration_since(pmu_start));
             }
         }
         res
@@ -204,11 +250,12 @@ impl Profiler {
 }
 
 impl Default for Profiler {
-    /// Creates a default profiler with 100 runs and 10 warmup runs
+    /// Creates a default profiler with 100 runs, 10 warmup runs, and without performance counters
     fn default() -> Self {
         Profiler {
             repeats: 100,
             warmup: 10,
+            pmu: false,
         }
     }
 }
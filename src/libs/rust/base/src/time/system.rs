@@ -0,0 +1,201 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use core::fmt;
+use core::ops::{Add, AddAssign, Sub, SubAssign};
+
+use crate::cell::StaticCell;
+use crate::env;
+use crate::time::{TimeDuration, TimeInstant};
+
+/// The environment variable that holds the boot time as a Unix timestamp in nanoseconds
+///
+/// The boot time is the wall-clock time at which the TCU time ([`TimeInstant`]) was zero. It is
+/// typically set by the activity that synchronized the clock via the clock service and inherited
+/// by all child activities created afterwards.
+pub const BOOT_TIME_VAR: &str = "M3_BOOT_TIME";
+
+#[derive(Copy, Clone)]
+enum BootTime {
+    Uninit,
+    Unknown,
+    Known(u64),
+}
+
+static BOOT_TIME: StaticCell<BootTime> = StaticCell::new(BootTime::Uninit);
+
+fn boot_nanos() -> Option<u64> {
+    if let BootTime::Uninit = BOOT_TIME.get() {
+        let time = match env::var(BOOT_TIME_VAR).and_then(|v| v.parse::<u64>().ok()) {
+            Some(nanos) => BootTime::Known(nanos),
+            None => BootTime::Unknown,
+        };
+        BOOT_TIME.set(time);
+    }
+
+    match BOOT_TIME.get() {
+        BootTime::Known(nanos) => Some(nanos),
+        _ => None,
+    }
+}
+
+/// A point in wall-clock time, represented as the duration since the Unix epoch
+///
+/// In contrast to [`TimeInstant`], which is based on the TCU time and thus starts at zero on boot,
+/// `SystemTime` refers to the real time in UTC. It is derived from the TCU time and the boot time
+/// (see [`BOOT_TIME_VAR`]), which is obtained from the clock service. If the boot time is unknown,
+/// [`SystemTime::now`] assumes that the system has been booted at the Unix epoch.
+///
+/// The time is timezone-free and ignores leap seconds, like Unix timestamps.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct SystemTime(u64);
+
+impl SystemTime {
+    /// The Unix epoch (1970-01-01 00:00:00 UTC)
+    pub const UNIX_EPOCH: Self = Self(0);
+
+    /// Returns the current wall-clock time
+    pub fn now() -> Self {
+        Self(boot_nanos().unwrap_or(0) + TimeInstant::now().as_nanos())
+    }
+
+    /// Returns the boot time or `None` if it is unknown
+    pub fn boot_time() -> Option<Self> {
+        boot_nanos().map(Self)
+    }
+
+    /// Sets the boot time, that is, the wall-clock time at which the TCU time was zero
+    ///
+    /// This only affects the current activity. To pass the boot time to child activities, the
+    /// environment variable [`BOOT_TIME_VAR`] needs to be set as well.
+    pub fn set_boot_time(time: Self) {
+        BOOT_TIME.set(BootTime::Known(time.0));
+    }
+
+    /// Returns true if the boot time is known and thus [`SystemTime::now`] yields the real time
+    pub fn is_synced() -> bool {
+        boot_nanos().is_some()
+    }
+
+    /// Creates a new `SystemTime` from the given duration since the Unix epoch
+    pub const fn from_unix(since_epoch: TimeDuration) -> Self {
+        Self(since_epoch.as_nanos() as u64)
+    }
+
+    /// Creates a new `SystemTime` from the given number of seconds since the Unix epoch
+    pub const fn from_unix_secs(secs: u64) -> Self {
+        Self(secs * 1_000_000_000)
+    }
+
+    /// Returns the duration since the Unix epoch
+    pub const fn as_unix(&self) -> TimeDuration {
+        TimeDuration::from_nanos(self.0)
+    }
+
+    /// Returns the number of seconds since the Unix epoch (the Unix timestamp)
+    pub const fn as_unix_secs(&self) -> u64 {
+        self.0 / 1_000_000_000
+    }
+
+    /// Returns the duration since `earlier` or `None` if `earlier` is later than `self`
+    pub fn checked_duration_since(&self, earlier: Self) -> Option<TimeDuration> {
+        self.0.checked_sub(earlier.0).map(TimeDuration::from_nanos)
+    }
+
+    /// Returns the duration since `earlier` or zero if `earlier` is later than `self`
+    pub fn duration_since(&self, earlier: Self) -> TimeDuration {
+        self.checked_duration_since(earlier)
+            .unwrap_or(TimeDuration::ZERO)
+    }
+
+    /// Returns the time elapsed since `self` or zero if `self` is in the future
+    pub fn elapsed(&self) -> TimeDuration {
+        Self::now().duration_since(*self)
+    }
+
+    /// Returns the date and time as (year, month, day, hour, minute, second) in UTC
+    pub fn to_datetime(&self) -> (u32, u32, u32, u32, u32, u32) {
+        let secs = self.as_unix_secs();
+        let days = secs / 86400;
+        let rem = secs % 86400;
+
+        // see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+        let z = days + 719468;
+        let era = z / 146097;
+        let doe = z - era * 146097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + (month <= 2) as u64;
+
+        (
+            year as u32,
+            month as u32,
+            day as u32,
+            (rem / 3600) as u32,
+            ((rem % 3600) / 60) as u32,
+            (rem % 60) as u32,
+        )
+    }
+}
+
+impl Add<TimeDuration> for SystemTime {
+    type Output = Self;
+
+    fn add(self, rhs: TimeDuration) -> Self {
+        Self(self.0 + rhs.as_nanos() as u64)
+    }
+}
+
+impl AddAssign<TimeDuration> for SystemTime {
+    fn add_assign(&mut self, rhs: TimeDuration) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub<TimeDuration> for SystemTime {
+    type Output = Self;
+
+    fn sub(self, rhs: TimeDuration) -> Self {
+        Self(self.0 - rhs.as_nanos() as u64)
+    }
+}
+
+impl SubAssign<TimeDuration> for SystemTime {
+    fn sub_assign(&mut self, rhs: TimeDuration) {
+        *self = *self - rhs;
+    }
+}
+
+/// Formats the time in ISO 8601 format with millisecond precision (e.g.,
+/// `2024-01-31T12:34:56.789Z`)
+impl fmt::Display for SystemTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (year, month, day, hour, min, sec) = self.to_datetime();
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+            year,
+            month,
+            day,
+            hour,
+            min,
+            sec,
+            (self.0 % 1_000_000_000) / 1_000_000
+        )
+    }
+}
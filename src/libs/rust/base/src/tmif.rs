@@ -51,6 +51,116 @@ pub enum Operation {
     InitTLS,
     /// Noop operation for testing purposes
     Noop,
+    /// Wait until a word in user memory is woken up via [`FutexWake`](Operation::FutexWake)
+    FutexWait,
+    /// Wake up activities that wait for a word in user memory
+    FutexWake,
+    /// Register a handler that is called instead of removing the activity on a crash
+    RegCrash,
+    /// Continue the activity with the state given to its crash handler
+    Resume,
+    /// Select the performance counters of the activity and reset them
+    PmuConfig,
+    /// Read the performance counters of the activity
+    PmuRead,
+    /// Claim an external interrupt and receive its occurrences as messages
+    ClaimIRQ,
+    /// Mask or unmask a claimed interrupt
+    MaskIRQ,
+}
+
+/// The number of registers in [`CrashState`]
+#[cfg(target_arch = "x86_64")]
+pub const CRASH_REGS: usize = 27;
+/// The number of registers in [`CrashState`]
+#[cfg(target_arch = "riscv64")]
+pub const CRASH_REGS: usize = 32;
+/// The number of registers in [`CrashState`]
+#[cfg(target_arch = "arm")]
+pub const CRASH_REGS: usize = 18;
+/// The number of registers in [`CrashState`]
+#[cfg(target_arch = "aarch64")]
+pub const CRASH_REGS: usize = 34;
+
+/// The state of a crashed activity, as passed to its crash handler by TileMux
+///
+/// The registers are stored in the order that is used in the `NT_PRSTATUS` note of Linux core
+/// files so that they can be written to core files without conversion.
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+pub struct CrashState {
+    /// The exception vector or cause
+    pub vector: usize,
+    /// The faulting address, if any
+    pub addr: usize,
+    /// The general purpose registers, including the instruction and stack pointer
+    pub regs: [usize; CRASH_REGS],
+}
+
+// Default is not implemented for arrays with more than 32 elements
+impl Default for CrashState {
+    fn default() -> Self {
+        Self {
+            vector: 0,
+            addr: 0,
+            regs: [0; CRASH_REGS],
+        }
+    }
+}
+
+/// The performance counters that TileMux virtualizes per activity
+///
+/// Not all counters are available on all platforms; [`pmu_config`] fails with
+/// [`NotSup`](Code::NotSup) if one of the requested counters is not available.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, IntoPrimitive, TryFromPrimitive)]
+#[repr(usize)]
+pub enum PmuCounter {
+    /// The number of elapsed CPU cycles
+    Cycles,
+    /// The number of retired instructions
+    Instrs,
+    /// The number of last-level cache misses
+    CacheMisses,
+}
+
+/// The number of [`PmuCounter`]s
+pub const PMU_COUNTERS: usize = 3;
+
+impl PmuCounter {
+    /// Returns the bitmask for the given counters, as passed to TileMux
+    pub fn mask(counters: &[PmuCounter]) -> usize {
+        counters.iter().fold(0, |m, c| m | (1 << *c as usize))
+    }
+}
+
+/// The values of the performance counters of an activity, as written by TileMux
+#[derive(Copy, Clone, Debug, Default)]
+#[repr(C)]
+pub struct PmuValues {
+    /// The bitmask of enabled counters (see [`PmuCounter::mask`])
+    pub enabled: usize,
+    /// The values of all counters, indexed by [`PmuCounter`]
+    pub values: [u64; PMU_COUNTERS],
+}
+
+impl PmuValues {
+    /// Returns the value of the given counter or `None` if the counter is not enabled
+    pub fn get(&self, counter: PmuCounter) -> Option<u64> {
+        match self.enabled & PmuCounter::mask(&[counter]) {
+            0 => None,
+            _ => Some(self.values[counter as usize]),
+        }
+    }
+}
+
+/// The message TileMux sends for a claimed interrupt (see [`claim_irq`])
+#[derive(Copy, Clone, Debug, Default)]
+#[repr(C)]
+pub struct IRQMsg {
+    /// The interrupt that occurred
+    pub irq: u64,
+    /// The number of occurrences since the last message
+    pub count: u64,
 }
 
 pub(crate) fn get_result(res: usize) -> Result<(), Error> {
@@ -107,6 +217,46 @@ cfg_if! {
         pub fn noop() -> Result<(), Error> {
             Err(Error::new(Code::NotSup))
         }
+
+        pub fn futex_wait(
+            _addr: VirtAddr,
+            _expected: u32,
+            _timeout: Option<TimeDuration>,
+        ) -> Result<(), Error> {
+            Err(Error::new(Code::NotSup))
+        }
+
+        pub fn futex_wake(_addr: VirtAddr, _count: usize) -> Result<(), Error> {
+            Err(Error::new(Code::NotSup))
+        }
+
+        pub fn reg_crash_handler(
+            _entry: usize,
+            _stack: VirtAddr,
+            _state: VirtAddr,
+        ) -> Result<(), Error> {
+            Err(Error::new(Code::NotSup))
+        }
+
+        pub fn resume(_state: VirtAddr) -> Result<(), Error> {
+            Err(Error::new(Code::NotSup))
+        }
+
+        pub fn pmu_config(_counters: &[PmuCounter]) -> Result<(), Error> {
+            Err(Error::new(Code::NotSup))
+        }
+
+        pub fn claim_irq(_irq: IRQId, _ep: EpId) -> Result<(), Error> {
+            Err(Error::new(Code::NotSup))
+        }
+
+        pub fn mask_irq(_irq: IRQId, _masked: bool) -> Result<(), Error> {
+            Err(Error::new(Code::NotSup))
+        }
+
+        pub fn pmu_read() -> Result<PmuValues, Error> {
+            Err(Error::new(Code::NotSup))
+        }
     }
     else {
         use crate::arch::{TMABIOps, TMABI};
@@ -166,5 +316,88 @@ cfg_if! {
         pub fn noop() -> Result<(), Error> {
             TMABI::call1(Operation::Noop, 0)
         }
+
+        /// Blocks the current activity until another activity on the same tile calls [`futex_wake`]
+        /// for `addr` or the optional timeout expired.
+        ///
+        /// TileMux checks atomically whether the 32-bit word at `addr` still contains `expected`
+        /// and returns [`WouldBlock`](Code::WouldBlock) otherwise. If the timeout expires,
+        /// [`Timeout`](Code::Timeout) is returned.
+        pub fn futex_wait(
+            addr: VirtAddr,
+            expected: u32,
+            timeout: Option<TimeDuration>,
+        ) -> Result<(), Error> {
+            TMABI::call3(
+                Operation::FutexWait,
+                addr.as_local(),
+                expected as usize,
+                match timeout {
+                    Some(d) => d.as_nanos() as usize,
+                    None => usize::MAX,
+                },
+            )
+        }
+
+        /// Wakes up at most `count` activities that wait for `addr` via [`futex_wait`].
+        pub fn futex_wake(addr: VirtAddr, count: usize) -> Result<(), Error> {
+            TMABI::call2(Operation::FutexWake, addr.as_local(), count)
+        }
+
+        /// Registers `entry` as the crash handler of the current activity.
+        ///
+        /// If the activity crashes afterwards, TileMux stores the [`CrashState`] at `state` and
+        /// continues the activity at `entry` on the given stack with a pointer to the state as the
+        /// first argument. The handler is not called again until it continues the activity via
+        /// [`resume`]; a crash within the handler removes the activity.
+        pub fn reg_crash_handler(
+            entry: usize,
+            stack: VirtAddr,
+            state: VirtAddr,
+        ) -> Result<(), Error> {
+            TMABI::call3(Operation::RegCrash, entry, stack.as_local(), state.as_local())
+        }
+
+        /// Leaves the crash handler and continues the activity with the given [`CrashState`].
+        ///
+        /// The state is typically the one TileMux passed to the crash handler, potentially with
+        /// modified registers. Only the registers are taken from the state; privileged parts like
+        /// the segment registers on x86_64 are ignored. On success, this function does not return.
+        pub fn resume(state: VirtAddr) -> Result<(), Error> {
+            TMABI::call1(Operation::Resume, state.as_local())
+        }
+
+        /// Enables the given performance counters for the current activity and resets them to zero.
+        ///
+        /// The counters are virtualized by TileMux, that is, they only count while the activity is
+        /// running. All other counters are disabled; an empty slice disables all counters.
+        pub fn pmu_config(counters: &[PmuCounter]) -> Result<(), Error> {
+            TMABI::call1(Operation::PmuConfig, PmuCounter::mask(counters))
+        }
+
+        /// Reads the performance counters of the current activity.
+        pub fn pmu_read() -> Result<PmuValues, Error> {
+            let mut vals = PmuValues::default();
+            TMABI::call1(Operation::PmuRead, &mut vals as *mut _ as usize)?;
+            Ok(vals)
+        }
+
+        /// Claims the external interrupt `irq` for the current activity.
+        ///
+        /// Each occurrence of the interrupt is sent as an [`IRQMsg`] via the send EP `ep`, which
+        /// typically refers to a receive gate of the activity itself. After a message has been
+        /// sent, the interrupt is masked until it is unmasked via [`mask_irq`]; occurrences in the
+        /// meantime are counted and reported with the next message. Fails with
+        /// [`Exists`](Code::Exists) if the interrupt has already been claimed and with
+        /// [`NoPerm`](Code::NoPerm) if the activity's tile capability does not permit the
+        /// interrupt.
+        pub fn claim_irq(irq: IRQId, ep: EpId) -> Result<(), Error> {
+            TMABI::call2(Operation::ClaimIRQ, irq as usize, ep as usize)
+        }
+
+        /// Masks or unmasks the interrupt `irq` that has been claimed via [`claim_irq`].
+        pub fn mask_irq(irq: IRQId, masked: bool) -> Result<(), Error> {
+            TMABI::call2(Operation::MaskIRQ, irq as usize, masked as usize)
+        }
     }
 }
@@ -0,0 +1,201 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! Contains a binary tracing facility with per-component event buffers
+//!
+//! In contrast to logging, tracing records typed events with a timestamp (the TCU's time in
+//! nanoseconds) into a fixed-size ring buffer. Each component (kernel, TileMux, and every
+//! activity) has its own buffer. If the buffer is full, the oldest events are overwritten.
+//! Recording an event is therefore cheap and does not involve the serial line. The events are
+//! written to the log via [`flush`], which is done automatically when the component exits. The
+//! tool `tracedec` extracts the events from the log, merges the traces of all components, and
+//! converts them into the Chrome trace format.
+//!
+//! Tracing is disabled by default and can be enabled at build time with the feature `trace` of
+//! this crate (`M3_TRACE=1` for the build script). Without this feature, the [`trace!`] macro
+//! compiles to nothing.
+//!
+//! # Examples
+//!
+//! ```
+//! trace!(Begin, EventId::Syscall, act_id, opcode);
+//! // ...
+//! trace!(End, EventId::Syscall, act_id);
+//! ```
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+
+/// The number of events in the buffer of each component
+pub const BUF_EVENTS: usize = 256;
+
+/// Whether tracing is enabled (via the feature `trace`)
+pub const ENABLED: bool = cfg!(feature = "trace");
+
+/// Records an event into the trace buffer of the current component
+///
+/// The first argument is the [`EventKind`], the second the event id (an [`EventId`] or a `u16`
+/// for application-defined events), and the third the context of the event (e.g., the activity
+/// id). The context determines the lane in which the event is displayed and begin/end events need
+/// to be properly nested per context. Up to two additional arguments are recorded with the event.
+#[macro_export]
+macro_rules! trace {
+    ($kind:ident, $id:expr, $ctx:expr) => {
+        $crate::trace!($kind, $id, $ctx, 0, 0)
+    };
+
+    ($kind:ident, $id:expr, $ctx:expr, $a0:expr) => {
+        $crate::trace!($kind, $id, $ctx, $a0, 0)
+    };
+
+    ($kind:ident, $id:expr, $ctx:expr, $a0:expr, $a1:expr) => {
+        // don't even evaluate the arguments if tracing is disabled
+        if $crate::trace::ENABLED {
+            $crate::trace::record($crate::trace::EventKind::$kind, $id, $ctx as u32, [
+                $a0 as u64, $a1 as u64,
+            ]);
+        }
+    };
+}
+
+/// The kind of an event
+#[derive(Copy, Clone, Debug, Eq, PartialEq, IntoPrimitive, TryFromPrimitive)]
+#[repr(u8)]
+pub enum EventKind {
+    /// The begin of a duration
+    Begin,
+    /// The end of the last begun duration in the same context
+    End,
+    /// An event without duration
+    Instant,
+    /// A new value (the first argument) of a counter
+    Counter,
+}
+
+/// The ids of the events used by M³ itself
+///
+/// Applications can use their own ids starting at [`EventId::User`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, IntoPrimitive, TryFromPrimitive)]
+#[repr(u16)]
+pub enum EventId {
+    /// A system call (arguments: opcode)
+    Syscall,
+    /// A TileMux call (arguments: opcode)
+    TMCall,
+    /// A sidecall from the kernel to TileMux (arguments: opcode)
+    Sidecall,
+    /// A page fault (arguments: virtual address, access permissions)
+    PageFault,
+    /// TileMux switched to another activity (arguments: activity id)
+    Schedule,
+    /// A message has been sent (arguments: endpoint, size)
+    MsgSend,
+    /// A message has been fetched (arguments: endpoint, offset in the receive buffer)
+    MsgRecv,
+    /// The first id for application-defined events
+    User = 0x100,
+}
+
+/// A recorded event
+#[derive(Copy, Clone, Debug, Default)]
+#[repr(C)]
+pub struct Event {
+    /// The TCU time in nanoseconds
+    pub time: u64,
+    /// The [`EventId`] or an application-defined id
+    pub id: u16,
+    /// The [`EventKind`]
+    pub kind: u8,
+    _pad: u8,
+    /// The context of the event (e.g., the activity id)
+    pub ctx: u32,
+    /// The event-specific arguments
+    pub args: [u64; 2],
+}
+
+/// Records an event with given kind, id, context, and arguments
+///
+/// This function is typically called via the [`trace!`] macro.
+#[inline(always)]
+#[cfg_attr(not(feature = "trace"), allow(unused_variables))]
+pub fn record<I: Into<u16>>(kind: EventKind, id: I, ctx: u32, args: [u64; 2]) {
+    #[cfg(feature = "trace")]
+    imp::record(Event {
+        time: crate::time::TimeInstant::now().as_nanos(),
+        id: id.into(),
+        kind: kind.into(),
+        _pad: 0,
+        ctx,
+        args,
+    });
+}
+
+/// Writes all recorded events to the log and clears the buffer
+///
+/// Each event is written as a line of the form `TRACE:<time>:<kind>:<id>:<ctx>:<arg0>:<arg1>` with
+/// all numbers in hexadecimal. The line `TRACE-LOST:<count>` is written before, if events have been
+/// overwritten.
+pub fn flush() {
+    #[cfg(feature = "trace")]
+    imp::flush();
+}
+
+#[cfg(feature = "trace")]
+mod imp {
+    use super::{Event, BUF_EVENTS};
+    use crate::cell::StaticRefCell;
+    use crate::io::log_str;
+
+    struct Buffer {
+        events: [Event; BUF_EVENTS],
+        // the total number of recorded events since the last flush
+        count: usize,
+    }
+
+    static BUF: StaticRefCell<Buffer> = StaticRefCell::new(Buffer {
+        events: [Event {
+            time: 0,
+            id: 0,
+            kind: 0,
+            _pad: 0,
+            ctx: 0,
+            args: [0, 0],
+        }; BUF_EVENTS],
+        count: 0,
+    });
+
+    pub fn record(ev: Event) {
+        let mut buf = BUF.borrow_mut();
+        let idx = buf.count % BUF_EVENTS;
+        buf.events[idx] = ev;
+        buf.count += 1;
+    }
+
+    pub fn flush() {
+        let mut buf = BUF.borrow_mut();
+        let start = buf.count.saturating_sub(BUF_EVENTS);
+        if start > 0 {
+            log_str(format_args!("TRACE-LOST:{:x}\n", start));
+        }
+
+        for i in start..buf.count {
+            let ev = &buf.events[i % BUF_EVENTS];
+            log_str(format_args!(
+                "TRACE:{:x}:{:x}:{:x}:{:x}:{:x}:{:x}\n",
+                ev.time, ev.kind, ev.id, ev.ctx, ev.args[0], ev.args[1]
+            ));
+        }
+        buf.count = 0;
+    }
+}
@@ -39,7 +39,7 @@ impl LinearCongruentialGenerator {
 
     /// Returns the next pseudo random number
     pub fn get(&mut self) -> u32 {
-        self.last = self.a * self.last + self.c;
+        self.last = self.a.wrapping_mul(self.last).wrapping_add(self.c);
         (self.last / 65536) % 32768
     }
 }
@@ -1,6 +1,9 @@
 dirs = [
     'base',
+    'dtb',
+    'gfx',
     'heap',
+    'http',
     'isr',
     'lang',
     'm3',
@@ -9,6 +12,9 @@ dirs = [
     'pci',
     'resmng',
     'thread',
+    'tls',
+    'usb',
+    'virtio',
 ]
 
 
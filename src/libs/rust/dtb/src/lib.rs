@@ -0,0 +1,380 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! A parser for flattened device trees (DTB)
+//!
+//! The parser reads the complete structure block into a flat list of [`Node`]s, which reference
+//! the names and property values within the given DTB. It supports the subset of the device tree
+//! specification that is required to discover devices: node names, properties, `compatible`,
+//! `reg` (with `#address-cells` and `#size-cells` of the parent), `interrupts` (with
+//! `#interrupt-cells` of the interrupt parent), and `m3,msi-address`.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use base::errors::{Code, Error};
+
+const FDT_MAGIC: u32 = 0xd00d_feed;
+const FDT_MIN_VERSION: u32 = 16;
+const FDT_HEADER_SIZE: usize = 40;
+
+const FDT_BEGIN_NODE: u32 = 0x1;
+const FDT_END_NODE: u32 = 0x2;
+const FDT_PROP: u32 = 0x3;
+const FDT_NOP: u32 = 0x4;
+const FDT_END: u32 = 0x9;
+
+// defaults according to the device tree specification
+const DEF_ADDR_CELLS: u32 = 2;
+const DEF_SIZE_CELLS: u32 = 1;
+const DEF_IRQ_CELLS: u32 = 1;
+
+// the maximum nesting depth we support
+const MAX_DEPTH: usize = 32;
+
+fn read_be32(data: &[u8], off: usize) -> Result<u32, Error> {
+    let bytes = data
+        .get(off..off + 4)
+        .ok_or_else(|| Error::new(Code::InvArgs))?;
+    Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_cells(data: &[u8], cells: u32) -> Result<u64, Error> {
+    match cells {
+        1 => read_be32(data, 0).map(|v| v as u64),
+        2 => Ok(((read_be32(data, 0)? as u64) << 32) | read_be32(data, 4)? as u64),
+        _ => Err(Error::new(Code::NotSup)),
+    }
+}
+
+fn read_str(data: &[u8], off: usize) -> Result<&str, Error> {
+    let rem = data.get(off..).ok_or_else(|| Error::new(Code::InvArgs))?;
+    let len = rem
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or_else(|| Error::new(Code::InvArgs))?;
+    core::str::from_utf8(&rem[..len]).map_err(|_| Error::new(Code::InvArgs))
+}
+
+const fn align4(off: usize) -> usize {
+    (off + 3) & !3
+}
+
+/// A property of a node
+#[derive(Copy, Clone)]
+pub struct Property<'d> {
+    name: &'d str,
+    value: &'d [u8],
+}
+
+impl<'d> Property<'d> {
+    /// Returns the name of the property
+    pub fn name(&self) -> &'d str {
+        self.name
+    }
+
+    /// Returns the raw value of the property
+    pub fn value(&self) -> &'d [u8] {
+        self.value
+    }
+
+    /// Interprets the value as a single big-endian u32
+    pub fn as_u32(&self) -> Result<u32, Error> {
+        read_be32(self.value, 0)
+    }
+
+    /// Interprets the value as a list of null-terminated strings
+    pub fn as_strs(&self) -> impl Iterator<Item = &'d str> {
+        self.value
+            .split(|&b| b == 0)
+            .filter(|s| !s.is_empty())
+            .filter_map(|s| core::str::from_utf8(s).ok())
+    }
+}
+
+impl fmt::Debug for Property<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}[{}b]", self.name, self.value.len())
+    }
+}
+
+/// A node in the device tree
+pub struct Node<'d> {
+    name: &'d str,
+    parent: Option<usize>,
+    props: Vec<Property<'d>>,
+}
+
+impl<'d> Node<'d> {
+    /// Returns the name of the node including the unit address (e.g., `serial@10000000`)
+    pub fn name(&self) -> &'d str {
+        self.name
+    }
+
+    /// Returns the index of the parent node or `None` for the root node
+    pub fn parent(&self) -> Option<usize> {
+        self.parent
+    }
+
+    /// Returns all properties of this node
+    pub fn props(&self) -> &[Property<'d>] {
+        &self.props
+    }
+
+    /// Returns the property with given name
+    pub fn prop(&self, name: &str) -> Option<&Property<'d>> {
+        self.props.iter().find(|p| p.name == name)
+    }
+
+    /// Returns true if the `compatible` property of this node contains `compat`
+    pub fn is_compatible(&self, compat: &str) -> bool {
+        self.prop("compatible")
+            .map(|p| p.as_strs().any(|c| c == compat))
+            .unwrap_or(false)
+    }
+
+    /// Returns true if this node is not disabled via the `status` property
+    pub fn is_enabled(&self) -> bool {
+        match self.prop("status").and_then(|p| p.as_strs().next()) {
+            Some(s) => s == "okay" || s == "ok",
+            None => true,
+        }
+    }
+}
+
+impl fmt::Debug for Node<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Node[{}, props={:?}]", self.name, self.props)
+    }
+}
+
+/// A parsed flattened device tree
+#[derive(Debug)]
+pub struct DeviceTree<'d> {
+    nodes: Vec<Node<'d>>,
+}
+
+impl<'d> DeviceTree<'d> {
+    /// Parses the given DTB
+    ///
+    /// Fails with `Code::InvArgs` if the DTB is malformed and with `Code::NotSup` if the version
+    /// is not supported.
+    pub fn new(data: &'d [u8]) -> Result<Self, Error> {
+        if data.len() < FDT_HEADER_SIZE || read_be32(data, 0)? != FDT_MAGIC {
+            return Err(Error::new(Code::InvArgs));
+        }
+
+        let total = read_be32(data, 4)? as usize;
+        let off_struct = read_be32(data, 8)? as usize;
+        let off_strings = read_be32(data, 12)? as usize;
+        let last_comp = read_be32(data, 24)?;
+        let size_strings = read_be32(data, 32)? as usize;
+        let size_struct = read_be32(data, 36)? as usize;
+        if last_comp > FDT_MIN_VERSION {
+            return Err(Error::new(Code::NotSup));
+        }
+
+        let data = data.get(..total).ok_or_else(|| Error::new(Code::InvArgs))?;
+        let structs = data
+            .get(off_struct..off_struct.saturating_add(size_struct))
+            .ok_or_else(|| Error::new(Code::InvArgs))?;
+        let strings = data
+            .get(off_strings..off_strings.saturating_add(size_strings))
+            .ok_or_else(|| Error::new(Code::InvArgs))?;
+
+        Ok(Self {
+            nodes: Self::parse_structs(structs, strings)?,
+        })
+    }
+
+    fn parse_structs(structs: &'d [u8], strings: &'d [u8]) -> Result<Vec<Node<'d>>, Error> {
+        let mut nodes: Vec<Node<'d>> = Vec::new();
+        let mut stack: Vec<usize> = Vec::new();
+        let mut off = 0;
+
+        loop {
+            let token = read_be32(structs, off)?;
+            off += 4;
+
+            match token {
+                FDT_BEGIN_NODE => {
+                    if stack.len() >= MAX_DEPTH || (stack.is_empty() && !nodes.is_empty()) {
+                        return Err(Error::new(Code::InvArgs));
+                    }
+
+                    let name = read_str(structs, off)?;
+                    off = align4(off + name.len() + 1);
+                    nodes.push(Node {
+                        name,
+                        parent: stack.last().copied(),
+                        props: Vec::new(),
+                    });
+                    stack.push(nodes.len() - 1);
+                },
+                FDT_END_NODE => {
+                    stack.pop().ok_or_else(|| Error::new(Code::InvArgs))?;
+                },
+                FDT_PROP => {
+                    let len = read_be32(structs, off)? as usize;
+                    let name_off = read_be32(structs, off + 4)? as usize;
+                    off += 8;
+                    let value = structs
+                        .get(off..off.saturating_add(len))
+                        .ok_or_else(|| Error::new(Code::InvArgs))?;
+                    off = align4(off + len);
+
+                    let cur = *stack.last().ok_or_else(|| Error::new(Code::InvArgs))?;
+                    nodes[cur].props.push(Property {
+                        name: read_str(strings, name_off)?,
+                        value,
+                    });
+                },
+                FDT_NOP => {},
+                FDT_END => break,
+                _ => return Err(Error::new(Code::InvArgs)),
+            }
+        }
+
+        if !stack.is_empty() || nodes.is_empty() {
+            return Err(Error::new(Code::InvArgs));
+        }
+        Ok(nodes)
+    }
+
+    /// Returns all nodes in the order of the DTB; the root node is at index 0
+    pub fn nodes(&self) -> &[Node<'d>] {
+        &self.nodes
+    }
+
+    /// Returns the node at given index
+    pub fn node(&self, idx: usize) -> Option<&Node<'d>> {
+        self.nodes.get(idx)
+    }
+
+    /// Returns the index of the `nth` enabled node that is compatible to `compat`
+    pub fn find_compatible(&self, compat: &str, nth: usize) -> Option<usize> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| n.is_enabled() && n.is_compatible(compat))
+            .map(|(i, _)| i)
+            .nth(nth)
+    }
+
+    /// Returns the index of the node with given `phandle`
+    pub fn find_phandle(&self, phandle: u32) -> Option<usize> {
+        self.nodes.iter().position(|n| {
+            n.prop("phandle")
+                .or_else(|| n.prop("linux,phandle"))
+                .and_then(|p| p.as_u32().ok())
+                == Some(phandle)
+        })
+    }
+
+    fn cells_of(&self, idx: Option<usize>, name: &str, def: u32) -> Result<u32, Error> {
+        match idx.and_then(|i| self.nodes[i].prop(name)) {
+            Some(p) => p.as_u32(),
+            None => Ok(def),
+        }
+    }
+
+    /// Returns the (address, size) pairs of the `reg` property of the node at given index
+    ///
+    /// The addresses are given in the address space of the parent bus; translations via `ranges`
+    /// are not performed.
+    pub fn reg(&self, idx: usize) -> Result<Vec<(u64, u64)>, Error> {
+        let node = &self.nodes[idx];
+        let parent = node.parent;
+        let addr_cells = self.cells_of(parent, "#address-cells", DEF_ADDR_CELLS)?;
+        let size_cells = self.cells_of(parent, "#size-cells", DEF_SIZE_CELLS)?;
+
+        let mut res = Vec::new();
+        if let Some(reg) = node.prop("reg") {
+            let entry_size = (addr_cells + size_cells) as usize * 4;
+            if entry_size == 0 || reg.value.len() % entry_size != 0 {
+                return Err(Error::new(Code::InvArgs));
+            }
+
+            for entry in reg.value.chunks(entry_size) {
+                let addr = read_cells(entry, addr_cells)?;
+                let size = match size_cells {
+                    0 => 0,
+                    n => read_cells(&entry[addr_cells as usize * 4..], n)?,
+                };
+                res.push((addr, size));
+            }
+        }
+        Ok(res)
+    }
+
+    /// Returns the interrupt numbers of the `interrupts` property of the node at given index
+    ///
+    /// The interrupt parent is determined via `interrupt-parent` of the node or its ancestors. Of
+    /// each interrupt specifier, only the first cell (the interrupt number) is returned.
+    pub fn interrupts(&self, idx: usize) -> Result<Vec<u32>, Error> {
+        let node = &self.nodes[idx];
+        let mut res = Vec::new();
+        if let Some(irqs) = node.prop("interrupts") {
+            let cells = self.cells_of(
+                self.interrupt_parent(idx)?,
+                "#interrupt-cells",
+                DEF_IRQ_CELLS,
+            )?;
+            let entry_size = cells as usize * 4;
+            if entry_size == 0 || irqs.value.len() % entry_size != 0 {
+                return Err(Error::new(Code::InvArgs));
+            }
+
+            for entry in irqs.value.chunks(entry_size) {
+                res.push(read_be32(entry, 0)?);
+            }
+        }
+        Ok(res)
+    }
+
+    /// Returns the address of the `m3,msi-address` property of the node at given index, if present
+    ///
+    /// Device tiles that forward message-signaled interrupts to the driver specify the address the
+    /// device has to write these messages to via this property. The address is given with the
+    /// `#address-cells` of the parent.
+    pub fn msi_address(&self, idx: usize) -> Result<Option<u64>, Error> {
+        let node = &self.nodes[idx];
+        match node.prop("m3,msi-address") {
+            Some(addr) => {
+                let cells = self.cells_of(node.parent, "#address-cells", DEF_ADDR_CELLS)?;
+                if addr.value.len() != cells as usize * 4 {
+                    return Err(Error::new(Code::InvArgs));
+                }
+                read_cells(addr.value, cells).map(Some)
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn interrupt_parent(&self, idx: usize) -> Result<Option<usize>, Error> {
+        let mut cur = Some(idx);
+        while let Some(i) = cur {
+            if let Some(p) = self.nodes[i].prop("interrupt-parent") {
+                return Ok(self.find_phandle(p.as_u32()?));
+            }
+            cur = self.nodes[i].parent;
+        }
+        Ok(None)
+    }
+}
@@ -0,0 +1,218 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use m3::client::{FbInfo, FbRect, Framebuffer};
+use m3::col::Vec;
+use m3::com::MemGate;
+use m3::errors::{Code, Error};
+use m3::io::Write;
+use m3::mem::GlobOff;
+use m3::vec;
+
+use crate::font::{self, GLYPH_HEIGHT, GLYPH_WIDTH};
+use crate::Color;
+
+/// The width of a character cell in pixels
+pub const CELL_WIDTH: usize = GLYPH_WIDTH;
+/// The height of a character cell in pixels; each row of the glyph is drawn twice
+pub const CELL_HEIGHT: usize = GLYPH_HEIGHT * 2;
+
+const TAB_WIDTH: usize = 8;
+
+/// A text console that renders characters onto a [`Framebuffer`]
+///
+/// The console keeps the characters of all cells and only renders the rows that have been changed
+/// since the last call of [`TextConsole::flush`]. Text is wrapped at the end of a row and the
+/// console scrolls up as soon as the cursor moves past the last row.
+pub struct TextConsole {
+    fb: Framebuffer,
+    mem: MemGate,
+    info: FbInfo,
+    cols: usize,
+    rows: usize,
+    cells: Vec<u8>,
+    dirty: Vec<bool>,
+    x: usize,
+    y: usize,
+    fg: Color,
+    bg: Color,
+    // the pixels of one row of cells
+    pixels: Vec<Color>,
+}
+
+impl TextConsole {
+    /// Creates a new text console that covers the whole framebuffer
+    pub fn new(fb: Framebuffer) -> Result<Self, Error> {
+        let info = *fb.info();
+        let cols = info.width as usize / CELL_WIDTH;
+        let rows = info.height as usize / CELL_HEIGHT;
+        if cols == 0 || rows == 0 {
+            return Err(Error::new(Code::InvArgs));
+        }
+
+        let mem = fb.get_mem()?;
+        Ok(Self {
+            fb,
+            mem,
+            info,
+            cols,
+            rows,
+            cells: vec![b' '; cols * rows],
+            dirty: vec![true; rows],
+            x: 0,
+            y: 0,
+            fg: crate::rgb(0xC0, 0xC0, 0xC0),
+            bg: crate::rgb(0, 0, 0),
+            pixels: vec![0; info.width as usize * CELL_HEIGHT],
+        })
+    }
+
+    /// Returns the framebuffer the console renders onto
+    pub fn framebuffer(&self) -> &Framebuffer {
+        &self.fb
+    }
+
+    /// Returns the number of columns and rows
+    pub fn size(&self) -> (usize, usize) {
+        (self.cols, self.rows)
+    }
+
+    /// Returns the column and row of the cursor
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.x, self.y)
+    }
+
+    /// Moves the cursor to the given column and row
+    pub fn set_cursor(&mut self, x: usize, y: usize) {
+        self.x = x.min(self.cols - 1);
+        self.y = y.min(self.rows - 1);
+    }
+
+    /// Sets the foreground and background color for the whole console
+    pub fn set_colors(&mut self, fg: Color, bg: Color) {
+        self.fg = fg;
+        self.bg = bg;
+        self.dirty.fill(true);
+    }
+
+    /// Clears the console and moves the cursor to the top left corner
+    pub fn clear(&mut self) {
+        self.cells.fill(b' ');
+        self.dirty.fill(true);
+        self.x = 0;
+        self.y = 0;
+    }
+
+    /// Puts the given character at the cursor position and advances the cursor
+    ///
+    /// Besides the printable characters, newline, carriage return, backspace, and tab are
+    /// supported. All other control characters are ignored.
+    pub fn put(&mut self, c: u8) {
+        match c {
+            b'\n' => self.newline(),
+            b'\r' => self.x = 0,
+            0x08 => self.x = self.x.saturating_sub(1),
+            b'\t' => self.x = ((self.x / TAB_WIDTH + 1) * TAB_WIDTH).min(self.cols - 1),
+            c if c.is_ascii_control() => {},
+            c => {
+                if self.x >= self.cols {
+                    self.newline();
+                }
+                self.cells[self.y * self.cols + self.x] = c;
+                self.dirty[self.y] = true;
+                self.x += 1;
+            },
+        }
+    }
+
+    /// Renders all changed rows and makes them visible on the display
+    pub fn flush(&mut self) -> Result<(), Error> {
+        let mut damage = FbRect::default();
+        for row in 0..self.rows {
+            if self.dirty[row] {
+                self.render_row(row)?;
+                self.dirty[row] = false;
+                let y = (row * CELL_HEIGHT) as u32;
+                damage = damage.union(&FbRect::new(0, y, self.info.width, CELL_HEIGHT as u32));
+            }
+        }
+
+        if !damage.is_empty() {
+            self.fb.flush(damage)?;
+        }
+        Ok(())
+    }
+
+    fn newline(&mut self) {
+        self.x = 0;
+        if self.y + 1 < self.rows {
+            self.y += 1;
+        }
+        else {
+            self.cells.copy_within(self.cols.., 0);
+            let last = (self.rows - 1) * self.cols;
+            self.cells[last..].fill(b' ');
+            self.dirty.fill(true);
+        }
+    }
+
+    fn render_row(&mut self, row: usize) -> Result<(), Error> {
+        let width = self.info.width as usize;
+        let cells = &self.cells[row * self.cols..(row + 1) * self.cols];
+        for py in 0..CELL_HEIGHT {
+            let line = &mut self.pixels[py * width..(py + 1) * width];
+            for (col, c) in cells.iter().enumerate() {
+                let bits = font::glyph(*c)[py / 2];
+                for px in 0..CELL_WIDTH {
+                    line[col * CELL_WIDTH + px] = if (bits >> px) & 1 != 0 {
+                        self.fg
+                    }
+                    else {
+                        self.bg
+                    };
+                }
+            }
+            // the pixels right of the last column
+            line[self.cols * CELL_WIDTH..].fill(self.bg);
+        }
+
+        let off = self.info.offset(0, (row * CELL_HEIGHT) as u32) as GlobOff;
+        if self.info.stride as usize == width * core::mem::size_of::<Color>() {
+            self.mem.write(&self.pixels, off)
+        }
+        else {
+            for py in 0..CELL_HEIGHT {
+                self.mem.write(
+                    &self.pixels[py * width..(py + 1) * width],
+                    off + (py * self.info.stride as usize) as GlobOff,
+                )?;
+            }
+            Ok(())
+        }
+    }
+}
+
+impl Write for TextConsole {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        for c in buf {
+            self.put(*c);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        TextConsole::flush(self)
+    }
+}
@@ -0,0 +1,34 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! Simple graphics on top of the framebuffer service
+//!
+//! This crate contains a bitmap font and a [`TextConsole`] that renders text onto a
+//! [`Framebuffer`](m3::client::Framebuffer).
+
+#![no_std]
+
+mod console;
+pub mod font;
+
+pub use console::{TextConsole, CELL_HEIGHT, CELL_WIDTH};
+
+/// A pixel in the format of the framebuffer (`0x00RRGGBB`)
+pub type Color = u32;
+
+/// Returns the color with given red, green, and blue components
+pub const fn rgb(r: u8, g: u8, b: u8) -> Color {
+    ((r as Color) << 16) | ((g as Color) << 8) | b as Color
+}
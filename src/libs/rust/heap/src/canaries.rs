@@ -0,0 +1,146 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! Canaries and poisoning to detect heap corruptions
+//!
+//! Each allocation is surrounded by a header and a trailer. The header stores the size of the
+//! allocation, followed by canary bytes up to the user data. The trailer directly behind the user
+//! data consists of canary bytes as well. Both are validated on free and realloc, so that buffer
+//! under- and overflows as well as double frees are detected at the latest when the allocation is
+//! released (double frees are only detected as long as the allocator has not reused the memory).
+//! Additionally, new allocations are filled with [`ALLOC_POISON`] and freed allocations
+//! with [`FREE_POISON`] to make uses of uninitialized or freed memory easier to spot.
+
+use base::libc;
+use base::mem;
+
+use crate::{ALLOC_POISON, FREE_POISON};
+
+// the header needs to keep the user data aligned as malloc does
+const HEADER_SIZE: usize = 16;
+const TRAILER_SIZE: usize = 8;
+
+const HEADER_CANARY: u8 = 0xCA;
+const TRAILER_CANARY: u8 = 0xFE;
+
+/// Returns the start of the allocation for the given user pointer
+fn base_of(ptr: *mut u8) -> *mut u8 {
+    // safety: all user pointers are HEADER_SIZE bytes behind the start of their allocation
+    unsafe { ptr.sub(HEADER_SIZE) }
+}
+
+/// Returns the bytes of the header behind the size
+///
+/// # Safety
+///
+/// The caller needs to ensure that `base` points to an allocation of this module.
+unsafe fn header_canary<'a>(base: *mut u8) -> &'a mut [u8] {
+    let start = mem::size_of::<usize>();
+    core::slice::from_raw_parts_mut(base.add(start), HEADER_SIZE - start)
+}
+
+/// Checks the canaries of the allocation at `ptr` and returns its size
+///
+/// # Safety
+///
+/// The caller needs to ensure that `ptr` has been returned by [`alloc`] or [`realloc`].
+unsafe fn check(ptr: *mut u8, op: &str) -> usize {
+    let base = base_of(ptr);
+    let header = header_canary(base);
+    if header.iter().all(|b| *b == FREE_POISON) {
+        panic!("heap: {}({:?}): allocation has already been freed", op, ptr);
+    }
+    if let Some(pos) = header.iter().position(|b| *b != HEADER_CANARY) {
+        panic!(
+            "heap: {}({:?}): buffer underflow; header canary corrupted at offset -{} ({:#x})",
+            op,
+            ptr,
+            header.len() - pos,
+            header[pos]
+        );
+    }
+
+    let size = *(base as *const usize);
+    let trailer = core::slice::from_raw_parts(ptr.add(size), TRAILER_SIZE);
+    if let Some(pos) = trailer.iter().position(|b| *b != TRAILER_CANARY) {
+        panic!(
+            "heap: {}({:?}): buffer overflow; trailer canary corrupted at offset {}/{} ({:#x})",
+            op,
+            ptr,
+            size + pos,
+            size,
+            trailer[pos]
+        );
+    }
+    size
+}
+
+/// Allocates `size` bytes with canaries via `malloc` and fills the memory with `fill`
+pub fn alloc(size: usize, fill: u8) -> *mut libc::c_void {
+    // safety: we only write within the allocated area
+    unsafe {
+        let base = super::malloc(HEADER_SIZE + size + TRAILER_SIZE) as *mut u8;
+        if base.is_null() {
+            return base as *mut libc::c_void;
+        }
+
+        *(base as *mut usize) = size;
+        header_canary(base).fill(HEADER_CANARY);
+        let ptr = base.add(HEADER_SIZE);
+        ptr.write_bytes(fill, size);
+        ptr.add(size).write_bytes(TRAILER_CANARY, TRAILER_SIZE);
+        ptr as *mut libc::c_void
+    }
+}
+
+/// Validates the canaries of the allocation at `ptr`, poisons it and frees it
+pub fn free(ptr: *mut libc::c_void) {
+    if ptr.is_null() {
+        return;
+    }
+
+    // safety: the pointer stems from `alloc` and we only write within the allocated area
+    unsafe {
+        let ptr = ptr as *mut u8;
+        let size = check(ptr, "free");
+        let base = base_of(ptr);
+        base.add(mem::size_of::<usize>()).write_bytes(
+            FREE_POISON,
+            HEADER_SIZE - mem::size_of::<usize>() + size + TRAILER_SIZE,
+        );
+        super::free(base as *mut libc::c_void);
+    }
+}
+
+/// Validates the canaries of the allocation at `ptr` and moves it to a new allocation of
+/// `new_size` bytes
+pub fn realloc(ptr: *mut libc::c_void, new_size: usize) -> *mut libc::c_void {
+    if ptr.is_null() {
+        return alloc(new_size, ALLOC_POISON);
+    }
+
+    // safety: the pointer stems from `alloc` and both areas are at least `min` bytes large
+    unsafe {
+        let old_size = check(ptr as *mut u8, "realloc");
+        // always move the allocation so that stale pointers to the old one hit poisoned memory
+        let res = alloc(new_size, ALLOC_POISON);
+        if !res.is_null() {
+            let min = old_size.min(new_size);
+            (res as *mut u8).copy_from_nonoverlapping(ptr as *const u8, min);
+            free(ptr);
+        }
+        res
+    }
+}
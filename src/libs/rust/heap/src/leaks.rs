@@ -0,0 +1,129 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! Tracking of outstanding allocations to report leaks
+//!
+//! The allocations are tracked in a fixed-size table, because we cannot allocate memory within the
+//! allocator. If the table is full, further allocations are only counted.
+
+use core::fmt;
+
+use base::backtrace;
+use base::cell::StaticRefCell;
+use base::io::LogFlags;
+use base::log;
+use base::mem::VirtAddr;
+
+const MAX_TRACKED: usize = 1024;
+const BT_LEN: usize = 8;
+
+#[derive(Clone, Copy)]
+struct Entry {
+    ptr: usize,
+    size: usize,
+    bt: [VirtAddr; BT_LEN],
+}
+
+impl Entry {
+    const fn new() -> Self {
+        Self {
+            ptr: 0,
+            size: 0,
+            bt: [VirtAddr::new(0); BT_LEN],
+        }
+    }
+}
+
+struct Backtrace<'e>(&'e [VirtAddr]);
+
+impl fmt::Display for Backtrace<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for addr in self.0.iter().take_while(|a| !a.is_null()) {
+            write!(f, " {:#x}", addr.as_local())?;
+        }
+        Ok(())
+    }
+}
+
+struct Tracker {
+    entries: [Entry; MAX_TRACKED],
+    untracked: usize,
+}
+
+static TRACKER: StaticRefCell<Tracker> = StaticRefCell::new(Tracker {
+    entries: [Entry::new(); MAX_TRACKED],
+    untracked: 0,
+});
+
+pub(crate) fn track(ptr: *mut u8, size: usize) {
+    if ptr.is_null() {
+        return;
+    }
+
+    let mut tracker = TRACKER.borrow_mut();
+    match tracker.entries.iter_mut().find(|e| e.ptr == 0) {
+        Some(e) => {
+            e.ptr = ptr as usize;
+            e.size = size;
+            e.bt = [VirtAddr::new(0); BT_LEN];
+            backtrace::collect(&mut e.bt);
+        },
+        None => tracker.untracked += 1,
+    }
+}
+
+pub(crate) fn untrack(ptr: *mut u8) {
+    if ptr.is_null() {
+        return;
+    }
+
+    let mut tracker = TRACKER.borrow_mut();
+    match tracker.entries.iter_mut().find(|e| e.ptr == ptr as usize) {
+        Some(e) => e.ptr = 0,
+        // the allocation was made while the table was full
+        None => tracker.untracked = tracker.untracked.saturating_sub(1),
+    }
+}
+
+/// Reports all allocations that have not been freed yet together with the backtrace of their
+/// allocation
+pub fn report_leaks() {
+    let tracker = TRACKER.borrow();
+    let (count, bytes) = tracker
+        .entries
+        .iter()
+        .filter(|e| e.ptr != 0)
+        .fold((0, 0), |(c, b), e| (c + 1, b + e.size));
+    if count == 0 && tracker.untracked == 0 {
+        return;
+    }
+
+    log!(
+        LogFlags::Info,
+        "heap: {} bytes in {} allocations have not been freed ({} untracked allocations):",
+        bytes,
+        count,
+        tracker.untracked
+    );
+    for e in tracker.entries.iter().filter(|e| e.ptr != 0) {
+        log!(
+            LogFlags::Info,
+            "heap:   {} bytes at {:#x} allocated at{}",
+            e.size,
+            e.ptr,
+            Backtrace(&e.bt)
+        );
+    }
+}
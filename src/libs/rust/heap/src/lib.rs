@@ -13,8 +13,26 @@
  * General Public License version 2 for more details.
  */
 
+//! The heap for Rust programs, which is based on the heap implementation of libc
+//!
+//! With the feature `canaries`, each allocation is surrounded by canaries that are validated on
+//! free and realloc and all allocated and freed memory is poisoned (see the `canaries` module).
+//! With the feature `leaks`, all outstanding allocations are tracked and can be reported via
+//! [`report_leaks`] together with the backtrace of their allocation.
+//!
+//! Independent of the features, the heap maintains statistics, which can be obtained via
+//! [`stats`].
+
 #![no_std]
 
+#[cfg(feature = "canaries")]
+mod canaries;
+#[cfg(feature = "leaks")]
+mod leaks;
+mod stats;
+
+pub use stats::{stats, Stats, SIZE_CLASSES};
+
 use base::io::LogFlags;
 use base::libc;
 use base::log;
@@ -24,12 +42,14 @@ extern "C" {
     fn malloc(size: usize) -> *mut libc::c_void;
 
     /// Allocates `n * size` on the heap and initializes it to 0
+    #[cfg(not(feature = "canaries"))]
     fn calloc(n: usize, size: usize) -> *mut libc::c_void;
 
     /// Reallocates `n` to be `size` bytes large
     ///
     /// This implementation might increase the size of the area or shink it. It might also free the
     /// current area and allocate a new area of `size` bytes.
+    #[cfg(not(feature = "canaries"))]
     fn realloc(p: *mut libc::c_void, size: usize) -> *mut libc::c_void;
 
     /// Frees the area at `p`
@@ -38,26 +58,39 @@ extern "C" {
 
 #[no_mangle]
 extern "C" fn __rdl_alloc(size: usize, _align: usize, _err: *mut u8) -> *mut libc::c_void {
+    #[cfg(feature = "canaries")]
+    let res = canaries::alloc(size, ALLOC_POISON);
+    #[cfg(not(feature = "canaries"))]
     let res = unsafe { malloc(size) };
     log!(LogFlags::LibHeap, "heap::alloc({}) -> {:?}", size, res);
+    record_alloc(res, size);
     res
 }
 
 #[no_mangle]
-extern "C" fn __rdl_dealloc(ptr: *mut libc::c_void, _size: usize, _align: usize) {
+extern "C" fn __rdl_dealloc(ptr: *mut libc::c_void, size: usize, _align: usize) {
     log!(LogFlags::LibHeap, "heap::free({:?})", ptr);
-    unsafe { free(ptr) };
+    record_free(ptr, size);
+    #[cfg(feature = "canaries")]
+    canaries::free(ptr);
+    #[cfg(not(feature = "canaries"))]
+    unsafe {
+        free(ptr)
+    };
 }
 
 #[no_mangle]
 extern "C" fn __rdl_realloc(
     ptr: *mut libc::c_void,
-    _old_size: usize,
+    old_size: usize,
     _old_align: usize,
     new_size: usize,
     _new_align: usize,
     _err: *mut u8,
 ) -> *mut libc::c_void {
+    #[cfg(feature = "canaries")]
+    let res = canaries::realloc(ptr, new_size);
+    #[cfg(not(feature = "canaries"))]
     let res = unsafe { realloc(ptr, new_size) };
     log!(
         LogFlags::LibHeap,
@@ -66,12 +99,64 @@ extern "C" fn __rdl_realloc(
         new_size,
         res
     );
+    if !res.is_null() {
+        // realloc with a null pointer is an allocation
+        if ptr.is_null() {
+            record_alloc(res, new_size);
+        }
+        else {
+            stats::record_realloc(old_size, new_size);
+            #[cfg(feature = "leaks")]
+            {
+                leaks::untrack(ptr as *mut u8);
+                leaks::track(res as *mut u8, new_size);
+            }
+        }
+    }
     res
 }
 
 #[no_mangle]
 extern "C" fn __rdl_alloc_zeroed(size: usize, _align: usize, _err: *mut u8) -> *mut libc::c_void {
+    #[cfg(feature = "canaries")]
+    let res = canaries::alloc(size, 0);
+    #[cfg(not(feature = "canaries"))]
     let res = unsafe { calloc(size, 1) };
     log!(LogFlags::LibHeap, "heap::calloc({}) -> {:?}", size, res);
+    record_alloc(res, size);
     res
 }
+
+fn record_alloc(ptr: *mut libc::c_void, size: usize) {
+    if !ptr.is_null() {
+        stats::record_alloc(size);
+        #[cfg(feature = "leaks")]
+        leaks::track(ptr as *mut u8, size);
+    }
+}
+
+fn record_free(ptr: *mut libc::c_void, size: usize) {
+    if !ptr.is_null() {
+        stats::record_free(size);
+        #[cfg(feature = "leaks")]
+        leaks::untrack(ptr as *mut u8);
+    }
+}
+
+/// The value that newly allocated (and not zeroed) memory is filled with, if the feature `canaries`
+/// is enabled
+pub const ALLOC_POISON: u8 = 0xAA;
+/// The value that freed memory is filled with, if the feature `canaries` is enabled
+pub const FREE_POISON: u8 = 0xDD;
+
+/// Returns true if the heap surrounds all allocations with canaries (feature `canaries`)
+pub fn has_canaries() -> bool {
+    cfg!(feature = "canaries")
+}
+
+/// Reports all allocations that have not been freed yet, if the feature `leaks` is enabled.
+/// Otherwise, this function does nothing.
+pub fn report_leaks() {
+    #[cfg(feature = "leaks")]
+    leaks::report_leaks();
+}
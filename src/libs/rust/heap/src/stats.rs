@@ -0,0 +1,96 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use base::cell::StaticRefCell;
+
+/// The number of size classes in the histogram of [`Stats`]
+pub const SIZE_CLASSES: usize = 16;
+
+/// Statistics about the usage of the heap
+///
+/// Only allocations made via Rust's global allocator are counted, but not allocations made via
+/// `malloc` directly (e.g., by C code).
+#[derive(Clone, Copy, Debug)]
+pub struct Stats {
+    /// The number of currently allocated bytes
+    pub cur_bytes: usize,
+    /// The maximum number of allocated bytes so far
+    pub peak_bytes: usize,
+    /// The total number of allocations
+    pub allocs: u64,
+    /// The total number of reallocations
+    pub reallocs: u64,
+    /// The total number of frees
+    pub frees: u64,
+    /// The number of allocations per size class. Class `i` counts the allocations with a size in
+    /// `[2^i, 2^(i+1))`, except that class 0 also contains empty allocations and the last class
+    /// also contains all larger allocations.
+    pub histogram: [u64; SIZE_CLASSES],
+}
+
+impl Stats {
+    const fn new() -> Self {
+        Self {
+            cur_bytes: 0,
+            peak_bytes: 0,
+            allocs: 0,
+            reallocs: 0,
+            frees: 0,
+            histogram: [0; SIZE_CLASSES],
+        }
+    }
+
+    /// Returns the number of allocations that have not been freed yet
+    pub fn live_allocs(&self) -> u64 {
+        self.allocs - self.frees
+    }
+}
+
+static STATS: StaticRefCell<Stats> = StaticRefCell::new(Stats::new());
+
+fn size_class(size: usize) -> usize {
+    let log2 = (usize::BITS - 1).saturating_sub(size.leading_zeros()) as usize;
+    log2.min(SIZE_CLASSES - 1)
+}
+
+fn add_bytes(stats: &mut Stats, size: usize) {
+    stats.cur_bytes += size;
+    stats.peak_bytes = stats.peak_bytes.max(stats.cur_bytes);
+}
+
+pub(crate) fn record_alloc(size: usize) {
+    let mut stats = STATS.borrow_mut();
+    stats.allocs += 1;
+    stats.histogram[size_class(size)] += 1;
+    add_bytes(&mut stats, size);
+}
+
+pub(crate) fn record_realloc(old_size: usize, new_size: usize) {
+    let mut stats = STATS.borrow_mut();
+    stats.reallocs += 1;
+    stats.cur_bytes -= old_size;
+    add_bytes(&mut stats, new_size);
+}
+
+pub(crate) fn record_free(size: usize) {
+    let mut stats = STATS.borrow_mut();
+    stats.frees += 1;
+    stats.cur_bytes -= size;
+}
+
+/// Returns the current statistics about the usage of the heap
+pub fn stats() -> Stats {
+    *STATS.borrow()
+}
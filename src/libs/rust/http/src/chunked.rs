@@ -0,0 +1,172 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use m3::col::Vec;
+use m3::errors::{Code, Error};
+use m3::io::Write;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum State {
+    // reading the chunk size; the extensions behind the size are ignored
+    Size {
+        size: usize,
+        digits: usize,
+        ext: bool,
+    },
+    // reading the data of the current chunk
+    Data(usize),
+    // reading the line end behind the data
+    DataEnd,
+    // reading the trailer fields, which are ignored
+    Trailer {
+        empty_line: bool,
+    },
+    Done,
+}
+
+impl State {
+    fn new_chunk() -> Self {
+        Self::Size {
+            size: 0,
+            digits: 0,
+            ext: false,
+        }
+    }
+}
+
+/// A decoder for the chunked transfer encoding
+///
+/// The decoder consumes the encoded body incrementally, so that it can be fed with the data as it
+/// is received.
+#[derive(Debug)]
+pub struct ChunkedDecoder {
+    state: State,
+}
+
+impl Default for ChunkedDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChunkedDecoder {
+    /// Creates a new decoder
+    pub fn new() -> Self {
+        Self {
+            state: State::new_chunk(),
+        }
+    }
+
+    /// Returns true if the last chunk and the trailer have been decoded
+    pub fn is_done(&self) -> bool {
+        self.state == State::Done
+    }
+
+    /// Decodes the given data, appends the contained body data to `out`, and returns the number of
+    /// consumed bytes.
+    ///
+    /// Less than `input.len()` bytes are consumed only if the end of the body has been reached.
+    /// The remaining bytes belong to the next message in this case.
+    pub fn decode(&mut self, input: &[u8], out: &mut Vec<u8>) -> Result<usize, Error> {
+        let mut pos = 0;
+        while pos < input.len() {
+            self.state = match self.state {
+                State::Done => break,
+
+                State::Data(rem) => {
+                    let amount = rem.min(input.len() - pos);
+                    out.extend_from_slice(&input[pos..pos + amount]);
+                    pos += amount;
+                    if amount == rem {
+                        State::DataEnd
+                    }
+                    else {
+                        State::Data(rem - amount)
+                    }
+                },
+
+                State::Size { size, digits, ext } => {
+                    let b = input[pos];
+                    pos += 1;
+                    match b {
+                        b'\n' if digits == 0 => return Err(Error::new(Code::InvArgs)),
+                        b'\n' if size == 0 => State::Trailer { empty_line: true },
+                        b'\n' => State::Data(size),
+                        b'\r' => State::Size { size, digits, ext },
+                        _ if ext => State::Size { size, digits, ext },
+                        b';' | b' ' | b'\t' => State::Size {
+                            size,
+                            digits,
+                            ext: true,
+                        },
+                        b => {
+                            let digit = (b as char)
+                                .to_digit(16)
+                                .ok_or_else(|| Error::new(Code::InvArgs))?;
+                            let size = size
+                                .checked_mul(16)
+                                .and_then(|s| s.checked_add(digit as usize))
+                                .ok_or_else(|| Error::new(Code::InvArgs))?;
+                            State::Size {
+                                size,
+                                digits: digits + 1,
+                                ext,
+                            }
+                        },
+                    }
+                },
+
+                State::DataEnd => {
+                    let b = input[pos];
+                    pos += 1;
+                    match b {
+                        b'\r' => State::DataEnd,
+                        b'\n' => State::new_chunk(),
+                        _ => return Err(Error::new(Code::InvArgs)),
+                    }
+                },
+
+                State::Trailer { empty_line } => {
+                    let b = input[pos];
+                    pos += 1;
+                    match b {
+                        b'\r' => State::Trailer { empty_line },
+                        b'\n' if empty_line => State::Done,
+                        b'\n' => State::Trailer { empty_line: true },
+                        _ => State::Trailer { empty_line: false },
+                    }
+                },
+            };
+        }
+        Ok(pos)
+    }
+}
+
+/// Writes `data` as a single chunk to `w`
+///
+/// Nothing is written if `data` is empty, because an empty chunk terminates the body.
+pub fn write_chunk<W: Write + ?Sized>(w: &mut W, data: &[u8]) -> Result<(), Error> {
+    if !data.is_empty() {
+        write!(w, "{:x}\r\n", data.len())?;
+        w.write_all(data)?;
+        w.write_all(b"\r\n")?;
+    }
+    Ok(())
+}
+
+/// Writes the last chunk with an empty trailer to `w`, which terminates the body
+pub fn write_last_chunk<W: Write + ?Sized>(w: &mut W) -> Result<(), Error> {
+    w.write_all(b"0\r\n\r\n")
+}
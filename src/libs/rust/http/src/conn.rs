@@ -0,0 +1,243 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use core::fmt::Write as FmtWrite;
+
+use m3::col::{String, Vec};
+use m3::errors::{Code, Error};
+use m3::io::{Read, Write};
+use m3::vec;
+
+use crate::chunked::{write_chunk, write_last_chunk, ChunkedDecoder};
+use crate::headers::Headers;
+use crate::message::{Method, Request, Response};
+use crate::MAX_HEAD_SIZE;
+
+// a function that parses the head of a message
+type ParseFn<T> = fn(&[u8]) -> Result<Option<(T, usize)>, Error>;
+
+/// A persistent HTTP connection on top of a stream
+///
+/// The connection buffers the received data, so that multiple messages can be exchanged over the
+/// same stream (keep-alive). Whether the connection should be kept open is determined via
+/// [`Request::keep_alive`] and [`Response::keep_alive`].
+pub struct HttpConnection<S: Read + Write> {
+    stream: S,
+    buf: Vec<u8>,
+    pos: usize,
+    len: usize,
+}
+
+impl<S: Read + Write> HttpConnection<S> {
+    /// Creates a new connection on top of given stream
+    pub fn new(stream: S) -> Self {
+        Self {
+            stream,
+            buf: vec![0u8; MAX_HEAD_SIZE],
+            pos: 0,
+            len: 0,
+        }
+    }
+
+    /// Returns a reference to the underlying stream
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    /// Returns a mutable reference to the underlying stream
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    /// Receives the next request.
+    ///
+    /// Returns `None` if the client closed the connection before sending another request.
+    pub fn read_request(&mut self) -> Result<Option<Request>, Error> {
+        self.read_head(Request::parse)
+    }
+
+    /// Receives the response to the last request
+    pub fn read_response(&mut self) -> Result<Response, Error> {
+        self.read_head(Response::parse)?
+            .ok_or_else(|| Error::new(Code::EndOfFile))
+    }
+
+    /// Receives the body of the given request, which may not be larger than `max` bytes
+    pub fn read_request_body(&mut self, req: &Request, max: usize) -> Result<Vec<u8>, Error> {
+        self.read_body(req.headers(), false, max)
+    }
+
+    /// Receives the body of the given response to a request with given method, which may not be
+    /// larger than `max` bytes
+    ///
+    /// If the response neither specifies a length nor uses the chunked encoding, the body extends
+    /// until the server closes the connection.
+    pub fn read_response_body(
+        &mut self,
+        resp: &Response,
+        method: &Method,
+        max: usize,
+    ) -> Result<Vec<u8>, Error> {
+        if *method == Method::Head || !resp.has_body() {
+            return Ok(Vec::new());
+        }
+        self.read_body(resp.headers(), true, max)
+    }
+
+    /// Sends the given request with given body
+    ///
+    /// The Content-Length field is added, unless the request already specifies the length or uses
+    /// the chunked encoding.
+    pub fn send_request(&mut self, req: &Request, body: &[u8]) -> Result<(), Error> {
+        let mut head = String::new();
+        write!(head, "{}", req).unwrap();
+        self.send_message(head, req.headers(), body)
+    }
+
+    /// Sends the given response with given body
+    ///
+    /// The Content-Length field is added, unless the response already specifies the length or
+    /// uses the chunked encoding.
+    pub fn send_response(&mut self, resp: &Response, body: &[u8]) -> Result<(), Error> {
+        let mut head = String::new();
+        write!(head, "{}", resp).unwrap();
+        self.send_message(head, resp.headers(), body)
+    }
+
+    /// Sends the head of the given response and announces the chunked transfer encoding.
+    ///
+    /// The body is sent afterwards via [`send_chunk`](Self::send_chunk) and terminated via
+    /// [`finish_chunked`](Self::finish_chunked).
+    pub fn send_chunked_head(&mut self, resp: &Response) -> Result<(), Error> {
+        let mut head = String::new();
+        write!(head, "{}", resp).unwrap();
+        if !resp.headers().is_chunked() {
+            head.push_str("Transfer-Encoding: chunked\r\n");
+        }
+        head.push_str("\r\n");
+        self.stream.write_all(head.as_bytes())
+    }
+
+    /// Sends the given data as the next chunk of the body
+    pub fn send_chunk(&mut self, data: &[u8]) -> Result<(), Error> {
+        write_chunk(&mut self.stream, data)
+    }
+
+    /// Terminates the body that is sent with the chunked transfer encoding
+    pub fn finish_chunked(&mut self) -> Result<(), Error> {
+        write_last_chunk(&mut self.stream)
+    }
+
+    fn send_message(
+        &mut self,
+        mut head: String,
+        headers: &Headers,
+        body: &[u8],
+    ) -> Result<(), Error> {
+        if !headers.is_chunked() && !headers.contains("Content-Length") {
+            write!(head, "Content-Length: {}\r\n", body.len()).unwrap();
+        }
+        head.push_str("\r\n");
+        self.stream.write_all(head.as_bytes())?;
+        self.stream.write_all(body)
+    }
+
+    fn read_head<T>(&mut self, parse: ParseFn<T>) -> Result<Option<T>, Error> {
+        loop {
+            if let Some((msg, len)) = parse(&self.buf[self.pos..self.len])? {
+                self.pos += len;
+                return Ok(Some(msg));
+            }
+
+            if self.fill()? == 0 {
+                return match self.len - self.pos {
+                    0 => Ok(None),
+                    _ => Err(Error::new(Code::EndOfFile)),
+                };
+            }
+        }
+    }
+
+    fn read_body(
+        &mut self,
+        headers: &Headers,
+        until_close: bool,
+        max: usize,
+    ) -> Result<Vec<u8>, Error> {
+        let mut body = Vec::new();
+
+        if headers.is_chunked() {
+            let mut dec = ChunkedDecoder::new();
+            while !dec.is_done() {
+                if self.pos == self.len && self.fill()? == 0 {
+                    return Err(Error::new(Code::EndOfFile));
+                }
+                self.pos += dec.decode(&self.buf[self.pos..self.len], &mut body)?;
+                if body.len() > max {
+                    return Err(Error::new(Code::NoSpace));
+                }
+            }
+        }
+        else if let Some(len) = headers.content_length()? {
+            if len > max {
+                return Err(Error::new(Code::NoSpace));
+            }
+            while body.len() < len {
+                if self.pos == self.len && self.fill()? == 0 {
+                    return Err(Error::new(Code::EndOfFile));
+                }
+                let amount = (len - body.len()).min(self.len - self.pos);
+                body.extend_from_slice(&self.buf[self.pos..self.pos + amount]);
+                self.pos += amount;
+            }
+        }
+        else if until_close {
+            loop {
+                if self.pos == self.len && self.fill()? == 0 {
+                    break;
+                }
+                body.extend_from_slice(&self.buf[self.pos..self.len]);
+                self.pos = self.len;
+                if body.len() > max {
+                    return Err(Error::new(Code::NoSpace));
+                }
+            }
+        }
+
+        Ok(body)
+    }
+
+    /// Receives more data into the buffer and returns the number of received bytes (0 on EOF)
+    fn fill(&mut self) -> Result<usize, Error> {
+        // move the remaining data to the front to make room
+        if self.pos > 0 {
+            self.buf.copy_within(self.pos..self.len, 0);
+            self.len -= self.pos;
+            self.pos = 0;
+        }
+        if self.len == self.buf.len() {
+            return Err(Error::new(Code::OutOfBounds));
+        }
+
+        match self.stream.read(&mut self.buf[self.len..]) {
+            Ok(len) => {
+                self.len += len;
+                Ok(len)
+            },
+            Err(e) if e.code() == Code::SocketClosed => Ok(0),
+            Err(e) => Err(e),
+        }
+    }
+}
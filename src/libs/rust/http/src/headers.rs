@@ -0,0 +1,156 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use core::fmt;
+
+use m3::col::{String, ToString, Vec};
+use m3::errors::{Code, Error};
+
+use crate::message::Version;
+
+/// The header fields of a request or response
+///
+/// The names of the fields are compared case-insensitively, but kept in the given spelling. A
+/// field can occur multiple times.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Headers {
+    fields: Vec<(String, String)>,
+}
+
+impl Headers {
+    /// Creates an empty list of header fields
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of header fields
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    /// Returns true if there are no header fields
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Returns an iterator over all fields as (name, value) pairs
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.fields.iter().map(|(n, v)| (n.as_str(), v.as_str()))
+    }
+
+    /// Returns the value of the first field with given name
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns true if there is a field with given name
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Adds a field with given name and value, keeping existing fields with the same name
+    pub fn add(&mut self, name: &str, value: &str) {
+        self.fields.push((name.to_string(), value.to_string()));
+    }
+
+    /// Sets the field with given name to given value, replacing all existing fields with that name
+    pub fn set(&mut self, name: &str, value: &str) {
+        self.remove(name);
+        self.add(name, value);
+    }
+
+    /// Removes all fields with given name
+    pub fn remove(&mut self, name: &str) {
+        self.fields.retain(|(n, _)| !n.eq_ignore_ascii_case(name));
+    }
+
+    /// Returns true if any field with given name contains `token` in its comma-separated list of
+    /// values (case-insensitive)
+    pub fn has_token(&self, name: &str, token: &str) -> bool {
+        self.fields
+            .iter()
+            .filter(|(n, _)| n.eq_ignore_ascii_case(name))
+            .flat_map(|(_, v)| v.split(','))
+            .any(|t| t.trim().eq_ignore_ascii_case(token))
+    }
+
+    /// Returns the value of the Content-Length field, if present
+    ///
+    /// Returns [`InvArgs`](Code::InvArgs) if the value is no valid length or if multiple fields
+    /// specify different lengths.
+    pub fn content_length(&self) -> Result<Option<usize>, Error> {
+        let mut res = None;
+        for (_, v) in self
+            .fields
+            .iter()
+            .filter(|(n, _)| n.eq_ignore_ascii_case("Content-Length"))
+        {
+            let len = v
+                .trim()
+                .parse::<usize>()
+                .map_err(|_| Error::new(Code::InvArgs))?;
+            if res.is_some() && res != Some(len) {
+                return Err(Error::new(Code::InvArgs));
+            }
+            res = Some(len);
+        }
+        Ok(res)
+    }
+
+    /// Returns true if the body is sent with the chunked transfer encoding
+    pub fn is_chunked(&self) -> bool {
+        // chunked has to be the last encoding, if present
+        self.fields
+            .iter()
+            .filter(|(n, _)| n.eq_ignore_ascii_case("Transfer-Encoding"))
+            .flat_map(|(_, v)| v.split(','))
+            .last()
+            .map(|t| t.trim().eq_ignore_ascii_case("chunked"))
+            .unwrap_or(false)
+    }
+
+    /// Returns true if the connection should be kept open after the message with given version
+    pub fn keep_alive(&self, version: Version) -> bool {
+        match version {
+            Version::Http10 => self.has_token("Connection", "keep-alive"),
+            Version::Http11 => !self.has_token("Connection", "close"),
+        }
+    }
+
+    /// Parses the given header line and adds the field
+    pub(crate) fn parse_line(&mut self, line: &str) -> Result<(), Error> {
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| Error::new(Code::InvArgs))?;
+        // whitespace between the name and the colon is not allowed
+        if name.is_empty() || name.contains(|c: char| c.is_ascii_whitespace()) {
+            return Err(Error::new(Code::InvArgs));
+        }
+        self.add(name, value.trim());
+        Ok(())
+    }
+}
+
+impl fmt::Display for Headers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (n, v) in &self.fields {
+            write!(f, "{}: {}\r\n", n, v)?;
+        }
+        Ok(())
+    }
+}
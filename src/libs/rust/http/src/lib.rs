@@ -0,0 +1,62 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! HTTP/1.1 support for M³ applications
+//!
+//! This crate contains the parts of HTTP/1.1 that clients and servers typically need: the
+//! [`Request`] and [`Response`] messages including their [`Headers`], the chunked transfer
+//! encoding, and persistent connections. The messages can be parsed from byte slices directly or
+//! exchanged via an [`HttpConnection`], which works on top of any stream that implements
+//! [`Read`](m3::io::Read) and [`Write`](m3::io::Write) (e.g., a
+//! [`TcpSocket`](m3::net::TcpSocket)).
+
+#![no_std]
+
+mod chunked;
+mod conn;
+mod headers;
+mod message;
+
+pub use chunked::{write_chunk, write_last_chunk, ChunkedDecoder};
+pub use conn::HttpConnection;
+pub use headers::Headers;
+pub use message::{reason_phrase, Method, Request, Response, Version};
+
+/// The maximum size of the head (start line and headers) of a message
+pub const MAX_HEAD_SIZE: usize = 8 * 1024;
+
+/// Returns the position behind the empty line that terminates the head of a message, if the given
+/// buffer contains the complete head
+pub(crate) fn find_head_end(buf: &[u8]) -> Option<usize> {
+    let mut line_start = 0;
+    for (i, b) in buf.iter().enumerate() {
+        if *b == b'\n' {
+            // the line is empty if it consists of nothing or just the carriage return
+            let line = &buf[line_start..i];
+            if line.is_empty() || line == b"\r" {
+                return Some(i + 1);
+            }
+            line_start = i + 1;
+        }
+    }
+    None
+}
+
+/// Splits the given head into lines without the line terminators
+pub(crate) fn head_lines(head: &str) -> impl Iterator<Item = &str> {
+    head.split('\n')
+        .map(|l| l.strip_suffix('\r').unwrap_or(l))
+        .take_while(|l| !l.is_empty())
+}
@@ -0,0 +1,352 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use core::fmt;
+use core::str;
+
+use m3::col::{String, ToString};
+use m3::errors::{Code, Error};
+
+use crate::headers::Headers;
+use crate::{find_head_end, head_lines};
+
+/// The HTTP version of a message
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Version {
+    Http10,
+    Http11,
+}
+
+impl Version {
+    fn parse(s: &str) -> Result<Self, Error> {
+        match s {
+            "HTTP/1.0" => Ok(Self::Http10),
+            "HTTP/1.1" => Ok(Self::Http11),
+            _ => Err(Error::new(Code::NotSup)),
+        }
+    }
+
+    /// Returns the version as it appears in the start line
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Http10 => "HTTP/1.0",
+            Self::Http11 => "HTTP/1.1",
+        }
+    }
+}
+
+/// The method of a request
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Method {
+    Get,
+    Head,
+    Post,
+    Put,
+    Delete,
+    Options,
+    Other(String),
+}
+
+impl Method {
+    fn parse(s: &str) -> Self {
+        match s {
+            "GET" => Self::Get,
+            "HEAD" => Self::Head,
+            "POST" => Self::Post,
+            "PUT" => Self::Put,
+            "DELETE" => Self::Delete,
+            "OPTIONS" => Self::Options,
+            s => Self::Other(s.to_string()),
+        }
+    }
+
+    /// Returns the method as it appears in the start line
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Get => "GET",
+            Self::Head => "HEAD",
+            Self::Post => "POST",
+            Self::Put => "PUT",
+            Self::Delete => "DELETE",
+            Self::Options => "OPTIONS",
+            Self::Other(s) => s,
+        }
+    }
+}
+
+/// Returns the standard reason phrase for the given status code
+pub fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        100 => "Continue",
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        206 => "Partial Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        304 => "Not Modified",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        411 => "Length Required",
+        413 => "Content Too Large",
+        414 => "URI Too Long",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        503 => "Service Unavailable",
+        505 => "HTTP Version Not Supported",
+        _ => "Unknown",
+    }
+}
+
+/// Parses the head at the beginning of `buf`, if complete, and returns the start line, the headers
+/// and the length of the head
+fn parse_head(buf: &[u8]) -> Result<Option<(&str, Headers, usize)>, Error> {
+    let len = match find_head_end(buf) {
+        Some(len) => len,
+        None => return Ok(None),
+    };
+
+    let head = str::from_utf8(&buf[..len]).map_err(|_| Error::new(Code::InvArgs))?;
+    let mut lines = head_lines(head);
+    let start = lines.next().ok_or_else(|| Error::new(Code::InvArgs))?;
+    let mut headers = Headers::new();
+    for line in lines {
+        headers.parse_line(line)?;
+    }
+    Ok(Some((start, headers, len)))
+}
+
+/// An HTTP request
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Request {
+    method: Method,
+    target: String,
+    version: Version,
+    headers: Headers,
+}
+
+impl Request {
+    /// Creates a new HTTP/1.1 request with given method for given target (e.g., "/index.html")
+    pub fn new(method: Method, target: &str) -> Self {
+        Self {
+            method,
+            target: target.to_string(),
+            version: Version::Http11,
+            headers: Headers::new(),
+        }
+    }
+
+    /// Adds the header field with given name and value
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.add(name, value);
+        self
+    }
+
+    /// Parses a request from the beginning of `buf`.
+    ///
+    /// Returns `None` if `buf` does not contain the complete head yet. Otherwise, the request and
+    /// the number of bytes of the head are returned; the body (if any) follows afterwards.
+    pub fn parse(buf: &[u8]) -> Result<Option<(Self, usize)>, Error> {
+        let (start, headers, len) = match parse_head(buf)? {
+            Some(res) => res,
+            None => return Ok(None),
+        };
+
+        let mut parts = start.split(' ');
+        let (method, target, version) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(m), Some(t), Some(v)) if !m.is_empty() && !t.is_empty() => (m, t, v),
+            _ => return Err(Error::new(Code::InvArgs)),
+        };
+        if parts.next().is_some() {
+            return Err(Error::new(Code::InvArgs));
+        }
+
+        Ok(Some((
+            Self {
+                method: Method::parse(method),
+                target: target.to_string(),
+                version: Version::parse(version)?,
+                headers,
+            },
+            len,
+        )))
+    }
+
+    /// Returns the method
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+
+    /// Returns the request target including the query
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// Returns the path of the request target without the query
+    pub fn path(&self) -> &str {
+        self.target
+            .split_once('?')
+            .map(|(p, _)| p)
+            .unwrap_or(&self.target)
+    }
+
+    /// Returns the query of the request target, if any
+    pub fn query(&self) -> Option<&str> {
+        self.target.split_once('?').map(|(_, q)| q)
+    }
+
+    /// Returns the HTTP version
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    /// Returns the header fields
+    pub fn headers(&self) -> &Headers {
+        &self.headers
+    }
+
+    /// Returns the header fields mutably
+    pub fn headers_mut(&mut self) -> &mut Headers {
+        &mut self.headers
+    }
+
+    /// Returns true if the client wants to keep the connection open after this request
+    pub fn keep_alive(&self) -> bool {
+        self.headers.keep_alive(self.version)
+    }
+}
+
+impl fmt::Display for Request {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {}\r\n{}",
+            self.method.as_str(),
+            self.target,
+            self.version.as_str(),
+            self.headers
+        )
+    }
+}
+
+/// An HTTP response
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Response {
+    version: Version,
+    status: u16,
+    reason: String,
+    headers: Headers,
+}
+
+impl Response {
+    /// Creates a new HTTP/1.1 response with given status code and the standard reason phrase
+    pub fn new(status: u16) -> Self {
+        Self {
+            version: Version::Http11,
+            status,
+            reason: reason_phrase(status).to_string(),
+            headers: Headers::new(),
+        }
+    }
+
+    /// Adds the header field with given name and value
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.add(name, value);
+        self
+    }
+
+    /// Parses a response from the beginning of `buf`.
+    ///
+    /// Returns `None` if `buf` does not contain the complete head yet. Otherwise, the response and
+    /// the number of bytes of the head are returned; the body (if any) follows afterwards.
+    pub fn parse(buf: &[u8]) -> Result<Option<(Self, usize)>, Error> {
+        let (start, headers, len) = match parse_head(buf)? {
+            Some(res) => res,
+            None => return Ok(None),
+        };
+
+        let (version, rest) = start
+            .split_once(' ')
+            .ok_or_else(|| Error::new(Code::InvArgs))?;
+        let (status, reason) = rest.split_once(' ').unwrap_or((rest, ""));
+        if status.len() != 3 {
+            return Err(Error::new(Code::InvArgs));
+        }
+        let status = status
+            .parse::<u16>()
+            .map_err(|_| Error::new(Code::InvArgs))?;
+
+        Ok(Some((
+            Self {
+                version: Version::parse(version)?,
+                status,
+                reason: reason.to_string(),
+                headers,
+            },
+            len,
+        )))
+    }
+
+    /// Returns the HTTP version
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    /// Returns the status code
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// Returns the reason phrase
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+
+    /// Returns the header fields
+    pub fn headers(&self) -> &Headers {
+        &self.headers
+    }
+
+    /// Returns the header fields mutably
+    pub fn headers_mut(&mut self) -> &mut Headers {
+        &mut self.headers
+    }
+
+    /// Returns true if the response has a body, assuming that the request was no HEAD request
+    pub fn has_body(&self) -> bool {
+        !(100..200).contains(&self.status) && self.status != 204 && self.status != 304
+    }
+
+    /// Returns true if the server keeps the connection open after this response
+    pub fn keep_alive(&self) -> bool {
+        self.headers.keep_alive(self.version)
+    }
+}
+
+impl fmt::Display for Response {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {}\r\n{}",
+            self.version.as_str(),
+            self.status,
+            self.reason,
+            self.headers
+        )
+    }
+}
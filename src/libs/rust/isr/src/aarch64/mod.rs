@@ -0,0 +1,240 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use base::backtrace;
+use base::kif::PageFlags;
+use base::libc;
+use base::mem::VirtAddr;
+use base::tcu;
+
+use core::arch::asm;
+use core::convert::TryFrom;
+use core::fmt;
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+
+use crate::IRQSource;
+use crate::StateArch;
+
+pub const ISR_COUNT: usize = 8;
+
+pub const TMC_ARG0: usize = 0; // x0
+pub const TMC_ARG1: usize = 1; // x1
+pub const TMC_ARG2: usize = 2; // x2
+pub const TMC_ARG3: usize = 3; // x3
+pub const TMC_ARG4: usize = 4; // x4
+
+// exception classes in ESR_EL1
+const EC_UNKNOWN: usize = 0x00;
+const EC_FP_ACCESS: usize = 0x07;
+const EC_SVC64: usize = 0x15;
+const EC_INSTR_ABORT_LOWER: usize = 0x20;
+const EC_INSTR_ABORT_SAME: usize = 0x21;
+const EC_DATA_ABORT_LOWER: usize = 0x24;
+const EC_DATA_ABORT_SAME: usize = 0x25;
+
+#[derive(Default)]
+// see comment in ARM code. the size needs to be a multiple of 16 to keep the stack aligned.
+#[repr(C, align(16))]
+pub struct AArch64State {
+    // general purpose registers
+    pub r: [usize; 31], // x0 .. x30
+    pub sp: usize,
+    pub pc: usize,
+    pub pstate: usize,
+    pub vec: usize,
+    pub esr: usize,
+}
+
+impl crate::StateArch for AArch64State {
+    fn instr_pointer(&self) -> VirtAddr {
+        VirtAddr::from(self.pc)
+    }
+
+    fn base_pointer(&self) -> VirtAddr {
+        VirtAddr::from(self.r[29])
+    }
+
+    fn came_from_user(&self) -> bool {
+        // EL0t
+        (self.pstate & 0x0F) == 0x0
+    }
+}
+
+/// The vectors we distinguish, which are derived from the entry in the vector table and the
+/// exception class in ESR_EL1
+#[derive(Copy, Clone, Debug, Eq, PartialEq, IntoPrimitive, TryFromPrimitive)]
+#[repr(usize)]
+pub enum Vector {
+    SVC,
+    DataAbort,
+    InstrAbort,
+    IllegalInstr,
+    OtherSync,
+    IRQ,
+    FIQ,
+    SError,
+}
+
+impl Vector {
+    fn new(entry: usize, esr: usize) -> Self {
+        // each group of four entries contains sync, IRQ, FIQ, and SError
+        match entry % 4 {
+            0 => match esr >> 26 {
+                EC_SVC64 => Self::SVC,
+                EC_DATA_ABORT_LOWER | EC_DATA_ABORT_SAME => Self::DataAbort,
+                EC_INSTR_ABORT_LOWER | EC_INSTR_ABORT_SAME => Self::InstrAbort,
+                EC_UNKNOWN | EC_FP_ACCESS => Self::IllegalInstr,
+                _ => Self::OtherSync,
+            },
+            1 => Self::IRQ,
+            2 => Self::FIQ,
+            _ => Self::SError,
+        }
+    }
+}
+
+impl fmt::Debug for AArch64State {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        writeln!(
+            fmt,
+            "  vec:    {:#x} ({:?})",
+            { self.vec },
+            Vector::try_from(self.vec)
+        )?;
+        for (idx, r) in { self.r }.iter().enumerate() {
+            writeln!(fmt, "  x[{:02}]:  {:#x}", idx, r)?;
+        }
+        writeln!(fmt, "  sp:     {:#x}", { self.sp })?;
+        writeln!(fmt, "  pc:     {:#x}", { self.pc })?;
+        writeln!(fmt, "  pstate: {:#x}", { self.pstate })?;
+        writeln!(fmt, "  esr:    {:#x}", { self.esr })?;
+
+        writeln!(fmt, "\nUser backtrace:")?;
+        let mut bt = [VirtAddr::default(); 16];
+        let bt_len = backtrace::collect_for(self.base_pointer(), &mut bt);
+        for addr in bt.iter().take(bt_len) {
+            writeln!(fmt, "  {:#x}", addr.as_local())?;
+        }
+        Ok(())
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn isr_handler(state: &mut AArch64State) -> *mut libc::c_void {
+    // the entry code stores the index of the entry in the vector table. in contrast to ARM, the
+    // exception link register already points to the instruction to repeat or to the instruction
+    // after the SVC, so that we don't need to adjust the PC.
+    state.vec = Vector::new(state.vec, state.esr).into();
+
+    crate::ISRS.borrow()[state.vec](state)
+}
+
+pub struct AArch64ISR {}
+
+impl crate::ISRArch for AArch64ISR {
+    type State = AArch64State;
+
+    fn init(_state: &mut Self::State) {
+        extern "C" {
+            fn isr_vectors();
+        }
+
+        unsafe {
+            asm!(
+                "msr vbar_el1, {0}",
+                "isb",
+                in(reg) isr_vectors as usize,
+                options(nostack),
+            );
+        }
+    }
+
+    fn set_entry_sp(_sp: VirtAddr) {
+        // nothing to do; the entry code leaves the stack pointer behind the restored state
+    }
+
+    fn reg_tm_calls(handler: crate::IsrFunc) {
+        crate::reg(Vector::SVC.into(), handler);
+    }
+
+    fn reg_page_faults(handler: crate::IsrFunc) {
+        crate::reg(Vector::InstrAbort.into(), handler);
+        crate::reg(Vector::DataAbort.into(), handler);
+    }
+
+    fn reg_cu_reqs(handler: crate::IsrFunc) {
+        crate::reg(Vector::IRQ.into(), handler);
+    }
+
+    fn reg_illegal_instr(handler: crate::IsrFunc) {
+        crate::reg(Vector::IllegalInstr.into(), handler);
+    }
+
+    fn reg_timer(handler: crate::IsrFunc) {
+        crate::reg(Vector::IRQ.into(), handler);
+    }
+
+    fn reg_external(_handler: crate::IsrFunc) {
+    }
+
+    fn get_pf_info(state: &Self::State) -> (VirtAddr, PageFlags) {
+        let far: usize;
+        unsafe {
+            asm!(
+                "mrs {0}, far_el1",
+                out(reg) far,
+                options(nostack, nomem),
+            );
+        }
+
+        let perm = if state.vec == Vector::DataAbort.into() {
+            // WnR: the abort was caused by a write
+            if state.esr & (1 << 6) != 0 {
+                PageFlags::RW
+            }
+            else {
+                PageFlags::R
+            }
+        }
+        else {
+            PageFlags::RX
+        };
+        (VirtAddr::from(far), perm)
+    }
+
+    fn init_tls(_addr: VirtAddr) {
+        // unused
+    }
+
+    fn enable_irqs() {
+        unsafe { asm!("msr daifclr, #2", options(nostack, nomem)) };
+    }
+
+    fn fetch_irq() -> IRQSource {
+        let irq = tcu::TCU::get_irq().unwrap();
+        tcu::TCU::clear_irq(irq);
+        IRQSource::TCU(irq)
+    }
+
+    fn register_ext_irq(_irq: u32) {
+    }
+
+    fn enable_ext_irqs(_mask: u32) {
+    }
+
+    fn disable_ext_irqs(_mask: u32) {
+    }
+}
@@ -70,6 +70,11 @@ cfg_if! {
         mod isa;
         pub type ISR = isa::ARMISR;
     }
+    else if #[cfg(target_arch = "aarch64")] {
+        #[path = "aarch64/mod.rs"]
+        mod isa;
+        pub type ISR = isa::AArch64ISR;
+    }
     else {
         #[path = "riscv/mod.rs"]
         mod isa;
@@ -442,7 +442,8 @@ impl crate::ISRArch for X86ISR {
             idt.set(0, isr_0, DPL::Kernel);
             idt.set(1, isr_1, DPL::Kernel);
             idt.set(2, isr_2, DPL::Kernel);
-            idt.set(3, isr_3, DPL::Kernel);
+            // allow breakpoints (int3) in user mode
+            idt.set(3, isr_3, DPL::User);
             idt.set(4, isr_4, DPL::Kernel);
             idt.set(5, isr_5, DPL::Kernel);
             idt.set(6, isr_6, DPL::Kernel);
@@ -103,6 +103,9 @@ pub extern "C" fn _Unwind_Resume() -> ! {
     intrinsics::abort()
 }
 
+#[cfg(target_arch = "arm")]
+mod softfloat;
+
 #[cfg(target_arch = "arm")]
 #[no_mangle]
 #[doc(hidden)]
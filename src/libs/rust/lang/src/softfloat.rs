@@ -0,0 +1,138 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! The soft-float helpers of the ARM run-time ABI
+//!
+//! Without FPU, the compiler translates floating-point operations into calls of the `__aeabi_*`
+//! functions defined by the ARM run-time ABI. C/C++ code gets them from libgcc, but we do not link
+//! libgcc into all binaries (e.g., TileMux). Therefore, we provide them here based on the
+//! implementations of the compiler-builtins crate, which uses the generic names (`__adddf3`, ...).
+//!
+//! Note that the implementations must not use floating-point operations, because these would be
+//! translated into calls of the functions defined here.
+
+macro_rules! aeabi_alias {
+    ($(fn $name:ident($($arg:ident: $ty:ty),*) -> $ret:ty = $impl:ident;)*) => {
+        extern "C" {
+            $(fn $impl($($arg: $ty),*) -> $ret;)*
+        }
+
+        $(
+            #[no_mangle]
+            #[doc(hidden)]
+            pub extern "aapcs" fn $name($($arg: $ty),*) -> $ret {
+                // safety: the function is provided by compiler-builtins and has no preconditions
+                unsafe { $impl($($arg),*) }
+            }
+        )*
+    };
+}
+
+aeabi_alias! {
+    // double-precision arithmetic
+    fn __aeabi_dadd(a: f64, b: f64) -> f64 = __adddf3;
+    fn __aeabi_dsub(a: f64, b: f64) -> f64 = __subdf3;
+    fn __aeabi_dmul(a: f64, b: f64) -> f64 = __muldf3;
+    fn __aeabi_ddiv(a: f64, b: f64) -> f64 = __divdf3;
+
+    // single-precision arithmetic
+    fn __aeabi_fadd(a: f32, b: f32) -> f32 = __addsf3;
+    fn __aeabi_fsub(a: f32, b: f32) -> f32 = __subsf3;
+    fn __aeabi_fmul(a: f32, b: f32) -> f32 = __mulsf3;
+    fn __aeabi_fdiv(a: f32, b: f32) -> f32 = __divsf3;
+
+    // conversions between floating-point formats
+    fn __aeabi_d2f(a: f64) -> f32 = __truncdfsf2;
+    fn __aeabi_f2d(a: f32) -> f64 = __extendsfdf2;
+
+    // floating-point to integer conversions (rounding towards zero)
+    fn __aeabi_d2iz(a: f64) -> i32 = __fixdfsi;
+    fn __aeabi_d2uiz(a: f64) -> u32 = __fixunsdfsi;
+    fn __aeabi_d2lz(a: f64) -> i64 = __fixdfdi;
+    fn __aeabi_d2ulz(a: f64) -> u64 = __fixunsdfdi;
+    fn __aeabi_f2iz(a: f32) -> i32 = __fixsfsi;
+    fn __aeabi_f2uiz(a: f32) -> u32 = __fixunssfsi;
+    fn __aeabi_f2lz(a: f32) -> i64 = __fixsfdi;
+    fn __aeabi_f2ulz(a: f32) -> u64 = __fixunssfdi;
+
+    // integer to floating-point conversions
+    fn __aeabi_i2d(a: i32) -> f64 = __floatsidf;
+    fn __aeabi_ui2d(a: u32) -> f64 = __floatunsidf;
+    fn __aeabi_l2d(a: i64) -> f64 = __floatdidf;
+    fn __aeabi_ul2d(a: u64) -> f64 = __floatundidf;
+    fn __aeabi_i2f(a: i32) -> f32 = __floatsisf;
+    fn __aeabi_ui2f(a: u32) -> f32 = __floatunsisf;
+    fn __aeabi_l2f(a: i64) -> f32 = __floatdisf;
+    fn __aeabi_ul2f(a: u64) -> f32 = __floatundisf;
+}
+
+// the generic comparison functions return a value that is less than, equal to, or greater than zero
+// and something that makes the comparison false for NaNs, whereas the ARM functions return a boolean.
+extern "C" {
+    fn __eqdf2(a: f64, b: f64) -> i32;
+    fn __ltdf2(a: f64, b: f64) -> i32;
+    fn __ledf2(a: f64, b: f64) -> i32;
+    fn __gedf2(a: f64, b: f64) -> i32;
+    fn __gtdf2(a: f64, b: f64) -> i32;
+    fn __unorddf2(a: f64, b: f64) -> i32;
+    fn __eqsf2(a: f32, b: f32) -> i32;
+    fn __ltsf2(a: f32, b: f32) -> i32;
+    fn __lesf2(a: f32, b: f32) -> i32;
+    fn __gesf2(a: f32, b: f32) -> i32;
+    fn __gtsf2(a: f32, b: f32) -> i32;
+    fn __unordsf2(a: f32, b: f32) -> i32;
+}
+
+macro_rules! aeabi_cmp {
+    ($($name:ident($ty:ty) = $impl:ident $op:tt 0;)*) => {
+        $(
+            #[no_mangle]
+            #[doc(hidden)]
+            pub extern "aapcs" fn $name(a: $ty, b: $ty) -> i32 {
+                // safety: the function is provided by compiler-builtins and has no preconditions
+                (unsafe { $impl(a, b) } $op 0) as i32
+            }
+        )*
+    };
+}
+
+aeabi_cmp! {
+    __aeabi_dcmpeq(f64) = __eqdf2 == 0;
+    __aeabi_dcmplt(f64) = __ltdf2 < 0;
+    __aeabi_dcmple(f64) = __ledf2 <= 0;
+    __aeabi_dcmpge(f64) = __gedf2 >= 0;
+    __aeabi_dcmpgt(f64) = __gtdf2 > 0;
+    __aeabi_dcmpun(f64) = __unorddf2 != 0;
+    __aeabi_fcmpeq(f32) = __eqsf2 == 0;
+    __aeabi_fcmplt(f32) = __ltsf2 < 0;
+    __aeabi_fcmple(f32) = __lesf2 <= 0;
+    __aeabi_fcmpge(f32) = __gesf2 >= 0;
+    __aeabi_fcmpgt(f32) = __gtsf2 > 0;
+    __aeabi_fcmpun(f32) = __unordsf2 != 0;
+}
+
+/// Computes `b - a` ("reverse subtract")
+#[no_mangle]
+#[doc(hidden)]
+pub extern "aapcs" fn __aeabi_drsub(a: f64, b: f64) -> f64 {
+    __aeabi_dsub(b, a)
+}
+
+/// Computes `b - a` ("reverse subtract")
+#[no_mangle]
+#[doc(hidden)]
+pub extern "aapcs" fn __aeabi_frsub(a: f32, b: f32) -> f32 {
+    __aeabi_fsub(b, a)
+}
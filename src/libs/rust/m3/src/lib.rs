@@ -51,13 +51,15 @@
 //! - [`networking`](`crate::net`): sockets (TCP/UDP) an DNS resolver
 //! - [`client`](`crate::client`): client-side APIs for the available M³ services
 //! - [`server`](`crate::server`): request handling, session management, etc.
+//! - [`sync`](`crate::sync`): mutexes and condition variables for activities on the same tile
 //! - [`tiles`](`crate::tiles`): tiles and activities on tiles
+//! - [`timer`](`crate::timer`): timers that call functions after a given time
 //! - [`vfs`](`crate::vfs`): virtual file system
 
 #![no_std]
 
-#[allow(unused_extern_crates)]
-extern crate heap;
+/// The heap allocator, which also provides statistics about the heap usage
+pub use heap;
 
 #[allow(unused_extern_crates)]
 extern crate lang;
@@ -27,3 +27,7 @@ pub use self::selspace::SelSpace;
 pub(crate) fn init() {
     self::selspace::init();
 }
+
+pub(crate) fn forget_inherited() {
+    self::selspace::forget_inherited();
+}
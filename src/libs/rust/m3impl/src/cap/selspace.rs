@@ -42,6 +42,11 @@ impl SelSpace {
     }
 }
 
+pub(crate) fn forget_inherited() {
+    // safety: we are called during initialization, before anybody can hold a reference
+    unsafe { SELSPACE.unset() };
+}
+
 pub(crate) fn init() {
     let env = crate::env::get();
     SELSPACE.set(SelSpace {
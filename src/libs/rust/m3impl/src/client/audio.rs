@@ -0,0 +1,212 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use serde_repr::{Deserialize_repr, Serialize_repr};
+
+use crate::client::ClientSession;
+use crate::com::{opcodes, MemGate, RecvGate, SendGate};
+use crate::errors::{Code, Error};
+use crate::mem::GlobOff;
+use crate::serialize::{Deserialize, Serialize};
+
+/// The formats of a single sample
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
+pub enum SampleFormat {
+    /// Unsigned 8-bit samples
+    U8,
+    /// Signed 16-bit little-endian samples
+    #[default]
+    S16LE,
+    /// Signed 32-bit little-endian samples
+    S32LE,
+}
+
+impl SampleFormat {
+    /// Returns the size of a single sample in bytes
+    pub fn size(self) -> usize {
+        match self {
+            Self::U8 => 1,
+            Self::S16LE => 2,
+            Self::S32LE => 4,
+        }
+    }
+
+    /// Returns the byte value that represents silence
+    pub fn silence(self) -> u8 {
+        match self {
+            Self::U8 => 0x80,
+            _ => 0,
+        }
+    }
+}
+
+/// The format of an audio stream
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "base::serde")]
+pub struct AudioConfig {
+    /// The number of frames per second
+    pub rate: u32,
+    /// The number of interleaved channels per frame
+    pub channels: u8,
+    /// The format of each sample
+    pub format: SampleFormat,
+}
+
+impl AudioConfig {
+    /// Creates a new configuration with given rate, number of channels, and sample format
+    pub fn new(rate: u32, channels: u8, format: SampleFormat) -> Self {
+        AudioConfig {
+            rate,
+            channels,
+            format,
+        }
+    }
+
+    /// Returns the size of a frame (one sample for each channel) in bytes
+    pub fn frame_size(&self) -> usize {
+        self.channels as usize * self.format.size()
+    }
+
+    /// Returns the number of bytes that are played per second
+    pub fn bytes_per_sec(&self) -> usize {
+        self.rate as usize * self.frame_size()
+    }
+}
+
+/// Represents a playback stream at the audio server
+///
+/// The audio server drives a sound device and plays the samples from a ring buffer, which is
+/// divided into periods of equal size. The server hands a period to the device as soon as the
+/// client has filled it and reuses it as soon as the device has played it.
+///
+/// When creating an `AudioSink`, the client requests a sample rate, format, and number of
+/// channels. The server picks the closest configuration the device supports, which is available
+/// via [`AudioSink::config`] afterwards. The samples are written interleaved via
+/// [`AudioSink::write`], which blocks until there is room in the ring buffer.
+pub struct AudioSink {
+    _sess: ClientSession,
+    sgate: SendGate,
+    mem: MemGate,
+    config: AudioConfig,
+    period_size: usize,
+    periods: usize,
+    // the position within the ring buffer at which the next samples are written
+    pos: usize,
+    // the number of bytes that can be written without waiting
+    free: usize,
+}
+
+impl AudioSink {
+    /// Creates a new `AudioSink` at service with given name and negotiates the configuration of
+    /// the stream, starting with `wanted`.
+    pub fn new(name: &str, wanted: AudioConfig) -> Result<Self, Error> {
+        let sess = ClientSession::new(name)?;
+        let sgate = sess.connect()?;
+
+        let mut reply = send_recv_res!(&sgate, RecvGate::def(), opcodes::Audio::Configure, wanted)?;
+        let config: AudioConfig = reply.pop()?;
+        let period_size: usize = reply.pop()?;
+        let periods: usize = reply.pop()?;
+        drop(reply);
+
+        let crd = sess.obtain(
+            1,
+            |os| {
+                os.push(opcodes::Audio::GetMem);
+            },
+            |_| Ok(()),
+        )?;
+        let mem = MemGate::new_owned_bind(crd.start())?;
+
+        Ok(AudioSink {
+            _sess: sess,
+            sgate,
+            mem,
+            config,
+            period_size,
+            periods,
+            pos: 0,
+            free: period_size * periods,
+        })
+    }
+
+    /// Returns the negotiated configuration of the stream
+    pub fn config(&self) -> &AudioConfig {
+        &self.config
+    }
+
+    /// Returns the size of a period in bytes
+    pub fn period_size(&self) -> usize {
+        self.period_size
+    }
+
+    /// Returns the size of the ring buffer in bytes
+    pub fn buffer_size(&self) -> usize {
+        self.period_size * self.periods
+    }
+
+    /// Writes the given interleaved samples into the ring buffer and hands them over to the
+    /// device.
+    ///
+    /// The length of `samples` needs to be a multiple of the frame size. The method blocks until
+    /// all samples have been written. Note that the device starts to play as soon as the first
+    /// period has been filled.
+    pub fn write(&mut self, samples: &[u8]) -> Result<(), Error> {
+        if samples.len() % self.config.frame_size() != 0 {
+            return Err(Error::new(Code::InvArgs));
+        }
+
+        let mut off = 0;
+        while off < samples.len() {
+            if self.free == 0 {
+                self.wait()?;
+            }
+
+            // write at most up to the end of the ring buffer and continue at the beginning
+            let amount = (samples.len() - off)
+                .min(self.free)
+                .min(self.buffer_size() - self.pos);
+            self.mem
+                .write(&samples[off..off + amount], self.pos as GlobOff)?;
+
+            let mut reply =
+                send_recv_res!(&self.sgate, RecvGate::def(), opcodes::Audio::Commit, amount)?;
+            self.free = reply.pop()?;
+            self.pos = (self.pos + amount) % self.buffer_size();
+            off += amount;
+        }
+        Ok(())
+    }
+
+    /// Waits until the device has played at least one period and returns the number of bytes
+    /// that can be written without waiting afterwards.
+    pub fn wait(&mut self) -> Result<usize, Error> {
+        let mut reply = send_recv_res!(&self.sgate, RecvGate::def(), opcodes::Audio::Wait)?;
+        self.free = reply.pop()?;
+        Ok(self.free)
+    }
+
+    /// Waits until all written samples have been played and stops the stream
+    ///
+    /// An incomplete period at the end is filled up with silence. Afterwards, the ring buffer is
+    /// empty again and the stream starts anew with the next [`AudioSink::write`].
+    pub fn drain(&mut self) -> Result<(), Error> {
+        send_recv_res!(&self.sgate, RecvGate::def(), opcodes::Audio::Drain)?;
+        self.pos = 0;
+        self.free = self.buffer_size();
+        Ok(())
+    }
+}
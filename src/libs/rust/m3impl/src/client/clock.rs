@@ -0,0 +1,59 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use crate::client::ClientSession;
+use crate::com::{opcodes, RecvGate, SendGate};
+use crate::env;
+use crate::errors::Error;
+use crate::format;
+use crate::time::{SystemTime, TimeDuration, BOOT_TIME_VAR};
+
+/// Represents a session at the clock server
+///
+/// The clock server knows the wall-clock time, obtained from a real-time clock or via NTP, and
+/// provides it in form of the boot time, that is, the wall-clock time at which the TCU time was
+/// zero. Since the TCU time is global, the current time can afterwards be derived locally via
+/// [`SystemTime::now`].
+pub struct Clock {
+    _sess: ClientSession,
+    sgate: SendGate,
+}
+
+impl Clock {
+    /// Creates a new `Clock` session at service with given name.
+    pub fn new(name: &str) -> Result<Self, Error> {
+        let sess = ClientSession::new(name)?;
+        let sgate = sess.connect()?;
+        Ok(Clock { _sess: sess, sgate })
+    }
+
+    /// Retrieves the boot time from the server
+    pub fn boot_time(&self) -> Result<SystemTime, Error> {
+        let mut reply = send_recv_res!(&self.sgate, RecvGate::def(), opcodes::Clock::BootTime)?;
+        let nanos: u64 = reply.pop()?;
+        Ok(SystemTime::from_unix(TimeDuration::from_nanos(nanos)))
+    }
+
+    /// Synchronizes the wall-clock time of this activity with the server
+    ///
+    /// Afterwards, [`SystemTime::now`] yields the real time in this activity and all child
+    /// activities that are created afterwards. Returns the current time.
+    pub fn sync(&self) -> Result<SystemTime, Error> {
+        let boot = self.boot_time()?;
+        SystemTime::set_boot_time(boot);
+        env::set_var(BOOT_TIME_VAR, format!("{}", boot.as_unix().as_nanos()));
+        Ok(SystemTime::now())
+    }
+}
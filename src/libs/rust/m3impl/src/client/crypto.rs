@@ -0,0 +1,213 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use core::ops::Range;
+
+use crate::client::ClientSession;
+use crate::com::{opcodes, RecvGate, SendGate, EP};
+use crate::crypto::HashAlgorithm;
+use crate::errors::Error;
+
+/// The identifier of a key within a [`CryptoSession`]
+pub type KeyId = usize;
+
+/// The number of bytes of the authentication tag that [`CryptoSession::seal`] appends
+pub const AEAD_TAG_SIZE: usize = 32;
+
+/// Represents a session at the crypto service
+///
+/// The crypto service performs hashing, KMAC, and authenticated encryption on behalf of its
+/// clients, using the Keccak accelerator if available. The data is exchanged via the
+/// [`EP`](CryptoSession::ep), which should be configured with a
+/// [`MemGate`](crate::com::MemGate) before the operations are used. All offsets refer to this
+/// memory region.
+///
+/// Keys are imported into the session and referred to by their [`KeyId`] afterwards. They never
+/// leave the crypto service and are only accessible via the session they have been imported into.
+pub struct CryptoSession {
+    _sess: ClientSession,
+    sgate: SendGate,
+    ep: EP,
+}
+
+impl CryptoSession {
+    /// Creates a new session at the crypto service with given name.
+    pub fn new(name: &str) -> Result<Self, Error> {
+        let sess = ClientSession::new(name)?;
+        let sgate = sess.connect()?;
+        let ep = sess.obtain(1, |is| is.push(opcodes::Crypto::GetMem), |_| Ok(()))?;
+
+        Ok(CryptoSession {
+            _sess: sess,
+            sgate,
+            ep: EP::new_bind(0, ep.start()),
+        })
+    }
+
+    /// Returns the [`EP`] that should be configured with [`MemGate`](crate::com::MemGate)s for
+    /// all operations.
+    pub fn ep(&self) -> &EP {
+        &self.ep
+    }
+
+    /// Imports the key at the given memory region into the session and returns its id.
+    pub fn import_key(&self, key: Range<usize>) -> Result<KeyId, Error> {
+        let mut reply = send_recv_res!(
+            &self.sgate,
+            RecvGate::def(),
+            opcodes::Crypto::ImportKey,
+            key.start,
+            key.end
+        )?;
+        reply.pop()
+    }
+
+    /// Deletes the given key from the session.
+    pub fn delete_key(&self, key: KeyId) -> Result<(), Error> {
+        send_recv_res!(
+            &self.sgate,
+            RecvGate::def(),
+            opcodes::Crypto::DeleteKey,
+            key
+        )
+        .map(|_| ())
+    }
+
+    /// Hashes the data in region `input` with the given algorithm and writes the hash to region
+    /// `output`.
+    ///
+    /// For fixed-size algorithms, `output` needs to have exactly the hash size. XOFs
+    /// (extendable-output functions) produce as many bytes as requested.
+    pub fn hash(
+        &self,
+        algo: &'static HashAlgorithm,
+        input: Range<usize>,
+        output: Range<usize>,
+    ) -> Result<(), Error> {
+        self.cshake(algo, "", "", input, output)
+    }
+
+    /// Like [`hash`](CryptoSession::hash), but uses the given function name `n` and
+    /// customization string `s` for cSHAKE (see NIST SP 800-185).
+    pub fn cshake(
+        &self,
+        algo: &'static HashAlgorithm,
+        n: &str,
+        s: &str,
+        input: Range<usize>,
+        output: Range<usize>,
+    ) -> Result<(), Error> {
+        send_recv_res!(
+            &self.sgate,
+            RecvGate::def(),
+            opcodes::Crypto::Hash,
+            algo.ty,
+            n,
+            s,
+            input.start,
+            input.end,
+            output.start,
+            output.end
+        )
+        .map(|_| ())
+    }
+
+    /// Computes the KMAC of the data in region `input` with given key and customization string
+    /// `s` and writes it to region `output`.
+    ///
+    /// The KMAC variant is determined by `algo`, which is either
+    /// [`CSHAKE128`](HashAlgorithm::CSHAKE128) for KMAC128 or
+    /// [`CSHAKE256`](HashAlgorithm::CSHAKE256) for KMAC256.
+    pub fn kmac(
+        &self,
+        key: KeyId,
+        algo: &'static HashAlgorithm,
+        s: &str,
+        input: Range<usize>,
+        output: Range<usize>,
+    ) -> Result<(), Error> {
+        send_recv_res!(
+            &self.sgate,
+            RecvGate::def(),
+            opcodes::Crypto::Kmac,
+            key,
+            algo.ty,
+            s,
+            input.start,
+            input.end,
+            output.start,
+            output.end
+        )
+        .map(|_| ())
+    }
+
+    /// Encrypts the data in region `input` with given key and nonce and authenticates it together
+    /// with the associated data in region `ad`.
+    ///
+    /// The ciphertext is written to `out` followed by the authentication tag of
+    /// [`AEAD_TAG_SIZE`] bytes. Returns the number of written bytes. The nonce must not be used
+    /// more than once with the same key.
+    pub fn seal(
+        &self,
+        key: KeyId,
+        nonce: u64,
+        ad: Range<usize>,
+        input: Range<usize>,
+        out: usize,
+    ) -> Result<usize, Error> {
+        self.aead(opcodes::Crypto::Seal, key, nonce, ad, input, out)
+    }
+
+    /// Verifies the ciphertext including the authentication tag in region `input` and the
+    /// associated data in region `ad` and decrypts it with given key and nonce.
+    ///
+    /// The plaintext is written to `out` and the number of written bytes is returned. If the
+    /// authentication fails, nothing is written and [`Code::InvChecksum`](crate::errors::Code)
+    /// is returned.
+    pub fn open(
+        &self,
+        key: KeyId,
+        nonce: u64,
+        ad: Range<usize>,
+        input: Range<usize>,
+        out: usize,
+    ) -> Result<usize, Error> {
+        self.aead(opcodes::Crypto::Open, key, nonce, ad, input, out)
+    }
+
+    fn aead(
+        &self,
+        op: opcodes::Crypto,
+        key: KeyId,
+        nonce: u64,
+        ad: Range<usize>,
+        input: Range<usize>,
+        out: usize,
+    ) -> Result<usize, Error> {
+        let mut reply = send_recv_res!(
+            &self.sgate,
+            RecvGate::def(),
+            op,
+            key,
+            nonce,
+            ad.start,
+            ad.end,
+            input.start,
+            input.end,
+            out
+        )?;
+        reply.pop()
+    }
+}
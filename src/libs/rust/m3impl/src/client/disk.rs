@@ -16,15 +16,17 @@
  */
 
 use crate::client::ClientSession;
+use crate::col::String;
 use crate::com::{opcodes, MemCap, RecvGate, SendGate};
 use crate::errors::Error;
 use crate::kif::{CapRngDesc, CapType};
 use crate::mem::GlobOff;
+use crate::serialize::{Deserialize, Serialize};
 use crate::util::math;
 
 use core::{cmp, fmt};
 
-pub const MSG_SIZE: usize = 128;
+pub const MSG_SIZE: usize = 256;
 pub const MSG_SLOTS: usize = 1;
 
 pub type DiskBlockNo = u32;
@@ -75,6 +77,42 @@ impl cmp::Ord for DiskBlockRange {
     }
 }
 
+/// The statistics of the block cache in the disk server, which is shared by all sessions
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+#[serde(crate = "base::serde")]
+pub struct DiskCacheStats {
+    /// The number of blocks the cache can hold
+    pub capacity: usize,
+    /// The number of blocks that are currently cached
+    pub used: usize,
+    /// The number of cached blocks that have been modified, but not written back yet
+    pub dirty: usize,
+    /// The number of cached blocks that are used by multiple sessions
+    pub shared: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub writebacks: u64,
+}
+
+/// Information about a partition that is provided by the disk server
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(crate = "base::serde")]
+pub struct DiskPartInfo {
+    /// The partition number, which is used as session argument (e.g., "part0")
+    pub id: usize,
+    /// The first sector of the partition on the disk
+    pub start: u64,
+    /// The number of 512-byte sectors
+    pub sectors: u64,
+    /// The type of the partition: "whole" if the disk has no partition table, the system id for
+    /// MBR partitions (e.g., "mbr:0x83"), or the type GUID for GPT partitions
+    pub ty: String,
+    /// The name of the partition (GPT only)
+    pub name: String,
+    pub bootable: bool,
+}
+
 /// Represents a session at the disk server
 pub struct Disk {
     sess: ClientSession,
@@ -151,4 +189,27 @@ impl Disk {
         )
         .map(|_| ())
     }
+
+    /// Writes all modified blocks of the disk server's cache back to the disk
+    pub fn flush(&self) -> Result<(), Error> {
+        send_recv_res!(&self.sgate, &self.rgate, opcodes::Disk::Flush).map(|_| ())
+    }
+
+    /// Returns the current statistics of the disk server's block cache
+    pub fn cache_stats(&self) -> Result<DiskCacheStats, Error> {
+        let mut reply = send_recv_res!(&self.sgate, &self.rgate, opcodes::Disk::Stats)?;
+        reply.pop()
+    }
+
+    /// Returns the number of partitions for `partition_info`, including the ones of other disks
+    pub fn partition_count(&self) -> Result<usize, Error> {
+        let mut reply = send_recv_res!(&self.sgate, &self.rgate, opcodes::Disk::PartCount)?;
+        reply.pop()
+    }
+
+    /// Retrieves information about the partition with given index (0..`partition_count`)
+    pub fn partition_info(&self, idx: usize) -> Result<DiskPartInfo, Error> {
+        let mut reply = send_recv_res!(&self.sgate, &self.rgate, opcodes::Disk::PartInfo, idx)?;
+        reply.pop()
+    }
 }
@@ -0,0 +1,136 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use crate::client::ClientSession;
+use crate::com::{opcodes, MemGate, RecvGate, SendGate};
+use crate::errors::Error;
+use crate::serialize::{Deserialize, Serialize};
+
+/// The number of bytes per pixel
+///
+/// Each pixel is a 32-bit little-endian value of the form `0x00RRGGBB`.
+pub const FB_BYTES_PER_PIXEL: usize = 4;
+
+/// The resolution and memory layout of a framebuffer
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "base::serde")]
+pub struct FbInfo {
+    /// The width in pixels
+    pub width: u32,
+    /// The height in pixels
+    pub height: u32,
+    /// The distance between two rows in bytes
+    pub stride: u32,
+}
+
+impl FbInfo {
+    /// Returns the size of the pixel buffer in bytes
+    pub fn size(&self) -> usize {
+        self.stride as usize * self.height as usize
+    }
+
+    /// Returns the offset of the given pixel within the pixel buffer
+    pub fn offset(&self, x: u32, y: u32) -> usize {
+        y as usize * self.stride as usize + x as usize * FB_BYTES_PER_PIXEL
+    }
+}
+
+/// A rectangle within a framebuffer
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "base::serde")]
+pub struct FbRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FbRect {
+    /// Creates a new rectangle at given position with given size
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        FbRect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Returns true if the rectangle does not contain any pixels
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    /// Returns the smallest rectangle that contains this and the given rectangle
+    pub fn union(&self, other: &FbRect) -> FbRect {
+        if self.is_empty() {
+            return *other;
+        }
+        if other.is_empty() {
+            return *self;
+        }
+
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let right = (self.x + self.width).max(other.x + other.width);
+        let bottom = (self.y + self.height).max(other.y + other.height);
+        FbRect::new(x, y, right - x, bottom - y)
+    }
+}
+
+/// Represents a session at the framebuffer server
+///
+/// The framebuffer server drives a graphics device and provides its pixel buffer to clients. The
+/// pixel buffer is obtained as a [`MemGate`] via [`Framebuffer::get_mem`] and is shared by all
+/// clients. After changing pixels, the client needs to tell the server via [`Framebuffer::flush`]
+/// which part of the buffer should be made visible on the display.
+pub struct Framebuffer {
+    sess: ClientSession,
+    sgate: SendGate,
+    info: FbInfo,
+}
+
+impl Framebuffer {
+    /// Creates a new `Framebuffer` session at service with given name.
+    pub fn new(name: &str) -> Result<Self, Error> {
+        let sess = ClientSession::new(name)?;
+        let sgate = sess.connect()?;
+        let mut reply = send_recv_res!(&sgate, RecvGate::def(), opcodes::Fb::Info)?;
+        let info = reply.pop()?;
+        Ok(Framebuffer { sess, sgate, info })
+    }
+
+    /// Returns the resolution and memory layout of the framebuffer
+    pub fn info(&self) -> &FbInfo {
+        &self.info
+    }
+
+    /// Obtains a [`MemGate`] for the pixel buffer, which has a size of [`FbInfo::size`] bytes
+    pub fn get_mem(&self) -> Result<MemGate, Error> {
+        let crd = self.sess.obtain(
+            1,
+            |os| {
+                os.push(opcodes::Fb::GetMem);
+            },
+            |_| Ok(()),
+        )?;
+        MemGate::new_owned_bind(crd.start())
+    }
+
+    /// Makes the given rectangle of the pixel buffer visible on the display
+    pub fn flush(&self, rect: FbRect) -> Result<(), Error> {
+        send_recv_res!(&self.sgate, RecvGate::def(), opcodes::Fb::Flush, rect).map(|_| ())
+    }
+}
@@ -0,0 +1,129 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use crate::client::ClientSession;
+use crate::col::{String, ToString, Vec};
+use crate::com::{opcodes, MemCap, MemGate, Perm, RecvGate, SendGate, EP};
+use crate::errors::{Code, Error};
+use crate::mem::GlobOff;
+use crate::vec;
+
+/// The maximum length of keys in bytes
+pub const KV_MAX_KEY_LEN: usize = 128;
+/// The maximum length of values in bytes
+pub const KV_MAX_VALUE_LEN: usize = 4096;
+
+/// Represents a session at the key-value store
+///
+/// The key-value store persistently stores values of up to [`KV_MAX_VALUE_LEN`] bytes under
+/// non-empty string keys of up to [`KV_MAX_KEY_LEN`] bytes. The keys are shared by all sessions of
+/// the same service. Values are exchanged via a memory region of the client, which is handed out
+/// to the service once when creating the session.
+pub struct KvStore {
+    _sess: ClientSession,
+    sgate: SendGate,
+    mem: MemGate,
+    _ep: EP,
+    _cmem: MemCap,
+}
+
+impl KvStore {
+    /// Creates a new session at the key-value store with given name.
+    pub fn new(name: &str) -> Result<Self, Error> {
+        let sess = ClientSession::new(name)?;
+        let sgate = sess.connect()?;
+        let crd = sess.obtain(1, |is| is.push(opcodes::KeyValue::GetMem), |_| Ok(()))?;
+        let ep = EP::new_bind(0, crd.start());
+
+        let mem = MemGate::new(KV_MAX_VALUE_LEN as GlobOff, Perm::RW)?;
+        // use a separate MemCap for the service, because both have to activate the gate
+        let cmem = mem.derive_cap(0, KV_MAX_VALUE_LEN as GlobOff, Perm::RW)?;
+        ep.configure(cmem.sel())?;
+
+        Ok(KvStore {
+            _sess: sess,
+            sgate,
+            mem,
+            _ep: ep,
+            _cmem: cmem,
+        })
+    }
+
+    /// Returns the value of the given key or [`Code::NotFound`] if it does not exist.
+    pub fn get(&self, key: &str) -> Result<Vec<u8>, Error> {
+        let mut reply = send_recv_res!(&self.sgate, RecvGate::def(), opcodes::KeyValue::Get, key)?;
+        let len: usize = reply.pop()?;
+
+        let mut value = vec![0u8; len];
+        self.mem.read(&mut value, 0)?;
+        Ok(value)
+    }
+
+    /// Sets the value of the given key, replacing the previous value, if any.
+    pub fn put(&self, key: &str, value: &[u8]) -> Result<(), Error> {
+        if key.is_empty() || key.len() > KV_MAX_KEY_LEN || value.len() > KV_MAX_VALUE_LEN {
+            return Err(Error::new(Code::InvArgs));
+        }
+
+        self.mem.write(value, 0)?;
+        send_recv_res!(
+            &self.sgate,
+            RecvGate::def(),
+            opcodes::KeyValue::Put,
+            key,
+            value.len()
+        )
+        .map(|_| ())
+    }
+
+    /// Removes the given key or returns [`Code::NotFound`] if it does not exist.
+    pub fn delete(&self, key: &str) -> Result<(), Error> {
+        send_recv_res!(&self.sgate, RecvGate::def(), opcodes::KeyValue::Delete, key).map(|_| ())
+    }
+
+    /// Returns all keys that start with the given prefix in ascending order.
+    pub fn scan(&self, prefix: &str) -> Result<Vec<String>, Error> {
+        let mut keys = Vec::new();
+        let mut buf = vec![0u8; KV_MAX_VALUE_LEN];
+        loop {
+            // continue after the last key we received
+            let after = keys.last().map(|k: &String| k.as_str()).unwrap_or("");
+            let mut reply = send_recv_res!(
+                &self.sgate,
+                RecvGate::def(),
+                opcodes::KeyValue::Scan,
+                prefix,
+                after
+            )?;
+            let len: usize = reply.pop()?;
+            let more: bool = reply.pop()?;
+
+            // the keys are stored as length byte followed by the key
+            self.mem.read(&mut buf[0..len], 0)?;
+            let mut pos = 0;
+            while pos < len {
+                let klen = buf[pos] as usize;
+                let key = core::str::from_utf8(&buf[pos + 1..pos + 1 + klen])
+                    .map_err(|_| Error::new(Code::InvArgs))?;
+                keys.push(key.to_string());
+                pos += 1 + klen;
+            }
+
+            if !more {
+                break Ok(keys);
+            }
+        }
+    }
+}
@@ -16,6 +16,7 @@
  * General Public License version 2 for more details.
  */
 
+use bitflags::bitflags;
 use core::any::Any;
 use core::fmt;
 
@@ -23,16 +24,85 @@ use crate::boxed::Box;
 use crate::cap::{SelSpace, Selector};
 use crate::cell::RefCell;
 use crate::client::ClientSession;
-use crate::col::Vec;
-use crate::com::{opcodes, recv_result, EpMng, RecvGate, SendGate, EP};
-use crate::errors::Error;
+use crate::col::{String, ToString, Vec};
+use crate::com::{opcodes, recv_result, EpMng, GateIStream, RecvGate, SendCap, SendGate, EP};
+use crate::errors::{Code, Error};
 use crate::kif;
 use crate::mem::GlobOff;
 use crate::rc::Rc;
-use crate::serialize::{M3Deserializer, M3Serializer, VecSink};
+use crate::serialize::{Deserialize, M3Deserializer, M3Serializer, Serialize, VecSink};
 use crate::tiles::{Activity, ChildActivity};
 use crate::vfs::{FSHandle, File, FileInfo, FileMode, FileSystem, GenericFile, OpenFlags};
 
+/// The size of notification messages for a [`Watch`] as a power of 2
+const WATCH_MSG_ORD: u32 = 8;
+/// The number of notification messages a [`Watch`] can buffer as a power of 2
+const WATCH_SLOTS_ORD: u32 = 2;
+
+bitflags! {
+    /// The events that can be watched via [`M3FS::watch`]
+    #[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(crate = "base::serde")]
+    pub struct WatchEvent : u64 {
+        /// A file or directory has been created
+        const CREATE    = 1;
+        /// The content of a file has been changed
+        const MODIFY    = 2;
+        /// A file or directory has been removed
+        const DELETE    = 4;
+        /// A file or directory has been renamed (reported for the old and the new name)
+        const RENAME    = 8;
+        /// Events have been lost, because the watcher did not fetch them in time
+        const OVERFLOW  = 0x8000_0000_0000_0000;
+    }
+}
+
+/// A watch for file-system events on a path at m3fs, created by [`M3FS::watch`]
+///
+/// If the watched path refers to a directory, the watch reports events for the entries of this
+/// directory and the name of the affected entry. Events for the watched path itself are reported
+/// with an empty name. Dropping the `Watch` removes it at m3fs as well.
+pub struct Watch {
+    id: usize,
+    rgate: RecvGate,
+    _sgate: SendCap,
+}
+
+impl Watch {
+    /// Returns the id of this watch
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Waits for the next event and returns it together with the name of the affected entry.
+    pub fn receive(&self) -> Result<(WatchEvent, String), Error> {
+        let msg = self.rgate.receive(None)?;
+        Self::handle_msg(GateIStream::new(msg, &self.rgate))
+    }
+
+    /// Returns the next event and the name of the affected entry, if there is any, without
+    /// blocking.
+    pub fn try_receive(&self) -> Result<Option<(WatchEvent, String)>, Error> {
+        match self.rgate.fetch() {
+            Ok(msg) => Self::handle_msg(GateIStream::new(msg, &self.rgate)).map(Some),
+            Err(e) if e.code() == Code::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn handle_msg(mut is: GateIStream<'_>) -> Result<(WatchEvent, String), Error> {
+        let events = WatchEvent::from_bits_truncate(is.pop()?);
+        let name: &str = is.pop()?;
+        Ok((events, name.to_string()))
+    }
+}
+
+impl fmt::Debug for Watch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Watch[id={}, rgate={:?}]", self.id, self.rgate)
+    }
+}
+
 struct CachedEP {
     id: usize,
     ep: EP,
@@ -84,6 +154,36 @@ impl M3FS {
         &self.sess
     }
 
+    /// Registers a watch for the given events on `path`.
+    ///
+    /// The path is interpreted relative to the root of this m3fs instance and has to exist. m3fs
+    /// will send a notification to the returned [`Watch`] whenever one of the given events occurs
+    /// on `path` or, if `path` is a directory, on one of its entries.
+    pub fn watch(&self, path: &str, events: WatchEvent) -> Result<Watch, Error> {
+        let rgate = RecvGate::new(WATCH_MSG_ORD + WATCH_SLOTS_ORD, WATCH_MSG_ORD)?;
+        let sgate = SendCap::new(&rgate)?;
+
+        let mut id = 0;
+        self.sess.delegate(
+            kif::CapRngDesc::new(kif::CapType::Object, sgate.sel(), 1),
+            |os| {
+                os.push(opcodes::FileSystem::EnableNotify);
+                os.push(path);
+                os.push(events);
+            },
+            |is| {
+                id = is.pop()?;
+                Ok(())
+            },
+        )?;
+
+        Ok(Watch {
+            id,
+            rgate,
+            _sgate: sgate,
+        })
+    }
+
     pub fn get_mem(
         sess: &ClientSession,
         off: GlobOff,
@@ -241,6 +341,18 @@ impl FileSystem for M3FS {
         .map(|_| ())
     }
 
+    fn utime(&self, path: &str, lastaccess: u32, lastmod: u32) -> Result<(), Error> {
+        send_recv_res!(
+            &self.sgate,
+            RecvGate::def(),
+            opcodes::FileSystem::Utime,
+            path,
+            lastaccess,
+            lastmod
+        )
+        .map(|_| ())
+    }
+
     fn fs_type(&self) -> u8 {
         b'M'
     }
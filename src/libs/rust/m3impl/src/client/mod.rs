@@ -27,22 +27,36 @@
 //! [`Pipes`] builds upon a [`ClientSession`] and uses it to perform capability exchanges in order
 //! to create pipes and channels to such pipes.
 
+mod audio;
+mod clock;
+mod crypto;
 mod disk;
+mod fb;
 mod hash;
+mod kvstore;
 mod m3fs;
 mod network;
 mod pager;
 mod pipe;
+mod pty;
 pub mod resmng;
 mod session;
+mod shm;
 mod vterm;
 
-pub use self::disk::{Disk, DiskBlockNo, DiskBlockRange};
+pub use self::audio::{AudioConfig, AudioSink, SampleFormat};
+pub use self::clock::Clock;
+pub use self::crypto::{CryptoSession, KeyId, AEAD_TAG_SIZE};
+pub use self::disk::{Disk, DiskBlockNo, DiskBlockRange, DiskCacheStats, DiskPartInfo};
+pub use self::fb::{FbInfo, FbRect, Framebuffer, FB_BYTES_PER_PIXEL};
 pub use self::hash::{HashInput, HashOutput, HashSession};
-pub use self::m3fs::M3FS;
+pub use self::kvstore::{KvStore, KV_MAX_KEY_LEN, KV_MAX_VALUE_LEN};
+pub use self::m3fs::{Watch, WatchEvent, M3FS};
 pub use self::network::Network;
 pub use self::pager::{MapFlags, Pager};
 pub use self::pipe::{Pipe, Pipes};
+pub use self::pty::Pty;
 pub use self::resmng::{ResMng, ResMngChild};
 pub use self::session::ClientSession;
+pub use self::shm::Shm;
 pub use self::vterm::VTerm;
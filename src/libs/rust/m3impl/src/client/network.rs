@@ -16,8 +16,10 @@
 
 use crate::client::ClientSession;
 use crate::com::{opcodes, RecvGate, SendGate};
-use crate::errors::Error;
-use crate::net::{BaseSocket, Endpoint, IpAddr, NetEventChannel, Port, Sd, SocketArgs, SocketType};
+use crate::errors::{Code, Error};
+use crate::net::{
+    BaseSocket, Endpoint, IpAddr, NetEventChannel, Port, Sd, SocketArgs, SocketOpt, SocketType,
+};
 use crate::rc::Rc;
 
 /// Represents a session at the network server, allowing to create and use sockets
@@ -42,10 +44,23 @@ impl Network {
     /// Returns the local IP address
     pub fn ip_addr(&self) -> Result<IpAddr, Error> {
         let mut reply = send_recv_res!(&self.sgate, RecvGate::def(), opcodes::Net::GetIP)?;
-        let addr = IpAddr(reply.pop::<u32>()?);
+        let addr = IpAddr::from_raw([reply.pop()?, reply.pop()?]);
         Ok(addr)
     }
 
+    /// Returns the local IPv6 address
+    ///
+    /// Returns [`NotSup`](crate::errors::Code::NotSup) if the server has no IPv6 address.
+    pub fn ip6_addr(&self) -> Result<IpAddr, Error> {
+        let mut reply = send_recv_res!(&self.sgate, RecvGate::def(), opcodes::Net::GetIP)?;
+        // the IPv6 address follows the IPv4 address
+        let _addr4 = IpAddr::from_raw([reply.pop()?, reply.pop()?]);
+        match IpAddr::from_raw([reply.pop()?, reply.pop()?]) {
+            IpAddr::V6(0) | IpAddr::V4(_) => Err(Error::new(Code::NotSup)),
+            addr => Ok(addr),
+        }
+    }
+
     pub(crate) fn create(
         &self,
         ty: SocketType,
@@ -76,36 +91,56 @@ impl Network {
 
     pub(crate) fn nameserver(&self) -> Result<IpAddr, Error> {
         let mut reply = send_recv_res!(&self.sgate, RecvGate::def(), opcodes::Net::GetNameSrv)?;
-        let addr = IpAddr(reply.pop::<u32>()?);
+        let addr = IpAddr::from_raw([reply.pop()?, reply.pop()?]);
         Ok(addr)
     }
 
     pub(crate) fn bind(&self, sd: Sd, port: Port) -> Result<(IpAddr, Port), Error> {
         let mut reply = send_recv_res!(&self.sgate, RecvGate::def(), opcodes::Net::Bind, sd, port)?;
-        let addr = IpAddr(reply.pop::<u32>()?);
+        let addr = IpAddr::from_raw([reply.pop()?, reply.pop()?]);
         let port = reply.pop::<Port>()?;
         Ok((addr, port))
     }
 
-    pub(crate) fn listen(&self, sd: Sd, port: Port) -> Result<IpAddr, Error> {
-        let mut reply =
-            send_recv_res!(&self.sgate, RecvGate::def(), opcodes::Net::Listen, sd, port)?;
-        let addr = IpAddr(reply.pop::<u32>()?);
+    pub(crate) fn listen(&self, sd: Sd, port: Port, backlog: usize) -> Result<IpAddr, Error> {
+        let mut reply = send_recv_res!(
+            &self.sgate,
+            RecvGate::def(),
+            opcodes::Net::Listen,
+            sd,
+            port,
+            backlog
+        )?;
+        let addr = IpAddr::from_raw([reply.pop()?, reply.pop()?]);
         Ok(addr)
     }
 
+    pub(crate) fn accept(&self, sd: Sd, new_sd: Sd) -> Result<Endpoint, Error> {
+        let mut reply = send_recv_res!(
+            &self.sgate,
+            RecvGate::def(),
+            opcodes::Net::Accept,
+            sd,
+            new_sd
+        )?;
+        let addr = IpAddr::from_raw([reply.pop()?, reply.pop()?]);
+        let port = reply.pop::<Port>()?;
+        Ok(Endpoint::new(addr, port))
+    }
+
     pub(crate) fn connect(&self, sd: Sd, endpoint: Endpoint) -> Result<Endpoint, Error> {
         let mut reply = send_recv_res!(
             &self.sgate,
             RecvGate::def(),
             opcodes::Net::Connect,
             sd,
-            endpoint.addr.0,
+            endpoint.addr.to_raw()[0],
+            endpoint.addr.to_raw()[1],
             endpoint.port
         )?;
-        let addr = reply.pop::<u32>()?;
+        let addr = IpAddr::from_raw([reply.pop()?, reply.pop()?]);
         let port = reply.pop::<Port>()?;
-        Ok(Endpoint::new(IpAddr(addr), port))
+        Ok(Endpoint::new(addr, port))
     }
 
     pub(crate) fn abort(&self, sd: Sd, remove: bool) -> Result<(), Error> {
@@ -118,4 +153,46 @@ impl Network {
         )
         .map(|_| ())
     }
+
+    pub(crate) fn set_option(&self, sd: Sd, opt: SocketOpt, value: u64) -> Result<(), Error> {
+        send_recv_res!(
+            &self.sgate,
+            RecvGate::def(),
+            opcodes::Net::SetOpt,
+            sd,
+            opt,
+            value
+        )
+        .map(|_| ())
+    }
+
+    pub(crate) fn get_option(&self, sd: Sd, opt: SocketOpt) -> Result<u64, Error> {
+        let mut reply =
+            send_recv_res!(&self.sgate, RecvGate::def(), opcodes::Net::GetOpt, sd, opt)?;
+        reply.pop()
+    }
+
+    pub(crate) fn join_multicast(&self, sd: Sd, addr: IpAddr) -> Result<(), Error> {
+        send_recv_res!(
+            &self.sgate,
+            RecvGate::def(),
+            opcodes::Net::JoinMulticast,
+            sd,
+            addr.to_raw()[0],
+            addr.to_raw()[1]
+        )
+        .map(|_| ())
+    }
+
+    pub(crate) fn leave_multicast(&self, sd: Sd, addr: IpAddr) -> Result<(), Error> {
+        send_recv_res!(
+            &self.sgate,
+            RecvGate::def(),
+            opcodes::Net::LeaveMulticast,
+            sd,
+            addr.to_raw()[0],
+            addr.to_raw()[1]
+        )
+        .map(|_| ())
+    }
 }
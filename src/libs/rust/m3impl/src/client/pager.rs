@@ -126,8 +126,16 @@ impl Pager {
         )?;
 
         // delegate session and sgate caps to child
-        act.delegate_obj(self.sess_sel())?;
-        act.delegate_obj(self.sgate_sel())?;
+        act.delegate_bulk(&[
+            kif::syscalls::ExchangeDesc::new(
+                kif::CapRngDesc::new(kif::CapType::Object, self.sess_sel(), 1),
+                self.sess_sel(),
+            ),
+            kif::syscalls::ExchangeDesc::new(
+                kif::CapRngDesc::new(kif::CapType::Object, self.sgate_sel(), 1),
+                self.sgate_sel(),
+            ),
+        ])?;
 
         // we only need to do that for clones
         if self.sess.is_owned() {
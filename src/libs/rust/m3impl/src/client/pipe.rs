@@ -105,7 +105,7 @@ impl Pipe {
             OpenFlags::R | OpenFlags::NEW_SESS
         }
         else {
-            OpenFlags::W | OpenFlags::NEW_SESS
+            OpenFlags::W | OpenFlags::NEW_SESS | OpenFlags::ATOMIC
         };
         Ok(Box::new(GenericFile::new(flags, crd.start(), None)))
     }
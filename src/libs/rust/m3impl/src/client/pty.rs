@@ -0,0 +1,69 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use crate::boxed::Box;
+use crate::client::ClientSession;
+use crate::com::opcodes;
+use crate::errors::Error;
+use crate::tiles::Activity;
+use crate::vfs::{FileRef, GenericFile, OpenFlags};
+
+/// Represents a session at the pseudo terminal server
+///
+/// A pseudo terminal consists of a master and a slave end. Everything written to the master end is
+/// processed by the line discipline and can afterwards be read from the slave end. Everything
+/// written to the slave end can be read from the master end. Typically, an interactive program like
+/// a shell uses the slave end as its terminal, whereas the master end is driven by a program that
+/// connects the terminal to the user (e.g., via the serial console or the network).
+///
+/// The line discipline is configured via [`Termios`](crate::vfs::Termios), which can be changed
+/// with [`File::set_termios`](crate::vfs::File::set_termios) on either end.
+pub struct Pty {
+    sess: ClientSession,
+}
+
+impl Pty {
+    /// Creates a new `Pty` session at service with given name.
+    pub fn new(name: &str) -> Result<Self, Error> {
+        let sess = ClientSession::new(name)?;
+        Ok(Self { sess })
+    }
+
+    /// Creates a new pseudo terminal and returns its master and slave end.
+    pub fn create_pair(&self) -> Result<(FileRef<GenericFile>, FileRef<GenericFile>), Error> {
+        let crd = self
+            .sess
+            .obtain(2, |os| os.push(opcodes::Pty::OpenPair), |_| Ok(()))?;
+        let master = Box::new(GenericFile::new(
+            OpenFlags::RW | OpenFlags::NEW_SESS,
+            crd.start(),
+            None,
+        ));
+
+        // the slave end is created via the master end's session
+        let msess = ClientSession::new_bind(crd.start());
+        let crd = msess.obtain(2, |os| os.push(opcodes::Pty::OpenSlave), |_| Ok(()))?;
+        let slave = Box::new(GenericFile::new(
+            OpenFlags::RW | OpenFlags::NEW_SESS,
+            crd.start(),
+            None,
+        ));
+
+        let mut files = Activity::own().files();
+        let mfd = files.add(master)?;
+        let sfd = files.add(slave)?;
+        Ok((files.get_as(mfd).unwrap(), files.get_as(sfd).unwrap()))
+    }
+}
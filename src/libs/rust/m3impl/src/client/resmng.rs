@@ -20,14 +20,19 @@ use crate::cap::Selector;
 use crate::cell::StaticRefCell;
 use crate::col::String;
 use crate::col::ToString;
-use crate::com::{opcodes, GateIStream, RecvGate, SendCap, SendGate};
+use crate::col::Vec;
+use crate::com::{opcodes, GateIStream, MemGate, RecvGate, SendCap, SendGate};
 use crate::errors::{Code, Error};
+use crate::io::log::{LogCtrl, LogFormat};
+use crate::io::LogFlags;
 use crate::kif;
 use crate::mem::{GlobOff, MsgBuf};
 use crate::quota::Quota;
+use crate::serialize::M3Deserializer;
 use crate::tcu::{ActId, TileId};
 use crate::tiles::Activity;
 use crate::time::TimeDuration;
+use crate::watchdog::Diagnostics;
 
 // use a separate message buffer here, because the default buffer could be in use for a message over
 // a SendGate, for which the reply gate needs to activated first, possibly involving a MemGate
@@ -41,6 +46,7 @@ pub struct RegServiceReq {
     pub sgate: Selector,
     pub name: String,
     pub sessions: u32,
+    pub version: kif::service::Version,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -70,6 +76,8 @@ pub struct AllocTileReq {
     pub dst: Selector,
     pub desc: kif::TileDesc,
     pub init: bool,
+    pub id: Option<TileId>,
+    pub near: Option<TileId>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -79,6 +87,30 @@ pub struct AllocTileReply {
     pub desc: kif::TileDesc,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "base::serde")]
+pub struct CtrlTileReq {
+    pub id: TileId,
+    pub online: bool,
+    pub desc: kif::TileDesc,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "base::serde")]
+pub struct WatchTilesReq {
+    pub sgate: Selector,
+}
+
+/// The message that is sent to all tile watchers (see [`ResMng::watch_tiles`]) whenever a tile
+/// went offline or online.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "base::serde")]
+pub struct TileEvent {
+    pub id: TileId,
+    pub desc: kif::TileDesc,
+    pub online: bool,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(crate = "base::serde")]
 pub struct UseReq {
@@ -105,6 +137,13 @@ pub struct GetInfoReq {
     pub idx: usize,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "base::serde")]
+pub struct SetLogReq {
+    pub name: String,
+    pub ctrl: LogCtrl,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(crate = "base::serde")]
 pub struct AddChildReq {
@@ -116,6 +155,27 @@ pub struct AddChildReq {
     pub name: String,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "base::serde")]
+pub struct StartAppReq {
+    pub cfg: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "base::serde")]
+pub struct StopAppReq {
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "base::serde")]
+pub struct SetQuotaReq {
+    pub name: String,
+    pub umem: Option<GlobOff>,
+    pub eps: Option<usize>,
+    pub time: Option<TimeDuration>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(crate = "base::serde")]
 pub struct ActInfo {
@@ -129,6 +189,58 @@ pub struct ActInfo {
     pub time: Quota<TimeDuration>,
     pub pts: Quota<usize>,
     pub tile: TileId,
+    pub cpu_time: TimeDuration,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "base::serde")]
+pub struct GetUsageReq {
+    pub mem: Selector,
+    pub size: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "base::serde")]
+pub struct GetUsageReply {
+    pub size: usize,
+}
+
+/// The quotas and the current resource usage of a child of a resource manager
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "base::serde")]
+pub struct ChildUsage {
+    /// The activity id
+    pub id: ActId,
+    /// The name of the child
+    pub name: String,
+    /// Whether the child is a daemon
+    pub daemon: bool,
+    /// Whether the child has been started by another resource manager
+    pub foreign: bool,
+    /// The tile the child is running on
+    pub tile: TileId,
+    /// The user-memory quota
+    pub umem: Quota<usize>,
+    /// The amount of user memory that has been promised to the child (see overcommit)
+    pub umem_committed: usize,
+    /// The amount of user memory that the child allocated via the resource manager
+    pub umem_allocated: usize,
+    /// The kernel-memory quota
+    pub kmem: Quota<usize>,
+    /// The endpoint quota
+    pub eps: Quota<usize>,
+    /// The time quota
+    pub time: Quota<TimeDuration>,
+    /// The page-table quota
+    pub pts: Quota<usize>,
+    /// The consumed CPU time
+    pub cpu_time: TimeDuration,
+    /// The number of tiles the child allocated
+    pub tiles: usize,
+    /// The number of services the child registered
+    pub services: usize,
+    /// The number of sessions the child opened
+    pub sessions: usize,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -188,12 +300,14 @@ impl ResMng {
         .map(|_| ResMngChild::new_clone(SendCap::new_bind(sgate), act.sel()))
     }
 
-    /// Registers a service with given name at selector `dst`, using `sgate` for session creations.
+    /// Registers a service with given name and protocol version at selector `dst`, using `sgate`
+    /// for session creations.
     pub fn reg_service(
         &self,
         dst: Selector,
         sgate: Selector,
         name: &str,
+        version: kif::service::Version,
         sessions: u32,
     ) -> Result<(), Error> {
         Self::send_receive(&self.sgate, opcodes::ResMng::RegServ, RegServiceReq {
@@ -201,6 +315,7 @@ impl ResMng {
             sgate,
             sessions,
             name: name.to_string(),
+            version,
         })
         .map(|_| ())
     }
@@ -211,6 +326,10 @@ impl ResMng {
     }
 
     /// Opens a session at service `name` using selector `dst`.
+    ///
+    /// The name can optionally request a protocol version in the form "name@major.minor" (see
+    /// [`Version`](kif::service::Version)). If the service does not support a compatible version,
+    /// the call fails with [`VersionMismatch`](Code::VersionMismatch).
     pub fn open_sess(&self, dst: Selector, name: &str) -> Result<(), Error> {
         Self::send_receive(&self.sgate, opcodes::ResMng::OpenSess, OpenSessionReq {
             dst,
@@ -242,18 +361,24 @@ impl ResMng {
     /// Allocates a new processing element of given type and assigns it to selector `dst`.
     ///
     /// If `init` is set, the tile is initialized with TileMux and all PMP EPs for the this tile are
-    /// inherited to the allocated tile.
+    /// inherited to the allocated tile. If `id` is given, only the tile with this id is considered.
+    /// If `near` is given, the matching tile with the smallest distance to the tile `near` is
+    /// chosen.
     pub fn alloc_tile(
         &self,
         dst: Selector,
         desc: kif::TileDesc,
         init: bool,
+        id: Option<TileId>,
+        near: Option<TileId>,
     ) -> Result<(TileId, kif::TileDesc), Error> {
         let mut reply =
             Self::send_receive(&self.sgate, opcodes::ResMng::AllocTile, AllocTileReq {
                 dst,
                 desc,
                 init,
+                id,
+                near,
             })?;
         let reply: AllocTileReply = reply.pop()?;
         Ok((reply.id, reply.desc))
@@ -301,6 +426,15 @@ impl ResMng {
         .map(|_| ())
     }
 
+    /// Attaches to the physical memory range with given name using selector `dst`.
+    pub fn use_physmem(&self, dst: Selector, name: &str) -> Result<(), Error> {
+        Self::send_receive(&self.sgate, opcodes::ResMng::UsePhysMem, UseReq {
+            dst,
+            name: name.to_string(),
+        })
+        .map(|_| ())
+    }
+
     /// Retrieves the receive gate to receive serial input
     pub fn get_serial(&self, dst: Selector) -> Result<RecvGate, Error> {
         Self::send_receive(&self.sgate, opcodes::ResMng::GetSerial, GetSerialReq {
@@ -312,6 +446,106 @@ impl ResMng {
         })
     }
 
+    /// Takes the tile with given id offline so that it can be reconfigured.
+    ///
+    /// The tile has to be free and the application needs the permission to control tiles.
+    pub fn set_tile_offline(&self, id: TileId) -> Result<(), Error> {
+        Self::send_receive(&self.sgate, opcodes::ResMng::CtrlTile, CtrlTileReq {
+            id,
+            online: false,
+            desc: kif::TileDesc::default(),
+        })
+        .map(|_| ())
+    }
+
+    /// Brings the offline tile with given id online again using the new description `desc`.
+    ///
+    /// The application needs the permission to control tiles.
+    pub fn set_tile_online(&self, id: TileId, desc: kif::TileDesc) -> Result<(), Error> {
+        Self::send_receive(&self.sgate, opcodes::ResMng::CtrlTile, CtrlTileReq {
+            id,
+            online: true,
+            desc,
+        })
+        .map(|_| ())
+    }
+
+    /// Subscribes to changes of the tile set.
+    ///
+    /// Whenever a tile went offline or online, the resource manager sends a [`TileEvent`] via the
+    /// send gate with selector `sgate`. The gate should have unlimited credits, because the
+    /// messages are not replied, but only acknowledged by the receiver.
+    pub fn watch_tiles(&self, sgate: Selector) -> Result<(), Error> {
+        Self::send_receive(&self.sgate, opcodes::ResMng::WatchTiles, WatchTilesReq {
+            sgate,
+        })
+        .map(|_| ())
+    }
+
+    /// Starts a new application as described by the given XML snippet.
+    ///
+    /// The snippet uses the same format as the boot configuration (e.g., `<app args="foo"
+    /// usermem="1M"/>`), but cannot contain domains. The application runs on the tile of the
+    /// caller and the quotas it specifies are split off from the caller's quotas. The
+    /// application needs the permission to control applications.
+    pub fn start_app(&self, cfg: &str) -> Result<(), Error> {
+        Self::send_receive(&self.sgate, opcodes::ResMng::StartApp, StartAppReq {
+            cfg: cfg.to_string(),
+        })
+        .map(|_| ())
+    }
+
+    /// Stops the application with given name.
+    ///
+    /// The application needs the permission to control applications.
+    pub fn stop_app(&self, name: &str) -> Result<(), Error> {
+        Self::send_receive(&self.sgate, opcodes::ResMng::StopApp, StopAppReq {
+            name: name.to_string(),
+        })
+        .map(|_| ())
+    }
+
+    /// Changes the quotas of the application with given name.
+    ///
+    /// The user-memory quota (`umem`) is changed immediately, but can only be changed for
+    /// applications that do not share their memory quota with others. The EP and time quotas take
+    /// effect the next time the application is started (e.g., after it has been restarted). The
+    /// application needs the permission to control applications.
+    pub fn set_quota(
+        &self,
+        name: &str,
+        umem: Option<GlobOff>,
+        eps: Option<usize>,
+        time: Option<TimeDuration>,
+    ) -> Result<(), Error> {
+        Self::send_receive(&self.sgate, opcodes::ResMng::SetQuota, SetQuotaReq {
+            name: name.to_string(),
+            umem,
+            eps,
+            time,
+        })
+        .map(|_| ())
+    }
+
+    /// Changes the log flags and format of all activities with given name at runtime.
+    ///
+    /// The activities are searched among the children of the resource manager and, if not found,
+    /// among the activities of the parent resource managers. This requires the permission `logctl`.
+    pub fn set_log(&self, name: &str, flags: LogFlags, format: LogFormat) -> Result<(), Error> {
+        Self::send_receive(&self.sgate, opcodes::ResMng::SetLog, SetLogReq {
+            name: name.to_string(),
+            ctrl: LogCtrl::new(flags, format),
+        })
+        .map(|_| ())
+    }
+
+    /// Sends a heartbeat with the given diagnostics to the watchdog of the resource manager.
+    ///
+    /// See [`watchdog`](crate::watchdog) for details.
+    pub fn heartbeat(&self, diag: &Diagnostics) -> Result<(), Error> {
+        Self::send_receive(&self.sgate, opcodes::ResMng::Heartbeat, *diag).map(|_| ())
+    }
+
     /// Gets the number of available activities for `get_activity_info` and the starting layer.
     pub fn get_activity_count(&self) -> Result<(usize, u32), Error> {
         match self.activity_info(None) {
@@ -330,6 +564,33 @@ impl ResMng {
         }
     }
 
+    /// Retrieves the quotas and the current resource usage of all childs of the resource manager.
+    ///
+    /// In contrast to [`ResMng::get_activity_info`], the information of all childs is obtained
+    /// with a single request and transferred via memory. Childs of sub resource managers are not
+    /// included. The application needs the permission to get information.
+    pub fn get_usage(&self) -> Result<Vec<ChildUsage>, Error> {
+        let mut size = 4096;
+        loop {
+            let mgate = MemGate::new(size as GlobOff, kif::Perm::RW)?;
+            let reply: GetUsageReply =
+                Self::send_receive(&self.sgate, opcodes::ResMng::GetUsage, GetUsageReq {
+                    mem: mgate.sel(),
+                    size,
+                })
+                .and_then(|mut is| is.pop())?;
+
+            // the resource manager did not write the summary if our memory was too small
+            if reply.size > size {
+                size = reply.size;
+                continue;
+            }
+
+            let words: Vec<u64> = mgate.read_into_vec(reply.size / 8, 0)?;
+            return M3Deserializer::new(&words).pop();
+        }
+    }
+
     fn activity_info(&self, act_idx: Option<usize>) -> Result<ActInfoResult, Error> {
         Self::send_receive(&self.sgate, opcodes::ResMng::GetInfo, GetInfoReq {
             idx: act_idx.unwrap_or(usize::MAX),
@@ -37,6 +37,10 @@ pub struct ClientSession {
 
 impl ClientSession {
     /// Creates a new `ClientSession` by opening a session at the server with given name.
+    ///
+    /// The name can be suffixed with "@version" (e.g., "m3fs@2.1") to request a specific protocol
+    /// version. In this case, the resource manager chooses a service with a compatible version or
+    /// fails with [`VersionMismatch`](crate::errors::Code::VersionMismatch) if there is none.
     pub fn new(name: &str) -> Result<Self, Error> {
         Self::new_with_sel(name, SelSpace::get().alloc_sel())
     }
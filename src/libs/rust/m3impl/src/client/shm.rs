@@ -0,0 +1,95 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use crate::client::ClientSession;
+use crate::com::{opcodes, MemGate, Perm, RecvGate, SendGate};
+use crate::errors::Error;
+use crate::mem::GlobOff;
+
+/// Represents a session at the shared memory server
+///
+/// The shared memory server manages named shared memory objects. An object is created by one
+/// activity via [`Shm::create`] and can afterwards be opened by name via [`Shm::open`] by any
+/// activity that has a session at the same server. Thereby, unrelated activities can share memory
+/// without exchanging capabilities themselves; which activities can access the server is defined
+/// by the configuration of the resource manager.
+///
+/// The memory of an object is accounted to the session that created it, which is limited by a
+/// quota (see [`Shm::quota`]). An object exists until it is removed via [`Shm::remove`] or the
+/// creating session is closed. In both cases, all [`MemGate`]s that refer to the object become
+/// invalid.
+pub struct Shm {
+    sess: ClientSession,
+    sgate: SendGate,
+}
+
+impl Shm {
+    /// Creates a new `Shm` session at service with given name.
+    pub fn new(name: &str) -> Result<Self, Error> {
+        let sess = ClientSession::new(name)?;
+        let sgate = sess.connect()?;
+        Ok(Shm { sess, sgate })
+    }
+
+    /// Creates a new shared memory object with given name and `size` bytes and returns a
+    /// [`MemGate`] with permissions `perm` for it.
+    ///
+    /// The size is rounded up to a multiple of the page size and accounted to this session.
+    /// Returns [`Exists`](crate::errors::Code::Exists) if an object with this name exists already
+    /// and [`NoSpace`](crate::errors::Code::NoSpace) if the quota of this session does not
+    /// suffice.
+    pub fn create(&self, name: &str, size: GlobOff, perm: Perm) -> Result<MemGate, Error> {
+        let crd = self.sess.obtain(
+            1,
+            |os| {
+                os.push(opcodes::Shm::Create);
+                os.push(name);
+                os.push(size);
+                os.push(perm.bits());
+            },
+            |_| Ok(()),
+        )?;
+        MemGate::new_owned_bind(crd.start())
+    }
+
+    /// Opens the existing shared memory object with given name and returns a [`MemGate`] with
+    /// permissions `perm` for it.
+    pub fn open(&self, name: &str, perm: Perm) -> Result<MemGate, Error> {
+        let crd = self.sess.obtain(
+            1,
+            |os| {
+                os.push(opcodes::Shm::Open);
+                os.push(name);
+                os.push(perm.bits());
+            },
+            |_| Ok(()),
+        )?;
+        MemGate::new_owned_bind(crd.start())
+    }
+
+    /// Removes the shared memory object with given name.
+    ///
+    /// Only the session that created the object can remove it. Afterwards, all [`MemGate`]s for
+    /// the object are invalid and its memory is returned to the quota of this session.
+    pub fn remove(&self, name: &str) -> Result<(), Error> {
+        send_recv_res!(&self.sgate, RecvGate::def(), opcodes::Shm::Remove, name).map(|_| ())
+    }
+
+    /// Returns the total quota and the currently used amount of memory of this session in bytes
+    pub fn quota(&self) -> Result<(GlobOff, GlobOff), Error> {
+        let mut reply = send_recv_res!(&self.sgate, RecvGate::def(), opcodes::Shm::Quota)?;
+        Ok((reply.pop()?, reply.pop()?))
+    }
+}
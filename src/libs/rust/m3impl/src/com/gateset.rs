@@ -0,0 +1,119 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use crate::cell::Cell;
+use crate::col::Vec;
+use crate::com::RecvGate;
+use crate::errors::{Code, Error};
+use crate::tcu::{EpId, TCU};
+use crate::tiles::OwnActivity;
+use crate::time::{TimeDuration, TimeInstant};
+
+/// A set of [`RecvGate`]s that can be waited on at once
+///
+/// Similar to `epoll`, the `GateSet` allows to block until any of the contained receive gates has
+/// a message and reports which gate became ready. The gates are identified by the index that is
+/// returned by [`add`](GateSet::add). To prevent that a busy gate starves the others, the gates are
+/// checked in a round-robin fashion.
+///
+/// While waiting, the own activity is suspended by TileMux (or the TCU) until a message arrives.
+/// If the set contains a single gate, the activity is only woken up for messages on this gate.
+#[derive(Default)]
+pub struct GateSet {
+    eps: Vec<EpId>,
+    next: Cell<usize>,
+}
+
+impl GateSet {
+    /// Creates a new and empty `GateSet`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the given receive gate to the set and returns its index within the set
+    pub fn add(&mut self, rgate: &RecvGate) -> usize {
+        self.eps.push(rgate.ep());
+        self.eps.len() - 1
+    }
+
+    /// Returns the number of gates in the set
+    pub fn len(&self) -> usize {
+        self.eps.len()
+    }
+
+    /// Returns true if the set is empty
+    pub fn is_empty(&self) -> bool {
+        self.eps.is_empty()
+    }
+
+    /// Returns the index of the next gate that has a message or `None` if there is none.
+    pub fn ready(&self) -> Option<usize> {
+        let count = self.eps.len();
+        let start = self.next.get();
+        for i in 0..count {
+            let idx = (start + i) % count;
+            if TCU::has_msgs(self.eps[idx]) {
+                self.next.set((idx + 1) % count);
+                return Some(idx);
+            }
+        }
+        None
+    }
+
+    /// Waits until any of the gates has a message and returns its index.
+    ///
+    /// The message is not fetched, so that the caller can fetch it from the corresponding gate.
+    pub fn wait(&self) -> Result<usize, Error> {
+        self.wait_until(None)
+    }
+
+    /// Waits until any of the gates has a message or `timeout` time has passed.
+    ///
+    /// Returns the index of the ready gate or an error with [`Code::Timeout`] if the timeout passed
+    /// before any message arrived.
+    pub fn wait_for(&self, timeout: TimeDuration) -> Result<usize, Error> {
+        self.wait_until(Some(TimeInstant::now() + timeout))
+    }
+
+    fn wait_until(&self, end: Option<TimeInstant>) -> Result<usize, Error> {
+        if self.eps.is_empty() {
+            return Err(Error::new(Code::InvArgs));
+        }
+
+        loop {
+            if let Some(idx) = self.ready() {
+                return Ok(idx);
+            }
+
+            let timeout = match end {
+                Some(end) => {
+                    let now = TimeInstant::now();
+                    if now >= end {
+                        return Err(Error::new(Code::Timeout));
+                    }
+                    Some(end.duration_since(now))
+                },
+                None => None,
+            };
+
+            if self.eps.len() == 1 {
+                OwnActivity::wait_for(Some(self.eps[0]), None, timeout)?;
+            }
+            else {
+                OwnActivity::sleep_for(timeout.unwrap_or(TimeDuration::MAX))?;
+            }
+        }
+    }
+}
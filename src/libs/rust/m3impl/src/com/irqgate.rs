@@ -0,0 +1,107 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use core::fmt;
+
+use crate::com::{GateCap, RecvGate, SendCap, SendGate};
+use crate::errors::Error;
+use crate::tcu;
+use crate::tmif::{self, IRQId};
+use crate::util::math;
+
+const MSG_SIZE: usize = 64;
+
+/// A gate that receives the occurrences of an external interrupt as messages.
+///
+/// The interrupt is claimed from TileMux on creation and thereby exclusively owned by this
+/// activity. TileMux sends a message to the contained [`RecvGate`] for each occurrence and masks
+/// the interrupt afterwards. Occurrences while the interrupt is masked are counted and reported
+/// with the next message. Hence, after handling the interrupt, [`IRQGate::unmask`] needs to be
+/// called to receive further interrupts.
+///
+/// Since the [`RecvGate`] can be waited for like any other gate, drivers can wait for interrupts
+/// and messages from clients at the same time.
+pub struct IRQGate {
+    irq: IRQId,
+    rgate: RecvGate,
+    _sgate: SendGate,
+}
+
+impl IRQGate {
+    /// Claims the external interrupt `irq` and creates a gate to receive its occurrences.
+    ///
+    /// Fails with [`Exists`](crate::errors::Code::Exists) if the interrupt has already been
+    /// claimed.
+    pub fn new(irq: IRQId) -> Result<Self, Error> {
+        // TileMux sends at most one message until the interrupt is unmasked again
+        let rgate = RecvGate::new(math::next_log2(MSG_SIZE), math::next_log2(MSG_SIZE))?;
+        let sgate = SendCap::new(&rgate)?.activate()?;
+        tmif::claim_irq(irq, sgate.ep().id())?;
+        Ok(Self {
+            irq,
+            rgate,
+            _sgate: sgate,
+        })
+    }
+
+    /// Returns the interrupt number
+    pub fn irq(&self) -> IRQId {
+        self.irq
+    }
+
+    /// Returns the [`RecvGate`] that receives the interrupt messages
+    pub fn rgate(&self) -> &RecvGate {
+        &self.rgate
+    }
+
+    /// Masks the interrupt so that no further messages are sent until it is unmasked
+    pub fn mask(&self) -> Result<(), Error> {
+        tmif::mask_irq(self.irq, true)
+    }
+
+    /// Unmasks the interrupt so that the next occurrence is sent as a message
+    ///
+    /// If the interrupt occurred while it was masked, a message is sent immediately.
+    pub fn unmask(&self) -> Result<(), Error> {
+        tmif::mask_irq(self.irq, false)
+    }
+
+    /// Fetches a pending interrupt message without blocking
+    ///
+    /// Returns the number of occurrences or `None` if no message is pending.
+    pub fn fetch(&self) -> Option<u64> {
+        self.rgate.fetch().ok().map(|msg| self.consume(msg))
+    }
+
+    /// Waits until the interrupt occurs and returns the number of occurrences
+    ///
+    /// The interrupt is masked afterwards; call [`IRQGate::unmask`] after handling it.
+    pub fn wait(&self) -> Result<u64, Error> {
+        let msg = self.rgate.receive(None)?;
+        Ok(self.consume(msg))
+    }
+
+    fn consume(&self, msg: &'static tcu::Message) -> u64 {
+        let count = msg.as_words()[1];
+        self.rgate.ack_msg(msg).ok();
+        count
+    }
+}
+
+impl fmt::Debug for IRQGate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "IRQGate[irq={}, rgate={}]", self.irq, self.rgate.sel())
+    }
+}
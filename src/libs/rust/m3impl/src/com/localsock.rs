@@ -0,0 +1,363 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use core::any::Any;
+use core::cmp;
+use core::fmt;
+use core::slice;
+
+use crate::boxed::Box;
+use crate::cap::{CapFlags, SelSpace, Selector};
+use crate::client::{HashInput, HashOutput};
+use crate::com::{RGateArgs, RecvCap, RecvGate, SGateArgs, SendCap, SendGate};
+use crate::errors::{Code, Error};
+use crate::io;
+use crate::kif::{CapRngDesc, CapType};
+use crate::mem::{self, MsgBuf};
+use crate::tcu::{Header, Message};
+use crate::tiles::{Activity, OwnActivity};
+use crate::util::math;
+use crate::vfs::{self, Fd, File, FileEvent, FileRef, INV_FD};
+
+const MSG_SIZE: usize = 512;
+const MSG_CREDITS: u32 = 4;
+const MSG_BUF_SIZE: usize = MSG_SIZE * MSG_CREDITS as usize;
+
+const REPLY_SIZE: usize = 64;
+const REPLY_BUF_SIZE: usize = REPLY_SIZE * MSG_CREDITS as usize;
+
+// the size of the type and size fields before the data
+const DATA_HEADER_SIZE: usize = 2 * mem::size_of::<u64>();
+
+/// The maximum number of bytes that are transferred with a single message
+pub const MAX_LOCAL_DATA: usize = MSG_SIZE - (mem::size_of::<Header>() + DATA_HEADER_SIZE);
+
+// the message types
+const MSG_DATA: u64 = 0;
+const MSG_CLOSE: u64 = 1;
+
+/// A stream-oriented socket for the communication with another activity
+///
+/// Local sockets come in connected pairs and offer a bidirectional byte stream between the two
+/// sockets, similar to Unix-domain stream sockets. In contrast to [`TcpSocket`]s, the data is
+/// directly exchanged via TCU messages instead of going through the network server. As local
+/// sockets implement [`File`], code written against the file or socket API can use them as well.
+///
+/// Each direction is backed by a [`SendGate`] and a [`RecvGate`] with a fixed number of message
+/// slots. Flow control is based on credits: the receiver returns the credit for a message as soon
+/// as all data of the message has been read. One credit is always reserved to be able to notify
+/// the other side about a close.
+///
+/// A pair is created via [`LocalSocket::new`], which returns the first socket and the capabilities
+/// for the second socket. The capabilities can be delegated to another activity, which turns them
+/// into the second socket via [`LocalSocket::new_bind`]. The capabilities are revoked as soon as
+/// the first socket is dropped.
+///
+/// [`TcpSocket`]: crate::net::TcpSocket
+pub struct LocalSocket {
+    fd: Fd,
+    rgate: RecvGate,
+    rpl_gate: RecvGate,
+    sgate: SendGate,
+    // the capabilities of the other side, if we created them
+    peer_caps: Option<Selector>,
+    // the message we are currently reading from and the position within its data
+    cur: Option<&'static Message>,
+    pos: usize,
+    blocking: bool,
+    // the other side has closed its sending direction
+    eof: bool,
+    // we have closed our sending direction
+    closed: bool,
+}
+
+impl LocalSocket {
+    /// Creates a new local socket and the capabilities for the other side of the pair
+    ///
+    /// Returns the socket and the first of two selectors that need to be delegated to the other
+    /// activity, which can then use [`LocalSocket::new_bind`] to obtain its socket.
+    ///
+    /// By default, the socket is in blocking mode, that is, reading and writing do not return
+    /// until the operation is complete. This can be changed via
+    /// [`set_blocking`](File::set_blocking).
+    pub fn new() -> Result<(FileRef<Self>, Selector), Error> {
+        let caps = SelSpace::get().alloc_sels(2);
+
+        let rgate = RecvGate::new(math::next_log2(MSG_BUF_SIZE), math::next_log2(MSG_SIZE))?;
+
+        // the receive gate of the other side and a send gate to our receive gate
+        let peer_rgate = RecvCap::new_with(
+            RGateArgs::default()
+                .sel(caps)
+                .msg_order(math::next_log2(MSG_SIZE))
+                .order(math::next_log2(MSG_BUF_SIZE))
+                .flags(CapFlags::KEEP_CAP),
+        )?;
+        SendCap::new_with(
+            SGateArgs::new(&rgate)
+                .sel(caps + 1)
+                .credits(MSG_CREDITS)
+                .flags(CapFlags::KEEP_CAP),
+        )?;
+
+        let sgate = SendGate::new_with(SGateArgs::new(&peer_rgate).credits(MSG_CREDITS))?;
+        Self::create(rgate, sgate, Some(caps)).map(|sock| (sock, caps))
+    }
+
+    /// Creates the other side of a local socket pair from the capabilities starting at `caps`
+    ///
+    /// The capabilities have been created by [`LocalSocket::new`].
+    pub fn new_bind(caps: Selector) -> Result<FileRef<Self>, Error> {
+        let rgate = RecvGate::new_bind(caps)?;
+        let sgate = SendGate::new_bind(caps + 1)?;
+        Self::create(rgate, sgate, None)
+    }
+
+    /// Creates a connected pair of local sockets within the own activity
+    pub fn pair() -> Result<(FileRef<Self>, FileRef<Self>), Error> {
+        let (first, caps) = Self::new()?;
+        let second = Self::new_bind(caps)?;
+        Ok((first, second))
+    }
+
+    fn create(
+        rgate: RecvGate,
+        sgate: SendGate,
+        peer_caps: Option<Selector>,
+    ) -> Result<FileRef<Self>, Error> {
+        let rpl_gate = RecvGate::new(math::next_log2(REPLY_BUF_SIZE), math::next_log2(REPLY_SIZE))?;
+
+        let sock = Box::new(LocalSocket {
+            fd: INV_FD,
+            rgate,
+            rpl_gate,
+            sgate,
+            peer_caps,
+            cur: None,
+            pos: 0,
+            blocking: true,
+            eof: false,
+            closed: false,
+        });
+        let fd = Activity::own().files().add(sock)?;
+        Ok(FileRef::new_owned(fd))
+    }
+
+    /// Returns true if data can be read without blocking
+    pub fn has_data(&self) -> bool {
+        self.cur.is_some() || self.rgate.has_msgs()
+    }
+
+    /// Closes the sending direction of this socket
+    ///
+    /// The other side receives all data that has been written before and afterwards an end of
+    /// file. Further writes to this socket fail with [`SocketClosed`](Code::SocketClosed). Note
+    /// that the close is always performed in blocking mode and is also done on drop.
+    pub fn close(&mut self) -> Result<(), Error> {
+        if self.closed {
+            return Ok(());
+        }
+
+        // the last credit is reserved for the close message
+        self.wait_for_credits(1, true)?;
+        self.send_msg(MSG_CLOSE, &[])?;
+        self.closed = true;
+        Ok(())
+    }
+
+    fn wait_for_credits(&self, credits: u32, blocking: bool) -> Result<(), Error> {
+        loop {
+            self.fetch_replies();
+            if self.sgate.credits()? >= credits {
+                return Ok(());
+            }
+            if !blocking {
+                return Err(Error::new(Code::WouldBlock));
+            }
+            OwnActivity::wait_for(Some(self.rpl_gate.ep()), None, None)?;
+        }
+    }
+
+    fn fetch_replies(&self) {
+        while let Ok(reply) = self.rpl_gate.fetch() {
+            self.rpl_gate.ack_msg(reply).unwrap();
+        }
+    }
+
+    fn send_msg(&self, ty: u64, data: &[u8]) -> Result<(), Error> {
+        debug_assert!(data.len() <= MAX_LOCAL_DATA);
+
+        let mut msg = MsgBuf::borrow_def();
+        // safety: we initialize the header and data below and set the size accordingly
+        unsafe {
+            let words = msg.words_mut();
+            words[0] = ty;
+            words[1] = data.len() as u64;
+            let bytes =
+                slice::from_raw_parts_mut(words[2..].as_mut_ptr() as *mut u8, MAX_LOCAL_DATA);
+            bytes[0..data.len()].copy_from_slice(data);
+            msg.set_size(DATA_HEADER_SIZE + data.len());
+        }
+        self.sgate.send(&msg, &self.rpl_gate)
+    }
+
+    fn fetch_msg(&mut self) -> Result<(), Error> {
+        loop {
+            match self.rgate.fetch() {
+                Ok(msg) if msg.as_words()[0] == MSG_CLOSE => {
+                    self.eof = true;
+                    self.reply_msg(msg);
+                    return Ok(());
+                },
+                Ok(msg) => {
+                    self.cur = Some(msg);
+                    self.pos = 0;
+                    return Ok(());
+                },
+                Err(e) if e.code() == Code::NotFound => {
+                    if !self.blocking {
+                        return Err(Error::new(Code::WouldBlock));
+                    }
+                    OwnActivity::wait_for(Some(self.rgate.ep()), None, None)?;
+                },
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn reply_msg(&self, msg: &'static Message) {
+        // return the credit to the sender; ignore failures here
+        let reply = MsgBuf::borrow_def();
+        self.rgate.reply(&reply, msg).ok();
+    }
+}
+
+impl File for LocalSocket {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn fd(&self) -> Fd {
+        self.fd
+    }
+
+    fn set_fd(&mut self, fd: Fd) {
+        self.fd = fd;
+    }
+
+    fn file_type(&self) -> u8 {
+        // not supported
+        b'\0'
+    }
+
+    fn is_blocking(&self) -> bool {
+        self.blocking
+    }
+
+    fn set_blocking(&mut self, blocking: bool) -> Result<(), Error> {
+        self.blocking = blocking;
+        Ok(())
+    }
+
+    fn check_events(&mut self, events: FileEvent) -> bool {
+        (events.contains(FileEvent::INPUT) && (self.eof || self.has_data()))
+            || (events.contains(FileEvent::OUTPUT)
+                && (self.closed || self.wait_for_credits(2, false).is_ok()))
+    }
+}
+
+impl io::Read for LocalSocket {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        while self.cur.is_none() {
+            if self.eof {
+                return Ok(0);
+            }
+            self.fetch_msg()?;
+        }
+
+        let msg = self.cur.unwrap();
+        let size = msg.as_words()[1] as usize;
+        let data = &msg.data[DATA_HEADER_SIZE..DATA_HEADER_SIZE + size];
+        let amount = cmp::min(buf.len(), size - self.pos);
+        buf[0..amount].copy_from_slice(&data[self.pos..self.pos + amount]);
+        self.pos += amount;
+
+        if self.pos == size {
+            self.cur = None;
+            self.reply_msg(msg);
+        }
+        Ok(amount)
+    }
+}
+
+impl io::Write for LocalSocket {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        if self.closed {
+            return Err(Error::new(Code::SocketClosed));
+        }
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        // keep the last credit for the close message
+        self.wait_for_credits(2, self.blocking)?;
+        let amount = cmp::min(buf.len(), MAX_LOCAL_DATA);
+        self.send_msg(MSG_DATA, &buf[0..amount])?;
+        Ok(amount)
+    }
+}
+
+impl vfs::Seek for LocalSocket {
+}
+
+impl vfs::Map for LocalSocket {
+}
+
+impl HashInput for LocalSocket {
+}
+
+impl HashOutput for LocalSocket {
+}
+
+impl fmt::Debug for LocalSocket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "LocalSocket[rgate={}, sgate={}]",
+            self.rgate.sel(),
+            self.sgate.sel()
+        )
+    }
+}
+
+impl Drop for LocalSocket {
+    fn drop(&mut self) {
+        // ignore failures here, e.g., if the other side is already gone
+        self.close().ok();
+        if let Some(msg) = self.cur.take() {
+            self.reply_msg(msg);
+        }
+
+        if let Some(caps) = self.peer_caps {
+            Activity::own()
+                .revoke(CapRngDesc::new(CapType::Object, caps, 2), false)
+                .ok();
+        }
+    }
+}
@@ -21,6 +21,7 @@ use core::fmt;
 use base::mem::GlobAddr;
 
 use crate::cap::{CapFlags, Capability, SelSpace, Selector};
+use crate::cfg;
 use crate::col::Vec;
 use crate::com::ep::EP;
 use crate::com::gate::Gate;
@@ -31,9 +32,14 @@ use crate::mem::{GlobOff, VirtAddr};
 use crate::syscalls;
 use crate::tcu;
 use crate::tiles::Activity;
+use crate::util::math;
+use crate::vec;
 
 pub use crate::kif::Perm;
 
+/// The size of the buffer in local memory that is used by [`MemGate::copy_to`]
+const COPY_BUF_SIZE: usize = 4096;
+
 /// A memory capability is the precursor of a `MemGate`
 ///
 /// `MemCap` implements `GateCap` and can therefore be turned into a `MemGate` through activation.
@@ -62,10 +68,20 @@ impl MemCap {
             args.sel
         };
 
+        // the resource manager aligns allocations of at least one large page to the large page
+        // size. thus, rounding up the size suffices to get memory that can be mapped with large
+        // pages.
+        let size = if args.large_pages {
+            math::round_up(args.size, cfg::LPAGE_SIZE as GlobOff)
+        }
+        else {
+            args.size
+        };
+
         Activity::own()
             .resmng()
             .unwrap()
-            .alloc_mem(sel, args.size as GlobOff, args.perm)?;
+            .alloc_mem(sel, size, args.perm)?;
         Ok(Self {
             cap: Capability::new(sel, CapFlags::empty()),
             resmng: true,
@@ -114,6 +130,18 @@ impl MemCap {
         })
     }
 
+    /// Binds a new `MemCap` to the physical memory range with given name.
+    ///
+    /// The range needs to be granted to this activity via a `<physmem>` element in its config.
+    pub fn new_bind_physmem(name: &str) -> Result<Self, Error> {
+        let sel = SelSpace::get().alloc_sel();
+        Activity::own().resmng().unwrap().use_physmem(sel, name)?;
+        Ok(Self {
+            cap: Capability::new(sel, CapFlags::empty()),
+            resmng: false,
+        })
+    }
+
     /// Returns the selector of this `MemCap`
     pub fn sel(&self) -> Selector {
         self.cap.sel()
@@ -245,6 +273,7 @@ pub struct MGateArgs {
     size: GlobOff,
     perm: Perm,
     sel: Selector,
+    large_pages: bool,
 }
 
 impl MGateArgs {
@@ -254,9 +283,21 @@ impl MGateArgs {
             size,
             perm,
             sel: INVALID_SEL,
+            large_pages: false,
         }
     }
 
+    /// Requests memory that is backed by large pages if `large` is true.
+    ///
+    /// In this case, the size is rounded up to a multiple of [`cfg::LPAGE_SIZE`] and the memory is
+    /// aligned accordingly. If the memory is mapped at a virtual address with the same alignment
+    /// (e.g., via [`Pager::map_mem`](crate::client::Pager::map_mem)), large pages are used for the
+    /// mapping, which reduces the pressure on the TLBs of the core and the TCU.
+    pub fn large_pages(mut self, large: bool) -> Self {
+        self.large_pages = large;
+        self
+    }
+
     /// Sets the capability selector that should be used for this [`MemGate`]. Otherwise and by
     /// default, [`SelSpace::get().alloc_sel`](crate::cap::SelSpace::alloc_sel) will be used to
     /// choose a free selector.
@@ -381,6 +422,15 @@ impl MemGate {
         tcu::TCU::read(self.gate.ep().id(), data, size, off)
     }
 
+    /// Reads from the memory region at offset `off` into the given segments via TCU read commands.
+    ///
+    /// The segments are filled back-to-back: the first segment receives the bytes starting at
+    /// `off`, the next one the bytes directly behind these, and so on. This allows to read a
+    /// contiguous part of the memory region into several buffers without intermediate copies.
+    pub fn read_v(&self, bufs: &mut [&mut [u8]], off: GlobOff) -> Result<(), Error> {
+        tcu::TCU::read_v(self.gate.ep().id(), bufs, off)
+    }
+
     /// Writes `data` with the TCU write command to the memory region at offset `off`.
     pub fn write<T>(&self, data: &[T], off: GlobOff) -> Result<(), Error> {
         tcu::TCU::write_slice(self.gate.ep().id(), data, off)
@@ -397,6 +447,38 @@ impl MemGate {
         tcu::TCU::write(self.gate.ep().id(), data, size, off)
     }
 
+    /// Writes the given segments back-to-back via TCU write commands to the memory region at offset
+    /// `off`.
+    ///
+    /// This is the counterpart of [`MemGate::read_v`].
+    pub fn write_v(&self, bufs: &[&[u8]], off: GlobOff) -> Result<(), Error> {
+        tcu::TCU::write_v(self.gate.ep().id(), bufs, off)
+    }
+
+    /// Copies `len` bytes from offset `src_off` in this memory region to offset `dst_off` in the
+    /// memory region of `dst`.
+    ///
+    /// The data is transferred in chunks through a buffer in local memory, using one TCU read and
+    /// one TCU write command per chunk. Both gates may refer to the same memory region, but the
+    /// source and destination ranges must not overlap.
+    pub fn copy_to(
+        &self,
+        dst: &MemGate,
+        len: usize,
+        src_off: GlobOff,
+        dst_off: GlobOff,
+    ) -> Result<(), Error> {
+        let mut buf = vec![0u8; len.min(COPY_BUF_SIZE)];
+        let mut pos = 0;
+        while pos < len {
+            let amount = (len - pos).min(buf.len());
+            self.read(&mut buf[0..amount], src_off + pos as GlobOff)?;
+            dst.write(&buf[0..amount], dst_off + pos as GlobOff)?;
+            pos += amount;
+        }
+        Ok(())
+    }
+
     // /// Deactivates this `MemGate` and thereby turns it back into a `MemCap`
     pub fn deactivate(mut self) -> MemCap {
         let (resmng, flags) = (self.resmng, self.gate.flags());
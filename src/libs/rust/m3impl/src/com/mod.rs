@@ -32,6 +32,8 @@
 //! types: [`SendGate`], [`RecvGate`], and [`MemGate`]. All gates therefore use a specific endpoint
 //! for the communication and need to be *activated* before they can be used. The activation of a
 //! gate allocates an endpoint (if required) and configures the endpoint for the gate.
+//! An [`IRQGate`] uses a [`RecvGate`] to receive the occurrences of an external interrupt as
+//! messages, which allows to write drivers entirely in user space.
 //!
 //! # Streams and channels
 //!
@@ -39,7 +41,8 @@
 //! types into a message, whereas [`GateIStream`] allows to unmarshall a message into data types.
 //! Both work in combination with [`SendGate`]s and [`RecvGate`]s, respectively. A
 //! [`channel`](`chan::sync_channel`) provides a synchronous uni-directional communication channel
-//! based on gates.
+//! based on gates. Finally, a [`LocalSocket`] provides a bidirectional byte stream between two
+//! activities that implements the [`File`](`crate::vfs::File`) interface.
 
 #[macro_use]
 mod stream;
@@ -47,6 +50,9 @@ mod stream;
 mod ep;
 mod epmng;
 mod gate;
+mod gateset;
+mod irqgate;
+mod localsock;
 mod mgate;
 pub mod opcodes;
 mod rbufs;
@@ -57,6 +63,9 @@ mod sgate;
 pub use self::ep::{EPArgs, EP};
 pub use self::epmng::EpMng;
 pub use self::gate::{Gate, GateCap, LazyGate};
+pub use self::gateset::GateSet;
+pub use self::irqgate::IRQGate;
+pub use self::localsock::{LocalSocket, MAX_LOCAL_DATA};
 pub use self::mgate::{MGateArgs, MemCap, MemGate, Perm};
 pub use self::rbufs::RecvBuf;
 pub use self::rgate::{RGateArgs, ReceivingGate, RecvCap, RecvGate};
@@ -72,3 +81,8 @@ pub(crate) fn pre_init() {
 pub(crate) fn init() {
     rbufs::init();
 }
+
+pub(crate) fn forget_inherited() {
+    rgate::forget_inherited();
+    rbufs::forget_inherited();
+}
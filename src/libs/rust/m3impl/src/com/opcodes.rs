@@ -40,6 +40,10 @@ pub enum File {
     GetPath,
     GetTMode,
     SetTMode,
+    GetTermios,
+    SetTermios,
+    GetWinSize,
+    SetWinSize,
     SetDest,
     EnableNotify,
     ReqNotify,
@@ -75,6 +79,8 @@ pub enum FileSystem {
     OpenPriv,
     ClosePriv,
     CloneMeta,
+    SetNextDest,
+    Utime,
 }
 
 /// The operations for the pipe protocol.
@@ -95,6 +101,29 @@ pub enum Pipe {
     SetMem,
 }
 
+/// The operations for the pseudo-terminal protocol.
+#[derive(Copy, Clone, Debug, IntoPrimitive, TryFromPrimitive, Serialize_repr, Deserialize_repr)]
+#[repr(usize)]
+pub enum Pty {
+    FStat        = File::FStat as usize,
+    Seek         = File::Seek as usize,
+    NextIn       = File::NextIn as usize,
+    NextOut      = File::NextOut as usize,
+    Commit       = File::Commit as usize,
+    CloneFile    = File::CloneFile as usize,
+    GetTMode     = File::GetTMode as usize,
+    SetTMode     = File::SetTMode as usize,
+    GetTermios   = File::GetTermios as usize,
+    SetTermios   = File::SetTermios as usize,
+    GetWinSize   = File::GetWinSize as usize,
+    SetWinSize   = File::SetWinSize as usize,
+    SetDest      = File::SetDest as usize,
+    EnableNotify = File::EnableNotify as usize,
+    ReqNotify    = File::ReqNotify as usize,
+    OpenPair,
+    OpenSlave,
+}
+
 /// The operations for the network protocol.
 #[derive(Copy, Clone, Debug, IntoPrimitive, TryFromPrimitive, Serialize_repr, Deserialize_repr)]
 #[repr(usize)]
@@ -106,6 +135,11 @@ pub enum Net {
     Create,
     GetIP,
     GetNameSrv,
+    Accept,
+    SetOpt,
+    GetOpt,
+    JoinMulticast,
+    LeaveMulticast,
 }
 
 /// The operations for the resmng protocol.
@@ -128,6 +162,15 @@ pub enum ResMng {
     UseMod,
     GetSerial,
     GetInfo,
+    CtrlTile,
+    WatchTiles,
+    StartApp,
+    StopApp,
+    SetQuota,
+    GetUsage,
+    SetLog,
+    Heartbeat,
+    UsePhysMem,
 }
 
 /// The operations for the pager protocol.
@@ -159,6 +202,10 @@ pub enum Disk {
     Read,
     Write,
     AddMem,
+    Flush,
+    Stats,
+    PartCount,
+    PartInfo,
 }
 
 /// The operations for the hash protocol.
@@ -170,3 +217,89 @@ pub enum Hash {
     Output,
     GetMem,
 }
+
+/// The operations for the crypto protocol.
+#[derive(Copy, Clone, Debug, IntoPrimitive, TryFromPrimitive, Serialize_repr, Deserialize_repr)]
+#[repr(usize)]
+pub enum Crypto {
+    /// Obtains the EP that is used to access the client's memory
+    GetMem,
+    /// Imports a key from the client's memory into the session
+    ImportKey,
+    /// Deletes a previously imported key
+    DeleteKey,
+    /// Hashes data with SHA-3, SHAKE, or cSHAKE
+    Hash,
+    /// Computes the KMAC of data with an imported key
+    Kmac,
+    /// Encrypts and authenticates data with an imported key
+    Seal,
+    /// Verifies and decrypts data with an imported key
+    Open,
+}
+
+/// The operations for the key-value store protocol.
+#[derive(Copy, Clone, Debug, IntoPrimitive, TryFromPrimitive, Serialize_repr, Deserialize_repr)]
+#[repr(usize)]
+pub enum KeyValue {
+    /// Obtains the EP that is used to transfer values and keys
+    GetMem,
+    /// Reads the value of a key
+    Get,
+    /// Sets the value of a key
+    Put,
+    /// Removes a key
+    Delete,
+    /// Lists the keys with a given prefix
+    Scan,
+}
+
+/// The operations for the shared memory protocol.
+#[derive(Copy, Clone, Debug, IntoPrimitive, TryFromPrimitive, Serialize_repr, Deserialize_repr)]
+#[repr(usize)]
+pub enum Shm {
+    /// Creates a new named shared memory object
+    Create,
+    /// Opens an existing shared memory object
+    Open,
+    /// Removes a shared memory object
+    Remove,
+    /// Retrieves the quota of the session
+    Quota,
+}
+
+/// The operations for the clock protocol.
+#[derive(Copy, Clone, Debug, IntoPrimitive, TryFromPrimitive, Serialize_repr, Deserialize_repr)]
+#[repr(usize)]
+pub enum Clock {
+    /// Retrieves the boot time as a Unix timestamp in nanoseconds
+    BootTime,
+}
+
+/// The operations for the framebuffer protocol.
+#[derive(Copy, Clone, Debug, IntoPrimitive, TryFromPrimitive, Serialize_repr, Deserialize_repr)]
+#[repr(usize)]
+pub enum Fb {
+    /// Retrieves the resolution and layout of the framebuffer
+    Info,
+    /// Obtains a memory capability for the pixel buffer
+    GetMem,
+    /// Copies a rectangle of the pixel buffer to the display
+    Flush,
+}
+
+/// The operations for the audio protocol.
+#[derive(Copy, Clone, Debug, IntoPrimitive, TryFromPrimitive, Serialize_repr, Deserialize_repr)]
+#[repr(usize)]
+pub enum Audio {
+    /// Negotiates the sample rate, format, and number of channels of the playback stream
+    Configure,
+    /// Obtains a memory capability for the playback ring buffer
+    GetMem,
+    /// Hands written bytes of the ring buffer over to the device
+    Commit,
+    /// Waits until a period of the ring buffer has been played
+    Wait,
+    /// Waits until all committed bytes have been played and stops the stream
+    Drain,
+}
@@ -21,7 +21,7 @@ use crate::cfg;
 use crate::com::MemGate;
 use crate::errors::Error;
 use crate::kif::Perm;
-use crate::mem::{GlobOff, MemMap, VirtAddr};
+use crate::mem::{self, GlobOff, MemMap, VirtAddr};
 use crate::tiles::Activity;
 use crate::util::math;
 
@@ -126,6 +126,10 @@ pub(crate) fn free_rbuf(rbuf: &RecvBuf) {
     BUFS.borrow_mut().free(rbuf.addr.as_local(), rbuf.size);
 }
 
+pub(crate) fn forget_inherited() {
+    mem::forget(BUFS.unset());
+}
+
 pub(crate) fn init() {
     let (addr, size) = Activity::own().tile_desc().rbuf_space();
     BUFS.set(MemMap::new(addr.as_local(), size));
@@ -28,7 +28,7 @@ use crate::com::{gate::Gate, GateCap, RecvBuf, SendGate, EP};
 use crate::env;
 use crate::errors::{Code, Error};
 use crate::kif::INVALID_SEL;
-use crate::mem::{GlobOff, MsgBuf, VirtAddr};
+use crate::mem::{self, GlobOff, MsgBuf, VirtAddr};
 use crate::syscalls;
 use crate::tcu;
 use crate::tiles::{Activity, OwnActivity};
@@ -490,6 +490,15 @@ impl ReceivingGate for RecvGate {
     }
 }
 
+pub(crate) fn forget_inherited() {
+    // safety: we are called during initialization, before anybody can hold a reference
+    unsafe {
+        mem::forget(SYS_RGATE.unset());
+        mem::forget(UPC_RGATE.unset());
+        mem::forget(DEF_RGATE.unset());
+    }
+}
+
 pub(crate) fn pre_init() {
     let eps_start = env::get().first_std_ep();
     let mut rbuf = env::get().tile_desc().rbuf_std_space().0;
@@ -17,12 +17,13 @@
  */
 
 use crate::cap::{CapFlags, Capability, SelSpace, Selector};
-use crate::errors::Error;
+use crate::errors::{Code, Error};
 use crate::kif;
 use crate::syscalls;
 use crate::tiles::Activity;
+use crate::time::TimeDuration;
 
-/// A syscall-based semaphore.
+/// A syscall-based counting semaphore.
 #[derive(Debug)]
 pub struct Semaphore {
     cap: Capability,
@@ -63,11 +64,36 @@ impl Semaphore {
 
     /// Performs the `up` operation on the semaphore
     pub fn up(&self) -> Result<(), Error> {
-        syscalls::sem_ctrl(self.sel(), kif::syscalls::SemOp::Up)
+        syscalls::sem_ctrl(self.sel(), kif::syscalls::SemOp::Up).map(|_| ())
     }
 
     /// Performs the `down` operation on the semaphore
     pub fn down(&self) -> Result<(), Error> {
-        syscalls::sem_ctrl(self.sel(), kif::syscalls::SemOp::Down)
+        syscalls::sem_ctrl(self.sel(), kif::syscalls::SemOp::Down).map(|_| ())
+    }
+
+    /// Performs the `down` operation on the semaphore, but waits at most `timeout`.
+    ///
+    /// Returns [`Timeout`](Code::Timeout) if the value did not become non-zero in time.
+    pub fn down_for(&self, timeout: TimeDuration) -> Result<(), Error> {
+        syscalls::sem_ctrl_with(self.sel(), kif::syscalls::SemOp::DownFor, timeout).map(|_| ())
+    }
+
+    /// Performs the `down` operation on the semaphore if that is possible without waiting.
+    ///
+    /// Returns true if the value has been decremented.
+    pub fn try_down(&self) -> Result<bool, Error> {
+        match syscalls::sem_ctrl(self.sel(), kif::syscalls::SemOp::TryDown) {
+            Ok(_) => Ok(true),
+            Err(e) if e.code() == Code::WouldBlock => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Returns the current value of the semaphore
+    ///
+    /// Note that the value might already be outdated when this method returns.
+    pub fn value(&self) -> Result<u32, Error> {
+        syscalls::sem_ctrl(self.sel(), kif::syscalls::SemOp::Value)
     }
 }
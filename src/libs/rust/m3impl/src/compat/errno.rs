@@ -0,0 +1,104 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! Mapping of M³ error codes to POSIX error numbers
+
+use crate::errors::{Code, Error};
+
+/// A POSIX error number
+///
+/// The values correspond to the error numbers used by Linux, which are also used by our musl port.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Errno(pub i32);
+
+impl Errno {
+    pub const EACCES: Errno = Errno(13);
+    pub const EAFNOSUPPORT: Errno = Errno(97);
+    pub const EAGAIN: Errno = Errno(11);
+    pub const EALREADY: Errno = Errno(114);
+    pub const EBADF: Errno = Errno(9);
+    pub const ECONNREFUSED: Errno = Errno(111);
+    pub const ECONNRESET: Errno = Errno(104);
+    pub const EDEADLK: Errno = Errno(35);
+    pub const EDESTADDRREQ: Errno = Errno(89);
+    pub const EEXIST: Errno = Errno(17);
+    pub const EILSEQ: Errno = Errno(84);
+    pub const EINPROGRESS: Errno = Errno(115);
+    pub const EINVAL: Errno = Errno(22);
+    pub const EIO: Errno = Errno(5);
+    pub const EISCONN: Errno = Errno(106);
+    pub const EISDIR: Errno = Errno(21);
+    pub const EMSGSIZE: Errno = Errno(90);
+    pub const ENOENT: Errno = Errno(2);
+    pub const ENOMEM: Errno = Errno(12);
+    pub const ENOSPC: Errno = Errno(28);
+    pub const ENOTCONN: Errno = Errno(107);
+    pub const ENOTDIR: Errno = Errno(20);
+    pub const ENOTEMPTY: Errno = Errno(39);
+    pub const ENOTSOCK: Errno = Errno(88);
+    pub const EOPNOTSUPP: Errno = Errno(95);
+    pub const EPERM: Errno = Errno(1);
+    pub const EPIPE: Errno = Errno(32);
+    pub const EPROTONOSUPPORT: Errno = Errno(93);
+    pub const ESPIPE: Errno = Errno(29);
+    pub const ETIMEDOUT: Errno = Errno(110);
+    pub const EXDEV: Errno = Errno(18);
+}
+
+impl From<Code> for Errno {
+    fn from(code: Code) -> Self {
+        match code {
+            Code::NoPerm => Self::EACCES,
+            Code::InvArgs | Code::InvState => Self::EINVAL,
+            Code::OutOfMem | Code::NoKernMem => Self::ENOMEM,
+            Code::NoSuchFile | Code::NotFound => Self::ENOENT,
+            Code::NotSup => Self::EOPNOTSUPP,
+            Code::NoSpace => Self::ENOSPC,
+            Code::Exists => Self::EEXIST,
+            Code::XfsLink => Self::EXDEV,
+            Code::DirNotEmpty => Self::ENOTEMPTY,
+            Code::IsDir => Self::EISDIR,
+            Code::IsNoDir => Self::ENOTDIR,
+            Code::Timeout => Self::ETIMEDOUT,
+            Code::Utf8Error => Self::EILSEQ,
+            Code::BadFd => Self::EBADF,
+            Code::SeekPipe => Self::ESPIPE,
+            Code::Deadlock => Self::EDEADLK,
+            Code::OutOfBounds => Self::EMSGSIZE,
+            Code::WouldBlock | Code::NoCredits => Self::EAGAIN,
+            Code::InProgress => Self::EINPROGRESS,
+            Code::AlreadyInProgress => Self::EALREADY,
+            Code::NotConnected => Self::ENOTCONN,
+            Code::IsConnected => Self::EISCONN,
+            Code::SocketClosed => Self::EPIPE,
+            Code::ConnectionFailed => Self::ECONNREFUSED,
+            Code::ConnClosed => Self::ECONNRESET,
+            _ => Self::EIO,
+        }
+    }
+}
+
+impl From<Error> for Errno {
+    fn from(e: Error) -> Self {
+        Self::from(e.code())
+    }
+}
+
+/// Returns the POSIX error number for the given M³ error code
+#[no_mangle]
+#[allow(clippy::missing_safety_doc)]
+pub unsafe extern "C" fn __m3c_errno(code: Code) -> i32 {
+    Errno::from(code).0
+}
@@ -16,7 +16,11 @@
 //! POSIX compatibility layer
 //!
 //! This compatibility layer is used by our translation layer in musl to translate between Linux
-//! system calls and the M³ API.
+//! system calls and the M³ API. Additionally, [`socket`] provides the Berkeley-socket API on top of
+//! M³'s sockets for ported C and Rust software.
+
+pub mod errno;
+pub mod socket;
 
 use core::convert::From;
 use core::mem;
@@ -387,19 +391,21 @@ static NETM: LazyStaticRefCell<Rc<Network>> = LazyStaticRefCell::default();
 #[no_mangle]
 #[allow(clippy::missing_safety_doc)]
 pub unsafe extern "C" fn __m3c_init_netmng(name: *const i8) -> Code {
-    if !NETM.is_some() {
-        NETM.set(try_res!(Network::new(util::cstr_to_str(name))));
-    }
+    try_res!(init_netmng(util::cstr_to_str(name)));
     Code::Success
 }
 
-fn create_netmng() -> Result<(), Error> {
+fn init_netmng(name: &str) -> Result<(), Error> {
     if !NETM.is_some() {
-        NETM.set(Network::new("net")?);
+        NETM.set(Network::new(name)?);
     }
     Ok(())
 }
 
+fn create_netmng() -> Result<(), Error> {
+    init_netmng("net")
+}
+
 #[no_mangle]
 #[allow(clippy::missing_safety_doc)]
 pub unsafe extern "C" fn __m3c_socket(ty: CompatSock, fd: *mut i32) -> Code {
@@ -428,7 +434,7 @@ impl From<CompatEndpoint> for Endpoint {
 impl From<Endpoint> for CompatEndpoint {
     fn from(ep: Endpoint) -> Self {
         Self {
-            addr: ep.addr.0,
+            addr: ep.addr.as_v4().unwrap_or(0),
             port: ep.port,
         }
     }
@@ -437,7 +443,7 @@ impl From<Endpoint> for CompatEndpoint {
 unsafe fn m3_ep_to_compat(m3: Option<Endpoint>, compat: *mut CompatEndpoint) -> Code {
     if let Some(ep) = m3 {
         *compat = CompatEndpoint {
-            addr: ep.addr.0,
+            addr: ep.addr.as_v4().unwrap_or(0),
             port: ep.port,
         };
         Code::Success
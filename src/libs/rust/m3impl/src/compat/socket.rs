@@ -0,0 +1,615 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! Berkeley-socket compatibility layer
+//!
+//! This module implements the traditional socket functions (`socket`, `bind`, `listen`, `accept`,
+//! `connect`, `send`, `recv`, `poll`, ...) with their usual semantics on top of M³'s sockets and
+//! files to simplify the port of existing software. All functions operate on file descriptors of
+//! M³'s file table and report errors as POSIX error numbers (see [`Errno`]). Sockets are created at
+//! the network session set via [`init`] or, by default, at the session "net".
+//!
+//! For the C library of Rust programs, the same functions are exported as `__m3c_bsd_*`, which
+//! follow the convention of Linux system calls: on failure, they return the negated error number.
+//! The C++ library does not provide these functions.
+//!
+//! The following differences to conventional TCP/IP stacks remain:
+//! - The address given to [`bind`] is ignored, because M³'s sockets are always bound to all
+//!   interfaces. For stream sockets, [`bind`] only records the port for the following [`listen`].
+//! - Stream sockets need to be bound before [`listen`]; otherwise, [`Errno::EDESTADDRREQ`] is
+//!   returned.
+//! - Raw sockets can neither be bound nor connected and receive packets without source address.
+
+use core::mem;
+use core::ptr;
+
+use crate::cell::StaticRefCell;
+use crate::client::Network;
+use crate::col::BTreeMap;
+use crate::compat::errno::Errno;
+use crate::compat::{create_netmng, get_file, get_file_as, init_netmng, NETM};
+use crate::errors::Error;
+use crate::libc;
+use crate::net::{
+    DGramSocket, DgramSocketArgs, Endpoint, IpAddr, Port, RawSocket, RawSocketArgs, Socket, State,
+    StreamSocketArgs, TcpSocket, UdpSocket,
+};
+use crate::rc::Rc;
+use crate::tiles::Activity;
+use crate::time::{TimeDuration, TimeInstant};
+use crate::util;
+use crate::vfs::{Fd, File, FileEvent, FileWaiter};
+
+/// IPv4 address family
+pub const AF_INET: i32 = 2;
+/// IPv6 address family
+pub const AF_INET6: i32 = 10;
+
+/// Socket type for stream sockets (TCP)
+pub const SOCK_STREAM: i32 = 1;
+/// Socket type for datagram sockets (UDP)
+pub const SOCK_DGRAM: i32 = 2;
+/// Socket type for raw IP sockets
+pub const SOCK_RAW: i32 = 3;
+/// Flag for the socket type to create the socket in non-blocking mode
+pub const SOCK_NONBLOCK: i32 = 0o4000;
+/// Flag for the socket type to close the socket on exec (ignored)
+pub const SOCK_CLOEXEC: i32 = 0o2000000;
+
+/// Protocol number of TCP
+pub const IPPROTO_TCP: i32 = 6;
+/// Protocol number of UDP
+pub const IPPROTO_UDP: i32 = 17;
+
+/// Flag for send and receive to perform the operation in non-blocking mode
+pub const MSG_DONTWAIT: i32 = 0x40;
+/// Flag for send to not raise `SIGPIPE` (always the case, because M³ has no signals)
+pub const MSG_NOSIGNAL: i32 = 0x4000;
+
+/// Poll event: data can be received
+pub const POLLIN: i16 = 0x01;
+/// Poll event: data can be sent
+pub const POLLOUT: i16 = 0x04;
+/// Poll event: the remote side has closed the connection
+pub const POLLHUP: i16 = 0x10;
+/// Poll event: the file descriptor is invalid
+pub const POLLNVAL: i16 = 0x20;
+
+// the net server reserves buffer space for every connection in the accept queue. thus, larger
+// backlogs are silently truncated to this value, as Linux does with `somaxconn`.
+const MAX_BACKLOG: usize = 8;
+
+/// Generic socket address (`struct sockaddr`)
+#[repr(C)]
+pub struct SockAddr {
+    pub sa_family: u16,
+    pub sa_data: [u8; 14],
+}
+
+/// IPv4 socket address (`struct sockaddr_in`) with port and address in network byte order
+#[derive(Default)]
+#[repr(C)]
+pub struct SockAddrIn {
+    pub sin_family: u16,
+    pub sin_port: u16,
+    pub sin_addr: u32,
+    pub sin_zero: [u8; 8],
+}
+
+/// IPv6 socket address (`struct sockaddr_in6`) with port and address in network byte order
+#[derive(Default)]
+#[repr(C)]
+pub struct SockAddrIn6 {
+    pub sin6_family: u16,
+    pub sin6_port: u16,
+    pub sin6_flowinfo: u32,
+    pub sin6_addr: [u8; 16],
+    pub sin6_scope_id: u32,
+}
+
+/// A file descriptor and the events of interest for [`poll`] (`struct pollfd`)
+#[derive(Copy, Clone, Debug, Default)]
+#[repr(C)]
+pub struct PollFd {
+    pub fd: i32,
+    pub events: i16,
+    pub revents: i16,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Kind {
+    Stream,
+    Dgram,
+    Raw,
+}
+
+// the ports of stream sockets that have been bound, but are not listening yet
+static BOUND: StaticRefCell<BTreeMap<Fd, Port>> = StaticRefCell::new(BTreeMap::new());
+
+fn kind(fd: i32) -> Result<Kind, Errno> {
+    let file = get_file(fd)?;
+    let file = file.borrow();
+    let any = file.as_any();
+    if any.is::<TcpSocket>() {
+        Ok(Kind::Stream)
+    }
+    else if any.is::<UdpSocket>() {
+        Ok(Kind::Dgram)
+    }
+    else if any.is::<RawSocket>() {
+        Ok(Kind::Raw)
+    }
+    else {
+        Err(Errno::ENOTSOCK)
+    }
+}
+
+fn network() -> Result<Rc<Network>, Errno> {
+    create_netmng()?;
+    Ok(NETM.borrow().clone())
+}
+
+/// Uses the network session with given name for all sockets created afterwards via [`socket`]
+///
+/// Has no effect if a session has already been established before.
+pub fn init(name: &str) -> Result<(), Errno> {
+    init_netmng(name).map_err(Errno::from)
+}
+
+/// Creates a new socket and returns its file descriptor
+///
+/// `domain` has to be [`AF_INET`] or [`AF_INET6`] and `ty` one of [`SOCK_STREAM`], [`SOCK_DGRAM`],
+/// and [`SOCK_RAW`], optionally combined with [`SOCK_NONBLOCK`] and [`SOCK_CLOEXEC`]. For raw
+/// sockets, `protocol` specifies the IP protocol to receive packets for (0 = all protocols).
+pub fn socket(domain: i32, ty: i32, protocol: i32) -> Result<i32, Errno> {
+    if domain != AF_INET && domain != AF_INET6 {
+        return Err(Errno::EAFNOSUPPORT);
+    }
+
+    let net = network()?;
+    let mut file = match (ty & !(SOCK_NONBLOCK | SOCK_CLOEXEC), protocol) {
+        (SOCK_STREAM, 0 | IPPROTO_TCP) => {
+            TcpSocket::new(StreamSocketArgs::new(net))?.into_generic()
+        },
+        (SOCK_DGRAM, 0 | IPPROTO_UDP) => UdpSocket::new(DgramSocketArgs::new(net))?.into_generic(),
+        (SOCK_RAW, 0..=255) => {
+            let proto = (protocol != 0).then_some(protocol as u8);
+            RawSocket::new(RawSocketArgs::new(net), proto)?.into_generic()
+        },
+        (SOCK_STREAM | SOCK_DGRAM | SOCK_RAW, _) => return Err(Errno::EPROTONOSUPPORT),
+        _ => return Err(Errno::EINVAL),
+    };
+
+    if ty & SOCK_NONBLOCK != 0 {
+        file.set_blocking(false)?;
+    }
+
+    file.claim();
+    // the file descriptor might have been used by a stream socket that was closed via close(2)
+    BOUND.borrow_mut().remove(&file.fd());
+    Ok(file.fd() as i32)
+}
+
+/// Binds the socket `fd` to the local endpoint `ep`
+///
+/// Datagram sockets are bound immediately, whereas stream sockets only record the port for the
+/// following [`listen`].
+pub fn bind(fd: i32, ep: Endpoint) -> Result<(), Errno> {
+    match kind(fd)? {
+        Kind::Stream => {
+            let s = get_file_as::<TcpSocket>(fd)?;
+            let mut bound = BOUND.borrow_mut();
+            if s.state() != State::Closed || bound.contains_key(&s.fd()) {
+                return Err(Errno::EINVAL);
+            }
+            bound.insert(s.fd(), ep.port);
+        },
+        Kind::Dgram => get_file_as::<UdpSocket>(fd)?.bind(ep.port)?,
+        Kind::Raw => return Err(Errno::EOPNOTSUPP),
+    }
+    Ok(())
+}
+
+/// Puts the stream socket `fd` into listen mode with an accept queue of `backlog` connections
+///
+/// The socket listens on the port given to [`bind`] before. Connections are taken from the accept
+/// queue via [`accept`].
+pub fn listen(fd: i32, backlog: i32) -> Result<(), Errno> {
+    if kind(fd)? != Kind::Stream {
+        return Err(Errno::EOPNOTSUPP);
+    }
+
+    let s = get_file_as::<TcpSocket>(fd)?;
+    if s.state() == State::Listening {
+        return Ok(());
+    }
+
+    let port = BOUND
+        .borrow()
+        .get(&s.fd())
+        .copied()
+        .ok_or(Errno::EDESTADDRREQ)?;
+    let backlog = (backlog.max(1) as usize).min(MAX_BACKLOG);
+    s.borrow_as().listen_with_backlog(port, backlog)?;
+    BOUND.borrow_mut().remove(&s.fd());
+    Ok(())
+}
+
+/// Accepts the next connection on the listening stream socket `fd`
+///
+/// Returns the file descriptor of the new socket, which is in blocking mode, and the remote
+/// endpoint. If `fd` is in non-blocking mode and no connection is pending, [`Errno::EAGAIN`] is
+/// returned.
+pub fn accept(fd: i32) -> Result<(i32, Endpoint), Errno> {
+    if kind(fd)? != Kind::Stream {
+        return Err(Errno::EOPNOTSUPP);
+    }
+
+    let s = get_file_as::<TcpSocket>(fd)?;
+    let mut cs = s
+        .borrow_as()
+        .accept_socket(StreamSocketArgs::new(network()?))?;
+    cs.claim();
+    Ok((cs.fd() as i32, cs.remote_endpoint().unwrap()))
+}
+
+/// Connects the socket `fd` to the remote endpoint `ep`
+///
+/// For datagram sockets, this only sets the destination for [`send`]. Stream sockets in
+/// non-blocking mode return [`Errno::EINPROGRESS`]; the completion can be awaited via [`poll`]
+/// with [`POLLOUT`].
+pub fn connect(fd: i32, ep: Endpoint) -> Result<(), Errno> {
+    match kind(fd)? {
+        Kind::Stream => get_file_as::<TcpSocket>(fd)?.connect(ep)?,
+        Kind::Dgram => get_file_as::<UdpSocket>(fd)?.connect(ep)?,
+        Kind::Raw => return Err(Errno::EOPNOTSUPP),
+    }
+    Ok(())
+}
+
+// performs `func`, but puts the socket `fd` temporarily into non-blocking mode if requested
+fn with_flags<F, R>(fd: i32, flags: i32, func: F) -> Result<R, Errno>
+where
+    F: FnOnce() -> Result<R, Error>,
+{
+    if flags & !(MSG_DONTWAIT | MSG_NOSIGNAL) != 0 {
+        return Err(Errno::EOPNOTSUPP);
+    }
+
+    let mut file = get_file(fd)?;
+    let dontwait = flags & MSG_DONTWAIT != 0 && file.is_blocking();
+    if dontwait {
+        file.set_blocking(false)?;
+    }
+    let res = func();
+    if dontwait {
+        file.set_blocking(true)?;
+    }
+    res.map_err(Errno::from)
+}
+
+/// Sends `data` via the connected socket `fd`
+///
+/// Returns the number of sent bytes. `flags` can contain [`MSG_DONTWAIT`] and [`MSG_NOSIGNAL`].
+pub fn send(fd: i32, data: &[u8], flags: i32) -> Result<usize, Errno> {
+    sendto(fd, data, flags, None)
+}
+
+/// Sends `data` via the socket `fd` to `dest` or, if `dest` is `None`, to the connected endpoint
+///
+/// Stream sockets ignore `dest`. Returns the number of sent bytes. `flags` can contain
+/// [`MSG_DONTWAIT`] and [`MSG_NOSIGNAL`].
+pub fn sendto(fd: i32, data: &[u8], flags: i32, dest: Option<Endpoint>) -> Result<usize, Errno> {
+    let kind = kind(fd)?;
+    if kind == Kind::Dgram
+        && dest.is_none()
+        && get_file_as::<UdpSocket>(fd)?.remote_endpoint().is_none()
+    {
+        return Err(Errno::EDESTADDRREQ);
+    }
+
+    with_flags(fd, flags, || match (kind, dest) {
+        (Kind::Stream, _) => get_file_as::<TcpSocket>(fd)?.send(data),
+        (Kind::Dgram, Some(ep)) => get_file_as::<UdpSocket>(fd)?
+            .send_to(data, ep)
+            .map(|_| data.len()),
+        (Kind::Dgram, None) => get_file_as::<UdpSocket>(fd)?.send(data),
+        (Kind::Raw, _) => {
+            let s = get_file_as::<RawSocket>(fd)?;
+            s.borrow_as().send(data)?;
+            Ok(data.len())
+        },
+    })
+}
+
+/// Receives data from the socket `fd` into `data`
+///
+/// Returns the number of received bytes. For stream sockets, 0 is returned as soon as the remote
+/// side has closed the connection and all data has been received. `flags` can contain
+/// [`MSG_DONTWAIT`].
+pub fn recv(fd: i32, data: &mut [u8], flags: i32) -> Result<usize, Errno> {
+    recvfrom(fd, data, flags).map(|(amount, _)| amount)
+}
+
+/// Receives data from the socket `fd` into `data`
+///
+/// Like [`recv`], but returns the endpoint the data was received from in addition, if known.
+pub fn recvfrom(fd: i32, data: &mut [u8], flags: i32) -> Result<(usize, Option<Endpoint>), Errno> {
+    let kind = kind(fd)?;
+    with_flags(fd, flags, || match kind {
+        Kind::Stream => {
+            let mut s = get_file_as::<TcpSocket>(fd)?;
+            // report the end of the stream instead of waiting for data that will never arrive
+            if s.state() == State::RemoteClosed && !s.has_data() {
+                return Ok((0, s.remote_endpoint()));
+            }
+            let amount = s.recv(data)?;
+            Ok((amount, s.remote_endpoint()))
+        },
+        Kind::Dgram => get_file_as::<UdpSocket>(fd)?
+            .recv_from(data)
+            .map(|(amount, ep)| (amount, Some(ep))),
+        Kind::Raw => {
+            let s = get_file_as::<RawSocket>(fd)?;
+            let amount = s.borrow_as().recv(data)?;
+            Ok((amount, None))
+        },
+    })
+}
+
+/// Closes the socket `fd` and releases its file descriptor
+pub fn close(fd: i32) -> Result<(), Errno> {
+    kind(fd)?;
+    BOUND.borrow_mut().remove(&(fd as Fd));
+    Activity::own().files().remove(fd as Fd);
+    Ok(())
+}
+
+fn file_events(events: i16) -> FileEvent {
+    let mut res = FileEvent::empty();
+    if events & POLLIN != 0 {
+        res |= FileEvent::INPUT;
+    }
+    if events & POLLOUT != 0 {
+        res |= FileEvent::OUTPUT;
+    }
+    res
+}
+
+fn hung_up(fd: i32) -> bool {
+    kind(fd) == Ok(Kind::Stream)
+        && get_file_as::<TcpSocket>(fd)
+            .map(|s| s.state() == State::RemoteClosed)
+            .unwrap_or(false)
+}
+
+// sets `revents` of all entries and returns the number of entries with any events
+fn fetch_events(fds: &mut [PollFd]) -> usize {
+    let mut ready = 0;
+    for pfd in fds.iter_mut() {
+        pfd.revents = 0;
+        if pfd.fd < 0 {
+            continue;
+        }
+
+        match get_file(pfd.fd) {
+            Err(_) => pfd.revents = POLLNVAL,
+            Ok(mut file) => {
+                if pfd.events & POLLIN != 0 && file.check_events(FileEvent::INPUT) {
+                    pfd.revents |= POLLIN;
+                }
+                if pfd.events & POLLOUT != 0 && file.check_events(FileEvent::OUTPUT) {
+                    pfd.revents |= POLLOUT;
+                }
+                // as usual, hang ups are reported even if they have not been requested
+                if hung_up(pfd.fd) {
+                    pfd.revents |= POLLHUP;
+                }
+            },
+        }
+
+        if pfd.revents != 0 {
+            ready += 1;
+        }
+    }
+    ready
+}
+
+/// Waits until any of the given file descriptors is ready for the requested events
+///
+/// The file descriptors can refer to arbitrary files, not only sockets; negative file descriptors
+/// are ignored. Waits at most `timeout` milliseconds or without limit if `timeout` is negative.
+/// Returns the number of entries in `fds` with non-zero `revents`.
+pub fn poll(fds: &mut [PollFd], timeout: i32) -> usize {
+    let mut waiter = FileWaiter::default();
+    for pfd in fds.iter().filter(|pfd| pfd.fd >= 0) {
+        waiter.set(pfd.fd as Fd, file_events(pfd.events));
+    }
+
+    let end = (timeout > 0).then(|| TimeInstant::now() + TimeDuration::from_millis(timeout as u64));
+    loop {
+        let ready = fetch_events(fds);
+        if ready > 0 || timeout == 0 {
+            break ready;
+        }
+
+        match end {
+            None => waiter.wait(),
+            Some(end) => match end.checked_duration_since(TimeInstant::now()) {
+                Some(remaining) => waiter.wait_for(remaining),
+                None => break 0,
+            },
+        }
+    }
+}
+
+unsafe fn addr_to_ep(addr: *const SockAddr, len: u32) -> Result<Endpoint, Errno> {
+    let len = len as usize;
+    if addr.is_null() || len < mem::size_of::<u16>() {
+        return Err(Errno::EINVAL);
+    }
+
+    match ptr::read_unaligned(addr as *const u16) as i32 {
+        AF_INET if len >= mem::size_of::<SockAddrIn>() => {
+            let sin = ptr::read_unaligned(addr as *const SockAddrIn);
+            Ok(Endpoint::new(
+                IpAddr::new_from_raw(u32::from_be(sin.sin_addr)),
+                u16::from_be(sin.sin_port),
+            ))
+        },
+        AF_INET6 if len >= mem::size_of::<SockAddrIn6>() => {
+            let sin6 = ptr::read_unaligned(addr as *const SockAddrIn6);
+            Ok(Endpoint::new(
+                IpAddr::from_octets(sin6.sin6_addr),
+                u16::from_be(sin6.sin6_port),
+            ))
+        },
+        AF_INET | AF_INET6 => Err(Errno::EINVAL),
+        _ => Err(Errno::EAFNOSUPPORT),
+    }
+}
+
+// writes `obj` to `addr`, truncated to `*len` bytes, and stores the size of `obj` in `len`
+unsafe fn write_addr<T>(obj: &T, addr: *mut SockAddr, len: *mut u32) {
+    let bytes = util::object_to_bytes(obj);
+    let amount = bytes.len().min(*len as usize);
+    ptr::copy_nonoverlapping(bytes.as_ptr(), addr as *mut u8, amount);
+    *len = bytes.len() as u32;
+}
+
+unsafe fn ep_to_addr(ep: Option<Endpoint>, addr: *mut SockAddr, len: *mut u32) {
+    if addr.is_null() || len.is_null() {
+        return;
+    }
+
+    match ep {
+        Some(Endpoint {
+            addr: IpAddr::V4(ip),
+            port,
+        }) => write_addr(
+            &SockAddrIn {
+                sin_family: AF_INET as u16,
+                sin_port: port.to_be(),
+                sin_addr: ip.to_be(),
+                ..Default::default()
+            },
+            addr,
+            len,
+        ),
+        Some(ep) => write_addr(
+            &SockAddrIn6 {
+                sin6_family: AF_INET6 as u16,
+                sin6_port: ep.port.to_be(),
+                sin6_addr: ep.addr.octets(),
+                ..Default::default()
+            },
+            addr,
+            len,
+        ),
+        // the source is unknown (raw sockets)
+        None => *len = 0,
+    }
+}
+
+// converts the result to the return value of a Linux system call
+fn sys_res(res: Result<usize, Errno>) -> isize {
+    match res {
+        Ok(val) => val as isize,
+        Err(e) => -(e.0 as isize),
+    }
+}
+
+#[no_mangle]
+#[allow(clippy::missing_safety_doc)]
+pub unsafe extern "C" fn __m3c_bsd_socket(domain: i32, ty: i32, protocol: i32) -> isize {
+    sys_res(socket(domain, ty, protocol).map(|fd| fd as usize))
+}
+
+#[no_mangle]
+#[allow(clippy::missing_safety_doc)]
+pub unsafe extern "C" fn __m3c_bsd_bind(fd: i32, addr: *const SockAddr, len: u32) -> isize {
+    sys_res(addr_to_ep(addr, len).and_then(|ep| bind(fd, ep)).map(|_| 0))
+}
+
+#[no_mangle]
+#[allow(clippy::missing_safety_doc)]
+pub unsafe extern "C" fn __m3c_bsd_listen(fd: i32, backlog: i32) -> isize {
+    sys_res(listen(fd, backlog).map(|_| 0))
+}
+
+#[no_mangle]
+#[allow(clippy::missing_safety_doc)]
+pub unsafe extern "C" fn __m3c_bsd_accept(fd: i32, addr: *mut SockAddr, len: *mut u32) -> isize {
+    sys_res(accept(fd).map(|(cfd, ep)| {
+        ep_to_addr(Some(ep), addr, len);
+        cfd as usize
+    }))
+}
+
+#[no_mangle]
+#[allow(clippy::missing_safety_doc)]
+pub unsafe extern "C" fn __m3c_bsd_connect(fd: i32, addr: *const SockAddr, len: u32) -> isize {
+    sys_res(
+        addr_to_ep(addr, len)
+            .and_then(|ep| connect(fd, ep))
+            .map(|_| 0),
+    )
+}
+
+#[no_mangle]
+#[allow(clippy::missing_safety_doc)]
+pub unsafe extern "C" fn __m3c_bsd_sendto(
+    fd: i32,
+    buf: *const libc::c_void,
+    len: usize,
+    flags: i32,
+    addr: *const SockAddr,
+    addr_len: u32,
+) -> isize {
+    let slice = util::slice_for(buf as *const u8, len);
+    sys_res(match addr.is_null() {
+        true => sendto(fd, slice, flags, None),
+        false => addr_to_ep(addr, addr_len).and_then(|ep| sendto(fd, slice, flags, Some(ep))),
+    })
+}
+
+#[no_mangle]
+#[allow(clippy::missing_safety_doc)]
+pub unsafe extern "C" fn __m3c_bsd_recvfrom(
+    fd: i32,
+    buf: *mut libc::c_void,
+    len: usize,
+    flags: i32,
+    addr: *mut SockAddr,
+    addr_len: *mut u32,
+) -> isize {
+    let slice = util::slice_for_mut(buf as *mut u8, len);
+    sys_res(recvfrom(fd, slice, flags).map(|(amount, ep)| {
+        ep_to_addr(ep, addr, addr_len);
+        amount
+    }))
+}
+
+#[no_mangle]
+#[allow(clippy::missing_safety_doc)]
+pub unsafe extern "C" fn __m3c_bsd_poll(fds: *mut PollFd, nfds: usize, timeout: i32) -> isize {
+    poll(util::slice_for_mut(fds, nfds), timeout) as isize
+}
+
+#[no_mangle]
+#[allow(clippy::missing_safety_doc)]
+pub unsafe extern "C" fn __m3c_bsd_close(fd: i32) -> isize {
+    sys_res(close(fd).map(|_| 0))
+}
@@ -0,0 +1,357 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! Writes ELF core files of crashed activities
+//!
+//! If the environment variable `M3_COREDUMP` is set (for example, via the `coredump` attribute of
+//! an app in the resource manager's config), the activity registers a crash handler at TileMux
+//! during startup. If the activity crashes afterwards, TileMux continues the activity in this
+//! handler, which writes an ELF core file with the registers and the memory of the activity to the
+//! path in `M3_COREDUMP`. The core file can be inspected offline with gdb and the binary built on
+//! the host (e.g., `gdb build/.../bin/<app> <core>`).
+//!
+//! The core file contains the data and bss segments, the used part of the stack, and the
+//! environment. The heap is only included if it has been mapped upfront, because otherwise reading
+//! it would let the pager allocate memory for the complete heap.
+
+use core::cmp;
+
+use crate::boxed::Box;
+use crate::cfg;
+use crate::col::Vec;
+use crate::elf;
+use crate::env;
+use crate::errors::{Code, Error};
+use crate::io::{LogFlags, Write};
+use crate::log;
+use crate::mem::{self, VirtAddr};
+use crate::tiles::{Activity, OwnActivity};
+use crate::tmif;
+use crate::util::{self, math};
+use crate::vec;
+use crate::vfs::{BufWriter, OpenFlags, VFS};
+
+const HANDLER_STACK_SIZE: usize = cfg::STACK_SIZE / 4;
+
+const SIGTRAP: i32 = 5;
+const SIGILL: i32 = 4;
+const SIGBUS: i32 = 7;
+#[cfg(target_arch = "x86_64")]
+const SIGFPE: i32 = 8;
+const SIGSEGV: i32 = 11;
+
+extern "C" {
+    static _data_start: u8;
+    static _data_end: u8;
+    static _bss_start: u8;
+    static _bss_end: u8;
+}
+
+#[cfg(target_arch = "x86_64")]
+pub(crate) mod arch {
+    use super::*;
+
+    pub const MACHINE: elf::Machine = elf::Machine::X86_64;
+
+    pub fn stack_pointer(state: &tmif::CrashState) -> usize {
+        state.regs[19]
+    }
+
+    pub fn signal(state: &tmif::CrashState) -> i32 {
+        match state.vector {
+            0x00 | 0x10 | 0x13 => SIGFPE,
+            0x01 | 0x03 => SIGTRAP,
+            0x06 => SIGILL,
+            0x11 => SIGBUS,
+            _ => SIGSEGV,
+        }
+    }
+}
+
+#[cfg(target_arch = "riscv64")]
+pub(crate) mod arch {
+    use super::*;
+
+    pub const MACHINE: elf::Machine = elf::Machine::RiscV;
+
+    pub fn stack_pointer(state: &tmif::CrashState) -> usize {
+        state.regs[2]
+    }
+
+    pub fn signal(state: &tmif::CrashState) -> i32 {
+        match state.vector {
+            0x00 | 0x04 | 0x06 => SIGBUS,
+            0x02 => SIGILL,
+            0x03 => SIGTRAP,
+            _ => SIGSEGV,
+        }
+    }
+}
+
+/// The status of the process (`struct elf_prstatus` in Linux)
+#[derive(Default)]
+#[repr(C)]
+#[allow(dead_code)]
+struct PrStatus {
+    signo: i32,
+    code: i32,
+    errno: i32,
+    cursig: i16,
+    _pad: i16,
+    sigpend: u64,
+    sighold: u64,
+    pid: i32,
+    ppid: i32,
+    pgrp: i32,
+    sid: i32,
+    // user, system, and children's user and system time as timevals
+    times: [u64; 8],
+    regs: [usize; tmif::CRASH_REGS],
+    fpvalid: i32,
+}
+
+/// The information about the process (`struct elf_prpsinfo` in Linux)
+#[repr(C)]
+#[allow(dead_code)]
+struct PrPsInfo {
+    state: u8,
+    sname: u8,
+    zomb: u8,
+    nice: i8,
+    flag: u64,
+    uid: u32,
+    gid: u32,
+    pid: i32,
+    ppid: i32,
+    pgrp: i32,
+    sid: i32,
+    fname: [u8; 16],
+    psargs: [u8; 80],
+}
+
+struct Region {
+    start: usize,
+    size: usize,
+    flags: elf::PHFlags,
+}
+
+/// Registers the crash handler at TileMux, if core dumps are enabled via `M3_COREDUMP`
+pub(crate) fn init() {
+    if env::var(env::COREDUMP_VAR).is_none() {
+        return;
+    }
+
+    // TileMux writes the state directly into our memory, so that it needs to be mapped already;
+    // the stack is only used by ourself and can therefore be mapped on demand.
+    let state = Box::leak(Box::<tmif::CrashState>::default());
+    let stack = vec![0u8; HANDLER_STACK_SIZE].leak();
+    let stack_top = VirtAddr::from(stack.as_ptr()) + stack.len();
+
+    if let Err(e) = tmif::reg_crash_handler(
+        crash_handler as usize,
+        stack_top,
+        VirtAddr::from(state as *const tmif::CrashState),
+    ) {
+        log!(LogFlags::Error, "Unable to register crash handler: {:?}", e);
+    }
+}
+
+extern "C" fn crash_handler(state: &tmif::CrashState) -> ! {
+    if let Some(path) = env::var(env::COREDUMP_VAR) {
+        match write_core(&path, state) {
+            Ok(_) => log!(LogFlags::Error, "Wrote core dump to {}", path),
+            Err(e) => log!(
+                LogFlags::Error,
+                "Unable to write core dump to {}: {:?}",
+                path,
+                e
+            ),
+        }
+    }
+
+    OwnActivity::exit_with(Code::Unspecified);
+}
+
+fn regions(state: &tmif::CrashState) -> Vec<Region> {
+    let mut regs = Vec::new();
+    let mut add = |start: usize, end: usize, flags: elf::PHFlags| {
+        if end > start {
+            regs.push(Region {
+                start,
+                size: end - start,
+                flags,
+            });
+        }
+    };
+
+    // safety: the symbols are defined by the linker script
+    let (data_start, data_end, bss_start, bss_end) = unsafe {
+        (
+            &_data_start as *const u8 as usize,
+            &_data_end as *const u8 as usize,
+            &_bss_start as *const u8 as usize,
+            &_bss_end as *const u8 as usize,
+        )
+    };
+    add(data_start, data_end, elf::PHFlags::R | elf::PHFlags::W);
+    add(bss_start, bss_end, elf::PHFlags::R | elf::PHFlags::W);
+
+    // the heap directly follows the bss segment (see tiles::loader)
+    if Activity::own().pager().is_none() {
+        let heap_start = math::round_up(bss_end, cfg::PAGE_SIZE);
+        let heap_end = heap_start + env::get().heap_size();
+        add(heap_start, heap_end, elf::PHFlags::R | elf::PHFlags::W);
+    }
+
+    // the stack grows downwards; only include the used part, if the stack pointer is still sane
+    let (stack_addr, stack_size) = env::get().tile_desc().stack_space();
+    let (stack_start, stack_end) = (stack_addr.as_local(), stack_addr.as_local() + stack_size);
+    let sp = arch::stack_pointer(state);
+    let used_start = if sp > stack_start && sp <= stack_end {
+        math::round_dn(sp, cfg::PAGE_SIZE)
+    }
+    else {
+        stack_start
+    };
+    add(used_start, stack_end, elf::PHFlags::R | elf::PHFlags::W);
+
+    let env_start = cfg::ENV_START.as_local();
+    add(env_start, env_start + cfg::ENV_SIZE, elf::PHFlags::R);
+
+    regs
+}
+
+fn push_note<T>(notes: &mut Vec<u8>, ty: u32, desc: &T) {
+    const NAME: &[u8] = b"CORE\0";
+
+    let desc = util::object_to_bytes(desc);
+    let hdr = elf::NoteHeader {
+        name_size: NAME.len() as u32,
+        desc_size: desc.len() as u32,
+        ty,
+    };
+    notes.extend_from_slice(util::object_to_bytes(&hdr));
+    notes.extend_from_slice(NAME);
+    notes.resize(math::round_up(notes.len(), 4), 0);
+    notes.extend_from_slice(desc);
+    notes.resize(math::round_up(notes.len(), 4), 0);
+}
+
+fn notes(state: &tmif::CrashState) -> Vec<u8> {
+    let pid = env::get().activity_id() as i32;
+    let sig = arch::signal(state);
+
+    let status = PrStatus {
+        signo: sig,
+        cursig: sig as i16,
+        pid,
+        regs: state.regs,
+        ..Default::default()
+    };
+
+    let mut info = PrPsInfo {
+        state: 0,
+        sname: b'R',
+        zomb: 0,
+        nice: 0,
+        flag: 0,
+        uid: 0,
+        gid: 0,
+        pid,
+        ppid: 0,
+        pgrp: 0,
+        sid: 0,
+        fname: [0; 16],
+        psargs: [0; 80],
+    };
+    if let Some(name) = env::args().next() {
+        let name = name.rsplit('/').next().unwrap();
+        let len = cmp::min(name.len(), info.fname.len() - 1);
+        info.fname[0..len].copy_from_slice(&name.as_bytes()[0..len]);
+    }
+    let mut pos = 0;
+    for arg in env::args() {
+        for b in arg.bytes().chain(core::iter::once(b' ')) {
+            // leave room for the null byte
+            if pos + 1 < info.psargs.len() {
+                info.psargs[pos] = b;
+                pos += 1;
+            }
+        }
+    }
+
+    let mut notes = Vec::new();
+    push_note(&mut notes, elf::NT_PRSTATUS, &status);
+    push_note(&mut notes, elf::NT_PRPSINFO, &info);
+    notes
+}
+
+fn write_core(path: &str, state: &tmif::CrashState) -> Result<(), Error> {
+    let regions = regions(state);
+    let notes = notes(state);
+
+    let file = VFS::open(path, OpenFlags::W | OpenFlags::CREATE | OpenFlags::TRUNC)?;
+    let mut file = BufWriter::new(file);
+
+    let ph_num = 1 + regions.len();
+    let hdr = elf::ElfHeader {
+        // 64-bit, little endian, current version
+        ident: *b"\x7FELF\x02\x01\x01\0\0\0\0\0\0\0\0\0",
+        ty: elf::ElfType::Core.into(),
+        machine: arch::MACHINE.into(),
+        version: 1,
+        ph_off: mem::size_of::<elf::ElfHeader>(),
+        eh_size: mem::size_of::<elf::ElfHeader>() as u16,
+        ph_entry_size: mem::size_of::<elf::ProgramHeader>() as u16,
+        ph_num: ph_num as u16,
+        ..Default::default()
+    };
+    file.write_all(util::object_to_bytes(&hdr))?;
+
+    // the notes and the memory contents follow the program headers
+    let mut off = mem::size_of::<elf::ElfHeader>() + ph_num * mem::size_of::<elf::ProgramHeader>();
+    let note_hdr = elf::ProgramHeader {
+        ty: elf::PHType::Note.into(),
+        offset: off as u64,
+        file_size: notes.len() as u64,
+        align: 4,
+        ..Default::default()
+    };
+    file.write_all(util::object_to_bytes(&note_hdr))?;
+    off += notes.len();
+
+    for r in &regions {
+        let phdr = elf::ProgramHeader {
+            ty: elf::PHType::Load.into(),
+            flags: r.flags.bits(),
+            offset: off as u64,
+            virt_addr: r.start,
+            file_size: r.size as u64,
+            mem_size: r.size as u64,
+            align: 1,
+            ..Default::default()
+        };
+        file.write_all(util::object_to_bytes(&phdr))?;
+        off += r.size;
+    }
+
+    file.write_all(&notes)?;
+    for r in &regions {
+        // safety: all regions are mapped and readable
+        let data = unsafe { util::slice_for(r.start as *const u8, r.size) };
+        file.write_all(data)?;
+    }
+    file.flush()
+}
@@ -0,0 +1,123 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! Information about devices that have been assigned to this app
+//!
+//! Devices are assigned to apps via `<dev>` elements in the config. The resource manager looks up
+//! the device in the device tree and passes its register regions and interrupts to the app via the
+//! environment variable [`DEV_VAR_PREFIX`](crate::env::DEV_VAR_PREFIX) followed by the device
+//! name. The value has the form `reg=<addr>+<size>,... irq=<irq>,... [msi=<addr>]`.
+
+use core::fmt;
+use core::str::FromStr;
+
+use crate::col::Vec;
+use crate::env;
+use crate::errors::{Code, Error};
+use crate::format;
+use crate::mem::GlobOff;
+use crate::util::parse;
+
+/// The register regions and interrupts of a device
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DeviceInfo {
+    regs: Vec<(GlobOff, GlobOff)>,
+    irqs: Vec<u32>,
+    msi: Option<GlobOff>,
+}
+
+impl DeviceInfo {
+    /// Creates a new device info with given register regions (address and size) and interrupts
+    pub fn new(regs: Vec<(GlobOff, GlobOff)>, irqs: Vec<u32>) -> Self {
+        Self {
+            regs,
+            irqs,
+            msi: None,
+        }
+    }
+
+    /// Sets the address that message-signaled interrupts need to be written to
+    pub fn with_msi(mut self, addr: GlobOff) -> Self {
+        self.msi = Some(addr);
+        self
+    }
+
+    /// Returns the information about the device with given name that has been assigned to this
+    /// app or `None` if there is no such device
+    pub fn get(name: &str) -> Option<Self> {
+        env::var(format!("{}{}", env::DEV_VAR_PREFIX, name)).and_then(|v| v.parse().ok())
+    }
+
+    /// Returns the register regions as pairs of address and size
+    pub fn regs(&self) -> &[(GlobOff, GlobOff)] {
+        &self.regs
+    }
+
+    /// Returns the interrupt numbers
+    pub fn irqs(&self) -> &[u32] {
+        &self.irqs
+    }
+
+    /// Returns the address that message-signaled interrupts need to be written to or `None` if
+    /// the device does not support them
+    pub fn msi(&self) -> Option<GlobOff> {
+        self.msi
+    }
+}
+
+impl fmt::Display for DeviceInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "reg=")?;
+        for (i, (addr, size)) in self.regs.iter().enumerate() {
+            write!(f, "{}{:#x}+{:#x}", if i > 0 { "," } else { "" }, addr, size)?;
+        }
+        write!(f, " irq=")?;
+        for (i, irq) in self.irqs.iter().enumerate() {
+            write!(f, "{}{}", if i > 0 { "," } else { "" }, irq)?;
+        }
+        if let Some(addr) = self.msi {
+            write!(f, " msi={:#x}", addr)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for DeviceInfo {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut info = Self::default();
+        for part in s.split_whitespace() {
+            match part.split_once('=') {
+                Some(("reg", regs)) => {
+                    for reg in regs.split(',').filter(|r| !r.is_empty()) {
+                        let (addr, size) = reg
+                            .split_once('+')
+                            .ok_or_else(|| Error::new(Code::InvArgs))?;
+                        info.regs.push((parse::addr(addr)?, parse::addr(size)?));
+                    }
+                },
+                Some(("irq", irqs)) => {
+                    for irq in irqs.split(',').filter(|i| !i.is_empty()) {
+                        info.irqs.push(parse::int(irq)? as u32);
+                    }
+                },
+                Some(("msi", addr)) => info.msi = Some(parse::addr(addr)?),
+                _ => return Err(Error::new(Code::InvArgs)),
+            }
+        }
+        Ok(info)
+    }
+}
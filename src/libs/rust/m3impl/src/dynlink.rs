@@ -0,0 +1,494 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! The dynamic linker for dynamically linked programs
+//!
+//! In contrast to other systems, the program and the shared objects it depends on are loaded by
+//! the parent (see [`ChildActivity::exec`](crate::tiles::ChildActivity::exec)), which passes the
+//! addresses of all loaded objects via the environment. The dynamic linker runs as the first step
+//! of the activity startup, before libc and libm3 are initialized. Thus, it cannot use the heap.
+//!
+//! The symbols of all objects are searched in load order, starting with the program. Calls to
+//! functions in shared objects are bound lazily on their first call, unless the environment
+//! variable `LD_BIND_NOW` is set to a non-empty value or the object requests immediate binding.
+
+use core::ffi::CStr;
+use core::mem;
+use core::ptr;
+
+use crate::cell::{StaticCell, StaticRefCell};
+use crate::elf::{self, DynTag, SymBind, SymType};
+use crate::env;
+use crate::tiles::loader::{DL_OBJ_WORDS, MAX_DL_OBJS};
+
+/// The kinds of relocations we support
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum RelocKind {
+    None,
+    /// Symbol address plus addend
+    Abs,
+    /// Symbol address (GOT or PLT entry)
+    Slot,
+    /// Load address plus addend
+    Relative,
+    /// Copies the data of the symbol from a shared object
+    Copy,
+}
+
+#[cfg(target_arch = "x86_64")]
+mod arch {
+    use super::RelocKind;
+
+    pub fn reloc_kind(ty: u32) -> Option<RelocKind> {
+        match ty {
+            0 => Some(RelocKind::None),
+            1 => Some(RelocKind::Abs),
+            5 => Some(RelocKind::Copy),
+            6 | 7 => Some(RelocKind::Slot),
+            8 => Some(RelocKind::Relative),
+            _ => None,
+        }
+    }
+
+    pub fn init_got(got: *mut usize, obj: usize, resolver: usize) {
+        // GOT[1] is pushed and GOT[2] is called by the first PLT entry
+        unsafe {
+            got.add(1).write(obj);
+            got.add(2).write(resolver);
+        }
+    }
+}
+
+#[cfg(target_arch = "riscv64")]
+mod arch {
+    use super::RelocKind;
+
+    pub fn reloc_kind(ty: u32) -> Option<RelocKind> {
+        match ty {
+            0 => Some(RelocKind::None),
+            2 => Some(RelocKind::Abs),
+            3 => Some(RelocKind::Relative),
+            4 => Some(RelocKind::Copy),
+            5 => Some(RelocKind::Slot),
+            _ => None,
+        }
+    }
+
+    pub fn init_got(got: *mut usize, obj: usize, resolver: usize) {
+        // the first PLT entry jumps to GOT[0] with GOT[1] in t0
+        unsafe {
+            got.add(0).write(resolver);
+            got.add(1).write(obj);
+        }
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod arch {
+    use super::RelocKind;
+
+    pub fn reloc_kind(ty: u32) -> Option<RelocKind> {
+        match ty {
+            0 => Some(RelocKind::None),
+            257 => Some(RelocKind::Abs),
+            1024 => Some(RelocKind::Copy),
+            1025 | 1026 => Some(RelocKind::Slot),
+            1027 => Some(RelocKind::Relative),
+            _ => None,
+        }
+    }
+
+    pub fn init_got(got: *mut usize, obj: usize, resolver: usize) {
+        // the first PLT entry jumps to GOT[2] with the address of GOT[2] in x16
+        unsafe {
+            got.add(1).write(obj);
+            got.add(2).write(resolver);
+        }
+    }
+}
+
+#[cfg(not(any(
+    target_arch = "x86_64",
+    target_arch = "riscv64",
+    target_arch = "aarch64"
+)))]
+mod arch {
+    use super::RelocKind;
+
+    pub fn reloc_kind(_ty: u32) -> Option<RelocKind> {
+        None
+    }
+
+    pub fn init_got(_got: *mut usize, _obj: usize, _resolver: usize) {
+        panic!("dynlink: lazy binding is not supported");
+    }
+}
+
+extern "C" {
+    fn _dl_runtime_resolve();
+}
+
+#[derive(Copy, Clone)]
+struct DynObject {
+    base: usize,
+    strtab: usize,
+    symtab: usize,
+    hash: usize,
+    gnu_hash: usize,
+    rela: usize,
+    rela_size: usize,
+    jmprel: usize,
+    jmprel_size: usize,
+    pltgot: usize,
+    init: usize,
+    init_array: usize,
+    init_array_size: usize,
+    bind_now: bool,
+}
+
+static OBJS: StaticRefCell<[DynObject; MAX_DL_OBJS]> =
+    StaticRefCell::new([DynObject::new(0); MAX_DL_OBJS]);
+static COUNT: StaticCell<usize> = StaticCell::new(0);
+
+impl DynObject {
+    const fn new(base: usize) -> Self {
+        Self {
+            base,
+            strtab: 0,
+            symtab: 0,
+            hash: 0,
+            gnu_hash: 0,
+            rela: 0,
+            rela_size: 0,
+            jmprel: 0,
+            jmprel_size: 0,
+            pltgot: 0,
+            init: 0,
+            init_array: 0,
+            init_array_size: 0,
+            bind_now: false,
+        }
+    }
+
+    fn parse(base: usize, dynamic: usize) -> Self {
+        let mut obj = Self::new(base);
+        let mut entry = dynamic as *const elf::Dyn;
+        loop {
+            // safety: we trust our loader and the linker that generated the dynamic section
+            let dyn_entry = unsafe { entry.read() };
+            match DynTag::try_from(dyn_entry.tag) {
+                Ok(DynTag::Null) => break,
+                Ok(DynTag::StrTab) => obj.strtab = base + dyn_entry.val,
+                Ok(DynTag::SymTab) => obj.symtab = base + dyn_entry.val,
+                Ok(DynTag::Hash) => obj.hash = base + dyn_entry.val,
+                Ok(DynTag::GnuHash) => obj.gnu_hash = base + dyn_entry.val,
+                Ok(DynTag::Rela) => obj.rela = base + dyn_entry.val,
+                Ok(DynTag::RelaSz) => obj.rela_size = dyn_entry.val,
+                Ok(DynTag::JmpRel) => obj.jmprel = base + dyn_entry.val,
+                Ok(DynTag::PltRelSz) => obj.jmprel_size = dyn_entry.val,
+                Ok(DynTag::PltGot) => obj.pltgot = base + dyn_entry.val,
+                Ok(DynTag::Init) => obj.init = base + dyn_entry.val,
+                Ok(DynTag::InitArray) => obj.init_array = base + dyn_entry.val,
+                Ok(DynTag::InitArraySz) => obj.init_array_size = dyn_entry.val,
+                Ok(DynTag::BindNow) => obj.bind_now = true,
+                Ok(DynTag::Flags) => obj.bind_now |= (dyn_entry.val & elf::DF_BIND_NOW) != 0,
+                Ok(DynTag::Rel) => panic!("dynlink: relocations without addend are not supported"),
+                Ok(DynTag::TextRel) => panic!("dynlink: text relocations are not supported"),
+                _ => {},
+            }
+            // safety: the dynamic section is terminated by a null entry
+            entry = unsafe { entry.add(1) };
+        }
+        obj
+    }
+
+    fn relocs(addr: usize, size: usize) -> &'static [elf::Rela] {
+        if addr == 0 {
+            return &[];
+        }
+        // safety: we trust the linker that generated the relocation table
+        unsafe {
+            crate::util::slice_for(addr as *const elf::Rela, size / mem::size_of::<elf::Rela>())
+        }
+    }
+
+    fn sym(&self, idx: usize) -> elf::Sym {
+        // safety: the index stems from a relocation of this object
+        unsafe { (self.symtab as *const elf::Sym).add(idx).read() }
+    }
+
+    fn sym_name(&self, sym: &elf::Sym) -> &'static [u8] {
+        // safety: the string table contains null-terminated strings
+        unsafe { CStr::from_ptr((self.strtab + sym.name as usize) as *const _).to_bytes() }
+    }
+
+    fn relocate(&self, objs: &[DynObject], idx: usize, lazy: bool) {
+        for rel in Self::relocs(self.rela, self.rela_size) {
+            self.apply(objs, idx, rel);
+        }
+
+        let plt_relocs = Self::relocs(self.jmprel, self.jmprel_size);
+        if lazy && self.pltgot != 0 && !plt_relocs.is_empty() {
+            arch::init_got(self.pltgot as *mut usize, idx, _dl_runtime_resolve as usize);
+            // the PLT entries initially point to the PLT, so that we just need to relocate them
+            for rel in plt_relocs {
+                let addr = (self.base + rel.offset) as *mut usize;
+                // safety: we trust the linker that generated the relocation
+                unsafe { addr.write(addr.read() + self.base) };
+            }
+        }
+        else {
+            for rel in plt_relocs {
+                self.apply(objs, idx, rel);
+            }
+        }
+    }
+
+    fn apply(&self, objs: &[DynObject], idx: usize, rel: &elf::Rela) {
+        let addr = (self.base + rel.offset) as *mut usize;
+        let kind = arch::reloc_kind(rel.ty())
+            .unwrap_or_else(|| panic!("dynlink: unsupported relocation type {}", rel.ty()));
+
+        // safety: we trust the linker that generated the relocation
+        unsafe {
+            match kind {
+                RelocKind::None => {},
+                RelocKind::Abs => addr.write(
+                    (self.resolve(objs, idx, rel.sym()) as isize).wrapping_add(rel.addend) as usize,
+                ),
+                RelocKind::Slot => addr.write(self.resolve(objs, idx, rel.sym())),
+                RelocKind::Relative => {
+                    addr.write((self.base as isize).wrapping_add(rel.addend) as usize)
+                },
+                RelocKind::Copy => {
+                    // the program contains the data and the shared object the initial value
+                    let sym = self.sym(rel.sym());
+                    let name = self.sym_name(&sym);
+                    let src = lookup(objs, name, Some(idx))
+                        .unwrap_or_else(|| panic!("dynlink: undefined symbol {}", sym_str(name)));
+                    ptr::copy_nonoverlapping(src as *const u8, addr as *mut u8, sym.size as usize);
+                },
+            }
+        }
+    }
+
+    fn resolve(&self, objs: &[DynObject], idx: usize, sym_idx: usize) -> usize {
+        if sym_idx == 0 {
+            return 0;
+        }
+
+        let sym = self.sym(sym_idx);
+        if sym.bind() == Some(SymBind::Local) {
+            return self.base + sym.value;
+        }
+
+        let name = self.sym_name(&sym);
+        match lookup(objs, name, None) {
+            Some(addr) => addr,
+            // undefined weak symbols are null
+            None if sym.bind() == Some(SymBind::Weak) => 0,
+            None => panic!(
+                "dynlink: undefined symbol {} in object {}",
+                sym_str(name),
+                idx
+            ),
+        }
+    }
+
+    fn defines(&self, idx: usize, name: &[u8]) -> Option<elf::Sym> {
+        let sym = self.sym(idx);
+        let visible = matches!(sym.bind(), Some(SymBind::Global) | Some(SymBind::Weak))
+            && sym.ty() != Some(SymType::Tls)
+            && sym.shndx != elf::SHN_UNDEF;
+        if visible && self.sym_name(&sym) == name {
+            Some(sym)
+        }
+        else {
+            None
+        }
+    }
+
+    fn find(&self, name: &[u8]) -> Option<elf::Sym> {
+        if self.gnu_hash != 0 {
+            self.find_gnu(name)
+        }
+        else if self.hash != 0 {
+            self.find_sysv(name)
+        }
+        else {
+            None
+        }
+    }
+
+    fn find_gnu(&self, name: &[u8]) -> Option<elf::Sym> {
+        let hash = name
+            .iter()
+            .fold(5381u32, |h, c| h.wrapping_mul(33).wrapping_add(*c as u32));
+
+        // safety: we trust the linker that generated the hash table
+        unsafe {
+            let hdr = self.gnu_hash as *const u32;
+            let (nbuckets, sym_off, bloom_size) =
+                (hdr.read(), hdr.add(1).read(), hdr.add(2).read());
+            let buckets = (hdr.add(4) as *const usize).add(bloom_size as usize) as *const u32;
+            let chain = buckets.add(nbuckets as usize);
+
+            let mut idx = buckets.add((hash % nbuckets) as usize).read();
+            if idx < sym_off {
+                return None;
+            }
+
+            loop {
+                let chain_hash = chain.add((idx - sym_off) as usize).read();
+                if (hash | 1) == (chain_hash | 1) {
+                    if let Some(sym) = self.defines(idx as usize, name) {
+                        return Some(sym);
+                    }
+                }
+                // the lowest bit marks the end of the chain
+                if (chain_hash & 1) != 0 {
+                    return None;
+                }
+                idx += 1;
+            }
+        }
+    }
+
+    fn find_sysv(&self, name: &[u8]) -> Option<elf::Sym> {
+        let hash = name.iter().fold(0u32, |h, c| {
+            let h = (h << 4).wrapping_add(*c as u32);
+            let g = h & 0xF000_0000;
+            (h ^ (g >> 24)) & !g
+        });
+
+        // safety: we trust the linker that generated the hash table
+        unsafe {
+            let hdr = self.hash as *const u32;
+            let nbuckets = hdr.read();
+            let buckets = hdr.add(2);
+            let chain = buckets.add(nbuckets as usize);
+
+            let mut idx = buckets.add((hash % nbuckets) as usize).read();
+            while idx != 0 {
+                if let Some(sym) = self.defines(idx as usize, name) {
+                    return Some(sym);
+                }
+                idx = chain.add(idx as usize).read();
+            }
+        }
+        None
+    }
+}
+
+fn sym_str(name: &[u8]) -> &str {
+    core::str::from_utf8(name).unwrap_or("<invalid>")
+}
+
+fn lookup(objs: &[DynObject], name: &[u8], skip: Option<usize>) -> Option<usize> {
+    objs.iter()
+        .enumerate()
+        .filter(|(i, _)| Some(*i) != skip)
+        .find_map(|(_, o)| o.find(name).map(|sym| o.base + sym.value))
+}
+
+/// Prepares the loaded objects and performs the relocations
+///
+/// This needs to be called before anything else during the startup, because the code might
+/// depend on data that is not yet cleared or relocated.
+pub(crate) fn relocate() {
+    let descs = env::get().load_dl_objs();
+    if descs.is_empty() {
+        return;
+    }
+
+    let mut count = 0;
+    {
+        let mut objs = OBJS.borrow_mut();
+        for desc in descs.chunks(DL_OBJ_WORDS) {
+            let (base, dynamic) = (desc[0] as usize, desc[1] as usize);
+            let (clear_addr, clear_len) = (desc[2] as usize, desc[3] as usize);
+
+            // clear the part of the last page that has been mapped from the file, but belongs to
+            // the zero-initialized data of the segment
+            if clear_len > 0 {
+                // safety: the loader has mapped this memory for us
+                unsafe { ptr::write_bytes(clear_addr as *mut u8, 0, clear_len) };
+            }
+
+            if dynamic != 0 {
+                objs[count] = DynObject::parse(base, dynamic);
+                count += 1;
+            }
+        }
+    }
+    COUNT.set(count);
+
+    let lazy = env::boot_var("LD_BIND_NOW")
+        .map(|v| v.is_empty())
+        .unwrap_or(true);
+
+    // relocate the shared objects first, so that the data of shared objects is relocated before
+    // it is copied into the program by copy relocations
+    let objs = OBJS.borrow();
+    for (idx, obj) in objs[0..count].iter().enumerate().rev() {
+        obj.relocate(&objs[0..count], idx, lazy && !obj.bind_now);
+    }
+}
+
+/// Calls the initialization functions of all shared objects
+///
+/// The program itself is initialized by its runtime as usual. The shared objects are initialized
+/// in reverse load order to initialize dependencies first.
+pub(crate) fn init_objects() {
+    let count = COUNT.get();
+    // skip the program at index 0
+    for idx in (1..count).rev() {
+        // copy the object to not hold the borrow while calling the functions, which might trigger
+        // a lazy binding
+        let obj = OBJS.borrow()[idx];
+
+        if obj.init != 0 {
+            // safety: we trust the linker that generated the dynamic section
+            let func: extern "C" fn() = unsafe { mem::transmute(obj.init) };
+            func();
+        }
+
+        let funcs = obj.init_array_size / mem::size_of::<usize>();
+        for i in 0..funcs {
+            // safety: as above
+            let addr = unsafe { (obj.init_array as *const usize).add(i).read() };
+            if addr != 0 && addr != usize::MAX {
+                let func: extern "C" fn() = unsafe { mem::transmute(addr) };
+                func();
+            }
+        }
+    }
+}
+
+/// Binds the function for the given relocation of the given object
+///
+/// This is called by `_dl_runtime_resolve` on the first call of a lazily bound function.
+#[no_mangle]
+pub extern "C" fn __m3_dl_fixup(obj: usize, rel_idx: usize) -> usize {
+    let objs = OBJS.borrow();
+    let objs = &objs[0..COUNT.get()];
+    let dobj = &objs[obj];
+
+    let rel = DynObject::relocs(dobj.jmprel, dobj.jmprel_size)[rel_idx];
+    let addr = dobj.resolve(objs, obj, rel.sym());
+    // safety: we trust the linker that generated the relocation
+    unsafe { ((dobj.base + rel.offset) as *mut usize).write(addr) };
+    addr
+}
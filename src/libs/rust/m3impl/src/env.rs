@@ -27,6 +27,8 @@ use crate::client::{Pager, ResMng};
 use crate::col::Vec;
 use crate::com::{MemGate, SendGate};
 use crate::errors::Error;
+use crate::io::log::{LogCtrl, LogFormat};
+use crate::io::LogFlags;
 use crate::kif::{self, TileDesc};
 use crate::mem::{self, GlobOff, VirtAddr};
 use crate::serialize::M3Deserializer;
@@ -37,6 +39,21 @@ use crate::vfs::{FileTable, MountTable};
 
 pub use base::env::*;
 
+/// The environment variable that enables core dumps and holds the path of the core file
+pub const COREDUMP_VAR: &str = "M3_COREDUMP";
+
+/// The environment variable that enables the GDB stub and holds the connection to gdb (`tcp:<port>`
+/// or the path of a file)
+pub const GDBSTUB_VAR: &str = "M3_GDBSTUB";
+
+/// The environment variable that enables the watchdog and holds the interval for heartbeats in
+/// microseconds
+pub const WATCHDOG_VAR: &str = "M3_WATCHDOG";
+
+/// The prefix of the environment variables that hold the information about assigned devices (see
+/// [`DeviceInfo`](crate::devinfo::DeviceInfo))
+pub const DEV_VAR_PREFIX: &str = "M3_DEV_";
+
 /// Writes the given arguments to `mem` at given address
 ///
 /// This is intended [`ChildActivity`](`crate::tiles::ChildActivity`) and other components that want
@@ -121,10 +138,18 @@ impl Env {
         self.base.sp = sp.as_raw();
     }
 
+    pub fn entry(&self) -> VirtAddr {
+        VirtAddr::new(self.base.entry)
+    }
+
     pub fn set_entry(&mut self, entry: VirtAddr) {
         self.base.entry = entry.as_raw();
     }
 
+    pub fn heap_size(&self) -> usize {
+        self.base.heap_size as usize
+    }
+
     pub fn set_heap_size(&mut self, size: usize) {
         self.base.heap_size = size as u64;
     }
@@ -209,6 +234,21 @@ impl Env {
         }
     }
 
+    pub fn load_dl_objs(&self) -> &[u64] {
+        if self.base.dl_objs_len != 0 {
+            // safety: we trust our loader
+            unsafe {
+                util::slice_for(
+                    self.base.dl_objs_addr as *const u64,
+                    self.base.dl_objs_len as usize / mem::size_of::<u64>(),
+                )
+            }
+        }
+        else {
+            &[]
+        }
+    }
+
     pub fn tile_ids(&self) -> &[u64] {
         &self.base.boot.raw_tile_ids[0..self.base.boot.raw_tile_count as usize]
     }
@@ -238,6 +278,14 @@ impl Env {
         self.base.closure = addr.as_raw();
     }
 
+    pub fn cloned(&self) -> bool {
+        self.base.cloned != 0
+    }
+
+    pub fn set_cloned(&mut self, cloned: bool) {
+        self.base.cloned = cloned as u64;
+    }
+
     pub fn set_first_sel(&mut self, sel: Selector) {
         self.base.first_sel = sel;
     }
@@ -261,12 +309,42 @@ impl Env {
         self.base.data_len = len as u64;
     }
 
+    pub fn set_dl_objs(&mut self, addr: VirtAddr, len: usize) {
+        self.base.dl_objs_addr = addr.as_raw();
+        self.base.dl_objs_len = len as u64;
+    }
+
+    /// Returns the offset of the log settings within the environment
+    pub fn log_ctrl_offset() -> usize {
+        let env = Self::default();
+        ptr::addr_of!(env.base.log_ctrl) as usize - ptr::addr_of!(env) as usize
+    }
+
     pub fn set_pager(&mut self, pager: &Pager) {
         self.base.pager_sess = pager.sess_sel();
         self.base.pager_sgate = pager.sgate_sel();
     }
 }
 
+/// Changes the log flags and format of the running activity with selector `act`.
+///
+/// The settings are written to the environment of the activity, from which its logger reads them
+/// for every log statement. Thus, the change takes effect immediately. Note that only activities
+/// using this library support this.
+pub fn set_log(act: Selector, flags: LogFlags, format: LogFormat) -> Result<(), Error> {
+    let mem = MemGate::new_foreign(act, cfg::ENV_START, cfg::ENV_SIZE as GlobOff, kif::Perm::W)?;
+    mem.write_obj(
+        &LogCtrl::new(flags, format),
+        Env::log_ctrl_offset() as GlobOff,
+    )
+}
+
+/// Returns a pointer to the log settings in the environment, which can be changed by our parent
+pub(crate) fn log_ctrl() -> *mut LogCtrl {
+    // safety: we trust our loader
+    unsafe { ptr::addr_of_mut!((*cfg::ENV_START.as_mut_ptr::<Env>()).base.log_ctrl) }
+}
+
 pub fn get() -> &'static Env {
     // safety: we trust our loader
     unsafe { &*(cfg::ENV_START.as_ptr()) }
@@ -280,6 +358,20 @@ extern "Rust" {
     fn main() -> Result<(), Error>;
 }
 
+/// Forgets the state of libm3 that has been inherited from the parent activity.
+///
+/// If the address space has been cloned from the parent (see
+/// [`ChildActivity::run_clone`](crate::tiles::ChildActivity::run_clone)), the static variables
+/// still contain the parent's objects. These refer to the capabilities of the parent and must
+/// therefore not be dropped. As the heap is a copy of the parent's heap, we can simply leak them.
+fn forget_inherited() {
+    crate::io::forget_inherited();
+    crate::tiles::forget_inherited();
+    crate::com::forget_inherited();
+    crate::syscalls::forget_inherited();
+    crate::cap::forget_inherited();
+}
+
 pub fn init() {
     #[cfg(feature = "linux")]
     crate::linux::init();
@@ -303,10 +395,31 @@ pub fn deinit() {
 
 #[no_mangle]
 pub extern "C" fn env_run() {
+    // a cloned address space has already been relocated by the parent
+    #[cfg(not(feature = "linux"))]
+    if !crate::env::get().cloned() {
+        crate::dynlink::relocate();
+    }
     unsafe {
         __m3_init_libc(0, ptr::null(), ptr::null(), false);
     }
+    if crate::env::get().cloned() {
+        forget_inherited();
+    }
     init();
+    #[cfg(not(feature = "linux"))]
+    if !crate::env::get().cloned() {
+        crate::dynlink::init_objects();
+    }
+    #[cfg(all(
+        not(feature = "linux"),
+        any(target_arch = "x86_64", target_arch = "riscv64")
+    ))]
+    {
+        crate::coredump::init();
+        // waits for gdb, if requested, and takes over the crash handler
+        crate::gdbstub::init();
+    }
 
     let res = if let Some(cl) = crate::env::get().load_closure() {
         cl()
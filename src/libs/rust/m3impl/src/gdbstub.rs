@@ -0,0 +1,531 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! A stub for the GDB remote serial protocol to debug activities on the target
+//!
+//! If the environment variable `M3_GDBSTUB` is set (for example, via the `gdbstub` attribute of an
+//! app in the resource manager's config), the activity waits for a connection from gdb during
+//! startup and stops afterwards before `main` is called. The value specifies the connection: with
+//! `tcp:<port>`, the activity listens on the given TCP port of the network service `net`;
+//! otherwise, the value is the path of a file (e.g., a serial line) that is used for the
+//! communication. On the host, gdb connects to the stub via `target remote <host>:<port>` or
+//! `target remote <device>`, respectively.
+//!
+//! The stub is built on the crash handler of TileMux: breakpoints, single steps, and crashes let
+//! TileMux call the stub, which talks to gdb until it continues the activity. Software breakpoints
+//! are supported by mapping the program writable (see `tiles::loader`), single stepping is done
+//! via the trap flag on x86_64 and by gdb itself on RISC-V. Registers and the memory of the program,
+//! its heap, its stack, and its environment can be read and written.
+
+use core::fmt::Write as _;
+
+use crate::boxed::Box;
+use crate::cell::{LazyStaticRefCell, StaticCell};
+use crate::cfg;
+use crate::client::Network;
+use crate::col::{String, Vec};
+use crate::coredump;
+use crate::env;
+use crate::errors::{Code, Error};
+use crate::format;
+use crate::io::{LogFlags, Read, Write};
+use crate::log;
+use crate::mem::VirtAddr;
+use crate::net::{Port, StreamSocket, StreamSocketArgs, TcpSocket};
+use crate::tiles::OwnActivity;
+use crate::tmif;
+use crate::util::math;
+use crate::vec;
+use crate::vfs::{File, FileRef, OpenFlags, VFS};
+
+const HANDLER_STACK_SIZE: usize = cfg::STACK_SIZE / 4;
+const PACKET_SIZE: usize = 4096;
+
+extern "C" {
+    static _text_start: u8;
+    static _text_end: u8;
+    static _data_start: u8;
+    static _bss_end: u8;
+}
+
+#[cfg(target_arch = "x86_64")]
+mod arch {
+    const RFLAGS_TF: usize = 1 << 8;
+
+    // the index in `tmif::CrashState::regs` and the size of the registers in gdb's order: rax, rbx,
+    // rcx, rdx, rsi, rdi, rbp, rsp, r8 to r15, rip, eflags, cs, ss, ds, es, fs, and gs
+    pub const REGS: [(Option<usize>, usize); 24] = [
+        (Some(10), 8),
+        (Some(5), 8),
+        (Some(11), 8),
+        (Some(12), 8),
+        (Some(13), 8),
+        (Some(14), 8),
+        (Some(4), 8),
+        (Some(19), 8),
+        (Some(9), 8),
+        (Some(8), 8),
+        (Some(7), 8),
+        (Some(6), 8),
+        (Some(3), 8),
+        (Some(2), 8),
+        (Some(1), 8),
+        (Some(0), 8),
+        (Some(16), 8),
+        (Some(18), 4),
+        (Some(17), 4),
+        (Some(20), 4),
+        (Some(23), 4),
+        (Some(24), 4),
+        (Some(25), 4),
+        (Some(26), 4),
+    ];
+
+    // gdb has no software single stepping for x86_64
+    pub const SINGLE_STEP: bool = true;
+
+    #[inline(always)]
+    pub fn breakpoint() {
+        // safety: the breakpoint traps into TileMux, which calls our handler
+        unsafe { core::arch::asm!("int3") };
+    }
+
+    pub fn stopped(state: &mut crate::tmif::CrashState, _initial: bool) {
+        // rip already points behind the int3 instruction, which is what gdb expects
+        state.regs[18] &= !RFLAGS_TF;
+    }
+
+    pub fn set_single_step(state: &mut crate::tmif::CrashState) {
+        state.regs[18] |= RFLAGS_TF;
+    }
+
+    pub fn flush_icache() {
+    }
+}
+
+#[cfg(target_arch = "riscv64")]
+mod arch {
+    // the index in `tmif::CrashState::regs` and the size of the registers in gdb's order: x0 to x31
+    // and pc. x0 is always zero and therefore not part of the state
+    pub const REGS: [(Option<usize>, usize); 33] = {
+        let mut regs = [(None, 8); 33];
+        let mut i = 1;
+        while i < 32 {
+            regs[i] = (Some(i), 8);
+            i += 1;
+        }
+        regs[32] = (Some(0), 8);
+        regs
+    };
+
+    // let gdb do the single stepping via breakpoints
+    pub const SINGLE_STEP: bool = false;
+
+    #[inline(always)]
+    pub fn breakpoint() {
+        // safety: the breakpoint traps into TileMux, which calls our handler. use the
+        // uncompressed ebreak to know its size in `stopped`
+        unsafe { core::arch::asm!(".4byte 0x00100073") };
+    }
+
+    pub fn stopped(state: &mut crate::tmif::CrashState, initial: bool) {
+        // in contrast to breakpoints that gdb inserted, we need to skip our own breakpoint
+        if initial {
+            state.regs[0] += 4;
+        }
+    }
+
+    pub fn set_single_step(_state: &mut crate::tmif::CrashState) {
+        // not used, because SINGLE_STEP is false
+    }
+
+    pub fn flush_icache() {
+        // gdb might have written breakpoints into the code
+        unsafe { core::arch::asm!("fence.i") };
+    }
+}
+
+/// The connection to gdb
+struct Conn {
+    file: FileRef<dyn File>,
+    buf: [u8; 256],
+    pos: usize,
+    len: usize,
+}
+
+impl Conn {
+    fn getc(&mut self) -> Result<u8, Error> {
+        while self.pos == self.len {
+            self.len = self.file.read(&mut self.buf)?;
+            self.pos = 0;
+            if self.len == 0 {
+                return Err(Error::new(Code::EndOfFile));
+            }
+        }
+        self.pos += 1;
+        Ok(self.buf[self.pos - 1])
+    }
+
+    fn recv_packet(&mut self, packet: &mut Vec<u8>) -> Result<(), Error> {
+        loop {
+            // skip everything before the packet start, including acks and interrupt requests
+            while self.getc()? != b'$' {}
+
+            packet.clear();
+            let mut sum = 0u8;
+            loop {
+                match self.getc()? {
+                    b'#' => break,
+                    c => {
+                        sum = sum.wrapping_add(c);
+                        packet.push(c);
+                    },
+                }
+            }
+
+            let expected = parse_hex(&[self.getc()?, self.getc()?]);
+            if expected == Some(sum as usize) {
+                return self.file.write_all(b"+");
+            }
+            self.file.write_all(b"-")?;
+        }
+    }
+
+    fn send_packet(&mut self, data: &str) -> Result<(), Error> {
+        let sum = data.bytes().fold(0u8, |sum, b| sum.wrapping_add(b));
+        let mut packet = String::with_capacity(data.len() + 4);
+        write!(packet, "${}#{:02x}", data, sum).unwrap();
+
+        loop {
+            self.file.write_all(packet.as_bytes())?;
+            match self.getc()? {
+                b'+' => return Ok(()),
+                // resend the packet on a nack or anything unexpected
+                _ => continue,
+            }
+        }
+    }
+}
+
+/// What to do after gdb let the activity continue
+enum Action {
+    Continue,
+    Step,
+    Kill,
+}
+
+static CONN: LazyStaticRefCell<Conn> = LazyStaticRefCell::default();
+static INITIAL: StaticCell<bool> = StaticCell::new(false);
+static RUNNING: StaticCell<bool> = StaticCell::new(false);
+
+/// Waits for gdb if requested via `M3_GDBSTUB` and stops the activity afterwards
+pub(crate) fn init() {
+    let spec = match env::var(env::GDBSTUB_VAR) {
+        Some(spec) => spec,
+        None => return,
+    };
+
+    log!(LogFlags::Info, "Waiting for gdb on {}", spec);
+    let file = match connect(&spec) {
+        Ok(file) => file,
+        Err(e) => {
+            log!(
+                LogFlags::Error,
+                "Unable to connect to gdb via {}: {:?}",
+                spec,
+                e
+            );
+            return;
+        },
+    };
+    CONN.set(Conn {
+        file,
+        buf: [0; 256],
+        pos: 0,
+        len: 0,
+    });
+
+    // replaces the handler for core dumps, if any
+    let state = Box::leak(Box::<tmif::CrashState>::default());
+    let stack = vec![0u8; HANDLER_STACK_SIZE].leak();
+    let stack_top = VirtAddr::from(stack.as_ptr()) + stack.len();
+    if let Err(e) = tmif::reg_crash_handler(
+        debug_handler as usize,
+        stack_top,
+        VirtAddr::from(state as *const tmif::CrashState),
+    ) {
+        log!(LogFlags::Error, "Unable to register debug handler: {:?}", e);
+        return;
+    }
+
+    // stop here to let gdb set breakpoints etc. before main is called
+    INITIAL.set(true);
+    arch::breakpoint();
+}
+
+fn connect(spec: &str) -> Result<FileRef<dyn File>, Error> {
+    if let Some(port) = spec.strip_prefix("tcp:") {
+        let port = port
+            .parse::<Port>()
+            .map_err(|_| Error::new(Code::InvArgs))?;
+        let net = Network::new("net")?;
+        let mut socket = TcpSocket::new(StreamSocketArgs::new(net))?;
+        socket.listen(port)?;
+        socket.accept()?;
+        Ok(socket.into_generic())
+    }
+    else {
+        VFS::open(spec, OpenFlags::RW).map(|f| f.into_generic())
+    }
+}
+
+extern "C" fn debug_handler(state: &mut tmif::CrashState) -> ! {
+    arch::stopped(state, INITIAL.replace(false));
+
+    match serve(state) {
+        Ok(Action::Kill) => OwnActivity::exit_with(Code::Unspecified),
+        Ok(action) => {
+            if let Action::Step = action {
+                arch::set_single_step(state);
+            }
+            arch::flush_icache();
+            let res = tmif::resume(VirtAddr::from(state as *const tmif::CrashState));
+            log!(LogFlags::Error, "Unable to resume activity: {:?}", res);
+        },
+        Err(e) => log!(LogFlags::Error, "Connection to gdb failed: {:?}", e),
+    }
+
+    OwnActivity::exit_with(Code::Unspecified);
+}
+
+fn serve(state: &mut tmif::CrashState) -> Result<Action, Error> {
+    let mut conn = CONN.borrow_mut();
+    let sig = coredump::arch::signal(state);
+
+    // gdb waits for the stop reply if it let the activity continue before
+    if RUNNING.replace(false) {
+        conn.send_packet(&format!("S{:02x}", sig))?;
+    }
+
+    let mut packet = Vec::with_capacity(PACKET_SIZE);
+    let mut reply = String::new();
+    loop {
+        conn.recv_packet(&mut packet)?;
+        reply.clear();
+
+        // empty packets get an empty reply like unsupported commands
+        let (cmd, args) = match packet.split_first() {
+            Some((cmd, args)) => (*cmd, args),
+            None => (0, &[][..]),
+        };
+        match cmd {
+            b'?' => write!(reply, "S{:02x}", sig).unwrap(),
+            b'g' => read_regs(state, &mut reply),
+            b'G' => match write_regs(state, args) {
+                Some(_) => reply.push_str("OK"),
+                None => reply.push_str("E01"),
+            },
+            b'm' => {
+                if read_mem(args, &mut reply).is_none() {
+                    reply.clear();
+                    reply.push_str("E14");
+                }
+            },
+            b'M' => match write_mem(args) {
+                Some(_) => reply.push_str("OK"),
+                None => reply.push_str("E14"),
+            },
+            b's' | b'v' if !arch::SINGLE_STEP && is_step(cmd, args) => {},
+            b'c' | b's' => {
+                RUNNING.set(true);
+                return Ok(if cmd == b'c' {
+                    Action::Continue
+                }
+                else {
+                    Action::Step
+                });
+            },
+            b'v' if args.starts_with(b"Cont?") => match arch::SINGLE_STEP {
+                true => reply.push_str("vCont;c;C;s;S"),
+                false => reply.push_str("vCont;c;C"),
+            },
+            b'v' if args.starts_with(b"Cont;") => {
+                RUNNING.set(true);
+                return Ok(match is_step(cmd, args) {
+                    true => Action::Step,
+                    false => Action::Continue,
+                });
+            },
+            b'k' => return Ok(Action::Kill),
+            b'D' => {
+                conn.send_packet("OK")?;
+                return Ok(Action::Continue);
+            },
+            // we only have a single thread
+            b'H' | b'T' => reply.push_str("OK"),
+            b'q' if args.starts_with(b"Supported") => {
+                write!(reply, "PacketSize={:x}", PACKET_SIZE).unwrap()
+            },
+            b'q' if args.starts_with(b"Attached") => reply.push('1'),
+            b'q' if args.starts_with(b"fThreadInfo") => reply.push_str("m1"),
+            b'q' if args.starts_with(b"sThreadInfo") => reply.push('l'),
+            b'q' if args.starts_with(b"C") => reply.push_str("QC1"),
+            // everything else (e.g., breakpoint packets) is not supported, which gdb is told by an
+            // empty reply
+            _ => {},
+        }
+
+        conn.send_packet(&reply)?;
+    }
+}
+
+/// Returns true if the given packet requests a single step (via `s` or `vCont;s`)
+fn is_step(cmd: u8, args: &[u8]) -> bool {
+    match cmd {
+        b's' => true,
+        _ => matches!(args.get(5), Some(b's') | Some(b'S')) && args.starts_with(b"Cont;"),
+    }
+}
+
+fn read_regs(state: &tmif::CrashState, reply: &mut String) {
+    for (idx, size) in arch::REGS {
+        let val = idx.map(|i| state.regs[i]).unwrap_or(0);
+        encode_hex(reply, &val.to_le_bytes()[0..size]);
+    }
+}
+
+fn write_regs(state: &mut tmif::CrashState, args: &[u8]) -> Option<()> {
+    let mut pos = 0;
+    for (idx, size) in arch::REGS {
+        // gdb might send fewer registers than we have
+        let hex = match args.get(pos..pos + size * 2) {
+            Some(hex) => hex,
+            None => break,
+        };
+        pos += size * 2;
+
+        let mut bytes = [0u8; 8];
+        decode_hex(hex, &mut bytes[0..size])?;
+        if let Some(i) = idx {
+            // keep the upper bits of registers that gdb considers smaller (e.g., eflags)
+            let mask = if size == 8 {
+                usize::MAX
+            }
+            else {
+                (1 << (size * 8)) - 1
+            };
+            state.regs[i] = (state.regs[i] & !mask) | usize::from_le_bytes(bytes);
+        }
+    }
+    Some(())
+}
+
+/// Parses the address and length of memory requests ("addr,length")
+fn parse_range(args: &[u8]) -> Option<(usize, usize)> {
+    let mut parts = args.splitn(2, |b| *b == b',');
+    let addr = parse_hex(parts.next()?)?;
+    let len = parse_hex(parts.next()?)?;
+    match is_accessible(addr, len) {
+        true => Some((addr, len)),
+        false => None,
+    }
+}
+
+fn read_mem(args: &[u8], reply: &mut String) -> Option<()> {
+    let (addr, len) = parse_range(args)?;
+    // safety: we've checked that the memory belongs to our program
+    let data = unsafe { core::slice::from_raw_parts(addr as *const u8, len) };
+    encode_hex(reply, data);
+    Some(())
+}
+
+fn write_mem(args: &[u8]) -> Option<()> {
+    let mut parts = args.splitn(2, |b| *b == b':');
+    let (addr, len) = parse_range(parts.next()?)?;
+    let data = parts.next()?;
+    if data.len() != len * 2 {
+        return None;
+    }
+
+    // safety: we've checked that the memory belongs to our program, which has been mapped
+    // writable if the stub is enabled
+    let mem = unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, len) };
+    decode_hex(data, mem)
+}
+
+/// Returns true if the given memory belongs to the program, its heap, its stack, or its
+/// environment
+///
+/// Shared objects are not supported yet.
+fn is_accessible(addr: usize, len: usize) -> bool {
+    let end = match addr.checked_add(len) {
+        Some(end) => end,
+        None => return false,
+    };
+
+    // safety: the symbols are defined by the linker script
+    let (text_start, text_end, data_start, bss_end) = unsafe {
+        (
+            &_text_start as *const u8 as usize,
+            &_text_end as *const u8 as usize,
+            &_data_start as *const u8 as usize,
+            &_bss_end as *const u8 as usize,
+        )
+    };
+    // the heap directly follows the bss segment (see tiles::loader)
+    let heap_end = math::round_up(bss_end, cfg::PAGE_SIZE) + env::get().heap_size();
+    let (stack_addr, stack_size) = env::get().tile_desc().stack_space();
+    let env_start = cfg::ENV_START.as_local();
+
+    [
+        (text_start, text_end),
+        (data_start, heap_end),
+        (stack_addr.as_local(), stack_addr.as_local() + stack_size),
+        (env_start, env_start + cfg::ENV_SIZE),
+    ]
+    .iter()
+    .any(|(start, limit)| addr >= *start && end <= *limit)
+}
+
+fn parse_hex(hex: &[u8]) -> Option<usize> {
+    if hex.is_empty() {
+        return None;
+    }
+    hex.iter().try_fold(0usize, |val, c| {
+        val.checked_mul(16)?.checked_add(hex_digit(*c)? as usize)
+    })
+}
+
+fn hex_digit(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+fn decode_hex(hex: &[u8], out: &mut [u8]) -> Option<()> {
+    for (o, pair) in out.iter_mut().zip(hex.chunks_exact(2)) {
+        *o = (hex_digit(pair[0])? << 4) | hex_digit(pair[1])?;
+    }
+    Some(())
+}
+
+fn encode_hex(out: &mut String, data: &[u8]) {
+    for b in data {
+        write!(out, "{:02x}", b).unwrap();
+    }
+}
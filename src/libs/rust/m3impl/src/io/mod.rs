@@ -23,7 +23,7 @@ mod std;
 
 pub use self::std::{stderr, stdin, stdout};
 pub use self::std::{STDERR_FILENO, STDIN_FILENO, STDOUT_FILENO};
-pub use base::io::{log_bytes, log_slice, read_object, LogFlags, Read, Serial, Write};
+pub use base::io::{log, log_bytes, log_slice, read_object, LogFlags, Read, Serial, Write};
 
 /// Uses stdout to print `$fmt` with given arguments
 #[macro_export]
@@ -47,9 +47,16 @@ pub(crate) fn init() {
         crate::env::get().tile_id(),
         crate::env::args().next().unwrap_or("Unknown"),
     );
+    // let our parent change the log settings at runtime (see `ChildActivity::set_log`)
+    // safety: the environment stays mapped for our whole lifetime
+    unsafe { log::place_ctrl(crate::env::log_ctrl()) };
     std::init();
 }
 
 pub(crate) fn deinit() {
     std::deinit();
 }
+
+pub(crate) fn forget_inherited() {
+    std::forget_inherited();
+}
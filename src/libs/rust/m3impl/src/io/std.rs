@@ -19,6 +19,7 @@
 use crate::boxed::Box;
 use crate::cell::{LazyStaticRefCell, RefMut};
 use crate::io::Serial;
+use crate::mem;
 use crate::tiles::Activity;
 use crate::vfs::{BufReader, BufWriter, Fd, File, FileRef};
 
@@ -58,6 +59,12 @@ pub(crate) fn init() {
     STDERR.set(BufWriter::new(FileRef::new_owned(STDERR_FILENO)));
 }
 
+pub(crate) fn forget_inherited() {
+    mem::forget(STDIN.unset());
+    mem::forget(STDOUT.unset());
+    mem::forget(STDERR.unset());
+}
+
 pub(crate) fn deinit() {
     STDIN.unset();
     STDOUT.unset();
@@ -30,21 +30,37 @@ pub mod net;
 
 pub use base::{
     backtrace, borrow, boxed, build_vmsg, cell, cfg, col, cpu, crypto, elf, errors, format,
-    function, impl_boxitem, kif, libc, log, mem, quota, rc, serde, serialize, sync, tcu, time,
-    tmif, util, vec,
+    function, impl_boxitem, kif, libc, log, math, mem, quota, rc, serde, serialize, tcu, time,
+    tmif, trace, util, vec,
 };
 
 pub mod cap;
 pub mod client;
 #[cfg(not(feature = "linux"))]
 pub mod compat;
+#[cfg(all(
+    not(feature = "linux"),
+    any(target_arch = "x86_64", target_arch = "riscv64")
+))]
+pub mod coredump;
+pub mod devinfo;
+#[cfg(not(feature = "linux"))]
+mod dynlink;
 pub mod env;
+#[cfg(all(
+    not(feature = "linux"),
+    any(target_arch = "x86_64", target_arch = "riscv64")
+))]
+mod gdbstub;
 pub mod server;
+pub mod sync;
 pub mod syscalls;
 #[macro_use]
 pub mod test;
 pub mod tiles;
+pub mod timer;
 pub mod vfs;
+pub mod watchdog;
 
 #[cfg(feature = "linux")]
 pub use base::linux;
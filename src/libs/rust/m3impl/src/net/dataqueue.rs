@@ -34,7 +34,7 @@ impl Item {
     }
 
     fn addr(&self) -> IpAddr {
-        IpAddr(self.msg().addr as u32)
+        IpAddr::from_raw(self.msg().addr)
     }
 
     fn port(&self) -> Port {
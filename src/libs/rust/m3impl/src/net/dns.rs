@@ -16,16 +16,17 @@
 use core::mem;
 use core::str::FromStr;
 
-use base::col::ToString;
+use base::col::{BTreeMap, String, ToString, Vec};
 use base::errors::{Code, Error, VerboseError};
 use base::rc::Rc;
-use base::time::TimeDuration;
+use base::time::{TimeDuration, TimeInstant};
 use base::util::random::LinearCongruentialGenerator;
 use base::vec;
 
 use crate::client::Network;
 use crate::net::{DGramSocket, DgramSocketArgs, Endpoint, IpAddr, Port, Socket, UdpSocket};
-use crate::vfs::{File, FileEvent, FileWaiter};
+use crate::timer::{self, TimerId};
+use crate::vfs::{Fd, File, FileEvent, FileRef, FileWaiter};
 
 // based on http://tools.ietf.org/html/rfc1035
 
@@ -33,6 +34,7 @@ const DNS_RECURSION_DESIRED: u16 = 0x100;
 const DNS_PORT: Port = 53;
 
 const TYPE_A: u16 = 1; // a host address
+const TYPE_AAAA: u16 = 28; // an IPv6 host address
 const CLASS_IN: u16 = 1; // the internet
 
 #[repr(C, packed)]
@@ -51,31 +53,145 @@ struct DNSQuestionEnd {
     cls: u16,
 }
 
+// the part of an answer after the name, followed by `length` bytes of data
 #[repr(C, packed)]
-struct DNSAnswer {
-    name: u16,
+struct DNSAnswerEnd {
     ty: u16,
     cls: u16,
     ttl: u32,
     length: u16,
-    // this is the data part of the answer, where we currently only support IPv4 addresses
-    ip_addr: u32,
+}
+
+/// The maximum time that we keep addresses in the cache, independent of the TTL of the answer
+const MAX_TTL: u32 = 24 * 60 * 60;
+/// The time that we remember that a name could not be resolved
+const NEG_TTL: u32 = 60;
+/// The maximum number of cached names
+const CACHE_SIZE: usize = 32;
+
+const RCODE_MASK: u16 = 0xF;
+const RCODE_NO_ERROR: u16 = 0;
+const RCODE_NAME_ERROR: u16 = 3;
+
+// the interpretation of a response from a nameserver
+enum Response {
+    Found(IpAddr, u32),
+    NotFound,
+    // the server could not answer the query, so that we ask the next one
+    Failed,
+    // the response does not belong to our query or is malformed
+    Invalid,
+}
+
+struct CacheEntry {
+    addr: Option<IpAddr>,
+    expires: TimeInstant,
+}
+
+enum QueryState {
+    Pending {
+        sock: FileRef<UdpSocket>,
+        txid: u16,
+        server: usize,
+        // wakes us up if the current nameserver does not answer in time
+        timer: TimerId,
+    },
+    Done(Result<IpAddr, Code>),
+}
+
+/// A DNS query that is resolved asynchronously
+///
+/// Queries are started via [`DNS::resolve_async`] or [`DNS::resolve_v6_async`] and driven by
+/// [`DNS::poll`]. To wait for the response without blocking on a single query, the file
+/// descriptor of the query can be added to a [`FileWaiter`] with [`FileEvent::INPUT`].
+pub struct DnsQuery {
+    name: String,
+    qtype: u16,
+    servers: Vec<IpAddr>,
+    timeout: TimeDuration,
+    state: QueryState,
+}
+
+impl DnsQuery {
+    /// Returns the name that is resolved
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns true if the query is finished, that is, [`DNS::poll`] will not block
+    pub fn is_done(&self) -> bool {
+        matches!(self.state, QueryState::Done(_))
+    }
+
+    /// Returns the file descriptor of the socket that receives the response or `None` if the query
+    /// is already finished
+    pub fn fd(&self) -> Option<Fd> {
+        match &self.state {
+            QueryState::Pending { sock, .. } => Some(sock.fd()),
+            QueryState::Done(_) => None,
+        }
+    }
+
+    /// Returns the time until the current nameserver is considered unresponsive and the query
+    /// should be polled again
+    pub fn remaining(&self) -> TimeDuration {
+        match &self.state {
+            QueryState::Pending { timer, .. } => timer::remaining(*timer).unwrap_or_default(),
+            QueryState::Done(_) => TimeDuration::ZERO,
+        }
+    }
+
+    fn finish(&mut self, res: Result<IpAddr, Code>) {
+        if let QueryState::Pending { timer, .. } = self.state {
+            timer::cancel(timer);
+        }
+        self.state = QueryState::Done(res);
+    }
+}
+
+impl Drop for DnsQuery {
+    fn drop(&mut self) {
+        if let QueryState::Pending { timer, .. } = self.state {
+            timer::cancel(timer);
+        }
+    }
 }
 
 /// Domain name service resolver
 ///
-/// The DNS type uses [`Network`] to resolve host names to IP addresses.
+/// The DNS type uses [`Network`] to resolve host names to IP addresses. The configured nameservers
+/// are asked in order until one of them answers; if none is configured, the nameserver of the
+/// network service is used. Successful lookups are cached according to the TTL of the answer and
+/// failed lookups for a short amount of time.
 #[derive(Default)]
 pub struct DNS {
-    nameserver: IpAddr,
+    nameservers: Vec<IpAddr>,
+    cache: BTreeMap<(String, u16), CacheEntry>,
     random: LinearCongruentialGenerator,
 }
 
 impl DNS {
+    /// Adds the given nameserver to the list of nameservers that are asked in order
+    pub fn add_nameserver(&mut self, addr: IpAddr) {
+        if !self.nameservers.contains(&addr) {
+            self.nameservers.push(addr);
+        }
+    }
+
+    /// Returns the configured nameservers
+    pub fn nameservers(&self) -> &[IpAddr] {
+        &self.nameservers
+    }
+
+    /// Removes all cached addresses
+    pub fn flush_cache(&mut self) {
+        self.cache.clear();
+    }
+
     /// Translates the given name into an IP address. If the name is already an IP address, it will
     /// simply be converted into an [`IpAddr`] object. Otherwise, the name will be solved via DNS.
     ///
-    /// The timeout specifies the maximum time to wait for the DNS response.
+    /// The timeout specifies the maximum time to wait for the response of each nameserver.
     pub fn get_addr(
         &mut self,
         netmng: Rc<Network>,
@@ -94,38 +210,236 @@ impl DNS {
     /// name. Use [`get_addr`](Self::get_addr) if you don't know whether it's a hostname or an IP
     /// address.
     ///
-    /// The timeout specifies the maximum time to wait for the DNS response.
+    /// The timeout specifies the maximum time to wait for the response of each nameserver.
     pub fn resolve(
         &mut self,
         netmng: Rc<Network>,
         name: &str,
         timeout: TimeDuration,
     ) -> Result<IpAddr, VerboseError> {
-        if self.nameserver == IpAddr::unspecified() {
-            self.nameserver = netmng.nameserver()?;
+        let mut query = self.start(netmng, name, timeout, TYPE_A)?;
+        self.wait(&mut query)
+    }
+
+    /// Resolves the given hostname to an IPv6 address. Like [`resolve`](Self::resolve), but asks
+    /// for the IPv6 address of the host.
+    ///
+    /// The timeout specifies the maximum time to wait for the response of each nameserver.
+    pub fn resolve_v6(
+        &mut self,
+        netmng: Rc<Network>,
+        name: &str,
+        timeout: TimeDuration,
+    ) -> Result<IpAddr, VerboseError> {
+        let mut query = self.start(netmng, name, timeout, TYPE_AAAA)?;
+        self.wait(&mut query)
+    }
+
+    /// Starts to resolve the given hostname to an IP address without waiting for the response.
+    ///
+    /// If the name is cached, the returned query is already finished. Otherwise, the request is
+    /// sent to the first nameserver and the query needs to be driven by [`poll`](Self::poll).
+    pub fn resolve_async(
+        &mut self,
+        netmng: Rc<Network>,
+        name: &str,
+        timeout: TimeDuration,
+    ) -> Result<DnsQuery, VerboseError> {
+        self.start(netmng, name, timeout, TYPE_A)
+    }
+
+    /// Like [`resolve_async`](Self::resolve_async), but asks for the IPv6 address of the host.
+    pub fn resolve_v6_async(
+        &mut self,
+        netmng: Rc<Network>,
+        name: &str,
+        timeout: TimeDuration,
+    ) -> Result<DnsQuery, VerboseError> {
+        self.start(netmng, name, timeout, TYPE_AAAA)
+    }
+
+    /// Checks whether the response for the given query has arrived.
+    ///
+    /// Returns `Ok(None)` if the query is still pending. If the current nameserver did not answer
+    /// within the timeout or could not answer the query, the next nameserver is asked.
+    pub fn poll(&mut self, query: &mut DnsQuery) -> Result<Option<IpAddr>, VerboseError> {
+        let mut buf = vec![0u8; 1024];
+        loop {
+            let (sock, txid, server, timer) = match &mut query.state {
+                QueryState::Done(res) => {
+                    return match *res {
+                        Ok(addr) => Ok(Some(addr)),
+                        Err(code) => Err(Self::query_error(code, query.qtype)),
+                    };
+                },
+                QueryState::Pending {
+                    sock,
+                    txid,
+                    server,
+                    timer,
+                } => (sock, *txid, *server, *timer),
+            };
+
+            let res = match sock.recv(&mut buf) {
+                Ok(len) => Self::handle_response(&buf[0..len], txid, query.qtype),
+                Err(e) if e.code() == Code::WouldBlock => {
+                    if timer::is_pending(timer) {
+                        return Ok(None);
+                    }
+                    Response::Failed
+                },
+                Err(e) => return Err(e.into()),
+            };
+
+            match res {
+                Response::Found(addr, ttl) => {
+                    self.insert(&query.name, query.qtype, Some(addr), ttl.min(MAX_TTL));
+                    query.finish(Ok(addr));
+                },
+                Response::NotFound => {
+                    self.insert(&query.name, query.qtype, None, NEG_TTL);
+                    query.finish(Err(Code::NotFound));
+                },
+                Response::Failed if server + 1 < query.servers.len() => {
+                    self.send_request(query, server + 1)?;
+                },
+                Response::Failed => {
+                    query.finish(Err(Code::Timeout));
+                },
+                // stale responses from previous nameservers are simply ignored
+                Response::Invalid => {},
+            }
         }
+    }
 
-        let total = mem::size_of::<DNSHeader>() + name.len() + 2 + mem::size_of::<DNSQuestionEnd>();
-        // reserve some space for the response as well
-        let mut buf = vec![0u8; total.max(1024)];
+    /// Waits until the given query is finished and returns the result
+    pub fn wait(&mut self, query: &mut DnsQuery) -> Result<IpAddr, VerboseError> {
+        loop {
+            if let Some(addr) = self.poll(query)? {
+                break Ok(addr);
+            }
 
-        let mut sock = UdpSocket::new(DgramSocketArgs::new(netmng))?;
+            let mut waiter = FileWaiter::default();
+            waiter.add(query.fd().unwrap(), FileEvent::INPUT);
+            waiter.wait_for(query.remaining());
+        }
+    }
 
-        let txid = self.random.get() as u16;
-        Self::generate_request(&mut buf, txid, name)?;
-        sock.send_to(&buf[0..total], Endpoint::new(self.nameserver, DNS_PORT))?;
+    fn start(
+        &mut self,
+        netmng: Rc<Network>,
+        name: &str,
+        timeout: TimeDuration,
+        qtype: u16,
+    ) -> Result<DnsQuery, VerboseError> {
+        let mut query = DnsQuery {
+            name: name.to_ascii_lowercase(),
+            qtype,
+            servers: Vec::new(),
+            timeout,
+            state: QueryState::Done(Err(Code::NotFound)),
+        };
+
+        if let Some(addr) = self.lookup(&query.name, qtype) {
+            query.state = QueryState::Done(addr.ok_or(Code::NotFound));
+            return Ok(query);
+        }
 
-        // wait for the response
+        query.servers = if self.nameservers.is_empty() {
+            vec![netmng.nameserver()?]
+        }
+        else {
+            self.nameservers.clone()
+        };
+
+        let mut sock = UdpSocket::new(DgramSocketArgs::new(netmng))?;
         sock.set_blocking(false)?;
-        let mut waiter = FileWaiter::default();
-        waiter.add(sock.fd(), FileEvent::INPUT);
-        waiter.wait_for(timeout);
+        query.state = QueryState::Pending {
+            sock,
+            txid: 0,
+            server: 0,
+            timer: timer::add_wakeup(timeout),
+        };
+
+        self.send_request(&mut query, 0)?;
+        Ok(query)
+    }
+
+    fn send_request(&mut self, query: &mut DnsQuery, server: usize) -> Result<(), VerboseError> {
+        let name = &query.name;
+        let total = mem::size_of::<DNSHeader>() + name.len() + 2 + mem::size_of::<DNSQuestionEnd>();
+        let mut buf = vec![0u8; total];
+
+        // use a new transaction id for every nameserver to ignore late answers of previous ones
+        let new_txid = self.random.get() as u16;
+        Self::generate_request(&mut buf, new_txid, name, query.qtype)?;
+
+        if let QueryState::Pending {
+            sock,
+            txid,
+            server: cur_server,
+            timer,
+        } = &mut query.state
+        {
+            *txid = new_txid;
+            *cur_server = server;
+            timer::cancel(*timer);
+            *timer = timer::add_wakeup(query.timeout);
+            sock.send_to(&buf, Endpoint::new(query.servers[server], DNS_PORT))?;
+        }
+        Ok(())
+    }
+
+    fn lookup(&mut self, name: &str, qtype: u16) -> Option<Option<IpAddr>> {
+        let key = (name.to_string(), qtype);
+        match self.cache.get(&key) {
+            Some(entry) if entry.expires > TimeInstant::now() => Some(entry.addr),
+            Some(_) => {
+                self.cache.remove(&key);
+                None
+            },
+            None => None,
+        }
+    }
+
+    fn insert(&mut self, name: &str, qtype: u16, addr: Option<IpAddr>, ttl: u32) {
+        let now = TimeInstant::now();
+        self.cache.retain(|_, e| e.expires > now);
+        if self.cache.len() >= CACHE_SIZE {
+            // make room by evicting the entry that expires first
+            let oldest = self
+                .cache
+                .iter()
+                .min_by_key(|(_, e)| e.expires)
+                .map(|(k, _)| k.clone())
+                .unwrap();
+            self.cache.remove(&oldest);
+        }
+
+        self.cache.insert((name.to_string(), qtype), CacheEntry {
+            addr,
+            expires: now + TimeDuration::from_secs(ttl as u64),
+        });
+    }
 
-        let len = sock.recv(&mut buf)?;
-        Self::handle_response(&buf[0..len], txid)
+    fn query_error(code: Code, qtype: u16) -> VerboseError {
+        VerboseError::new(
+            code,
+            match (code, qtype) {
+                (Code::NotFound, TYPE_A) => "No IPv4 address found via DNS",
+                (Code::NotFound, _) => "No IPv6 address found via DNS",
+                _ => "No response from any nameserver",
+            }
+            .to_string(),
+        )
     }
 
-    fn generate_request(buf: &mut [u8], txid: u16, name: &str) -> Result<(), VerboseError> {
+    fn generate_request(
+        buf: &mut [u8],
+        txid: u16,
+        name: &str,
+        qtype: u16,
+    ) -> Result<(), VerboseError> {
         // safety: we are still within the allocated vector and DNSHeader has no alignment
         // requirements
         let header = unsafe { &mut *(buf.as_mut_ptr() as *mut DNSHeader) };
@@ -150,7 +464,7 @@ impl DNS {
                 .add(mem::size_of::<DNSHeader>() + name.len() + 2)
                 as *mut DNSQuestionEnd)
         };
-        qend.ty = TYPE_A.to_be();
+        qend.ty = qtype.to_be();
         qend.cls = CLASS_IN.to_be();
 
         Ok(())
@@ -181,28 +495,28 @@ impl DNS {
         Ok(())
     }
 
-    fn handle_response(buf: &[u8], txid: u16) -> Result<IpAddr, VerboseError> {
+    fn handle_response(buf: &[u8], txid: u16, qtype: u16) -> Response {
         if buf.len() < mem::size_of::<DNSHeader>() {
-            return Err(VerboseError::new(
-                Code::NotFound,
-                "Invalid DNS response".to_string(),
-            ));
+            return Response::Invalid;
         }
 
         // safety: the length is sufficient now and heap allocations are 16-byte aligned
         let header = unsafe { &*(buf.as_ptr() as *const DNSHeader) };
         if u16::from_be(header.id) != txid {
-            return Err(VerboseError::new(
-                Code::NotFound,
-                "Received DNS response with wrong transaction id".to_string(),
-            ));
+            return Response::Invalid;
+        }
+
+        match u16::from_be(header.flags) & RCODE_MASK {
+            RCODE_NO_ERROR => {},
+            RCODE_NAME_ERROR => return Response::NotFound,
+            _ => return Response::Failed,
         }
 
         let questions = u16::from_be(header.qd_count);
         let answers = u16::from_be(header.an_count);
 
         let answers_off = Self::skip_questions(buf, questions as usize);
-        Self::parse_answers(buf, answers as usize, answers_off)
+        Self::parse_answers(buf, answers_off, answers as usize, qtype)
     }
 
     fn skip_questions(buf: &[u8], count: usize) -> usize {
@@ -227,27 +541,54 @@ impl DNS {
         total + 1
     }
 
-    fn parse_answers(buf: &[u8], start: usize, count: usize) -> Result<IpAddr, VerboseError> {
-        for off in start..start + count {
-            if off + mem::size_of::<DNSAnswer>() > buf.len() {
-                return Err(VerboseError::new(
-                    Code::NotFound,
-                    "Invalid DNS response".to_string(),
-                ));
+    fn parse_answers(buf: &[u8], start: usize, count: usize, qtype: u16) -> Response {
+        let mut off = start;
+        for _ in 0..count {
+            off += match buf.get(off..) {
+                Some(rem) => Self::name_length(rem),
+                None => return Response::Invalid,
+            };
+            if off + mem::size_of::<DNSAnswerEnd>() > buf.len() {
+                return Response::Invalid;
             }
 
-            // safety: we check above whether we are in bounds and DNSAnswer has no alignment req.
-            let ans = unsafe { &*(buf.as_ptr().add(off) as *const DNSAnswer) };
-            if u16::from_be(ans.ty) == TYPE_A
-                && u16::from_be(ans.length) == mem::size_of::<IpAddr>() as u16
-            {
-                return Ok(IpAddr::new_from_raw(u32::from_be(ans.ip_addr)));
+            // safety: we check above whether we are in bounds and DNSAnswerEnd has no alignment
+            // requirements
+            let ans = unsafe { &*(buf.as_ptr().add(off) as *const DNSAnswerEnd) };
+            let data_off = off + mem::size_of::<DNSAnswerEnd>();
+            let data_len = u16::from_be(ans.length) as usize;
+            let data = match buf.get(data_off..data_off + data_len) {
+                Some(data) => data,
+                None => return Response::Invalid,
+            };
+            let ttl = u32::from_be(ans.ttl);
+
+            match (u16::from_be(ans.ty), data_len) {
+                (TYPE_A, 4) if qtype == TYPE_A => {
+                    return Response::Found(IpAddr::new(data[0], data[1], data[2], data[3]), ttl);
+                },
+                (TYPE_AAAA, 16) if qtype == TYPE_AAAA => {
+                    return Response::Found(IpAddr::from_octets(data.try_into().unwrap()), ttl);
+                },
+                _ => {},
             }
+
+            off = data_off + data_len;
         }
 
-        Err(VerboseError::new(
-            Code::NotFound,
-            "No IPv4 address in DNS response".to_string(),
-        ))
+        Response::NotFound
+    }
+
+    fn name_length(buf: &[u8]) -> usize {
+        let mut off = 0;
+        while off < buf.len() && buf[off] != 0 {
+            // a pointer to a name elsewhere in the message ends the name
+            if buf[off] & 0xC0 == 0xC0 {
+                return off + 2;
+            }
+            off += buf[off] as usize + 1;
+        }
+        // skip zero ending, too
+        off + 1
     }
 }
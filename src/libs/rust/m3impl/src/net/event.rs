@@ -41,7 +41,10 @@ const REPLY_BUF_SIZE: usize = REPLY_SIZE * MSG_CREDITS;
 /// The maximum transmission unit when sending network packets via TCU messages
 // The receive buffer slots are 2048 bytes, but we need to substract the TCU header and the other
 // fields in DataMessage.
-pub const MTU: usize = MSG_SIZE - (mem::size_of::<Header>() + 4 * mem::size_of::<u64>());
+pub const MTU: usize = MSG_SIZE - (mem::size_of::<Header>() + DATA_HEADER_SIZE);
+
+// the size of the fields in DataMessage before the data
+const DATA_HEADER_SIZE: usize = 5 * mem::size_of::<u64>();
 
 /// The different network event types
 #[derive(Copy, Clone, Debug, Eq, PartialEq, IntoPrimitive, TryFromPrimitive)]
@@ -61,7 +64,7 @@ pub enum NetEventType {
 #[repr(C, align(2048))]
 pub struct DataMessage {
     ty: NetEventType,
-    pub addr: u64,
+    pub addr: [u64; 2],
     pub port: u64,
     pub size: u64,
     pub data: [u8; MTU],
@@ -71,7 +74,7 @@ pub struct DataMessage {
 #[repr(C)]
 pub struct ConnectedMessage {
     ty: NetEventType,
-    pub remote_addr: u64,
+    pub remote_addr: [u64; 2],
     pub remote_port: u64,
 }
 
@@ -79,7 +82,7 @@ impl ConnectedMessage {
     pub fn new(endpoint: Endpoint) -> Self {
         Self {
             ty: NetEventType::Connected,
-            remote_addr: endpoint.addr.0 as u64,
+            remote_addr: endpoint.addr.to_raw(),
             remote_port: endpoint.port as u64,
         }
     }
@@ -90,7 +93,7 @@ impl fmt::Debug for ConnectedMessage {
         write!(
             f,
             "remote={}",
-            Endpoint::new(IpAddr(self.remote_addr as u32), self.remote_port as Port)
+            Endpoint::new(IpAddr::from_raw(self.remote_addr), self.remote_port as Port)
         )
     }
 }
@@ -99,19 +102,30 @@ impl fmt::Debug for ConnectedMessage {
 #[repr(C)]
 pub struct ClosedMessage {
     ty: NetEventType,
+    pub error: u64,
 }
 
-impl Default for ClosedMessage {
-    fn default() -> Self {
+impl ClosedMessage {
+    pub fn new(error: Code) -> Self {
         Self {
             ty: NetEventType::Closed,
+            error: error as u64,
         }
     }
 }
 
+impl Default for ClosedMessage {
+    fn default() -> Self {
+        Self::new(Code::Success)
+    }
+}
+
 impl fmt::Debug for ClosedMessage {
-    fn fmt(&self, _f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        Ok(())
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match Code::from(self.error as u32) {
+            Code::Success => Ok(()),
+            e => write!(f, "error={:?}", e),
+        }
     }
 }
 
@@ -271,7 +285,7 @@ impl NetEventChannel {
         #[allow(clippy::uninit_assumed_init)]
         let mut msg = DataMessage {
             ty: NetEventType::Data,
-            addr: endpoint.addr.0 as u64,
+            addr: endpoint.addr.to_raw(),
             port: endpoint.port as u64,
             size: size as u64,
             // safety: data[0..size] will be initialized below; the rest will not be sent
@@ -296,7 +310,7 @@ impl NetEventChannel {
         if self.can_send()? {
             self.fetch_replies();
 
-            let msg_size = 4 * mem::size_of::<u64>() + msg.size as usize;
+            let msg_size = DATA_HEADER_SIZE + msg.size as usize;
             self.sgate.borrow_mut().get()?.send_aligned(
                 msg as *const _ as *const u8,
                 msg_size,
@@ -91,12 +91,12 @@ pub use self::event::{
 mod socket;
 pub(crate) use self::socket::BaseSocket;
 pub use self::socket::{
-    DGramSocket, DgramSocketArgs, RawSocket, RawSocketArgs, Socket, SocketArgs, State,
+    DGramSocket, DgramSocketArgs, RawSocket, RawSocketArgs, Socket, SocketArgs, SocketOpt, State,
     StreamSocket, StreamSocketArgs, TcpSocket, UdpSocket,
 };
 
 mod dns;
-pub use dns::DNS;
+pub use dns::{DnsQuery, DNS};
 
 /// A socket descriptor
 pub type Sd = usize;
@@ -116,30 +116,148 @@ pub const REPLY_SIZE: usize = 64;
 pub const REPLY_BUF_SIZE: usize = REPLY_SIZE * MSG_CREDITS;
 
 /// Represents an internet protocol (IP) address
-#[derive(Debug, Default, Eq, PartialEq, Clone, Copy)]
-pub struct IpAddr(pub u32);
+///
+/// Both variants store the address in host byte order.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum IpAddr {
+    /// An IPv4 address
+    V4(u32),
+    /// An IPv6 address
+    V6(u128),
+}
+
+// the prefix of IPv4-mapped IPv6 addresses (::ffff:0:0/96)
+const IPV4_MAPPED_PREFIX: u128 = 0xFFFF_0000_0000;
 
 impl IpAddr {
-    /// Creates an IP address from given 4 bytes
+    /// Creates an IPv4 address from given 4 bytes
     pub fn new(v0: u8, v1: u8, v2: u8, v3: u8) -> Self {
-        IpAddr(u32::from_be_bytes([v0, v1, v2, v3]))
+        IpAddr::V4(u32::from_be_bytes([v0, v1, v2, v3]))
     }
 
-    /// Creates an IP address from given raw value
+    /// Creates an IPv6 address from given 8 segments
+    pub fn new_v6(segs: [u16; 8]) -> Self {
+        IpAddr::V6(segs.iter().fold(0, |acc, s| (acc << 16) | *s as u128))
+    }
+
+    /// Creates an IPv4 address from given raw value
     pub fn new_from_raw(val: u32) -> Self {
-        IpAddr(val)
+        IpAddr::V4(val)
     }
 
-    /// Creates an unspecified IP address
+    /// Creates an unspecified IPv4 address
     pub fn unspecified() -> Self {
         IpAddr::new(0, 0, 0, 0)
     }
+
+    /// Returns true if this is an IPv4 address
+    pub fn is_v4(&self) -> bool {
+        matches!(self, IpAddr::V4(_))
+    }
+
+    /// Returns true if this is an IPv6 address
+    pub fn is_v6(&self) -> bool {
+        matches!(self, IpAddr::V6(_))
+    }
+
+    /// Returns the raw IPv4 address or `None` if this is an IPv6 address
+    pub fn as_v4(&self) -> Option<u32> {
+        match self {
+            IpAddr::V4(a) => Some(*a),
+            IpAddr::V6(_) => None,
+        }
+    }
+
+    /// Returns the address as 16 bytes in network byte order, using the IPv4-mapped form for IPv4
+    /// addresses
+    pub fn octets(&self) -> [u8; 16] {
+        self.to_u128().to_be_bytes()
+    }
+
+    /// Creates an address from 16 bytes in network byte order; IPv4-mapped addresses yield IPv4
+    /// addresses
+    pub fn from_octets(octets: [u8; 16]) -> Self {
+        Self::from_u128(u128::from_be_bytes(octets))
+    }
+
+    /// Returns the representation used in the protocol between client and net server
+    #[doc(hidden)]
+    pub fn to_raw(&self) -> [u64; 2] {
+        let val = self.to_u128();
+        [(val >> 64) as u64, val as u64]
+    }
+
+    /// Creates an address from the representation used in the protocol between client and net
+    /// server
+    #[doc(hidden)]
+    pub fn from_raw(raw: [u64; 2]) -> Self {
+        Self::from_u128(((raw[0] as u128) << 64) | raw[1] as u128)
+    }
+
+    fn to_u128(self) -> u128 {
+        match self {
+            IpAddr::V4(a) => IPV4_MAPPED_PREFIX | a as u128,
+            IpAddr::V6(a) => a,
+        }
+    }
+
+    fn from_u128(val: u128) -> Self {
+        if (val >> 32) == IPV4_MAPPED_PREFIX >> 32 {
+            IpAddr::V4(val as u32)
+        }
+        else {
+            IpAddr::V6(val)
+        }
+    }
+}
+
+impl Default for IpAddr {
+    fn default() -> Self {
+        Self::unspecified()
+    }
 }
 
 impl core::fmt::Display for IpAddr {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        let [b0, b1, b2, b3] = self.0.to_be_bytes();
-        write!(f, "{}.{}.{}.{}", b0, b1, b2, b3)
+        match self {
+            IpAddr::V4(a) => {
+                let [b0, b1, b2, b3] = a.to_be_bytes();
+                write!(f, "{}.{}.{}.{}", b0, b1, b2, b3)
+            },
+            IpAddr::V6(a) => {
+                let segs: [u16; 8] = core::array::from_fn(|i| (a >> (112 - i * 16)) as u16);
+
+                // find the longest run of zero segments to replace it by "::" (RFC 5952)
+                let (mut zstart, mut zlen) = (0, 0);
+                let mut i = 0;
+                while i < segs.len() {
+                    let len = segs[i..].iter().take_while(|s| **s == 0).count();
+                    if len > zlen {
+                        (zstart, zlen) = (i, len);
+                    }
+                    i += len.max(1);
+                }
+
+                let write_segs = |f: &mut core::fmt::Formatter<'_>, segs: &[u16]| {
+                    for (i, s) in segs.iter().enumerate() {
+                        if i > 0 {
+                            write!(f, ":")?;
+                        }
+                        write!(f, "{:x}", s)?;
+                    }
+                    Ok(())
+                };
+
+                if zlen < 2 {
+                    write_segs(f, &segs)
+                }
+                else {
+                    write_segs(f, &segs[0..zstart])?;
+                    write!(f, "::")?;
+                    write_segs(f, &segs[zstart + zlen..])
+                }
+            },
+        }
     }
 }
 
@@ -147,6 +265,10 @@ impl core::str::FromStr for IpAddr {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.contains(':') {
+            return parse_v6(s);
+        }
+
         let parse_part = |s: &mut core::str::Split<'_, char>| {
             s.next()
                 .ok_or_else(|| Error::new(Code::InvArgs))?
@@ -163,6 +285,44 @@ impl core::str::FromStr for IpAddr {
     }
 }
 
+fn parse_v6(s: &str) -> Result<IpAddr, Error> {
+    let parse_segs = |s: &str, segs: &mut [u16]| -> Result<usize, Error> {
+        if s.is_empty() {
+            return Ok(0);
+        }
+
+        let mut count = 0;
+        for part in s.split(':') {
+            if count == segs.len() || part.is_empty() || part.len() > 4 {
+                return Err(Error::new(Code::InvArgs));
+            }
+            segs[count] = u16::from_str_radix(part, 16).map_err(|_| Error::new(Code::InvArgs))?;
+            count += 1;
+        }
+        Ok(count)
+    };
+
+    let mut segs = [0u16; 8];
+    match s.split_once("::") {
+        // the "::" replaces at least one zero segment
+        Some((head, tail)) => {
+            let mut tail_segs = [0u16; 7];
+            let hcount = parse_segs(head, &mut segs[0..7])?;
+            let tcount = parse_segs(tail, &mut tail_segs)?;
+            if hcount + tcount > 7 {
+                return Err(Error::new(Code::InvArgs));
+            }
+            segs[8 - tcount..].copy_from_slice(&tail_segs[0..tcount]);
+        },
+        None => {
+            if parse_segs(s, &mut segs)? != 8 {
+                return Err(Error::new(Code::InvArgs));
+            }
+        },
+    }
+    Ok(IpAddr::new_v6(segs))
+}
+
 /// Represents an TCP/UDP endpoint consisting of an IP address and a port
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
 pub struct Endpoint {
@@ -187,7 +347,10 @@ impl Endpoint {
 
 impl core::fmt::Display for Endpoint {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        write!(f, "{}:{}", self.addr, self.port)
+        match self.addr {
+            IpAddr::V4(_) => write!(f, "{}:{}", self.addr, self.port),
+            IpAddr::V6(_) => write!(f, "[{}]:{}", self.addr, self.port),
+        }
     }
 }
 
@@ -15,6 +15,10 @@
  * General Public License version 2 for more details.
  */
 
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+
+use serde_repr::{Deserialize_repr, Serialize_repr};
+
 use crate::errors::{Code, Error};
 use crate::io::LogFlags;
 use crate::log;
@@ -78,6 +82,35 @@ pub enum State {
     Closed,
 }
 
+/// The options that can be set for sockets via [`set_option`](Socket::set_option)
+///
+/// All options have integer values; boolean options use 0 and 1.
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    Eq,
+    PartialEq,
+    IntoPrimitive,
+    TryFromPrimitive,
+    Serialize_repr,
+    Deserialize_repr,
+)]
+#[repr(usize)]
+pub enum SocketOpt {
+    /// The time-to-live (hop limit) of outgoing packets (0 = default of the net server)
+    Ttl,
+    /// Whether the local port can be shared with other sockets. Note that all sockets that use the
+    /// same port need to enable this option before [`bind`](DGramSocket::bind) or
+    /// [`listen`](StreamSocket::listen).
+    ReuseAddr,
+    /// Whether Nagle's algorithm is disabled (TCP only; disabled by default)
+    NoDelay,
+    /// The interval in milliseconds in which keep-alive packets are sent on idle connections
+    /// (TCP only; 0 = disabled)
+    KeepAlive,
+}
+
 /// Trait for all sockets
 pub trait Socket: File {
     /// Returns the current state of the socket
@@ -136,6 +169,15 @@ pub trait Socket: File {
     /// the socket) and some of the data has already been sent, the number of sent bytes is
     /// returned. Otherwise, the error is returned.
     fn send(&mut self, data: &[u8]) -> Result<usize, Error>;
+
+    /// Sets the option `opt` of this socket to `value`
+    ///
+    /// Returns [`NotSup`](Code::NotSup) if the option is not supported for this type of socket and
+    /// [`InvArgs`](Code::InvArgs) if the value is out of range.
+    fn set_option(&mut self, opt: SocketOpt, value: u64) -> Result<(), Error>;
+
+    /// Returns the current value of the option `opt` of this socket
+    fn get_option(&self, opt: SocketOpt) -> Result<u64, Error>;
 }
 
 /// Socket prototype that is shared between sockets.
@@ -149,6 +191,13 @@ pub(crate) struct BaseSocket {
     local_ep: Option<Endpoint>,
     remote_ep: Option<Endpoint>,
 
+    // the maximum number of pending connections (0 = no accept queue)
+    backlog: usize,
+    // the number of connections that are ready to be accepted
+    pending: usize,
+    // the error that closed the socket, if any
+    error: Option<Code>,
+
     channel: Rc<NetEventChannel>,
     recv_queue: DataQueue,
 }
@@ -165,6 +214,10 @@ impl BaseSocket {
             local_ep: None,
             remote_ep: None,
 
+            backlog: 0,
+            pending: 0,
+            error: None,
+
             channel,
             recv_queue: DataQueue::default(),
         }
@@ -189,6 +242,8 @@ impl BaseSocket {
     pub fn disconnect(&mut self) {
         self.local_ep = None;
         self.remote_ep = None;
+        self.backlog = 0;
+        self.pending = 0;
         self.state = State::Closed;
     }
 
@@ -261,11 +316,28 @@ impl BaseSocket {
         }
     }
 
+    pub fn take_error(&mut self) -> Option<Code> {
+        self.error.take()
+    }
+
     pub fn has_events(&mut self, events: FileEvent) -> bool {
         self.fetch_replies();
 
-        (events.contains(FileEvent::INPUT) && (self.process_events() || self.has_data()))
-            || (events.contains(FileEvent::OUTPUT) && self.can_send())
+        if events.contains(FileEvent::INPUT)
+            && (self.process_events() || self.has_data() || self.pending > 0)
+        {
+            return true;
+        }
+
+        if events.contains(FileEvent::OUTPUT) {
+            // a pending connect is complete as soon as we are connected or the connect failed
+            if self.state == State::Connecting {
+                self.process_events();
+                return self.state != State::Connecting;
+            }
+            return self.can_send();
+        }
+        false
     }
 
     pub fn tear_down(&self) {
@@ -345,7 +417,7 @@ impl BaseSocket {
                         "socket {}: received data with {}b from {}",
                         self.sd,
                         _msg.size,
-                        Endpoint::new(IpAddr(_msg.addr as u32), _msg.port as Port)
+                        Endpoint::new(IpAddr::from_raw(_msg.addr), _msg.port as Port)
                     );
                     self.recv_queue.append(event, 0);
                 }
@@ -353,20 +425,37 @@ impl BaseSocket {
 
             NetEventType::Connected => {
                 let msg = event.msg::<event::ConnectedMessage>();
-                let ep = Endpoint::new(IpAddr(msg.remote_addr as u32), msg.remote_port as Port);
+                let ep = Endpoint::new(IpAddr::from_raw(msg.remote_addr), msg.remote_port as Port);
                 log_net(
                     NetLogEvent::RecvConnected,
                     self.sd,
                     msg.remote_port as usize,
                 );
-                log!(LogFlags::LibNet, "socket {}: connected to {}", self.sd, ep);
-                self.state = State::Connected;
-                self.remote_ep = Some(ep);
+                if self.backlog > 0 {
+                    // the connection waits in the accept queue at the server
+                    log!(
+                        LogFlags::LibNet,
+                        "socket {}: pending connection from {}",
+                        self.sd,
+                        ep
+                    );
+                    self.pending += 1;
+                }
+                else {
+                    log!(LogFlags::LibNet, "socket {}: connected to {}", self.sd, ep);
+                    self.state = State::Connected;
+                    self.remote_ep = Some(ep);
+                }
             },
 
             NetEventType::Closed => {
+                let msg = event.msg::<event::ClosedMessage>();
                 log_net(NetLogEvent::RecvClosed, self.sd, 0);
-                log!(LogFlags::LibNet, "socket {}: closed", self.sd);
+                log!(LogFlags::LibNet, "socket {}: closed {:?}", self.sd, msg);
+                match Code::from(msg.error as u32) {
+                    Code::Success => {},
+                    e => self.error = Some(e),
+                }
                 self.disconnect();
             },
 
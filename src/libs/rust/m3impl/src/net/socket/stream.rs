@@ -65,7 +65,10 @@ pub trait StreamSocket: Socket {
     /// The socket has to be put into listen mode first. Note that in contrast to conventional
     /// TCP/IP stacks, accept does not yield a new socket, but uses this socket for the accepted
     /// connection. Thus, to support multiple connections to the same port, put multiple sockets in
-    /// listen mode on this port and call accept on each of them.
+    /// listen mode on this port and call accept on each of them. Alternatively, use
+    /// [`TcpSocket::listen_with_backlog`](crate::net::TcpSocket::listen_with_backlog) and
+    /// [`TcpSocket::accept_socket`](crate::net::TcpSocket::accept_socket) to obtain a new socket
+    /// per accepted connection.
     fn accept(&mut self) -> Result<Endpoint, Error>;
 
     /// Closes the connection
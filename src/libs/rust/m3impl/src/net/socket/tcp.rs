@@ -24,7 +24,7 @@ use crate::errors::{Code, Error};
 use crate::io;
 use crate::net::{
     event, log_net,
-    socket::{BaseSocket, Socket, State, StreamSocket, StreamSocketArgs},
+    socket::{BaseSocket, Socket, SocketOpt, State, StreamSocket, StreamSocketArgs},
     Endpoint, NetLogEvent, Port, SocketType,
 };
 use crate::rc::Rc;
@@ -54,6 +54,89 @@ impl TcpSocket {
         let fd = Activity::own().files().add(sock)?;
         Ok(FileRef::new_owned(fd))
     }
+
+    /// Returns the error that occurred on this socket and clears it
+    ///
+    /// For example, if a non-blocking [`connect`](Socket::connect) fails, the socket is closed and
+    /// the reason (e.g., [`ConnectionFailed`](Code::ConnectionFailed) if the remote side refused
+    /// the connection or [`Timeout`](Code::Timeout)) can be obtained via this function. The
+    /// completion of a non-blocking connect can be awaited via
+    /// [`FileEvent::OUTPUT`](crate::vfs::FileEvent::OUTPUT).
+    pub fn take_error(&mut self) -> Option<Error> {
+        self.socket.take_error().map(Error::new)
+    }
+
+    /// Puts this socket into listen mode on the given port with an accept queue of `backlog`
+    /// connections
+    ///
+    /// In contrast to [`listen`](StreamSocket::listen), the net server accepts up to `backlog`
+    /// remote connections concurrently and keeps them in a queue until they are taken via
+    /// [`accept_socket`](TcpSocket::accept_socket). This socket stays in listen mode afterwards.
+    /// Note that the server reserves the buffer space for `backlog` connections of the size this
+    /// socket was created with.
+    ///
+    /// Returns an error if `backlog` is zero or the socket is not in state
+    /// [`Closed`](crate::net::State::Closed).
+    pub fn listen_with_backlog(&mut self, port: Port, backlog: usize) -> Result<(), Error> {
+        if backlog == 0 {
+            return Err(Error::new(Code::InvArgs));
+        }
+        if self.socket.state() != State::Closed {
+            return Err(Error::new(Code::InvState));
+        }
+
+        let addr = self.net.listen(self.socket.sd(), port, backlog)?;
+        self.socket.local_ep = Some(Endpoint::new(addr, port));
+        self.socket.state = State::Listening;
+        self.socket.backlog = backlog;
+        Ok(())
+    }
+
+    /// Accepts the next connection from the accept queue of this socket
+    ///
+    /// The socket has to be put into listen mode via
+    /// [`listen_with_backlog`](TcpSocket::listen_with_backlog) first. The accepted connection is
+    /// handed over to a new socket, created with the given arguments, which is already in state
+    /// [`Connected`](crate::net::State::Connected). The arguments have to refer to the same
+    /// [`Network`] session as this socket.
+    ///
+    /// In non-blocking mode, [`WouldBlock`](Code::WouldBlock) is returned if no connection is
+    /// pending.
+    pub fn accept_socket(&mut self, args: StreamSocketArgs) -> Result<FileRef<Self>, Error> {
+        if self.state() != State::Listening || self.socket.backlog == 0 {
+            return Err(Error::new(Code::InvState));
+        }
+        if !Rc::ptr_eq(&args.net, &self.net) {
+            return Err(Error::new(Code::InvArgs));
+        }
+
+        if !self.is_blocking() {
+            self.socket.process_events();
+        }
+        while self.socket.pending == 0 {
+            if !self.is_blocking() {
+                return Err(Error::new(Code::WouldBlock));
+            }
+            self.socket.wait_for_events(false)?;
+        }
+
+        let mut sock = Box::new(TcpSocket {
+            socket: args.net.create(SocketType::Stream, None, &args.args)?,
+            net: args.net,
+            fd: INV_FD,
+        });
+
+        // on errors, the new socket is removed again at the server on drop
+        let remote_ep = self.net.accept(self.socket.sd(), sock.socket.sd())?;
+        self.socket.pending -= 1;
+
+        sock.socket.state = State::Connected;
+        sock.socket.local_ep = self.socket.local_ep;
+        sock.socket.remote_ep = Some(remote_ep);
+
+        let fd = Activity::own().files().add(sock)?;
+        Ok(FileRef::new_owned(fd))
+    }
 }
 
 impl Socket for TcpSocket {
@@ -85,6 +168,7 @@ impl Socket for TcpSocket {
         }
 
         let local_ep = self.net.connect(self.socket.sd(), endpoint)?;
+        self.socket.error = None;
         self.socket.state = State::Connecting;
         self.socket.remote_ep = Some(endpoint);
         self.socket.local_ep = Some(local_ep);
@@ -147,6 +231,14 @@ impl Socket for TcpSocket {
         }
         Ok(total)
     }
+
+    fn set_option(&mut self, opt: SocketOpt, value: u64) -> Result<(), Error> {
+        self.net.set_option(self.socket.sd(), opt, value)
+    }
+
+    fn get_option(&self, opt: SocketOpt) -> Result<u64, Error> {
+        self.net.get_option(self.socket.sd(), opt)
+    }
 }
 
 impl StreamSocket for TcpSocket {
@@ -155,7 +247,7 @@ impl StreamSocket for TcpSocket {
             return Err(Error::new(Code::InvState));
         }
 
-        let addr = self.net.listen(self.socket.sd(), port)?;
+        let addr = self.net.listen(self.socket.sd(), port, 0)?;
         self.socket.local_ep = Some(Endpoint::new(addr, port));
         self.socket.state = State::Listening;
         Ok(())
@@ -168,7 +260,8 @@ impl StreamSocket for TcpSocket {
         if self.state() == State::Connecting {
             return Err(Error::new(Code::AlreadyInProgress));
         }
-        if self.state() != State::Listening {
+        // sockets with an accept queue hand out connections via accept_socket
+        if self.state() != State::Listening || self.socket.backlog > 0 {
             return Err(Error::new(Code::InvState));
         }
 
@@ -24,8 +24,8 @@ use crate::errors::{Code, Error};
 use crate::io;
 use crate::net::{
     log_net,
-    socket::{BaseSocket, DGramSocket, DgramSocketArgs, Socket, State},
-    Endpoint, NetLogEvent, Port, SocketType,
+    socket::{BaseSocket, DGramSocket, DgramSocketArgs, Socket, SocketOpt, State},
+    Endpoint, IpAddr, NetLogEvent, Port, SocketType,
 };
 use crate::rc::Rc;
 use crate::tiles::Activity;
@@ -54,6 +54,21 @@ impl UdpSocket {
         let fd = Activity::own().files().add(sock)?;
         Ok(FileRef::new_owned(fd))
     }
+
+    /// Joins the IPv4 multicast group `addr`
+    ///
+    /// Afterwards, this socket receives the datagrams that are sent to the group and to the port
+    /// this socket is bound to. Returns [`Exists`](Code::Exists) if the socket is already member of
+    /// this group and [`NotSup`](Code::NotSup) for IPv6 addresses.
+    pub fn join_multicast(&mut self, addr: IpAddr) -> Result<(), Error> {
+        self.net.join_multicast(self.socket.sd(), addr)
+    }
+
+    /// Leaves the multicast group `addr`, which has been joined via
+    /// [`join_multicast`](UdpSocket::join_multicast) before
+    pub fn leave_multicast(&mut self, addr: IpAddr) -> Result<(), Error> {
+        self.net.leave_multicast(self.socket.sd(), addr)
+    }
 }
 
 impl Socket for UdpSocket {
@@ -99,6 +114,14 @@ impl Socket for UdpSocket {
         )
         .map(|_| data.len())
     }
+
+    fn set_option(&mut self, opt: SocketOpt, value: u64) -> Result<(), Error> {
+        self.net.set_option(self.socket.sd(), opt, value)
+    }
+
+    fn get_option(&self, opt: SocketOpt) -> Result<u64, Error> {
+        self.net.get_option(self.socket.sd(), opt)
+    }
 }
 
 impl DGramSocket for UdpSocket {
@@ -31,6 +31,7 @@ mod reqhdl;
 mod server;
 mod sesscon;
 mod session;
+mod waitpolicy;
 
 pub use self::reqhdl::{
     ClientManager, RequestHandler, RequestSession, DEF_MAX_CLIENTS, DEF_MSG_SIZE,
@@ -38,14 +39,22 @@ pub use self::reqhdl::{
 pub use self::server::{CapExchange, ExcType, Handler, Server};
 pub use self::sesscon::{SessId, SessionContainer};
 pub use self::session::ServerSession;
+pub use self::waitpolicy::WaitPolicy;
 
 use crate::errors::Error;
-use crate::tiles::OwnActivity;
+use crate::time::TimeDuration;
+use crate::timer;
+use crate::watchdog;
 
 /// Executes the server loop, calling `func` in every iteration.
+///
+/// The loop drives the [`timer`](crate::timer)s of the activity. If the server is watched by the
+/// resource manager, the loop also sends the heartbeats (see [`watchdog`](crate::watchdog)).
 pub fn server_loop<F: FnMut() -> Result<(), Error>>(mut func: F) -> Result<(), Error> {
     loop {
-        OwnActivity::sleep().ok();
+        timer::sleep_for(watchdog::next_timeout().unwrap_or(TimeDuration::MAX)).ok();
+
+        watchdog::check_in();
 
         func()?;
     }
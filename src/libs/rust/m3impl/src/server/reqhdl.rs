@@ -21,18 +21,20 @@ use crate::boxed::Box;
 use crate::cap::{SelSpace, Selector};
 use crate::cfg;
 use crate::col::{ToString, Vec};
-use crate::com::{opcodes, GateIStream, RecvGate, SGateArgs, SendCap};
+use crate::com::{opcodes, GateIStream, GateSet, RecvGate, SGateArgs, SendCap};
 use crate::errors::{Code, Error};
 use crate::format;
 use crate::io::LogFlags;
 use crate::kif;
 use crate::log;
 use crate::server::{
-    server_loop, CapExchange, ExcType, Handler, Server, ServerSession, SessId, SessionContainer,
+    CapExchange, ExcType, Handler, Server, ServerSession, SessId, SessionContainer, WaitPolicy,
 };
 use crate::tcu::Label;
+use crate::timer;
 use crate::util::math;
 use crate::vec;
+use crate::watchdog;
 
 /// The default maximum number of clients a service supports
 pub const DEF_MAX_CLIENTS: usize = if cfg::MAX_ACTS < 32 {
@@ -70,6 +72,14 @@ pub trait RequestSession {
         Self: Sized,
     {
     }
+
+    /// This method is called if the capability with selector `sel`, which has been delegated to
+    /// this session, was revoked by someone else.
+    ///
+    /// The notification needs to be requested during the exchange via
+    /// [`CapExchange::notify_revoke`](crate::server::CapExchange::notify_revoke).
+    fn revoked(&mut self, _sel: Selector) {
+    }
 }
 
 impl<S: RequestSession + 'static, O: Into<usize> + TryFrom<usize> + Debug> Handler<S>
@@ -114,6 +124,12 @@ impl<S: RequestSession + 'static, O: Into<usize> + TryFrom<usize> + Debug> Handl
     fn close(&mut self, crt: usize, sid: SessId) {
         self.clients.remove(crt, sid);
     }
+
+    fn revoked(&mut self, _crt: usize, sid: SessId, sel: Selector) {
+        if let Some(sess) = self.clients.get_mut(sid) {
+            sess.revoked(sel);
+        }
+    }
 }
 
 /// The client manager holds all sessions and the connections to clients
@@ -340,6 +356,7 @@ pub struct RequestHandler<S, O> {
     clients: ClientManager<S>,
     msg_hdls: Vec<MsgHandlerFunc<S>>,
     cap_hdls: Vec<CapHandler<S>>,
+    wait: WaitPolicy,
     _opcode: PhantomData<O>,
 }
 
@@ -360,6 +377,7 @@ impl<S: RequestSession + 'static, O: Into<usize> + TryFrom<usize> + Debug> Reque
             clients: ClientManager::new(max_clients, msg_size, max_cli_cons)?,
             msg_hdls: Vec::new(),
             cap_hdls: Vec::new(),
+            wait: WaitPolicy::default(),
             _opcode: PhantomData,
         })
     }
@@ -374,6 +392,16 @@ impl<S: RequestSession + 'static, O: Into<usize> + TryFrom<usize> + Debug> Reque
         &mut self.clients
     }
 
+    /// Returns the policy that is used by [`run`](RequestHandler::run) to wait for requests
+    pub fn wait_policy(&self) -> WaitPolicy {
+        self.wait
+    }
+
+    /// Sets the policy that is used by [`run`](RequestHandler::run) to wait for requests
+    pub fn set_wait_policy(&mut self, policy: WaitPolicy) {
+        self.wait = policy;
+    }
+
     /// Registers `func` as the capability handler for given opcode and exchange type
     ///
     /// This function is called whenever a capability should be exchanged with a client and the
@@ -527,6 +555,25 @@ impl<S: RequestSession + 'static, O: Into<usize> + TryFrom<usize> + Debug> Reque
             &mut S,
             &mut GateIStream<'_>,
         ) -> Result<(), Error>,
+    {
+        self.fetch_and_handle_msg_with_sessions(|handler, opcode, sid, sessions, is| {
+            func(handler, opcode, sessions.get_mut(sid).unwrap(), is)
+        })
+    }
+
+    /// Fetches the next message from the receive gate and calls the given function to handle it.
+    ///
+    /// In contrast to `fetch_and_handle_msg_with`, the function receives the id of the session
+    /// and all sessions, which allows the handler to access other sessions as well.
+    pub fn fetch_and_handle_msg_with_sessions<F>(&mut self, func: F)
+    where
+        F: FnOnce(
+            &Vec<MsgHandlerFunc<S>>,
+            usize,
+            SessId,
+            &mut SessionContainer<S>,
+            &mut GateIStream<'_>,
+        ) -> Result<(), Error>,
     {
         if let Ok(msg) = self.clients.rgate.fetch() {
             let mut is = GateIStream::new(msg, &self.clients.rgate);
@@ -552,8 +599,13 @@ impl<S: RequestSession + 'static, O: Into<usize> + TryFrom<usize> + Debug> Reque
                 op_name(opcode),
             );
 
-            let sess = self.clients.sessions.get_mut(sid).unwrap();
-            let res = func(&self.msg_hdls, opcode, sess, &mut is);
+            let res = func(
+                &self.msg_hdls,
+                opcode,
+                sid,
+                &mut self.clients.sessions,
+                &mut is,
+            );
 
             log!(
                 LogFlags::LibServReqs,
@@ -571,13 +623,44 @@ impl<S: RequestSession + 'static, O: Into<usize> + TryFrom<usize> + Debug> Reque
     }
 
     /// Runs the default server loop
+    ///
+    /// The loop waits for messages on the server's control channel and the request channel and
+    /// handles only the channel that has a message. Before going to sleep, the loop spins according
+    /// to the [`WaitPolicy`] (see [`set_wait_policy`](RequestHandler::set_wait_policy)). The loop
+    /// also drives the [`timer`](crate::timer)s of the activity. If the server is watched by the
+    /// resource manager, the loop also sends the heartbeats (see [`watchdog`](crate::watchdog)).
     pub fn run(&mut self, srv: &mut Server) -> Result<(), Error> {
-        let res = server_loop(|| {
-            srv.fetch_and_handle(self)?;
-            self.fetch_and_handle_msg();
+        let mut gates = GateSet::new();
+        let ctrl_gate = gates.add(srv.rgate());
+        gates.add(&self.clients.rgate);
+
+        let res: Result<(), Error> = loop {
+            let ready = match self.wait.spin(|| gates.ready()) {
+                Some(idx) => Ok(idx),
+                None => match [watchdog::next_timeout(), timer::next_timeout()]
+                    .into_iter()
+                    .flatten()
+                    .min()
+                {
+                    Some(timeout) => gates.wait_for(timeout),
+                    None => gates.wait(),
+                },
+            };
+
+            timer::run();
+            watchdog::check_in();
 
-            Ok(())
-        });
+            match ready {
+                Ok(idx) if idx == ctrl_gate => {
+                    if let Err(e) = srv.fetch_and_handle(self) {
+                        break Err(e);
+                    }
+                },
+                Ok(_) => self.fetch_and_handle_msg(),
+                Err(e) if e.code() == Code::Timeout => {},
+                Err(e) => break Err(e),
+            }
+        };
 
         match res {
             Ok(_) => Ok(()),
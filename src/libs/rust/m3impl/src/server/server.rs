@@ -23,7 +23,7 @@ use crate::com::{GateIStream, RecvGate};
 use crate::errors::{Code, Error};
 use crate::io::LogFlags;
 use crate::kif::{
-    service::{DeriveCreatorReply, ExchangeData, ExchangeReply, OpenReply, Request},
+    service::{DeriveCreatorReply, ExchangeData, ExchangeReply, OpenReply, Request, Version},
     CapRngDesc,
 };
 use crate::log;
@@ -51,6 +51,7 @@ pub struct CapExchange<'d> {
     src: M3Deserializer<'d>,
     sink: M3Serializer<SliceSink<'d>>,
     pub(crate) out_crd: CapRngDesc,
+    pub(crate) notify_revoke: bool,
 }
 
 impl<'d> CapExchange<'d> {
@@ -63,6 +64,7 @@ impl<'d> CapExchange<'d> {
             src: M3Deserializer::new(&input.args.data[..len]),
             sink: M3Serializer::new(SliceSink::new(&mut output.args.data)),
             out_crd: CapRngDesc::default(),
+            notify_revoke: false,
         }
     }
 
@@ -85,14 +87,22 @@ impl<'d> CapExchange<'d> {
     pub fn out_caps(&mut self, crd: CapRngDesc) {
         self.out_crd = crd;
     }
+
+    /// Requests a notification for each delegated capability once it gets revoked
+    ///
+    /// This is only meaningful for delegations. Afterwards, [`Handler::revoked`] is called with the
+    /// selector of each capability from the output capabilities that is revoked by someone else.
+    pub fn notify_revoke(&mut self) {
+        self.notify_revoke = true;
+    }
 }
 
 impl<'d> fmt::Debug for CapExchange<'d> {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
         write!(
             fmt,
-            "CapExchange[ty={:?}, out_crd={}]",
-            self.ty, self.out_crd,
+            "CapExchange[ty={:?}, out_crd={}, notify_revoke={}]",
+            self.ty, self.out_crd, self.notify_revoke,
         )
     }
 }
@@ -154,6 +164,15 @@ pub trait Handler<S> {
     /// allows handlers to performs cleanup actions before actually shutting down.
     fn shutdown(&mut self) {
     }
+
+    /// Notifies the handler that a capability has been revoked
+    ///
+    /// This method is called by `Server` whenever a capability that has been delegated to us via
+    /// the given session was revoked by someone else, but only if the handler requested that via
+    /// [`CapExchange::notify_revoke`]. It receives the session creator (`crt`), the session id, and
+    /// the selector of the revoked capability, which is invalid at this point.
+    fn revoked(&mut self, _crt: usize, _sid: SessId, _sel: Selector) {
+    }
 }
 
 /// Represents a server that provides a service for clients.
@@ -178,7 +197,17 @@ impl Server {
     where
         H: Handler<S>,
     {
-        Self::create(name, hdl, true)
+        Self::create(name, Version::default(), hdl, true)
+    }
+
+    /// Creates a new server with given service name that implements protocol version `version`.
+    ///
+    /// Clients can request a compatible version by opening a session at "name@version".
+    pub fn new_versioned<H, S>(name: &str, version: Version, hdl: &mut H) -> Result<Self, Error>
+    where
+        H: Handler<S>,
+    {
+        Self::create(name, version, hdl, true)
     }
 
     /// Creates a new private server that is not visible to anyone
@@ -186,10 +215,10 @@ impl Server {
     where
         H: Handler<S>,
     {
-        Self::create(name, hdl, false)
+        Self::create(name, Version::default(), hdl, false)
     }
 
-    fn create<H, S>(name: &str, hdl: &mut H, public: bool) -> Result<Self, Error>
+    fn create<H, S>(name: &str, version: Version, hdl: &mut H, public: bool) -> Result<Self, Error>
     where
         H: Handler<S>,
     {
@@ -204,7 +233,7 @@ impl Server {
             Activity::own()
                 .resmng()
                 .unwrap()
-                .reg_service(sel, sgate, name, max)?;
+                .reg_service(sel, sgate, name, version, max)?;
         }
 
         let serv = Server {
@@ -269,6 +298,7 @@ impl Server {
                 Ok(_) => return Ok(true),
                 Err(e) => Err(e),
             },
+            Request::Revoked { sid, sel } => Self::handle_revoked(hdl, is, sid as SessId, sel),
         }
         .map(|_| false)
     }
@@ -348,7 +378,7 @@ impl Server {
             ExcType::Del(data.caps.count())
         };
 
-        let (res, args_size, crd) = {
+        let (res, args_size, crd, notify) = {
             let mut xchg = CapExchange::new(ty, data, &mut reply.data);
 
             let res = if !hdl.sessions().creator_owns(crt, sid) {
@@ -358,12 +388,18 @@ impl Server {
                 hdl.exchange(crt, sid, &mut xchg)
             };
 
-            (res, xchg.out_args().size(), xchg.out_crd)
+            (
+                res,
+                xchg.out_args().size(),
+                xchg.out_crd,
+                xchg.notify_revoke,
+            )
         };
 
         let res = res.err().map(|e| e.code()).unwrap_or(Code::Success);
         reply.data.args.bytes = args_size;
         reply.data.caps = crd;
+        reply.notify_revoke = !obtain && notify;
         reply_vmsg!(is, res, reply)
     }
 
@@ -384,6 +420,34 @@ impl Server {
         is.reply_error(Code::Success)
     }
 
+    fn handle_revoked<H, S>(
+        hdl: &mut H,
+        is: &mut GateIStream<'_>,
+        sid: SessId,
+        sel: Selector,
+    ) -> Result<(), Error>
+    where
+        H: Handler<S>,
+    {
+        let crt = is.label() as usize;
+
+        log!(
+            LogFlags::LibServ,
+            "server::revoked(crt={}, sid={}, sel={})",
+            crt,
+            sid,
+            sel
+        );
+
+        if !hdl.sessions().creator_owns(crt, sid) {
+            return Err(Error::new(Code::NoPerm));
+        }
+
+        hdl.revoked(crt, sid, sel);
+
+        is.reply_error(Code::Success)
+    }
+
     fn handle_shutdown<H, S>(hdl: &mut H, is: &mut GateIStream<'_>) -> Result<(), Error>
     where
         H: Handler<S>,
@@ -0,0 +1,66 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use crate::time::{TimeDuration, TimeInstant};
+
+/// Determines how a server waits for the next request
+///
+/// Sleeping until the next request arrives frees the core for other activities, but adds the
+/// wakeup latency to the handling of the request. Under bursty load, it is therefore beneficial to
+/// spin for a short time after the last request before going to sleep. The `WaitPolicy` specifies
+/// how long to spin; by default, servers go to sleep immediately.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct WaitPolicy {
+    spin: TimeDuration,
+}
+
+impl WaitPolicy {
+    /// Creates a policy that goes to sleep immediately
+    pub const fn sleep() -> Self {
+        Self {
+            spin: TimeDuration::ZERO,
+        }
+    }
+
+    /// Creates a policy that spins for `spin` time before going to sleep
+    pub const fn spin_then_sleep(spin: TimeDuration) -> Self {
+        Self { spin }
+    }
+
+    /// Returns the time to spin before going to sleep
+    pub fn spin_time(&self) -> TimeDuration {
+        self.spin
+    }
+
+    /// Calls `poll` repeatedly until it returns `Some` or the spin time has passed.
+    ///
+    /// Returns the result of the last call to `poll`. If the spin time is zero, `poll` is not
+    /// called at all and `None` is returned.
+    pub fn spin<T, F: FnMut() -> Option<T>>(&self, mut poll: F) -> Option<T> {
+        if self.spin.is_zero() {
+            return None;
+        }
+
+        let end = TimeInstant::now() + self.spin;
+        loop {
+            if let Some(res) = poll() {
+                return Some(res);
+            }
+            if TimeInstant::now() >= end {
+                return None;
+            }
+        }
+    }
+}
@@ -0,0 +1,107 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use core::fmt;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::errors::Code;
+use crate::mem::VirtAddr;
+use crate::time::TimeDuration;
+use crate::tmif;
+
+use super::MutexGuard;
+
+/// A condition variable for activities on the same tile
+///
+/// A condition variable is used together with a [`Mutex`](super::Mutex) to wait until a condition
+/// on the protected data holds. As usual, waiting can return spuriously, so that the condition
+/// needs to be checked again after [`Condvar::wait`] returns.
+pub struct Condvar {
+    // incremented on every notification
+    seq: AtomicU32,
+}
+
+impl Condvar {
+    /// Creates a new condition variable
+    pub const fn new() -> Self {
+        Condvar {
+            seq: AtomicU32::new(0),
+        }
+    }
+
+    /// Releases the lock of given guard, blocks until this condition variable is notified and
+    /// acquires the lock again.
+    pub fn wait<'m, T: ?Sized>(&self, guard: MutexGuard<'m, T>) -> MutexGuard<'m, T> {
+        self.wait_until(guard, None).0
+    }
+
+    /// Like [`Condvar::wait`], but waits at most `timeout`.
+    ///
+    /// Returns the guard and whether the timeout expired.
+    pub fn wait_timeout<'m, T: ?Sized>(
+        &self,
+        guard: MutexGuard<'m, T>,
+        timeout: TimeDuration,
+    ) -> (MutexGuard<'m, T>, bool) {
+        self.wait_until(guard, Some(timeout))
+    }
+
+    /// Wakes up one activity that waits for this condition variable
+    pub fn notify_one(&self) {
+        self.seq.fetch_add(1, Ordering::Release);
+        tmif::futex_wake(self.seq_addr(), 1).ok();
+    }
+
+    /// Wakes up all activities that wait for this condition variable
+    pub fn notify_all(&self) {
+        self.seq.fetch_add(1, Ordering::Release);
+        tmif::futex_wake(self.seq_addr(), usize::MAX).ok();
+    }
+
+    fn wait_until<'m, T: ?Sized>(
+        &self,
+        guard: MutexGuard<'m, T>,
+        timeout: Option<TimeDuration>,
+    ) -> (MutexGuard<'m, T>, bool) {
+        // remember the sequence number before unlocking so that we don't miss notifications that
+        // arrive between unlocking and waiting (TileMux will not block us in this case)
+        let seq = self.seq.load(Ordering::Acquire);
+        let mutex = guard.lock;
+        drop(guard);
+
+        let res = tmif::futex_wait(self.seq_addr(), seq, timeout);
+
+        // others might wait for the lock as well, so that we have to wake them up on unlock
+        mutex.lock_contended();
+        let timed_out = matches!(res, Err(e) if e.code() == Code::Timeout);
+        (MutexGuard { lock: mutex }, timed_out)
+    }
+
+    fn seq_addr(&self) -> VirtAddr {
+        VirtAddr::from(self.seq.as_ptr() as *const u32)
+    }
+}
+
+impl Default for Condvar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Condvar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Condvar")
+    }
+}
@@ -0,0 +1,31 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! Contains synchronization primitives
+//!
+//! In contrast to [`Semaphore`](crate::com::Semaphore), which is implemented by the kernel, the
+//! primitives in this module are implemented in user space on top of a 32-bit word in memory. Only
+//! if an activity needs to wait, it asks TileMux to block it until another activity wakes it up
+//! (see [`tmif::futex_wait`](crate::tmif::futex_wait)). Therefore, the uncontended case does not
+//! require any call to TileMux or the kernel. Note that these primitives can only be used for
+//! synchronization between activities on the same tile, which share the memory that holds the
+//! primitive.
+
+mod condvar;
+mod mutex;
+
+pub use self::condvar::Condvar;
+pub use self::mutex::{Mutex, MutexGuard};
+pub use base::sync::{Arc, Weak};
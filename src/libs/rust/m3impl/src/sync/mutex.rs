@@ -0,0 +1,152 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::mem::VirtAddr;
+use crate::tmif;
+
+const UNLOCKED: u32 = 0;
+const LOCKED: u32 = 1;
+// locked and there are potentially activities waiting for the lock
+const CONTENDED: u32 = 2;
+
+/// A mutual exclusion lock for activities on the same tile
+///
+/// The lock is acquired in user space via an atomic operation. Only if the lock is held by another
+/// activity, the current activity is blocked by TileMux until the lock is released.
+pub struct Mutex<T: ?Sized> {
+    state: AtomicU32,
+    data: UnsafeCell<T>,
+}
+
+// safety: the lock ensures that only one activity at a time has access to the data
+unsafe impl<T: ?Sized + Send> Sync for Mutex<T> {
+}
+
+/// Provides access to the data protected by a [`Mutex`] and releases the lock on drop
+pub struct MutexGuard<'m, T: ?Sized> {
+    pub(super) lock: &'m Mutex<T>,
+}
+
+impl<T> Mutex<T> {
+    /// Creates a new unlocked mutex that protects `data`
+    pub const fn new(data: T) -> Self {
+        Mutex {
+            state: AtomicU32::new(UNLOCKED),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Consumes the mutex and returns the protected data
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    /// Acquires the lock, blocking the current activity until it is available
+    pub fn lock(&self) -> MutexGuard<'_, T> {
+        if self
+            .state
+            .compare_exchange(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            self.lock_contended();
+        }
+        MutexGuard { lock: self }
+    }
+
+    /// Tries to acquire the lock without blocking
+    ///
+    /// Returns `None` if the lock is currently held by someone else.
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+        self.state
+            .compare_exchange(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| MutexGuard { lock: self })
+    }
+
+    /// Returns true if the lock is currently held
+    pub fn is_locked(&self) -> bool {
+        self.state.load(Ordering::Relaxed) != UNLOCKED
+    }
+
+    /// Returns a mutable reference to the protected data
+    ///
+    /// No locking is required, because the mutable borrow guarantees exclusive access.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    pub(super) fn lock_contended(&self) {
+        // mark the lock as contended so that the owner wakes us up on unlock. if it was unlocked in
+        // the meantime, we got the lock (and might unnecessarily wake up someone later).
+        while self.state.swap(CONTENDED, Ordering::Acquire) != UNLOCKED {
+            // errors are treated like spurious wakeups; we check the state again anyway
+            tmif::futex_wait(self.state_addr(), CONTENDED, None).ok();
+        }
+    }
+
+    fn unlock(&self) {
+        if self.state.swap(UNLOCKED, Ordering::Release) == CONTENDED {
+            tmif::futex_wake(self.state_addr(), 1).ok();
+        }
+    }
+
+    fn state_addr(&self) -> VirtAddr {
+        VirtAddr::from(self.state.as_ptr() as *const u32)
+    }
+}
+
+impl<T: Default> Default for Mutex<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for Mutex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.try_lock() {
+            Some(guard) => write!(f, "Mutex {{ data: {:?} }}", &*guard),
+            None => write!(f, "Mutex {{ <locked> }}"),
+        }
+    }
+}
+
+impl<'m, T: ?Sized> Deref for MutexGuard<'m, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        // safety: we hold the lock
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'m, T: ?Sized> DerefMut for MutexGuard<'m, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // safety: we hold the lock
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<'m, T: ?Sized> Drop for MutexGuard<'m, T> {
+    fn drop(&mut self) {
+        self.lock.unlock();
+    }
+}
@@ -21,17 +21,18 @@
 use base::kif::{self, syscalls, CapRngDesc, Perm, INVALID_SEL};
 use base::tcu::TileId;
 
+use core::marker::PhantomData;
 use core::mem::MaybeUninit;
 
 use crate::build_vmsg;
 use crate::cap::Selector;
-use crate::cell::{LazyStaticRefCell, Ref, StaticRefCell};
+use crate::cell::{LazyStaticRefCell, Ref, StaticCell, StaticRefCell};
 use crate::cfg;
 use crate::com::{RecvGate, SendGate};
 use crate::errors::{Code, Error};
-use crate::mem::{GlobAddr, GlobOff, MsgBuf, VirtAddr};
+use crate::mem::{self, GlobAddr, GlobOff, MsgBuf, VirtAddr};
 use crate::quota::Quota;
-use crate::serialize::{Deserialize, M3Deserializer, M3Serializer, SliceSink};
+use crate::serialize::{Deserialize, M3Deserializer, M3Serializer, Serialize, SliceSink};
 use crate::tcu::{ActId, EpId, Label, Message, SYSC_SEP_OFF};
 use crate::tiles::TileQuota;
 use crate::time::TimeDuration;
@@ -40,6 +41,8 @@ static SGATE: LazyStaticRefCell<SendGate> = LazyStaticRefCell::default();
 // use a separate message buffer here, because the default buffer could be in use for a message over
 // a SendGate, which might have to be activated first using a syscall.
 static SYSC_BUF: StaticRefCell<MsgBuf> = StaticRefCell::new(MsgBuf::new_initialized());
+// the event of the currently outstanding asynchronous system call, if any
+static ASYNC_EVENT: StaticCell<Option<u64>> = StaticCell::new(None);
 
 struct Reply<R> {
     msg: &'static Message,
@@ -54,6 +57,11 @@ impl<R> Drop for Reply<R> {
 
 #[inline(always)]
 fn send_receive<'de, R: Deserialize<'de>>(buf: &MsgBuf) -> Result<Reply<R>, Error> {
+    // the syscall receive buffer has space for a single reply only
+    if ASYNC_EVENT.get().is_some() {
+        return Err(Error::new(Code::NoCredits));
+    }
+
     let reply_raw = SGATE.borrow().call(buf, RecvGate::syscall())?;
 
     let mut de = M3Deserializer::new(reply_raw.as_words());
@@ -77,6 +85,110 @@ fn send_receive_result(buf: &MsgBuf) -> Result<(), Error> {
     send_receive::<Empty>(buf).map(|_| ())
 }
 
+/// A ticket for an outstanding asynchronous system call, created by [`send_async`]
+///
+/// The reply of the system call is delivered via [`fetch_async_reply`] together with the event
+/// that has been passed to [`send_async`]. Multithreaded programs will typically forward the reply
+/// to the thread waiting for this event (e.g., via `thread::notify`), which in turn completes the
+/// system call via [`AsyncTicket::finish`] or [`AsyncTicket::finish_result`].
+#[must_use]
+pub struct AsyncTicket<R> {
+    event: u64,
+    _phantom: PhantomData<R>,
+}
+
+impl<R: Deserialize<'static>> AsyncTicket<R> {
+    /// Returns the event that is passed to the callback of [`fetch_async_reply`] as soon as the
+    /// reply for this system call has been received.
+    pub fn event(&self) -> u64 {
+        self.event
+    }
+
+    /// Completes the system call with the given reply and returns the reply data.
+    ///
+    /// `reply` is expected to be a copy of the message passed to the callback of
+    /// [`fetch_async_reply`]. If `reply` is `None`, the system call is considered aborted and
+    /// [`Code::RecvGone`] is returned.
+    pub fn finish(self, reply: Option<&'static Message>) -> Result<R, Error> {
+        let reply = reply.ok_or_else(|| Error::new(Code::RecvGone))?;
+
+        let mut de = M3Deserializer::new(reply.as_words());
+        let res: Code = de.pop()?;
+        if res != Code::Success {
+            return Err(Error::new(res));
+        }
+
+        de.pop()
+    }
+}
+
+impl AsyncTicket<()> {
+    /// Completes a system call without reply data and returns its result.
+    ///
+    /// See [`AsyncTicket::finish`].
+    pub fn finish_result(self, reply: Option<&'static Message>) -> Result<(), Error> {
+        let reply = reply.ok_or_else(|| Error::new(Code::RecvGone))?;
+
+        let mut de = M3Deserializer::new(reply.as_words());
+        let res: Code = de.pop()?;
+        match res {
+            Code::Success => Ok(()),
+            e => Err(Error::new(e)),
+        }
+    }
+}
+
+/// Starts the system call `op` with the arguments `args` without waiting for the reply.
+///
+/// In contrast to the other functions in this module, the caller does not block until the kernel
+/// has replied. Instead, the reply is later obtained via [`fetch_async_reply`], which passes the
+/// given `event` to identify the system call. Note that only one system call can be outstanding at
+/// a time, because the kernel replies into a single-slot receive buffer. Therefore, both this
+/// function and all synchronous system calls fail with [`Code::NoCredits`] until the reply of the
+/// outstanding system call has been fetched.
+pub fn send_async<A: Serialize, R>(
+    op: syscalls::Operation,
+    args: &A,
+    event: u64,
+) -> Result<AsyncTicket<R>, Error> {
+    if ASYNC_EVENT.get().is_some() {
+        return Err(Error::new(Code::NoCredits));
+    }
+
+    let mut buf = SYSC_BUF.borrow_mut();
+    build_vmsg!(buf, op, args);
+    SGATE.borrow().send(&buf, RecvGate::syscall())?;
+
+    ASYNC_EVENT.set(Some(event));
+    Ok(AsyncTicket {
+        event,
+        _phantom: PhantomData,
+    })
+}
+
+/// Fetches the reply of the outstanding asynchronous system call, if it has already been received.
+///
+/// If so, `func` is called with the event given to [`send_async`] and the reply message, which is
+/// marked as read afterwards. Thus, `func` needs to copy the message if it should be used
+/// afterwards. Returns true if a reply has been fetched.
+pub fn fetch_async_reply<F: FnOnce(u64, &'static Message)>(func: F) -> bool {
+    let event = match ASYNC_EVENT.get() {
+        Some(ev) => ev,
+        None => return false,
+    };
+
+    let rgate = RecvGate::syscall();
+    match rgate.fetch() {
+        Ok(msg) => {
+            ASYNC_EVENT.set(None);
+            func(event, msg);
+            rgate.ack_msg(msg).ok();
+            true
+        },
+        Err(_) => false,
+    }
+}
+
 #[doc(hidden)]
 pub fn send_gate() -> Ref<'static, SendGate> {
     SGATE.borrow()
@@ -252,6 +364,16 @@ pub fn create_sem(dst: Selector, value: u32) -> Result<(), Error> {
     send_receive_result(&buf)
 }
 
+/// Creates a new gang at selector `dst` that consists of `size` activities.
+pub fn create_gang(dst: Selector, size: u32) -> Result<(), Error> {
+    let mut buf = SYSC_BUF.borrow_mut();
+    build_vmsg!(buf, syscalls::Operation::CreateGang, syscalls::CreateGang {
+        dst,
+        size
+    });
+    send_receive_result(&buf)
+}
+
 /// Allocates a new endpoint for the given activity at selector `dst`. Optionally, it can have `replies`
 /// reply slots attached to it (for receive gate activations).
 pub fn alloc_ep(dst: Selector, act: Selector, epid: EpId, replies: usize) -> Result<EpId, Error> {
@@ -309,12 +431,17 @@ pub fn derive_kmem(kmem: Selector, dst: Selector, quota: usize) -> Result<(), Er
 /// If a value is not `None`, the corresponding amount is substracted from the current quota (and
 /// therefore, needs to be available). If a value is `None`, the quota will be shared with the
 /// current tile object.
+///
+/// `irqs` is the mask of external interrupts that activities on the new tile object may use,
+/// which needs to be a subset of the interrupts permitted for `tile`. If `irqs` is `None`, the new
+/// tile object permits the same interrupts as `tile`.
 pub fn derive_tile(
     tile: Selector,
     dst: Selector,
     eps: Option<usize>,
     time: Option<TimeDuration>,
     pts: Option<usize>,
+    irqs: Option<u32>,
 ) -> Result<(), Error> {
     let mut buf = SYSC_BUF.borrow_mut();
     build_vmsg!(buf, syscalls::Operation::DeriveTile, syscalls::DeriveTile {
@@ -323,6 +450,7 @@ pub fn derive_tile(
         eps,
         time: time.map(|t| t.as_nanos() as u64),
         pts,
+        irqs,
     });
     send_receive_result(&buf)
 }
@@ -505,6 +633,21 @@ pub fn tile_reset(tile: Selector, mux_mem: Selector, ep_count: Option<usize>) ->
     send_receive_result(&buf)
 }
 
+/// Performs the tile operation `op` with the given tile.
+///
+/// A tile can only be taken offline if it is not in use (no activities and no multiplexer). An
+/// offline tile is brought online with the new description `desc`, which allows to reconfigure
+/// tiles at runtime. This call requires a non-derived tile capability.
+pub fn tile_ctrl(tile: Selector, op: syscalls::TileOp, desc: kif::TileDesc) -> Result<(), Error> {
+    let mut buf = SYSC_BUF.borrow_mut();
+    build_vmsg!(buf, syscalls::Operation::TileCtrl, syscalls::TileCtrl {
+        tile,
+        op,
+        desc
+    });
+    send_receive_result(&buf)
+}
+
 /// Performs the activity operation `op` with the given activity.
 pub fn activity_ctrl(act: Selector, op: syscalls::ActivityOp, arg: u64) -> Result<(), Error> {
     let mut buf = SYSC_BUF.borrow_mut();
@@ -522,6 +665,19 @@ pub fn activity_ctrl(act: Selector, op: syscalls::ActivityOp, arg: u64) -> Resul
     }
 }
 
+/// Returns the CPU time that the activity with selector `act` has consumed so far.
+///
+/// This is only supported for activities on tiles with TileMux.
+pub fn activity_time(act: Selector) -> Result<TimeDuration, Error> {
+    let mut buf = SYSC_BUF.borrow_mut();
+    build_vmsg!(buf, syscalls::Operation::ActTime, syscalls::ActivityTime {
+        act
+    });
+
+    let reply: Reply<syscalls::ActivityTimeReply> = send_receive(&buf)?;
+    Ok(TimeDuration::from_nanos(reply.data.nanos))
+}
+
 /// Waits until any of the given activities exits.
 ///
 /// If `event` is non-zero, the kernel replies immediately and acknowledges the validity of the
@@ -554,14 +710,42 @@ pub fn activity_wait(sels: &[Selector], event: u64) -> Result<(Selector, Code),
     }
 }
 
+/// Performs the gang operation `op` with the given gang and activity.
+pub fn gang_ctrl(gang: Selector, act: Selector, op: syscalls::GangOp) -> Result<(), Error> {
+    let mut buf = SYSC_BUF.borrow_mut();
+    build_vmsg!(buf, syscalls::Operation::GangCtrl, syscalls::GangCtrl {
+        gang,
+        act,
+        op
+    });
+    send_receive_result(&buf)
+}
+
 /// Performs the semaphore operation `op` with the given semaphore.
-pub fn sem_ctrl(sem: Selector, op: syscalls::SemOp) -> Result<(), Error> {
+///
+/// Returns the value of the semaphore after the operation.
+pub fn sem_ctrl(sem: Selector, op: syscalls::SemOp) -> Result<u32, Error> {
+    sem_ctrl_with(sem, op, TimeDuration::ZERO)
+}
+
+/// Performs the semaphore operation `op` with the given semaphore and timeout.
+///
+/// The timeout is only used by [`DownFor`](syscalls::SemOp::DownFor). Returns the value of the
+/// semaphore after the operation.
+pub fn sem_ctrl_with(
+    sem: Selector,
+    op: syscalls::SemOp,
+    timeout: TimeDuration,
+) -> Result<u32, Error> {
     let mut buf = SYSC_BUF.borrow_mut();
     build_vmsg!(buf, syscalls::Operation::SemCtrl, syscalls::SemCtrl {
         sem,
-        op
+        op,
+        timeout: timeout.as_nanos() as u64,
     });
-    send_receive_result(&buf)
+
+    let reply: Reply<syscalls::SemCtrlReply> = send_receive(&buf)?;
+    Ok(reply.data.value)
 }
 
 /// Exchanges capabilities between your activity and the activity `act`.
@@ -574,14 +758,33 @@ pub fn exchange(
     other: Selector,
     obtain: bool,
 ) -> Result<(), Error> {
-    let mut buf = SYSC_BUF.borrow_mut();
-    build_vmsg!(buf, syscalls::Operation::Exchange, syscalls::Exchange {
-        act,
-        own,
-        other,
-        obtain,
-    });
-    send_receive_result(&buf)
+    exchange_bulk(act, &[syscalls::ExchangeDesc::new(own, other)], obtain)
+}
+
+/// Exchanges multiple capability ranges between your activity and the activity `act`.
+///
+/// Each descriptor is handled like a call to [`exchange`] with the same `obtain` argument. The
+/// descriptors are transferred in batches of at most [`syscalls::MAX_EXCHG_DESCS`] per system
+/// call and the exchange stops at the first descriptor that fails.
+pub fn exchange_bulk(
+    act: Selector,
+    descs: &[syscalls::ExchangeDesc],
+    obtain: bool,
+) -> Result<(), Error> {
+    for chunk in descs.chunks(syscalls::MAX_EXCHG_DESCS) {
+        let mut buf = SYSC_BUF.borrow_mut();
+
+        let mut all_descs = [syscalls::ExchangeDesc::default(); syscalls::MAX_EXCHG_DESCS];
+        all_descs[0..chunk.len()].copy_from_slice(chunk);
+        build_vmsg!(buf, syscalls::Operation::Exchange, syscalls::Exchange {
+            act,
+            obtain,
+            desc_count: chunk.len(),
+            descs: all_descs,
+        });
+        send_receive_result(&buf)?;
+    }
+    Ok(())
 }
 
 /// Delegates the capabilities `crd` of activity `act` via the session `sess` to the server managing the
@@ -715,6 +918,22 @@ pub fn reset_stats() -> Result<(), Error> {
     send_receive_result(&buf)
 }
 
+/// Retrieves runtime statistics of the kernel and the activity at `act`.
+///
+/// The statistics contain the number of capabilities and system calls of the activity, but also
+/// global information like the total number of system calls, the state of the kernel's message
+/// queues, and the usage of user memory. These are intended for debugging purposes like finding
+/// capability leaks or memory fragmentation.
+pub fn get_stats(act: Selector) -> Result<syscalls::GetStatsReply, Error> {
+    let mut buf = SYSC_BUF.borrow_mut();
+    build_vmsg!(buf, syscalls::Operation::GetStats, syscalls::GetStats {
+        act
+    });
+
+    let reply: Reply<syscalls::GetStatsReply> = send_receive(&buf)?;
+    Ok(reply.data.clone())
+}
+
 /// The noop system call for benchmarking
 pub fn noop() -> Result<(), Error> {
     let mut buf = SYSC_BUF.borrow_mut();
@@ -722,6 +941,11 @@ pub fn noop() -> Result<(), Error> {
     send_receive_result(&buf)
 }
 
+pub(crate) fn forget_inherited() {
+    mem::forget(SGATE.unset());
+    ASYNC_EVENT.set(None);
+}
+
 pub(crate) fn init() {
     let env = crate::env::get();
     SGATE.set(SendGate::new_def(
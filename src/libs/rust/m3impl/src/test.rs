@@ -20,12 +20,55 @@
 
 use core::fmt::{Display, Formatter};
 
+use crate::col::{String, ToString, Vec};
+use crate::env;
+use crate::format;
 use crate::println;
 
+/// The environment variable that holds a comma-separated list of filters (see
+/// [`DefaultWvTester::with_filter`])
+pub const FILTER_VAR: &str = "WV_FILTER";
+
+/// The environment variable that stops the tests after the first failed test if set to "1"
+pub const FAIL_FAST_VAR: &str = "WV_FAIL_FAST";
+
+/// A fixture with functions that are called before the first and after the last test of a suite
+///
+/// The functions are only called if at least one test of the suite is actually run, that is, not
+/// excluded by a filter.
+#[derive(Copy, Clone, Debug)]
+pub struct WvFixture {
+    setup: fn(),
+    teardown: fn(),
+}
+
+impl WvFixture {
+    /// Creates a new fixture with given setup and teardown functions
+    pub const fn new(setup: fn(), teardown: fn()) -> Self {
+        Self { setup, teardown }
+    }
+
+    /// Calls the setup function
+    pub fn setup(&self) {
+        (self.setup)()
+    }
+
+    /// Calls the teardown function
+    pub fn teardown(&self) {
+        (self.teardown)()
+    }
+}
+
 /// Runs the tests
 pub trait WvTester {
     /// Runs the given test suite
     fn run_suite(&mut self, name: &str, f: &dyn Fn(&mut dyn WvTester));
+    /// Runs the given test suite with given fixture
+    fn run_suite_with(&mut self, name: &str, fixture: WvFixture, f: &dyn Fn(&mut dyn WvTester)) {
+        fixture.setup();
+        self.run_suite(name, f);
+        fixture.teardown();
+    }
     /// Runs the given test
     fn run_test(&mut self, name: &str, file: &str, f: &dyn Fn(&mut dyn WvTester));
     /// Is called on succeeded failures
@@ -34,14 +77,98 @@ pub trait WvTester {
     fn test_failed(&mut self);
 }
 
+/// A filter that selects test suites and tests by name
+///
+/// A filter of the form `<suite>` selects all tests of the suites whose name starts with
+/// `<suite>`, whereas `<suite>/<test>` selects the tests within these suites whose name contains
+/// `<test>`.
+#[derive(Clone, Debug)]
+struct Filter {
+    suite: String,
+    test: Option<String>,
+}
+
+impl Filter {
+    fn new(filter: &str) -> Self {
+        match filter.split_once('/') {
+            Some((suite, test)) => Self {
+                suite: suite.to_string(),
+                test: Some(test.to_string()),
+            },
+            None => Self {
+                suite: filter.to_string(),
+                test: None,
+            },
+        }
+    }
+
+    fn matches_suite(&self, suite: &str) -> bool {
+        suite.starts_with(self.suite.as_str())
+    }
+
+    fn matches_test(&self, suite: &str, test: &str) -> bool {
+        self.matches_suite(suite)
+            && self
+                .test
+                .as_ref()
+                .map_or(true, |t| test.contains(t.as_str()))
+    }
+}
+
 /// The default implementation for the [`WvTester`]
-#[derive(Default, Copy, Clone, Debug)]
+///
+/// By default, all tests are run and the tester continues after failed assertions so that the
+/// final summary (see the [`Display`] implementation) lists all failed tests. Note that
+/// [`wv_assert_ok`] and [`wv_assert_some`] stop the tests in any case, because they cannot produce
+/// a value on failure.
+#[derive(Default, Clone, Debug)]
 pub struct DefaultWvTester {
     tests: u64,
     fails: u64,
+    skipped: u64,
+    filters: Vec<Filter>,
+    fail_fast: bool,
+    suite: String,
+    pending_fixture: Option<WvFixture>,
+    failed_tests: Vec<String>,
 }
 
 impl DefaultWvTester {
+    /// Creates a new tester that is configured via the command line and the environment
+    ///
+    /// All command-line arguments (except for the program name) and the comma-separated entries in
+    /// [`FILTER_VAR`] are used as filters. The tester stops after the first failed test if
+    /// [`FAIL_FAST_VAR`] is set to "1".
+    pub fn from_env() -> Self {
+        let mut tester = Self::default();
+        for arg in env::args().skip(1) {
+            tester = tester.with_filter(arg);
+        }
+        if let Some(filters) = env::var(FILTER_VAR) {
+            for f in filters.split(',').filter(|f| !f.is_empty()) {
+                tester = tester.with_filter(f);
+            }
+        }
+        tester.with_fail_fast(env::var(FAIL_FAST_VAR).map_or(false, |v| v == "1"))
+    }
+
+    /// Adds the given filter
+    ///
+    /// If at least one filter is present, only the tests that match any filter are run. A filter
+    /// of the form `<suite>` selects all tests of the suites whose name starts with `<suite>`,
+    /// whereas `<suite>/<test>` selects the tests within these suites whose name contains
+    /// `<test>`.
+    pub fn with_filter(mut self, filter: &str) -> Self {
+        self.filters.push(Filter::new(filter));
+        self
+    }
+
+    /// Sets whether the tests should be stopped after the first failed test
+    pub fn with_fail_fast(mut self, fail_fast: bool) -> Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+
     pub fn tests(&self) -> u64 {
         self.tests
     }
@@ -53,19 +180,67 @@ impl DefaultWvTester {
     pub fn successes(&self) -> u64 {
         self.tests - self.fails
     }
+
+    /// Returns the number of tests that have been skipped due to filters
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+
+    fn suite_selected(&self, suite: &str) -> bool {
+        self.filters.is_empty() || self.filters.iter().any(|f| f.matches_suite(suite))
+    }
+
+    fn test_selected(&self, suite: &str, test: &str) -> bool {
+        self.filters.is_empty() || self.filters.iter().any(|f| f.matches_test(suite, test))
+    }
 }
 
 impl WvTester for DefaultWvTester {
     fn run_suite(&mut self, name: &str, f: &dyn Fn(&mut dyn WvTester)) {
+        if !self.suite_selected(name) {
+            println!("Skipping test suite {}\n", name);
+            return;
+        }
+
         println!("Running test suite {} ...\n", name);
+        let old_suite = core::mem::replace(&mut self.suite, name.to_string());
         f(self);
+        self.suite = old_suite;
         println!();
     }
 
+    fn run_suite_with(&mut self, name: &str, fixture: WvFixture, f: &dyn Fn(&mut dyn WvTester)) {
+        // the setup is done lazily before the first test that is not filtered
+        self.pending_fixture = Some(fixture);
+        self.run_suite(name, f);
+        // if the fixture is still pending, no test has been run
+        if self.pending_fixture.take().is_none() {
+            fixture.teardown();
+        }
+    }
+
     fn run_test(&mut self, name: &str, file: &str, f: &dyn Fn(&mut dyn WvTester)) {
+        if !self.test_selected(&self.suite, name) {
+            self.skipped += 1;
+            return;
+        }
+
+        if let Some(fixture) = self.pending_fixture.take() {
+            fixture.setup();
+        }
+
         println!("Testing \"{}\" in {}:", name, file);
+        let fails_before = self.fails;
         f(self);
         println!();
+
+        if self.fails > fails_before {
+            self.failed_tests.push(format!("{}/{}", self.suite, name));
+            if self.fail_fast {
+                println!("{}", self);
+                panic!("Stopping tests here.");
+            }
+        }
     }
 
     fn test_succeeded(&mut self) {
@@ -86,21 +261,33 @@ impl Display for DefaultWvTester {
                 "\x1B[1;31m{} of {} tests failed\x1B[0;m",
                 self.failures(),
                 self.tests()
-            )
+            )?;
+            for t in &self.failed_tests {
+                write!(f, "\n  failed: {}", t)?;
+            }
         }
         else {
-            write!(f, "\x1B[1;32mAll tests successful!\x1B[0;m")
+            write!(f, "\x1B[1;32mAll tests successful!\x1B[0;m")?;
+        }
+        if self.skipped() > 0 {
+            write!(f, " ({} tests skipped)", self.skipped())?;
         }
+        Ok(())
     }
 }
 
 /// Convenience macro that calls [`WvTester::run_suite`](WvTester::run_suite) and uses the function
 /// name as suite name
+///
+/// If a [`WvFixture`] is given as third argument, [`WvTester::run_suite_with`] is called instead.
 #[macro_export]
 macro_rules! wv_run_suite {
     ($t:expr, $func:path) => {
         $t.run_suite(stringify!($func), &$func)
     };
+    ($t:expr, $func:path, $fixture:expr) => {
+        $t.run_suite_with(stringify!($func), $fixture, &$func)
+    };
 }
 
 /// Convenience macro that calls [`WvTester::run_test`](WvTester::run_test) and uses the function
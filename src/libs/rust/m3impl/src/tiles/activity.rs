@@ -28,11 +28,12 @@ use crate::com::MemGate;
 use crate::errors::Error;
 use crate::kif;
 use crate::kif::{CapRngDesc, TileDesc};
-use crate::mem::{GlobOff, VirtAddr};
+use crate::mem::{self, GlobOff, VirtAddr};
 use crate::rc::Rc;
 use crate::syscalls;
 use crate::tcu::{ActId, EpId, TileId};
 use crate::tiles::{KMem, OwnActivity, Tile};
+use crate::time::TimeDuration;
 
 /// Represents an activity on a tile
 ///
@@ -110,6 +111,13 @@ impl Activity {
         self.pager.as_ref()
     }
 
+    /// Returns the CPU time that this activity has consumed so far.
+    ///
+    /// This is only supported for activities on tiles with TileMux.
+    pub fn cpu_time(&self) -> Result<TimeDuration, Error> {
+        syscalls::activity_time(self.sel())
+    }
+
     /// Revokes the given capability range from `self`.
     ///
     /// If `del_only` is true, only the delegations are revoked, that is, the capability is not
@@ -142,3 +150,8 @@ impl fmt::Debug for Activity {
 pub(crate) fn init() {
     OWN.set(OwnActivity::new());
 }
+
+pub(crate) fn forget_inherited() {
+    // safety: we are called during initialization, before anybody can hold a reference
+    mem::forget(unsafe { OWN.unset() });
+}
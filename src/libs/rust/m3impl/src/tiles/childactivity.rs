@@ -22,7 +22,7 @@ use core::cmp;
 use core::fmt;
 use core::ops::{Deref, DerefMut};
 
-use base::kif::syscalls::MuxType;
+use base::kif::syscalls::{ExchangeDesc, MuxType};
 
 use crate::cap::{CapFlags, Capability, SelSpace, Selector};
 use crate::cell::Cell;
@@ -31,7 +31,10 @@ use crate::client::{Pager, ResMngChild};
 use crate::col::{String, ToString, Vec};
 use crate::com::SendCap;
 use crate::env::{self, Env};
-use crate::errors::Error;
+use crate::errors::{Code, Error};
+use crate::format;
+use crate::io::log::LogFormat;
+use crate::io::LogFlags;
 use crate::kif::{self, CapRngDesc, CapType};
 use crate::mem::{self, GlobOff, VirtAddr};
 use crate::rc::Rc;
@@ -55,13 +58,16 @@ use crate::vfs::{BufReader, Fd, File, FileRef, OpenFlags, VFS};
 /// - capabilities (see [`ChildActivity::delegate`])
 /// - files (see [`ChildActivity::add_file`])
 /// - mount points (see [`ChildActivity::add_mount`])
+/// - environment variables (see [`ChildActivity::set_env`])
 ///
 /// Finally, child activities are started with either:
 /// - [`ChildActivity::start`] to run on a non-programmable accelerator
 /// - [`ChildActivity::run`] to execute a function of our program in the child activity
+/// - [`ChildActivity::run_clone`] to execute a function in a copy-on-write clone of our address
+///   space
 /// - [`ChildActivity::exec`] to execute a given executable in the child activity
 ///
-/// All three variants consume [`ChildActivity`] and yield a [`RunningActivity`] that holds the
+/// All four variants consume [`ChildActivity`] and yield a [`RunningActivity`] that holds the
 /// activity during its execution and allows to stop it forcefully or wait until its completion.
 ///
 /// # Example
@@ -83,6 +89,7 @@ pub struct ChildActivity {
     rmng: ResMngChild,
     files: Vec<(Fd, Fd)>,
     mounts: Vec<(String, String)>,
+    env: Vec<String>,
 }
 
 /// The arguments for [`ChildActivity`] creations.
@@ -161,6 +168,8 @@ impl ChildActivity {
             child_sel: Cell::from(args.first_sel),
             files: Vec::new(),
             mounts: Vec::new(),
+            // the child inherits our environment by default
+            env: crate::env::vars_raw(),
         };
 
         // create activity
@@ -263,6 +272,61 @@ impl ChildActivity {
         }
     }
 
+    /// Returns the environment variables in the form `key`=`value` that are going to be passed to
+    /// this child activity on [`run`](ChildActivity::run) and [`exec`](ChildActivity::exec).
+    pub fn env(&self) -> &[String] {
+        &self.env
+    }
+
+    /// Sets the environment variable `key` to `val` for this child activity.
+    ///
+    /// Initially, child activities inherit the environment variables of the current activity at
+    /// the time of their creation. Changes to the environment of a child activity do not affect the
+    /// environment of the current activity and vice versa.
+    pub fn set_env<K: AsRef<str>, V: AsRef<str>>(&mut self, key: K, val: V) {
+        assert!(!key.as_ref().contains('='));
+
+        let var = format!("{}={}", key.as_ref(), val.as_ref());
+        if let Some(pair) = self
+            .env
+            .iter_mut()
+            .find(|p| Self::is_env_var(p, key.as_ref()))
+        {
+            *pair = var;
+        }
+        else {
+            self.env.push(var);
+        }
+    }
+
+    /// Removes the environment variable `key` for this child activity.
+    pub fn remove_env<K: AsRef<str>>(&mut self, key: K) {
+        assert!(!key.as_ref().contains('='));
+
+        self.env.retain(|p| !Self::is_env_var(p, key.as_ref()));
+    }
+
+    /// Removes all environment variables for this child activity.
+    pub fn clear_env(&mut self) {
+        self.env.clear();
+    }
+
+    fn lib_path(&self) -> &str {
+        // the search path for shared objects can be changed via LD_LIBRARY_PATH
+        self.env_var("LD_LIBRARY_PATH").unwrap_or("/lib")
+    }
+
+    fn env_var(&self, key: &str) -> Option<&str> {
+        self.env
+            .iter()
+            .find(|p| Self::is_env_var(p, key))
+            .map(|p| &p[key.len() + 1..])
+    }
+
+    fn is_env_var(pair: &str, key: &str) -> bool {
+        pair.starts_with(key) && pair.as_bytes().get(key.len()) == Some(&b'=')
+    }
+
     /// Returns a sink for the activity-local data
     ///
     /// The sink overwrites the activity-local data and will be transmitted to the activity when calling
@@ -292,6 +356,20 @@ impl ChildActivity {
         Ok(())
     }
 
+    /// Delegates all given capability ranges of [`Activity::own`](Activity::own) to `self`.
+    ///
+    /// Each descriptor specifies the capability range of [`Activity::own`](Activity::own) and the
+    /// first destination selector in `self`. In contrast to multiple calls of
+    /// [`delegate_to`](ChildActivity::delegate_to), the capabilities are exchanged in as few
+    /// system calls as possible.
+    pub fn delegate_bulk(&self, descs: &[ExchangeDesc]) -> Result<(), Error> {
+        syscalls::exchange_bulk(self.sel(), descs, false)?;
+        if let Some(max) = descs.iter().map(|d| d.other + d.own.count()).max() {
+            self.child_sel.set(cmp::max(self.child_sel.get(), max));
+        }
+        Ok(())
+    }
+
     /// Obtains the object capability with selector `sel` from `self` to
     /// [`Activity::own`](Activity::own).
     pub fn obtain_obj(&self, sel: Selector) -> Result<Selector, Error> {
@@ -312,6 +390,13 @@ impl ChildActivity {
         syscalls::exchange(self.sel(), own, crd.start(), true)
     }
 
+    /// Changes the log flags and format of this activity while it is running.
+    ///
+    /// See [`env::set_log`] for details.
+    pub fn set_log(&self, flags: LogFlags, format: LogFormat) -> Result<(), Error> {
+        env::set_log(self.sel(), flags, format)
+    }
+
     /// Starts the activity without running any code on it. This is intended for non-programmable
     /// accelerators and devices that implement the TileMux protocol to get started, but don't
     /// execute any code.
@@ -353,6 +438,47 @@ impl ChildActivity {
         }
     }
 
+    /// Clones the address space of [`Activity::own`](Activity::own) into this activity and calls
+    /// the given function in the child.
+    ///
+    /// In contrast to [`run`](Self::run), the program is not loaded from the file system. Instead,
+    /// the pager shares all pages with the child and marks writable pages as copy-on-write. That is,
+    /// the first write access to such a page by either activity creates a copy of the page.
+    /// Therefore, the child starts with the heap and static data that the parent has at the time of
+    /// this call.
+    ///
+    /// This has a few requirements/limitations:
+    /// 1. both activities need to have a pager and the pager of this activity needs to be a clone
+    ///    of the own pager, which is the default (see [`ActivityArgs::pager`])
+    /// 2. libm3 is reinitialized in the child without dropping the inherited objects. All other
+    ///    inherited objects (e.g., gates stored by the application) still refer to capabilities
+    ///    of the parent and can therefore not be used by the child.
+    ///
+    /// The method returns the [`RunningProgramActivity`] on success that can be used to wait for
+    /// the functions completeness or to stop it.
+    pub fn run_clone(
+        self,
+        func: fn() -> Result<(), Error>,
+    ) -> Result<RunningProgramActivity, Error> {
+        if self.tile().mux_type()? != MuxType::TileMux || Activity::own().pager().is_none() {
+            return Err(Error::new(Code::NotSup));
+        }
+        let pager = self.pager().ok_or_else(|| Error::new(Code::NotSup))?;
+
+        self.obtain_files_and_mounts()?;
+
+        // let the pager clone our address space; this replaces all mappings of the child
+        pager.clone()?;
+
+        // start the child at our own entry point, which will call the closure after initialization
+        let args = crate::env::args().collect::<Vec<_>>();
+        let func_addr = VirtAddr::from(func as *const ());
+        self.load_environment(&args, Some(func_addr), crate::env::get().entry(), &[], true)?;
+
+        let act = RunningProgramActivity::new(self, None);
+        act.start().map(|_| act)
+    }
+
     /// Executes the given program and arguments with `self`.
     ///
     /// The method returns the [`RunningProgramActivity`] on success that can be used to wait for
@@ -393,18 +519,25 @@ impl ChildActivity {
     ) -> Result<RunningProgramActivity, Error> {
         self.obtain_files_and_mounts()?;
 
-        let (file, entry) = if let Some((mapper, file)) = program {
+        let (file, prog) = if let Some((mapper, file)) = program {
             let mut file = BufReader::new(file);
-            let entry = loader::load_program(&self, mapper, &mut file)?;
-            (Some(file), entry)
+            let debug = self.env_var(env::GDBSTUB_VAR).is_some();
+            let prog = loader::load_program(&self, mapper, &mut file, self.lib_path(), debug)?;
+            (Some(file), Some(prog))
         }
         else {
-            (None, VirtAddr::null())
+            (None, None)
         };
 
-        self.load_environment(args, closure, entry)?;
+        let (entry, dl_objs, libs) = match prog {
+            Some(p) => (p.entry, p.dl_objs, p.libs),
+            None => (VirtAddr::null(), Vec::new(), Vec::new()),
+        };
+
+        self.load_environment(args, closure, entry, &dl_objs, false)?;
 
-        let act = RunningProgramActivity::new(self, file);
+        let mut act = RunningProgramActivity::new(self, file);
+        act.set_libs(libs);
         act.start().map(|_| act)
     }
 
@@ -420,6 +553,8 @@ impl ChildActivity {
         args: &[S],
         closure: Option<VirtAddr>,
         entry: VirtAddr,
+        dl_objs: &[u64],
+        cloned: bool,
     ) -> Result<(), Error> {
         let mem = self.get_mem(cfg::ENV_START, cfg::ENV_SIZE as GlobOff, kif::Perm::RW)?;
 
@@ -438,6 +573,7 @@ impl ChildActivity {
         if let Some(addr) = closure {
             cenv.set_closure(addr);
         }
+        cenv.set_cloned(cloned);
 
         if let Some(ref pg) = self.pager {
             cenv.set_pager(pg);
@@ -452,18 +588,18 @@ impl ChildActivity {
         let env_off = cfg::ENV_START.as_goff();
         cenv.set_argc(args.len());
         cenv.set_argv(env::write_args(args, &mem, &mut addr, env_off)?);
-        cenv.set_envp(env::write_args(
-            &crate::env::vars_raw(),
-            &mem,
-            &mut addr,
-            env_off,
-        )?);
+        cenv.set_envp(env::write_args(&self.env, &mem, &mut addr, env_off)?);
 
         // serialize files, mounts, and data and write them to the child's memory
         let write_words =
             |words: &[u64], addr: VirtAddr| mem.write(words, (addr - cfg::ENV_START).as_goff());
         self.serialize_files(write_words, &mut cenv, &mut addr)?;
         self.serialize_mounts(write_words, &mut cenv, &mut addr)?;
+        if !dl_objs.is_empty() {
+            write_words(dl_objs, addr)?;
+            cenv.set_dl_objs(addr, mem::size_of_val(dl_objs));
+            addr += mem::size_of_val(dl_objs);
+        }
         self.serialize_data(write_words, &mut cenv, &mut addr)?;
 
         // write environment to tile
@@ -0,0 +1,70 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use crate::cap::{CapFlags, Capability, SelSpace, Selector};
+use crate::errors::Error;
+use crate::kif;
+use crate::syscalls;
+use crate::tiles::ChildActivity;
+
+/// A group of activities that are started and stopped together
+///
+/// A gang is created with a fixed number of members. Afterwards, the not yet started activities
+/// join the gang via [`Gang::join`]. Starting a member of the gang is deferred until all members
+/// have joined the gang and have been started, at which point the kernel starts all members at
+/// once. Similarly, stopping a member of a started gang (e.g., by dropping the
+/// [`RunningActivity`](crate::tiles::RunningActivity)) stops all other members as well.
+#[derive(Debug)]
+pub struct Gang {
+    cap: Capability,
+}
+
+impl Gang {
+    /// Creates a new gang that consists of `size` activities
+    pub fn new(size: u32) -> Result<Self, Error> {
+        let sel = SelSpace::get().alloc_sel();
+        syscalls::create_gang(sel, size)?;
+
+        Ok(Gang {
+            cap: Capability::new(sel, CapFlags::empty()),
+        })
+    }
+
+    /// Binds a gang to the given selector.
+    pub fn bind(sel: Selector) -> Self {
+        Gang {
+            cap: Capability::new(sel, CapFlags::KEEP_CAP),
+        }
+    }
+
+    /// Returns the capability selector
+    pub fn sel(&self) -> Selector {
+        self.cap.sel()
+    }
+
+    /// Adds the given activity to this gang
+    ///
+    /// The activity must not have been started yet and must not be a member of another gang.
+    pub fn join(&self, act: &ChildActivity) -> Result<(), Error> {
+        syscalls::gang_ctrl(self.sel(), act.sel(), kif::syscalls::GangOp::Join)
+    }
+
+    /// Removes the given activity from this gang
+    ///
+    /// This is only possible as long as the gang has not been started.
+    pub fn leave(&self, act: &ChildActivity) -> Result<(), Error> {
+        syscalls::gang_ctrl(self.sel(), act.sel(), kif::syscalls::GangOp::Leave)
+    }
+}
@@ -20,23 +20,109 @@ use core::cmp;
 
 use crate::cfg;
 use crate::client::MapFlags;
+use crate::col::{String, Vec};
 use crate::com::MemGate;
 use crate::elf;
 use crate::errors::{Code, Error};
 use crate::io::{read_object, Read};
 use crate::kif;
-use crate::mem::{GlobOff, VirtAddr};
+use crate::mem::{self, GlobOff, VirtAddr};
 use crate::tiles::{Activity, Mapper};
 use crate::util::math;
 use crate::vec;
 use crate::vfs::{BufReader, File, FileRef, Seek, SeekMode};
 
+/// The maximum number of objects (the program and its shared objects) of a program
+pub(crate) const MAX_DL_OBJS: usize = 16;
+
+/// The number of words that describe a loaded object for the dynamic linker
+///
+/// Each object is described by its load address, the address of its dynamic section (0 if there
+/// is none), and the address and size of the memory that is not backed by the file, but mapped
+/// from the file and therefore needs to be cleared by the dynamic linker.
+pub(crate) const DL_OBJ_WORDS: usize = 4;
+
+/// A program that has been loaded into the address space of an activity
+pub(crate) struct Program {
+    /// The entry point of the program
+    pub entry: VirtAddr,
+    /// The descriptions of all loaded objects for the dynamic linker (see [`DL_OBJ_WORDS`])
+    pub dl_objs: Vec<u64>,
+    /// The files of the loaded shared objects, which need to stay open while the program runs
+    pub libs: Vec<BufReader<FileRef<dyn File>>>,
+}
+
+/// An object (program or shared object) whose segments have been loaded
+#[derive(Default)]
+struct LoadedObject {
+    /// The address the object has been loaded at
+    base: usize,
+    /// The end of the last segment, rounded up to a page boundary
+    end: VirtAddr,
+    /// The file offset and size of the dynamic section
+    dynamic: Option<(usize, usize)>,
+    /// The virtual address of the dynamic section (relative to `base`)
+    dynamic_addr: usize,
+    /// The memory that is mapped from the file, but not part of the segment's file contents
+    clear: Option<(usize, usize)>,
+    /// The virtual address, file offset, and file size of all load segments
+    segments: Vec<(usize, usize, usize)>,
+}
+
+impl LoadedObject {
+    fn describe(&self, objs: &mut Vec<u64>) {
+        objs.push(self.base as u64);
+        objs.push(match self.dynamic {
+            Some(_) => (self.base + self.dynamic_addr) as u64,
+            None => 0,
+        });
+        let (clear_addr, clear_len) = self.clear.unwrap_or((0, 0));
+        objs.push(clear_addr as u64);
+        objs.push(clear_len as u64);
+    }
+
+    fn file_offset(&self, virt: usize) -> Option<usize> {
+        self.segments
+            .iter()
+            .find(|(addr, _off, size)| virt >= *addr && virt < *addr + *size)
+            .map(|(addr, off, _size)| off + (virt - addr))
+    }
+}
+
 pub(crate) fn load_program(
     act: &Activity,
     mapper: &mut dyn Mapper,
     file: &mut BufReader<FileRef<dyn File>>,
-) -> Result<VirtAddr, Error> {
+    lib_path: &str,
+    debug: bool,
+) -> Result<Program, Error> {
     let mut buf = vec![0u8; 4096];
+    let hdr = read_header(file)?;
+
+    // if the program is debugged, gdb needs to insert breakpoints into the code
+    let obj = load_segments(act, mapper, file, &hdr, 0, debug, &mut buf)?;
+    let heap_end = create_heap(act, mapper, obj.end)?;
+    create_stack(act, mapper)?;
+
+    let mut prog = Program {
+        entry: VirtAddr::from(hdr.entry),
+        dl_objs: Vec::new(),
+        libs: Vec::new(),
+    };
+
+    // the dynamic linker in the child relocates the program and the shared objects and clears the
+    // memory that has been mapped from the file, but does not belong to the segment
+    if obj.dynamic.is_some() || obj.clear.is_some() {
+        obj.describe(&mut prog.dl_objs);
+        load_libs(
+            act, mapper, file, &obj, heap_end, lib_path, &mut buf, &mut prog,
+        )?;
+    }
+
+    Ok(prog)
+}
+
+fn read_header(file: &mut BufReader<FileRef<dyn File>>) -> Result<elf::ElfHeader, Error> {
     let hdr: elf::ElfHeader = read_object(file)?;
 
     if hdr.ident[0] != b'\x7F'
@@ -47,11 +133,98 @@ pub(crate) fn load_program(
         return Err(Error::new(Code::InvalidElf));
     }
 
-    let heap_begin = load_segments(act, mapper, file, &hdr, &mut buf)?;
-    create_heap(act, mapper, heap_begin)?;
-    create_stack(act, mapper)?;
+    Ok(hdr)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn load_libs(
+    act: &Activity,
+    mapper: &mut dyn Mapper,
+    file: &mut BufReader<FileRef<dyn File>>,
+    obj: &LoadedObject,
+    start: VirtAddr,
+    lib_path: &str,
+    buf: &mut [u8],
+    prog: &mut Program,
+) -> Result<(), Error> {
+    // load the shared objects in breadth-first order, which is also the order in which the dynamic
+    // linker searches for symbols
+    let mut pending = needed_libs(file, obj)?;
+    let mut loaded = Vec::<String>::new();
+    let mut next = start;
+    let mut i = 0;
+    while i < pending.len() {
+        if loaded.contains(&pending[i]) {
+            i += 1;
+            continue;
+        }
+        if loaded.len() + 1 == MAX_DL_OBJS {
+            return Err(Error::new(Code::NoSpace));
+        }
+
+        let mut lib = BufReader::new(mapper.open_lib(&pending[i], lib_path)?);
+        let hdr = read_header(&mut lib)?;
+        if hdr.ty != elf::ElfType::Dyn.into() {
+            return Err(Error::new(Code::InvalidElf));
+        }
+
+        let lobj = load_segments(act, mapper, &mut lib, &hdr, next.as_local(), false, buf)?;
+        pending.extend(needed_libs(&mut lib, &lobj)?);
+        lobj.describe(&mut prog.dl_objs);
+
+        next = lobj.end;
+        loaded.push(pending[i].clone());
+        prog.libs.push(lib);
+        i += 1;
+    }
+    Ok(())
+}
+
+fn needed_libs(
+    file: &mut BufReader<FileRef<dyn File>>,
+    obj: &LoadedObject,
+) -> Result<Vec<String>, Error> {
+    let (off, size) = match obj.dynamic {
+        Some(dynamic) => dynamic,
+        None => return Ok(Vec::new()),
+    };
+
+    let mut strtab = None;
+    let mut needed = Vec::new();
+    file.seek(off, SeekMode::Set)?;
+    for _ in 0..size / mem::size_of::<elf::Dyn>() {
+        let entry: elf::Dyn = read_object(file)?;
+        match elf::DynTag::try_from(entry.tag) {
+            Ok(elf::DynTag::Null) => break,
+            Ok(elf::DynTag::Needed) => needed.push(entry.val),
+            Ok(elf::DynTag::StrTab) => strtab = Some(entry.val),
+            _ => {},
+        }
+    }
+
+    if needed.is_empty() {
+        return Ok(Vec::new());
+    }
 
-    Ok(VirtAddr::from(hdr.entry))
+    let strtab = strtab
+        .and_then(|s| obj.file_offset(s))
+        .ok_or_else(|| Error::new(Code::InvalidElf))?;
+    needed
+        .iter()
+        .map(|name| read_string(file, strtab + name))
+        .collect()
+}
+
+fn read_string(file: &mut BufReader<FileRef<dyn File>>, off: usize) -> Result<String, Error> {
+    let mut res = String::new();
+    file.seek(off, SeekMode::Set)?;
+    loop {
+        let c: u8 = read_object(file)?;
+        if c == 0 {
+            break Ok(res);
+        }
+        res.push(c as char);
+    }
 }
 
 fn create_stack(act: &Activity, mapper: &mut dyn Mapper) -> Result<(), Error> {
@@ -67,7 +240,11 @@ fn create_stack(act: &Activity, mapper: &mut dyn Mapper) -> Result<(), Error> {
         .map(|_| ())
 }
 
-fn create_heap(act: &Activity, mapper: &mut dyn Mapper, start: VirtAddr) -> Result<(), Error> {
+fn create_heap(
+    act: &Activity,
+    mapper: &mut dyn Mapper,
+    start: VirtAddr,
+) -> Result<VirtAddr, Error> {
     let (heap_size, flags) = if act.pager().is_some() {
         (cfg::APP_HEAP_SIZE, MapFlags::NOLPAGE)
     }
@@ -82,7 +259,7 @@ fn create_heap(act: &Activity, mapper: &mut dyn Mapper, start: VirtAddr) -> Resu
             kif::Perm::RW,
             MapFlags::PRIVATE | MapFlags::UNINIT | flags,
         )
-        .map(|_| ())
+        .map(|_| start + heap_size)
 }
 
 fn load_segments(
@@ -90,79 +267,131 @@ fn load_segments(
     mapper: &mut dyn Mapper,
     file: &mut BufReader<FileRef<dyn File>>,
     hdr: &elf::ElfHeader,
+    start: usize,
+    writable: bool,
     buf: &mut [u8],
-) -> Result<VirtAddr, Error> {
-    let mut end = 0;
+) -> Result<LoadedObject, Error> {
+    let mut phdrs = Vec::new();
     let mut off = hdr.ph_off;
     for _ in 0..hdr.ph_num {
         // load program header
         file.seek(off, SeekMode::Set)?;
         let phdr: elf::ProgramHeader = read_object(file)?;
         off += hdr.ph_entry_size as usize;
+        phdrs.push(phdr);
+    }
+
+    let mut obj = LoadedObject::default();
+
+    // shared objects are placed at the next suitably aligned address after `start`
+    if hdr.ty == elf::ElfType::Dyn.into() {
+        let align = phdrs
+            .iter()
+            .filter(|p| p.ty == elf::PHType::Load.into())
+            .fold(cfg::PAGE_SIZE, |align, p| cmp::max(align, p.align as usize));
+        obj.base = math::round_up(start, align);
+    }
+
+    let mut end = obj.base;
+    for phdr in &phdrs {
+        if phdr.ty == elf::PHType::Dynamic.into() {
+            obj.dynamic = Some((phdr.offset as usize, phdr.file_size as usize));
+            obj.dynamic_addr = phdr.virt_addr;
+        }
 
         // we're only interested in non-empty load segments
         if phdr.ty != elf::PHType::Load.into() || phdr.mem_size == 0 {
             continue;
         }
 
-        load_segment(act, mapper, file, &phdr, buf)?;
+        if let Some(clear) = load_segment(act, mapper, file, obj.base, phdr, writable, buf)? {
+            // the dynamic linker supports only one such area per object
+            if obj.clear.replace(clear).is_some() {
+                return Err(Error::new(Code::NotSup));
+            }
+        }
+        obj.segments.push((
+            phdr.virt_addr,
+            phdr.offset as usize,
+            phdr.file_size as usize,
+        ));
 
-        end = phdr.virt_addr + phdr.mem_size as usize;
+        end = obj.base + phdr.virt_addr + phdr.mem_size as usize;
     }
 
-    Ok(VirtAddr::from(math::round_up(end, cfg::PAGE_SIZE)))
+    obj.end = VirtAddr::from(math::round_up(end, cfg::PAGE_SIZE));
+    Ok(obj)
 }
 
 fn load_segment(
     act: &Activity,
     mapper: &mut dyn Mapper,
     file: &mut BufReader<FileRef<dyn File>>,
+    base: usize,
     phdr: &elf::ProgramHeader,
+    writable: bool,
     buf: &mut [u8],
-) -> Result<(), Error> {
-    let prot = kif::Perm::from(elf::PHFlags::from_bits_truncate(phdr.flags));
-    let size = math::round_up(phdr.mem_size as usize, cfg::PAGE_SIZE);
+) -> Result<Option<(usize, usize)>, Error> {
+    let mut prot = kif::Perm::from(elf::PHFlags::from_bits_truncate(phdr.flags));
+    if writable {
+        prot |= kif::Perm::W;
+    }
 
-    let needs_init = if phdr.mem_size == phdr.file_size {
-        mapper.map_file(
-            act.pager(),
-            file,
-            phdr.offset as usize,
-            VirtAddr::from(phdr.virt_addr),
-            size,
-            prot,
-            MapFlags::PRIVATE,
-        )
+    // segments of shared objects do not necessarily start at a page boundary. in this case, we
+    // map the entire page and thereby also the preceding part of the file
+    let pad = phdr.virt_addr & cfg::PAGE_MASK;
+    let virt = VirtAddr::from(base + phdr.virt_addr - pad);
+    let offset = phdr.offset as usize - pad;
+    let file_size = phdr.file_size as usize + pad;
+    let mem_size = phdr.mem_size as usize + pad;
+    let size = math::round_up(mem_size, cfg::PAGE_SIZE);
+
+    if phdr.file_size == 0 {
+        if mapper.map_anon(act.pager(), virt, size, prot, MapFlags::PRIVATE)? {
+            let mem = act.get_mem(virt, size as GlobOff, kif::Perm::RW)?;
+            clear_mem(buf, &mem, 0, mem_size)?;
+        }
+        return Ok(None);
     }
-    else {
-        assert!(phdr.file_size == 0);
-        mapper.map_anon(
-            act.pager(),
-            VirtAddr::from(phdr.virt_addr),
-            size,
-            prot,
-            MapFlags::PRIVATE,
-        )
-    }?;
 
-    if needs_init {
-        let mem = act.get_mem(
-            VirtAddr::from(phdr.virt_addr),
-            math::round_up(size, cfg::PAGE_SIZE) as GlobOff,
-            kif::Perm::RW,
-        )?;
+    let mut clear = None;
+    let file_pages = math::round_up(file_size, cfg::PAGE_SIZE);
+    if mapper.map_file(
+        act.pager(),
+        file,
+        offset,
+        virt,
+        file_pages,
+        prot,
+        MapFlags::PRIVATE,
+    )? {
+        let mem = act.get_mem(virt, file_pages as GlobOff, kif::Perm::RW)?;
         init_mem(
             buf,
             &mem,
             file,
-            phdr.offset as usize,
-            phdr.file_size as usize,
-            phdr.mem_size as usize,
-        )
+            offset,
+            file_size,
+            cmp::min(mem_size, file_pages),
+        )?;
     }
-    else {
-        Ok(())
+    else if mem_size > file_size && file_pages > file_size {
+        // the rest of the last page has been mapped from the file as well, but belongs to the
+        // memory that needs to be zeroed. we leave that to the dynamic linker in the child
+        clear = Some((virt.as_local() + file_size, file_pages - file_size));
     }
+
+    // the rest of the segment (typically .bss) is not backed by the file
+    if size > file_pages {
+        let anon_virt = virt + file_pages;
+        let anon_size = size - file_pages;
+        if mapper.map_anon(act.pager(), anon_virt, anon_size, prot, MapFlags::PRIVATE)? {
+            let mem = act.get_mem(anon_virt, anon_size as GlobOff, kif::Perm::RW)?;
+            clear_mem(buf, &mem, 0, mem_size - file_pages)?;
+        }
+    }
+
+    Ok(clear)
 }
 
 fn init_mem(
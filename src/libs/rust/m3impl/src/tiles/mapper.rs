@@ -17,9 +17,10 @@
 
 use crate::client::{MapFlags, Pager};
 use crate::errors::{Code, Error};
+use crate::format;
 use crate::kif;
 use crate::mem::VirtAddr;
-use crate::vfs::{BufReader, File, FileRef, Map};
+use crate::vfs::{BufReader, File, FileRef, Map, OpenFlags, VFS};
 
 /// The mapper trait is used to map the memory of an activity before running it.
 pub trait Mapper {
@@ -45,6 +46,20 @@ pub trait Mapper {
         perm: kif::Perm,
         flags: MapFlags,
     ) -> Result<bool, Error>;
+
+    /// Opens the shared object with given name, which is required by the program to load.
+    ///
+    /// By default, the shared object is searched for in the colon-separated list of directories
+    /// given by `path`.
+    fn open_lib(&mut self, name: &str, path: &str) -> Result<FileRef<dyn File>, Error> {
+        for dir in path.split(':').filter(|d| !d.is_empty()) {
+            let lib_path = format!("{}/{}", dir.trim_end_matches('/'), name);
+            if let Ok(file) = VFS::open(&lib_path, OpenFlags::RX | OpenFlags::NEW_SESS) {
+                return Ok(file.into_generic());
+            }
+        }
+        Err(Error::new(Code::NotFound))
+    }
 }
 
 /// The default implementation of the [`Mapper`] trait.
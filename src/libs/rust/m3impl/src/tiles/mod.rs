@@ -43,11 +43,17 @@
 //! [`ResMng`](`crate::client::ResMng`). After creation of a [`ChildActivity`], it is first
 //! configured accordingly (delegating capabilities, files, mount points, and data to the child) and
 //! finally started, which yields a [`RunningActivity`].
+//!
+//! # Gangs
+//!
+//! Multiple child activities can be grouped into a [`Gang`], which lets the kernel start all of
+//! them at once as soon as every member has been started and stop them together.
 
 mod activity;
 mod childactivity;
+mod gang;
 mod kmem;
-mod loader;
+pub(crate) mod loader;
 mod mapper;
 mod ownactivity;
 mod running;
@@ -55,6 +61,7 @@ mod tile;
 
 pub use self::activity::Activity;
 pub use self::childactivity::{ActivityArgs, ChildActivity};
+pub use self::gang::Gang;
 pub use self::kmem::KMem;
 pub use self::mapper::{DefaultMapper, Mapper};
 pub use self::ownactivity::OwnActivity;
@@ -64,3 +71,7 @@ pub use self::tile::{Tile, TileArgs, TileQuota};
 pub(crate) fn init() {
     self::activity::init();
 }
+
+pub(crate) fn forget_inherited() {
+    self::activity::forget_inherited();
+}
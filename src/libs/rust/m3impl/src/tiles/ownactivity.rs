@@ -78,6 +78,7 @@ impl OwnActivity {
 
     /// Exits with an unspecified error without deinitialization
     pub fn abort() -> ! {
+        base::trace::flush();
         base::machine::write_coverage(env::get().activity_id() as u64 + 1);
         tmif::exit(Code::Unspecified);
     }
@@ -94,6 +95,9 @@ impl OwnActivity {
     // Deinitializes all data structures and exits with given error
     pub fn exit_with(err: Code) -> ! {
         crate::env::deinit();
+        #[cfg(feature = "heap")]
+        heap::report_leaks();
+        base::trace::flush();
         base::machine::write_coverage(env::get().activity_id() as u64 + 1);
         tmif::exit(err);
     }
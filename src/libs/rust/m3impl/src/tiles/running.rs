@@ -15,6 +15,7 @@
 
 //! The different types that are used to hold the own activity running on a activity.
 
+use crate::col::Vec;
 use crate::errors::{Code, Error};
 use crate::kif;
 use crate::syscalls;
@@ -40,6 +41,20 @@ pub trait RunningActivity {
             .map(|_| ())
     }
 
+    /// Suspends the activity, i.e., it is no longer scheduled until it is resumed.
+    ///
+    /// This requires that the activity runs on a tile with TileMux.
+    fn suspend(&self) -> Result<(), Error> {
+        syscalls::activity_ctrl(self.activity().sel(), kif::syscalls::ActivityOp::Suspend, 0)
+            .map(|_| ())
+    }
+
+    /// Resumes the previously suspended activity.
+    fn resume(&self) -> Result<(), Error> {
+        syscalls::activity_ctrl(self.activity().sel(), kif::syscalls::ActivityOp::Resume, 0)
+            .map(|_| ())
+    }
+
     /// Waits until the activity exits and returns the error code.
     fn wait(&self) -> Result<Code, Error> {
         syscalls::activity_wait(&[self.activity().sel()], 0).map(|r| r.1)
@@ -83,12 +98,22 @@ impl Drop for RunningDeviceActivity {
 pub struct RunningProgramActivity {
     act: ChildActivity,
     _file: Option<BufReader<FileRef<dyn File>>>,
+    _libs: Vec<BufReader<FileRef<dyn File>>>,
 }
 
 impl RunningProgramActivity {
     /// Creates a new `ExecActivity` for the given activity and executable.
     pub fn new(act: ChildActivity, file: Option<BufReader<FileRef<dyn File>>>) -> Self {
-        Self { act, _file: file }
+        Self {
+            act,
+            _file: file,
+            _libs: Vec::new(),
+        }
+    }
+
+    /// Sets the shared objects of the executable, which are kept open while the activity runs.
+    pub(crate) fn set_libs(&mut self, libs: Vec<BufReader<FileRef<dyn File>>>) {
+        self._libs = libs;
     }
 }
 
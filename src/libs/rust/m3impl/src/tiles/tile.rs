@@ -16,12 +16,16 @@
  * General Public License version 2 for more details.
  */
 
+use core::cell::Cell;
 use core::fmt;
 
 use crate::cap::{CapFlags, Capability, SelSpace, Selector};
 use crate::com::MemGate;
 use crate::errors::{Code, Error};
-use crate::kif::{syscalls::MuxType, TileDesc};
+use crate::kif::{
+    syscalls::{MuxType, TileOp},
+    TileDesc,
+};
 use crate::quota::Quota;
 use crate::rc::Rc;
 use crate::syscalls;
@@ -52,7 +56,7 @@ use crate::time::TimeDuration;
 pub struct Tile {
     cap: Capability,
     id: TileId,
-    desc: TileDesc,
+    desc: Cell<TileDesc>,
     free: bool,
 }
 
@@ -99,14 +103,24 @@ impl fmt::Debug for TileQuota {
 }
 
 /// Additional arguments for the allocation of tiles
+///
+/// Besides the attributes in the [`TileDesc`] (e.g., the clock class via "perf"/"effi" or
+/// accelerators like "nic"), the allocation can be constrained to a specific tile or can prefer
+/// tiles that are close to a given tile (e.g., the tile of a client or a memory tile).
 #[derive(Copy, Clone)]
 pub struct TileArgs {
     init: bool,
+    id: Option<TileId>,
+    near: Option<TileId>,
 }
 
 impl Default for TileArgs {
     fn default() -> Self {
-        Self { init: true }
+        Self {
+            init: true,
+            id: None,
+            near: None,
+        }
     }
 }
 
@@ -117,6 +131,19 @@ impl TileArgs {
         self.init = init;
         self
     }
+
+    /// Requests the tile with given id, which still needs to match the tile description
+    pub fn id(mut self, id: TileId) -> Self {
+        self.id = Some(id);
+        self
+    }
+
+    /// Prefers the tile with the smallest distance to the tile with given id among all matching
+    /// tiles
+    pub fn near(mut self, tile: TileId) -> Self {
+        self.near = Some(tile);
+        self
+    }
 }
 
 impl Tile {
@@ -131,11 +158,11 @@ impl Tile {
         let (id, ndesc) = Activity::own()
             .resmng()
             .unwrap()
-            .alloc_tile(sel, desc, args.init)?;
+            .alloc_tile(sel, desc, args.init, args.id, args.near)?;
         Ok(Rc::new(Tile {
             cap: Capability::new(sel, CapFlags::KEEP_CAP),
             id,
-            desc: ndesc,
+            desc: Cell::new(ndesc),
             free: true,
         }))
     }
@@ -154,7 +181,7 @@ impl Tile {
         Tile {
             cap: Capability::new(sel, CapFlags::KEEP_CAP),
             id,
-            desc,
+            desc: Cell::new(desc),
             free: false,
         }
     }
@@ -228,18 +255,31 @@ impl Tile {
     /// `self`
     ///
     /// The three resources are the number of EPs, the time slice length, and the number of page
-    /// tables.
+    /// tables. In contrast to the others, the time slice is not removed from `self`: time quotas are
+    /// hierarchical and the derived object shares the time budget of `self`.
     pub fn derive(
         &self,
         eps: Option<usize>,
         time: Option<TimeDuration>,
         pts: Option<usize>,
+    ) -> Result<Rc<Self>, Error> {
+        self.derive_with_irqs(eps, time, pts, None)
+    }
+
+    /// Like [`Tile::derive`], but restricts the external interrupts that activities on the new tile
+    /// object may use to the mask `irqs`, if not `None`
+    pub fn derive_with_irqs(
+        &self,
+        eps: Option<usize>,
+        time: Option<TimeDuration>,
+        pts: Option<usize>,
+        irqs: Option<u32>,
     ) -> Result<Rc<Self>, Error> {
         let sel = SelSpace::get().alloc_sel();
-        syscalls::derive_tile(self.sel(), sel, eps, time, pts)?;
+        syscalls::derive_tile(self.sel(), sel, eps, time, pts, irqs)?;
         Ok(Rc::new(Tile {
             cap: Capability::new(sel, CapFlags::empty()),
-            desc: self.desc(),
+            desc: Cell::new(self.desc()),
             id: self.id(),
             free: false,
         }))
@@ -257,7 +297,7 @@ impl Tile {
 
     /// Returns the tile description
     pub fn desc(&self) -> TileDesc {
-        self.desc
+        self.desc.get()
     }
 
     /// Returns the number of endpoints available on this tile (via syscall)
@@ -283,13 +323,36 @@ impl Tile {
         syscalls::tile_set_quota(self.sel(), time, pts)
     }
 
+    /// Takes this tile offline so that it can be reconfigured
+    ///
+    /// The tile must not be in use, that is, there must not be any activities on the tile and no
+    /// multiplexer must be running on it. While being offline, no activities can be created on the
+    /// tile.
+    ///
+    /// This call requires a non-derived tile capability.
+    pub fn set_offline(&self) -> Result<(), Error> {
+        syscalls::tile_ctrl(self.sel(), TileOp::Offline, self.desc())
+    }
+
+    /// Brings this tile online again with the new description `desc`
+    ///
+    /// This is intended for reconfigurable tiles (e.g., FPGA-based accelerator tiles) that might
+    /// have changed their type in the meantime.
+    ///
+    /// This call requires a non-derived tile capability.
+    pub fn set_online(&self, desc: TileDesc) -> Result<(), Error> {
+        syscalls::tile_ctrl(self.sel(), TileOp::Online, desc)?;
+        self.desc.set(desc);
+        Ok(())
+    }
+
     /// Creates a [`MemGate`] for the internal memory of this tile
     ///
     /// The tile needs to have internal memory (see [`TileDesc::has_memory`]).
     ///
     /// This call requires a non-derived tile capability.
     pub fn memory(&self) -> Result<MemGate, Error> {
-        if self.desc.has_memory() {
+        if self.desc().has_memory() {
             let sel = SelSpace::get().alloc_sel();
             syscalls::tile_mem(sel, self.sel())?;
             MemGate::new_owned_bind(sel)
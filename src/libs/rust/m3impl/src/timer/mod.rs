@@ -0,0 +1,203 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! Timers that call a function or wake up the activity after a given duration
+//!
+//! Instead of computing deadlines from [`TimeInstant::now`] by hand, activities can register
+//! timers here. A timer either calls a function once ([`add`]), calls a function periodically
+//! ([`add_periodic`]), or only ensures that the activity wakes up in time ([`add_wakeup`]); the
+//! latter can be combined with [`is_pending`] to implement timeouts.
+//!
+//! All timers of an activity are kept in a hierarchical [`TimerWheel`] with a resolution of
+//! [`RESOLUTION`]. Timers never expire early, but can expire up to [`RESOLUTION`] late. The timers
+//! are driven by the activity: [`sleep_for`] sleeps via TileMux until the next timer expires and
+//! runs all expired timers afterwards. Activities that sleep on their own should not sleep longer
+//! than [`next_timeout`] and call [`run`] afterwards.
+
+mod wheel;
+
+pub use wheel::{TimerId, TimerWheel};
+
+use crate::boxed::Box;
+use crate::cell::{StaticCell, StaticRefCell};
+use crate::errors::Error;
+use crate::tiles::OwnActivity;
+use crate::time::{TimeDuration, TimeInstant};
+
+/// The resolution of the timers
+pub const RESOLUTION: TimeDuration = TimeDuration::from_millis(1);
+
+struct Timer {
+    deadline: TimeInstant,
+    period: Option<TimeDuration>,
+    func: Option<Box<dyn FnMut()>>,
+}
+
+static TIMERS: StaticRefCell<TimerWheel<Timer>> = StaticRefCell::new(TimerWheel::new());
+// the periodic timer whose function is currently called and whether it has been cancelled meanwhile
+static RUNNING: StaticCell<Option<(TimerId, bool)>> = StaticCell::new(None);
+
+fn res_nanos() -> u64 {
+    RESOLUTION.as_nanos() as u64
+}
+
+fn tick_of(time: TimeInstant) -> u64 {
+    time.as_nanos() / res_nanos()
+}
+
+fn deadline_tick(deadline: TimeInstant) -> u64 {
+    // round up to never expire early
+    (deadline.as_nanos() + res_nanos() - 1) / res_nanos()
+}
+
+fn insert(timer: Timer) -> TimerId {
+    let now = TimeInstant::now();
+    let mut timers = TIMERS.borrow_mut();
+    timers.advance(tick_of(now));
+    // timers without timeout are due immediately
+    let expires = match timer.deadline > now {
+        true => deadline_tick(timer.deadline),
+        false => timers.now(),
+    };
+    timers.insert(expires, timer)
+}
+
+/// Calls `func` once after `timeout` has passed and returns the id of the timer
+pub fn add<F: FnMut() + 'static>(timeout: TimeDuration, func: F) -> TimerId {
+    insert(Timer {
+        deadline: TimeInstant::now() + timeout,
+        period: None,
+        func: Some(Box::new(func)),
+    })
+}
+
+/// Calls `func` every `period` until the timer is cancelled and returns the id of the timer
+///
+/// The first call happens after `period` has passed. If the activity falls behind, missed calls
+/// are skipped instead of being caught up.
+pub fn add_periodic<F: FnMut() + 'static>(period: TimeDuration, func: F) -> TimerId {
+    assert!(period >= RESOLUTION);
+    insert(Timer {
+        deadline: TimeInstant::now() + period,
+        period: Some(period),
+        func: Some(Box::new(func)),
+    })
+}
+
+/// Wakes up the activity from [`sleep_for`] after `timeout` has passed and returns the id of the
+/// timer
+///
+/// Whether the timeout has passed can be checked via [`is_pending`]. The timer is removed once it
+/// has expired and [`run`] was called or if it is cancelled.
+pub fn add_wakeup(timeout: TimeDuration) -> TimerId {
+    insert(Timer {
+        deadline: TimeInstant::now() + timeout,
+        period: None,
+        func: None,
+    })
+}
+
+/// Cancels the timer with given id
+///
+/// Returns true if the timer existed. Periodic timers can also be cancelled by their own function.
+pub fn cancel(id: TimerId) -> bool {
+    if TIMERS.borrow_mut().cancel(id).is_some() {
+        return true;
+    }
+    match RUNNING.get() {
+        Some((running, false)) if running == id => {
+            RUNNING.set(Some((id, true)));
+            true
+        },
+        _ => false,
+    }
+}
+
+/// Returns true if the timer with given id exists and its deadline has not passed yet
+pub fn is_pending(id: TimerId) -> bool {
+    remaining(id).is_some_and(|r| r > TimeDuration::ZERO)
+}
+
+/// Returns the time until the timer with given id expires or `None` if it does not exist
+pub fn remaining(id: TimerId) -> Option<TimeDuration> {
+    let deadline = TIMERS.borrow().get(id)?.deadline;
+    Some(
+        deadline
+            .checked_duration_since(TimeInstant::now())
+            .unwrap_or_default(),
+    )
+}
+
+/// Returns the time until the next timer needs to be handled or `None` if there is no timer
+pub fn next_timeout() -> Option<TimeDuration> {
+    let tick = TIMERS.borrow().next_expiry()?;
+    let next = TimeInstant::from_nanos(tick * res_nanos());
+    Some(
+        next.checked_duration_since(TimeInstant::now())
+            .unwrap_or_default(),
+    )
+}
+
+/// Calls the functions of all expired timers and returns the number of expired timers
+pub fn run() -> usize {
+    let now = TimeInstant::now();
+    TIMERS.borrow_mut().advance(tick_of(now));
+
+    let mut count = 0;
+    loop {
+        // don't hold the borrow while calling the function, which might add or cancel timers
+        let (id, mut timer) = match TIMERS.borrow_mut().pop_expired() {
+            Some(t) => t,
+            None => break,
+        };
+        count += 1;
+
+        let mut func = match timer.func.take() {
+            Some(f) => f,
+            None => continue,
+        };
+
+        match timer.period {
+            None => func(),
+            Some(period) => {
+                RUNNING.set(Some((id, false)));
+                func();
+                let cancelled = matches!(RUNNING.get(), Some((_, true)));
+                RUNNING.set(None);
+
+                if !cancelled {
+                    timer.deadline += period;
+                    let now = TimeInstant::now();
+                    if timer.deadline <= now {
+                        timer.deadline = now + period;
+                    }
+                    timer.func = Some(func);
+                    let expires = deadline_tick(timer.deadline);
+                    TIMERS.borrow_mut().insert_with_id(id, expires, timer);
+                }
+            },
+        }
+    }
+    count
+}
+
+/// Puts the own activity to sleep until the next timer expires, the next message arrives, or
+/// `max` time has passed, and runs all expired timers afterwards.
+pub fn sleep_for(max: TimeDuration) -> Result<(), Error> {
+    let timeout = next_timeout().map_or(max, |t| t.min(max));
+    let res = OwnActivity::sleep_for(timeout);
+    run();
+    res
+}
@@ -0,0 +1,257 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use base::col::{BTreeMap, VecDeque};
+
+use crate::col::Vec;
+
+/// The identifier of a timer
+pub type TimerId = u64;
+
+// the number of bits of the tick that are covered by one level
+const LEVEL_BITS: usize = 6;
+const SLOTS: usize = 1 << LEVEL_BITS;
+const SLOT_MASK: u64 = SLOTS as u64 - 1;
+const LEVELS: usize = 4;
+// timers that expire further in the future are put into the last slot of the last level
+const MAX_DELTA: u64 = (1 << (LEVEL_BITS * LEVELS)) - 1;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Pos {
+    Slot(usize, usize),
+    Due,
+}
+
+struct Entry<T> {
+    expires: u64,
+    pos: Pos,
+    item: T,
+}
+
+/// A hierarchical timer wheel
+///
+/// The timer wheel stores items that expire at a given tick. The first level has one slot per tick
+/// for the next 64 ticks, the second level one slot per 64 ticks for the next 4096 ticks, and so
+/// on. Whenever the current tick crosses the range of a slot in a higher level, the items in this
+/// slot are redistributed to the lower levels ("cascaded"). Thereby, inserting and cancelling items
+/// is cheap and advancing the time only touches the items that are due, independent of the number
+/// of pending items.
+///
+/// The wheel does not know about time itself; the unit of ticks is defined by the user.
+pub struct TimerWheel<T> {
+    now: u64,
+    slots: [[Vec<TimerId>; SLOTS]; LEVELS],
+    counts: [usize; LEVELS],
+    entries: BTreeMap<TimerId, Entry<T>>,
+    due: VecDeque<TimerId>,
+    next_id: TimerId,
+}
+
+impl<T> TimerWheel<T> {
+    const EMPTY_LEVEL: [Vec<TimerId>; SLOTS] = [Self::EMPTY_SLOT; SLOTS];
+    // only used to initialize the slots
+    const EMPTY_SLOT: Vec<TimerId> = Vec::new();
+
+    /// Creates an empty timer wheel that starts at tick 0
+    pub const fn new() -> Self {
+        Self {
+            now: 0,
+            slots: [Self::EMPTY_LEVEL; LEVELS],
+            counts: [0; LEVELS],
+            entries: BTreeMap::new(),
+            due: VecDeque::new(),
+            next_id: 1,
+        }
+    }
+
+    /// Returns the current tick, that is, the tick up to which all expired items have been
+    /// collected
+    pub fn now(&self) -> u64 {
+        self.now
+    }
+
+    /// Returns the number of items in the wheel, including expired, but not yet collected ones
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if the wheel contains no items
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Inserts `item` to expire at tick `expires` and returns its identifier
+    ///
+    /// If `expires` is not in the future, the item is due immediately.
+    pub fn insert(&mut self, expires: u64, item: T) -> TimerId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.insert_with_id(id, expires, item);
+        id
+    }
+
+    /// Inserts `item` with the previously returned identifier `id` to expire at tick `expires`
+    ///
+    /// This can be used to reschedule a timer that has been returned by
+    /// [`pop_expired`](Self::pop_expired) without changing its identifier.
+    pub fn insert_with_id(&mut self, id: TimerId, expires: u64, item: T) {
+        assert!(id < self.next_id && !self.entries.contains_key(&id));
+        self.entries.insert(id, Entry {
+            expires,
+            pos: Pos::Due,
+            item,
+        });
+        self.place(id, expires);
+    }
+
+    /// Returns the item with given id, if it exists
+    pub fn get(&self, id: TimerId) -> Option<&T> {
+        self.entries.get(&id).map(|e| &e.item)
+    }
+
+    /// Returns the tick at which the item with given id expires, if it exists
+    pub fn expires(&self, id: TimerId) -> Option<u64> {
+        self.entries.get(&id).map(|e| e.expires)
+    }
+
+    /// Removes the item with given id and returns it, if it exists
+    pub fn cancel(&mut self, id: TimerId) -> Option<T> {
+        let entry = self.entries.remove(&id)?;
+        match entry.pos {
+            Pos::Slot(level, slot) => {
+                self.slots[level][slot].retain(|i| *i != id);
+                self.counts[level] -= 1;
+            },
+            Pos::Due => self.due.retain(|i| *i != id),
+        }
+        Some(entry.item)
+    }
+
+    /// Returns the tick at which the wheel needs to be advanced next or `None` if it is empty
+    ///
+    /// For items in the first level, the returned tick is the exact expiry time. For items in
+    /// higher levels, it is the tick at which the items need to be cascaded, which is never later
+    /// than their expiry time.
+    pub fn next_expiry(&self) -> Option<u64> {
+        if !self.due.is_empty() {
+            return Some(self.now);
+        }
+
+        let mut next = None;
+        for level in 0..LEVELS {
+            if self.counts[level] == 0 {
+                continue;
+            }
+
+            let shift = LEVEL_BITS * level;
+            let cur = self.now >> shift;
+            let tick = (1..=SLOTS as u64)
+                .find(|i| !self.slots[level][((cur + i) & SLOT_MASK) as usize].is_empty())
+                .map(|i| (cur + i) << shift);
+            next = match (next, tick) {
+                (Some(n), Some(t)) => Some(t.min(n)),
+                (n, t) => n.or(t),
+            };
+        }
+        next
+    }
+
+    /// Advances the wheel to tick `now` and collects all items that expired until then
+    ///
+    /// The expired items can be retrieved via [`pop_expired`](Self::pop_expired).
+    pub fn advance(&mut self, now: u64) {
+        while self.now < now {
+            // skip all ticks in which nothing happens: if the first levels are empty, we only need to
+            // stop at the next tick at which the next non-empty level is cascaded
+            let mut next = self.now + 1;
+            for level in 0..LEVELS {
+                if self.counts[level] != 0 {
+                    break;
+                }
+                let shift = LEVEL_BITS * (level + 1);
+                next = ((self.now >> shift) + 1) << shift;
+            }
+
+            if next > now {
+                self.now = now;
+                break;
+            }
+
+            self.now = next;
+            self.process_tick();
+        }
+    }
+
+    /// Removes the next expired item and returns it together with its identifier
+    pub fn pop_expired(&mut self) -> Option<(TimerId, T)> {
+        let id = self.due.pop_front()?;
+        let entry = self.entries.remove(&id).unwrap();
+        Some((id, entry.item))
+    }
+
+    fn process_tick(&mut self) {
+        // cascade the slots of the higher levels whose range starts now
+        for level in 1..LEVELS {
+            let shift = LEVEL_BITS * level;
+            if self.now & ((1 << shift) - 1) != 0 {
+                break;
+            }
+
+            let slot = ((self.now >> shift) & SLOT_MASK) as usize;
+            let ids = core::mem::take(&mut self.slots[level][slot]);
+            self.counts[level] -= ids.len();
+            for id in ids {
+                let expires = self.entries[&id].expires;
+                self.place(id, expires);
+            }
+        }
+
+        // all items in the current slot of the first level expire now
+        let slot = (self.now & SLOT_MASK) as usize;
+        let ids = core::mem::take(&mut self.slots[0][slot]);
+        self.counts[0] -= ids.len();
+        for id in ids {
+            self.entries.get_mut(&id).unwrap().pos = Pos::Due;
+            self.due.push_back(id);
+        }
+    }
+
+    fn place(&mut self, id: TimerId, expires: u64) {
+        let pos = if expires <= self.now {
+            self.due.push_back(id);
+            Pos::Due
+        }
+        else {
+            let delta = expires - self.now;
+            let mut level = 0;
+            while level < LEVELS - 1 && delta >> (LEVEL_BITS * (level + 1)) != 0 {
+                level += 1;
+            }
+
+            let expires = expires.min(self.now + MAX_DELTA);
+            let slot = ((expires >> (LEVEL_BITS * level)) & SLOT_MASK) as usize;
+            self.slots[level][slot].push(id);
+            self.counts[level] += 1;
+            Pos::Slot(level, slot)
+        };
+        self.entries.get_mut(&id).unwrap().pos = pos;
+    }
+}
+
+impl<T> Default for TimerWheel<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
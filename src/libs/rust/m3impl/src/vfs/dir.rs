@@ -18,9 +18,11 @@
 
 use core::iter;
 
-use crate::col::String;
+use crate::col::{BTreeSet, String, Vec};
 use crate::io::{read_object, Read};
 use crate::mem;
+use crate::vec;
+use crate::vfs::overlay::{OPAQUE_MARKER, WHITEOUT_PREFIX};
 use crate::vfs::{BufReader, FileRef, GenericFile, INodeId, Seek, SeekMode};
 
 /// Represents a directory entry
@@ -47,22 +49,35 @@ impl DirEntry {
 }
 
 /// An iterator to walk over a directory
+///
+/// For directories of an [`OverlayFS`](crate::vfs::OverlayFS), the entries of all layers are
+/// merged: entries of upper layers hide entries with the same name in lower layers and whiteouts
+/// are not returned, but hide the corresponding entries instead.
 pub struct ReadDir {
-    reader: BufReader<FileRef<GenericFile>>,
+    layers: Vec<BufReader<FileRef<GenericFile>>>,
+    merge: bool,
+    // the names that have been returned or hidden by whiteouts
+    seen: BTreeSet<String>,
 }
 
 impl ReadDir {
     pub(crate) fn new(file: FileRef<GenericFile>) -> Self {
         Self {
-            reader: BufReader::new(file),
+            layers: vec![BufReader::new(file)],
+            merge: false,
+            seen: BTreeSet::new(),
         }
     }
-}
 
-impl iter::Iterator for ReadDir {
-    type Item = DirEntry;
+    pub(crate) fn new_merged(files: Vec<FileRef<GenericFile>>) -> Self {
+        Self {
+            layers: files.into_iter().map(BufReader::new).collect(),
+            merge: true,
+            seen: BTreeSet::new(),
+        }
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
+    fn read_entry(reader: &mut BufReader<FileRef<GenericFile>>) -> Option<DirEntry> {
         #[derive(Default)]
         #[repr(C, packed)]
         struct M3FSDirEntry {
@@ -72,7 +87,7 @@ impl iter::Iterator for ReadDir {
         }
 
         // read header
-        let entry: M3FSDirEntry = match read_object(&mut self.reader) {
+        let entry: M3FSDirEntry = match read_object(reader) {
             Ok(obj) => obj,
             Err(_) => return None,
         };
@@ -80,7 +95,7 @@ impl iter::Iterator for ReadDir {
         // read name
         let res = DirEntry::new(
             entry.inode,
-            match self.reader.read_string(entry.name_len as usize) {
+            match reader.read_string(entry.name_len as usize) {
                 Ok(s) => s,
                 Err(_) => return None,
             },
@@ -88,10 +103,42 @@ impl iter::Iterator for ReadDir {
 
         // move to next entry
         let off = entry.next as usize - (mem::size_of::<M3FSDirEntry>() + entry.name_len as usize);
-        if off != 0 && self.reader.seek(off, SeekMode::Cur).is_err() {
+        if off != 0 && reader.seek(off, SeekMode::Cur).is_err() {
             return None;
         }
 
         Some(res)
     }
 }
+
+impl iter::Iterator for ReadDir {
+    type Item = DirEntry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let entry = match Self::read_entry(self.layers.first_mut()?) {
+                Some(entry) => entry,
+                None => {
+                    // continue with the next layer
+                    self.layers.remove(0);
+                    continue;
+                },
+            };
+
+            if !self.merge {
+                return Some(entry);
+            }
+
+            // the upper layers come first, so that all whiteouts are known before the entries of
+            // the lower layers are read
+            if let Some(name) = entry.file_name().strip_prefix(WHITEOUT_PREFIX) {
+                if entry.file_name() != OPAQUE_MARKER {
+                    self.seen.insert(name.into());
+                }
+            }
+            else if self.seen.insert(entry.file_name().into()) {
+                return Some(entry);
+            }
+        }
+    }
+}
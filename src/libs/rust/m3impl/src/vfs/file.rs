@@ -68,6 +68,12 @@ bitflags! {
         const NODATA    = 0b0100_0000;
         /// Create a new file session
         const NEW_SESS  = 0b1000_0000;
+        /// Pipelines sequential transfers: the server prepares the next chunk while the client
+        /// works on the current one (requires NEW_SESS; only supported by m3fs)
+        const PIPELINE  = 0b1_0000_0000;
+        /// Does not split writes across chunks if they fit into a fresh chunk (used for pipes to
+        /// prevent that writes are interleaved with the data of other writers)
+        const ATOMIC    = 0b10_0000_0000;
 
         /// Opens the file for reading and writing
         const RW        = Self::R.bits() | Self::W.bits();
@@ -143,10 +149,21 @@ pub struct FileInfo {
     pub inode: INodeId,
     pub mode: FileMode,
     pub links: u32,
+    /// The user id of the owner
+    pub uid: u16,
+    /// The group id of the owner
+    pub gid: u16,
     pub size: usize,
+    /// The time of the last access in seconds since the Unix epoch
     pub lastaccess: u32,
+    /// The time of the last modification of the content in seconds since the Unix epoch
     pub lastmod: u32,
+    /// The time of the last change of the content or metadata in seconds since the Unix epoch
+    pub lastchange: u32,
     pub blocksize: u32,
+    /// The number of blocks that are allocated for the file, which can be less than its size
+    /// suggests if the file has holes
+    pub blocks: u32,
     // for debugging
     pub extents: u32,
     pub firstblock: BlockId,
@@ -163,6 +180,8 @@ bitflags! {
         const OUTPUT        = 2;
         /// A signal is available (see [`File::fetch_signal`])
         const SIGNAL        = 4;
+        /// A request to suspend the foreground job is available (see [`File::fetch_suspend`])
+        const SUSPEND       = 8;
     }
 }
 
@@ -176,6 +195,89 @@ pub enum TMode {
     Cooked,
 }
 
+bitflags! {
+    /// The input flags of [`Termios`]
+    #[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(crate = "base::serde")]
+    pub struct InputFlags : u32 {
+        /// Translate carriage return to newline on input
+        const ICRNL     = 0x1;
+    }
+}
+
+bitflags! {
+    /// The output flags of [`Termios`]
+    #[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(crate = "base::serde")]
+    pub struct OutputFlags : u32 {
+        /// Translate newline to carriage return and newline on output
+        const ONLCR     = 0x1;
+    }
+}
+
+bitflags! {
+    /// The local flags of [`Termios`]
+    #[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(crate = "base::serde")]
+    pub struct LocalFlags : u32 {
+        /// Echo the input characters
+        const ECHO      = 0x1;
+        /// Canonical mode: support line editing and pass full lines to the reader
+        const ICANON    = 0x2;
+        /// Generate a signal if the interrupt character is received
+        const ISIG      = 0x4;
+        /// Visually erase characters in canonical mode
+        const ECHOE     = 0x8;
+    }
+}
+
+/// The settings of a terminal, similar to `struct termios` on UNIX
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "base::serde")]
+pub struct Termios {
+    pub iflags: InputFlags,
+    pub oflags: OutputFlags,
+    pub lflags: LocalFlags,
+    /// The character that sends a signal to the reader (with [`LocalFlags::ISIG`])
+    pub intr: u8,
+    /// The character that sends a suspend request to the reader (with [`LocalFlags::ISIG`])
+    pub susp: u8,
+    /// The character that signals the end of file (with [`LocalFlags::ICANON`])
+    pub eof: u8,
+    /// The character that erases the previous character (with [`LocalFlags::ICANON`])
+    pub erase: u8,
+    /// The character that erases the current line (with [`LocalFlags::ICANON`])
+    pub kill: u8,
+}
+
+impl Default for Termios {
+    fn default() -> Self {
+        Self {
+            iflags: InputFlags::ICRNL,
+            oflags: OutputFlags::ONLCR,
+            lflags: LocalFlags::ECHO | LocalFlags::ICANON | LocalFlags::ISIG | LocalFlags::ECHOE,
+            // ^C
+            intr: 0x03,
+            // ^Z
+            susp: 0x1a,
+            // ^D
+            eof: 0x04,
+            // DEL
+            erase: 0x7f,
+            // ^U
+            kill: 0x15,
+        }
+    }
+}
+
+/// The size of a terminal window in characters
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "base::serde")]
+pub struct WinSize {
+    pub rows: u16,
+    pub cols: u16,
+}
+
 /// Trait for files
 ///
 /// All files can be read, written, seeked and mapped into memory.
@@ -220,6 +322,26 @@ pub trait File: Read + Write + Seek + Map + Debug + HashInput + HashOutput {
         Err(Error::new(Code::NotSup))
     }
 
+    /// Returns the terminal settings in case the server is a pseudo terminal
+    fn get_termios(&self) -> Result<Termios, Error> {
+        Err(Error::new(Code::NotSup))
+    }
+
+    /// Changes the terminal settings in case the server is a pseudo terminal
+    fn set_termios(&mut self, _termios: &Termios) -> Result<(), Error> {
+        Err(Error::new(Code::NotSup))
+    }
+
+    /// Returns the window size in case the server is a pseudo terminal
+    fn get_winsize(&self) -> Result<WinSize, Error> {
+        Err(Error::new(Code::NotSup))
+    }
+
+    /// Changes the window size in case the server is a pseudo terminal
+    fn set_winsize(&mut self, _size: WinSize) -> Result<(), Error> {
+        Err(Error::new(Code::NotSup))
+    }
+
     /// Returns the type of the file implementation used for serialization
     fn file_type(&self) -> u8;
     /// Delegates this file to `act`
@@ -267,6 +389,15 @@ pub trait File: Read + Write + Seek + Map + Debug + HashInput + HashOutput {
         Err(Error::new(Code::NotSup))
     }
 
+    /// Tries to fetch a suspend request (e.g., due to ^Z) from the file, if any
+    ///
+    /// This works like [`File::fetch_signal`], but for [`FileEvent::SUSPEND`].
+    ///
+    /// Returns true if a suspend request was found
+    fn fetch_suspend(&mut self) -> Result<bool, Error> {
+        Err(Error::new(Code::NotSup))
+    }
+
     /// Checks whether any of the given events has arrived
     ///
     /// More specifically, if [`FileEvent::INPUT`] is given and reading from the file might result
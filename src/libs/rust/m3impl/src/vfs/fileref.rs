@@ -31,7 +31,7 @@ use crate::mem::VirtAddr;
 use crate::net::{DGramSocket, Socket, StreamSocket};
 use crate::serialize::{M3Serializer, VecSink};
 use crate::tiles::{Activity, ChildActivity};
-use crate::vfs::{Fd, File, FileEvent, FileTable, Map, Seek, SeekMode, TMode};
+use crate::vfs::{Fd, File, FileEvent, FileTable, Map, Seek, SeekMode, TMode, Termios, WinSize};
 
 /// A file reference provides access to a file of type `T`
 ///
@@ -155,6 +155,22 @@ impl<T: ?Sized + 'static> File for FileRef<T> {
         self.borrow().get_tmode()
     }
 
+    fn get_termios(&self) -> Result<Termios, Error> {
+        self.borrow().get_termios()
+    }
+
+    fn set_termios(&mut self, termios: &Termios) -> Result<(), Error> {
+        self.borrow().set_termios(termios)
+    }
+
+    fn get_winsize(&self) -> Result<WinSize, Error> {
+        self.borrow().get_winsize()
+    }
+
+    fn set_winsize(&mut self, size: WinSize) -> Result<(), Error> {
+        self.borrow().set_winsize(size)
+    }
+
     fn delegate(&self, act: &ChildActivity) -> Result<Selector, Error> {
         self.borrow().delegate(act)
     }
@@ -175,6 +191,10 @@ impl<T: ?Sized + 'static> File for FileRef<T> {
         self.borrow().fetch_signal()
     }
 
+    fn fetch_suspend(&mut self) -> Result<bool, Error> {
+        self.borrow().fetch_suspend()
+    }
+
     fn check_events(&mut self, events: FileEvent) -> bool {
         self.borrow().check_events(events)
     }
@@ -260,6 +280,14 @@ impl<T: 'static + Socket> Socket for FileRef<T> {
     fn send(&mut self, data: &[u8]) -> Result<usize, Error> {
         self.borrow_as().send(data)
     }
+
+    fn set_option(&mut self, opt: crate::net::SocketOpt, value: u64) -> Result<(), Error> {
+        self.borrow_as().set_option(opt, value)
+    }
+
+    fn get_option(&self, opt: crate::net::SocketOpt) -> Result<u64, Error> {
+        self.borrow_as().get_option(opt)
+    }
 }
 
 impl<T: 'static + DGramSocket> DGramSocket for FileRef<T> {
@@ -55,6 +55,9 @@ pub trait FileSystem: fmt::Debug {
     /// Renames `new_path` to `old_path`
     fn rename(&self, old_path: &str, new_path: &str) -> Result<(), Error>;
 
+    /// Sets the access and modification time of the file at `path` in seconds since the Unix epoch
+    fn utime(&self, path: &str, lastaccess: u32, lastmod: u32) -> Result<(), Error>;
+
     /// Returns the type of the file system implementation used for serialization
     fn fs_type(&self) -> u8;
     /// Delegates this file system to `act`
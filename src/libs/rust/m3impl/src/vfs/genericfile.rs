@@ -19,6 +19,7 @@
 use core::any::Any;
 use core::cmp;
 use core::fmt;
+use core::mem;
 
 use crate::boxed::Box;
 use crate::cap::Selector;
@@ -38,7 +39,10 @@ use crate::tcu::EpId;
 use crate::tcu::TCU;
 use crate::tiles::{Activity, ChildActivity};
 use crate::util::math;
-use crate::vfs::{filetable, Fd, File, FileEvent, FileInfo, Map, OpenFlags, Seek, SeekMode, TMode};
+use crate::vfs::{
+    filetable, Fd, File, FileEvent, FileInfo, Map, OpenFlags, Seek, SeekMode, TMode, Termios,
+    WinSize,
+};
 
 const NOTIFY_MSG_SIZE: usize = 64;
 
@@ -106,6 +110,11 @@ struct NonBlocking {
 ///
 /// Besides these mandatory requests, servers can optionally also support others like
 /// [`Seek`](`opcodes::File::Seek`) or [`FStat`](`opcodes::File::FStat`).
+///
+/// Files opened with [`OpenFlags::PIPELINE`] additionally delegate a second EP to the server via
+/// [`SetNextDest`](`opcodes::FileSystem::SetNextDest`). The server uses it to prepare the next chunk
+/// while the client is still busy with the current one and replies to `NextIn` and `NextOut`
+/// whether the client should continue with the other EP.
 pub struct GenericFile {
     id: Option<usize>,
     fs_id: Option<usize>,
@@ -114,6 +123,8 @@ pub struct GenericFile {
     sess: ClientSession,
     sgate: Rc<SendGate>,
     memep: Option<EP>,
+    // the second EP for pipelined transfers
+    next_memep: Option<EP>,
     delegated_ep: Selector,
     blocking: bool,
     nb_state: Option<NonBlocking>,
@@ -134,6 +145,7 @@ impl GenericFile {
             sess: ClientSession::new_owned_bind(sel),
             sgate: Rc::new(SendGate::new_bind(sel + 1).unwrap()),
             memep: None,
+            next_memep: None,
             delegated_ep: INVALID_SEL,
             blocking: true,
             nb_state: None,
@@ -161,6 +173,7 @@ impl GenericFile {
             sess: ClientSession::new_bind(sel),
             sgate,
             memep: Some(EP::new_bind(mep, INVALID_SEL)),
+            next_memep: None,
             delegated_ep: INVALID_SEL,
             blocking: true,
             nb_state: None,
@@ -235,7 +248,56 @@ impl GenericFile {
             let ep = self.memep.as_ref().unwrap();
             (ep.sel(), ep.id())
         };
-        self.delegate_ep(ep_sel, ep_id)
+        self.delegate_ep(ep_sel, ep_id)?;
+
+        let pipelined = self
+            .flags
+            .contains(OpenFlags::PIPELINE | OpenFlags::NEW_SESS);
+        if pipelined && self.next_memep.is_none() {
+            self.delegate_next_ep()?;
+        }
+        Ok(())
+    }
+
+    fn delegate_next_ep(&mut self) -> Result<(), Error> {
+        let ep = EpMng::get().acquire(0)?;
+        log!(
+            LogFlags::LibFS,
+            "GenFile[{}]::delegate_next_ep({})",
+            self.fd,
+            ep.id()
+        );
+
+        let crd = CapRngDesc::new(CapType::Object, ep.sel(), 1);
+        let res = self.sess.delegate(
+            crd,
+            |s| s.push(opcodes::FileSystem::SetNextDest),
+            |_| Ok(()),
+        );
+        match res {
+            Ok(_) => {
+                self.next_memep = Some(ep);
+                Ok(())
+            },
+            Err(e) => {
+                EpMng::get().release(ep, true);
+                Err(e)
+            },
+        }
+    }
+
+    fn receive_chunk(&mut self, reply: &mut GateIStream<'_>) -> Result<(), Error> {
+        self.goff += self.len;
+        self.off = reply.pop()?;
+        self.len = reply.pop()?;
+        self.pos = 0;
+
+        // in pipelined mode, the server tells us whether the chunk has been prepared on the other EP
+        if self.next_memep.is_some() && reply.pop::<bool>()? {
+            mem::swap(&mut self.memep, &mut self.next_memep);
+            self.delegated_ep = self.memep.as_ref().unwrap().sel();
+        }
+        Ok(())
     }
 
     fn next_in(&mut self, len: usize) -> Result<usize, Error> {
@@ -255,10 +317,7 @@ impl GenericFile {
                 opcodes::File::NextIn,
                 self.file_id()
             )?;
-            self.goff += self.len;
-            self.off = reply.pop()?;
-            self.len = reply.pop()?;
-            self.pos = 0;
+            self.receive_chunk(&mut reply)?;
         }
 
         Ok(cmp::min(len, self.len - self.pos))
@@ -280,10 +339,7 @@ impl GenericFile {
                 opcodes::File::NextOut,
                 self.file_id()
             )?;
-            self.goff += self.len;
-            self.off = reply.pop()?;
-            self.len = reply.pop()?;
-            self.pos = 0;
+            self.receive_chunk(&mut reply)?;
         }
 
         Ok(cmp::min(len, self.len - self.pos))
@@ -410,6 +466,9 @@ impl Drop for GenericFile {
             if let Some(ep) = self.memep.take() {
                 EpMng::get().release(ep, true);
             }
+            if let Some(ep) = self.next_memep.take() {
+                EpMng::get().release(ep, true);
+            }
         }
     }
 }
@@ -452,6 +511,9 @@ impl File for GenericFile {
             if let Some(ep) = self.memep.take() {
                 EpMng::get().release(ep, true);
             }
+            if let Some(ep) = self.next_memep.take() {
+                EpMng::get().release(ep, true);
+            }
         }
     }
 
@@ -513,6 +575,48 @@ impl File for GenericFile {
         reply.pop()
     }
 
+    fn get_termios(&self) -> Result<Termios, Error> {
+        let mut reply = send_recv_res!(
+            &self.sgate,
+            RecvGate::def(),
+            opcodes::File::GetTermios,
+            self.file_id()
+        )?;
+        reply.pop()
+    }
+
+    fn set_termios(&mut self, termios: &Termios) -> Result<(), Error> {
+        send_recv_res!(
+            &self.sgate,
+            RecvGate::def(),
+            opcodes::File::SetTermios,
+            self.file_id(),
+            termios
+        )
+        .map(|_| ())
+    }
+
+    fn get_winsize(&self) -> Result<WinSize, Error> {
+        let mut reply = send_recv_res!(
+            &self.sgate,
+            RecvGate::def(),
+            opcodes::File::GetWinSize,
+            self.file_id()
+        )?;
+        reply.pop()
+    }
+
+    fn set_winsize(&mut self, size: WinSize) -> Result<(), Error> {
+        send_recv_res!(
+            &self.sgate,
+            RecvGate::def(),
+            opcodes::File::SetWinSize,
+            self.file_id(),
+            size
+        )
+        .map(|_| ())
+    }
+
     fn file_type(&self) -> u8 {
         b'F'
     }
@@ -552,6 +656,12 @@ impl File for GenericFile {
         self.receive_notify(FileEvent::SIGNAL, true)
     }
 
+    fn fetch_suspend(&mut self) -> Result<bool, Error> {
+        self.enable_notifications()?;
+
+        self.receive_notify(FileEvent::SUSPEND, true)
+    }
+
     fn check_events(&mut self, events: FileEvent) -> bool {
         if self.blocking {
             true
@@ -601,7 +711,7 @@ impl Seek for GenericFile {
         self.off = reply.pop()?;
         self.pos = 0;
         self.len = 0;
-        Ok(self.goff + off)
+        Ok(self.goff + self.off)
     }
 }
 
@@ -662,6 +772,16 @@ impl Write for GenericFile {
             self.off + self.pos
         );
 
+        // start with a new chunk if the write does not fit into the rest of the current one, but
+        // into a complete one. This way, the server can ensure that it's not interleaved with
+        // writes of others.
+        if self.flags.contains(OpenFlags::ATOMIC)
+            && buf.len() > self.len - self.pos
+            && buf.len() <= self.len
+        {
+            self.submit(false)?;
+        }
+
         let amount = self.next_out(buf.len())?;
         if amount > 0 {
             TCU::write(
@@ -688,6 +808,11 @@ impl Map for GenericFile {
         flags: MapFlags,
     ) -> Result<(), Error> {
         // TODO maybe check here whether self is a pipe and return an error?
+        // files without their own session cannot hand out memory capabilities to the pager
+        if self.id.is_some() {
+            return Err(Error::new(Code::NotSup));
+        }
+
         pager
             .map_ds(virt, len, off, prot, flags, &self.sess)
             .map(|_| ())
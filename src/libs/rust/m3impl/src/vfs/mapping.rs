@@ -0,0 +1,154 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use core::fmt;
+
+use crate::client::MapFlags;
+use crate::errors::{Code, Error};
+use crate::io::{LogFlags, Write};
+use crate::kif::Perm;
+use crate::log;
+use crate::mem::VirtAddr;
+use crate::tiles::Activity;
+use crate::vfs::{FileRef, Map};
+
+/// A file that is mapped into the own address space
+///
+/// The mapping is established via the own pager, which loads the file contents lazily on page
+/// faults by requesting the memory capabilities for the touched parts of the file from the file
+/// system. Shared, writable mappings refer to the memory of the file system directly. Therefore,
+/// modifications are visible to other users of the file immediately and are written back to the
+/// storage via [`FileMapping::sync`] and when the mapping is dropped.
+///
+/// The `FileMapping` takes ownership of the file to ensure that the file stays open as long as the
+/// mapping exists. On drop, the region is unmapped and the file is closed afterwards.
+pub struct FileMapping<T: ?Sized + 'static> {
+    file: FileRef<T>,
+    virt: VirtAddr,
+    len: usize,
+    write_back: bool,
+}
+
+impl<T: ?Sized + 'static> FileMapping<T> {
+    /// Maps the region `off`..`off`+`len` of `file` at address `virt` with permissions `prot`.
+    ///
+    /// Note that files of m3fs need to be opened with
+    /// [`OpenFlags::NEW_SESS`](`crate::vfs::OpenFlags::NEW_SESS`) to be mappable.
+    pub fn new(
+        file: FileRef<T>,
+        virt: VirtAddr,
+        off: usize,
+        len: usize,
+        prot: Perm,
+        flags: MapFlags,
+    ) -> Result<Self, Error> {
+        let pager = Activity::own()
+            .pager()
+            .ok_or_else(|| Error::new(Code::NotSup))?;
+        file.map(pager, virt, off, len, prot, flags)?;
+
+        log!(
+            LogFlags::LibFS,
+            "FileMapping[fd={}]: mapped {}..{} at {} ({:?})",
+            file.fd(),
+            off,
+            off + len,
+            virt,
+            prot
+        );
+
+        Ok(Self {
+            file,
+            virt,
+            len,
+            write_back: flags.contains(MapFlags::SHARED) && prot.contains(Perm::W),
+        })
+    }
+
+    /// Returns the virtual address of the mapping
+    pub fn virt(&self) -> VirtAddr {
+        self.virt
+    }
+
+    /// Returns the length of the mapping in bytes
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the mapping is empty
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the mapped file
+    pub fn file(&self) -> &FileRef<T> {
+        &self.file
+    }
+
+    /// Returns the mapped memory as a byte slice
+    ///
+    /// # Safety
+    ///
+    /// The caller needs to ensure that the mapping is readable and that the file is not truncated
+    /// while the slice is in use.
+    pub unsafe fn as_slice(&self) -> &[u8] {
+        core::slice::from_raw_parts(self.virt.as_ptr(), self.len)
+    }
+
+    /// Returns the mapped memory as a mutable byte slice
+    ///
+    /// # Safety
+    ///
+    /// The caller needs to ensure that the mapping is writable and that the file is not truncated
+    /// while the slice is in use.
+    #[allow(clippy::mut_from_ref)]
+    pub unsafe fn as_mut_slice(&self) -> &mut [u8] {
+        core::slice::from_raw_parts_mut(self.virt.as_mut_ptr(), self.len)
+    }
+
+    /// Writes the modifications of the mapped region back to the storage of the file system.
+    ///
+    /// This is only required for shared, writable mappings; otherwise the call does nothing.
+    pub fn sync(&mut self) -> Result<(), Error> {
+        if self.write_back {
+            log!(LogFlags::LibFS, "FileMapping[fd={}]: sync", self.file.fd());
+            self.file.sync()
+        }
+        else {
+            Ok(())
+        }
+    }
+}
+
+impl<T: ?Sized + 'static> Drop for FileMapping<T> {
+    fn drop(&mut self) {
+        self.sync().ok();
+        if let Some(pager) = Activity::own().pager() {
+            pager.unmap(self.virt).ok();
+        }
+    }
+}
+
+impl<T: ?Sized + 'static> fmt::Debug for FileMapping<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "FileMapping[fd={}, virt={}, len={}]",
+            self.file.fd(),
+            self.virt,
+            self.len
+        )
+    }
+}
@@ -19,8 +19,9 @@
 //! The virtual file system (VFS)
 //!
 //! The VFS provides access to file systems and files. All file systems implement the [`FileSystem`]
-//! trait, whereas files implement the [`File`] trait. The former is currently only implemented by
-//! [`M3FS`](`crate::client::M3FS`) as this is the only available file system on M³. The latter is
+//! trait, whereas files implement the [`File`] trait. The former is implemented by
+//! [`M3FS`](`crate::client::M3FS`) as this is the only available file system on M³ and by
+//! [`OverlayFS`], which layers a writable file system over a read-only one. The latter is
 //! implemented by multiple types:
 //! - files that implement the *file protocol*: [`GenericFile`]
 //! - sockets: [`UdpSocket`](`crate::net::UdpSocket`), [`TcpSocket`](`crate::net::TcpSocket`), and
@@ -83,7 +84,9 @@ mod filesystem;
 mod filetable;
 mod genericfile;
 mod indirpipe;
+mod mapping;
 mod mounttable;
+mod overlay;
 #[allow(clippy::module_inception)]
 mod vfs;
 mod waiter;
@@ -97,14 +100,18 @@ pub type BlockId = u32;
 
 pub use self::bufio::{BufReader, BufWriter};
 pub use self::dir::{DirEntry, ReadDir};
-pub use self::file::{File, FileEvent, FileInfo, FileMode, Map, OpenFlags, Seek, SeekMode, TMode};
+pub use self::file::{
+    File, FileEvent, FileInfo, FileMode, InputFlags, LocalFlags, Map, OpenFlags, OutputFlags, Seek,
+    SeekMode, TMode, Termios, WinSize,
+};
 pub use self::fileref::FileRef;
 pub use self::filesystem::FileSystem;
-pub(crate) use self::filetable::INV_FD;
-pub use self::filetable::{Fd, FileTable};
+pub use self::filetable::{Fd, FileTable, INV_FD};
 pub use self::genericfile::GenericFile;
 pub use self::indirpipe::IndirectPipe;
+pub use self::mapping::FileMapping;
 pub use self::mounttable::{FSHandle, MountTable};
+pub use self::overlay::OverlayFS;
 pub use self::waiter::FileWaiter;
 
 /// The VFS module provides the application-facing API for files and file systems
@@ -27,7 +27,8 @@ use crate::errors::{Code, Error};
 use crate::rc::Rc;
 use crate::serialize::{M3Deserializer, M3Serializer, VecSink};
 use crate::tiles::ChildActivity;
-use crate::vfs::{FileSystem, VFS};
+use crate::vec;
+use crate::vfs::{FileSystem, OverlayFS, VFS};
 
 /// A reference to a file system
 pub type FSHandle = Rc<RefCell<dyn FileSystem>>;
@@ -36,16 +37,32 @@ pub type FSHandle = Rc<RefCell<dyn FileSystem>>;
 pub struct MountPoint {
     path: String,
     fs: FSHandle,
+    // the ids of the file system and, in case of overlays, its layers. files opened via an overlay
+    // belong to one of its layers. as the overlay might be borrowed while these files are closed,
+    // we remember the ids here instead of borrowing the file systems during the lookup.
+    ids: Vec<(usize, FSHandle)>,
 }
 
 impl MountPoint {
     /// Creates a new mount point for given path and file system
     pub fn new(path: &str, fs: FSHandle) -> MountPoint {
+        let mut ids = vec![(fs.borrow().id(), fs.clone())];
+        if let Some(ovl) = fs.borrow().as_any().downcast_ref::<OverlayFS>() {
+            for layer in ovl.layers() {
+                ids.push((layer.borrow().id(), layer.clone()));
+            }
+        }
+
         MountPoint {
             path: path.to_string(),
             fs,
+            ids,
         }
     }
+
+    fn find_id(&self, mid: usize) -> Option<&FSHandle> {
+        self.ids.iter().find(|(id, _)| *id == mid).map(|(_, fs)| fs)
+    }
 }
 
 /// The table of mount points
@@ -74,9 +91,12 @@ impl MountTable {
         }
 
         let pos = self.insert_pos(path);
+        let mp = MountPoint::new(path, fs);
         // ensure that we don't reuse ids, even if this filesystem was added after unserialization
-        self.next_id = self.next_id.max(fs.borrow().id());
-        self.mounts.insert(pos, MountPoint::new(path, fs));
+        for (id, _) in &mp.ids {
+            self.next_id = self.next_id.max(*id);
+        }
+        self.mounts.insert(pos, mp);
         Ok(())
     }
 
@@ -85,19 +105,20 @@ impl MountTable {
         self.path_to_idx(path).map(|i| self.mounts[i].fs.clone())
     }
 
-    /// Returns the mount point with id `mid`
+    /// Returns the file system with id `mid`
+    ///
+    /// This includes the layers of overlays, which are not mounted directly.
     pub fn get_by_id(&self, mid: usize) -> Option<FSHandle> {
-        self.mounts
-            .iter()
-            .find(|mp| mp.fs.borrow().id() == mid)
-            .map(|mp| mp.fs.clone())
+        self.mounts.iter().find_map(|mp| mp.find_id(mid)).cloned()
     }
 
     /// Returns the mount path of the mount with given id
+    ///
+    /// For the layers of an overlay, the mount path of the overlay is returned.
     pub fn path_of_id(&self, mid: usize) -> Option<&String> {
         self.mounts
             .iter()
-            .find(|mp| mp.fs.borrow().id() == mid)
+            .find(|mp| mp.find_id(mid).is_some())
             .map(|mp| &mp.path)
     }
 
@@ -162,17 +183,21 @@ impl MountTable {
         let count = s.pop().unwrap();
         for _ in 0..count {
             let path: String = s.pop().unwrap();
-            let fs_type: u8 = s.pop().unwrap();
-            mt.add(&path, match fs_type {
-                b'M' => M3FS::unserialize(s),
-                _ => panic!("Unexpected fs type {}", fs_type),
-            })
-            .unwrap();
+            mt.add(&path, Self::unserialize_fs(s)).unwrap();
         }
 
         mt
     }
 
+    pub(crate) fn unserialize_fs(s: &mut M3Deserializer<'_>) -> FSHandle {
+        let fs_type: u8 = s.pop().unwrap();
+        match fs_type {
+            b'M' => M3FS::unserialize(s),
+            b'O' => OverlayFS::unserialize(s),
+            _ => panic!("Unexpected fs type {}", fs_type),
+        }
+    }
+
     fn path_to_idx(&self, path: &str) -> Option<usize> {
         // TODO support imperfect paths
         assert!(path.starts_with('/'));
@@ -0,0 +1,430 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use core::any::Any;
+use core::fmt;
+
+use crate::boxed::Box;
+use crate::cap::Selector;
+use crate::cell::RefCell;
+use crate::col::{String, ToString, Vec};
+use crate::errors::{Code, Error};
+use crate::format;
+use crate::io::{LogFlags, Read, Write};
+use crate::log;
+use crate::rc::Rc;
+use crate::serialize::{M3Deserializer, M3Serializer, VecSink};
+use crate::tiles::{Activity, ChildActivity};
+use crate::vec;
+use crate::vfs::{
+    FSHandle, File, FileInfo, FileMode, FileRef, FileSystem, GenericFile, MountTable, OpenFlags,
+    ReadDir, VFS,
+};
+
+/// The prefix of whiteouts, which hide the entry with the remaining name in the lower layer
+pub(crate) const WHITEOUT_PREFIX: &str = ".wh.";
+/// The marker within a directory of the upper layer that hides all entries in the lower layer
+pub(crate) const OPAQUE_MARKER: &str = ".wh..opq";
+
+const COPY_BUF_SIZE: usize = 4096;
+
+fn join(dir: &str, name: &str) -> String {
+    if dir.is_empty() {
+        name.to_string()
+    }
+    else {
+        format!("{}/{}", dir, name)
+    }
+}
+
+fn split(path: &str) -> (&str, &str) {
+    path.rsplit_once('/').unwrap_or(("", path))
+}
+
+fn whiteout(path: &str) -> String {
+    let (dir, name) = split(path);
+    join(dir, &format!("{}{}", WHITEOUT_PREFIX, name))
+}
+
+fn canon(path: &str) -> Result<String, Error> {
+    let path = VFS::canon_path(path);
+    // the whiteouts are internal to the overlay and cannot be accessed directly
+    if path.split('/').any(|c| c.starts_with(WHITEOUT_PREFIX)) {
+        return Err(Error::new(Code::InvArgs));
+    }
+    Ok(path)
+}
+
+fn open_layer(
+    layer: &FSHandle,
+    path: &str,
+    flags: OpenFlags,
+) -> Result<FileRef<GenericFile>, Error> {
+    let file = layer.borrow_mut().open(path, flags)?;
+    let fd = Activity::own().files().add(file)?;
+    Ok(FileRef::new_owned(fd))
+}
+
+/// A file system that layers a writable file system over a read-only one
+///
+/// All modifications go to the *upper* layer, whereas the *lower* layer is never changed. Files and
+/// directories are looked up in the upper layer first and in the lower layer afterwards. Before a
+/// file of the lower layer is modified, it is copied to the upper layer along with its parent
+/// directories (*copy-up*). Removed entries of the lower layer are hidden by *whiteouts* in the
+/// upper layer, that is, empty files with the prefix `.wh.`. Directories in the upper layer that
+/// replace a removed directory of the lower layer contain the marker `.wh..opq` to hide the
+/// entries of the lower directory.
+///
+/// Note that directories of the lower layer cannot be renamed, because this would require to copy
+/// up their entire contents. In this case, [`Code::XfsLink`] is returned.
+pub struct OverlayFS {
+    id: usize,
+    upper: FSHandle,
+    lower: FSHandle,
+}
+
+impl OverlayFS {
+    /// Creates a new overlay with given id that layers `upper` over `lower`
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(id: usize, upper: FSHandle, lower: FSHandle) -> FSHandle {
+        Rc::new(RefCell::new(OverlayFS { id, upper, lower }))
+    }
+
+    /// Returns the upper and lower layer
+    pub fn layers(&self) -> [&FSHandle; 2] {
+        [&self.upper, &self.lower]
+    }
+
+    /// Opens the directory at `path` in all layers that contribute to its entries
+    pub(crate) fn open_dir(&self, path: &str) -> Result<Vec<FileRef<GenericFile>>, Error> {
+        let path = canon(path)?;
+
+        let mut dirs = Vec::new();
+        let upper = self.upper.borrow().stat(&path);
+        match upper {
+            Ok(info) => {
+                if !info.mode.is_dir() {
+                    return Err(Error::new(Code::IsNoDir));
+                }
+                dirs.push(open_layer(&self.upper, &path, OpenFlags::R)?);
+                if self.upper_exists(&join(&path, OPAQUE_MARKER)) {
+                    return Ok(dirs);
+                }
+            },
+            Err(e) if e.code() == Code::NoSuchFile => {},
+            Err(e) => return Err(e),
+        }
+
+        match self.lower_stat(&path) {
+            Ok(info) if info.mode.is_dir() => {
+                dirs.push(open_layer(&self.lower, &path, OpenFlags::R)?)
+            },
+            Ok(_) if dirs.is_empty() => return Err(Error::new(Code::IsNoDir)),
+            Err(e) if dirs.is_empty() => return Err(e),
+            _ => {},
+        }
+        Ok(dirs)
+    }
+
+    fn upper_exists(&self, path: &str) -> bool {
+        self.upper.borrow().stat(path).is_ok()
+    }
+
+    fn is_hidden(&self, path: &str) -> bool {
+        let mut cur = String::new();
+        for c in path.split('/').filter(|c| !c.is_empty()) {
+            if self.upper_exists(&join(&cur, OPAQUE_MARKER)) {
+                return true;
+            }
+            cur = join(&cur, c);
+            if self.upper_exists(&whiteout(&cur)) {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn lower_stat(&self, path: &str) -> Result<FileInfo, Error> {
+        if self.is_hidden(path) {
+            return Err(Error::new(Code::NoSuchFile));
+        }
+        self.lower.borrow().stat(path)
+    }
+
+    fn create_marker(&self, path: &str) -> Result<(), Error> {
+        open_layer(&self.upper, path, OpenFlags::W | OpenFlags::CREATE).map(|_| ())
+    }
+
+    fn remove_whiteout(&self, path: &str) -> Result<(), Error> {
+        let wh = whiteout(path);
+        if self.upper_exists(&wh) {
+            self.upper.borrow().unlink(&wh)?;
+        }
+        Ok(())
+    }
+
+    fn copy_up_parents(&self, path: &str) -> Result<(), Error> {
+        let (dir, _) = split(path);
+        let mut cur = String::new();
+        for c in dir.split('/').filter(|c| !c.is_empty()) {
+            cur = join(&cur, c);
+            if !self.upper_exists(&cur) {
+                let info = self.lower_stat(&cur)?;
+                if !info.mode.is_dir() {
+                    return Err(Error::new(Code::IsNoDir));
+                }
+                self.upper
+                    .borrow()
+                    .mkdir(&cur, info.mode & FileMode::PERM)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn copy_up(&self, path: &str) -> Result<(), Error> {
+        if self.upper_exists(path) {
+            return Ok(());
+        }
+
+        let info = self.lower_stat(path)?;
+        self.copy_up_parents(path)?;
+
+        log!(LogFlags::LibFS, "OverlayFS[{}]::copy_up({})", self.id, path);
+
+        if info.mode.is_dir() {
+            return self.upper.borrow().mkdir(path, info.mode & FileMode::PERM);
+        }
+
+        {
+            let mut src = open_layer(&self.lower, path, OpenFlags::R)?;
+            let mut dst = open_layer(
+                &self.upper,
+                path,
+                OpenFlags::W | OpenFlags::CREATE | OpenFlags::TRUNC,
+            )?;
+            let mut buf = vec![0u8; COPY_BUF_SIZE];
+            loop {
+                let count = src.read(&mut buf)?;
+                if count == 0 {
+                    break;
+                }
+                dst.write_all(&buf[0..count])?;
+            }
+        }
+
+        self.upper
+            .borrow()
+            .utime(path, info.lastaccess, info.lastmod)
+    }
+
+    pub(crate) fn unserialize(s: &mut M3Deserializer<'_>) -> FSHandle {
+        let id: usize = s.pop().unwrap();
+        let upper = MountTable::unserialize_fs(s);
+        let lower = MountTable::unserialize_fs(s);
+        OverlayFS::new(id, upper, lower)
+    }
+}
+
+impl FileSystem for OverlayFS {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+
+    fn open(&mut self, path: &str, flags: OpenFlags) -> Result<Box<dyn File>, Error> {
+        let path = canon(path)?;
+
+        let modifies = OpenFlags::W | OpenFlags::TRUNC | OpenFlags::APPEND | OpenFlags::CREATE;
+        if flags.intersects(modifies) {
+            let mut flags = flags;
+            if !self.upper_exists(&path) {
+                match self.lower_stat(&path) {
+                    // no need to copy the content if it is truncated anyway
+                    Ok(info) if !info.mode.is_dir() && flags.contains(OpenFlags::TRUNC) => {
+                        self.copy_up_parents(&path)?;
+                        flags |= OpenFlags::CREATE;
+                    },
+                    Ok(_) => self.copy_up(&path)?,
+                    Err(e) if e.code() == Code::NoSuchFile && flags.contains(OpenFlags::CREATE) => {
+                        self.copy_up_parents(&path)?;
+                        self.remove_whiteout(&path)?;
+                    },
+                    Err(e) => return Err(e),
+                }
+            }
+            return self.upper.borrow_mut().open(&path, flags);
+        }
+
+        let res = self.upper.borrow_mut().open(&path, flags);
+        match res {
+            Err(e) if e.code() == Code::NoSuchFile => {
+                self.lower_stat(&path)?;
+                self.lower.borrow_mut().open(&path, flags)
+            },
+            res => res,
+        }
+    }
+
+    fn close(&mut self, _file_id: usize) -> Result<(), Error> {
+        // files are opened at the layers and therefore closed there
+        Err(Error::new(Code::NotSup))
+    }
+
+    fn stat(&self, path: &str) -> Result<FileInfo, Error> {
+        let path = canon(path)?;
+        let res = self.upper.borrow().stat(&path);
+        match res {
+            Err(e) if e.code() == Code::NoSuchFile => self.lower_stat(&path),
+            res => res,
+        }
+    }
+
+    fn mkdir(&self, path: &str, mode: FileMode) -> Result<(), Error> {
+        let path = canon(path)?;
+        if self.stat(&path).is_ok() {
+            return Err(Error::new(Code::Exists));
+        }
+
+        self.copy_up_parents(&path)?;
+
+        // if we replace a removed directory of the lower layer, its entries need to stay hidden
+        let wh = whiteout(&path);
+        let replaces = self.upper_exists(&wh);
+        if replaces {
+            self.upper.borrow().unlink(&wh)?;
+        }
+        self.upper.borrow().mkdir(&path, mode)?;
+        if replaces {
+            self.create_marker(&join(&path, OPAQUE_MARKER))?;
+        }
+        Ok(())
+    }
+
+    fn rmdir(&self, path: &str) -> Result<(), Error> {
+        let path = canon(path)?;
+        if !self.stat(&path)?.mode.is_dir() {
+            return Err(Error::new(Code::IsNoDir));
+        }
+
+        // the directory needs to be empty in all layers
+        let mut entries = ReadDir::new_merged(self.open_dir(&path)?);
+        if entries.any(|e| e.file_name() != "." && e.file_name() != "..") {
+            return Err(Error::new(Code::DirNotEmpty));
+        }
+
+        if self.upper_exists(&path) {
+            // remove whiteouts and the opaque marker first
+            let markers = ReadDir::new(open_layer(&self.upper, &path, OpenFlags::R)?)
+                .filter(|e| e.file_name().starts_with(WHITEOUT_PREFIX))
+                .map(|e| join(&path, e.file_name()))
+                .collect::<Vec<_>>();
+            for m in markers {
+                self.upper.borrow().unlink(&m)?;
+            }
+            self.upper.borrow().rmdir(&path)?;
+        }
+
+        if self.lower_stat(&path).is_ok() {
+            self.copy_up_parents(&path)?;
+            self.create_marker(&whiteout(&path))?;
+        }
+        Ok(())
+    }
+
+    fn link(&self, old_path: &str, new_path: &str) -> Result<(), Error> {
+        let (old_path, new_path) = (canon(old_path)?, canon(new_path)?);
+        if self.stat(&new_path).is_ok() {
+            return Err(Error::new(Code::Exists));
+        }
+
+        self.copy_up(&old_path)?;
+        self.copy_up_parents(&new_path)?;
+        self.remove_whiteout(&new_path)?;
+        self.upper.borrow().link(&old_path, &new_path)
+    }
+
+    fn unlink(&self, path: &str) -> Result<(), Error> {
+        let path = canon(path)?;
+        if self.stat(&path)?.mode.is_dir() {
+            return Err(Error::new(Code::IsDir));
+        }
+
+        if self.upper_exists(&path) {
+            self.upper.borrow().unlink(&path)?;
+        }
+        if self.lower_stat(&path).is_ok() {
+            self.copy_up_parents(&path)?;
+            self.create_marker(&whiteout(&path))?;
+        }
+        Ok(())
+    }
+
+    fn rename(&self, old_path: &str, new_path: &str) -> Result<(), Error> {
+        let (old_path, new_path) = (canon(old_path)?, canon(new_path)?);
+        if self.stat(&old_path)?.mode.is_dir() && self.lower_stat(&old_path).is_ok() {
+            return Err(Error::new(Code::XfsLink));
+        }
+
+        self.copy_up(&old_path)?;
+        self.copy_up_parents(&new_path)?;
+        self.remove_whiteout(&new_path)?;
+        self.upper.borrow().rename(&old_path, &new_path)?;
+
+        if self.lower_stat(&old_path).is_ok() {
+            self.create_marker(&whiteout(&old_path))?;
+        }
+        Ok(())
+    }
+
+    fn utime(&self, path: &str, lastaccess: u32, lastmod: u32) -> Result<(), Error> {
+        let path = canon(path)?;
+        self.copy_up(&path)?;
+        self.upper.borrow().utime(&path, lastaccess, lastmod)
+    }
+
+    fn fs_type(&self) -> u8 {
+        b'O'
+    }
+
+    fn delegate(&self, act: &ChildActivity) -> Result<Selector, Error> {
+        let upper = self.upper.borrow().delegate(act)?;
+        let lower = self.lower.borrow().delegate(act)?;
+        Ok(upper.max(lower))
+    }
+
+    fn serialize(&self, s: &mut M3Serializer<VecSink<'_>>) {
+        s.push(self.id);
+        for layer in self.layers() {
+            let layer = layer.borrow();
+            s.push(layer.fs_type());
+            layer.serialize(s);
+        }
+    }
+}
+
+impl fmt::Debug for OverlayFS {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "OverlayFS[id={}, upper={:?}, lower={:?}]",
+            self.id,
+            self.upper.borrow(),
+            self.lower.borrow()
+        )
+    }
+}
@@ -23,7 +23,8 @@ use crate::errors::{Code, Error};
 use crate::rc::Rc;
 use crate::tiles::Activity;
 use crate::vfs::{
-    FSHandle, File, FileInfo, FileMode, FileRef, GenericFile, OpenFlags, ReadDir, SeekMode,
+    FSHandle, File, FileInfo, FileMode, FileRef, GenericFile, OpenFlags, OverlayFS, ReadDir,
+    SeekMode,
 };
 
 /// Mounts the file system of type `fstype` at `path`, creating a session at `service`
@@ -36,6 +37,21 @@ pub fn mount(path: &str, fstype: &str, service: &str) -> Result<(), Error> {
     Activity::own().mounts().add(path, fsobj)
 }
 
+/// Mounts an overlay at `path` that layers the m3fs instance at `upper` over the one at `lower`
+///
+/// All modifications are performed on `upper`, whereas `lower` is only read. Files of `lower` are
+/// copied to `upper` on the first write. See [`OverlayFS`] for details.
+pub fn mount_overlay(path: &str, upper: &str, lower: &str) -> Result<(), Error> {
+    let upper_id = Activity::own().mounts().alloc_id();
+    let upper = M3FS::new(upper_id, upper)?;
+    let lower_id = Activity::own().mounts().alloc_id();
+    let lower = M3FS::new(lower_id, lower)?;
+    let id = Activity::own().mounts().alloc_id();
+    Activity::own()
+        .mounts()
+        .add(path, OverlayFS::new(id, upper, lower))
+}
+
 /// Umounts the file system mounted at `path`
 pub fn unmount(path: &str) -> Result<(), Error> {
     Activity::own().mounts().remove(path)
@@ -147,8 +163,18 @@ pub fn open(path: &str, flags: OpenFlags) -> Result<FileRef<GenericFile>, Error>
 
 /// Returns an iterator for entries in the directory at `path`
 pub fn read_dir(path: &str) -> Result<ReadDir, Error> {
-    let dir = open(path, OpenFlags::R)?;
-    Ok(ReadDir::new(dir))
+    // directories of overlays consist of the directories in all layers
+    let layers = with_path(path, |fs, fs_path| {
+        match fs.borrow().as_any().downcast_ref::<OverlayFS>() {
+            Some(ovl) => ovl.open_dir(fs_path).map(Some),
+            None => Ok(None),
+        }
+    })?;
+
+    match layers {
+        Some(dirs) => Ok(ReadDir::new_merged(dirs)),
+        None => Ok(ReadDir::new(open(path, OpenFlags::R)?)),
+    }
 }
 
 /// Retrieves the file information from the file at `path`
@@ -184,6 +210,16 @@ pub fn unlink(path: &str) -> Result<(), Error> {
     with_path(path, |fs, fs_path| fs.borrow().unlink(fs_path))
 }
 
+/// Sets the access and modification time of the file at `path`
+///
+/// The times are given in seconds since the Unix epoch (see
+/// [`SystemTime::as_unix_secs`](crate::time::SystemTime::as_unix_secs)).
+pub fn utime(path: &str, lastaccess: u32, lastmod: u32) -> Result<(), Error> {
+    with_path(path, |fs, fs_path| {
+        fs.borrow().utime(fs_path, lastaccess, lastmod)
+    })
+}
+
 /// Renames `new` to `old`
 pub fn rename(old: &str, new: &str) -> Result<(), Error> {
     let mut old = Cow::from(old);
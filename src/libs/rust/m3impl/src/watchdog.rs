@@ -0,0 +1,122 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! Sends heartbeats to the watchdog of the resource manager
+//!
+//! If an app is watched by its resource manager (`<watchdog>` element in the config), the resource
+//! manager passes the interval for heartbeats in microseconds via the environment variable
+//! `M3_WATCHDOG`. The app is expected to call [`check_in`] regularly, typically in its main loop,
+//! and to not sleep longer than [`next_timeout`]. If the resource manager does not receive a
+//! heartbeat within the configured timeout, it logs the last reported [`Diagnostics`] and, if
+//! requested, kills the app.
+//!
+//! For apps that are not watched, [`check_in`] does nothing and [`next_timeout`] returns `None`.
+
+use crate::cell::StaticCell;
+use crate::env;
+use crate::io::LogFlags;
+use crate::log;
+use crate::serialize::{Deserialize, Serialize};
+use crate::tiles::Activity;
+use crate::time::{TimeDuration, TimeInstant};
+
+/// Diagnostic information that is sent along with every heartbeat
+///
+/// The information is printed by the resource manager if a heartbeat is missed. Since the app is
+/// most likely hung at that point, the information stems from the last heartbeat.
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(crate = "base::serde")]
+pub struct Diagnostics {
+    /// The total number of threads
+    pub threads: u32,
+    /// The number of threads that are ready to run
+    pub ready: u32,
+    /// The number of threads that wait for an event
+    pub blocked: u32,
+    /// The number of threads that wait for a timeout
+    pub sleeping: u32,
+    /// The number of requests that are queued, but not yet handled
+    pub queued: u32,
+}
+
+#[derive(Copy, Clone)]
+enum State {
+    Uninit,
+    Disabled,
+    Enabled {
+        interval: TimeDuration,
+        next: TimeInstant,
+    },
+}
+
+static STATE: StaticCell<State> = StaticCell::new(State::Uninit);
+
+fn state() -> State {
+    if let State::Uninit = STATE.get() {
+        let state = match env::var(env::WATCHDOG_VAR).and_then(|v| v.parse::<u64>().ok()) {
+            Some(us) if us > 0 => State::Enabled {
+                interval: TimeDuration::from_micros(us),
+                next: TimeInstant::now(),
+            },
+            _ => State::Disabled,
+        };
+        STATE.set(state);
+    }
+    STATE.get()
+}
+
+/// Returns true if this app is watched by the resource manager
+pub fn enabled() -> bool {
+    matches!(state(), State::Enabled { .. })
+}
+
+/// Returns the time until the next heartbeat is due or `None` if the watchdog is disabled
+pub fn next_timeout() -> Option<TimeDuration> {
+    match state() {
+        State::Enabled { next, .. } => Some(
+            next.checked_duration_since(TimeInstant::now())
+                .unwrap_or(TimeDuration::ZERO),
+        ),
+        _ => None,
+    }
+}
+
+/// Sends a heartbeat with default diagnostics to the resource manager, if one is due
+pub fn check_in() {
+    check_in_with(Diagnostics::default);
+}
+
+/// Sends a heartbeat to the resource manager, if one is due
+///
+/// The function `diag` is only called if a heartbeat is due and provides the diagnostics to send.
+pub fn check_in_with<F: FnOnce() -> Diagnostics>(diag: F) {
+    if let State::Enabled { interval, next } = state() {
+        let now = TimeInstant::now();
+        if now < next {
+            return;
+        }
+
+        if let Some(resmng) = Activity::own().resmng() {
+            if let Err(e) = resmng.heartbeat(&diag()) {
+                log!(LogFlags::Error, "Unable to send heartbeat: {}", e);
+            }
+        }
+
+        STATE.set(State::Enabled {
+            interval,
+            next: now + interval,
+        });
+    }
+}
@@ -34,6 +34,9 @@ pub const LEVEL_CNT: usize = 3;
 pub const LEVEL_BITS: usize = cfg::PAGE_BITS - PTE_BITS;
 pub const LEVEL_MASK: usize = (1 << LEVEL_BITS) - 1;
 
+// we only use 2 MiB blocks
+pub const MAX_LEAF_LEVEL: usize = 1;
+
 bitflags! {
     #[derive(Copy, Clone, Debug, PartialEq, Eq)]
     pub struct ARMMMUFlags : MMUPTE {
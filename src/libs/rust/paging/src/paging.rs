@@ -29,7 +29,7 @@ use base::tcu::TCU;
 use base::util::math;
 use core::fmt;
 
-use arch::{LEVEL_BITS, LEVEL_CNT, LEVEL_MASK};
+use arch::{LEVEL_BITS, LEVEL_CNT, LEVEL_MASK, MAX_LEAF_LEVEL};
 
 pub type ActId = u64;
 
@@ -90,6 +90,12 @@ cfg_if::cfg_if! {
         pub type Paging = arch::ARMPaging;
         pub type MMUFlags = <arch::ARMPaging as ArchPaging>::MMUFlags;
     }
+    else if #[cfg(target_arch = "aarch64")] {
+        #[path = "aarch64/mod.rs"]
+        mod arch;
+        pub type Paging = arch::AArch64Paging;
+        pub type MMUFlags = <arch::AArch64Paging as ArchPaging>::MMUFlags;
+    }
     else if #[cfg(target_arch = "riscv64")] {
         #[path = "riscv/mod.rs"]
         mod arch;
@@ -111,6 +117,11 @@ pub trait Allocator {
     fn free_pt(&mut self, phys: PhysAddr);
 }
 
+/// Returns the size of the memory that is mapped by a leaf PTE at given level
+fn page_size(level: usize) -> usize {
+    1 << (cfg::PAGE_BITS + level * LEVEL_BITS)
+}
+
 pub struct AddrSpace<A: Allocator> {
     id: ActId,
     root: MMUPTE,
@@ -167,7 +178,13 @@ impl<A: Allocator> AddrSpace<A> {
 
             let pte_flags = MMUFlags::from_bits_truncate(pte);
             if pte_flags.is_leaf(lvl) || !pte_flags.access_allowed(perm) {
-                let res = Paging::pte_to_phys(pte);
+                let mut res = Paging::pte_to_phys(pte);
+                // the TCU and our users only know about large pages of LPAGE_SIZE. Therefore, we
+                // return the large page within larger pages that contains `virt`.
+                if lvl > 1 && pte_flags.is_leaf(lvl) {
+                    let off = virt.as_local() & (page_size(lvl) - 1) & !cfg::LPAGE_MASK;
+                    res += off as PhysAddrRaw;
+                }
                 let flags = MMUFlags::from_bits_truncate(pte);
                 return (res, Paging::to_page_flags(lvl, flags));
             }
@@ -232,19 +249,14 @@ impl<A: Allocator> AddrSpace<A> {
             else {
                 level == 0
                 // can we use a large page?
-                || (level == 1
-                    && math::is_aligned((*virt).as_local(), cfg::LPAGE_SIZE)
-                    && math::is_aligned((*phys).as_raw(), cfg::LPAGE_SIZE as PhysAddrRaw)
-                    && *pages * cfg::PAGE_SIZE >= cfg::LPAGE_SIZE)
+                || (level <= MAX_LEAF_LEVEL
+                    && math::is_aligned((*virt).as_local(), page_size(level))
+                    && math::is_aligned((*phys).as_raw(), page_size(level) as PhysAddrRaw)
+                    && *pages * cfg::PAGE_SIZE >= page_size(level))
             };
 
             if is_leaf {
-                let psize = if level == 1 {
-                    cfg::LPAGE_SIZE
-                }
-                else {
-                    cfg::PAGE_SIZE
-                };
+                let psize = page_size(level);
 
                 let new_pte = Paging::build_pte(*phys, perm, level, true);
 
@@ -33,6 +33,9 @@ pub const LEVEL_CNT: usize = 3;
 pub const LEVEL_BITS: usize = cfg::PAGE_BITS - PTE_BITS;
 pub const LEVEL_MASK: usize = (1 << LEVEL_BITS) - 1;
 
+// Sv39 supports megapages (2 MiB) at level 1 and gigapages (1 GiB) at level 2
+pub const MAX_LEAF_LEVEL: usize = 2;
+
 pub const MODE_BARE: u64 = 0;
 pub const MODE_SV39: u64 = 8;
 
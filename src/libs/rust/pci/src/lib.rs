@@ -21,21 +21,30 @@ use bitflags::bitflags;
 
 use num_enum::IntoPrimitive;
 
-use m3::cfg;
+use m3::cell::RefCell;
+use m3::col::{BitArray, Vec};
 use m3::com::{EpMng, MemCap, MemGate, RecvGate, SendCap, EP};
-use m3::errors::Error;
+use m3::devinfo::DeviceInfo;
+use m3::errors::{Code, Error};
 use m3::kif::{Perm, TileDesc, TileISA, TileType};
 use m3::mem::{GlobOff, VirtAddr};
-use m3::tcu::EpId;
+use m3::tcu::{self, EpId};
 use m3::tiles::{ChildActivity, RunningDeviceActivity, Tile};
 use m3::util::math;
 
 const EP_INT: EpId = 16;
 const EP_DMA: EpId = 17;
 
-// hardcoded for now
-const REG_ADDR: GlobOff = 0x4000;
-const PCI_CFG_ADDR: GlobOff = 0x0F00_0000;
+// defaults if the device has not been assigned via the device tree; otherwise, the first "reg"
+// region holds the registers (BAR0) and the second one the configuration space
+const DEF_REG_ADDR: GlobOff = 0x4000;
+const DEF_CFG_ADDR: GlobOff = DEF_REG_ADDR + 0x0F00_0000;
+
+// the device tile provides the (ECAM-style) configuration space of a single bus
+const MAX_DEVICES: u8 = 32;
+const MAX_FUNCTIONS: u8 = 8;
+const CFG_FUNC_SIZE: GlobOff = 0x1000;
+const CFG_SPACE_SIZE: GlobOff = MAX_DEVICES as GlobOff * MAX_FUNCTIONS as GlobOff * CFG_FUNC_SIZE;
 
 const MSG_SIZE: usize = 64;
 const BUF_SIZE: usize = MSG_SIZE * 8;
@@ -80,13 +89,182 @@ pub enum Type0 {
     MaxLatency    = 0x3F, // Maximum Latency              ro
 }
 
+// Bits in the status register
+const STATUS_CAP_LIST: u16 = 1 << 4;
+
+// Bits in the header type register
+const HEADER_MULTI_FUNC: u8 = 1 << 7;
+
+// upper bound for the capability list to survive malformed (cyclic) lists
+const MAX_CAPS: usize = 48;
+
+// MSI-X capability offsets (relative to the capability)
+const MSIX_CTRL: GlobOff = 0x02;
+const MSIX_TABLE: GlobOff = 0x04;
+
+// MSI-X message control bits
+const MSIX_CTRL_SIZE_MASK: u16 = 0x7FF;
+const MSIX_CTRL_FUNC_MASK: u16 = 1 << 14;
+const MSIX_CTRL_ENABLE: u16 = 1 << 15;
+
+// MSI-X table entry layout
+const MSIX_ENTRY_SIZE: GlobOff = 16;
+const MSIX_ENTRY_ADDR_LO: GlobOff = 0x0;
+const MSIX_ENTRY_ADDR_HI: GlobOff = 0x4;
+const MSIX_ENTRY_DATA: GlobOff = 0x8;
+const MSIX_ENTRY_CTRL: GlobOff = 0xC;
+const MSIX_ENTRY_CTRL_MASKED: u32 = 0x1;
+
+/// The tag in the upper half of the MSI-X message data ("MS"); the lower half holds the vector
+///
+/// The device tile delivers MSI-X messages with their data as payload to the same endpoint as
+/// legacy interrupts. The driver decodes the payload of every interrupt message via
+/// [`Interrupt::from_payload`] (called by `Device::decode_irq`), which treats tagged payloads as
+/// MSI-X vectors and everything else as legacy interrupts. The tag is not interpreted by the device
+/// or the device tile.
+const MSIX_DATA_TAG: u32 = 0x4D53_0000;
+/// The mask to extract the [`MSIX_DATA_TAG`] from the message data
+const MSIX_DATA_TAG_MASK: u32 = 0xFFFF_0000;
+
+/// The number of a MSI-X vector
+pub type MsiXVector = u16;
+
+/// An interrupt that has been received from the device
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Interrupt {
+    /// A legacy (INTx) interrupt
+    Legacy,
+    /// A message-signaled interrupt for the given vector
+    MsiX(MsiXVector),
+}
+
+impl Interrupt {
+    /// Returns the message data the device writes for interrupts of the given MSI-X vector
+    pub fn msix_data(vec: MsiXVector) -> u32 {
+        MSIX_DATA_TAG | vec as u32
+    }
+
+    /// Decodes the interrupt from the payload of a message from the device tile
+    ///
+    /// Only payloads that have been produced by [`msix_data`](Self::msix_data) denote MSI-X
+    /// interrupts; all other messages are legacy interrupts.
+    pub fn from_payload(payload: Option<u64>) -> Self {
+        match payload.map(u32::try_from) {
+            Some(Ok(data)) if (data & MSIX_DATA_TAG_MASK) == MSIX_DATA_TAG => {
+                Interrupt::MsiX((data & !MSIX_DATA_TAG_MASK) as MsiXVector)
+            },
+            _ => Interrupt::Legacy,
+        }
+    }
+}
+
+/// The id of a PCI capability
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CapId {
+    PowerMgmt,
+    MSI,
+    PCIe,
+    MSIX,
+    Other(u8),
+}
+
+impl From<u8> for CapId {
+    fn from(val: u8) -> Self {
+        match val {
+            0x01 => CapId::PowerMgmt,
+            0x05 => CapId::MSI,
+            0x10 => CapId::PCIe,
+            0x11 => CapId::MSIX,
+            id => CapId::Other(id),
+        }
+    }
+}
+
+/// An entry in the capability list of a PCI function
+#[derive(Copy, Clone, Debug)]
+pub struct Capability {
+    id: CapId,
+    offset: GlobOff,
+}
+
+impl Capability {
+    /// Returns the id of the capability
+    pub fn id(&self) -> CapId {
+        self.id
+    }
+
+    /// Returns the offset of the capability within the configuration space of the function
+    pub fn offset(&self) -> GlobOff {
+        self.offset
+    }
+}
+
+/// Iterates over the capability list of a PCI function
+///
+/// The iteration stops at the end of the list or as soon as the configuration space cannot be
+/// read.
+pub struct CapIterator<'d> {
+    dev: &'d Device,
+    bdf: BDF,
+    next: u8,
+    remaining: usize,
+}
+
+impl<'d> CapIterator<'d> {
+    fn new(dev: &'d Device, bdf: BDF) -> Result<Self, Error> {
+        let status: u16 = dev.read_config_of(bdf, Reg::Status.into())?;
+        let next = if (status & STATUS_CAP_LIST) != 0 {
+            dev.read_config_of(bdf, Type0::CapPtr.into())?
+        }
+        else {
+            0
+        };
+        Ok(Self {
+            dev,
+            bdf,
+            next,
+            remaining: MAX_CAPS,
+        })
+    }
+}
+
+impl<'d> Iterator for CapIterator<'d> {
+    type Item = Capability;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let offset = (self.next & !0x3) as GlobOff;
+        if offset == 0 || self.remaining == 0 {
+            return None;
+        }
+
+        let id: u8 = self.dev.read_config_of(self.bdf, offset).ok()?;
+        self.next = self.dev.read_config_of(self.bdf, offset + 1).ok()?;
+        self.remaining -= 1;
+        Some(Capability {
+            id: CapId::from(id),
+            offset,
+        })
+    }
+}
+
+struct MsiX {
+    cap: GlobOff,
+    table: GlobOff,
+    addr: GlobOff,
+    vectors: BitArray,
+}
+
 pub struct Device {
     _activity: RunningDeviceActivity,
     mem: MemGate,
+    reg_addr: GlobOff,
+    cfg_addr: GlobOff,
     _sep: EP,
     mep: EP,
     rgate: RecvGate,
     _scap: SendCap,
+    msi_addr: Option<GlobOff>,
+    msix: RefCell<Option<MsiX>>,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -143,7 +321,7 @@ impl Bar {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
 pub struct BDF {
     bus: u8,
     device: u8,
@@ -254,13 +432,27 @@ impl Info {
 }
 
 impl Device {
+    /// Creates a new device with given name on a new tile with given ISA
+    ///
+    /// If a device with given name has been assigned to us via the config, its register and
+    /// configuration space addresses are taken from the device tree. MSI-X can only be used if the
+    /// device tree specifies the address the device tile forwards MSI-X messages from.
     pub fn new(name: &str, isa: TileISA) -> Result<Self, Error> {
+        let (reg_addr, reg_size, cfg_addr, msi_addr) = match DeviceInfo::get(name) {
+            Some(info) if info.regs().len() >= 2 => {
+                let (reg, cfg) = (info.regs()[0], info.regs()[1]);
+                (reg.0, reg.1, cfg.0, info.msi())
+            },
+            Some(_) => return Err(Error::new(Code::InvArgs)),
+            None => (DEF_REG_ADDR, 0, DEF_CFG_ADDR, None),
+        };
+
         let tile = Tile::new(TileDesc::new(TileType::Comp, isa, 0))?;
         let act = ChildActivity::new(tile, name)?;
         let act_sel = act.sel();
         let mem = act.get_mem(
             VirtAddr::null(),
-            (PCI_CFG_ADDR + REG_ADDR) + cfg::PAGE_SIZE as GlobOff,
+            (reg_addr + reg_size).max(cfg_addr + CFG_SPACE_SIZE),
             Perm::RW,
         )?;
         let sep = EpMng::acquire_for(act_sel, EP_INT, 0)?;
@@ -272,10 +464,14 @@ impl Device {
         Ok(Self {
             _activity: act.start()?,
             mem,
+            reg_addr,
+            cfg_addr,
             _sep: sep,
             mep,
             rgate,
             _scap: scap,
+            msi_addr,
+            msix: RefCell::new(None),
         })
     }
 
@@ -284,71 +480,260 @@ impl Device {
     }
 
     pub fn check_for_irq(&self) -> bool {
-        if let Ok(msg) = self.rgate.fetch() {
-            self.rgate.ack_msg(msg).unwrap();
-            true
+        self.fetch_irq().is_some()
+    }
+
+    pub fn wait_for_irq(&self) -> Result<(), Error> {
+        self.receive_irq().map(|_| ())
+    }
+
+    /// Fetches the next pending interrupt, if any, without blocking
+    pub fn fetch_irq(&self) -> Option<Interrupt> {
+        let msg = self.rgate.fetch().ok()?;
+        let irq = self.decode_irq(msg);
+        self.rgate.ack_msg(msg).unwrap();
+        Some(irq)
+    }
+
+    /// Waits until the next interrupt arrives and returns it
+    pub fn receive_irq(&self) -> Result<Interrupt, Error> {
+        let msg = self.rgate.receive(None)?;
+        let irq = self.decode_irq(msg);
+        self.rgate.ack_msg(msg)?;
+        Ok(irq)
+    }
+
+    fn decode_irq(&self, msg: &tcu::Message) -> Interrupt {
+        Interrupt::from_payload(msg.as_words().first().copied())
+    }
+
+    /// Enables MSI-X interrupt delivery and returns the number of supported vectors.
+    ///
+    /// All vectors are masked initially and need to be allocated via
+    /// [`alloc_msix_vector`](Self::alloc_msix_vector). Fails with `Code::NotSup` if the device
+    /// does not have the MSI-X capability or the device tile does not forward MSI-X messages.
+    pub fn enable_msix(&self) -> Result<u16, Error> {
+        if let Some(msix) = &*self.msix.borrow() {
+            return Ok(msix.vectors.size() as u16);
         }
-        else {
-            false
+
+        let addr = self.msi_addr.ok_or_else(|| Error::new(Code::NotSup))?;
+
+        let cap = self
+            .find_capability(BDF::default(), CapId::MSIX)?
+            .ok_or_else(|| Error::new(Code::NotSup))?;
+
+        let ctrl: u16 = self.read_config(cap + MSIX_CTRL)?;
+        let count = (ctrl & MSIX_CTRL_SIZE_MASK) + 1;
+        let table: u32 = self.read_config(cap + MSIX_TABLE)?;
+        // the device tile only maps BAR0 at our register address
+        if (table & 0x7) != 0 {
+            return Err(Error::new(Code::NotSup));
+        }
+        let table = (table & !0x7) as GlobOff;
+
+        // mask all vectors before enabling MSI-X
+        self.write_config(cap + MSIX_CTRL, ctrl | MSIX_CTRL_FUNC_MASK)?;
+        for i in 0..count as GlobOff {
+            let entry = table + i * MSIX_ENTRY_SIZE;
+            self.write_reg(entry + MSIX_ENTRY_CTRL, MSIX_ENTRY_CTRL_MASKED)?;
+        }
+        self.write_config(cap + MSIX_CTRL, ctrl | MSIX_CTRL_ENABLE)?;
+
+        self.msix.replace(Some(MsiX {
+            cap,
+            table,
+            addr,
+            vectors: BitArray::new(count as usize),
+        }));
+        Ok(count)
+    }
+
+    /// Disables MSI-X and falls back to legacy interrupt delivery
+    pub fn disable_msix(&self) -> Result<(), Error> {
+        if let Some(msix) = self.msix.borrow_mut().take() {
+            let ctrl: u16 = self.read_config(msix.cap + MSIX_CTRL)?;
+            self.write_config(msix.cap + MSIX_CTRL, ctrl & !MSIX_CTRL_ENABLE)?;
         }
+        Ok(())
     }
 
-    pub fn wait_for_irq(&self) -> Result<(), Error> {
-        self.rgate
-            .receive(None)
-            .and_then(|msg| self.rgate.ack_msg(msg))
+    /// Returns true if MSI-X has been enabled via [`enable_msix`](Self::enable_msix)
+    pub fn msix_enabled(&self) -> bool {
+        self.msix.borrow().is_some()
+    }
+
+    /// Allocates the next free MSI-X vector, configures it and unmasks it.
+    ///
+    /// Interrupts for this vector are reported as `Interrupt::MsiX(vector)`.
+    pub fn alloc_msix_vector(&self) -> Result<MsiXVector, Error> {
+        let mut msix = self.msix.borrow_mut();
+        let msix = msix.as_mut().ok_or_else(|| Error::new(Code::InvState))?;
+
+        let vec = msix.vectors.first_clear();
+        if vec >= msix.vectors.size() {
+            return Err(Error::new(Code::NoSpace));
+        }
+
+        let entry = msix.table + vec as GlobOff * MSIX_ENTRY_SIZE;
+        self.write_reg(entry + MSIX_ENTRY_ADDR_LO, msix.addr as u32)?;
+        self.write_reg(entry + MSIX_ENTRY_ADDR_HI, (msix.addr >> 32) as u32)?;
+        self.write_reg(
+            entry + MSIX_ENTRY_DATA,
+            Interrupt::msix_data(vec as MsiXVector),
+        )?;
+        self.write_reg(entry + MSIX_ENTRY_CTRL, 0u32)?;
+
+        msix.vectors.set(vec);
+        Ok(vec as MsiXVector)
+    }
+
+    /// Masks and releases the given MSI-X vector
+    pub fn free_msix_vector(&self, vec: MsiXVector) -> Result<(), Error> {
+        let mut msix = self.msix.borrow_mut();
+        let msix = msix.as_mut().ok_or_else(|| Error::new(Code::InvState))?;
+        if vec as usize >= msix.vectors.size() || !msix.vectors.is_set(vec as usize) {
+            return Err(Error::new(Code::InvArgs));
+        }
+
+        let entry = msix.table + vec as GlobOff * MSIX_ENTRY_SIZE;
+        self.write_reg(entry + MSIX_ENTRY_CTRL, MSIX_ENTRY_CTRL_MASKED)?;
+        msix.vectors.clear(vec as usize);
+        Ok(())
+    }
+
+    /// Masks or unmasks the given (allocated) MSI-X vector
+    pub fn mask_msix_vector(&self, vec: MsiXVector, masked: bool) -> Result<(), Error> {
+        let msix = self.msix.borrow();
+        let msix = msix.as_ref().ok_or_else(|| Error::new(Code::InvState))?;
+        if vec as usize >= msix.vectors.size() || !msix.vectors.is_set(vec as usize) {
+            return Err(Error::new(Code::InvArgs));
+        }
+
+        let entry = msix.table + vec as GlobOff * MSIX_ENTRY_SIZE;
+        let ctrl = if masked { MSIX_ENTRY_CTRL_MASKED } else { 0 };
+        self.write_reg(entry + MSIX_ENTRY_CTRL, ctrl)
+    }
+
+    /// Returns an iterator over the capability list of the given function
+    pub fn capabilities(&self, bdf: BDF) -> Result<CapIterator<'_>, Error> {
+        CapIterator::new(self, bdf)
+    }
+
+    /// Returns the offset of the capability with given id of the given function, if present
+    pub fn find_capability(&self, bdf: BDF, id: CapId) -> Result<Option<GlobOff>, Error> {
+        Ok(self
+            .capabilities(bdf)?
+            .find(|c| c.id() == id)
+            .map(|c| c.offset()))
+    }
+
+    /// Scans the configuration space of the device tile and returns all present functions
+    pub fn enumerate(&self) -> Result<Vec<BDF>, Error> {
+        let mut res = Vec::new();
+        for dev in 0..MAX_DEVICES {
+            let bdf = BDF::new(0, dev, 0);
+            if !self.is_present(bdf)? {
+                continue;
+            }
+            res.push(bdf);
+
+            let header: u8 = self.read_config_of(bdf, Reg::HeaderType.into())?;
+            if (header & HEADER_MULTI_FUNC) != 0 {
+                for func in 1..MAX_FUNCTIONS {
+                    let bdf = BDF::new(0, dev, func);
+                    if self.is_present(bdf)? {
+                        res.push(bdf);
+                    }
+                }
+            }
+        }
+        Ok(res)
+    }
+
+    fn is_present(&self, bdf: BDF) -> Result<bool, Error> {
+        let vendor: u16 = self.read_config_of(bdf, Reg::VendorId.into())?;
+        Ok(vendor != 0xFFFF && vendor != 0)
     }
 
     pub fn read_reg<T>(&self, off: GlobOff) -> Result<T, Error> {
-        self.mem.read_obj(REG_ADDR + off)
+        self.mem.read_obj(self.reg_addr + off)
     }
 
     pub fn write_reg<T>(&self, off: GlobOff, val: T) -> Result<(), Error> {
-        self.mem.write_obj(&val, REG_ADDR + off)
+        self.mem.write_obj(&val, self.reg_addr + off)
     }
 
+    /// Reads from the configuration space of the first function (0.0.0)
     pub fn read_config<T>(&self, off: GlobOff) -> Result<T, Error> {
-        self.mem.read_obj(REG_ADDR + PCI_CFG_ADDR + off)
+        self.read_config_of(BDF::default(), off)
     }
 
+    /// Writes to the configuration space of the first function (0.0.0)
     pub fn write_config<T>(&self, off: GlobOff, val: T) -> Result<(), Error> {
-        self.mem.write_obj(&val, REG_ADDR + PCI_CFG_ADDR + off)
+        self.write_config_of(BDF::default(), off, val)
+    }
+
+    /// Reads from the configuration space of the given function
+    pub fn read_config_of<T>(&self, bdf: BDF, off: GlobOff) -> Result<T, Error> {
+        self.mem.read_obj(self.config_addr(bdf, off)?)
     }
 
+    /// Writes to the configuration space of the given function
+    pub fn write_config_of<T>(&self, bdf: BDF, off: GlobOff, val: T) -> Result<(), Error> {
+        self.mem.write_obj(&val, self.config_addr(bdf, off)?)
+    }
+
+    fn config_addr(&self, bdf: BDF, off: GlobOff) -> Result<GlobOff, Error> {
+        if bdf.bus != 0
+            || bdf.device >= MAX_DEVICES
+            || bdf.function >= MAX_FUNCTIONS
+            || off >= CFG_FUNC_SIZE
+        {
+            return Err(Error::new(Code::InvArgs));
+        }
+
+        let func_off = ((bdf.device as GlobOff) << 15) | ((bdf.function as GlobOff) << 12);
+        Ok(self.cfg_addr + func_off + off)
+    }
+
+    /// Returns the information about the first function (0.0.0)
     pub fn get_info(&self) -> Result<Info, Error> {
+        self.get_info_of(BDF::default())
+    }
+
+    /// Returns the information about the given function
+    pub fn get_info_of(&self, bdf: BDF) -> Result<Info, Error> {
         Ok(Info {
-            // TODO this is hardcoded atm, because the device tile contains exactly one PCI device
-            id: BDF::new(0, 0, 0),
-            vendor: self.read_config(Reg::VendorId.into())?,
-            device: self.read_config(Reg::DeviceId.into())?,
-            ty: self.read_config(Reg::HeaderType.into())?,
-            revision: self.read_config(Reg::RevisionId.into())?,
-            prog_if: self.read_config(Reg::ClassCode.into())?,
+            id: bdf,
+            vendor: self.read_config_of(bdf, Reg::VendorId.into())?,
+            device: self.read_config_of(bdf, Reg::DeviceId.into())?,
+            ty: self.read_config_of(bdf, Reg::HeaderType.into())?,
+            revision: self.read_config_of(bdf, Reg::RevisionId.into())?,
+            prog_if: self.read_config_of(bdf, Reg::ClassCode.into())?,
             class: Class::new(
-                self.read_config(Reg::BaseClassCode.into())?,
-                self.read_config(Reg::SubClassCode.into())?,
+                self.read_config_of(bdf, Reg::BaseClassCode.into())?,
+                self.read_config_of(bdf, Reg::SubClassCode.into())?,
             ),
-            irq: 0,
+            irq: self.read_config_of(bdf, Type0::InterruptLine.into())?,
             bars: [
-                self.read_bar(0)?,
-                self.read_bar(1)?,
-                self.read_bar(2)?,
-                self.read_bar(3)?,
-                self.read_bar(4)?,
-                self.read_bar(5)?,
+                self.read_bar(bdf, 0)?,
+                self.read_bar(bdf, 1)?,
+                self.read_bar(bdf, 2)?,
+                self.read_bar(bdf, 3)?,
+                self.read_bar(bdf, 4)?,
+                self.read_bar(bdf, 5)?,
             ],
         })
     }
 
-    fn read_bar(&self, idx: usize) -> Result<Bar, Error> {
-        let val: u32 = self.read_config(Type0::BaseAddr0 as GlobOff + idx as GlobOff * 4)?;
-        self.write_config(
-            Type0::BaseAddr0 as GlobOff + idx as GlobOff * 4,
-            0xFFFF_FFF0 | (val & 0x1),
-        )?;
+    fn read_bar(&self, bdf: BDF, idx: usize) -> Result<Bar, Error> {
+        let off = Type0::BaseAddr0 as GlobOff + idx as GlobOff * 4;
+        let val: u32 = self.read_config_of(bdf, off)?;
+        self.write_config_of(bdf, off, 0xFFFF_FFF0 | (val & 0x1))?;
 
         let mut flags = BarFlags::empty();
-        let mut size: u32 = self.read_config(Type0::BaseAddr0 as GlobOff + idx as GlobOff * 4)?;
+        let mut size: u32 = self.read_config_of(bdf, off)?;
         let size = if size == 0 || size == 0xFFFF_FFFF {
             0
         }
@@ -371,7 +756,7 @@ impl Device {
             }
             size & (size - 1)
         };
-        self.write_config(0x10 + idx as GlobOff * 4, val)?;
+        self.write_config_of(bdf, off, val)?;
 
         Ok(Bar {
             ty: BarType::from((val & 0x1) as u8),
@@ -20,24 +20,27 @@ use m3::cap::{SelSpace, Selector};
 use m3::cell::{Cell, RefCell};
 use m3::client::resmng;
 use m3::col::{String, ToString, Treap, Vec};
-use m3::com::{GateCap, MemCap, RecvGate, SGateArgs, SendCap};
+use m3::com::{GateCap, MemCap, MemGate, RecvGate, SGateArgs, SendCap, SendGate};
 use m3::errors::{Code, Error};
 use m3::format;
+use m3::io::log::LogFormat;
 use m3::io::LogFlags;
-use m3::kif::{self, CapRngDesc, CapType, Perm};
+use m3::kif::{self, service::Version, CapRngDesc, CapType, Perm};
 use m3::log;
 use m3::mem::{GlobOff, MsgBuf};
 use m3::println;
 use m3::quota::{Id as QuotaId, Quota};
 use m3::rc::Rc;
-use m3::serialize::M3Deserializer;
+use m3::serialize::{M3Deserializer, M3Serializer, VecSink};
 use m3::syscalls;
 use m3::tcu;
 use m3::tiles::{Activity, KMem, RunningActivity, Tile};
+use m3::time::{TimeDuration, TimeInstant};
 use m3::util::math;
+use m3::watchdog::Diagnostics;
 use m3::{cfg, env};
 
-use crate::config::AppConfig;
+use crate::config::{AppConfig, RestartPolicy, WatchdogAction};
 use crate::requests::Requests;
 use crate::resources::{
     memory::{Allocation, MemPool},
@@ -46,6 +49,7 @@ use crate::resources::{
     Resources,
 };
 use crate::subsys::{ChildStarter, SubsystemBuilder};
+use crate::watchdog::Watchdogs;
 use crate::{events, subsys};
 
 pub type Id = u32;
@@ -53,8 +57,9 @@ pub type Id = u32;
 pub struct ChildMem {
     id: Id,
     pool: Rc<RefCell<MemPool>>,
-    total: GlobOff,
+    total: Cell<GlobOff>,
     quota: Cell<GlobOff>,
+    committed: Cell<GlobOff>,
 }
 
 impl ChildMem {
@@ -62,8 +67,9 @@ impl ChildMem {
         Rc::new(Self {
             id,
             pool,
-            total: quota,
+            total: Cell::new(quota),
             quota: Cell::new(quota),
+            committed: Cell::new(0),
         })
     }
 
@@ -75,6 +81,19 @@ impl ChildMem {
         self.quota.get()
     }
 
+    /// Changes the total quota to `total` bytes, keeping the memory that is currently in use.
+    ///
+    /// Fails with [`Code::NoSpace`] if more than `total` bytes are currently in use.
+    pub fn set_total(&self, total: GlobOff) -> Result<(), Error> {
+        let used = self.total.get() - self.quota.get();
+        if total < used {
+            return Err(Error::new(Code::NoSpace));
+        }
+        self.total.replace(total);
+        self.quota.replace(total - used);
+        Ok(())
+    }
+
     pub(crate) fn have_quota(&self, size: GlobOff) -> bool {
         self.quota.get() >= size
     }
@@ -87,11 +106,42 @@ impl ChildMem {
     pub(crate) fn free_mem(&self, size: GlobOff) {
         self.quota.replace(self.quota.get() + size);
     }
+
+    /// Returns the amount of memory that has been promised to the child (e.g., by mapping memory
+    /// that is allocated lazily on first touch)
+    pub fn committed(&self) -> GlobOff {
+        self.committed.get()
+    }
+
+    /// Promises `size` bytes of memory to the child without allocating it yet.
+    ///
+    /// If `overcommit` is false, the commitment fails with [`Code::NoSpace`] if the child would
+    /// get more memory promised than its total quota. Otherwise, arbitrary amounts of memory can
+    /// be promised and the allocation might fail later when the memory is actually used.
+    pub fn commit(&self, size: GlobOff, overcommit: bool) -> Result<(), Error> {
+        if !overcommit && self.committed.get() + size > self.total.get() {
+            return Err(Error::new(Code::NoSpace));
+        }
+        self.committed.replace(self.committed.get() + size);
+        Ok(())
+    }
+
+    /// Takes back the promise of `size` bytes of memory that has been given via
+    /// [`ChildMem::commit`].
+    pub fn uncommit(&self, size: GlobOff) {
+        assert!(self.committed.get() >= size);
+        self.committed.replace(self.committed.get() - size);
+    }
 }
 
 impl fmt::Debug for ChildMem {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
-        write!(f, "ChildMem[quota={}]", self.quota.get(),)
+        write!(
+            f,
+            "ChildMem[quota={}, committed={}]",
+            self.quota.get(),
+            self.committed.get()
+        )
     }
 }
 
@@ -102,6 +152,7 @@ pub struct ChildResources {
     sessions: Vec<(usize, Session)>,
     mem: Vec<(Option<Selector>, Allocation)>,
     mods: Vec<MemCap>,
+    physmems: Vec<MemCap>,
     tiles: Vec<(TileUsage, usize, Selector)>,
     scaps: Vec<SendCap>,
 }
@@ -158,6 +209,22 @@ pub trait Child {
     fn res_mut(&mut self) -> &mut ChildResources;
     fn kmem(&self) -> Rc<KMem>;
 
+    /// Changes the EP and time quota of this child, which takes effect at the next start
+    fn set_tile_quota(
+        &mut self,
+        _eps: Option<usize>,
+        _time: Option<TimeDuration>,
+    ) -> Result<(), Error> {
+        Err(Error::new(Code::NotSup))
+    }
+
+    /// Creates a new instance of this child that can be started again after this one exited
+    ///
+    /// Returns `None` if the child cannot be restarted.
+    fn respawn(&self) -> Option<Box<OwnChild>> {
+        None
+    }
+
     fn delegate(&self, src: Selector, dst: Selector) -> Result<(), Error> {
         let crd = CapRngDesc::new(CapType::Object, src, 1);
         syscalls::exchange(self.activity_sel(), crd, dst, false)
@@ -179,15 +246,17 @@ pub trait Child {
         srv_sel: Selector,
         sgate_sel: Selector,
         name: String,
+        version: Version,
         sessions: u32,
     ) -> Result<(), Error> {
         log!(
             LogFlags::ResMngServ,
-            "{}: reg_serv(srv_sel={}, sgate_sel={}, name={}, sessions={})",
+            "{}: reg_serv(srv_sel={}, sgate_sel={}, name={}, version={}, sessions={})",
             self.name(),
             srv_sel,
             sgate_sel,
             name,
+            version,
             sessions,
         );
 
@@ -206,9 +275,16 @@ pub trait Child {
             our_srv,
             our_sgate,
             sdesc.name().global().to_string(),
+            version,
             sessions,
             true,
         )?;
+        if let Some(alias) = sdesc.alias() {
+            res.services_mut()
+                .get_mut_by_id(id)
+                .unwrap()
+                .set_alias(alias.clone());
+        }
 
         sdesc.mark_used();
         self.res_mut().services.push((id, srv_sel));
@@ -243,15 +319,18 @@ pub trait Child {
         dst_sel: Selector,
         name: &str,
     ) -> Result<(), Error> {
-        let (sname, sarg) = {
-            log!(
-                LogFlags::ResMngServ,
-                "{}: open_sess(dst_sel={}, name={})",
-                self.name(),
-                dst_sel,
-                name
-            );
+        log!(
+            LogFlags::ResMngServ,
+            "{}: open_sess(dst_sel={}, name={})",
+            self.name(),
+            dst_sel,
+            name
+        );
+
+        // the name might request a specific version ("name@version")
+        let (name, version) = Version::split_name(name)?;
 
+        let (sname, sarg) = {
             let cfg = self.cfg();
             let (_idx, sdesc) = cfg
                 .get_session(name)
@@ -262,7 +341,7 @@ pub trait Child {
             (sdesc.name().global().clone(), sdesc.arg().clone())
         };
 
-        let serv = res.services_mut().get_mut_by_name(&sname)?;
+        let serv = res.services_mut().get_mut_by_version(&sname, version)?;
         let serv_sel = serv.sel();
         let sess = Session::new_async(id, dst_sel, serv, &sarg)?;
 
@@ -513,6 +592,30 @@ pub trait Child {
         self.delegate(our_sel, sel)
     }
 
+    fn use_physmem(&mut self, res: &Resources, name: &str, sel: Selector) -> Result<(), Error> {
+        log!(
+            LogFlags::ResMngMem,
+            "{}: use_physmem(name={}, sel={})",
+            self.name(),
+            name,
+            sel,
+        );
+
+        let cfg = self.cfg();
+        let pdesc = cfg
+            .get_physmem(name)
+            .ok_or_else(|| Error::new(Code::InvArgs))?;
+        let slice = res
+            .memory()
+            .find_mem(pdesc.addr(), pdesc.size(), pdesc.perm())
+            .map_err(|_| Error::new(Code::NotFound))?;
+
+        let mcap = slice.derive()?;
+        let our_sel = mcap.sel();
+        self.res_mut().physmems.push(mcap);
+        self.delegate(our_sel, sel)
+    }
+
     fn get_serial(&mut self, sel: Selector) -> Result<(), Error> {
         log!(
             LogFlags::ResMngSerial,
@@ -530,6 +633,7 @@ pub trait Child {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn alloc_tile(
         &mut self,
         res: &mut Resources,
@@ -537,19 +641,23 @@ pub trait Child {
         sel: Selector,
         desc: kif::TileDesc,
         init: bool,
+        id: Option<tcu::TileId>,
+        near: Option<tcu::TileId>,
     ) -> Result<(tcu::TileId, kif::TileDesc), Error> {
         log!(
             LogFlags::ResMngTiles,
-            "{}: alloc_tile(sel={}, desc={:?}, init={})",
+            "{}: alloc_tile(sel={}, desc={:?}, init={}, id={:?}, near={:?})",
             self.name(),
             sel,
             desc,
-            init
+            init,
+            id,
+            near
         );
 
         let cfg = self.cfg();
         let idx = cfg.get_tile_idx(desc)?;
-        let mut tile_usage = res.tiles().find(desc)?;
+        let mut tile_usage = res.tiles().find_with(desc, id, near)?;
 
         if init {
             tile_usage.state_mut().load_mux(
@@ -621,6 +729,43 @@ pub trait Child {
         Ok(())
     }
 
+    fn ctrl_tile(
+        &mut self,
+        res: &Resources,
+        id: tcu::TileId,
+        online: bool,
+        desc: kif::TileDesc,
+    ) -> Result<(), Error> {
+        log!(
+            LogFlags::ResMngTiles,
+            "{}: ctrl_tile(id={}, online={}, desc={:?})",
+            self.name(),
+            id,
+            online,
+            desc
+        );
+
+        if !self.cfg().can_ctrl_tiles() {
+            return Err(Error::new(Code::NoPerm));
+        }
+
+        res.tiles().set_online(id, online, desc)
+    }
+
+    fn watch_tiles(&mut self, res: &mut Resources, sel: Selector) -> Result<(), Error> {
+        log!(
+            LogFlags::ResMngTiles,
+            "{}: watch_tiles(sel={})",
+            self.name(),
+            sel
+        );
+
+        let our_sel = self.obtain(sel)?;
+        let sgate = SendGate::new_bind(our_sel)?;
+        res.tiles_mut().add_watcher(self.id(), sgate);
+        Ok(())
+    }
+
     fn remove_pe_by_idx(&mut self, res: &Resources, idx: usize) -> Result<(), Error> {
         let (mut tile_usage, idx, ep_sel) = self.res_mut().tiles.remove(idx);
         log!(
@@ -675,6 +820,8 @@ pub trait Child {
         while !self.res().tiles.is_empty() {
             self.remove_pe_by_idx(res, 0).ok();
         }
+
+        res.tiles_mut().remove_watchers(self.id());
     }
 }
 
@@ -693,6 +840,8 @@ pub struct OwnChild {
     sub: Option<SubsystemBuilder>,
     daemon: bool,
     kmem: Rc<KMem>,
+    restarts: u32,
+    dynamic: bool,
 }
 
 impl OwnChild {
@@ -723,9 +872,23 @@ impl OwnChild {
             daemon,
             activity: None,
             kmem,
+            restarts: 0,
+            dynamic: false,
         }
     }
 
+    /// Returns true if this child stems from the boot configuration and has not been restarted
+    ///
+    /// Failing to start such a child is fatal, whereas other childs are simply dropped.
+    pub fn is_initial(&self) -> bool {
+        !self.dynamic && self.restarts == 0
+    }
+
+    /// Returns the number of times this child has been restarted
+    pub fn restarts(&self) -> u32 {
+        self.restarts
+    }
+
     pub fn set_running(&mut self, act: Box<dyn RunningActivity>) {
         log!(
             LogFlags::Info,
@@ -825,6 +988,50 @@ impl Child for OwnChild {
     fn kmem(&self) -> Rc<KMem> {
         self.kmem.clone()
     }
+
+    fn set_tile_quota(
+        &mut self,
+        eps: Option<usize>,
+        time: Option<TimeDuration>,
+    ) -> Result<(), Error> {
+        // childs without own quotas share them with their domain
+        let base = self
+            ._domain_tile
+            .as_ref()
+            .ok_or_else(|| Error::new(Code::NotSup))?;
+
+        let quota = self.child_tile.tile_obj().quota()?;
+        let eps = eps.unwrap_or(quota.endpoints().total());
+        let time = time.unwrap_or(quota.time().total());
+        let pts = self.cfg.page_tables();
+        // the running activity keeps the old tile object until it exits
+        self.child_tile = base.derive(Some(eps), Some(time), pts)?;
+        Ok(())
+    }
+
+    fn respawn(&self) -> Option<Box<OwnChild>> {
+        // subsystems cannot be restarted, because their resources have been handed out already
+        if self.sub.is_some() || !self.cfg.domains().is_empty() {
+            return None;
+        }
+
+        // the new instance keeps the ID so that the boot module and the quotas are reused
+        let mut child = OwnChild::new(
+            self.id,
+            self.our_tile.clone(),
+            self._domain_tile.clone(),
+            self.child_tile.clone(),
+            self.args.clone(),
+            self.daemon,
+            self.kmem.clone(),
+            self.mem.clone(),
+            self.cfg.clone(),
+            None,
+        );
+        child.restarts = self.restarts + 1;
+        child.dynamic = self.dynamic;
+        Some(Box::new(child))
+    }
 }
 
 impl fmt::Debug for OwnChild {
@@ -986,6 +1193,11 @@ pub struct ChildManager {
     next_id: Id,
     daemons: usize,
     foreigns: usize,
+    // childs that will be started at the given time (restarted or started at runtime)
+    pending: Vec<(TimeInstant, Box<OwnChild>)>,
+    // user memory that childs started at runtime took from the quota of the requester
+    donations: Vec<(Id, Rc<ChildMem>, GlobOff)>,
+    watchdogs: Watchdogs,
 }
 
 impl Default for ChildManager {
@@ -997,6 +1209,9 @@ impl Default for ChildManager {
             next_id: 0,
             daemons: 0,
             foreigns: 0,
+            pending: Vec::new(),
+            donations: Vec::new(),
+            watchdogs: Watchdogs::default(),
         }
     }
 }
@@ -1006,7 +1221,7 @@ impl ChildManager {
         // don't stop if we didn't have a child yet. this is necessary, because we use derive_srv
         // asynchronously and thus switch to a different thread while starting a subsystem. thus, if
         // the subsystem is the first child, we would stop without waiting without this workaround.
-        !self.flags.contains(Flags::STARTING) && self.children() == 0
+        !self.flags.contains(Flags::STARTING) && self.children() == 0 && self.pending.is_empty()
     }
 
     pub fn children(&self) -> usize {
@@ -1021,6 +1236,36 @@ impl ChildManager {
         self.foreigns
     }
 
+    /// Returns the time until the next pending child needs to be started, if any
+    pub fn next_pending(&self) -> Option<TimeDuration> {
+        let now = TimeInstant::now();
+        self.pending
+            .iter()
+            .map(|(at, _)| at.checked_duration_since(now).unwrap_or(TimeDuration::ZERO))
+            .min()
+    }
+
+    /// Moves all pending childs whose start time has come to `delayed` so that they are started
+    ///
+    /// Returns true if at least one child has been moved.
+    #[allow(clippy::vec_box)]
+    pub fn take_pending(&mut self, delayed: &mut Vec<Box<OwnChild>>) -> bool {
+        let now = TimeInstant::now();
+        let mut found = false;
+        let mut idx = 0;
+        while idx < self.pending.len() {
+            if self.pending[idx].0 <= now {
+                let (_, child) = self.pending.remove(idx);
+                delayed.push(child);
+                found = true;
+            }
+            else {
+                idx += 1;
+            }
+        }
+        found
+    }
+
     pub fn next_id(&mut self) -> Id {
         self.next_id
     }
@@ -1039,12 +1284,35 @@ impl ChildManager {
             self.foreigns += 1;
             self.next_id += 1;
         }
+        else if let Some(wd) = child.cfg().watchdog() {
+            self.watchdogs.add(child.id(), wd);
+        }
         self.ids.push(child.id());
         self.childs.insert(child.id(), child);
         // now that we have a child, we want to stop as soon as we've no childs anymore
         self.flags.remove(Flags::STARTING);
     }
 
+    /// Drops the given child that could not be started and releases its resources
+    pub fn discard(&mut self, res: &Resources, child: Box<OwnChild>) {
+        res.tiles().remove_user(child.our_tile());
+        self.return_donation(child.id());
+    }
+
+    fn return_donation(&mut self, id: Id) {
+        if let Some(idx) = self.donations.iter().position(|(cid, ..)| *cid == id) {
+            let (_, mem, size) = self.donations.remove(idx);
+            mem.free_mem(size);
+        }
+    }
+
+    fn child_by_name(&self, name: &str) -> Option<Id> {
+        self.ids
+            .iter()
+            .find(|&&id| self.child_by_id(id).unwrap().name() == name)
+            .copied()
+    }
+
     pub fn child_by_id(&self, id: Id) -> Option<&dyn Child> {
         self.childs.get(&id).map(|c| c.as_ref())
     }
@@ -1098,18 +1366,33 @@ impl ChildManager {
         let upcall: kif::upcalls::ActivityWait = de.pop().unwrap();
 
         self.kill_child_async(reqs, res, upcall.act_sel, upcall.exitcode);
+        self.child_removed_async(reqs, res);
+    }
+
+    fn child_removed_async(&mut self, reqs: &Requests, res: &mut Resources) {
+        self.check_shutdown_async(reqs, res);
 
         // wait for the next
+        if !self.should_stop() {
+            self.start_waiting(1);
+        }
+    }
+
+    fn check_shutdown_async(&mut self, reqs: &Requests, res: &mut Resources) {
         let no_wait_childs = self.daemons() + self.foreigns();
-        if !self.flags.contains(Flags::SHUTDOWN) && self.children() == no_wait_childs {
+        let pending_apps = self.pending.iter().any(|(_, c)| !c.daemon());
+        if !self.flags.contains(Flags::SHUTDOWN)
+            && self.children() == no_wait_childs
+            && !pending_apps
+        {
             self.flags.set(Flags::SHUTDOWN, true);
+            // daemons that are waiting for their start are not needed anymore
+            while let Some((_, child)) = self.pending.pop() {
+                self.discard(res, child);
+            }
             self.kill_daemons_async(reqs, res);
             res.services_mut().shutdown_async();
         }
-
-        if !self.should_stop() {
-            self.start_waiting(1);
-        }
     }
 
     fn upcall_derive_srv(&mut self, msg: &'static tcu::Message, de: &mut M3Deserializer<'_>) {
@@ -1135,7 +1418,256 @@ impl ChildManager {
                     exitcode
                 );
             }
+
+            // the restarted child keeps the memory it got from its requester
+            if self.flags.contains(Flags::SHUTDOWN)
+                || !self.schedule_restart(res, child.as_ref(), exitcode)
+            {
+                self.return_donation(id);
+            }
+        }
+    }
+
+    fn schedule_restart(&mut self, res: &Resources, child: &dyn Child, exitcode: Code) -> bool {
+        let cfg = child.cfg();
+        let restart = match cfg.restart() {
+            RestartPolicy::Never => false,
+            RestartPolicy::Always => true,
+            RestartPolicy::OnFailure => exitcode != Code::Success,
+        };
+        if !restart {
+            return false;
+        }
+
+        let nchild = match child.respawn() {
+            Some(nchild) => nchild,
+            None => return false,
+        };
+
+        if let Some(max) = cfg.max_restarts() {
+            if nchild.restarts() > max {
+                println!(
+                    "Child '{}' has been restarted {} times; giving up",
+                    child.name(),
+                    max
+                );
+                return false;
+            }
+        }
+
+        // the old instance gave up the tile and the send gates during its removal
+        res.tiles().add_user(nchild.our_tile());
+        cfg.reset_sgates();
+
+        log!(
+            LogFlags::Info,
+            "Restarting '{}' in {:?} (restart {})",
+            child.name(),
+            cfg.backoff(),
+            nchild.restarts()
+        );
+        self.pending
+            .push((TimeInstant::now() + cfg.backoff(), nchild));
+        true
+    }
+
+    pub fn start_app(&mut self, res: &Resources, id: Id, xml: &str) -> Result<(), Error> {
+        let (our_tile, base_tile, kmem, mem) = {
+            let child = self.child_by_id(id).unwrap();
+            log!(
+                LogFlags::ResMngChild,
+                "{}: start_app(cfg={})",
+                child.name(),
+                xml
+            );
+
+            if !child.cfg().can_ctrl_apps() {
+                return Err(Error::new(Code::NoPerm));
+            }
+            (
+                child.our_tile().clone(),
+                child.child_tile().clone(),
+                child.kmem(),
+                child.mem().clone(),
+            )
+        };
+
+        let cfg = AppConfig::parse(xml).map_err(|e| {
+            log!(LogFlags::Error, "Invalid app config: {}", e);
+            Error::new(e.code())
+        })?;
+        let cfg = Rc::new(cfg);
+        // subsystems can only be defined in the boot configuration
+        if !cfg.domains().is_empty() {
+            return Err(Error::new(Code::NotSup));
+        }
+        if self.child_by_name(cfg.name()).is_some()
+            || self.pending.iter().any(|(_, c)| c.name() == cfg.name())
+        {
+            return Err(Error::new(Code::Exists));
+        }
+
+        // split off the requested quotas from the quotas of the requester
+        let (domain_tile, child_tile) =
+            if cfg.eps().is_some() || cfg.time().is_some() || cfg.page_tables().is_some() {
+                let tile = base_tile.derive(cfg.eps(), cfg.time(), cfg.page_tables())?;
+                (Some(base_tile), tile)
+            }
+            else {
+                (None, base_tile)
+            };
+
+        let kmem = match cfg.kernel_mem() {
+            Some(bytes) => kmem.derive(bytes)?,
+            None => kmem,
+        };
+
+        let nid = self.alloc_id();
+        let mem = match cfg.user_mem() {
+            Some(umem) => {
+                let umem = umem as GlobOff;
+                if !mem.have_quota(umem) {
+                    return Err(Error::new(Code::NoSpace));
+                }
+                mem.alloc_mem(umem);
+                self.donations.push((nid, mem.clone(), umem));
+                ChildMem::new(nid, mem.pool().clone(), umem)
+            },
+            None => mem,
+        };
+
+        res.tiles().add_user(&our_tile);
+        let mut child = OwnChild::new(
+            nid,
+            our_tile,
+            domain_tile,
+            child_tile,
+            cfg.args().clone(),
+            cfg.daemon(),
+            kmem,
+            mem,
+            cfg,
+            None,
+        );
+        child.dynamic = true;
+        log!(LogFlags::ResMngChild, "Created {:?}", child);
+
+        self.pending.push((TimeInstant::now(), Box::new(child)));
+        Ok(())
+    }
+
+    pub fn stop_app_async(
+        &mut self,
+        reqs: &Requests,
+        res: &mut Resources,
+        id: Id,
+        name: &str,
+    ) -> Result<(), Error> {
+        {
+            let child = self.child_by_id(id).unwrap();
+            log!(
+                LogFlags::ResMngChild,
+                "{}: stop_app(name={})",
+                child.name(),
+                name
+            );
+
+            if !child.cfg().can_ctrl_apps() {
+                return Err(Error::new(Code::NoPerm));
+            }
+            // we cannot reply to the requester if it stopped itself
+            if child.name() == name {
+                return Err(Error::new(Code::InvArgs));
+            }
+        }
+
+        // childs that are waiting for their start can simply be dropped
+        if let Some(idx) = self.pending.iter().position(|(_, c)| c.name() == name) {
+            let (_, child) = self.pending.remove(idx);
+            self.discard(res, child);
+            return Ok(());
+        }
+
+        let cid = self
+            .child_by_name(name)
+            .ok_or_else(|| Error::new(Code::NotFound))?;
+        // childs of other resource managers are stopped by them
+        if self.child_by_id(cid).unwrap().foreign() {
+            return Err(Error::new(Code::NoPerm));
+        }
+
+        self.remove_rec_async(reqs, res, cid);
+        self.return_donation(cid);
+
+        self.check_shutdown_async(reqs, res);
+        if !self.should_stop() {
+            self.start_waiting(1);
+        }
+        Ok(())
+    }
+
+    pub fn set_quota(
+        &mut self,
+        id: Id,
+        name: &str,
+        umem: Option<GlobOff>,
+        eps: Option<usize>,
+        time: Option<TimeDuration>,
+    ) -> Result<(), Error> {
+        {
+            let child = self.child_by_id(id).unwrap();
+            log!(
+                LogFlags::ResMngChild,
+                "{}: set_quota(name={}, umem={:?}, eps={:?}, time={:?})",
+                child.name(),
+                name,
+                umem,
+                eps,
+                time
+            );
+
+            if !child.cfg().can_ctrl_apps() {
+                return Err(Error::new(Code::NoPerm));
+            }
+        }
+
+        let cid = self
+            .child_by_name(name)
+            .ok_or_else(|| Error::new(Code::NotFound))?;
+        let child = self.childs.get_mut(&cid).unwrap();
+        if child.foreign() {
+            return Err(Error::new(Code::NoPerm));
+        }
+
+        if let Some(umem) = umem {
+            // changing a shared quota would affect the other childs as well
+            if child.cfg().user_mem().is_none() {
+                return Err(Error::new(Code::NotSup));
+            }
+
+            // for childs started at runtime, the difference is taken from/given to the requester
+            match self.donations.iter_mut().find(|(did, ..)| *did == cid) {
+                Some((_, donor, size)) => {
+                    if umem > *size && !donor.have_quota(umem - *size) {
+                        return Err(Error::new(Code::NoSpace));
+                    }
+                    child.mem().set_total(umem)?;
+                    if umem > *size {
+                        donor.alloc_mem(umem - *size);
+                    }
+                    else {
+                        donor.free_mem(*size - umem);
+                    }
+                    *size = umem;
+                },
+                None => child.mem().set_total(umem)?,
+            }
+        }
+
+        if eps.is_some() || time.is_some() {
+            child.set_tile_quota(eps, time)?;
         }
+        Ok(())
     }
 
     fn kill_daemons_async(&mut self, reqs: &Requests, res: &mut Resources) {
@@ -1155,6 +1687,7 @@ impl ChildManager {
 
             if can_kill {
                 self.remove_rec_async(reqs, res, id).unwrap();
+                self.return_donation(id);
             }
         }
     }
@@ -1226,6 +1759,7 @@ impl ChildManager {
                         time: *tile_quota.time(),
                         pts: *tile_quota.page_tables(),
                         tile: Activity::own().tile_id(),
+                        cpu_time: Activity::own().cpu_time().unwrap_or_default(),
                     }));
                 }
                 idx -= 1;
@@ -1249,7 +1783,7 @@ impl ChildManager {
                     daemon: act.daemon(),
                     umem: Quota::new(
                         parent_num as QuotaId + act.mem().id as QuotaId,
-                        act.mem().total as usize,
+                        act.mem().total.get() as usize,
                         act.mem().quota.get() as usize,
                     ),
                     kmem: kmem_quota,
@@ -1257,6 +1791,8 @@ impl ChildManager {
                     time: *tile_quota.time(),
                     pts: *tile_quota.page_tables(),
                     tile: act.child_tile().tile_id(),
+                    // not all tiles support CPU time accounting
+                    cpu_time: syscalls::activity_time(act.activity_sel()).unwrap_or_default(),
                 }))
             }
         }
@@ -1266,6 +1802,194 @@ impl ChildManager {
         }
     }
 
+    pub fn get_usage(&self, id: Id, mem: Selector, size: usize) -> Result<usize, Error> {
+        let child = self.child_by_id(id).unwrap();
+        log!(
+            LogFlags::ResMngChild,
+            "{}: get_usage(mem={}, size={})",
+            child.name(),
+            mem,
+            size
+        );
+
+        if !child.cfg().can_get_info() {
+            return Err(Error::new(Code::NoPerm));
+        }
+
+        let usages = self.usage()?;
+        let mut words = Vec::new();
+        let mut ser = M3Serializer::new(VecSink::new(&mut words));
+        ser.push(&usages);
+
+        // if the memory is too small, the client retries with the size we return
+        if ser.size() <= size {
+            let mgate = MemGate::new_owned_bind(child.obtain(mem)?)?;
+            mgate.write(ser.words(), 0)?;
+        }
+        Ok(ser.size())
+    }
+
+    /// Returns the quotas and the current resource usage of all childs
+    pub fn usage(&self) -> Result<Vec<resmng::ChildUsage>, Error> {
+        self.ids
+            .iter()
+            .map(|cid| Self::child_usage(self.child_by_id(*cid).unwrap()))
+            .collect()
+    }
+
+    fn child_usage(child: &dyn Child) -> Result<resmng::ChildUsage, Error> {
+        let mem = child.mem();
+        let tile_quota = child.child_tile().tile_obj().quota()?;
+        Ok(resmng::ChildUsage {
+            id: child.activity_id(),
+            name: child.name().to_string(),
+            daemon: child.daemon(),
+            foreign: child.foreign(),
+            tile: child.child_tile().tile_id(),
+            umem: Quota::new(
+                mem.id as QuotaId,
+                mem.total.get() as usize,
+                mem.quota.get() as usize,
+            ),
+            umem_committed: mem.committed() as usize,
+            umem_allocated: child
+                .res()
+                .memories()
+                .iter()
+                .map(|(_, alloc)| alloc.size() as usize)
+                .sum(),
+            kmem: child.kmem().quota()?,
+            eps: *tile_quota.endpoints(),
+            time: *tile_quota.time(),
+            pts: *tile_quota.page_tables(),
+            // not all tiles support CPU time accounting
+            cpu_time: syscalls::activity_time(child.activity_sel()).unwrap_or_default(),
+            tiles: child.res().tiles().len(),
+            services: child.res().services().len(),
+            sessions: child.res().sessions().len(),
+        })
+    }
+
+    pub fn set_log(
+        &mut self,
+        id: Id,
+        name: &str,
+        flags: LogFlags,
+        format: LogFormat,
+    ) -> Result<(), Error> {
+        {
+            let child = self.child_by_id(id).unwrap();
+            log!(
+                LogFlags::ResMngChild,
+                "{}: set_log(name={}, flags={:?}, format={:?})",
+                child.name(),
+                name,
+                flags,
+                format
+            );
+
+            if !child.cfg().can_ctrl_log() {
+                return Err(Error::new(Code::NoPerm));
+            }
+        }
+
+        let mut found = false;
+        for cid in &self.ids {
+            let child = self.child_by_id(*cid).unwrap();
+            if child.name() == name {
+                env::set_log(child.activity_sel(), flags, format)?;
+                found = true;
+            }
+        }
+
+        match (found, Activity::own().resmng()) {
+            (true, _) => Ok(()),
+            // the activity might be somewhere else in the hierarchy
+            (false, Some(presmng)) => presmng.set_log(name, flags, format),
+            (false, None) => Err(Error::new(Code::NotFound)),
+        }
+    }
+
+    pub fn heartbeat(&mut self, id: Id, diag: Diagnostics) -> Result<(), Error> {
+        let child = self.childs.get(&id).unwrap();
+        log!(
+            LogFlags::ResMngChild,
+            "{}: heartbeat(diag={:?})",
+            child.name(),
+            diag
+        );
+
+        if self.watchdogs.heartbeat(id, diag)? {
+            println!("Watchdog: child '{}' is alive again", child.name());
+        }
+        Ok(())
+    }
+
+    /// Returns the time until the next heartbeat of a child is due, if any
+    pub fn next_watchdog_timeout(&self) -> Option<TimeDuration> {
+        self.watchdogs.next_timeout()
+    }
+
+    /// Checks whether children have missed their heartbeat and performs the configured action
+    pub fn check_watchdogs_async(&mut self, reqs: &Requests, res: &mut Resources) {
+        for id in self.watchdogs.expired() {
+            let (sel, action) = {
+                let child = self.child_by_id(id).unwrap();
+                let wd = self.watchdogs.get(id).unwrap();
+                let diag = wd.diag();
+
+                // the requests that wait for the services of the child
+                let sids = child
+                    .res()
+                    .services()
+                    .iter()
+                    .map(|(sid, _)| *sid)
+                    .collect::<Vec<_>>();
+                let mut queued = 0;
+                for sid in sids {
+                    if let Ok(s) = res.services_mut().get_mut_by_id(sid) {
+                        queued += s.queue().pending();
+                    }
+                }
+
+                match wd.since_last() {
+                    Some(d) => println!(
+                        "Watchdog: child '{}' missed its heartbeat (last one {:?} ago)",
+                        child.name(),
+                        d
+                    ),
+                    None => println!(
+                        "Watchdog: child '{}' did not send a heartbeat yet",
+                        child.name()
+                    ),
+                }
+                println!(
+                    "Watchdog:   cpu-time={:?}, threads={} (ready={}, blocked={}, sleeping={})",
+                    syscalls::activity_time(child.activity_sel()).unwrap_or_default(),
+                    diag.threads,
+                    diag.ready,
+                    diag.blocked,
+                    diag.sleeping,
+                );
+                println!(
+                    "Watchdog:   queued requests: {} in child, {} for its services",
+                    diag.queued, queued
+                );
+
+                (child.activity_sel(), wd.action())
+            };
+
+            if action == WatchdogAction::Kill {
+                println!(
+                    "Watchdog: killing child '{}'",
+                    self.child_by_id(id).unwrap().name()
+                );
+                self.kill_child_async(reqs, res, sel, Code::Timeout);
+                self.child_removed_async(reqs, res);
+            }
+        }
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub fn add_child(
         &mut self,
@@ -1402,6 +2126,7 @@ impl ChildManager {
             res.tiles().remove_user(child.our_tile());
 
             self.ids.retain(|&i| i != id);
+            self.watchdogs.remove(id);
             if child.daemon() {
                 self.daemons -= 1;
             }
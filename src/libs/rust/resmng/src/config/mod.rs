@@ -20,8 +20,9 @@ use core::fmt;
 use m3::cell::Cell;
 use m3::cfg;
 use m3::col::{String, Vec};
-use m3::errors::{Code, Error};
+use m3::errors::{Code, Error, VerboseError};
 use m3::kif;
+use m3::mem::{GlobAddr, GlobOff};
 use m3::rc::Rc;
 use m3::tcu::Label;
 use m3::time::TimeDuration;
@@ -83,6 +84,84 @@ impl ModDesc {
     }
 }
 
+/// A physical memory range that is made accessible to the child
+///
+/// The range has to be part of the memory that is available to the resource manager, which is
+/// checked by the validator. The child obtains a memory capability for the range by its name.
+#[derive(Debug, Eq, PartialEq)]
+pub struct PhysMemDesc {
+    name: String,
+    addr: GlobAddr,
+    size: GlobOff,
+    perm: kif::Perm,
+}
+
+impl PhysMemDesc {
+    pub fn new(name: String, addr: GlobAddr, size: GlobOff, perm: kif::Perm) -> Self {
+        Self {
+            name,
+            addr,
+            size,
+            perm,
+        }
+    }
+
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn addr(&self) -> GlobAddr {
+        self.addr
+    }
+
+    pub fn size(&self) -> GlobOff {
+        self.size
+    }
+
+    pub fn perm(&self) -> kif::Perm {
+        self.perm
+    }
+
+    /// Returns true if this range overlaps with the given one
+    pub fn overlaps(&self, other: &PhysMemDesc) -> bool {
+        self.addr < other.addr + other.size && other.addr < self.addr + self.size
+    }
+}
+
+/// A device that is assigned to the child
+///
+/// The device is looked up in the device tree by its `compatible` string. If multiple nodes are
+/// compatible, `index` selects one of them. The register regions and interrupts of the device are
+/// passed to the child via the environment (see `m3::devinfo`).
+#[derive(Default, Debug, Eq, PartialEq)]
+pub struct DeviceDesc {
+    name: String,
+    compatible: String,
+    index: usize,
+}
+
+impl DeviceDesc {
+    pub fn new(name: String, compatible: String, index: usize) -> Self {
+        Self {
+            name,
+            compatible,
+            index,
+        }
+    }
+
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn compatible(&self) -> &String {
+        &self.compatible
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
 #[derive(Default, Debug, Eq, PartialEq)]
 pub struct MountDesc {
     fs: String,
@@ -103,9 +182,66 @@ impl MountDesc {
     }
 }
 
+#[derive(Default, Debug, Eq, PartialEq)]
+pub struct EnvDesc {
+    name: String,
+    value: String,
+}
+
+impl EnvDesc {
+    pub fn new(name: String, value: String) -> Self {
+        Self { name, value }
+    }
+
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn value(&self) -> &String {
+        &self.value
+    }
+}
+
+/// The action of the watchdog if a heartbeat is missed
+#[derive(Copy, Clone, Default, Debug, Eq, PartialEq)]
+pub enum WatchdogAction {
+    /// Only log diagnostics about the child
+    #[default]
+    Log,
+    /// Log diagnostics and kill the child afterwards
+    Kill,
+}
+
+#[derive(Default, Debug, Eq, PartialEq)]
+pub struct WatchdogDesc {
+    timeout: TimeDuration,
+    action: WatchdogAction,
+}
+
+impl WatchdogDesc {
+    pub fn new(timeout: TimeDuration, action: WatchdogAction) -> Self {
+        Self { timeout, action }
+    }
+
+    /// Returns the maximum time between two heartbeats of the child
+    pub fn timeout(&self) -> TimeDuration {
+        self.timeout
+    }
+
+    /// Returns the interval in which the child should send heartbeats
+    pub fn interval(&self) -> TimeDuration {
+        self.timeout / 2
+    }
+
+    pub fn action(&self) -> WatchdogAction {
+        self.action
+    }
+}
+
 #[derive(Default, Debug, Eq, PartialEq)]
 pub struct ServiceDesc {
     name: DualName,
+    alias: Option<String>,
     used: Cell<bool>,
 }
 
@@ -113,14 +249,28 @@ impl ServiceDesc {
     pub fn new(name: DualName) -> Self {
         Self {
             name,
+            alias: None,
             used: Cell::new(false),
         }
     }
 
+    /// Sets an alternative global name under which the service can be found as well
+    ///
+    /// Multiple services can share the same alias (e.g., an interface identifier like "fs"), in
+    /// which case clients are connected to the one with the highest compatible version.
+    pub fn with_alias(mut self, alias: String) -> Self {
+        self.alias = Some(alias);
+        self
+    }
+
     pub fn name(&self) -> &DualName {
         &self.name
     }
 
+    pub fn alias(&self) -> Option<&String> {
+        self.alias.as_ref()
+    }
+
     pub fn is_used(&self) -> bool {
         self.used.get()
     }
@@ -283,6 +433,7 @@ impl TileType {
                 "perf" => desc.attr().contains(kif::TileAttr::PERF),
                 "effi" => desc.attr().contains(kif::TileAttr::EFFI),
                 "kecacc" => desc.attr().contains(kif::TileAttr::KECACC),
+                "vec" => desc.attr().contains(kif::TileAttr::VEC),
                 "serial" => desc.attr().contains(kif::TileAttr::SERIAL),
                 "imem" => desc.attr().contains(kif::TileAttr::IMEM),
 
@@ -293,6 +444,7 @@ impl TileType {
                 "idedev" => desc.isa() == kif::TileISA::IDEDev,
                 "nicdev" => desc.isa() == kif::TileISA::NICDev,
                 "serdev" => desc.isa() == kif::TileISA::SerialDev,
+                "usbdev" => desc.isa() == kif::TileISA::USBDev,
                 _ => false,
             };
             if !matches {
@@ -361,6 +513,18 @@ pub struct SerialDesc {
     used: Cell<bool>,
 }
 
+/// Determines whether an app is restarted after it exited
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum RestartPolicy {
+    /// The app is never restarted
+    #[default]
+    Never,
+    /// The app is restarted whenever it exits
+    Always,
+    /// The app is only restarted if it exited with an error
+    OnFailure,
+}
+
 #[derive(Default, Debug)]
 pub struct Domain {
     pub(crate) pseudo: bool,
@@ -421,15 +585,29 @@ pub struct AppConfig {
     pub(crate) cfg_range: (usize, usize),
     pub(crate) daemon: bool,
     pub(crate) getinfo: bool,
+    pub(crate) tilectrl: bool,
+    pub(crate) appctrl: bool,
+    pub(crate) logctl: bool,
+    pub(crate) overcommit: Option<bool>,
     pub(crate) eps: Option<usize>,
     pub(crate) user_mem: Option<usize>,
     pub(crate) kern_mem: Option<usize>,
     pub(crate) time: Option<TimeDuration>,
     pub(crate) pts: Option<usize>,
+    pub(crate) gang: Option<String>,
+    pub(crate) restart: RestartPolicy,
+    pub(crate) max_restarts: Option<u32>,
+    pub(crate) backoff: Option<TimeDuration>,
+    pub(crate) coredump: Option<String>,
+    pub(crate) gdbstub: Option<String>,
+    pub(crate) watchdog: Option<WatchdogDesc>,
     pub(crate) serial: Option<SerialDesc>,
     pub(crate) domains: Vec<Domain>,
     pub(crate) mounts: Vec<MountDesc>,
+    pub(crate) env: Vec<EnvDesc>,
     pub(crate) mods: Vec<ModDesc>,
+    pub(crate) physmems: Vec<PhysMemDesc>,
+    pub(crate) devices: Vec<DeviceDesc>,
     pub(crate) services: Vec<ServiceDesc>,
     pub(crate) sesscrt: Vec<SessCrtDesc>,
     pub(crate) sessions: Vec<SessionDesc>,
@@ -440,7 +618,11 @@ pub struct AppConfig {
 }
 
 impl AppConfig {
-    pub fn parse(xml: &str) -> Result<Self, Error> {
+    /// Parses the given XML string into an `AppConfig`
+    ///
+    /// On errors, the returned error contains a message that describes the problem and its position
+    /// (`<line>:<column>`) within `xml`.
+    pub fn parse(xml: &str) -> Result<Self, VerboseError> {
         parser::parse(xml)
     }
 
@@ -465,6 +647,30 @@ impl AppConfig {
         self.getinfo
     }
 
+    /// Returns whether this app may take tiles offline and bring them online again.
+    pub fn can_ctrl_tiles(&self) -> bool {
+        self.tilectrl
+    }
+
+    /// Returns whether this app may start and stop other apps and change their quotas.
+    pub fn can_ctrl_apps(&self) -> bool {
+        self.appctrl
+    }
+
+    /// Returns whether this app may change the log flags and format of other running activities.
+    pub fn can_ctrl_log(&self) -> bool {
+        self.logctl
+    }
+
+    /// Returns whether the pager may map more memory for this app than its user-memory quota.
+    ///
+    /// If enabled (the default), mappings are backed with memory on first touch only and can
+    /// therefore be larger than the quota. Otherwise, the pager ensures that all mappings fit into
+    /// the quota, although the memory is still allocated on first touch.
+    pub fn overcommit(&self) -> bool {
+        self.overcommit.unwrap_or(true)
+    }
+
     pub fn can_get_serial(&self) -> bool {
         self.serial.is_some()
     }
@@ -489,6 +695,43 @@ impl AppConfig {
         self.pts
     }
 
+    /// Returns the name of the gang this app belongs to, if any
+    ///
+    /// All apps with the same gang name are started together.
+    pub fn gang(&self) -> Option<&str> {
+        self.gang.as_deref()
+    }
+
+    /// Returns the policy that determines whether the app is restarted after it exited
+    pub fn restart(&self) -> RestartPolicy {
+        self.restart
+    }
+
+    /// Returns the maximum number of restarts (unlimited if `None`)
+    pub fn max_restarts(&self) -> Option<u32> {
+        self.max_restarts
+    }
+
+    /// Returns the time to wait before the app is restarted
+    pub fn backoff(&self) -> TimeDuration {
+        self.backoff.unwrap_or(TimeDuration::ZERO)
+    }
+
+    /// Returns the path of the core file to write if this app crashes, if any
+    pub fn coredump(&self) -> Option<&str> {
+        self.coredump.as_deref()
+    }
+
+    /// Returns the connection to gdb (`tcp:<port>` or a path) if this app should be debugged
+    pub fn gdbstub(&self) -> Option<&str> {
+        self.gdbstub.as_deref()
+    }
+
+    /// Returns the watchdog settings if this app should send heartbeats
+    pub fn watchdog(&self) -> Option<&WatchdogDesc> {
+        self.watchdog.as_ref()
+    }
+
     pub fn name(&self) -> &str {
         &self.name
     }
@@ -505,10 +748,22 @@ impl AppConfig {
         &self.mounts
     }
 
+    pub fn env(&self) -> &Vec<EnvDesc> {
+        &self.env
+    }
+
     pub fn mods(&self) -> &Vec<ModDesc> {
         &self.mods
     }
 
+    pub fn physmems(&self) -> &Vec<PhysMemDesc> {
+        &self.physmems
+    }
+
+    pub fn devices(&self) -> &Vec<DeviceDesc> {
+        &self.devices
+    }
+
     pub fn services(&self) -> &Vec<ServiceDesc> {
         &self.services
     }
@@ -553,6 +808,10 @@ impl AppConfig {
         self.sems.iter().find(|s| s.name().local() == lname)
     }
 
+    pub fn get_physmem(&self, name: &str) -> Option<&PhysMemDesc> {
+        self.physmems.iter().find(|p| p.name() == name)
+    }
+
     pub fn get_service(&self, lname: &str) -> Option<&ServiceDesc> {
         self.services.iter().find(|s| s.name().local() == lname)
     }
@@ -602,6 +861,13 @@ impl AppConfig {
         }
     }
 
+    /// Marks all send gates as unused again so that a restarted app can use them
+    pub(crate) fn reset_sgates(&self) {
+        for s in &self.sgates {
+            s.used.replace(false);
+        }
+    }
+
     pub fn alloc_tile(&self, idx: usize) {
         self.tiles[idx].alloc();
     }
@@ -632,6 +898,36 @@ impl AppConfig {
         if let Some(n) = self.pts {
             writeln!(f, "{:0w$}PageTables[{}],", "", n, w = layer + 2)?;
         }
+        if let Some(g) = &self.gang {
+            writeln!(f, "{:0w$}Gang[{}],", "", g, w = layer + 2)?;
+        }
+        if self.restart != RestartPolicy::Never {
+            writeln!(
+                f,
+                "{:0w$}Restart[{:?}, max={:?}, backoff={:?}],",
+                "",
+                self.restart,
+                self.max_restarts,
+                self.backoff(),
+                w = layer + 2
+            )?;
+        }
+        if let Some(p) = &self.coredump {
+            writeln!(f, "{:0w$}CoreDump[{}],", "", p, w = layer + 2)?;
+        }
+        if let Some(g) = &self.gdbstub {
+            writeln!(f, "{:0w$}GdbStub[{}],", "", g, w = layer + 2)?;
+        }
+        if let Some(w) = &self.watchdog {
+            writeln!(
+                f,
+                "{:0w$}Watchdog[timeout={:?}, action={:?}],",
+                "",
+                w.timeout,
+                w.action,
+                w = layer + 2
+            )?;
+        }
         if let Some(umem) = self.user_mem {
             writeln!(
                 f,
@@ -650,6 +946,9 @@ impl AppConfig {
                 w = layer + 2
             )?;
         }
+        if let Some(oc) = self.overcommit {
+            writeln!(f, "{:0w$}Overcommit[{}],", "", oc, w = layer + 2)?;
+        }
         for m in &self.mods {
             writeln!(
                 f,
@@ -660,11 +959,44 @@ impl AppConfig {
                 w = layer + 2
             )?;
         }
+        for p in &self.physmems {
+            writeln!(
+                f,
+                "{:0w$}PhysMem[name='{}', addr={}, size={:#x}, perm={:?}],",
+                "",
+                p.name,
+                p.addr,
+                p.size,
+                p.perm,
+                w = layer + 2
+            )?;
+        }
+        for d in &self.devices {
+            writeln!(
+                f,
+                "{:0w$}Device[name='{}', compatible='{}', index={}],",
+                "",
+                d.name,
+                d.compatible,
+                d.index,
+                w = layer + 2
+            )?;
+        }
         for s in &self.sems {
             writeln!(f, "{:0w$}Semaphore[{:?}],", "", s.name, w = layer + 2)?;
         }
         for s in &self.services {
-            writeln!(f, "{:0w$}Service[{:?}],", "", s.name, w = layer + 2)?;
+            match &s.alias {
+                Some(alias) => writeln!(
+                    f,
+                    "{:0w$}Service[{:?}, alias={}],",
+                    "",
+                    s.name,
+                    alias,
+                    w = layer + 2
+                )?,
+                None => writeln!(f, "{:0w$}Service[{:?}],", "", s.name, w = layer + 2)?,
+            }
         }
         for s in &self.sesscrt {
             writeln!(
@@ -719,6 +1051,16 @@ impl AppConfig {
                 w = layer + 2
             )?;
         }
+        for e in &self.env {
+            writeln!(
+                f,
+                "{:0w$}Env[name='{}', value='{}'],",
+                "",
+                e.name,
+                e.value,
+                w = layer + 2
+            )?;
+        }
         for tile in &self.tiles {
             writeln!(
                 f,
@@ -736,6 +1078,15 @@ impl AppConfig {
         if self.can_get_info() {
             writeln!(f, "{:0w$}GetInfo[],", "", w = layer + 2)?;
         }
+        if self.can_ctrl_tiles() {
+            writeln!(f, "{:0w$}TileCtrl[],", "", w = layer + 2)?;
+        }
+        if self.can_ctrl_apps() {
+            writeln!(f, "{:0w$}AppCtrl[],", "", w = layer + 2)?;
+        }
+        if self.can_ctrl_log() {
+            writeln!(f, "{:0w$}LogCtrl[],", "", w = layer + 2)?;
+        }
         for d in &self.domains {
             let mut sub_layer = layer;
             if !d.pseudo {
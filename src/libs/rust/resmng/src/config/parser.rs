@@ -14,39 +14,138 @@
  */
 
 use m3::col::{String, ToString, Vec};
-use m3::errors::{Code, Error};
+use m3::errors::{Code, Error, VerboseError};
 use m3::format;
 use m3::kif;
+use m3::mem::{GlobAddr, GlobOff};
 use m3::rc::Rc;
 use m3::tcu::{Label, UNLIM_CREDITS};
 use m3::util::parse;
 
 use crate::config;
 
+/// The maximum nesting depth of `<app>` and `<dom>` elements
+const MAX_DEPTH: usize = 32;
+/// The maximum number of elements in a config
+const MAX_ELEMENTS: usize = 1024;
+/// The maximum number of attributes of a single element
+const MAX_ATTRS: usize = 32;
+/// The maximum length of element and attribute names
+const MAX_NAME_LEN: usize = 64;
+/// The maximum length of attribute values
+const MAX_VALUE_LEN: usize = 1024;
+
 struct ConfigParser {
     chars: Vec<char>,
+    offsets: Vec<usize>,
+    len: usize,
     pos: usize,
+    // the name and position of the current element
+    tag: String,
+    tag_pos: usize,
+    // the position of the current attribute
+    arg_pos: usize,
+    attrs: usize,
+    elements: usize,
+    depth: usize,
 }
 
 impl ConfigParser {
     fn new(xml: &str) -> Self {
         ConfigParser {
             chars: xml.chars().collect(),
+            offsets: xml.char_indices().map(|(i, _)| i).collect(),
+            len: xml.len(),
             pos: 0,
+            tag: String::new(),
+            tag_pos: 0,
+            arg_pos: 0,
+            attrs: 0,
+            elements: 0,
+            depth: 0,
+        }
+    }
+
+    /// Returns the current position as a byte offset into the XML string
+    fn byte_pos(&self) -> usize {
+        self.offsets.get(self.pos).copied().unwrap_or(self.len)
+    }
+
+    fn error_at(&self, pos: usize, code: Code, msg: &str) -> VerboseError {
+        let pos = pos.min(self.chars.len());
+        let mut line = 1;
+        let mut col = 1;
+        for c in &self.chars[0..pos] {
+            if *c == '\n' {
+                line += 1;
+                col = 1;
+            }
+            else {
+                col += 1;
+            }
         }
+        VerboseError::new(code, format!("{}:{}: {}", line, col, msg))
+    }
+
+    fn error(&self, msg: &str) -> VerboseError {
+        self.error_at(self.pos, Code::InvArgs, msg)
+    }
+
+    fn tag_error(&self, msg: &str) -> VerboseError {
+        self.error_at(
+            self.tag_pos,
+            Code::InvArgs,
+            &format!("<{}>: {}", self.tag, msg),
+        )
+    }
+
+    fn unknown_arg(&self, name: &str) -> VerboseError {
+        self.error_at(
+            self.arg_pos,
+            Code::InvArgs,
+            &format!("<{}>: unknown attribute '{}'", self.tag, name),
+        )
     }
 
-    fn get(&mut self) -> Result<char, Error> {
+    fn invalid_value(&self, name: &str, code: Code) -> VerboseError {
+        self.error_at(
+            self.arg_pos,
+            code,
+            &format!("<{}>: invalid value for attribute '{}'", self.tag, name),
+        )
+    }
+
+    fn value<T>(&self, name: &str, res: Result<T, Error>) -> Result<T, VerboseError> {
+        res.map_err(|e| self.invalid_value(name, e.code()))
+    }
+
+    fn enter(&mut self) -> Result<(), VerboseError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(self.tag_error("elements nested too deeply"));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn leave(&mut self) {
+        self.depth -= 1;
+    }
+
+    fn get(&mut self) -> Result<char, VerboseError> {
         if self.pos < self.chars.len() {
             let idx = self.pos;
             self.pos += 1;
             Ok(self.chars[idx])
         }
         else {
-            Err(Error::new(Code::InvArgs))
+            Err(self.error("unexpected end of config"))
         }
     }
 
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
     fn put(&mut self) -> Option<char> {
         if self.pos > 0 {
             self.pos -= 1;
@@ -57,17 +156,17 @@ impl ConfigParser {
         }
     }
 
-    fn finish(&mut self) -> Result<(), Error> {
+    fn finish(&mut self) -> Result<(), VerboseError> {
         while self.pos < self.chars.len() {
             if !self.chars[self.pos].is_whitespace() {
-                return Err(Error::new(Code::InvArgs));
+                return Err(self.error("unexpected content after the end of the config"));
             }
             self.pos += 1;
         }
         Ok(())
     }
 
-    fn get_no_ws(&mut self) -> Result<char, Error> {
+    fn get_no_ws(&mut self) -> Result<char, VerboseError> {
         loop {
             let c = self.get()?;
             if c.is_whitespace() {
@@ -77,22 +176,27 @@ impl ConfigParser {
         }
     }
 
-    fn consume(&mut self, c: char) -> Result<(), Error> {
+    fn consume(&mut self, c: char) -> Result<(), VerboseError> {
         let nc = self.get_no_ws()?;
         if nc != c {
-            Err(Error::new(Code::InvArgs))
+            Err(self.error_at(
+                self.pos - 1,
+                Code::InvArgs,
+                &format!("expected '{}', found '{}'", c, nc),
+            ))
         }
         else {
             Ok(())
         }
     }
 
-    fn parse_ident(&mut self, delim: char) -> Result<String, Error> {
+    fn parse_ident(&mut self, delim: char) -> Result<String, VerboseError> {
         let mut name_buf = String::new();
         let first = self.get_no_ws()?;
         name_buf.push(first);
 
-        while let Ok(c) = self.get() {
+        while let Some(c) = self.peek() {
+            self.pos += 1;
             if c == delim {
                 self.put();
                 break;
@@ -101,35 +205,64 @@ impl ConfigParser {
                 break;
             }
 
+            if name_buf.len() >= MAX_NAME_LEN {
+                return Err(self.error("name too long"));
+            }
             name_buf.push(c);
         }
         Ok(name_buf)
     }
 
-    fn parse_arg(&mut self) -> Result<Option<(String, String)>, Error> {
+    fn parse_arg(&mut self) -> Result<Option<(String, String)>, VerboseError> {
         let first = self.get_no_ws()?;
         self.put();
         if first == '>' || first == '/' {
             return Ok(None);
         }
 
+        self.arg_pos = self.pos;
+        self.attrs += 1;
+        if self.attrs > MAX_ATTRS {
+            return Err(self.error("too many attributes"));
+        }
+
         let name = self.parse_ident('=')?;
         self.consume('=')?;
         self.consume('"')?;
 
         let mut val_buf = String::new();
-        while let Ok(c) = self.get() {
-            if c == '"' {
-                break;
+        loop {
+            match self.peek() {
+                Some('"') => {
+                    self.pos += 1;
+                    break;
+                },
+                Some(c) => {
+                    if val_buf.len() >= MAX_VALUE_LEN {
+                        return Err(self.error_at(
+                            self.arg_pos,
+                            Code::InvArgs,
+                            &format!("value of attribute '{}' too long", name),
+                        ));
+                    }
+                    val_buf.push(c);
+                    self.pos += 1;
+                },
+                None => {
+                    return Err(self.error_at(
+                        self.arg_pos,
+                        Code::InvArgs,
+                        &format!("unterminated value of attribute '{}'", name),
+                    ))
+                },
             }
-
-            val_buf.push(c);
         }
         Ok(Some((name, val_buf)))
     }
 
-    fn parse_tag_name(&mut self) -> Result<Option<String>, Error> {
+    fn parse_tag_name(&mut self) -> Result<Option<String>, VerboseError> {
         self.consume('<')?;
+        let tag_pos = self.pos - 1;
 
         let mut name_buf = String::new();
         let first = self.get_no_ws()?;
@@ -143,35 +276,52 @@ impl ConfigParser {
         }
         name_buf.push(first);
 
-        while let Ok(c) = self.get() {
+        while let Some(c) = self.peek() {
             if c.is_whitespace() {
+                self.pos += 1;
                 break;
             }
             if c == '>' || c == '/' {
-                self.put();
                 break;
             }
 
+            if name_buf.len() >= MAX_NAME_LEN {
+                return Err(self.error("element name too long"));
+            }
             name_buf.push(c);
+            self.pos += 1;
         }
 
+        self.elements += 1;
+        if self.elements > MAX_ELEMENTS {
+            return Err(self.error_at(tag_pos, Code::InvArgs, "too many elements"));
+        }
+
+        self.tag = name_buf.clone();
+        self.tag_pos = tag_pos;
+        self.attrs = 0;
         Ok(Some(name_buf))
     }
 }
 
-pub(crate) fn parse(xml: &str) -> Result<config::AppConfig, Error> {
+pub(crate) fn parse(xml: &str) -> Result<config::AppConfig, VerboseError> {
     let mut p = ConfigParser::new(xml);
 
     let app = match p.parse_tag_name()? {
         Some(tag) if tag == "app" => parse_app(&mut p, 0),
-        _ => Err(Error::new(Code::InvArgs)),
+        Some(tag) => Err(p.error_at(
+            p.tag_pos,
+            Code::InvArgs,
+            &format!("expected <app>, found <{}>", tag),
+        )),
+        None => Err(p.error("expected <app>")),
     }?;
 
     p.finish()?;
     Ok(app)
 }
 
-fn parse_app(p: &mut ConfigParser, start: usize) -> Result<config::AppConfig, Error> {
+fn parse_app(p: &mut ConfigParser, start: usize) -> Result<config::AppConfig, VerboseError> {
     let mut app = config::AppConfig::default();
 
     loop {
@@ -186,21 +336,32 @@ fn parse_app(p: &mut ConfigParser, start: usize) -> Result<config::AppConfig, Er
                         app.args.push(a.to_string());
                     }
                 },
-                "usermem" => app.user_mem = Some(parse::size(&v)?),
-                "kernmem" => app.kern_mem = Some(parse::size(&v)?),
-                "time" => app.time = Some(parse::time(&v)?),
-                "pagetables" => app.pts = Some(parse::int(&v)? as usize),
-                "eps" => app.eps = Some(parse::int(&v)? as usize),
-                "daemon" => app.daemon = parse::bool(&v)?,
-                "getinfo" => app.getinfo = parse::bool(&v)?,
-                _ => return Err(Error::new(Code::InvArgs)),
+                "usermem" => app.user_mem = Some(p.value(&n, parse::size(&v))?),
+                "kernmem" => app.kern_mem = Some(p.value(&n, parse::size(&v))?),
+                "time" => app.time = Some(p.value(&n, parse::time(&v))?),
+                "pagetables" => app.pts = Some(p.value(&n, parse::int(&v))? as usize),
+                "eps" => app.eps = Some(p.value(&n, parse::int(&v))? as usize),
+                "gang" => app.gang = Some(v),
+                "coredump" => app.coredump = Some(v),
+                "gdbstub" => app.gdbstub = Some(v),
+                "daemon" => app.daemon = p.value(&n, parse::bool(&v))?,
+                "restart" => app.restart = p.value(&n, parse_restart(&v))?,
+                "max-restarts" => app.max_restarts = Some(p.value(&n, parse::int(&v))? as u32),
+                "backoff" => app.backoff = Some(p.value(&n, parse::time(&v))?),
+                "getinfo" => app.getinfo = p.value(&n, parse::bool(&v))?,
+                "tilectrl" => app.tilectrl = p.value(&n, parse::bool(&v))?,
+                "appctrl" => app.appctrl = p.value(&n, parse::bool(&v))?,
+                "logctl" => app.logctl = p.value(&n, parse::bool(&v))?,
+                "overcommit" => app.overcommit = Some(p.value(&n, parse::bool(&v))?),
+                _ => return Err(p.unknown_arg(&n)),
             },
         }
     }
 
     if app.name.is_empty() || app.args.is_empty() {
-        return Err(Error::new(Code::InvArgs));
+        return Err(p.tag_error("missing attribute 'args'"));
     }
+    p.enter()?;
 
     // put all apps that belong to the same domain as `app` into a pseudo domain
     let mut pseudo_dom = config::Domain {
@@ -214,41 +375,47 @@ fn parse_app(p: &mut ConfigParser, start: usize) -> Result<config::AppConfig, Er
         p.consume('>')?;
     }
     else if nc == '>' {
-        let mut app_start = p.pos;
+        let mut app_start = p.byte_pos();
         while let Some(tag) = p.parse_tag_name()? {
             match tag.as_ref() {
                 "app" => pseudo_dom.apps.push(Rc::new(parse_app(p, app_start)?)),
                 "dom" => app.domains.push(parse_domain(p)?),
                 "mount" => app.mounts.push(parse_mount(p)?),
+                "env" => app.env.push(parse_env(p)?),
                 "sess" => app.sessions.push(parse_session(p)?),
                 "sesscrt" => app.sesscrt.push(parse_sesscrt(p)?),
                 "serv" => app.services.push(parse_service(p)?),
                 "mod" => app.mods.push(parse_mod(p)?),
+                "physmem" => app.physmems.push(parse_physmem(p)?),
+                "dev" => app.devices.push(parse_device(p)?),
                 "tiles" => app.tiles.push(parse_tile(p)?),
                 "rgate" => app.rgates.push(parse_rgate(p)?),
                 "sgate" => app.sgates.push(parse_sgate(p)?),
                 "sem" => app.sems.push(parse_sem(p)?),
                 "serial" => app.serial = Some(config::SerialDesc::default()),
-                _ => return Err(Error::new(Code::InvArgs)),
+                "watchdog" => app.watchdog = Some(parse_watchdog(p)?),
+                _ => return Err(p.tag_error("unknown element")),
             }
 
             if tag != "dom" && tag != "app" {
                 p.consume('/')?;
                 p.consume('>')?;
             }
-            app_start = p.pos;
+            app_start = p.byte_pos();
         }
         parse_close_tag(p, "app")?;
     }
     else {
-        return Err(Error::new(Code::InvArgs));
+        p.put();
+        return Err(p.error("expected '>' or '/>'"));
     }
+    p.leave();
 
     if !pseudo_dom.apps.is_empty() {
         app.domains.insert(0, pseudo_dom);
     }
 
-    app.cfg_range = (start, p.pos);
+    app.cfg_range = (start, p.byte_pos());
     // don't collect session creators for root
     if start != 0 {
         let mut crts = Vec::new();
@@ -265,6 +432,15 @@ fn parse_app(p: &mut ConfigParser, start: usize) -> Result<config::AppConfig, Er
     Ok(app)
 }
 
+fn parse_restart(s: &str) -> Result<config::RestartPolicy, Error> {
+    match s {
+        "never" => Ok(config::RestartPolicy::Never),
+        "always" => Ok(config::RestartPolicy::Always),
+        "on-failure" => Ok(config::RestartPolicy::OnFailure),
+        _ => Err(Error::new(Code::InvArgs)),
+    }
+}
+
 fn hosts_service(app: &config::AppConfig, name: &str) -> bool {
     for d in app.domains() {
         for a in d.apps() {
@@ -289,7 +465,12 @@ fn collect_sess_crts(app: &config::AppConfig, crts: &mut Vec<config::SessCrtDesc
     }
 }
 
-fn parse_dual_name(dual: &mut config::DualName, n: String, v: String) -> Result<(), Error> {
+fn parse_dual_name(
+    p: &ConfigParser,
+    dual: &mut config::DualName,
+    n: String,
+    v: String,
+) -> Result<(), VerboseError> {
     match n.as_ref() {
         "name" => {
             dual.local = v.clone();
@@ -297,12 +478,12 @@ fn parse_dual_name(dual: &mut config::DualName, n: String, v: String) -> Result<
         },
         "lname" => dual.local = v,
         "gname" => dual.global = v,
-        _ => return Err(Error::new(Code::InvArgs)),
+        _ => return Err(p.unknown_arg(&n)),
     }
     Ok(())
 }
 
-fn parse_domain(p: &mut ConfigParser) -> Result<config::Domain, Error> {
+fn parse_domain(p: &mut ConfigParser) -> Result<config::Domain, VerboseError> {
     let mut dom = config::Domain::default();
 
     loop {
@@ -311,10 +492,10 @@ fn parse_domain(p: &mut ConfigParser) -> Result<config::Domain, Error> {
             Some((n, v)) => match n.as_ref() {
                 "tile" => dom.tile = config::TileType(v),
                 "mux" => dom.mux = Some(v),
-                "muxmem" => dom.mux_mem = Some(parse::size(&v)?),
+                "muxmem" => dom.mux_mem = Some(p.value(&n, parse::size(&v))?),
                 "initrd" => dom.initrd = Some(v),
                 "dtb" => dom.dtb = Some(v),
-                _ => return Err(Error::new(Code::InvArgs)),
+                _ => return Err(p.unknown_arg(&n)),
             },
         }
     }
@@ -324,22 +505,74 @@ fn parse_domain(p: &mut ConfigParser) -> Result<config::Domain, Error> {
     }
 
     p.consume('>')?;
+    p.enter()?;
 
-    let mut app_start = p.pos;
+    let mut app_start = p.byte_pos();
     while let Some(tag) = p.parse_tag_name()? {
         if tag != "app" {
-            return Err(Error::new(Code::InvArgs));
+            return Err(p.tag_error("expected <app> within <dom>"));
         }
 
         dom.apps.push(Rc::new(parse_app(p, app_start)?));
-        app_start = p.pos;
+        app_start = p.byte_pos();
     }
 
     parse_close_tag(p, "dom")?;
+    p.leave();
     Ok(dom)
 }
 
-fn parse_mount(p: &mut ConfigParser) -> Result<config::MountDesc, Error> {
+fn parse_env(p: &mut ConfigParser) -> Result<config::EnvDesc, VerboseError> {
+    let mut name = String::new();
+    let mut value = String::new();
+
+    loop {
+        match p.parse_arg()? {
+            None => break,
+            Some((n, v)) => match n.as_ref() {
+                "name" => name = v.clone(),
+                "value" => value = v.clone(),
+                _ => return Err(p.unknown_arg(&n)),
+            },
+        }
+    }
+
+    if name.is_empty() || name.contains('=') {
+        Err(p.tag_error("missing or invalid attribute 'name'"))
+    }
+    else {
+        Ok(config::EnvDesc::new(name, value))
+    }
+}
+
+fn parse_watchdog(p: &mut ConfigParser) -> Result<config::WatchdogDesc, VerboseError> {
+    let mut timeout = None;
+    let mut action = config::WatchdogAction::default();
+
+    loop {
+        match p.parse_arg()? {
+            None => break,
+            Some((n, v)) => match n.as_ref() {
+                "timeout" => timeout = Some(p.value(&n, parse::time(&v))?),
+                "action" => {
+                    action = match v.as_ref() {
+                        "log" => config::WatchdogAction::Log,
+                        "kill" => config::WatchdogAction::Kill,
+                        _ => return Err(p.invalid_value(&n, Code::InvArgs)),
+                    }
+                },
+                _ => return Err(p.unknown_arg(&n)),
+            },
+        }
+    }
+
+    match timeout {
+        Some(t) if !t.is_zero() => Ok(config::WatchdogDesc::new(t, action)),
+        _ => Err(p.tag_error("missing or zero attribute 'timeout'")),
+    }
+}
+
+fn parse_mount(p: &mut ConfigParser) -> Result<config::MountDesc, VerboseError> {
     let mut fs = String::new();
     let mut path = String::new();
 
@@ -356,20 +589,20 @@ fn parse_mount(p: &mut ConfigParser) -> Result<config::MountDesc, Error> {
                         path = format!("{}/", v);
                     }
                 },
-                _ => return Err(Error::new(Code::InvArgs)),
+                _ => return Err(p.unknown_arg(&n)),
             },
         }
     }
 
     if fs.is_empty() || path.is_empty() {
-        Err(Error::new(Code::InvArgs))
+        Err(p.tag_error("missing attribute 'fs' or 'path'"))
     }
     else {
         Ok(config::MountDesc::new(fs, path))
     }
 }
 
-fn parse_mod(p: &mut ConfigParser) -> Result<config::ModDesc, Error> {
+fn parse_mod(p: &mut ConfigParser) -> Result<config::ModDesc, VerboseError> {
     let mut name = config::DualName::default();
     let mut perm = kif::Perm::RWX;
 
@@ -377,40 +610,104 @@ fn parse_mod(p: &mut ConfigParser) -> Result<config::ModDesc, Error> {
         match p.parse_arg()? {
             None => break,
             Some((n, v)) => match n.as_ref() {
-                "name" | "lname" | "gname" => parse_dual_name(&mut name, n, v)?,
-                "perm" => perm = parse::perm(&v)?,
-                _ => return Err(Error::new(Code::InvArgs)),
+                "name" | "lname" | "gname" => parse_dual_name(p, &mut name, n, v)?,
+                "perm" => perm = p.value(&n, parse::perm(&v))?,
+                _ => return Err(p.unknown_arg(&n)),
             },
         }
     }
 
     if name.is_empty() {
-        Err(Error::new(Code::InvArgs))
+        Err(p.tag_error("missing attribute 'name'"))
     }
     else {
         Ok(config::ModDesc::new(name, perm))
     }
 }
 
-fn parse_service(p: &mut ConfigParser) -> Result<config::ServiceDesc, Error> {
+fn parse_physmem(p: &mut ConfigParser) -> Result<config::PhysMemDesc, VerboseError> {
+    let mut name = String::new();
+    let mut addr = None;
+    let mut size = 0;
+    let mut perm = kif::Perm::RW;
+
+    loop {
+        match p.parse_arg()? {
+            None => break,
+            Some((n, v)) => match n.as_ref() {
+                "name" => name = v,
+                "addr" => addr = Some(p.value(&n, parse::addr(&v))?),
+                "size" => size = p.value(&n, parse::size(&v))? as GlobOff,
+                "perm" => perm = p.value(&n, parse::perm(&v))?,
+                _ => return Err(p.unknown_arg(&n)),
+            },
+        }
+    }
+
+    match addr {
+        Some(addr) if !name.is_empty() && size > 0 => Ok(config::PhysMemDesc::new(
+            name,
+            GlobAddr::new(addr),
+            size,
+            perm,
+        )),
+        _ => Err(p.tag_error("missing attribute 'name', 'addr', or 'size'")),
+    }
+}
+
+fn parse_device(p: &mut ConfigParser) -> Result<config::DeviceDesc, VerboseError> {
+    let mut name = String::new();
+    let mut compatible = String::new();
+    let mut index = 0;
+
+    loop {
+        match p.parse_arg()? {
+            None => break,
+            Some((n, v)) => match n.as_ref() {
+                "name" => name = v,
+                "compatible" => compatible = v,
+                "index" => index = p.value(&n, parse::int(&v))? as usize,
+                _ => return Err(p.unknown_arg(&n)),
+            },
+        }
+    }
+
+    // the name is used as part of an environment variable
+    if name.is_empty() || name.contains('=') || compatible.is_empty() {
+        Err(p.tag_error("missing or invalid attribute 'name' or 'compatible'"))
+    }
+    else {
+        Ok(config::DeviceDesc::new(name, compatible, index))
+    }
+}
+
+fn parse_service(p: &mut ConfigParser) -> Result<config::ServiceDesc, VerboseError> {
     let mut name = config::DualName::default();
+    let mut alias = None;
 
     loop {
         match p.parse_arg()? {
             None => break,
-            Some((n, v)) => parse_dual_name(&mut name, n, v)?,
+            Some((n, v)) => match n.as_ref() {
+                "alias" => alias = Some(v),
+                _ => parse_dual_name(p, &mut name, n, v)?,
+            },
         }
     }
 
     if name.is_empty() {
-        Err(Error::new(Code::InvArgs))
+        Err(p.tag_error("missing attribute 'name'"))
     }
     else {
-        Ok(config::ServiceDesc::new(name))
+        let desc = config::ServiceDesc::new(name);
+        Ok(match alias {
+            Some(alias) => desc.with_alias(alias),
+            None => desc,
+        })
     }
 }
 
-fn parse_sesscrt(p: &mut ConfigParser) -> Result<config::SessCrtDesc, Error> {
+fn parse_sesscrt(p: &mut ConfigParser) -> Result<config::SessCrtDesc, VerboseError> {
     let mut name = String::new();
     let mut count = None;
 
@@ -419,21 +716,21 @@ fn parse_sesscrt(p: &mut ConfigParser) -> Result<config::SessCrtDesc, Error> {
             None => break,
             Some((n, v)) => match n.as_ref() {
                 "name" => name = v,
-                "count" => count = Some(parse::int(&v)? as u32),
-                _ => return Err(Error::new(Code::InvArgs)),
+                "count" => count = Some(p.value(&n, parse::int(&v))? as u32),
+                _ => return Err(p.unknown_arg(&n)),
             },
         }
     }
 
     if name.is_empty() {
-        Err(Error::new(Code::InvArgs))
+        Err(p.tag_error("missing attribute 'name'"))
     }
     else {
         Ok(config::SessCrtDesc::new(name, count))
     }
 }
 
-fn parse_session(p: &mut ConfigParser) -> Result<config::SessionDesc, Error> {
+fn parse_session(p: &mut ConfigParser) -> Result<config::SessionDesc, VerboseError> {
     let mut name = config::DualName::default();
     let mut arg = String::new();
     let mut dep = true;
@@ -442,23 +739,23 @@ fn parse_session(p: &mut ConfigParser) -> Result<config::SessionDesc, Error> {
         match p.parse_arg()? {
             None => break,
             Some((n, v)) => match n.as_ref() {
-                "name" | "lname" | "gname" => parse_dual_name(&mut name, n, v)?,
+                "name" | "lname" | "gname" => parse_dual_name(p, &mut name, n, v)?,
                 "args" => arg = v,
-                "dep" => dep = parse::bool(&v)?,
-                _ => return Err(Error::new(Code::InvArgs)),
+                "dep" => dep = p.value(&n, parse::bool(&v))?,
+                _ => return Err(p.unknown_arg(&n)),
             },
         }
     }
 
     if name.is_empty() {
-        Err(Error::new(Code::InvArgs))
+        Err(p.tag_error("missing attribute 'name'"))
     }
     else {
         Ok(config::SessionDesc::new(name, arg, dep))
     }
 }
 
-fn parse_tile(p: &mut ConfigParser) -> Result<config::TileDesc, Error> {
+fn parse_tile(p: &mut ConfigParser) -> Result<config::TileDesc, VerboseError> {
     let mut ty = String::new();
     let mut count = 1;
     let mut optional = false;
@@ -468,22 +765,22 @@ fn parse_tile(p: &mut ConfigParser) -> Result<config::TileDesc, Error> {
             None => break,
             Some((n, v)) => match n.as_ref() {
                 "type" => ty = v,
-                "count" => count = parse::int(&v)? as u32,
-                "optional" => optional = parse::bool(&v)?,
-                _ => return Err(Error::new(Code::InvArgs)),
+                "count" => count = p.value(&n, parse::int(&v))? as u32,
+                "optional" => optional = p.value(&n, parse::bool(&v))?,
+                _ => return Err(p.unknown_arg(&n)),
             },
         }
     }
 
     if ty.is_empty() {
-        Err(Error::new(Code::InvArgs))
+        Err(p.tag_error("missing attribute 'type'"))
     }
     else {
         Ok(config::TileDesc::new(ty, count, optional))
     }
 }
 
-fn parse_rgate(p: &mut ConfigParser) -> Result<config::RGateDesc, Error> {
+fn parse_rgate(p: &mut ConfigParser) -> Result<config::RGateDesc, VerboseError> {
     let mut name = config::DualName::default();
     let mut msg_size = 64;
     let mut slots = 1;
@@ -492,23 +789,23 @@ fn parse_rgate(p: &mut ConfigParser) -> Result<config::RGateDesc, Error> {
         match p.parse_arg()? {
             None => break,
             Some((n, v)) => match n.as_ref() {
-                "name" | "lname" | "gname" => parse_dual_name(&mut name, n, v)?,
-                "msgsize" => msg_size = parse::int(&v)? as usize,
-                "slots" => slots = parse::int(&v)? as usize,
-                _ => return Err(Error::new(Code::InvArgs)),
+                "name" | "lname" | "gname" => parse_dual_name(p, &mut name, n, v)?,
+                "msgsize" => msg_size = p.value(&n, parse::int(&v))? as usize,
+                "slots" => slots = p.value(&n, parse::int(&v))? as usize,
+                _ => return Err(p.unknown_arg(&n)),
             },
         }
     }
 
     if name.is_empty() {
-        Err(Error::new(Code::InvArgs))
+        Err(p.tag_error("missing attribute 'name'"))
     }
     else {
         Ok(config::RGateDesc::new(name, msg_size, slots))
     }
 }
 
-fn parse_sgate(p: &mut ConfigParser) -> Result<config::SGateDesc, Error> {
+fn parse_sgate(p: &mut ConfigParser) -> Result<config::SGateDesc, VerboseError> {
     let mut name = config::DualName::default();
     let mut credits = 1;
     let mut label = 0;
@@ -517,16 +814,16 @@ fn parse_sgate(p: &mut ConfigParser) -> Result<config::SGateDesc, Error> {
         match p.parse_arg()? {
             None => break,
             Some((n, v)) => match n.as_ref() {
-                "name" | "lname" | "gname" => parse_dual_name(&mut name, n, v)?,
-                "credits" => credits = parse::int(&v)? as u32,
-                "label" => label = parse::int(&v)? as Label,
-                _ => return Err(Error::new(Code::InvArgs)),
+                "name" | "lname" | "gname" => parse_dual_name(p, &mut name, n, v)?,
+                "credits" => credits = p.value(&n, parse::int(&v))? as u32,
+                "label" => label = p.value(&n, parse::int(&v))? as Label,
+                _ => return Err(p.unknown_arg(&n)),
             },
         }
     }
 
     if name.is_empty() {
-        Err(Error::new(Code::InvArgs))
+        Err(p.tag_error("missing attribute 'name'"))
     }
     else {
         if credits == 0 {
@@ -536,31 +833,36 @@ fn parse_sgate(p: &mut ConfigParser) -> Result<config::SGateDesc, Error> {
     }
 }
 
-fn parse_sem(p: &mut ConfigParser) -> Result<config::SemDesc, Error> {
+fn parse_sem(p: &mut ConfigParser) -> Result<config::SemDesc, VerboseError> {
     let mut name = config::DualName::default();
 
     loop {
         match p.parse_arg()? {
             None => break,
-            Some((n, v)) => parse_dual_name(&mut name, n, v)?,
+            Some((n, v)) => parse_dual_name(p, &mut name, n, v)?,
         }
     }
 
     if name.is_empty() {
-        Err(Error::new(Code::InvArgs))
+        Err(p.tag_error("missing attribute 'name'"))
     }
     else {
         Ok(config::SemDesc::new(name))
     }
 }
 
-fn parse_close_tag(p: &mut ConfigParser, name: &str) -> Result<(), Error> {
+fn parse_close_tag(p: &mut ConfigParser, name: &str) -> Result<(), VerboseError> {
     p.consume('<')?;
+    let tag_pos = p.pos - 1;
     p.consume('/')?;
 
     let tname = p.parse_ident('>')?;
     if tname != name {
-        Err(Error::new(Code::InvArgs))
+        Err(p.error_at(
+            tag_pos,
+            Code::InvArgs,
+            &format!("expected </{}>, found </{}>", name, tname),
+        ))
     }
     else {
         p.consume('>')
@@ -13,18 +13,42 @@
  * General Public License version 2 for more details.
  */
 
-use m3::col::{BTreeMap, BTreeSet, String};
+use m3::col::{BTreeMap, BTreeSet, String, Vec};
 use m3::errors::{Code, VerboseError};
 use m3::format;
+use m3::kif::Perm;
 
-use crate::config::{AppConfig, TileDesc};
+use crate::config::{AppConfig, DeviceDesc, PhysMemDesc, RestartPolicy, TileDesc};
 use crate::resources::Resources;
 
 pub fn validate(cfg: &AppConfig, res: &Resources) -> Result<(), VerboseError> {
     validate_services(cfg, &BTreeSet::new())?;
     validate_gates(cfg)?;
     validate_tiles(cfg, res)?;
-    validate_mods(cfg, res)
+    validate_restarts(cfg)?;
+    validate_mods(cfg, res)?;
+    validate_physmems(cfg, res)?;
+    validate_devices(cfg, res)
+}
+
+fn validate_restarts(cfg: &AppConfig) -> Result<(), VerboseError> {
+    for d in cfg.domains() {
+        for a in d.apps() {
+            // restarting an entire subsystem is not supported
+            if a.restart() != RestartPolicy::Never && !a.domains().is_empty() {
+                return Err(VerboseError::new(
+                    Code::NotSup,
+                    format!(
+                        "config '{}': resource managers cannot be restarted",
+                        a.name()
+                    ),
+                ));
+            }
+            validate_restarts(a)?;
+        }
+    }
+
+    Ok(())
 }
 
 fn validate_tiles(cfg: &AppConfig, res: &Resources) -> Result<(), VerboseError> {
@@ -67,6 +91,8 @@ fn count_tiles(res: &Resources, tile: &TileDesc) -> u32 {
 
 fn validate_services(cfg: &AppConfig, parent_set: &BTreeSet<String>) -> Result<(), VerboseError> {
     let mut set = BTreeSet::new();
+    // aliases can be shared among services and are therefore not part of the duplicate check
+    let mut aliases = BTreeSet::new();
     for d in cfg.domains() {
         for a in d.apps() {
             for serv in a.services() {
@@ -81,10 +107,15 @@ fn validate_services(cfg: &AppConfig, parent_set: &BTreeSet<String>) -> Result<(
                     ));
                 }
                 set.insert(serv.name().global().clone());
+                if let Some(alias) = serv.alias() {
+                    aliases.insert(alias.clone());
+                }
             }
         }
     }
 
+    set.append(&mut aliases);
+
     let mut subset = set.clone();
     for s in parent_set.iter() {
         if !subset.contains(s) {
@@ -192,3 +223,107 @@ fn validate_mods(cfg: &AppConfig, res: &Resources) -> Result<(), VerboseError> {
 
     Ok(())
 }
+
+fn validate_physmems(cfg: &AppConfig, res: &Resources) -> Result<(), VerboseError> {
+    let mut all = Vec::new();
+    collect_physmems(cfg, &mut all);
+
+    for (i, (app, pm)) in all.iter().enumerate() {
+        if res
+            .memory()
+            .find_mem(pm.addr(), pm.size(), pm.perm())
+            .is_err()
+        {
+            return Err(VerboseError::new(
+                Code::NotFound,
+                format!(
+                    "AppConfig '{}' needs physical memory {}..{} ('{}'), which is not available",
+                    app,
+                    pm.addr(),
+                    pm.addr() + (pm.size() - 1),
+                    pm.name(),
+                ),
+            ));
+        }
+
+        // writable ranges must not be shared with anyone
+        for (other_app, other) in &all[i + 1..] {
+            if pm.overlaps(other) && (pm.perm().contains(Perm::W) || other.perm().contains(Perm::W))
+            {
+                return Err(VerboseError::new(
+                    Code::Exists,
+                    format!(
+                        "physical memory '{}' of '{}' overlaps with '{}' of '{}'",
+                        pm.name(),
+                        app,
+                        other.name(),
+                        other_app,
+                    ),
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn collect_physmems<'c>(cfg: &'c AppConfig, all: &mut Vec<(&'c str, &'c PhysMemDesc)>) {
+    for d in cfg.domains() {
+        for a in d.apps() {
+            collect_physmems(a, all);
+        }
+    }
+
+    for pm in cfg.physmems() {
+        all.push((cfg.name(), pm));
+    }
+}
+
+fn validate_devices(cfg: &AppConfig, res: &Resources) -> Result<(), VerboseError> {
+    let mut all = Vec::new();
+    collect_devices(cfg, &mut all);
+
+    for (i, (app, dev)) in all.iter().enumerate() {
+        if res.devices().resolve(dev).is_err() {
+            return Err(VerboseError::new(
+                Code::NotFound,
+                format!(
+                    "AppConfig '{}' needs device '{}' (compatible='{}', index={}), which does not exist",
+                    app,
+                    dev.name(),
+                    dev.compatible(),
+                    dev.index(),
+                ),
+            ));
+        }
+
+        // devices are assigned exclusively
+        for (other_app, other) in &all[i + 1..] {
+            if dev.compatible() == other.compatible() && dev.index() == other.index() {
+                return Err(VerboseError::new(
+                    Code::Exists,
+                    format!(
+                        "device '{}' of '{}' is also assigned to '{}'",
+                        dev.name(),
+                        app,
+                        other_app,
+                    ),
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn collect_devices<'c>(cfg: &'c AppConfig, all: &mut Vec<(&'c str, &'c DeviceDesc)>) {
+    for d in cfg.domains() {
+        for a in d.apps() {
+            collect_devices(a, all);
+        }
+    }
+
+    for dev in cfg.devices() {
+        all.push((cfg.name(), dev));
+    }
+}
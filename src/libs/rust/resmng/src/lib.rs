@@ -27,3 +27,4 @@ pub mod requests;
 pub mod resources;
 pub mod sendqueue;
 pub mod subsys;
+pub mod watchdog;
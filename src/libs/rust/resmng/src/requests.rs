@@ -21,7 +21,9 @@ use m3::io::LogFlags;
 use m3::log;
 use m3::reply_vmsg;
 use m3::tiles::OwnActivity;
+use m3::time::TimeDuration;
 use m3::vec::Vec;
+use m3::watchdog::{self, Diagnostics};
 
 use crate::childs::{ChildManager, Id, OwnChild};
 use crate::resources::Resources;
@@ -69,10 +71,25 @@ impl Requests {
                 childs.handle_upcall_async(self, res, msg);
             }
 
+            if childs.take_pending(delayed) {
+                Subsystem::start_async(childs, delayed, self, res, starter)?;
+            }
+
             sendqueue::check_replies(res);
 
+            childs.check_watchdogs_async(self, res);
+            // we might be watched by our own resource manager as well
+            watchdog::check_in_with(|| Diagnostics {
+                threads: thread::thread_count() as u32,
+                ready: thread::ready_count() as u32,
+                blocked: thread::blocked_count() as u32,
+                sleeping: thread::sleeping_count() as u32,
+                queued: 0,
+            });
+
             func(childs, res);
 
+            thread::check_timeouts();
             if thread::ready_count() > 0 {
                 thread::try_yield();
             }
@@ -81,7 +98,18 @@ impl Requests {
                 break;
             }
 
-            OwnActivity::sleep().ok();
+            // don't sleep longer than the next thread timeout, pending child start, or watchdog
+            // deadline
+            let timeout = [
+                thread::next_timeout(),
+                childs.next_pending(),
+                childs.next_watchdog_timeout(),
+                watchdog::next_timeout(),
+            ]
+            .into_iter()
+            .flatten()
+            .min();
+            OwnActivity::sleep_for(timeout.unwrap_or(TimeDuration::MAX)).ok();
         }
 
         if !thread::cur().is_main() {
@@ -123,6 +151,14 @@ impl Requests {
                 }
             },
             Ok(opcodes::ResMng::FreeTile) => self.free_tile(childs, res, &mut is, id),
+            Ok(opcodes::ResMng::CtrlTile) => self.ctrl_tile(childs, res, &mut is, id),
+            Ok(opcodes::ResMng::WatchTiles) => self.watch_tiles(childs, res, &mut is, id),
+
+            Ok(opcodes::ResMng::StartApp) => self.start_app(childs, res, &mut is, id),
+            Ok(opcodes::ResMng::StopApp) => self.stop_app_async(childs, res, &mut is, id),
+            Ok(opcodes::ResMng::SetQuota) => self.set_quota(childs, &mut is, id),
+            Ok(opcodes::ResMng::SetLog) => self.set_log(childs, &mut is, id),
+            Ok(opcodes::ResMng::Heartbeat) => self.heartbeat(childs, &mut is, id),
 
             Ok(opcodes::ResMng::UseRGate) => match self.use_rgate(childs, res, &mut is, id) {
                 // reply already done
@@ -135,9 +171,12 @@ impl Requests {
 
             Ok(opcodes::ResMng::UseMod) => self.use_mod(childs, res, &mut is, id),
 
+            Ok(opcodes::ResMng::UsePhysMem) => self.use_physmem(childs, res, &mut is, id),
+
             Ok(opcodes::ResMng::GetSerial) => self.get_serial(childs, res, &mut is, id),
 
             Ok(opcodes::ResMng::GetInfo) => self.get_info(childs, res, &mut is, id),
+            Ok(opcodes::ResMng::GetUsage) => self.get_usage(childs, &mut is, id),
 
             _ => Err(Error::new(Code::InvArgs)),
         };
@@ -163,7 +202,7 @@ impl Requests {
         let req: resmng::RegServiceReq = is.pop()?;
 
         let child = childs.child_by_id_mut(id).unwrap();
-        child.reg_service(res, req.dst, req.sgate, req.name, req.sessions)
+        child.reg_service(res, req.dst, req.sgate, req.name, req.version, req.sessions)
     }
 
     fn unreg_serv(
@@ -277,7 +316,7 @@ impl Requests {
 
         let child = childs.child_by_id_mut(id).unwrap();
         child
-            .alloc_tile(res, starter, req.dst, req.desc, req.init)
+            .alloc_tile(res, starter, req.dst, req.desc, req.init, req.id, req.near)
             .and_then(|(id, desc)| {
                 reply_vmsg!(is, Code::Success, resmng::AllocTileReply { id, desc })
             })
@@ -296,6 +335,89 @@ impl Requests {
         child.free_tile(res, req.sel)
     }
 
+    fn ctrl_tile(
+        &self,
+        childs: &mut ChildManager,
+        res: &mut Resources,
+        is: &mut GateIStream<'_>,
+        id: Id,
+    ) -> Result<(), Error> {
+        let req: resmng::CtrlTileReq = is.pop()?;
+
+        let child = childs.child_by_id_mut(id).unwrap();
+        child.ctrl_tile(res, req.id, req.online, req.desc)
+    }
+
+    fn watch_tiles(
+        &self,
+        childs: &mut ChildManager,
+        res: &mut Resources,
+        is: &mut GateIStream<'_>,
+        id: Id,
+    ) -> Result<(), Error> {
+        let req: resmng::WatchTilesReq = is.pop()?;
+
+        let child = childs.child_by_id_mut(id).unwrap();
+        child.watch_tiles(res, req.sgate)
+    }
+
+    fn start_app(
+        &self,
+        childs: &mut ChildManager,
+        res: &mut Resources,
+        is: &mut GateIStream<'_>,
+        id: Id,
+    ) -> Result<(), Error> {
+        let req: resmng::StartAppReq = is.pop()?;
+
+        childs.start_app(res, id, &req.cfg)
+    }
+
+    fn stop_app_async(
+        &self,
+        childs: &mut ChildManager,
+        res: &mut Resources,
+        is: &mut GateIStream<'_>,
+        id: Id,
+    ) -> Result<(), Error> {
+        let req: resmng::StopAppReq = is.pop()?;
+
+        childs.stop_app_async(self, res, id, &req.name)
+    }
+
+    fn set_quota(
+        &self,
+        childs: &mut ChildManager,
+        is: &mut GateIStream<'_>,
+        id: Id,
+    ) -> Result<(), Error> {
+        let req: resmng::SetQuotaReq = is.pop()?;
+
+        childs.set_quota(id, &req.name, req.umem, req.eps, req.time)
+    }
+
+    fn set_log(
+        &self,
+        childs: &mut ChildManager,
+        is: &mut GateIStream<'_>,
+        id: Id,
+    ) -> Result<(), Error> {
+        let req: resmng::SetLogReq = is.pop()?;
+
+        childs.set_log(id, &req.name, req.ctrl.flags(), req.ctrl.format())
+    }
+
+    fn heartbeat(
+        &self,
+        childs: &mut ChildManager,
+        is: &mut GateIStream<'_>,
+        id: Id,
+    ) -> Result<(), Error> {
+        let diag: Diagnostics = is.pop()?;
+
+        childs.heartbeat(id, diag)
+    }
+
     fn use_rgate(
         &self,
         childs: &mut ChildManager,
@@ -355,6 +477,19 @@ impl Requests {
         child.use_mod(res, &req.name, req.dst)
     }
 
+    fn use_physmem(
+        &self,
+        childs: &mut ChildManager,
+        res: &mut Resources,
+        is: &mut GateIStream<'_>,
+        id: Id,
+    ) -> Result<(), Error> {
+        let req: resmng::UseReq = is.pop()?;
+
+        let child = childs.child_by_id_mut(id).unwrap();
+        child.use_physmem(res, &req.name, req.dst)
+    }
+
     fn get_serial(
         &self,
         childs: &mut ChildManager,
@@ -386,4 +521,17 @@ impl Requests {
             .get_info(res, id, idx)
             .and_then(|info| reply_vmsg!(is, Code::Success, info))
     }
+
+    fn get_usage(
+        &self,
+        childs: &mut ChildManager,
+        is: &mut GateIStream<'_>,
+        id: Id,
+    ) -> Result<(), Error> {
+        let req: resmng::GetUsageReq = is.pop()?;
+
+        childs
+            .get_usage(id, req.mem, req.size)
+            .and_then(|size| reply_vmsg!(is, Code::Success, resmng::GetUsageReply { size }))
+    }
 }
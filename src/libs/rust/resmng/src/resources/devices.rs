@@ -0,0 +1,67 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! Assigns devices from the device tree to children
+//!
+//! The device tree is provided as the boot module [`DTB_MOD`]. Children request devices via
+//! `<dev>` elements in their config, which are resolved to the register regions, interrupts, and
+//! MSI address of the corresponding device tree node.
+
+use m3::col::Vec;
+use m3::devinfo::DeviceInfo;
+use m3::errors::{Code, Error};
+
+use crate::config::DeviceDesc;
+
+/// The name of the boot module that contains the device tree
+pub const DTB_MOD: &str = "devices.dtb";
+
+/// Holds the device tree and resolves devices requested by children
+#[derive(Default)]
+pub struct DeviceManager {
+    dtb: Option<Vec<u8>>,
+}
+
+impl DeviceManager {
+    /// Returns true if a device tree has been loaded
+    pub fn available(&self) -> bool {
+        self.dtb.is_some()
+    }
+
+    /// Sets the device tree to the given DTB, which is validated first
+    pub fn set_dtb(&mut self, dtb: Vec<u8>) -> Result<(), Error> {
+        dtb::DeviceTree::new(&dtb)?;
+        self.dtb = Some(dtb);
+        Ok(())
+    }
+
+    /// Looks up the given device in the device tree and returns its register regions, interrupts,
+    /// and MSI address
+    pub fn resolve(&self, desc: &DeviceDesc) -> Result<DeviceInfo, Error> {
+        let dtb = self
+            .dtb
+            .as_ref()
+            .ok_or_else(|| Error::new(Code::NotFound))?;
+        let tree = dtb::DeviceTree::new(dtb)?;
+        let node = tree
+            .find_compatible(desc.compatible(), desc.index())
+            .ok_or_else(|| Error::new(Code::NotFound))?;
+        let info = DeviceInfo::new(tree.reg(node)?, tree.interrupts(node)?);
+        Ok(match tree.msi_address(node)? {
+            Some(addr) => info.with_msi(addr),
+            None => info,
+        })
+    }
+}
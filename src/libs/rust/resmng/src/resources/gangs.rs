@@ -0,0 +1,69 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use m3::col::{String, Vec};
+use m3::errors::{Code, Error};
+use m3::io::LogFlags;
+use m3::log;
+use m3::tiles::{ChildActivity, Gang};
+
+#[derive(Default)]
+pub struct GangManager {
+    gangs: Vec<(String, Gang)>,
+}
+
+impl GangManager {
+    pub const fn new() -> Self {
+        GangManager { gangs: Vec::new() }
+    }
+
+    pub fn add_gang(&mut self, name: String, size: u32) -> Result<(), Error> {
+        if self.get(&name).is_some() {
+            return Err(Error::new(Code::Exists));
+        }
+
+        let gang = Gang::new(size)?;
+        log!(
+            LogFlags::ResMngChild,
+            "Created gang {} with {} members @ {}",
+            name,
+            size,
+            gang.sel()
+        );
+        self.gangs.push((name, gang));
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Gang> {
+        for (gname, gang) in &self.gangs {
+            if gname == name {
+                return Some(gang);
+            }
+        }
+        None
+    }
+
+    /// Adds the not yet started activity `act` to the gang with given name
+    pub fn join(&self, name: &str, act: &ChildActivity) -> Result<(), Error> {
+        let gang = self.get(name).ok_or_else(|| Error::new(Code::NotFound))?;
+        log!(
+            LogFlags::ResMngChild,
+            "Adding activity {} to gang {}",
+            act.id(),
+            name
+        );
+        gang.join(act)
+    }
+}
@@ -13,6 +13,8 @@
  * General Public License version 2 for more details.
  */
 
+pub mod devices;
+pub mod gangs;
 pub mod gates;
 pub mod memory;
 pub mod mods;
@@ -20,6 +22,8 @@ pub mod sems;
 pub mod services;
 pub mod tiles;
 
+use devices::DeviceManager;
+use gangs::GangManager;
 use gates::GateManager;
 use memory::MemoryManager;
 use mods::ModManager;
@@ -33,8 +37,10 @@ pub struct Resources {
     gates: GateManager,
     services: ServiceManager,
     sems: SemManager,
+    gangs: GangManager,
     tiles: TileManager,
     mods: ModManager,
+    devices: DeviceManager,
 }
 
 impl Resources {
@@ -70,6 +76,14 @@ impl Resources {
         &mut self.sems
     }
 
+    pub fn gangs(&self) -> &GangManager {
+        &self.gangs
+    }
+
+    pub fn gangs_mut(&mut self) -> &mut GangManager {
+        &mut self.gangs
+    }
+
     pub fn tiles(&self) -> &TileManager {
         &self.tiles
     }
@@ -85,4 +99,12 @@ impl Resources {
     pub fn mods_mut(&mut self) -> &mut ModManager {
         &mut self.mods
     }
+
+    pub fn devices(&self) -> &DeviceManager {
+        &self.devices
+    }
+
+    pub fn devices_mut(&mut self) -> &mut DeviceManager {
+        &mut self.devices
+    }
 }
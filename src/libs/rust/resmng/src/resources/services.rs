@@ -18,6 +18,7 @@ use m3::col::{String, Vec};
 use m3::com::SendGate;
 use m3::errors::{Code, Error};
 use m3::io::LogFlags;
+use m3::kif::service::Version;
 use m3::log;
 use m3::mem::MsgBuf;
 use m3::serialize::M3Deserializer;
@@ -29,7 +30,7 @@ use core::cmp::Reverse;
 use crate::childs;
 use crate::events;
 use crate::resources::Resources;
-use crate::sendqueue::SendQueue;
+use crate::sendqueue::{SendQueue, WaitGuard};
 
 pub type Id = u32;
 
@@ -61,21 +62,31 @@ pub struct Service {
     cap: Capability,
     queue: SendQueue,
     name: String,
+    alias: Option<String>,
+    version: Version,
     sessions: u32,
     owned: bool,
 }
 
 impl Service {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         id: Id,
         child: childs::Id,
         srv_sel: Selector,
         sgate_sel: Selector,
         name: String,
+        version: Version,
         sessions: u32,
         owned: bool,
     ) -> Result<Self, Error> {
-        log!(LogFlags::ResMngServ, "Creating service {}:{}", id, name);
+        log!(
+            LogFlags::ResMngServ,
+            "Creating service {}:{}@{}",
+            id,
+            name,
+            version
+        );
 
         Ok(Service {
             id,
@@ -83,6 +94,8 @@ impl Service {
             cap: Capability::new(srv_sel, CapFlags::empty()),
             queue: SendQueue::new(id, SendGate::new_bind(sgate_sel)?),
             name,
+            alias: None,
+            version,
             sessions,
             owned,
         })
@@ -100,6 +113,23 @@ impl Service {
         &self.name
     }
 
+    pub fn alias(&self) -> Option<&String> {
+        self.alias.as_ref()
+    }
+
+    pub fn set_alias(&mut self, alias: String) {
+        self.alias = Some(alias);
+    }
+
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    /// Returns true if this service is known under the given name, either directly or via its alias
+    pub fn has_name(&self, name: &str) -> bool {
+        self.name == name || self.alias.as_deref() == Some(name)
+    }
+
     pub fn queue(&mut self) -> &mut SendQueue {
         &mut self.queue
     }
@@ -163,6 +193,9 @@ impl Session {
     ) -> Result<Self, Error> {
         let sid = serv.id;
 
+        // refuse the request if the service's child is waiting for us, directly or indirectly
+        let _wait = WaitGuard::new(child, serv.child, sid, &serv.name, serv.sgate_sel())?;
+
         let mut smsg_buf = MsgBuf::borrow_def();
         build_vmsg!(smsg_buf, kif::service::Request::Open { arg });
         let event = serv.queue.send(&smsg_buf);
@@ -249,23 +282,56 @@ impl ServiceManager {
     }
 
     pub fn get_by_name(&self, name: &str) -> Result<&Service, Error> {
-        self.get_with(|s| s.name == name)
+        self.get_with(|s| s.has_name(name))
     }
 
     pub fn get_mut_by_name(&mut self, name: &str) -> Result<&mut Service, Error> {
-        self.get_mut_with(|s| s.name == name)
+        self.get_mut_with(|s| s.has_name(name))
+    }
+
+    /// Finds the service for a session request with given name and optional version
+    ///
+    /// Without a version, a service with exactly that name is preferred over services that only
+    /// have it as an alias. With a version, the service with the highest compatible version is
+    /// chosen. If services with that name exist, but none of them has a compatible version, the
+    /// call fails with [`VersionMismatch`](Code::VersionMismatch).
+    pub fn get_mut_by_version(
+        &mut self,
+        name: &str,
+        version: Option<Version>,
+    ) -> Result<&mut Service, Error> {
+        let mut candidates = self
+            .servs
+            .iter_mut()
+            .filter(|s| s.has_name(name))
+            .peekable();
+        if candidates.peek().is_none() {
+            return Err(Error::new(Code::InvArgs));
+        }
+
+        match version {
+            None => candidates
+                .max_by_key(|s| (s.name == name, s.version))
+                .ok_or_else(|| Error::new(Code::InvArgs)),
+            Some(ver) => candidates
+                .filter(|s| s.version.is_compatible(&ver))
+                .max_by_key(|s| s.version)
+                .ok_or_else(|| Error::new(Code::VersionMismatch)),
+        }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn add_service(
         &mut self,
         child: childs::Id,
         srv_sel: Selector,
         sgate_sel: Selector,
         name: String,
+        version: Version,
         sessions: u32,
         owned: bool,
     ) -> Result<Id, Error> {
-        if self.get_mut_by_name(&name).is_ok() {
+        if self.servs.iter().any(|s| s.name == name) {
             return Err(Error::new(Code::Exists));
         }
 
@@ -275,6 +341,7 @@ impl ServiceManager {
             srv_sel,
             sgate_sel,
             name,
+            version,
             sessions,
             owned,
         )?;
@@ -13,17 +13,19 @@
  * General Public License version 2 for more details.
  */
 
+use m3::build_vmsg;
 use m3::cell::{Cell, Ref, RefCell, RefMut};
 use m3::cfg;
+use m3::client::resmng;
 use m3::col::Vec;
-use m3::com::{MemCap, MemGate};
+use m3::com::{MemCap, MemGate, RecvGate, SendGate};
 use m3::elf;
 use m3::env;
 use m3::errors::{Code, Error};
 use m3::io::LogFlags;
 use m3::kif::{Perm, TileDesc, INVALID_SEL};
 use m3::log;
-use m3::mem::{size_of, GlobOff};
+use m3::mem::{size_of, GlobOff, MsgBuf};
 use m3::rc::Rc;
 use m3::syscalls;
 use m3::tcu::{EpId, TileId};
@@ -31,6 +33,7 @@ use m3::tiles::Tile;
 use m3::time::TimeDuration;
 use m3::util::math;
 
+use crate::childs::Id;
 use crate::resources::memory::Allocation;
 
 // PMP EPs start at 1, because 0 is reserved for TileMux
@@ -365,6 +368,7 @@ struct ManagedTile {
     id: TileId,
     tile: Rc<Tile>,
     users: Cell<u32>,
+    online: Cell<bool>,
 }
 
 impl ManagedTile {
@@ -382,6 +386,7 @@ impl ManagedTile {
 #[derive(Default)]
 pub struct TileManager {
     tiles: Vec<ManagedTile>,
+    watchers: Vec<(Id, SendGate)>,
 }
 
 impl TileManager {
@@ -398,9 +403,77 @@ impl TileManager {
             id: tile.id(),
             tile,
             users: Cell::from(0),
+            online: Cell::from(true),
         });
     }
 
+    /// Takes the tile with given id offline or brings it online again with the description `desc`
+    ///
+    /// Offline tiles are not considered for allocations. A tile can only be taken offline if it
+    /// is not in use. Afterwards, all tile watchers are notified about the change.
+    pub fn set_online(&self, id: TileId, online: bool, desc: TileDesc) -> Result<(), Error> {
+        let tile = self
+            .tiles
+            .iter()
+            .find(|t| t.id == id)
+            .ok_or_else(|| Error::new(Code::NotFound))?;
+        if tile.online.get() == online {
+            return Err(Error::new(Code::InvState));
+        }
+
+        if online {
+            tile.tile.set_online(desc)?;
+        }
+        else {
+            if tile.users.get() > 0 {
+                return Err(Error::new(Code::InvState));
+            }
+            tile.tile.set_offline()?;
+        }
+        tile.online.set(online);
+
+        log!(
+            LogFlags::ResMngTiles,
+            "Tile {} is now {}: {:?}",
+            id,
+            if online { "online" } else { "offline" },
+            tile.tile.desc()
+        );
+
+        self.notify_watchers(resmng::TileEvent {
+            id,
+            desc: tile.tile.desc(),
+            online,
+        });
+        Ok(())
+    }
+
+    /// Adds the given send gate of child `child` to the tile watchers
+    pub fn add_watcher(&mut self, child: Id, sgate: SendGate) {
+        self.watchers.push((child, sgate));
+    }
+
+    /// Removes all tile watchers of child `child`
+    pub fn remove_watchers(&mut self, child: Id) {
+        self.watchers.retain(|(id, _)| *id != child);
+    }
+
+    fn notify_watchers(&self, event: resmng::TileEvent) {
+        let mut msg = MsgBuf::borrow_def();
+        build_vmsg!(msg, event);
+        for (id, sgate) in &self.watchers {
+            // the watcher does not reply; ignore failures to not block the tile reconfiguration
+            if let Err(e) = sgate.send(&msg, RecvGate::def()) {
+                log!(
+                    LogFlags::Error,
+                    "Unable to notify tile watcher {}: {:?}",
+                    id,
+                    e
+                );
+            }
+        }
+    }
+
     pub fn add_user(&self, usage: &TileUsage) {
         if let Some(idx) = usage.idx {
             if self.tiles[idx].add_user() == 0 {
@@ -428,17 +501,58 @@ impl TileManager {
     }
 
     pub fn find(&self, desc: TileDesc) -> Result<TileUsage, Error> {
-        for (id, tile) in self.tiles.iter().enumerate() {
-            if tile.users.get() == 0
+        self.find_with(desc, None, None)
+    }
+
+    /// Finds a free tile that matches `desc`
+    ///
+    /// If `id` is given, only the tile with that id is considered. If `near` is given, the matching
+    /// tile with the smallest [`distance`](Self::distance) to the tile `near` is chosen. Otherwise,
+    /// the first matching tile is chosen.
+    pub fn find_with(
+        &self,
+        desc: TileDesc,
+        id: Option<TileId>,
+        near: Option<TileId>,
+    ) -> Result<TileUsage, Error> {
+        let mut candidates = self.tiles.iter().enumerate().filter(|(_, tile)| {
+            tile.users.get() == 0
+                && tile.online.get()
+                && id.map(|id| id == tile.id).unwrap_or(true)
                 && tile.tile.desc().isa() == desc.isa()
                 && tile.tile.desc().tile_type() == desc.tile_type()
                 && (tile.tile.desc().attr() & desc.attr()) == desc.attr()
-            {
-                return Ok(TileUsage::new(id, tile.tile.clone()));
-            }
+        });
+
+        let res = match near {
+            Some(near) => candidates.min_by_key(|(_, tile)| Self::distance(tile.id, near)),
+            None => candidates.next(),
+        };
+
+        match res {
+            Some((idx, tile)) => Ok(TileUsage::new(idx, tile.tile.clone())),
+            None => {
+                log!(
+                    LogFlags::ResMngTiles,
+                    "Unable to find tile with {:?} (id={:?}, near={:?})",
+                    desc,
+                    id,
+                    near
+                );
+                Err(Error::new(Code::NotFound))
+            },
         }
-        log!(LogFlags::ResMngTiles, "Unable to find tile with {:?}", desc);
-        Err(Error::new(Code::NotFound))
+    }
+
+    /// Returns the distance between the tiles `a` and `b` in the network-on-chip
+    ///
+    /// The tiles of a chip are assumed to be connected in the order of their chip-local ids, so
+    /// that the distance grows with the difference between the ids. Tiles on other chips are
+    /// further away than all tiles on the same chip.
+    pub fn distance(a: TileId, b: TileId) -> u32 {
+        let chips = (a.chip() as i32 - b.chip() as i32).unsigned_abs();
+        let tiles = (a.tile() as i32 - b.tile() as i32).unsigned_abs();
+        chips * (u8::MAX as u32 + 1) + tiles
     }
 
     pub fn find_with_attr(&self, base: TileDesc, attr: &str) -> Result<TileUsage, Error> {
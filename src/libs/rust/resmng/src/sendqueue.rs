@@ -14,14 +14,17 @@
  */
 
 use m3::cap::Selector;
-use m3::cell::LazyStaticRefCell;
+use m3::cell::{LazyStaticRefCell, StaticRefCell};
+use m3::col::{String, Vec};
 use m3::com::{MsgQueue, MsgSender, RecvGate, SendGate};
-use m3::errors::Error;
+use m3::errors::{Code, Error};
 use m3::io::LogFlags;
 use m3::log;
 use m3::mem::MsgBuf;
+use m3::println;
 use m3::server::DEF_MAX_CLIENTS;
 use m3::tcu;
+use m3::vec;
 
 use crate::childs::Id;
 use crate::events;
@@ -54,6 +57,111 @@ impl MsgSender<thread::Event> for GateSender {
 }
 
 static RGATE: LazyStaticRefCell<RecvGate> = LazyStaticRefCell::default();
+static WAITS: StaticRefCell<Vec<Wait>> = StaticRefCell::new(Vec::new());
+
+/// A child that waits for the reply of a service that is provided by another child
+struct Wait {
+    waiter: Id,
+    owner: Id,
+    sid: Id,
+    serv: String,
+    sgate: Selector,
+}
+
+/// Records that a child waits for a service of another child while the guard exists
+///
+/// Children send requests to services via the resource manager (e.g., to open a session), which
+/// forwards the requests via the services' [`SendQueue`]s. If the child providing the service is
+/// itself waiting for a service of the requesting child, directly or via other children, neither
+/// request will ever complete. The resource manager therefore keeps track of all waiting children
+/// and refuses requests that would close such a cycle with [`Code::Deadlock`].
+pub struct WaitGuard {
+    waiter: Id,
+    sid: Id,
+}
+
+impl WaitGuard {
+    /// Registers that child `waiter` waits for the service `sid` with given name and `SendGate`,
+    /// which is provided by child `owner`.
+    ///
+    /// Returns an error if this would lead to a deadlock, in which case the cycle is reported.
+    pub fn new(waiter: Id, owner: Id, sid: Id, serv: &str, sgate: Selector) -> Result<Self, Error> {
+        let mut waits = WAITS.borrow_mut();
+
+        if let Some(cycle) = find_cycle(&waits, waiter, owner) {
+            println!(
+                "Deadlock: child {} waits for service {} (sgate {}) of child {}",
+                waiter, serv, sgate, owner
+            );
+            for w in cycle {
+                println!(
+                    "Deadlock:   child {} waits for service {} (sgate {}) of child {}",
+                    w.waiter, w.serv, w.sgate, w.owner
+                );
+            }
+            return Err(Error::new(Code::Deadlock));
+        }
+
+        log!(
+            LogFlags::ResMngSQueue,
+            "{}:squeue: child {} waits for child {}",
+            sid,
+            waiter,
+            owner
+        );
+        waits.push(Wait {
+            waiter,
+            owner,
+            sid,
+            serv: String::from(serv),
+            sgate,
+        });
+        Ok(Self { waiter, sid })
+    }
+}
+
+impl Drop for WaitGuard {
+    fn drop(&mut self) {
+        let mut waits = WAITS.borrow_mut();
+        if let Some(idx) = waits
+            .iter()
+            .position(|w| w.waiter == self.waiter && w.sid == self.sid)
+        {
+            waits.remove(idx);
+        }
+    }
+}
+
+/// Searches for a chain of waiting children from `owner` to `waiter` and returns it, if any
+fn find_cycle(waits: &[Wait], waiter: Id, owner: Id) -> Option<Vec<&Wait>> {
+    if waiter == owner {
+        return Some(Vec::new());
+    }
+
+    // depth-first search through the waiting children, starting at the owner of the service
+    let mut visited = vec![owner];
+    let mut path: Vec<&Wait> = Vec::new();
+    let mut stack: Vec<(usize, &Wait)> = waits
+        .iter()
+        .filter(|w| w.waiter == owner)
+        .map(|w| (0, w))
+        .collect();
+    while let Some((depth, w)) = stack.pop() {
+        path.truncate(depth);
+        path.push(w);
+        if w.owner == waiter {
+            return Some(path);
+        }
+
+        if !visited.contains(&w.owner) {
+            visited.push(w.owner);
+            for next in waits.iter().filter(|n| n.waiter == w.owner) {
+                stack.push((depth + 1, next));
+            }
+        }
+    }
+    None
+}
 
 pub fn init(rgate: RecvGate) {
     RGATE.set(rgate);
@@ -94,6 +202,11 @@ impl SendQueue {
         self.queue.sender().sgate.sel()
     }
 
+    /// Returns the number of messages that have been sent or queued, but not yet been replied to
+    pub fn pending(&self) -> usize {
+        self.queue.len() + self.queue.sender().cur_event.is_some() as usize
+    }
+
     pub fn send(&mut self, msg: &MsgBuf) -> Result<thread::Event, Error> {
         let event = events::alloc_event();
         if !self.queue.send(event, msg)? {
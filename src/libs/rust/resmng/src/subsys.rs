@@ -21,7 +21,7 @@ use m3::col::{String, ToString, Vec};
 use m3::com::{GateCap, MemCap, MemGate};
 use m3::errors::{Code, Error, VerboseError};
 use m3::io::LogFlags;
-use m3::kif::{boot, CapRngDesc, CapType, Perm, TileDesc, FIRST_FREE_SEL};
+use m3::kif::{boot, syscalls::ExchangeDesc, CapRngDesc, CapType, Perm, TileDesc, FIRST_FREE_SEL};
 use m3::log;
 use m3::mem::{size_of, GlobOff};
 use m3::rc::Rc;
@@ -30,13 +30,13 @@ use m3::tcu::TileId;
 use m3::tiles::{Activity, ChildActivity, Tile, TileArgs};
 use m3::time::TimeDuration;
 use m3::util::math;
-use m3::{format, tcu};
+use m3::{format, println, tcu};
 
-use crate::childs;
+use crate::childs::{self, Child};
 use crate::config;
 use crate::config::validator;
 use crate::requests::Requests;
-use crate::resources::{memory, mods, services, tiles, Resources};
+use crate::resources::{devices, memory, mods, services, tiles, Resources};
 
 //
 // Our parent/kernel initializes our cap space as follows:
@@ -144,6 +144,30 @@ impl Subsystem {
             res.mods_mut().add(i, m);
         }
 
+        if let Some(idx) = self
+            .mods()
+            .iter()
+            .position(|m| m.name() == devices::DTB_MOD)
+        {
+            let size = self.mods()[idx].size;
+            let mgate = Self::get_mod(idx).derive(0, size, Perm::R)?.activate()?;
+            let dtb = mgate.read_into_vec::<u8>(size as usize, 0)?;
+            res.devices_mut().set_dtb(dtb).map_err(|e| {
+                log!(
+                    LogFlags::Error,
+                    "Unable to parse {}: {}",
+                    devices::DTB_MOD,
+                    e
+                );
+                e
+            })?;
+            log!(
+                LogFlags::Info,
+                "Loaded device tree from {}",
+                devices::DTB_MOD
+            );
+        }
+
         log!(LogFlags::Info, "Available tiles:");
         for (i, tile) in self.tiles().iter().enumerate() {
             log!(LogFlags::Info, "  {:?}", tile);
@@ -168,8 +192,9 @@ impl Subsystem {
                 let sel = self.get_service(i);
                 log!(
                     LogFlags::Info,
-                    "  Service[name={}, sessions={}]",
+                    "  Service[name={}, version={}, sessions={}]",
                     s.name(),
+                    s.version(),
                     s.sessions()
                 );
                 res.services_mut()
@@ -178,6 +203,7 @@ impl Subsystem {
                         sel,
                         sel + 1,
                         s.name().to_string(),
+                        s.version(),
                         s.sessions(),
                         false,
                     )
@@ -222,7 +248,10 @@ impl Subsystem {
 
         // parse boot config
         let xml_str = String::from_utf8(xml).map_err(|_| Error::new(Code::InvArgs))?;
-        let cfg = config::AppConfig::parse(&xml_str)?;
+        let cfg = config::AppConfig::parse(&xml_str).map_err(|e| {
+            log!(LogFlags::Error, "Unable to parse boot config: {}", e.msg());
+            Error::new(e.code())
+        })?;
         Ok((xml_str, cfg))
     }
 
@@ -319,6 +348,26 @@ impl Subsystem {
             ));
         }
 
+        // create the gangs with as many members as there are apps that want to join them
+        let mut gangs: Vec<(&str, u32)> = Vec::new();
+        for name in root
+            .domains()
+            .iter()
+            .flat_map(|d| d.apps().iter().filter_map(|a| a.gang()))
+        {
+            match gangs.iter_mut().find(|(g, _)| *g == name) {
+                Some((_, size)) => *size += 1,
+                None => gangs.push((name, 1)),
+            }
+        }
+        for (name, size) in gangs {
+            res.gangs_mut()
+                .add_gang(name.to_string(), size)
+                .map_err(|e| {
+                    VerboseError::new(e.code(), format!("Unable to create gang {}", name))
+                })?;
+        }
+
         // determine default mem and kmem per child
         let (def_kmem, def_umem) = split_mem(res, root)?;
 
@@ -497,7 +546,9 @@ impl Subsystem {
                     )
                 })?;
 
-            // derive a new tile object for the entire domain (so that they cannot change the PMP EPs)
+            // derive a new tile object for the entire domain (so that they cannot change the PMP EPs).
+            // time quotas are hierarchical: the domain's budget bounds the time of all its childs,
+            // including the childs that derive their own time quota from it.
             let domain_pe_usage = if dom.apps().iter().next().unwrap().domains().is_empty() {
                 let domain_eps = Some(domain_total_eps);
                 let domain_time = Some(domain_total_time);
@@ -633,7 +684,15 @@ impl Subsystem {
             }
 
             let mut child = childs.remove(idx);
-            starter.start_async(reqs, res, &mut child)?;
+            if let Err(e) = starter.start_async(reqs, res, &mut child) {
+                // childs from the boot configuration are essential; all others are just dropped
+                if child.is_initial() {
+                    return Err(e);
+                }
+                println!("Unable to start '{}': {}", child.name(), e);
+                childmng.discard(res, child);
+                continue;
+            }
             childmng.add(child);
             new_wait = true;
         }
@@ -675,6 +734,14 @@ impl Subsystem {
         // add remaining boot modules
         pass_down_mods(res.mods(), &mut sub, cfg)?;
 
+        // add the device tree if grandchildren request devices
+        if uses_devices(cfg) {
+            pass_down_dtb(res.mods(), &mut sub)?;
+        }
+
+        // add physical memory ranges used by grandchildren as reserved memory
+        pass_down_physmems(res.memory(), &mut sub, cfg)?;
+
         // add tiles
         sub.add_tile(child_tile_usage.tile_obj().clone());
         pass_down_tiles(res.tiles(), &mut sub, cfg);
@@ -771,6 +838,11 @@ impl SubsystemBuilder {
     ) -> Result<(), VerboseError> {
         let mut sel = SUBSYS_SELS;
         let mut off: GlobOff = 0;
+        // collect all capabilities first and delegate them at once to save system calls
+        let mut xchgs = Vec::new();
+        let obj_xchg = |own: Selector, dst: Selector| {
+            ExchangeDesc::new(CapRngDesc::new(CapType::Object, own, 1), dst)
+        };
 
         let mem = res
             .memory_mut()
@@ -792,13 +864,13 @@ impl SubsystemBuilder {
             serv_count: self.servs.len() as u64,
         };
         mem.write_obj(&info, off)?;
-        act.delegate_to(CapRngDesc::new(CapType::Object, mem.sel(), 1), sel)?;
+        xchgs.push(obj_xchg(mem.sel(), sel));
         off += size_of::<boot::Info>() as GlobOff;
         sel += 1;
 
         // serial rgate
         if self.serial {
-            act.delegate_to(CapRngDesc::new(CapType::Object, SERIAL_RGATE_SEL, 1), sel)?;
+            xchgs.push(obj_xchg(SERIAL_RGATE_SEL, sel));
         }
         sel += 1;
 
@@ -808,7 +880,7 @@ impl SubsystemBuilder {
             let m = boot::Mod::new(addr, size, name);
             mem.write_obj(&m, off)?;
 
-            act.delegate_to(CapRngDesc::new(CapType::Object, mgate.sel(), 1), sel)?;
+            xchgs.push(obj_xchg(mgate.sel(), sel));
 
             off += size_of::<boot::Mod>() as GlobOff;
             sel += 1;
@@ -819,7 +891,7 @@ impl SubsystemBuilder {
             let boot_tile = boot::Tile::new(tile.id(), tile.desc());
             mem.write_obj(&boot_tile, off)?;
 
-            act.delegate_to(CapRngDesc::new(CapType::Object, tile.sel(), 1), sel)?;
+            xchgs.push(obj_xchg(tile.sel(), sel));
 
             off += size_of::<boot::Tile>() as GlobOff;
             sel += 1;
@@ -831,7 +903,7 @@ impl SubsystemBuilder {
             let boot_mem = boot::Mem::new(addr, size, *reserved);
             mem.write_obj(&boot_mem, off)?;
 
-            act.delegate_to(CapRngDesc::new(CapType::Object, mgate.sel(), 1), sel)?;
+            xchgs.push(obj_xchg(mgate.sel(), sel));
 
             off += size_of::<boot::Mem>() as GlobOff;
             sel += 1;
@@ -860,14 +932,11 @@ impl SubsystemBuilder {
             let subserv = serv.derive_async(child, sessions).map_err(|e| {
                 VerboseError::new(e.code(), format!("Unable to derive from service {}", name))
             })?;
-            let boot_serv = boot::Service::new(name, sessions);
+            let boot_serv = boot::Service::new(name, serv.version(), sessions);
             mem.write_obj(&boot_serv, off)?;
 
-            act.delegate_to(CapRngDesc::new(CapType::Object, subserv.serv_sel(), 1), sel)?;
-            act.delegate_to(
-                CapRngDesc::new(CapType::Object, subserv.sgate_sel(), 1),
-                sel + 1,
-            )?;
+            xchgs.push(obj_xchg(subserv.serv_sel(), sel));
+            xchgs.push(obj_xchg(subserv.sgate_sel(), sel + 1));
 
             off += size_of::<boot::Service>() as GlobOff;
             sel += 2;
@@ -875,6 +944,8 @@ impl SubsystemBuilder {
             self.serv_objs.push(subserv);
         }
 
+        act.delegate_bulk(&xchgs)?;
+
         self._desc = Some(mem.deactivate());
         Ok(())
     }
@@ -945,6 +1016,51 @@ fn pass_down_mods(
     Ok(())
 }
 
+fn uses_devices(app: &config::AppConfig) -> bool {
+    app.domains().iter().any(|d| {
+        d.apps()
+            .iter()
+            .any(|child| !child.devices().is_empty() || uses_devices(child))
+    })
+}
+
+fn pass_down_dtb(mods: &mods::ModManager, sub: &mut SubsystemBuilder) -> Result<(), VerboseError> {
+    let bmod = mods.find(devices::DTB_MOD).ok_or_else(|| {
+        VerboseError::new(
+            Code::NotFound,
+            format!("Unable to find device tree {} for subsys", devices::DTB_MOD),
+        )
+    })?;
+    let mgate = bmod.memory().derive(0, bmod.size(), Perm::R)?;
+    sub.add_mod(mgate, bmod.name());
+    Ok(())
+}
+
+fn pass_down_physmems(
+    mem: &memory::MemoryManager,
+    sub: &mut SubsystemBuilder,
+    app: &config::AppConfig,
+) -> Result<(), VerboseError> {
+    for d in app.domains() {
+        for child in d.apps() {
+            for pm in child.physmems() {
+                let slice = mem.find_mem(pm.addr(), pm.size(), pm.perm()).map_err(|e| {
+                    VerboseError::new(
+                        e.code(),
+                        format!("Unable to find physical memory {} for subsys", pm.name()),
+                    )
+                })?;
+
+                // mark it as reserved so that the child does not use it for allocations
+                sub.add_mem(slice.derive()?, true);
+            }
+
+            pass_down_physmems(mem, sub, child)?;
+        }
+    }
+    Ok(())
+}
+
 fn split_child_mem(cfg: &config::AppConfig, mem: &Rc<childs::ChildMem>, tiles: usize) {
     let mut def_childs = 0;
     for d in cfg.domains() {
@@ -0,0 +1,142 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! Watches children that are expected to send heartbeats
+//!
+//! Children with a `<watchdog>` element in their config receive the interval for heartbeats via
+//! the environment (see `m3::watchdog`). If no heartbeat arrives within the configured timeout, the
+//! child is considered hung and the configured action is performed by the [`ChildManager`].
+//!
+//! [`ChildManager`]: crate::childs::ChildManager
+
+use m3::col::Vec;
+use m3::errors::{Code, Error};
+use m3::time::{TimeDuration, TimeInstant};
+use m3::watchdog::Diagnostics;
+
+use crate::childs::Id;
+use crate::config::{WatchdogAction, WatchdogDesc};
+
+/// The watchdog state of a single child
+pub struct Watchdog {
+    id: Id,
+    timeout: TimeDuration,
+    action: WatchdogAction,
+    deadline: TimeInstant,
+    last: Option<TimeInstant>,
+    diag: Diagnostics,
+    missed: bool,
+}
+
+impl Watchdog {
+    /// Returns the id of the watched child
+    pub fn id(&self) -> Id {
+        self.id
+    }
+
+    /// Returns the action to perform if a heartbeat is missed
+    pub fn action(&self) -> WatchdogAction {
+        self.action
+    }
+
+    /// Returns the time since the last heartbeat or `None` if there was none yet
+    pub fn since_last(&self) -> Option<TimeDuration> {
+        self.last.map(|l| l.elapsed())
+    }
+
+    /// Returns the diagnostics of the last heartbeat
+    pub fn diag(&self) -> &Diagnostics {
+        &self.diag
+    }
+}
+
+/// The watchdogs of all children
+#[derive(Default)]
+pub struct Watchdogs {
+    list: Vec<Watchdog>,
+}
+
+impl Watchdogs {
+    /// Starts watching the child with given id. The first heartbeat is expected within the
+    /// timeout from now.
+    pub fn add(&mut self, id: Id, desc: &WatchdogDesc) {
+        self.list.push(Watchdog {
+            id,
+            timeout: desc.timeout(),
+            action: desc.action(),
+            deadline: TimeInstant::now() + desc.timeout(),
+            last: None,
+            diag: Diagnostics::default(),
+            missed: false,
+        });
+    }
+
+    /// Stops watching the child with given id
+    pub fn remove(&mut self, id: Id) {
+        self.list.retain(|w| w.id != id);
+    }
+
+    /// Records a heartbeat of the child with given id
+    ///
+    /// Returns true if the child has missed a heartbeat before and is thus alive again.
+    pub fn heartbeat(&mut self, id: Id, diag: Diagnostics) -> Result<bool, Error> {
+        let wd = self
+            .list
+            .iter_mut()
+            .find(|w| w.id == id)
+            .ok_or_else(|| Error::new(Code::InvArgs))?;
+
+        let now = TimeInstant::now();
+        wd.last = Some(now);
+        wd.deadline = now + wd.timeout;
+        wd.diag = diag;
+        Ok(core::mem::replace(&mut wd.missed, false))
+    }
+
+    /// Returns the time until the next deadline or `None` if there is no pending deadline
+    pub fn next_timeout(&self) -> Option<TimeDuration> {
+        let now = TimeInstant::now();
+        self.list
+            .iter()
+            .filter(|w| !w.missed)
+            .map(|w| {
+                w.deadline
+                    .checked_duration_since(now)
+                    .unwrap_or(TimeDuration::ZERO)
+            })
+            .min()
+    }
+
+    /// Returns the watchdog of the child with given id
+    pub fn get(&self, id: Id) -> Option<&Watchdog> {
+        self.list.iter().find(|w| w.id == id)
+    }
+
+    /// Returns the ids of all children that have missed their deadline since the last call
+    ///
+    /// Each missed deadline is reported once; the child is reported again only after it sent
+    /// another heartbeat and missed the following deadline.
+    pub fn expired(&mut self) -> Vec<Id> {
+        let now = TimeInstant::now();
+        let mut res = Vec::new();
+        for w in &mut self.list {
+            if !w.missed && now >= w.deadline {
+                w.missed = true;
+                res.push(w.id);
+            }
+        }
+        res
+    }
+}
@@ -21,21 +21,45 @@
 use base::boxed::Box;
 use base::cell::{LazyStaticRefCell, Ref, StaticCell};
 use base::cfg;
-use base::col::{BoxList, Vec};
+use base::col::{BoxList, MpscQueue, Vec};
+use base::errors::{Code, Error};
 use base::impl_boxitem;
 use base::io::LogFlags;
 use base::libc;
 use base::log;
 use base::mem::{self, VirtAddr};
 use base::tcu::{self, Message};
+use base::time::{TimeDuration, TimeInstant};
 use base::vec;
 use core::intrinsics::transmute;
-use core::ptr::NonNull;
+use core::ptr::{self, NonNull};
 
 pub type Event = u64;
 
+/// The priority of a thread; threads with higher values are preferred
+pub type Priority = u8;
+
+/// The number of different thread priorities
+pub const PRIO_COUNT: usize = 4;
+/// The highest priority
+pub const MAX_PRIO: Priority = (PRIO_COUNT - 1) as Priority;
+/// The priority that threads get by default
+pub const DEF_PRIO: Priority = 1;
+
 const MAX_MSG_SIZE: usize = 1024;
 
+/// The maximum number of stacks of destroyed threads that are kept for reuse
+const MAX_CACHED_STACKS: usize = 4;
+
+/// The stack size that threads get by default
+pub const DEF_STACK_SIZE: usize = cfg::STACK_SIZE;
+/// The minimum stack size of threads
+pub const MIN_STACK_SIZE: usize = cfg::PAGE_SIZE;
+
+// the value and number of words at the bottom of each stack to detect stack overflows
+const STACK_CANARY: usize = 0x5AFE_57AC;
+const STACK_CANARY_WORDS: usize = 4;
+
 #[cfg(target_arch = "x86_64")]
 #[derive(Default)]
 #[repr(C, align(8))]
@@ -69,6 +93,26 @@ pub struct Regs {
     cpsr: usize,
 }
 
+#[cfg(target_arch = "aarch64")]
+#[derive(Default)]
+#[repr(C, align(8))]
+pub struct Regs {
+    x0: usize,
+    x19: usize,
+    x20: usize,
+    x21: usize,
+    x22: usize,
+    x23: usize,
+    x24: usize,
+    x25: usize,
+    x26: usize,
+    x27: usize,
+    x28: usize,
+    x29: usize,
+    x30: usize,
+    sp: usize,
+}
+
 #[cfg(target_arch = "riscv64")]
 #[derive(Default)]
 #[repr(C, align(8))]
@@ -111,6 +155,15 @@ fn thread_init(thread: &mut Thread, func_addr: VirtAddr, arg: usize) {
     thread.regs.cpsr = 0x13; // supervisor mode
 }
 
+#[cfg(target_arch = "aarch64")]
+fn thread_init(thread: &mut Thread, func_addr: VirtAddr, arg: usize) {
+    let top_idx = thread.stack.len() - 2;
+    thread.regs.x0 = arg; // arg
+    thread.regs.sp = &thread.stack[top_idx] as *const usize as usize;
+    thread.regs.x29 = 0; // fp
+    thread.regs.x30 = func_addr.as_local(); // lr
+}
+
 #[cfg(target_arch = "riscv64")]
 fn thread_init(thread: &mut Thread, func_addr: VirtAddr, arg: usize) {
     let top_idx = thread.stack.len() - 2;
@@ -130,9 +183,12 @@ pub struct Thread {
     prev: Option<NonNull<Thread>>,
     next: Option<NonNull<Thread>>,
     id: u32,
+    prio: Priority,
     regs: Regs,
     stack: Vec<usize>,
     event: Event,
+    deadline: Option<TimeInstant>,
+    wait_res: Code,
     has_msg: bool,
     msg: [mem::MaybeUninit<u64>; MAX_MSG_SIZE / 8],
 }
@@ -149,9 +205,12 @@ impl Thread {
             prev: None,
             next: None,
             id: alloc_id(),
+            prio: DEF_PRIO,
             regs: Regs::default(),
             stack: Vec::new(),
             event: 0,
+            deadline: None,
+            wait_res: Code::Success,
             has_msg: false,
             // safety: will only be safe to access if `has_msg` is true
             msg: unsafe { mem::MaybeUninit::uninit().assume_init() },
@@ -159,13 +218,31 @@ impl Thread {
     }
 
     pub fn new(func_addr: VirtAddr, arg: usize) -> Box<Self> {
+        Self::new_with_size(func_addr, arg, DEF_STACK_SIZE)
+    }
+
+    /// Creates a new thread with a stack of `stack_size` bytes, which needs to be at least
+    /// [`MIN_STACK_SIZE`].
+    pub fn new_with_size(func_addr: VirtAddr, arg: usize, stack_size: usize) -> Box<Self> {
+        assert!(stack_size >= MIN_STACK_SIZE);
+        let stack = vec![0usize; stack_size / mem::size_of::<usize>()];
+        Self::new_with_stack(func_addr, arg, stack)
+    }
+
+    fn new_with_stack(func_addr: VirtAddr, arg: usize, mut stack: Vec<usize>) -> Box<Self> {
+        // stacks grow downwards, so that an overflow overwrites the canary at the bottom first
+        stack[0..STACK_CANARY_WORDS].fill(STACK_CANARY);
+
         let mut thread = Box::new(Thread {
             prev: None,
             next: None,
             id: alloc_id(),
+            prio: DEF_PRIO,
             regs: Regs::default(),
-            stack: vec![0usize; cfg::STACK_SIZE / mem::size_of::<usize>()],
+            stack,
             event: 0,
+            deadline: None,
+            wait_res: Code::Success,
             has_msg: false,
             // safety: will only be safe to access if `has_msg` is true
             msg: unsafe { mem::MaybeUninit::uninit().assume_init() },
@@ -186,6 +263,45 @@ impl Thread {
         self.id
     }
 
+    /// Returns the size of the stack of this thread in bytes (0 for the main thread)
+    pub fn stack_size(&self) -> usize {
+        self.stack.len() * mem::size_of::<usize>()
+    }
+
+    /// Panics if this thread has overflowed its stack
+    ///
+    /// Threads have no guard pages, because their stacks are allocated on the heap. Instead, the
+    /// bottom of each stack contains a canary, which is checked whenever we switch away from a
+    /// thread. Thus, overflows are detected late, but at least before the damage spreads further.
+    fn check_stack(&self) {
+        if !self.is_main()
+            && self.stack[0..STACK_CANARY_WORDS]
+                .iter()
+                .any(|w| *w != STACK_CANARY)
+        {
+            panic!(
+                "Stack overflow in thread {} (stack size: {} bytes)",
+                self.id,
+                self.stack_size()
+            );
+        }
+    }
+
+    /// Returns the priority of this thread
+    pub fn priority(&self) -> Priority {
+        self.prio
+    }
+
+    /// Sets the priority of this thread to `prio`.
+    ///
+    /// Whenever a thread is chosen to run next, ready threads with a higher priority are preferred
+    /// over ready threads with a lower priority. Threads with the same priority are scheduled in
+    /// FIFO order.
+    pub fn set_priority(&mut self, prio: Priority) {
+        assert!(prio <= MAX_PRIO);
+        self.prio = prio;
+    }
+
     pub fn fetch_msg(&mut self) -> Option<&'static tcu::Message> {
         if mem::replace(&mut self.has_msg, false) {
             // safety: has_msg is true and we trust the TCU
@@ -200,14 +316,18 @@ impl Thread {
         }
     }
 
-    fn subscribe(&mut self, event: Event) {
+    fn subscribe(&mut self, event: Event, deadline: Option<TimeInstant>) {
         assert!(self.event == 0);
         self.event = event;
+        self.deadline = deadline;
+        self.wait_res = Code::Success;
     }
 
-    fn trigger_event(&mut self, event: Event) -> bool {
+    fn trigger_event(&mut self, event: Event, res: Code) -> bool {
         if self.event == event {
             self.event = 0;
+            self.deadline = None;
+            self.wait_res = res;
             true
         }
         else {
@@ -237,12 +357,23 @@ impl Drop for Thread {
 
 struct ThreadManager {
     current: Option<Box<Thread>>,
-    ready: BoxList<Thread>,
+    // one ready list per priority
+    ready: Vec<BoxList<Thread>>,
     block: BoxList<Thread>,
     sleep: BoxList<Thread>,
+    // pool threads that have not run yet or have been parked while waiting for work
+    idle: BoxList<Thread>,
+    // the number of threads that are destroyed as soon as they are parked
+    surplus: usize,
+    // the last stopped thread, which cannot be destroyed while we are still running on its stack
+    zombie: Option<Box<Thread>>,
+    free_stacks: Vec<Vec<usize>>,
 }
 
 static TMNG: LazyStaticRefCell<ThreadManager> = LazyStaticRefCell::default();
+// notifications that have not been delivered to the blocked threads yet. in contrast to TMNG, the
+// queue can be filled without exclusive access to the thread manager.
+static PENDING_EVENTS: MpscQueue<(Event, Option<&'static Message>)> = MpscQueue::new();
 
 pub fn init() {
     TMNG.set(ThreadManager::new());
@@ -252,16 +383,41 @@ impl ThreadManager {
     fn new() -> Self {
         ThreadManager {
             current: Some(Thread::new_main()),
-            ready: BoxList::new(),
+            ready: (0..PRIO_COUNT).map(|_| BoxList::new()).collect(),
             block: BoxList::new(),
             sleep: BoxList::new(),
+            idle: BoxList::new(),
+            surplus: 0,
+            zombie: None,
+            free_stacks: Vec::new(),
+        }
+    }
+
+    fn create_thread(&mut self, func_addr: VirtAddr, arg: usize, stack_size: usize) -> Box<Thread> {
+        let words = stack_size / mem::size_of::<usize>();
+        match self.free_stacks.iter().position(|s| s.len() == words) {
+            Some(idx) => Thread::new_with_stack(func_addr, arg, self.free_stacks.swap_remove(idx)),
+            None => Thread::new_with_size(func_addr, arg, stack_size),
+        }
+    }
+
+    fn destroy_thread(&mut self, mut thread: Box<Thread>) {
+        if self.free_stacks.len() < MAX_CACHED_STACKS && !thread.is_main() {
+            self.free_stacks.push(core::mem::take(&mut thread.stack));
+        }
+    }
+
+    fn reap_zombie(&mut self) {
+        if let Some(t) = self.zombie.take() {
+            self.destroy_thread(t);
         }
     }
 
-    fn notify(&mut self, event: Event, msg: Option<&'static tcu::Message>) {
+    fn notify(&mut self, event: Event, msg: Option<&'static tcu::Message>, res: Code) -> usize {
+        let mut woken = 0;
         let mut it = self.block.iter_mut();
         while let Some(t) = it.next() {
-            if t.trigger_event(event) {
+            if t.trigger_event(event, res) {
                 if let Some(m) = msg {
                     t.set_msg(m);
                 }
@@ -271,19 +427,57 @@ impl ThreadManager {
                     t.id,
                     event
                 );
-                let t = it.remove();
-                self.ready.push_back(t.unwrap());
+                let t = it.remove().unwrap();
+                self.ready[t.prio as usize].push_back(t);
+                woken += 1;
             }
         }
+        woken
     }
 
-    fn get_next(&mut self) -> Option<Box<Thread>> {
-        if !self.ready.is_empty() {
-            self.ready.pop_front()
+    fn deliver_events(&mut self) {
+        while let Some((event, msg)) = PENDING_EVENTS.pop() {
+            self.notify(event, msg, Code::Success);
         }
-        else {
-            self.sleep.pop_front()
+    }
+
+    fn check_timeouts(&mut self, now: TimeInstant) -> usize {
+        let mut woken = 0;
+        let mut it = self.block.iter_mut();
+        while let Some(t) = it.next() {
+            if t.deadline.map(|d| d <= now).unwrap_or(false) {
+                log!(
+                    LogFlags::LibThread,
+                    "Waking up thread {} for event {:#x} due to timeout",
+                    t.id,
+                    t.event
+                );
+                let event = t.event;
+                t.trigger_event(event, Code::Timeout);
+                let t = it.remove().unwrap();
+                self.ready[t.prio as usize].push_back(t);
+                woken += 1;
+            }
         }
+        woken
+    }
+
+    fn next_deadline(&self) -> Option<TimeInstant> {
+        self.block.iter().filter_map(|t| t.deadline).min()
+    }
+
+    fn ready_count(&self) -> usize {
+        self.ready.iter().map(|l| l.len()).sum()
+    }
+
+    fn get_next_ready(&mut self) -> Option<Box<Thread>> {
+        self.ready.iter_mut().rev().find_map(|l| l.pop_front())
+    }
+
+    fn get_next(&mut self) -> Option<Box<Thread>> {
+        self.get_next_ready()
+            .or_else(|| self.sleep.pop_front())
+            .or_else(|| self.idle.pop_front())
     }
 }
 
@@ -293,11 +487,11 @@ pub fn cur() -> Ref<'static, Box<Thread>> {
 
 pub fn thread_count() -> usize {
     let tmng = TMNG.borrow();
-    tmng.ready.len() + tmng.block.len() + tmng.sleep.len()
+    tmng.ready_count() + tmng.block.len() + tmng.sleep.len() + tmng.idle.len()
 }
 
 pub fn ready_count() -> usize {
-    TMNG.borrow().ready.len()
+    TMNG.borrow().ready_count()
 }
 
 pub fn blocked_count() -> usize {
@@ -308,6 +502,23 @@ pub fn sleeping_count() -> usize {
     TMNG.borrow().sleep.len()
 }
 
+/// Returns the number of idle threads, that is, pool threads that have not run yet or have been
+/// parked via [`park`]
+pub fn idle_count() -> usize {
+    TMNG.borrow().idle.len()
+}
+
+/// Sets the priority of the current thread to `prio`
+///
+/// See [`Thread::set_priority`].
+pub fn set_priority(prio: Priority) {
+    TMNG.borrow_mut()
+        .current
+        .as_mut()
+        .unwrap()
+        .set_priority(prio);
+}
+
 pub fn fetch_msg() -> Option<&'static tcu::Message> {
     match TMNG.borrow_mut().current {
         Some(ref mut t) => t.fetch_msg(),
@@ -316,19 +527,72 @@ pub fn fetch_msg() -> Option<&'static tcu::Message> {
 }
 
 pub fn add_thread(func_addr: VirtAddr, arg: usize) {
-    TMNG.borrow_mut()
-        .sleep
-        .push_back(Thread::new(func_addr, arg));
+    spawn_pool(func_addr, arg, 1);
+}
+
+/// Like [`add_thread`], but uses a stack of `stack_size` bytes for the new thread.
+pub fn add_thread_with_stack(func_addr: VirtAddr, arg: usize, stack_size: usize) {
+    spawn_pool_with_stack(func_addr, arg, 1, stack_size);
 }
 
+/// Removes one thread from the pool.
+///
+/// If no thread can be destroyed right away (see [`shrink_pool`]), the next thread other than the
+/// main thread that is parked via [`park`] is destroyed instead.
 pub fn remove_thread() {
-    TMNG.borrow_mut().sleep.pop_front().unwrap();
+    if shrink_pool(1) == 0 {
+        TMNG.borrow_mut().surplus += 1;
+    }
+}
+
+/// Adds `count` new threads that start at `func_addr` with argument `arg`.
+///
+/// The new threads are idle until they are scheduled. Stacks of previously destroyed threads are
+/// reused if available. Pending removals via [`remove_thread`] are cancelled first, so that fewer
+/// threads are created.
+pub fn spawn_pool(func_addr: VirtAddr, arg: usize, count: usize) {
+    spawn_pool_with_stack(func_addr, arg, count, DEF_STACK_SIZE);
+}
+
+/// Like [`spawn_pool`], but uses stacks of `stack_size` bytes for the new threads.
+///
+/// The stack size needs to be at least [`MIN_STACK_SIZE`]. Overflows of the stack are detected on
+/// the next thread switch and result in a panic.
+pub fn spawn_pool_with_stack(func_addr: VirtAddr, arg: usize, count: usize, stack_size: usize) {
+    let mut tmng = TMNG.borrow_mut();
+    tmng.reap_zombie();
+    let kept = count.min(tmng.surplus);
+    tmng.surplus -= kept;
+    for _ in kept..count {
+        let t = tmng.create_thread(func_addr, arg, stack_size);
+        tmng.idle.push_back(t);
+    }
+}
+
+/// Destroys up to `count` idle threads and returns the number of destroyed threads.
+///
+/// Only pool threads that have not run yet or have been parked via [`park`] are destroyed. Threads
+/// that are ready, blocked on an event, or have yielded via [`try_yield`] might be in the middle of
+/// their work and are therefore never destroyed. The same holds for the main thread. The stacks of
+/// the destroyed threads are kept for reuse by [`spawn_pool`] up to a small limit.
+pub fn shrink_pool(count: usize) -> usize {
+    let mut tmng = TMNG.borrow_mut();
+    tmng.reap_zombie();
+    let mut destroyed = 0;
+    while destroyed < count {
+        match tmng.idle.remove_if(|t| !t.is_main()) {
+            Some(t) => tmng.destroy_thread(t),
+            None => break,
+        }
+        destroyed += 1;
+    }
+    destroyed
 }
 
 pub fn alloc_event() -> Event {
     static NEXT_EVENT: StaticCell<Event> = StaticCell::new(0);
     // if we have no other threads available, don't use events
-    if sleeping_count() == 0 {
+    if sleeping_count() == 0 && idle_count() == 0 {
         0
     }
     // otherwise, use a unique number
@@ -338,25 +602,89 @@ pub fn alloc_event() -> Event {
     }
 }
 
+/// Blocks the current thread until `event` is notified via [`notify`]
 pub fn wait_for(event: Event) {
-    let mut tmng = TMNG.borrow_mut();
-    let next = tmng.get_next().unwrap();
+    block(event, None);
+}
+
+/// Blocks the current thread until `event` is notified via [`notify`] or `timeout` has passed.
+///
+/// Expired timeouts are detected on every thread switch and by [`check_timeouts`]. Since threads
+/// cannot wake up a sleeping activity, the main loop of the activity should not sleep longer than
+/// [`next_timeout`] and call [`check_timeouts`] afterwards.
+///
+/// # Errors
+///
+/// Returns `Code::Timeout` if the event was not notified in time and `Code::Abort` if the wait was
+/// cancelled via [`cancel`].
+pub fn wait_for_timeout(event: Event, timeout: TimeDuration) -> Result<(), Error> {
+    block(event, Some(TimeInstant::now().saturating_add(timeout)));
+    match TMNG.borrow().current.as_ref().unwrap().wait_res {
+        Code::Success => Ok(()),
+        code => Err(Error::new(code)),
+    }
+}
+
+/// Cancels the wait for `event` and returns the number of woken threads.
+///
+/// All threads waiting for `event` are woken up without a message. Threads waiting via
+/// [`wait_for_timeout`] receive `Code::Abort`.
+pub fn cancel(event: Event) -> usize {
+    TMNG.borrow_mut().notify(event, None, Code::Abort)
+}
+
+/// Wakes up all threads whose timeout has passed and returns the number of woken threads
+pub fn check_timeouts() -> usize {
+    TMNG.borrow_mut().check_timeouts(TimeInstant::now())
+}
+
+/// Returns the time until the next timeout of a blocked thread, if any.
+///
+/// This can be used to limit the time the activity sleeps, so that timeouts are detected in time.
+pub fn next_timeout() -> Option<TimeDuration> {
+    let deadline = TMNG.borrow().next_deadline()?;
+    Some(
+        deadline
+            .checked_duration_since(TimeInstant::now())
+            .unwrap_or_default(),
+    )
+}
+
+fn block(event: Event, deadline: Option<TimeInstant>) {
+    cur().check_stack();
 
-    log!(
-        LogFlags::LibThread,
-        "Thread {} waits for {:#x}, switching to {}",
-        tmng.current.as_ref().unwrap().id,
-        event,
-        next.id
-    );
+    let mut tmng = TMNG.borrow_mut();
+    tmng.reap_zombie();
 
-    let mut cur = mem::replace(&mut tmng.current, Some(next)).unwrap();
-    cur.subscribe(event);
+    let mut cur = tmng.current.take().unwrap();
+    cur.subscribe(event, deadline);
 
     // safety: moving between two lists is fine
     unsafe {
         let old = Box::into_raw(cur);
         tmng.block.push_back(Box::from_raw(old));
+
+        // deliver pending events and timeouts only now that we are on the block list, so that we do
+        // not miss them for ourself
+        tmng.deliver_events();
+        tmng.check_timeouts(TimeInstant::now());
+        let next = tmng.get_next().unwrap();
+
+        // the event has already been notified and we are the next thread to run
+        if ptr::eq(&*next, old) {
+            tmng.current = Some(next);
+            return;
+        }
+
+        log!(
+            LogFlags::LibThread,
+            "Thread {} waits for {:#x}, switching to {}",
+            (*old).id,
+            event,
+            next.id
+        );
+
+        tmng.current = Some(next);
         let next_ptr = &mut tmng.current.as_mut().unwrap().regs as *mut _;
         drop(tmng);
 
@@ -364,13 +692,24 @@ pub fn wait_for(event: Event) {
     }
 }
 
+/// Wakes up all threads waiting for `event` and passes `msg` to them.
+///
+/// The notification is queued and delivered immediately afterwards together with all other queued
+/// notifications. Notifications that could not be delivered yet are delivered on the next thread
+/// switch.
 pub fn notify(event: Event, msg: Option<&'static Message>) {
-    TMNG.borrow_mut().notify(event, msg)
+    PENDING_EVENTS.push((event, msg));
+    TMNG.borrow_mut().deliver_events();
 }
 
 pub fn try_yield() {
+    cur().check_stack();
+
     let mut tmng = TMNG.borrow_mut();
-    match tmng.ready.pop_front() {
+    tmng.reap_zombie();
+    tmng.deliver_events();
+    tmng.check_timeouts(TimeInstant::now());
+    match tmng.get_next_ready() {
         None => {},
         Some(next) => {
             log!(
@@ -395,8 +734,65 @@ pub fn try_yield() {
     }
 }
 
+/// Parks the current thread until it is needed again, if another thread is ready to run.
+///
+/// In contrast to [`try_yield`], the current thread is put on the idle list. Thus, it is only
+/// resumed if no other thread is ready or has yielded, and it can be destroyed via [`shrink_pool`]
+/// in the meantime. Pool threads should therefore park at the point where they wait for new work.
+/// If a removal via [`remove_thread`] is pending, the current thread is destroyed instead, unless
+/// it is the main thread.
+pub fn park() {
+    cur().check_stack();
+
+    let mut tmng = TMNG.borrow_mut();
+    tmng.reap_zombie();
+    tmng.deliver_events();
+    tmng.check_timeouts(TimeInstant::now());
+
+    // a surplus thread does not need to wait for a ready thread, because it will not run again
+    let surplus = tmng.surplus > 0 && !tmng.current.as_ref().unwrap().is_main();
+    let next = if surplus {
+        tmng.get_next()
+    }
+    else {
+        tmng.get_next_ready()
+    };
+
+    if let Some(next) = next {
+        log!(
+            LogFlags::LibThread,
+            "{} thread {}, switching to {}",
+            if surplus { "Removing" } else { "Parking" },
+            tmng.current.as_ref().unwrap().id,
+            next.id
+        );
+
+        let cur = mem::replace(&mut tmng.current, Some(next)).unwrap();
+
+        // safety: moving between two lists is fine and the zombie is only destroyed after we
+        // switched away from it
+        unsafe {
+            let old = Box::into_raw(cur);
+            if surplus {
+                tmng.surplus -= 1;
+                tmng.zombie = Some(Box::from_raw(old));
+            }
+            else {
+                tmng.idle.push_back(Box::from_raw(old));
+            }
+            let next_ptr = &mut tmng.current.as_mut().unwrap().regs as *mut _;
+            drop(tmng);
+
+            thread_switch(&mut (*old).regs as *mut _, next_ptr);
+        }
+    }
+}
+
 pub fn stop() {
+    cur().check_stack();
+
     let mut tmng = TMNG.borrow_mut();
+    tmng.reap_zombie();
     if let Some(next) = tmng.get_next() {
         log!(
             LogFlags::LibThread,
@@ -405,13 +801,16 @@ pub fn stop() {
             next.id
         );
 
-        let mut cur = mem::replace(&mut tmng.current, Some(next)).unwrap();
-
-        let next_ptr = &mut tmng.current.as_mut().unwrap().regs as *mut _;
-        drop(tmng);
+        let cur = mem::replace(&mut tmng.current, Some(next)).unwrap();
 
+        // safety: the zombie is only destroyed after we switched away from it
         unsafe {
-            thread_switch(&mut cur.regs as *mut _, next_ptr);
+            let old = Box::into_raw(cur);
+            tmng.zombie = Some(Box::from_raw(old));
+            let next_ptr = &mut tmng.current.as_mut().unwrap().regs as *mut _;
+            drop(tmng);
+
+            thread_switch(&mut (*old).regs as *mut _, next_ptr);
         }
     }
 }
@@ -0,0 +1,163 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use core::time::Duration;
+
+use m3::boxed::Box;
+use m3::col::{ToString, Vec};
+use m3::errors::{Code, Error};
+use m3::net::TcpSocket;
+use m3::sync::Arc;
+use m3::time::TimeInstant;
+use m3::vfs::FileRef;
+
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::time_provider::TimeProvider;
+use rustls::{ClientConfig, RootCertStore};
+
+use crate::stream::TlsStream;
+use crate::tls_error;
+use crate::verify::{CertVerifier, Verifier};
+
+/// Provides the wall-clock time based on the time since boot and a given time at startup
+#[derive(Debug)]
+struct ClockTime {
+    // the unix time in seconds at boot
+    boot_secs: u64,
+}
+
+impl TimeProvider for ClockTime {
+    fn current_time(&self) -> Option<UnixTime> {
+        let since_boot = Duration::from_nanos(TimeInstant::now().as_nanos());
+        Some(UnixTime::since_unix_epoch(
+            Duration::from_secs(self.boot_secs) + since_boot,
+        ))
+    }
+}
+
+/// The configuration for TLS connections
+///
+/// The configuration is built via the builder-style methods and turned into a [`TlsConnector`]
+/// afterwards, which is used to establish the connections.
+pub struct TlsConfig {
+    roots: RootCertStore,
+    verifier: Option<Box<dyn CertVerifier>>,
+    alpn: Vec<Vec<u8>>,
+    boot_secs: u64,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TlsConfig {
+    /// Creates a new configuration without root certificates
+    pub fn new() -> Self {
+        Self {
+            roots: RootCertStore::empty(),
+            verifier: None,
+            alpn: Vec::new(),
+            boot_secs: 0,
+        }
+    }
+
+    /// Adds the given DER-encoded certificate as a trust anchor for the verification of server
+    /// certificates
+    pub fn root_cert(mut self, der: &[u8]) -> Result<Self, Error> {
+        self.roots
+            .add(CertificateDer::from(der.to_vec()))
+            .map_err(tls_error)?;
+        Ok(self)
+    }
+
+    /// Replaces the verification against the root certificates by the given verifier
+    pub fn verifier<V: CertVerifier + 'static>(mut self, verifier: V) -> Self {
+        self.verifier = Some(Box::new(verifier));
+        self
+    }
+
+    /// Adds the given protocol to the protocols offered via application-layer protocol
+    /// negotiation (e.g., `b"http/1.1"`)
+    pub fn alpn(mut self, proto: &[u8]) -> Self {
+        self.alpn.push(proto.to_vec());
+        self
+    }
+
+    /// Sets the current time in seconds since the unix epoch
+    ///
+    /// Since M³ has no real-time clock, the time needs to be provided by the application to check
+    /// the validity period of certificates.
+    pub fn unix_time(mut self, secs: u64) -> Self {
+        let since_boot = TimeInstant::now().as_nanos() / 1_000_000_000;
+        self.boot_secs = secs.saturating_sub(since_boot);
+        self
+    }
+}
+
+/// Establishes TLS connections based on a [`TlsConfig`]
+pub struct TlsConnector {
+    config: Arc<ClientConfig>,
+}
+
+impl TlsConnector {
+    /// Creates a new connector with given configuration
+    pub fn new(config: TlsConfig) -> Result<Self, Error> {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let time = Arc::new(ClockTime {
+            boot_secs: config.boot_secs,
+        });
+
+        let builder = ClientConfig::builder_with_details(provider.clone(), time)
+            .with_safe_default_protocol_versions()
+            .map_err(tls_error)?;
+
+        let builder = match config.verifier {
+            Some(v) => {
+                let algos = provider.signature_verification_algorithms;
+                builder
+                    .dangerous()
+                    .with_custom_certificate_verifier(Arc::new(Verifier::new(v, algos)))
+            },
+            None => {
+                if config.roots.is_empty() {
+                    return Err(Error::new(Code::InvArgs));
+                }
+                builder.with_root_certificates(config.roots)
+            },
+        };
+
+        let mut client = builder.with_no_client_auth();
+        client.alpn_protocols = config.alpn;
+        Ok(Self {
+            config: Arc::new(client),
+        })
+    }
+
+    /// Performs the TLS handshake with the server `server_name` via the given connected socket.
+    ///
+    /// The server name is used for the verification of the certificate and sent to the server
+    /// via the server name indication. The handshake is always performed in blocking mode.
+    pub fn connect(
+        &self,
+        socket: FileRef<TcpSocket>,
+        server_name: &str,
+    ) -> Result<FileRef<TlsStream>, Error> {
+        let name =
+            ServerName::try_from(server_name.to_string()).map_err(|_| Error::new(Code::InvArgs))?;
+        TlsStream::new(self.config.clone(), socket, name)
+    }
+}
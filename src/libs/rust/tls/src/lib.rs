@@ -0,0 +1,56 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! TLS client support for M³ applications
+//!
+//! This crate layers TLS (1.2 and 1.3) on top of [`TcpSocket`](m3::net::TcpSocket) by using the
+//! unbuffered API of rustls. A connection is established via [`TlsConnector::connect`], which
+//! performs the handshake and returns a [`TlsStream`]. Since the stream implements
+//! [`File`](m3::vfs::File), it can be used like any other file afterwards (e.g., read, written,
+//! and waited for with a [`FileWaiter`](m3::vfs::FileWaiter)).
+//!
+//! By default, the server certificate is verified against the root certificates given to
+//! [`TlsConfig`]. Note that M³ has no real-time clock, so that the current time needs to be set
+//! via [`TlsConfig::unix_time`] for this verification. Alternatively, the verification can be
+//! replaced by a custom [`CertVerifier`] such as the [`PinnedCertVerifier`], which accepts only
+//! certificates with known SHA3-256 fingerprints and uses the hash multiplexer to compute them.
+
+#![no_std]
+
+mod config;
+mod stream;
+mod verify;
+
+pub use config::{TlsConfig, TlsConnector};
+pub use stream::TlsStream;
+pub use verify::{CertVerifier, PinnedCertVerifier};
+
+use m3::errors::{Code, Error};
+use m3::io::LogFlags;
+use m3::log;
+
+/// Converts the given rustls error into an M³ error
+pub(crate) fn tls_error(e: rustls::Error) -> Error {
+    log!(LogFlags::LibTls, "tls: {}", e);
+    Error::new(match e {
+        rustls::Error::InvalidCertificate(_)
+        | rustls::Error::NoCertificatesPresented
+        | rustls::Error::UnsupportedNameType => Code::NoPerm,
+        rustls::Error::AlertReceived(_) | rustls::Error::PeerIncompatible(_) => {
+            Code::ConnectionFailed
+        },
+        _ => Code::InvState,
+    })
+}
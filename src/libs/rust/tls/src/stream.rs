@@ -0,0 +1,392 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use core::any::Any;
+use core::fmt;
+
+use m3::boxed::Box;
+use m3::client::{HashInput, HashOutput};
+use m3::col::{Vec, VecDeque};
+use m3::errors::{Code, Error};
+use m3::io::{self, LogFlags};
+use m3::log;
+use m3::net::{Socket, StreamSocket, TcpSocket};
+use m3::sync::Arc;
+use m3::tiles::Activity;
+use m3::vec;
+use m3::vfs::{self, Fd, File, FileEvent, FileRef, INV_FD};
+
+use rustls::client::UnbufferedClientConnection;
+use rustls::pki_types::ServerName;
+use rustls::unbuffered::{ConnectionState, EncodeError, EncryptError, UnbufferedStatus};
+use rustls::ClientConfig;
+
+use crate::tls_error;
+
+/// The size of the buffer for received TLS records (a record has at most 16 KiB plus overhead)
+const INCOMING_SIZE: usize = 18 * 1024;
+/// The maximum amount of application data that is encrypted at once
+const MAX_FRAGMENT: usize = 16 * 1024;
+
+// the result of processing the TLS records
+enum Progress {
+    // more data from the server is required
+    NeedData,
+    // the connection is established and the given number of bytes has been encrypted
+    Ready(usize),
+    // the connection has been closed by the server
+    Closed,
+}
+
+/// A TLS connection to a server on top of a [`TcpSocket`]
+///
+/// The stream is created via [`TlsConnector::connect`](crate::TlsConnector::connect) and can be
+/// used as a [`File`] afterwards. In non-blocking mode, encrypted data that cannot be sent
+/// immediately is kept in the stream and sent on the next operation.
+pub struct TlsStream {
+    fd: Fd,
+    socket: FileRef<TcpSocket>,
+    conn: UnbufferedClientConnection,
+    incoming: Vec<u8>,
+    in_len: usize,
+    outgoing: Vec<u8>,
+    out_len: usize,
+    plain: VecDeque<u8>,
+    eof: bool,
+}
+
+impl TlsStream {
+    pub(crate) fn new(
+        config: Arc<ClientConfig>,
+        socket: FileRef<TcpSocket>,
+        name: ServerName<'static>,
+    ) -> Result<FileRef<Self>, Error> {
+        let conn = UnbufferedClientConnection::new(config, name).map_err(tls_error)?;
+        let mut stream = Box::new(TlsStream {
+            fd: INV_FD,
+            socket,
+            conn,
+            incoming: vec![0; INCOMING_SIZE],
+            in_len: 0,
+            outgoing: Vec::new(),
+            out_len: 0,
+            plain: VecDeque::new(),
+            eof: false,
+        });
+
+        let blocking = stream.socket.is_blocking();
+        stream.socket.set_blocking(true)?;
+        let res = stream.handshake();
+        stream.socket.set_blocking(blocking)?;
+        res?;
+
+        let fd = Activity::own().files().add(stream)?;
+        Ok(FileRef::new_owned(fd))
+    }
+
+    /// Sends the close-notify alert to the server and closes the underlying socket
+    pub fn close(&mut self) -> Result<(), Error> {
+        let UnbufferedStatus { discard, state } = self
+            .conn
+            .process_tls_records(&mut self.incoming[..self.in_len]);
+        if let Ok(ConnectionState::WriteTraffic(mut traffic)) = state {
+            loop {
+                match traffic.queue_close_notify(&mut self.outgoing[self.out_len..]) {
+                    Ok(len) => {
+                        self.out_len += len;
+                        break;
+                    },
+                    Err(EncryptError::InsufficientSize(e)) => {
+                        self.outgoing.resize(self.out_len + e.required_size, 0);
+                    },
+                    Err(_) => break,
+                }
+            }
+        }
+        self.discard(discard);
+
+        self.flush_pending()?;
+        self.socket.close()
+    }
+
+    fn handshake(&mut self) -> Result<(), Error> {
+        loop {
+            match self.process(&[])? {
+                Progress::Ready(_) => break Ok(()),
+                Progress::NeedData => {
+                    if self.fill()? == 0 {
+                        break Err(Error::new(Code::ConnectionFailed));
+                    }
+                },
+                Progress::Closed => break Err(Error::new(Code::ConnectionFailed)),
+            }
+        }
+    }
+
+    /// Processes the received TLS records until more data is needed or the connection is ready
+    /// to encrypt application data, in which case `data` is encrypted and sent
+    fn process(&mut self, data: &[u8]) -> Result<Progress, Error> {
+        loop {
+            let UnbufferedStatus { mut discard, state } = self
+                .conn
+                .process_tls_records(&mut self.incoming[..self.in_len]);
+
+            let res = match state.map_err(tls_error)? {
+                ConnectionState::ReadTraffic(mut traffic) => {
+                    while let Some(rec) = traffic.next_record() {
+                        let rec = rec.map_err(tls_error)?;
+                        discard += rec.discard;
+                        self.plain.extend(rec.payload);
+                    }
+                    None
+                },
+
+                ConnectionState::EncodeTlsData(mut enc) => {
+                    loop {
+                        match enc.encode(&mut self.outgoing[self.out_len..]) {
+                            Ok(len) => {
+                                self.out_len += len;
+                                break;
+                            },
+                            Err(EncodeError::InsufficientSize(e)) => {
+                                self.outgoing.resize(self.out_len + e.required_size, 0);
+                            },
+                            Err(e) => {
+                                log!(LogFlags::LibTls, "tls: encoding failed: {}", e);
+                                return Err(Error::new(Code::InvState));
+                            },
+                        }
+                    }
+                    None
+                },
+
+                ConnectionState::TransmitTlsData(tx) => {
+                    Self::send_pending(&mut self.socket, &mut self.outgoing, &mut self.out_len)?;
+                    tx.done();
+                    None
+                },
+
+                ConnectionState::BlockedHandshake => Some(Progress::NeedData),
+
+                ConnectionState::WriteTraffic(mut traffic) => {
+                    let amount = data.len().min(MAX_FRAGMENT);
+                    if amount > 0 {
+                        // don't accept more data until the previous data has been sent
+                        Self::send_pending(
+                            &mut self.socket,
+                            &mut self.outgoing,
+                            &mut self.out_len,
+                        )?;
+                        if self.out_len > 0 {
+                            return Err(Error::new(Code::WouldBlock));
+                        }
+
+                        loop {
+                            match traffic.encrypt(&data[..amount], &mut self.outgoing[..]) {
+                                Ok(len) => {
+                                    self.out_len = len;
+                                    break;
+                                },
+                                Err(EncryptError::InsufficientSize(e)) => {
+                                    self.outgoing.resize(e.required_size, 0);
+                                },
+                                Err(e) => {
+                                    log!(LogFlags::LibTls, "tls: encryption failed: {}", e);
+                                    return Err(Error::new(Code::InvState));
+                                },
+                            }
+                        }
+                        Self::send_pending(
+                            &mut self.socket,
+                            &mut self.outgoing,
+                            &mut self.out_len,
+                        )?;
+                    }
+                    Some(Progress::Ready(amount))
+                },
+
+                // the server will not send further data
+                ConnectionState::Closed => {
+                    self.eof = true;
+                    Some(Progress::Closed)
+                },
+
+                // early data is only received by servers
+                _ => return Err(Error::new(Code::NotSup)),
+            };
+
+            self.discard(discard);
+            if let Some(progress) = res {
+                return Ok(progress);
+            }
+        }
+    }
+
+    /// Receives TLS records from the socket and returns the number of received bytes (0 if the
+    /// connection was closed)
+    fn fill(&mut self) -> Result<usize, Error> {
+        if self.in_len == self.incoming.len() {
+            return Err(Error::new(Code::NoSpace));
+        }
+
+        match self.socket.recv(&mut self.incoming[self.in_len..]) {
+            Ok(len) => {
+                self.in_len += len;
+                Ok(len)
+            },
+            Err(e) if e.code() == Code::SocketClosed || e.code() == Code::NotConnected => Ok(0),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Sends as much of the pending encrypted data as possible without blocking in non-blocking
+    /// mode
+    fn send_pending(
+        socket: &mut FileRef<TcpSocket>,
+        outgoing: &mut [u8],
+        out_len: &mut usize,
+    ) -> Result<(), Error> {
+        while *out_len > 0 {
+            match socket.send(&outgoing[..*out_len]) {
+                Ok(sent) => {
+                    outgoing.copy_within(sent..*out_len, 0);
+                    *out_len -= sent;
+                },
+                Err(e) if e.code() == Code::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    fn flush_pending(&mut self) -> Result<(), Error> {
+        Self::send_pending(&mut self.socket, &mut self.outgoing, &mut self.out_len)
+    }
+
+    fn discard(&mut self, amount: usize) {
+        self.incoming.copy_within(amount..self.in_len, 0);
+        self.in_len -= amount;
+    }
+}
+
+impl File for TlsStream {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn fd(&self) -> Fd {
+        self.fd
+    }
+
+    fn set_fd(&mut self, fd: Fd) {
+        self.fd = fd;
+    }
+
+    fn file_type(&self) -> u8 {
+        // not supported
+        b'\0'
+    }
+
+    fn is_blocking(&self) -> bool {
+        self.socket.is_blocking()
+    }
+
+    fn set_blocking(&mut self, blocking: bool) -> Result<(), Error> {
+        self.socket.set_blocking(blocking)
+    }
+
+    fn check_events(&mut self, events: FileEvent) -> bool {
+        (events.contains(FileEvent::INPUT) && !self.plain.is_empty())
+            || self.socket.check_events(events)
+    }
+}
+
+impl io::Read for TlsStream {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        loop {
+            if !self.plain.is_empty() {
+                let amount = buf.len().min(self.plain.len());
+                for (dst, src) in buf.iter_mut().zip(self.plain.drain(..amount)) {
+                    *dst = src;
+                }
+                return Ok(amount);
+            }
+            if self.eof {
+                return Ok(0);
+            }
+
+            match self.process(&[])? {
+                Progress::Closed => return Ok(0),
+                // we might have received application data; otherwise we need more records
+                _ if !self.plain.is_empty() || self.eof => {},
+                _ => {
+                    self.flush_pending()?;
+                    if self.fill()? == 0 {
+                        self.eof = true;
+                    }
+                },
+            }
+        }
+    }
+}
+
+impl io::Write for TlsStream {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        loop {
+            match self.process(buf)? {
+                Progress::Ready(amount) => break Ok(amount),
+                Progress::NeedData => {
+                    if self.fill()? == 0 {
+                        break Err(Error::new(Code::SocketClosed));
+                    }
+                },
+                Progress::Closed => break Err(Error::new(Code::SocketClosed)),
+            }
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.flush_pending()
+    }
+}
+
+impl vfs::Seek for TlsStream {
+}
+
+impl vfs::Map for TlsStream {
+}
+
+impl HashInput for TlsStream {
+}
+
+impl HashOutput for TlsStream {
+}
+
+impl fmt::Debug for TlsStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "TlsStream[socket={:?}]", self.socket)
+    }
+}
+
+impl Drop for TlsStream {
+    fn drop(&mut self) {
+        // ignore errors; the socket is aborted anyway, if the close did not succeed
+        self.close().ok();
+    }
+}
@@ -0,0 +1,200 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use core::fmt;
+
+use m3::boxed::Box;
+use m3::cell::RefCell;
+use m3::client::HashSession;
+use m3::col::{ToString, Vec};
+use m3::com::{MemCap, MemGate};
+use m3::crypto::HashAlgorithm;
+use m3::errors::{Code, Error};
+use m3::format;
+use m3::io::LogFlags;
+use m3::kif::Perm;
+use m3::log;
+use m3::mem::GlobOff;
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{self, WebPkiSupportedAlgorithms};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{CertificateError, DigitallySignedStruct, SignatureScheme};
+
+/// The maximum size of a certificate that the [`PinnedCertVerifier`] can hash
+const MAX_CERT_SIZE: usize = 16 * 1024;
+
+/// Hook to verify the certificate of the server
+///
+/// The verifier decides whether the certificate chain presented by the server is trusted for the
+/// given server name. The signatures of the handshake are still checked by the TLS library based
+/// on the public key of the end-entity certificate.
+pub trait CertVerifier {
+    /// Verifies the DER-encoded certificate `end_entity` of the server `server_name`.
+    ///
+    /// `intermediates` contains the remaining certificates that were sent by the server.
+    fn verify(
+        &self,
+        server_name: &str,
+        end_entity: &[u8],
+        intermediates: &[&[u8]],
+    ) -> Result<(), Error>;
+}
+
+/// A [`CertVerifier`] that accepts only certificates with known fingerprints
+///
+/// The fingerprint is the SHA3-256 hash of the DER-encoded end-entity certificate, which is
+/// computed by the hash multiplexer. Since the certificate is trusted as is, neither the server
+/// name nor the validity period is checked.
+pub struct PinnedCertVerifier {
+    hash: RefCell<HashSession>,
+    mem: MemGate,
+    _dev_mem: MemCap,
+    pins: Vec<[u8; HashAlgorithm::SHA3_256.output_bytes]>,
+}
+
+impl PinnedCertVerifier {
+    /// Creates a new verifier that uses the hash multiplexer session with given name
+    pub fn new(hash_sess: &str) -> Result<Self, Error> {
+        let hash = HashSession::new(hash_sess, &HashAlgorithm::SHA3_256)?;
+        let mem = MemGate::new(MAX_CERT_SIZE as GlobOff, Perm::RW)?;
+        let dev_mem = mem.derive_cap(0, MAX_CERT_SIZE as GlobOff, Perm::R)?;
+        hash.ep().configure(dev_mem.sel())?;
+
+        Ok(Self {
+            hash: RefCell::new(hash),
+            mem,
+            _dev_mem: dev_mem,
+            pins: Vec::new(),
+        })
+    }
+
+    /// Adds the given fingerprint to the list of accepted certificates
+    pub fn pin(&mut self, fingerprint: [u8; HashAlgorithm::SHA3_256.output_bytes]) {
+        self.pins.push(fingerprint);
+    }
+
+    /// Computes the fingerprint of the given DER-encoded certificate
+    pub fn fingerprint(
+        &self,
+        cert: &[u8],
+    ) -> Result<[u8; HashAlgorithm::SHA3_256.output_bytes], Error> {
+        if cert.len() > MAX_CERT_SIZE {
+            return Err(Error::new(Code::OutOfBounds));
+        }
+
+        let mut hash = self.hash.borrow_mut();
+        hash.reset(&HashAlgorithm::SHA3_256)?;
+        self.mem.write(cert, 0)?;
+        hash.input(0, cert.len())?;
+
+        let mut res = [0u8; HashAlgorithm::SHA3_256.output_bytes];
+        hash.finish(&mut res)?;
+        Ok(res)
+    }
+}
+
+impl CertVerifier for PinnedCertVerifier {
+    fn verify(
+        &self,
+        server_name: &str,
+        end_entity: &[u8],
+        _intermediates: &[&[u8]],
+    ) -> Result<(), Error> {
+        let fp = self.fingerprint(end_entity)?;
+        if !self.pins.contains(&fp) {
+            log!(
+                LogFlags::LibTls,
+                "tls: certificate of {} has unknown fingerprint {:x?}",
+                server_name,
+                fp
+            );
+            return Err(Error::new(Code::NoPerm));
+        }
+        Ok(())
+    }
+}
+
+/// Adapter between [`CertVerifier`] and the verifier interface of rustls
+pub(crate) struct Verifier {
+    inner: Box<dyn CertVerifier>,
+    algos: WebPkiSupportedAlgorithms,
+}
+
+// the verifier is only used by the activity that created it, which is single threaded
+unsafe impl Send for Verifier {
+}
+unsafe impl Sync for Verifier {
+}
+
+impl Verifier {
+    pub fn new(inner: Box<dyn CertVerifier>, algos: WebPkiSupportedAlgorithms) -> Self {
+        Self { inner, algos }
+    }
+}
+
+impl ServerCertVerifier for Verifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let name = match server_name {
+            ServerName::DnsName(name) => name.as_ref().to_string(),
+            name => format!("{:?}", name),
+        };
+        let intermediates = intermediates.iter().map(|c| c.as_ref()).collect::<Vec<_>>();
+        match self.inner.verify(&name, end_entity, &intermediates) {
+            Ok(_) => Ok(ServerCertVerified::assertion()),
+            Err(e) if e.code() == Code::NoPerm => Err(rustls::Error::InvalidCertificate(
+                CertificateError::ApplicationVerificationFailure,
+            )),
+            Err(_) => Err(rustls::Error::General(
+                "certificate verification failed".into(),
+            )),
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls12_signature(message, cert, dss, &self.algos)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls13_signature(message, cert, dss, &self.algos)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.algos.supported_schemes()
+    }
+}
+
+impl fmt::Debug for Verifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Verifier")
+    }
+}
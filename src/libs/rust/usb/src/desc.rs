@@ -0,0 +1,257 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! Standard USB requests and descriptors
+
+use m3::col::Vec;
+use m3::errors::{Code, Error};
+
+/// The direction of a transfer from the host's point of view
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Direction {
+    Out,
+    In,
+}
+
+// bits in bmRequestType
+pub const REQ_DIR_IN: u8 = 1 << 7;
+pub const REQ_TYPE_CLASS: u8 = 1 << 5;
+pub const REQ_RECIP_INTERFACE: u8 = 1;
+pub const REQ_RECIP_ENDPOINT: u8 = 2;
+
+// standard requests
+pub const REQ_CLEAR_FEATURE: u8 = 0x01;
+pub const REQ_GET_DESCRIPTOR: u8 = 0x06;
+pub const REQ_SET_CONFIGURATION: u8 = 0x09;
+
+// feature selector for REQ_CLEAR_FEATURE
+pub const FEATURE_ENDPOINT_HALT: u16 = 0;
+
+pub const DESC_DEVICE: u8 = 1;
+pub const DESC_CONFIG: u8 = 2;
+pub const DESC_INTERFACE: u8 = 4;
+pub const DESC_ENDPOINT: u8 = 5;
+
+pub const DEVICE_DESC_SIZE: usize = 18;
+pub const CONFIG_DESC_SIZE: usize = 9;
+
+/// The setup packet of a control transfer
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SetupPacket {
+    pub request_type: u8,
+    pub request: u8,
+    pub value: u16,
+    pub index: u16,
+    pub length: u16,
+}
+
+impl SetupPacket {
+    /// Creates a GET_DESCRIPTOR request for the descriptor with given type and index
+    pub fn get_descriptor(ty: u8, idx: u8, length: u16) -> Self {
+        Self {
+            request_type: REQ_DIR_IN,
+            request: REQ_GET_DESCRIPTOR,
+            value: (ty as u16) << 8 | idx as u16,
+            index: 0,
+            length,
+        }
+    }
+
+    /// Creates a SET_CONFIGURATION request
+    pub fn set_configuration(value: u8) -> Self {
+        Self {
+            request_type: 0,
+            request: REQ_SET_CONFIGURATION,
+            value: value as u16,
+            index: 0,
+            length: 0,
+        }
+    }
+
+    /// Creates a CLEAR_FEATURE(ENDPOINT_HALT) request for the endpoint with given address
+    pub fn clear_halt(ep_addr: u8) -> Self {
+        Self {
+            request_type: REQ_RECIP_ENDPOINT,
+            request: REQ_CLEAR_FEATURE,
+            value: FEATURE_ENDPOINT_HALT,
+            index: ep_addr as u16,
+            length: 0,
+        }
+    }
+
+    /// Returns the direction of the data stage
+    pub fn direction(&self) -> Direction {
+        match self.request_type & REQ_DIR_IN {
+            0 => Direction::Out,
+            _ => Direction::In,
+        }
+    }
+
+    /// Returns the packet in the form it is sent on the bus
+    pub fn to_raw(&self) -> u64 {
+        self.request_type as u64
+            | (self.request as u64) << 8
+            | (self.value as u64) << 16
+            | (self.index as u64) << 32
+            | (self.length as u64) << 48
+    }
+}
+
+fn read_u16(bytes: &[u8], off: usize) -> u16 {
+    u16::from_le_bytes([bytes[off], bytes[off + 1]])
+}
+
+/// The device descriptor
+#[derive(Copy, Clone, Debug, Default)]
+pub struct DeviceDesc {
+    pub usb_version: u16,
+    pub class: u8,
+    pub subclass: u8,
+    pub protocol: u8,
+    pub max_packet_size0: u8,
+    pub vendor: u16,
+    pub product: u16,
+    pub num_configs: u8,
+}
+
+impl DeviceDesc {
+    /// Parses the device descriptor from given bytes
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() < DEVICE_DESC_SIZE || bytes[1] != DESC_DEVICE {
+            return Err(Error::new(Code::InvArgs));
+        }
+
+        Ok(Self {
+            usb_version: read_u16(bytes, 2),
+            class: bytes[4],
+            subclass: bytes[5],
+            protocol: bytes[6],
+            max_packet_size0: bytes[7],
+            vendor: read_u16(bytes, 8),
+            product: read_u16(bytes, 10),
+            num_configs: bytes[17],
+        })
+    }
+}
+
+/// An endpoint descriptor
+#[derive(Copy, Clone, Debug, Default)]
+pub struct EndpointDesc {
+    /// The endpoint number and direction (bit 7)
+    pub address: u8,
+    pub attributes: u8,
+    pub max_packet_size: u16,
+    pub interval: u8,
+}
+
+impl EndpointDesc {
+    pub const TYPE_BULK: u8 = 2;
+    pub const TYPE_INTERRUPT: u8 = 3;
+
+    /// Returns the endpoint number
+    pub fn number(&self) -> u8 {
+        self.address & 0xF
+    }
+
+    /// Returns the direction of the endpoint
+    pub fn direction(&self) -> Direction {
+        match self.address & 0x80 {
+            0 => Direction::Out,
+            _ => Direction::In,
+        }
+    }
+
+    /// Returns the transfer type (control, isochronous, bulk, or interrupt)
+    pub fn transfer_type(&self) -> u8 {
+        self.attributes & 0x3
+    }
+}
+
+/// An interface descriptor with its endpoints
+#[derive(Clone, Debug, Default)]
+pub struct InterfaceDesc {
+    pub number: u8,
+    pub alt_setting: u8,
+    pub class: u8,
+    pub subclass: u8,
+    pub protocol: u8,
+    pub endpoints: Vec<EndpointDesc>,
+}
+
+/// A configuration descriptor with its interfaces
+#[derive(Clone, Debug, Default)]
+pub struct ConfigDesc {
+    pub value: u8,
+    pub attributes: u8,
+    pub interfaces: Vec<InterfaceDesc>,
+}
+
+impl ConfigDesc {
+    /// Returns the total length of the configuration, given its first [`CONFIG_DESC_SIZE`] bytes
+    pub fn total_length(bytes: &[u8]) -> Result<usize, Error> {
+        if bytes.len() < CONFIG_DESC_SIZE || bytes[1] != DESC_CONFIG {
+            return Err(Error::new(Code::InvArgs));
+        }
+        Ok(read_u16(bytes, 2) as usize)
+    }
+
+    /// Parses the configuration descriptor including all interface and endpoint descriptors
+    ///
+    /// Other descriptors (e.g., class-specific ones) are skipped.
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        let total = Self::total_length(bytes)?.min(bytes.len());
+        let mut cfg = Self {
+            value: bytes[5],
+            attributes: bytes[7],
+            interfaces: Vec::new(),
+        };
+
+        let mut off = bytes[0] as usize;
+        while off + 2 <= total {
+            let len = bytes[off] as usize;
+            if len < 2 || off + len > total {
+                return Err(Error::new(Code::InvArgs));
+            }
+
+            let desc = &bytes[off..off + len];
+            match desc[1] {
+                DESC_INTERFACE if len >= 9 => cfg.interfaces.push(InterfaceDesc {
+                    number: desc[2],
+                    alt_setting: desc[3],
+                    class: desc[5],
+                    subclass: desc[6],
+                    protocol: desc[7],
+                    endpoints: Vec::new(),
+                }),
+                DESC_ENDPOINT if len >= 7 => {
+                    // endpoints belong to the last interface
+                    let intf = cfg
+                        .interfaces
+                        .last_mut()
+                        .ok_or_else(|| Error::new(Code::InvArgs))?;
+                    intf.endpoints.push(EndpointDesc {
+                        address: desc[2],
+                        attributes: desc[3],
+                        max_packet_size: read_u16(desc, 4) & 0x7FF,
+                        interval: desc[6],
+                    });
+                },
+                _ => {},
+            }
+            off += len;
+        }
+        Ok(cfg)
+    }
+}
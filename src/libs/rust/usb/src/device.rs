@@ -0,0 +1,121 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use crate::desc::{ConfigDesc, DeviceDesc, InterfaceDesc};
+
+/// The speed of a USB device
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Speed {
+    Full,
+    Low,
+    High,
+    Super,
+}
+
+impl Speed {
+    /// Returns the speed for given protocol speed id of the xHCI
+    pub(crate) fn from_id(id: u32) -> Option<Self> {
+        match id {
+            1 => Some(Self::Full),
+            2 => Some(Self::Low),
+            3 => Some(Self::High),
+            // treat SuperSpeedPlus like SuperSpeed
+            4 | 5 => Some(Self::Super),
+            _ => None,
+        }
+    }
+
+    /// Returns the protocol speed id of the xHCI
+    pub(crate) fn id(self) -> u32 {
+        match self {
+            Self::Full => 1,
+            Self::Low => 2,
+            Self::High => 3,
+            Self::Super => 4,
+        }
+    }
+
+    /// Returns the maximum packet size of the default control endpoint to use until the device
+    /// descriptor is known
+    pub(crate) fn default_max_packet0(self) -> u16 {
+        match self {
+            Self::Full | Self::Low => 8,
+            Self::High => 64,
+            Self::Super => 512,
+        }
+    }
+}
+
+/// A device that is attached to a root hub port and has been addressed
+#[derive(Clone, Debug)]
+pub struct UsbDevice {
+    slot: u8,
+    port: u8,
+    speed: Speed,
+    desc: DeviceDesc,
+    config: ConfigDesc,
+}
+
+impl UsbDevice {
+    pub(crate) fn new(
+        slot: u8,
+        port: u8,
+        speed: Speed,
+        desc: DeviceDesc,
+        config: ConfigDesc,
+    ) -> Self {
+        Self {
+            slot,
+            port,
+            speed,
+            desc,
+            config,
+        }
+    }
+
+    /// Returns the slot id the controller has assigned to the device
+    pub fn slot(&self) -> u8 {
+        self.slot
+    }
+
+    /// Returns the root hub port the device is attached to
+    pub fn port(&self) -> u8 {
+        self.port
+    }
+
+    pub fn speed(&self) -> Speed {
+        self.speed
+    }
+
+    /// Returns the device descriptor
+    pub fn descriptor(&self) -> &DeviceDesc {
+        &self.desc
+    }
+
+    /// Returns the first configuration of the device
+    pub fn config(&self) -> &ConfigDesc {
+        &self.config
+    }
+
+    /// Returns the first interface with given class, subclass, and protocol
+    pub fn find_interface(&self, class: u8, subclass: u8, protocol: u8) -> Option<&InterfaceDesc> {
+        self.config.interfaces.iter().find(|i| {
+            i.alt_setting == 0
+                && i.class == class
+                && i.subclass == subclass
+                && i.protocol == protocol
+        })
+    }
+}
@@ -0,0 +1,35 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! A minimal USB host stack
+//!
+//! This crate contains a driver for xHCI host controllers, which is accessed via the PCI device
+//! tile, and the USB core functionality on top of it: enumeration of the devices that are attached
+//! to the root hub ports, and control and bulk transfers. Class drivers build on these transfers;
+//! currently, there is a driver for mass-storage devices that use the bulk-only transport.
+
+#![no_std]
+
+pub mod desc;
+mod device;
+mod msc;
+mod xhci;
+
+pub use device::{Speed, UsbDevice};
+pub use msc::MassStorage;
+pub use xhci::XhciController;
+
+/// The TRB rings of the xHCI driver, which are independent of the controller
+pub use xhci::ring;
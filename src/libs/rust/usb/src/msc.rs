@@ -0,0 +1,324 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! Mass-storage class driver (bulk-only transport with the SCSI command set)
+
+use m3::errors::{Code, Error};
+use m3::io::LogFlags;
+use m3::log;
+use m3::tiles::OwnActivity;
+use m3::time::TimeDuration;
+
+use crate::desc::{Direction, EndpointDesc, SetupPacket, REQ_RECIP_INTERFACE, REQ_TYPE_CLASS};
+use crate::device::UsbDevice;
+use crate::xhci::XhciController;
+
+const CLASS_MASS_STORAGE: u8 = 0x08;
+const SUBCLASS_SCSI: u8 = 0x06;
+const PROTOCOL_BULK_ONLY: u8 = 0x50;
+
+// the bulk-only mass storage reset request
+const REQ_BOT_RESET: u8 = 0xFF;
+
+const CBW_SIGNATURE: u32 = 0x4342_5355;
+const CBW_SIZE: usize = 31;
+const CBW_FLAG_IN: u8 = 0x80;
+
+const CSW_SIGNATURE: u32 = 0x5342_5355;
+const CSW_SIZE: usize = 13;
+const CSW_PASSED: u8 = 0;
+const CSW_FAILED: u8 = 1;
+
+const SCSI_TEST_UNIT_READY: u8 = 0x00;
+const SCSI_REQUEST_SENSE: u8 = 0x03;
+const SCSI_READ_CAPACITY10: u8 = 0x25;
+const SCSI_READ10: u8 = 0x28;
+const SCSI_WRITE10: u8 = 0x2A;
+
+const SENSE_SIZE: usize = 18;
+
+// devices need some time to spin up after they have been configured
+const READY_RETRIES: usize = 10;
+const READY_DELAY: TimeDuration = TimeDuration::from_millis(100);
+
+/// A storage device that uses the bulk-only transport
+///
+/// Only logical unit 0 is supported.
+pub struct MassStorage {
+    slot: u8,
+    interface: u8,
+    ep_in: u8,
+    ep_out: u8,
+    tag: u32,
+    block_size: usize,
+    block_count: u64,
+    max_transfer: usize,
+}
+
+impl MassStorage {
+    /// Returns true if the given device is supported by this driver
+    pub fn is_supported(dev: &UsbDevice) -> bool {
+        dev.find_interface(CLASS_MASS_STORAGE, SUBCLASS_SCSI, PROTOCOL_BULK_ONLY)
+            .is_some()
+    }
+
+    /// Configures the device with given index in [`XhciController::devices`] and determines its
+    /// capacity
+    pub fn new(hc: &mut XhciController, dev_idx: usize) -> Result<Self, Error> {
+        let dev = hc
+            .devices()
+            .get(dev_idx)
+            .ok_or_else(|| Error::new(Code::InvArgs))?;
+        let intf = dev
+            .find_interface(CLASS_MASS_STORAGE, SUBCLASS_SCSI, PROTOCOL_BULK_ONLY)
+            .ok_or_else(|| Error::new(Code::NotSup))?;
+
+        let find_ep = |dir| {
+            intf.endpoints
+                .iter()
+                .find(|ep| ep.transfer_type() == EndpointDesc::TYPE_BULK && ep.direction() == dir)
+                .copied()
+                .ok_or_else(|| Error::new(Code::NotSup))
+        };
+        let ep_in = find_ep(Direction::In)?;
+        let ep_out = find_ep(Direction::Out)?;
+
+        let slot = dev.slot();
+        let interface = intf.number;
+        let config = dev.config().value;
+        hc.configure(slot, config, &[ep_in, ep_out])?;
+
+        let mut msc = Self {
+            slot,
+            interface,
+            ep_in: ep_in.address,
+            ep_out: ep_out.address,
+            tag: 0,
+            block_size: 0,
+            block_count: 0,
+            max_transfer: hc.max_transfer(),
+        };
+
+        msc.wait_ready(hc)?;
+
+        let mut cap = [0u8; 8];
+        let mut cb = [0u8; 10];
+        cb[0] = SCSI_READ_CAPACITY10;
+        msc.command_in(hc, &cb, &mut cap)?;
+        let last_lba = u32::from_be_bytes([cap[0], cap[1], cap[2], cap[3]]);
+        msc.block_size = u32::from_be_bytes([cap[4], cap[5], cap[6], cap[7]]) as usize;
+        msc.block_count = last_lba as u64 + 1;
+        if msc.block_size == 0 {
+            return Err(Error::new(Code::NotSup));
+        }
+
+        log!(
+            LogFlags::LibUsb,
+            "msc: slot {}: {} blocks with {} bytes",
+            slot,
+            msc.block_count,
+            msc.block_size
+        );
+        Ok(msc)
+    }
+
+    /// Returns the slot of the device
+    pub fn slot(&self) -> u8 {
+        self.slot
+    }
+
+    /// Returns the size of a block in bytes
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Returns the number of blocks
+    pub fn block_count(&self) -> u64 {
+        self.block_count
+    }
+
+    /// Returns the maximum number of bytes that can be read or written at once
+    pub fn max_transfer(&self) -> usize {
+        self.max_transfer
+    }
+
+    /// Reads `buf.len()` bytes starting at block `lba` into `buf`
+    ///
+    /// The length needs to be a multiple of the block size and must not exceed
+    /// [`max_transfer`](Self::max_transfer).
+    pub fn read(&mut self, hc: &mut XhciController, lba: u64, buf: &mut [u8]) -> Result<(), Error> {
+        let cb = self.rw_command(SCSI_READ10, lba, buf.len())?;
+        match self.command_in(hc, &cb, buf) {
+            Ok(len) if len == buf.len() => Ok(()),
+            Ok(_) => Err(Error::new(Code::ReadFailed)),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Writes `buf` to the device, starting at block `lba`
+    ///
+    /// The length needs to be a multiple of the block size and must not exceed
+    /// [`max_transfer`](Self::max_transfer).
+    pub fn write(&mut self, hc: &mut XhciController, lba: u64, buf: &[u8]) -> Result<(), Error> {
+        let cb = self.rw_command(SCSI_WRITE10, lba, buf.len())?;
+        self.command(hc, &cb, Direction::Out, buf.len(), |hc, msc| {
+            hc.bulk_out(msc.slot, msc.ep_out, buf).map(|_| buf.len())
+        })
+        .map(|_| ())
+        .map_err(|e| match e.code() {
+            Code::ReadFailed => Error::new(Code::WriteFailed),
+            _ => e,
+        })
+    }
+
+    fn rw_command(&self, op: u8, lba: u64, len: usize) -> Result<[u8; 10], Error> {
+        let blocks = len / self.block_size;
+        if len % self.block_size != 0
+            || len > self.max_transfer
+            || lba + blocks as u64 > self.block_count
+            || lba > u32::MAX as u64
+        {
+            return Err(Error::new(Code::InvArgs));
+        }
+
+        let mut cb = [0u8; 10];
+        cb[0] = op;
+        cb[2..6].copy_from_slice(&(lba as u32).to_be_bytes());
+        cb[7..9].copy_from_slice(&(blocks as u16).to_be_bytes());
+        Ok(cb)
+    }
+
+    fn wait_ready(&mut self, hc: &mut XhciController) -> Result<(), Error> {
+        let cb = [SCSI_TEST_UNIT_READY, 0, 0, 0, 0, 0];
+        for _ in 0..READY_RETRIES {
+            match self.command(hc, &cb, Direction::Out, 0, |_, _| Ok(0)) {
+                Ok(_) => return Ok(()),
+                // the device reports a unit attention after reset; fetch the sense data to clear it
+                Err(e) if e.code() == Code::ReadFailed => {
+                    let mut sense = [0u8; SENSE_SIZE];
+                    let cb = [SCSI_REQUEST_SENSE, 0, 0, 0, SENSE_SIZE as u8, 0];
+                    self.command_in(hc, &cb, &mut sense)?;
+                    log!(
+                        LogFlags::LibUsb,
+                        "msc: slot {}: not ready (sense key {:#x}, asc {:#x})",
+                        self.slot,
+                        sense[2] & 0xF,
+                        sense[12]
+                    );
+                },
+                Err(e) => return Err(e),
+            }
+            OwnActivity::sleep_for(READY_DELAY)?;
+        }
+        Err(Error::new(Code::Timeout))
+    }
+
+    fn command_in(
+        &mut self,
+        hc: &mut XhciController,
+        cb: &[u8],
+        buf: &mut [u8],
+    ) -> Result<usize, Error> {
+        let len = buf.len();
+        self.command(hc, cb, Direction::In, len, |hc, msc| {
+            hc.bulk_in(msc.slot, msc.ep_in, buf)
+        })
+    }
+
+    /// Executes the SCSI command `cb` with a data phase of `len` bytes in direction `dir`
+    ///
+    /// The data phase is performed by `data`. Returns the number of transferred bytes.
+    fn command<F>(
+        &mut self,
+        hc: &mut XhciController,
+        cb: &[u8],
+        dir: Direction,
+        len: usize,
+        data: F,
+    ) -> Result<usize, Error>
+    where
+        F: FnOnce(&mut XhciController, &Self) -> Result<usize, Error>,
+    {
+        self.tag = self.tag.wrapping_add(1);
+
+        let mut cbw = [0u8; CBW_SIZE];
+        cbw[0..4].copy_from_slice(&CBW_SIGNATURE.to_le_bytes());
+        cbw[4..8].copy_from_slice(&self.tag.to_le_bytes());
+        cbw[8..12].copy_from_slice(&(len as u32).to_le_bytes());
+        cbw[12] = match dir {
+            Direction::In => CBW_FLAG_IN,
+            Direction::Out => 0,
+        };
+        cbw[14] = cb.len() as u8;
+        cbw[15..15 + cb.len()].copy_from_slice(cb);
+        hc.bulk_out(self.slot, self.ep_out, &cbw)?;
+
+        // the device sends the status even if the data phase stalled
+        let res = match len {
+            0 => Ok(0),
+            _ => data(hc, self),
+        };
+
+        let mut csw = [0u8; CSW_SIZE];
+        let csw_len = match hc.bulk_in(self.slot, self.ep_in, &mut csw) {
+            Ok(len) => len,
+            // the endpoint has been reset after the stall; try once more
+            Err(e) if e.code() == Code::InvState => hc.bulk_in(self.slot, self.ep_in, &mut csw)?,
+            Err(e) => return Err(e),
+        };
+
+        let sig = u32::from_le_bytes([csw[0], csw[1], csw[2], csw[3]]);
+        let tag = u32::from_le_bytes([csw[4], csw[5], csw[6], csw[7]]);
+        if csw_len != CSW_SIZE || sig != CSW_SIGNATURE || tag != self.tag {
+            log!(
+                LogFlags::Error,
+                "msc: slot {}: invalid command status",
+                self.slot
+            );
+            self.reset_recovery(hc)?;
+            return Err(Error::new(Code::InvState));
+        }
+
+        match csw[12] {
+            CSW_PASSED => res,
+            CSW_FAILED => Err(Error::new(Code::ReadFailed)),
+            // phase error
+            _ => {
+                self.reset_recovery(hc)?;
+                Err(Error::new(Code::InvState))
+            },
+        }
+    }
+
+    fn reset_recovery(&mut self, hc: &mut XhciController) -> Result<(), Error> {
+        log!(
+            LogFlags::LibUsb,
+            "msc: slot {}: performing reset recovery",
+            self.slot
+        );
+
+        let reset = SetupPacket {
+            request_type: REQ_TYPE_CLASS | REQ_RECIP_INTERFACE,
+            request: REQ_BOT_RESET,
+            value: 0,
+            index: self.interface as u16,
+            length: 0,
+        };
+        hc.control(self.slot, reset, &mut [])?;
+        hc.control(self.slot, SetupPacket::clear_halt(self.ep_in), &mut [])?;
+        hc.control(self.slot, SetupPacket::clear_halt(self.ep_out), &mut [])
+            .map(|_| ())
+    }
+}
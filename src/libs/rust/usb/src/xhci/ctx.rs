@@ -0,0 +1,95 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! The input context that is passed to the controller to configure slots and endpoints
+
+use m3::col::Vec;
+use m3::com::MemGate;
+use m3::errors::Error;
+use m3::mem::GlobOff;
+use m3::vec;
+
+// the input control context, slot context, and 31 endpoint contexts
+const CTX_COUNT: usize = 33;
+
+// endpoint types
+pub const EP_BULK_OUT: u32 = 2;
+pub const EP_CONTROL: u32 = 4;
+pub const EP_BULK_IN: u32 = 6;
+
+// the number of retries on transfer errors
+const ERROR_COUNT: u32 = 3;
+
+/// Returns the size of the input context for given context size in bytes
+pub fn input_size(csz: usize) -> usize {
+    CTX_COUNT * csz
+}
+
+/// Returns the size of the device context for given context size in bytes
+pub fn device_size(csz: usize) -> usize {
+    (CTX_COUNT - 1) * csz
+}
+
+/// An input context that is built in local memory and copied to the DMA memory afterwards
+pub struct InputContext {
+    words: Vec<u32>,
+    csz: usize,
+}
+
+impl InputContext {
+    /// Creates an empty input context for contexts of `csz` bytes
+    pub fn new(csz: usize) -> Self {
+        Self {
+            words: vec![0; input_size(csz) / 4],
+            csz,
+        }
+    }
+
+    fn ctx(&mut self, idx: usize) -> &mut [u32] {
+        let words = self.csz / 4;
+        &mut self.words[idx * words..(idx + 1) * words]
+    }
+
+    /// Marks the context with given device context index (0 = slot) as added or changed
+    pub fn add(&mut self, dci: u8) {
+        self.ctx(0)[1] |= 1 << dci;
+    }
+
+    /// Sets the slot context for a device with given speed at given root hub port, whose last
+    /// valid endpoint has device context index `last_dci`
+    pub fn set_slot(&mut self, speed: u32, port: u8, last_dci: u8) {
+        let slot = self.ctx(1);
+        slot[0] = speed << 20 | (last_dci as u32) << 27;
+        slot[1] = (port as u32) << 16;
+    }
+
+    /// Sets the endpoint context with given device context index
+    pub fn set_endpoint(&mut self, dci: u8, ty: u32, max_packet: u16, ring: GlobOff, cycle: bool) {
+        let ep = self.ctx(dci as usize + 1);
+        ep[1] = ERROR_COUNT << 1 | ty << 3 | (max_packet as u32) << 16;
+        ep[2] = ring as u32 | cycle as u32;
+        ep[3] = (ring >> 32) as u32;
+        // the average TRB length is a hint for the bandwidth calculation
+        ep[4] = match ty {
+            EP_CONTROL => 8,
+            _ => max_packet as u32,
+        };
+    }
+
+    /// Writes the context to given address within the DMA memory
+    pub fn write(&self, dma: &MemGate, addr: GlobOff) -> Result<(), Error> {
+        dma.write(&self.words, addr)
+    }
+}
@@ -0,0 +1,883 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! Driver for xHCI host controllers
+
+mod ctx;
+pub mod ring;
+
+use bitflags::bitflags;
+
+use m3::cfg;
+use m3::col::Vec;
+use m3::com::MemGate;
+use m3::errors::{Code, Error};
+use m3::io::LogFlags;
+use m3::kif::{Perm, TileISA};
+use m3::log;
+use m3::mem::GlobOff;
+use m3::tiles::OwnActivity;
+use m3::time::TimeDuration;
+use m3::util::math;
+use m3::vec;
+
+use self::ctx::{InputContext, EP_BULK_IN, EP_BULK_OUT, EP_CONTROL};
+use self::ring::*;
+use crate::desc::{
+    ConfigDesc, DeviceDesc, Direction, EndpointDesc, SetupPacket, CONFIG_DESC_SIZE, DESC_CONFIG,
+    DESC_DEVICE, DEVICE_DESC_SIZE,
+};
+use crate::device::{Speed, UsbDevice};
+
+const XHCI_CLASS: u8 = 0x0C;
+const XHCI_SUBCLASS: u8 = 0x03;
+const XHCI_PROG_IF: u8 = 0x30;
+
+/// The maximum number of device slots we enable
+const MAX_SLOTS: u8 = 16;
+
+const CMD_RING_SIZE: usize = 256;
+const EVENT_RING_SIZE: usize = 256;
+const XFER_RING_SIZE: usize = 256;
+
+/// The size of the transfer buffer, which is also the maximum size of a single transfer
+///
+/// A TRB must not cross a 64 KiB boundary. Thus, the buffer is aligned to its size so that each
+/// transfer needs a single TRB.
+const XFER_BUF_SIZE: usize = 0x10000;
+
+/// The size of the DMA memory for everything but the scratchpad buffers
+const DMA_SIZE: usize = 512 * 1024;
+
+const RESET_TIMEOUT: TimeDuration = TimeDuration::from_millis(100);
+const EVENT_TIMEOUT: TimeDuration = TimeDuration::from_millis(1000);
+const SLEEPTIME: TimeDuration = TimeDuration::from_micros(20);
+
+/// Capability registers
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u64)]
+enum CapReg {
+    Length     = 0x00,
+    HcsParams1 = 0x04,
+    HcsParams2 = 0x08,
+    HccParams1 = 0x10,
+    DbOff      = 0x14,
+    RtsOff     = 0x18,
+}
+
+/// Operational registers (relative to the end of the capability registers)
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u64)]
+enum OpReg {
+    UsbCmd   = 0x00,
+    UsbSts   = 0x04,
+    PageSize = 0x08,
+    CrCr     = 0x18,
+    Dcbaap   = 0x30,
+    Config   = 0x38,
+}
+
+const PORT_REGS_BASE: GlobOff = 0x400;
+const PORT_REGS_SIZE: GlobOff = 0x10;
+
+/// Registers of the first interrupter (relative to the runtime registers)
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u64)]
+enum IntrReg {
+    ErstSz = 0x28,
+    ErstBa = 0x30,
+    ErDp   = 0x38,
+}
+
+// the event handler busy flag in ERDP (write 1 to clear)
+const ERDP_EHB: u64 = 1 << 3;
+
+// the capability id of the USB legacy support in the extended capabilities
+const XCAP_LEGACY: u32 = 1;
+const LEGACY_BIOS_OWNED: u32 = 1 << 16;
+const LEGACY_OS_OWNED: u32 = 1 << 24;
+
+bitflags! {
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    struct UsbCmd : u32 {
+        const RUN = 1 << 0;
+        const RESET = 1 << 1;
+    }
+}
+
+bitflags! {
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    struct UsbSts : u32 {
+        const HALTED = 1 << 0;
+        const HOST_ERROR = 1 << 2;
+        const NOT_READY = 1 << 11;
+    }
+}
+
+bitflags! {
+    /// The port status and control register
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    struct PortSc : u32 {
+        const CONNECTED = 1 << 0;
+        const ENABLED = 1 << 1;
+        const RESET = 1 << 4;
+        const POWER = 1 << 9;
+        const CONNECT_CHANGE = 1 << 17;
+        const RESET_CHANGE = 1 << 21;
+    }
+}
+
+// the bits of PORTSC that are preserved on writes; all others are either read-only or cleared by
+// writing a one (e.g., the change bits and the enabled bit)
+const PORTSC_PRESERVE: u32 = 0x0E00_C3E0;
+
+/// A bump allocator for the DMA memory
+///
+/// Everything that is allocated lives as long as the controller.
+struct DmaAlloc {
+    next: usize,
+    size: usize,
+}
+
+impl DmaAlloc {
+    fn alloc(&mut self, size: usize, align: usize) -> Result<GlobOff, Error> {
+        let start = math::round_up(self.next, align);
+        if start + size > self.size {
+            return Err(Error::new(Code::NoSpace));
+        }
+        self.next = start + size;
+        Ok(start as GlobOff)
+    }
+
+    fn alloc_zeroed(&mut self, dma: &MemGate, size: usize, align: usize) -> Result<GlobOff, Error> {
+        let addr = self.alloc(size, align)?;
+        let zeros = [0u64; 64];
+        for off in (0..size).step_by(zeros.len() * 8) {
+            let amount = (size - off).min(zeros.len() * 8);
+            dma.write(&zeros[0..amount / 8], addr + off as GlobOff)?;
+        }
+        Ok(addr)
+    }
+}
+
+/// Access to the registers of the controller
+struct Regs {
+    pci: pci::Device,
+    op_base: GlobOff,
+    rt_base: GlobOff,
+    db_base: GlobOff,
+}
+
+/// The state of an enabled device slot
+struct Slot {
+    port: u8,
+    speed: Speed,
+    input: GlobOff,
+    // the transfer rings, indexed by device context index
+    rings: Vec<Option<Ring>>,
+}
+
+/// An xHCI host controller with the devices attached to its root hub
+///
+/// The driver enumerates all devices that are connected to the root hub ports during
+/// initialization; hubs and hot plugging are not supported. All transfers are synchronous and
+/// performed on a single transfer buffer within the DMA memory. As the DMA transfers of the
+/// device tile refer to this memory, all addresses that are handed to the controller are offsets
+/// within it.
+pub struct XhciController {
+    regs: Regs,
+    dma: MemGate,
+    alloc: DmaAlloc,
+    csz: usize,
+    dcbaa: GlobOff,
+    cmd: Ring,
+    events: EventRing,
+    buf: GlobOff,
+    slots: Vec<Option<Slot>>,
+    devices: Vec<UsbDevice>,
+}
+
+impl XhciController {
+    /// Creates a new driver for the xHCI controller with given name on a new tile with given ISA
+    /// and enumerates the attached devices
+    pub fn new(name: &str, isa: TileISA) -> Result<Self, Error> {
+        let pci = pci::Device::new(name, isa)?;
+        let info = pci.get_info()?;
+        if info.class().base() != XHCI_CLASS
+            || info.class().sub() != XHCI_SUBCLASS
+            || info.programming_interface() != XHCI_PROG_IF
+        {
+            return Err(Error::new(Code::NotFound));
+        }
+
+        log!(
+            LogFlags::LibUsb,
+            "xhci: found controller ({}): vendor {:x} device {:x} rev {}",
+            info.id(),
+            info.vendor(),
+            info.device(),
+            info.revision(),
+        );
+
+        // enable the memory space and bus mastering
+        let cmd: u16 = pci.read_config(pci::Reg::Command.into())?;
+        pci.write_config(pci::Reg::Command.into(), cmd | 0x02 | 0x04)?;
+
+        let cap_len: u8 = pci.read_reg(CapReg::Length as GlobOff)?;
+        let hcs1: u32 = pci.read_reg(CapReg::HcsParams1 as GlobOff)?;
+        let hcs2: u32 = pci.read_reg(CapReg::HcsParams2 as GlobOff)?;
+        let hcc1: u32 = pci.read_reg(CapReg::HccParams1 as GlobOff)?;
+        let db_off: u32 = pci.read_reg(CapReg::DbOff as GlobOff)?;
+        let rts_off: u32 = pci.read_reg(CapReg::RtsOff as GlobOff)?;
+
+        let max_slots = ((hcs1 & 0xFF) as u8).min(MAX_SLOTS);
+        let max_ports = (hcs1 >> 24) as u8;
+        let scratchpads = (((hcs2 >> 21) & 0x1F) << 5 | (hcs2 >> 27)) as usize;
+        let csz = if (hcc1 & (1 << 2)) != 0 { 64 } else { 32 };
+
+        let regs = Regs {
+            pci,
+            op_base: cap_len as GlobOff,
+            rt_base: (rts_off & !0x1F) as GlobOff,
+            db_base: (db_off & !0x3) as GlobOff,
+        };
+        regs.take_ownership(hcc1)?;
+        regs.reset()?;
+
+        let page_size: u32 = regs.read_op(OpReg::PageSize)?;
+        if (page_size & 1) == 0 {
+            log!(LogFlags::Error, "xhci: 4 KiB pages are not supported");
+            return Err(Error::new(Code::NotSup));
+        }
+
+        let dma_size = DMA_SIZE + scratchpads * cfg::PAGE_SIZE;
+        let dma = MemGate::new(dma_size as GlobOff, Perm::RW)?;
+        let dev_buf = dma.derive_cap(0, dma_size as GlobOff, Perm::RW)?;
+        regs.pci.set_dma_buffer(&dev_buf)?;
+        let mut alloc = DmaAlloc {
+            next: 0,
+            size: dma_size,
+        };
+
+        // device context base address array; entry 0 points to the scratchpad buffer array
+        let dcbaa = alloc.alloc_zeroed(&dma, (max_slots as usize + 1) * 8, 64)?;
+        if scratchpads > 0 {
+            let array = alloc.alloc_zeroed(&dma, scratchpads * 8, 64)?;
+            for i in 0..scratchpads {
+                let page = alloc.alloc_zeroed(&dma, cfg::PAGE_SIZE, cfg::PAGE_SIZE)?;
+                dma.write_obj(&page, array + (i * 8) as GlobOff)?;
+            }
+            dma.write_obj(&array, dcbaa)?;
+        }
+        regs.write_op(OpReg::Config, max_slots as u32)?;
+        regs.write_op64(OpReg::Dcbaap, dcbaa)?;
+
+        let cmd_addr = alloc.alloc(CMD_RING_SIZE * TRB_SIZE, 64)?;
+        let cmd = Ring::new(&dma, cmd_addr, CMD_RING_SIZE)?;
+        regs.write_op64(OpReg::CrCr, cmd_addr | cmd.cycle() as u64)?;
+
+        // a single event ring segment, described by the segment table
+        let ev_addr = alloc.alloc(EVENT_RING_SIZE * TRB_SIZE, 64)?;
+        let events = EventRing::new(&dma, ev_addr, EVENT_RING_SIZE)?;
+        let erst = alloc.alloc(16, 64)?;
+        dma.write_obj(&ev_addr, erst)?;
+        dma.write_obj(&(EVENT_RING_SIZE as u64), erst + 8)?;
+        regs.write_intr(IntrReg::ErstSz, 1u32)?;
+        regs.write_intr64(IntrReg::ErDp, ev_addr)?;
+        regs.write_intr64(IntrReg::ErstBa, erst)?;
+
+        let buf = alloc.alloc(XFER_BUF_SIZE, XFER_BUF_SIZE)?;
+
+        regs.write_op(OpReg::UsbCmd, UsbCmd::RUN.bits())?;
+        regs.wait_status(UsbSts::HALTED, false)?;
+
+        log!(
+            LogFlags::LibUsb,
+            "xhci: {} slots, {} ports, {} scratchpad buffers, {}-byte contexts",
+            max_slots,
+            max_ports,
+            scratchpads,
+            csz
+        );
+
+        let mut hc = Self {
+            regs,
+            dma,
+            alloc,
+            csz,
+            dcbaa,
+            cmd,
+            events,
+            buf,
+            slots: (0..=max_slots).map(|_| None).collect(),
+            devices: Vec::new(),
+        };
+
+        for port in 1..=max_ports {
+            let sc = PortSc::from_bits_truncate(hc.regs.read_port(port)?);
+            if !sc.contains(PortSc::CONNECTED) {
+                continue;
+            }
+
+            match hc.attach(port) {
+                Ok(dev) => {
+                    log!(
+                        LogFlags::LibUsb,
+                        "xhci: port {}: device {:04x}:{:04x} ({:?}) in slot {}",
+                        port,
+                        dev.descriptor().vendor,
+                        dev.descriptor().product,
+                        dev.speed(),
+                        dev.slot()
+                    );
+                    hc.devices.push(dev);
+                },
+                Err(e) => log!(
+                    LogFlags::Error,
+                    "xhci: port {}: ignoring device: {}",
+                    port,
+                    e
+                ),
+            }
+        }
+
+        Ok(hc)
+    }
+
+    /// Returns all devices that have been found during initialization
+    pub fn devices(&self) -> &[UsbDevice] {
+        &self.devices
+    }
+
+    /// Returns the maximum number of bytes that can be transferred at once
+    pub fn max_transfer(&self) -> usize {
+        XFER_BUF_SIZE
+    }
+
+    /// Resets the given port and addresses the device behind it
+    fn attach(&mut self, port: u8) -> Result<UsbDevice, Error> {
+        let speed = self.regs.reset_port(port)?;
+
+        let slot = self.command(Trb::new(TRB_ENABLE_SLOT, 0, 0, 0))?.slot();
+        if slot == 0 || slot as usize >= self.slots.len() {
+            return Err(Error::new(Code::NoSpace));
+        }
+
+        // the output device context, which is owned by the controller
+        let out = self
+            .alloc
+            .alloc_zeroed(&self.dma, ctx::device_size(self.csz), 64)?;
+        self.dma.write_obj(&out, self.dcbaa + slot as GlobOff * 8)?;
+
+        let input = self
+            .alloc
+            .alloc_zeroed(&self.dma, ctx::input_size(self.csz), 64)?;
+        let ring_addr = self.alloc.alloc(XFER_RING_SIZE * TRB_SIZE, 64)?;
+        let ring = Ring::new(&self.dma, ring_addr, XFER_RING_SIZE)?;
+
+        let mut ictx = InputContext::new(self.csz);
+        ictx.add(0);
+        ictx.add(1);
+        ictx.set_slot(speed.id(), port, 1);
+        ictx.set_endpoint(
+            1,
+            EP_CONTROL,
+            speed.default_max_packet0(),
+            ring.addr(),
+            ring.cycle(),
+        );
+        ictx.write(&self.dma, input)?;
+
+        let mut rings: Vec<Option<Ring>> = (0..32).map(|_| None).collect();
+        rings[1] = Some(ring);
+        self.slots[slot as usize] = Some(Slot {
+            port,
+            speed,
+            input,
+            rings,
+        });
+
+        self.command(Trb::new(TRB_ADDRESS_DEVICE, input, 0, (slot as u32) << 24))?;
+
+        // the first 8 bytes of the device descriptor tell us the real packet size of endpoint 0
+        let mut desc = [0u8; DEVICE_DESC_SIZE];
+        self.control(
+            slot,
+            SetupPacket::get_descriptor(DESC_DEVICE, 0, 8),
+            &mut desc[0..8],
+        )?;
+        let max_packet = match speed {
+            Speed::Super => 1 << desc[7].min(15),
+            _ => desc[7] as u16,
+        };
+        if max_packet != speed.default_max_packet0() {
+            self.update_max_packet0(slot, max_packet)?;
+        }
+
+        self.control(
+            slot,
+            SetupPacket::get_descriptor(DESC_DEVICE, 0, DEVICE_DESC_SIZE as u16),
+            &mut desc,
+        )?;
+        let desc = DeviceDesc::parse(&desc)?;
+
+        let mut cfg = vec![0u8; CONFIG_DESC_SIZE];
+        self.control(
+            slot,
+            SetupPacket::get_descriptor(DESC_CONFIG, 0, CONFIG_DESC_SIZE as u16),
+            &mut cfg,
+        )?;
+        let total = ConfigDesc::total_length(&cfg)?.min(XFER_BUF_SIZE);
+        cfg.resize(total, 0);
+        self.control(
+            slot,
+            SetupPacket::get_descriptor(DESC_CONFIG, 0, total as u16),
+            &mut cfg,
+        )?;
+        let config = ConfigDesc::parse(&cfg)?;
+
+        Ok(UsbDevice::new(slot, port, speed, desc, config))
+    }
+
+    fn update_max_packet0(&mut self, slot: u8, max_packet: u16) -> Result<(), Error> {
+        log!(
+            LogFlags::LibUsb,
+            "xhci: slot {}: endpoint 0 has packets of {} bytes",
+            slot,
+            max_packet
+        );
+
+        let input = self.slots[slot as usize].as_ref().unwrap().input;
+        let ring = ring_of(&mut self.slots, slot, 1)?;
+        let mut ictx = InputContext::new(self.csz);
+        ictx.add(1);
+        ictx.set_endpoint(1, EP_CONTROL, max_packet, ring.addr(), ring.cycle());
+        ictx.write(&self.dma, input)?;
+
+        self.command(Trb::new(TRB_EVALUATE_CTX, input, 0, (slot as u32) << 24))
+            .map(|_| ())
+    }
+
+    /// Sets up the transfer rings for the given bulk endpoints of the device in given slot and
+    /// selects the configuration with given value
+    pub fn configure(&mut self, slot: u8, config: u8, eps: &[EndpointDesc]) -> Result<(), Error> {
+        let s = self
+            .slots
+            .get(slot as usize)
+            .and_then(|s| s.as_ref())
+            .ok_or_else(|| Error::new(Code::InvArgs))?;
+        let (port, speed, input) = (s.port, s.speed, s.input);
+
+        let mut ictx = InputContext::new(self.csz);
+        ictx.add(0);
+        let mut last_dci = 1;
+        let mut rings = Vec::new();
+        for ep in eps {
+            if ep.transfer_type() != EndpointDesc::TYPE_BULK {
+                return Err(Error::new(Code::NotSup));
+            }
+
+            let (dci, ty) = match ep.direction() {
+                Direction::Out => (ep.number() * 2, EP_BULK_OUT),
+                Direction::In => (ep.number() * 2 + 1, EP_BULK_IN),
+            };
+            let ring_addr = self.alloc.alloc(XFER_RING_SIZE * TRB_SIZE, 64)?;
+            let ring = Ring::new(&self.dma, ring_addr, XFER_RING_SIZE)?;
+            ictx.add(dci);
+            ictx.set_endpoint(dci, ty, ep.max_packet_size, ring.addr(), ring.cycle());
+            last_dci = last_dci.max(dci);
+            rings.push((dci, ring));
+        }
+        ictx.set_slot(speed.id(), port, last_dci);
+        ictx.write(&self.dma, input)?;
+
+        self.command(Trb::new(TRB_CONFIGURE_EP, input, 0, (slot as u32) << 24))?;
+
+        let s = self.slots[slot as usize].as_mut().unwrap();
+        for (dci, ring) in rings {
+            s.rings[dci as usize] = Some(ring);
+        }
+
+        self.control(slot, SetupPacket::set_configuration(config), &mut [])
+            .map(|_| ())
+    }
+
+    /// Performs a control transfer on the default endpoint of the device in given slot
+    ///
+    /// The direction of the data stage is determined by `setup`. For IN transfers, the received
+    /// data is stored in `data`, for OUT transfers, `data` is sent to the device. Returns the
+    /// number of transferred bytes.
+    pub fn control(
+        &mut self,
+        slot: u8,
+        mut setup: SetupPacket,
+        data: &mut [u8],
+    ) -> Result<usize, Error> {
+        if data.len() > XFER_BUF_SIZE {
+            return Err(Error::new(Code::InvArgs));
+        }
+        setup.length = data.len() as u16;
+        let dir = setup.direction();
+        if dir == Direction::Out && !data.is_empty() {
+            self.dma.write(data, self.buf)?;
+        }
+
+        let (setup_trt, data_dir, status_dir) = match (data.is_empty(), dir) {
+            (true, _) => (0, 0, TRB_DIR_IN),
+            (false, Direction::Out) => (2 << 16, 0, TRB_DIR_IN),
+            (false, Direction::In) => (3 << 16, TRB_DIR_IN, 0),
+        };
+
+        let buf = self.buf;
+        let ring = ring_of(&mut self.slots, slot, 1)?;
+        ring.push(
+            &self.dma,
+            Trb::new(TRB_SETUP, setup.to_raw(), 8, TRB_IDT | setup_trt),
+        )?;
+        let data_trb = match data.is_empty() {
+            true => None,
+            false => Some(ring.push(
+                &self.dma,
+                Trb::new(
+                    TRB_DATA,
+                    buf,
+                    data.len() as u32,
+                    data_dir | TRB_ISP | TRB_IOC,
+                ),
+            )?),
+        };
+        let status_trb = ring.push(&self.dma, Trb::new(TRB_STATUS, 0, 0, status_dir | TRB_IOC))?;
+        self.regs.ring_doorbell(slot, 1)?;
+
+        let mut len = 0;
+        if let Some(addr) = data_trb {
+            let ev = self.wait_transfer(slot, 1, addr)?;
+            len = data.len() - ev.residual().min(data.len());
+        }
+        self.wait_transfer(slot, 1, status_trb)?;
+
+        if dir == Direction::In && len > 0 {
+            self.dma.read(&mut data[0..len], self.buf)?;
+        }
+        Ok(len)
+    }
+
+    /// Receives up to `data.len()` bytes from the bulk IN endpoint with given address of the
+    /// device in given slot and returns the number of received bytes
+    pub fn bulk_in(&mut self, slot: u8, ep_addr: u8, data: &mut [u8]) -> Result<usize, Error> {
+        let len = self.bulk(slot, ep_addr, Direction::In, data.len())?;
+        self.dma.read(&mut data[0..len], self.buf)?;
+        Ok(len)
+    }
+
+    /// Sends `data` to the bulk OUT endpoint with given address of the device in given slot
+    pub fn bulk_out(&mut self, slot: u8, ep_addr: u8, data: &[u8]) -> Result<(), Error> {
+        if data.len() > XFER_BUF_SIZE {
+            return Err(Error::new(Code::InvArgs));
+        }
+        self.dma.write(data, self.buf)?;
+        self.bulk(slot, ep_addr, Direction::Out, data.len())
+            .map(|_| ())
+    }
+
+    fn bulk(&mut self, slot: u8, ep_addr: u8, dir: Direction, len: usize) -> Result<usize, Error> {
+        if len > XFER_BUF_SIZE {
+            return Err(Error::new(Code::InvArgs));
+        }
+
+        let dci = match dir {
+            Direction::Out => (ep_addr & 0xF) * 2,
+            Direction::In => (ep_addr & 0xF) * 2 + 1,
+        };
+        let buf = self.buf;
+        let ring = ring_of(&mut self.slots, slot, dci)?;
+        let trb = ring.push(
+            &self.dma,
+            Trb::new(TRB_NORMAL, buf, len as u32, TRB_ISP | TRB_IOC),
+        )?;
+        self.regs.ring_doorbell(slot, dci)?;
+
+        match self.wait_transfer(slot, dci, trb) {
+            Ok(ev) => Ok(len - ev.residual().min(len)),
+            Err(e) if e.code() == Code::InvState => {
+                // the endpoint is halted; reset it on both sides before reporting the error
+                self.reset_endpoint(slot, dci)?;
+                self.control(slot, SetupPacket::clear_halt(ep_addr), &mut [])?;
+                Err(e)
+            },
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Resets the halted endpoint with given device context index and lets it continue behind
+    /// the last TRB on its ring
+    fn reset_endpoint(&mut self, slot: u8, dci: u8) -> Result<(), Error> {
+        log!(
+            LogFlags::LibUsb,
+            "xhci: slot {}: resetting endpoint {}",
+            slot,
+            dci
+        );
+
+        let target = (slot as u32) << 24 | (dci as u32) << 16;
+        self.command(Trb::new(TRB_RESET_EP, 0, 0, target))?;
+        let ring = ring_of(&mut self.slots, slot, dci)?;
+        let deq = ring.enqueue_addr() | ring.cycle() as u64;
+        self.command(Trb::new(TRB_SET_TR_DEQUEUE, deq, 0, target))
+            .map(|_| ())
+    }
+
+    /// Issues the given command and waits for its completion
+    fn command(&mut self, trb: Trb) -> Result<Trb, Error> {
+        let addr = self.cmd.push(&self.dma, trb)?;
+        self.regs.ring_doorbell(0, 0)?;
+
+        let ev = self.wait_event(TRB_CMD_COMPLETION, addr)?;
+        if ev.completion_code() != CC_SUCCESS {
+            log!(
+                LogFlags::Error,
+                "xhci: command {} failed with completion code {}",
+                trb.ty(),
+                ev.completion_code()
+            );
+            return Err(Error::new(Code::InvState));
+        }
+        Ok(ev)
+    }
+
+    /// Waits for the transfer event for the TRB at `addr` on the given endpoint
+    ///
+    /// Returns an error with [`Code::InvState`] if the endpoint has been halted.
+    fn wait_transfer(&mut self, slot: u8, dci: u8, addr: GlobOff) -> Result<Trb, Error> {
+        loop {
+            let ev = self.next_event()?;
+            if ev.ty() != TRB_TRANSFER_EVENT || ev.slot() != slot || ev.endpoint() != dci {
+                self.ignore_event(&ev);
+                continue;
+            }
+
+            match ev.completion_code() {
+                CC_SUCCESS | CC_SHORT_PACKET if ev.param == addr => return Ok(ev),
+                CC_SUCCESS | CC_SHORT_PACKET => {
+                    // a short packet in the data stage of a control transfer also reports the
+                    // data stage if we wait for the status stage
+                    self.ignore_event(&ev);
+                },
+                code => {
+                    log!(
+                        LogFlags::Error,
+                        "xhci: slot {}: transfer on endpoint {} failed with completion code {}",
+                        slot,
+                        dci,
+                        code
+                    );
+                    return Err(Error::new(match code {
+                        CC_STALL => Code::InvState,
+                        _ => Code::ReadFailed,
+                    }));
+                },
+            }
+        }
+    }
+
+    fn wait_event(&mut self, ty: u32, addr: GlobOff) -> Result<Trb, Error> {
+        loop {
+            let ev = self.next_event()?;
+            if ev.ty() == ty && ev.param == addr {
+                return Ok(ev);
+            }
+            self.ignore_event(&ev);
+        }
+    }
+
+    fn ignore_event(&self, ev: &Trb) {
+        log!(
+            LogFlags::LibUsb,
+            "xhci: ignoring event {} (param={:#x}, code={})",
+            ev.ty(),
+            ev.param,
+            ev.completion_code()
+        );
+    }
+
+    fn next_event(&mut self) -> Result<Trb, Error> {
+        let mut elapsed = TimeDuration::ZERO;
+        loop {
+            if let Some(ev) = self.events.pop(&self.dma)? {
+                // tell the controller how far we got
+                self.regs
+                    .write_intr64(IntrReg::ErDp, self.events.dequeue_addr() | ERDP_EHB)?;
+                return Ok(ev);
+            }
+
+            let sts = UsbSts::from_bits_truncate(self.regs.read_op(OpReg::UsbSts)?);
+            if sts.contains(UsbSts::HOST_ERROR) {
+                log!(LogFlags::Error, "xhci: host system error");
+                return Err(Error::new(Code::InvState));
+            }
+
+            if elapsed >= EVENT_TIMEOUT {
+                return Err(Error::new(Code::Timeout));
+            }
+            OwnActivity::sleep_for(SLEEPTIME)?;
+            elapsed += SLEEPTIME;
+        }
+    }
+}
+
+fn ring_of(slots: &mut [Option<Slot>], slot: u8, dci: u8) -> Result<&mut Ring, Error> {
+    slots
+        .get_mut(slot as usize)
+        .and_then(|s| s.as_mut())
+        .and_then(|s| s.rings[dci as usize].as_mut())
+        .ok_or_else(|| Error::new(Code::InvArgs))
+}
+
+fn port_reg(port: u8) -> GlobOff {
+    PORT_REGS_BASE + (port as GlobOff - 1) * PORT_REGS_SIZE
+}
+
+impl Regs {
+    fn reset_port(&self, port: u8) -> Result<Speed, Error> {
+        let sc: u32 = self.read_port(port)?;
+        self.write_port(port, (sc & PORTSC_PRESERVE) | PortSc::RESET.bits())?;
+
+        let mut elapsed = TimeDuration::ZERO;
+        loop {
+            let sc = PortSc::from_bits_retain(self.read_port(port)?);
+            if sc.contains(PortSc::RESET_CHANGE) && !sc.contains(PortSc::RESET) {
+                // acknowledge the changes
+                self.write_port(
+                    port,
+                    (sc.bits() & PORTSC_PRESERVE)
+                        | (PortSc::RESET_CHANGE | PortSc::CONNECT_CHANGE).bits(),
+                )?;
+                if !sc.contains(PortSc::ENABLED) {
+                    return Err(Error::new(Code::NotFound));
+                }
+                return Speed::from_id((sc.bits() >> 10) & 0xF)
+                    .ok_or_else(|| Error::new(Code::NotSup));
+            }
+
+            if elapsed >= RESET_TIMEOUT {
+                return Err(Error::new(Code::Timeout));
+            }
+            OwnActivity::sleep_for(SLEEPTIME)?;
+            elapsed += SLEEPTIME;
+        }
+    }
+
+    /// Asks the firmware to hand over the controller, if it has not done that already
+    fn take_ownership(&self, hcc1: u32) -> Result<(), Error> {
+        let mut off = ((hcc1 >> 16) << 2) as GlobOff;
+        while off != 0 {
+            let cap: u32 = self.pci.read_reg(off)?;
+            if (cap & 0xFF) == XCAP_LEGACY {
+                if (cap & LEGACY_BIOS_OWNED) != 0 {
+                    self.pci.write_reg(off, cap | LEGACY_OS_OWNED)?;
+                    let mut elapsed = TimeDuration::ZERO;
+                    while (self.pci.read_reg::<u32>(off)? & LEGACY_BIOS_OWNED) != 0 {
+                        if elapsed >= RESET_TIMEOUT {
+                            log!(
+                                LogFlags::Error,
+                                "xhci: firmware does not release controller"
+                            );
+                            return Err(Error::new(Code::Timeout));
+                        }
+                        OwnActivity::sleep_for(SLEEPTIME)?;
+                        elapsed += SLEEPTIME;
+                    }
+                }
+                break;
+            }
+
+            off = match (cap >> 8) & 0xFF {
+                0 => 0,
+                next => off + ((next as GlobOff) << 2),
+            };
+        }
+        Ok(())
+    }
+
+    fn wait_status(&self, mask: UsbSts, set: bool) -> Result<(), Error> {
+        let mut elapsed = TimeDuration::ZERO;
+        while UsbSts::from_bits_truncate(self.read_op(OpReg::UsbSts)?).contains(mask) != set {
+            if elapsed >= RESET_TIMEOUT {
+                return Err(Error::new(Code::Timeout));
+            }
+            OwnActivity::sleep_for(SLEEPTIME)?;
+            elapsed += SLEEPTIME;
+        }
+        Ok(())
+    }
+
+    fn reset(&self) -> Result<(), Error> {
+        self.wait_status(UsbSts::NOT_READY, false)?;
+
+        // the controller needs to be halted before it can be reset
+        self.write_op(OpReg::UsbCmd, 0u32)?;
+        self.wait_status(UsbSts::HALTED, true)?;
+
+        self.write_op(OpReg::UsbCmd, UsbCmd::RESET.bits())?;
+        let mut elapsed = TimeDuration::ZERO;
+        while (self.read_op::<u32>(OpReg::UsbCmd)? & UsbCmd::RESET.bits()) != 0 {
+            if elapsed >= RESET_TIMEOUT {
+                return Err(Error::new(Code::Timeout));
+            }
+            OwnActivity::sleep_for(SLEEPTIME)?;
+            elapsed += SLEEPTIME;
+        }
+        self.wait_status(UsbSts::NOT_READY, false)
+    }
+
+    fn ring_doorbell(&self, slot: u8, target: u8) -> Result<(), Error> {
+        self.pci
+            .write_reg(self.db_base + slot as GlobOff * 4, target as u32)
+    }
+
+    fn read_op<T>(&self, reg: OpReg) -> Result<T, Error> {
+        self.pci.read_reg(self.op_base + reg as GlobOff)
+    }
+
+    fn write_op<T>(&self, reg: OpReg, val: T) -> Result<(), Error> {
+        self.pci.write_reg(self.op_base + reg as GlobOff, val)
+    }
+
+    fn write_op64(&self, reg: OpReg, val: u64) -> Result<(), Error> {
+        self.write64(self.op_base + reg as GlobOff, val)
+    }
+
+    fn write_intr<T>(&self, reg: IntrReg, val: T) -> Result<(), Error> {
+        self.pci.write_reg(self.rt_base + reg as GlobOff, val)
+    }
+
+    fn write_intr64(&self, reg: IntrReg, val: u64) -> Result<(), Error> {
+        self.write64(self.rt_base + reg as GlobOff, val)
+    }
+
+    /// Writes a 64-bit register as two 32-bit halves, starting with the lower half
+    fn write64(&self, off: GlobOff, val: u64) -> Result<(), Error> {
+        self.pci.write_reg(off, val as u32)?;
+        self.pci.write_reg(off + 4, (val >> 32) as u32)
+    }
+
+    fn read_port(&self, port: u8) -> Result<u32, Error> {
+        self.pci.read_reg(self.op_base + port_reg(port))
+    }
+
+    fn write_port(&self, port: u8, val: u32) -> Result<(), Error> {
+        self.pci.write_reg(self.op_base + port_reg(port), val)
+    }
+}
@@ -0,0 +1,215 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! Transfer request blocks (TRBs) and the rings that contain them
+
+use m3::com::MemGate;
+use m3::errors::Error;
+use m3::mem::GlobOff;
+
+pub const TRB_SIZE: usize = 16;
+
+// TRB types
+pub const TRB_NORMAL: u32 = 1;
+pub const TRB_SETUP: u32 = 2;
+pub const TRB_DATA: u32 = 3;
+pub const TRB_STATUS: u32 = 4;
+pub const TRB_LINK: u32 = 6;
+pub const TRB_ENABLE_SLOT: u32 = 9;
+pub const TRB_ADDRESS_DEVICE: u32 = 11;
+pub const TRB_CONFIGURE_EP: u32 = 12;
+pub const TRB_EVALUATE_CTX: u32 = 13;
+pub const TRB_RESET_EP: u32 = 14;
+pub const TRB_SET_TR_DEQUEUE: u32 = 16;
+pub const TRB_TRANSFER_EVENT: u32 = 32;
+pub const TRB_CMD_COMPLETION: u32 = 33;
+
+// bits in the control field
+pub const TRB_CYCLE: u32 = 1 << 0;
+pub const TRB_TOGGLE_CYCLE: u32 = 1 << 1;
+pub const TRB_ISP: u32 = 1 << 2;
+pub const TRB_IOC: u32 = 1 << 5;
+pub const TRB_IDT: u32 = 1 << 6;
+pub const TRB_DIR_IN: u32 = 1 << 16;
+
+// completion codes
+pub const CC_SUCCESS: u8 = 1;
+pub const CC_STALL: u8 = 6;
+pub const CC_SHORT_PACKET: u8 = 13;
+
+/// A transfer request block
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Trb {
+    pub param: u64,
+    pub status: u32,
+    pub control: u32,
+}
+
+impl Trb {
+    pub fn new(ty: u32, param: u64, status: u32, flags: u32) -> Self {
+        Self {
+            param,
+            status,
+            control: ty << 10 | flags,
+        }
+    }
+
+    pub fn ty(&self) -> u32 {
+        (self.control >> 10) & 0x3F
+    }
+
+    pub fn cycle(&self) -> bool {
+        (self.control & TRB_CYCLE) != 0
+    }
+
+    /// Returns the completion code of an event
+    pub fn completion_code(&self) -> u8 {
+        (self.status >> 24) as u8
+    }
+
+    /// Returns the number of bytes that have not been transferred (transfer events only)
+    pub fn residual(&self) -> usize {
+        (self.status & 0xFF_FFFF) as usize
+    }
+
+    /// Returns the slot id of an event
+    pub fn slot(&self) -> u8 {
+        (self.control >> 24) as u8
+    }
+
+    /// Returns the endpoint (device context index) of a transfer event
+    pub fn endpoint(&self) -> u8 {
+        ((self.control >> 16) & 0x1F) as u8
+    }
+}
+
+fn trb_addr(base: GlobOff, idx: usize) -> GlobOff {
+    base + (idx * TRB_SIZE) as GlobOff
+}
+
+fn clear(dma: &MemGate, addr: GlobOff, size: usize) -> Result<(), Error> {
+    let zeros = [Trb::default(); 16];
+    for i in (0..size).step_by(zeros.len()) {
+        let count = zeros.len().min(size - i);
+        dma.write(&zeros[0..count], trb_addr(addr, i))?;
+    }
+    Ok(())
+}
+
+/// A ring the driver produces TRBs on (command and transfer rings)
+///
+/// The ring consists of a single segment whose last TRB links back to the beginning.
+pub struct Ring {
+    addr: GlobOff,
+    size: usize,
+    enqueue: usize,
+    cycle: bool,
+}
+
+impl Ring {
+    /// Creates a new ring with `size` TRBs at address `addr` within the DMA memory
+    pub fn new(dma: &MemGate, addr: GlobOff, size: usize) -> Result<Self, Error> {
+        clear(dma, addr, size)?;
+
+        let link = Trb::new(TRB_LINK, addr, 0, TRB_TOGGLE_CYCLE);
+        dma.write_obj(&link, trb_addr(addr, size - 1))?;
+
+        Ok(Self {
+            addr,
+            size,
+            enqueue: 0,
+            cycle: true,
+        })
+    }
+
+    /// Returns the address of the ring
+    pub fn addr(&self) -> GlobOff {
+        self.addr
+    }
+
+    /// Returns the producer cycle state
+    pub fn cycle(&self) -> bool {
+        self.cycle
+    }
+
+    /// Returns the address of the next TRB to be written
+    pub fn enqueue_addr(&self) -> GlobOff {
+        trb_addr(self.addr, self.enqueue)
+    }
+
+    /// Appends the given TRB to the ring and returns its address
+    ///
+    /// The cycle bit of the TRB is set by the ring.
+    pub fn push(&mut self, dma: &MemGate, mut trb: Trb) -> Result<GlobOff, Error> {
+        let addr = self.enqueue_addr();
+        trb.control = (trb.control & !TRB_CYCLE) | self.cycle as u32;
+        dma.write_obj(&trb, addr)?;
+
+        self.enqueue += 1;
+        if self.enqueue == self.size - 1 {
+            // hand the link TRB to the controller and continue at the beginning
+            let link = Trb::new(TRB_LINK, self.addr, 0, TRB_TOGGLE_CYCLE | self.cycle as u32);
+            dma.write_obj(&link, trb_addr(self.addr, self.enqueue))?;
+            self.enqueue = 0;
+            self.cycle = !self.cycle;
+        }
+        Ok(addr)
+    }
+}
+
+/// The event ring, on which the controller produces TRBs
+///
+/// The ring consists of a single segment, described by the event ring segment table.
+pub struct EventRing {
+    addr: GlobOff,
+    size: usize,
+    dequeue: usize,
+    cycle: bool,
+}
+
+impl EventRing {
+    /// Creates a new event ring with `size` TRBs at address `addr` within the DMA memory
+    pub fn new(dma: &MemGate, addr: GlobOff, size: usize) -> Result<Self, Error> {
+        clear(dma, addr, size)?;
+
+        Ok(Self {
+            addr,
+            size,
+            dequeue: 0,
+            cycle: true,
+        })
+    }
+
+    /// Returns the address of the next TRB to be consumed
+    pub fn dequeue_addr(&self) -> GlobOff {
+        trb_addr(self.addr, self.dequeue)
+    }
+
+    /// Returns the next event or `None` if the controller has not produced a new event
+    pub fn pop(&mut self, dma: &MemGate) -> Result<Option<Trb>, Error> {
+        let trb: Trb = dma.read_obj(self.dequeue_addr())?;
+        if trb.cycle() != self.cycle {
+            return Ok(None);
+        }
+
+        self.dequeue += 1;
+        if self.dequeue == self.size {
+            self.dequeue = 0;
+            self.cycle = !self.cycle;
+        }
+        Ok(Some(trb))
+    }
+}
@@ -0,0 +1,247 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! Common infrastructure for virtio drivers
+//!
+//! This crate implements the legacy virtio PCI transport (as provided by transitional devices of
+//! QEMU, for example) and split virtqueues. The device is accessed via the PCI device tile as
+//! usual: the I/O registers of BAR0 are available at the register region and all DMA transfers of
+//! the device refer to a single memory region that is owned by the driver. All addresses that are
+//! handed to the device (queues and buffers) are therefore offsets within this region.
+
+#![no_std]
+
+mod queue;
+
+pub use queue::{Buffer, VirtQueue};
+
+use bitflags::bitflags;
+
+use num_enum::IntoPrimitive;
+
+use m3::com::MemGate;
+use m3::errors::{Code, Error};
+use m3::io::LogFlags;
+use m3::kif::{Perm, TileISA};
+use m3::log;
+use m3::mem::GlobOff;
+
+const VIRTIO_VENDOR: u16 = 0x1AF4;
+// transitional devices use 0x1000 + device type - 1 as the PCI device id
+const LEGACY_DEVICE_BASE: u16 = 0x1000;
+
+// the device-specific configuration follows the common registers (without MSI-X)
+const DEV_CONFIG_OFF: GlobOff = 0x14;
+
+/// The virtio device types that are supported
+#[derive(Copy, Clone, Debug, Eq, PartialEq, IntoPrimitive)]
+#[repr(u16)]
+pub enum DeviceType {
+    Net   = 1,
+    Block = 2,
+}
+
+/// Registers of the legacy PCI transport
+#[derive(Copy, Clone, Debug, Eq, PartialEq, IntoPrimitive)]
+#[repr(u64)]
+enum Reg {
+    DeviceFeatures = 0x00,
+    DriverFeatures = 0x04,
+    QueueAddr      = 0x08,
+    QueueSize      = 0x0C,
+    QueueSelect    = 0x0E,
+    QueueNotify    = 0x10,
+    Status         = 0x12,
+    ISRStatus      = 0x13,
+}
+
+bitflags! {
+    /// The device status
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    pub struct DeviceStatus : u8 {
+        /// The driver has noticed the device
+        const ACKNOWLEDGE = 1 << 0;
+        /// The driver knows how to drive the device
+        const DRIVER = 1 << 1;
+        /// The driver is ready to use the device
+        const DRIVER_OK = 1 << 2;
+        /// The device needs to be reset due to an error
+        const NEEDS_RESET = 1 << 6;
+        /// Something went wrong in the driver
+        const FAILED = 1 << 7;
+    }
+}
+
+/// A virtio device that is attached via PCI
+pub struct Device {
+    ty: DeviceType,
+    pci: pci::Device,
+    dma: MemGate,
+    features: u32,
+}
+
+impl Device {
+    /// Creates a new driver for the virtio device of type `ty` on a tile with given ISA.
+    ///
+    /// The device is reset and acknowledged, but not yet ready to use. The driver needs to
+    /// negotiate the features, allocate the DMA memory, setup the queues, and call
+    /// [`Device::finish_init`] afterwards.
+    pub fn new(name: &str, isa: TileISA, ty: DeviceType) -> Result<Self, Error> {
+        let pci = pci::Device::new(name, isa)?;
+
+        let info = pci.get_info()?;
+        if info.vendor() != VIRTIO_VENDOR || info.device() != LEGACY_DEVICE_BASE + ty as u16 - 1 {
+            log!(
+                LogFlags::LibVirtio,
+                "virtio: unsupported device {:x}:{:x}",
+                info.vendor(),
+                info.device()
+            );
+            return Err(Error::new(Code::NotSup));
+        }
+
+        // enable the I/O space and bus mastering
+        let cmd: u16 = pci.read_config(pci::Reg::Command.into())?;
+        pci.write_config(pci::Reg::Command.into(), cmd | 0x01 | 0x04)?;
+
+        let dev = Self {
+            ty,
+            pci,
+            // replaced as soon as the driver knows how much memory it needs
+            dma: MemGate::new(1, Perm::RW)?,
+            features: 0,
+        };
+
+        // writing zero resets the device
+        dev.set_status(DeviceStatus::empty())?;
+        dev.set_status(DeviceStatus::ACKNOWLEDGE | DeviceStatus::DRIVER)?;
+        Ok(dev)
+    }
+
+    /// Returns the type of the device
+    pub fn device_type(&self) -> DeviceType {
+        self.ty
+    }
+
+    /// Returns the negotiated features
+    pub fn features(&self) -> u32 {
+        self.features
+    }
+
+    /// Negotiates the features with the device: the features in `wanted` that are supported by
+    /// the device are enabled and returned.
+    pub fn negotiate(&mut self, wanted: u32) -> Result<u32, Error> {
+        let offered: u32 = self.read_reg(Reg::DeviceFeatures)?;
+        self.features = offered & wanted;
+        log!(
+            LogFlags::LibVirtio,
+            "virtio[{:?}]: device offers {:#x}, using {:#x}",
+            self.ty,
+            offered,
+            self.features
+        );
+        self.write_reg(Reg::DriverFeatures, self.features)?;
+        Ok(self.features)
+    }
+
+    /// Returns the number of descriptors of queue `idx`, which is fixed by the device
+    pub fn queue_size(&self, idx: u16) -> Result<u16, Error> {
+        self.write_reg(Reg::QueueSelect, idx)?;
+        match self.read_reg::<u16>(Reg::QueueSize)? {
+            0 => Err(Error::new(Code::NotFound)),
+            size => Ok(size),
+        }
+    }
+
+    /// Allocates `size` bytes of memory for all DMA transfers of the device
+    pub fn alloc_dma(&mut self, size: usize) -> Result<(), Error> {
+        self.dma = MemGate::new(size as GlobOff, Perm::RW)?;
+        let dev_buf = self.dma.derive_cap(0, size as GlobOff, Perm::RW)?;
+        self.pci.set_dma_buffer(&dev_buf)
+    }
+
+    /// Returns the memory for DMA transfers
+    pub fn dma(&self) -> &MemGate {
+        &self.dma
+    }
+
+    /// Sets up queue `idx` at offset `off` in the DMA memory. The offset needs to be page aligned
+    /// and the memory needs to have room for [`VirtQueue::mem_size`] bytes.
+    pub fn setup_queue(&self, idx: u16, off: GlobOff) -> Result<VirtQueue, Error> {
+        let size = self.queue_size(idx)?;
+        let queue = VirtQueue::new(&self.dma, idx, off, size)?;
+        log!(
+            LogFlags::LibVirtio,
+            "virtio[{:?}]: queue {} with {} descriptors at {:#x}",
+            self.ty,
+            idx,
+            size,
+            off
+        );
+        self.write_reg(Reg::QueueAddr, (off / queue::QUEUE_ALIGN as GlobOff) as u32)?;
+        Ok(queue)
+    }
+
+    /// Tells the device that the driver is ready
+    pub fn finish_init(&self) -> Result<(), Error> {
+        self.set_status(DeviceStatus::ACKNOWLEDGE | DeviceStatus::DRIVER | DeviceStatus::DRIVER_OK)
+    }
+
+    /// Notifies the device about new buffers in the given queue
+    pub fn notify(&self, queue: &VirtQueue) -> Result<(), Error> {
+        self.write_reg(Reg::QueueNotify, queue.index())
+    }
+
+    /// Returns the device status
+    pub fn status(&self) -> Result<DeviceStatus, Error> {
+        Ok(DeviceStatus::from_bits_retain(self.read_reg(Reg::Status)?))
+    }
+
+    /// Reads from the device-specific configuration
+    pub fn read_config<T>(&self, off: GlobOff) -> Result<T, Error> {
+        self.pci.read_reg(DEV_CONFIG_OFF + off)
+    }
+
+    /// Returns true if an interrupt is pending and acknowledges it at the device
+    pub fn check_irq(&self) -> Result<bool, Error> {
+        let irq = self.pci.check_for_irq();
+        // reading the ISR status acknowledges the interrupt
+        let isr: u8 = self.read_reg(Reg::ISRStatus)?;
+        Ok(irq || isr != 0)
+    }
+
+    /// Waits for the next interrupt and acknowledges it at the device
+    pub fn wait_irq(&self) -> Result<(), Error> {
+        log!(
+            LogFlags::LibVirtio,
+            "virtio[{:?}]: waiting for IRQ",
+            self.ty
+        );
+        self.pci.wait_for_irq()?;
+        self.read_reg::<u8>(Reg::ISRStatus).map(|_| ())
+    }
+
+    fn set_status(&self, status: DeviceStatus) -> Result<(), Error> {
+        self.write_reg(Reg::Status, status.bits())
+    }
+
+    fn read_reg<T>(&self, reg: Reg) -> Result<T, Error> {
+        self.pci.read_reg(reg.into())
+    }
+
+    fn write_reg<T>(&self, reg: Reg, val: T) -> Result<(), Error> {
+        self.pci.write_reg(reg.into(), val)
+    }
+}
@@ -0,0 +1,256 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use core::mem::size_of;
+
+use m3::col::Vec;
+use m3::com::MemGate;
+use m3::errors::{Code, Error};
+use m3::io::LogFlags;
+use m3::log;
+use m3::mem::GlobOff;
+use m3::util::math;
+use m3::vec;
+
+/// The alignment of the queue and its used ring (legacy interface)
+pub(crate) const QUEUE_ALIGN: usize = 4096;
+
+const DESC_F_NEXT: u16 = 1 << 0;
+const DESC_F_WRITE: u16 = 1 << 1;
+
+// the rings start with flags and index
+const RING_HEADER: usize = 4;
+
+#[repr(C)]
+struct Descriptor {
+    addr: u64,
+    len: u32,
+    flags: u16,
+    next: u16,
+}
+
+#[repr(C)]
+struct UsedElem {
+    id: u32,
+    len: u32,
+}
+
+/// A buffer in the DMA memory that is passed to the device
+#[derive(Copy, Clone, Debug)]
+pub struct Buffer {
+    /// The offset within the DMA memory
+    pub addr: GlobOff,
+    /// The length in bytes
+    pub len: u32,
+    /// Whether the device writes to the buffer (otherwise, it reads from it)
+    pub writable: bool,
+}
+
+impl Buffer {
+    pub fn new(addr: GlobOff, len: usize, writable: bool) -> Self {
+        Self {
+            addr,
+            len: len as u32,
+            writable,
+        }
+    }
+}
+
+/// A split virtqueue
+///
+/// The queue consists of the descriptor table, the available ring, which contains the descriptor
+/// chains for the device, and the used ring, which contains the chains that the device is done
+/// with. All of them reside in the DMA memory; the queue keeps a shadow copy of the descriptor
+/// links to manage the free descriptors without reading them back.
+pub struct VirtQueue {
+    idx: u16,
+    off: GlobOff,
+    size: u16,
+    free_head: u16,
+    num_free: u16,
+    avail_idx: u16,
+    last_used: u16,
+    next: Vec<u16>,
+    chain_len: Vec<u16>,
+}
+
+impl VirtQueue {
+    /// Returns the number of bytes required for a queue with `size` descriptors
+    pub const fn mem_size(size: u16) -> usize {
+        let size = size as usize;
+        let avail_end = size * size_of::<Descriptor>() + RING_HEADER + size * 2 + 2;
+        let used = RING_HEADER + size * size_of::<UsedElem>() + 2;
+        Self::align(avail_end) + Self::align(used)
+    }
+
+    const fn align(bytes: usize) -> usize {
+        (bytes + QUEUE_ALIGN - 1) & !(QUEUE_ALIGN - 1)
+    }
+
+    /// Creates a new queue with index `idx` and `size` descriptors, which resides at offset `off`
+    /// in the DMA memory `mem`.
+    ///
+    /// The offset needs to be aligned to 4096 bytes and the memory needs to provide
+    /// [`VirtQueue::mem_size`] bytes.
+    pub fn new(mem: &MemGate, idx: u16, off: GlobOff, size: u16) -> Result<Self, Error> {
+        if !math::is_aligned(off, QUEUE_ALIGN as GlobOff) {
+            return Err(Error::new(Code::InvArgs));
+        }
+
+        // the rings need to start empty
+        let zeros = [0u8; 64];
+        let mut pos = 0;
+        let total = Self::mem_size(size);
+        while pos < total {
+            let amount = zeros.len().min(total - pos);
+            mem.write(&zeros[0..amount], off + pos as GlobOff)?;
+            pos += amount;
+        }
+
+        Ok(Self {
+            idx,
+            off,
+            size,
+            free_head: 0,
+            num_free: size,
+            avail_idx: 0,
+            last_used: 0,
+            next: (1..=size).collect(),
+            chain_len: vec![0; size as usize],
+        })
+    }
+
+    /// Returns the index of the queue
+    pub fn index(&self) -> u16 {
+        self.idx
+    }
+
+    /// Returns the number of descriptors
+    pub fn size(&self) -> u16 {
+        self.size
+    }
+
+    /// Returns the number of free descriptors
+    pub fn num_free(&self) -> usize {
+        self.num_free as usize
+    }
+
+    /// Makes the given buffers available to the device as a single descriptor chain.
+    ///
+    /// Returns the token of the chain (the index of its first descriptor), which is returned by
+    /// [`VirtQueue::pop_used`] as soon as the device is done with the chain. Note that the device
+    /// needs to be notified afterwards.
+    pub fn add(&mut self, mem: &MemGate, bufs: &[Buffer]) -> Result<u16, Error> {
+        if bufs.is_empty() {
+            return Err(Error::new(Code::InvArgs));
+        }
+        if bufs.len() > self.num_free as usize {
+            return Err(Error::new(Code::NoSpace));
+        }
+
+        let head = self.free_head;
+        let mut cur = head;
+        for (i, buf) in bufs.iter().enumerate() {
+            let last = i == bufs.len() - 1;
+            let desc = Descriptor {
+                addr: buf.addr,
+                len: buf.len,
+                flags: if buf.writable { DESC_F_WRITE } else { 0 }
+                    | if last { 0 } else { DESC_F_NEXT },
+                next: if last { 0 } else { self.next[cur as usize] },
+            };
+            mem.write_obj(&desc, self.desc_off(cur))?;
+            if !last {
+                cur = self.next[cur as usize];
+            }
+        }
+        self.free_head = self.next[cur as usize];
+        self.num_free -= bufs.len() as u16;
+        self.chain_len[head as usize] = bufs.len() as u16;
+
+        // put the chain into the available ring and publish it afterwards
+        let slot = self.avail_idx % self.size;
+        mem.write_obj(
+            &head,
+            self.avail_off() + (RING_HEADER + slot as usize * 2) as GlobOff,
+        )?;
+        self.avail_idx = self.avail_idx.wrapping_add(1);
+        mem.write_obj(&self.avail_idx, self.avail_off() + 2)?;
+
+        log!(
+            LogFlags::LibVirtio,
+            "virtio: queue {}: added chain {} with {} buffers",
+            self.idx,
+            head,
+            bufs.len()
+        );
+        Ok(head)
+    }
+
+    /// Returns true if the device is done with at least one chain
+    pub fn has_used(&self, mem: &MemGate) -> Result<bool, Error> {
+        let used_idx: u16 = mem.read_obj(self.used_off() + 2)?;
+        Ok(used_idx != self.last_used)
+    }
+
+    /// Fetches the next chain the device is done with and frees its descriptors.
+    ///
+    /// Returns the token of the chain and the number of bytes the device has written into it.
+    pub fn pop_used(&mut self, mem: &MemGate) -> Result<Option<(u16, u32)>, Error> {
+        if !self.has_used(mem)? {
+            return Ok(None);
+        }
+
+        let slot = self.last_used % self.size;
+        let elem: UsedElem = mem.read_obj(
+            self.used_off() + (RING_HEADER + slot as usize * size_of::<UsedElem>()) as GlobOff,
+        )?;
+        self.last_used = self.last_used.wrapping_add(1);
+
+        // put the chain back into the free list
+        let head = elem.id as u16;
+        let count = self.chain_len[head as usize];
+        let mut cur = head;
+        for _ in 1..count {
+            cur = self.next[cur as usize];
+        }
+        self.next[cur as usize] = self.free_head;
+        self.free_head = head;
+        self.num_free += count;
+
+        log!(
+            LogFlags::LibVirtio,
+            "virtio: queue {}: chain {} used with {} bytes",
+            self.idx,
+            head,
+            elem.len
+        );
+        Ok(Some((head, elem.len)))
+    }
+
+    fn desc_off(&self, idx: u16) -> GlobOff {
+        self.off + (idx as usize * size_of::<Descriptor>()) as GlobOff
+    }
+
+    fn avail_off(&self) -> GlobOff {
+        self.off + (self.size as usize * size_of::<Descriptor>()) as GlobOff
+    }
+
+    fn used_off(&self) -> GlobOff {
+        let avail_end =
+            self.size as usize * size_of::<Descriptor>() + RING_HEADER + self.size as usize * 2 + 2;
+        self.off + Self::align(avail_end) as GlobOff
+    }
+}
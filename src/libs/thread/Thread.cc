@@ -26,16 +26,35 @@ namespace m3 {
 
 int Thread::_next_id = 1;
 
-Thread::Thread(thread_func func, void *arg) : _id(_next_id++), _regs(), _stack(), _event(0) {
-    // TODO
-    // better leave one page before and behind each stack free to detect stack-under-/overflows
-    void *addr = malloc(T_STACK_SZ);
+Thread::Thread(thread_func func, void *arg, size_t stack_size)
+    : _id(_next_id++),
+      _regs(),
+      _stack(),
+      // keep the stack pointer 16-byte aligned
+      _stack_size(stack_size & ~static_cast<size_t>(15)),
+      _event(0) {
+    if(_stack_size < MIN_STACK_SZ)
+        panic("Stack size {} is too small"_cf, stack_size);
+
+    void *addr = malloc(_stack_size);
+    if(!addr)
+        panic("Unable to allocate stack of {} bytes"_cf, _stack_size);
 
     _stack = reinterpret_cast<word_t *>(reinterpret_cast<uintptr_t>(addr));
-    thread_init(func, arg, &_regs, _stack);
+    // stacks grow downwards, so that an overflow overwrites the canary at the bottom first
+    for(size_t i = 0; i < STACK_CANARY_WORDS; ++i)
+        _stack[i] = STACK_CANARY;
+    thread_init(func, arg, &_regs, _stack, _stack_size / sizeof(word_t));
     ThreadManager::get().add(this);
 }
 
+void Thread::check_stack() const {
+    for(size_t i = 0; _stack && i < STACK_CANARY_WORDS; ++i) {
+        if(_stack[i] != STACK_CANARY)
+            panic("Stack overflow in thread {} (stack size: {} bytes)"_cf, _id, _stack_size);
+    }
+}
+
 Thread::~Thread() {
     ThreadManager::get().remove(this);
     if(_stack)
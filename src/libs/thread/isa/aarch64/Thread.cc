@@ -0,0 +1,27 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+#include <thread/Thread.h>
+
+namespace m3 {
+
+void thread_init(thread_func func, void *arg, Regs *regs, word_t *stack, size_t stack_words) {
+    regs->x0 = reinterpret_cast<word_t>(arg);
+    regs->sp = reinterpret_cast<word_t>(stack + stack_words - 2);
+    regs->x29 = 0;
+    regs->x30 = reinterpret_cast<word_t>(func);
+}
+
+}
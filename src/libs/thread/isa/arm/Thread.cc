@@ -20,12 +20,12 @@
 
 namespace m3 {
 
-void thread_init(thread_func func, void *arg, Regs *regs, word_t *stack) {
-    regs->r0 = reinterpret_cast<word_t>(arg);                        // arg
-    regs->r13 = reinterpret_cast<word_t>(stack + T_STACK_WORDS - 2); // sp
-    regs->r11 = 0;                                                   // fp
-    regs->r14 = reinterpret_cast<word_t>(func);                      // lr
-    regs->cpsr = 0x13;                                               // supervisor mode
+void thread_init(thread_func func, void *arg, Regs *regs, word_t *stack, size_t stack_words) {
+    regs->r0 = reinterpret_cast<word_t>(arg);                      // arg
+    regs->r13 = reinterpret_cast<word_t>(stack + stack_words - 2); // sp
+    regs->r11 = 0;                                                 // fp
+    regs->r14 = reinterpret_cast<word_t>(func);                    // lr
+    regs->cpsr = 0x13;                                             // supervisor mode
 }
 
 }
@@ -20,9 +20,9 @@
 
 namespace m3 {
 
-void thread_init(thread_func func, void *arg, Regs *regs, word_t *stack) {
+void thread_init(thread_func func, void *arg, Regs *regs, word_t *stack, size_t stack_words) {
     regs->a0 = reinterpret_cast<word_t>(arg);
-    regs->sp = reinterpret_cast<word_t>(stack + T_STACK_WORDS - 2);
+    regs->sp = reinterpret_cast<word_t>(stack + stack_words - 2);
     regs->fp = 0;
     regs->ra = reinterpret_cast<word_t>(func);
 }
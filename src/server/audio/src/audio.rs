@@ -0,0 +1,241 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+#![no_std]
+
+mod backend;
+mod null;
+mod stream;
+
+use m3::boxed::Box;
+use m3::cell::LazyStaticRefCell;
+use m3::client::AudioConfig;
+use m3::col::Vec;
+use m3::com::{opcodes, GateIStream, MemCap, Perm};
+use m3::env;
+use m3::errors::{Code, Error};
+use m3::io::LogFlags;
+use m3::kif;
+use m3::log;
+use m3::println;
+use m3::reply_vmsg;
+use m3::server::{
+    CapExchange, ClientManager, ExcType, RequestHandler, RequestSession, Server, ServerSession,
+    SessId, DEF_MAX_CLIENTS,
+};
+use m3::tiles::OwnActivity;
+use m3::time::TimeDuration;
+use m3::watchdog;
+
+use backend::PcmDevice;
+use null::NullDevice;
+use stream::Stream;
+
+const MAX_BUFFER: usize = 256 * 1024;
+const DEF_PERIOD_MS: u64 = 10;
+const DEF_PERIODS: usize = 4;
+const MAX_PERIODS: usize = 16;
+
+static STREAM: LazyStaticRefCell<Stream> = LazyStaticRefCell::default();
+
+struct AudioSession {
+    serv: ServerSession,
+    // the capabilities for the ring buffer that have been handed out to the client
+    mems: Vec<MemCap>,
+}
+
+impl RequestSession for AudioSession {
+    fn new(serv: ServerSession, _arg: &str) -> Result<Self, Error>
+    where
+        Self: Sized,
+    {
+        log!(LogFlags::AudioReqs, "[{}] audio::open()", serv.id());
+
+        Ok(AudioSession {
+            serv,
+            mems: Vec::new(),
+        })
+    }
+
+    fn close(&mut self, cli: &mut ClientManager<Self>, sid: SessId, _sub_ids: &mut Vec<SessId>) {
+        log!(LogFlags::AudioReqs, "[{}] audio::close()", sid);
+
+        STREAM.borrow_mut().release(sid, cli.recv_gate());
+    }
+}
+
+impl AudioSession {
+    fn configure(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let wanted: AudioConfig = is.pop()?;
+
+        let layout = STREAM.borrow_mut().configure(self.serv.id(), wanted)?;
+
+        log!(
+            LogFlags::AudioReqs,
+            "[{}] audio::configure(wanted={:?}) -> (cfg={:?}, period_size={}, periods={})",
+            self.serv.id(),
+            wanted,
+            layout.cfg,
+            layout.period_size,
+            layout.periods
+        );
+
+        reply_vmsg!(
+            is,
+            Code::Success,
+            layout.cfg,
+            layout.period_size,
+            layout.periods
+        )
+    }
+
+    fn get_mem(
+        cli: &mut ClientManager<Self>,
+        _crt: usize,
+        sid: SessId,
+        xchg: &mut CapExchange<'_>,
+    ) -> Result<(), Error> {
+        log!(LogFlags::AudioReqs, "[{}] audio::get_mem()", sid);
+
+        let sess = cli.get_mut(sid).ok_or_else(|| Error::new(Code::InvArgs))?;
+
+        let stream = STREAM.borrow();
+        let layout = stream.layout_of(sid)?;
+        let (mem, off) = stream.device().buffer();
+        let cap = mem.derive_cap(off, layout.buffer_size() as _, Perm::RW)?;
+        let sel = cap.sel();
+        sess.mems.push(cap);
+
+        xchg.out_caps(kif::CapRngDesc::new(kif::CapType::Object, sel, 1));
+        Ok(())
+    }
+
+    fn commit(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let amount: usize = is.pop()?;
+
+        let free = STREAM.borrow_mut().commit(self.serv.id(), amount)?;
+
+        log!(
+            LogFlags::AudioReqs,
+            "[{}] audio::commit(amount={}) -> {}",
+            self.serv.id(),
+            amount,
+            free
+        );
+
+        reply_vmsg!(is, Code::Success, free)
+    }
+
+    fn wait(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        log!(LogFlags::AudioReqs, "[{}] audio::wait()", self.serv.id());
+
+        // the reply is sent as soon as a period is free
+        let msg = is.take_msg();
+        STREAM.borrow_mut().wait(self.serv.id(), is.rgate(), msg)
+    }
+
+    fn drain(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        log!(LogFlags::AudioReqs, "[{}] audio::drain()", self.serv.id());
+
+        // the reply is sent as soon as everything has been played
+        let msg = is.take_msg();
+        STREAM.borrow_mut().drain(self.serv.id(), is.rgate(), msg)
+    }
+}
+
+fn usage() -> ! {
+    println!(
+        "Usage: {} [-p <ms>] [-n <periods>]",
+        env::args().next().unwrap()
+    );
+    println!();
+    println!(
+        "  -p: the duration of a period in milliseconds ({} by default)",
+        DEF_PERIOD_MS
+    );
+    println!(
+        "  -n: the number of periods in the ring buffer (2..{}; {} by default)",
+        MAX_PERIODS, DEF_PERIODS
+    );
+    OwnActivity::exit_with(Code::InvArgs);
+}
+
+fn parse_num(args: &[&str], name: &str, def: usize) -> usize {
+    match args.iter().position(|a| *a == name) {
+        Some(idx) => args
+            .get(idx + 1)
+            .and_then(|n| n.parse::<usize>().ok())
+            .filter(|n| *n > 0)
+            .unwrap_or_else(|| usage()),
+        None => def,
+    }
+}
+
+#[no_mangle]
+pub fn main() -> Result<(), Error> {
+    let args: Vec<&str> = env::args().collect();
+
+    let period_ms = parse_num(&args, "-p", DEF_PERIOD_MS as usize);
+    let periods = parse_num(&args, "-n", DEF_PERIODS);
+    if !(2..=MAX_PERIODS).contains(&periods) {
+        usage();
+    }
+
+    let dev: Box<dyn PcmDevice> =
+        Box::new(NullDevice::new(MAX_BUFFER).expect("Unable to create null device"));
+    STREAM.set(Stream::new(
+        dev,
+        TimeDuration::from_millis(period_ms as u64),
+        periods,
+    ));
+
+    let mut hdl = RequestHandler::new_with(DEF_MAX_CLIENTS, 128, 1)
+        .expect("Unable to create request handler");
+    let srv = Server::new("audio", &mut hdl).expect("Unable to create service 'audio'");
+
+    use opcodes::Audio;
+    hdl.reg_cap_handler(Audio::GetMem, ExcType::Obt(1), AudioSession::get_mem);
+    hdl.reg_msg_handler(Audio::Configure, AudioSession::configure);
+    hdl.reg_msg_handler(Audio::Commit, AudioSession::commit);
+    hdl.reg_msg_handler(Audio::Wait, AudioSession::wait);
+    hdl.reg_msg_handler(Audio::Drain, AudioSession::drain);
+
+    loop {
+        watchdog::check_in();
+
+        if srv.fetch_and_handle(&mut hdl).is_err() {
+            break;
+        }
+
+        hdl.fetch_and_handle_msg();
+
+        // fetch the played periods, which wakes up waiting clients
+        STREAM.borrow_mut().poll(hdl.clients_mut().recv_gate());
+
+        // sleep until the device needs to be checked, a message arrives, or we need to check in
+        let timeout = match (STREAM.borrow().next_timeout(), watchdog::next_timeout()) {
+            (Some(dev), Some(wd)) => Some(dev.min(wd)),
+            (dev, wd) => dev.or(wd),
+        };
+        match timeout {
+            Some(t) => OwnActivity::sleep_for(t).ok(),
+            None => OwnActivity::sleep().ok(),
+        };
+    }
+
+    STREAM.unset();
+
+    Ok(())
+}
@@ -0,0 +1,92 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use m3::client::{AudioConfig, SampleFormat};
+use m3::col::Vec;
+use m3::com::MemGate;
+use m3::errors::Error;
+use m3::mem::GlobOff;
+use m3::time::TimeDuration;
+
+/// The configurations a device supports
+pub struct PcmCaps {
+    /// The supported sample rates in ascending order
+    pub rates: Vec<u32>,
+    /// The supported sample formats
+    pub formats: Vec<SampleFormat>,
+    pub min_channels: u8,
+    pub max_channels: u8,
+}
+
+impl PcmCaps {
+    /// Returns the supported configuration that comes closest to `wanted`
+    ///
+    /// If the wanted rate is not supported, the closest supported rate is used. If the wanted
+    /// format is not supported, 16-bit samples are preferred.
+    pub fn negotiate(&self, wanted: AudioConfig) -> AudioConfig {
+        let rate = *self
+            .rates
+            .iter()
+            .min_by_key(|r| r.abs_diff(wanted.rate))
+            .unwrap();
+        let format = if self.formats.contains(&wanted.format) {
+            wanted.format
+        }
+        else if self.formats.contains(&SampleFormat::S16LE) {
+            SampleFormat::S16LE
+        }
+        else {
+            self.formats[0]
+        };
+        let channels = wanted.channels.clamp(self.min_channels, self.max_channels);
+        AudioConfig::new(rate, channels, format)
+    }
+}
+
+pub trait PcmDevice {
+    /// Returns the supported configurations
+    fn caps(&self) -> &PcmCaps;
+
+    /// Returns the maximum size of the ring buffer in bytes
+    fn max_buffer(&self) -> usize;
+
+    /// Returns the memory that contains the ring buffer and the offset of the buffer within it
+    fn buffer(&self) -> (&MemGate, GlobOff);
+
+    /// Prepares the device to play a ring buffer of `periods` periods with `period_size` bytes
+    /// each in the given configuration
+    fn prepare(
+        &mut self,
+        cfg: AudioConfig,
+        period_size: usize,
+        periods: usize,
+    ) -> Result<(), Error>;
+
+    /// Hands the period with given index over to the device
+    ///
+    /// The device starts to play with the first submitted period and plays the periods in the
+    /// order of submission.
+    fn submit(&mut self, period: usize) -> Result<(), Error>;
+
+    /// Returns the number of periods the device has played since the last call
+    fn completed(&mut self) -> Result<usize, Error>;
+
+    /// Returns the time after which [`PcmDevice::completed`] should be called again or `None` if
+    /// no period is in flight
+    fn next_timeout(&self) -> Option<TimeDuration>;
+
+    /// Stops the playback and drops all periods that have not been played yet
+    fn stop(&mut self) -> Result<(), Error>;
+}
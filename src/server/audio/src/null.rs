@@ -0,0 +1,138 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use m3::client::{AudioConfig, SampleFormat};
+use m3::com::{MemGate, Perm};
+use m3::errors::Error;
+use m3::io::LogFlags;
+use m3::log;
+use m3::mem::GlobOff;
+use m3::time::{TimeDuration, TimeInstant};
+use m3::vec;
+
+use crate::backend::{PcmCaps, PcmDevice};
+
+/// A sound device without hardware behind it
+///
+/// The ring buffer is held in ordinary memory and the periods are "played" in real time, that is,
+/// each period completes after the time it would take to play it. This allows to use the audio
+/// service and its clients (e.g., for benchmarks) on platforms without sound device.
+pub struct NullDevice {
+    caps: PcmCaps,
+    mem: MemGate,
+    max_buffer: usize,
+    period_time: TimeDuration,
+    // the number of submitted periods that have not been played yet
+    queued: usize,
+    // the time at which the first queued period has been played
+    next: Option<TimeInstant>,
+}
+
+impl NullDevice {
+    pub fn new(max_buffer: usize) -> Result<Self, Error> {
+        let caps = PcmCaps {
+            rates: vec![8000, 11025, 16000, 22050, 32000, 44100, 48000, 96000],
+            formats: vec![SampleFormat::U8, SampleFormat::S16LE, SampleFormat::S32LE],
+            min_channels: 1,
+            max_channels: 8,
+        };
+        let mem = MemGate::new(max_buffer as GlobOff, Perm::RW)?;
+
+        log!(LogFlags::AudioDev, "null: {} bytes buffer", max_buffer);
+
+        Ok(Self {
+            caps,
+            mem,
+            max_buffer,
+            period_time: TimeDuration::ZERO,
+            queued: 0,
+            next: None,
+        })
+    }
+}
+
+impl PcmDevice for NullDevice {
+    fn caps(&self) -> &PcmCaps {
+        &self.caps
+    }
+
+    fn max_buffer(&self) -> usize {
+        self.max_buffer
+    }
+
+    fn buffer(&self) -> (&MemGate, GlobOff) {
+        (&self.mem, 0)
+    }
+
+    fn prepare(
+        &mut self,
+        cfg: AudioConfig,
+        period_size: usize,
+        _periods: usize,
+    ) -> Result<(), Error> {
+        let nanos = period_size as u64 * 1_000_000_000 / cfg.bytes_per_sec() as u64;
+        self.period_time = TimeDuration::from_nanos(nanos);
+        log!(
+            LogFlags::AudioDev,
+            "null: prepare {:?} with {:?} per period",
+            cfg,
+            self.period_time
+        );
+        Ok(())
+    }
+
+    fn submit(&mut self, period: usize) -> Result<(), Error> {
+        log!(LogFlags::AudioDev, "null: submit period {}", period);
+        if self.next.is_none() {
+            self.next = Some(TimeInstant::now() + self.period_time);
+        }
+        self.queued += 1;
+        Ok(())
+    }
+
+    fn completed(&mut self) -> Result<usize, Error> {
+        let now = TimeInstant::now();
+        let mut count = 0;
+        while let Some(next) = self.next {
+            if next > now {
+                break;
+            }
+
+            count += 1;
+            self.queued -= 1;
+            // if the client did not submit the next period in time, we have an underrun and
+            // start again as soon as the next period arrives
+            self.next = match self.queued {
+                0 => None,
+                _ => Some(next + self.period_time),
+            };
+        }
+        Ok(count)
+    }
+
+    fn next_timeout(&self) -> Option<TimeDuration> {
+        self.next.map(|n| {
+            n.checked_duration_since(TimeInstant::now())
+                .unwrap_or_default()
+        })
+    }
+
+    fn stop(&mut self) -> Result<(), Error> {
+        log!(LogFlags::AudioDev, "null: stop");
+        self.queued = 0;
+        self.next = None;
+        Ok(())
+    }
+}
@@ -0,0 +1,307 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use m3::boxed::Box;
+use m3::client::AudioConfig;
+use m3::com::RecvGate;
+use m3::errors::{Code, Error};
+use m3::io::LogFlags;
+use m3::log;
+use m3::mem::GlobOff;
+use m3::server::SessId;
+use m3::tcu::Message;
+use m3::time::TimeDuration;
+use m3::vec;
+
+use crate::backend::PcmDevice;
+
+macro_rules! reply_vmsg_late {
+    ( $rgate:expr, $msg:expr, $( $args:expr ),* ) => ({
+        let mut msg = m3::mem::MsgBuf::borrow_def();
+        m3::build_vmsg!(&mut msg, $( $args ),*);
+        $rgate.reply(&msg, $msg)
+    });
+}
+
+/// The layout of the ring buffer
+#[derive(Copy, Clone, Debug)]
+pub struct Layout {
+    pub cfg: AudioConfig,
+    pub period_size: usize,
+    pub periods: usize,
+}
+
+impl Layout {
+    pub fn buffer_size(&self) -> usize {
+        self.period_size * self.periods
+    }
+}
+
+/// The playback stream, which is used by one session at a time
+///
+/// The ring buffer is filled by the client and consumed by the device in periods. The stream
+/// counts the bytes that have been committed by the client, submitted to the device, and played
+/// by the device. Full periods are submitted right away, whereas the last incomplete period is
+/// only submitted on a drain.
+pub struct Stream {
+    dev: Box<dyn PcmDevice>,
+    period_time: TimeDuration,
+    periods: usize,
+    owner: Option<SessId>,
+    layout: Option<Layout>,
+    prepared: bool,
+    committed: u64,
+    submitted: u64,
+    played: u64,
+    // the pending wait and drain requests of the owner
+    waiter: Option<&'static Message>,
+    drainer: Option<&'static Message>,
+}
+
+impl Stream {
+    pub fn new(dev: Box<dyn PcmDevice>, period_time: TimeDuration, periods: usize) -> Self {
+        Self {
+            dev,
+            period_time,
+            periods,
+            owner: None,
+            layout: None,
+            prepared: false,
+            committed: 0,
+            submitted: 0,
+            played: 0,
+            waiter: None,
+            drainer: None,
+        }
+    }
+
+    pub fn device(&self) -> &dyn PcmDevice {
+        &*self.dev
+    }
+
+    /// Returns the layout if the given session owns the stream
+    pub fn layout_of(&self, sid: SessId) -> Result<Layout, Error> {
+        match (self.owner, self.layout) {
+            (Some(o), Some(l)) if o == sid => Ok(l),
+            _ => Err(Error::new(Code::InvState)),
+        }
+    }
+
+    /// Returns the number of bytes the client can write without waiting
+    pub fn free(&self) -> usize {
+        let layout = self.layout.unwrap();
+        layout.buffer_size() - (self.committed - self.played) as usize
+    }
+
+    fn is_idle(&self) -> bool {
+        self.committed == 0 && self.waiter.is_none() && self.drainer.is_none()
+    }
+
+    /// Configures the stream for the given session based on the wanted configuration
+    pub fn configure(&mut self, sid: SessId, wanted: AudioConfig) -> Result<Layout, Error> {
+        match self.owner {
+            Some(o) if o != sid => return Err(Error::new(Code::Exists)),
+            Some(_) if !self.is_idle() => return Err(Error::new(Code::InvState)),
+            _ => {},
+        }
+
+        if wanted.rate == 0 || wanted.channels == 0 {
+            return Err(Error::new(Code::InvArgs));
+        }
+
+        let cfg = self.dev.caps().negotiate(wanted);
+        let frame = cfg.frame_size();
+        let period_bytes = cfg.bytes_per_sec() as u64 * self.period_time.as_micros() as u64;
+        let max_period = self.dev.max_buffer() / self.periods;
+        let period_size = ((period_bytes / 1_000_000) as usize)
+            .min(max_period)
+            .max(frame);
+        let layout = Layout {
+            cfg,
+            // the periods need to consist of complete frames
+            period_size: period_size - period_size % frame,
+            periods: self.periods,
+        };
+
+        self.owner = Some(sid);
+        self.layout = Some(layout);
+        self.prepared = false;
+        Ok(layout)
+    }
+
+    fn submit_periods(&mut self) -> Result<(), Error> {
+        let layout = self.layout.unwrap();
+        if !self.prepared {
+            self.dev
+                .prepare(layout.cfg, layout.period_size, layout.periods)?;
+            self.prepared = true;
+        }
+
+        while self.committed - self.submitted >= layout.period_size as u64 {
+            let period = (self.submitted / layout.period_size as u64) as usize % layout.periods;
+            self.dev.submit(period)?;
+            self.submitted += layout.period_size as u64;
+        }
+        Ok(())
+    }
+
+    /// Hands `amount` bytes, written by the client behind the previously committed bytes, over to
+    /// the device
+    pub fn commit(&mut self, sid: SessId, amount: usize) -> Result<usize, Error> {
+        let layout = self.layout_of(sid)?;
+        if self.drainer.is_some() {
+            return Err(Error::new(Code::InvState));
+        }
+        if amount % layout.cfg.frame_size() != 0 || amount > self.free() {
+            return Err(Error::new(Code::InvArgs));
+        }
+
+        self.committed += amount as u64;
+        self.submit_periods()?;
+        Ok(self.free())
+    }
+
+    /// Replies to `msg` as soon as at least one period is free
+    pub fn wait(
+        &mut self,
+        sid: SessId,
+        rgate: &RecvGate,
+        msg: &'static Message,
+    ) -> Result<(), Error> {
+        let layout = self.layout_of(sid)?;
+        if self.waiter.is_some() || self.drainer.is_some() {
+            return Err(Error::new(Code::InvState));
+        }
+
+        if self.free() >= layout.period_size {
+            reply_vmsg_late!(rgate, msg, Code::Success, self.free())
+        }
+        else {
+            self.waiter = Some(msg);
+            Ok(())
+        }
+    }
+
+    /// Replies to `msg` as soon as all committed bytes have been played and stops the device
+    pub fn drain(
+        &mut self,
+        sid: SessId,
+        rgate: &RecvGate,
+        msg: &'static Message,
+    ) -> Result<(), Error> {
+        let layout = self.layout_of(sid)?;
+        if self.waiter.is_some() || self.drainer.is_some() {
+            return Err(Error::new(Code::InvState));
+        }
+
+        // fill up the incomplete period with silence
+        let rem = (self.committed - self.submitted) as usize;
+        if rem > 0 {
+            let (mem, off) = self.dev.buffer();
+            let pos = (self.committed % layout.buffer_size() as u64) as usize;
+            let silence = vec![layout.cfg.format.silence(); layout.period_size - rem];
+            mem.write(&silence, off + pos as GlobOff)?;
+            self.committed += silence.len() as u64;
+            self.submit_periods()?;
+        }
+
+        self.drainer = Some(msg);
+        self.check_drained(rgate);
+        Ok(())
+    }
+
+    fn check_drained(&mut self, rgate: &RecvGate) {
+        if self.played == self.committed {
+            if let Some(msg) = self.drainer.take() {
+                let code = match self.reset() {
+                    Ok(_) => Code::Success,
+                    Err(e) => e.code(),
+                };
+                reply_vmsg_late!(rgate, msg, code).ok();
+            }
+        }
+    }
+
+    fn reset(&mut self) -> Result<(), Error> {
+        self.committed = 0;
+        self.submitted = 0;
+        self.played = 0;
+        if self.prepared {
+            self.prepared = false;
+            self.dev.stop()
+        }
+        else {
+            Ok(())
+        }
+    }
+
+    /// Releases the stream if it is owned by the given session
+    pub fn release(&mut self, sid: SessId, rgate: &RecvGate) {
+        if self.owner != Some(sid) {
+            return;
+        }
+
+        // the client is gone; free the slots of its pending requests
+        for msg in [self.waiter.take(), self.drainer.take()]
+            .into_iter()
+            .flatten()
+        {
+            rgate.ack_msg(msg).ok();
+        }
+        self.reset().ok();
+        self.owner = None;
+        self.layout = None;
+    }
+
+    /// Fetches the periods the device has played and replies to pending requests accordingly
+    pub fn poll(&mut self, rgate: &RecvGate) {
+        if !self.prepared {
+            return;
+        }
+
+        let count = match self.dev.completed() {
+            Ok(c) => c,
+            Err(e) => {
+                log!(LogFlags::Error, "audio: unable to fetch periods: {:?}", e);
+                return;
+            },
+        };
+        if count == 0 {
+            return;
+        }
+
+        let layout = self.layout.unwrap();
+        self.played += (count * layout.period_size) as u64;
+        log!(
+            LogFlags::AudioDev,
+            "audio: played {} periods ({} bytes in total)",
+            count,
+            self.played
+        );
+
+        if let Some(msg) = self.waiter.take() {
+            reply_vmsg_late!(rgate, msg, Code::Success, self.free()).ok();
+        }
+        self.check_drained(rgate);
+    }
+
+    /// Returns the time after which the stream should be polled again
+    pub fn next_timeout(&self) -> Option<TimeDuration> {
+        match self.prepared {
+            true => self.dev.next_timeout(),
+            false => None,
+        }
+    }
+}
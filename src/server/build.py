@@ -1,12 +1,20 @@
 dirs = [
     'arith',
+    'audio',
+    'clock',
     'crypto',
     'disk',
+    'fatfs',
+    'fb',
+    'hostfs',
+    'kvstore',
     'm3fs',
     'net',
     'pager',
     'pipes',
+    'pty',
     'root',
+    'shm',
     'timer',
     'vterm',
 ]
@@ -0,0 +1,215 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+#![no_std]
+
+use m3::cell::LazyReadOnlyCell;
+use m3::client::Network;
+use m3::col::{String, ToString, Vec};
+use m3::com::{opcodes, GateCap, GateIStream, MemCap};
+use m3::env;
+use m3::errors::{Code, Error};
+use m3::io::LogFlags;
+use m3::log;
+use m3::net::{DGramSocket, DgramSocketArgs, Endpoint, IpAddr, Socket, UdpSocket};
+use m3::println;
+use m3::reply_vmsg;
+use m3::server::{
+    ClientManager, RequestHandler, RequestSession, Server, ServerSession, SessId, DEF_MAX_CLIENTS,
+};
+use m3::tiles::OwnActivity;
+use m3::time::{SystemTime, TimeDuration, TimeInstant};
+use m3::vfs::{File, FileEvent, FileWaiter};
+
+const MSG_SIZE: usize = 64;
+
+// the NTP timestamps start at 1900-01-01, whereas Unix timestamps start at 1970-01-01
+const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
+const NTP_PORT: u16 = 123;
+const NTP_PACKET_SIZE: usize = 48;
+const NTP_RETRIES: usize = 3;
+const NTP_TIMEOUT: TimeDuration = TimeDuration::from_secs(1);
+
+static BOOT_TIME: LazyReadOnlyCell<SystemTime> = LazyReadOnlyCell::default();
+
+enum TimeSource {
+    Rtc(String),
+    Ntp(IpAddr),
+    Fixed(u64),
+}
+
+struct ClockSession {
+    serv: ServerSession,
+}
+
+impl RequestSession for ClockSession {
+    fn new(serv: ServerSession, _arg: &str) -> Result<Self, Error>
+    where
+        Self: Sized,
+    {
+        log!(LogFlags::ClockReqs, "[{}] clock::open()", serv.id());
+        Ok(ClockSession { serv })
+    }
+
+    fn close(&mut self, _cli: &mut ClientManager<Self>, sid: SessId, _sub_ids: &mut Vec<SessId>)
+    where
+        Self: Sized,
+    {
+        log!(LogFlags::ClockReqs, "[{}] clock::close()", sid);
+    }
+}
+
+impl ClockSession {
+    fn boot_time(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        log!(
+            LogFlags::ClockReqs,
+            "[{}] clock::boot_time()",
+            self.serv.id()
+        );
+
+        let nanos = BOOT_TIME.get().as_unix().as_nanos() as u64;
+        reply_vmsg!(is, Code::Success, nanos)
+    }
+}
+
+/// Reads the current time from a PL031-compatible real-time clock, whose data register holds the
+/// seconds since the Unix epoch.
+fn read_rtc(name: &str) -> Result<SystemTime, Error> {
+    let mem = MemCap::new_bind_physmem(name)?.activate()?;
+    let secs: u32 = mem.read_obj(0)?;
+    Ok(SystemTime::from_unix_secs(secs as u64))
+}
+
+/// Requests the current time from the given NTP server via SNTP (RFC 4330).
+fn query_ntp(server: IpAddr) -> Result<SystemTime, Error> {
+    let net = Network::new("net")?;
+    let mut socket = UdpSocket::new(DgramSocketArgs::new(net))?;
+    socket.set_blocking(false)?;
+
+    let mut waiter = FileWaiter::default();
+    waiter.add(socket.fd(), FileEvent::INPUT);
+
+    // LI = 0 (no warning), VN = 4, mode = 3 (client)
+    let mut request = [0u8; NTP_PACKET_SIZE];
+    request[0] = 0x23;
+
+    let dest = Endpoint::new(server, NTP_PORT);
+    let mut reply = [0u8; NTP_PACKET_SIZE];
+    for _ in 0..NTP_RETRIES {
+        log!(LogFlags::ClockSync, "Sending NTP request to {}", dest);
+        socket.send_to(&request, dest)?;
+
+        waiter.wait_for(NTP_TIMEOUT);
+        if !socket.has_data() {
+            continue;
+        }
+
+        let (size, src) = socket.recv_from(&mut reply)?;
+        // ignore replies that are truncated, from someone else, or not in server mode
+        if size < NTP_PACKET_SIZE || src != dest || (reply[0] & 0x7) != 4 {
+            log!(
+                LogFlags::ClockSync,
+                "Ignoring invalid NTP reply from {}",
+                src
+            );
+            continue;
+        }
+
+        // the transmit timestamp is a 32.32 fixed-point number of seconds
+        let secs = u32::from_be_bytes([reply[40], reply[41], reply[42], reply[43]]) as u64;
+        let frac = u32::from_be_bytes([reply[44], reply[45], reply[46], reply[47]]) as u64;
+        let secs = secs
+            .checked_sub(NTP_UNIX_OFFSET)
+            .ok_or_else(|| Error::new(Code::InvArgs))?;
+        let nanos = (frac * 1_000_000_000) >> 32;
+        return Ok(SystemTime::from_unix(TimeDuration::from_nanos(
+            secs * 1_000_000_000 + nanos,
+        )));
+    }
+
+    Err(Error::new(Code::Timeout))
+}
+
+fn determine_boot_time(src: &TimeSource) -> Result<SystemTime, Error> {
+    let now = match src {
+        TimeSource::Rtc(name) => read_rtc(name)?,
+        TimeSource::Ntp(server) => query_ntp(*server)?,
+        TimeSource::Fixed(secs) => SystemTime::from_unix_secs(*secs),
+    };
+    // the TCU time is zero on boot, so that we can derive the boot time from the current time
+    Ok(now - TimeDuration::from_nanos(TimeInstant::now().as_nanos()))
+}
+
+fn usage() -> ! {
+    println!(
+        "Usage: {} (-r <physmem> | -n <ip> | -t <secs>)",
+        env::args().next().unwrap()
+    );
+    println!();
+    println!("  -r: read the time from the real-time clock in the given physical memory range");
+    println!("  -n: request the time from the NTP server with given IP address");
+    println!("  -t: use the given Unix timestamp as the current time");
+    OwnActivity::exit_with(Code::InvArgs);
+}
+
+fn parse_args() -> Result<TimeSource, String> {
+    let args: Vec<&str> = env::args().collect();
+    if args.len() != 3 {
+        return Err("Expected exactly one time source".to_string());
+    }
+
+    match args[1] {
+        "-r" => Ok(TimeSource::Rtc(args[2].to_string())),
+        "-n" => args[2]
+            .parse::<IpAddr>()
+            .map(TimeSource::Ntp)
+            .map_err(|_| String::from("Failed to parse IP address")),
+        "-t" => args[2]
+            .parse::<u64>()
+            .map(TimeSource::Fixed)
+            .map_err(|_| String::from("Failed to parse timestamp")),
+        _ => Err(String::from("Unknown time source")),
+    }
+}
+
+#[no_mangle]
+pub fn main() -> Result<(), Error> {
+    let src = parse_args().unwrap_or_else(|e| {
+        println!("Invalid arguments: {}", e);
+        usage();
+    });
+
+    let boot = determine_boot_time(&src).expect("Unable to determine time");
+    BOOT_TIME.set(boot);
+    // synchronize ourself as well so that our log messages carry the time
+    SystemTime::set_boot_time(boot);
+    log!(
+        LogFlags::ClockSync,
+        "Booted at {}; current time is {}",
+        boot,
+        SystemTime::now()
+    );
+
+    let mut hdl = RequestHandler::new_with(DEF_MAX_CLIENTS, MSG_SIZE, 1)
+        .expect("Unable to create request handler");
+    let mut srv = Server::new("clock", &mut hdl).expect("Unable to create service 'clock'");
+
+    use opcodes::Clock;
+    hdl.reg_msg_handler(Clock::BootTime, ClockSession::boot_time);
+
+    hdl.run(&mut srv).expect("Server loop failed");
+
+    Ok(())
+}
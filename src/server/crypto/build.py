@@ -1,4 +1,5 @@
 dirs = [
+    'cryptosrv',
     'hashmux',
 ]
 
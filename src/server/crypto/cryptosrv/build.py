@@ -0,0 +1,8 @@
+def build(gen, env):
+    features = []
+    # FIXME: Enable hardware accelerator on "hw" target
+    if env['TGT'] != 'gem5':
+        features = ['kecacc/backend-xkcp']
+
+    # libkecacc-xkcp is only needed if the hardware accelerator is not available (see hashmux)
+    env.m3_rust_exe(gen, out='crypto', libs=['kecacc-xkcp'], features=features)
@@ -0,0 +1,488 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+#![no_std]
+
+mod sponge;
+
+use core::ops::Range;
+
+use m3::col::Vec;
+use m3::com::{opcodes, EpMng, GateIStream, EP};
+use m3::crypto::{HashAlgorithm, HashType};
+use m3::errors::{Code, Error};
+use m3::io::LogFlags;
+use m3::kif::{CapRngDesc, CapType};
+use m3::log;
+use m3::reply_vmsg;
+use m3::server::{
+    CapExchange, ClientManager, ExcType, RequestHandler, RequestSession, Server, ServerSession,
+    SessId, DEF_MAX_CLIENTS,
+};
+use m3::tcu::{EpId, TCU};
+
+use cshake::kmac;
+
+/// The message size is larger than usual, because requests contain customization strings
+const MSG_SIZE: usize = 256;
+
+/// The minimum key size in bytes (the security strength of KMAC128)
+const MIN_KEY_SIZE: usize = 16;
+/// The maximum key size in bytes
+const MAX_KEY_SIZE: usize = 64;
+/// The maximum number of keys per session
+const MAX_KEYS: usize = 16;
+/// The maximum length of function names and customization strings
+const MAX_STR_LEN: usize = 64;
+
+/// The size of the authentication tag for AEAD (has to match the client library)
+const TAG_SIZE: usize = m3::client::AEAD_TAG_SIZE;
+
+// The customization strings to derive independent key streams and tags from the same key
+const AEAD_ENC_STR: &str = "M3 AEAD encryption";
+const AEAD_AUTH_STR: &str = "M3 AEAD authentication";
+
+/// A key that has been imported by a client
+struct Key {
+    data: [u8; MAX_KEY_SIZE],
+    len: usize,
+}
+
+impl Key {
+    fn bytes(&self) -> &[u8] {
+        &self.data[..self.len]
+    }
+}
+
+impl Drop for Key {
+    fn drop(&mut self) {
+        // use volatile writes to prevent that the compiler optimizes the wiping away
+        for b in self.data.iter_mut() {
+            unsafe { core::ptr::write_volatile(b, 0) };
+        }
+    }
+}
+
+struct CryptoSession {
+    serv: ServerSession,
+    mem: Option<EP>,
+    // the keys are only accessible via the session they have been imported into
+    keys: Vec<Option<Key>>,
+}
+
+impl RequestSession for CryptoSession {
+    fn new(serv: ServerSession, _arg: &str) -> Result<Self, Error>
+    where
+        Self: Sized,
+    {
+        log!(LogFlags::CryptoReqs, "[{}] crypto::open()", serv.id());
+        Ok(Self {
+            serv,
+            mem: None,
+            keys: Vec::new(),
+        })
+    }
+
+    fn close(&mut self, _cli: &mut ClientManager<Self>, sid: SessId, _sub_ids: &mut Vec<SessId>)
+    where
+        Self: Sized,
+    {
+        log!(LogFlags::CryptoReqs, "[{}] crypto::close()", sid);
+
+        // Revoke EP capability that was delegated for memory accesses
+        if let Some(ep) = self.mem.take() {
+            EpMng::get().release(ep, true);
+        }
+        // dropping the keys wipes them
+        self.keys.clear();
+    }
+}
+
+/// Pops a memory region within the client's memory from the given stream
+fn pop_range(is: &mut GateIStream<'_>) -> Result<Range<usize>, Error> {
+    let start: usize = is.pop()?;
+    let end: usize = is.pop()?;
+    if end < start {
+        return Err(Error::new(Code::InvArgs));
+    }
+    Ok(start..end)
+}
+
+/// Pops a function name or customization string from the given stream
+fn pop_str(is: &mut GateIStream<'_>) -> Result<&'static str, Error> {
+    let s: &str = is.pop()?;
+    if s.len() > MAX_STR_LEN {
+        return Err(Error::new(Code::InvArgs));
+    }
+    Ok(s)
+}
+
+fn kmac_algo(ty: HashType) -> Result<&'static HashAlgorithm, Error> {
+    match ty {
+        HashType::CSHAKE128 => Ok(&HashAlgorithm::CSHAKE128),
+        HashType::CSHAKE256 => Ok(&HashAlgorithm::CSHAKE256),
+        _ => Err(Error::new(Code::InvArgs)),
+    }
+}
+
+/// Starts a KMAC computation with given key and customization string
+fn kmac_start(algo: &HashAlgorithm, key: &Key, s: &str) -> Result<(), Error> {
+    if !sponge::supports(algo.ty) {
+        return Err(Error::new(Code::NotSup));
+    }
+
+    sponge::init(algo.ty);
+    sponge::absorb_with(|buf| kmac::prepend_header(buf, s, algo.block_bytes));
+    sponge::absorb_with(|buf| kmac::prepend_key(buf, key.bytes(), algo.block_bytes));
+    Ok(())
+}
+
+/// Finishes the input of a KMAC computation with given output length in bytes
+fn kmac_finish(out_bytes: usize) -> Result<(), Error> {
+    let bits = out_bytes
+        .checked_mul(8)
+        .ok_or_else(|| Error::new(Code::InvArgs))?;
+    sponge::absorb_with(|buf| kmac::append_output_length(buf, bits));
+    sponge::pad();
+    Ok(())
+}
+
+/// Starts the key stream for the given key and nonce (KMACXOF256)
+fn aead_stream(key: &Key, nonce: u64) -> Result<(), Error> {
+    kmac_start(&HashAlgorithm::CSHAKE256, key, AEAD_ENC_STR)?;
+    sponge::absorb_with(|buf| {
+        buf[0..8].copy_from_slice(&nonce.to_le_bytes());
+        8
+    });
+    kmac_finish(kmac::XOF_OUTPUT_LENGTH)
+}
+
+/// Computes the authentication tag over the nonce, the associated data, and the ciphertext
+/// (KMAC256)
+fn aead_tag(
+    ep: EpId,
+    key: &Key,
+    nonce: u64,
+    ad: &Range<usize>,
+    ct: &Range<usize>,
+) -> Result<[u8; TAG_SIZE], Error> {
+    kmac_start(&HashAlgorithm::CSHAKE256, key, AEAD_AUTH_STR)?;
+    sponge::absorb_with(|buf| {
+        buf[0..8].copy_from_slice(&nonce.to_le_bytes());
+        8
+    });
+    sponge::absorb_mem(ep, ad.start, ad.len())?;
+    sponge::absorb_mem(ep, ct.start, ct.len())?;
+    // the lengths make the boundary between associated data and ciphertext unambiguous
+    sponge::absorb_with(|buf| {
+        buf[0..8].copy_from_slice(&(ad.len() as u64).to_le_bytes());
+        buf[8..16].copy_from_slice(&(ct.len() as u64).to_le_bytes());
+        16
+    });
+    kmac_finish(TAG_SIZE)?;
+
+    let mut tag = [0u8; TAG_SIZE];
+    sponge::squeeze(&mut tag);
+    Ok(tag)
+}
+
+/// Compares the given slices in constant time
+fn tags_equal(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b.iter()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+impl CryptoSession {
+    fn get_mem(
+        cli: &mut ClientManager<Self>,
+        _crt: usize,
+        sid: SessId,
+        xchg: &mut CapExchange<'_>,
+    ) -> Result<(), Error> {
+        log!(LogFlags::CryptoReqs, "[{}] crypto::get_mem()", sid);
+        let sess = cli.get_mut(sid).ok_or_else(|| Error::new(Code::InvArgs))?;
+
+        if sess.mem.is_some() {
+            return Err(Error::new(Code::Exists));
+        }
+
+        let ep = EpMng::get().acquire(0)?;
+        let ep_sel = ep.sel();
+        sess.mem = Some(ep);
+        xchg.out_caps(CapRngDesc::new(CapType::Object, ep_sel, 1));
+
+        Ok(())
+    }
+
+    fn epid(&self) -> Result<EpId, Error> {
+        self.mem
+            .as_ref()
+            .map(|ep| ep.id())
+            .ok_or_else(|| Error::new(Code::InvState))
+    }
+
+    fn key(&self, id: usize) -> Result<&Key, Error> {
+        match self.keys.get(id) {
+            Some(Some(key)) => Ok(key),
+            _ => Err(Error::new(Code::InvArgs)),
+        }
+    }
+
+    fn import_key(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let range = pop_range(is)?;
+
+        log!(
+            LogFlags::CryptoKeys,
+            "[{}] crypto::import_key(range={:?})",
+            self.serv.id(),
+            range
+        );
+
+        if range.len() < MIN_KEY_SIZE || range.len() > MAX_KEY_SIZE {
+            return Err(Error::new(Code::InvArgs));
+        }
+
+        let id = match self.keys.iter().position(|k| k.is_none()) {
+            Some(id) => id,
+            None if self.keys.len() < MAX_KEYS => {
+                self.keys.push(None);
+                self.keys.len() - 1
+            },
+            None => return Err(Error::new(Code::NoSpace)),
+        };
+
+        let mut key = Key {
+            data: [0u8; MAX_KEY_SIZE],
+            len: range.len(),
+        };
+        TCU::read(
+            self.epid()?,
+            key.data.as_mut_ptr(),
+            key.len,
+            range.start as u64,
+        )?;
+        self.keys[id] = Some(key);
+
+        log!(
+            LogFlags::CryptoKeys,
+            "[{}] crypto::import_key() -> {}",
+            self.serv.id(),
+            id
+        );
+
+        reply_vmsg!(is, Code::Success, id)
+    }
+
+    fn delete_key(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let id: usize = is.pop()?;
+
+        log!(
+            LogFlags::CryptoKeys,
+            "[{}] crypto::delete_key(key={})",
+            self.serv.id(),
+            id
+        );
+
+        self.key(id)?;
+        self.keys[id] = None;
+
+        is.reply_error(Code::Success)
+    }
+
+    fn hash(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let ty: HashType = is.pop()?;
+        let n = pop_str(is)?;
+        let s = pop_str(is)?;
+        let input = pop_range(is)?;
+        let output = pop_range(is)?;
+
+        log!(
+            LogFlags::CryptoReqs,
+            "[{}] crypto::hash(algo={:?}, n={}, s={}, in={:?}, out={:?})",
+            self.serv.id(),
+            ty,
+            n,
+            s,
+            input,
+            output
+        );
+
+        let algo = HashAlgorithm::from_type(ty).ok_or_else(|| Error::new(Code::InvArgs))?;
+        // fixed-size algorithms produce exactly the hash size
+        if !algo.is_xof() && output.len() != algo.output_bytes {
+            return Err(Error::new(Code::InvArgs));
+        }
+
+        let custom = !n.is_empty() || !s.is_empty();
+        let ty = match ty {
+            // cSHAKE without function name and customization string is equal to SHAKE
+            HashType::CSHAKE128 if !custom => HashType::SHAKE128,
+            HashType::CSHAKE256 if !custom => HashType::SHAKE256,
+            HashType::CSHAKE128 | HashType::CSHAKE256 => ty,
+            _ if custom => return Err(Error::new(Code::InvArgs)),
+            _ => ty,
+        };
+        if !sponge::supports(ty) {
+            return Err(Error::new(Code::NotSup));
+        }
+
+        let ep = self.epid()?;
+        sponge::init(ty);
+        if custom {
+            sponge::absorb_with(|buf| cshake::prepend_header(buf, n, s, algo.block_bytes));
+        }
+        sponge::absorb_mem(ep, input.start, input.len())?;
+        sponge::pad();
+        sponge::squeeze_mem(ep, output.start, output.len())?;
+
+        is.reply_error(Code::Success)
+    }
+
+    fn kmac(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let key_id: usize = is.pop()?;
+        let ty: HashType = is.pop()?;
+        let s = pop_str(is)?;
+        let input = pop_range(is)?;
+        let output = pop_range(is)?;
+
+        log!(
+            LogFlags::CryptoReqs,
+            "[{}] crypto::kmac(key={}, algo={:?}, s={}, in={:?}, out={:?})",
+            self.serv.id(),
+            key_id,
+            ty,
+            s,
+            input,
+            output
+        );
+
+        let algo = kmac_algo(ty)?;
+        let ep = self.epid()?;
+        let key = self.key(key_id)?;
+
+        let res = kmac_start(algo, key, s)
+            .and_then(|_| sponge::absorb_mem(ep, input.start, input.len()))
+            .and_then(|_| kmac_finish(output.len()))
+            .and_then(|_| sponge::squeeze_mem(ep, output.start, output.len()));
+        sponge::wipe();
+        res?;
+
+        is.reply_error(Code::Success)
+    }
+
+    fn seal(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let key_id: usize = is.pop()?;
+        let nonce: u64 = is.pop()?;
+        let ad = pop_range(is)?;
+        let input = pop_range(is)?;
+        let out: usize = is.pop()?;
+
+        log!(
+            LogFlags::CryptoReqs,
+            "[{}] crypto::seal(key={}, nonce={}, ad={:?}, in={:?}, out={:#x})",
+            self.serv.id(),
+            key_id,
+            nonce,
+            ad,
+            input,
+            out
+        );
+
+        let ep = self.epid()?;
+        let key = self.key(key_id)?;
+        let ct = out..out
+            .checked_add(input.len())
+            .ok_or_else(|| Error::new(Code::InvArgs))?;
+
+        // encrypt first and compute the tag over the ciphertext afterwards
+        let res = aead_stream(key, nonce)
+            .and_then(|_| sponge::xor_mem(ep, input.start, ct.start, input.len()))
+            .and_then(|_| aead_tag(ep, key, nonce, &ad, &ct));
+        sponge::wipe();
+
+        let tag = res?;
+        TCU::write(ep, tag.as_ptr(), TAG_SIZE, ct.end as u64)?;
+
+        reply_vmsg!(is, Code::Success, input.len() + TAG_SIZE)
+    }
+
+    fn open(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let key_id: usize = is.pop()?;
+        let nonce: u64 = is.pop()?;
+        let ad = pop_range(is)?;
+        let input = pop_range(is)?;
+        let out: usize = is.pop()?;
+
+        log!(
+            LogFlags::CryptoReqs,
+            "[{}] crypto::open(key={}, nonce={}, ad={:?}, in={:?}, out={:#x})",
+            self.serv.id(),
+            key_id,
+            nonce,
+            ad,
+            input,
+            out
+        );
+
+        if input.len() < TAG_SIZE {
+            return Err(Error::new(Code::InvArgs));
+        }
+
+        let ep = self.epid()?;
+        let key = self.key(key_id)?;
+        let ct = input.start..input.end - TAG_SIZE;
+
+        let mut expected = [0u8; TAG_SIZE];
+        TCU::read(ep, expected.as_mut_ptr(), TAG_SIZE, ct.end as u64)?;
+
+        // verify the tag before we decrypt anything
+        let res = aead_tag(ep, key, nonce, &ad, &ct).and_then(|tag| {
+            if !tags_equal(&tag, &expected) {
+                log!(
+                    LogFlags::CryptoReqs,
+                    "[{}] crypto::open() authentication failed",
+                    self.serv.id()
+                );
+                return Err(Error::new(Code::InvChecksum));
+            }
+
+            aead_stream(key, nonce)?;
+            sponge::xor_mem(ep, ct.start, out, ct.len())
+        });
+        sponge::wipe();
+        res?;
+
+        reply_vmsg!(is, Code::Success, ct.len())
+    }
+}
+
+#[no_mangle]
+pub fn main() -> Result<(), Error> {
+    let mut hdl = RequestHandler::new_with(DEF_MAX_CLIENTS, MSG_SIZE, 1)
+        .expect("Unable to create request handler");
+    let mut srv = Server::new("crypto", &mut hdl).expect("Unable to create service 'crypto'");
+
+    use opcodes::Crypto;
+    hdl.reg_cap_handler(Crypto::GetMem, ExcType::Obt(1), CryptoSession::get_mem);
+    hdl.reg_msg_handler(Crypto::ImportKey, CryptoSession::import_key);
+    hdl.reg_msg_handler(Crypto::DeleteKey, CryptoSession::delete_key);
+    hdl.reg_msg_handler(Crypto::Hash, CryptoSession::hash);
+    hdl.reg_msg_handler(Crypto::Kmac, CryptoSession::kmac);
+    hdl.reg_msg_handler(Crypto::Seal, CryptoSession::seal);
+    hdl.reg_msg_handler(Crypto::Open, CryptoSession::open);
+
+    hdl.run(&mut srv).expect("Server loop failed");
+
+    Ok(())
+}
@@ -0,0 +1,136 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! Helpers to feed the accelerator from and into the memory of clients.
+//!
+//! All operations run to completion before the next request is handled. Therefore, the
+//! accelerator state is never shared between sessions and there is no need to save and restore it
+//! as in hashmux.
+
+use core::cmp::min;
+
+use m3::cell::StaticRefCell;
+use m3::crypto::{HashAlgorithm, HashType};
+use m3::errors::Error;
+use m3::mem::AlignedBuf;
+use m3::tcu::{EpId, TCU};
+
+use kecacc::KecAcc;
+
+/// The size of the buffers for data transfers between the clients and the accelerator
+const BUF_SIZE: usize = 4 * 1024;
+
+/// The size of the buffer for headers (encoded strings, keys, and lengths). cSHAKE headers with
+/// the maximum customization string length need two blocks with cSHAKE256.
+const HDR_SIZE: usize = 512;
+
+// The accelerator can only access buffers within the first GiB, so we use static buffers.
+static DATA: StaticRefCell<AlignedBuf<BUF_SIZE>> = StaticRefCell::new(AlignedBuf::new_zeroed());
+static STREAM: StaticRefCell<AlignedBuf<BUF_SIZE>> = StaticRefCell::new(AlignedBuf::new_zeroed());
+static HDR: StaticRefCell<AlignedBuf<HDR_SIZE>> = StaticRefCell::new(AlignedBuf::new_zeroed());
+
+static KECACC: KecAcc = KecAcc::new(0xF4200000);
+
+/// Returns true if the accelerator (or the software backend) supports the given hash type
+pub fn supports(ty: HashType) -> bool {
+    HashAlgorithm::from_type(ty).map_or(false, |a| KECACC.supports_algo(a))
+}
+
+/// Starts a new hash computation with the given type
+pub fn init(ty: HashType) {
+    KECACC.start_init(ty);
+}
+
+/// Absorbs the bytes that are produced by `write` into the header buffer
+pub fn absorb_with(write: impl FnOnce(&mut [u8]) -> usize) {
+    let hdr = &mut HDR.borrow_mut()[..];
+    let len = write(hdr);
+    KECACC.start_absorb(&hdr[..len]);
+    KECACC.poll_complete();
+}
+
+/// Absorbs `len` bytes at offset `off` from the client memory behind `ep`
+pub fn absorb_mem(ep: EpId, off: usize, len: usize) -> Result<(), Error> {
+    let data = &mut DATA.borrow_mut()[..];
+    let mut pos = 0;
+    while pos < len {
+        let n = min(len - pos, BUF_SIZE);
+        TCU::read(ep, data.as_mut_ptr(), n, (off + pos) as u64)?;
+        KECACC.start_absorb(&data[..n]);
+        // wait until the accelerator is done before we overwrite the buffer
+        KECACC.poll_complete();
+        pos += n;
+    }
+    Ok(())
+}
+
+/// Pads the input, which is required once before squeezing
+pub fn pad() {
+    KECACC.start_pad();
+}
+
+/// Squeezes `buf.len()` bytes into `buf`
+pub fn squeeze(buf: &mut [u8]) {
+    let hdr = &mut HDR.borrow_mut()[..];
+    for chunk in buf.chunks_mut(HDR_SIZE) {
+        KECACC.start_squeeze(&mut hdr[..chunk.len()]);
+        KECACC.poll_complete_barrier();
+        chunk.copy_from_slice(&hdr[..chunk.len()]);
+    }
+}
+
+/// Squeezes `len` bytes and writes them to offset `off` in the client memory behind `ep`
+pub fn squeeze_mem(ep: EpId, off: usize, len: usize) -> Result<(), Error> {
+    let data = &mut DATA.borrow_mut()[..];
+    let mut pos = 0;
+    while pos < len {
+        let n = min(len - pos, BUF_SIZE);
+        KECACC.start_squeeze(&mut data[..n]);
+        KECACC.poll_complete_barrier();
+        TCU::write(ep, data.as_ptr(), n, (off + pos) as u64)?;
+        pos += n;
+    }
+    Ok(())
+}
+
+/// Uses the squeezed bytes as key stream: reads `len` bytes at offset `src` from the client
+/// memory behind `ep`, XORs them with the key stream, and writes them to offset `dst`.
+pub fn xor_mem(ep: EpId, src: usize, dst: usize, len: usize) -> Result<(), Error> {
+    let data = &mut DATA.borrow_mut()[..];
+    let stream = &mut STREAM.borrow_mut()[..];
+    let mut pos = 0;
+    while pos < len {
+        let n = min(len - pos, BUF_SIZE);
+        // fetch the data while the accelerator produces the key stream
+        KECACC.start_squeeze(&mut stream[..n]);
+        TCU::read(ep, data.as_mut_ptr(), n, (src + pos) as u64)?;
+        KECACC.poll_complete_barrier();
+
+        for (d, s) in data[..n].iter_mut().zip(stream[..n].iter()) {
+            *d ^= *s;
+        }
+        TCU::write(ep, data.as_ptr(), n, (dst + pos) as u64)?;
+        pos += n;
+    }
+    Ok(())
+}
+
+/// Clears the buffers that might contain keys, key streams, or plaintext
+pub fn wipe() {
+    KECACC.poll_complete();
+    HDR.borrow_mut()[..].fill(0);
+    DATA.borrow_mut()[..].fill(0);
+    STREAM.borrow_mut()[..].fill(0);
+}
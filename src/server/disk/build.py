@@ -1,3 +1,9 @@
+import os
+
+
 def build(gen, env):
+    features = []
+    if os.environ.get('M3_SRVTESTS', '0') == '1':
+        features = ['disk/tests']
     if env['TGT'] == 'gem5':
-        env.m3_rust_exe(gen, out='disk', dir='sbin')
+        env.m3_rust_exe(gen, out='disk', dir='sbin', features=features)
@@ -0,0 +1,305 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use bitflags::bitflags;
+
+use num_enum::IntoPrimitive;
+
+use m3::col::Vec;
+use m3::com::{opcodes, MemGate};
+use m3::errors::{Code, Error};
+use m3::io::LogFlags;
+use m3::kif::{self, Perm};
+use m3::log;
+use m3::mem::GlobOff;
+use m3::rc::Rc;
+use m3::tiles::OwnActivity;
+use m3::time::TimeDuration;
+
+use super::port::{Port, PortReg, DMA_REGION_SIZE};
+use super::PartDesc;
+
+const AHCI_CTRL_CLASS: u8 = 0x01;
+const AHCI_CTRL_SUBCLASS: u8 = 0x06;
+const AHCI_CTRL_PROG_IF: u8 = 0x01;
+
+/// The AHCI base memory register (ABAR)
+const AHCI_CTRL_BAR: usize = 5;
+
+const PORT_REGS_BASE: GlobOff = 0x100;
+const PORT_REGS_SIZE: GlobOff = 0x80;
+
+pub const MAX_PORTS: usize = 32;
+
+const RESET_TIMEOUT: TimeDuration = TimeDuration::from_millis(1);
+const RESET_SLEEPTIME: TimeDuration = TimeDuration::from_micros(20);
+
+/// Generic host control registers
+#[derive(Copy, Clone, Debug, Eq, PartialEq, IntoPrimitive)]
+#[repr(u64)]
+enum HBAReg {
+    Capabilities = 0x00,
+    GlobalCtrl   = 0x04,
+    IntStatus    = 0x08,
+    PortsImpl    = 0x0C,
+    Version      = 0x10,
+}
+
+bitflags! {
+    /// HBA capabilities
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    pub struct HBACaps : u32 {
+        /// Supports native command queuing
+        const NCQ = 1 << 30;
+        /// Supports 64-bit addressing
+        const ADDR64 = 1 << 31;
+    }
+}
+
+bitflags! {
+    /// Global HBA control
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    struct GlobalCtrl : u32 {
+        const RESET = 1 << 0;
+        const INT_ENABLE = 1 << 1;
+        const AHCI_ENABLE = 1 << 31;
+    }
+}
+
+/// Represents an AHCI host bus adapter (HBA) with the SATA devices attached to its ports
+pub struct AHCIController {
+    use_irq: bool,
+    caps: u32,
+    pci_dev: Rc<pci::Device>,
+    dma: MemGate,
+    ports: Vec<Port>,
+}
+
+impl AHCIController {
+    pub fn new(use_irq: bool) -> Result<Self, Error> {
+        // find AHCI controller via PCI
+        let pci_dev = Rc::new(pci::Device::new("ahcictrl", kif::TileISA::IDEDev)?);
+        let ahci_ctrl = pci_dev.get_info()?;
+        if ahci_ctrl.class().base() != AHCI_CTRL_CLASS
+            || ahci_ctrl.class().sub() != AHCI_CTRL_SUBCLASS
+            || ahci_ctrl.programming_interface() != AHCI_CTRL_PROG_IF
+        {
+            return Err(Error::new(Code::NotFound));
+        }
+
+        let abar = ahci_ctrl.bar(AHCI_CTRL_BAR);
+        log!(
+            LogFlags::DiskCtrl,
+            "Found AHCI controller ({}): vendor {:x} device {:x} rev {}, ABAR {:#x} ({} bytes)",
+            ahci_ctrl.id(),
+            ahci_ctrl.vendor(),
+            ahci_ctrl.device(),
+            ahci_ctrl.revision(),
+            abar.addr(),
+            abar.size(),
+        );
+        // the registers are memory mapped; the device tile maps them at its register region
+        if !matches!(abar.bar_type(), pci::BarType::Memory) {
+            return Err(Error::new(Code::NotSup));
+        }
+
+        // ensure that the memory space is enabled and bus mastering is enabled
+        let status_cmd: u32 = pci_dev.read_config(pci::Reg::Command.into())?;
+        pci_dev.write_config(
+            pci::Reg::Command.into(),
+            (status_cmd & !0x400) | 0x02 | 0x04,
+        )?;
+
+        let mut hba = Self {
+            use_irq,
+            caps: 0,
+            pci_dev,
+            // replaced as soon as we know how many ports are in use
+            dma: MemGate::new(1, Perm::RW)?,
+            ports: Vec::new(),
+        };
+
+        hba.reset()?;
+        hba.caps = hba.read_reg(HBAReg::Capabilities)?;
+        let version: u32 = hba.read_reg(HBAReg::Version)?;
+        log!(
+            LogFlags::DiskCtrl,
+            "AHCI {}.{}: {} ports, {} command slots, caps={:?}",
+            version >> 16,
+            version & 0xFFFF,
+            (hba.caps & 0x1F) + 1,
+            hba.cmd_slots(),
+            hba.features(),
+        );
+
+        if use_irq {
+            // prefer message-signaled interrupts, but fall back to legacy interrupts
+            match hba.pci_dev.enable_msix() {
+                Ok(_) => {
+                    hba.pci_dev.alloc_msix_vector()?;
+                },
+                Err(e) if e.code() == Code::NotSup => {},
+                Err(e) => return Err(e),
+            }
+            hba.write_reg(
+                HBAReg::GlobalCtrl,
+                (GlobalCtrl::AHCI_ENABLE | GlobalCtrl::INT_ENABLE).bits(),
+            )?;
+        }
+
+        // detect the ports with attached devices
+        let impl_ports: u32 = hba.read_reg(HBAReg::PortsImpl)?;
+        let present = (0..MAX_PORTS as u8)
+            .filter(|p| (impl_ports & (1 << p)) != 0)
+            .filter(|p| Port::is_present(&hba, *p).unwrap_or(false))
+            .collect::<Vec<_>>();
+
+        // all DMA transfers of the device are performed on a single memory region, containing the
+        // command lists, received FISes, command tables and transfer buffers for all ports
+        let size = (DMA_REGION_SIZE * present.len().max(1)) as GlobOff;
+        hba.dma = MemGate::new(size, Perm::RW)?;
+        let dev_buf = hba.dma.derive_cap(0, size, Perm::RW)?;
+        hba.pci_dev.set_dma_buffer(&dev_buf)?;
+
+        for (i, p) in present.iter().enumerate() {
+            match Port::new(&hba, i as u8, *p, (i * DMA_REGION_SIZE) as GlobOff) {
+                Err(e) => log!(LogFlags::Error, "port[{}] ignoring device: {}", p, e),
+                Ok(port) => {
+                    log!(
+                        LogFlags::DiskCtrl,
+                        "port[{}] found device {}: {} MiB",
+                        p,
+                        i,
+                        port.size() / (1024 * 1024)
+                    );
+                    for part in port.partitions() {
+                        log!(LogFlags::DiskCtrl, "port[{}] registered {:?}", p, part);
+                    }
+                    hba.ports.push(port);
+                },
+            }
+        }
+
+        Ok(hba)
+    }
+
+    pub fn ports(&self) -> &Vec<Port> {
+        &self.ports
+    }
+
+    pub fn use_irq(&self) -> bool {
+        self.use_irq
+    }
+
+    pub fn features(&self) -> HBACaps {
+        HBACaps::from_bits_truncate(self.caps)
+    }
+
+    pub fn cmd_slots(&self) -> usize {
+        (((self.caps >> 8) & 0x1F) + 1) as usize
+    }
+
+    pub fn dma(&self) -> &MemGate {
+        &self.dma
+    }
+
+    /// Returns the maximum number of bytes that can be transferred with a single request
+    pub fn max_transfer(&self) -> usize {
+        self.ports
+            .iter()
+            .map(|p| p.max_transfer())
+            .min()
+            .unwrap_or(0)
+    }
+
+    pub fn read_write(
+        &self,
+        part: PartDesc,
+        op: opcodes::Disk,
+        buf: &MemGate,
+        buf_off: usize,
+        disk_off: usize,
+        bytes: usize,
+    ) -> Result<(), Error> {
+        let port = &self.ports[part.port as usize];
+
+        // check arguments
+        let part_size = part.part.sector_count() as usize * port.sector_size();
+        if disk_off.checked_add(bytes).is_none() || disk_off + bytes > part_size {
+            log!(
+                LogFlags::DiskChan,
+                "Invalid request: disk_off={}, bytes={}, part-size: {}",
+                disk_off,
+                bytes,
+                part_size
+            );
+            return Err(Error::new(Code::InvArgs));
+        }
+
+        let lba = part.part.start_sector() + disk_off as u64 / port.sector_size() as u64;
+        let count = bytes / port.sector_size();
+        let write = matches!(op, opcodes::Disk::Write);
+        port.read_write(self, write, buf, buf_off, lba, count)
+    }
+
+    /// Waits for the next interrupt and acknowledges it at the HBA
+    pub fn wait_irq(&self) -> Result<(), Error> {
+        log!(LogFlags::DiskDbg, "ahci: waiting for IRQ...");
+        self.pci_dev.receive_irq()?;
+        let pending: u32 = self.read_reg(HBAReg::IntStatus)?;
+        self.write_reg(HBAReg::IntStatus, pending)
+    }
+
+    pub fn read_port<T>(&self, port: u8, reg: PortReg) -> Result<T, Error> {
+        self.pci_dev.read_reg(Self::port_reg(port, reg))
+    }
+
+    pub fn write_port<T>(&self, port: u8, reg: PortReg, val: T) -> Result<(), Error> {
+        self.pci_dev.write_reg(Self::port_reg(port, reg), val)
+    }
+
+    fn port_reg(port: u8, reg: PortReg) -> GlobOff {
+        PORT_REGS_BASE + port as GlobOff * PORT_REGS_SIZE + reg as GlobOff
+    }
+
+    fn read_reg<T>(&self, reg: HBAReg) -> Result<T, Error> {
+        self.pci_dev.read_reg(reg as GlobOff)
+    }
+
+    fn write_reg<T>(&self, reg: HBAReg, val: T) -> Result<(), Error> {
+        self.pci_dev.write_reg(reg as GlobOff, val)
+    }
+
+    fn reset(&self) -> Result<(), Error> {
+        self.write_reg(HBAReg::GlobalCtrl, GlobalCtrl::AHCI_ENABLE.bits())?;
+        self.write_reg(
+            HBAReg::GlobalCtrl,
+            (GlobalCtrl::AHCI_ENABLE | GlobalCtrl::RESET).bits(),
+        )?;
+
+        // the HBA clears the reset bit as soon as the reset is complete
+        let mut elapsed = TimeDuration::ZERO;
+        while (self.read_reg::<u32>(HBAReg::GlobalCtrl)? & GlobalCtrl::RESET.bits()) != 0 {
+            if elapsed >= RESET_TIMEOUT {
+                return Err(Error::new(Code::Timeout));
+            }
+            OwnActivity::sleep_for(RESET_SLEEPTIME)?;
+            elapsed += RESET_SLEEPTIME;
+        }
+
+        // the reset clears the AHCI enable bit
+        self.write_reg(HBAReg::GlobalCtrl, GlobalCtrl::AHCI_ENABLE.bits())
+    }
+}
@@ -0,0 +1,122 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+mod hba;
+mod port;
+
+use m3::col::Vec;
+use m3::com::{opcodes, MemGate};
+use m3::errors::Error;
+use m3::vec;
+
+use self::hba::MAX_PORTS;
+use crate::backend::BlockDevice;
+use crate::partition::{Partition, PART_COUNT};
+
+#[derive(Clone, Copy)]
+pub struct PartDesc {
+    port: u8,
+    part: Partition,
+}
+
+/// Block device for SATA disks behind an AHCI host bus adapter
+///
+/// In contrast to the IDE driver, the AHCI driver always uses DMA and distributes each request
+/// among multiple command slots. If the device supports native command queuing (NCQ), the
+/// commands are issued as queued commands so that the device can reorder them.
+pub struct AHCIBlockDevice {
+    hba: hba::AHCIController,
+    devs: Vec<Option<PartDesc>>,
+}
+
+impl AHCIBlockDevice {
+    pub fn new(args: Vec<&str>) -> Result<Self, Error> {
+        let use_irq = args.iter().any(|s| *s == "-i");
+
+        let hba = hba::AHCIController::new(use_irq)?;
+
+        let mut devs = vec![None; MAX_PORTS * PART_COUNT];
+        for port in hba.ports() {
+            for p in port.partitions() {
+                devs[port.id() as usize * PART_COUNT + p.id()] = Some(PartDesc {
+                    port: port.id(),
+                    part: *p,
+                });
+            }
+        }
+
+        Ok(AHCIBlockDevice { hba, devs })
+    }
+}
+
+impl BlockDevice for AHCIBlockDevice {
+    fn partition_exists(&self, part: usize) -> bool {
+        part < self.devs.len() && self.devs[part].is_some()
+    }
+
+    fn partitions(&self) -> Vec<(usize, Partition)> {
+        self.devs
+            .iter()
+            .enumerate()
+            .filter_map(|(i, d)| d.map(|d| (i, d.part)))
+            .collect()
+    }
+
+    fn partition_size(&self, part: usize) -> usize {
+        self.devs[part].unwrap().part.size()
+    }
+
+    fn max_transfer(&self) -> usize {
+        self.hba.max_transfer()
+    }
+
+    fn read(
+        &mut self,
+        part: usize,
+        buf: &MemGate,
+        buf_off: usize,
+        disk_off: usize,
+        bytes: usize,
+    ) -> Result<(), Error> {
+        let part_desc = self.devs[part].unwrap();
+        self.hba.read_write(
+            part_desc,
+            opcodes::Disk::Read,
+            buf,
+            buf_off,
+            disk_off,
+            bytes,
+        )
+    }
+
+    fn write(
+        &mut self,
+        part: usize,
+        buf: &MemGate,
+        buf_off: usize,
+        disk_off: usize,
+        bytes: usize,
+    ) -> Result<(), Error> {
+        let part_desc = self.devs[part].unwrap();
+        self.hba.read_write(
+            part_desc,
+            opcodes::Disk::Write,
+            buf,
+            buf_off,
+            disk_off,
+            bytes,
+        )
+    }
+}
@@ -0,0 +1,605 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use bitflags::bitflags;
+
+use num_enum::IntoPrimitive;
+
+use m3::cell::StaticRefCell;
+use m3::col::Vec;
+use m3::com::MemGate;
+use m3::errors::{Code, Error};
+use m3::io::LogFlags;
+use m3::log;
+use m3::mem::GlobOff;
+use m3::tiles::OwnActivity;
+use m3::time::TimeDuration;
+
+use super::hba::{AHCIController, HBACaps};
+use crate::partition::{read_partitions, Partition};
+
+/// The maximum number of commands that we issue concurrently per port
+pub const QUEUE_DEPTH: usize = 8;
+/// The size of the transfer buffer of each command slot
+pub const SLOT_BUF_SIZE: usize = 0x10000;
+
+const SECTOR_SIZE: usize = 512;
+
+// layout of the DMA region of each port. The command list needs to be 1 KiB aligned, the received
+// FIS area 256 byte aligned, and the command tables 128 byte aligned.
+const CMD_LIST_OFF: usize = 0;
+const CMD_LIST_SIZE: usize = 32 * 32;
+const FIS_OFF: usize = CMD_LIST_OFF + CMD_LIST_SIZE;
+const FIS_SIZE: usize = 256;
+const CMD_TABLES_OFF: usize = FIS_OFF + FIS_SIZE;
+// the command FIS, ATAPI command, and a single PRDT entry
+const CMD_TABLE_SIZE: usize = 0x100;
+const CMD_TABLE_PRDT_OFF: usize = 0x80;
+const BUFS_OFF: usize = (CMD_TABLES_OFF + QUEUE_DEPTH * CMD_TABLE_SIZE + 0xFFF) & !0xFFF;
+
+/// The size of the DMA region of each port
+pub const DMA_REGION_SIZE: usize = BUFS_OFF + QUEUE_DEPTH * SLOT_BUF_SIZE;
+
+const START_TIMEOUT: TimeDuration = TimeDuration::from_millis(500);
+const START_SLEEPTIME: TimeDuration = TimeDuration::from_micros(20);
+
+const XFER_TIMEOUT: TimeDuration = TimeDuration::from_millis(30);
+const XFER_SLEEPTIME: TimeDuration = TimeDuration::from_micros(20);
+
+static BUF: StaticRefCell<[u8; SLOT_BUF_SIZE]> = StaticRefCell::new([0; SLOT_BUF_SIZE]);
+
+/// Port registers as offsets from the port's register base
+#[allow(dead_code)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, IntoPrimitive)]
+#[repr(u64)]
+pub enum PortReg {
+    CmdListBase   = 0x00,
+    CmdListBaseHi = 0x04,
+    FISBase       = 0x08,
+    FISBaseHi     = 0x0C,
+    IntStatus     = 0x10,
+    IntEnable     = 0x14,
+    Command       = 0x18,
+    TaskFile      = 0x20,
+    Signature     = 0x24,
+    SATAStatus    = 0x28,
+    SATACtrl      = 0x2C,
+    SATAError     = 0x30,
+    SATAActive    = 0x34,
+    CmdIssue      = 0x38,
+}
+
+bitflags! {
+    /// Port command and status
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    struct PortCmd : u32 {
+        /// Start processing the command list
+        const START = 1 << 0;
+        /// Spin-up device
+        const SPIN_UP = 1 << 1;
+        /// Power-on device
+        const POWER_ON = 1 << 2;
+        /// Enable FIS receive
+        const FIS_RECV = 1 << 4;
+        /// FIS receive is running
+        const FIS_RUNNING = 1 << 14;
+        /// Command list is running
+        const CMD_RUNNING = 1 << 15;
+    }
+}
+
+bitflags! {
+    /// Port interrupt status and enable
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    struct PortInt : u32 {
+        /// Device to host register FIS received
+        const D2H_FIS = 1 << 0;
+        /// PIO setup FIS received
+        const PIO_SETUP = 1 << 1;
+        /// Set device bits FIS received (completion of queued commands)
+        const SET_DEV_BITS = 1 << 3;
+        /// Interface fatal error
+        const FATAL = 1 << 30;
+        /// Task file error
+        const TASK_FILE_ERR = 1 << 31;
+    }
+}
+
+bitflags! {
+    /// ATA status in the task file register
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    struct TaskFileStatus : u32 {
+        const BUSY = 1 << 7;
+        const DRQ = 1 << 3;
+        const ERROR = 1 << 0;
+    }
+}
+
+// device detection and interface communication established
+const SSTS_DET_PRESENT: u32 = 0x3;
+const SIG_SATA: u32 = 0x0000_0101;
+
+const FIS_TYPE_REG_H2D: u8 = 0x27;
+// the FIS contains a command (instead of a control update)
+const FIS_CMD: u8 = 1 << 7;
+// LBA addressing mode
+const DEV_LBA: u8 = 1 << 6;
+
+/// ATA commands
+#[derive(Copy, Clone, Debug, Eq, PartialEq, IntoPrimitive)]
+#[repr(u8)]
+enum Command {
+    Identify         = 0xEC,
+    ReadDMA          = 0xC8,
+    ReadDMAExt       = 0x25,
+    WriteDMA         = 0xCA,
+    WriteDMAExt      = 0x35,
+    ReadFPDMAQueued  = 0x60,
+    WriteFPDMAQueued = 0x61,
+}
+
+/// Command header in the command list
+#[repr(C)]
+struct CmdHeader {
+    /// FIS length in dwords, ATAPI, write, prefetchable, reset, BIST, clear busy, PMP
+    flags: u16,
+    /// number of PRDT entries
+    prdt_len: u16,
+    /// number of transferred bytes (updated by the HBA)
+    prd_bytes: u32,
+    table_base: u32,
+    table_base_hi: u32,
+    _reserved: [u32; 4],
+}
+
+/// Register FIS (host to device)
+#[repr(C)]
+#[derive(Default)]
+struct RegFIS {
+    fis_type: u8,
+    flags: u8,
+    command: u8,
+    feature_lo: u8,
+    lba0: u8,
+    lba1: u8,
+    lba2: u8,
+    device: u8,
+    lba3: u8,
+    lba4: u8,
+    lba5: u8,
+    feature_hi: u8,
+    count_lo: u8,
+    count_hi: u8,
+    icc: u8,
+    control: u8,
+    _reserved: [u8; 4],
+}
+
+/// Physical region descriptor table entry
+#[repr(C)]
+struct PRDTEntry {
+    base: u32,
+    base_hi: u32,
+    _reserved: u32,
+    /// byte count - 1 and interrupt on completion
+    bytes: u32,
+}
+
+/// Represents a SATA device attached to a port of the HBA
+pub struct Port {
+    id: u8,
+    port: u8,
+    dma_off: usize,
+    slots: usize,
+    ncq: bool,
+    lba48: bool,
+    capacity: u64,
+    parts: Vec<Partition>,
+}
+
+impl Port {
+    /// Returns whether a SATA device is attached to the given port
+    pub fn is_present(hba: &AHCIController, port: u8) -> Result<bool, Error> {
+        let ssts: u32 = hba.read_port(port, PortReg::SATAStatus)?;
+        let sig: u32 = hba.read_port(port, PortReg::Signature)?;
+        log!(
+            LogFlags::DiskChan,
+            "port[{}] status={:#x} signature={:#x}",
+            port,
+            ssts,
+            sig
+        );
+        Ok((ssts & 0xF) == SSTS_DET_PRESENT && sig == SIG_SATA)
+    }
+
+    pub fn new(hba: &AHCIController, id: u8, port: u8, dma_off: GlobOff) -> Result<Self, Error> {
+        let mut dev = Self {
+            id,
+            port,
+            dma_off: dma_off as usize,
+            slots: QUEUE_DEPTH.min(hba.cmd_slots()),
+            ncq: false,
+            lba48: false,
+            capacity: 0,
+            parts: Vec::new(),
+        };
+
+        dev.start(hba)?;
+
+        // send IDENTIFY command to device
+        let mut words = [0u16; SECTOR_SIZE / 2];
+        dev.identify(hba, &mut words).map_err(|e| {
+            log!(
+                LogFlags::DiskDev,
+                "port[{}] command {:?} failed: {}",
+                port,
+                Command::Identify,
+                e
+            );
+            e
+        })?;
+
+        dev.lba48 = (words[83] & (1 << 10)) != 0;
+        dev.capacity = if dev.lba48 {
+            (words[100] as u64)
+                | (words[101] as u64) << 16
+                | (words[102] as u64) << 32
+                | (words[103] as u64) << 48
+        }
+        else {
+            (words[60] as u64) | (words[61] as u64) << 16
+        };
+
+        // use native command queuing if both HBA and device support it
+        if hba.features().contains(HBACaps::NCQ) && (words[76] & (1 << 8)) != 0 {
+            dev.ncq = true;
+            dev.slots = dev.slots.min((words[75] & 0x1F) as usize + 1);
+        }
+
+        log!(
+            LogFlags::DiskDev,
+            "port[{}] {} sectors, lba48={}, ncq={}, slots={}",
+            port,
+            dev.capacity,
+            dev.lba48,
+            dev.ncq,
+            dev.slots
+        );
+
+        dev.parts = read_partitions(dev.capacity, |lba, buffer| {
+            dev.issue(hba, 0, dev.rw_fis(false, lba, 1, 0), false, SECTOR_SIZE)?;
+            dev.wait(hba, 1, false)?;
+            hba.dma().read(&mut buffer[..], dev.buf_addr(0) as GlobOff)
+        })?;
+
+        Ok(dev)
+    }
+
+    pub fn id(&self) -> u8 {
+        self.id
+    }
+
+    pub fn size(&self) -> usize {
+        self.capacity as usize * SECTOR_SIZE
+    }
+
+    pub fn sector_size(&self) -> usize {
+        SECTOR_SIZE
+    }
+
+    pub fn partitions(&self) -> &Vec<Partition> {
+        &self.parts
+    }
+
+    /// Returns the maximum number of bytes that can be transferred with all slots at once
+    pub fn max_transfer(&self) -> usize {
+        self.slots * SLOT_BUF_SIZE
+    }
+
+    pub fn read_write(
+        &self,
+        hba: &AHCIController,
+        write: bool,
+        buf: &MemGate,
+        off: usize,
+        lba: u64,
+        sec_count: usize,
+    ) -> Result<(), Error> {
+        log!(
+            LogFlags::DiskDev,
+            "port[{}] {} for sectors {}..{}",
+            self.port,
+            if write { "WRITE" } else { "READ" },
+            lba,
+            lba + sec_count as u64 - 1,
+        );
+
+        let secs_per_slot = SLOT_BUF_SIZE / SECTOR_SIZE;
+        let mut done = 0;
+        while done < sec_count {
+            // distribute the request among all slots and issue the commands at once, so that the
+            // device can process them in the order it sees fit
+            let mut slots = 0;
+            let mut pos = done;
+            while pos < sec_count && slots < self.slots {
+                let count = secs_per_slot.min(sec_count - pos);
+                let bytes = count * SECTOR_SIZE;
+                if write {
+                    let mut tmp = BUF.borrow_mut();
+                    buf.read(&mut tmp[0..bytes], (off + pos * SECTOR_SIZE) as GlobOff)?;
+                    hba.dma()
+                        .write(&tmp[0..bytes], self.buf_addr(slots) as GlobOff)?;
+                }
+
+                let fis = self.rw_fis(write, lba + pos as u64, count, slots);
+                self.prepare(hba, slots, fis, write, bytes)?;
+                slots += 1;
+                pos += count;
+            }
+
+            let mask = (1u32 << slots) - 1;
+            if self.ncq {
+                hba.write_port(self.port, PortReg::SATAActive, mask)?;
+            }
+            hba.write_port(self.port, PortReg::CmdIssue, mask)?;
+            self.wait(hba, mask, write)?;
+
+            if !write {
+                let mut tmp = BUF.borrow_mut();
+                for slot in 0..slots {
+                    let start = done + slot * secs_per_slot;
+                    let bytes = secs_per_slot.min(sec_count - start) * SECTOR_SIZE;
+                    hba.dma()
+                        .read(&mut tmp[0..bytes], self.buf_addr(slot) as GlobOff)?;
+                    buf.write(&tmp[0..bytes], (off + start * SECTOR_SIZE) as GlobOff)?;
+                }
+            }
+
+            done = pos;
+        }
+
+        Ok(())
+    }
+
+    fn start(&self, hba: &AHCIController) -> Result<(), Error> {
+        // stop command processing before we change the command list and FIS area
+        let cmd = PortCmd::from_bits_retain(hba.read_port(self.port, PortReg::Command)?);
+        hba.write_port(
+            self.port,
+            PortReg::Command,
+            (cmd - PortCmd::START - PortCmd::FIS_RECV).bits(),
+        )?;
+        self.wait_cmd(hba, PortCmd::CMD_RUNNING | PortCmd::FIS_RUNNING, false)?;
+
+        hba.write_port(
+            self.port,
+            PortReg::CmdListBase,
+            (self.dma_off + CMD_LIST_OFF) as u32,
+        )?;
+        hba.write_port(self.port, PortReg::CmdListBaseHi, 0u32)?;
+        hba.write_port(self.port, PortReg::FISBase, (self.dma_off + FIS_OFF) as u32)?;
+        hba.write_port(self.port, PortReg::FISBaseHi, 0u32)?;
+
+        // clear errors and pending interrupts
+        hba.write_port(self.port, PortReg::SATAError, 0xFFFF_FFFFu32)?;
+        hba.write_port(self.port, PortReg::IntStatus, 0xFFFF_FFFFu32)?;
+        if hba.use_irq() {
+            let ints = PortInt::D2H_FIS
+                | PortInt::PIO_SETUP
+                | PortInt::SET_DEV_BITS
+                | PortInt::FATAL
+                | PortInt::TASK_FILE_ERR;
+            hba.write_port(self.port, PortReg::IntEnable, ints.bits())?;
+        }
+
+        let cmd = cmd | PortCmd::SPIN_UP | PortCmd::POWER_ON | PortCmd::FIS_RECV;
+        hba.write_port(self.port, PortReg::Command, cmd.bits())?;
+        self.wait_cmd(hba, PortCmd::FIS_RUNNING, true)?;
+        hba.write_port(self.port, PortReg::Command, (cmd | PortCmd::START).bits())
+    }
+
+    fn wait_cmd(&self, hba: &AHCIController, flags: PortCmd, set: bool) -> Result<(), Error> {
+        let mut elapsed = TimeDuration::ZERO;
+        loop {
+            let cmd = PortCmd::from_bits_retain(hba.read_port(self.port, PortReg::Command)?);
+            if cmd.intersects(flags) == set {
+                return Ok(());
+            }
+            if elapsed >= START_TIMEOUT {
+                log!(
+                    LogFlags::DiskChan,
+                    "port[{}] timeout waiting for {:?} (set={}), cmd={:?}",
+                    self.port,
+                    flags,
+                    set,
+                    cmd
+                );
+                return Err(Error::new(Code::Timeout));
+            }
+            OwnActivity::sleep_for(START_SLEEPTIME)?;
+            elapsed += START_SLEEPTIME;
+        }
+    }
+
+    fn identify(
+        &self,
+        hba: &AHCIController,
+        words: &mut [u16; SECTOR_SIZE / 2],
+    ) -> Result<(), Error> {
+        let fis = RegFIS {
+            fis_type: FIS_TYPE_REG_H2D,
+            flags: FIS_CMD,
+            command: Command::Identify.into(),
+            ..Default::default()
+        };
+        self.issue(hba, 0, fis, false, SECTOR_SIZE)?;
+        self.wait(hba, 1, false)?;
+        hba.dma().read(words, self.buf_addr(0) as GlobOff)
+    }
+
+    fn rw_fis(&self, write: bool, lba: u64, count: usize, slot: usize) -> RegFIS {
+        let mut fis = RegFIS {
+            fis_type: FIS_TYPE_REG_H2D,
+            flags: FIS_CMD,
+            device: DEV_LBA,
+            lba0: lba as u8,
+            lba1: (lba >> 8) as u8,
+            lba2: (lba >> 16) as u8,
+            lba3: (lba >> 24) as u8,
+            lba4: (lba >> 32) as u8,
+            lba5: (lba >> 40) as u8,
+            ..Default::default()
+        };
+
+        if self.ncq {
+            // the sector count is specified in the features register and the tag in the count
+            fis.command = if write {
+                Command::WriteFPDMAQueued
+            }
+            else {
+                Command::ReadFPDMAQueued
+            }
+            .into();
+            fis.feature_lo = count as u8;
+            fis.feature_hi = (count >> 8) as u8;
+            fis.count_lo = (slot as u8) << 3;
+        }
+        else {
+            fis.command = match (write, self.lba48) {
+                (false, true) => Command::ReadDMAExt,
+                (false, false) => Command::ReadDMA,
+                (true, true) => Command::WriteDMAExt,
+                (true, false) => Command::WriteDMA,
+            }
+            .into();
+            if !self.lba48 {
+                // the upper 4 bits of the LBA are part of the device register
+                fis.device |= (lba >> 24) as u8 & 0xF;
+                fis.lba3 = 0;
+                fis.lba4 = 0;
+                fis.lba5 = 0;
+            }
+            fis.count_lo = count as u8;
+            fis.count_hi = (count >> 8) as u8;
+        }
+        fis
+    }
+
+    fn issue(
+        &self,
+        hba: &AHCIController,
+        slot: usize,
+        fis: RegFIS,
+        write: bool,
+        bytes: usize,
+    ) -> Result<(), Error> {
+        self.prepare(hba, slot, fis, write, bytes)?;
+        hba.write_port(self.port, PortReg::CmdIssue, 1u32 << slot)
+    }
+
+    fn prepare(
+        &self,
+        hba: &AHCIController,
+        slot: usize,
+        fis: RegFIS,
+        write: bool,
+        bytes: usize,
+    ) -> Result<(), Error> {
+        log!(
+            LogFlags::DiskDbg,
+            "port[{}] slot {}: command {:#x} with {} bytes",
+            self.port,
+            slot,
+            fis.command,
+            bytes
+        );
+
+        // command table with the command FIS and the PRDT
+        let table = self.dma_off + CMD_TABLES_OFF + slot * CMD_TABLE_SIZE;
+        hba.dma().write_obj(&fis, table as GlobOff)?;
+        let prdt = PRDTEntry {
+            base: self.buf_addr(slot) as u32,
+            base_hi: 0,
+            _reserved: 0,
+            bytes: (bytes - 1) as u32 | (1 << 31),
+        };
+        hba.dma()
+            .write_obj(&prdt, (table + CMD_TABLE_PRDT_OFF) as GlobOff)?;
+
+        // command header that refers to the table
+        let hdr = CmdHeader {
+            flags: (core::mem::size_of::<RegFIS>() / 4) as u16 | ((write as u16) << 6),
+            prdt_len: 1,
+            prd_bytes: 0,
+            table_base: table as u32,
+            table_base_hi: 0,
+            _reserved: [0; 4],
+        };
+        hba.dma().write_obj(
+            &hdr,
+            (self.dma_off + CMD_LIST_OFF + slot * core::mem::size_of::<CmdHeader>()) as GlobOff,
+        )
+    }
+
+    /// Waits until the commands in the slots of `mask` are completed
+    fn wait(&self, hba: &AHCIController, mask: u32, write: bool) -> Result<(), Error> {
+        let mut elapsed = TimeDuration::ZERO;
+        loop {
+            let status = PortInt::from_bits_retain(hba.read_port(self.port, PortReg::IntStatus)?);
+            hba.write_port(self.port, PortReg::IntStatus, status.bits())?;
+            if status.intersects(PortInt::TASK_FILE_ERR | PortInt::FATAL) {
+                let tfd =
+                    TaskFileStatus::from_bits_retain(hba.read_port(self.port, PortReg::TaskFile)?);
+                log!(
+                    LogFlags::Error,
+                    "port[{}] command failed: status={:?}, taskfile={:?}",
+                    self.port,
+                    status,
+                    tfd
+                );
+                self.start(hba)?;
+                return Err(Error::new(if write {
+                    Code::WriteFailed
+                }
+                else {
+                    Code::ReadFailed
+                }));
+            }
+
+            let mut busy: u32 = hba.read_port(self.port, PortReg::CmdIssue)?;
+            if self.ncq {
+                busy |= hba.read_port::<u32>(self.port, PortReg::SATAActive)?;
+            }
+            if (busy & mask) == 0 {
+                return Ok(());
+            }
+
+            if hba.use_irq() {
+                hba.wait_irq()?;
+            }
+            else {
+                if elapsed >= XFER_TIMEOUT {
+                    return Err(Error::new(Code::Timeout));
+                }
+                OwnActivity::sleep_for(XFER_SLEEPTIME)?;
+                elapsed += XFER_SLEEPTIME;
+            }
+        }
+    }
+
+    fn buf_addr(&self, slot: usize) -> usize {
+        self.dma_off + BUFS_OFF + slot * SLOT_BUF_SIZE
+    }
+}
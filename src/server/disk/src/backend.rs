@@ -16,12 +16,24 @@
  * General Public License version 2 for more details.
  */
 
+use m3::col::Vec;
 use m3::com::MemGate;
 use m3::errors::Error;
 
+use crate::partition::Partition;
+
 pub trait BlockDevice {
     fn partition_exists(&self, part: usize) -> bool;
 
+    /// Returns all partitions together with their number
+    fn partitions(&self) -> Vec<(usize, Partition)>;
+
+    /// Returns the size of the given partition in bytes
+    fn partition_size(&self, part: usize) -> usize;
+
+    /// Returns the maximum number of bytes that can be read or written at once
+    fn max_transfer(&self) -> usize;
+
     fn read(
         &mut self,
         part: usize,
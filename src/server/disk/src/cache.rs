@@ -0,0 +1,313 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use m3::client::DiskCacheStats;
+use m3::col::{Treap, Vec};
+use m3::com::MemGate;
+use m3::errors::{Code, Error};
+use m3::io::LogFlags;
+use m3::kif::Perm;
+use m3::log;
+use m3::mem::GlobOff;
+use m3::server::SessId;
+use m3::vec;
+
+use crate::backend::BlockDevice;
+
+/// The granularity of the cache in bytes
+pub const BLOCK_SIZE: usize = 4096;
+
+/// Determines when modified blocks are written to the disk
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum WritePolicy {
+    /// Writes are passed on to the disk immediately
+    Through,
+    /// Writes are kept in the cache until the block is evicted, the cache is flushed, or the last
+    /// session that used the block is closed
+    Back,
+}
+
+// a cached block is identified by the partition and the block number within the partition
+type BlockKey = (usize, usize);
+
+#[derive(Default)]
+struct Slot {
+    key: Option<BlockKey>,
+    // the number of valid bytes (less than BLOCK_SIZE at the end of a partition)
+    len: usize,
+    dirty: bool,
+    // the sessions that have accessed the block; the block is shared if there are multiple
+    users: Vec<SessId>,
+    last_use: u64,
+}
+
+/// A block cache that is shared by all sessions
+///
+/// Sessions that access the same blocks of a partition (e.g., multiple file system instances)
+/// thereby use the same buffer instead of keeping separate copies. Each block is reference counted
+/// by the sessions using it, which is used to prefer unused blocks for eviction and to write back
+/// modified blocks as soon as the last session has been closed.
+pub struct BlockCache {
+    mem: MemGate,
+    slots: Vec<Slot>,
+    map: Treap<BlockKey, usize>,
+    policy: WritePolicy,
+    tick: u64,
+    // bounce buffer to transfer data between the cache and the clients
+    buf: Vec<u8>,
+    stats: DiskCacheStats,
+}
+
+impl BlockCache {
+    pub fn new(blocks: usize, policy: WritePolicy) -> Result<Self, Error> {
+        let mut slots = Vec::with_capacity(blocks);
+        slots.resize_with(blocks, Slot::default);
+        Ok(Self {
+            mem: MemGate::new((blocks * BLOCK_SIZE) as GlobOff, Perm::RW)?,
+            slots,
+            map: Treap::new(),
+            policy,
+            tick: 0,
+            buf: vec![0u8; BLOCK_SIZE],
+            stats: DiskCacheStats {
+                capacity: blocks,
+                ..Default::default()
+            },
+        })
+    }
+
+    pub fn stats(&self) -> DiskCacheStats {
+        let mut stats = self.stats.clone();
+        for s in self.slots.iter().filter(|s| s.key.is_some()) {
+            stats.used += 1;
+            stats.dirty += s.dirty as usize;
+            stats.shared += (s.users.len() > 1) as usize;
+        }
+        stats
+    }
+
+    fn slot_off(slot: usize) -> usize {
+        slot * BLOCK_SIZE
+    }
+
+    fn write_back(&mut self, dev: &mut dyn BlockDevice, idx: usize) -> Result<(), Error> {
+        let slot = &mut self.slots[idx];
+        if let (true, Some((part, bno))) = (slot.dirty, slot.key) {
+            log!(
+                LogFlags::DiskCache,
+                "cache: writing back block {} of partition {}",
+                bno,
+                part
+            );
+            dev.write(
+                part,
+                &self.mem,
+                Self::slot_off(idx),
+                bno * BLOCK_SIZE,
+                slot.len,
+            )?;
+            slot.dirty = false;
+            self.stats.writebacks += 1;
+        }
+        Ok(())
+    }
+
+    /// Chooses a slot for a new block, preferring free slots and unused blocks
+    fn alloc_slot(&mut self, dev: &mut dyn BlockDevice) -> Result<usize, Error> {
+        let idx = match self.slots.iter().position(|s| s.key.is_none()) {
+            Some(idx) => idx,
+            None => {
+                let (idx, _) = self
+                    .slots
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, s)| (!s.users.is_empty(), s.last_use))
+                    .unwrap();
+                self.write_back(dev, idx)?;
+
+                let key = self.slots[idx].key.take().unwrap();
+                log!(
+                    LogFlags::DiskCache,
+                    "cache: evicting block {} of partition {}",
+                    key.1,
+                    key.0
+                );
+                self.map.remove(&key);
+                self.stats.evictions += 1;
+                idx
+            },
+        };
+        Ok(idx)
+    }
+
+    /// Returns the slot for the given block, loading its content from the disk if `load` is true
+    fn get(
+        &mut self,
+        dev: &mut dyn BlockDevice,
+        sid: SessId,
+        key: BlockKey,
+        load: bool,
+    ) -> Result<usize, Error> {
+        let idx = match self.map.get(&key) {
+            Some(idx) => {
+                self.stats.hits += 1;
+                *idx
+            },
+            None => {
+                self.stats.misses += 1;
+                let idx = self.alloc_slot(dev)?;
+
+                let (part, bno) = key;
+                let len = (dev.partition_size(part) - bno * BLOCK_SIZE).min(BLOCK_SIZE);
+                if load {
+                    log!(
+                        LogFlags::DiskCache,
+                        "cache: loading block {} of partition {}",
+                        bno,
+                        part
+                    );
+                    dev.read(part, &self.mem, Self::slot_off(idx), bno * BLOCK_SIZE, len)?;
+                }
+
+                let slot = &mut self.slots[idx];
+                slot.key = Some(key);
+                slot.len = len;
+                slot.dirty = false;
+                slot.users.clear();
+                self.map.insert(key, idx);
+                idx
+            },
+        };
+
+        self.tick += 1;
+        let slot = &mut self.slots[idx];
+        slot.last_use = self.tick;
+        if !slot.users.contains(&sid) {
+            slot.users.push(sid);
+        }
+        Ok(idx)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn transfer(
+        &mut self,
+        dev: &mut dyn BlockDevice,
+        sid: SessId,
+        part: usize,
+        buf: &MemGate,
+        buf_off: usize,
+        disk_off: usize,
+        bytes: usize,
+        write: bool,
+    ) -> Result<(), Error> {
+        let part_size = dev.partition_size(part);
+        if disk_off.checked_add(bytes).is_none() || disk_off + bytes > part_size {
+            return Err(Error::new(Code::InvArgs));
+        }
+
+        let mut pos = 0;
+        while pos < bytes {
+            let off = disk_off + pos;
+            let bno = off / BLOCK_SIZE;
+            let inner = off % BLOCK_SIZE;
+            let amount = (BLOCK_SIZE - inner).min(bytes - pos);
+            let block_len = (part_size - bno * BLOCK_SIZE).min(BLOCK_SIZE);
+
+            // there is no need to load blocks that are overwritten completely
+            let load = !write || inner != 0 || amount != block_len;
+            let idx = self.get(dev, sid, (part, bno), load)?;
+            let mem_off = (Self::slot_off(idx) + inner) as GlobOff;
+
+            if write {
+                buf.read(&mut self.buf[0..amount], (buf_off + pos) as GlobOff)?;
+                self.mem.write(&self.buf[0..amount], mem_off)?;
+                self.slots[idx].dirty = true;
+                if self.policy == WritePolicy::Through {
+                    self.write_back(dev, idx)?;
+                }
+            }
+            else {
+                self.mem.read(&mut self.buf[0..amount], mem_off)?;
+                buf.write(&self.buf[0..amount], (buf_off + pos) as GlobOff)?;
+            }
+
+            pos += amount;
+        }
+        Ok(())
+    }
+
+    /// Reads `bytes` bytes at `disk_off` from partition `part` into `buf` at `buf_off`
+    #[allow(clippy::too_many_arguments)]
+    pub fn read(
+        &mut self,
+        dev: &mut dyn BlockDevice,
+        sid: SessId,
+        part: usize,
+        buf: &MemGate,
+        buf_off: usize,
+        disk_off: usize,
+        bytes: usize,
+    ) -> Result<(), Error> {
+        self.transfer(dev, sid, part, buf, buf_off, disk_off, bytes, false)
+    }
+
+    /// Writes `bytes` bytes from `buf` at `buf_off` to partition `part` at `disk_off`
+    #[allow(clippy::too_many_arguments)]
+    pub fn write(
+        &mut self,
+        dev: &mut dyn BlockDevice,
+        sid: SessId,
+        part: usize,
+        buf: &MemGate,
+        buf_off: usize,
+        disk_off: usize,
+        bytes: usize,
+    ) -> Result<(), Error> {
+        self.transfer(dev, sid, part, buf, buf_off, disk_off, bytes, true)
+    }
+
+    /// Writes all modified blocks of partition `part` back to the disk
+    pub fn flush(&mut self, dev: &mut dyn BlockDevice, part: usize) -> Result<(), Error> {
+        for idx in 0..self.slots.len() {
+            if matches!(self.slots[idx].key, Some((p, _)) if p == part) {
+                self.write_back(dev, idx)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes all modified blocks back to the disk
+    pub fn flush_all(&mut self, dev: &mut dyn BlockDevice) -> Result<(), Error> {
+        for idx in 0..self.slots.len() {
+            self.write_back(dev, idx)?;
+        }
+        Ok(())
+    }
+
+    /// Drops the references of session `sid` and writes back the modified blocks that are no
+    /// longer used by any session
+    pub fn release(&mut self, dev: &mut dyn BlockDevice, sid: SessId) -> Result<(), Error> {
+        for idx in 0..self.slots.len() {
+            let slot = &mut self.slots[idx];
+            if let Some(pos) = slot.users.iter().position(|u| *u == sid) {
+                slot.users.swap_remove(pos);
+                if slot.users.is_empty() {
+                    self.write_back(dev, idx)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
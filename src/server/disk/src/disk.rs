@@ -15,35 +15,45 @@
 
 #![no_std]
 
+mod ahci;
 mod backend;
+mod cache;
 mod gem5;
+mod nvme;
 mod partition;
+mod virtblk;
 
+use m3::boxed::Box;
+use m3::build_vmsg;
 use m3::cap::{SelSpace, Selector};
 use m3::cell::LazyStaticRefCell;
-use m3::client::{DiskBlockNo, DiskBlockRange};
-use m3::col::{Treap, Vec};
+use m3::client::{DiskBlockNo, DiskBlockRange, DiskCacheStats, DiskPartInfo};
+use m3::col::{ToString, Treap, Vec};
 use m3::com::{opcodes, GateIStream, MemGate};
 use m3::env;
 use m3::errors::{Code, Error};
 use m3::io::LogFlags;
 use m3::kif;
 use m3::log;
+use m3::println;
 use m3::server::{
     CapExchange, ClientManager, ExcType, RequestHandler, RequestSession, Server, ServerSession,
     SessId, DEF_MAX_CLIENTS,
 };
+use m3::tiles::OwnActivity;
 
+use ahci::AHCIBlockDevice;
 use backend::BlockDevice;
+use cache::{BlockCache, WritePolicy};
 use gem5::IDEBlockDevice;
-
-// we can only read 255 sectors (<31 blocks) at once (see ata.cc ata_setupCommand)
-// and the max DMA size is 0x10000 in gem5
-const MAX_DMA_SIZE: usize = 0x10000;
+use nvme::NVMeBlockDevice;
+use virtblk::VirtioBlockDevice;
 
 const MIN_SEC_SIZE: usize = 512;
+const DEF_CACHE_BLOCKS: usize = 64;
 
-static DEVICE: LazyStaticRefCell<IDEBlockDevice> = LazyStaticRefCell::default();
+static DEVICE: LazyStaticRefCell<Box<dyn BlockDevice>> = LazyStaticRefCell::default();
+static CACHE: LazyStaticRefCell<BlockCache> = LazyStaticRefCell::default();
 
 struct DiskSession {
     serv: ServerSession,
@@ -56,7 +66,10 @@ impl RequestSession for DiskSession {
     where
         Self: Sized,
     {
+        // accept both "part<N>" and "<N>"
         let dev = arg
+            .strip_prefix("part")
+            .unwrap_or(arg)
             .parse::<usize>()
             .map_err(|_| Error::new(Code::InvArgs))?;
         if !DEVICE.borrow().partition_exists(dev) {
@@ -79,6 +92,19 @@ impl RequestSession for DiskSession {
 
     fn close(&mut self, _hdl: &mut ClientManager<Self>, sid: SessId, _sub_ids: &mut Vec<SessId>) {
         log!(LogFlags::DiskReqs, "[{}] disk::close()", sid);
+
+        if CACHE.is_some() {
+            let mut dev = DEVICE.borrow_mut();
+            if let Err(e) = CACHE.borrow_mut().release(&mut **dev, sid) {
+                log!(
+                    LogFlags::Error,
+                    "[{}] disk: unable to write back cached blocks: {:?}",
+                    sid,
+                    e
+                );
+            }
+            log!(LogFlags::DiskCache, "cache: {:?}", CACHE.borrow().stats());
+        }
     }
 }
 
@@ -111,17 +137,101 @@ impl DiskSession {
     }
 
     fn read(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let sid = self.serv.id();
         self.read_write(is, "read", |part, mgate, off, start, count| {
-            DEVICE.borrow_mut().read(part, mgate, off, start, count)
+            let mut dev = DEVICE.borrow_mut();
+            if CACHE.is_some() {
+                CACHE
+                    .borrow_mut()
+                    .read(&mut **dev, sid, part, mgate, off, start, count)
+            }
+            else {
+                dev.read(part, mgate, off, start, count)
+            }
         })
     }
 
     fn write(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let sid = self.serv.id();
         self.read_write(is, "write", |part, mgate, off, start, count| {
-            DEVICE.borrow_mut().write(part, mgate, off, start, count)
+            let mut dev = DEVICE.borrow_mut();
+            if CACHE.is_some() {
+                CACHE
+                    .borrow_mut()
+                    .write(&mut **dev, sid, part, mgate, off, start, count)
+            }
+            else {
+                dev.write(part, mgate, off, start, count)
+            }
         })
     }
 
+    fn flush(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        log!(LogFlags::DiskReqs, "[{}] disk::flush()", self.serv.id());
+
+        if CACHE.is_some() {
+            CACHE
+                .borrow_mut()
+                .flush(&mut **DEVICE.borrow_mut(), self.part)?;
+        }
+        is.reply_error(Code::Success)
+    }
+
+    fn stats(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        log!(LogFlags::DiskReqs, "[{}] disk::stats()", self.serv.id());
+
+        let stats = if CACHE.is_some() {
+            CACHE.borrow().stats()
+        }
+        else {
+            DiskCacheStats::default()
+        };
+
+        let mut reply = m3::mem::MsgBuf::borrow_def();
+        build_vmsg!(reply, Code::Success, stats);
+        is.reply(&reply)
+    }
+
+    fn part_count(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        log!(
+            LogFlags::DiskReqs,
+            "[{}] disk::part_count()",
+            self.serv.id()
+        );
+
+        let count = DEVICE.borrow().partitions().len();
+
+        let mut reply = m3::mem::MsgBuf::borrow_def();
+        build_vmsg!(reply, Code::Success, count);
+        is.reply(&reply)
+    }
+
+    fn part_info(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let idx: usize = is.pop()?;
+
+        log!(
+            LogFlags::DiskReqs,
+            "[{}] disk::part_info(idx={})",
+            self.serv.id(),
+            idx
+        );
+
+        let parts = DEVICE.borrow().partitions();
+        let (id, part) = parts.get(idx).ok_or_else(|| Error::new(Code::NotFound))?;
+        let info = DiskPartInfo {
+            id: *id,
+            start: part.start_sector(),
+            sectors: part.sector_count(),
+            ty: partition::type_name(part.ty()),
+            name: part.name().to_string(),
+            bootable: part.bootable(),
+        };
+
+        let mut reply = m3::mem::MsgBuf::borrow_def();
+        build_vmsg!(reply, Code::Success, info);
+        is.reply(&reply)
+    }
+
     fn read_write<F>(&mut self, is: &mut GateIStream<'_>, name: &str, func: F) -> Result<(), Error>
     where
         F: Fn(usize, &MemGate, usize, usize, usize) -> Result<(), Error>,
@@ -158,11 +268,12 @@ impl DiskSession {
         let mut start = start as usize * block_size;
         let mut len = len * block_size;
 
-        while len >= MAX_DMA_SIZE {
-            func(self.part, &mgate, off, start, MAX_DMA_SIZE)?;
-            start += MAX_DMA_SIZE;
-            off += MAX_DMA_SIZE;
-            len -= MAX_DMA_SIZE;
+        let max = DEVICE.borrow().max_transfer();
+        while len >= max {
+            func(self.part, &mgate, off, start, max)?;
+            start += max;
+            off += max;
+            len -= max;
         }
 
         // now write the rest
@@ -174,9 +285,83 @@ impl DiskSession {
     }
 }
 
+fn usage() -> ! {
+    println!(
+        "Usage: {} [-a|-n|-v] [-d] [-i] [-c <blocks>] [-w]",
+        env::args().next().unwrap()
+    );
+    println!();
+    println!("  -a: use the AHCI controller");
+    println!("  -n: use the NVMe controller");
+    println!("  -v: use the virtio block device");
+    println!("  -d: use DMA (IDE only)");
+    println!("  -i: use interrupts");
+    println!(
+        "  -c: the number of {}b blocks of the shared cache (0 disables it; {} by default)",
+        cache::BLOCK_SIZE,
+        DEF_CACHE_BLOCKS
+    );
+    println!("  -w: write modified blocks back lazily instead of immediately");
+    OwnActivity::exit_with(Code::InvArgs);
+}
+
+/// Runs the tests of the partition-table parsing instead of the server
+#[cfg(feature = "tests")]
+fn run_tests() -> ! {
+    use m3::test::{DefaultWvTester, WvTester};
+    use m3::wv_run_suite;
+
+    let mut tester = DefaultWvTester::default();
+    wv_run_suite!(tester, partition::tests::run);
+    println!("{}", tester);
+    OwnActivity::exit_with(if tester.failures() == 0 {
+        Code::Success
+    }
+    else {
+        Code::InvState
+    });
+}
+
 #[no_mangle]
 pub fn main() -> Result<(), Error> {
-    DEVICE.set(IDEBlockDevice::new(env::args().collect()).expect("Unable to create block device"));
+    let args: Vec<&str> = env::args().collect();
+
+    #[cfg(feature = "tests")]
+    if args.get(1) == Some(&"-T") {
+        run_tests();
+    }
+
+    let cache_blocks = match args.iter().position(|a| *a == "-c") {
+        Some(idx) => args
+            .get(idx + 1)
+            .and_then(|n| n.parse::<usize>().ok())
+            .unwrap_or_else(|| usage()),
+        None => DEF_CACHE_BLOCKS,
+    };
+    let policy = if args.iter().any(|a| *a == "-w") {
+        WritePolicy::Back
+    }
+    else {
+        WritePolicy::Through
+    };
+
+    let dev: Box<dyn BlockDevice> = if args.iter().any(|a| *a == "-a") {
+        Box::new(AHCIBlockDevice::new(args).expect("Unable to create AHCI block device"))
+    }
+    else if args.iter().any(|a| *a == "-n") {
+        Box::new(NVMeBlockDevice::new(args).expect("Unable to create NVMe block device"))
+    }
+    else if args.iter().any(|a| *a == "-v") {
+        Box::new(VirtioBlockDevice::new(args).expect("Unable to create virtio block device"))
+    }
+    else {
+        Box::new(IDEBlockDevice::new(args).expect("Unable to create IDE block device"))
+    };
+    DEVICE.set(dev);
+
+    if cache_blocks > 0 {
+        CACHE.set(BlockCache::new(cache_blocks, policy).expect("Unable to create block cache"));
+    }
 
     let mut hdl = RequestHandler::new_with(DEF_MAX_CLIENTS, 256, 1)
         .expect("Unable to create request handler");
@@ -186,9 +371,20 @@ pub fn main() -> Result<(), Error> {
     hdl.reg_cap_handler(Disk::AddMem, ExcType::Del(1), DiskSession::add_mem);
     hdl.reg_msg_handler(Disk::Read, DiskSession::read);
     hdl.reg_msg_handler(Disk::Write, DiskSession::write);
+    hdl.reg_msg_handler(Disk::Flush, DiskSession::flush);
+    hdl.reg_msg_handler(Disk::Stats, DiskSession::stats);
+    hdl.reg_msg_handler(Disk::PartCount, DiskSession::part_count);
+    hdl.reg_msg_handler(Disk::PartInfo, DiskSession::part_info);
 
     hdl.run(&mut srv).expect("Server loop failed");
 
+    // write back the cache before we delete the device
+    if let Some(mut cache) = CACHE.unset() {
+        cache
+            .flush_all(&mut **DEVICE.borrow_mut())
+            .expect("Unable to write back cached blocks");
+    }
+
     // delete device
     DEVICE.unset();
 
@@ -145,7 +145,7 @@ impl Channel {
             return Err(Error::new(Code::InvArgs));
         }
 
-        let lba = desc.part.start_sector() as u64 + disk_off as u64 / dev.sector_size() as u64;
+        let lba = desc.part.start_sector() + disk_off as u64 / dev.sector_size() as u64;
         let count = bytes / dev.sector_size();
 
         let dev_op = match op {
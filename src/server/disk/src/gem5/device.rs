@@ -30,7 +30,7 @@ use m3::time::TimeDuration;
 
 use super::chan::Channel;
 use super::ctrl::ControlFlag;
-use crate::partition::{parse_partitions, Partition};
+use crate::partition::{read_partitions, Partition};
 
 const ATA_WAIT_TIMEOUT: TimeDuration = TimeDuration::from_micros(500);
 
@@ -229,31 +229,15 @@ impl Device {
             return Err(Error::new(Code::NotSup));
         }
 
-        // read MBR from disk
-        let mut buffer = [0u8; 512];
-        let size = (mem::size_of_val(&buffer) + mem::size_of::<PRD>()) as GlobOff;
+        // read the partition table via a buffer that is usable for DMA
+        let size = (512 + mem::size_of::<PRD>()) as GlobOff;
         let mg_buf = MemGate::new(size, Perm::RW)?;
         let dev_buf = mg_buf.derive_cap(0, size, Perm::RW)?;
         chan.set_dma_buffer(&dev_buf)?;
-        dev.read_write(chan, DevOp::READ, &mg_buf, 0, 0, dev.sec_size, 1)?;
-
-        // parse partition table
-        mg_buf.read(&mut buffer, 0)?;
-        for p in parse_partitions(&buffer) {
-            if p.present() {
-                dev.parts.push(p);
-            }
-        }
-        // If no partitions exist just expose the whole disk
-        if dev.parts.is_empty() {
-            log!(
-                LogFlags::DiskDev,
-                "chan[{}] no partitions found, using whole disk",
-                chan.id(),
-            );
-            dev.parts
-                .push(Partition::new_whole_disk(dev.capacity as u32));
-        }
+        dev.parts = read_partitions(dev.capacity as u64, |lba, buffer| {
+            dev.read_write(chan, DevOp::READ, &mg_buf, 0, lba, dev.sec_size, 1)?;
+            mg_buf.read(&mut buffer[..], 0)
+        })?;
 
         Ok(dev)
     }
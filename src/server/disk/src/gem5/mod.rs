@@ -25,6 +25,10 @@ use self::ctrl::DEVICE_COUNT;
 use crate::backend::BlockDevice;
 use crate::partition::{Partition, PART_COUNT};
 
+// we can only read 255 sectors (<31 blocks) at once (see ata.cc ata_setupCommand)
+// and the max DMA size is 0x10000 in gem5
+const MAX_DMA_SIZE: usize = 0x10000;
+
 #[derive(Clone, Copy)]
 pub struct PartDesc {
     chan: u8,
@@ -56,13 +60,11 @@ impl IDEBlockDevice {
         for c in ide_ctrl.channel() {
             for d in c.devices() {
                 for p in d.partitions() {
-                    if p.present() {
-                        devs[d.id() as usize * PART_COUNT + p.id()] = Some(PartDesc {
-                            chan: c.id(),
-                            device: d.id(),
-                            part: *p,
-                        });
-                    }
+                    devs[d.id() as usize * PART_COUNT + p.id()] = Some(PartDesc {
+                        chan: c.id(),
+                        device: d.id(),
+                        part: *p,
+                    });
                 }
             }
         }
@@ -76,6 +78,22 @@ impl BlockDevice for IDEBlockDevice {
         part < self.devs.len() && self.devs[part].is_some()
     }
 
+    fn partitions(&self) -> Vec<(usize, Partition)> {
+        self.devs
+            .iter()
+            .enumerate()
+            .filter_map(|(i, d)| d.map(|d| (i, d.part)))
+            .collect()
+    }
+
+    fn partition_size(&self, part: usize) -> usize {
+        self.devs[part].unwrap().part.size()
+    }
+
+    fn max_transfer(&self) -> usize {
+        MAX_DMA_SIZE
+    }
+
     fn read(
         &mut self,
         part: usize,
@@ -0,0 +1,740 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use bitflags::bitflags;
+
+use num_enum::IntoPrimitive;
+
+use m3::cell::StaticRefCell;
+use m3::col::Vec;
+use m3::com::MemGate;
+use m3::errors::{Code, Error};
+use m3::io::LogFlags;
+use m3::kif::{self, Perm};
+use m3::log;
+use m3::mem::GlobOff;
+use m3::tiles::OwnActivity;
+use m3::time::TimeDuration;
+
+use super::queue::{Command, Completion, QueuePair};
+use super::PartDesc;
+use crate::partition::{read_partitions, Partition};
+
+const NVME_CTRL_CLASS: u8 = 0x01;
+const NVME_CTRL_SUBCLASS: u8 = 0x08;
+const NVME_CTRL_PROG_IF: u8 = 0x02;
+
+/// The maximum number of I/O queue pairs that we create
+const MAX_IO_QUEUES: usize = 4;
+/// The maximum number of commands that we issue concurrently per I/O queue
+const QUEUE_DEPTH: usize = 4;
+/// The size of the transfer buffer of each command slot
+const SLOT_BUF_SIZE: usize = 0x10000;
+/// The maximum number of namespaces that we use
+const MAX_NAMESPACES: usize = 4;
+
+const ADMIN_QUEUE_SIZE: usize = 16;
+// one more entry than commands, because one entry always stays free
+const IO_QUEUE_SIZE: usize = QUEUE_DEPTH + 1;
+
+const PAGE_SIZE: usize = 0x1000;
+const SECTOR_SIZE: usize = 512;
+
+// layout of the DMA region: the admin region contains the admin queues and a buffer for identify
+// data, followed by a region per I/O queue pair with both queues, the PRP lists of all slots, and
+// the transfer buffers. All queues need to be page aligned.
+const ADMIN_SQ_OFF: usize = 0;
+const ADMIN_CQ_OFF: usize = ADMIN_SQ_OFF + PAGE_SIZE;
+const IDENTIFY_OFF: usize = ADMIN_CQ_OFF + PAGE_SIZE;
+const ADMIN_REGION_SIZE: usize = IDENTIFY_OFF + PAGE_SIZE;
+
+const IO_SQ_OFF: usize = 0;
+const IO_CQ_OFF: usize = IO_SQ_OFF + PAGE_SIZE;
+const PRP_LISTS_OFF: usize = IO_CQ_OFF + PAGE_SIZE;
+const PRP_LIST_SIZE: usize = (SLOT_BUF_SIZE / PAGE_SIZE) * 8;
+const BUFS_OFF: usize = PRP_LISTS_OFF + PAGE_SIZE;
+const IO_REGION_SIZE: usize = BUFS_OFF + QUEUE_DEPTH * SLOT_BUF_SIZE;
+
+const ENABLE_SLEEPTIME: TimeDuration = TimeDuration::from_micros(20);
+
+const XFER_TIMEOUT: TimeDuration = TimeDuration::from_millis(500);
+const XFER_SLEEPTIME: TimeDuration = TimeDuration::from_micros(20);
+
+static BUF: StaticRefCell<[u8; SLOT_BUF_SIZE]> = StaticRefCell::new([0; SLOT_BUF_SIZE]);
+
+/// Controller registers
+#[derive(Copy, Clone, Debug, Eq, PartialEq, IntoPrimitive)]
+#[repr(u64)]
+enum NVMeReg {
+    Capabilities   = 0x00,
+    CapabilitiesHi = 0x04,
+    Version        = 0x08,
+    Config         = 0x14,
+    Status         = 0x1C,
+    AdminQueueAttr = 0x24,
+    AdminSQBase    = 0x28,
+    AdminCQBase    = 0x30,
+}
+
+const DOORBELL_BASE: GlobOff = 0x1000;
+
+bitflags! {
+    /// Controller configuration
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    struct CtrlConfig : u32 {
+        const ENABLE = 1 << 0;
+        /// Submission queue entries have 2^6 bytes
+        const IOSQES_64 = 6 << 16;
+        /// Completion queue entries have 2^4 bytes
+        const IOCQES_16 = 4 << 20;
+    }
+}
+
+bitflags! {
+    /// Controller status
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    struct CtrlStatus : u32 {
+        const READY = 1 << 0;
+        const FATAL = 1 << 1;
+    }
+}
+
+/// Admin command opcodes
+#[derive(Copy, Clone, Debug, Eq, PartialEq, IntoPrimitive)]
+#[repr(u8)]
+enum AdminCmd {
+    CreateIOSQ  = 0x01,
+    CreateIOCQ  = 0x05,
+    Identify    = 0x06,
+    SetFeatures = 0x09,
+}
+
+/// I/O command opcodes
+#[derive(Copy, Clone, Debug, Eq, PartialEq, IntoPrimitive)]
+#[repr(u8)]
+enum IOCmd {
+    Write = 0x01,
+    Read  = 0x02,
+}
+
+// values for the controller or namespace structure (CNS) of the identify command
+const CNS_NAMESPACE: u32 = 0x00;
+const CNS_CONTROLLER: u32 = 0x01;
+const CNS_ACTIVE_NS_LIST: u32 = 0x02;
+
+const FEATURE_NUM_QUEUES: u32 = 0x07;
+
+// flags for the creation of I/O queues
+const QUEUE_CONTIGUOUS: u32 = 1 << 0;
+const QUEUE_IRQ_ENABLE: u32 = 1 << 1;
+
+/// A namespace with a 512-byte LBA format
+pub struct Namespace {
+    id: u32,
+    blocks: u64,
+    parts: Vec<Partition>,
+}
+
+impl Namespace {
+    pub fn partitions(&self) -> &[Partition] {
+        &self.parts
+    }
+}
+
+/// Represents an NVMe controller with the namespaces attached to it
+///
+/// The controller uses multiple I/O queue pairs, each with its own MSI-X vector if interrupts are
+/// used. Every request is split into chunks that are distributed among the command slots of all
+/// queues and handed to the controller at once, so that it can process them in parallel.
+pub struct NVMeController {
+    use_irq: bool,
+    pci_dev: pci::Device,
+    dma: MemGate,
+    db_stride: GlobOff,
+    admin: QueuePair,
+    queues: Vec<QueuePair>,
+    chunk_size: usize,
+    namespaces: Vec<Namespace>,
+}
+
+impl NVMeController {
+    pub fn new(use_irq: bool) -> Result<Self, Error> {
+        // find NVMe controller via PCI
+        let pci_dev = pci::Device::new("nvme", kif::TileISA::IDEDev)?;
+        let info = pci_dev.get_info()?;
+        if info.class().base() != NVME_CTRL_CLASS
+            || info.class().sub() != NVME_CTRL_SUBCLASS
+            || info.programming_interface() != NVME_CTRL_PROG_IF
+        {
+            return Err(Error::new(Code::NotFound));
+        }
+
+        log!(
+            LogFlags::DiskCtrl,
+            "Found NVMe controller ({}): vendor {:x} device {:x} rev {}",
+            info.id(),
+            info.vendor(),
+            info.device(),
+            info.revision(),
+        );
+
+        // ensure that the memory space is enabled and bus mastering is enabled
+        let status_cmd: u32 = pci_dev.read_config(pci::Reg::Command.into())?;
+        pci_dev.write_config(
+            pci::Reg::Command.into(),
+            (status_cmd & !0x400) | 0x02 | 0x04,
+        )?;
+
+        // all DMA transfers of the device are performed on a single memory region
+        let size = (ADMIN_REGION_SIZE + MAX_IO_QUEUES * IO_REGION_SIZE) as GlobOff;
+        let dma = MemGate::new(size, Perm::RW)?;
+        let dev_buf = dma.derive_cap(0, size, Perm::RW)?;
+        pci_dev.set_dma_buffer(&dev_buf)?;
+
+        let mut ctrl = Self {
+            use_irq,
+            pci_dev,
+            dma,
+            db_stride: 4,
+            admin: QueuePair::new(
+                0,
+                ADMIN_QUEUE_SIZE,
+                ADMIN_SQ_OFF as GlobOff,
+                ADMIN_CQ_OFF as GlobOff,
+            ),
+            queues: Vec::new(),
+            chunk_size: SLOT_BUF_SIZE,
+            namespaces: Vec::new(),
+        };
+
+        let cap_lo: u32 = ctrl.read_reg(NVMeReg::Capabilities)?;
+        let cap_hi: u32 = ctrl.read_reg(NVMeReg::CapabilitiesHi)?;
+        let version: u32 = ctrl.read_reg(NVMeReg::Version)?;
+        // the maximum queue size is 0-based
+        let max_queue_size = (cap_lo & 0xFFFF) as usize + 1;
+        // the timeout for enabling and disabling the controller is given in 500ms units
+        let timeout = TimeDuration::from_millis(((cap_lo >> 24) & 0xFF).max(1) as u64 * 500);
+        ctrl.db_stride = 4 << (cap_hi & 0xF);
+        if max_queue_size < IO_QUEUE_SIZE.max(ADMIN_QUEUE_SIZE) || ((cap_hi >> 16) & 0xF) != 0 {
+            log!(
+                LogFlags::Error,
+                "nvme: unsupported queue size ({}) or page size",
+                max_queue_size
+            );
+            return Err(Error::new(Code::NotSup));
+        }
+
+        log!(
+            LogFlags::DiskCtrl,
+            "NVMe {}.{}: max queue size {}, doorbell stride {}",
+            version >> 16,
+            (version >> 8) & 0xFF,
+            max_queue_size,
+            ctrl.db_stride
+        );
+
+        ctrl.reset(timeout)?;
+
+        // interrupts are only used for the I/O queues; the admin queue is polled
+        if use_irq {
+            // prefer message-signaled interrupts, but fall back to legacy interrupts
+            match ctrl.pci_dev.enable_msix() {
+                Ok(_) => {
+                    let vec = ctrl.pci_dev.alloc_msix_vector()?;
+                    ctrl.pci_dev.mask_msix_vector(vec, true)?;
+                },
+                Err(e) if e.code() == Code::NotSup => {},
+                Err(e) => return Err(e),
+            }
+        }
+
+        ctrl.identify_ctrl()?;
+        ctrl.create_io_queues()?;
+        ctrl.identify_namespaces()?;
+
+        for i in 0..ctrl.namespaces.len() {
+            ctrl.load_partitions(i)?;
+        }
+
+        // drop all interrupts that have been caused by the initialization
+        while ctrl.pci_dev.fetch_irq().is_some() {}
+
+        Ok(ctrl)
+    }
+
+    pub fn namespaces(&self) -> &[Namespace] {
+        &self.namespaces
+    }
+
+    /// Returns the maximum number of bytes that can be transferred with a single request
+    pub fn max_transfer(&self) -> usize {
+        self.queues.len() * QUEUE_DEPTH * self.chunk_size
+    }
+
+    pub fn read_write(
+        &mut self,
+        part: PartDesc,
+        write: bool,
+        buf: &MemGate,
+        off: usize,
+        disk_off: usize,
+        bytes: usize,
+    ) -> Result<(), Error> {
+        // check arguments
+        let part_size = part.part.sector_count() as usize * SECTOR_SIZE;
+        if disk_off.checked_add(bytes).is_none() || disk_off + bytes > part_size {
+            log!(
+                LogFlags::DiskChan,
+                "Invalid request: disk_off={}, bytes={}, part-size: {}",
+                disk_off,
+                bytes,
+                part_size
+            );
+            return Err(Error::new(Code::InvArgs));
+        }
+
+        let nsid = self.namespaces[part.ns].id;
+        let lba = part.part.start_sector() + (disk_off / SECTOR_SIZE) as u64;
+        let sec_count = bytes / SECTOR_SIZE;
+        log!(
+            LogFlags::DiskDev,
+            "nvme: {} on namespace {} for sectors {}..{}",
+            if write { "WRITE" } else { "READ" },
+            nsid,
+            lba,
+            lba + sec_count as u64 - 1,
+        );
+
+        let secs_per_slot = self.chunk_size / SECTOR_SIZE;
+        let slots = self.queues.len() * QUEUE_DEPTH;
+        let mut tmp = BUF.borrow_mut();
+        let mut done = 0;
+        while done < sec_count {
+            // distribute the request among the slots of all queues and hand them to the
+            // controller at once
+            let mut slot = 0;
+            let mut pos = done;
+            while pos < sec_count && slot < slots {
+                let (queue, qslot) = self.slot_pos(slot);
+                let count = secs_per_slot.min(sec_count - pos);
+                if write {
+                    let bytes = count * SECTOR_SIZE;
+                    buf.read(&mut tmp[0..bytes], (off + pos * SECTOR_SIZE) as GlobOff)?;
+                    self.dma
+                        .write(&tmp[0..bytes], self.buf_addr(queue, qslot))?;
+                }
+
+                self.issue(queue, qslot, nsid, write, lba + pos as u64, count)?;
+                slot += 1;
+                pos += count;
+            }
+
+            for q in 0..self.queues.len().min(slot) {
+                self.ring_sq(q)?;
+            }
+            self.wait(write)?;
+
+            if !write {
+                for i in 0..slot {
+                    let (queue, qslot) = self.slot_pos(i);
+                    let start = done + i * secs_per_slot;
+                    let bytes = secs_per_slot.min(sec_count - start) * SECTOR_SIZE;
+                    self.dma
+                        .read(&mut tmp[0..bytes], self.buf_addr(queue, qslot))?;
+                    buf.write(&tmp[0..bytes], (off + start * SECTOR_SIZE) as GlobOff)?;
+                }
+            }
+
+            done = pos;
+        }
+
+        Ok(())
+    }
+
+    fn reset(&self, timeout: TimeDuration) -> Result<(), Error> {
+        // disable the controller before we configure the admin queues
+        let config: u32 = self.read_reg(NVMeReg::Config)?;
+        if (config & CtrlConfig::ENABLE.bits()) != 0 {
+            self.write_reg(NVMeReg::Config, config & !CtrlConfig::ENABLE.bits())?;
+        }
+        self.wait_ready(false, timeout)?;
+
+        let qsize = (ADMIN_QUEUE_SIZE - 1) as u32;
+        self.write_reg(NVMeReg::AdminQueueAttr, qsize << 16 | qsize)?;
+        self.write_reg64(NVMeReg::AdminSQBase, self.admin.sq_addr())?;
+        self.write_reg64(NVMeReg::AdminCQBase, self.admin.cq_addr())?;
+
+        // NVM command set and 4 KiB pages
+        let config = CtrlConfig::ENABLE | CtrlConfig::IOSQES_64 | CtrlConfig::IOCQES_16;
+        self.write_reg(NVMeReg::Config, config.bits())?;
+        self.wait_ready(true, timeout)
+    }
+
+    fn wait_ready(&self, ready: bool, timeout: TimeDuration) -> Result<(), Error> {
+        let mut elapsed = TimeDuration::ZERO;
+        loop {
+            let status = CtrlStatus::from_bits_truncate(self.read_reg(NVMeReg::Status)?);
+            if status.contains(CtrlStatus::FATAL) {
+                log!(LogFlags::Error, "nvme: controller reported fatal status");
+                return Err(Error::new(Code::InvState));
+            }
+            if status.contains(CtrlStatus::READY) == ready {
+                return Ok(());
+            }
+
+            if elapsed >= timeout {
+                return Err(Error::new(Code::Timeout));
+            }
+            OwnActivity::sleep_for(ENABLE_SLEEPTIME)?;
+            elapsed += ENABLE_SLEEPTIME;
+        }
+    }
+
+    fn identify_ctrl(&mut self) -> Result<(), Error> {
+        self.identify(CNS_CONTROLLER, 0)?;
+
+        let mut data = [0u8; 1024];
+        self.dma.read(&mut data, IDENTIFY_OFF as GlobOff)?;
+
+        // the maximum data transfer size is a power of two in units of the minimum page size
+        let mdts = data[77];
+        if mdts != 0 {
+            let max = PAGE_SIZE << mdts.min(16);
+            self.chunk_size = self.chunk_size.min(max);
+        }
+
+        let model = core::str::from_utf8(&data[24..64]).unwrap_or("?").trim();
+        log!(
+            LogFlags::DiskCtrl,
+            "nvme: model '{}', {} namespaces, {} KiB per command",
+            model,
+            u32::from_le_bytes([data[516], data[517], data[518], data[519]]),
+            self.chunk_size / 1024
+        );
+        Ok(())
+    }
+
+    fn create_io_queues(&mut self) -> Result<(), Error> {
+        // ask for as many queues as we want to use; both counts are 0-based
+        let wanted = (MAX_IO_QUEUES - 1) as u32;
+        let res = self.admin_cmd(Command {
+            opcode: AdminCmd::SetFeatures.into(),
+            cdw10: FEATURE_NUM_QUEUES,
+            cdw11: wanted << 16 | wanted,
+            ..Default::default()
+        })?;
+        let granted = ((res.result & 0xFFFF).min(res.result >> 16) + 1) as usize;
+        let count = MAX_IO_QUEUES.min(granted);
+
+        for i in 0..count {
+            let id = i as u16 + 1;
+            let vector = match (self.use_irq, self.pci_dev.msix_enabled()) {
+                (true, true) => match self.pci_dev.alloc_msix_vector() {
+                    Ok(vec) => vec,
+                    // use the remaining queues without interrupts
+                    Err(e) if e.code() == Code::NoSpace && i > 0 => break,
+                    Err(e) => return Err(e),
+                },
+                // with legacy interrupts, all queues use vector 0
+                _ => 0,
+            };
+
+            let region = ADMIN_REGION_SIZE + i * IO_REGION_SIZE;
+            let queue = QueuePair::new(
+                id,
+                IO_QUEUE_SIZE,
+                (region + IO_SQ_OFF) as GlobOff,
+                (region + IO_CQ_OFF) as GlobOff,
+            );
+
+            let irq = if self.use_irq { QUEUE_IRQ_ENABLE } else { 0 };
+            self.admin_cmd(Command {
+                opcode: AdminCmd::CreateIOCQ.into(),
+                prp1: queue.cq_addr(),
+                cdw10: ((queue.size() - 1) as u32) << 16 | id as u32,
+                cdw11: (vector as u32) << 16 | irq | QUEUE_CONTIGUOUS,
+                ..Default::default()
+            })?;
+            self.admin_cmd(Command {
+                opcode: AdminCmd::CreateIOSQ.into(),
+                prp1: queue.sq_addr(),
+                cdw10: ((queue.size() - 1) as u32) << 16 | id as u32,
+                cdw11: (id as u32) << 16 | QUEUE_CONTIGUOUS,
+                ..Default::default()
+            })?;
+
+            // the transfer buffers and thus the PRP lists of all slots never change
+            for slot in 0..QUEUE_DEPTH {
+                let buf = (region + BUFS_OFF + slot * SLOT_BUF_SIZE) as GlobOff;
+                let list = (region + PRP_LISTS_OFF + slot * PRP_LIST_SIZE) as GlobOff;
+                for page in 0..SLOT_BUF_SIZE / PAGE_SIZE {
+                    let addr = buf + (page * PAGE_SIZE) as GlobOff;
+                    self.dma.write_obj(&addr, list + (page * 8) as GlobOff)?;
+                }
+            }
+
+            self.queues.push(queue);
+        }
+
+        log!(
+            LogFlags::DiskCtrl,
+            "nvme: using {} I/O queues with {} slots each",
+            self.queues.len(),
+            QUEUE_DEPTH
+        );
+        Ok(())
+    }
+
+    fn identify_namespaces(&mut self) -> Result<(), Error> {
+        self.identify(CNS_ACTIVE_NS_LIST, 0)?;
+        let mut ids = [0u32; MAX_NAMESPACES];
+        self.dma.read(&mut ids, IDENTIFY_OFF as GlobOff)?;
+
+        for id in ids.iter().take_while(|id| **id != 0) {
+            self.identify(CNS_NAMESPACE, *id)?;
+            let mut data = [0u8; 256];
+            self.dma.read(&mut data, IDENTIFY_OFF as GlobOff)?;
+
+            let blocks = u64::from_le_bytes(data[0..8].try_into().unwrap());
+            let format = (data[26] & 0xF) as usize;
+            let lba_size = 1usize << data[128 + format * 4 + 2];
+            if lba_size != SECTOR_SIZE {
+                log!(
+                    LogFlags::Error,
+                    "nvme: ignoring namespace {} with {}-byte blocks",
+                    id,
+                    lba_size
+                );
+                continue;
+            }
+
+            log!(
+                LogFlags::DiskCtrl,
+                "nvme: namespace {}: {} MiB",
+                id,
+                (blocks as usize * SECTOR_SIZE) / (1024 * 1024)
+            );
+            self.namespaces.push(Namespace {
+                id: *id,
+                blocks,
+                parts: Vec::new(),
+            });
+        }
+
+        Ok(())
+    }
+
+    fn load_partitions(&mut self, idx: usize) -> Result<(), Error> {
+        let nsid = self.namespaces[idx].id;
+        let parts = read_partitions(self.namespaces[idx].blocks, |lba, buffer| {
+            self.issue(0, 0, nsid, false, lba, 1)?;
+            self.ring_sq(0)?;
+            self.wait(false)?;
+            self.dma.read(&mut buffer[..], self.buf_addr(0, 0))
+        })?;
+
+        for p in &parts {
+            log!(
+                LogFlags::DiskCtrl,
+                "nvme: namespace {}: registered {:?}",
+                nsid,
+                p
+            );
+        }
+        self.namespaces[idx].parts = parts;
+        Ok(())
+    }
+
+    fn identify(&mut self, cns: u32, nsid: u32) -> Result<(), Error> {
+        self.admin_cmd(Command {
+            opcode: AdminCmd::Identify.into(),
+            nsid,
+            prp1: IDENTIFY_OFF as GlobOff,
+            cdw10: cns,
+            ..Default::default()
+        })
+        .map(|_| ())
+    }
+
+    /// Issues the given admin command and polls for its completion
+    fn admin_cmd(&mut self, cmd: Command) -> Result<Completion, Error> {
+        log!(LogFlags::DiskDbg, "nvme: admin command {:#x}", cmd.opcode);
+
+        self.admin.push(&self.dma, &cmd)?;
+        self.write_doorbell(0, false, self.admin.sq_tail())?;
+
+        let mut elapsed = TimeDuration::ZERO;
+        loop {
+            if let Some(comp) = self.admin.pop(&self.dma)? {
+                self.write_doorbell(0, true, self.admin.cq_head())?;
+                if comp.status_code() != 0 {
+                    log!(
+                        LogFlags::Error,
+                        "nvme: admin command {:#x} failed with status {:#x}",
+                        cmd.opcode,
+                        comp.status_code()
+                    );
+                    return Err(Error::new(Code::InvState));
+                }
+                return Ok(comp);
+            }
+
+            if elapsed >= XFER_TIMEOUT {
+                return Err(Error::new(Code::Timeout));
+            }
+            OwnActivity::sleep_for(XFER_SLEEPTIME)?;
+            elapsed += XFER_SLEEPTIME;
+        }
+    }
+
+    fn issue(
+        &mut self,
+        queue: usize,
+        slot: usize,
+        nsid: u32,
+        write: bool,
+        lba: u64,
+        count: usize,
+    ) -> Result<(), Error> {
+        let buf = self.buf_addr(queue, slot);
+        let bytes = count * SECTOR_SIZE;
+        let prp2 = match bytes.div_ceil(PAGE_SIZE) {
+            1 => 0,
+            2 => buf + PAGE_SIZE as GlobOff,
+            // the first entry of the list is already covered by PRP1
+            _ => self.prp_list_addr(queue, slot) + 8,
+        };
+
+        log!(
+            LogFlags::DiskDbg,
+            "nvme: queue {} slot {}: {} of {} sectors at {}",
+            queue,
+            slot,
+            if write { "write" } else { "read" },
+            count,
+            lba
+        );
+
+        let cmd = Command {
+            opcode: if write { IOCmd::Write } else { IOCmd::Read }.into(),
+            cid: slot as u16,
+            nsid,
+            prp1: buf,
+            prp2,
+            cdw10: lba as u32,
+            cdw11: (lba >> 32) as u32,
+            // the number of blocks is 0-based
+            cdw12: (count - 1) as u32,
+            ..Default::default()
+        };
+        self.queues[queue].push(&self.dma, &cmd)
+    }
+
+    /// Waits until all commands of all I/O queues are completed and checks their status
+    fn wait(&mut self, write: bool) -> Result<(), Error> {
+        let mut failed = false;
+        let mut elapsed = TimeDuration::ZERO;
+        loop {
+            for q in 0..self.queues.len() {
+                let mut popped = false;
+                while let Some(comp) = self.queues[q].pop(&self.dma)? {
+                    if comp.status_code() != 0 {
+                        log!(
+                            LogFlags::Error,
+                            "nvme: command {} on queue {} failed with status {:#x}",
+                            comp.cid,
+                            self.queues[q].id(),
+                            comp.status_code()
+                        );
+                        failed = true;
+                    }
+                    popped = true;
+                }
+                if popped {
+                    self.ring_cq(q)?;
+                }
+            }
+
+            if self.queues.iter().all(|q| q.pending() == 0) {
+                break;
+            }
+
+            if self.use_irq {
+                log!(LogFlags::DiskDbg, "nvme: waiting for IRQ...");
+                self.pci_dev.receive_irq()?;
+            }
+            else {
+                if elapsed >= XFER_TIMEOUT {
+                    return Err(Error::new(Code::Timeout));
+                }
+                OwnActivity::sleep_for(XFER_SLEEPTIME)?;
+                elapsed += XFER_SLEEPTIME;
+            }
+        }
+
+        match failed {
+            true if write => Err(Error::new(Code::WriteFailed)),
+            true => Err(Error::new(Code::ReadFailed)),
+            false => Ok(()),
+        }
+    }
+
+    /// Returns the queue and the slot within the queue for the given slot of a request
+    ///
+    /// Consecutive slots are put onto different queues to spread the load evenly.
+    fn slot_pos(&self, slot: usize) -> (usize, usize) {
+        (slot % self.queues.len(), slot / self.queues.len())
+    }
+
+    fn buf_addr(&self, queue: usize, slot: usize) -> GlobOff {
+        (ADMIN_REGION_SIZE + queue * IO_REGION_SIZE + BUFS_OFF + slot * SLOT_BUF_SIZE) as GlobOff
+    }
+
+    fn prp_list_addr(&self, queue: usize, slot: usize) -> GlobOff {
+        (ADMIN_REGION_SIZE + queue * IO_REGION_SIZE + PRP_LISTS_OFF + slot * PRP_LIST_SIZE)
+            as GlobOff
+    }
+
+    fn ring_sq(&self, queue: usize) -> Result<(), Error> {
+        let q = &self.queues[queue];
+        self.write_doorbell(q.id(), false, q.sq_tail())
+    }
+
+    fn ring_cq(&self, queue: usize) -> Result<(), Error> {
+        let q = &self.queues[queue];
+        self.write_doorbell(q.id(), true, q.cq_head())
+    }
+
+    fn write_doorbell(&self, qid: u16, completion: bool, val: u32) -> Result<(), Error> {
+        let idx = qid as GlobOff * 2 + completion as GlobOff;
+        self.pci_dev
+            .write_reg(DOORBELL_BASE + idx * self.db_stride, val)
+    }
+
+    fn read_reg<T>(&self, reg: NVMeReg) -> Result<T, Error> {
+        self.pci_dev.read_reg(reg as GlobOff)
+    }
+
+    fn write_reg<T>(&self, reg: NVMeReg, val: T) -> Result<(), Error> {
+        self.pci_dev.write_reg(reg as GlobOff, val)
+    }
+
+    /// Writes a 64-bit register as two 32-bit halves, starting with the lower half
+    fn write_reg64(&self, reg: NVMeReg, val: u64) -> Result<(), Error> {
+        self.pci_dev.write_reg(reg as GlobOff, val as u32)?;
+        self.pci_dev
+            .write_reg(reg as GlobOff + 4, (val >> 32) as u32)
+    }
+}
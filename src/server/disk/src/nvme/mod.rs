@@ -0,0 +1,105 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+mod ctrl;
+mod queue;
+
+use m3::col::Vec;
+use m3::com::MemGate;
+use m3::errors::Error;
+use m3::vec;
+
+use crate::backend::BlockDevice;
+use crate::partition::{Partition, PART_COUNT};
+
+#[derive(Clone, Copy)]
+pub struct PartDesc {
+    ns: usize,
+    part: Partition,
+}
+
+/// Block device for NVMe namespaces
+///
+/// The partitions of the first namespace are numbered 0..3, the ones of the second namespace
+/// 4..7, and so on.
+pub struct NVMeBlockDevice {
+    ctrl: ctrl::NVMeController,
+    devs: Vec<Option<PartDesc>>,
+}
+
+impl NVMeBlockDevice {
+    pub fn new(args: Vec<&str>) -> Result<Self, Error> {
+        let use_irq = args.iter().any(|s| *s == "-i");
+
+        let ctrl = ctrl::NVMeController::new(use_irq)?;
+
+        let mut devs = vec![None; ctrl.namespaces().len() * PART_COUNT];
+        for (ns, desc) in ctrl.namespaces().iter().enumerate() {
+            for p in desc.partitions() {
+                devs[ns * PART_COUNT + p.id()] = Some(PartDesc { ns, part: *p });
+            }
+        }
+
+        Ok(NVMeBlockDevice { ctrl, devs })
+    }
+}
+
+impl BlockDevice for NVMeBlockDevice {
+    fn partition_exists(&self, part: usize) -> bool {
+        part < self.devs.len() && self.devs[part].is_some()
+    }
+
+    fn partitions(&self) -> Vec<(usize, Partition)> {
+        self.devs
+            .iter()
+            .enumerate()
+            .filter_map(|(i, d)| d.map(|d| (i, d.part)))
+            .collect()
+    }
+
+    fn partition_size(&self, part: usize) -> usize {
+        self.devs[part].unwrap().part.size()
+    }
+
+    fn max_transfer(&self) -> usize {
+        self.ctrl.max_transfer()
+    }
+
+    fn read(
+        &mut self,
+        part: usize,
+        buf: &MemGate,
+        buf_off: usize,
+        disk_off: usize,
+        bytes: usize,
+    ) -> Result<(), Error> {
+        let part_desc = self.devs[part].unwrap();
+        self.ctrl
+            .read_write(part_desc, false, buf, buf_off, disk_off, bytes)
+    }
+
+    fn write(
+        &mut self,
+        part: usize,
+        buf: &MemGate,
+        buf_off: usize,
+        disk_off: usize,
+        bytes: usize,
+    ) -> Result<(), Error> {
+        let part_desc = self.devs[part].unwrap();
+        self.ctrl
+            .read_write(part_desc, true, buf, buf_off, disk_off, bytes)
+    }
+}
@@ -0,0 +1,159 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use m3::com::MemGate;
+use m3::errors::{Code, Error};
+use m3::mem::GlobOff;
+
+pub const SQ_ENTRY_SIZE: usize = 64;
+pub const CQ_ENTRY_SIZE: usize = 16;
+
+/// A command in a submission queue
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Command {
+    pub opcode: u8,
+    pub flags: u8,
+    pub cid: u16,
+    pub nsid: u32,
+    pub(crate) _reserved: u64,
+    pub mptr: u64,
+    pub prp1: u64,
+    pub prp2: u64,
+    pub cdw10: u32,
+    pub cdw11: u32,
+    pub cdw12: u32,
+    pub cdw13: u32,
+    pub cdw14: u32,
+    pub cdw15: u32,
+}
+
+/// An entry in a completion queue
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Completion {
+    pub result: u32,
+    _reserved: u32,
+    pub sq_head: u16,
+    pub sq_id: u16,
+    pub cid: u16,
+    pub status: u16,
+}
+
+impl Completion {
+    fn phase(&self) -> bool {
+        (self.status & 1) != 0
+    }
+
+    /// Returns the status code type and status code, which are zero on success
+    pub fn status_code(&self) -> u16 {
+        self.status >> 1
+    }
+}
+
+/// A submission queue and its completion queue
+///
+/// Both queues are located in the DMA region and have the same number of entries.
+pub struct QueuePair {
+    id: u16,
+    size: usize,
+    sq_addr: GlobOff,
+    cq_addr: GlobOff,
+    sq_tail: usize,
+    cq_head: usize,
+    phase: bool,
+    // the number of submitted commands that have not completed yet
+    pending: usize,
+}
+
+impl QueuePair {
+    pub fn new(id: u16, size: usize, sq_addr: GlobOff, cq_addr: GlobOff) -> Self {
+        Self {
+            id,
+            size,
+            sq_addr,
+            cq_addr,
+            sq_tail: 0,
+            cq_head: 0,
+            phase: true,
+            pending: 0,
+        }
+    }
+
+    pub fn id(&self) -> u16 {
+        self.id
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn sq_addr(&self) -> GlobOff {
+        self.sq_addr
+    }
+
+    pub fn cq_addr(&self) -> GlobOff {
+        self.cq_addr
+    }
+
+    pub fn sq_tail(&self) -> u32 {
+        self.sq_tail as u32
+    }
+
+    pub fn cq_head(&self) -> u32 {
+        self.cq_head as u32
+    }
+
+    /// Returns the number of commands that have not completed yet
+    pub fn pending(&self) -> usize {
+        self.pending
+    }
+
+    /// Writes the given command into the submission queue
+    ///
+    /// The command is not visible to the controller until the new tail has been written to the
+    /// doorbell register.
+    pub fn push(&mut self, dma: &MemGate, cmd: &Command) -> Result<(), Error> {
+        // one entry always stays free to distinguish a full from an empty queue
+        if self.pending == self.size - 1 {
+            return Err(Error::new(Code::NoSpace));
+        }
+
+        let addr = self.sq_addr + (self.sq_tail * SQ_ENTRY_SIZE) as GlobOff;
+        dma.write_obj(cmd, addr)?;
+        self.sq_tail = (self.sq_tail + 1) % self.size;
+        self.pending += 1;
+        Ok(())
+    }
+
+    /// Returns the next completion or `None` if the controller has not posted a new one
+    ///
+    /// The consumed entries need to be released by writing the new head to the doorbell register.
+    pub fn pop(&mut self, dma: &MemGate) -> Result<Option<Completion>, Error> {
+        let addr = self.cq_addr + (self.cq_head * CQ_ENTRY_SIZE) as GlobOff;
+        let comp: Completion = dma.read_obj(addr)?;
+        if comp.phase() != self.phase {
+            return Ok(None);
+        }
+
+        self.cq_head += 1;
+        if self.cq_head == self.size {
+            self.cq_head = 0;
+            self.phase = !self.phase;
+        }
+        self.pending -= 1;
+        Ok(Some(comp))
+    }
+}
@@ -13,91 +13,645 @@
  * General Public License version 2 for more details.
  */
 
+//! Parsing of MBR and GPT partition tables
+
 use core::fmt;
 
-use m3::col::Vec;
+use m3::col::{String, Vec};
+use m3::errors::{Code, Error};
+use m3::format;
+use m3::io::LogFlags;
+use m3::log;
+
+const SECTOR_SIZE: usize = 512;
+
+/// The maximum number of partitions per disk
+///
+/// An MBR has at most four primary partitions. Of a GPT, only the first `PART_COUNT` entries are
+/// used.
+pub const PART_COUNT: usize = 16;
+
+// offset of partition-table in MBR
+const PART_TABLE_OFFSET: usize = 0x1BE;
+const MBR_PART_COUNT: usize = 4;
+const MBR_BOOTABLE: u8 = 0x80;
+// the system id of the single partition in the protective MBR of a GPT disk
+const MBR_TYPE_GPT: u8 = 0xEE;
+
+const GPT_HEADER_LBA: u64 = 1;
+const GPT_SIGNATURE: &[u8] = b"EFI PART";
+const GPT_HEADER_MIN_SIZE: usize = 92;
+const GPT_ENTRY_MIN_SIZE: usize = 128;
+// we refuse to read more entries to not spend ages on a corrupt table
+const GPT_MAX_ENTRIES: usize = 1024;
+const GPT_NAME_LEN: usize = 36;
+// the "legacy BIOS bootable" attribute
+const GPT_ATTR_BOOTABLE: u64 = 1 << 2;
+
+/// The type of a partition as specified in the partition table
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PartType {
+    /// There is no partition table and the partition spans the whole disk
+    Whole,
+    /// A primary partition of an MBR with given system id
+    Mbr(u8),
+    /// A GPT partition with given type GUID (in on-disk byte order)
+    Gpt([u8; 16]),
+}
 
 #[derive(Clone, Copy)]
 pub struct Partition {
     id: usize,
-    present: bool,
-    start: u32,
-    size: u32,
+    start: u64,
+    size: u64,
+    ty: PartType,
+    bootable: bool,
+    // the GPT partition name, reduced to ASCII and padded with zeros
+    name: [u8; GPT_NAME_LEN],
 }
 
 impl Partition {
-    pub fn new_whole_disk(size: u32) -> Self {
+    pub fn new_whole_disk(size: u64) -> Self {
         Partition {
             id: 0,
-            present: true,
             start: 0,
             size,
+            ty: PartType::Whole,
+            bootable: false,
+            name: [0; GPT_NAME_LEN],
         }
     }
 
+    /// Returns the index of the partition within the partition table
     pub fn id(&self) -> usize {
         self.id
     }
 
-    pub fn present(&self) -> bool {
-        self.present
-    }
-
-    pub fn start_sector(&self) -> u32 {
+    pub fn start_sector(&self) -> u64 {
         self.start
     }
 
-    pub fn sector_count(&self) -> u32 {
+    pub fn sector_count(&self) -> u64 {
         self.size
     }
+
+    pub fn size(&self) -> usize {
+        self.size as usize * SECTOR_SIZE
+    }
+
+    pub fn ty(&self) -> PartType {
+        self.ty
+    }
+
+    pub fn bootable(&self) -> bool {
+        self.bootable
+    }
+
+    /// Returns the name of the partition (empty for MBR partitions)
+    pub fn name(&self) -> &str {
+        let len = self
+            .name
+            .iter()
+            .position(|c| *c == 0)
+            .unwrap_or(GPT_NAME_LEN);
+        // the name consists of ASCII characters only
+        core::str::from_utf8(&self.name[0..len]).unwrap()
+    }
 }
 
 impl fmt::Debug for Partition {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
         write!(
             f,
-            "Partition[sectors={}..{}]",
+            "Partition[id={}, sectors={}..{}, type={}",
+            self.id,
             self.start,
-            self.start + self.size - 1
-        )
+            self.start + self.size - 1,
+            type_name(self.ty)
+        )?;
+        if !self.name().is_empty() {
+            write!(f, ", name='{}'", self.name())?;
+        }
+        write!(f, "]")
     }
 }
 
-// offset of partition-table in MBR
-const PART_TABLE_OFFSET: isize = 0x1BE;
-
-pub const PART_COUNT: usize = 4;
-
-#[repr(C, packed)]
-struct DiskParition {
-    // boot indicator bit flag: 0 = no, 0x80 = bootable (or "active")
-    bootable: u8,
-    // start: Cylinder, Head, Sector
-    start_head: u8,
-    start_sec_cylinder: u16,
-    system_id: u8,
-    // end: Cylinder, Head, Sector
-    end_head: u8,
-    end_sec_cylinder: u16,
-    // relative Sector (to start of partition -- also equals the partition's starting LBA value)
-    start: u32,
-    // total Sectors in partition
-    size: u32,
+/// Returns a textual representation of the given partition type
+///
+/// For GPT partitions, this is the type GUID in its canonical form.
+pub fn type_name(ty: PartType) -> String {
+    match ty {
+        PartType::Whole => String::from("whole"),
+        PartType::Mbr(id) => format!("mbr:{:#04x}", id),
+        PartType::Gpt(g) => format!(
+            "{:08X}-{:04X}-{:04X}-{:02X}{:02X}-{:02X}{:02X}{:02X}{:02X}{:02X}{:02X}",
+            u32::from_le_bytes([g[0], g[1], g[2], g[3]]),
+            u16::from_le_bytes([g[4], g[5]]),
+            u16::from_le_bytes([g[6], g[7]]),
+            g[8],
+            g[9],
+            g[10],
+            g[11],
+            g[12],
+            g[13],
+            g[14],
+            g[15]
+        ),
+    }
+}
+
+/// Reads the partition table of a disk with `capacity` sectors and returns its partitions
+///
+/// `read` is called to read single sectors. If the MBR is the protective MBR of a GUID partition
+/// table, the partitions are taken from the GPT, otherwise from the MBR. If the disk has no
+/// partitions or the GPT is invalid, a single partition for the whole disk is returned.
+pub fn read_partitions<F>(capacity: u64, mut read: F) -> Result<Vec<Partition>, Error>
+where
+    F: FnMut(u64, &mut [u8; SECTOR_SIZE]) -> Result<(), Error>,
+{
+    let mut mbr = [0u8; SECTOR_SIZE];
+    read(0, &mut mbr)?;
+
+    let mbr_parts = parse_mbr(&mbr);
+    let mut parts = if mbr_parts
+        .iter()
+        .any(|p| p.ty == PartType::Mbr(MBR_TYPE_GPT))
+    {
+        match parse_gpt(capacity, &mut read) {
+            Ok(parts) => parts,
+            Err(e) => {
+                log!(LogFlags::Error, "Ignoring invalid GPT: {}", e);
+                Vec::new()
+            },
+        }
+    }
+    else {
+        mbr_parts
+    };
+
+    // If no partitions exist just expose the whole disk
+    if parts.is_empty() {
+        log!(LogFlags::DiskDev, "No partitions found, using whole disk");
+        parts.push(Partition::new_whole_disk(capacity));
+    }
+    Ok(parts)
 }
 
-pub fn parse_partitions(mbr: &[u8]) -> Vec<Partition> {
-    let mut parts = Vec::with_capacity(PART_COUNT);
-    let mut src = unsafe { mbr.as_ptr().offset(PART_TABLE_OFFSET) as *const DiskParition };
-    for i in 0..PART_COUNT {
-        unsafe {
+fn read_u16(bytes: &[u8], off: usize) -> u16 {
+    u16::from_le_bytes([bytes[off], bytes[off + 1]])
+}
+
+fn read_u32(bytes: &[u8], off: usize) -> u32 {
+    u32::from_le_bytes(bytes[off..off + 4].try_into().unwrap())
+}
+
+fn read_u64(bytes: &[u8], off: usize) -> u64 {
+    u64::from_le_bytes(bytes[off..off + 8].try_into().unwrap())
+}
+
+fn parse_mbr(mbr: &[u8; SECTOR_SIZE]) -> Vec<Partition> {
+    let mut parts = Vec::with_capacity(MBR_PART_COUNT);
+    for i in 0..MBR_PART_COUNT {
+        // bootable flag, start CHS, system id, end CHS, start LBA, and number of sectors
+        let entry = &mbr[PART_TABLE_OFFSET + i * 16..PART_TABLE_OFFSET + (i + 1) * 16];
+        if entry[4] != 0 {
             parts.push(Partition {
                 id: i,
-                present: (*src).system_id != 0,
-                start: (*src).start,
-                size: (*src).size,
+                start: read_u32(entry, 8) as u64,
+                size: read_u32(entry, 12) as u64,
+                ty: PartType::Mbr(entry[4]),
+                bootable: entry[0] == MBR_BOOTABLE,
+                name: [0; GPT_NAME_LEN],
             });
-            src = src.add(1);
         }
     }
     parts
 }
+
+fn parse_gpt<F>(capacity: u64, read: &mut F) -> Result<Vec<Partition>, Error>
+where
+    F: FnMut(u64, &mut [u8; SECTOR_SIZE]) -> Result<(), Error>,
+{
+    let mut hdr = [0u8; SECTOR_SIZE];
+    read(GPT_HEADER_LBA, &mut hdr)?;
+    if &hdr[0..8] != GPT_SIGNATURE {
+        return Err(Error::new(Code::InvArgs));
+    }
+
+    // the checksum of the header is computed with the checksum field set to zero
+    let hdr_size = read_u32(&hdr, 12) as usize;
+    if !(GPT_HEADER_MIN_SIZE..=SECTOR_SIZE).contains(&hdr_size) {
+        return Err(Error::new(Code::InvArgs));
+    }
+    let hdr_crc = read_u32(&hdr, 16);
+    hdr[16..20].fill(0);
+    if crc32(0, &hdr[0..hdr_size]) != hdr_crc {
+        return Err(Error::new(Code::InvChecksum));
+    }
+
+    let entries_lba = read_u64(&hdr, 72);
+    let entry_count = read_u32(&hdr, 80) as usize;
+    let entry_size = read_u32(&hdr, 84) as usize;
+    let entries_crc = read_u32(&hdr, 88);
+    if entry_size < GPT_ENTRY_MIN_SIZE
+        || SECTOR_SIZE % entry_size != 0
+        || entry_count > GPT_MAX_ENTRIES
+    {
+        return Err(Error::new(Code::InvArgs));
+    }
+
+    // read all entries to check the checksum, but only use the first ones
+    let mut parts = Vec::new();
+    let mut crc = 0;
+    let per_sector = SECTOR_SIZE / entry_size;
+    let mut sector = [0u8; SECTOR_SIZE];
+    for i in 0..entry_count {
+        if i % per_sector == 0 {
+            read(entries_lba + (i / per_sector) as u64, &mut sector)?;
+        }
+
+        let off = (i % per_sector) * entry_size;
+        let entry = &sector[off..off + entry_size];
+        crc = crc32(crc, entry);
+
+        let ty: [u8; 16] = entry[0..16].try_into().unwrap();
+        if ty.iter().all(|b| *b == 0) {
+            continue;
+        }
+
+        let first = read_u64(entry, 32);
+        let last = read_u64(entry, 40);
+        if i >= PART_COUNT || last < first || last >= capacity {
+            log!(
+                LogFlags::Error,
+                "Ignoring GPT partition {} with sectors {}..{}",
+                i,
+                first,
+                last
+            );
+            continue;
+        }
+
+        let mut name = [0u8; GPT_NAME_LEN];
+        for (j, c) in name.iter_mut().enumerate() {
+            *c = match read_u16(entry, 56 + j * 2) {
+                0 => break,
+                c if c < 0x80 => c as u8,
+                _ => b'?',
+            };
+        }
+
+        parts.push(Partition {
+            id: i,
+            start: first,
+            size: last - first + 1,
+            ty: PartType::Gpt(ty),
+            bootable: (read_u64(entry, 48) & GPT_ATTR_BOOTABLE) != 0,
+            name,
+        });
+    }
+
+    if crc != entries_crc {
+        return Err(Error::new(Code::InvChecksum));
+    }
+    Ok(parts)
+}
+
+/// Computes the CRC-32 (as used by GPT) of `data`, continuing with the checksum `crc`
+fn crc32(crc: u32, data: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for b in data {
+        crc ^= *b as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+#[cfg(feature = "tests")]
+pub mod tests {
+    use super::*;
+
+    use m3::test::WvTester;
+    use m3::{vec, wv_assert, wv_assert_eq, wv_assert_err, wv_assert_ok, wv_run_test};
+
+    type Sector = [u8; SECTOR_SIZE];
+
+    const DISK_SECTORS: u64 = 64;
+    const GPT_ENTRIES: usize = 128;
+    const GPT_ENTRIES_LBA: usize = 2;
+    // the type GUID of Linux file systems (0FC63DAF-8483-4772-8E79-3D69D8477DE4)
+    const LINUX_FS: [u8; 16] = [
+        0xAF, 0x3D, 0xC6, 0x0F, 0x83, 0x84, 0x72, 0x47, 0x8E, 0x79, 0x3D, 0x69, 0xD8, 0x47, 0x7D,
+        0xE4,
+    ];
+
+    pub fn run(t: &mut dyn WvTester) {
+        wv_run_test!(t, checksum);
+        wv_run_test!(t, no_table);
+        wv_run_test!(t, mbr);
+        wv_run_test!(t, gpt);
+        wv_run_test!(t, gpt_large_disk);
+        wv_run_test!(t, gpt_ignored_entries);
+        wv_run_test!(t, gpt_invalid);
+    }
+
+    struct GptEntry {
+        idx: usize,
+        first: u64,
+        last: u64,
+        attrs: u64,
+        name: &'static str,
+    }
+
+    fn read_from(disk: &[Sector]) -> impl FnMut(u64, &mut Sector) -> Result<(), Error> + '_ {
+        |lba, buf| {
+            let sector = disk
+                .get(lba as usize)
+                .ok_or_else(|| Error::new(Code::InvArgs))?;
+            buf.copy_from_slice(sector);
+            Ok(())
+        }
+    }
+
+    fn set_mbr_entry(
+        mbr: &mut Sector,
+        idx: usize,
+        bootable: bool,
+        sysid: u8,
+        start: u32,
+        size: u32,
+    ) {
+        let entry = &mut mbr[PART_TABLE_OFFSET + idx * 16..PART_TABLE_OFFSET + (idx + 1) * 16];
+        entry[0] = if bootable { MBR_BOOTABLE } else { 0 };
+        entry[4] = sysid;
+        entry[8..12].copy_from_slice(&start.to_le_bytes());
+        entry[12..16].copy_from_slice(&size.to_le_bytes());
+    }
+
+    /// Recomputes the checksums of the GPT header and entries
+    fn seal_gpt(disk: &mut [Sector]) {
+        let count = read_u32(&disk[1], 80) as usize;
+        let size = read_u32(&disk[1], 84) as usize;
+        let mut entries = Vec::new();
+        for s in &disk[GPT_ENTRIES_LBA..] {
+            entries.extend_from_slice(s);
+        }
+        let crc = crc32(0, &entries[0..(count * size).min(entries.len())]);
+        disk[1][88..92].copy_from_slice(&crc.to_le_bytes());
+
+        disk[1][16..20].fill(0);
+        let crc = crc32(0, &disk[1][0..GPT_HEADER_MIN_SIZE]);
+        disk[1][16..20].copy_from_slice(&crc.to_le_bytes());
+    }
+
+    fn gpt_disk(parts: &[GptEntry]) -> Vec<Sector> {
+        let mut disk = vec![[0u8; SECTOR_SIZE]; DISK_SECTORS as usize];
+        set_mbr_entry(
+            &mut disk[0],
+            0,
+            false,
+            MBR_TYPE_GPT,
+            1,
+            DISK_SECTORS as u32 - 1,
+        );
+
+        let hdr = &mut disk[1];
+        hdr[0..8].copy_from_slice(GPT_SIGNATURE);
+        hdr[8..12].copy_from_slice(&0x0001_0000u32.to_le_bytes());
+        hdr[12..16].copy_from_slice(&(GPT_HEADER_MIN_SIZE as u32).to_le_bytes());
+        hdr[24..32].copy_from_slice(&GPT_HEADER_LBA.to_le_bytes());
+        hdr[32..40].copy_from_slice(&(DISK_SECTORS - 1).to_le_bytes());
+        hdr[72..80].copy_from_slice(&(GPT_ENTRIES_LBA as u64).to_le_bytes());
+        hdr[80..84].copy_from_slice(&(GPT_ENTRIES as u32).to_le_bytes());
+        hdr[84..88].copy_from_slice(&(GPT_ENTRY_MIN_SIZE as u32).to_le_bytes());
+
+        let per_sector = SECTOR_SIZE / GPT_ENTRY_MIN_SIZE;
+        for p in parts {
+            let off = (p.idx % per_sector) * GPT_ENTRY_MIN_SIZE;
+            let entry = &mut disk[GPT_ENTRIES_LBA + p.idx / per_sector][off..];
+            entry[0..16].copy_from_slice(&LINUX_FS);
+            entry[32..40].copy_from_slice(&p.first.to_le_bytes());
+            entry[40..48].copy_from_slice(&p.last.to_le_bytes());
+            entry[48..56].copy_from_slice(&p.attrs.to_le_bytes());
+            for (i, c) in p.name.encode_utf16().enumerate() {
+                entry[56 + i * 2..58 + i * 2].copy_from_slice(&c.to_le_bytes());
+            }
+        }
+
+        seal_gpt(&mut disk);
+        disk
+    }
+
+    fn checksum(t: &mut dyn WvTester) {
+        wv_assert_eq!(t, crc32(0, b""), 0);
+        wv_assert_eq!(t, crc32(0, b"123456789"), 0xCBF4_3926);
+        // the checksum can be computed piecewise
+        wv_assert_eq!(t, crc32(crc32(0, b"1234"), b"56789"), 0xCBF4_3926);
+    }
+
+    fn no_table(t: &mut dyn WvTester) {
+        let disk = vec![[0u8; SECTOR_SIZE]; 1];
+        let parts = wv_assert_ok!(read_partitions(DISK_SECTORS, read_from(&disk)));
+        wv_assert_eq!(t, parts.len(), 1);
+        wv_assert_eq!(t, parts[0].id(), 0);
+        wv_assert_eq!(t, parts[0].start_sector(), 0);
+        wv_assert_eq!(t, parts[0].sector_count(), DISK_SECTORS);
+        wv_assert!(t, parts[0].ty() == PartType::Whole);
+        wv_assert_eq!(t, type_name(parts[0].ty()), "whole");
+
+        // the whole-disk partition is not limited to 32-bit sector numbers
+        let parts = wv_assert_ok!(read_partitions(1 << 40, read_from(&disk)));
+        wv_assert_eq!(t, parts[0].sector_count(), 1 << 40);
+
+        // errors of the device are passed on
+        wv_assert_err!(
+            t,
+            read_partitions(DISK_SECTORS, read_from(&[])).map(|_| ()),
+            Code::InvArgs
+        );
+    }
+
+    fn mbr(t: &mut dyn WvTester) {
+        let mut disk = vec![[0u8; SECTOR_SIZE]; 1];
+        set_mbr_entry(&mut disk[0], 0, false, 0x83, 8, 16);
+        set_mbr_entry(&mut disk[0], 2, true, 0x0C, 24, 40);
+
+        let parts = wv_assert_ok!(read_partitions(DISK_SECTORS, read_from(&disk)));
+        wv_assert_eq!(t, parts.len(), 2);
+
+        wv_assert_eq!(t, parts[0].id(), 0);
+        wv_assert_eq!(t, parts[0].start_sector(), 8);
+        wv_assert_eq!(t, parts[0].size(), 16 * SECTOR_SIZE);
+        wv_assert_eq!(t, type_name(parts[0].ty()), "mbr:0x83");
+        wv_assert!(t, !parts[0].bootable());
+        wv_assert_eq!(t, parts[0].name(), "");
+
+        // unused entries are skipped, but the partitions keep their position in the table
+        wv_assert_eq!(t, parts[1].id(), 2);
+        wv_assert_eq!(t, parts[1].start_sector(), 24);
+        wv_assert_eq!(t, parts[1].sector_count(), 40);
+        wv_assert_eq!(t, type_name(parts[1].ty()), "mbr:0x0c");
+        wv_assert!(t, parts[1].bootable());
+    }
+
+    fn gpt(t: &mut dyn WvTester) {
+        let disk = gpt_disk(&[
+            GptEntry {
+                idx: 0,
+                first: 34,
+                last: 47,
+                attrs: GPT_ATTR_BOOTABLE,
+                name: "root",
+            },
+            GptEntry {
+                idx: 2,
+                first: 48,
+                last: 63,
+                attrs: 0,
+                name: "d\u{e4}ta",
+            },
+        ]);
+
+        let parts = wv_assert_ok!(read_partitions(DISK_SECTORS, read_from(&disk)));
+        wv_assert_eq!(t, parts.len(), 2);
+
+        // the protective MBR entry is not used as a partition
+        wv_assert_eq!(t, parts[0].id(), 0);
+        wv_assert_eq!(t, parts[0].start_sector(), 34);
+        wv_assert_eq!(t, parts[0].sector_count(), 14);
+        wv_assert!(t, parts[0].ty() == PartType::Gpt(LINUX_FS));
+        wv_assert_eq!(
+            t,
+            type_name(parts[0].ty()),
+            "0FC63DAF-8483-4772-8E79-3D69D8477DE4"
+        );
+        wv_assert_eq!(t, parts[0].name(), "root");
+        wv_assert!(t, parts[0].bootable());
+
+        // non-ASCII characters are replaced in the name
+        wv_assert_eq!(t, parts[1].id(), 2);
+        wv_assert_eq!(t, parts[1].start_sector(), 48);
+        wv_assert_eq!(t, parts[1].sector_count(), 16);
+        wv_assert_eq!(t, parts[1].name(), "d?ta");
+        wv_assert!(t, !parts[1].bootable());
+    }
+
+    fn gpt_large_disk(t: &mut dyn WvTester) {
+        let disk = gpt_disk(&[GptEntry {
+            idx: 0,
+            first: 1 << 32,
+            last: (1 << 33) - 1,
+            attrs: 0,
+            name: "big",
+        }]);
+
+        // partitions beyond 32-bit sector numbers are supported
+        let parts = wv_assert_ok!(read_partitions(1 << 34, read_from(&disk)));
+        wv_assert_eq!(t, parts.len(), 1);
+        wv_assert_eq!(t, parts[0].start_sector(), 1 << 32);
+        wv_assert_eq!(t, parts[0].sector_count(), 1 << 32);
+        wv_assert_eq!(t, parts[0].name(), "big");
+    }
+
+    fn gpt_ignored_entries(t: &mut dyn WvTester) {
+        let disk = gpt_disk(&[
+            // beyond the end of the disk
+            GptEntry {
+                idx: 0,
+                first: 34,
+                last: DISK_SECTORS,
+                attrs: 0,
+                name: "",
+            },
+            // last sector before the first
+            GptEntry {
+                idx: 1,
+                first: 40,
+                last: 39,
+                attrs: 0,
+                name: "",
+            },
+            GptEntry {
+                idx: 3,
+                first: 34,
+                last: 63,
+                attrs: 0,
+                name: "ok",
+            },
+            // only the first PART_COUNT entries are used
+            GptEntry {
+                idx: PART_COUNT,
+                first: 34,
+                last: 63,
+                attrs: 0,
+                name: "",
+            },
+        ]);
+
+        let parts = wv_assert_ok!(read_partitions(DISK_SECTORS, read_from(&disk)));
+        wv_assert_eq!(t, parts.len(), 1);
+        wv_assert_eq!(t, parts[0].id(), 3);
+        wv_assert_eq!(t, parts[0].name(), "ok");
+    }
+
+    fn gpt_invalid(t: &mut dyn WvTester) {
+        let good = gpt_disk(&[GptEntry {
+            idx: 0,
+            first: 34,
+            last: 63,
+            attrs: 0,
+            name: "",
+        }]);
+        wv_assert_ok!(parse_gpt(DISK_SECTORS, &mut read_from(&good)));
+
+        let mut disk = good.clone();
+        disk[1][0] = b'X';
+        wv_assert_err!(
+            t,
+            parse_gpt(DISK_SECTORS, &mut read_from(&disk)).map(|_| ()),
+            Code::InvArgs
+        );
+
+        let mut disk = good.clone();
+        disk[1][72] = 3;
+        wv_assert_err!(
+            t,
+            parse_gpt(DISK_SECTORS, &mut read_from(&disk)).map(|_| ()),
+            Code::InvChecksum
+        );
+
+        let mut disk = good.clone();
+        disk[GPT_ENTRIES_LBA][33] ^= 1;
+        wv_assert_err!(
+            t,
+            parse_gpt(DISK_SECTORS, &mut read_from(&disk)).map(|_| ()),
+            Code::InvChecksum
+        );
+
+        // an entry size that does not divide the sector size
+        let mut disk = good.clone();
+        disk[1][84..88].copy_from_slice(&192u32.to_le_bytes());
+        seal_gpt(&mut disk);
+        wv_assert_err!(
+            t,
+            parse_gpt(DISK_SECTORS, &mut read_from(&disk)).map(|_| ()),
+            Code::InvArgs
+        );
+
+        let mut disk = good.clone();
+        disk[1][80..84].copy_from_slice(&(GPT_MAX_ENTRIES as u32 + 1).to_le_bytes());
+        seal_gpt(&mut disk);
+        wv_assert_err!(
+            t,
+            parse_gpt(DISK_SECTORS, &mut read_from(&disk)).map(|_| ()),
+            Code::InvArgs
+        );
+
+        // an invalid GPT is ignored and the whole disk is used instead
+        let parts = wv_assert_ok!(read_partitions(DISK_SECTORS, read_from(&disk)));
+        wv_assert_eq!(t, parts.len(), 1);
+        wv_assert!(t, parts[0].ty() == PartType::Whole);
+    }
+}
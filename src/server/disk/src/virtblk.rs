@@ -0,0 +1,324 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use m3::cell::StaticRefCell;
+use m3::col::Vec;
+use m3::com::MemGate;
+use m3::errors::{Code, Error};
+use m3::io::LogFlags;
+use m3::kif::TileISA;
+use m3::log;
+use m3::mem::GlobOff;
+use m3::tiles::OwnActivity;
+use m3::time::TimeDuration;
+
+use virtio::{Buffer, DeviceType, VirtQueue};
+
+use crate::backend::BlockDevice;
+use crate::partition::{read_partitions, Partition, PART_COUNT};
+
+/// The maximum number of requests that we issue concurrently
+const QUEUE_DEPTH: usize = 8;
+/// The size of the transfer buffer of each request
+const SLOT_BUF_SIZE: usize = 0x10000;
+
+const SECTOR_SIZE: usize = 512;
+
+// the device is read-only
+const FEATURE_RO: u32 = 1 << 5;
+
+const REQ_IN: u32 = 0;
+const REQ_OUT: u32 = 1;
+const STATUS_OK: u8 = 0;
+
+// each slot has a header and a status byte in front of the transfer buffers
+const SLOT_HDR_SIZE: usize = 64;
+const SLOT_STATUS_OFF: usize = 16;
+
+const XFER_TIMEOUT: TimeDuration = TimeDuration::from_millis(30);
+const XFER_SLEEPTIME: TimeDuration = TimeDuration::from_micros(20);
+
+static BUF: StaticRefCell<[u8; SLOT_BUF_SIZE]> = StaticRefCell::new([0; SLOT_BUF_SIZE]);
+
+/// The request header that is read by the device
+#[repr(C)]
+struct ReqHeader {
+    ty: u32,
+    _reserved: u32,
+    sector: u64,
+}
+
+/// Block device for virtio-blk disks
+///
+/// Like the AHCI driver, requests are distributed among multiple slots that are handed to the
+/// device at once.
+pub struct VirtioBlockDevice {
+    dev: virtio::Device,
+    queue: VirtQueue,
+    use_irq: bool,
+    slots: usize,
+    read_only: bool,
+    hdrs_off: usize,
+    bufs_off: usize,
+    devs: [Option<Partition>; PART_COUNT],
+}
+
+impl VirtioBlockDevice {
+    pub fn new(args: Vec<&str>) -> Result<Self, Error> {
+        let use_irq = args.iter().any(|s| *s == "-i");
+
+        let mut dev = virtio::Device::new("virtio-blk", TileISA::IDEDev, DeviceType::Block)?;
+        let features = dev.negotiate(FEATURE_RO)?;
+
+        // layout: queue, slot headers, transfer buffers
+        let qsize = dev.queue_size(0)?;
+        let hdrs_off = VirtQueue::mem_size(qsize);
+        let bufs_off = (hdrs_off + QUEUE_DEPTH * SLOT_HDR_SIZE + 0xFFF) & !0xFFF;
+        dev.alloc_dma(bufs_off + QUEUE_DEPTH * SLOT_BUF_SIZE)?;
+        let queue = dev.setup_queue(0, 0)?;
+        dev.finish_init()?;
+
+        let capacity: u64 = dev.read_config(0)?;
+
+        let mut blk = Self {
+            dev,
+            queue,
+            use_irq,
+            // each request needs three descriptors
+            slots: QUEUE_DEPTH.min(qsize as usize / 3),
+            read_only: (features & FEATURE_RO) != 0,
+            hdrs_off,
+            bufs_off,
+            devs: [None; PART_COUNT],
+        };
+
+        log!(
+            LogFlags::DiskCtrl,
+            "virtio-blk: {} MiB, read-only={}, queue size {}",
+            (capacity as usize * SECTOR_SIZE) / (1024 * 1024),
+            blk.read_only,
+            qsize
+        );
+
+        let parts = read_partitions(capacity, |lba, buffer| {
+            blk.issue(false, lba, 1)?;
+            blk.dev
+                .dma()
+                .read(&mut buffer[..], blk.buf_addr(0) as GlobOff)
+        })?;
+        for p in parts {
+            log!(LogFlags::DiskCtrl, "virtio-blk: registered {:?}", p);
+            blk.devs[p.id()] = Some(p);
+        }
+
+        Ok(blk)
+    }
+
+    fn read_write(
+        &mut self,
+        part: usize,
+        write: bool,
+        buf: &MemGate,
+        off: usize,
+        disk_off: usize,
+        bytes: usize,
+    ) -> Result<(), Error> {
+        let part = self.devs[part].unwrap();
+
+        // check arguments
+        let part_size = part.sector_count() as usize * SECTOR_SIZE;
+        if disk_off.checked_add(bytes).is_none() || disk_off + bytes > part_size {
+            log!(
+                LogFlags::DiskChan,
+                "Invalid request: disk_off={}, bytes={}, part-size: {}",
+                disk_off,
+                bytes,
+                part_size
+            );
+            return Err(Error::new(Code::InvArgs));
+        }
+        if write && self.read_only {
+            return Err(Error::new(Code::NoPerm));
+        }
+
+        let lba = part.start_sector() + (disk_off / SECTOR_SIZE) as u64;
+        let sec_count = bytes / SECTOR_SIZE;
+        log!(
+            LogFlags::DiskDev,
+            "virtio-blk: {} for sectors {}..{}",
+            if write { "WRITE" } else { "READ" },
+            lba,
+            lba + sec_count as u64 - 1,
+        );
+
+        let secs_per_slot = SLOT_BUF_SIZE / SECTOR_SIZE;
+        let mut tmp = BUF.borrow_mut();
+        let mut done = 0;
+        while done < sec_count {
+            // distribute the request among all slots and hand them to the device at once
+            let mut slots = 0;
+            let mut pos = done;
+            while pos < sec_count && slots < self.slots {
+                let count = secs_per_slot.min(sec_count - pos);
+                if write {
+                    let bytes = count * SECTOR_SIZE;
+                    buf.read(&mut tmp[0..bytes], (off + pos * SECTOR_SIZE) as GlobOff)?;
+                    self.dev
+                        .dma()
+                        .write(&tmp[0..bytes], self.buf_addr(slots) as GlobOff)?;
+                }
+
+                self.prepare(slots, write, lba + pos as u64, count)?;
+                slots += 1;
+                pos += count;
+            }
+
+            self.dev.notify(&self.queue)?;
+            self.wait(slots, write)?;
+
+            if !write {
+                for slot in 0..slots {
+                    let start = done + slot * secs_per_slot;
+                    let bytes = secs_per_slot.min(sec_count - start) * SECTOR_SIZE;
+                    self.dev
+                        .dma()
+                        .read(&mut tmp[0..bytes], self.buf_addr(slot) as GlobOff)?;
+                    buf.write(&tmp[0..bytes], (off + start * SECTOR_SIZE) as GlobOff)?;
+                }
+            }
+
+            done = pos;
+        }
+
+        Ok(())
+    }
+
+    fn issue(&mut self, write: bool, lba: u64, count: usize) -> Result<(), Error> {
+        self.prepare(0, write, lba, count)?;
+        self.dev.notify(&self.queue)?;
+        self.wait(1, write)
+    }
+
+    fn prepare(&mut self, slot: usize, write: bool, lba: u64, count: usize) -> Result<(), Error> {
+        let hdr = ReqHeader {
+            ty: if write { REQ_OUT } else { REQ_IN },
+            _reserved: 0,
+            sector: lba,
+        };
+        let hdr_addr = (self.hdrs_off + slot * SLOT_HDR_SIZE) as GlobOff;
+        let status_addr = hdr_addr + SLOT_STATUS_OFF as GlobOff;
+        self.dev.dma().write_obj(&hdr, hdr_addr)?;
+        // set the status to something else than OK to detect requests the device did not finish
+        self.dev.dma().write_obj(&0xFFu8, status_addr)?;
+
+        let bufs = [
+            Buffer::new(hdr_addr, core::mem::size_of::<ReqHeader>(), false),
+            Buffer::new(self.buf_addr(slot) as GlobOff, count * SECTOR_SIZE, !write),
+            Buffer::new(status_addr, 1, true),
+        ];
+        self.queue.add(self.dev.dma(), &bufs).map(|_| ())
+    }
+
+    /// Waits until the device has finished `slots` requests and checks their status
+    fn wait(&mut self, slots: usize, write: bool) -> Result<(), Error> {
+        let mut finished = 0;
+        let mut elapsed = TimeDuration::ZERO;
+        while finished < slots {
+            if self.queue.pop_used(self.dev.dma())?.is_some() {
+                finished += 1;
+                continue;
+            }
+
+            if self.use_irq {
+                self.dev.wait_irq()?;
+            }
+            else {
+                if elapsed >= XFER_TIMEOUT {
+                    return Err(Error::new(Code::Timeout));
+                }
+                OwnActivity::sleep_for(XFER_SLEEPTIME)?;
+                elapsed += XFER_SLEEPTIME;
+            }
+        }
+
+        for slot in 0..slots {
+            let addr = self.hdrs_off + slot * SLOT_HDR_SIZE + SLOT_STATUS_OFF;
+            let status: u8 = self.dev.dma().read_obj(addr as GlobOff)?;
+            if status != STATUS_OK {
+                log!(
+                    LogFlags::Error,
+                    "virtio-blk: request in slot {} failed with status {}",
+                    slot,
+                    status
+                );
+                return Err(Error::new(if write {
+                    Code::WriteFailed
+                }
+                else {
+                    Code::ReadFailed
+                }));
+            }
+        }
+        Ok(())
+    }
+
+    fn buf_addr(&self, slot: usize) -> usize {
+        self.bufs_off + slot * SLOT_BUF_SIZE
+    }
+}
+
+impl BlockDevice for VirtioBlockDevice {
+    fn partition_exists(&self, part: usize) -> bool {
+        part < self.devs.len() && self.devs[part].is_some()
+    }
+
+    fn partitions(&self) -> Vec<(usize, Partition)> {
+        self.devs
+            .iter()
+            .enumerate()
+            .filter_map(|(i, d)| d.map(|d| (i, d)))
+            .collect()
+    }
+
+    fn partition_size(&self, part: usize) -> usize {
+        self.devs[part].unwrap().size()
+    }
+
+    fn max_transfer(&self) -> usize {
+        self.slots * SLOT_BUF_SIZE
+    }
+
+    fn read(
+        &mut self,
+        part: usize,
+        buf: &MemGate,
+        buf_off: usize,
+        disk_off: usize,
+        bytes: usize,
+    ) -> Result<(), Error> {
+        self.read_write(part, false, buf, buf_off, disk_off, bytes)
+    }
+
+    fn write(
+        &mut self,
+        part: usize,
+        buf: &MemGate,
+        buf_off: usize,
+        disk_off: usize,
+        bytes: usize,
+    ) -> Result<(), Error> {
+        self.read_write(part, true, buf, buf_off, disk_off, bytes)
+    }
+}
@@ -0,0 +1,113 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use m3::client::{Disk, DiskBlockNo, DiskBlockRange};
+use m3::com::{MemCap, MemGate, Perm};
+use m3::errors::Error;
+use m3::io::LogFlags;
+use m3::log;
+use m3::mem::GlobOff;
+
+pub const SECTOR_SIZE: usize = 512;
+
+// the number of sectors we transfer from the disk at once
+const XFER_SECTORS: usize = 64;
+// the disk service needs room for the PRDT behind the buffer
+const PRDT_SIZE: usize = 8;
+
+/// The storage that contains the FAT image
+pub trait Device {
+    /// Reads `buf.len()` bytes at byte offset `off` into `buf`
+    fn read(&mut self, buf: &mut [u8], off: u64) -> Result<(), Error>;
+}
+
+/// An image in a boot module
+pub struct MemDevice {
+    mem: MemGate,
+}
+
+impl MemDevice {
+    pub fn new(name: &str) -> Result<Self, Error> {
+        Ok(Self {
+            mem: MemGate::new_bind_bootmod(name)?,
+        })
+    }
+}
+
+impl Device for MemDevice {
+    fn read(&mut self, buf: &mut [u8], off: u64) -> Result<(), Error> {
+        log!(
+            LogFlags::FatFSDev,
+            "mem: reading {}b at {:#x}",
+            buf.len(),
+            off
+        );
+        self.mem.read(buf, off as GlobOff)
+    }
+}
+
+/// An image on a partition of the disk service
+pub struct DiskDevice {
+    disk: Disk,
+    buf: MemGate,
+    _disk_buf: MemCap,
+}
+
+impl DiskDevice {
+    pub fn new(name: &str) -> Result<Self, Error> {
+        let disk = Disk::new(name)?;
+
+        let size = (XFER_SECTORS * SECTOR_SIZE + PRDT_SIZE) as GlobOff;
+        let buf = MemGate::new(size, Perm::RW)?;
+        // use a separate MemCap for the disk service, because both have to activate the gate
+        let disk_buf = buf.derive_cap(0, size, Perm::RW)?;
+        disk.delegate_mem(&disk_buf, DiskBlockRange::new(0))?;
+
+        Ok(Self {
+            disk,
+            buf,
+            _disk_buf: disk_buf,
+        })
+    }
+}
+
+impl Device for DiskDevice {
+    fn read(&mut self, buf: &mut [u8], off: u64) -> Result<(), Error> {
+        let mut done = 0;
+        while done < buf.len() {
+            let pos = off + done as u64;
+            let sector = pos / SECTOR_SIZE as u64;
+            let skip = (pos % SECTOR_SIZE as u64) as usize;
+            let count = (skip + buf.len() - done)
+                .div_ceil(SECTOR_SIZE)
+                .min(XFER_SECTORS);
+
+            log!(
+                LogFlags::FatFSDev,
+                "disk: reading sectors {}..{}",
+                sector,
+                sector + count as u64 - 1
+            );
+            let range = DiskBlockRange::new_range(sector as DiskBlockNo, count as DiskBlockNo);
+            self.disk.read(0, range, SECTOR_SIZE, None)?;
+
+            let amount = (count * SECTOR_SIZE - skip).min(buf.len() - done);
+            self.buf
+                .read(&mut buf[done..done + amount], skip as GlobOff)?;
+            done += amount;
+        }
+        Ok(())
+    }
+}
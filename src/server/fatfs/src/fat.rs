@@ -0,0 +1,524 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use m3::boxed::Box;
+use m3::col::{String, Vec};
+use m3::errors::{Code, Error};
+use m3::io::LogFlags;
+use m3::log;
+use m3::vec;
+use m3::vfs::{FileInfo, FileMode, INodeId};
+
+use crate::device::{Device, SECTOR_SIZE};
+
+/// The inode number we use for the root directory, which has no directory entry
+pub const ROOT_INODE: INodeId = 1;
+
+const DIR_ENTRY_SIZE: usize = 32;
+
+const ATTR_VOLUME_ID: u8 = 0x08;
+const ATTR_DIR: u8 = 0x10;
+const ATTR_LFN: u8 = 0x0F;
+
+// flags in the reserved byte of short entries (used by Windows NT for all-lowercase names)
+const NT_LOWER_BASE: u8 = 0x08;
+const NT_LOWER_EXT: u8 = 0x10;
+
+const LFN_LAST: u8 = 0x40;
+const LFN_CHARS: usize = 13;
+// the offsets of the UCS-2 characters within a long file name entry
+const LFN_OFFSETS: [usize; LFN_CHARS] = [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+
+const ENTRY_END: u8 = 0x00;
+const ENTRY_FREE: u8 = 0xE5;
+// a name starting with 0xE5 is stored as 0x05, since 0xE5 marks free entries
+const ENTRY_KANJI: u8 = 0x05;
+
+const CLUSTER_MASK: u32 = 0x0FFF_FFFF;
+const CLUSTER_EOC: u32 = 0x0FFF_FFF8;
+const CLUSTER_BAD: u32 = 0x0FFF_FFF7;
+
+const PART_TYPES_FAT32: [u8; 2] = [0x0B, 0x0C];
+
+fn le16(buf: &[u8], off: usize) -> u16 {
+    u16::from_le_bytes([buf[off], buf[off + 1]])
+}
+
+fn le32(buf: &[u8], off: usize) -> u32 {
+    u32::from_le_bytes([buf[off], buf[off + 1], buf[off + 2], buf[off + 3]])
+}
+
+/// Converts a FAT date and time (local time, 2-second resolution) to seconds since the Unix epoch
+fn to_unix_time(date: u16, time: u16) -> u32 {
+    if date == 0 {
+        return 0;
+    }
+
+    let year = 1980 + (date >> 9) as i64;
+    let month = ((date >> 5) & 0xF).clamp(1, 12) as i64;
+    let day = (date & 0x1F).max(1) as i64;
+
+    // days since the epoch for a date in the proleptic Gregorian calendar
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y / 400;
+    let yoe = y - era * 400;
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+
+    let secs =
+        (time >> 11) as i64 * 3600 + ((time >> 5) & 0x3F) as i64 * 60 + (time & 0x1F) as i64 * 2;
+    (days * 86400 + secs) as u32
+}
+
+fn lfn_checksum(short: &[u8]) -> u8 {
+    short[0..11].iter().fold(0u8, |sum, b| {
+        (sum >> 1).wrapping_add(sum << 7).wrapping_add(*b)
+    })
+}
+
+fn short_name(entry: &[u8]) -> String {
+    let nt = entry[12];
+    let mut base = entry[0..8].to_vec();
+    if base[0] == ENTRY_KANJI {
+        base[0] = ENTRY_FREE;
+    }
+
+    let conv = |bytes: &[u8], lower: bool| -> String {
+        let end = bytes
+            .iter()
+            .rposition(|b| *b != b' ')
+            .map(|p| p + 1)
+            .unwrap_or(0);
+        bytes[0..end]
+            .iter()
+            .map(|b| {
+                // we treat everything beyond ASCII as Latin-1, because we don't know the code page
+                let c = *b as char;
+                if lower {
+                    c.to_ascii_lowercase()
+                }
+                else {
+                    c
+                }
+            })
+            .collect()
+    };
+
+    let mut name = conv(&base, nt & NT_LOWER_BASE != 0);
+    let ext = conv(&entry[8..11], nt & NT_LOWER_EXT != 0);
+    if !ext.is_empty() {
+        name.push('.');
+        name.push_str(&ext);
+    }
+    name
+}
+
+/// Collects the parts of a long file name that precede a short entry
+#[derive(Default)]
+struct LongName {
+    chars: Vec<u16>,
+    checksum: u8,
+    // the ordinal of the entry we expect next (they are stored in reverse order)
+    next: u8,
+    valid: bool,
+}
+
+impl LongName {
+    fn add(&mut self, entry: &[u8]) {
+        let ord = entry[0] & 0x1F;
+        let checksum = entry[13];
+        if entry[0] & LFN_LAST != 0 {
+            self.chars = vec![0xFFFF; ord as usize * LFN_CHARS];
+            self.checksum = checksum;
+            self.next = ord;
+            self.valid = ord > 0;
+        }
+
+        if !self.valid || ord != self.next || checksum != self.checksum {
+            self.valid = false;
+            return;
+        }
+
+        let start = (ord as usize - 1) * LFN_CHARS;
+        for (i, off) in LFN_OFFSETS.iter().enumerate() {
+            self.chars[start + i] = le16(entry, *off);
+        }
+        self.next -= 1;
+    }
+
+    fn take(&mut self, short: &[u8]) -> Option<String> {
+        let valid = self.valid && self.next == 0 && self.checksum == lfn_checksum(short);
+        self.valid = false;
+        if !valid {
+            return None;
+        }
+
+        let end = self
+            .chars
+            .iter()
+            .position(|c| *c == 0x0000 || *c == 0xFFFF)
+            .unwrap_or(self.chars.len());
+        Some(
+            char::decode_utf16(self.chars[0..end].iter().copied())
+                .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                .collect(),
+        )
+    }
+}
+
+/// A file or directory on the volume
+#[derive(Clone, Debug)]
+pub struct Node {
+    /// The position of the directory entry in units of entries
+    pub inode: INodeId,
+    /// The inode of the directory that contains this node
+    pub parent: INodeId,
+    pub attr: u8,
+    pub cluster: u32,
+    pub size: usize,
+    pub created: u32,
+    pub accessed: u32,
+    pub modified: u32,
+}
+
+impl Node {
+    fn root(cluster: u32) -> Self {
+        Self {
+            inode: ROOT_INODE,
+            parent: ROOT_INODE,
+            attr: ATTR_DIR,
+            cluster,
+            size: 0,
+            created: 0,
+            accessed: 0,
+            modified: 0,
+        }
+    }
+
+    pub fn is_dir(&self) -> bool {
+        (self.attr & ATTR_DIR) != 0
+    }
+}
+
+/// A named entry in a directory
+pub struct Entry {
+    pub name: String,
+    pub node: Node,
+}
+
+/// A position within a cluster chain, which allows to continue sequential reads without walking
+/// the chain from the beginning
+#[derive(Clone, Copy, Default)]
+pub struct ChainPos {
+    idx: usize,
+    cluster: u32,
+}
+
+/// A FAT32 volume
+pub struct Volume {
+    dev: Box<dyn Device>,
+    // the byte offset of the volume on the device
+    base: u64,
+    bytes_per_sec: usize,
+    cluster_size: usize,
+    fat_start: u64,
+    data_start: u64,
+    clusters: u32,
+    root: u32,
+    // the most recently used sector of the FAT
+    fat_sec: Option<u64>,
+    fat_buf: Vec<u8>,
+}
+
+impl Volume {
+    pub fn new(mut dev: Box<dyn Device>) -> Result<Self, Error> {
+        let mut sec = [0u8; SECTOR_SIZE];
+        dev.read(&mut sec, 0)?;
+        if le16(&sec, 510) != 0xAA55 {
+            log!(LogFlags::Error, "fatfs: no boot sector signature found");
+            return Err(Error::new(Code::InvArgs));
+        }
+
+        // without a valid BPB, we expect a partition table and use the first FAT32 partition
+        let mut base = 0;
+        if !Self::valid_bpb(&sec) {
+            let part = (0..4)
+                .map(|i| &sec[446 + i * 16..446 + (i + 1) * 16])
+                .find(|p| PART_TYPES_FAT32.contains(&p[4]))
+                .ok_or_else(|| {
+                    log!(LogFlags::Error, "fatfs: no FAT32 partition found");
+                    Error::new(Code::InvArgs)
+                })?;
+            base = le32(part, 8) as u64 * SECTOR_SIZE as u64;
+            dev.read(&mut sec, base)?;
+            if !Self::valid_bpb(&sec) {
+                log!(
+                    LogFlags::Error,
+                    "fatfs: partition contains no FAT32 file system"
+                );
+                return Err(Error::new(Code::InvArgs));
+            }
+        }
+
+        let bytes_per_sec = le16(&sec, 11) as usize;
+        let sec_per_clus = sec[13] as usize;
+        let reserved = le16(&sec, 14) as u64;
+        let fats = sec[16] as u64;
+        let total = le32(&sec, 32) as u64;
+        let fat_size = le32(&sec, 36) as u64;
+        let root = le32(&sec, 44);
+
+        let bps = bytes_per_sec as u64;
+        let fat_start = reserved * bps;
+        let data_start = (reserved + fats * fat_size) * bps;
+        let clusters =
+            ((total * bps).saturating_sub(data_start) / (sec_per_clus as u64 * bps)) as u32;
+
+        log!(
+            LogFlags::FatFSReqs,
+            "fatfs: volume at {:#x}: {} clusters of {}b, root cluster {}",
+            base,
+            clusters,
+            bytes_per_sec * sec_per_clus,
+            root
+        );
+
+        Ok(Self {
+            dev,
+            base,
+            bytes_per_sec,
+            cluster_size: bytes_per_sec * sec_per_clus,
+            fat_start,
+            data_start,
+            clusters,
+            root,
+            fat_sec: None,
+            fat_buf: vec![0u8; bytes_per_sec],
+        })
+    }
+
+    fn valid_bpb(sec: &[u8]) -> bool {
+        let bps = le16(sec, 11);
+        let spc = sec[13];
+        // FAT32 has no fixed root directory and stores the FAT size in the extended BPB
+        matches!(bps, 512 | 1024 | 2048 | 4096)
+            && spc.is_power_of_two()
+            && sec[16] > 0
+            && le16(sec, 17) == 0
+            && le16(sec, 22) == 0
+            && le32(sec, 36) != 0
+    }
+
+    pub fn root(&self) -> Node {
+        Node::root(self.root)
+    }
+
+    fn cluster_off(&self, cluster: u32) -> u64 {
+        self.base + self.data_start + (cluster as u64 - 2) * self.cluster_size as u64
+    }
+
+    fn valid_cluster(&self, cluster: u32) -> bool {
+        cluster >= 2 && cluster < self.clusters + 2
+    }
+
+    /// Returns the cluster following `cluster` in its chain or `None` at the end of the chain
+    fn next_cluster(&mut self, cluster: u32) -> Result<Option<u32>, Error> {
+        let off = self.fat_start + cluster as u64 * 4;
+        let sec = off / self.bytes_per_sec as u64;
+        if self.fat_sec != Some(sec) {
+            let pos = self.base + sec * self.bytes_per_sec as u64;
+            self.dev.read(&mut self.fat_buf, pos)?;
+            self.fat_sec = Some(sec);
+        }
+
+        let next = le32(&self.fat_buf, (off % self.bytes_per_sec as u64) as usize) & CLUSTER_MASK;
+        match next {
+            n if n >= CLUSTER_EOC => Ok(None),
+            n if n == CLUSTER_BAD || !self.valid_cluster(n) => {
+                log!(
+                    LogFlags::Error,
+                    "fatfs: invalid cluster {} after {}",
+                    n,
+                    cluster
+                );
+                Err(Error::new(Code::InvState))
+            },
+            n => Ok(Some(n)),
+        }
+    }
+
+    /// Reads the file `node` at position `pos` into `buf` and returns the number of read bytes
+    ///
+    /// The cluster chain is walked starting at `chain` if possible, which is updated afterwards.
+    pub fn read(
+        &mut self,
+        node: &Node,
+        chain: &mut ChainPos,
+        pos: usize,
+        buf: &mut [u8],
+    ) -> Result<usize, Error> {
+        let total = buf.len().min(node.size.saturating_sub(pos));
+        if total == 0 || !self.valid_cluster(node.cluster) {
+            return Ok(0);
+        }
+
+        let idx = pos / self.cluster_size;
+        if chain.cluster == 0 || chain.idx > idx {
+            *chain = ChainPos {
+                idx: 0,
+                cluster: node.cluster,
+            };
+        }
+
+        let mut done = 0;
+        while done < total {
+            while chain.idx < (pos + done) / self.cluster_size {
+                chain.cluster = self
+                    .next_cluster(chain.cluster)?
+                    .ok_or_else(|| Error::new(Code::InvState))?;
+                chain.idx += 1;
+            }
+
+            let off = (pos + done) % self.cluster_size;
+            let amount = (self.cluster_size - off).min(total - done);
+            let dev_off = self.cluster_off(chain.cluster) + off as u64;
+            self.dev.read(&mut buf[done..done + amount], dev_off)?;
+            done += amount;
+        }
+
+        Ok(done)
+    }
+
+    /// Reads all entries of the directory `dir`, except for "." and ".."
+    pub fn read_dir(&mut self, dir: &Node) -> Result<Vec<Entry>, Error> {
+        let mut entries = Vec::new();
+        let mut lfn = LongName::default();
+        let mut buf = vec![0u8; self.cluster_size];
+
+        let mut cluster = Some(dir.cluster);
+        // guard against cycles in corrupt chains
+        let mut remaining = self.clusters;
+        while let Some(cur) = cluster {
+            if !self.valid_cluster(cur) || remaining == 0 {
+                return Err(Error::new(Code::InvState));
+            }
+            remaining -= 1;
+
+            let cur_off = self.cluster_off(cur);
+            self.dev.read(&mut buf, cur_off)?;
+
+            for (i, e) in buf.chunks_exact(DIR_ENTRY_SIZE).enumerate() {
+                match (e[0], e[11]) {
+                    (ENTRY_END, _) => return Ok(entries),
+                    (ENTRY_FREE, _) => lfn.valid = false,
+                    (_, attr) if (attr & 0x3F) == ATTR_LFN => lfn.add(e),
+                    (_, attr) if (attr & ATTR_VOLUME_ID) != 0 => lfn.valid = false,
+                    (b'.', _) if e[1..11].iter().all(|b| *b == b' ' || *b == b'.') => {
+                        lfn.valid = false
+                    },
+                    (_, attr) => {
+                        let name = lfn.take(e).unwrap_or_else(|| short_name(e));
+                        let pos = cur_off - self.base + (i * DIR_ENTRY_SIZE) as u64;
+                        let cluster = ((le16(e, 20) as u32) << 16) | le16(e, 26) as u32;
+                        entries.push(Entry {
+                            name,
+                            node: Node {
+                                inode: (pos / DIR_ENTRY_SIZE as u64) as INodeId,
+                                parent: dir.inode,
+                                attr,
+                                cluster,
+                                size: if (attr & ATTR_DIR) != 0 {
+                                    0
+                                }
+                                else {
+                                    le32(e, 28) as usize
+                                },
+                                created: to_unix_time(le16(e, 16), le16(e, 14)),
+                                accessed: to_unix_time(le16(e, 18), 0),
+                                modified: to_unix_time(le16(e, 24), le16(e, 22)),
+                            },
+                        });
+                    },
+                }
+            }
+
+            cluster = self.next_cluster(cur)?;
+        }
+
+        Ok(entries)
+    }
+
+    /// Looks up the node with given name in directory `dir`, ignoring the case of ASCII letters
+    pub fn find(&mut self, dir: &Node, name: &str) -> Result<Node, Error> {
+        if !dir.is_dir() {
+            return Err(Error::new(Code::IsNoDir));
+        }
+
+        self.read_dir(dir)?
+            .into_iter()
+            .find(|e| e.name.eq_ignore_ascii_case(name))
+            .map(|e| e.node)
+            .ok_or_else(|| Error::new(Code::NoSuchFile))
+    }
+
+    /// Resolves the given absolute path to a node
+    pub fn lookup(&mut self, path: &str) -> Result<Node, Error> {
+        let mut nodes = vec![self.root()];
+        for comp in path.split('/') {
+            match comp {
+                "" | "." => {},
+                ".." => {
+                    if nodes.len() > 1 {
+                        nodes.pop();
+                    }
+                },
+                name => {
+                    let next = self.find(nodes.last().unwrap(), name)?;
+                    nodes.push(next);
+                },
+            }
+        }
+        Ok(nodes.pop().unwrap())
+    }
+
+    pub fn stat(&self, node: &Node) -> FileInfo {
+        // we only support reading so far
+        let mode = if node.is_dir() {
+            FileMode::IFDIR | FileMode::from_bits_truncate(0o555)
+        }
+        else {
+            FileMode::IFREG | FileMode::from_bits_truncate(0o444)
+        };
+
+        FileInfo {
+            devno: 0,
+            inode: node.inode,
+            mode,
+            links: 1,
+            uid: 0,
+            gid: 0,
+            size: node.size,
+            lastaccess: node.accessed,
+            lastmod: node.modified,
+            lastchange: node.modified.max(node.created),
+            blocksize: self.cluster_size as u32,
+            blocks: node.size.div_ceil(self.cluster_size) as u32,
+            extents: 0,
+            firstblock: node.cluster,
+        }
+    }
+}
@@ -0,0 +1,485 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! A file system server for FAT32 volumes
+//!
+//! fatfs implements the m3fs protocol on top of a FAT32 volume, which is either stored in a boot
+//! module or on the disk (e.g., an SD card). This allows M3 to consume images that have been
+//! produced by other systems. FAT32 volumes directly at the start of the device and volumes in
+//! the first FAT32 partition of an MBR-partitioned device are supported, including long file
+//! names. So far, fatfs is read-only and does not support memory mappings, notifications, or
+//! pipelined transfers.
+
+#![no_std]
+
+mod device;
+mod fat;
+mod file;
+
+use m3::boxed::Box;
+use m3::build_vmsg;
+use m3::cap::{SelSpace, Selector};
+use m3::cell::{LazyStaticRefCell, RefMut, StaticCell};
+use m3::col::{String, ToString, Treap, Vec};
+use m3::com::{opcodes, GateIStream};
+use m3::env;
+use m3::errors::{Code, Error};
+use m3::format;
+use m3::io::LogFlags;
+use m3::kif::{CapRngDesc, CapType, INVALID_SEL};
+use m3::log;
+use m3::println;
+use m3::reply_vmsg;
+use m3::server::{
+    CapExchange, ClientManager, ExcType, RequestHandler, RequestSession, Server, ServerSession,
+    SessId, DEF_MAX_CLIENTS,
+};
+use m3::tiles::OwnActivity;
+use m3::vfs::OpenFlags;
+
+use device::{Device, DiskDevice, MemDevice};
+use fat::Volume;
+use file::OpenFile;
+
+const MSG_SIZE: usize = 128;
+
+static VOLUME: LazyStaticRefCell<Volume> = LazyStaticRefCell::default();
+static NEXT_PRIV_ID: StaticCell<usize> = StaticCell::new(1);
+
+fn volume_mut() -> RefMut<'static, Volume> {
+    VOLUME.borrow_mut()
+}
+
+struct Meta {
+    priv_eps: Vec<Selector>,
+    priv_files: Treap<usize, OpenFile>,
+}
+
+#[allow(clippy::large_enum_variant)]
+enum SessionData {
+    Meta(Meta),
+    File(OpenFile),
+}
+
+struct FatFSSession {
+    serv: ServerSession,
+    data: SessionData,
+    parent: Option<SessId>,
+    childs: Vec<SessId>,
+}
+
+impl RequestSession for FatFSSession {
+    fn new(serv: ServerSession, _arg: &str) -> Result<Self, Error>
+    where
+        Self: Sized,
+    {
+        log!(LogFlags::FatFSReqs, "[{}] fatfs::open()", serv.id());
+        Ok(Self::new_meta(serv))
+    }
+
+    fn close(&mut self, cli: &mut ClientManager<Self>, sid: SessId, sub_ids: &mut Vec<SessId>)
+    where
+        Self: Sized,
+    {
+        log!(
+            LogFlags::FatFSReqs,
+            "[{}] fatfs::close(): closing {:?}",
+            sid,
+            sub_ids
+        );
+
+        // close child sessions as well
+        sub_ids.extend_from_slice(&self.childs);
+
+        // remove us from parent
+        if let Some(pid) = self.parent.take() {
+            if let Some(p) = cli.get_mut(pid) {
+                p.childs.retain(|cid| *cid != sid);
+            }
+        }
+    }
+
+    fn revoked(&mut self, sel: Selector) {
+        log!(LogFlags::FatFSReqs, "fatfs::revoked(sel={})", sel);
+
+        // keep the indices of the other EPs stable
+        if let SessionData::Meta(m) = &mut self.data {
+            if let Some(ep) = m.priv_eps.iter_mut().find(|ep| **ep == sel) {
+                *ep = INVALID_SEL;
+            }
+        }
+    }
+}
+
+impl FatFSSession {
+    fn new_meta(serv: ServerSession) -> Self {
+        FatFSSession {
+            serv,
+            data: SessionData::Meta(Meta {
+                priv_eps: Vec::new(),
+                priv_files: Treap::new(),
+            }),
+            parent: None,
+            childs: Vec::new(),
+        }
+    }
+
+    fn get_sess(cli: &mut ClientManager<Self>, sid: SessId) -> Result<&mut Self, Error> {
+        cli.get_mut(sid).ok_or_else(|| Error::new(Code::InvArgs))
+    }
+
+    fn meta(&mut self) -> Result<&mut Meta, Error> {
+        match &mut self.data {
+            SessionData::Meta(m) => Ok(m),
+            SessionData::File(_) => Err(Error::new(Code::InvArgs)),
+        }
+    }
+
+    fn with_file<F>(&mut self, is: &mut GateIStream<'_>, func: F) -> Result<(), Error>
+    where
+        F: Fn(&mut OpenFile, &mut GateIStream<'_>) -> Result<(), Error>,
+    {
+        let fid: usize = is.pop()?;
+        match &mut self.data {
+            SessionData::Meta(m) => match m.priv_files.get_mut(&fid) {
+                Some(f) => func(f, is),
+                None => Err(Error::new(Code::InvArgs)),
+            },
+            SessionData::File(f) => func(f, is),
+        }
+    }
+
+    fn new_file(
+        parent: SessId,
+        serv: ServerSession,
+        path: &str,
+        flags: OpenFlags,
+    ) -> Result<Self, Error> {
+        Ok(FatFSSession {
+            data: SessionData::File(OpenFile::new(serv.id(), path, flags)?),
+            serv,
+            parent: Some(parent),
+            childs: Vec::new(),
+        })
+    }
+
+    fn open(
+        cli: &mut ClientManager<Self>,
+        crt: usize,
+        sid: SessId,
+        xchg: &mut CapExchange<'_>,
+    ) -> Result<(), Error> {
+        let flags: OpenFlags = xchg.in_args().pop()?;
+        let path: String = xchg.in_args().pop::<&str>()?.to_string();
+
+        log!(
+            LogFlags::FatFSReqs,
+            "[{}] meta::open(path={}, flags={:?})",
+            sid,
+            path,
+            flags
+        );
+
+        let (sel, _nsid) = cli.add_connected(crt, |cli, serv, _sgate| {
+            let parent = Self::get_sess(cli, sid)?;
+            parent.meta()?;
+
+            let child = Self::new_file(sid, serv, &path, flags)?;
+            parent.childs.push(child.serv.id());
+            Ok(child)
+        })?;
+
+        xchg.out_caps(CapRngDesc::new(CapType::Object, sel, 2));
+        Ok(())
+    }
+
+    fn clone(
+        cli: &mut ClientManager<Self>,
+        crt: usize,
+        sid: SessId,
+        xchg: &mut CapExchange<'_>,
+    ) -> Result<(), Error> {
+        log!(LogFlags::FatFSReqs, "[{}] fatfs::clone(crt={})", sid, crt);
+
+        let (sel, _nsid) = cli.add_connected(crt, |cli, serv, _sgate| {
+            let parent = Self::get_sess(cli, sid)?;
+            match &parent.data {
+                // meta sessions are independent of each other
+                SessionData::Meta(_) => Ok(Self::new_meta(serv)),
+
+                // the clone of a file is a new file at the same path
+                SessionData::File(f) => {
+                    let flags = f.flags() & !(OpenFlags::CREATE | OpenFlags::TRUNC);
+                    let child = Self::new_file(sid, serv, f.path(), flags)?;
+                    parent.childs.push(child.serv.id());
+                    Ok(child)
+                },
+            }
+        })?;
+
+        xchg.out_caps(CapRngDesc::new(CapType::Object, sel, 2));
+        Ok(())
+    }
+
+    fn del_ep(
+        cli: &mut ClientManager<Self>,
+        _crt: usize,
+        sid: SessId,
+        xchg: &mut CapExchange<'_>,
+    ) -> Result<(), Error> {
+        let meta = Self::get_sess(cli, sid)?.meta()?;
+
+        let sel = SelSpace::get().alloc_sel();
+        meta.priv_eps.push(sel);
+        let id = meta.priv_eps.len() - 1;
+        // drop the EP registration as soon as the client revokes the EP
+        xchg.notify_revoke();
+
+        log!(
+            LogFlags::FatFSReqs,
+            "[{}] meta::del_ep(sel={}) -> {}",
+            sid,
+            sel,
+            id
+        );
+
+        xchg.out_caps(CapRngDesc::new(CapType::Object, sel, 1));
+        xchg.out_args().push(id);
+        Ok(())
+    }
+
+    fn set_dest(
+        cli: &mut ClientManager<Self>,
+        _crt: usize,
+        sid: SessId,
+        xchg: &mut CapExchange<'_>,
+    ) -> Result<(), Error> {
+        log!(LogFlags::FatFSReqs, "[{}] file::set_dest()", sid);
+
+        match &mut Self::get_sess(cli, sid)?.data {
+            SessionData::File(f) => {
+                let sel = SelSpace::get().alloc_sel();
+                f.set_dest(sel);
+                xchg.out_caps(CapRngDesc::new(CapType::Object, sel, 1));
+                Ok(())
+            },
+            SessionData::Meta(_) => Err(Error::new(Code::InvArgs)),
+        }
+    }
+
+    fn open_priv(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let path: &str = is.pop()?;
+        let flags = OpenFlags::from_bits_truncate(is.pop::<u32>()?);
+        let ep: usize = is.pop()?;
+
+        log!(
+            LogFlags::FatFSReqs,
+            "[{}] meta::open_priv(path={}, flags={:?}, ep={})",
+            self.serv.id(),
+            path,
+            flags,
+            ep
+        );
+
+        let meta = self.meta()?;
+        let ep_sel = meta
+            .priv_eps
+            .get(ep)
+            .copied()
+            .filter(|sel| *sel != INVALID_SEL)
+            .ok_or_else(|| Error::new(Code::InvArgs))?;
+
+        let id = NEXT_PRIV_ID.get();
+        let mut file = OpenFile::new(id, path, flags)?;
+        file.set_dest(ep_sel);
+        NEXT_PRIV_ID.set(id + 1);
+        meta.priv_files.insert(id, file);
+
+        reply_vmsg!(is, Code::Success, id)
+    }
+
+    fn close_priv(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let id: usize = is.pop()?;
+
+        log!(
+            LogFlags::FatFSReqs,
+            "[{}] meta::close_priv(id={})",
+            self.serv.id(),
+            id
+        );
+
+        match self.meta()?.priv_files.remove(&id) {
+            Some(_) => is.reply_error(Code::Success),
+            None => Err(Error::new(Code::InvArgs)),
+        }
+    }
+
+    fn stat(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let path: &str = is.pop()?;
+
+        log!(
+            LogFlags::FatFSReqs,
+            "[{}] meta::stat(path={})",
+            self.serv.id(),
+            path
+        );
+
+        self.meta()?;
+        let mut vol = volume_mut();
+        let node = vol.lookup(path)?;
+        let info = vol.stat(&node);
+
+        let mut reply = m3::mem::MsgBuf::borrow_def();
+        build_vmsg!(reply, Code::Success, info);
+        is.reply(&reply)
+    }
+
+    fn read_only(&mut self, is: &mut GateIStream<'_>, op: &str) -> Result<(), Error> {
+        let path: &str = is.pop()?;
+
+        log!(
+            LogFlags::FatFSReqs,
+            "[{}] meta::{}(path={}): not supported",
+            self.serv.id(),
+            op,
+            path
+        );
+
+        Err(Error::new(Code::NoPerm))
+    }
+}
+
+struct Settings {
+    name: String,
+    backend: String,
+    module: String,
+    disk: String,
+}
+
+fn usage() -> ! {
+    println!(
+        "Usage: {} [-n <name>] [-m <module>] [-d <disk>] (disk|mem)",
+        env::args().next().unwrap()
+    );
+    println!();
+    println!("  -n: the name of the service (fatfs by default)");
+    println!("  -m: the name of the boot module for the mem backend (fat by default)");
+    println!("  -d: the name of the disk service for the disk backend (disk by default)");
+    println!();
+    println!("Provides the files of the FAT32 volume read-only via the m3fs protocol.");
+    OwnActivity::exit_with(Code::InvArgs);
+}
+
+fn parse_args() -> Result<Settings, String> {
+    let mut name = String::from("fatfs");
+    let mut module = String::from("fat");
+    let mut disk = String::from("disk");
+
+    let args: Vec<&str> = env::args().collect();
+    let mut i = 1;
+    while i + 1 < args.len() {
+        match args[i] {
+            "-n" => name = args[i + 1].to_string(),
+            "-m" => module = args[i + 1].to_string(),
+            "-d" => disk = args[i + 1].to_string(),
+            _ => break,
+        }
+        i += 2;
+    }
+
+    if i + 1 != args.len() {
+        return Err(String::from("Expected exactly one backend"));
+    }
+    let backend = args[i].to_string();
+    if backend != "disk" && backend != "mem" {
+        return Err(format!("Unknown backend {}", backend));
+    }
+
+    Ok(Settings {
+        name,
+        backend,
+        module,
+        disk,
+    })
+}
+
+#[no_mangle]
+pub fn main() -> Result<(), Error> {
+    let settings = parse_args().unwrap_or_else(|e| {
+        println!("Invalid arguments: {}", e);
+        usage();
+    });
+
+    let dev = if settings.backend == "mem" {
+        Box::new(MemDevice::new(&settings.module).expect("Unable to access boot module"))
+            as Box<dyn Device>
+    }
+    else {
+        Box::new(DiskDevice::new(&settings.disk).expect("Unable to connect to disk service"))
+            as Box<dyn Device>
+    };
+    VOLUME.set(Volume::new(dev).expect("Unable to load FAT32 volume"));
+
+    let mut hdl = RequestHandler::new_with(DEF_MAX_CLIENTS, MSG_SIZE, 1)
+        .expect("Unable to create request handler");
+    let mut srv = Server::new(&settings.name, &mut hdl).expect("Unable to create service");
+
+    use opcodes::FileSystem;
+
+    hdl.reg_cap_handler(FileSystem::Open, ExcType::Obt(2), FatFSSession::open);
+    hdl.reg_cap_handler(FileSystem::DelEP, ExcType::Del(1), FatFSSession::del_ep);
+    hdl.reg_cap_handler(FileSystem::CloneFile, ExcType::Obt(2), FatFSSession::clone);
+    hdl.reg_cap_handler(FileSystem::CloneMeta, ExcType::Obt(2), FatFSSession::clone);
+    hdl.reg_cap_handler(FileSystem::SetDest, ExcType::Del(1), FatFSSession::set_dest);
+
+    hdl.reg_msg_handler(FileSystem::NextIn, |sess, is| {
+        sess.with_file(is, |f, is| f.next_in(is))
+    });
+    hdl.reg_msg_handler(FileSystem::Commit, |sess, is| {
+        sess.with_file(is, |f, is| f.commit(is))
+    });
+    hdl.reg_msg_handler(FileSystem::Seek, |sess, is| {
+        sess.with_file(is, |f, is| f.seek(is))
+    });
+    hdl.reg_msg_handler(FileSystem::FStat, |sess, is| {
+        sess.with_file(is, |f, is| f.stat(is))
+    });
+    hdl.reg_msg_handler(FileSystem::GetPath, |sess, is| {
+        sess.with_file(is, |f, is| f.get_path(is))
+    });
+    // there is nothing to write back
+    hdl.reg_msg_handler(FileSystem::Sync, |sess, is| {
+        sess.with_file(is, |_f, is| is.reply_error(Code::Success))
+    });
+    hdl.reg_msg_handler(FileSystem::NextOut, |_sess, _is| {
+        Err(Error::new(Code::NoPerm))
+    });
+    hdl.reg_msg_handler(FileSystem::Truncate, |_sess, _is| {
+        Err(Error::new(Code::NoPerm))
+    });
+    hdl.reg_msg_handler(FileSystem::Stat, FatFSSession::stat);
+    hdl.reg_msg_handler(FileSystem::Mkdir, |sess, is| sess.read_only(is, "mkdir"));
+    hdl.reg_msg_handler(FileSystem::Rmdir, |sess, is| sess.read_only(is, "rmdir"));
+    hdl.reg_msg_handler(FileSystem::Unlink, |sess, is| sess.read_only(is, "unlink"));
+    hdl.reg_msg_handler(FileSystem::Link, |sess, is| sess.read_only(is, "link"));
+    hdl.reg_msg_handler(FileSystem::Rename, |sess, is| sess.read_only(is, "rename"));
+    hdl.reg_msg_handler(FileSystem::Utime, |sess, is| sess.read_only(is, "utime"));
+    hdl.reg_msg_handler(FileSystem::OpenPriv, FatFSSession::open_priv);
+    hdl.reg_msg_handler(FileSystem::ClosePriv, FatFSSession::close_priv);
+
+    hdl.run(&mut srv).expect("Server loop failed");
+
+    Ok(())
+}
@@ -0,0 +1,266 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use m3::build_vmsg;
+use m3::cap::Selector;
+use m3::cell::StaticRefCell;
+use m3::col::{String, ToString, Vec};
+use m3::com::{GateIStream, MemCap, MemGate, EP};
+use m3::errors::{Code, Error};
+use m3::io::LogFlags;
+use m3::kif::Perm;
+use m3::log;
+use m3::mem::GlobOff;
+use m3::reply_vmsg;
+use m3::server::SessId;
+use m3::vfs::{INodeId, OpenFlags, SeekMode};
+
+use crate::fat::{ChainPos, Node, Volume};
+
+/// The size of the buffer that is shared with the client for each file
+pub const BUF_SIZE: usize = 4096;
+
+static TMP_BUF: StaticRefCell<[u8; BUF_SIZE]> = StaticRefCell::new([0u8; BUF_SIZE]);
+
+// the directory entries start with the inode number, the name length, and the distance to the
+// next entry (see `m3::vfs::ReadDir`)
+const DIR_ENTRY_HDR_SIZE: usize = 3 * 4;
+
+fn push_dir_entry(buf: &mut Vec<u8>, inode: INodeId, name: &str) {
+    let next = DIR_ENTRY_HDR_SIZE + name.len();
+    buf.extend_from_slice(&inode.to_ne_bytes());
+    buf.extend_from_slice(&(name.len() as u32).to_ne_bytes());
+    buf.extend_from_slice(&(next as u32).to_ne_bytes());
+    buf.extend_from_slice(name.as_bytes());
+}
+
+fn read_dir(vol: &mut Volume, dir: &Node) -> Result<Vec<u8>, Error> {
+    let mut res = Vec::new();
+    // FAT has no "." and ".." entries in the root directory and their cluster numbers do not
+    // identify our inodes. Therefore, we always produce them ourself.
+    push_dir_entry(&mut res, dir.inode, ".");
+    push_dir_entry(&mut res, dir.parent, "..");
+    for e in vol.read_dir(dir)? {
+        push_dir_entry(&mut res, e.node.inode, &e.name);
+    }
+    Ok(res)
+}
+
+/// A file on the FAT volume that has been opened by a client
+///
+/// The data is transferred via a buffer that we share with the client: on each read, we fetch the
+/// next chunk from the volume into the buffer. Since the buffer is always at offset 0, the
+/// position within the file is tracked by us.
+pub struct OpenFile {
+    id: SessId,
+    path: String,
+    flags: OpenFlags,
+    node: Node,
+    // the directory listing in the format expected by clients
+    dir: Option<Vec<u8>>,
+    mem: MemGate,
+    cmem: MemCap,
+    ep: Option<Selector>,
+    active: bool,
+    // the file position of the current chunk
+    pos: usize,
+    // the length of the current chunk
+    chunk: usize,
+    chain: ChainPos,
+}
+
+impl OpenFile {
+    pub fn new(id: SessId, path: &str, flags: OpenFlags) -> Result<Self, Error> {
+        // we only support reading so far
+        if flags.intersects(OpenFlags::W | OpenFlags::CREATE | OpenFlags::TRUNC) {
+            return Err(Error::new(Code::NoPerm));
+        }
+
+        let mut vol = crate::volume_mut();
+        let node = vol.lookup(path)?;
+        let dir = if node.is_dir() {
+            Some(read_dir(&mut vol, &node)?)
+        }
+        else {
+            None
+        };
+
+        let mem = MemGate::new(BUF_SIZE as GlobOff, Perm::RW)?;
+        let cmem = mem.derive_cap(0, BUF_SIZE as GlobOff, Perm::R)?;
+        Ok(Self {
+            id,
+            path: path.to_string(),
+            flags,
+            node,
+            dir,
+            mem,
+            cmem,
+            ep: None,
+            active: false,
+            pos: 0,
+            chunk: 0,
+            chain: ChainPos::default(),
+        })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn flags(&self) -> OpenFlags {
+        self.flags
+    }
+
+    pub fn set_dest(&mut self, ep: Selector) {
+        self.ep = Some(ep);
+        self.active = false;
+    }
+
+    fn activate(&mut self) -> Result<(), Error> {
+        if !self.active {
+            let sel = self.ep.ok_or_else(|| Error::new(Code::InvArgs))?;
+            EP::new_bind(0, sel).configure(self.cmem.sel())?;
+            self.active = true;
+        }
+        Ok(())
+    }
+
+    fn size(&self) -> usize {
+        match &self.dir {
+            Some(dir) => dir.len(),
+            None => self.node.size,
+        }
+    }
+
+    pub fn next_in(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        log!(
+            LogFlags::FatFSReqs,
+            "[{}] file::next_in(path={}, pos={})",
+            self.id,
+            self.path,
+            self.pos + self.chunk
+        );
+
+        if !self.flags.contains(OpenFlags::R) {
+            return Err(Error::new(Code::NoPerm));
+        }
+
+        self.pos += self.chunk;
+        self.activate()?;
+
+        self.chunk = match &self.dir {
+            Some(dir) => {
+                let data = &dir[self.pos.min(dir.len())..];
+                let len = data.len().min(BUF_SIZE);
+                self.mem.write(&data[0..len], 0)?;
+                len
+            },
+            None => {
+                let mut buf = TMP_BUF.borrow_mut();
+                let len = crate::volume_mut().read(
+                    &self.node,
+                    &mut self.chain,
+                    self.pos,
+                    &mut buf[..],
+                )?;
+                self.mem.write(&buf[0..len], 0)?;
+                len
+            },
+        };
+
+        reply_vmsg!(is, Code::Success, 0usize, self.chunk)
+    }
+
+    pub fn commit(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let nbytes: usize = is.pop()?;
+
+        log!(
+            LogFlags::FatFSReqs,
+            "[{}] file::commit(path={}, nbytes={})",
+            self.id,
+            self.path,
+            nbytes
+        );
+
+        if nbytes > self.chunk {
+            return Err(Error::new(Code::InvArgs));
+        }
+
+        self.pos += nbytes;
+        self.chunk = 0;
+
+        is.reply_error(Code::Success)
+    }
+
+    pub fn seek(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let off: usize = is.pop()?;
+        let whence = is.pop::<SeekMode>()?;
+
+        log!(
+            LogFlags::FatFSReqs,
+            "[{}] file::seek(path={}, off={}, whence={:?})",
+            self.id,
+            self.path,
+            off,
+            whence
+        );
+
+        self.chunk = 0;
+        self.pos = match whence {
+            SeekMode::Set => off,
+            SeekMode::End => self.size() + off,
+            SeekMode::Cur => return Err(Error::new(Code::InvArgs)),
+        };
+
+        reply_vmsg!(is, Code::Success, self.pos, 0usize)
+    }
+
+    pub fn stat(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        log!(
+            LogFlags::FatFSReqs,
+            "[{}] file::fstat(path={})",
+            self.id,
+            self.path
+        );
+
+        let info = crate::volume_mut().stat(&self.node);
+
+        let mut reply = m3::mem::MsgBuf::borrow_def();
+        build_vmsg!(reply, Code::Success, info);
+        is.reply(&reply)
+    }
+
+    pub fn get_path(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        log!(
+            LogFlags::FatFSReqs,
+            "[{}] file::get_path(path={})",
+            self.id,
+            self.path
+        );
+
+        reply_vmsg!(is, Code::Success, self.path)
+    }
+}
+
+impl Drop for OpenFile {
+    fn drop(&mut self) {
+        log!(
+            LogFlags::FatFSReqs,
+            "[{}] file::close(path={})",
+            self.id,
+            self.path
+        );
+    }
+}
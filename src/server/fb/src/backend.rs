@@ -0,0 +1,32 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use m3::client::{FbInfo, FbRect};
+use m3::com::MemGate;
+use m3::errors::Error;
+use m3::mem::GlobOff;
+
+pub trait Display {
+    /// Returns the resolution and memory layout of the pixel buffer
+    fn info(&self) -> FbInfo;
+
+    /// Returns the memory that contains the pixel buffer and the offset of the buffer within it
+    fn buffer(&self) -> (&MemGate, GlobOff);
+
+    /// Makes the given rectangle of the pixel buffer visible on the display
+    ///
+    /// The rectangle has already been checked to be within the bounds of the pixel buffer.
+    fn flush(&mut self, rect: FbRect) -> Result<(), Error>;
+}
@@ -0,0 +1,173 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+#![no_std]
+
+mod backend;
+mod headless;
+
+use m3::boxed::Box;
+use m3::cell::LazyStaticRefCell;
+use m3::client::FbRect;
+use m3::col::Vec;
+use m3::com::{opcodes, GateIStream, MemCap, Perm};
+use m3::env;
+use m3::errors::{Code, Error};
+use m3::io::LogFlags;
+use m3::kif;
+use m3::log;
+use m3::println;
+use m3::reply_vmsg;
+use m3::server::{
+    CapExchange, ClientManager, ExcType, RequestHandler, RequestSession, Server, ServerSession,
+    SessId, DEF_MAX_CLIENTS,
+};
+use m3::tiles::OwnActivity;
+
+use backend::Display;
+use headless::HeadlessDisplay;
+
+const DEF_WIDTH: u32 = 1024;
+const DEF_HEIGHT: u32 = 768;
+
+static DISPLAY: LazyStaticRefCell<Box<dyn Display>> = LazyStaticRefCell::default();
+
+struct FbSession {
+    serv: ServerSession,
+    // the capabilities for the pixel buffer that have been handed out to the client
+    mems: Vec<MemCap>,
+}
+
+impl RequestSession for FbSession {
+    fn new(serv: ServerSession, _arg: &str) -> Result<Self, Error>
+    where
+        Self: Sized,
+    {
+        log!(LogFlags::FbReqs, "[{}] fb::open()", serv.id());
+
+        Ok(FbSession {
+            serv,
+            mems: Vec::new(),
+        })
+    }
+
+    fn close(&mut self, _cli: &mut ClientManager<Self>, sid: SessId, _sub_ids: &mut Vec<SessId>) {
+        // the capabilities are revoked as soon as the session is dropped
+        log!(LogFlags::FbReqs, "[{}] fb::close()", sid);
+    }
+}
+
+impl FbSession {
+    fn get_mem(
+        cli: &mut ClientManager<Self>,
+        _crt: usize,
+        sid: SessId,
+        xchg: &mut CapExchange<'_>,
+    ) -> Result<(), Error> {
+        log!(LogFlags::FbReqs, "[{}] fb::get_mem()", sid);
+
+        let sess = cli.get_mut(sid).ok_or_else(|| Error::new(Code::InvArgs))?;
+
+        let display = DISPLAY.borrow();
+        let (mem, off) = display.buffer();
+        let cap = mem.derive_cap(off, display.info().size() as _, Perm::RW)?;
+        let sel = cap.sel();
+        sess.mems.push(cap);
+
+        xchg.out_caps(kif::CapRngDesc::new(kif::CapType::Object, sel, 1));
+        Ok(())
+    }
+
+    fn info(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        log!(LogFlags::FbReqs, "[{}] fb::info()", self.serv.id());
+
+        reply_vmsg!(is, Code::Success, DISPLAY.borrow().info())
+    }
+
+    fn flush(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let rect: FbRect = is.pop()?;
+
+        log!(
+            LogFlags::FbReqs,
+            "[{}] fb::flush(rect={:?})",
+            self.serv.id(),
+            rect
+        );
+
+        let mut display = DISPLAY.borrow_mut();
+        let info = display.info();
+        let right = rect.x.checked_add(rect.width);
+        let bottom = rect.y.checked_add(rect.height);
+        match (right, bottom) {
+            (Some(r), Some(b)) if r <= info.width && b <= info.height => {},
+            _ => return Err(Error::new(Code::InvArgs)),
+        }
+
+        if !rect.is_empty() {
+            display.flush(rect)?;
+        }
+
+        is.reply_error(Code::Success)
+    }
+}
+
+fn usage() -> ! {
+    println!(
+        "Usage: {} [-W <width>] [-H <height>]",
+        env::args().next().unwrap()
+    );
+    println!();
+    println!("  -W: the width in pixels ({} by default)", DEF_WIDTH);
+    println!("  -H: the height in pixels ({} by default)", DEF_HEIGHT);
+    OwnActivity::exit_with(Code::InvArgs);
+}
+
+fn parse_dim(args: &[&str], name: &str, def: u32) -> u32 {
+    match args.iter().position(|a| *a == name) {
+        Some(idx) => args
+            .get(idx + 1)
+            .and_then(|n| n.parse::<u32>().ok())
+            .filter(|n| *n > 0)
+            .unwrap_or_else(|| usage()),
+        None => def,
+    }
+}
+
+#[no_mangle]
+pub fn main() -> Result<(), Error> {
+    let args: Vec<&str> = env::args().collect();
+
+    let width = parse_dim(&args, "-W", DEF_WIDTH);
+    let height = parse_dim(&args, "-H", DEF_HEIGHT);
+
+    let display: Box<dyn Display> =
+        Box::new(HeadlessDisplay::new(width, height).expect("Unable to create headless display"));
+    DISPLAY.set(display);
+
+    let mut hdl = RequestHandler::new_with(DEF_MAX_CLIENTS, 128, 1)
+        .expect("Unable to create request handler");
+    let mut srv = Server::new("fb", &mut hdl).expect("Unable to create service 'fb'");
+
+    use opcodes::Fb;
+    hdl.reg_cap_handler(Fb::GetMem, ExcType::Obt(1), FbSession::get_mem);
+    hdl.reg_msg_handler(Fb::Info, FbSession::info);
+    hdl.reg_msg_handler(Fb::Flush, FbSession::flush);
+
+    hdl.run(&mut srv).expect("Server loop failed");
+
+    DISPLAY.unset();
+
+    Ok(())
+}
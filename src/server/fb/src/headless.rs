@@ -0,0 +1,62 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use m3::client::{FbInfo, FbRect, FB_BYTES_PER_PIXEL};
+use m3::com::{MemGate, Perm};
+use m3::errors::Error;
+use m3::io::LogFlags;
+use m3::log;
+use m3::mem::GlobOff;
+
+use crate::backend::Display;
+
+/// A display without a device behind it
+///
+/// The pixel buffer is held in ordinary memory and flushes have no effect. This allows to use
+/// the framebuffer service and its clients on platforms without graphics device.
+pub struct HeadlessDisplay {
+    info: FbInfo,
+    mem: MemGate,
+}
+
+impl HeadlessDisplay {
+    pub fn new(width: u32, height: u32) -> Result<Self, Error> {
+        let info = FbInfo {
+            width,
+            height,
+            stride: width * FB_BYTES_PER_PIXEL as u32,
+        };
+        let mem = MemGate::new(info.size() as GlobOff, Perm::RW)?;
+
+        log!(LogFlags::FbDev, "headless: {}x{}", width, height);
+
+        Ok(Self { info, mem })
+    }
+}
+
+impl Display for HeadlessDisplay {
+    fn info(&self) -> FbInfo {
+        self.info
+    }
+
+    fn buffer(&self) -> (&MemGate, GlobOff) {
+        (&self.mem, 0)
+    }
+
+    fn flush(&mut self, rect: FbRect) -> Result<(), Error> {
+        log!(LogFlags::FbDev, "headless: flush {:?}", rect);
+        Ok(())
+    }
+}
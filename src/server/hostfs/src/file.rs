@@ -0,0 +1,407 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use m3::build_vmsg;
+use m3::cap::Selector;
+use m3::cell::StaticRefCell;
+use m3::col::{String, ToString, Vec};
+use m3::com::{GateIStream, MemCap, MemGate, EP};
+use m3::errors::{Code, Error};
+use m3::io::LogFlags;
+use m3::kif::Perm;
+use m3::log;
+use m3::mem::GlobOff;
+use m3::reply_vmsg;
+use m3::server::SessId;
+use m3::vfs::{FileMode, OpenFlags, SeekMode};
+
+use crate::p9::{self, Fid};
+
+/// The size of the buffer that is shared with the client for each file
+pub const BUF_SIZE: usize = 4096;
+
+static TMP_BUF: StaticRefCell<[u8; BUF_SIZE]> = StaticRefCell::new([0u8; BUF_SIZE]);
+
+// the directory entries start with the inode number, the name length, and the distance to the
+// next entry (see `m3::vfs::ReadDir`)
+const DIR_ENTRY_HDR_SIZE: usize = 3 * 4;
+
+/// A file on the host that has been opened by a client
+///
+/// The data is transferred via a buffer that we share with the client: on read, we fetch the next
+/// chunk from the host into the buffer and on write, we hand out the buffer and send its content
+/// to the host as soon as the client has filled it. Since the buffer is always at offset 0, the
+/// position within the file is tracked by us.
+pub struct OpenFile {
+    id: SessId,
+    path: String,
+    flags: OpenFlags,
+    fid: Fid,
+    // the directory listing in the format expected by clients
+    dir: Option<Vec<u8>>,
+    mem: MemGate,
+    cmem: MemCap,
+    ep: Option<Selector>,
+    active: bool,
+    // the file position of the current chunk
+    pos: usize,
+    // the length of the current chunk
+    chunk: usize,
+    writing: bool,
+}
+
+fn lopen_flags(flags: OpenFlags) -> u32 {
+    let mut res = if flags.contains(OpenFlags::RW) {
+        p9::O_RDWR
+    }
+    else if flags.contains(OpenFlags::W) {
+        p9::O_WRONLY
+    }
+    else {
+        p9::O_RDONLY
+    };
+    if flags.contains(OpenFlags::TRUNC) {
+        res |= p9::O_TRUNC;
+    }
+    res
+}
+
+fn read_dir(client: &mut p9::Client, fid: Fid) -> Result<Vec<u8>, Error> {
+    let mut res = Vec::new();
+    let mut off = 0;
+    loop {
+        let entries = client.readdir(fid, off)?;
+        if entries.is_empty() {
+            break Ok(res);
+        }
+
+        for e in entries {
+            let next = DIR_ENTRY_HDR_SIZE + e.name.len();
+            res.extend_from_slice(&(e.qid.path as u32).to_ne_bytes());
+            res.extend_from_slice(&(e.name.len() as u32).to_ne_bytes());
+            res.extend_from_slice(&(next as u32).to_ne_bytes());
+            res.extend_from_slice(e.name.as_bytes());
+            off = e.offset;
+        }
+    }
+}
+
+impl OpenFile {
+    pub fn new(id: SessId, path: &str, flags: OpenFlags) -> Result<Self, Error> {
+        let mut client = crate::client_mut();
+
+        let fid = match client.walk(path) {
+            Ok(fid) => fid,
+            Err(e) if e.code() == Code::NoSuchFile && flags.contains(OpenFlags::CREATE) => {
+                let (dir, name) = crate::split_path(path)?;
+                let fid = client.walk(dir)?;
+                // on success, the fid refers to the new file
+                if let Err(e) = client.lcreate(fid, name, lopen_flags(flags), FileMode::FILE_DEF) {
+                    client.clunk(fid).ok();
+                    return Err(e);
+                }
+                return Self::create(id, path, flags, fid, None, 0);
+            },
+            Err(e) => return Err(e),
+        };
+
+        let res = (|| {
+            let info = client.getattr(fid)?;
+            if info.mode.is_dir() {
+                if flags.contains(OpenFlags::W) {
+                    return Err(Error::new(Code::IsDir));
+                }
+                client.lopen(fid, p9::O_RDONLY | p9::O_DIRECTORY)?;
+                Ok((Some(read_dir(&mut client, fid)?), 0))
+            }
+            else {
+                client.lopen(fid, lopen_flags(flags))?;
+                // we append by starting at the end of the file (the size is from before the open)
+                let pos = if flags.contains(OpenFlags::APPEND) && !flags.contains(OpenFlags::TRUNC)
+                {
+                    info.size
+                }
+                else {
+                    0
+                };
+                Ok((None, pos))
+            }
+        })();
+
+        match res {
+            Ok((dir, pos)) => Self::create(id, path, flags, fid, dir, pos),
+            Err(e) => {
+                client.clunk(fid).ok();
+                Err(e)
+            },
+        }
+    }
+
+    fn create(
+        id: SessId,
+        path: &str,
+        flags: OpenFlags,
+        fid: Fid,
+        dir: Option<Vec<u8>>,
+        pos: usize,
+    ) -> Result<Self, Error> {
+        let mem = MemGate::new(BUF_SIZE as GlobOff, Perm::RW)?;
+        let cmem = mem.derive_cap(0, BUF_SIZE as GlobOff, Perm::RW)?;
+        Ok(Self {
+            id,
+            path: path.to_string(),
+            flags,
+            fid,
+            dir,
+            mem,
+            cmem,
+            ep: None,
+            active: false,
+            pos,
+            chunk: 0,
+            writing: false,
+        })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn flags(&self) -> OpenFlags {
+        self.flags
+    }
+
+    pub fn set_dest(&mut self, ep: Selector) {
+        self.ep = Some(ep);
+        self.active = false;
+    }
+
+    fn activate(&mut self) -> Result<(), Error> {
+        if !self.active {
+            let sel = self.ep.ok_or_else(|| Error::new(Code::InvArgs))?;
+            EP::new_bind(0, sel).configure(self.cmem.sel())?;
+            self.active = true;
+        }
+        Ok(())
+    }
+
+    /// Writes the first `nbytes` of the buffer to the host at the current position
+    fn flush(&mut self, nbytes: usize) -> Result<(), Error> {
+        let mut buf = TMP_BUF.borrow_mut();
+        self.mem.read(&mut buf[0..nbytes], 0)?;
+
+        let mut client = crate::client_mut();
+        let mut done = 0;
+        while done < nbytes {
+            let off = (self.pos + done) as u64;
+            match client.write(self.fid, off, &buf[done..nbytes])? {
+                0 => return Err(Error::new(Code::WriteFailed)),
+                n => done += n,
+            }
+        }
+        Ok(())
+    }
+
+    /// Finishes the current chunk, which the client has consumed or filled completely
+    fn finish_chunk(&mut self) -> Result<(), Error> {
+        if self.writing && self.chunk > 0 {
+            self.flush(self.chunk)?;
+        }
+        self.pos += self.chunk;
+        self.chunk = 0;
+        self.writing = false;
+        Ok(())
+    }
+
+    pub fn next_in(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        log!(
+            LogFlags::HostFSReqs,
+            "[{}] file::next_in(path={}, pos={})",
+            self.id,
+            self.path,
+            self.pos + self.chunk
+        );
+
+        if !self.flags.contains(OpenFlags::R) {
+            return Err(Error::new(Code::NoPerm));
+        }
+
+        self.finish_chunk()?;
+        self.activate()?;
+
+        self.chunk = match &self.dir {
+            Some(dir) => {
+                let data = &dir[self.pos.min(dir.len())..];
+                let len = data.len().min(BUF_SIZE);
+                self.mem.write(&data[0..len], 0)?;
+                len
+            },
+            None => {
+                let mut client = crate::client_mut();
+                let data = client.read(self.fid, self.pos as u64, BUF_SIZE)?;
+                self.mem.write(data, 0)?;
+                data.len()
+            },
+        };
+
+        reply_vmsg!(is, Code::Success, 0usize, self.chunk)
+    }
+
+    pub fn next_out(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        log!(
+            LogFlags::HostFSReqs,
+            "[{}] file::next_out(path={}, pos={})",
+            self.id,
+            self.path,
+            self.pos + self.chunk
+        );
+
+        if !self.flags.contains(OpenFlags::W) {
+            return Err(Error::new(Code::NoPerm));
+        }
+
+        self.finish_chunk()?;
+        self.activate()?;
+
+        self.chunk = BUF_SIZE;
+        self.writing = true;
+
+        reply_vmsg!(is, Code::Success, 0usize, self.chunk)
+    }
+
+    pub fn commit(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let nbytes: usize = is.pop()?;
+
+        log!(
+            LogFlags::HostFSReqs,
+            "[{}] file::commit(path={}, nbytes={})",
+            self.id,
+            self.path,
+            nbytes
+        );
+
+        if nbytes > self.chunk {
+            return Err(Error::new(Code::InvArgs));
+        }
+
+        if self.writing {
+            self.flush(nbytes)?;
+        }
+        self.pos += nbytes;
+        self.chunk = 0;
+        self.writing = false;
+
+        is.reply_error(Code::Success)
+    }
+
+    pub fn seek(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let off: usize = is.pop()?;
+        let whence = is.pop::<SeekMode>()?;
+
+        log!(
+            LogFlags::HostFSReqs,
+            "[{}] file::seek(path={}, off={}, whence={:?})",
+            self.id,
+            self.path,
+            off,
+            whence
+        );
+
+        self.finish_chunk()?;
+        self.pos = match whence {
+            SeekMode::Set => off,
+            SeekMode::End => match &self.dir {
+                Some(dir) => dir.len() + off,
+                None => crate::client_mut().getattr(self.fid)?.size + off,
+            },
+            SeekMode::Cur => return Err(Error::new(Code::InvArgs)),
+        };
+
+        reply_vmsg!(is, Code::Success, self.pos, 0usize)
+    }
+
+    pub fn stat(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        log!(
+            LogFlags::HostFSReqs,
+            "[{}] file::fstat(path={})",
+            self.id,
+            self.path
+        );
+
+        let info = crate::client_mut().getattr(self.fid)?;
+
+        let mut reply = m3::mem::MsgBuf::borrow_def();
+        build_vmsg!(reply, Code::Success, info);
+        is.reply(&reply)
+    }
+
+    pub fn get_path(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        log!(
+            LogFlags::HostFSReqs,
+            "[{}] file::get_path(path={})",
+            self.id,
+            self.path
+        );
+
+        reply_vmsg!(is, Code::Success, self.path)
+    }
+
+    pub fn truncate(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let off: usize = is.pop()?;
+
+        log!(
+            LogFlags::HostFSReqs,
+            "[{}] file::truncate(path={}, off={})",
+            self.id,
+            self.path,
+            off
+        );
+
+        if self.dir.is_some() {
+            return Err(Error::new(Code::IsDir));
+        }
+
+        self.finish_chunk()?;
+        crate::client_mut().truncate(self.fid, off)?;
+        self.pos = off;
+
+        reply_vmsg!(is, Code::Success, self.pos, 0usize)
+    }
+
+    pub fn sync(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        log!(
+            LogFlags::HostFSReqs,
+            "[{}] file::sync(path={})",
+            self.id,
+            self.path
+        );
+
+        crate::client_mut().fsync(self.fid)?;
+        is.reply_error(Code::Success)
+    }
+}
+
+impl Drop for OpenFile {
+    fn drop(&mut self) {
+        log!(
+            LogFlags::HostFSReqs,
+            "[{}] file::close(path={})",
+            self.id,
+            self.path
+        );
+
+        crate::client_mut().clunk(self.fid).ok();
+    }
+}
@@ -0,0 +1,598 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! A file system server that provides access to a directory on the host
+//!
+//! hostfs connects to a 9P2000.L server on the host (e.g., diod or QEMU's 9P export) via TCP and
+//! implements the m3fs protocol on top of it. Hence, clients can mount it like any m3fs instance
+//! (e.g., via `<mount fs="hostfs" path="/host" />`) to exchange files with the host without
+//! rebuilding the disk images. Note that hostfs does not support memory mappings, notifications,
+//! or pipelined transfers.
+
+#![no_std]
+
+mod file;
+mod p9;
+
+use m3::build_vmsg;
+use m3::cap::{SelSpace, Selector};
+use m3::cell::{LazyStaticRefCell, RefMut, StaticCell};
+use m3::col::{String, ToString, Treap, Vec};
+use m3::com::{opcodes, GateIStream};
+use m3::env;
+use m3::errors::{Code, Error};
+use m3::io::LogFlags;
+use m3::kif::{CapRngDesc, CapType, INVALID_SEL};
+use m3::log;
+use m3::net::{Endpoint, IpAddr, Port};
+use m3::println;
+use m3::reply_vmsg;
+use m3::server::{
+    CapExchange, ClientManager, ExcType, RequestHandler, RequestSession, Server, ServerSession,
+    SessId, DEF_MAX_CLIENTS,
+};
+use m3::tiles::OwnActivity;
+use m3::vfs::{FileMode, OpenFlags};
+
+use file::OpenFile;
+
+const MSG_SIZE: usize = 128;
+const DEF_PORT: Port = 564;
+
+static CLIENT: LazyStaticRefCell<p9::Client> = LazyStaticRefCell::default();
+static NEXT_PRIV_ID: StaticCell<usize> = StaticCell::new(1);
+
+fn client_mut() -> RefMut<'static, p9::Client> {
+    CLIENT.borrow_mut()
+}
+
+/// Splits `path` into the directory and the name of the last component
+fn split_path(path: &str) -> Result<(&str, &str), Error> {
+    let path = path.trim_end_matches('/');
+    let (dir, name) = match path.rfind('/') {
+        Some(pos) => (&path[0..pos], &path[pos + 1..]),
+        None => ("", path),
+    };
+    if name.is_empty() || name == "." || name == ".." {
+        return Err(Error::new(Code::InvArgs));
+    }
+    Ok((dir, name))
+}
+
+struct Meta {
+    priv_eps: Vec<Selector>,
+    priv_files: Treap<usize, OpenFile>,
+}
+
+#[allow(clippy::large_enum_variant)]
+enum SessionData {
+    Meta(Meta),
+    File(OpenFile),
+}
+
+struct HostFSSession {
+    serv: ServerSession,
+    data: SessionData,
+    parent: Option<SessId>,
+    childs: Vec<SessId>,
+}
+
+impl RequestSession for HostFSSession {
+    fn new(serv: ServerSession, _arg: &str) -> Result<Self, Error>
+    where
+        Self: Sized,
+    {
+        log!(LogFlags::HostFSReqs, "[{}] hostfs::open()", serv.id());
+        Ok(Self::new_meta(serv))
+    }
+
+    fn close(&mut self, cli: &mut ClientManager<Self>, sid: SessId, sub_ids: &mut Vec<SessId>)
+    where
+        Self: Sized,
+    {
+        log!(
+            LogFlags::HostFSReqs,
+            "[{}] hostfs::close(): closing {:?}",
+            sid,
+            sub_ids
+        );
+
+        // close child sessions as well
+        sub_ids.extend_from_slice(&self.childs);
+
+        // remove us from parent
+        if let Some(pid) = self.parent.take() {
+            if let Some(p) = cli.get_mut(pid) {
+                p.childs.retain(|cid| *cid != sid);
+            }
+        }
+    }
+
+    fn revoked(&mut self, sel: Selector) {
+        log!(LogFlags::HostFSReqs, "hostfs::revoked(sel={})", sel);
+
+        // keep the indices of the other EPs stable
+        if let SessionData::Meta(m) = &mut self.data {
+            if let Some(ep) = m.priv_eps.iter_mut().find(|ep| **ep == sel) {
+                *ep = INVALID_SEL;
+            }
+        }
+    }
+}
+
+impl HostFSSession {
+    fn new_meta(serv: ServerSession) -> Self {
+        HostFSSession {
+            serv,
+            data: SessionData::Meta(Meta {
+                priv_eps: Vec::new(),
+                priv_files: Treap::new(),
+            }),
+            parent: None,
+            childs: Vec::new(),
+        }
+    }
+
+    fn get_sess(cli: &mut ClientManager<Self>, sid: SessId) -> Result<&mut Self, Error> {
+        cli.get_mut(sid).ok_or_else(|| Error::new(Code::InvArgs))
+    }
+
+    fn meta(&mut self) -> Result<&mut Meta, Error> {
+        match &mut self.data {
+            SessionData::Meta(m) => Ok(m),
+            SessionData::File(_) => Err(Error::new(Code::InvArgs)),
+        }
+    }
+
+    fn with_file<F>(&mut self, is: &mut GateIStream<'_>, func: F) -> Result<(), Error>
+    where
+        F: Fn(&mut OpenFile, &mut GateIStream<'_>) -> Result<(), Error>,
+    {
+        let fid: usize = is.pop()?;
+        match &mut self.data {
+            SessionData::Meta(m) => match m.priv_files.get_mut(&fid) {
+                Some(f) => func(f, is),
+                None => Err(Error::new(Code::InvArgs)),
+            },
+            SessionData::File(f) => func(f, is),
+        }
+    }
+
+    fn new_file(
+        parent: SessId,
+        serv: ServerSession,
+        path: &str,
+        flags: OpenFlags,
+    ) -> Result<Self, Error> {
+        Ok(HostFSSession {
+            data: SessionData::File(OpenFile::new(serv.id(), path, flags)?),
+            serv,
+            parent: Some(parent),
+            childs: Vec::new(),
+        })
+    }
+
+    fn open(
+        cli: &mut ClientManager<Self>,
+        crt: usize,
+        sid: SessId,
+        xchg: &mut CapExchange<'_>,
+    ) -> Result<(), Error> {
+        let flags: OpenFlags = xchg.in_args().pop()?;
+        let path: String = xchg.in_args().pop::<&str>()?.to_string();
+
+        log!(
+            LogFlags::HostFSReqs,
+            "[{}] meta::open(path={}, flags={:?})",
+            sid,
+            path,
+            flags
+        );
+
+        let (sel, _nsid) = cli.add_connected(crt, |cli, serv, _sgate| {
+            let parent = Self::get_sess(cli, sid)?;
+            parent.meta()?;
+
+            let child = Self::new_file(sid, serv, &path, flags)?;
+            parent.childs.push(child.serv.id());
+            Ok(child)
+        })?;
+
+        xchg.out_caps(CapRngDesc::new(CapType::Object, sel, 2));
+        Ok(())
+    }
+
+    fn clone(
+        cli: &mut ClientManager<Self>,
+        crt: usize,
+        sid: SessId,
+        xchg: &mut CapExchange<'_>,
+    ) -> Result<(), Error> {
+        log!(LogFlags::HostFSReqs, "[{}] hostfs::clone(crt={})", sid, crt);
+
+        let (sel, _nsid) = cli.add_connected(crt, |cli, serv, _sgate| {
+            let parent = Self::get_sess(cli, sid)?;
+            match &parent.data {
+                // meta sessions are independent of each other
+                SessionData::Meta(_) => Ok(Self::new_meta(serv)),
+
+                // the clone of a file is a new file at the same path
+                SessionData::File(f) => {
+                    let flags = f.flags() & !(OpenFlags::CREATE | OpenFlags::TRUNC);
+                    let child = Self::new_file(sid, serv, f.path(), flags)?;
+                    parent.childs.push(child.serv.id());
+                    Ok(child)
+                },
+            }
+        })?;
+
+        xchg.out_caps(CapRngDesc::new(CapType::Object, sel, 2));
+        Ok(())
+    }
+
+    fn del_ep(
+        cli: &mut ClientManager<Self>,
+        _crt: usize,
+        sid: SessId,
+        xchg: &mut CapExchange<'_>,
+    ) -> Result<(), Error> {
+        let meta = Self::get_sess(cli, sid)?.meta()?;
+
+        let sel = SelSpace::get().alloc_sel();
+        meta.priv_eps.push(sel);
+        let id = meta.priv_eps.len() - 1;
+        // drop the EP registration as soon as the client revokes the EP
+        xchg.notify_revoke();
+
+        log!(
+            LogFlags::HostFSReqs,
+            "[{}] meta::del_ep(sel={}) -> {}",
+            sid,
+            sel,
+            id
+        );
+
+        xchg.out_caps(CapRngDesc::new(CapType::Object, sel, 1));
+        xchg.out_args().push(id);
+        Ok(())
+    }
+
+    fn set_dest(
+        cli: &mut ClientManager<Self>,
+        _crt: usize,
+        sid: SessId,
+        xchg: &mut CapExchange<'_>,
+    ) -> Result<(), Error> {
+        log!(LogFlags::HostFSReqs, "[{}] file::set_dest()", sid);
+
+        match &mut Self::get_sess(cli, sid)?.data {
+            SessionData::File(f) => {
+                let sel = SelSpace::get().alloc_sel();
+                f.set_dest(sel);
+                xchg.out_caps(CapRngDesc::new(CapType::Object, sel, 1));
+                Ok(())
+            },
+            SessionData::Meta(_) => Err(Error::new(Code::InvArgs)),
+        }
+    }
+
+    fn open_priv(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let path: &str = is.pop()?;
+        let flags = OpenFlags::from_bits_truncate(is.pop::<u32>()?);
+        let ep: usize = is.pop()?;
+
+        log!(
+            LogFlags::HostFSReqs,
+            "[{}] meta::open_priv(path={}, flags={:?}, ep={})",
+            self.serv.id(),
+            path,
+            flags,
+            ep
+        );
+
+        let meta = self.meta()?;
+        let ep_sel = meta
+            .priv_eps
+            .get(ep)
+            .copied()
+            .filter(|sel| *sel != INVALID_SEL)
+            .ok_or_else(|| Error::new(Code::InvArgs))?;
+
+        let id = NEXT_PRIV_ID.get();
+        let mut file = OpenFile::new(id, path, flags)?;
+        file.set_dest(ep_sel);
+        NEXT_PRIV_ID.set(id + 1);
+        meta.priv_files.insert(id, file);
+
+        reply_vmsg!(is, Code::Success, id)
+    }
+
+    fn close_priv(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let id: usize = is.pop()?;
+
+        log!(
+            LogFlags::HostFSReqs,
+            "[{}] meta::close_priv(id={})",
+            self.serv.id(),
+            id
+        );
+
+        match self.meta()?.priv_files.remove(&id) {
+            Some(_) => is.reply_error(Code::Success),
+            None => Err(Error::new(Code::InvArgs)),
+        }
+    }
+
+    fn stat(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let path: &str = is.pop()?;
+
+        log!(
+            LogFlags::HostFSReqs,
+            "[{}] meta::stat(path={})",
+            self.serv.id(),
+            path
+        );
+
+        self.meta()?;
+        let info = client_mut().with_walk(path, |c, fid| c.getattr(fid))?;
+
+        let mut reply = m3::mem::MsgBuf::borrow_def();
+        build_vmsg!(reply, Code::Success, info);
+        is.reply(&reply)
+    }
+
+    fn mkdir(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let path: &str = is.pop()?;
+        let mode = FileMode::from_bits_truncate(is.pop::<u16>()?) & FileMode::PERM;
+
+        log!(
+            LogFlags::HostFSReqs,
+            "[{}] meta::mkdir(path={}, mode={:o})",
+            self.serv.id(),
+            path,
+            mode
+        );
+
+        self.meta()?;
+        let (dir, name) = split_path(path)?;
+        client_mut().with_walk(dir, |c, dfid| c.mkdir(dfid, name, mode))?;
+
+        is.reply_error(Code::Success)
+    }
+
+    fn remove(&mut self, is: &mut GateIStream<'_>, flags: u32) -> Result<(), Error> {
+        let path: &str = is.pop()?;
+
+        log!(
+            LogFlags::HostFSReqs,
+            "[{}] meta::{}(path={})",
+            self.serv.id(),
+            if flags == p9::AT_REMOVEDIR {
+                "rmdir"
+            }
+            else {
+                "unlink"
+            },
+            path
+        );
+
+        self.meta()?;
+        let (dir, name) = split_path(path)?;
+        client_mut().with_walk(dir, |c, dfid| c.unlinkat(dfid, name, flags))?;
+
+        is.reply_error(Code::Success)
+    }
+
+    fn link(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let old_path: &str = is.pop()?;
+        let new_path: &str = is.pop()?;
+
+        log!(
+            LogFlags::HostFSReqs,
+            "[{}] meta::link(old_path={}, new_path={})",
+            self.serv.id(),
+            old_path,
+            new_path
+        );
+
+        self.meta()?;
+        let (dir, name) = split_path(new_path)?;
+        client_mut().with_walk(old_path, |c, fid| {
+            c.with_walk(dir, |c, dfid| c.link(dfid, fid, name))
+        })?;
+
+        is.reply_error(Code::Success)
+    }
+
+    fn rename(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let old_path: &str = is.pop()?;
+        let new_path: &str = is.pop()?;
+
+        log!(
+            LogFlags::HostFSReqs,
+            "[{}] meta::rename(old_path={}, new_path={})",
+            self.serv.id(),
+            old_path,
+            new_path
+        );
+
+        self.meta()?;
+        let (old_dir, old_name) = split_path(old_path)?;
+        let (new_dir, new_name) = split_path(new_path)?;
+        client_mut().with_walk(old_dir, |c, old_dfid| {
+            c.with_walk(new_dir, |c, new_dfid| {
+                c.renameat(old_dfid, old_name, new_dfid, new_name)
+            })
+        })?;
+
+        is.reply_error(Code::Success)
+    }
+
+    fn utime(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let path: &str = is.pop()?;
+        let lastaccess: u32 = is.pop()?;
+        let lastmod: u32 = is.pop()?;
+
+        log!(
+            LogFlags::HostFSReqs,
+            "[{}] meta::utime(path={}, lastaccess={}, lastmod={})",
+            self.serv.id(),
+            path,
+            lastaccess,
+            lastmod
+        );
+
+        self.meta()?;
+        client_mut().with_walk(path, |c, fid| c.set_times(fid, lastaccess, lastmod))?;
+
+        is.reply_error(Code::Success)
+    }
+}
+
+struct Settings {
+    name: String,
+    net: String,
+    uname: String,
+    aname: String,
+    ep: Endpoint,
+}
+
+fn usage() -> ! {
+    println!(
+        "Usage: {} [-n <name>] [-N <net>] [-p <port>] [-u <user>] [-a <path>] <ip>",
+        env::args().next().unwrap()
+    );
+    println!();
+    println!("  -n: the name of the service (hostfs by default)");
+    println!("  -N: the name of the network service (net by default)");
+    println!("  -p: the TCP port of the 9P server (564 by default)");
+    println!("  -u: the user name to attach as (m3 by default)");
+    println!("  -a: the exported directory to attach to (the server's default by default)");
+    println!();
+    println!(
+        "Connects to the 9P2000.L server at <ip> and provides its files via the m3fs protocol."
+    );
+    OwnActivity::exit_with(Code::InvArgs);
+}
+
+fn parse_args() -> Result<Settings, String> {
+    let mut name = String::from("hostfs");
+    let mut net = String::from("net");
+    let mut uname = String::from("m3");
+    let mut aname = String::new();
+    let mut port = DEF_PORT;
+
+    let args: Vec<&str> = env::args().collect();
+    let mut i = 1;
+    while i + 1 < args.len() {
+        match args[i] {
+            "-n" => name = args[i + 1].to_string(),
+            "-N" => net = args[i + 1].to_string(),
+            "-u" => uname = args[i + 1].to_string(),
+            "-a" => aname = args[i + 1].to_string(),
+            "-p" => {
+                port = args[i + 1]
+                    .parse::<Port>()
+                    .map_err(|_| String::from("Failed to parse port"))?;
+            },
+            _ => break,
+        }
+        i += 2;
+    }
+
+    if i + 1 != args.len() {
+        return Err(String::from("Expected exactly one IP address"));
+    }
+    let addr = args[i]
+        .parse::<IpAddr>()
+        .map_err(|_| String::from("Failed to parse IP address"))?;
+
+    Ok(Settings {
+        name,
+        net,
+        uname,
+        aname,
+        ep: Endpoint::new(addr, port),
+    })
+}
+
+#[no_mangle]
+pub fn main() -> Result<(), Error> {
+    let settings = parse_args().unwrap_or_else(|e| {
+        println!("Invalid arguments: {}", e);
+        usage();
+    });
+
+    CLIENT.set(
+        p9::Client::new(&settings.net, settings.ep, &settings.uname, &settings.aname)
+            .expect("Unable to connect to 9P server"),
+    );
+
+    let mut hdl = RequestHandler::new_with(DEF_MAX_CLIENTS, MSG_SIZE, 1)
+        .expect("Unable to create request handler");
+    let mut srv = Server::new(&settings.name, &mut hdl).expect("Unable to create service");
+
+    use opcodes::FileSystem;
+
+    hdl.reg_cap_handler(FileSystem::Open, ExcType::Obt(2), HostFSSession::open);
+    hdl.reg_cap_handler(FileSystem::DelEP, ExcType::Del(1), HostFSSession::del_ep);
+    hdl.reg_cap_handler(FileSystem::CloneFile, ExcType::Obt(2), HostFSSession::clone);
+    hdl.reg_cap_handler(FileSystem::CloneMeta, ExcType::Obt(2), HostFSSession::clone);
+    hdl.reg_cap_handler(
+        FileSystem::SetDest,
+        ExcType::Del(1),
+        HostFSSession::set_dest,
+    );
+
+    hdl.reg_msg_handler(FileSystem::NextIn, |sess, is| {
+        sess.with_file(is, |f, is| f.next_in(is))
+    });
+    hdl.reg_msg_handler(FileSystem::NextOut, |sess, is| {
+        sess.with_file(is, |f, is| f.next_out(is))
+    });
+    hdl.reg_msg_handler(FileSystem::Commit, |sess, is| {
+        sess.with_file(is, |f, is| f.commit(is))
+    });
+    hdl.reg_msg_handler(FileSystem::Seek, |sess, is| {
+        sess.with_file(is, |f, is| f.seek(is))
+    });
+    hdl.reg_msg_handler(FileSystem::FStat, |sess, is| {
+        sess.with_file(is, |f, is| f.stat(is))
+    });
+    hdl.reg_msg_handler(FileSystem::GetPath, |sess, is| {
+        sess.with_file(is, |f, is| f.get_path(is))
+    });
+    hdl.reg_msg_handler(FileSystem::Truncate, |sess, is| {
+        sess.with_file(is, |f, is| f.truncate(is))
+    });
+    hdl.reg_msg_handler(FileSystem::Sync, |sess, is| {
+        sess.with_file(is, |f, is| f.sync(is))
+    });
+    hdl.reg_msg_handler(FileSystem::Stat, HostFSSession::stat);
+    hdl.reg_msg_handler(FileSystem::Mkdir, HostFSSession::mkdir);
+    hdl.reg_msg_handler(FileSystem::Rmdir, |sess, is| {
+        sess.remove(is, p9::AT_REMOVEDIR)
+    });
+    hdl.reg_msg_handler(FileSystem::Unlink, |sess, is| sess.remove(is, 0));
+    hdl.reg_msg_handler(FileSystem::Link, HostFSSession::link);
+    hdl.reg_msg_handler(FileSystem::Rename, HostFSSession::rename);
+    hdl.reg_msg_handler(FileSystem::Utime, HostFSSession::utime);
+    hdl.reg_msg_handler(FileSystem::OpenPriv, HostFSSession::open_priv);
+    hdl.reg_msg_handler(FileSystem::ClosePriv, HostFSSession::close_priv);
+
+    hdl.run(&mut srv).expect("Server loop failed");
+
+    Ok(())
+}
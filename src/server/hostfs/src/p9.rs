@@ -0,0 +1,589 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! A minimal client for the 9P2000.L protocol
+//!
+//! All requests are sent synchronously, that is, there is at most one outstanding request and all
+//! requests therefore use the same tag. See <https://github.com/chaos/diod/blob/master/protocol.md>
+//! for the protocol description.
+
+use m3::client::Network;
+use m3::col::{String, ToString, Vec};
+use m3::errors::{Code, Error};
+use m3::io::LogFlags;
+use m3::log;
+use m3::net::{Endpoint, Socket, StreamSocketArgs, TcpSocket};
+use m3::vfs::{FileInfo, FileMode, FileRef};
+
+pub type Fid = u32;
+
+/// The fid that refers to the root of the attached file tree
+pub const ROOT_FID: Fid = 0;
+
+/// The maximum message size we negotiate with the server
+pub const MAX_MSIZE: usize = 8192;
+/// The header size of Twrite messages, which is the largest header of all read/write messages
+pub const IO_HDR_SIZE: usize = 4 + 1 + 2 + 4 + 8 + 4;
+
+const VERSION: &str = "9P2000.L";
+const NOTAG: u16 = !0;
+const TAG: u16 = 1;
+const NOFID: Fid = !0;
+// the maximum number of path components per Twalk
+const MAX_WELEM: usize = 16;
+
+// the subset of the 9P2000.L message types that we use; the reply type is always type + 1
+const RLERROR: u8 = 7;
+const TLOPEN: u8 = 12;
+const TLCREATE: u8 = 14;
+const TGETATTR: u8 = 24;
+const TSETATTR: u8 = 26;
+const TREADDIR: u8 = 40;
+const TFSYNC: u8 = 50;
+const TLINK: u8 = 70;
+const TMKDIR: u8 = 72;
+const TRENAMEAT: u8 = 74;
+const TUNLINKAT: u8 = 76;
+const TVERSION: u8 = 100;
+const TATTACH: u8 = 104;
+const TWALK: u8 = 110;
+const TREAD: u8 = 116;
+const TWRITE: u8 = 118;
+const TCLUNK: u8 = 120;
+
+const GETATTR_BASIC: u64 = 0x7FF;
+
+const SETATTR_SIZE: u32 = 0x8;
+const SETATTR_ATIME: u32 = 0x10;
+const SETATTR_MTIME: u32 = 0x20;
+const SETATTR_ATIME_SET: u32 = 0x80;
+const SETATTR_MTIME_SET: u32 = 0x100;
+
+/// Flags for [`Client::lopen`] and [`Client::lcreate`] (as defined by Linux)
+pub const O_RDONLY: u32 = 0o0;
+pub const O_WRONLY: u32 = 0o1;
+pub const O_RDWR: u32 = 0o2;
+pub const O_TRUNC: u32 = 0o1000;
+pub const O_DIRECTORY: u32 = 0o200000;
+
+/// Flag for [`Client::unlinkat`] to remove a directory
+pub const AT_REMOVEDIR: u32 = 0x200;
+
+/// The unique identification of a file on the server
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Qid {
+    pub ty: u8,
+    pub version: u32,
+    pub path: u64,
+}
+
+/// An entry of a directory as returned by [`Client::readdir`]
+#[derive(Debug)]
+pub struct DirEntry {
+    pub qid: Qid,
+    /// The offset to pass to [`Client::readdir`] to continue after this entry
+    pub offset: u64,
+    pub name: String,
+}
+
+fn errno_to_code(errno: u32) -> Code {
+    match errno {
+        1 | 13 => Code::NoPerm,
+        2 => Code::NoSuchFile,
+        12 => Code::OutOfMem,
+        17 => Code::Exists,
+        18 => Code::XfsLink,
+        20 => Code::IsNoDir,
+        21 => Code::IsDir,
+        22 => Code::InvArgs,
+        28 => Code::NoSpace,
+        39 => Code::DirNotEmpty,
+        95 => Code::NotSup,
+        _ => Code::Unspecified,
+    }
+}
+
+/// A request that is built in the message buffer of the client
+struct Request<'b> {
+    buf: &'b mut Vec<u8>,
+}
+
+impl<'b> Request<'b> {
+    fn new(buf: &'b mut Vec<u8>, ty: u8, tag: u16) -> Self {
+        buf.clear();
+        // the size is filled in before sending
+        buf.extend_from_slice(&[0; 4]);
+        buf.push(ty);
+        buf.extend_from_slice(&tag.to_le_bytes());
+        Self { buf }
+    }
+
+    fn u16(self, val: u16) -> Self {
+        self.buf.extend_from_slice(&val.to_le_bytes());
+        self
+    }
+
+    fn u32(self, val: u32) -> Self {
+        self.buf.extend_from_slice(&val.to_le_bytes());
+        self
+    }
+
+    fn u64(self, val: u64) -> Self {
+        self.buf.extend_from_slice(&val.to_le_bytes());
+        self
+    }
+
+    fn str(self, val: &str) -> Self {
+        self.u16(val.len() as u16).bytes(val.as_bytes())
+    }
+
+    fn bytes(self, val: &[u8]) -> Self {
+        self.buf.extend_from_slice(val);
+        self
+    }
+}
+
+/// The body of a reply
+struct Reply<'b> {
+    buf: &'b [u8],
+    pos: usize,
+}
+
+impl<'b> Reply<'b> {
+    fn bytes(&mut self, len: usize) -> Result<&'b [u8], Error> {
+        if self.pos + len > self.buf.len() {
+            return Err(Error::new(Code::InvState));
+        }
+        let res = &self.buf[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(res)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_le_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, Error> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    fn str(&mut self) -> Result<&'b str, Error> {
+        let len = self.u16()? as usize;
+        core::str::from_utf8(self.bytes(len)?).map_err(|_| Error::new(Code::InvState))
+    }
+
+    fn qid(&mut self) -> Result<Qid, Error> {
+        Ok(Qid {
+            ty: self.u8()?,
+            version: self.u32()?,
+            path: self.u64()?,
+        })
+    }
+}
+
+/// A 9P2000.L client that talks to a server on the host via TCP
+pub struct Client {
+    socket: FileRef<TcpSocket>,
+    msize: usize,
+    next_fid: Fid,
+    free_fids: Vec<Fid>,
+    buf: Vec<u8>,
+}
+
+impl Client {
+    /// Connects to the 9P server at `ep` and attaches to the file tree `aname` as user `uname`.
+    /// The root of the tree is available via [`ROOT_FID`] afterwards.
+    pub fn new(net: &str, ep: Endpoint, uname: &str, aname: &str) -> Result<Self, Error> {
+        let net = Network::new(net)?;
+        let mut socket = TcpSocket::new(StreamSocketArgs::new(net))?;
+        socket.connect(ep)?;
+
+        let mut client = Self {
+            socket,
+            msize: MAX_MSIZE,
+            next_fid: ROOT_FID + 1,
+            free_fids: Vec::new(),
+            buf: Vec::with_capacity(MAX_MSIZE),
+        };
+
+        Request::new(&mut client.buf, TVERSION, NOTAG)
+            .u32(MAX_MSIZE as u32)
+            .str(VERSION);
+        let (msize, version) = {
+            let mut reply = client.transact(TVERSION)?;
+            (reply.u32()? as usize, reply.str()?.to_string())
+        };
+        if version != VERSION {
+            log!(
+                LogFlags::Error,
+                "hostfs: server does not support {} (got {})",
+                VERSION,
+                version
+            );
+            return Err(Error::new(Code::VersionMismatch));
+        }
+        client.msize = msize.min(MAX_MSIZE);
+
+        Request::new(&mut client.buf, TATTACH, TAG)
+            .u32(ROOT_FID)
+            .u32(NOFID)
+            .str(uname)
+            .str(aname)
+            .u32(!0);
+        client.transact(TATTACH)?;
+
+        log!(
+            LogFlags::HostFS9P,
+            "attached to {} at {} as {} (msize={})",
+            ep,
+            aname,
+            uname,
+            client.msize
+        );
+        Ok(client)
+    }
+
+    /// Returns the maximum number of bytes that can be transferred with a single read or write
+    pub fn iounit(&self) -> usize {
+        self.msize - IO_HDR_SIZE
+    }
+
+    fn alloc_fid(&mut self) -> Fid {
+        self.free_fids.pop().unwrap_or_else(|| {
+            self.next_fid += 1;
+            self.next_fid - 1
+        })
+    }
+
+    fn send_all(&mut self) -> Result<(), Error> {
+        let mut pos = 0;
+        while pos < self.buf.len() {
+            pos += self.socket.send(&self.buf[pos..])?;
+        }
+        Ok(())
+    }
+
+    fn recv_exact(&mut self, len: usize) -> Result<(), Error> {
+        self.buf.resize(len, 0);
+        let mut pos = 0;
+        while pos < len {
+            match self.socket.recv(&mut self.buf[pos..])? {
+                0 => return Err(Error::new(Code::ConnClosed)),
+                n => pos += n,
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends the request in the message buffer and receives the reply into it
+    fn transact(&mut self, ty: u8) -> Result<Reply<'_>, Error> {
+        let size = self.buf.len() as u32;
+        self.buf[0..4].copy_from_slice(&size.to_le_bytes());
+
+        log!(LogFlags::HostFS9P, "-> type={}, size={}", ty, size);
+        self.send_all()?;
+
+        self.recv_exact(4)?;
+        let size = u32::from_le_bytes(self.buf[0..4].try_into().unwrap()) as usize;
+        if !(7..=MAX_MSIZE).contains(&size) {
+            return Err(Error::new(Code::InvState));
+        }
+        self.recv_exact(size - 4)?;
+
+        let rty = self.buf[0];
+        let mut reply = Reply {
+            buf: &self.buf[3..],
+            pos: 0,
+        };
+        log!(LogFlags::HostFS9P, "<- type={}, size={}", rty, size);
+
+        if rty == RLERROR {
+            let errno = reply.u32()?;
+            log!(LogFlags::HostFS9P, "<- error {}", errno);
+            return Err(Error::new(errno_to_code(errno)));
+        }
+        if rty != ty + 1 {
+            return Err(Error::new(Code::InvState));
+        }
+        Ok(reply)
+    }
+
+    /// Walks from the root to `path` and returns a new fid for the file
+    pub fn walk(&mut self, path: &str) -> Result<Fid, Error> {
+        let names = path
+            .split('/')
+            .filter(|c| !c.is_empty() && *c != ".")
+            .collect::<Vec<_>>();
+
+        let fid = self.alloc_fid();
+        let mut from = ROOT_FID;
+        let mut chunks = names.chunks(MAX_WELEM);
+        // walking without names clones the fid, which we need for the root
+        let mut next = Some(chunks.next().unwrap_or(&[]));
+        while let Some(chunk) = next {
+            let req = Request::new(&mut self.buf, TWALK, TAG)
+                .u32(from)
+                .u32(fid)
+                .u16(chunk.len() as u16);
+            chunk.iter().fold(req, |req, n| req.str(n));
+
+            // the server stops at the first component that does not exist
+            let res = self.transact(TWALK).and_then(|mut reply| reply.u16());
+            let res = match res {
+                Ok(nwqid) if nwqid as usize != chunk.len() => Err(Error::new(Code::NoSuchFile)),
+                res => res.map(|_| ()),
+            };
+            if let Err(e) = res {
+                // the fid is only valid if a previous walk succeeded
+                if from == fid {
+                    self.clunk(fid).ok();
+                }
+                else {
+                    self.free_fids.push(fid);
+                }
+                return Err(e);
+            }
+
+            from = fid;
+            next = chunks.next();
+        }
+        Ok(fid)
+    }
+
+    /// Walks to `path`, calls `func` with the fid, and releases the fid afterwards
+    pub fn with_walk<F, R>(&mut self, path: &str, func: F) -> Result<R, Error>
+    where
+        F: FnOnce(&mut Self, Fid) -> Result<R, Error>,
+    {
+        let fid = self.walk(path)?;
+        let res = func(self, fid);
+        self.clunk(fid).ok();
+        res
+    }
+
+    /// Releases the given fid
+    pub fn clunk(&mut self, fid: Fid) -> Result<(), Error> {
+        Request::new(&mut self.buf, TCLUNK, TAG).u32(fid);
+        let res = self.transact(TCLUNK).map(|_| ());
+        // the fid is released even if the request failed
+        self.free_fids.push(fid);
+        res
+    }
+
+    /// Opens the file behind `fid` with given flags
+    pub fn lopen(&mut self, fid: Fid, flags: u32) -> Result<Qid, Error> {
+        Request::new(&mut self.buf, TLOPEN, TAG).u32(fid).u32(flags);
+        self.transact(TLOPEN)?.qid()
+    }
+
+    /// Creates and opens the file `name` in the directory `fid`, which refers to the new file
+    /// afterwards
+    pub fn lcreate(
+        &mut self,
+        fid: Fid,
+        name: &str,
+        flags: u32,
+        mode: FileMode,
+    ) -> Result<Qid, Error> {
+        Request::new(&mut self.buf, TLCREATE, TAG)
+            .u32(fid)
+            .str(name)
+            .u32(flags)
+            .u32((mode & FileMode::PERM).bits() as u32)
+            .u32(0);
+        self.transact(TLCREATE)?.qid()
+    }
+
+    /// Reads up to `count` bytes at `off` from the opened file `fid`
+    pub fn read(&mut self, fid: Fid, off: u64, count: usize) -> Result<&[u8], Error> {
+        let count = count.min(self.iounit());
+        Request::new(&mut self.buf, TREAD, TAG)
+            .u32(fid)
+            .u64(off)
+            .u32(count as u32);
+        let mut reply = self.transact(TREAD)?;
+        let len = reply.u32()? as usize;
+        reply.bytes(len)
+    }
+
+    /// Writes `data` at `off` to the opened file `fid` and returns the number of written bytes
+    pub fn write(&mut self, fid: Fid, off: u64, data: &[u8]) -> Result<usize, Error> {
+        let data = &data[0..data.len().min(self.iounit())];
+        Request::new(&mut self.buf, TWRITE, TAG)
+            .u32(fid)
+            .u64(off)
+            .u32(data.len() as u32)
+            .bytes(data);
+        Ok(self.transact(TWRITE)?.u32()? as usize)
+    }
+
+    /// Reads the directory entries of the opened directory `fid`, starting at `off`
+    pub fn readdir(&mut self, fid: Fid, off: u64) -> Result<Vec<DirEntry>, Error> {
+        let count = self.iounit();
+        Request::new(&mut self.buf, TREADDIR, TAG)
+            .u32(fid)
+            .u64(off)
+            .u32(count as u32);
+        let mut reply = self.transact(TREADDIR)?;
+        let len = reply.u32()? as usize;
+        let mut data = Reply {
+            buf: reply.bytes(len)?,
+            pos: 0,
+        };
+
+        let mut entries = Vec::new();
+        while data.pos < len {
+            let qid = data.qid()?;
+            let offset = data.u64()?;
+            let _ty = data.u8()?;
+            let name = data.str()?.to_string();
+            entries.push(DirEntry { qid, offset, name });
+        }
+        Ok(entries)
+    }
+
+    /// Retrieves the attributes of `fid`
+    pub fn getattr(&mut self, fid: Fid) -> Result<FileInfo, Error> {
+        Request::new(&mut self.buf, TGETATTR, TAG)
+            .u32(fid)
+            .u64(GETATTR_BASIC);
+        let mut reply = self.transact(TGETATTR)?;
+
+        let _valid = reply.u64()?;
+        let qid = reply.qid()?;
+        let mode = reply.u32()?;
+        let uid = reply.u32()?;
+        let gid = reply.u32()?;
+        let nlink = reply.u64()?;
+        let _rdev = reply.u64()?;
+        let size = reply.u64()?;
+        let blksize = reply.u64()?;
+        let blocks = reply.u64()?;
+        let atime = reply.u64()?;
+        let _atime_ns = reply.u64()?;
+        let mtime = reply.u64()?;
+        let _mtime_ns = reply.u64()?;
+        let ctime = reply.u64()?;
+
+        Ok(FileInfo {
+            devno: 0,
+            inode: qid.path as u32,
+            mode: FileMode::from_bits_truncate(mode as u16),
+            links: nlink as u32,
+            uid: uid as u16,
+            gid: gid as u16,
+            size: size as usize,
+            lastaccess: atime as u32,
+            lastmod: mtime as u32,
+            lastchange: ctime as u32,
+            blocksize: blksize as u32,
+            // the host counts in units of 512 bytes
+            blocks: ((blocks * 512) / blksize.max(1)) as u32,
+            extents: 0,
+            firstblock: 0,
+        })
+    }
+
+    fn setattr(
+        &mut self,
+        fid: Fid,
+        valid: u32,
+        size: u64,
+        atime: u64,
+        mtime: u64,
+    ) -> Result<(), Error> {
+        Request::new(&mut self.buf, TSETATTR, TAG)
+            .u32(fid)
+            .u32(valid)
+            .u32(0)
+            .u32(0)
+            .u32(0)
+            .u64(size)
+            .u64(atime)
+            .u64(0)
+            .u64(mtime)
+            .u64(0);
+        self.transact(TSETATTR).map(|_| ())
+    }
+
+    /// Sets the size of `fid` to `size`
+    pub fn truncate(&mut self, fid: Fid, size: usize) -> Result<(), Error> {
+        self.setattr(fid, SETATTR_SIZE, size as u64, 0, 0)
+    }
+
+    /// Sets the access and modification times (in seconds since the Unix epoch) of `fid`
+    pub fn set_times(&mut self, fid: Fid, atime: u32, mtime: u32) -> Result<(), Error> {
+        let valid = SETATTR_ATIME | SETATTR_MTIME | SETATTR_ATIME_SET | SETATTR_MTIME_SET;
+        self.setattr(fid, valid, 0, atime as u64, mtime as u64)
+    }
+
+    /// Writes all modifications of `fid` to the storage of the host
+    pub fn fsync(&mut self, fid: Fid) -> Result<(), Error> {
+        Request::new(&mut self.buf, TFSYNC, TAG).u32(fid).u32(0);
+        self.transact(TFSYNC).map(|_| ())
+    }
+
+    /// Creates the directory `name` in the directory `dfid`
+    pub fn mkdir(&mut self, dfid: Fid, name: &str, mode: FileMode) -> Result<(), Error> {
+        Request::new(&mut self.buf, TMKDIR, TAG)
+            .u32(dfid)
+            .str(name)
+            .u32((mode & FileMode::PERM).bits() as u32)
+            .u32(0);
+        self.transact(TMKDIR).map(|_| ())
+    }
+
+    /// Removes the entry `name` from the directory `dfid`
+    pub fn unlinkat(&mut self, dfid: Fid, name: &str, flags: u32) -> Result<(), Error> {
+        Request::new(&mut self.buf, TUNLINKAT, TAG)
+            .u32(dfid)
+            .str(name)
+            .u32(flags);
+        self.transact(TUNLINKAT).map(|_| ())
+    }
+
+    /// Creates the hard link `name` in the directory `dfid` to the file `fid`
+    pub fn link(&mut self, dfid: Fid, fid: Fid, name: &str) -> Result<(), Error> {
+        Request::new(&mut self.buf, TLINK, TAG)
+            .u32(dfid)
+            .u32(fid)
+            .str(name);
+        self.transact(TLINK).map(|_| ())
+    }
+
+    /// Renames `old_name` in directory `old_dfid` to `new_name` in directory `new_dfid`
+    pub fn renameat(
+        &mut self,
+        old_dfid: Fid,
+        old_name: &str,
+        new_dfid: Fid,
+        new_name: &str,
+    ) -> Result<(), Error> {
+        Request::new(&mut self.buf, TRENAMEAT, TAG)
+            .u32(old_dfid)
+            .str(old_name)
+            .u32(new_dfid)
+            .str(new_name);
+        self.transact(TRENAMEAT).map(|_| ())
+    }
+}
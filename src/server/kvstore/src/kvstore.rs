@@ -0,0 +1,257 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! A persistent key-value store
+//!
+//! kvstore offers a simple alternative to a file system for applications that need to store small
+//! amounts of configuration or state. The data is kept in a log file on a file system that has been
+//! mounted for kvstore, typically m3fs. Values are transferred via a memory region that each client
+//! provides via the EP obtained by `GetMem`.
+
+#![no_std]
+
+mod store;
+
+use m3::cell::LazyStaticRefCell;
+use m3::client::{KV_MAX_KEY_LEN, KV_MAX_VALUE_LEN};
+use m3::col::{String, ToString, Vec};
+use m3::com::{opcodes, EpMng, GateIStream, EP};
+use m3::env;
+use m3::errors::{Code, Error};
+use m3::io::LogFlags;
+use m3::kif::{CapRngDesc, CapType};
+use m3::log;
+use m3::println;
+use m3::reply_vmsg;
+use m3::server::{
+    CapExchange, ClientManager, ExcType, RequestHandler, RequestSession, Server, ServerSession,
+    SessId, DEF_MAX_CLIENTS,
+};
+use m3::tcu::{EpId, TCU};
+use m3::tiles::OwnActivity;
+use m3::vec;
+
+use store::Store;
+
+/// The message size is larger than usual, because requests contain up to two keys
+const MSG_SIZE: usize = 512;
+
+static STORE: LazyStaticRefCell<Store> = LazyStaticRefCell::default();
+static BUF: LazyStaticRefCell<Vec<u8>> = LazyStaticRefCell::default();
+
+struct KvSession {
+    serv: ServerSession,
+    mem: Option<EP>,
+}
+
+impl RequestSession for KvSession {
+    fn new(serv: ServerSession, _arg: &str) -> Result<Self, Error>
+    where
+        Self: Sized,
+    {
+        log!(LogFlags::KVReqs, "[{}] kv::open()", serv.id());
+        Ok(Self { serv, mem: None })
+    }
+
+    fn close(&mut self, _cli: &mut ClientManager<Self>, sid: SessId, _sub_ids: &mut Vec<SessId>)
+    where
+        Self: Sized,
+    {
+        log!(LogFlags::KVReqs, "[{}] kv::close()", sid);
+
+        // Revoke EP capability that was delegated for memory accesses
+        if let Some(ep) = self.mem.take() {
+            EpMng::get().release(ep, true);
+        }
+    }
+}
+
+fn check_key(key: &str) -> Result<(), Error> {
+    if key.is_empty() || key.len() > KV_MAX_KEY_LEN {
+        return Err(Error::new(Code::InvArgs));
+    }
+    Ok(())
+}
+
+impl KvSession {
+    fn get_mem(
+        cli: &mut ClientManager<Self>,
+        _crt: usize,
+        sid: SessId,
+        xchg: &mut CapExchange<'_>,
+    ) -> Result<(), Error> {
+        log!(LogFlags::KVReqs, "[{}] kv::get_mem()", sid);
+        let sess = cli.get_mut(sid).ok_or_else(|| Error::new(Code::InvArgs))?;
+
+        if sess.mem.is_some() {
+            return Err(Error::new(Code::Exists));
+        }
+
+        let ep = EpMng::get().acquire(0)?;
+        let ep_sel = ep.sel();
+        sess.mem = Some(ep);
+        xchg.out_caps(CapRngDesc::new(CapType::Object, ep_sel, 1));
+
+        Ok(())
+    }
+
+    fn epid(&self) -> Result<EpId, Error> {
+        self.mem
+            .as_ref()
+            .map(|ep| ep.id())
+            .ok_or_else(|| Error::new(Code::InvState))
+    }
+
+    fn get(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let key: &str = is.pop()?;
+
+        log!(
+            LogFlags::KVReqs,
+            "[{}] kv::get(key={})",
+            self.serv.id(),
+            key
+        );
+
+        let store = STORE.borrow();
+        let value = store.get(key).ok_or_else(|| Error::new(Code::NotFound))?;
+        TCU::write(self.epid()?, value.as_ptr(), value.len(), 0)?;
+
+        reply_vmsg!(is, Code::Success, value.len())
+    }
+
+    fn put(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let key: &str = is.pop()?;
+        let len: usize = is.pop()?;
+
+        log!(
+            LogFlags::KVReqs,
+            "[{}] kv::put(key={}, len={})",
+            self.serv.id(),
+            key,
+            len
+        );
+
+        check_key(key)?;
+        if len > KV_MAX_VALUE_LEN {
+            return Err(Error::new(Code::InvArgs));
+        }
+
+        let mut buf = BUF.borrow_mut();
+        TCU::read(self.epid()?, buf.as_mut_ptr(), len, 0)?;
+        STORE.borrow_mut().put(key, &buf[0..len])?;
+
+        is.reply_error(Code::Success)
+    }
+
+    fn delete(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let key: &str = is.pop()?;
+
+        log!(
+            LogFlags::KVReqs,
+            "[{}] kv::delete(key={})",
+            self.serv.id(),
+            key
+        );
+
+        STORE.borrow_mut().delete(key)?;
+
+        is.reply_error(Code::Success)
+    }
+
+    fn scan(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let prefix: &str = is.pop()?;
+        let after: &str = is.pop()?;
+
+        log!(
+            LogFlags::KVReqs,
+            "[{}] kv::scan(prefix={}, after={})",
+            self.serv.id(),
+            prefix,
+            after
+        );
+
+        // write as many keys as fit into the client's buffer, each preceded by its length
+        let mut buf = BUF.borrow_mut();
+        let store = STORE.borrow();
+        let mut len = 0;
+        let mut more = false;
+        for key in store.scan(prefix, after) {
+            if len + 1 + key.len() > buf.len() {
+                more = true;
+                break;
+            }
+            buf[len] = key.len() as u8;
+            buf[len + 1..len + 1 + key.len()].copy_from_slice(key.as_bytes());
+            len += 1 + key.len();
+        }
+        TCU::write(self.epid()?, buf.as_ptr(), len, 0)?;
+
+        reply_vmsg!(is, Code::Success, len, more)
+    }
+}
+
+fn usage() -> ! {
+    println!("Usage: {} [-n <name>] <file>", env::args().next().unwrap());
+    println!();
+    println!("  -n: the name of the service (kvstore by default)");
+    println!();
+    println!("Stores key-value pairs persistently in a log in <file>.");
+    OwnActivity::exit_with(Code::InvArgs);
+}
+
+fn parse_args() -> Result<(String, String), String> {
+    let mut name = String::from("kvstore");
+
+    let args: Vec<&str> = env::args().collect();
+    let mut i = 1;
+    while i + 1 < args.len() {
+        match args[i] {
+            "-n" => name = args[i + 1].to_string(),
+            _ => break,
+        }
+        i += 2;
+    }
+
+    if i + 1 != args.len() {
+        return Err(String::from("Expected exactly one file"));
+    }
+    Ok((name, args[i].to_string()))
+}
+
+#[no_mangle]
+pub fn main() -> Result<(), Error> {
+    let (name, path) = parse_args().unwrap_or_else(|e| {
+        println!("Invalid arguments: {}", e);
+        usage();
+    });
+
+    STORE.set(Store::open(&path).expect("Unable to open key-value log"));
+    BUF.set(vec![0u8; KV_MAX_VALUE_LEN]);
+
+    let mut hdl = RequestHandler::new_with(DEF_MAX_CLIENTS, MSG_SIZE, 1)
+        .expect("Unable to create request handler");
+    let mut srv = Server::new(&name, &mut hdl).expect("Unable to create service");
+
+    use opcodes::KeyValue;
+    hdl.reg_cap_handler(KeyValue::GetMem, ExcType::Obt(1), KvSession::get_mem);
+    hdl.reg_msg_handler(KeyValue::Get, KvSession::get);
+    hdl.reg_msg_handler(KeyValue::Put, KvSession::put);
+    hdl.reg_msg_handler(KeyValue::Delete, KvSession::delete);
+    hdl.reg_msg_handler(KeyValue::Scan, KvSession::scan);
+
+    hdl.run(&mut srv).expect("Server loop failed");
+
+    Ok(())
+}
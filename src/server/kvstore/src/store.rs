@@ -0,0 +1,304 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use core::mem;
+use core::ops::Bound;
+
+use m3::col::{BTreeMap, String, ToString, Vec};
+use m3::errors::{Code, Error};
+use m3::format;
+use m3::io::{LogFlags, Read, Write};
+use m3::log;
+use m3::vfs::{File, FileRef, GenericFile, OpenFlags, Seek, SeekMode, VFS};
+
+/// The magic number at the beginning of the log
+const MAGIC: &[u8; 8] = b"M3KVLOG1";
+
+// each record starts with the checksum of the remaining record, the kind, the key length, and the
+// value length, followed by the key and the value
+const REC_HDR_SIZE: usize = 4 + 1 + 1 + 2;
+const REC_PUT: u8 = 1;
+const REC_DELETE: u8 = 2;
+
+/// The log is not compacted below this size
+const COMPACT_MIN_SIZE: usize = 64 * 1024;
+
+/// Computes the CRC-32 (IEEE 802.3) of the given data
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for b in data {
+        crc ^= *b as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+fn record_size(key: &str, value: &[u8]) -> usize {
+    REC_HDR_SIZE + key.len() + value.len()
+}
+
+fn encode_record(buf: &mut Vec<u8>, kind: u8, key: &str, value: &[u8]) {
+    let start = buf.len();
+    buf.extend_from_slice(&[0u8; 4]);
+    buf.push(kind);
+    buf.push(key.len() as u8);
+    buf.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    buf.extend_from_slice(key.as_bytes());
+    buf.extend_from_slice(value);
+
+    let crc = crc32(&buf[start + 4..]);
+    buf[start..start + 4].copy_from_slice(&crc.to_le_bytes());
+}
+
+/// A record that has been read from the log
+struct Record<'d> {
+    kind: u8,
+    key: &'d str,
+    value: &'d [u8],
+}
+
+/// Decodes the record at the start of `data` and returns it together with its size or `None` if
+/// there is no complete and valid record
+fn decode_record(data: &[u8]) -> Option<(Record<'_>, usize)> {
+    if data.len() < REC_HDR_SIZE {
+        return None;
+    }
+
+    let klen = data[5] as usize;
+    let vlen = u16::from_le_bytes([data[6], data[7]]) as usize;
+    let size = REC_HDR_SIZE + klen + vlen;
+    if data.len() < size {
+        return None;
+    }
+
+    let crc = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+    if crc32(&data[4..size]) != crc {
+        return None;
+    }
+
+    let key = core::str::from_utf8(&data[REC_HDR_SIZE..REC_HDR_SIZE + klen]).ok()?;
+    let value = &data[REC_HDR_SIZE + klen..size];
+    Some((
+        Record {
+            kind: data[4],
+            key,
+            value,
+        },
+        size,
+    ))
+}
+
+/// The persistent key-value store
+///
+/// All modifications are appended to a log file, which is replayed on startup to rebuild the
+/// in-memory index. Each record is protected by a checksum so that a record that has only been
+/// written partially (e.g., due to a crash) is detected and discarded together with everything
+/// behind it.
+///
+/// If the log contains mostly outdated records, it is compacted by writing the current content to
+/// a temporary file and renaming it over the log afterwards. Since the rename replaces the log
+/// atomically, a crash during compaction leaves either the old or the new log behind.
+pub struct Store {
+    path: String,
+    file: FileRef<GenericFile>,
+    map: BTreeMap<String, Vec<u8>>,
+    // the total size of the log in bytes
+    log_size: usize,
+    // the size of the records that are still needed
+    live_size: usize,
+}
+
+impl Store {
+    pub fn open(path: &str) -> Result<Self, Error> {
+        // a left over temporary file stems from an incomplete compaction, which never replaced
+        // the log; thus, we can simply throw it away
+        let tmp = Self::tmp_path(path);
+        if VFS::unlink(&tmp).is_ok() {
+            log!(
+                LogFlags::KVLog,
+                "log: removed incomplete compaction {}",
+                tmp
+            );
+        }
+
+        let mut file = VFS::open(path, OpenFlags::RW | OpenFlags::CREATE)?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+
+        let mut store = Self {
+            path: path.to_string(),
+            file,
+            map: BTreeMap::new(),
+            log_size: MAGIC.len(),
+            live_size: 0,
+        };
+
+        if data.is_empty() {
+            store.file.write_all(MAGIC)?;
+            store.file.sync()?;
+            return Ok(store);
+        }
+        if !data.starts_with(MAGIC) {
+            log!(LogFlags::Error, "log: {} is no key-value log", path);
+            return Err(Error::new(Code::InvArgs));
+        }
+
+        store.replay(&data[MAGIC.len()..]);
+        if store.log_size < data.len() {
+            log!(
+                LogFlags::Error,
+                "log: discarding {} bytes of incomplete or corrupt records",
+                data.len() - store.log_size
+            );
+            store.file.truncate(store.log_size)?;
+            store.file.seek(store.log_size, SeekMode::Set)?;
+        }
+
+        log!(
+            LogFlags::KVLog,
+            "log: replayed {} bytes with {} keys ({} bytes live)",
+            store.log_size,
+            store.map.len(),
+            store.live_size
+        );
+        Ok(store)
+    }
+
+    fn tmp_path(path: &str) -> String {
+        format!("{}.tmp", path)
+    }
+
+    fn replay(&mut self, mut data: &[u8]) {
+        while let Some((rec, size)) = decode_record(data) {
+            match rec.kind {
+                REC_PUT => self.apply_put(rec.key, rec.value.to_vec()),
+                REC_DELETE => self.apply_delete(rec.key),
+                _ => break,
+            }
+            self.log_size += size;
+            data = &data[size..];
+        }
+    }
+
+    fn apply_put(&mut self, key: &str, value: Vec<u8>) {
+        self.live_size += record_size(key, &value);
+        if let Some(old) = self.map.insert(key.to_string(), value) {
+            self.live_size -= record_size(key, &old);
+        }
+    }
+
+    fn apply_delete(&mut self, key: &str) {
+        if let Some(old) = self.map.remove(key) {
+            self.live_size -= record_size(key, &old);
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&[u8]> {
+        self.map.get(key).map(|v| v.as_slice())
+    }
+
+    /// Returns the keys that start with `prefix` and are larger than `after`
+    pub fn scan<'s>(&'s self, prefix: &'s str, after: &'s str) -> impl Iterator<Item = &'s str> {
+        let start = if after < prefix {
+            Bound::Included(prefix)
+        }
+        else {
+            Bound::Excluded(after)
+        };
+        self.map
+            .range::<str, _>((start, Bound::Unbounded))
+            .map(|(k, _)| k.as_str())
+            .take_while(move |k| k.starts_with(prefix))
+    }
+
+    pub fn put(&mut self, key: &str, value: &[u8]) -> Result<(), Error> {
+        self.append(REC_PUT, key, value)?;
+        self.apply_put(key, value.to_vec());
+        self.maybe_compact();
+        Ok(())
+    }
+
+    pub fn delete(&mut self, key: &str) -> Result<(), Error> {
+        if !self.map.contains_key(key) {
+            return Err(Error::new(Code::NotFound));
+        }
+
+        self.append(REC_DELETE, key, &[])?;
+        self.apply_delete(key);
+        self.maybe_compact();
+        Ok(())
+    }
+
+    fn append(&mut self, kind: u8, key: &str, value: &[u8]) -> Result<(), Error> {
+        let mut rec = Vec::with_capacity(record_size(key, value));
+        encode_record(&mut rec, kind, key, value);
+
+        self.file.write_all(&rec)?;
+        // the modification is only acknowledged after it has been made persistent
+        self.file.sync()?;
+        self.log_size += rec.len();
+        Ok(())
+    }
+
+    fn maybe_compact(&mut self) {
+        let needed = MAGIC.len() + self.live_size;
+        if self.log_size >= COMPACT_MIN_SIZE && self.log_size > needed * 2 {
+            // the modification is already persistent; we just try again next time
+            if let Err(e) = self.compact() {
+                log!(LogFlags::Error, "log: compaction failed: {:?}", e);
+            }
+        }
+    }
+
+    fn compact(&mut self) -> Result<(), Error> {
+        log!(
+            LogFlags::KVLog,
+            "log: compacting {} bytes into {} bytes",
+            self.log_size,
+            MAGIC.len() + self.live_size
+        );
+
+        let tmp = Self::tmp_path(&self.path);
+        let mut file = VFS::open(&tmp, OpenFlags::W | OpenFlags::CREATE | OpenFlags::TRUNC)?;
+
+        let mut buf = Vec::with_capacity(COMPACT_MIN_SIZE);
+        buf.extend_from_slice(MAGIC);
+        for (k, v) in &self.map {
+            encode_record(&mut buf, REC_PUT, k, v);
+            if buf.len() >= COMPACT_MIN_SIZE {
+                file.write_all(&buf)?;
+                buf.clear();
+            }
+        }
+        file.write_all(&buf)?;
+        file.sync()?;
+
+        // continue with the new log, which is already positioned at the end. We close the old log
+        // before the rename, because it will be deleted by the rename.
+        drop(mem::replace(&mut self.file, file));
+        if let Err(e) = VFS::rename(&tmp, &self.path) {
+            // go back to the old log; the temporary file would be removed on the next start
+            self.file = VFS::open(&self.path, OpenFlags::W)?;
+            self.file.seek(0, SeekMode::End)?;
+            VFS::unlink(&tmp).ok();
+            return Err(e);
+        }
+
+        self.log_size = MAGIC.len() + self.live_size;
+        Ok(())
+    }
+}
@@ -109,6 +109,26 @@ impl Backend for DiskBackend {
         Ok(())
     }
 
+    fn load_block(&self, dst: &mut [u8], bno: BlockNo) -> Result<(), Error> {
+        let off = crate::buf::META_BUFFER_SIZE * (self.blocksize + PRDT_SIZE);
+        self.disk
+            .read(0, BlockRange::new(bno), self.blocksize, Some(off as u64))?;
+        self.metabuf
+            .as_ref()
+            .unwrap()
+            .read_bytes(dst.as_mut_ptr(), self.blocksize, off as u64)
+    }
+
+    fn store_block(&self, src: &[u8], bno: BlockNo) -> Result<(), Error> {
+        let off = crate::buf::META_BUFFER_SIZE * (self.blocksize + PRDT_SIZE);
+        self.metabuf
+            .as_ref()
+            .unwrap()
+            .write_bytes(src.as_ptr(), self.blocksize, off as u64)?;
+        self.disk
+            .write(0, BlockRange::new(bno), self.blocksize, Some(off as u64))
+    }
+
     fn sync_meta(&self, block: &mut MetaBufferBlock) -> Result<(), Error> {
         // check if there is a filebuffer entry for it or create one
         let msel = SelSpace::get().alloc_sel();
@@ -177,9 +197,10 @@ impl Backend for DiskBackend {
         self.disk.read(0, BlockRange::new(0), 512, None)?;
         let super_block = tmp.activate()?.read_obj::<SuperBlock>(0)?;
 
-        // use separate transfer buffer for each entry to allow parallel disk requests
+        // use separate transfer buffer for each entry to allow parallel disk requests and one
+        // additional buffer for blocks that are not part of the meta buffer (e.g., the journal)
         self.blocksize = super_block.block_size as usize;
-        let size = (self.blocksize + PRDT_SIZE) * crate::buf::META_BUFFER_SIZE;
+        let size = (self.blocksize + PRDT_SIZE) * (crate::buf::META_BUFFER_SIZE + 1);
         self.metabuf = Some(MemGate::new(size as GlobOff, Perm::RW)?);
         // separate MemGate for the same reason as above
         self.metabuf_disk = Some(self.metabuf.as_ref().unwrap().derive_cap(
@@ -196,6 +217,9 @@ impl Backend for DiskBackend {
 
     fn store_sb(&self, super_block: &SuperBlock) -> Result<(), Error> {
         self.metabuf.as_ref().unwrap().write_obj(super_block, 0)?;
-        self.disk.write(0, BlockRange::new(0), 512, None)
+        self.disk.write(0, BlockRange::new(0), 512, None)?;
+        // the superblock is stored on syncs; make sure that everything reaches the disk in case
+        // the disk service caches writes
+        self.disk.flush()
     }
 }
@@ -16,13 +16,15 @@
 
 use crate::backend::{Backend, SuperBlock};
 use crate::buf::{LoadLimit, MetaBufferBlock};
-use crate::data::{BlockNo, BlockRange, Extent};
+use crate::data::{Bitmap, BlockNo, BlockRange, Extent, INode, Time, DIR_ENTRY_LEN, FS_VERSION};
 
 use m3::cap::Selector;
 use m3::com::{MemCap, MemGate, Perm};
-use m3::errors::Error;
+use m3::errors::{Code, Error};
 use m3::mem::GlobOff;
 use m3::syscalls::derive_mem;
+use m3::time::SystemTime;
+use m3::vfs::FileMode;
 
 use thread::Event;
 
@@ -39,6 +41,104 @@ impl MemBackend {
             blocksize: 0, // gets set when the superblock is read
         }
     }
+
+    /// Creates a new memory backend with an empty file system of `size` bytes
+    ///
+    /// In contrast to [`MemBackend::new`], the file system is not loaded from a boot module, but
+    /// created in newly allocated memory. Hence, the file system is not persistent.
+    pub fn new_empty(size: usize) -> Result<Self, Error> {
+        const BLOCK_SIZE: u32 = 4096;
+
+        let total_blocks = (size / BLOCK_SIZE as usize) as u32;
+        let mut sb = SuperBlock {
+            block_size: BLOCK_SIZE,
+            // one inode per four blocks should be plenty for scratch space
+            total_inodes: (total_blocks / 4).max(1),
+            total_blocks,
+            free_inodes: 0,
+            free_blocks: 0,
+            first_free_inode: 0,
+            first_free_block: 0,
+            checksum: 0,
+            version: FS_VERSION,
+            // the journal is not used without persistence
+            journal_blocks: 0,
+        };
+
+        // the first data block holds the root directory
+        let root_block = sb.first_data_block();
+        if root_block >= total_blocks {
+            return Err(Error::new(Code::NoSpace));
+        }
+
+        let backend = MemBackend {
+            mem: MemGate::new(total_blocks as GlobOff * BLOCK_SIZE as GlobOff, Perm::RW)?,
+            blocksize: BLOCK_SIZE as usize,
+        };
+
+        // start with zeroed metadata (bitmaps and inodes)
+        let zeros = vec![0u8; backend.blocksize];
+        for bno in 0..root_block {
+            backend.store_block(&zeros, bno)?;
+        }
+
+        // allocate the root inode and all blocks up to the root directory
+        let mut bits = zeros.clone();
+        Bitmap::from_bytes(&mut bits).set_bit(0);
+        backend.store_block(&bits, sb.first_inodebm_block())?;
+
+        let bits_per_block = backend.blocksize * 8;
+        let used_blocks = root_block as usize + 1;
+        for (i, first) in (0..used_blocks).step_by(bits_per_block).enumerate() {
+            let mut bits = zeros.clone();
+            let mut bitmap = Bitmap::from_bytes(&mut bits);
+            for bit in 0..(used_blocks - first).min(bits_per_block) {
+                bitmap.set_bit(bit);
+            }
+            backend.store_block(&bits, sb.first_blockbm_block() + i as BlockNo)?;
+        }
+
+        // like /tmp, the root directory is writable for everyone
+        let now = SystemTime::now().as_unix_secs() as Time;
+        let mut root = INode::new(0, FileMode::IFDIR | FileMode::PERM);
+        root.links = 2;
+        root.lastaccess = now;
+        root.lastmod = now;
+        root.lastchange = now;
+        root.size = BLOCK_SIZE as u64;
+        root.extents = 1;
+        root.direct[0] = Extent {
+            start: root_block,
+            length: 1,
+        };
+        backend.mem.write_obj(
+            &root,
+            sb.first_inode_block() as GlobOff * BLOCK_SIZE as GlobOff,
+        )?;
+
+        // the root directory contains "." and "..", both referring to itself; the last entry
+        // spans the remaining block
+        let mut dir = zeros;
+        let dot_len = DIR_ENTRY_LEN + 4;
+        for (off, name, next) in [
+            (0, ".", dot_len),
+            (dot_len, "..", backend.blocksize - dot_len),
+        ] {
+            dir[off..off + 4].copy_from_slice(&0u32.to_le_bytes());
+            dir[off + 4..off + 8].copy_from_slice(&(name.len() as u32).to_le_bytes());
+            dir[off + 8..off + 12].copy_from_slice(&(next as u32).to_le_bytes());
+            dir[off + DIR_ENTRY_LEN..off + DIR_ENTRY_LEN + name.len()]
+                .copy_from_slice(name.as_bytes());
+        }
+        backend.store_block(&dir, root_block)?;
+
+        sb.update_inodebm(sb.total_inodes - 1, 1);
+        sb.update_blockbm(total_blocks - used_blocks as u32, used_blocks as u32);
+        sb.checksum = sb.get_checksum();
+        backend.store_sb(&sb)?;
+
+        Ok(backend)
+    }
 }
 
 impl Backend for MemBackend {
@@ -84,6 +184,19 @@ impl Backend for MemBackend {
         Ok(())
     }
 
+    fn load_block(&self, dst: &mut [u8], bno: BlockNo) -> Result<(), Error> {
+        self.mem.read_bytes(
+            dst.as_mut_ptr(),
+            self.blocksize,
+            (bno as usize * self.blocksize) as u64,
+        )
+    }
+
+    fn store_block(&self, src: &[u8], bno: BlockNo) -> Result<(), Error> {
+        self.mem
+            .write(&src[..self.blocksize], bno as u64 * self.blocksize as u64)
+    }
+
     fn sync_meta(&self, _block: &mut MetaBufferBlock) -> Result<(), Error> {
         // nothing to do here
         Ok(())
@@ -56,6 +56,10 @@ pub trait Backend {
 
     fn store_data(&self, blocks: BlockRange, unlock: Event) -> Result<(), Error>;
 
+    fn load_block(&self, dst: &mut [u8], bno: BlockNo) -> Result<(), Error>;
+
+    fn store_block(&self, src: &[u8], bno: BlockNo) -> Result<(), Error>;
+
     fn sync_meta(&self, block: &mut MetaBufferBlock) -> Result<(), Error>;
 
     fn get_filedata(
@@ -0,0 +1,65 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! Read-ahead and write-behind for the file buffer.
+//!
+//! The jobs are performed by a separate thread, which is scheduled by the server loop whenever it
+//! has handled the pending requests. Thereby, the disk is kept busy while clients work on the
+//! data they already obtained, instead of loading and storing blocks only on demand.
+
+use m3::cell::StaticCell;
+use m3::io::LogFlags;
+use m3::mem::VirtAddr;
+
+use thread::Event;
+
+static WORK: StaticCell<Event> = StaticCell::new(0);
+
+/// Starts the background thread
+pub fn start() {
+    thread::add_thread(VirtAddr::from(worker as *const ()), 0);
+
+    // events are only unique if there are other threads, so allocate it afterwards
+    WORK.set(thread::alloc_event());
+
+    // let the thread run until it waits for work
+    thread::wait_for(WORK.get());
+}
+
+/// Lets the background thread perform the next job, if there is any
+pub fn run() {
+    if pending() {
+        thread::notify(WORK.get(), None);
+        thread::try_yield();
+    }
+}
+
+/// Returns true if there are jobs for the background thread
+pub fn pending() -> bool {
+    crate::file_buffer_mut().has_jobs()
+}
+
+fn worker(_arg: usize) {
+    // the main thread waits for us to start
+    thread::notify(WORK.get(), None);
+
+    loop {
+        thread::wait_for(WORK.get());
+
+        if let Err(e) = crate::file_buffer_mut().run_job() {
+            log!(LogFlags::Error, "background job failed: {:?}", e);
+        }
+    }
+}
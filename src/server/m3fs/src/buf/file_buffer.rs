@@ -24,7 +24,7 @@ use core::ptr::NonNull;
 
 use m3::boxed::Box;
 use m3::cap::Selector;
-use m3::col::{BoxList, Treap};
+use m3::col::{BoxList, Treap, VecDeque};
 use m3::com::{MemCap, Perm};
 use m3::errors::Error;
 use m3::io::LogFlags;
@@ -44,6 +44,8 @@ pub struct FileBufferEntry {
 
     locked: bool,
     dirty: bool,
+    // the number of clients that got write access and did not release it yet
+    writers: usize,
     unlock: Event,
 }
 
@@ -61,6 +63,7 @@ impl FileBufferEntry {
 
             locked: true,
             dirty: false,
+            writers: 0,
             unlock: thread::alloc_event(),
         })
     }
@@ -113,9 +116,20 @@ impl fmt::Debug for LoadLimit {
     }
 }
 
+/// A job that is performed in the background, see [`crate::buf::background`]
+enum Job {
+    /// Load the given blocks into the buffer
+    ReadAhead(BlockRange),
+    /// Write the entry containing the given block back
+    WriteBehind(BlockNo),
+}
+
 pub struct FileBuffer {
     size: usize,
 
+    // pending read-ahead and write-behind requests
+    jobs: VecDeque<Job>,
+
     // contains the actual FileBufferEntry objects and keeps them sorted by LRU
     lru: BoxList<FileBufferEntry>,
     // gives us a quick translation from block number to FileBufferEntry
@@ -129,6 +143,8 @@ impl FileBuffer {
         FileBuffer {
             size: 0,
 
+            jobs: VecDeque::new(),
+
             lru: BoxList::new(),
             entries: Treap::new(),
 
@@ -195,7 +211,10 @@ impl FileBuffer {
                         perm,
                     )?;
 
-                    head.dirty |= perm.contains(Perm::W);
+                    if perm.contains(Perm::W) {
+                        head.dirty = true;
+                        head.writers += 1;
+                    }
 
                     return Ok(len * self.block_size);
                 }
@@ -285,7 +304,10 @@ impl FileBuffer {
             perm,
         )?;
 
-        new_head.dirty = perm.contains(Perm::W);
+        if perm.contains(Perm::W) {
+            new_head.dirty = true;
+            new_head.writers = 1;
+        }
 
         // everything went fine, so insert pointer into treap and the object into the LRU list
         let ptr = unsafe { NonNull::new_unchecked(&mut *new_head as *mut _) };
@@ -295,6 +317,99 @@ impl FileBuffer {
         Ok(load_size * self.block_size)
     }
 
+    /// Requests to load the given blocks in the background, because they will probably be read
+    /// soon.
+    pub fn read_ahead(&mut self, blocks: BlockRange) {
+        let count = cmp::min(blocks.count as usize, crate::settings().readahead);
+        if count > 0 && self.entries.get(&BlockRange::new(blocks.start)).is_none() {
+            let blocks = BlockRange::new_range(blocks.start, count as BlockNo);
+            log!(LogFlags::FSBuf, "filebuffer: read ahead <{:?}>", blocks);
+            self.jobs.push_back(Job::ReadAhead(blocks));
+        }
+    }
+
+    /// Releases the write access to the entry containing block `bno` that has been obtained via
+    /// [`FileBuffer::get_extent`].
+    ///
+    /// As soon as no client can write to the entry anymore, it is written back in the background.
+    pub fn release(&mut self, bno: BlockNo) {
+        if let Some(head) = self.entries.get_mut(&BlockRange::new(bno)) {
+            // safety: the entry is owned by the LRU list and we have exclusive access to it
+            let head = unsafe { head.as_mut() };
+            head.writers = head.writers.saturating_sub(1);
+            if head.writers == 0 && head.dirty && crate::settings().writebehind {
+                self.jobs.push_back(Job::WriteBehind(head.blocks.start));
+            }
+        }
+    }
+
+    /// Returns true if there are jobs to perform in the background
+    pub fn has_jobs(&self) -> bool {
+        !self.jobs.is_empty()
+    }
+
+    /// Performs the next job for the background, if there is any
+    pub fn run_job(&mut self) -> Result<(), Error> {
+        match self.jobs.pop_front() {
+            Some(Job::ReadAhead(blocks)) => self.load_ahead(blocks),
+            Some(Job::WriteBehind(bno)) => match self.entries.get_mut(&BlockRange::new(bno)) {
+                // safety: as above
+                Some(head) => {
+                    let head = unsafe { head.as_mut() };
+                    // a client might have obtained write access in the meantime
+                    if head.writers == 0 && !head.locked {
+                        head.flush()?;
+                    }
+                    Ok(())
+                },
+                None => Ok(()),
+            },
+            None => Ok(()),
+        }
+    }
+
+    fn load_ahead(&mut self, blocks: BlockRange) -> Result<(), Error> {
+        // read-ahead should not evict blocks that are in use
+        let mut count = cmp::min(
+            blocks.count as usize,
+            MAX_BUFFERED_BLKS.saturating_sub(self.size),
+        );
+        // stop at the first block that has been buffered in the meantime
+        for i in 0..count {
+            if self
+                .entries
+                .get(&BlockRange::new(blocks.start + i as BlockNo))
+                .is_some()
+            {
+                count = i;
+                break;
+            }
+        }
+        if count == 0 {
+            return Ok(());
+        }
+
+        let mut new_head = Box::new(FileBufferEntry::new(
+            BlockRange::new_range(blocks.start, count as BlockNo),
+            self.block_size,
+        )?);
+
+        log!(
+            LogFlags::FSBuf,
+            "filebuffer: loading blocks <{:?}> ahead",
+            new_head.blocks
+        );
+
+        crate::backend_mut().load_data(&new_head.data, new_head.blocks, true, new_head.unlock)?;
+        new_head.locked = false;
+
+        self.size += count;
+        let ptr = unsafe { NonNull::new_unchecked(&mut *new_head as *mut _) };
+        self.entries.insert(new_head.blocks, ptr);
+        self.lru.push_back(new_head);
+        Ok(())
+    }
+
     pub fn flush(&mut self) -> Result<(), Error> {
         while let Some(mut b) = self.lru.pop_front() {
             self.entries.remove(&b.blocks);
@@ -304,3 +419,179 @@ impl FileBuffer {
         Ok(())
     }
 }
+
+/// Tests for read-ahead and write-behind, which are run on the actual file system via `m3fs -T`
+#[cfg(feature = "tests")]
+pub mod tests {
+    use super::*;
+
+    use m3::cap::SelSpace;
+    use m3::col::Vec;
+    use m3::com::MemGate;
+    use m3::test::WvTester;
+    use m3::{wv_assert, wv_assert_eq, wv_assert_ok, wv_run_test};
+
+    const BLOCKS: usize = 4;
+
+    pub fn run(t: &mut dyn WvTester) {
+        if crate::settings().readahead >= BLOCKS {
+            wv_run_test!(t, read_ahead);
+            wv_run_test!(t, read_ahead_limit);
+            wv_run_test!(t, read_ahead_partial);
+        }
+        if crate::settings().writebehind {
+            wv_run_test!(t, write_behind);
+            wv_run_test!(t, write_behind_writers);
+        }
+    }
+
+    fn alloc_blocks() -> BlockRange {
+        // start with an empty buffer to not depend on previous tests
+        wv_assert_ok!(crate::file_buffer_mut().flush());
+
+        let mut count = BLOCKS;
+        let start = wv_assert_ok!(crate::blocks_mut().alloc(Some(&mut count)));
+        wv_assert_ok!(crate::journal_mut().write_back());
+        BlockRange::new_range(start, count as BlockNo)
+    }
+
+    fn free_blocks(blocks: BlockRange) {
+        wv_assert_ok!(crate::file_buffer_mut().flush());
+        wv_assert_ok!(crate::blocks_mut().free(blocks.start as usize, blocks.count as usize));
+        wv_assert_ok!(crate::journal_mut().write_back());
+    }
+
+    fn load(bno: BlockNo) -> Vec<u8> {
+        let mut data = vec![0; crate::superblock().block_size as usize];
+        wv_assert_ok!(crate::backend_mut().load_block(&mut data, bno));
+        data
+    }
+
+    fn get_extent(blocks: BlockRange, perm: Perm) -> MemGate {
+        let sel = SelSpace::get().alloc_sel();
+        let backend = crate::backend_mut();
+        wv_assert_ok!(crate::file_buffer_mut().get_extent(
+            &**backend,
+            blocks.start,
+            blocks.count as usize,
+            sel,
+            perm,
+            None,
+        ));
+        // the client loses the access as soon as the gate is dropped
+        wv_assert_ok!(MemGate::new_owned_bind(sel))
+    }
+
+    /// Returns the blocks, the dirty flag, and the number of writers of the entry containing `bno`
+    fn entry(bno: BlockNo) -> Option<(BlockRange, bool, usize)> {
+        crate::file_buffer_mut()
+            .entries
+            .get(&BlockRange::new(bno))
+            // safety: the entry is owned by the LRU list
+            .map(|e| unsafe { e.as_ref() })
+            .map(|e| (e.blocks, e.dirty, e.writers))
+    }
+
+    fn read_ahead(t: &mut dyn WvTester) {
+        let blocks = alloc_blocks();
+
+        crate::file_buffer_mut().read_ahead(blocks);
+        wv_assert!(t, crate::file_buffer_mut().has_jobs());
+        wv_assert_eq!(t, entry(blocks.start), None);
+
+        wv_assert_ok!(crate::file_buffer_mut().run_job());
+        wv_assert!(t, !crate::file_buffer_mut().has_jobs());
+        wv_assert_eq!(t, entry(blocks.start), Some((blocks, false, 0)));
+
+        // the blocks are not loaded again
+        crate::file_buffer_mut().read_ahead(blocks);
+        wv_assert!(t, !crate::file_buffer_mut().has_jobs());
+
+        free_blocks(blocks);
+    }
+
+    fn read_ahead_limit(t: &mut dyn WvTester) {
+        let limit = crate::settings().readahead;
+        let mut fb = crate::file_buffer_mut();
+
+        // the job is not run, so that the blocks do not need to be allocated
+        fb.read_ahead(BlockRange::new_range(1, (limit * 2) as BlockNo));
+        match fb.jobs.pop_front() {
+            Some(Job::ReadAhead(blocks)) => {
+                wv_assert_eq!(t, blocks, BlockRange::new_range(1, limit as BlockNo))
+            },
+            _ => wv_assert!(t, false),
+        }
+        wv_assert!(t, !fb.has_jobs());
+    }
+
+    fn read_ahead_partial(t: &mut dyn WvTester) {
+        let blocks = alloc_blocks();
+
+        // a client obtains the last block before the read-ahead is performed
+        let last = blocks.start + blocks.count - 1;
+        drop(get_extent(BlockRange::new(last), Perm::R));
+
+        crate::file_buffer_mut().read_ahead(blocks);
+        wv_assert_ok!(crate::file_buffer_mut().run_job());
+
+        // the read-ahead stops at the already buffered block
+        let ahead = BlockRange::new_range(blocks.start, blocks.count - 1);
+        wv_assert_eq!(t, entry(blocks.start), Some((ahead, false, 0)));
+        wv_assert_eq!(t, entry(last), Some((BlockRange::new(last), false, 0)));
+
+        free_blocks(blocks);
+    }
+
+    fn write_behind(t: &mut dyn WvTester) {
+        let blocks = alloc_blocks();
+        let bsize = crate::superblock().block_size as usize;
+
+        let mem = get_extent(blocks, Perm::RW);
+        wv_assert_eq!(t, entry(blocks.start), Some((blocks, true, 1)));
+        let data = vec![0x5Au8; blocks.count as usize * bsize];
+        wv_assert_ok!(mem.write(&data, 0));
+        drop(mem);
+
+        crate::file_buffer_mut().release(blocks.start);
+        wv_assert!(t, crate::file_buffer_mut().has_jobs());
+        wv_assert_ok!(crate::file_buffer_mut().run_job());
+
+        // the entry stays in the buffer, but has been written back
+        wv_assert_eq!(t, entry(blocks.start), Some((blocks, false, 0)));
+        for i in 0..blocks.count {
+            wv_assert!(t, load(blocks.start + i).iter().all(|b| *b == 0x5A));
+        }
+
+        free_blocks(blocks);
+    }
+
+    fn write_behind_writers(t: &mut dyn WvTester) {
+        let blocks = alloc_blocks();
+
+        // read-only access does not cause a write-back
+        drop(get_extent(blocks, Perm::R));
+        crate::file_buffer_mut().release(blocks.start);
+        wv_assert!(t, !crate::file_buffer_mut().has_jobs());
+
+        // the entry is written back as soon as the last writer released it
+        drop(get_extent(blocks, Perm::RW));
+        drop(get_extent(blocks, Perm::RW));
+        wv_assert_eq!(t, entry(blocks.start), Some((blocks, true, 2)));
+        crate::file_buffer_mut().release(blocks.start);
+        wv_assert!(t, !crate::file_buffer_mut().has_jobs());
+        crate::file_buffer_mut().release(blocks.start);
+        wv_assert!(t, crate::file_buffer_mut().has_jobs());
+
+        // a new writer in the meantime prevents the write-back
+        drop(get_extent(blocks, Perm::RW));
+        wv_assert_ok!(crate::file_buffer_mut().run_job());
+        wv_assert_eq!(t, entry(blocks.start), Some((blocks, true, 1)));
+
+        crate::file_buffer_mut().release(blocks.start);
+        wv_assert_ok!(crate::file_buffer_mut().run_job());
+        wv_assert_eq!(t, entry(blocks.start), Some((blocks, false, 0)));
+
+        free_blocks(blocks);
+    }
+}
@@ -0,0 +1,464 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use crate::data::{BlockNo, SuperBlock};
+
+use base::io::LogFlags;
+use m3::col::Vec;
+use m3::errors::{Code, Error};
+
+const JOURNAL_MAGIC: u32 = 0x4C4E_524A; // "JRNL"
+const COMMIT_MAGIC: u32 = 0x5449_4D43; // "CMIT"
+
+// the header consists of magic, sequence number, and block count, followed by the block numbers
+const HEADER_WORDS: usize = 3;
+
+const CHECKSUM_INIT: u32 = 0x811C_9DC5;
+
+fn get_word(buf: &[u8], idx: usize) -> u32 {
+    let mut bytes = [0u8; 4];
+    bytes.copy_from_slice(&buf[idx * 4..idx * 4 + 4]);
+    u32::from_le_bytes(bytes)
+}
+
+fn set_word(buf: &mut [u8], idx: usize, val: u32) {
+    buf[idx * 4..idx * 4 + 4].copy_from_slice(&val.to_le_bytes());
+}
+
+fn checksum(sum: u32, data: &[u8]) -> u32 {
+    data.iter()
+        .fold(sum, |sum, b| (sum ^ *b as u32).wrapping_mul(0x0100_0193))
+}
+
+/// Runs `func` as a transaction
+///
+/// All metadata that has been modified when the outermost transaction ends is written back
+/// atomically via the journal. Thus, after an unclean shutdown, the metadata is either in the
+/// state before or after the transaction. If `func` fails or the transaction cannot be committed,
+/// all modifications of the transaction are discarded.
+pub fn transaction<R, F>(func: F) -> Result<R, Error>
+where
+    F: FnOnce() -> Result<R, Error>,
+{
+    crate::journal_mut().begin()?;
+    let res = func();
+    let commit = crate::journal_mut().end(res.is_ok());
+    match commit {
+        Ok(_) => res,
+        Err(e) => {
+            rollback()?;
+            res.and(Err(e))
+        },
+    }
+}
+
+fn rollback() -> Result<(), Error> {
+    let discarded = crate::meta_buffer_mut().discard()?;
+    log!(
+        LogFlags::FSInfo,
+        "journal: rolled back transaction with {} blocks",
+        discarded
+    );
+    if discarded > 0 {
+        // the allocators keep the number of free blocks and inodes in memory
+        crate::blocks_mut().recount()?;
+        crate::inodes_mut().recount()?;
+    }
+    Ok(())
+}
+
+/// The metadata journal
+///
+/// Instead of writing modified metadata blocks directly to their location on disk, they are first
+/// written to the journal area, followed by a commit record. Afterwards, the blocks are written to
+/// their actual location and the journal is cleared. If m3fs is not shut down cleanly, committed
+/// but not yet cleared transactions are replayed on the next mount.
+///
+/// The journal consists of the header with the locations of the journaled blocks, which occupies
+/// as many blocks as needed, the contents of these blocks, and the commit record that contains a
+/// checksum over the header and the block contents.
+pub struct Journal {
+    first: BlockNo,
+    blocks: u32,
+    block_size: usize,
+    seq: u32,
+    depth: usize,
+    failed: bool,
+    buf: Vec<u8>,
+}
+
+impl Journal {
+    /// Creates the journal for the given superblock
+    pub fn new(sb: &SuperBlock) -> Self {
+        Journal {
+            first: sb.first_journal_block(),
+            blocks: sb.journal_blocks,
+            block_size: sb.block_size as usize,
+            seq: 0,
+            depth: 0,
+            failed: false,
+            buf: vec![0; sb.block_size as usize],
+        }
+    }
+
+    /// Creates a journal that writes back all metadata in place
+    pub fn new_disabled() -> Self {
+        Journal {
+            first: 0,
+            blocks: 0,
+            block_size: 0,
+            seq: 0,
+            depth: 0,
+            failed: false,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Returns true if metadata is written back via the journal
+    pub fn enabled(&self) -> bool {
+        self.capacity() > 0
+    }
+
+    /// Returns true if a transaction is running
+    pub fn in_transaction(&self) -> bool {
+        self.depth > 0
+    }
+
+    /// Returns the maximum number of blocks per transaction
+    pub fn capacity(&self) -> usize {
+        // each block needs a word in the header and the commit record needs a block. thus, the
+        // largest count with (HEADER_WORDS + count) / words + count + 1 <= blocks (rounded up).
+        let words = self.block_size / 4;
+        let avail = (self.blocks as usize).saturating_sub(1) * words;
+        avail.saturating_sub(HEADER_WORDS) / (words + 1)
+    }
+
+    fn header_blocks(&self, count: usize) -> usize {
+        ((HEADER_WORDS + count) * 4 + self.block_size - 1) / self.block_size
+    }
+
+    /// Starts a new transaction. Transactions can be nested, in which case only the outermost
+    /// transaction writes back the metadata.
+    pub fn begin(&mut self) -> Result<(), Error> {
+        if self.depth == 0 && self.enabled() {
+            // write back modifications outside of transactions, so that the transaction can be
+            // discarded without losing them
+            self.write_back()?;
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    /// Ends the current transaction, which is considered failed if `success` is false.
+    ///
+    /// If the outermost transaction ends, the transaction is committed. An error is returned if
+    /// any of the nested transactions failed or the transaction could not be committed. In this
+    /// case, the modifications need to be discarded.
+    pub fn end(&mut self, success: bool) -> Result<(), Error> {
+        assert!(self.depth > 0);
+        self.depth -= 1;
+        self.failed |= !success;
+        if self.depth > 0 || !self.enabled() {
+            return Ok(());
+        }
+
+        if core::mem::take(&mut self.failed) {
+            Err(Error::new(Code::Abort))
+        }
+        else {
+            self.write_back()
+        }
+    }
+
+    /// Writes back all dirty blocks of the meta buffer
+    pub fn write_back(&mut self) -> Result<(), Error> {
+        let count = crate::meta_buffer_mut().dirty_blocks().count();
+        if !self.enabled() || count == 0 {
+            return crate::meta_buffer_mut().flush();
+        }
+
+        if count > self.capacity() {
+            log!(
+                LogFlags::Error,
+                "journal: {} dirty blocks exceed the capacity of {}",
+                count,
+                self.capacity()
+            );
+            return Err(Error::new(Code::NoSpace));
+        }
+
+        self.commit(count)?;
+        self.checkpoint()
+    }
+
+    /// Writes all dirty blocks of the meta buffer to the journal, including the commit record
+    fn commit(&mut self, count: usize) -> Result<(), Error> {
+        let mb = crate::meta_buffer_mut();
+        let backend = crate::backend_mut();
+
+        self.seq = self.seq.wrapping_add(1);
+        log!(
+            LogFlags::FSBuf,
+            "journal: writing transaction {} with {} blocks",
+            self.seq,
+            count
+        );
+
+        // header with the locations of the blocks
+        let hdr_blocks = self.header_blocks(count);
+        self.buf.clear();
+        self.buf.resize(hdr_blocks * self.block_size, 0);
+        set_word(&mut self.buf, 0, JOURNAL_MAGIC);
+        set_word(&mut self.buf, 1, self.seq);
+        set_word(&mut self.buf, 2, count as u32);
+        for (i, block) in mb.dirty_blocks().enumerate() {
+            set_word(&mut self.buf, HEADER_WORDS + i, block.blockno());
+        }
+        let mut sum = checksum(CHECKSUM_INIT, &self.buf);
+        for (i, hdr) in self.buf.chunks(self.block_size).enumerate() {
+            backend.store_block(hdr, self.first + i as BlockNo)?;
+        }
+
+        // contents of the blocks
+        let data_start = self.first + hdr_blocks as BlockNo;
+        for (i, block) in mb.dirty_blocks().enumerate() {
+            sum = checksum(sum, block.data());
+            backend.store_block(block.data(), data_start + i as BlockNo)?;
+        }
+
+        // the commit record makes the transaction valid
+        self.buf.truncate(self.block_size);
+        self.buf.fill(0);
+        set_word(&mut self.buf, 0, COMMIT_MAGIC);
+        set_word(&mut self.buf, 1, self.seq);
+        set_word(&mut self.buf, 2, count as u32);
+        set_word(&mut self.buf, 3, sum);
+        backend.store_block(&self.buf, data_start + count as BlockNo)
+    }
+
+    /// Writes the committed blocks to their actual location and clears the journal
+    fn checkpoint(&mut self) -> Result<(), Error> {
+        crate::meta_buffer_mut().flush()?;
+        self.clear()
+    }
+
+    fn clear(&mut self) -> Result<(), Error> {
+        // keep the sequence number to not mistake old commit records for new ones
+        self.buf.truncate(self.block_size);
+        self.buf.fill(0);
+        set_word(&mut self.buf, 0, JOURNAL_MAGIC);
+        set_word(&mut self.buf, 1, self.seq);
+        crate::backend_mut().store_block(&self.buf, self.first)
+    }
+
+    /// Replays the committed transaction in the journal, if there is any.
+    ///
+    /// Returns true if a transaction has been replayed.
+    pub fn replay(&mut self) -> Result<bool, Error> {
+        if !self.enabled() {
+            return Ok(false);
+        }
+
+        let backend = crate::backend_mut();
+        self.buf.resize(self.block_size, 0);
+        backend.load_block(&mut self.buf, self.first)?;
+        // the journal has never been used
+        if get_word(&self.buf, 0) != JOURNAL_MAGIC {
+            return Ok(false);
+        }
+
+        self.seq = get_word(&self.buf, 1);
+        let count = get_word(&self.buf, 2) as usize;
+        if count == 0 || count > self.capacity() {
+            return Ok(false);
+        }
+
+        // load the remaining header blocks
+        let hdr_blocks = self.header_blocks(count);
+        self.buf.resize(hdr_blocks * self.block_size, 0);
+        for i in 1..hdr_blocks {
+            let off = i * self.block_size;
+            backend.load_block(
+                &mut self.buf[off..off + self.block_size],
+                self.first + i as BlockNo,
+            )?;
+        }
+
+        let bnos = (0..count)
+            .map(|i| get_word(&self.buf, HEADER_WORDS + i))
+            .collect::<Vec<_>>();
+
+        let mut sum = checksum(CHECKSUM_INIT, &self.buf);
+        let mut data = vec![0; self.block_size];
+        let data_start = self.first + hdr_blocks as BlockNo;
+        for i in 0..count {
+            backend.load_block(&mut data, data_start + i as BlockNo)?;
+            sum = checksum(sum, &data);
+        }
+
+        self.buf.truncate(self.block_size);
+        backend.load_block(&mut self.buf, data_start + count as BlockNo)?;
+        let valid = get_word(&self.buf, 0) == COMMIT_MAGIC
+            && get_word(&self.buf, 1) == self.seq
+            && get_word(&self.buf, 2) as usize == count
+            && get_word(&self.buf, 3) == sum;
+
+        if valid {
+            log!(
+                LogFlags::FSInfo,
+                "journal: replaying transaction {} with {} blocks",
+                self.seq,
+                count
+            );
+
+            for (i, bno) in bnos.iter().enumerate() {
+                backend.load_block(&mut data, data_start + i as BlockNo)?;
+                backend.store_block(&data, *bno)?;
+            }
+        }
+        else {
+            // the transaction has not been committed, so that the metadata is still unchanged
+            log!(
+                LogFlags::FSInfo,
+                "journal: discarding incomplete transaction {}",
+                self.seq
+            );
+        }
+
+        drop(backend);
+        self.clear()?;
+        Ok(valid)
+    }
+}
+
+/// Tests for the journal, which are run on the actual file system via `m3fs -T`
+#[cfg(feature = "tests")]
+pub mod tests {
+    use super::*;
+
+    use m3::test::WvTester;
+    use m3::{wv_assert, wv_assert_eq, wv_assert_err, wv_assert_ok, wv_run_test};
+
+    pub fn run(t: &mut dyn WvTester) {
+        wv_run_test!(t, commit);
+        wv_run_test!(t, replay_committed);
+        wv_run_test!(t, discard_uncommitted);
+        wv_run_test!(t, rollback_failed);
+    }
+
+    fn load(bno: BlockNo) -> Vec<u8> {
+        let mut data = vec![0; crate::superblock().block_size as usize];
+        wv_assert_ok!(crate::backend_mut().load_block(&mut data, bno));
+        data
+    }
+
+    fn modify(bno: BlockNo, val: u8) {
+        let mut block = wv_assert_ok!(crate::meta_buffer_mut().get_block(bno));
+        block.data_mut().fill(val);
+        block.mark_dirty();
+    }
+
+    fn alloc_block() -> BlockNo {
+        let bno = wv_assert_ok!(crate::blocks_mut().alloc(None));
+        // write back the allocation, so that the following transactions only contain our block
+        wv_assert_ok!(crate::journal_mut().write_back());
+        bno
+    }
+
+    fn free_block(bno: BlockNo) {
+        wv_assert_ok!(crate::blocks_mut().free(bno as usize, 1));
+        wv_assert_ok!(crate::journal_mut().write_back());
+    }
+
+    fn commit(t: &mut dyn WvTester) {
+        let bno = alloc_block();
+
+        wv_assert_ok!(transaction(|| {
+            modify(bno, 0x12);
+            Ok(())
+        }));
+        wv_assert_eq!(t, crate::meta_buffer_mut().dirty_blocks().count(), 0);
+        wv_assert!(t, load(bno).iter().all(|b| *b == 0x12));
+        // the journal has been cleared
+        wv_assert_eq!(t, wv_assert_ok!(crate::journal_mut().replay()), false);
+
+        free_block(bno);
+    }
+
+    fn replay_committed(t: &mut dyn WvTester) {
+        let bno = alloc_block();
+        let old = load(bno);
+
+        // simulate a crash after the commit: the block is only in the journal
+        modify(bno, 0xAB);
+        wv_assert_ok!(crate::journal_mut().commit(1));
+        wv_assert_eq!(t, load(bno), old);
+
+        wv_assert_eq!(t, wv_assert_ok!(crate::journal_mut().replay()), true);
+        wv_assert!(t, load(bno).iter().all(|b| *b == 0xAB));
+        wv_assert_eq!(t, wv_assert_ok!(crate::journal_mut().replay()), false);
+
+        // the meta buffer still considers the block modified
+        wv_assert_eq!(t, wv_assert_ok!(crate::meta_buffer_mut().discard()), 1);
+        free_block(bno);
+    }
+
+    fn discard_uncommitted(t: &mut dyn WvTester) {
+        let bno = alloc_block();
+        let old = load(bno);
+
+        modify(bno, 0xCD);
+        wv_assert_ok!(crate::journal_mut().commit(1));
+
+        // simulate a crash while writing the journal by corrupting the journaled block, so that
+        // the checksum in the commit record does not match
+        let jrnl_block = {
+            let jr = crate::journal_mut();
+            jr.first + jr.header_blocks(1) as BlockNo
+        };
+        let mut data = load(jrnl_block);
+        data[0] ^= 0xFF;
+        wv_assert_ok!(crate::backend_mut().store_block(&data, jrnl_block));
+
+        wv_assert_eq!(t, wv_assert_ok!(crate::journal_mut().replay()), false);
+        wv_assert_eq!(t, load(bno), old);
+
+        wv_assert_eq!(t, wv_assert_ok!(crate::meta_buffer_mut().discard()), 1);
+        free_block(bno);
+    }
+
+    fn rollback_failed(t: &mut dyn WvTester) {
+        let bno = alloc_block();
+        let old = load(bno);
+
+        wv_assert_err!(
+            t,
+            transaction(|| {
+                modify(bno, 0xEF);
+                Err::<(), _>(Error::new(Code::InvArgs))
+            }),
+            Code::InvArgs
+        );
+
+        // the modification has been discarded in the meta buffer and was not written to disk
+        wv_assert_eq!(t, crate::meta_buffer_mut().dirty_blocks().count(), 0);
+        let block = wv_assert_ok!(crate::meta_buffer_mut().get_block(bno));
+        wv_assert_eq!(t, block.data(), &old[..]);
+        drop(block);
+        wv_assert_eq!(t, load(bno), old);
+
+        free_block(bno);
+    }
+}
@@ -23,7 +23,7 @@ use core::ptr::NonNull;
 use base::io::LogFlags;
 use m3::boxed::Box;
 use m3::col::{BoxList, Treap, Vec};
-use m3::errors::Error;
+use m3::errors::{Code, Error};
 
 use thread::Event;
 
@@ -219,20 +219,33 @@ impl MetaBuffer {
             }
         }
 
-        // find first unused head
-        let mut use_block = None;
-        for lru_element in self.lru.iter() {
-            if lru_element.links == 0 {
-                use_block = Some(lru_element.id);
-                break;
+        // find first unused head; prefer clean blocks to write back metadata only on commits
+        let use_block = self
+            .lru
+            .iter()
+            .find(|b| b.links == 0 && !b.dirty)
+            .or_else(|| self.lru.iter().find(|b| b.links == 0))
+            .map(|b| b.id);
+
+        let block = unsafe { &mut (*self.blocks[use_block.unwrap()].as_ptr()) };
+
+        // all dirty blocks need to go through the journal to keep the metadata consistent
+        if block.dirty && crate::journal_mut().enabled() {
+            // writing back the blocks would commit the running transaction only partially
+            if crate::journal_mut().in_transaction() {
+                log!(
+                    LogFlags::Error,
+                    "metabuffer: transaction exceeds the meta buffer size of {} blocks",
+                    META_BUFFER_SIZE
+                );
+                return Err(Error::new(Code::NoSpace));
             }
+            crate::journal_mut().write_back()?;
         }
 
-        let block = unsafe {
-            let block = &mut (*self.blocks[use_block.unwrap()].as_ptr());
+        unsafe {
             self.lru.move_to_back(block);
-            block
-        };
+        }
 
         // flush if there is still a block present with the given bno.
         if block.bno != 0 {
@@ -258,6 +271,39 @@ impl MetaBuffer {
         Ok(MetaBufferBlockRef::new(block.id))
     }
 
+    /// Returns an iterator over all blocks that have been modified
+    pub fn dirty_blocks(&self) -> impl Iterator<Item = &MetaBufferBlock> + '_ {
+        self.blocks
+            .iter()
+            .map(|b| unsafe { &*b.as_ptr() })
+            .filter(|b| b.dirty)
+    }
+
+    /// Discards all modifications by reloading the modified blocks from the backend
+    ///
+    /// Returns the number of discarded blocks.
+    pub fn discard(&mut self) -> Result<usize, Error> {
+        let mut count = 0;
+        for block_ptr in &mut self.blocks {
+            let block = unsafe { &mut (*block_ptr.as_ptr()) };
+            if block.dirty {
+                log!(
+                    LogFlags::FSBuf,
+                    "metabuffer: discarding block <{}>",
+                    block.bno
+                );
+
+                block.locked = true;
+                let unlock = block.unlock;
+                crate::backend_mut().load_meta(block, block.id, block.bno, unlock)?;
+                block.dirty = false;
+                block.locked = false;
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
     pub fn flush(&mut self) -> Result<(), Error> {
         for block_ptr in &mut self.blocks {
             let block = unsafe { &mut (*block_ptr.as_ptr()) };
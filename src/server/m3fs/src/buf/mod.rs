@@ -15,8 +15,16 @@
  * General Public License version 2 for more details.
  */
 
+pub mod background;
+
 mod file_buffer;
+mod journal;
 mod meta_buffer;
 
+#[cfg(feature = "tests")]
+pub use file_buffer::tests as file_buffer_tests;
 pub use file_buffer::{FileBuffer, LoadLimit};
+#[cfg(feature = "tests")]
+pub use journal::tests as journal_tests;
+pub use journal::{transaction, Journal};
 pub use meta_buffer::{MetaBuffer, MetaBufferBlock, MetaBufferBlockRef, META_BUFFER_SIZE};
@@ -63,6 +63,37 @@ impl Allocator {
         self.free
     }
 
+    /// Determines the number of free items and the first free item from the bitmap
+    pub fn recount(&mut self) -> Result<(), Error> {
+        let perblock: usize = self.blocksize * 8;
+
+        let mut free = 0;
+        let mut first_free = None;
+        for i in 0..self.blocks as usize {
+            let mut block = crate::meta_buffer_mut().get_block(self.first + i as u32)?;
+            let bitmap = Bitmap::from_bytes(block.data_mut());
+
+            let max = (self.total as usize - i * perblock).min(perblock);
+            for bit in 0..max {
+                if !bitmap.is_bit_set(bit) {
+                    free += 1;
+                    first_free.get_or_insert(i * perblock + bit);
+                }
+            }
+        }
+
+        self.free = free;
+        self.first_free = first_free.unwrap_or(self.total as usize) as u32;
+        log!(
+            LogFlags::FSAlloc,
+            "allocator[{}]::recount() -> free={}, first_free={}",
+            self.name,
+            self.free,
+            self.first_free
+        );
+        Ok(())
+    }
+
     pub fn alloc(&mut self, count: Option<&mut usize>) -> Result<u32, Error> {
         let mut tmp_count = 1;
         let count = count.unwrap_or(&mut tmp_count);
@@ -48,6 +48,10 @@ impl fmt::Debug for ExtPos {
 }
 
 /// Represents an extent as stored on disk
+///
+/// Extents with a start of zero are holes: they cover `length` blocks of the file that have not
+/// been allocated and read as zeros. Block zero holds the superblock and can therefore never be
+/// part of a file.
 #[derive(Clone, Copy, Debug)]
 #[repr(C, align(8))]
 pub struct Extent {
@@ -60,6 +64,16 @@ impl Extent {
         Self { start, length }
     }
 
+    /// Creates a new hole that spans `length` blocks
+    pub fn new_hole(length: u32) -> Self {
+        Self { start: 0, length }
+    }
+
+    /// Returns true if this extent is a hole, i.e., has no blocks allocated
+    pub fn is_hole(&self) -> bool {
+        self.start == 0 && self.length > 0
+    }
+
     pub fn block_range(&self) -> core::ops::Range<BlockNo> {
         core::ops::Range {
             start: self.start,
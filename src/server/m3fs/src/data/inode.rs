@@ -37,10 +37,13 @@ pub struct INode {
 
     pub lastaccess: Time,
     pub lastmod: Time,
+    pub lastchange: Time,
     pub extents: u32,
 
     pub inode: InodeNo,
     pub mode: FileMode,
+    pub uid: u16,
+    pub gid: u16,
     pub size: u64,
 
     pub direct: [Extent; INODE_DIR_COUNT], // direct entries
@@ -58,10 +61,13 @@ impl Clone for INode {
 
             inode: self.inode,
             mode: self.mode,
+            uid: self.uid,
+            gid: self.gid,
             size: self.size,
 
             lastaccess: self.lastaccess,
             lastmod: self.lastmod,
+            lastchange: self.lastchange,
             extents: self.extents,
 
             direct: self.direct,
@@ -72,14 +78,44 @@ impl Clone for INode {
 }
 
 impl INode {
+    /// Creates a new inode with given number and mode, which has no links and no content yet
+    pub fn new(inode: InodeNo, mode: FileMode) -> Self {
+        INode {
+            devno: 0,
+            _pad: 0,
+            links: 0,
+
+            lastaccess: 0,
+            lastmod: 0,
+            lastchange: 0,
+            extents: 0,
+
+            inode,
+            mode,
+            uid: 0,
+            gid: 0,
+            size: 0,
+
+            direct: [Extent {
+                start: 0,
+                length: 0,
+            }; INODE_DIR_COUNT],
+            indirect: 0,
+            dindirect: 0,
+        }
+    }
+
     pub fn reset(&mut self) {
         self.devno = 0;
         self.links = 0;
         self.inode = 0;
         self.mode = FileMode::empty();
+        self.uid = 0;
+        self.gid = 0;
         self.size = 0;
         self.lastaccess = 0;
         self.lastmod = 0;
+        self.lastchange = 0;
         self.extents = 0;
 
         self.direct = [Extent {
@@ -89,21 +125,6 @@ impl INode {
         self.indirect = 0;
         self.dindirect = 0;
     }
-
-    pub fn to_file_info(&self) -> FileInfo {
-        FileInfo {
-            devno: self.devno,
-            inode: self.inode,
-            mode: self.mode,
-            links: self.links as u32,
-            size: self.size as usize,
-            lastaccess: self.lastaccess,
-            lastmod: self.lastmod,
-            extents: self.extents,
-            blocksize: crate::superblock().block_size,
-            firstblock: self.direct[0].start,
-        }
-    }
 }
 
 /// A reference to an inode within a loaded MetaBuffer block.
@@ -144,6 +165,32 @@ impl INodeRef {
         }
     }
 
+    pub fn to_file_info(&self) -> FileInfo {
+        // holes do not occupy any blocks
+        let blocks = self
+            .extent_iter()
+            .filter(|ext| !ext.is_hole())
+            .fold(0, |total, ext| total + ext.length);
+
+        let inode: &INode = self;
+        FileInfo {
+            devno: inode.devno,
+            inode: inode.inode,
+            mode: inode.mode,
+            links: inode.links as u32,
+            uid: inode.uid,
+            gid: inode.gid,
+            size: inode.size as usize,
+            lastaccess: inode.lastaccess,
+            lastmod: inode.lastmod,
+            lastchange: inode.lastchange,
+            extents: inode.extents,
+            blocksize: crate::superblock().block_size,
+            blocks,
+            firstblock: inode.direct[0].start,
+        }
+    }
+
     pub fn extent_iter(&self) -> ExtentIterator<'_> {
         ExtentIterator {
             inode: self,
@@ -22,10 +22,11 @@ mod inode;
 mod superblock;
 
 pub use allocator::Allocator;
+pub use bitmap::Bitmap;
 pub use direntry::{DirEntry, DirEntryIterator};
 pub use extent::{ExtPos, Extent, ExtentCache, ExtentRef};
-pub use inode::INodeRef;
-pub use superblock::SuperBlock;
+pub use inode::{INode, INodeRef};
+pub use superblock::{SuperBlock, FS_VERSION};
 
 pub type BlockNo = m3::client::DiskBlockNo;
 pub type BlockRange = m3::client::DiskBlockRange;
@@ -33,7 +34,7 @@ pub type Dev = u8;
 pub type InodeNo = u32;
 pub type Time = u32;
 
-pub const INODE_DIR_COUNT: usize = 3;
+pub const INODE_DIR_COUNT: usize = 2;
 pub const MAX_BLOCK_SIZE: u32 = 4096;
 pub const NUM_INODE_BYTES: usize = 64;
 pub const NUM_EXT_BYTES: usize = 8;
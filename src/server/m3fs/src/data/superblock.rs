@@ -16,6 +16,15 @@
 
 use crate::data::{BlockNo, NUM_EXT_BYTES, NUM_INODE_BYTES};
 
+use base::io::LogFlags;
+use m3::errors::{Code, Error};
+
+/// The current version of the file system format
+///
+/// Version 0 is the format without journal. Since the following versions only append fields after
+/// the checksum, file systems of version 0 can still be used, but without journal.
+pub const FS_VERSION: u32 = 1;
+
 /// Represents a superblock
 #[derive(Debug)]
 #[repr(C, align(8))]
@@ -28,10 +37,17 @@ pub struct SuperBlock {
     pub first_free_inode: u32,
     pub first_free_block: u32,
     pub checksum: u32,
+    // the following fields have been added in version 1
+    pub version: u32,
+    pub journal_blocks: u32,
 }
 
 impl SuperBlock {
     pub fn get_checksum(&self) -> u32 {
+        self.get_checksum_v0() + self.version * 19 + self.journal_blocks * 23
+    }
+
+    fn get_checksum_v0(&self) -> u32 {
         1 + self.block_size * 2
             + self.total_inodes * 3
             + self.total_blocks * 5
@@ -41,6 +57,42 @@ impl SuperBlock {
             + self.first_free_block * 17
     }
 
+    /// Converts the superblock of an older file system to the current version.
+    ///
+    /// File systems of version 0 have no space reserved for the journal and are therefore used
+    /// without journal. The converted superblock is written back on the next sync.
+    pub fn migrate(&mut self) -> Result<(), Error> {
+        // version 0 ends with the checksum; the following bytes are undefined
+        if self.checksum != self.get_checksum() && self.checksum == self.get_checksum_v0() {
+            self.version = 0;
+            self.journal_blocks = 0;
+        }
+
+        match self.version {
+            FS_VERSION => Ok(()),
+            0 => {
+                log!(
+                    LogFlags::FSInfo,
+                    "Converting file system from version 0 to {} (without journal)",
+                    FS_VERSION
+                );
+                self.version = FS_VERSION;
+                self.journal_blocks = 0;
+                self.checksum = self.get_checksum();
+                Ok(())
+            },
+            v => {
+                log!(
+                    LogFlags::Error,
+                    "Unsupported file system version {} (expected {})",
+                    v,
+                    FS_VERSION
+                );
+                Err(Error::new(Code::NotSup))
+            },
+        }
+    }
+
     pub fn first_inodebm_block(&self) -> BlockNo {
         1
     }
@@ -61,6 +113,18 @@ impl SuperBlock {
         self.first_blockbm_block() + self.blockbm_blocks()
     }
 
+    pub fn inode_blocks(&self) -> BlockNo {
+        (self.total_inodes * NUM_INODE_BYTES as u32 + self.block_size - 1) / self.block_size
+    }
+
+    pub fn first_journal_block(&self) -> BlockNo {
+        self.first_inode_block() + self.inode_blocks()
+    }
+
+    pub fn first_data_block(&self) -> BlockNo {
+        self.first_journal_block() + self.journal_blocks
+    }
+
     pub fn extents_per_block(&self) -> usize {
         self.block_size as usize / NUM_EXT_BYTES
     }
@@ -26,22 +26,26 @@ mod ops;
 mod sess;
 
 use crate::backend::{Backend, DiskBackend, MemBackend};
-use crate::buf::{FileBuffer, MetaBuffer};
+use crate::buf::{FileBuffer, Journal, MetaBuffer};
 use crate::data::{Allocator, SuperBlock};
-use crate::sess::{FSSession, M3FSSession, OpenFiles};
+use crate::sess::{FSSession, M3FSSession, OpenFiles, Watches};
 
 use m3::server::ExcType;
 use m3::{
     boxed::Box,
     cap::Selector,
     cell::{LazyReadOnlyCell, LazyStaticRefCell, LazyStaticUnsafeCell, Ref, RefMut, StaticRefCell},
+    client::Clock,
     col::{String, ToString, Vec},
     com::opcodes,
     env,
     errors::{Code, Error},
     io::LogFlags,
-    server::{RequestHandler, Server, DEF_MAX_CLIENTS},
+    server::{RequestHandler, Server, WaitPolicy, DEF_MAX_CLIENTS},
     tiles::OwnActivity,
+    time::TimeDuration,
+    util::parse,
+    watchdog,
 };
 
 // Server constants
@@ -52,7 +56,9 @@ static SB: LazyStaticRefCell<SuperBlock> = LazyStaticRefCell::default();
 // basicalled used in all modules, making it really hard to use something like a RefCell here.
 static MB: LazyStaticUnsafeCell<MetaBuffer> = LazyStaticUnsafeCell::default();
 static FB: LazyStaticRefCell<FileBuffer> = LazyStaticRefCell::default();
+static JR: LazyStaticRefCell<Journal> = LazyStaticRefCell::default();
 static FILES: StaticRefCell<OpenFiles> = StaticRefCell::new(OpenFiles::new());
+static WATCHES: StaticRefCell<Watches> = StaticRefCell::new(Watches::new());
 static BA: LazyStaticRefCell<Allocator> = LazyStaticRefCell::default();
 static IA: LazyStaticRefCell<Allocator> = LazyStaticRefCell::default();
 static SETTINGS: LazyReadOnlyCell<FsSettings> = LazyReadOnlyCell::default();
@@ -71,9 +77,15 @@ fn meta_buffer_mut() -> &'static mut MetaBuffer {
 fn file_buffer_mut() -> RefMut<'static, FileBuffer> {
     FB.borrow_mut()
 }
+fn journal_mut() -> RefMut<'static, Journal> {
+    JR.borrow_mut()
+}
 fn open_files_mut() -> RefMut<'static, OpenFiles> {
     FILES.borrow_mut()
 }
+fn watches_mut() -> RefMut<'static, Watches> {
+    WATCHES.borrow_mut()
+}
 fn blocks_mut() -> RefMut<'static, Allocator> {
     BA.borrow_mut()
 }
@@ -88,7 +100,7 @@ fn backend_mut() -> RefMut<'static, Box<dyn Backend>> {
 }
 
 fn flush_buffer() -> Result<(), Error> {
-    crate::meta_buffer_mut().flush()?;
+    crate::journal_mut().write_back()?;
     crate::file_buffer_mut().flush()?;
 
     // update superblock and write it back to disk/memory
@@ -106,11 +118,19 @@ pub struct FsSettings {
     name: String,
     backend: String,
     mem_mod: String,
+    tmp_size: usize,
     extend: usize,
     max_load: usize,
     max_clients: usize,
+    readahead: usize,
+    writebehind: bool,
     clear: bool,
+    check: bool,
+    #[cfg(feature = "tests")]
+    test: bool,
     selector: Option<Selector>,
+    clock: Option<String>,
+    spin: TimeDuration,
 }
 
 impl core::default::Default for FsSettings {
@@ -119,29 +139,52 @@ impl core::default::Default for FsSettings {
             name: String::from("m3fs"),
             backend: String::from("mem"),
             mem_mod: String::from("fs"),
+            tmp_size: 16 * 1024 * 1024,
             extend: 128,
             max_load: 128,
             max_clients: DEF_MAX_CLIENTS,
+            readahead: 64,
+            writebehind: true,
             clear: false,
+            check: false,
+            #[cfg(feature = "tests")]
+            test: false,
             selector: None,
+            clock: None,
+            spin: TimeDuration::ZERO,
         }
     }
 }
 
 fn usage() -> ! {
     println!(
-        "Usage: {} [-n <name>] [-s <sel>] [-e <blocks>] [-c] [-f <name>] [-b <blocks>]",
+        "Usage: {} [-n <name>] [-s <sel>] [-e <blocks>] [-c] [-C] [-f <name>] [-b <blocks>]",
         env::args().next().unwrap()
     );
-    println!("       [-m <clients>] (disk|mem)");
+    println!(
+        "       [-m <clients>] [-r <blocks>] [-W] [-t <clock>] [-S <size>] [-p <us>] (disk|mem|tmp)"
+    );
     println!();
     println!("  -n: the name of the service (m3fs by default)");
     println!("  -s: don't create service, use selectors <sel>..<sel+1>");
     println!("  -e: the number of blocks to extend files when appending");
     println!("  -c: clear allocated blocks");
+    println!("  -C: check the file system for consistency and exit");
+    #[cfg(feature = "tests")]
+    println!("  -T: run the tests of the journal and the file buffer and exit");
     println!("  -b: the maximum number of blocks loaded from the disk");
     println!("  -m: the maximum number of clients (receive slots)");
+    println!("  -r: the number of blocks to read ahead for sequential reads (0 = disabled)");
+    println!("  -W: don't write back modified blocks in the background");
     println!("  -f: the name of the FS boot module ('fs' by default)");
+    println!("  -t: synchronize the time with the clock service <clock> for timestamps");
+    println!("  -S: the size of the file system for the tmp backend (16M by default)");
+    println!("  -p: spin for <us> microseconds before going to sleep (0 by default)");
+    println!();
+    println!("The backends:");
+    println!("  disk: use the file system on the disk");
+    println!("  mem:  use the file system in the FS boot module");
+    println!("  tmp:  create an empty, non-persistent file system in memory");
     OwnActivity::exit_with(Code::InvArgs);
 }
 
@@ -154,6 +197,11 @@ fn parse_args() -> Result<FsSettings, String> {
         match args[i] {
             "-n" => settings.name = args[i + 1].to_string(),
             "-f" => settings.mem_mod = args[i + 1].to_string(),
+            "-t" => settings.clock = Some(args[i + 1].to_string()),
+            "-S" => {
+                settings.tmp_size = parse::size(args[i + 1])
+                    .map_err(|_| String::from("Failed to parse file system size"))?;
+            },
             "-s" => {
                 if let Ok(s) = args[i + 1].parse::<Selector>() {
                     settings.selector = Some(s);
@@ -174,10 +222,35 @@ fn parse_args() -> Result<FsSettings, String> {
                     .parse::<usize>()
                     .map_err(|_| String::from("Failed to parse client count"))?;
             },
+            "-p" => {
+                settings.spin = TimeDuration::from_micros(
+                    args[i + 1]
+                        .parse::<u64>()
+                        .map_err(|_| String::from("Failed to parse spin time"))?,
+                );
+            },
+            "-r" => {
+                settings.readahead = args[i + 1]
+                    .parse::<usize>()
+                    .map_err(|_| String::from("Failed to parse read-ahead blocks"))?;
+            },
             "-c" => {
                 settings.clear = true;
                 i -= 1; // argument has no value
             },
+            "-C" => {
+                settings.check = true;
+                i -= 1; // argument has no value
+            },
+            #[cfg(feature = "tests")]
+            "-T" => {
+                settings.test = true;
+                i -= 1; // argument has no value
+            },
+            "-W" => {
+                settings.writebehind = false;
+                i -= 1; // argument has no value
+            },
             _ => break,
         }
         // move forward 2 by default, since most arguments have a value
@@ -186,7 +259,19 @@ fn parse_args() -> Result<FsSettings, String> {
 
     settings.backend = args[i].to_string();
     match settings.backend.as_str() {
-        "mem" | "disk" => {},
+        // the memory backend hands out the memory directly instead of using the file buffer
+        "mem" => {
+            settings.readahead = 0;
+            settings.writebehind = false;
+        },
+        // same for tmp; in addition, the memory might contain old data that should not be visible
+        // in newly allocated blocks
+        "tmp" => {
+            settings.readahead = 0;
+            settings.writebehind = false;
+            settings.clear = true;
+        },
+        "disk" => {},
         backend => return Err(format!("Unknown backend {}", backend)),
     }
 
@@ -197,7 +282,8 @@ fn init_fs(mut backend: Box<dyn Backend>) {
     // init thread manager, otherwise the waiting within the file and meta buffer impl. panics.
     thread::init();
 
-    let sb = backend.load_sb().expect("Unable to load super block");
+    let mut sb = backend.load_sb().expect("Unable to load super block");
+    sb.migrate().expect("Unable to use file system");
     log!(LogFlags::FSInfo, "Loaded {:#?}", sb);
 
     BA.set(Allocator::new(
@@ -219,14 +305,57 @@ fn init_fs(mut backend: Box<dyn Backend>) {
         sb.block_size as usize,
     ));
 
+    // the memory backend is not persistent and therefore does not need the journal
+    let journal = if settings().backend == "disk" {
+        Journal::new(&sb)
+    }
+    else {
+        Journal::new_disabled()
+    };
+
     // safety: we pass in a newly constructed MetaBuffer and have not initialized MB before
     unsafe {
         MB.set(MetaBuffer::new(sb.block_size as usize));
     }
     FB.set(FileBuffer::new(sb.block_size as usize));
     SB.set(sb);
+    JR.set(journal);
 
     BACKEND.set(backend);
+
+    if journal_mut().enabled() {
+        // bring the metadata into a consistent state in case we have not been shut down cleanly
+        journal_mut().replay().expect("Unable to replay journal");
+        // the superblock is only written on syncs, so that the counters might be outdated
+        blocks_mut().recount().expect("Unable to count free blocks");
+        inodes_mut().recount().expect("Unable to count free inodes");
+    }
+
+    if settings().readahead > 0 || settings().writebehind {
+        buf::background::start();
+    }
+}
+
+/// Runs the tests of the journal and the file buffer on the mounted file system and exits
+#[cfg(feature = "tests")]
+fn run_tests() -> ! {
+    use m3::test::{DefaultWvTester, WvTester};
+
+    if !journal_mut().enabled() {
+        println!("m3fs: the tests require a file system with journal");
+        OwnActivity::exit_with(Code::NotSup);
+    }
+
+    let mut tester = DefaultWvTester::default();
+    wv_run_suite!(tester, buf::journal_tests::run);
+    wv_run_suite!(tester, buf::file_buffer_tests::run);
+    println!("{}", tester);
+    OwnActivity::exit_with(if tester.failures() == 0 {
+        Code::Success
+    }
+    else {
+        Code::InvState
+    });
 }
 
 #[no_mangle]
@@ -238,23 +367,47 @@ pub fn main() -> Result<(), Error> {
     }));
     log!(LogFlags::FSInfo, "{:#?}", SETTINGS.get());
 
-    // create and initialize backend for the file system
-    let backend = if SETTINGS.get().backend == "mem" {
-        Box::new(MemBackend::new(&SETTINGS.get().mem_mod)) as Box<dyn Backend>
+    // synchronize the wall-clock time to get meaningful modification timestamps
+    if let Some(name) = &SETTINGS.get().clock {
+        let clock = Clock::new(name).expect("Unable to connect to clock service");
+        let now = clock.sync().expect("Unable to synchronize time");
+        log!(LogFlags::FSInfo, "Synchronized time: {}", now);
     }
-    else {
-        Box::new(DiskBackend::new().expect("Failed to initialize disk backend!"))
-            as Box<dyn Backend>
+
+    // create and initialize backend for the file system
+    let backend = match SETTINGS.get().backend.as_str() {
+        "mem" => Box::new(MemBackend::new(&SETTINGS.get().mem_mod)) as Box<dyn Backend>,
+        "tmp" => Box::new(
+            MemBackend::new_empty(SETTINGS.get().tmp_size)
+                .expect("Failed to create empty file system!"),
+        ) as Box<dyn Backend>,
+        _ => Box::new(DiskBackend::new().expect("Failed to initialize disk backend!"))
+            as Box<dyn Backend>,
     };
     init_fs(backend);
 
+    if SETTINGS.get().check {
+        let errors = ops::fsck::check().expect("Unable to check file system");
+        println!("m3fs: fsck: found {} inconsistencies", errors);
+        OwnActivity::exit_with(if errors == 0 {
+            Code::Success
+        }
+        else {
+            Code::InvState
+        });
+    }
+
+    #[cfg(feature = "tests")]
+    if SETTINGS.get().test {
+        run_tests();
+    }
+
     // create request handler and server
     // TODO just temporary: set a very high limit for client connections until we repair the way
     // the meta session is used.
     let mut hdl = RequestHandler::new_with(SETTINGS.get().max_clients, MSG_SIZE, 1024)
         .expect("Unable to create request handler");
-    let mut srv =
-        Server::new(&SETTINGS.get().name, &mut hdl).expect("Could not create service 'm3fs'");
+    let srv = Server::new(&SETTINGS.get().name, &mut hdl).expect("Could not create service 'm3fs'");
 
     use opcodes::FileSystem;
 
@@ -265,6 +418,11 @@ pub fn main() -> Result<(), Error> {
     hdl.reg_cap_handler(FileSystem::CloneFile, ExcType::Obt(2), FSSession::clone);
     hdl.reg_cap_handler(FileSystem::CloneMeta, ExcType::Obt(2), FSSession::clone);
     hdl.reg_cap_handler(FileSystem::SetDest, ExcType::Del(1), FSSession::set_dest);
+    hdl.reg_cap_handler(
+        FileSystem::SetNextDest,
+        ExcType::Del(1),
+        FSSession::set_next_dest,
+    );
     hdl.reg_cap_handler(
         FileSystem::EnableNotify,
         ExcType::Del(1),
@@ -286,10 +444,48 @@ pub fn main() -> Result<(), Error> {
     hdl.reg_msg_handler(FileSystem::Link, FSSession::link);
     hdl.reg_msg_handler(FileSystem::Unlink, FSSession::unlink);
     hdl.reg_msg_handler(FileSystem::Rename, FSSession::rename);
+    hdl.reg_msg_handler(FileSystem::Utime, FSSession::utime);
     hdl.reg_msg_handler(FileSystem::OpenPriv, FSSession::open_priv);
     hdl.reg_msg_handler(FileSystem::ClosePriv, FSSession::close_priv);
 
-    hdl.run(&mut srv).expect("Server loop failed");
+    let wait = WaitPolicy::spin_then_sleep(SETTINGS.get().spin);
+    let res = loop {
+        // don't wait for requests as long as there is work to do in the background
+        let has_reqs =
+            || (srv.rgate().has_msgs() || hdl.clients().recv_gate().has_msgs()).then_some(());
+        if !buf::background::pending() && wait.spin(has_reqs).is_none() {
+            // don't sleep longer than the next heartbeat or thread timeout
+            let timeout = [watchdog::next_timeout(), thread::next_timeout()]
+                .into_iter()
+                .flatten()
+                .min();
+            OwnActivity::sleep_for(timeout.unwrap_or(TimeDuration::MAX)).ok();
+        }
+
+        // let the threads whose timeout expired continue
+        thread::check_timeouts();
+        if thread::ready_count() > 0 {
+            thread::try_yield();
+        }
+
+        watchdog::check_in_with(|| watchdog::Diagnostics {
+            threads: thread::thread_count() as u32,
+            ready: thread::ready_count() as u32,
+            blocked: thread::blocked_count() as u32,
+            sleeping: thread::sleeping_count() as u32,
+            queued: 0,
+        });
+
+        if let Err(e) = srv.fetch_and_handle(&mut hdl) {
+            break e;
+        }
+        hdl.fetch_and_handle_msg();
+
+        buf::background::run();
+    };
 
-    Ok(())
+    match res.code() {
+        Code::EndOfFile => Ok(()),
+        _ => panic!("Server loop failed: {:?}", res),
+    }
 }
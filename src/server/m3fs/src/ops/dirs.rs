@@ -15,9 +15,11 @@
  */
 
 use crate::data::{DirEntry, DirEntryIterator, INodeRef, InodeNo};
+use crate::ops::perms::{self, Identity};
 use crate::ops::{inodes, links};
 
 use base::io::LogFlags;
+use m3::client::WatchEvent;
 use m3::errors::{Code, Error};
 use m3::vfs::FileMode;
 
@@ -69,12 +71,24 @@ fn find_entry(inode: &INodeRef, name: &str) -> Result<InodeNo, Error> {
     Err(Error::new(Code::NoSuchFile))
 }
 
-/// Searches for the given path, optionally creates a new file, and returns the inode number.
-pub fn search(path: &str, create: bool) -> Result<InodeNo, Error> {
+/// Checks whether `id` is allowed to add or remove entries in the directory `dir`
+fn check_write(dir: &INodeRef, id: &Identity) -> Result<(), Error> {
+    // the superuser can always change directories, because directories created without
+    // permission bits would be unusable otherwise
+    if id.is_root() {
+        return Ok(());
+    }
+    perms::check(dir, id, FileMode::IWOTH | FileMode::IXOTH)
+}
+
+/// Searches for the given path and returns the inode number.
+///
+/// If `create` is `Some`, the file is created with the given owner if it doesn't exist.
+pub fn search(path: &str, create: Option<&Identity>) -> Result<InodeNo, Error> {
     let ino = do_search(path, create);
     log!(
         LogFlags::FSDirs,
-        "dirs::search(path={}, create={}) -> {:?}",
+        "dirs::search(path={}, create={:?}) -> {:?}",
         path,
         create,
         ino.as_ref().map_err(|e| e.code()),
@@ -82,7 +96,9 @@ pub fn search(path: &str, create: bool) -> Result<InodeNo, Error> {
     ino
 }
 
-fn do_search(mut path: &str, create: bool) -> Result<InodeNo, Error> {
+fn do_search(full_path: &str, create: Option<&Identity>) -> Result<InodeNo, Error> {
+    let mut path = full_path;
+
     // remove all leading /
     while path.starts_with('/') {
         path = &path[1..];
@@ -136,22 +152,26 @@ fn do_search(mut path: &str, create: bool) -> Result<InodeNo, Error> {
         path = end;
     };
 
-    if create {
+    if let Some(owner) = create {
+        check_write(&inode, owner)?;
+
         // create inode and put link into directory
         let new_inode = inodes::create(FileMode::FILE_DEF)?;
+        perms::set_owner(&new_inode, owner);
         if let Err(e) = links::create(&inode, filename, &new_inode) {
             crate::open_files_mut().delete_file(new_inode.inode).ok();
             return Err(e);
         };
+        crate::watches_mut().notify(WatchEvent::CREATE, full_path);
         return Ok(new_inode.inode);
     }
 
     Err(Error::new(Code::NoSuchFile))
 }
 
-/// Creates a new directory with given mode at given path
-pub fn create(path: &str, mode: FileMode) -> Result<(), Error> {
-    let res = do_create(path, mode);
+/// Creates a new directory with given mode and owner at given path
+pub fn create(path: &str, mode: FileMode, owner: &Identity) -> Result<(), Error> {
+    let res = do_create(path, mode, owner);
     log!(
         LogFlags::FSDirs,
         "dirs::create(path={}, mode={:o}) -> {:?}",
@@ -162,19 +182,23 @@ pub fn create(path: &str, mode: FileMode) -> Result<(), Error> {
     res
 }
 
-fn do_create(path: &str, mode: FileMode) -> Result<(), Error> {
+fn do_create(path: &str, mode: FileMode, owner: &Identity) -> Result<(), Error> {
     let (dir, name) = split_path(path);
 
     // get parent directory
-    let parent_ino = search(dir, false)?;
+    let parent_ino = search(dir, None)?;
 
     // ensure that the entry doesn't exist
-    if search(path, false).is_ok() {
+    if search(path, None).is_ok() {
         return Err(Error::new(Code::Exists));
     }
 
     let parinode = inodes::get(parent_ino)?;
+    check_write(&parinode, owner)?;
+
     if let Ok(dirino) = inodes::create(FileMode::DIR_DEF | mode) {
+        perms::set_owner(&dirino, owner);
+
         // create directory itself
         if let Err(e) = links::create(&parinode, name, &dirino) {
             crate::open_files_mut().delete_file(dirino.inode).ok();
@@ -202,10 +226,12 @@ fn do_create(path: &str, mode: FileMode) -> Result<(), Error> {
 }
 
 /// Removes the directory at given path if it is empty
-pub fn remove(path: &str) -> Result<(), Error> {
+///
+/// Fails if `id` is not allowed to change the parent directory.
+pub fn remove(path: &str, id: &Identity) -> Result<(), Error> {
     log!(LogFlags::FSDirs, "dirs::remove(path={})", path);
 
-    let ino = search(path, false)?;
+    let ino = search(path, None)?;
     // cannot remove root directory
     if ino == 0 {
         return Err(Error::new(Code::InvArgs));
@@ -232,7 +258,7 @@ pub fn remove(path: &str) -> Result<(), Error> {
     // hardlinks to directories are not possible, thus we always have 2 ( . and ..)
     assert!(inode.links == 2, "expected 2 links, found {}", inode.links);
 
-    let parent_inode = unlink(path, false)?;
+    let parent_inode = unlink(path, false, id)?;
 
     // we have already removed the entry; if something fails now we're screwed
     inodes::decrease_links(&parent_inode).unwrap();
@@ -242,7 +268,9 @@ pub fn remove(path: &str) -> Result<(), Error> {
 }
 
 /// Creates a link at `new_path` to `old_path`
-pub fn link(old_path: &str, new_path: &str) -> Result<(), Error> {
+///
+/// Fails if `id` is not allowed to change the directory of `new_path`.
+pub fn link(old_path: &str, new_path: &str, id: &Identity) -> Result<(), Error> {
     log!(
         LogFlags::FSDirs,
         "dirs::link(old_path={}, new_path={})",
@@ -250,7 +278,7 @@ pub fn link(old_path: &str, new_path: &str) -> Result<(), Error> {
         new_path
     );
 
-    let old_ino = search(old_path, false)?;
+    let old_ino = search(old_path, None)?;
 
     // it can't be a directory
     let old_inode = inodes::get(old_ino)?;
@@ -260,8 +288,9 @@ pub fn link(old_path: &str, new_path: &str) -> Result<(), Error> {
 
     let (dir, name) = split_path(new_path);
 
-    let base_ino = search(dir, false)?;
+    let base_ino = search(dir, None)?;
     let base_inode = inodes::get(base_ino)?;
+    check_write(&base_inode, id)?;
 
     // the destination cannot already exist
     if find_entry(&base_inode, name).is_ok() {
@@ -273,10 +302,11 @@ pub fn link(old_path: &str, new_path: &str) -> Result<(), Error> {
 
 /// Removes the directory entry at given path
 ///
-/// If `deny_dir` is true and the path points to a directory, the call fails.
+/// If `deny_dir` is true and the path points to a directory, the call fails. The call also fails
+/// if `id` is not allowed to change the directory.
 ///
 /// Returns the directory inode
-pub fn unlink(path: &str, deny_dir: bool) -> Result<INodeRef, Error> {
+pub fn unlink(path: &str, deny_dir: bool, id: &Identity) -> Result<INodeRef, Error> {
     log!(
         LogFlags::FSDirs,
         "dirs::unlink(path={}, deny_dir={})",
@@ -290,14 +320,17 @@ pub fn unlink(path: &str, deny_dir: bool) -> Result<INodeRef, Error> {
         return Err(Error::new(Code::InvArgs));
     }
 
-    let par_ino = search(dir, false)?;
+    let par_ino = search(dir, None)?;
     let par_inode = inodes::get(par_ino)?;
+    check_write(&par_inode, id)?;
 
     links::remove(&par_inode, name, deny_dir).map(|_| par_inode)
 }
 
 /// Renames `old_path` to `new_path`
-pub fn rename(old_path: &str, new_path: &str) -> Result<(), Error> {
+///
+/// Fails if `id` is not allowed to change the directories of both paths.
+pub fn rename(old_path: &str, new_path: &str, id: &Identity) -> Result<(), Error> {
     log!(
         LogFlags::FSDirs,
         "dirs::rename(old_path={}, new_path={})",
@@ -311,8 +344,9 @@ pub fn rename(old_path: &str, new_path: &str) -> Result<(), Error> {
     if old_name.is_empty() || old_name == "." || old_name == ".." {
         return Err(Error::new(Code::InvArgs));
     }
-    let old_dir_ino = search(old_dir, false)?;
+    let old_dir_ino = search(old_dir, None)?;
     let old_dir_inode = inodes::get(old_dir_ino)?;
+    check_write(&old_dir_inode, id)?;
 
     // get old inode to link to
     let old_ino = find_entry(&old_dir_inode, old_name)?;
@@ -329,8 +363,9 @@ pub fn rename(old_path: &str, new_path: &str) -> Result<(), Error> {
     if new_name.is_empty() || new_name == "." || new_name == ".." {
         return Err(Error::new(Code::InvArgs));
     }
-    let new_dir_ino = search(new_dir, false)?;
+    let new_dir_ino = search(new_dir, None)?;
     let new_dir_inode = inodes::get(new_dir_ino)?;
+    check_write(&new_dir_inode, id)?;
 
     // search for the entry in the new directory and change link to new inode if found
     let mut prev_ino = None;
@@ -352,6 +387,7 @@ pub fn rename(old_path: &str, new_path: &str) -> Result<(), Error> {
 
                     // set new inode
                     entry.nodeno = old_ino;
+                    inodes::mark_modified(&new_dir_inode);
                     break 'search_loop;
                 }
 
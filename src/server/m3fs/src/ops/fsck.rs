@@ -0,0 +1,311 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use crate::data::{
+    Bitmap, BlockNo, DirEntry, ExtentCache, InodeNo, DIR_ENTRY_LEN, INODE_DIR_COUNT,
+};
+use crate::ops::inodes;
+
+use core::mem;
+
+use m3::col::Vec;
+use m3::errors::Error;
+
+/// Collects the blocks and inodes that are reachable from the root directory
+struct Checker {
+    blocks: Vec<u8>,
+    inodes: Vec<u8>,
+    total_blocks: u32,
+    total_inodes: u32,
+    errors: usize,
+}
+
+macro_rules! report {
+    ($chk:expr, $fmt:tt, $($args:tt)*) => {{
+        println!(concat!("m3fs: fsck: ", $fmt), $($args)*);
+        $chk.errors += 1;
+    }};
+}
+
+impl Checker {
+    fn use_inode(&mut self, ino: InodeNo) -> bool {
+        let mut bitmap = Bitmap::from_bytes(&mut self.inodes);
+        if bitmap.is_bit_set(ino as usize) {
+            return false;
+        }
+        bitmap.set_bit(ino as usize);
+        true
+    }
+
+    fn use_block(&mut self, ino: InodeNo, bno: BlockNo) {
+        if bno >= self.total_blocks {
+            report!(self, "INode {} uses invalid block {}", ino, bno);
+            return;
+        }
+
+        let mut bitmap = Bitmap::from_bytes(&mut self.blocks);
+        if bitmap.is_bit_set(bno as usize) {
+            report!(self, "Block {} is used (at least) twice", bno);
+        }
+        bitmap.set_bit(bno as usize);
+    }
+
+    fn check_inode(&mut self, ino: InodeNo, todo: &mut Vec<InodeNo>) -> Result<(), Error> {
+        let inode = inodes::get(ino)?;
+        if inode.inode != ino {
+            report!(
+                self,
+                "INode {} says that its inode-number is {}",
+                ino,
+                inode.inode
+            );
+        }
+
+        let mut extents = 0;
+        let mut dir_blocks = Vec::new();
+        for ext in inode.extent_iter() {
+            extents += 1;
+            // files can have holes, which do not occupy blocks
+            if ext.is_hole() {
+                continue;
+            }
+            if ext.length == 0 {
+                report!(self, "INode {} has empty extent {}", ino, extents - 1);
+                continue;
+            }
+
+            for bno in ext.block_range() {
+                self.use_block(ino, bno);
+                if inode.mode.is_dir() && bno < self.total_blocks {
+                    dir_blocks.push(bno);
+                }
+            }
+        }
+        if extents != inode.extents {
+            report!(
+                self,
+                "INode {} has {} extents, but only {} can be found",
+                ino,
+                inode.extents,
+                extents
+            );
+        }
+
+        let ext_per_block = crate::superblock().extents_per_block() as u32;
+        if inode.extents as usize > INODE_DIR_COUNT {
+            if inode.indirect == 0 {
+                report!(
+                    self,
+                    "INode {} has {} extents, but no indirect block",
+                    ino,
+                    inode.extents
+                );
+            }
+            else {
+                self.use_block(ino, inode.indirect);
+            }
+        }
+        else if inode.indirect != 0 {
+            report!(
+                self,
+                "INode {} has {} extents, but an indirect block",
+                ino,
+                inode.extents
+            );
+        }
+
+        if inode.extents > INODE_DIR_COUNT as u32 + ext_per_block {
+            if inode.dindirect == 0 {
+                report!(
+                    self,
+                    "INode {} has {} extents, but no double-indirect block",
+                    ino,
+                    inode.extents
+                );
+            }
+            else {
+                self.use_block(ino, inode.dindirect);
+
+                let count = inode.extents - (INODE_DIR_COUNT as u32 + ext_per_block);
+                let count = (count + ext_per_block - 1) / ext_per_block;
+                let dind =
+                    ExtentCache::from_buffer(crate::meta_buffer_mut().get_block(inode.dindirect)?);
+                for i in 0..count as usize {
+                    if dind[i].length != 1 {
+                        report!(
+                            self,
+                            "Double-indirect entry {} of INode {} has a length of {} instead of 1",
+                            i,
+                            ino,
+                            dind[i].length
+                        );
+                    }
+                    self.use_block(ino, dind[i].start);
+                }
+            }
+        }
+        else if inode.dindirect != 0 {
+            report!(
+                self,
+                "INode {} has {} extents, but a double-indirect block",
+                ino,
+                inode.extents
+            );
+        }
+
+        // release the inode before walking through the directory to not pin too many blocks
+        let is_dir = inode.mode.is_dir();
+        drop(inode);
+        if is_dir {
+            for bno in dir_blocks {
+                self.collect_entries(ino, bno, todo)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn collect_entries(
+        &mut self,
+        ino: InodeNo,
+        bno: BlockNo,
+        todo: &mut Vec<InodeNo>,
+    ) -> Result<(), Error> {
+        let block = crate::meta_buffer_mut().get_block(bno)?;
+        let end = block.data().len();
+        let mut off = 0;
+        while off + DIR_ENTRY_LEN <= end {
+            let entry = DirEntry::from_buffer(block.data(), off);
+            // actually next is not allowed to be 0, but we don't want to loop endlessly
+            if entry.next == 0 {
+                report!(
+                    self,
+                    "Directory {} has an invalid entry in block {}",
+                    ino,
+                    bno
+                );
+                break;
+            }
+
+            if entry.name() != "." && entry.name() != ".." {
+                if entry.nodeno >= self.total_inodes {
+                    report!(
+                        self,
+                        "Found invalid inode number {} in directory {}, entry '{}'",
+                        entry.nodeno,
+                        ino,
+                        entry.name()
+                    );
+                }
+                else {
+                    todo.push(entry.nodeno);
+                }
+            }
+            off += entry.next as usize;
+        }
+        Ok(())
+    }
+
+    fn compare(
+        &mut self,
+        name: &str,
+        first: BlockNo,
+        total: u32,
+        free: u32,
+        mut used: Vec<u8>,
+    ) -> Result<(), Error> {
+        let perblock = crate::superblock().block_size as usize * 8;
+        let used = Bitmap::from_bytes(&mut used);
+
+        let mut count = 0;
+        for (i, bno) in (first..)
+            .enumerate()
+            .take((total as usize + perblock - 1) / perblock)
+        {
+            let mut block = crate::meta_buffer_mut().get_block(bno)?;
+            let marked = Bitmap::from_bytes(block.data_mut());
+
+            let max = (total as usize - i * perblock).min(perblock);
+            for bit in 0..max {
+                let no = i * perblock + bit;
+                if !marked.is_bit_set(bit) {
+                    count += 1;
+                }
+
+                if used.is_bit_set(no) && !marked.is_bit_set(bit) {
+                    report!(self, "{} {} is in use, but NOT marked used", name, no);
+                }
+                else if !used.is_bit_set(no) && marked.is_bit_set(bit) {
+                    report!(self, "{} {} is NOT in use, but marked used", name, no);
+                }
+            }
+        }
+
+        if count != free {
+            report!(
+                self,
+                "Allocator says {} free {}s, but the bitmap has {} free {}s",
+                free,
+                name,
+                count,
+                name
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Checks the consistency of the file system.
+///
+/// All inodes and blocks that are reachable from the root directory are collected and compared
+/// against the inode and block bitmap. Returns the number of found inconsistencies.
+pub fn check() -> Result<usize, Error> {
+    let (total_blocks, total_inodes, first_data) = {
+        let sb = crate::superblock();
+        (sb.total_blocks, sb.total_inodes, sb.first_data_block())
+    };
+
+    let mut chk = Checker {
+        blocks: vec![0; (total_blocks as usize + 7) / 8],
+        inodes: vec![0; (total_inodes as usize + 7) / 8],
+        total_blocks,
+        total_inodes,
+        errors: 0,
+    };
+
+    // superblock, bitmaps, inode blocks, and journal are always in use
+    for bno in 0..first_data {
+        chk.use_block(0, bno);
+    }
+
+    // collect all inode and block numbers from the directory tree
+    let mut todo = vec![0];
+    while let Some(ino) = todo.pop() {
+        if chk.use_inode(ino) {
+            chk.check_inode(ino, &mut todo)?;
+        }
+    }
+
+    let (inodebm, blockbm) = {
+        let sb = crate::superblock();
+        (sb.first_inodebm_block(), sb.first_blockbm_block())
+    };
+    let free_inodes = crate::inodes_mut().free_count();
+    let free_blocks = crate::blocks_mut().free_count();
+    let (inodes, blocks) = (mem::take(&mut chk.inodes), mem::take(&mut chk.blocks));
+    chk.compare("INode", inodebm, total_inodes, free_inodes, inodes)?;
+    chk.compare("Block", blockbm, total_blocks, free_blocks, blocks)?;
+
+    Ok(chk.errors)
+}
@@ -16,19 +16,32 @@
 
 use crate::buf::LoadLimit;
 use crate::data::{
-    ExtPos, Extent, ExtentCache, ExtentRef, INodeRef, InodeNo, INODE_DIR_COUNT, NUM_EXT_BYTES,
-    NUM_INODE_BYTES,
+    BlockRange, ExtPos, Extent, ExtentCache, ExtentRef, INodeRef, InodeNo, Time, INODE_DIR_COUNT,
+    MAX_BLOCK_SIZE, NUM_EXT_BYTES, NUM_INODE_BYTES,
 };
 
 use base::io::LogFlags;
+use core::cmp;
 use m3::{
-    cap::Selector,
-    com::Perm,
+    cap::{SelSpace, Selector},
+    cell::LazyStaticRefCell,
+    com::{MemGate, Perm},
     errors::{Code, Error},
+    kif::{CapRngDesc, CapType},
+    mem::GlobOff,
+    syscalls,
+    tiles::Activity,
+    time::SystemTime,
     util::math,
     vfs::{FileMode, SeekMode},
 };
 
+/// The number of blocks of the zero-filled memory that is handed out for holes
+const HOLE_BLOCKS: usize = 16;
+
+/// Zero-filled memory that is handed out read-only to read holes in sparse files
+static ZEROS: LazyStaticRefCell<MemGate> = LazyStaticRefCell::default();
+
 /// Creates a new inode with given mode and returns its INodeRef
 pub fn create(mode: FileMode) -> Result<INodeRef, Error> {
     log!(LogFlags::FSINodes, "inodes::create(mode={:o})", mode);
@@ -40,12 +53,53 @@ pub fn create(mode: FileMode) -> Result<INodeRef, Error> {
     inode.as_mut().inode = ino;
     inode.as_mut().devno = 0; // TODO
     inode.as_mut().mode = mode;
+    let now = now();
+    inode.as_mut().lastaccess = now;
+    inode.as_mut().lastmod = now;
+    inode.as_mut().lastchange = now;
     Ok(inode)
 }
 
+/// Returns the current time in seconds since the Unix epoch
+///
+/// The time is only meaningful if the wall-clock time has been synchronized via the clock service.
+fn now() -> Time {
+    SystemTime::now().as_unix_secs() as Time
+}
+
+/// Updates the modification and change time of the given inode after its content was modified
+pub fn mark_modified(inode: &INodeRef) {
+    let now = now();
+    inode.as_mut().lastmod = now;
+    inode.as_mut().lastchange = now;
+}
+
+/// Updates the change time of the given inode after its metadata was changed
+pub fn mark_changed(inode: &INodeRef) {
+    inode.as_mut().lastchange = now();
+}
+
+/// Sets the access and modification time of the given inode explicitly
+///
+/// The change time is set to the current time, as the metadata has been changed.
+pub fn set_times(inode: &INodeRef, lastaccess: Time, lastmod: Time) {
+    log!(
+        LogFlags::FSINodes,
+        "inodes::set_times(inode={}, lastaccess={}, lastmod={})",
+        inode.inode,
+        lastaccess,
+        lastmod
+    );
+
+    inode.as_mut().lastaccess = lastaccess;
+    inode.as_mut().lastmod = lastmod;
+    mark_changed(inode);
+}
+
 /// Decreases the number of links for the given inode and deletes it, if there are no links anymore
 pub fn decrease_links(inode: &INodeRef) -> Result<(), Error> {
     inode.as_mut().links -= 1;
+    mark_changed(inode);
     if inode.links == 0 {
         let ino = inode.inode;
         crate::open_files_mut().delete_file(ino)?;
@@ -170,7 +224,12 @@ pub fn get_extent_mem(
     let blocksize = crate::superblock().block_size;
     let mut extlen = (ext.length * blocksize) as usize;
 
-    let mut bytes = crate::backend_mut().get_filedata(*ext, start.off, perms, sel, Some(limit))?;
+    let mut bytes = if ext.is_hole() {
+        get_hole_mem(*ext, start.off, sel)?
+    }
+    else {
+        crate::backend_mut().get_filedata(*ext, start.off, perms, sel, Some(limit))?
+    };
 
     // stop at file end
     if (start.ext == (inode.extents - 1) as usize)
@@ -186,6 +245,162 @@ pub fn get_extent_mem(
     Ok((bytes, extlen))
 }
 
+/// Returns the blocks from the given position until the end of its extent.
+///
+/// Returns `None` if the position is within a hole or beyond the last extent
+pub fn get_blocks(inode: &INodeRef, pos: &ExtPos) -> Result<Option<BlockRange>, Error> {
+    if pos.ext >= inode.extents as usize {
+        return Ok(None);
+    }
+
+    let mut indir = None;
+    let ext = get_extent(inode, pos.ext, &mut indir, false)?;
+    let first = (pos.off / crate::superblock().block_size as usize) as u32;
+    if ext.is_hole() || first >= ext.length {
+        return Ok(None);
+    }
+    Ok(Some(BlockRange::new_range(
+        ext.start + first,
+        ext.length - first,
+    )))
+}
+
+/// Derives a read-only capability for zeros that covers the hole `ext`, beginning at the block
+/// containing `extoff`.
+///
+/// Returns the number of bytes covered by the capability
+fn get_hole_mem(ext: Extent, extoff: usize, sel: Selector) -> Result<usize, Error> {
+    let blocksize = crate::superblock().block_size as usize;
+
+    if !ZEROS.is_some() {
+        let mem = MemGate::new((HOLE_BLOCKS * blocksize) as GlobOff, Perm::RW)?;
+        let zeros = [0u8; MAX_BLOCK_SIZE as usize];
+        for i in 0..HOLE_BLOCKS {
+            mem.write_bytes(zeros.as_ptr(), blocksize, (i * blocksize) as GlobOff)?;
+        }
+        ZEROS.set(mem);
+    }
+
+    let first_block = extoff / blocksize;
+    let bytes = cmp::min(ext.length as usize - first_block, HOLE_BLOCKS) * blocksize;
+    syscalls::derive_mem(
+        Activity::own().sel(),
+        sel,
+        ZEROS.borrow().sel(),
+        0,
+        bytes as GlobOff,
+        Perm::R,
+    )?;
+    Ok(bytes)
+}
+
+/// Extends the given inode to `off` bytes without allocating blocks for the new part.
+///
+/// The remainder of the currently last block is zeroed and all further blocks up to `off` are
+/// added as a hole.
+pub fn extend(inode: &INodeRef, off: usize) -> Result<(), Error> {
+    log!(
+        LogFlags::FSINodes,
+        "inodes::extend(inode={}, off={})",
+        inode.inode,
+        off,
+    );
+
+    let blocksize = crate::superblock().block_size as usize;
+    let size = inode.size as usize;
+    assert!(off > size);
+
+    // the client might have written beyond the end of the file within the last block
+    let unaligned = size % blocksize;
+    if unaligned > 0 {
+        let mut indir = None;
+        let ext = get_extent(inode, (inode.extents - 1) as usize, &mut indir, false)?;
+        if !ext.is_hole() {
+            let last = Extent::new(ext.start + ext.length - 1, 1);
+            let sel = SelSpace::get().alloc_sel();
+            let mut limit = LoadLimit::new();
+            crate::backend_mut().get_filedata(last, 0, Perm::RW, sel, Some(&mut limit))?;
+            let mem = MemGate::new_bind(sel)?;
+            let zeros = [0u8; MAX_BLOCK_SIZE as usize];
+            mem.write_bytes(zeros.as_ptr(), blocksize - unaligned, unaligned as GlobOff)?;
+            Activity::own().revoke(CapRngDesc::new(CapType::Object, sel, 1), false)?;
+        }
+    }
+
+    let blocks = (math::round_up(off, blocksize) - math::round_up(size, blocksize)) / blocksize;
+    if blocks > 0 {
+        append_extent(inode, Extent::new_hole(blocks as u32))?;
+    }
+
+    inode.as_mut().size = off as u64;
+    Ok(())
+}
+
+/// Allocates blocks for the hole at given position, if the position refers to a hole.
+///
+/// The hole is split so that the block containing `pos` and up to `extend` following blocks are
+/// backed by new and cleared blocks. Afterwards, `pos` refers to the same file position within the
+/// new extent.
+pub fn fill_hole(inode: &INodeRef, pos: &mut ExtPos) -> Result<(), Error> {
+    if pos.ext >= inode.extents as usize {
+        return Ok(());
+    }
+
+    let mut indir = None;
+    let ext = get_extent(inode, pos.ext, &mut indir, false)?;
+    if !ext.is_hole() {
+        return Ok(());
+    }
+
+    log!(
+        LogFlags::FSINodes,
+        "inodes::fill_hole(inode={}, pos={:?})",
+        inode.inode,
+        pos,
+    );
+
+    let blocksize = crate::superblock().block_size as usize;
+    let first = (pos.off / blocksize) as u32;
+    let mut count = cmp::min(crate::settings().extend, (ext.length - first) as usize);
+    let start = crate::blocks_mut().alloc(Some(&mut count))?;
+    let data = Extent::new(start, count as u32);
+    // the blocks need to read as zeros, even if the client does not write all of them
+    crate::backend_mut().clear_extent(data)?;
+
+    let after = ext.length - first - count as u32;
+    let mut idx = pos.ext;
+    if first > 0 {
+        ext.as_mut().length = first;
+        idx += 1;
+        insert_extent(inode, idx, data)?;
+        pos.ext = idx;
+        pos.off -= first as usize * blocksize;
+    }
+    else {
+        *ext.as_mut() = data;
+    }
+    if after > 0 {
+        insert_extent(inode, idx + 1, Extent::new_hole(after))?;
+    }
+
+    Ok(())
+}
+
+/// Inserts `ext` at index `idx` into the extents of given inode, moving all following extents
+/// back by one.
+fn insert_extent(inode: &INodeRef, idx: usize, ext: Extent) -> Result<(), Error> {
+    let mut indir = None;
+    let mut i = inode.extents as usize;
+    while i > idx {
+        let prev = *get_extent(inode, i - 1, &mut indir, false)?;
+        *get_extent(inode, i, &mut indir, true)?.as_mut() = prev;
+        i -= 1;
+    }
+    *get_extent(inode, idx, &mut indir, true)?.as_mut() = ext;
+    inode.as_mut().extents += 1;
+    Ok(())
+}
+
 /// Requests an append of a new block to given inode and creates a MemGate to access the block.
 ///
 /// Note that this only requests the append, but does not append anything.
@@ -253,7 +468,14 @@ pub fn append_extent(inode: &INodeRef, next: Extent) -> Result<bool, Error> {
     // try to load existing inode
     let ext = if inode.extents > 0 {
         let ext = get_extent(inode, (inode.extents - 1) as usize, &mut indir, false)?;
-        if ext.start + ext.length != next.start {
+        // holes can only be merged with holes
+        let mergeable = if ext.is_hole() || next.is_hole() {
+            ext.is_hole() && next.is_hole()
+        }
+        else {
+            ext.start + ext.length == next.start
+        };
+        if !mergeable {
             None
         }
         else {
@@ -524,7 +746,9 @@ pub fn truncate(inode: &INodeRef, pos: &ExtPos) -> Result<(), Error> {
         let mut i = iextents - 1;
         while i > pos.ext {
             let ext = change_extent(inode, i, &mut indir, true)?;
-            crate::blocks_mut().free(ext.start as usize, ext.length as usize)?;
+            if !ext.is_hole() {
+                crate::blocks_mut().free(ext.start as usize, ext.length as usize)?;
+            }
             inode.as_mut().extents -= 1;
             inode.as_mut().size -= (ext.length * blocksize) as u64;
             ext.as_mut().start = 0;
@@ -552,7 +776,7 @@ pub fn truncate(inode: &INodeRef, pos: &ExtPos) -> Result<(), Error> {
                     diff
                 };
                 let blocks = bdiff / blocksize as usize;
-                if blocks > 0 {
+                if blocks > 0 && !ext.is_hole() {
                     // free all of these blocks
                     crate::blocks_mut().free((ext.start + ext.length) as usize - blocks, blocks)?;
                 }
@@ -577,7 +801,7 @@ pub fn sync_metadata(inode: &INodeRef) -> Result<(), Error> {
         inode.inode,
     );
 
-    for ext in inode.extent_iter() {
+    for ext in inode.extent_iter().filter(|ext| !ext.is_hole()) {
         for mut block in ext.block_iter() {
             crate::backend_mut().sync_meta(&mut block)?;
             block.flush()?;
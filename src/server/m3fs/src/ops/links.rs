@@ -84,6 +84,8 @@ pub fn create(dir: &INodeRef, name: &str, inode: &INodeRef) -> Result<(), Error>
     }
 
     inode.as_mut().links += 1;
+    inodes::mark_changed(inode);
+    inodes::mark_modified(dir);
     Ok(())
 }
 
@@ -142,6 +144,8 @@ pub fn remove(dir: &INodeRef, name: &str, deny_dir: bool) -> Result<(), Error> {
                         }
                     }
 
+                    inodes::mark_modified(dir);
+
                     // reduce links and free if necessary
                     inodes::decrease_links(&inode)?;
 
@@ -15,5 +15,7 @@
  */
 
 pub mod dirs;
+pub mod fsck;
 pub mod inodes;
 pub mod links;
+pub mod perms;
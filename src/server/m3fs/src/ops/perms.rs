@@ -0,0 +1,98 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use crate::data::INodeRef;
+
+use base::io::LogFlags;
+use m3::errors::{Code, Error};
+use m3::util::parse;
+use m3::vfs::FileMode;
+
+/// The identity of a client, which is used for permission checks
+///
+/// The identity is assigned to a session via the session arguments in the config of the resource
+/// manager (`uid=<id>` and `gid=<id>`) and thus cannot be chosen by the client. Sessions without
+/// identity belong to the superuser.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Identity {
+    pub uid: u16,
+    pub gid: u16,
+}
+
+impl Identity {
+    /// The superuser, which is treated as the owner of all files
+    pub const ROOT: Self = Self { uid: 0, gid: 0 };
+
+    /// Returns true if this is the superuser
+    pub fn is_root(&self) -> bool {
+        self.uid == 0
+    }
+
+    /// Parses the given session argument and updates the identity accordingly
+    ///
+    /// Returns false if the argument does not belong to the identity.
+    pub fn parse_arg(&mut self, arg: &str) -> Result<bool, Error> {
+        if let Some(uid) = arg.strip_prefix("uid=") {
+            self.uid = parse::int(uid)? as u16;
+        }
+        else if let Some(gid) = arg.strip_prefix("gid=") {
+            self.gid = parse::int(gid)? as u16;
+        }
+        else {
+            return Ok(false);
+        }
+        Ok(true)
+    }
+}
+
+/// Checks whether `id` has the permissions `perm` for the given inode
+///
+/// The permissions are given in terms of the bits for others (`IROTH`, `IWOTH`, and `IXOTH`) and
+/// are checked against the owner, group, or other bits of the inode, depending on `id`. The
+/// superuser uses the owner bits for all files.
+pub fn check(inode: &INodeRef, id: &Identity, perm: FileMode) -> Result<(), Error> {
+    let perm = perm & FileMode::IRWXO;
+    let granted = if id.is_root() || id.uid == inode.uid {
+        (inode.mode & FileMode::IRWXU).bits() >> 6
+    }
+    else if id.gid == inode.gid {
+        (inode.mode & FileMode::IRWXG).bits() >> 3
+    }
+    else {
+        (inode.mode & FileMode::IRWXO).bits()
+    };
+
+    if (granted & perm.bits()) != perm.bits() {
+        log!(
+            LogFlags::FSSess,
+            "insufficient permissions: inode={}, mode={:o}, owner={}:{}, id={}:{}, perm={:o}",
+            inode.inode,
+            inode.mode,
+            inode.uid,
+            inode.gid,
+            id.uid,
+            id.gid,
+            perm
+        );
+        return Err(Error::new(Code::NoPerm));
+    }
+    Ok(())
+}
+
+/// Makes `id` the owner of the given inode
+pub fn set_owner(inode: &INodeRef, id: &Identity) {
+    inode.as_mut().uid = id.uid;
+    inode.as_mut().gid = id.gid;
+}
@@ -15,14 +15,15 @@
  * General Public License version 2 for more details.
  */
 
-use crate::buf::LoadLimit;
-use crate::data::{ExtPos, Extent, INodeRef, InodeNo};
+use crate::buf::{self, LoadLimit};
+use crate::data::{BlockNo, ExtPos, Extent, INodeRef, InodeNo};
 use crate::ops::inodes;
 use crate::sess::{meta_session::FileLimit, M3FSSession};
 
 use m3::{
     cap::{SelSpace, Selector},
     cell::RefCell,
+    client::WatchEvent,
     col::{String, ToString, Vec},
     com::GateIStream,
     errors::{Code, Error},
@@ -34,6 +35,8 @@ use m3::{
     vfs::{OpenFlags, SeekMode},
 };
 
+use core::mem;
+
 struct Entry {
     sel: Selector,
 }
@@ -60,26 +63,57 @@ impl CapContainer {
     }
 }
 
+// the position state of a session, which is restored if a prefetched chunk is not used
+#[derive(Copy, Clone)]
+struct ChunkState {
+    cur_pos: ExtPos,
+    cur_extlen: usize,
+    cur_bytes: usize,
+    next_pos: ExtPos,
+    next_fileoff: usize,
+    seq_off: usize,
+    modified: bool,
+}
+
+// a chunk that has already been made accessible via the second EP in pipelined mode
+struct Prefetch {
+    out: bool,
+    prev: ChunkState,
+    capoff: usize,
+    sel: Selector,
+    block: Option<BlockNo>,
+}
+
 pub struct FileSession {
     // current position (the one that the client has access to)
     cur_pos: ExtPos,   // extent position
     cur_extlen: usize, // length of the extent
     cur_bytes: usize,  // number of bytes
     cur_sel: Selector, // memory capability
+    // first block of the memory capability, if the client can write to it
+    cur_block: Option<BlockNo>,
 
     // next position (the one that the client gets access to next)
     next_pos: ExtPos,    // extent position
     next_fileoff: usize, // file position (global offset)
 
     load_limit: LoadLimit,
+    // file position at which the next read continues sequentially
+    seq_off: usize,
 
     // for an ongoing append
     appending: bool,
     append_ext: Option<Extent>,
 
+    // whether the client got write access since we notified watches the last time
+    modified: bool,
+
     // capabilities
     capscon: CapContainer,
     epcap: Selector,
+    // the second EP for pipelined transfers and the chunk we prepared on it
+    next_epcap: Selector,
+    prefetch: Option<Prefetch>,
 
     // the file the client has access to
     oflags: OpenFlags,
@@ -113,17 +147,23 @@ impl FileSession {
             cur_extlen: 0,
             cur_bytes: 0,
             cur_sel: m3::kif::INVALID_SEL,
+            cur_block: None,
 
             next_pos: ExtPos::new(0, 0),
             next_fileoff: 0,
 
             load_limit: LoadLimit::new(),
+            seq_off: 0,
 
             appending: false,
             append_ext: None,
 
+            modified: false,
+
             capscon: CapContainer { caps: vec![] },
             epcap: m3::kif::INVALID_SEL,
+            next_epcap: m3::kif::INVALID_SEL,
+            prefetch: None,
 
             oflags,
             filename: filename.to_string(),
@@ -192,7 +232,12 @@ impl FileSession {
         let inode = inodes::get(self.ino)?;
 
         // determine extent from byte offset
-        let (_, extpos) = inodes::get_seek_pos(&inode, offset as usize, SeekMode::Set)?;
+        let (_, mut extpos) = inodes::get_seek_pos(&inode, offset as usize, SeekMode::Set)?;
+
+        // writable mappings need real blocks instead of the shared zeros of holes
+        if self.oflags.contains(OpenFlags::W) {
+            inodes::fill_hole(&inode, &mut extpos)?;
+        }
 
         let sel = SelSpace::get().alloc_sel();
         let (len, _) = inodes::get_extent_mem(
@@ -222,21 +267,54 @@ impl FileSession {
     }
 
     fn revoke_cap(&mut self) {
-        if self.cur_sel != m3::kif::INVALID_SEL {
+        Self::release_chunk(self.cur_sel, self.cur_block.take());
+        self.cur_sel = m3::kif::INVALID_SEL;
+    }
+
+    fn release_chunk(sel: Selector, block: Option<BlockNo>) {
+        if sel != m3::kif::INVALID_SEL {
             m3::tiles::Activity::own()
                 .revoke(
-                    m3::kif::CapRngDesc::new(m3::kif::CapType::Object, self.cur_sel, 1),
+                    m3::kif::CapRngDesc::new(m3::kif::CapType::Object, sel, 1),
                     false,
                 )
                 .unwrap();
-            self.cur_sel = m3::kif::INVALID_SEL;
+        }
+
+        // the client cannot write to the blocks anymore, so that they can be written back
+        if let Some(bno) = block {
+            crate::file_buffer_mut().release(bno);
         }
     }
 
+    fn pipelined(&self) -> bool {
+        self.next_epcap != INVALID_SEL
+    }
+
     pub fn set_ep(&mut self, ep: Selector) {
         self.epcap = ep;
     }
 
+    pub fn set_next_ep(&mut self, ep: Selector) -> Result<(), Error> {
+        if !self.oflags.contains(OpenFlags::PIPELINE) {
+            return Err(Error::new(Code::InvArgs));
+        }
+
+        self.cancel_prefetch();
+        self.next_epcap = ep;
+        Ok(())
+    }
+
+    pub fn remove_ep(&mut self, ep: Selector) {
+        if self.epcap == ep {
+            self.epcap = INVALID_SEL;
+        }
+        if self.next_epcap == ep {
+            self.cancel_prefetch();
+            self.next_epcap = INVALID_SEL;
+        }
+    }
+
     pub fn ino(&self) -> InodeNo {
         self.ino
     }
@@ -279,15 +357,58 @@ impl FileSession {
 
         let inode = inodes::get(self.ino)?;
 
+        // continue with the prepared chunk, if there is one for this kind of request
+        if let Some(pf) = self.prefetch.take() {
+            if pf.out == out {
+                return self.use_prefetch(is, &inode, pf);
+            }
+            self.drop_prefetch(pf);
+        }
+
         // in/out implicitly commits the previous in/out request
         if out && self.appending {
             self.commit_append(&inode, self.cur_bytes)?;
         }
+        self.notify_modified();
 
+        let (capoff, sel, block) = self.next_chunk(&inode, out, self.epcap)?;
+
+        log!(
+            LogFlags::FSSess,
+            "[{}] file::next_{}() -> ({:?}, {})",
+            self.session_id,
+            if out { "out" } else { "in" },
+            self.cur_pos,
+            self.cur_bytes
+        );
+
+        if self.pipelined() {
+            reply_vmsg!(is, Code::Success, capoff, self.cur_bytes, false)?;
+        }
+        else {
+            reply_vmsg!(is, Code::Success, capoff, self.cur_bytes)?;
+        }
+
+        self.revoke_cap();
+        self.cur_sel = sel;
+        if sel != INVALID_SEL && self.oflags.contains(OpenFlags::W) {
+            self.cur_block = block;
+        }
+
+        self.prefetch_next(&inode, out);
+        Ok(())
+    }
+
+    fn next_chunk(
+        &mut self,
+        inode: &INodeRef,
+        out: bool,
+        ep: Selector,
+    ) -> Result<(usize, Selector, Option<BlockNo>), Error> {
         let mut sel = SelSpace::get().alloc_sel();
 
         // do we need to append to the file?
-        let (len, extlen) = if out && (self.next_fileoff as u64 == inode.size) {
+        let (len, extlen, block) = if out && (self.next_fileoff as u64 >= inode.size) {
             let mut files = crate::open_files_mut();
             let open_file = files.get_file_mut(self.ino).unwrap();
 
@@ -300,34 +421,52 @@ impl FileSession {
                 return Err(Error::new(Code::Exists));
             }
 
+            // the client has seeked beyond the end; leave a hole in between
+            if self.next_fileoff as u64 > inode.size {
+                inodes::extend(inode, self.next_fileoff)?;
+                let (_, extpos) = inodes::get_seek_pos(inode, 0, SeekMode::End)?;
+                self.next_pos = extpos;
+            }
+
             // continue in last extent, if there is space
             if (self.next_pos.ext > 0)
                 && (self.next_fileoff as u64 == inode.size)
                 && ((self.next_fileoff % crate::superblock().block_size as usize) != 0)
             {
-                let (fileoff, extpos) = inodes::get_seek_pos(&inode, 0, SeekMode::End)?;
+                let (fileoff, extpos) = inodes::get_seek_pos(inode, 0, SeekMode::End)?;
                 self.next_fileoff = fileoff;
                 self.next_pos = extpos;
             }
+            inodes::fill_hole(inode, &mut self.next_pos)?;
 
             let (len, extlen, new_ext) = inodes::req_append(
-                &inode,
+                inode,
                 &self.next_pos,
                 sel,
                 Perm::from(self.oflags),
                 &mut self.load_limit,
             )?;
+            let block = match new_ext {
+                Some(ext) => Some(ext.start),
+                None => inodes::get_blocks(inode, &self.next_pos)?.map(|b| b.start),
+            };
 
             self.appending = true;
             self.append_ext = new_ext;
 
             open_file.set_appending(true);
-            (len, extlen)
+            (len, extlen, block)
         }
         else {
+            // the client can write via the capability if it has write permission. Thus, we can
+            // only hand out the shared zeros of holes to readers.
+            if self.oflags.contains(OpenFlags::W) {
+                inodes::fill_hole(inode, &mut self.next_pos)?;
+            }
+
             // get next mem_cap
             let res = inodes::get_extent_mem(
-                &inode,
+                inode,
                 &self.next_pos,
                 Perm::from(self.oflags),
                 sel,
@@ -335,9 +474,13 @@ impl FileSession {
             );
             match res {
                 // if we didn't find the extent, turn that into EOF
-                Err(e) if e.code() == Code::NotFound => (0, 0),
+                Err(e) if e.code() == Code::NotFound => (0, 0, None),
                 Err(e) => return Err(e),
-                Ok((len, extlen)) => (len, extlen),
+                Ok((len, extlen)) => (
+                    len,
+                    extlen,
+                    inodes::get_blocks(inode, &self.next_pos)?.map(|b| b.start),
+                ),
             }
         };
 
@@ -345,7 +488,9 @@ impl FileSession {
         // to start is the offset within the first of these blocks
         let mut capoff = self.next_pos.off % crate::superblock().block_size as usize;
         if len > 0 {
-            syscalls::activate(self.epcap, sel, INVALID_SEL, 0)?;
+            syscalls::activate(ep, sel, INVALID_SEL, 0)?;
+
+            let sequential = !out && self.next_fileoff == self.seq_off;
 
             // move forward
             self.cur_pos = self.next_pos;
@@ -357,6 +502,16 @@ impl FileSession {
             }
 
             self.next_fileoff += len - capoff;
+
+            // sequential reads probably continue with the following blocks; load them ahead
+            if sequential {
+                if let Some(blocks) = inodes::get_blocks(inode, &self.next_pos)? {
+                    crate::file_buffer_mut().read_ahead(blocks);
+                }
+            }
+            if !out {
+                self.seq_off = self.next_fileoff;
+            }
         }
         else {
             self.cur_pos = ExtPos::new(0, 0);
@@ -366,25 +521,124 @@ impl FileSession {
 
         self.cur_extlen = extlen;
         self.cur_bytes = len - capoff;
+        if out && self.cur_bytes > 0 {
+            inodes::mark_modified(inode);
+            self.modified = true;
+        }
+
+        Ok((capoff, sel, block))
+    }
+
+    fn use_prefetch(
+        &mut self,
+        is: &mut GateIStream<'_>,
+        inode: &INodeRef,
+        pf: Prefetch,
+    ) -> Result<(), Error> {
+        self.notify_modified();
 
         log!(
             LogFlags::FSSess,
-            "[{}] file::next_{}() -> ({:?}, {})",
+            "[{}] file::next_{}() -> ({:?}, {}) [prefetched]",
             self.session_id,
-            if out { "out" } else { "in" },
+            if pf.out { "out" } else { "in" },
             self.cur_pos,
             self.cur_bytes
         );
 
-        reply_vmsg!(is, Code::Success, capoff, self.cur_bytes)?;
+        if let Err(e) = reply_vmsg!(is, Code::Success, pf.capoff, self.cur_bytes, true) {
+            self.drop_prefetch(pf);
+            return Err(e);
+        }
 
+        // the prepared chunk is accessible via the other EP, which is the current one from now on
         self.revoke_cap();
-        self.cur_sel = sel;
+        mem::swap(&mut self.epcap, &mut self.next_epcap);
+        self.cur_sel = pf.sel;
+        self.cur_block = pf.block;
 
+        self.prefetch_next(inode, pf.out);
         Ok(())
     }
 
+    fn prefetch_next(&mut self, inode: &INodeRef, out: bool) {
+        // appends need to be committed first and there is nothing to prepare beyond the end
+        if !self.pipelined()
+            || self.appending
+            || self.cur_bytes == 0
+            || self.next_fileoff as u64 >= inode.size
+        {
+            return;
+        }
+
+        let prev = self.save_state();
+        match self.next_chunk(inode, out, self.next_epcap) {
+            Ok((capoff, sel, block)) if sel != INVALID_SEL => {
+                log!(
+                    LogFlags::FSSess,
+                    "[{}] file::prefetch_{}() -> ({:?}, {})",
+                    self.session_id,
+                    if out { "out" } else { "in" },
+                    self.cur_pos,
+                    self.cur_bytes
+                );
+
+                self.prefetch = Some(Prefetch {
+                    out,
+                    prev,
+                    capoff,
+                    sel,
+                    block: block.filter(|_| self.oflags.contains(OpenFlags::W)),
+                });
+            },
+            // nothing to prepare; the next request will load the chunk on demand
+            _ => self.restore_state(prev),
+        }
+    }
+
+    fn drop_prefetch(&mut self, pf: Prefetch) {
+        log!(
+            LogFlags::FSSess,
+            "[{}] file::drop_prefetch(sel={})",
+            self.session_id,
+            pf.sel
+        );
+
+        self.restore_state(pf.prev);
+        Self::release_chunk(pf.sel, pf.block);
+    }
+
+    fn cancel_prefetch(&mut self) {
+        if let Some(pf) = self.prefetch.take() {
+            self.drop_prefetch(pf);
+        }
+    }
+
+    fn save_state(&self) -> ChunkState {
+        ChunkState {
+            cur_pos: self.cur_pos,
+            cur_extlen: self.cur_extlen,
+            cur_bytes: self.cur_bytes,
+            next_pos: self.next_pos,
+            next_fileoff: self.next_fileoff,
+            seq_off: self.seq_off,
+            modified: self.modified,
+        }
+    }
+
+    fn restore_state(&mut self, state: ChunkState) {
+        self.cur_pos = state.cur_pos;
+        self.cur_extlen = state.cur_extlen;
+        self.cur_bytes = state.cur_bytes;
+        self.next_pos = state.next_pos;
+        self.next_fileoff = state.next_fileoff;
+        self.seq_off = state.seq_off;
+        self.modified = state.modified;
+    }
+
     pub fn file_seek(&mut self, stream: &mut GateIStream<'_>) -> Result<(), Error> {
+        self.cancel_prefetch();
+
         let off: usize = stream.pop()?;
         let whence = stream.pop::<SeekMode>()?;
 
@@ -402,7 +656,13 @@ impl FileSession {
         }
 
         let inode = inodes::get(self.ino)?;
-        let (pos, extpos) = inodes::get_seek_pos(&inode, off, whence)?;
+        let (pos, extpos) = if whence == SeekMode::Set && off as u64 > inode.size {
+            // the gap is turned into a hole on the next write
+            (off, ExtPos::new(inode.extents as usize, 0))
+        }
+        else {
+            inodes::get_seek_pos(&inode, off, whence)?
+        };
         self.next_pos = extpos;
         self.next_fileoff = pos;
 
@@ -437,6 +697,9 @@ impl FileSession {
     }
 
     pub fn file_truncate(&mut self, stream: &mut GateIStream<'_>) -> Result<(), Error> {
+        // the prepared chunk might cover parts that are about to be removed
+        self.cancel_prefetch();
+
         let off: usize = stream.pop()?;
 
         log!(
@@ -449,12 +712,21 @@ impl FileSession {
 
         let inode = inodes::get(self.ino)?;
 
-        if off as u64 > inode.size {
-            return Err(Error::new(Code::InvArgs));
-        }
-
-        let (fileoff, extpos) = inodes::get_seek_pos(&inode, off, SeekMode::Set)?;
-        inodes::truncate(&inode, &extpos)?;
+        let (fileoff, extpos) = buf::transaction(|| {
+            if off as u64 > inode.size {
+                // growing the file leaves a hole at the end
+                inodes::extend(&inode, off)?;
+                inodes::get_seek_pos(&inode, off, SeekMode::Set)
+            }
+            else {
+                let (fileoff, extpos) = inodes::get_seek_pos(&inode, off, SeekMode::Set)?;
+                inodes::truncate(&inode, &extpos)?;
+                Ok((fileoff, extpos))
+            }
+        })?;
+        inodes::mark_modified(&inode);
+        self.modified = true;
+        self.notify_modified();
 
         // stay within the file bounds
         if self.next_fileoff > fileoff {
@@ -471,6 +743,9 @@ impl FileSession {
     }
 
     pub fn file_commit(&mut self, stream: &mut GateIStream<'_>) -> Result<(), Error> {
+        // the request refers to the current chunk, not to the prepared one
+        self.cancel_prefetch();
+
         let nbytes: usize = stream.pop()?;
 
         log!(
@@ -507,9 +782,17 @@ impl FileSession {
 
         self.cur_bytes = 0;
         res?;
+        self.notify_modified();
         stream.reply_error(Code::Success)
     }
 
+    fn notify_modified(&mut self) {
+        if self.modified {
+            crate::watches_mut().notify(WatchEvent::MODIFY, &self.filename);
+            self.modified = false;
+        }
+    }
+
     fn commit_append(&mut self, inode: &INodeRef, submit: usize) -> Result<(), Error> {
         log!(
             LogFlags::FSSess,
@@ -602,7 +885,11 @@ impl Drop for FileSession {
         // remove session from open_files and from its meta session
         crate::open_files_mut().remove_session(self.ino).unwrap();
 
+        // the client might have written to the file without committing it
+        self.notify_modified();
+
         // revoke caps if needed
+        self.cancel_prefetch();
         self.revoke_cap();
     }
 }
@@ -667,6 +954,10 @@ impl M3FSSession for FileSession {
         Err(Error::new(Code::NotSup))
     }
 
+    fn utime(&mut self, _stream: &mut GateIStream<'_>) -> Result<(), Error> {
+        Err(Error::new(Code::NotSup))
+    }
+
     fn sync(&mut self, stream: &mut GateIStream<'_>) -> Result<(), Error> {
         let _: usize = stream.pop()?;
         self.file_sync(stream)
@@ -14,18 +14,21 @@
  * General Public License version 2 for more details.
  */
 
+use crate::buf;
 use crate::data::ExtPos;
+use crate::ops::perms::{self, Identity};
 use crate::ops::{dirs, inodes};
 use crate::sess::{FileSession, M3FSSession};
 
 use m3::{
     cap::Selector,
     cell::{RefCell, StaticCell},
+    client::WatchEvent,
     col::{Treap, Vec},
     com::GateIStream,
     errors::{Code, Error},
     io::LogFlags,
-    kif::{CapRngDesc, CapType},
+    kif::{CapRngDesc, CapType, INVALID_SEL},
     rc::Rc,
     server::CapExchange,
     server::{ServerSession, SessId},
@@ -86,16 +89,18 @@ pub struct MetaSession {
     priv_files: Treap<SessId, FileSession>,
     file_limit: Rc<RefCell<FileLimit>>,
     priv_eps: Vec<Selector>,
+    id: Identity,
 }
 
 impl MetaSession {
-    pub fn new(serv: ServerSession, file_limit: Rc<RefCell<FileLimit>>) -> Self {
+    pub fn new(serv: ServerSession, file_limit: Rc<RefCell<FileLimit>>, id: Identity) -> Self {
         MetaSession {
             serv,
             files: Vec::new(),
             priv_files: Treap::new(),
             file_limit,
             priv_eps: Vec::new(),
+            id,
         }
     }
 
@@ -103,6 +108,7 @@ impl MetaSession {
         self.priv_eps
             .get(idx)
             .copied()
+            .filter(|sel| *sel != INVALID_SEL)
             .ok_or_else(|| Error::new(Code::InvArgs))
     }
 
@@ -111,6 +117,13 @@ impl MetaSession {
         self.priv_eps.len() - 1
     }
 
+    pub fn remove_ep(&mut self, ep: Selector) {
+        // keep the indices of the other EPs stable
+        if let Some(sel) = self.priv_eps.iter_mut().find(|sel| **sel == ep) {
+            *sel = INVALID_SEL;
+        }
+    }
+
     pub fn file_sessions(&self) -> &[SessId] {
         &self.files
     }
@@ -135,9 +148,9 @@ impl MetaSession {
         );
 
         // the session shares the file count with the parent to prevent that clients can sidestep
-        // the limit by cloning sessions.
+        // the limit by cloning sessions. Similarly, it inherits the identity of the parent.
         let sel = serv.sel();
-        let nsess = MetaSession::new(serv, self.file_limit.clone());
+        let nsess = MetaSession::new(serv, self.file_limit.clone(), self.id);
 
         data.out_caps(CapRngDesc::new(CapType::Object, sel, 2));
 
@@ -195,26 +208,24 @@ impl MetaSession {
     ) -> Result<FileSession, Error> {
         self.file_limit.borrow().check(self.serv.id())?;
 
-        let ino = dirs::search(path, flags.contains(OpenFlags::CREATE))?;
+        let create = flags.contains(OpenFlags::CREATE).then_some(&self.id);
+        let ino = buf::transaction(|| dirs::search(path, create))?;
         let inode = inodes::get(ino)?;
-        let inode_mode = inode.mode;
 
-        if (flags.contains(OpenFlags::W) && !inode_mode.contains(FileMode::IWUSR))
-            || (flags.contains(OpenFlags::R) && !inode_mode.contains(FileMode::IRUSR))
-        {
-            log!(
-                LogFlags::FSSess,
-                "insufficient permissions: flags={:o}, mode={:o}",
-                flags,
-                inode.mode,
-            );
-            return Err(Error::new(Code::NoPerm));
+        let mut perm = FileMode::empty();
+        if flags.contains(OpenFlags::R) {
+            perm |= FileMode::IROTH;
+        }
+        if flags.contains(OpenFlags::W) {
+            perm |= FileMode::IWOTH;
         }
+        perms::check(&inode, &self.id, perm)?;
 
         // only determine the current size, if we're writing and the file isn't empty
         if flags.contains(OpenFlags::TRUNC) {
-            inodes::truncate(&inode, &ExtPos::new(0, 0))?;
+            buf::transaction(|| inodes::truncate(&inode, &ExtPos::new(0, 0)))?;
             // TODO revoke access, if necessary
+            crate::watches_mut().notify(WatchEvent::MODIFY, path);
         }
 
         // for directories: ensure that we don't have a changed version in the cache
@@ -289,7 +300,7 @@ impl M3FSSession for MetaSession {
             path
         );
 
-        let ino = dirs::search(path, false)?;
+        let ino = dirs::search(path, None)?;
         let inode = inodes::get(ino)?;
 
         let info = inode.to_file_info();
@@ -311,7 +322,8 @@ impl M3FSSession for MetaSession {
             mode
         );
 
-        dirs::create(path, mode)?;
+        buf::transaction(|| dirs::create(path, mode, &self.id))?;
+        crate::watches_mut().notify(WatchEvent::CREATE, path);
 
         stream.reply_error(Code::Success)
     }
@@ -326,7 +338,8 @@ impl M3FSSession for MetaSession {
             path
         );
 
-        dirs::remove(path)?;
+        buf::transaction(|| dirs::remove(path, &self.id))?;
+        crate::watches_mut().notify(WatchEvent::DELETE, path);
 
         stream.reply_error(Code::Success)
     }
@@ -343,7 +356,8 @@ impl M3FSSession for MetaSession {
             new_path
         );
 
-        dirs::link(old_path, new_path)?;
+        buf::transaction(|| dirs::link(old_path, new_path, &self.id))?;
+        crate::watches_mut().notify(WatchEvent::CREATE, new_path);
 
         stream.reply_error(Code::Success)
     }
@@ -358,7 +372,8 @@ impl M3FSSession for MetaSession {
             path
         );
 
-        dirs::unlink(path, true)?;
+        buf::transaction(|| dirs::unlink(path, true, &self.id))?;
+        crate::watches_mut().notify(WatchEvent::DELETE, path);
 
         stream.reply_error(Code::Success)
     }
@@ -375,7 +390,37 @@ impl M3FSSession for MetaSession {
             new_path
         );
 
-        dirs::rename(old_path, new_path)?;
+        buf::transaction(|| dirs::rename(old_path, new_path, &self.id))?;
+        crate::watches_mut().notify(WatchEvent::RENAME, old_path);
+        crate::watches_mut().notify(WatchEvent::RENAME, new_path);
+
+        stream.reply_error(Code::Success)
+    }
+
+    fn utime(&mut self, stream: &mut GateIStream<'_>) -> Result<(), Error> {
+        let path: &str = stream.pop()?;
+        let lastaccess: u32 = stream.pop()?;
+        let lastmod: u32 = stream.pop()?;
+
+        log!(
+            LogFlags::FSSess,
+            "[{}] meta::utime(path={}, lastaccess={}, lastmod={})",
+            self.serv.id(),
+            path,
+            lastaccess,
+            lastmod
+        );
+
+        buf::transaction(|| {
+            let ino = dirs::search(path, None)?;
+            let inode = inodes::get(ino)?;
+            // only the owner is allowed to set the times
+            if !self.id.is_root() && self.id.uid != inode.uid {
+                return Err(Error::new(Code::NoPerm));
+            }
+            inodes::set_times(&inode, lastaccess, lastmod);
+            Ok(())
+        })?;
 
         stream.reply_error(Code::Success)
     }
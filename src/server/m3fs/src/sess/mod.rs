@@ -17,13 +17,19 @@
 mod file_session;
 mod meta_session;
 mod open_files;
+mod watches;
 
 pub use file_session::FileSession;
 use meta_session::FileLimit;
 pub use meta_session::MetaSession;
 pub use open_files::OpenFiles;
+pub use watches::Watches;
 
-use m3::cap::SelSpace;
+use crate::ops::dirs;
+use crate::ops::perms::Identity;
+
+use m3::cap::{SelSpace, Selector};
+use m3::client::WatchEvent;
 use m3::col::Vec;
 use m3::com::GateIStream;
 use m3::errors::{Code, Error};
@@ -41,23 +47,32 @@ impl RequestSession for FSSession {
     where
         Self: Sized,
     {
-        // get max number of files
+        // get max number of files and the identity of the client
         let mut max_files: usize = 16;
-        if arg.len() > 6 && &arg[..6] == "files=" {
-            max_files = arg[6..].parse().map_err(|_| Error::new(Code::InvArgs))?;
+        let mut id = Identity::ROOT;
+        for a in arg.split_whitespace() {
+            if let Some(files) = a.strip_prefix("files=") {
+                max_files = files.parse().map_err(|_| Error::new(Code::InvArgs))?;
+            }
+            else if !id.parse_arg(a)? {
+                return Err(Error::new(Code::InvArgs));
+            }
         }
 
         log!(
             LogFlags::FSSess,
-            "[{}] creating session(crt={}, max_files={})",
+            "[{}] creating session(crt={}, max_files={}, uid={}, gid={})",
             serv.id(),
             serv.creator(),
-            max_files
+            max_files,
+            id.uid,
+            id.gid
         );
 
         Ok(FSSession::Meta(MetaSession::new(
             serv,
             FileLimit::new(max_files),
+            id,
         )))
     }
 
@@ -73,6 +88,8 @@ impl RequestSession for FSSession {
             FSSession::Meta(ref meta) => {
                 // remove contained file sessions
                 sub_ids.extend_from_slice(meta.file_sessions());
+                // remove the watches of this session
+                crate::watches_mut().remove_sess(sid);
             },
 
             FSSession::File(ref file) => {
@@ -98,6 +115,15 @@ impl RequestSession for FSSession {
             },
         }
     }
+
+    fn revoked(&mut self, sel: Selector) {
+        log!(LogFlags::FSSess, "fs::revoked(sel={})", sel);
+
+        match self {
+            FSSession::Meta(ref mut meta) => meta.remove_ep(sel),
+            FSSession::File(ref mut file) => file.remove_ep(sel),
+        }
+    }
 }
 
 impl FSSession {
@@ -140,6 +166,8 @@ impl FSSession {
             FSSession::Meta(m) => {
                 let new_sel = SelSpace::get().alloc_sel();
                 let id = m.add_ep(new_sel);
+                // drop the EP registration as soon as the client revokes the EP
+                xchg.notify_revoke();
                 log!(
                     LogFlags::FSSess,
                     "[{}] fs::add_ep(sel={}) -> {}",
@@ -183,6 +211,35 @@ impl FSSession {
                 let new_sel = SelSpace::get().alloc_sel();
                 log!(LogFlags::FSSess, "[{}] fs::set_dest(sel={})", sid, new_sel);
                 fs.set_ep(new_sel);
+                xchg.notify_revoke();
+                xchg.out_caps(m3::kif::CapRngDesc::new(
+                    m3::kif::CapType::Object,
+                    new_sel,
+                    1,
+                ));
+                Ok(())
+            },
+            _ => Err(Error::new(Code::InvArgs)),
+        }
+    }
+
+    pub fn set_next_dest(
+        cli: &mut ClientManager<Self>,
+        _crt: usize,
+        sid: SessId,
+        xchg: &mut CapExchange<'_>,
+    ) -> Result<(), Error> {
+        match Self::get_sess(cli, sid)? {
+            FSSession::File(fs) => {
+                let new_sel = SelSpace::get().alloc_sel();
+                log!(
+                    LogFlags::FSSess,
+                    "[{}] fs::set_next_dest(sel={})",
+                    sid,
+                    new_sel
+                );
+                fs.set_next_ep(new_sel)?;
+                xchg.notify_revoke();
                 xchg.out_caps(m3::kif::CapRngDesc::new(
                     m3::kif::CapType::Object,
                     new_sel,
@@ -195,12 +252,38 @@ impl FSSession {
     }
 
     pub fn enable_notify(
-        _cli: &mut ClientManager<Self>,
+        cli: &mut ClientManager<Self>,
         _crt: usize,
-        _sid: SessId,
-        _xchg: &mut CapExchange<'_>,
+        sid: SessId,
+        xchg: &mut CapExchange<'_>,
     ) -> Result<(), Error> {
-        Err(Error::new(Code::NotSup))
+        match Self::get_sess(cli, sid)? {
+            // watches are registered at the meta session; file sessions are always ready
+            FSSession::Meta(_) => {
+                let path: &str = xchg.in_args().pop()?;
+                let events: WatchEvent = xchg.in_args().pop()?;
+
+                // we can only watch existing files and directories
+                dirs::search(path, None)?;
+
+                let sel = SelSpace::get().alloc_sel();
+                let id = crate::watches_mut().add(sid, path, events, sel);
+                log!(
+                    LogFlags::FSSess,
+                    "[{}] fs::enable_notify(path={}, events={:?}, sel={}) -> {}",
+                    sid,
+                    path,
+                    events,
+                    sel,
+                    id
+                );
+
+                xchg.out_caps(m3::kif::CapRngDesc::new(m3::kif::CapType::Object, sel, 1));
+                xchg.out_args().push(id);
+                Ok(())
+            },
+            _ => Err(Error::new(Code::NotSup)),
+        }
     }
 }
 
@@ -296,6 +379,13 @@ impl M3FSSession for FSSession {
         }
     }
 
+    fn utime(&mut self, stream: &mut GateIStream<'_>) -> Result<(), Error> {
+        match self {
+            FSSession::Meta(m) => m.utime(stream),
+            FSSession::File(f) => f.utime(stream),
+        }
+    }
+
     fn sync(&mut self, stream: &mut GateIStream<'_>) -> Result<(), Error> {
         match self {
             FSSession::Meta(m) => m.sync(stream),
@@ -333,6 +423,7 @@ pub trait M3FSSession {
     fn link(&mut self, stream: &mut GateIStream<'_>) -> Result<(), Error>;
     fn unlink(&mut self, stream: &mut GateIStream<'_>) -> Result<(), Error>;
     fn rename(&mut self, stream: &mut GateIStream<'_>) -> Result<(), Error>;
+    fn utime(&mut self, stream: &mut GateIStream<'_>) -> Result<(), Error>;
     fn sync(&mut self, stream: &mut GateIStream<'_>) -> Result<(), Error>;
     fn open_priv(&mut self, stream: &mut GateIStream<'_>) -> Result<(), Error>;
     fn close_priv(&mut self, stream: &mut GateIStream<'_>) -> Result<(), Error>;
@@ -0,0 +1,141 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use m3::cap::Selector;
+use m3::client::WatchEvent;
+use m3::col::{String, ToString, Vec};
+use m3::com::{LazyGate, RecvGate, SendCap};
+use m3::errors::Code;
+use m3::io::LogFlags;
+use m3::server::SessId;
+
+struct Watch {
+    id: usize,
+    sess: SessId,
+    path: String,
+    events: WatchEvent,
+    overflow: bool,
+    sgate: LazyGate<SendCap>,
+}
+
+impl Watch {
+    /// Sends the event to the client. Returns false if the watch is gone.
+    fn send(&mut self, event: WatchEvent, name: &str) -> bool {
+        let sg = match self.sgate.get() {
+            Ok(sg) => sg,
+            Err(_) => return false,
+        };
+
+        let events = if self.overflow {
+            event | WatchEvent::OVERFLOW
+        }
+        else {
+            event
+        };
+
+        log!(
+            LogFlags::FSSess,
+            "[{}] watch[id={}, path={}]: sending {:?} for '{}'",
+            self.sess,
+            self.id,
+            self.path,
+            events,
+            name
+        );
+
+        // no reply is expected, because the client does not need to give us credits back
+        match send_vmsg!(sg, RecvGate::def(), events.bits(), name) {
+            Ok(_) => {
+                self.overflow = false;
+                true
+            },
+            // the client did not fetch the previous events yet; remember that we lost one
+            Err(e) if e.code() == Code::RecvNoSpace => {
+                self.overflow = true;
+                true
+            },
+            Err(_) => false,
+        }
+    }
+}
+
+/// Strips leading and trailing slashes to make paths comparable
+fn normalize(path: &str) -> &str {
+    path.trim_matches('/')
+}
+
+/// Splits the given normalized path into directory and name
+fn split(path: &str) -> (&str, &str) {
+    match path.rfind('/') {
+        Some(pos) => (normalize(&path[..pos]), &path[pos + 1..]),
+        None => ("", path),
+    }
+}
+
+pub struct Watches {
+    watches: Vec<Watch>,
+    next_id: usize,
+}
+
+impl Watches {
+    pub const fn new() -> Self {
+        Watches {
+            watches: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    pub fn add(&mut self, sess: SessId, path: &str, events: WatchEvent, sgate: Selector) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.watches.push(Watch {
+            id,
+            sess,
+            path: normalize(path).to_string(),
+            events,
+            overflow: false,
+            sgate: LazyGate::new(sgate),
+        });
+        id
+    }
+
+    pub fn remove_sess(&mut self, sess: SessId) {
+        self.watches.retain(|w| w.sess != sess);
+    }
+
+    /// Notifies all watches that are interested in `event` on `path`
+    pub fn notify(&mut self, event: WatchEvent, path: &str) {
+        if self.watches.is_empty() {
+            return;
+        }
+
+        let path = normalize(path);
+        let (dir, name) = split(path);
+        self.watches.retain_mut(|w| {
+            if !w.events.contains(event) {
+                true
+            }
+            else if w.path == path {
+                w.send(event, "")
+            }
+            else if w.path == dir {
+                w.send(event, name)
+            }
+            else {
+                true
+            }
+        });
+    }
+}
@@ -1,6 +1,12 @@
+import os
+
+
 def build(gen, env):
     if env['TGT'] in ['hw', 'hw22', 'hw23']:
         libs = ['axieth', 'base', 'supc++']
     else:
         libs = []
-    env.m3_rust_exe(gen, out='net', libs=libs, dir='sbin', features=['net/' + env['TGT']])
+    features = ['net/' + env['TGT']]
+    if os.environ.get('M3_SRVTESTS', '0') == '1':
+        features += ['net/tests']
+    env.m3_rust_exe(gen, out='net', libs=libs, dir='sbin', features=features)
@@ -62,6 +62,7 @@ pub enum REG {
     TDCTL  = 0x3828, /* transmit descriptor control */
     TADV   = 0x382c, /* transmit absolute interrupt delay timer */
 
+    MTA    = 0x5200, /* filtering: multicast table array */
     RAL    = 0x5400, /* filtering: receive address low */
     RAH    = 0x5404, /* filtering: receive address high */
 
@@ -342,6 +343,8 @@ pub const TX_BUF_COUNT: usize = 256;
 pub const RX_BUF_SIZE: usize = 2048;
 pub const TX_BUF_SIZE: usize = 2048;
 
+pub const MTA_ENTRIES: usize = 128;
+
 #[repr(C, align(16))]
 pub struct Buffers {
     pub rx_descs: [RxDesc; RX_BUF_COUNT],
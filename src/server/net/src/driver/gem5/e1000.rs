@@ -50,6 +50,9 @@ pub struct E1000 {
 
     txd_context_proto: TxoProto,
 
+    // the multicast addresses we want to receive packets for
+    multicast: Vec<u64>,
+
     needs_poll: bool,
 }
 
@@ -75,6 +78,8 @@ impl E1000 {
 
             txd_context_proto: TxoProto::UNSUPPORTED,
 
+            multicast: Vec::new(),
+
             needs_poll: false,
         };
 
@@ -209,6 +214,9 @@ impl E1000 {
             (((macval >> 32) as u32) & 0xFFFF) | RAH::VALID.bits(),
         );
 
+        // only accept multicast packets for the addresses in the multicast table
+        self.update_multicast_table();
+
         // enable transmitter
         let mut tctl: u32 = self.read_reg(REG::TCTL);
         tctl &= !((TCTL::COLT_MASK | TCTL::COLD_MASK).bits());
@@ -218,11 +226,47 @@ impl E1000 {
         // enable receiver
         let mut rctl: u32 = self.read_reg(REG::RCTL);
         rctl &= !((RCTL::BSIZE_MASK | RCTL::BSEX_MASK).bits());
-        rctl |= (RCTL::ENABLE | RCTL::UPE | RCTL::MPE | RCTL::BAM | RCTL::BSIZE_2K | RCTL::SECRC)
-            .bits();
+        rctl &= !RCTL::MPE.bits();
+        rctl |= (RCTL::ENABLE | RCTL::UPE | RCTL::BAM | RCTL::BSIZE_2K | RCTL::SECRC).bits();
         self.write_reg(REG::RCTL, rctl);
     }
 
+    /// Lets the NIC receive packets sent to the multicast address `mac`
+    pub fn add_multicast(&mut self, mac: MAC) {
+        log!(LogFlags::NetNIC, "e1000: adding multicast address {}", mac);
+        self.multicast.push(mac.raw());
+        self.update_multicast_table();
+    }
+
+    /// Stops receiving packets sent to the multicast address `mac`
+    pub fn remove_multicast(&mut self, mac: MAC) {
+        log!(
+            LogFlags::NetNIC,
+            "e1000: removing multicast address {}",
+            mac
+        );
+        if let Some(idx) = self.multicast.iter().position(|m| *m == mac.raw()) {
+            self.multicast.swap_remove(idx);
+        }
+        self.update_multicast_table();
+    }
+
+    fn update_multicast_table(&self) {
+        let mut table = [0u32; MTA_ENTRIES];
+        for mac in &self.multicast {
+            // the hash consists of the bits 47:36 of the address (multicast offset 0)
+            let hash = ((mac >> 36) & 0xFFF) as usize;
+            table[hash >> 5] |= 1 << (hash & 0x1F);
+        }
+
+        for (i, bits) in table.iter().enumerate() {
+            // there is no reasonable way to continue if that fails -> panic
+            self.nic
+                .write_reg(REG::MTA as GlobOff + (i * 4) as GlobOff, *bits)
+                .expect("failed to write NIC register");
+        }
+    }
+
     pub const fn mtu() -> usize {
         // gem5 limits us to TX_BUF_SIZE - 1 (2047)
         TX_BUF_SIZE - 1
@@ -17,6 +17,7 @@
 
 use m3::cell::{RefCell, StaticRefCell};
 use m3::errors::Error;
+use m3::net::MAC;
 use m3::rc::Rc;
 use m3::vec::Vec;
 
@@ -41,6 +42,14 @@ impl E1000Device {
     pub fn needs_poll(&self) -> bool {
         self.dev.borrow().needs_poll()
     }
+
+    pub fn add_multicast(&self, mac: MAC) {
+        self.dev.borrow_mut().add_multicast(mac);
+    }
+
+    pub fn remove_multicast(&self, mac: MAC) {
+        self.dev.borrow_mut().remove_multicast(mac);
+    }
 }
 
 impl<'a> smoltcp::phy::Device<'a> for E1000Device {
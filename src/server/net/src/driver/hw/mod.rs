@@ -22,6 +22,7 @@ use m3::com::MemGate;
 use m3::errors::{Code, Error};
 use m3::kif::{PageFlags, Perm};
 use m3::mem::{GlobOff, PhysAddrRaw, VirtAddr};
+use m3::net::MAC;
 use m3::tiles::Activity;
 use m3::vec;
 
@@ -72,6 +73,13 @@ impl AXIEthDevice {
     pub fn needs_poll(&self) -> bool {
         false
     }
+
+    pub fn add_multicast(&self, _mac: MAC) {
+        // TODO the multicast filter of the AXI ethernet core is not configured yet
+    }
+
+    pub fn remove_multicast(&self, _mac: MAC) {
+    }
 }
 
 impl Drop for AXIEthDevice {
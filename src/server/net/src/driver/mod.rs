@@ -25,9 +25,16 @@ mod inner;
 
 pub use inner::*;
 
+mod virtnet;
+
+pub use virtnet::VirtioNetDevice;
+
+use m3::net::MAC;
+
 use smoltcp::iface::{Context, Interface, SocketHandle};
-use smoltcp::socket::AnySocket;
+use smoltcp::socket::{AnySocket, Socket};
 use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::Ipv4Address;
 
 pub enum DriverInterface<'a> {
     Lo(Interface<'a, smoltcp::phy::Loopback>),
@@ -35,6 +42,7 @@ pub enum DriverInterface<'a> {
     Eth(Interface<'a, E1000Device>),
     #[cfg(not(feature = "gem5"))]
     Eth(Interface<'a, AXIEthDevice>),
+    Virtio(Interface<'a, VirtioNetDevice>),
 }
 
 impl<'a> DriverInterface<'a> {
@@ -42,6 +50,28 @@ impl<'a> DriverInterface<'a> {
         match self {
             Self::Lo(l) => l.add_socket(socket),
             Self::Eth(e) => e.add_socket(socket),
+            Self::Virtio(v) => v.add_socket(socket),
+        }
+    }
+
+    /// Removes the socket with given handle from this interface, so that it can be added to
+    /// another interface via [`DriverInterface::add_any_socket`]
+    pub fn remove_socket(&mut self, handle: SocketHandle) -> Socket<'a> {
+        match self {
+            Self::Lo(l) => l.remove_socket(handle),
+            Self::Eth(e) => e.remove_socket(handle),
+            Self::Virtio(v) => v.remove_socket(handle),
+        }
+    }
+
+    pub fn add_any_socket(&mut self, socket: Socket<'a>) -> SocketHandle {
+        match socket {
+            Socket::Tcp(s) => self.add_socket(s),
+            Socket::Udp(s) => self.add_socket(s),
+            Socket::Raw(s) => self.add_socket(s),
+            // we do not create other types of sockets
+            #[allow(unreachable_patterns)]
+            _ => unreachable!(),
         }
     }
 
@@ -49,6 +79,7 @@ impl<'a> DriverInterface<'a> {
         match self {
             Self::Lo(l) => l.get_socket(handle),
             Self::Eth(e) => e.get_socket(handle),
+            Self::Virtio(v) => v.get_socket(handle),
         }
     }
 
@@ -59,6 +90,47 @@ impl<'a> DriverInterface<'a> {
         match self {
             Self::Lo(l) => l.get_socket_and_context(handle),
             Self::Eth(e) => e.get_socket_and_context(handle),
+            Self::Virtio(v) => v.get_socket_and_context(handle),
+        }
+    }
+
+    pub fn join_multicast_group(
+        &mut self,
+        addr: Ipv4Address,
+        timestamp: Instant,
+    ) -> smoltcp::Result<bool> {
+        match self {
+            Self::Lo(l) => l.join_multicast_group(addr, timestamp),
+            Self::Eth(e) => e.join_multicast_group(addr, timestamp),
+            Self::Virtio(v) => v.join_multicast_group(addr, timestamp),
+        }
+    }
+
+    pub fn leave_multicast_group(
+        &mut self,
+        addr: Ipv4Address,
+        timestamp: Instant,
+    ) -> smoltcp::Result<bool> {
+        match self {
+            Self::Lo(l) => l.leave_multicast_group(addr, timestamp),
+            Self::Eth(e) => e.leave_multicast_group(addr, timestamp),
+            Self::Virtio(v) => v.leave_multicast_group(addr, timestamp),
+        }
+    }
+
+    /// Lets the NIC receive packets sent to the multicast address `mac`
+    pub fn add_multicast_mac(&mut self, mac: MAC) {
+        match self {
+            // virtio-net devices without control queue receive all multicast packets
+            Self::Lo(_) | Self::Virtio(_) => {},
+            Self::Eth(e) => e.device().add_multicast(mac),
+        }
+    }
+
+    pub fn remove_multicast_mac(&mut self, mac: MAC) {
+        match self {
+            Self::Lo(_) | Self::Virtio(_) => {},
+            Self::Eth(e) => e.device().remove_multicast(mac),
         }
     }
 
@@ -66,6 +138,7 @@ impl<'a> DriverInterface<'a> {
         match self {
             Self::Lo(l) => l.poll(timestamp),
             Self::Eth(e) => e.poll(timestamp),
+            Self::Virtio(v) => v.poll(timestamp),
         }
     }
 
@@ -73,6 +146,7 @@ impl<'a> DriverInterface<'a> {
         match self {
             Self::Lo(l) => l.poll_delay(timestamp),
             Self::Eth(e) => e.poll_delay(timestamp),
+            Self::Virtio(v) => v.poll_delay(timestamp),
         }
     }
 
@@ -80,6 +154,7 @@ impl<'a> DriverInterface<'a> {
         match self {
             Self::Lo(_) => false,
             Self::Eth(e) => e.device().needs_poll(),
+            Self::Virtio(v) => v.device().needs_poll(),
         }
     }
 }
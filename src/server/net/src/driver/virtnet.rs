@@ -0,0 +1,307 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use m3::cell::{RefCell, StaticRefCell};
+use m3::col::Vec;
+use m3::errors::{Code, Error};
+use m3::io::LogFlags;
+use m3::kif::TileISA;
+use m3::log;
+use m3::mem::GlobOff;
+use m3::net::{log_net, NetLogEvent, MAC};
+use m3::rc::Rc;
+use m3::vec;
+
+use smoltcp::time::Instant;
+
+use virtio::{Buffer, DeviceType, VirtQueue};
+
+// the device has a MAC address in its configuration
+const FEATURE_MAC: u32 = 1 << 5;
+
+const RX_QUEUE: u16 = 0;
+const TX_QUEUE: u16 = 1;
+
+const RX_BUF_COUNT: usize = 16;
+const TX_BUF_COUNT: usize = 16;
+const BUF_SIZE: usize = 2048;
+
+// every packet is preceded by the virtio-net header, which we leave empty (no offloading)
+const NET_HDR_SIZE: usize = 10;
+
+const MTU: usize = 1514;
+
+/// Driver for virtio-net devices
+pub struct VirtioNet {
+    dev: virtio::Device,
+    rxq: VirtQueue,
+    txq: VirtQueue,
+    rx_off: usize,
+    tx_off: usize,
+    // the buffer index for each descriptor chain
+    rx_bufs: Vec<usize>,
+    tx_bufs: Vec<usize>,
+    tx_free: Vec<usize>,
+    needs_poll: bool,
+}
+
+impl VirtioNet {
+    pub fn new() -> Result<Self, Error> {
+        let mut dev = virtio::Device::new("virtio-net", TileISA::NICDev, DeviceType::Net)?;
+        let features = dev.negotiate(FEATURE_MAC)?;
+
+        // layout: receive queue, transmit queue, receive buffers, transmit buffers
+        let rxq_size = dev.queue_size(RX_QUEUE)?;
+        let txq_size = dev.queue_size(TX_QUEUE)?;
+        let txq_off = VirtQueue::mem_size(rxq_size);
+        let rx_off = txq_off + VirtQueue::mem_size(txq_size);
+        let tx_off = rx_off + RX_BUF_COUNT * BUF_SIZE;
+        dev.alloc_dma(tx_off + TX_BUF_COUNT * BUF_SIZE)?;
+
+        let rxq = dev.setup_queue(RX_QUEUE, 0)?;
+        let txq = dev.setup_queue(TX_QUEUE, txq_off as GlobOff)?;
+
+        let mut nic = Self {
+            dev,
+            rxq,
+            txq,
+            rx_off,
+            tx_off,
+            rx_bufs: vec![0; rxq_size as usize],
+            tx_bufs: vec![0; txq_size as usize],
+            tx_free: (0..TX_BUF_COUNT).collect(),
+            needs_poll: false,
+        };
+
+        // hand all receive buffers to the device
+        for i in 0..RX_BUF_COUNT.min(rxq_size as usize) {
+            nic.add_rx_buf(i)?;
+        }
+        nic.dev.finish_init()?;
+        nic.dev.notify(&nic.rxq)?;
+
+        if (features & FEATURE_MAC) != 0 {
+            let mut bytes = [0u8; 6];
+            for (i, b) in bytes.iter_mut().enumerate() {
+                *b = nic.dev.read_config(i as GlobOff)?;
+            }
+            let mac = MAC::new(bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5]);
+            log!(LogFlags::NetNIC, "virtio-net: got MAC: {}", mac);
+        }
+
+        Ok(nic)
+    }
+
+    pub const fn mtu() -> usize {
+        MTU
+    }
+
+    pub fn needs_poll(&self) -> bool {
+        self.needs_poll
+    }
+
+    pub fn send(&mut self, packet: &[u8]) -> bool {
+        assert!(packet.len() <= Self::mtu());
+
+        if let Err(e) = self.reclaim_tx() {
+            log!(
+                LogFlags::NetNIC,
+                "virtio-net: unable to reclaim buffers: {}",
+                e
+            );
+        }
+
+        let idx = match self.tx_free.pop() {
+            Some(idx) => idx,
+            None => {
+                log!(LogFlags::NetNIC, "virtio-net: no free buffers for sending");
+                return false;
+            },
+        };
+
+        match self.transmit(idx, packet) {
+            Ok(_) => {
+                log_net(NetLogEvent::SentPacket, 0, packet.len());
+                true
+            },
+            Err(e) => {
+                log!(LogFlags::NetNIC, "virtio-net: sending failed: {}", e);
+                self.tx_free.push(idx);
+                false
+            },
+        }
+    }
+
+    /// Receives a single packet with at most [`VirtioNet::mtu`] bytes
+    pub fn receive(&mut self) -> Result<Vec<u8>, Error> {
+        // acknowledge the interrupt, but always check the queue in case we received a single IRQ
+        // for multiple packets
+        self.dev.check_irq()?;
+
+        let (token, len) = match self.rxq.pop_used(self.dev.dma())? {
+            Some(used) => used,
+            None => {
+                self.needs_poll = false;
+                return Err(Error::new(Code::NotFound));
+            },
+        };
+
+        let idx = self.rx_bufs[token as usize];
+        let len = (len as usize).saturating_sub(NET_HDR_SIZE).min(Self::mtu());
+        log_net(NetLogEvent::RecvPacket, 0, len);
+        log!(LogFlags::NetNIC, "virtio-net: RX {}: {} bytes", idx, len);
+
+        let mut buf = vec![0u8; len];
+        let res = self
+            .dev
+            .dma()
+            .read(&mut buf, (self.rx_buf(idx) + NET_HDR_SIZE) as GlobOff);
+
+        // give the buffer back to the device in any case
+        self.add_rx_buf(idx)?;
+        self.dev.notify(&self.rxq)?;
+
+        // remind ourself to call receive again if there is another packet
+        self.needs_poll = self.rxq.has_used(self.dev.dma())?;
+
+        res.map(|_| buf)
+    }
+
+    fn add_rx_buf(&mut self, idx: usize) -> Result<(), Error> {
+        let buf = Buffer::new(self.rx_buf(idx) as GlobOff, BUF_SIZE, true);
+        let token = self.rxq.add(self.dev.dma(), &[buf])?;
+        self.rx_bufs[token as usize] = idx;
+        Ok(())
+    }
+
+    fn transmit(&mut self, idx: usize, packet: &[u8]) -> Result<(), Error> {
+        let addr = self.tx_buf(idx);
+        self.dev
+            .dma()
+            .write(&[0u8; NET_HDR_SIZE], addr as GlobOff)?;
+        self.dev
+            .dma()
+            .write(packet, (addr + NET_HDR_SIZE) as GlobOff)?;
+
+        let buf = Buffer::new(addr as GlobOff, NET_HDR_SIZE + packet.len(), false);
+        let token = self.txq.add(self.dev.dma(), &[buf])?;
+        self.tx_bufs[token as usize] = idx;
+        log!(
+            LogFlags::NetNIC,
+            "virtio-net: TX {}: {} bytes",
+            idx,
+            packet.len()
+        );
+        self.dev.notify(&self.txq)
+    }
+
+    fn reclaim_tx(&mut self) -> Result<(), Error> {
+        while let Some((token, _)) = self.txq.pop_used(self.dev.dma())? {
+            self.tx_free.push(self.tx_bufs[token as usize]);
+        }
+        Ok(())
+    }
+
+    fn rx_buf(&self, idx: usize) -> usize {
+        self.rx_off + idx * BUF_SIZE
+    }
+
+    fn tx_buf(&self, idx: usize) -> usize {
+        self.tx_off + idx * BUF_SIZE
+    }
+}
+
+/// Wrapper around the virtio-net driver, implementing smols Device trait
+pub struct VirtioNetDevice {
+    dev: Rc<RefCell<VirtioNet>>,
+}
+
+impl VirtioNetDevice {
+    pub fn new() -> Result<Self, Error> {
+        Ok(VirtioNetDevice {
+            dev: Rc::new(RefCell::new(VirtioNet::new()?)),
+        })
+    }
+
+    pub fn needs_poll(&self) -> bool {
+        self.dev.borrow().needs_poll()
+    }
+}
+
+impl<'a> smoltcp::phy::Device<'a> for VirtioNetDevice {
+    type RxToken = RxToken;
+    type TxToken = TxToken;
+
+    fn capabilities(&self) -> smoltcp::phy::DeviceCapabilities {
+        let mut caps = smoltcp::phy::DeviceCapabilities::default();
+        caps.max_transmission_unit = VirtioNet::mtu();
+        caps
+    }
+
+    fn receive(&'a mut self) -> Option<(Self::RxToken, Self::TxToken)> {
+        match self.dev.borrow_mut().receive() {
+            Ok(buffer) => {
+                let rx = RxToken { buffer };
+                let tx = TxToken {
+                    device: self.dev.clone(),
+                };
+                Some((rx, tx))
+            },
+            Err(_) => None,
+        }
+    }
+
+    fn transmit(&'a mut self) -> Option<Self::TxToken> {
+        Some(TxToken {
+            device: self.dev.clone(),
+        })
+    }
+}
+
+pub struct RxToken {
+    buffer: Vec<u8>,
+}
+
+impl smoltcp::phy::RxToken for RxToken {
+    fn consume<R, F>(mut self, _timestamp: Instant, f: F) -> smoltcp::Result<R>
+    where
+        F: FnOnce(&mut [u8]) -> smoltcp::Result<R>,
+    {
+        f(&mut self.buffer[..])
+    }
+}
+
+pub struct TxToken {
+    device: Rc<RefCell<VirtioNet>>,
+}
+
+// use a static and initialized buffer for all packets we send
+static SEND_BUF: StaticRefCell<[u8; VirtioNet::mtu()]> =
+    StaticRefCell::new([0u8; VirtioNet::mtu()]);
+
+impl smoltcp::phy::TxToken for TxToken {
+    fn consume<R, F>(self, _timestamp: Instant, len: usize, f: F) -> smoltcp::Result<R>
+    where
+        F: FnOnce(&mut [u8]) -> smoltcp::Result<R>,
+    {
+        // fill buffer with "to be send" data
+        assert!(len <= SEND_BUF.borrow().len());
+        let res = f(&mut SEND_BUF.borrow_mut()[0..len])?;
+        match self.device.borrow_mut().send(&SEND_BUF.borrow()[0..len]) {
+            true => Ok(res),
+            false => Err(smoltcp::Error::Exhausted),
+        }
+    }
+}
@@ -0,0 +1,102 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use base::io::LogFlags;
+use m3::errors::{Code, Error};
+use m3::log;
+use m3::net::MAC;
+
+use smoltcp::wire::{Ipv4Address, Ipv6Address};
+
+use crate::driver::DriverInterface;
+use crate::netif::NetIf;
+
+/// Returns the MAC address that packets to the IPv4 multicast group `addr` are sent to
+fn ipv4_mac(addr: Ipv4Address) -> MAC {
+    let b = addr.as_bytes();
+    MAC::new(0x01, 0x00, 0x5e, b[1] & 0x7F, b[2], b[3])
+}
+
+/// Returns the MAC address that packets to the IPv6 multicast address `addr` are sent to
+fn ipv6_mac(addr: Ipv6Address) -> MAC {
+    let b = addr.as_bytes();
+    MAC::new(0x33, 0x33, b[12], b[13], b[14], b[15])
+}
+
+/// Lets the NIC receive the multicast packets that we need independent of joined groups: IGMP
+/// queries and the all-nodes and solicited-node addresses for the IPv6 neighbor discovery.
+pub fn init(iface: &mut DriverInterface<'_>, ip6_addrs: &[Ipv6Address]) {
+    iface.add_multicast_mac(ipv4_mac(Ipv4Address::MULTICAST_ALL_SYSTEMS));
+    iface.add_multicast_mac(ipv6_mac(Ipv6Address::LINK_LOCAL_ALL_NODES));
+    for addr in ip6_addrs {
+        let b = addr.as_bytes();
+        iface.add_multicast_mac(MAC::new(0x33, 0x33, 0xff, b[13], b[14], b[15]));
+    }
+}
+
+/// Joins the multicast group `addr` on behalf of a socket.
+///
+/// The group is only joined at the interface (and announced via IGMP) for the first socket.
+pub fn join(netif: &mut NetIf<'_>, addr: Ipv4Address) -> Result<(), Error> {
+    if !addr.is_multicast() {
+        return Err(Error::new(Code::InvArgs));
+    }
+
+    let iface = &mut netif.dev;
+    let count = netif.groups.get(&addr).copied().unwrap_or(0);
+    if count == 0 {
+        if let Err(e) = iface.join_multicast_group(addr, crate::timestamp()) {
+            log!(
+                LogFlags::Error,
+                "joining multicast group {} failed: {}",
+                addr,
+                e
+            );
+            return Err(Error::new(Code::NoSpace));
+        }
+        iface.add_multicast_mac(ipv4_mac(addr));
+        log!(LogFlags::NetSess, "multicast: joined group {}", addr);
+    }
+
+    netif.groups.insert(addr, count + 1);
+    Ok(())
+}
+
+/// Leaves the multicast group `addr` on behalf of a socket.
+///
+/// The group is only left at the interface as soon as no socket is in this group anymore.
+pub fn leave(netif: &mut NetIf<'_>, addr: Ipv4Address) -> Result<(), Error> {
+    let iface = &mut netif.dev;
+    let count = netif
+        .groups
+        .get_mut(&addr)
+        .ok_or_else(|| Error::new(Code::NotFound))?;
+
+    *count -= 1;
+    if *count == 0 {
+        netif.groups.remove(&addr);
+        iface.remove_multicast_mac(ipv4_mac(addr));
+        if let Err(e) = iface.leave_multicast_group(addr, crate::timestamp()) {
+            log!(
+                LogFlags::Error,
+                "leaving multicast group {} failed: {}",
+                addr,
+                e
+            );
+        }
+        log!(LogFlags::NetSess, "multicast: left group {}", addr);
+    }
+    Ok(())
+}
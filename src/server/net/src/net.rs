@@ -20,29 +20,30 @@
 use core::str::FromStr;
 
 use m3::cap::Selector;
-use m3::cell::{LazyStaticCell, StaticRefCell};
-use m3::col::{BTreeMap, String, ToString, Vec};
+use m3::cell::LazyStaticCell;
+use m3::col::{String, ToString, Vec};
 use m3::com::{opcodes, GateIStream};
 use m3::errors::{Code, Error};
 use m3::io::LogFlags;
 use m3::net::{log_net, NetLogEvent};
 use m3::server::{
-    CapExchange, ExcType, Handler, RequestHandler, Server, SessId, SessionContainer,
+    CapExchange, ExcType, Handler, RequestHandler, Server, SessId, SessionContainer, WaitPolicy,
     DEF_MAX_CLIENTS,
 };
 use m3::tiles::OwnActivity;
 use m3::time::{TimeDuration, TimeInstant};
-use m3::{env, reply_vmsg};
+use m3::{env, reply_vmsg, timer, vec, watchdog};
 use m3::{log, println};
 
-use smoltcp::iface::{InterfaceBuilder, NeighborCache, Routes, SocketHandle};
-use smoltcp::wire::{EthernetAddress, IpAddress, Ipv4Cidr};
+use smoltcp::wire::{IpAddress, Ipv4Cidr, Ipv6Cidr};
 
-use crate::driver::DriverInterface;
+use crate::netif::{IfConfig, Interfaces, Route};
 use crate::sess::SocketSession;
 use crate::smoltcpif::socket::to_m3_addr;
 
 mod driver;
+mod multicast;
+mod netif;
 mod ports;
 mod sess;
 mod smoltcpif;
@@ -50,31 +51,18 @@ mod smoltcpif;
 const MAX_SOCKETS: usize = 64;
 const MSG_SIZE: usize = 128;
 
-static OWN_IP: LazyStaticCell<IpAddress> = LazyStaticCell::default();
+static START: LazyStaticCell<TimeInstant> = LazyStaticCell::default();
 static NAMESERVER: LazyStaticCell<IpAddress> = LazyStaticCell::default();
-static OWN_MAC: [u8; 6] = [0x00, 0x0A, 0x35, 0x03, 0x02, 0x03];
-static TIMEOUTS: StaticRefCell<Vec<(SocketHandle, TimeInstant)>> = StaticRefCell::new(Vec::new());
 
-pub fn add_timeout(handle: SocketHandle, timeout: TimeInstant) {
-    TIMEOUTS.borrow_mut().push((handle, timeout));
-}
-
-pub fn remove_timeout(handle: SocketHandle) {
-    TIMEOUTS.borrow_mut().retain(|t| t.0 != handle);
-}
-
-fn next_timeout() -> Option<TimeInstant> {
-    TIMEOUTS
-        .borrow()
-        .iter()
-        .min_by(|a, b| a.1.cmp(&b.1))
-        .map(|t| t.1)
+/// Returns the current time for smoltcp
+pub fn timestamp() -> smoltcp::time::Instant {
+    smoltcp::time::Instant::from_millis(START.get().elapsed().as_millis() as i64)
 }
 
 struct NetHandler<'a> {
     reqhdl: RequestHandler<SocketSession, opcodes::Net>,
-    // holds all the actual smoltcp sockets. Used for polling events on them.
-    iface: DriverInterface<'a>,
+    // the network interfaces, which hold all the actual smoltcp sockets
+    ifaces: Interfaces<'a>,
 }
 
 impl Handler<SocketSession> for NetHandler<'_> {
@@ -97,7 +85,7 @@ impl Handler<SocketSession> for NetHandler<'_> {
         sid: SessId,
         xchg: &mut CapExchange<'_>,
     ) -> Result<(), Error> {
-        let Self { reqhdl, iface } = self;
+        let Self { reqhdl, ifaces } = self;
 
         reqhdl.handle_capxchg_with(crt, sid, xchg, |reqhdl, opcode, xchg| {
             assert!(opcode == opcodes::Net::Create.into());
@@ -107,7 +95,7 @@ impl Handler<SocketSession> for NetHandler<'_> {
                 .get_mut(sid)
                 .ok_or_else(|| Error::new(Code::InvArgs))?;
             match xchg.ty() {
-                ExcType::Obt(_) => sess.create_socket(xchg, iface),
+                ExcType::Obt(_) => sess.create_socket(xchg, ifaces),
                 ExcType::Del(_) => Err(Error::new(Code::InvArgs)),
             }
         })
@@ -115,7 +103,7 @@ impl Handler<SocketSession> for NetHandler<'_> {
 
     fn close(&mut self, crt: usize, sid: SessId) {
         if let Some(s) = self.reqhdl.clients_mut().get_mut(sid) {
-            s.abort_all(&mut self.iface).unwrap();
+            s.abort_all(&mut self.ifaces).unwrap();
         }
 
         self.reqhdl.clients_mut().remove(crt, sid);
@@ -124,22 +112,30 @@ impl Handler<SocketSession> for NetHandler<'_> {
 
 impl NetHandler<'_> {
     fn fetch_and_handle(&mut self) {
-        let Self { reqhdl, iface } = self;
+        let Self { reqhdl, ifaces } = self;
 
         reqhdl.fetch_and_handle_msg_with(|_, opcode, sess, is| match opcode {
-            o if o == opcodes::Net::Bind.into() => sess.bind(is, iface),
-            o if o == opcodes::Net::Listen.into() => sess.listen(is, iface),
-            o if o == opcodes::Net::Connect.into() => sess.connect(is, iface),
-            o if o == opcodes::Net::Abort.into() => sess.abort(is, iface),
-            o if o == opcodes::Net::GetIP.into() => Self::get_ip(is),
+            o if o == opcodes::Net::Bind.into() => sess.bind(is, ifaces),
+            o if o == opcodes::Net::Listen.into() => sess.listen(is, ifaces),
+            o if o == opcodes::Net::Accept.into() => sess.accept(is, ifaces),
+            o if o == opcodes::Net::Connect.into() => sess.connect(is, ifaces),
+            o if o == opcodes::Net::Abort.into() => sess.abort(is, ifaces),
+            o if o == opcodes::Net::SetOpt.into() => sess.set_option(is, ifaces),
+            o if o == opcodes::Net::GetOpt.into() => sess.get_option(is),
+            o if o == opcodes::Net::JoinMulticast.into() => sess.join_multicast(is, ifaces),
+            o if o == opcodes::Net::LeaveMulticast.into() => sess.leave_multicast(is, ifaces),
+            o if o == opcodes::Net::GetIP.into() => Self::get_ip(is, ifaces),
             o if o == opcodes::Net::GetNameSrv.into() => Self::get_nameserver(is),
             _ => Err(Error::new(Code::InvArgs)),
         });
     }
 
-    fn get_ip(is: &mut GateIStream<'_>) -> Result<(), Error> {
-        let addr = to_m3_addr(OWN_IP.get());
-        reply_vmsg!(is, Code::Success, addr.0)
+    fn get_ip(is: &mut GateIStream<'_>, ifaces: &Interfaces<'_>) -> Result<(), Error> {
+        // report the addresses of the default interface
+        let netif = ifaces.default_netif();
+        let addr = to_m3_addr(ifaces.ip(netif)).to_raw();
+        let addr6 = to_m3_addr(ifaces.ip6(netif)).to_raw();
+        reply_vmsg!(is, Code::Success, addr[0], addr[1], addr6[0], addr6[1])
     }
 
     fn get_nameserver(is: &mut GateIStream<'_>) -> Result<(), Error> {
@@ -147,35 +143,31 @@ impl NetHandler<'_> {
             return Err(Error::new(Code::NotSup));
         }
 
-        let addr = to_m3_addr(NAMESERVER.get());
-        reply_vmsg!(is, Code::Success, addr.0)
+        let addr = to_m3_addr(NAMESERVER.get()).to_raw();
+        reply_vmsg!(is, Code::Success, addr[0], addr[1])
     }
 
     // processes outgoing events to clients
     fn process_outgoing(&mut self) -> bool {
-        let iface = &mut self.iface;
+        let ifaces = &mut self.ifaces;
         let mut res = false;
         self.reqhdl.clients_mut().for_each(|s| {
-            res |= s.process_outgoing(iface);
+            res |= s.process_outgoing(ifaces);
         });
         res
     }
 
     // processes incoming events from clients and returns whether there is still work to do
     fn process_incoming(&mut self) -> bool {
-        let iface = &mut self.iface;
+        let ifaces = &mut self.ifaces;
         let mut res = false;
         self.reqhdl.clients_mut().for_each(|s| {
-            res |= s.process_incoming(iface);
+            res |= s.process_incoming(ifaces);
         });
         res
     }
 }
 
-pub fn own_ip() -> IpAddress {
-    OWN_IP.get()
-}
-
 #[derive(Clone, Debug)]
 pub struct NetSettings {
     driver: String,
@@ -184,7 +176,12 @@ pub struct NetSettings {
     netmask: smoltcp::wire::Ipv4Address,
     nameserver: Option<smoltcp::wire::Ipv4Address>,
     gateway: Option<smoltcp::wire::Ipv4Address>,
+    ip6: Option<Ipv6Cidr>,
+    // additional interfaces besides the first one
+    ifaces: Vec<IfConfig>,
+    routes: Vec<Route>,
     max_clients: usize,
+    spin: TimeDuration,
 }
 
 impl Default for NetSettings {
@@ -196,22 +193,33 @@ impl Default for NetSettings {
             ip: smoltcp::wire::Ipv4Address::default(),
             nameserver: None,
             gateway: None,
+            ip6: None,
+            ifaces: Vec::new(),
+            routes: Vec::new(),
             max_clients: DEF_MAX_CLIENTS,
+            spin: TimeDuration::ZERO,
         }
     }
 }
 
 fn usage() -> ! {
     println!(
-        "Usage: {} [-d <driver>] [-m <max-clients>] [-a <netmask>] [-n <nameserver>] [-g <gateway>] <name> <ip>",
+        "Usage: {} [-d <driver>] [-m <max-clients>] [-a <netmask>] [-n <nameserver>] [-g <gateway>] [-6 <ip6>/<prefix>] [-i <driver>,<ip>/<prefix>[,<ip6>/<prefix>]]... [-r <net>/<prefix>,<if>,<gateway>]... [-p <us>] <name> <ip>",
         env::args().next().unwrap()
     );
     println!();
-    println!("  -d: the driver to use (lo=loopback or default=E1000/Fifo)");
+    println!("  -d: the driver to use (lo=loopback, virtio=virtio-net, or default=E1000/Fifo)");
     println!("  -m: the maximum number of clients (receive slots)");
     println!("  -a: the network mask to use (default: 255.255.255.0)");
     println!("  -n: the IP address of the DNS server");
     println!("  -g: the IP address of the default gateway");
+    println!("  -6: an IPv6 address with prefix length in addition to the link-local address");
+    println!("  -i: adds another interface with given driver and address(es)");
+    println!("  -r: adds a route to <net> via <gateway> on interface <if> (0 is the first)");
+    println!("  -p: keep polling for <us> microseconds before going to sleep (0 by default)");
+    println!();
+    println!("A loopback interface with 127.0.0.1/8 and ::1 is added, unless 127.0.0.1 is already");
+    println!("in the network of one of the configured interfaces.");
     OwnActivity::exit_with(Code::InvArgs);
 }
 
@@ -228,6 +236,14 @@ fn parse_args() -> Result<NetSettings, String> {
                     .map_err(|_| String::from("Failed to parse client count"))?;
                 i += 1;
             },
+            "-p" => {
+                settings.spin = TimeDuration::from_micros(
+                    args[i + 1]
+                        .parse::<u64>()
+                        .map_err(|_| String::from("Failed to parse spin time"))?,
+                );
+                i += 1;
+            },
             "-d" => {
                 settings.driver = args[i + 1].to_string();
                 i += 1;
@@ -248,6 +264,27 @@ fn parse_args() -> Result<NetSettings, String> {
                 );
                 i += 1;
             },
+            "-6" => {
+                settings.ip6 = Some(
+                    Ipv6Cidr::from_str(args.get(i + 1).expect("Failed to read IPv6 address!"))
+                        .map_err(|_| String::from("Failed to parse IPv6 address"))?,
+                );
+                i += 1;
+            },
+            "-i" => {
+                settings.ifaces.push(parse_iface(
+                    args.get(i + 1)
+                        .ok_or_else(|| String::from("Missing interface"))?,
+                )?);
+                i += 1;
+            },
+            "-r" => {
+                settings.routes.push(parse_route(
+                    args.get(i + 1)
+                        .ok_or_else(|| String::from("Missing route"))?,
+                )?);
+                i += 1;
+            },
             "-g" => {
                 settings.gateway = Some(
                     smoltcp::wire::Ipv4Address::from_str(
@@ -273,24 +310,80 @@ fn parse_args() -> Result<NetSettings, String> {
     Ok(settings)
 }
 
+fn parse_iface(arg: &str) -> Result<IfConfig, String> {
+    let mut parts = arg.split(',');
+    let driver = parts.next().unwrap().to_string();
+    let ip = parts
+        .next()
+        .and_then(|ip| Ipv4Cidr::from_str(ip).ok())
+        .ok_or_else(|| String::from("Failed to parse interface address"))?;
+    let ip6 = match parts.next() {
+        Some(ip6) => Some(
+            Ipv6Cidr::from_str(ip6)
+                .map_err(|_| String::from("Failed to parse interface IPv6 address"))?,
+        ),
+        None => None,
+    };
+    Ok(IfConfig { driver, ip, ip6 })
+}
+
+fn parse_route(arg: &str) -> Result<Route, String> {
+    let parts = arg.split(',').collect::<Vec<_>>();
+    if parts.len() != 3 {
+        return Err(String::from(
+            "Route needs to be <net>/<prefix>,<if>,<gateway>",
+        ));
+    }
+
+    let dest =
+        Ipv4Cidr::from_str(parts[0]).map_err(|_| String::from("Failed to parse route network"))?;
+    let netif = parts[1]
+        .parse::<usize>()
+        .map_err(|_| String::from("Failed to parse route interface"))?;
+    let gateway = smoltcp::wire::Ipv4Address::from_str(parts[2])
+        .map_err(|_| String::from("Failed to parse route gateway"))?;
+    Ok(Route {
+        dest,
+        netif,
+        gateway,
+    })
+}
+
+/// Runs the tests of the interfaces, the routing, and the traffic shaping instead of the server
+#[cfg(feature = "tests")]
+fn run_tests() -> ! {
+    use m3::test::{DefaultWvTester, WvTester};
+    use m3::wv_run_suite;
+
+    let mut tester = DefaultWvTester::default();
+    wv_run_suite!(tester, netif::tests::run);
+    wv_run_suite!(tester, sess::settings_tests::run);
+    wv_run_suite!(tester, sess::shaper_tests::run);
+    println!("{}", tester);
+    OwnActivity::exit_with(if tester.failures() == 0 {
+        Code::Success
+    }
+    else {
+        Code::InvState
+    });
+}
+
 #[no_mangle]
 pub fn main() -> Result<(), Error> {
     smoltcpif::logger::init().unwrap();
 
+    #[cfg(feature = "tests")]
+    if env::args().nth(1) == Some("-T") {
+        run_tests();
+    }
+
     let settings = parse_args().unwrap_or_else(|e| {
         println!("Invalid arguments: {}", e);
         usage();
     });
 
-    let mut neighbor_cache_entries = [None; 8];
-    let neighbor_cache = NeighborCache::new(&mut neighbor_cache_entries[..]);
-
-    let ip_cidr = smoltcp::wire::IpCidr::Ipv4(
-        Ipv4Cidr::from_netmask(settings.ip, settings.netmask)
-            .expect("Invalid IP-address/netmask pair"),
-    );
-    let ip_addr = ip_cidr.address();
-    OWN_IP.set(ip_addr);
+    let ip_cidr = Ipv4Cidr::from_netmask(settings.ip, settings.netmask)
+        .expect("Invalid IP-address/netmask pair");
 
     if let Some(ns) = settings.nameserver {
         let ns_cidr =
@@ -298,47 +391,32 @@ pub fn main() -> Result<(), Error> {
         NAMESERVER.set(IpAddress::Ipv4(ns_cidr.address()));
     }
 
-    let mut routes = Routes::new(BTreeMap::new());
+    // the first interface is configured by the "global" arguments
+    let mut cfgs = vec![IfConfig {
+        driver: settings.driver.clone(),
+        ip: ip_cidr,
+        ip6: settings.ip6,
+    }];
+    cfgs.extend(settings.ifaces.iter().cloned());
+
+    // the default gateway is reachable via the first interface
+    let mut routes = settings.routes.clone();
     if let Some(gw) = settings.gateway {
-        routes
-            .add_default_ipv4_route(gw)
-            .expect("Cannot add default route");
+        routes.push(Route {
+            dest: Ipv4Cidr::new(smoltcp::wire::Ipv4Address::UNSPECIFIED, 0),
+            netif: 0,
+            gateway: gw,
+        });
     }
 
     ports::init(MAX_SOCKETS);
 
-    let iface = if settings.driver == "lo" {
-        driver::DriverInterface::Lo(
-            InterfaceBuilder::new(
-                smoltcp::phy::Loopback::new(smoltcp::phy::Medium::Ethernet),
-                Vec::with_capacity(MAX_SOCKETS),
-            )
-            .hardware_addr(EthernetAddress::from_bytes(&OWN_MAC).into())
-            .neighbor_cache(neighbor_cache)
-            .ip_addrs([ip_cidr])
-            .routes(routes)
-            .finalize(),
-        )
-    }
-    else {
-        #[cfg(feature = "gem5")]
-        let device = driver::E1000Device::new().expect("Failed to create E1000 driver");
-        #[cfg(not(feature = "gem5"))]
-        let device = driver::AXIEthDevice::new().expect("Failed to create AXI ethernet driver");
-        driver::DriverInterface::Eth(
-            InterfaceBuilder::new(device, Vec::with_capacity(MAX_SOCKETS))
-                .hardware_addr(EthernetAddress::from_bytes(&OWN_MAC).into())
-                .neighbor_cache(neighbor_cache)
-                .ip_addrs([ip_cidr])
-                .routes(routes)
-                .finalize(),
-        )
-    };
+    let ifaces = Interfaces::new(&cfgs, routes).expect("Failed to create network interfaces");
 
     let mut handler = NetHandler {
         reqhdl: RequestHandler::new_with(settings.max_clients, MSG_SIZE, 1)
             .expect("Unable to create request handler"),
-        iface,
+        ifaces,
     };
 
     let serv = Server::new(&settings.name, &mut handler).expect("Failed to create server!");
@@ -351,6 +429,8 @@ pub fn main() -> Result<(), Error> {
             "  ip={:?},\n",
             "  nameserver={:?},\n",
             "  gateway={:?},\n",
+            "  interfaces={},\n",
+            "  routes={:?},\n",
             "}}"
         ),
         settings.name,
@@ -358,12 +438,20 @@ pub fn main() -> Result<(), Error> {
         settings.ip,
         settings.nameserver,
         settings.gateway,
+        cfgs.len(),
+        settings.routes,
     );
 
-    let start = TimeInstant::now();
+    START.set(TimeInstant::now());
 
+    let wait = WaitPolicy::spin_then_sleep(settings.spin);
+    let mut idle_since = None;
     'outer: loop {
         let sleep_nanos = loop {
+            watchdog::check_in();
+            // handle expired timers, in case we are too busy to sleep
+            timer::run();
+
             if serv.fetch_and_handle(&mut handler).is_err() {
                 break 'outer;
             }
@@ -374,19 +462,17 @@ pub fn main() -> Result<(), Error> {
             // receive events from clients and push data to send into smoltcp sockets
             let sends_pending = handler.process_incoming();
 
-            let cur_time = smoltcp::time::Instant::from_millis(start.elapsed().as_millis() as i64);
+            let cur_time = timestamp();
 
             // now poll smoltcp to send and receive packets
-            if let Err(e) = handler.iface.poll(cur_time) {
-                log!(LogFlags::NetPoll, "netrs: poll failed: {}", e);
-            }
+            handler.ifaces.poll(cur_time);
 
             // check for outgoing events we have to send to clients
             let recvs_pending = handler.process_outgoing();
 
-            if !sends_pending && !recvs_pending && !handler.iface.needs_poll() {
+            if !sends_pending && !recvs_pending && !handler.ifaces.needs_poll() {
                 // ask smoltcp how long we can sleep
-                match handler.iface.poll_delay(cur_time) {
+                match handler.ifaces.poll_delay(cur_time) {
                     // we need to call it again immediately => continue the loop
                     Some(d) if d.total_millis() == 0 => continue,
                     // we should not wait longer than `n` => sleep for `n`
@@ -397,15 +483,23 @@ pub fn main() -> Result<(), Error> {
             }
         };
 
+        // under bursty load, new requests or packets are likely to arrive soon. thus, keep polling
+        // for a while before going to sleep to avoid the wakeup latency
         let now = TimeInstant::now();
-        let sleep_nanos = match next_timeout() {
-            Some(timeout) if timeout > now && timeout - now < sleep_nanos => timeout - now,
-            _ => sleep_nanos,
-        };
+        let idle = *idle_since.get_or_insert(now);
+        if now.duration_since(idle) < wait.spin_time() {
+            continue;
+        }
+        idle_since = None;
+
+        // wake up in time for the next heartbeat to the watchdog
+        let sleep_nanos = watchdog::next_timeout().map_or(sleep_nanos, |t| t.min(sleep_nanos));
 
         log_net(NetLogEvent::StartedWaiting, 0, 0);
         log!(LogFlags::NetPoll, "Sleeping for {:?}", sleep_nanos);
-        OwnActivity::sleep_for(sleep_nanos).ok();
+        // sleep via TileMux until smoltcp needs to be polled, a message arrives, or a timer
+        // (e.g., for a connect timeout) expires
+        timer::sleep_for(sleep_nanos).ok();
         log_net(NetLogEvent::StoppedWaiting, 0, 0);
     }
 
@@ -0,0 +1,487 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! The network interfaces and the static routing table
+//!
+//! Every network interface has its own driver, MAC address, and IP configuration. Besides the
+//! configured interfaces, there is always a loopback interface for 127.0.0.1 and ::1. The routing
+//! table determines the interface a socket uses to reach a remote address. Datagram sockets exist
+//! on all interfaces and send every packet via the interface of its destination. Note that packets
+//! are not forwarded between the interfaces.
+
+use m3::col::{BTreeMap, String, ToString, Vec};
+use m3::errors::{Code, Error};
+use m3::io::LogFlags;
+use m3::{log, vec};
+
+use smoltcp::iface::{Interface, InterfaceBuilder, NeighborCache, Route as SmolRoute, Routes};
+use smoltcp::phy::{Device, Loopback, Medium};
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::{
+    EthernetAddress, IpAddress, IpCidr, Ipv4Address, Ipv4Cidr, Ipv6Address, Ipv6Cidr,
+};
+
+use crate::driver::{self, DriverInterface};
+use crate::multicast;
+use crate::MAX_SOCKETS;
+
+// the MAC address of the first interface; the others increment the last byte
+const BASE_MAC: [u8; 6] = [0x00, 0x0A, 0x35, 0x03, 0x02, 0x03];
+
+/// The configuration of a network interface
+#[derive(Clone, Debug)]
+pub struct IfConfig {
+    /// The driver to use (lo=loopback, virtio=virtio-net, or default=E1000/Fifo)
+    pub driver: String,
+    /// The IPv4 address and the network
+    pub ip: Ipv4Cidr,
+    /// An optional IPv6 address in addition to the link-local address
+    pub ip6: Option<Ipv6Cidr>,
+}
+
+/// An entry in the static routing table
+#[derive(Copy, Clone, Debug)]
+pub struct Route {
+    /// The destination network
+    pub dest: Ipv4Cidr,
+    /// The index of the interface the network is reachable by
+    pub netif: usize,
+    /// The router to send the packets to
+    pub gateway: Ipv4Address,
+}
+
+/// A network interface
+pub struct NetIf<'a> {
+    pub dev: DriverInterface<'a>,
+    cfg: IfConfig,
+    // the IPv6 address we use by default (the configured one or the link-local address)
+    ip6: Ipv6Address,
+    // the joined multicast groups and the number of sockets that joined them
+    pub groups: BTreeMap<Ipv4Address, usize>,
+}
+
+impl<'a> NetIf<'a> {
+    fn new(idx: usize, cfg: IfConfig, routes: &[Route]) -> Result<Self, Error> {
+        let mut mac = BASE_MAC;
+        mac[5] = mac[5].wrapping_add(idx as u8);
+
+        // we always have a link-local IPv6 address and use the configured one, if any, by default
+        let link_local = link_local_ip6(&mac);
+        let mut ip_addrs = vec![
+            IpCidr::Ipv4(cfg.ip),
+            IpCidr::Ipv6(Ipv6Cidr::new(link_local, 64)),
+        ];
+        if let Some(ip6) = cfg.ip6 {
+            ip_addrs.push(IpCidr::Ipv6(ip6));
+        }
+        let ip6_addrs = ip_addrs
+            .iter()
+            .filter_map(|cidr| match cidr.address() {
+                IpAddress::Ipv6(addr) => Some(addr),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        let mut smol_routes = Routes::new(BTreeMap::new());
+        smol_routes.update(|map| {
+            for r in routes.iter().filter(|r| r.netif == idx) {
+                map.insert(IpCidr::Ipv4(r.dest), SmolRoute::new_ipv4_gateway(r.gateway))
+                    .ok();
+            }
+        });
+
+        let mac = EthernetAddress::from_bytes(&mac);
+        let mut dev = match cfg.driver.as_str() {
+            "lo" => DriverInterface::Lo(build(
+                Loopback::new(Medium::Ethernet),
+                mac,
+                ip_addrs,
+                smol_routes,
+            )),
+            "virtio" => DriverInterface::Virtio(build(
+                driver::VirtioNetDevice::new()?,
+                mac,
+                ip_addrs,
+                smol_routes,
+            )),
+            #[cfg(feature = "gem5")]
+            _ => DriverInterface::Eth(build(
+                driver::E1000Device::new()?,
+                mac,
+                ip_addrs,
+                smol_routes,
+            )),
+            #[cfg(not(feature = "gem5"))]
+            _ => DriverInterface::Eth(build(
+                driver::AXIEthDevice::new()?,
+                mac,
+                ip_addrs,
+                smol_routes,
+            )),
+        };
+        multicast::init(&mut dev, &ip6_addrs);
+
+        Ok(Self {
+            dev,
+            ip6: cfg.ip6.map_or(link_local, |c| c.address()),
+            cfg,
+            groups: BTreeMap::new(),
+        })
+    }
+}
+
+fn build<'a, D>(
+    device: D,
+    mac: EthernetAddress,
+    ip_addrs: Vec<IpCidr>,
+    routes: Routes<'a>,
+) -> Interface<'a, D>
+where
+    D: for<'d> Device<'d>,
+{
+    InterfaceBuilder::new(device, Vec::with_capacity(MAX_SOCKETS))
+        .hardware_addr(mac.into())
+        .neighbor_cache(NeighborCache::new(BTreeMap::new()))
+        .ip_addrs(ip_addrs)
+        .routes(routes)
+        .ipv4_multicast_groups(BTreeMap::new())
+        .finalize()
+}
+
+// the IPv6 link-local address, derived from the MAC address (EUI-64)
+fn link_local_ip6(m: &[u8; 6]) -> Ipv6Address {
+    Ipv6Address::new(
+        0xfe80,
+        0,
+        0,
+        0,
+        u16::from_be_bytes([m[0] ^ 0x02, m[1]]),
+        u16::from_be_bytes([m[2], 0xff]),
+        u16::from_be_bytes([0xfe, m[3]]),
+        u16::from_be_bytes([m[4], m[5]]),
+    )
+}
+
+/// The registry of all network interfaces
+pub struct Interfaces<'a> {
+    ifs: Vec<NetIf<'a>>,
+    routes: Vec<Route>,
+}
+
+impl<'a> Interfaces<'a> {
+    /// Creates the network interfaces for the given configurations with the given static routes
+    ///
+    /// The gateway of every route needs to be in the network of its interface. A loopback
+    /// interface is added after the configured ones, unless the network of one of them already
+    /// contains 127.0.0.1.
+    pub fn new(cfgs: &[IfConfig], routes: Vec<Route>) -> Result<Self, Error> {
+        for r in &routes {
+            match cfgs.get(r.netif) {
+                Some(cfg) if cfg.ip.contains_addr(&r.gateway) => {},
+                _ => return Err(Error::new(Code::InvArgs)),
+            }
+        }
+
+        let mut cfgs = cfgs.to_vec();
+        let lo_addr = Ipv4Address::new(127, 0, 0, 1);
+        if !cfgs.iter().any(|cfg| cfg.ip.contains_addr(&lo_addr)) {
+            cfgs.push(IfConfig {
+                driver: "lo".to_string(),
+                ip: Ipv4Cidr::new(lo_addr, 8),
+                ip6: Some(Ipv6Cidr::new(Ipv6Address::LOOPBACK, 128)),
+            });
+        }
+
+        let mut ifs = Vec::with_capacity(cfgs.len());
+        for (idx, cfg) in cfgs.iter().enumerate() {
+            let netif = NetIf::new(idx, cfg.clone(), &routes)?;
+            log!(
+                LogFlags::Info,
+                "netrs: created interface {} (driver={}, ip={}, ip6={})",
+                idx,
+                netif.cfg.driver,
+                netif.cfg.ip,
+                netif.ip6
+            );
+            ifs.push(netif);
+        }
+
+        Ok(Self { ifs, routes })
+    }
+
+    /// Returns the number of interfaces
+    pub fn count(&self) -> usize {
+        self.ifs.len()
+    }
+
+    /// Returns the interface with given index
+    pub fn get(&mut self, netif: usize) -> &mut NetIf<'a> {
+        &mut self.ifs[netif]
+    }
+
+    /// Returns the driver interface of the interface with given index
+    pub fn dev(&mut self, netif: usize) -> &mut DriverInterface<'a> {
+        &mut self.ifs[netif].dev
+    }
+
+    /// Returns the IPv4 address of the interface with given index
+    pub fn ip(&self, netif: usize) -> IpAddress {
+        IpAddress::Ipv4(self.ifs[netif].cfg.ip.address())
+    }
+
+    /// Returns the default IPv6 address of the interface with given index
+    pub fn ip6(&self, netif: usize) -> IpAddress {
+        IpAddress::Ipv6(self.ifs[netif].ip6)
+    }
+
+    /// Returns the interface that is used by sockets that are not connected to a remote address,
+    /// which is the interface of the default route or the first interface
+    pub fn default_netif(&self) -> usize {
+        self.routes
+            .iter()
+            .find(|r| r.dest.prefix_len() == 0)
+            .map_or(0, |r| r.netif)
+    }
+
+    /// Determines the interface to reach `addr`
+    ///
+    /// For IPv4, the longest prefix of the directly connected networks and the routes wins. IPv6
+    /// addresses are only matched against the networks of the interfaces. If no interface matches,
+    /// the default interface is used.
+    pub fn route(&self, addr: IpAddress) -> usize {
+        match addr {
+            IpAddress::Ipv4(a) => {
+                let connected = self
+                    .ifs
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, nif)| (nif.cfg.ip, idx));
+                let routes = self.routes.iter().map(|r| (r.dest, r.netif));
+                // on equal prefixes, directly connected networks take precedence
+                connected
+                    .chain(routes)
+                    .filter(|(net, _)| net.contains_addr(&a))
+                    .fold(None, |best: Option<(u8, usize)>, (net, idx)| match best {
+                        Some((len, _)) if len >= net.prefix_len() => best,
+                        _ => Some((net.prefix_len(), idx)),
+                    })
+                    .map_or(self.default_netif(), |(_, idx)| idx)
+            },
+            IpAddress::Ipv6(a) => self
+                .ifs
+                .iter()
+                .position(|nif| nif.cfg.ip6.map_or(false, |net| net.contains_addr(&a)))
+                .unwrap_or_else(|| self.default_netif()),
+            _ => self.default_netif(),
+        }
+    }
+
+    /// Polls all interfaces to send and receive packets
+    pub fn poll(&mut self, timestamp: Instant) {
+        for (idx, nif) in self.ifs.iter_mut().enumerate() {
+            if let Err(e) = nif.dev.poll(timestamp) {
+                log!(
+                    LogFlags::NetPoll,
+                    "netrs: poll of interface {} failed: {}",
+                    idx,
+                    e
+                );
+            }
+        }
+    }
+
+    /// Returns the time until the next interface needs to be polled
+    pub fn poll_delay(&mut self, timestamp: Instant) -> Option<Duration> {
+        self.ifs
+            .iter_mut()
+            .filter_map(|nif| nif.dev.poll_delay(timestamp))
+            .min()
+    }
+
+    /// Returns true if any interface needs to be polled immediately
+    pub fn needs_poll(&self) -> bool {
+        self.ifs.iter().any(|nif| nif.dev.needs_poll())
+    }
+}
+
+#[cfg(feature = "tests")]
+pub mod tests {
+    use super::*;
+
+    use core::str::FromStr;
+
+    use m3::test::WvTester;
+    use m3::{wv_assert, wv_assert_eq, wv_assert_err, wv_assert_ok, wv_run_test};
+
+    use crate::{parse_iface, parse_route};
+
+    pub fn run(t: &mut dyn WvTester) {
+        wv_run_test!(t, args);
+        wv_run_test!(t, invalid_args);
+        wv_run_test!(t, addresses);
+        wv_run_test!(t, routing);
+        wv_run_test!(t, default_route);
+        wv_run_test!(t, invalid_routes);
+    }
+
+    fn ip(s: &str) -> IpAddress {
+        IpAddress::from_str(s).unwrap()
+    }
+
+    fn cidr(s: &str) -> Ipv4Cidr {
+        Ipv4Cidr::from_str(s).unwrap()
+    }
+
+    fn lo(net: &str, net6: Option<&str>) -> IfConfig {
+        IfConfig {
+            driver: "lo".to_string(),
+            ip: cidr(net),
+            ip6: net6.map(|n| Ipv6Cidr::from_str(n).unwrap()),
+        }
+    }
+
+    fn route(dest: &str, netif: usize, gateway: &str) -> Route {
+        Route {
+            dest: cidr(dest),
+            netif,
+            gateway: Ipv4Address::from_str(gateway).unwrap(),
+        }
+    }
+
+    /// Creates three interfaces with a default route via the first one and two overlapping
+    /// routes via the others
+    fn interfaces() -> Result<Interfaces<'static>, Error> {
+        Interfaces::new(
+            &[
+                lo("192.168.0.1/24", None),
+                lo("10.0.1.1/24", Some("fd00::1/64")),
+                lo("10.0.2.1/24", None),
+            ],
+            vec![
+                route("10.1.0.0/16", 1, "10.0.1.254"),
+                route("10.1.5.0/24", 2, "10.0.2.254"),
+                route("0.0.0.0/0", 0, "192.168.0.254"),
+            ],
+        )
+    }
+
+    fn args(t: &mut dyn WvTester) {
+        let cfg = wv_assert_ok!(parse_iface("virtio,10.0.1.1/24"));
+        wv_assert_eq!(t, cfg.driver, "virtio");
+        wv_assert_eq!(t, cfg.ip, cidr("10.0.1.1/24"));
+        wv_assert_eq!(t, cfg.ip6, None);
+
+        let cfg = wv_assert_ok!(parse_iface("lo,10.0.1.1/24,fd00::1/64"));
+        wv_assert_eq!(t, cfg.ip6, Some(Ipv6Cidr::from_str("fd00::1/64").unwrap()));
+
+        let r = wv_assert_ok!(parse_route("10.1.0.0/16,1,10.0.1.254"));
+        wv_assert_eq!(t, r.dest, cidr("10.1.0.0/16"));
+        wv_assert_eq!(t, r.netif, 1);
+        wv_assert_eq!(t, r.gateway, Ipv4Address::new(10, 0, 1, 254));
+    }
+
+    fn invalid_args(t: &mut dyn WvTester) {
+        wv_assert!(t, parse_iface("lo").is_err());
+        wv_assert!(t, parse_iface("lo,10.0.1/24").is_err());
+        wv_assert!(t, parse_iface("lo,10.0.1.1/24,fd00:x::1/64").is_err());
+
+        wv_assert!(t, parse_route("10.1.0.0/16,1").is_err());
+        wv_assert!(t, parse_route("10.1.0.0/16,1,10.0.1.254,1").is_err());
+        wv_assert!(t, parse_route("10.1.0.0,1,10.0.1.254").is_err());
+        wv_assert!(t, parse_route("10.1.0.0/16,eth1,10.0.1.254").is_err());
+        wv_assert!(t, parse_route("10.1.0.0/16,1,gateway").is_err());
+    }
+
+    fn addresses(t: &mut dyn WvTester) {
+        let ifs = wv_assert_ok!(interfaces());
+        // the loopback interface is added after the configured ones
+        wv_assert_eq!(t, ifs.count(), 4);
+        wv_assert_eq!(t, ifs.ip(1), ip("10.0.1.1"));
+        wv_assert_eq!(t, ifs.ip(3), ip("127.0.0.1"));
+
+        // the configured IPv6 address is preferred over the link-local address
+        wv_assert_eq!(t, ifs.ip6(1), ip("fd00::1"));
+        wv_assert_eq!(t, ifs.ip6(0), ip("fe80::20a:35ff:fe03:203"));
+        wv_assert_eq!(t, ifs.ip6(2), ip("fe80::20a:35ff:fe03:205"));
+        wv_assert_eq!(t, ifs.ip6(3), ip("::1"));
+
+        // no additional loopback interface if there is one already
+        let ifs = wv_assert_ok!(Interfaces::new(&[lo("127.0.0.1/8", None)], vec![]));
+        wv_assert_eq!(t, ifs.count(), 1);
+    }
+
+    fn routing(t: &mut dyn WvTester) {
+        let ifs = wv_assert_ok!(interfaces());
+
+        // directly connected networks
+        wv_assert_eq!(t, ifs.route(ip("192.168.0.7")), 0);
+        wv_assert_eq!(t, ifs.route(ip("10.0.1.8")), 1);
+        wv_assert_eq!(t, ifs.route(ip("10.0.2.9")), 2);
+        wv_assert_eq!(t, ifs.route(ip("127.0.0.1")), 3);
+
+        // the longest prefix wins
+        wv_assert_eq!(t, ifs.route(ip("10.1.9.9")), 1);
+        wv_assert_eq!(t, ifs.route(ip("10.1.5.1")), 2);
+        wv_assert_eq!(t, ifs.route(ip("8.8.8.8")), 0);
+
+        // IPv6 addresses are only matched against the networks of the interfaces
+        wv_assert_eq!(t, ifs.route(ip("fd00::5")), 1);
+        wv_assert_eq!(t, ifs.route(ip("::1")), 3);
+        wv_assert_eq!(t, ifs.route(ip("2001:db8::1")), 0);
+    }
+
+    fn default_route(t: &mut dyn WvTester) {
+        // without default route, the first interface is used
+        let cfgs = [lo("192.168.0.1/24", None), lo("10.0.1.1/24", None)];
+        let ifs = wv_assert_ok!(Interfaces::new(&cfgs, vec![]));
+        wv_assert_eq!(t, ifs.default_netif(), 0);
+        wv_assert_eq!(t, ifs.route(ip("8.8.8.8")), 0);
+
+        let ifs = wv_assert_ok!(Interfaces::new(&cfgs, vec![route(
+            "0.0.0.0/0",
+            1,
+            "10.0.1.254"
+        )]));
+        wv_assert_eq!(t, ifs.default_netif(), 1);
+        wv_assert_eq!(t, ifs.route(ip("8.8.8.8")), 1);
+        wv_assert_eq!(t, ifs.route(ip("2001:db8::1")), 1);
+
+        // on equal prefixes, the directly connected network takes precedence over the route
+        let ifs = wv_assert_ok!(Interfaces::new(&cfgs, vec![route(
+            "192.168.0.0/24",
+            1,
+            "10.0.1.254"
+        )]));
+        wv_assert_eq!(t, ifs.route(ip("192.168.0.7")), 0);
+    }
+
+    fn invalid_routes(t: &mut dyn WvTester) {
+        let cfgs = [lo("192.168.0.1/24", None), lo("10.0.1.1/24", None)];
+
+        // the gateway is not in the network of the interface
+        wv_assert_err!(
+            t,
+            Interfaces::new(&cfgs, vec![route("10.1.0.0/16", 0, "10.0.1.254")]).map(|_| ()),
+            Code::InvArgs
+        );
+
+        // the interface does not exist (the loopback interface cannot be used either)
+        wv_assert_err!(
+            t,
+            Interfaces::new(&cfgs, vec![route("10.1.0.0/16", 2, "127.0.0.2")]).map(|_| ()),
+            Code::InvArgs
+        );
+    }
+}
@@ -16,27 +16,50 @@
 use core::fmt;
 
 use base::io::LogFlags;
-use m3::cell::{LazyStaticRefCell, StaticCell};
-use m3::col::BitArray;
+use m3::cell::{LazyStaticRefCell, StaticCell, StaticRefCell};
+use m3::col::{BitArray, Vec};
+use m3::errors::{Code, Error};
 use m3::log;
-use m3::net::Port;
+use m3::net::{Port, SocketType};
 
 static PORTS: LazyStaticRefCell<BitArray> = LazyStaticRefCell::default();
 static NEXT_PORT: StaticCell<Port> = StaticCell::new(0);
 
+// the manually chosen ports that are in use: socket type, port, and whether it can be shared
+static MANUAL_PORTS: StaticRefCell<Vec<(SocketType, Port, bool)>> = StaticRefCell::new(Vec::new());
+
 // ephemeral port range is from 49152 to 65535
 const FIRST_PORT: Port = 49152;
 
 pub enum AnyPort {
     Ephemeral(EphemeralPort),
-    Manual(Port),
+    Manual(ManualPort),
 }
 
 impl AnyPort {
     pub fn number(&self) -> Port {
         match self {
             Self::Ephemeral(e) => e.port,
-            Self::Manual(p) => *p,
+            Self::Manual(m) => m.port,
+        }
+    }
+}
+
+/// A port that has been chosen by the client and is in use until dropped
+pub struct ManualPort {
+    ty: SocketType,
+    port: Port,
+}
+
+impl Drop for ManualPort {
+    fn drop(&mut self) {
+        log!(LogFlags::NetPorts, "manual-ports: releasing {}", self.port);
+        let mut ports = MANUAL_PORTS.borrow_mut();
+        if let Some(idx) = ports
+            .iter()
+            .position(|(ty, port, _)| *ty == self.ty && *port == self.port)
+        {
+            ports.swap_remove(idx);
         }
     }
 }
@@ -99,6 +122,24 @@ pub fn alloc() -> EphemeralPort {
     res
 }
 
+/// Marks `port` as used by a socket of type `ty`.
+///
+/// Fails with [`Exists`](Code::Exists) if the port is already in use by another socket of the same
+/// type, unless all these sockets and the new one allow to share it (`reuse`).
+pub fn use_manual(ty: SocketType, port: Port, reuse: bool) -> Result<ManualPort, Error> {
+    let mut ports = MANUAL_PORTS.borrow_mut();
+    if ports
+        .iter()
+        .any(|(pty, pport, preuse)| *pty == ty && *pport == port && !(*preuse && reuse))
+    {
+        return Err(Error::new(Code::Exists));
+    }
+
+    log!(LogFlags::NetPorts, "manual-ports: using {}", port);
+    ports.push((ty, port, reuse));
+    Ok(ManualPort { ty, port })
+}
+
 pub fn is_ephemeral(port: Port) -> bool {
     port >= FIRST_PORT
 }
@@ -16,6 +16,7 @@
  */
 
 mod settings;
+mod shaper;
 
 use core::cmp;
 
@@ -27,15 +28,24 @@ use m3::col::Vec;
 use m3::com::GateIStream;
 use m3::errors::{Code, Error};
 use m3::kif::{CapRngDesc, CapType};
-use m3::net::{log_net, IpAddr, NetLogEvent, Port, Sd, SocketArgs, SocketType, MTU};
+use m3::net::{log_net, IpAddr, NetLogEvent, Port, Sd, SocketArgs, SocketOpt, SocketType, MTU};
 use m3::rc::Rc;
 use m3::server::{CapExchange, RequestSession, ServerSession};
 use m3::{log, reply_vmsg, vec};
 
-use crate::driver::DriverInterface;
+use smoltcp::wire::IpAddress;
+
+use crate::netif::Interfaces;
 use crate::ports::{self, AnyPort};
 use crate::smoltcpif::socket::{to_m3_addr, to_m3_ep, SendNetEvent, Socket};
 
+use shaper::Shaper;
+
+#[cfg(feature = "tests")]
+pub use settings::tests as settings_tests;
+#[cfg(feature = "tests")]
+pub use shaper::tests as shaper_tests;
+
 pub struct SocketSession {
     // our session cap
     serv: ServerSession,
@@ -43,6 +53,8 @@ pub struct SocketSession {
     settings: settings::Settings,
     // sockets the client has open
     sockets: Vec<Option<Rc<RefCell<Socket>>>>,
+    // limits the data the client sends
+    shaper: Shaper,
 }
 
 impl RequestSession for SocketSession {
@@ -53,9 +65,14 @@ impl RequestSession for SocketSession {
         log!(LogFlags::NetSess, "[{}] net::open(arg={})", serv.id(), arg,);
 
         let settings = settings::parse_arguments(arg)?;
+        // by default, the client can send the data of 100ms at once
+        let burst = settings
+            .burst
+            .unwrap_or_else(|| settings.rate.unwrap_or(0) / 10);
         Ok(SocketSession {
             serv,
             sockets: vec![None; settings.socks],
+            shaper: Shaper::new(settings.rate, burst),
             settings,
         })
     }
@@ -76,7 +93,7 @@ impl SocketSession {
     pub fn create_socket(
         &mut self,
         xchg: &mut CapExchange<'_>,
-        iface: &mut DriverInterface<'_>,
+        ifaces: &mut Interfaces<'_>,
     ) -> Result<(), Error> {
         let is = xchg.in_args();
 
@@ -100,7 +117,7 @@ impl SocketSession {
                 sbuf_size,
             },
             caps,
-            iface,
+            ifaces,
         );
 
         log!(
@@ -140,13 +157,13 @@ impl SocketSession {
         protocol: u8,
         args: &SocketArgs,
         caps: Selector,
-        iface: &mut DriverInterface<'_>,
+        ifaces: &mut Interfaces<'_>,
     ) -> Result<Sd, Error> {
         if ty == SocketType::Raw && !self.settings.raw {
             return Err(Error::new(Code::NoPerm));
         }
 
-        let total_space = Socket::required_space(ty, args);
+        let total_space = Socket::required_space(ty, args, ifaces.count());
         if self.settings.bufs < total_space {
             return Err(Error::new(Code::NoSpace));
         }
@@ -154,7 +171,7 @@ impl SocketSession {
         for (i, s) in self.sockets.iter_mut().enumerate() {
             if s.is_none() {
                 *s = Some(Rc::new(RefCell::new(Socket::new(
-                    i, ty, protocol, args, caps, iface,
+                    i, ty, protocol, args, caps, ifaces,
                 )?)));
                 self.settings.bufs -= total_space;
                 return Ok(i);
@@ -186,7 +203,7 @@ impl SocketSession {
     pub fn bind(
         &mut self,
         is: &mut GateIStream<'_>,
-        iface: &mut DriverInterface<'_>,
+        ifaces: &mut Interfaces<'_>,
     ) -> Result<(), Error> {
         let sd: Sd = is.pop()?;
         let port: Port = is.pop()?;
@@ -204,54 +221,99 @@ impl SocketSession {
             AnyPort::Ephemeral(ports::alloc())
         }
         else {
-            if !self.can_use_port(sock.borrow().socket_type(), port) {
+            let ty = sock.borrow().socket_type();
+            if !self.can_use_port(ty, port) {
                 return Err(Error::new(Code::NoPerm));
             }
 
-            AnyPort::Manual(port)
+            AnyPort::Manual(ports::use_manual(ty, port, sock.borrow().reuse_addr())?)
         };
 
         let port_no = port.number();
-        sock.borrow_mut().bind(crate::own_ip(), port, iface)?;
+        // bind to all local addresses to be able to communicate via IPv4 and IPv6
+        sock.borrow_mut()
+            .bind(IpAddress::Unspecified, port, ifaces)?;
 
-        let addr = to_m3_addr(crate::own_ip());
-        reply_vmsg!(is, Code::Success, addr.0, port_no)
+        let addr = to_m3_addr(ifaces.ip(sock.borrow().netif())).to_raw();
+        reply_vmsg!(is, Code::Success, addr[0], addr[1], port_no)
     }
 
     pub fn listen(
         &mut self,
         is: &mut GateIStream<'_>,
-        iface: &mut DriverInterface<'_>,
+        ifaces: &mut Interfaces<'_>,
     ) -> Result<(), Error> {
         let sd: Sd = is.pop()?;
         let port: Port = is.pop()?;
+        let backlog: usize = is.pop()?;
 
         log!(
             LogFlags::NetSess,
-            "[{}] net::listen(sd={}, port={})",
+            "[{}] net::listen(sd={}, port={}, backlog={})",
             self.serv.id(),
             sd,
-            port
+            port,
+            backlog
         );
 
         let sock = self.get_socket(sd)?;
-        if !self.can_use_port(sock.borrow().socket_type(), port) {
+        let ty = sock.borrow().socket_type();
+        if !self.can_use_port(ty, port) {
             return Err(Error::new(Code::NoPerm));
         }
+        let port = AnyPort::Manual(ports::use_manual(ty, port, sock.borrow().reuse_addr())?);
 
-        sock.borrow_mut().listen(iface, crate::own_ip(), port)?;
+        // the accept queue needs buffers for every pending connection
+        let space = sock.borrow().listen_space(ifaces, backlog);
+        if self.settings.bufs < space {
+            return Err(Error::new(Code::NoSpace));
+        }
+
+        sock.borrow_mut()
+            .listen(ifaces, IpAddress::Unspecified, port, backlog)?;
+        self.settings.bufs -= space;
 
-        let addr = to_m3_addr(crate::own_ip());
-        reply_vmsg!(is, Code::Success, addr.0)
+        let addr = to_m3_addr(ifaces.ip(sock.borrow().netif())).to_raw();
+        reply_vmsg!(is, Code::Success, addr[0], addr[1])
+    }
+
+    pub fn accept(
+        &mut self,
+        is: &mut GateIStream<'_>,
+        ifaces: &mut Interfaces<'_>,
+    ) -> Result<(), Error> {
+        let sd: Sd = is.pop()?;
+        let new_sd: Sd = is.pop()?;
+
+        log!(
+            LogFlags::NetSess,
+            "[{}] net::accept(sd={}, new_sd={})",
+            self.serv.id(),
+            sd,
+            new_sd
+        );
+
+        if sd == new_sd {
+            return Err(Error::new(Code::InvArgs));
+        }
+
+        let listener = self.get_socket(sd)?;
+        let sock = self.get_socket(new_sd)?;
+        let ep = sock
+            .borrow_mut()
+            .accept(&mut listener.borrow_mut(), ifaces)?;
+
+        let addr = ep.addr.to_raw();
+        reply_vmsg!(is, Code::Success, addr[0], addr[1], ep.port)
     }
 
     pub fn connect(
         &mut self,
         is: &mut GateIStream<'_>,
-        iface: &mut DriverInterface<'_>,
+        ifaces: &mut Interfaces<'_>,
     ) -> Result<(), Error> {
         let sd: Sd = is.pop()?;
-        let remote_addr = IpAddr(is.pop::<u32>()?);
+        let remote_addr = IpAddr::from_raw([is.pop()?, is.pop()?]);
         let remote_port: Port = is.pop()?;
 
         let local_port = ports::alloc();
@@ -268,37 +330,118 @@ impl SocketSession {
         let sock = self.get_socket(sd)?;
         let port_no = *local_port;
         sock.borrow_mut()
-            .connect(remote_addr, remote_port, local_port, iface)?;
+            .connect(remote_addr, remote_port, local_port, ifaces)?;
 
-        let addr = to_m3_addr(crate::own_ip());
-        reply_vmsg!(is, Code::Success, addr.0, port_no)
+        let netif = sock.borrow().netif();
+        let addr = match remote_addr {
+            IpAddr::V4(_) => to_m3_addr(ifaces.ip(netif)),
+            IpAddr::V6(_) => to_m3_addr(ifaces.ip6(netif)),
+        }
+        .to_raw();
+        reply_vmsg!(is, Code::Success, addr[0], addr[1], port_no)
+    }
+
+    pub fn set_option(
+        &mut self,
+        is: &mut GateIStream<'_>,
+        ifaces: &mut Interfaces<'_>,
+    ) -> Result<(), Error> {
+        let sd: Sd = is.pop()?;
+        let opt: SocketOpt = is.pop()?;
+        let value: u64 = is.pop()?;
+
+        log!(
+            LogFlags::NetSess,
+            "[{}] net::set_option(sd={}, opt={:?}, value={})",
+            self.serv.id(),
+            sd,
+            opt,
+            value
+        );
+
+        let sock = self.get_socket(sd)?;
+        sock.borrow_mut().set_option(opt, value, ifaces)?;
+        is.reply_error(Code::Success)
+    }
+
+    pub fn get_option(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let sd: Sd = is.pop()?;
+        let opt: SocketOpt = is.pop()?;
+
+        log!(
+            LogFlags::NetSess,
+            "[{}] net::get_option(sd={}, opt={:?})",
+            self.serv.id(),
+            sd,
+            opt
+        );
+
+        let value = self.get_socket(sd)?.borrow().get_option(opt)?;
+        reply_vmsg!(is, Code::Success, value)
+    }
+
+    pub fn join_multicast(
+        &mut self,
+        is: &mut GateIStream<'_>,
+        ifaces: &mut Interfaces<'_>,
+    ) -> Result<(), Error> {
+        let sd: Sd = is.pop()?;
+        let addr = IpAddr::from_raw([is.pop()?, is.pop()?]);
+
+        log!(
+            LogFlags::NetSess,
+            "[{}] net::join_multicast(sd={}, addr={})",
+            self.serv.id(),
+            sd,
+            addr
+        );
+
+        let sock = self.get_socket(sd)?;
+        sock.borrow_mut().join_multicast(addr, ifaces)?;
+        is.reply_error(Code::Success)
+    }
+
+    pub fn leave_multicast(
+        &mut self,
+        is: &mut GateIStream<'_>,
+        ifaces: &mut Interfaces<'_>,
+    ) -> Result<(), Error> {
+        let sd: Sd = is.pop()?;
+        let addr = IpAddr::from_raw([is.pop()?, is.pop()?]);
+
+        log!(
+            LogFlags::NetSess,
+            "[{}] net::leave_multicast(sd={}, addr={})",
+            self.serv.id(),
+            sd,
+            addr
+        );
+
+        let sock = self.get_socket(sd)?;
+        sock.borrow_mut().leave_multicast(addr, ifaces)?;
+        is.reply_error(Code::Success)
     }
 
     pub fn abort(
         &mut self,
         is: &mut GateIStream<'_>,
-        iface: &mut DriverInterface<'_>,
+        ifaces: &mut Interfaces<'_>,
     ) -> Result<(), Error> {
         let sd: Sd = is.pop()?;
         let remove: bool = is.pop()?;
 
-        self.do_abort(sd, remove, iface)?;
+        self.do_abort(sd, remove, ifaces)?;
         is.reply_error(Code::Success)
     }
 
-    pub fn abort_all(&mut self, iface: &mut DriverInterface<'_>) -> Result<(), Error> {
+    pub fn abort_all(&mut self, ifaces: &mut Interfaces<'_>) -> Result<(), Error> {
         for sd in 0..self.sockets.len() {
-            self.do_abort(sd, true, iface).ok();
+            self.do_abort(sd, true, ifaces).ok();
         }
         Ok(())
     }
 
-    fn do_abort(
-        &mut self,
-        sd: Sd,
-        remove: bool,
-        iface: &mut DriverInterface<'_>,
-    ) -> Result<(), Error> {
+    fn do_abort(&mut self, sd: Sd, remove: bool, ifaces: &mut Interfaces<'_>) -> Result<(), Error> {
         log!(
             LogFlags::NetSess,
             "[{}] net::abort(sd={}, remove={})",
@@ -308,17 +451,21 @@ impl SocketSession {
         );
 
         let socket = self.get_socket(sd)?;
-        socket.borrow_mut().abort(iface);
+        socket.borrow_mut().abort(ifaces);
         if remove {
             self.remove_socket(sd);
         }
         Ok(())
     }
 
-    pub fn process_incoming(&mut self, iface: &mut DriverInterface<'_>) -> bool {
+    pub fn process_incoming(&mut self, ifaces: &mut Interfaces<'_>) -> bool {
         let sess = self.serv.id();
         let mut needs_recheck = false;
 
+        // the number of bytes we can send in this round
+        let avail = self.shaper.start_round();
+        let mut budget = avail;
+
         // iterate over all sockets and check for events
         'outer_loop: for idx in 0..self.sockets.len() {
             if let Some(socket) = self.sockets.get(idx).unwrap() {
@@ -327,14 +474,14 @@ impl SocketSession {
 
                 chan.fetch_replies();
 
-                if sock.process_queued_events(sess, iface) {
+                if sock.process_queued_events(sess, ifaces, &mut budget) {
                     needs_recheck = true;
                     continue 'outer_loop;
                 }
 
                 // receive everything in the channel
                 while let Some(event) = chan.fetch_event() {
-                    if sock.process_event(sess, iface, event) {
+                    if sock.process_event(sess, ifaces, event, &mut budget) {
                         needs_recheck = true;
                         continue 'outer_loop;
                     }
@@ -342,10 +489,10 @@ impl SocketSession {
             }
         }
 
-        needs_recheck
+        self.shaper.finish_round(avail - budget, needs_recheck)
     }
 
-    pub fn process_outgoing(&mut self, iface: &mut DriverInterface<'_>) -> bool {
+    pub fn process_outgoing(&mut self, ifaces: &mut Interfaces<'_>) -> bool {
         let mut needs_recheck = false;
         // iterate over all sockets and try to receive
         for socket in self.sockets.iter().flatten() {
@@ -363,7 +510,7 @@ impl SocketSession {
                     break;
                 }
 
-                if let Some(event) = socket.borrow_mut().fetch_event(iface) {
+                if let Some(event) = socket.borrow_mut().fetch_event(ifaces) {
                     log!(
                         LogFlags::NetData,
                         "[{}] socket {}: received event {:?}",
@@ -400,7 +547,7 @@ impl SocketSession {
                 }
 
                 let mut received = false;
-                socket.borrow_mut().receive(iface, |data, addr| {
+                socket.borrow_mut().receive(ifaces, |data, addr| {
                     let ep = to_m3_ep(addr);
                     let amount = cmp::min(MTU, data.len());
 
@@ -31,6 +31,10 @@ pub struct Settings {
     pub raw: bool,
     pub tcp_ports: Vec<(Port, Port)>,
     pub udp_ports: Vec<(Port, Port)>,
+    // the maximum data rate in bytes per second
+    pub rate: Option<usize>,
+    // the number of bytes that can be sent at once despite the rate limit
+    pub burst: Option<usize>,
 }
 
 impl Default for Settings {
@@ -41,6 +45,8 @@ impl Default for Settings {
             raw: false,
             tcp_ports: Vec::new(),
             udp_ports: Vec::new(),
+            rate: None,
+            burst: None,
         }
     }
 }
@@ -86,9 +92,63 @@ pub fn parse_arguments(args_str: &str) -> Result<Settings, Error> {
         else if let Some(portdesc) = arg.strip_prefix("udp=") {
             parse_ports(portdesc, &mut args.udp_ports)?;
         }
+        else if let Some(rate) = arg.strip_prefix("rate=") {
+            args.rate = Some(parse::size(rate)?);
+        }
+        else if let Some(burst) = arg.strip_prefix("burst=") {
+            args.burst = Some(parse::size(burst)?);
+        }
         else {
             return Err(Error::new(Code::InvArgs));
         }
     }
+    if args.rate == Some(0) {
+        return Err(Error::new(Code::InvArgs));
+    }
     Ok(args)
 }
+
+#[cfg(feature = "tests")]
+pub mod tests {
+    use super::*;
+
+    use m3::test::WvTester;
+    use m3::{wv_assert_eq, wv_assert_err, wv_assert_ok, wv_run_test};
+
+    pub fn run(t: &mut dyn WvTester) {
+        wv_run_test!(t, defaults);
+        wv_run_test!(t, rate_limits);
+        wv_run_test!(t, invalid);
+    }
+
+    fn defaults(t: &mut dyn WvTester) {
+        let s = wv_assert_ok!(parse_arguments("bufs=32K socks=2"));
+        wv_assert_eq!(t, s.bufs, 32 * 1024);
+        wv_assert_eq!(t, s.socks, 2);
+        wv_assert_eq!(t, s.rate, None);
+        wv_assert_eq!(t, s.burst, None);
+    }
+
+    fn rate_limits(t: &mut dyn WvTester) {
+        let s = wv_assert_ok!(parse_arguments("rate=1M burst=64K"));
+        wv_assert_eq!(t, s.rate, Some(1024 * 1024));
+        wv_assert_eq!(t, s.burst, Some(64 * 1024));
+
+        // the burst can be specified without rate, but has no effect then
+        let s = wv_assert_ok!(parse_arguments("burst=3000"));
+        wv_assert_eq!(t, s.rate, None);
+        wv_assert_eq!(t, s.burst, Some(3000));
+    }
+
+    fn invalid(t: &mut dyn WvTester) {
+        wv_assert_err!(t, parse_arguments("rate=0").map(|_| ()), Code::InvArgs);
+        wv_assert_err!(t, parse_arguments("rate=1X").map(|_| ()), Code::InvArgs);
+        wv_assert_err!(t, parse_arguments("rate=").map(|_| ()), Code::InvArgs);
+        wv_assert_err!(t, parse_arguments("burst=fast").map(|_| ()), Code::InvArgs);
+        wv_assert_err!(
+            t,
+            parse_arguments("ratelimit=1M").map(|_| ()),
+            Code::InvArgs
+        );
+    }
+}
@@ -0,0 +1,241 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! Traffic shaping for the data the clients send
+//!
+//! The data rate of every session can be limited by a token bucket. Additionally, the sessions
+//! share the NIC via deficit round robin: in every round of the server loop, each session with
+//! pending data can send up to a quantum of bytes, so that a greedy session cannot starve others.
+
+use m3::net::MTU;
+use m3::time::{TimeDuration, TimeInstant};
+use m3::timer::{self, TimerId};
+
+/// The number of bytes a session can send per round
+const QUANTUM: usize = 4 * MTU;
+
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+struct TokenBucket {
+    // the rate in bytes per second
+    rate: u64,
+    // the maximum number of tokens
+    burst: u64,
+    tokens: u64,
+    // the time up to which tokens have been added
+    last: TimeInstant,
+}
+
+impl TokenBucket {
+    fn new(rate: u64, burst: u64) -> Self {
+        Self {
+            rate,
+            burst,
+            tokens: burst,
+            last: TimeInstant::now(),
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = TimeInstant::now();
+        let elapsed = now.duration_since(self.last).as_nanos();
+        let new = elapsed * self.rate as u128 / NANOS_PER_SEC;
+        if self.tokens as u128 + new >= self.burst as u128 {
+            self.tokens = self.burst;
+            self.last = now;
+        }
+        else if new > 0 {
+            self.tokens += new as u64;
+            // only account the time for the added tokens to not lose fractions
+            self.last += TimeDuration::from_nanos((new * NANOS_PER_SEC / self.rate as u128) as u64);
+        }
+    }
+
+    fn consume(&mut self, bytes: usize) {
+        self.tokens = self.tokens.saturating_sub(bytes as u64);
+    }
+
+    /// Returns the time until `bytes` tokens are available
+    fn time_until(&self, bytes: usize) -> TimeDuration {
+        let missing = (bytes as u64).min(self.burst).saturating_sub(self.tokens) as u128;
+        let rate = self.rate as u128;
+        TimeDuration::from_nanos(((missing * NANOS_PER_SEC + rate - 1) / rate) as u64)
+    }
+}
+
+/// Shapes the outgoing traffic of a session
+pub struct Shaper {
+    bucket: Option<TokenBucket>,
+    // the number of bytes the session can still send in the current round
+    deficit: usize,
+    // the timer to wake up as soon as we can send again
+    wakeup: Option<TimerId>,
+}
+
+impl Shaper {
+    /// Creates a new shaper that limits the rate to `rate` bytes per second, if given. The session
+    /// can send up to `burst` bytes at once, but at least one packet.
+    pub fn new(rate: Option<usize>, burst: usize) -> Self {
+        Self {
+            bucket: rate.map(|r| TokenBucket::new(r as u64, burst.max(MTU) as u64)),
+            deficit: 0,
+            wakeup: None,
+        }
+    }
+
+    /// Starts a new round and returns the number of bytes the session can send in this round
+    pub fn start_round(&mut self) -> usize {
+        // sessions cannot save up their quantum for more than one round
+        self.deficit = (self.deficit + QUANTUM).min(2 * QUANTUM);
+        match &mut self.bucket {
+            Some(b) => {
+                b.refill();
+                self.deficit.min(b.tokens as usize)
+            },
+            None => self.deficit,
+        }
+    }
+
+    /// Finishes the current round, in which `used` bytes have been sent
+    ///
+    /// `pending` denotes whether the session still has data to send. Returns true if the session
+    /// should be processed again immediately. If the session has exceeded its rate, false is
+    /// returned and the server is woken up as soon as the session can send the next packet.
+    pub fn finish_round(&mut self, used: usize, pending: bool) -> bool {
+        self.deficit -= used;
+        if let Some(b) = &mut self.bucket {
+            b.consume(used);
+        }
+        if !pending {
+            self.deficit = 0;
+            return false;
+        }
+
+        match &self.bucket {
+            Some(b) => {
+                if (b.tokens as usize) < MTU {
+                    if !self.wakeup.is_some_and(timer::is_pending) {
+                        self.wakeup = Some(timer::add_wakeup(b.time_until(MTU)));
+                    }
+                    return false;
+                }
+                true
+            },
+            None => true,
+        }
+    }
+}
+
+impl Drop for Shaper {
+    fn drop(&mut self) {
+        if let Some(t) = self.wakeup.take() {
+            timer::cancel(t);
+        }
+    }
+}
+
+#[cfg(feature = "tests")]
+pub mod tests {
+    use super::*;
+
+    use m3::test::WvTester;
+    use m3::tiles::OwnActivity;
+    use m3::{wv_assert, wv_assert_eq, wv_assert_ok, wv_run_test};
+
+    pub fn run(t: &mut dyn WvTester) {
+        wv_run_test!(t, bucket);
+        wv_run_test!(t, unlimited);
+        wv_run_test!(t, limited);
+        wv_run_test!(t, burst);
+        wv_run_test!(t, refill);
+    }
+
+    fn bucket(t: &mut dyn WvTester) {
+        let mut b = TokenBucket::new(1000, 1500);
+        wv_assert_eq!(t, b.time_until(1500), TimeDuration::ZERO);
+
+        b.consume(2000);
+        wv_assert_eq!(t, b.tokens, 0);
+        wv_assert_eq!(t, b.time_until(500), TimeDuration::from_millis(500));
+        // we never need to wait for more than a full bucket
+        wv_assert_eq!(t, b.time_until(3000), TimeDuration::from_millis(1500));
+    }
+
+    fn unlimited(t: &mut dyn WvTester) {
+        let mut s = Shaper::new(None, 0);
+        wv_assert_eq!(t, s.start_round(), QUANTUM);
+        wv_assert!(t, s.finish_round(QUANTUM, true));
+        wv_assert_eq!(t, s.start_round(), QUANTUM);
+
+        // unused bytes are carried over to the next round, but only once
+        wv_assert!(t, s.finish_round(0, true));
+        wv_assert_eq!(t, s.start_round(), 2 * QUANTUM);
+        wv_assert!(t, s.finish_round(0, true));
+        wv_assert_eq!(t, s.start_round(), 2 * QUANTUM);
+
+        // sessions without pending data lose their deficit
+        wv_assert!(t, !s.finish_round(MTU, false));
+        wv_assert_eq!(t, s.start_round(), QUANTUM);
+        wv_assert!(t, s.wakeup.is_none());
+    }
+
+    fn limited(t: &mut dyn WvTester) {
+        // the burst is at least one packet
+        let mut s = Shaper::new(Some(1000), 0);
+        wv_assert_eq!(t, s.start_round(), MTU);
+
+        // the rate has been exceeded: the session is not processed again until the timer fired
+        wv_assert!(t, !s.finish_round(MTU, true));
+        let wakeup = s.wakeup.unwrap();
+        wv_assert!(t, timer::is_pending(wakeup));
+        // at 1000 bytes per second, a packet takes MTU milliseconds
+        let max = TimeDuration::from_millis(MTU as u64);
+        wv_assert!(t, timer::remaining(wakeup).unwrap() <= max);
+        wv_assert!(t, s.start_round() < MTU);
+
+        // the timer is not added twice
+        wv_assert!(t, !s.finish_round(0, true));
+        wv_assert_eq!(t, s.wakeup, Some(wakeup));
+
+        // and removed with the session
+        drop(s);
+        wv_assert!(t, !timer::is_pending(wakeup));
+    }
+
+    fn burst(t: &mut dyn WvTester) {
+        let mut s = Shaper::new(Some(1), 3 * MTU);
+        wv_assert_eq!(t, s.start_round(), 3 * MTU);
+
+        // as long as another packet can be sent, the session continues
+        wv_assert!(t, s.finish_round(MTU, true));
+        wv_assert_eq!(t, s.start_round(), 2 * MTU);
+        wv_assert!(t, s.finish_round(MTU, true));
+        wv_assert_eq!(t, s.start_round(), MTU);
+        wv_assert!(t, !s.finish_round(MTU, true));
+    }
+
+    fn refill(t: &mut dyn WvTester) {
+        // one packet per 10ms
+        let mut s = Shaper::new(Some(100 * MTU), MTU);
+        wv_assert_eq!(t, s.start_round(), MTU);
+        wv_assert!(t, !s.finish_round(MTU, true));
+
+        wv_assert_ok!(OwnActivity::sleep_for(TimeDuration::from_millis(20)));
+
+        // the bucket does not hold more than the burst
+        wv_assert_eq!(t, s.start_round(), MTU);
+        wv_assert!(t, s.finish_round(0, true));
+    }
+}
@@ -16,16 +16,19 @@
 
 use base::io::LogFlags;
 use m3::cap::Selector;
+use m3::col::{Vec, VecDeque};
 use m3::errors::{Code, Error};
 use m3::log;
-use m3::mem::size_of;
+use m3::mem::{self, size_of};
 use m3::net::{
     log_net, CloseReqMessage, ClosedMessage, ConnectedMessage, DataMessage, DataQueue, Endpoint,
-    IpAddr, NetEvent, NetEventChannel, NetEventType, NetLogEvent, Port, Sd, SocketArgs, SocketType,
+    IpAddr, NetEvent, NetEventChannel, NetEventType, NetLogEvent, Port, Sd, SocketArgs, SocketOpt,
+    SocketType,
 };
 use m3::rc::Rc;
 use m3::server::SessId;
-use m3::time::{TimeDuration, TimeInstant};
+use m3::time::TimeDuration;
+use m3::timer::{self, TimerId};
 use m3::vec;
 
 use smoltcp::iface::SocketHandle;
@@ -33,26 +36,34 @@ use smoltcp::socket::{
     RawSocket, RawSocketBuffer, TcpSocket, TcpSocketBuffer, TcpState, UdpSocket, UdpSocketBuffer,
 };
 use smoltcp::storage::PacketMetadata;
+use smoltcp::time::Duration;
 use smoltcp::wire::IpVersion;
-use smoltcp::wire::{IpAddress, IpEndpoint, Ipv4Address};
+use smoltcp::wire::{IpAddress, IpEndpoint, Ipv4Address, Ipv6Address};
 
 use crate::driver::DriverInterface;
+use crate::netif::Interfaces;
 use crate::ports::{AnyPort, EphemeralPort};
 
 const CONNECT_TIMEOUT: TimeDuration = TimeDuration::from_secs(6);
 
 pub fn to_m3_addr(addr: IpAddress) -> IpAddr {
-    if addr.as_bytes().len() != 4 {
-        IpAddr::unspecified()
+    let bytes = addr.as_bytes();
+    match bytes.len() {
+        4 => IpAddr::new(bytes[0], bytes[1], bytes[2], bytes[3]),
+        16 => IpAddr::from_octets(bytes.try_into().unwrap()),
+        _ => IpAddr::unspecified(),
     }
-    else {
-        let bytes = addr.as_bytes();
-        IpAddr::new(bytes[0], bytes[1], bytes[2], bytes[3])
+}
+
+/// Converts an M³ IpAddr into an IpAddress from smoltcp.
+pub fn to_smol_addr(addr: IpAddr) -> IpAddress {
+    match addr {
+        IpAddr::V4(a) => IpAddress::Ipv4(Ipv4Address::from_bytes(&a.to_be_bytes())),
+        IpAddr::V6(_) => IpAddress::Ipv6(Ipv6Address::from_bytes(&addr.octets())),
     }
 }
 
 /// Converts an IpEndpoint from smoltcp into an M³ (IpAddr, Port) tuple.
-/// Assumes that the IpEndpoint a is Ipv4 address, otherwise this will panic.
 pub fn to_m3_ep(addr: IpEndpoint) -> Endpoint {
     Endpoint::new(to_m3_addr(addr.addr), addr.port)
 }
@@ -71,17 +82,64 @@ pub enum State {
     Connecting,
     Connected,
     RemoteClosed,
+    Listening,
+}
+
+/// The options of a socket that have been set by the client
+#[derive(Copy, Clone)]
+struct Options {
+    hop_limit: Option<u8>,
+    reuse_addr: bool,
+    nagle: bool,
+    keep_alive: Option<Duration>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            hop_limit: None,
+            reuse_addr: false,
+            // Nagle's algorithm is disabled by default, because it delays sends, which at least for
+            // us reduces the achieved bandwidth in our benchmarks dramatically (factor 10). Maybe
+            // we don't transfer enough data?
+            nagle: false,
+            keep_alive: None,
+        }
+    }
+}
+
+impl Options {
+    fn apply_tcp(&self, tcp_socket: &mut TcpSocket<'_>) {
+        tcp_socket.set_hop_limit(self.hop_limit);
+        tcp_socket.set_nagle_enabled(self.nagle);
+        tcp_socket.set_keep_alive(self.keep_alive);
+    }
 }
 
 /// Socket abstraction that unifies the different socket types
 pub struct Socket {
     sd: Sd,
+    // the network interface that holds our smoltcp socket
+    netif: usize,
     socket: SocketHandle,
     ty: SocketType,
     state: State,
-    connect_start: Option<TimeInstant>,
-    _local_port: Option<EphemeralPort>,
+    // the timer for the connect timeout, if we are connecting to a remote endpoint
+    connect_timer: Option<TimerId>,
+    _local_port: Option<AnyPort>,
     buffer_space: usize,
+    opts: Options,
+
+    // the endpoint we listen on, if we have an accept queue
+    listen_ep: Option<IpEndpoint>,
+    // the sockets that wait for incoming connections on `listen_ep` with their interface
+    backlog: Vec<(usize, SocketHandle)>,
+    // the established connections that have not been accepted yet
+    accept_queue: VecDeque<(usize, SocketHandle)>,
+    // the multicast groups this socket has joined
+    groups: Vec<Ipv4Address>,
+    // the UDP sockets on the other interfaces, so that datagrams can be exchanged via all of them
+    dgram_socks: Vec<(usize, SocketHandle)>,
 
     // communication channel to client for incoming data/close-requests and outgoing events/data
     channel: Rc<NetEventChannel>,
@@ -90,18 +148,20 @@ pub struct Socket {
 }
 
 impl Socket {
-    pub fn required_space(ty: SocketType, args: &SocketArgs) -> usize {
-        args.rbuf_size
-            + args.sbuf_size
-            + match ty {
-                SocketType::Dgram => {
-                    (args.sbuf_slots + args.rbuf_slots) * size_of::<UdpSocketBuffer<'_>>()
-                },
-                SocketType::Raw => {
-                    (args.sbuf_slots + args.rbuf_slots) * size_of::<RawSocketBuffer<'_>>()
-                },
-                _ => 0,
-            }
+    pub fn required_space(ty: SocketType, args: &SocketArgs, netifs: usize) -> usize {
+        let bufs = args.rbuf_size + args.sbuf_size;
+        match ty {
+            // datagram sockets have their buffers on every interface
+            SocketType::Dgram => {
+                netifs
+                    * (bufs
+                        + (args.sbuf_slots + args.rbuf_slots) * size_of::<UdpSocketBuffer<'_>>())
+            },
+            SocketType::Raw => {
+                bufs + (args.sbuf_slots + args.rbuf_slots) * size_of::<RawSocketBuffer<'_>>()
+            },
+            _ => bufs,
+        }
     }
 
     pub fn new(
@@ -110,14 +170,10 @@ impl Socket {
         protocol: u8,
         args: &SocketArgs,
         caps: Selector,
-        iface: &mut DriverInterface<'_>,
+        ifaces: &mut Interfaces<'_>,
     ) -> Result<Self, Error> {
-        let socket = match ty {
-            SocketType::Stream => iface.add_socket(TcpSocket::new(
-                TcpSocketBuffer::new(vec![0u8; args.rbuf_size]),
-                TcpSocketBuffer::new(vec![0u8; args.sbuf_size]),
-            )),
-            SocketType::Dgram => iface.add_socket(UdpSocket::new(
+        let udp_socket = || {
+            UdpSocket::new(
                 UdpSocketBuffer::new(vec![PacketMetadata::EMPTY; args.rbuf_slots], vec![
                     0u8;
                     args.rbuf_size
@@ -126,7 +182,18 @@ impl Socket {
                     0u8;
                     args.sbuf_size
                 ]),
+            )
+        };
+
+        // until we know the remote endpoint, the socket lives on the default interface
+        let netif = ifaces.default_netif();
+        let iface = ifaces.dev(netif);
+        let socket = match ty {
+            SocketType::Stream => iface.add_socket(TcpSocket::new(
+                TcpSocketBuffer::new(vec![0u8; args.rbuf_size]),
+                TcpSocketBuffer::new(vec![0u8; args.sbuf_size]),
             )),
+            SocketType::Dgram => iface.add_socket(udp_socket()),
             SocketType::Raw => iface.add_socket(RawSocket::new(
                 IpVersion::Ipv4,
                 protocol.into(),
@@ -142,14 +209,30 @@ impl Socket {
             _ => return Err(Error::new(Code::InvArgs)),
         };
 
+        // datagrams are not bound to a connection and can therefore arrive on every interface
+        let mut dgram_socks = Vec::new();
+        if ty == SocketType::Dgram {
+            for other in (0..ifaces.count()).filter(|i| *i != netif) {
+                dgram_socks.push((other, ifaces.dev(other).add_socket(udp_socket())));
+            }
+        }
+
         Ok(Socket {
             sd,
+            netif,
             socket,
             ty,
             state: State::Closed,
-            connect_start: None,
+            connect_timer: None,
             _local_port: None,
-            buffer_space: Self::required_space(ty, args),
+            buffer_space: Self::required_space(ty, args, ifaces.count()),
+            opts: Options::default(),
+
+            listen_ep: None,
+            backlog: Vec::new(),
+            accept_queue: VecDeque::new(),
+            groups: Vec::new(),
+            dgram_socks,
 
             channel: NetEventChannel::new_server(caps)?,
             send_queue: DataQueue::default(),
@@ -172,22 +255,70 @@ impl Socket {
         self.buffer_space
     }
 
-    pub fn fetch_event(&mut self, iface: &mut DriverInterface<'_>) -> Option<SendNetEvent> {
+    /// Returns the index of the network interface this socket uses
+    pub fn netif(&self) -> usize {
+        self.netif
+    }
+
+    /// Moves our smoltcp socket to the network interface `netif`
+    ///
+    /// This is only done for TCP sockets as long as they are neither listening nor connected.
+    fn move_to(&mut self, ifaces: &mut Interfaces<'_>, netif: usize) {
+        if netif != self.netif {
+            let socket = ifaces.dev(self.netif).remove_socket(self.socket);
+            self.socket = ifaces.dev(netif).add_any_socket(socket);
+            self.netif = netif;
+        }
+    }
+
+    /// Returns the buffer space required to listen with an accept queue of `backlog` connections
+    ///
+    /// As we accept connections on all interfaces, every interface gets an accept queue.
+    pub fn listen_space(&self, ifaces: &mut Interfaces<'_>, backlog: usize) -> usize {
+        match self.ty {
+            SocketType::Stream => {
+                let space = Self::tcp_space(ifaces.dev(self.netif), self.socket);
+                ifaces.count() * backlog * space
+            },
+            _ => 0,
+        }
+    }
+
+    fn tcp_space(iface: &mut DriverInterface<'_>, handle: SocketHandle) -> usize {
+        let tcp_socket = iface.get_socket::<TcpSocket<'_>>(handle);
+        tcp_socket.recv_capacity() + tcp_socket.send_capacity()
+    }
+
+    pub fn fetch_event(&mut self, ifaces: &mut Interfaces<'_>) -> Option<SendNetEvent> {
         match (self.ty, self.state) {
             (SocketType::Stream, State::Connecting) => {
-                let tcp_socket = iface.get_socket::<TcpSocket<'_>>(self.socket);
+                let tcp_socket = ifaces
+                    .dev(self.netif)
+                    .get_socket::<TcpSocket<'_>>(self.socket);
                 if tcp_socket.state() == TcpState::Established {
-                    // disable Nagle's algorithm, because it delays sends, which at least for us
-                    // reduces the achieved bandwidth in our benchmarks dramatically (factor 10).
-                    // Maybe we don't transfer enough data?
-                    tcp_socket.set_nagle_enabled(false);
-                    if self.connect_start.take().is_some() {
-                        crate::remove_timeout(self.socket);
+                    // smoltcp resets some of the options on connect and listen
+                    self.opts.apply_tcp(tcp_socket);
+                    if let Some(t) = self.connect_timer.take() {
+                        timer::cancel(t);
+                    }
+                    else {
+                        // we are no longer listening, so that others can listen on the port
+                        self._local_port = None;
                     }
                     self.state = State::Connected;
                     let ep = to_m3_ep(tcp_socket.remote_endpoint());
                     Some(SendNetEvent::Connected(ConnectedMessage::new(ep)))
                 }
+                // has the remote side refused our connection attempt?
+                else if tcp_socket.state() == TcpState::Closed && self.connect_timer.is_some() {
+                    timer::cancel(self.connect_timer.take().unwrap());
+                    self._local_port = None;
+                    self.state = State::Closed;
+                    self.send_queue.clear();
+                    Some(SendNetEvent::Closed(ClosedMessage::new(
+                        Code::ConnectionFailed,
+                    )))
+                }
                 // are we already trying to close the socket again?
                 else if tcp_socket.state() != TcpState::Listen
                     && tcp_socket.state() != TcpState::SynSent
@@ -196,15 +327,14 @@ impl Socket {
                     self.state = State::RemoteClosed;
                     Some(SendNetEvent::CloseReq(CloseReqMessage::default()))
                 }
-                else if let Some(start) = self.connect_start {
-                    if TimeInstant::now() >= start + CONNECT_TIMEOUT {
+                else if let Some(t) = self.connect_timer {
+                    if !timer::is_pending(t) {
                         tcp_socket.abort();
-                        self.connect_start = None;
-                        crate::remove_timeout(self.socket);
+                        timer::cancel(self.connect_timer.take().unwrap());
                         self._local_port = None;
                         self.state = State::Closed;
                         self.send_queue.clear();
-                        Some(SendNetEvent::Closed(ClosedMessage::default()))
+                        Some(SendNetEvent::Closed(ClosedMessage::new(Code::Timeout)))
                     }
                     else {
                         None
@@ -215,8 +345,40 @@ impl Socket {
                 }
             },
 
+            (SocketType::Stream, State::Listening) => {
+                // the backlog and the accept queue are only empty after a close request
+                if self.backlog.is_empty() && self.accept_queue.is_empty() {
+                    self.listen_ep = None;
+                    self._local_port = None;
+                    self.state = State::Closed;
+                    return Some(SendNetEvent::Closed(ClosedMessage::default()));
+                }
+
+                let endpoint = self.listen_ep.unwrap();
+                for i in 0..self.backlog.len() {
+                    let (netif, handle) = self.backlog[i];
+                    let tcp_socket = ifaces.dev(netif).get_socket::<TcpSocket<'_>>(handle);
+                    match tcp_socket.state() {
+                        TcpState::Established => {
+                            // the connections inherit the options of the listening socket
+                            self.opts.apply_tcp(tcp_socket);
+                            let ep = to_m3_ep(tcp_socket.remote_endpoint());
+                            let entry = self.backlog.remove(i);
+                            self.accept_queue.push_back(entry);
+                            return Some(SendNetEvent::Connected(ConnectedMessage::new(ep)));
+                        },
+                        // the connection attempt failed; wait for the next one
+                        TcpState::Closed => tcp_socket.listen(endpoint).unwrap(),
+                        _ => {},
+                    }
+                }
+                None
+            },
+
             (SocketType::Stream, State::Connected | State::RemoteClosed) => {
-                let tcp_socket = iface.get_socket::<TcpSocket<'_>>(self.socket);
+                let tcp_socket = ifaces
+                    .dev(self.netif)
+                    .get_socket::<TcpSocket<'_>>(self.socket);
                 if !tcp_socket.is_open() {
                     self._local_port = None;
                     self.state = State::Closed;
@@ -244,7 +406,7 @@ impl Socket {
         &mut self,
         addr: IpAddress,
         port: AnyPort,
-        iface: &mut DriverInterface<'_>,
+        ifaces: &mut Interfaces<'_>,
     ) -> Result<(), Error> {
         if self.ty != SocketType::Dgram {
             return Err(Error::new(Code::InvArgs));
@@ -254,28 +416,54 @@ impl Socket {
         }
 
         let endpoint = IpEndpoint::new(addr, port.number());
-        let udp_socket = iface.get_socket::<UdpSocket<'_>>(self.socket);
-        match udp_socket.bind(endpoint) {
-            Ok(_) => {
-                if let AnyPort::Ephemeral(e) = port {
-                    self._local_port = Some(e);
-                }
-                self.state = State::Bound;
-                Ok(())
-            },
-            Err(e) => {
+        for (netif, handle) in self.udp_sockets() {
+            let udp_socket = ifaces.dev(netif).get_socket::<UdpSocket<'_>>(handle);
+            if let Err(e) = udp_socket.bind(endpoint) {
                 log!(LogFlags::Error, "bind failed: {}", e);
                 // bind can only fail if the port is zero
-                Err(Error::new(Code::InvArgs))
-            },
+                return Err(Error::new(Code::InvArgs));
+            }
+        }
+
+        self._local_port = Some(port);
+        self.state = State::Bound;
+        Ok(())
+    }
+
+    /// Returns all UDP sockets with their interface, starting with the one on our own interface
+    fn udp_sockets(&self) -> impl Iterator<Item = (usize, SocketHandle)> + '_ {
+        Some((self.netif, self.socket))
+            .into_iter()
+            .chain(self.dgram_socks.iter().copied())
+    }
+
+    /// Returns the interface and the smoltcp socket to send packets to `dest` with
+    fn send_socket(
+        netif: usize,
+        socket: SocketHandle,
+        dgram_socks: &[(usize, SocketHandle)],
+        dest: IpAddr,
+        ifaces: &Interfaces<'_>,
+    ) -> (usize, SocketHandle) {
+        if dgram_socks.is_empty() {
+            return (netif, socket);
         }
+
+        // datagrams are sent via the interface the destination is reachable by
+        let route = ifaces.route(to_smol_addr(dest));
+        dgram_socks
+            .iter()
+            .find(|(n, _)| *n == route)
+            .copied()
+            .unwrap_or((netif, socket))
     }
 
     pub fn listen(
         &mut self,
-        iface: &mut DriverInterface<'_>,
+        ifaces: &mut Interfaces<'_>,
         addr: IpAddress,
-        port: Port,
+        port: AnyPort,
+        backlog: usize,
     ) -> Result<(), Error> {
         if self.ty != SocketType::Stream {
             return Err(Error::new(Code::InvArgs));
@@ -284,11 +472,22 @@ impl Socket {
             return Err(Error::new(Code::InvState));
         }
 
-        let endpoint = IpEndpoint::new(addr, port);
-        let tcp_socket = iface.get_socket::<TcpSocket<'_>>(self.socket);
+        let endpoint = IpEndpoint::new(addr, port.number());
+        if backlog > 0 {
+            self.listen_with_backlog(ifaces, endpoint, backlog)?;
+            self._local_port = Some(port);
+            return Ok(());
+        }
+
+        let tcp_socket = ifaces
+            .dev(self.netif)
+            .get_socket::<TcpSocket<'_>>(self.socket);
         match tcp_socket.listen(endpoint) {
             Ok(_) => {
-                self.connect_start = None;
+                if let Some(t) = self.connect_timer.take() {
+                    timer::cancel(t);
+                }
+                self._local_port = Some(port);
                 self.state = State::Connecting;
                 Ok(())
             },
@@ -300,12 +499,98 @@ impl Socket {
         }
     }
 
+    fn listen_with_backlog(
+        &mut self,
+        ifaces: &mut Interfaces<'_>,
+        endpoint: IpEndpoint,
+        backlog: usize,
+    ) -> Result<(), Error> {
+        // listen can only fail if the port is zero
+        if endpoint.port == 0 {
+            return Err(Error::new(Code::InvArgs));
+        }
+
+        // our own socket stays unused; the connections are established on separate sockets with
+        // the same buffer sizes, so that every pending connection can be handed out on accept
+        let tcp_socket = ifaces
+            .dev(self.netif)
+            .get_socket::<TcpSocket<'_>>(self.socket);
+        let rbuf_size = tcp_socket.recv_capacity();
+        let sbuf_size = tcp_socket.send_capacity();
+        // accept connections on all interfaces
+        for netif in 0..ifaces.count() {
+            let iface = ifaces.dev(netif);
+            for _ in 0..backlog {
+                let handle = iface.add_socket(TcpSocket::new(
+                    TcpSocketBuffer::new(vec![0u8; rbuf_size]),
+                    TcpSocketBuffer::new(vec![0u8; sbuf_size]),
+                ));
+                iface
+                    .get_socket::<TcpSocket<'_>>(handle)
+                    .listen(endpoint)
+                    .unwrap();
+                self.backlog.push((netif, handle));
+            }
+        }
+
+        self.buffer_space += ifaces.count() * backlog * (rbuf_size + sbuf_size);
+        self.listen_ep = Some(endpoint);
+        self.state = State::Listening;
+        Ok(())
+    }
+
+    /// Takes the next connection from the accept queue of `listener` and makes it the connection
+    /// of this socket. Returns the remote endpoint of the connection.
+    pub fn accept(
+        &mut self,
+        listener: &mut Socket,
+        ifaces: &mut Interfaces<'_>,
+    ) -> Result<Endpoint, Error> {
+        if self.ty != SocketType::Stream || listener.ty != SocketType::Stream {
+            return Err(Error::new(Code::InvArgs));
+        }
+        if self.state != State::Closed || listener.state != State::Listening {
+            return Err(Error::new(Code::InvState));
+        }
+        if listener.accept_queue.is_empty() {
+            return Err(Error::new(Code::WouldBlock));
+        }
+
+        // our socket replaces the accepted one in the backlog of the listener. thus, it needs to
+        // be on the same interface as the accepted connection.
+        let (netif, _) = *listener.accept_queue.front().unwrap();
+        self.move_to(ifaces, netif);
+
+        let endpoint = listener.listen_ep.unwrap();
+        let iface = ifaces.dev(netif);
+        let tcp_socket = iface.get_socket::<TcpSocket<'_>>(self.socket);
+        tcp_socket.abort();
+        if let Err(e) = tcp_socket.listen(endpoint) {
+            log!(LogFlags::Error, "listen failed: {}", e);
+            return Err(Error::new(Code::InvState));
+        }
+
+        let (_, handle) = listener.accept_queue.pop_front().unwrap();
+        let unused = mem::replace(&mut self.socket, handle);
+        listener.backlog.push((netif, unused));
+
+        // the buffers move with the sockets
+        let unused_space = Self::tcp_space(iface, unused);
+        let accepted_space = Self::tcp_space(iface, handle);
+        self.buffer_space = self.buffer_space - unused_space + accepted_space;
+        listener.buffer_space = listener.buffer_space - accepted_space + unused_space;
+
+        self.state = State::Connected;
+        let tcp_socket = iface.get_socket::<TcpSocket<'_>>(self.socket);
+        Ok(to_m3_ep(tcp_socket.remote_endpoint()))
+    }
+
     pub fn connect(
         &mut self,
         remote_addr: IpAddr,
         remote_port: Port,
         local_port: EphemeralPort,
-        iface: &mut DriverInterface<'_>,
+        ifaces: &mut Interfaces<'_>,
     ) -> Result<(), Error> {
         if self.ty != SocketType::Stream {
             return Err(Error::new(Code::InvArgs));
@@ -314,19 +599,22 @@ impl Socket {
             return Err(Error::new(Code::InvState));
         }
 
-        let remote_endpoint = IpEndpoint::new(
-            IpAddress::Ipv4(Ipv4Address::from_bytes(&remote_addr.0.to_be_bytes())),
-            remote_port,
-        );
+        let remote_endpoint = IpEndpoint::new(to_smol_addr(remote_addr), remote_port);
         let local_endpoint = IpEndpoint::from(*local_port);
 
-        let (tcp_socket, cx) = iface.get_socket_and_context::<TcpSocket<'_>>(self.socket);
+        // use the interface the remote endpoint is reachable by
+        let netif = ifaces.route(remote_endpoint.addr);
+        self.move_to(ifaces, netif);
+
+        let (tcp_socket, cx) = ifaces
+            .dev(netif)
+            .get_socket_and_context::<TcpSocket<'_>>(self.socket);
         match tcp_socket.connect(cx, remote_endpoint, local_endpoint) {
             Ok(_) => {
-                self.connect_start = Some(TimeInstant::now());
-                crate::add_timeout(self.socket, TimeInstant::now() + CONNECT_TIMEOUT);
+                // make sure that we wake up in time to report the timeout
+                self.connect_timer = Some(timer::add_wakeup(CONNECT_TIMEOUT));
                 self.state = State::Connecting;
-                self._local_port = Some(local_port);
+                self._local_port = Some(AnyPort::Ephemeral(local_port));
                 Ok(())
             },
             Err(e) => {
@@ -337,33 +625,178 @@ impl Socket {
         }
     }
 
-    pub fn close(&mut self, iface: &mut DriverInterface<'_>) -> Result<(), Error> {
+    pub fn join_multicast(
+        &mut self,
+        addr: IpAddr,
+        ifaces: &mut Interfaces<'_>,
+    ) -> Result<(), Error> {
+        if self.ty != SocketType::Dgram {
+            return Err(Error::new(Code::InvArgs));
+        }
+
+        let group = match to_smol_addr(addr) {
+            IpAddress::Ipv4(a) => a,
+            // we only support IGMP, not MLD
+            _ => return Err(Error::new(Code::NotSup)),
+        };
+        if self.groups.contains(&group) {
+            return Err(Error::new(Code::Exists));
+        }
+
+        crate::multicast::join(ifaces.get(self.netif), group)?;
+        self.groups.push(group);
+        Ok(())
+    }
+
+    pub fn leave_multicast(
+        &mut self,
+        addr: IpAddr,
+        ifaces: &mut Interfaces<'_>,
+    ) -> Result<(), Error> {
+        let pos = self
+            .groups
+            .iter()
+            .position(|g| IpAddress::Ipv4(*g) == to_smol_addr(addr))
+            .ok_or_else(|| Error::new(Code::NotFound))?;
+
+        let group = self.groups.remove(pos);
+        crate::multicast::leave(ifaces.get(self.netif), group)
+    }
+
+    pub fn reuse_addr(&self) -> bool {
+        self.opts.reuse_addr
+    }
+
+    pub fn set_option(
+        &mut self,
+        opt: SocketOpt,
+        value: u64,
+        ifaces: &mut Interfaces<'_>,
+    ) -> Result<(), Error> {
+        match (self.ty, opt) {
+            (SocketType::Stream | SocketType::Dgram, SocketOpt::Ttl) => {
+                self.opts.hop_limit = match value {
+                    0 => None,
+                    ttl => Some(u8::try_from(ttl).map_err(|_| Error::new(Code::InvArgs))?),
+                };
+            },
+            (SocketType::Stream | SocketType::Dgram, SocketOpt::ReuseAddr) => {
+                self.opts.reuse_addr = Self::to_bool(value)?;
+            },
+            (SocketType::Stream, SocketOpt::NoDelay) => {
+                self.opts.nagle = !Self::to_bool(value)?;
+            },
+            (SocketType::Stream, SocketOpt::KeepAlive) => {
+                self.opts.keep_alive = match value {
+                    0 => None,
+                    ms => Some(Duration::from_millis(ms)),
+                };
+            },
+            _ => return Err(Error::new(Code::NotSup)),
+        }
+
+        match self.ty {
+            SocketType::Stream => {
+                self.opts.apply_tcp(
+                    ifaces
+                        .dev(self.netif)
+                        .get_socket::<TcpSocket<'_>>(self.socket),
+                );
+                for (netif, handle) in self.backlog.iter().chain(self.accept_queue.iter()) {
+                    self.opts
+                        .apply_tcp(ifaces.dev(*netif).get_socket::<TcpSocket<'_>>(*handle));
+                }
+            },
+            SocketType::Dgram => {
+                for (netif, handle) in self.udp_sockets() {
+                    let udp_socket = ifaces.dev(netif).get_socket::<UdpSocket<'_>>(handle);
+                    udp_socket.set_hop_limit(self.opts.hop_limit);
+                }
+            },
+            _ => {},
+        }
+        Ok(())
+    }
+
+    pub fn get_option(&self, opt: SocketOpt) -> Result<u64, Error> {
+        match (self.ty, opt) {
+            (SocketType::Stream | SocketType::Dgram, SocketOpt::Ttl) => {
+                Ok(self.opts.hop_limit.unwrap_or(0) as u64)
+            },
+            (SocketType::Stream | SocketType::Dgram, SocketOpt::ReuseAddr) => {
+                Ok(self.opts.reuse_addr as u64)
+            },
+            (SocketType::Stream, SocketOpt::NoDelay) => Ok(!self.opts.nagle as u64),
+            (SocketType::Stream, SocketOpt::KeepAlive) => {
+                Ok(self.opts.keep_alive.map(|d| d.total_millis()).unwrap_or(0))
+            },
+            _ => Err(Error::new(Code::NotSup)),
+        }
+    }
+
+    fn to_bool(value: u64) -> Result<bool, Error> {
+        match value {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(Error::new(Code::InvArgs)),
+        }
+    }
+
+    pub fn close(&mut self, ifaces: &mut Interfaces<'_>) -> Result<(), Error> {
         if self.ty != SocketType::Stream {
             return Err(Error::new(Code::InvArgs));
         }
 
-        let tcp_socket = iface.get_socket::<TcpSocket<'_>>(self.socket);
+        if self.state == State::Listening {
+            // stop listening and drop all connections that have not been accepted yet. the closed
+            // event will be sent afterwards in fetch_event.
+            self.abort_backlog(ifaces);
+            return Ok(());
+        }
+
+        let tcp_socket = ifaces
+            .dev(self.netif)
+            .get_socket::<TcpSocket<'_>>(self.socket);
         tcp_socket.close();
         Ok(())
     }
 
-    pub fn abort(&mut self, iface: &mut DriverInterface<'_>) {
+    fn abort_backlog(&mut self, ifaces: &mut Interfaces<'_>) {
+        for (netif, handle) in self.backlog.drain(..).chain(self.accept_queue.drain(..)) {
+            ifaces
+                .dev(netif)
+                .get_socket::<TcpSocket<'_>>(handle)
+                .abort();
+        }
+    }
+
+    pub fn abort(&mut self, ifaces: &mut Interfaces<'_>) {
         if self.ty == SocketType::Stream {
-            let tcp_socket = iface.get_socket::<TcpSocket<'_>>(self.socket);
+            let tcp_socket = ifaces
+                .dev(self.netif)
+                .get_socket::<TcpSocket<'_>>(self.socket);
             tcp_socket.abort();
+            self.abort_backlog(ifaces);
+        }
+
+        for group in self.groups.drain(..) {
+            crate::multicast::leave(ifaces.get(self.netif), group).ok();
         }
 
+        self.listen_ep = None;
         self._local_port = None;
         self.state = State::Closed;
     }
 
-    pub fn receive<F>(&mut self, iface: &mut DriverInterface<'_>, func: F)
+    pub fn receive<F>(&mut self, ifaces: &mut Interfaces<'_>, func: F)
     where
         F: FnOnce(&[u8], IpEndpoint) -> usize,
     {
         match self.ty {
             SocketType::Stream => {
-                let tcp_socket = iface.get_socket::<TcpSocket<'_>>(self.socket);
+                let tcp_socket = ifaces
+                    .dev(self.netif)
+                    .get_socket::<TcpSocket<'_>>(self.socket);
                 if self.state == State::Connected || self.state == State::RemoteClosed {
                     let addr = tcp_socket.remote_endpoint();
                     // don't even log errors here, since they occur often and are uninteresting
@@ -381,14 +814,20 @@ impl Socket {
             },
 
             SocketType::Dgram => {
-                let udp_socket = iface.get_socket::<UdpSocket<'_>>(self.socket);
-                if let Ok((data, remote_endpoint)) = udp_socket.recv() {
-                    func(data, remote_endpoint);
+                // take the next datagram from the first interface that has one
+                for (netif, handle) in self.udp_sockets() {
+                    let udp_socket = ifaces.dev(netif).get_socket::<UdpSocket<'_>>(handle);
+                    if let Ok((data, remote_endpoint)) = udp_socket.recv() {
+                        func(data, remote_endpoint);
+                        break;
+                    }
                 }
             },
 
             SocketType::Raw => {
-                let raw_socket = iface.get_socket::<RawSocket<'_>>(self.socket);
+                let raw_socket = ifaces
+                    .dev(self.netif)
+                    .get_socket::<RawSocket<'_>>(self.socket);
                 if let Ok(data) = raw_socket.recv() {
                     func(data, IpEndpoint::UNSPECIFIED);
                 }
@@ -398,6 +837,7 @@ impl Socket {
         }
     }
 
+    /// Sends at most `budget` bytes of `data` and reduces `budget` accordingly
     fn send(
         ty: SocketType,
         socket: SocketHandle,
@@ -405,25 +845,25 @@ impl Socket {
         dest_addr: IpAddr,
         dest_port: Port,
         iface: &mut DriverInterface<'_>,
+        budget: &mut usize,
     ) -> usize {
-        match ty {
+        let amount = match ty {
             SocketType::Stream => {
                 let tcp_socket = iface.get_socket::<TcpSocket<'_>>(socket);
-                if tcp_socket.can_send() {
-                    tcp_socket.send_slice(data).unwrap()
+                if tcp_socket.can_send() && *budget > 0 {
+                    let len = data.len().min(*budget);
+                    tcp_socket.send_slice(&data[0..len]).unwrap()
                 }
                 else {
                     0
                 }
             },
 
+            // datagrams can only be sent as a whole
             SocketType::Dgram => {
                 let udp_socket = iface.get_socket::<UdpSocket<'_>>(socket);
-                if udp_socket.can_send() {
-                    let rend = IpEndpoint::new(
-                        IpAddress::Ipv4(Ipv4Address::from_bytes(&dest_addr.0.to_be_bytes())),
-                        dest_port,
-                    );
+                if udp_socket.can_send() && data.len() <= *budget {
+                    let rend = IpEndpoint::new(to_smol_addr(dest_addr), dest_port);
 
                     udp_socket.send_slice(data, rend).unwrap();
                     data.len()
@@ -435,7 +875,7 @@ impl Socket {
 
             SocketType::Raw => {
                 let raw_socket = iface.get_socket::<RawSocket<'_>>(socket);
-                if raw_socket.can_send() {
+                if raw_socket.can_send() && data.len() <= *budget {
                     raw_socket.send_slice(data).unwrap();
                     data.len()
                 }
@@ -445,18 +885,30 @@ impl Socket {
             },
 
             SocketType::Undefined => panic!("cannot send to undefined socket"),
-        }
+        };
+        *budget -= amount;
+        amount
     }
 
-    pub fn process_queued_events(&mut self, sess: SessId, iface: &mut DriverInterface<'_>) -> bool {
+    pub fn process_queued_events(
+        &mut self,
+        sess: SessId,
+        ifaces: &mut Interfaces<'_>,
+        budget: &mut usize,
+    ) -> bool {
+        let netif = self.netif;
         let socket = self.socket;
+        let dgram_socks = &self.dgram_socks;
         let ty = self.ty;
         let sd = self.sd;
         #[allow(clippy::blocks_in_if_conditions)]
         while self
             .send_queue
             .next_data(usize::MAX, &mut |data, ep: Endpoint| {
-                let amount = Self::send(ty, socket, data, ep.addr, ep.port, iface);
+                let (netif, socket) =
+                    Self::send_socket(netif, socket, dgram_socks, ep.addr, ifaces);
+                let iface = ifaces.dev(netif);
+                let amount = Self::send(ty, socket, data, ep.addr, ep.port, iface, budget);
                 if amount > 0 {
                     log_net(NetLogEvent::SubmitData, sd, amount);
                     log!(
@@ -478,22 +930,26 @@ impl Socket {
     pub fn process_event(
         &mut self,
         sess: SessId,
-        iface: &mut DriverInterface<'_>,
+        ifaces: &mut Interfaces<'_>,
         event: NetEvent,
+        budget: &mut usize,
     ) -> bool {
         match event.msg_type() {
             NetEventType::Data => {
                 let data = event.msg::<DataMessage>();
-                let ip = IpAddr(data.addr as u32);
+                let ip = IpAddr::from_raw(data.addr);
                 let port = data.port as Port;
 
+                let (netif, socket) =
+                    Self::send_socket(self.netif, self.socket, &self.dgram_socks, ip, ifaces);
                 let res = Self::send(
                     self.ty,
-                    self.socket,
+                    socket,
                     &data.data[0..data.size as usize],
                     ip,
                     port,
-                    iface,
+                    ifaces.dev(netif),
+                    budget,
                 );
                 if res > 0 {
                     log_net(NetLogEvent::SubmitData, self.sd, res);
@@ -533,7 +989,7 @@ impl Socket {
                 );
 
                 // ignore error
-                self.close(iface).ok();
+                self.close(ifaces).ok();
             },
 
             m => log!(LogFlags::Error, "Unexpected message from client: {:?}", m),
@@ -541,3 +997,11 @@ impl Socket {
         false
     }
 }
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        if let Some(t) = self.connect_timer.take() {
+            timer::cancel(t);
+        }
+    }
+}
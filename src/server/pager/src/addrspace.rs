@@ -22,23 +22,40 @@ use m3::errors::{Code, Error};
 use m3::io::LogFlags;
 use m3::kif::{CapRngDesc, CapType, PageFlags, Perm};
 use m3::log;
-use m3::mem::{GlobOff, VirtAddr};
+use m3::mem::{GlobOff, VirtAddr, VirtAddrRaw};
+use m3::rc::Rc;
 use m3::reply_vmsg;
-use m3::server::{CapExchange, ClientManager, RequestSession, ServerSession, SessId};
+use m3::server::{
+    CapExchange, ClientManager, RequestSession, ServerSession, SessId, SessionContainer,
+};
 use m3::util::math;
 
-use resmng::childs;
+use resmng::childs::{self, ChildMem};
 
 use crate::dataspace::DataSpace;
 
 const MAX_VIRT_ADDR: VirtAddr = VirtAddr::new(cfg::MEM_CAP_END.as_raw() - 1);
 
+// the guard area below the stack (see `TileDesc::stack_guard`), which has to stay unmapped so that
+// TileMux can detect stack overflows
+const STACK_GUARD_END: VirtAddr =
+    VirtAddr::new(cfg::RBUF_STD_ADDR.as_raw() - cfg::STACK_SIZE as VirtAddrRaw);
+const STACK_GUARD_START: VirtAddr =
+    VirtAddr::new(STACK_GUARD_END.as_raw() - cfg::STACK_GUARD_SIZE as VirtAddrRaw);
+
+/// The memory that the dataspaces of an address space are accounted to
+#[derive(Clone)]
+struct MemCommit {
+    mem: Rc<ChildMem>,
+    overcommit: bool,
+}
+
 pub struct AddrSpace {
-    #[allow(unused)]
     parent: Option<SessId>,
     serv: ServerSession,
     child: Option<childs::Id>,
     owner: Option<Selector>,
+    commit: Option<MemCommit>,
     ds: Vec<DataSpace>,
 }
 
@@ -66,6 +83,7 @@ impl AddrSpace {
             serv,
             child,
             owner: None,
+            commit: None,
             ds: Vec::new(),
         }
     }
@@ -78,7 +96,6 @@ impl AddrSpace {
         self.child
     }
 
-    #[allow(unused)]
     pub fn parent(&self) -> Option<SessId> {
         self.parent
     }
@@ -93,9 +110,12 @@ impl AddrSpace {
         sid: SessId,
         xchg: &mut CapExchange<'_>,
     ) -> Result<(), Error> {
-        let child_id = cli.get_mut(sid).unwrap().child_id();
+        let (child_id, commit) = {
+            let aspace = cli.get_mut(sid).unwrap();
+            (aspace.child_id(), aspace.commit.clone())
+        };
 
-        let (sel, _) = cli.add(crt, |_cli, serv| {
+        let (sel, nsid) = cli.add(crt, |_cli, serv| {
             log!(
                 LogFlags::PgReqs,
                 "[{}] pager::add_child(nsid={})",
@@ -104,6 +124,8 @@ impl AddrSpace {
             );
             Ok(AddrSpace::new(serv, Some(sid), child_id))
         })?;
+        // the new address space belongs to the same child and is therefore accounted to it as well
+        cli.get_mut(nsid).unwrap().commit = commit;
 
         xchg.out_caps(CapRngDesc::new(CapType::Object, sel, 1));
 
@@ -149,8 +171,35 @@ impl AddrSpace {
         }
     }
 
-    #[allow(unused)]
-    pub fn clone(&mut self, is: &mut GateIStream<'_>, parent: &mut AddrSpace) -> Result<(), Error> {
+    /// Accounts all mappings of this address space to `mem`.
+    ///
+    /// If `overcommit` is false, mappings are refused if they don't fit into the quota of `mem`.
+    pub fn set_mem_commit(&mut self, mem: Rc<ChildMem>, overcommit: bool) {
+        self.commit = Some(MemCommit { mem, overcommit });
+    }
+
+    pub fn clone(
+        sessions: &mut SessionContainer<Self>,
+        sid: SessId,
+        is: &mut GateIStream<'_>,
+    ) -> Result<(), Error> {
+        // only sessions that have been created via add_child have a parent to clone from
+        let parent = sessions
+            .get(sid)
+            .unwrap()
+            .parent()
+            .ok_or_else(|| Error::new(Code::InvArgs))?;
+        match sessions.get_two_mut(sid, parent) {
+            (Some(child), Some(parent)) => child.do_clone(is, parent),
+            _ => Err(Error::new(Code::InvArgs)),
+        }
+    }
+
+    fn do_clone(&mut self, is: &mut GateIStream<'_>, parent: &mut AddrSpace) -> Result<(), Error> {
+        if self.owner.is_none() {
+            return Err(Error::new(Code::InvArgs));
+        }
+
         log!(
             LogFlags::PgReqs,
             "[{}] pager::clone(parent={})",
@@ -166,7 +215,8 @@ impl AddrSpace {
                 }
                 // otherwise, remove it
                 else {
-                    self.ds.remove(cur);
+                    let old = self.ds.remove(cur);
+                    self.uncommit(&old);
                     None
                 }
             }
@@ -175,7 +225,9 @@ impl AddrSpace {
             };
 
             if ds_idx.is_none() {
-                self.ds.push(ds.clone_for(self.owner.unwrap()));
+                let mut nds = ds.clone_for(self.owner.unwrap());
+                self.commit(&mut nds)?;
+                self.ds.push(nds);
                 ds_idx.replace(self.ds.len() - 1);
             }
 
@@ -287,7 +339,7 @@ impl AddrSpace {
 
         self.check_map_args(virt, len, perm)?;
 
-        let ds = DataSpace::new_extern(
+        let mut ds = DataSpace::new_extern(
             self.owner.unwrap(),
             self.child.unwrap(),
             virt,
@@ -297,6 +349,7 @@ impl AddrSpace {
             off,
             sess,
         );
+        self.commit(&mut ds)?;
         self.ds.push(ds);
 
         Ok(virt)
@@ -336,7 +389,7 @@ impl AddrSpace {
 
         self.check_map_args(virt, len, perm)?;
 
-        let ds = DataSpace::new_anon(
+        let mut ds = DataSpace::new_anon(
             self.owner.unwrap(),
             self.child.unwrap(),
             virt,
@@ -344,6 +397,7 @@ impl AddrSpace {
             perm,
             flags,
         );
+        self.commit(&mut ds)?;
         self.ds.push(ds);
 
         Ok(())
@@ -408,7 +462,8 @@ impl AddrSpace {
         );
 
         if let Some(idx) = self.find_ds_idx(virt) {
-            self.ds.remove(idx);
+            let ds = self.ds.remove(idx);
+            self.uncommit(&ds);
         }
         else {
             log!(LogFlags::Error, "No dataspace at {}", virt);
@@ -418,6 +473,30 @@ impl AddrSpace {
         is.reply_error(Code::Success)
     }
 
+    fn commit(&self, ds: &mut DataSpace) -> Result<(), Error> {
+        if let Some(ref c) = self.commit {
+            let size = ds.commit_size();
+            c.mem.commit(size, c.overcommit).map_err(|e| {
+                log!(
+                    LogFlags::Error,
+                    "Unable to commit {:#x} bytes for {} (already committed: {:#x})",
+                    size,
+                    ds.virt(),
+                    c.mem.committed()
+                );
+                e
+            })?;
+            ds.set_committed(size);
+        }
+        Ok(())
+    }
+
+    fn uncommit(&self, ds: &DataSpace) {
+        if let Some(ref c) = self.commit {
+            c.mem.uncommit(ds.committed());
+        }
+    }
+
     fn check_map_args(&self, virt: VirtAddr, len: GlobOff, perm: Perm) -> Result<(), Error> {
         if virt >= MAX_VIRT_ADDR {
             return Err(Error::new(Code::InvArgs));
@@ -430,6 +509,9 @@ impl AddrSpace {
         if perm.is_empty() {
             return Err(Error::new(Code::InvArgs));
         }
+        if math::overlaps(STACK_GUARD_START, STACK_GUARD_END, virt, virt + len) {
+            return Err(Error::new(Code::InvArgs));
+        }
         if self.overlaps(virt, len) {
             return Err(Error::new(Code::Exists));
         }
@@ -466,6 +548,9 @@ impl Drop for AddrSpace {
         // the activity is destroyed anyway, therefore we don't need to do that.
         for ds in &mut self.ds {
             ds.kill();
+            if let Some(ref c) = self.commit {
+                c.mem.uncommit(ds.committed());
+            }
         }
     }
 }
@@ -74,6 +74,7 @@ pub struct DataSpace {
     regions: RegionList,
     owner: Selector,
     file: Option<FileMapping>,
+    committed: GlobOff,
 }
 
 impl DataSpace {
@@ -98,6 +99,7 @@ impl DataSpace {
             owner,
             regions: RegionList::new(owner, child, virt, size),
             file: Some(FileMapping::new(sel, off)),
+            committed: 0,
         }
     }
 
@@ -119,6 +121,7 @@ impl DataSpace {
             owner,
             regions: RegionList::new(owner, child, virt, size),
             file: None,
+            committed: 0,
         }
     }
 
@@ -133,6 +136,7 @@ impl DataSpace {
             owner,
             regions: RegionList::new(owner, self.child, self.virt, self.size),
             file: self.file.clone(),
+            committed: 0,
         }
     }
 
@@ -152,6 +156,28 @@ impl DataSpace {
         self.perms
     }
 
+    /// Returns the amount of memory that might need to be allocated for this dataspace on page
+    /// faults. Shared or read-only file mappings use the memory of the file system instead.
+    pub fn commit_size(&self) -> GlobOff {
+        match self.file {
+            Some(_)
+                if self.flags.contains(MapFlags::SHARED) || !self.perms.contains(kif::Perm::W) =>
+            {
+                0
+            },
+            _ => self.size,
+        }
+    }
+
+    /// Returns the amount of memory that has been committed for this dataspace
+    pub fn committed(&self) -> GlobOff {
+        self.committed
+    }
+
+    pub fn set_committed(&mut self, size: GlobOff) {
+        self.committed = size;
+    }
+
     pub fn inherit(&mut self, ds: &mut DataSpace) -> Result<(), Error> {
         self.id = ds.id;
 
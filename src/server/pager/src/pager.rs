@@ -28,6 +28,7 @@ use m3::cell::LazyStaticRefCell;
 use m3::client::{ClientSession, Pager, M3FS};
 use m3::col::{String, ToString, Vec};
 use m3::com::{opcodes, MemCap, RecvGate, SGateArgs, SendCap};
+use m3::env;
 use m3::errors::{Code, Error, VerboseError};
 use m3::format;
 use m3::kif::syscalls::MuxType;
@@ -114,12 +115,43 @@ impl subsys::ChildStarter for PagedChildStarter {
             sub.finalize_async(res, id, &mut act)?;
         }
 
+        // let the child join its gang so that it is started together with the other members
+        if let Some(gang) = child.cfg().gang() {
+            res.gangs().join(gang, &act).map_err(|e| {
+                VerboseError::new(e.code(), format!("Unable to join gang {}", gang))
+            })?;
+        }
+
         // mount file systems for childs
         for m in child.cfg().mounts() {
             let path = self.get_mount(m.fs())?;
             act.add_mount(m.path(), &path);
         }
 
+        // pass the environment variables of the config to childs
+        for e in child.cfg().env() {
+            act.set_env(e.name(), e.value());
+        }
+        if let Some(path) = child.cfg().coredump() {
+            act.set_env(env::COREDUMP_VAR, path);
+        }
+        if let Some(conn) = child.cfg().gdbstub() {
+            act.set_env(env::GDBSTUB_VAR, conn);
+        }
+        if let Some(wd) = child.cfg().watchdog() {
+            let interval = wd.interval().as_micros().max(1);
+            act.set_env(env::WATCHDOG_VAR, format!("{}", interval));
+        }
+        for d in child.cfg().devices() {
+            let info = res.devices().resolve(d).map_err(|e| {
+                VerboseError::new(e.code(), format!("Unable to find device {}", d.name()))
+            })?;
+            act.set_env(
+                format!("{}{}", env::DEV_VAR_PREFIX, d.name()),
+                info.to_string(),
+            );
+        }
+
         // if TileMux is running on that tile, we have control about the activity's virtual address
         // space and can thus load the program into the address space.
         let run = if tile.mux_type()? == MuxType::TileMux {
@@ -127,6 +159,7 @@ impl subsys::ChildStarter for PagedChildStarter {
             let mut hdl = REQHDL.borrow_mut();
             let aspace = hdl.clients_mut().get_mut(child_sid).unwrap();
             aspace.do_init(Some(child.id()), Some(act.sel())).unwrap();
+            aspace.set_mem_commit(child.mem().clone(), child.cfg().overcommit());
 
             // start activity
             let file = vfs::VFS::open(child.name(), vfs::OpenFlags::RX | vfs::OpenFlags::NEW_SESS)
@@ -196,12 +229,20 @@ fn workloop(args: &mut WorkloopArgs<'_, '_, '_, '_, '_, '_>) {
         |childmng, _res| {
             serv.fetch_and_handle(REQHDL.borrow_mut().deref_mut()).ok();
 
-            REQHDL.borrow_mut().fetch_and_handle_msg_with(
-                |_handler, opcode, sess, is| match opcode {
-                    o if o == opcodes::Pager::Pagefault.into() => sess.pagefault(childmng, is),
-                    o if o == opcodes::Pager::MapAnon.into() => sess.map_anon(is),
-                    o if o == opcodes::Pager::Unmap.into() => sess.unmap(is),
-                    _ => Err(Error::new(Code::InvArgs)),
+            REQHDL.borrow_mut().fetch_and_handle_msg_with_sessions(
+                |_handler, opcode, sid, sessions, is| {
+                    // cloning needs access to the parent's session as well
+                    if opcode == opcodes::Pager::Clone.into() {
+                        return AddrSpace::clone(sessions, sid, is);
+                    }
+
+                    let sess = sessions.get_mut(sid).unwrap();
+                    match opcode {
+                        o if o == opcodes::Pager::Pagefault.into() => sess.pagefault(childmng, is),
+                        o if o == opcodes::Pager::MapAnon.into() => sess.map_anon(is),
+                        o if o == opcodes::Pager::Unmap.into() => sess.unmap(is),
+                        _ => Err(Error::new(Code::InvArgs)),
+                    }
                 },
             );
         },
@@ -195,8 +195,7 @@ impl Channel {
                 if commit > 0 {
                     return Err(Error::new(Code::InvArgs));
                 }
-                state.append_request(self.id, is, true);
-                return Ok(());
+                return self.queue_request(&mut state, is, true);
             }
 
             // this client is the current reader, so commit the read by pulling it from the ringbuf
@@ -220,8 +219,7 @@ impl Channel {
         if state.has_pending_reads() {
             // only queue the request if we still have writers
             if !state.flags().contains(Flags::WRITE_EOF) {
-                state.append_request(self.id, is, true);
-                return Ok(());
+                return self.queue_request(&mut state, is, true);
             }
         }
 
@@ -249,15 +247,7 @@ impl Channel {
                 reply_vmsg!(is, Code::Success, 0usize, 0usize)
             }
             else {
-                // if we promised the client that input would be available, report WouldBlock
-                // instead of delaying the response.
-                if self.promised_events.get().contains(FileEvent::INPUT) {
-                    return Err(Error::new(Code::WouldBlock));
-                }
-
-                // otherwise queue the request
-                state.append_request(self.id, is, true);
-                Ok(())
+                self.queue_request(&mut state, is, true)
             }
         }
     }
@@ -280,8 +270,7 @@ impl Channel {
                 if commit > 0 {
                     return Err(Error::new(Code::InvArgs));
                 }
-                state.append_request(self.id, is, false);
-                return Ok(());
+                return self.queue_request(&mut state, is, false);
             }
 
             // this client is the current reader, so commit the write by pushing it to the ringbuf
@@ -303,8 +292,7 @@ impl Channel {
 
         // if there are already queued write requests, just append this request
         if state.has_pending_writes() {
-            state.append_request(self.id, is, false);
-            return Ok(());
+            return self.queue_request(&mut state, is, false);
         }
 
         // request new write position
@@ -325,18 +313,34 @@ impl Channel {
             reply_vmsg!(is, Code::Success, pos, amount)
         }
         else {
-            // if we promised the client that input would be available, report WouldBlock
-            // instead of delaying the response.
-            if self.promised_events.get().contains(FileEvent::OUTPUT) {
-                return Err(Error::new(Code::WouldBlock));
-            }
-
             // nothing to write, so queue the request
-            state.append_request(self.id, is, false);
-            Ok(())
+            self.queue_request(&mut state, is, false)
         }
     }
 
+    fn queue_request(
+        &self,
+        state: &mut State,
+        is: &mut GateIStream<'_>,
+        read: bool,
+    ) -> Result<(), Error> {
+        // if we promised the client that the operation would not block, report WouldBlock instead
+        // of delaying the response. With multiple readers or writers, others might have been
+        // faster than this client.
+        let event = if read {
+            FileEvent::INPUT
+        }
+        else {
+            FileEvent::OUTPUT
+        };
+        if self.promised_events.get().contains(event) {
+            return Err(Error::new(Code::WouldBlock));
+        }
+
+        state.append_request(self.id, is, read);
+        Ok(())
+    }
+
     fn close_reader(&mut self) -> Result<(), Error> {
         let mut state = self.state.borrow_mut();
         state.remove_pending(true, self.id);
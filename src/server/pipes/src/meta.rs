@@ -27,7 +27,7 @@ pub struct Meta {
 impl Meta {
     pub fn create_pipe(&mut self, sid: SessId, mem_size: usize) -> Pipe {
         self.pipes.push(sid);
-        Pipe::new(sid, mem_size)
+        Pipe::new(sid, mem_size, crate::settings().atomic_size)
     }
 
     pub fn close(&mut self, sids: &mut Vec<SessId>) -> Result<(), Error> {
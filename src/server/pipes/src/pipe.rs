@@ -14,6 +14,7 @@
  */
 
 use bitflags::bitflags;
+use core::cmp;
 use m3::cap::Selector;
 use m3::cell::{Cell, RefCell};
 use m3::col::{VarRingBuf, Vec};
@@ -107,6 +108,7 @@ pub struct State {
     flags: Flags,
     mem: Option<MemCap>,
     mem_size: usize,
+    atomic_size: usize,
     pub rbuf: VarRingBuf,
     pub last_read: Option<(SessId, usize)>,
     pub last_write: Option<(SessId, usize)>,
@@ -118,11 +120,12 @@ pub struct State {
 }
 
 impl State {
-    fn new(mem_size: usize) -> Self {
+    fn new(mem_size: usize, atomic_size: usize) -> Self {
         State {
             flags: Flags::empty(),
             mem: None,
             mem_size,
+            atomic_size,
             rbuf: VarRingBuf::new(mem_size),
             last_read: None,
             last_write: None,
@@ -157,7 +160,20 @@ impl State {
 
     pub fn get_write_size(&self) -> usize {
         assert!(!self.writer.is_empty());
-        self.rbuf.size() / (4 * self.writer.len())
+        // never hand out less than the atomic size so that writes up to this size fit into a single
+        // chunk and are thus not interleaved with the data of other writers
+        let atomic = cmp::min(self.atomic_size, self.rbuf.size());
+        cmp::max(self.rbuf.size() / (4 * self.writer.len()), atomic)
+    }
+
+    fn can_read(&self) -> bool {
+        // at write-EOF, reads do not block either, because they report EOF
+        self.rbuf.get_read_pos(1).is_some() || self.flags.contains(Flags::WRITE_EOF)
+    }
+
+    fn can_write(&self) -> bool {
+        // at read-EOF, writes do not block either, because they report EOF
+        self.rbuf.get_write_pos(1).is_some() || self.flags.contains(Flags::READ_EOF)
     }
 
     pub fn get_notify_gate(&mut self, sess: SessId) -> Option<&mut NotifyGate> {
@@ -201,10 +217,8 @@ impl State {
     }
 
     pub fn request_notify(&mut self, id: SessId, events: FileEvent) -> Result<(), Error> {
-        // we don't support multiple readers/writers in combination with notifications
-        assert!(self.reader.len() <= 1 && self.writer.len() <= 1);
-        let can_read = self.rbuf.get_read_pos(1).is_some();
-        let can_write = self.rbuf.get_write_pos(1).is_some();
+        let can_read = self.can_read();
+        let can_write = self.can_write();
         let ng = self
             .get_notify_gate(id)
             .ok_or_else(|| Error::new(Code::NotSup))?;
@@ -303,7 +317,7 @@ impl State {
         }
 
         // if there is any chance to read something, notify all that are waiting for this event
-        if !self.notify_gates.is_empty() && self.rbuf.get_read_pos(1).is_some() {
+        if !self.notify_gates.is_empty() && self.can_read() {
             self.add_event(FileEvent::INPUT);
         }
     }
@@ -349,7 +363,7 @@ impl State {
         }
 
         // if there is any chance to write something, notify all that are waiting for this event
-        if !self.notify_gates.is_empty() && self.rbuf.get_write_pos(1).is_some() {
+        if !self.notify_gates.is_empty() && self.can_write() {
             self.add_event(FileEvent::OUTPUT);
         }
     }
@@ -383,10 +397,10 @@ pub struct Pipe {
 }
 
 impl Pipe {
-    pub fn new(id: SessId, mem_size: usize) -> Self {
+    pub fn new(id: SessId, mem_size: usize, atomic_size: usize) -> Self {
         Pipe {
             id,
-            state: Rc::new(RefCell::new(State::new(mem_size))),
+            state: Rc::new(RefCell::new(State::new(mem_size, atomic_size))),
         }
     }
 
@@ -20,6 +20,7 @@ mod meta;
 mod pipe;
 mod sess;
 
+use m3::cell::LazyReadOnlyCell;
 use m3::col::{String, Vec};
 use m3::com::opcodes;
 use m3::env;
@@ -27,26 +28,40 @@ use m3::errors::{Code, Error};
 use m3::println;
 use m3::server::{ExcType, RequestHandler, Server, DEF_MAX_CLIENTS, DEF_MSG_SIZE};
 use m3::tiles::OwnActivity;
+use m3::util::parse;
 
 use sess::PipesSession;
 
+static SETTINGS: LazyReadOnlyCell<PipesSettings> = LazyReadOnlyCell::default();
+
 #[derive(Clone, Debug)]
 pub struct PipesSettings {
     max_clients: usize,
+    atomic_size: usize,
 }
 
 impl Default for PipesSettings {
     fn default() -> Self {
         PipesSettings {
             max_clients: DEF_MAX_CLIENTS,
+            // the minimum that POSIX requires for PIPE_BUF
+            atomic_size: 512,
         }
     }
 }
 
+fn settings() -> &'static PipesSettings {
+    SETTINGS.get()
+}
+
 fn usage() -> ! {
-    println!("Usage: {} [-m <clients>]", env::args().next().unwrap());
+    println!(
+        "Usage: {} [-m <clients>] [-a <bytes>]",
+        env::args().next().unwrap()
+    );
     println!();
     println!("  -m: the maximum number of clients (receive slots)");
+    println!("  -a: the maximum size of writes that are not interleaved with other writers");
     OwnActivity::exit_with(Code::InvArgs);
 }
 
@@ -63,6 +78,11 @@ fn parse_args() -> Result<PipesSettings, String> {
                     .map_err(|_| String::from("Failed to parse client count"))?;
                 i += 1;
             },
+            "-a" => {
+                settings.atomic_size = parse::size(args[i + 1])
+                    .map_err(|_| String::from("Failed to parse atomic size"))?;
+                i += 1;
+            },
             _ => break,
         }
         i += 1;
@@ -72,13 +92,13 @@ fn parse_args() -> Result<PipesSettings, String> {
 
 #[no_mangle]
 pub fn main() -> Result<(), Error> {
-    let settings = parse_args().unwrap_or_else(|e| {
+    SETTINGS.set(parse_args().unwrap_or_else(|e| {
         println!("Invalid arguments: {}", e);
         usage();
-    });
+    }));
 
     // create request handler and server
-    let mut hdl = RequestHandler::new_with(settings.max_clients, DEF_MSG_SIZE, 1)
+    let mut hdl = RequestHandler::new_with(settings().max_clients, DEF_MSG_SIZE, 1)
         .expect("Unable to create request handler");
     let mut srv = Server::new("pipes", &mut hdl).expect("Unable to create service 'pipes'");
 
@@ -0,0 +1,341 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use m3::build_vmsg;
+use m3::cap::Selector;
+use m3::cell::{Cell, RefCell, StaticRefCell};
+use m3::com::{GateIStream, MemCap, RecvGate, EP};
+use m3::errors::{Code, Error};
+use m3::io::LogFlags;
+use m3::kif;
+use m3::log;
+use m3::mem::GlobOff;
+use m3::rc::Rc;
+use m3::reply_vmsg;
+use m3::server::SessId;
+use m3::vfs::{FileEvent, FileInfo, FileMode, TMode, Termios, WinSize};
+
+use crate::pair::{Pair, Side};
+
+pub const BUF_SIZE: usize = 256;
+
+static TMP_BUF: StaticRefCell<[u8; BUF_SIZE]> = StaticRefCell::new([0u8; BUF_SIZE]);
+
+pub fn mem_off(id: SessId) -> GlobOff {
+    id as GlobOff * BUF_SIZE as GlobOff
+}
+
+pub struct Channel {
+    id: SessId,
+    side: Side,
+    pair: Rc<RefCell<Pair>>,
+    ep: Option<Selector>,
+    active: bool,
+    mem: MemCap,
+    // the size of the chunk that has been handed out for writing
+    out_len: usize,
+    promised_events: Rc<Cell<FileEvent>>,
+}
+
+impl Channel {
+    pub fn new(id: SessId, side: Side, pair: Rc<RefCell<Pair>>) -> Result<Self, Error> {
+        let mem =
+            crate::MEM
+                .borrow()
+                .derive_cap(mem_off(id), BUF_SIZE as GlobOff, kif::Perm::RW)?;
+
+        pair.borrow_mut().attach(side);
+
+        Ok(Channel {
+            id,
+            side,
+            pair,
+            ep: None,
+            active: false,
+            mem,
+            out_len: 0,
+            promised_events: Rc::new(Cell::from(FileEvent::empty())),
+        })
+    }
+
+    pub fn side(&self) -> Side {
+        self.side
+    }
+
+    pub fn pair(&self) -> &Rc<RefCell<Pair>> {
+        &self.pair
+    }
+
+    pub fn set_dest(&mut self, ep: Selector) {
+        self.ep = Some(ep);
+    }
+
+    pub fn enable_notify(&mut self, sgate: Selector) -> Result<(), Error> {
+        self.pair.borrow_mut().enable_notify(
+            self.id,
+            self.side,
+            sgate,
+            self.promised_events.clone(),
+        )
+    }
+
+    fn activate(&mut self) -> Result<(), Error> {
+        if !self.active {
+            let sel = self.ep.ok_or_else(|| Error::new(Code::InvArgs))?;
+            EP::new_bind(0, sel).configure(self.mem.sel())?;
+            self.active = true;
+        }
+        Ok(())
+    }
+
+    pub fn next_in(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let _: usize = is.pop()?;
+
+        log!(LogFlags::PtyInOut, "[{}] pty::next_in()", self.id);
+
+        let res = self.read(is);
+        // reading might have made room for blocked writers
+        self.pair.borrow_mut().handle_pending(is.rgate());
+        res
+    }
+
+    pub fn next_out(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let _: usize = is.pop()?;
+
+        log!(LogFlags::PtyInOut, "[{}] pty::next_out()", self.id);
+
+        let res = self.write(is);
+        // the written data might unblock readers
+        self.pair.borrow_mut().handle_pending(is.rgate());
+        res
+    }
+
+    pub fn commit(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let _fid: usize = is.pop()?;
+        let nbytes: usize = is.pop()?;
+
+        log!(
+            LogFlags::PtyInOut,
+            "[{}] pty::commit(nbytes={})",
+            self.id,
+            nbytes
+        );
+
+        // read data has already been removed from the buffer, so that there is only something to
+        // do for writes
+        let res = if self.out_len > 0 {
+            if nbytes > self.out_len {
+                return Err(Error::new(Code::InvArgs));
+            }
+            self.flush(nbytes)
+        }
+        else {
+            Ok(())
+        };
+
+        self.pair.borrow_mut().handle_pending(is.rgate());
+        res?;
+        is.reply_error(Code::Success)
+    }
+
+    pub fn stat(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let info = FileInfo {
+            mode: FileMode::IFCHR | FileMode::IRUSR | FileMode::IWUSR,
+            ..Default::default()
+        };
+
+        let mut reply = m3::mem::MsgBuf::borrow_def();
+        build_vmsg!(reply, Code::Success, info);
+        is.reply(&reply)
+    }
+
+    pub fn get_tmode(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let _fid: usize = is.pop()?;
+
+        log!(LogFlags::PtyReqs, "[{}] pty::get_tmode()", self.id);
+
+        let mode = self.pair.borrow().tmode();
+        reply_vmsg!(is, Code::Success, mode)
+    }
+
+    pub fn set_tmode(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let _fid: usize = is.pop()?;
+        let mode = is.pop::<TMode>()?;
+
+        log!(
+            LogFlags::PtyReqs,
+            "[{}] pty::set_tmode(mode={:?})",
+            self.id,
+            mode
+        );
+
+        self.pair.borrow_mut().set_tmode(mode);
+        self.pair.borrow_mut().handle_pending(is.rgate());
+        is.reply_error(Code::Success)
+    }
+
+    pub fn get_termios(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let _fid: usize = is.pop()?;
+
+        log!(LogFlags::PtyReqs, "[{}] pty::get_termios()", self.id);
+
+        let termios = *self.pair.borrow().termios();
+        reply_vmsg!(is, Code::Success, termios)
+    }
+
+    pub fn set_termios(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let _fid: usize = is.pop()?;
+        let termios = is.pop::<Termios>()?;
+
+        log!(
+            LogFlags::PtyReqs,
+            "[{}] pty::set_termios(termios={:?})",
+            self.id,
+            termios
+        );
+
+        self.pair.borrow_mut().set_termios(termios);
+        // leaving the canonical mode makes the current line available to readers
+        self.pair.borrow_mut().handle_pending(is.rgate());
+        is.reply_error(Code::Success)
+    }
+
+    pub fn get_winsize(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let _fid: usize = is.pop()?;
+
+        log!(LogFlags::PtyReqs, "[{}] pty::get_winsize()", self.id);
+
+        let size = self.pair.borrow().winsize();
+        reply_vmsg!(is, Code::Success, size)
+    }
+
+    pub fn set_winsize(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let _fid: usize = is.pop()?;
+        let size = is.pop::<WinSize>()?;
+
+        log!(
+            LogFlags::PtyReqs,
+            "[{}] pty::set_winsize(size={:?})",
+            self.id,
+            size
+        );
+
+        self.pair.borrow_mut().set_winsize(size);
+        is.reply_error(Code::Success)
+    }
+
+    pub fn request_notify(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let _: usize = is.pop()?;
+        let events: FileEvent = FileEvent::from_bits_truncate(is.pop()?);
+
+        log!(
+            LogFlags::PtyReqs,
+            "[{}] pty::request_notify(events={:?})",
+            self.id,
+            events
+        );
+
+        self.pair.borrow_mut().request_notify(self.id, events)?;
+
+        is.reply_error(Code::Success)
+    }
+
+    pub fn close(&mut self, rgate: &RecvGate) {
+        let mut pair = self.pair.borrow_mut();
+        pair.detach(self.id, self.side);
+        // readers and writers of the other side might be waiting for the hangup
+        pair.handle_pending(rgate);
+    }
+
+    fn read(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        self.activate()?;
+
+        // if the client switched from writing to reading, it did not write anything
+        self.out_len = 0;
+
+        let mut pair = self.pair.borrow_mut();
+        if let Some(amount) = pair.fetch(self.id, self.side)? {
+            // okay, input is available, so we fulfilled our promise
+            self.promised_events
+                .set(self.promised_events.get() & !FileEvent::INPUT);
+            log!(
+                LogFlags::PtyInOut,
+                "[{}] pty::next_in() -> (0, {})",
+                self.id,
+                amount
+            );
+            reply_vmsg!(is, Code::Success, 0usize, amount)
+        }
+        else {
+            // if we promised the client that input would be available, report WouldBlock
+            // instead of delaying the response.
+            if self.promised_events.get().contains(FileEvent::INPUT) {
+                return Err(Error::new(Code::WouldBlock));
+            }
+
+            pair.append_request(self.id, self.side, is, true);
+            Ok(())
+        }
+    }
+
+    fn write(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        self.activate()?;
+
+        // the previously handed out chunk has been written completely
+        self.flush(self.out_len)?;
+
+        let mut pair = self.pair.borrow_mut();
+        if pair.hungup(self.side) {
+            return Err(Error::new(Code::EndOfFile));
+        }
+
+        if !pair.can_write(self.side) {
+            // see read
+            if self.promised_events.get().contains(FileEvent::OUTPUT) {
+                return Err(Error::new(Code::WouldBlock));
+            }
+
+            // the chunk is handed out as soon as there is space again
+            self.out_len = BUF_SIZE;
+            pair.append_request(self.id, self.side, is, false);
+            return Ok(());
+        }
+
+        self.promised_events
+            .set(self.promised_events.get() & !FileEvent::OUTPUT);
+        self.out_len = BUF_SIZE;
+        reply_vmsg!(is, Code::Success, 0usize, BUF_SIZE)
+    }
+
+    fn flush(&mut self, nbytes: usize) -> Result<(), Error> {
+        self.out_len = 0;
+        if nbytes > 0 {
+            let mut buf = TMP_BUF.borrow_mut();
+            crate::MEM
+                .borrow()
+                .read(&mut buf[0..nbytes], mem_off(self.id))?;
+
+            log!(
+                LogFlags::PtyInOut,
+                "[{}] pty::write({:?})",
+                self.id,
+                &buf[0..nbytes]
+            );
+
+            self.pair.borrow_mut().write(self.side, &buf[0..nbytes])?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,509 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use core::cmp;
+
+use m3::cap::Selector;
+use m3::cell::Cell;
+use m3::col::{Vec, VecDeque};
+use m3::com::{GateIStream, LazyGate, RGateArgs, RecvGate, SendCap};
+use m3::errors::{Code, Error};
+use m3::io::LogFlags;
+use m3::log;
+use m3::rc::Rc;
+use m3::send_vmsg;
+use m3::server::SessId;
+use m3::tcu::Message;
+use m3::vfs::{FileEvent, InputFlags, LocalFlags, OutputFlags, TMode, Termios, WinSize};
+
+use crate::chan::{mem_off, BUF_SIZE};
+
+/// The maximum number of bytes that are buffered in each direction
+const MAX_BUF: usize = 4096;
+
+macro_rules! reply_vmsg_late {
+    ( $rgate:expr, $msg:expr, $( $args:expr ),* ) => ({
+        let mut msg = m3::mem::MsgBuf::borrow_def();
+        m3::build_vmsg!(&mut msg, $( $args ),*);
+        $rgate.reply(&msg, $msg)
+    });
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Side {
+    Master,
+    Slave,
+}
+
+struct NotifyGate {
+    sess: SessId,
+    side: Side,
+    rgate: RecvGate,
+    sgate: LazyGate<SendCap>,
+    notify_events: FileEvent,
+    pending_events: FileEvent,
+    promised_events: Rc<Cell<FileEvent>>,
+}
+
+impl NotifyGate {
+    fn send_events(&mut self) {
+        let sg = self.sgate.get().unwrap();
+        if !self.pending_events.is_empty() && sg.credits().unwrap() > 0 {
+            log!(
+                LogFlags::PtyEvents,
+                "[{}] pty::notify({:?})",
+                self.sess,
+                self.pending_events
+            );
+            // ignore errors
+            send_vmsg!(sg, &self.rgate, self.pending_events.bits()).ok();
+            // we promise the client that these operations will not block on the next call
+            self.promised_events.set(self.pending_events);
+            self.notify_events &= !self.pending_events;
+            self.pending_events = FileEvent::empty();
+        }
+    }
+}
+
+struct PendingRequest {
+    chan: SessId,
+    side: Side,
+    read: bool,
+    msg: &'static Message,
+}
+
+/// A master/slave pair including the line discipline between them
+///
+/// Data written to the master end is passed through the line discipline, which handles echoing,
+/// line editing, and signal characters according to the current [`Termios`], and is afterwards
+/// available for reading at the slave end. Data written to the slave end is post-processed and made
+/// available for reading at the master end.
+#[derive(Default)]
+pub struct Pair {
+    termios: Termios,
+    winsize: WinSize,
+    // the data for the master end: the output of the slaves and echoed input
+    output: VecDeque<u8>,
+    // the data for the slave end that passed the line discipline
+    input: VecDeque<u8>,
+    // the line that is currently edited in canonical mode
+    line: Vec<u8>,
+    // whether the next read at the slave end should report EOF
+    eof: bool,
+    masters: usize,
+    slaves: usize,
+    // whether all masters/slaves closed their end (the slave does not exist initially)
+    masters_gone: bool,
+    slaves_gone: bool,
+    pending: Vec<PendingRequest>,
+    notify_gates: Vec<NotifyGate>,
+}
+
+impl Pair {
+    pub fn attach(&mut self, side: Side) {
+        match side {
+            Side::Master => self.masters += 1,
+            Side::Slave => self.slaves += 1,
+        }
+    }
+
+    pub fn detach(&mut self, id: SessId, side: Side) {
+        self.pending.retain(|req| req.chan != id);
+        self.notify_gates.retain(|n| n.sess != id);
+
+        match side {
+            Side::Master => {
+                self.masters -= 1;
+                self.masters_gone = self.masters == 0;
+            },
+            Side::Slave => {
+                self.slaves -= 1;
+                self.slaves_gone = self.slaves == 0;
+            },
+        }
+        log!(
+            LogFlags::PtyReqs,
+            "[{}] pty::detach(): masters={}, slaves={}",
+            id,
+            self.masters,
+            self.slaves
+        );
+    }
+
+    pub fn termios(&self) -> &Termios {
+        &self.termios
+    }
+
+    pub fn set_termios(&mut self, termios: Termios) {
+        // when leaving the canonical mode, the current line is passed on as it is
+        if !termios.lflags.contains(LocalFlags::ICANON) {
+            self.input.extend(self.line.drain(..));
+        }
+        self.termios = termios;
+    }
+
+    pub fn tmode(&self) -> TMode {
+        if self.termios.lflags.contains(LocalFlags::ICANON) {
+            TMode::Cooked
+        }
+        else {
+            TMode::Raw
+        }
+    }
+
+    pub fn set_tmode(&mut self, mode: TMode) {
+        let def = Termios::default();
+        let mut termios = self.termios;
+        match mode {
+            TMode::Raw => {
+                termios.iflags &= !InputFlags::ICRNL;
+                termios.lflags &= !(LocalFlags::ICANON | LocalFlags::ECHO | LocalFlags::ISIG);
+            },
+            TMode::Cooked => {
+                termios.iflags |= def.iflags;
+                termios.lflags |= def.lflags;
+            },
+        }
+        self.set_termios(termios);
+    }
+
+    pub fn winsize(&self) -> WinSize {
+        self.winsize
+    }
+
+    pub fn set_winsize(&mut self, size: WinSize) {
+        self.winsize = size;
+    }
+
+    /// Returns true if the other end has been closed
+    pub fn hungup(&self, side: Side) -> bool {
+        match side {
+            Side::Master => self.slaves_gone,
+            Side::Slave => self.masters_gone,
+        }
+    }
+
+    fn can_read(&self, side: Side) -> bool {
+        let avail = match side {
+            Side::Master => !self.output.is_empty(),
+            Side::Slave => !self.input.is_empty() || self.eof,
+        };
+        // after a hangup, reads do not block either, because they report EOF
+        avail || self.hungup(side)
+    }
+
+    pub fn can_write(&self, side: Side) -> bool {
+        let used = match side {
+            Side::Master => self.input.len(),
+            Side::Slave => self.output.len(),
+        };
+        used < MAX_BUF || self.hungup(side)
+    }
+
+    /// Moves the next data for the given side into the buffer of channel `id`.
+    ///
+    /// Returns the number of bytes (0 in case of EOF) or `None` if no data is available.
+    pub fn fetch(&mut self, id: SessId, side: Side) -> Result<Option<usize>, Error> {
+        let amount = match side {
+            Side::Master => cmp::min(self.output.len(), BUF_SIZE),
+            Side::Slave => {
+                let avail = cmp::min(self.input.len(), BUF_SIZE);
+                // in canonical mode, pass at most one line to the reader
+                if self.termios.lflags.contains(LocalFlags::ICANON) {
+                    self.input
+                        .iter()
+                        .take(avail)
+                        .position(|b| *b == b'\n')
+                        .map(|pos| pos + 1)
+                        .unwrap_or(avail)
+                }
+                else {
+                    avail
+                }
+            },
+        };
+
+        if amount == 0 {
+            if side == Side::Slave && self.eof {
+                self.eof = false;
+                return Ok(Some(0));
+            }
+            return Ok(if self.hungup(side) { Some(0) } else { None });
+        }
+
+        let buf = match side {
+            Side::Master => &mut self.output,
+            Side::Slave => &mut self.input,
+        };
+        let data = buf.drain(0..amount).collect::<Vec<u8>>();
+        crate::MEM.borrow().write(&data, mem_off(id))?;
+        Ok(Some(amount))
+    }
+
+    pub fn write(&mut self, side: Side, data: &[u8]) -> Result<(), Error> {
+        if self.hungup(side) {
+            return Err(Error::new(Code::EndOfFile));
+        }
+
+        match side {
+            Side::Master => {
+                for b in data {
+                    self.input_char(*b);
+                }
+            },
+            Side::Slave => {
+                for b in data {
+                    self.output_char(*b);
+                }
+            },
+        }
+        Ok(())
+    }
+
+    fn input_char(&mut self, mut c: u8) {
+        let termios = self.termios;
+
+        if termios.iflags.contains(InputFlags::ICRNL) && c == b'\r' {
+            c = b'\n';
+        }
+
+        if termios.lflags.contains(LocalFlags::ISIG) && c == termios.intr {
+            self.line.clear();
+            self.echo(c);
+            self.add_event(Side::Slave, FileEvent::SIGNAL);
+            return;
+        }
+
+        if termios.lflags.contains(LocalFlags::ISIG) && c == termios.susp {
+            self.echo(c);
+            self.add_event(Side::Slave, FileEvent::SUSPEND);
+            return;
+        }
+
+        if !termios.lflags.contains(LocalFlags::ICANON) {
+            self.input.push_back(c);
+            self.echo(c);
+            return;
+        }
+
+        if c == termios.erase {
+            if self.line.pop().is_some() {
+                self.echo_erase();
+            }
+        }
+        else if c == termios.kill {
+            while self.line.pop().is_some() {
+                self.echo_erase();
+            }
+        }
+        else if c == termios.eof {
+            // EOF on an empty line is reported to the reader; otherwise the line is passed on
+            if self.line.is_empty() {
+                self.eof = true;
+            }
+            else {
+                self.input.extend(self.line.drain(..));
+            }
+        }
+        // drop characters if the line is full, but always accept the newline
+        else if self.line.len() < MAX_BUF || c == b'\n' {
+            self.line.push(c);
+            self.echo(c);
+            if c == b'\n' {
+                self.input.extend(self.line.drain(..));
+            }
+        }
+    }
+
+    fn output_char(&mut self, c: u8) {
+        if self.termios.oflags.contains(OutputFlags::ONLCR) && c == b'\n' {
+            self.output.push_back(b'\r');
+        }
+        self.output.push_back(c);
+    }
+
+    fn echo(&mut self, c: u8) {
+        if self.termios.lflags.contains(LocalFlags::ECHO) {
+            // print control characters as ^X
+            if c.is_ascii_control() && c != b'\n' && c != b'\t' {
+                self.output.push_back(b'^');
+                self.output.push_back(c ^ 0x40);
+            }
+            else {
+                self.output_char(c);
+            }
+        }
+    }
+
+    fn echo_erase(&mut self) {
+        let flags = LocalFlags::ECHO | LocalFlags::ECHOE;
+        if self.termios.lflags.contains(flags) {
+            self.output.extend(b"\x08 \x08");
+        }
+    }
+
+    pub fn append_request(
+        &mut self,
+        chan: SessId,
+        side: Side,
+        is: &mut GateIStream<'_>,
+        read: bool,
+    ) {
+        log!(
+            LogFlags::PtyInOut,
+            "[{}] pty::{}_wait()",
+            chan,
+            if read { "read" } else { "write" }
+        );
+        self.pending.push(PendingRequest {
+            chan,
+            side,
+            read,
+            msg: is.take_msg(),
+        });
+    }
+
+    pub fn handle_pending(&mut self, rgate: &RecvGate) {
+        self.receive_acks();
+
+        // handle the requests in FIFO order
+        let mut i = 0;
+        while i < self.pending.len() {
+            let PendingRequest {
+                chan,
+                side,
+                read,
+                msg,
+            } = self.pending[i];
+
+            let done = if read {
+                match self.fetch(chan, side) {
+                    Ok(Some(amount)) => {
+                        log!(
+                            LogFlags::PtyInOut,
+                            "[{}] pty::late_read() -> (0, {})",
+                            chan,
+                            amount
+                        );
+                        reply_vmsg_late!(rgate, msg, Code::Success, 0usize, amount).ok();
+                        true
+                    },
+                    Ok(None) => false,
+                    Err(e) => {
+                        reply_vmsg_late!(rgate, msg, e.code()).ok();
+                        true
+                    },
+                }
+            }
+            else if self.hungup(side) {
+                log!(LogFlags::PtyInOut, "[{}] pty::late_write(): EOF", chan);
+                reply_vmsg_late!(rgate, msg, Code::EndOfFile).ok();
+                true
+            }
+            else if self.can_write(side) {
+                log!(LogFlags::PtyInOut, "[{}] pty::late_write()", chan);
+                reply_vmsg_late!(rgate, msg, Code::Success, 0usize, BUF_SIZE).ok();
+                true
+            }
+            else {
+                false
+            };
+
+            if done {
+                self.pending.remove(i);
+            }
+            else {
+                i += 1;
+            }
+        }
+
+        // notify all that are waiting for input or output
+        for side in [Side::Master, Side::Slave] {
+            if self.can_read(side) {
+                self.add_event(side, FileEvent::INPUT);
+            }
+            if self.can_write(side) {
+                self.add_event(side, FileEvent::OUTPUT);
+            }
+        }
+    }
+
+    pub fn enable_notify(
+        &mut self,
+        id: SessId,
+        side: Side,
+        sgate: Selector,
+        promised_events: Rc<Cell<FileEvent>>,
+    ) -> Result<(), Error> {
+        if self.notify_gates.iter().any(|n| n.sess == id) {
+            return Err(Error::new(Code::Exists));
+        }
+
+        let rgate = RecvGate::new_with(RGateArgs::default().order(6).msg_order(6))?;
+        self.notify_gates.push(NotifyGate {
+            sess: id,
+            side,
+            rgate,
+            sgate: LazyGate::new(sgate),
+            notify_events: FileEvent::empty(),
+            pending_events: FileEvent::empty(),
+            promised_events,
+        });
+        Ok(())
+    }
+
+    pub fn request_notify(&mut self, id: SessId, events: FileEvent) -> Result<(), Error> {
+        let side = self
+            .notify_gates
+            .iter()
+            .find(|n| n.sess == id)
+            .ok_or_else(|| Error::new(Code::NotSup))?
+            .side;
+        let can_read = self.can_read(side);
+        let can_write = self.can_write(side);
+
+        let ng = self.notify_gates.iter_mut().find(|n| n.sess == id).unwrap();
+        ng.notify_events |= events;
+        // remove from promised events, because we need to notify the client about them again first
+        ng.promised_events.set(ng.promised_events.get() & !events);
+        if events.contains(FileEvent::INPUT) && can_read {
+            ng.pending_events |= FileEvent::INPUT;
+        }
+        if events.contains(FileEvent::OUTPUT) && can_write {
+            ng.pending_events |= FileEvent::OUTPUT;
+        }
+        ng.send_events();
+        Ok(())
+    }
+
+    fn add_event(&mut self, side: Side, event: FileEvent) {
+        for n in &mut self.notify_gates {
+            if n.side == side && n.notify_events.contains(event) {
+                n.pending_events |= event;
+                n.send_events();
+            }
+        }
+    }
+
+    fn receive_acks(&mut self) {
+        for n in &mut self.notify_gates {
+            if let Ok(msg) = n.rgate.fetch() {
+                n.rgate.ack_msg(msg).unwrap();
+                // try again to send events, if there are some
+                n.send_events();
+            }
+        }
+    }
+}
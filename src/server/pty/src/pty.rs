@@ -0,0 +1,276 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+#![no_std]
+
+mod chan;
+mod pair;
+
+use m3::cap::SelSpace;
+use m3::cell::{LazyStaticRefCell, RefCell};
+use m3::col::Vec;
+use m3::com::{opcodes, GateIStream, MemGate, Perm};
+use m3::errors::{Code, Error};
+use m3::io::LogFlags;
+use m3::kif;
+use m3::log;
+use m3::mem::GlobOff;
+use m3::rc::Rc;
+use m3::server::{
+    CapExchange, ClientManager, ExcType, RequestHandler, RequestSession, Server, ServerSession,
+    SessId, DEF_MAX_CLIENTS,
+};
+
+use chan::Channel;
+use pair::{Pair, Side};
+
+static MEM: LazyStaticRefCell<Rc<MemGate>> = LazyStaticRefCell::default();
+
+enum SessionData {
+    Meta,
+    Chan(Channel),
+}
+
+pub struct PtySession {
+    _serv: ServerSession,
+    data: SessionData,
+}
+
+impl RequestSession for PtySession {
+    fn new(serv: ServerSession, _arg: &str) -> Result<Self, Error>
+    where
+        Self: Sized,
+    {
+        Ok(PtySession {
+            _serv: serv,
+            data: SessionData::Meta,
+        })
+    }
+
+    fn close(&mut self, cli: &mut ClientManager<Self>, sid: SessId, _sub_ids: &mut Vec<SessId>)
+    where
+        Self: Sized,
+    {
+        log!(LogFlags::PtyReqs, "[{}] pty::close()", sid);
+
+        if let SessionData::Chan(c) = &mut self.data {
+            c.close(cli.recv_gate());
+        }
+    }
+}
+
+impl PtySession {
+    fn get_sess(cli: &mut ClientManager<Self>, sid: SessId) -> Result<&mut Self, Error> {
+        cli.get_mut(sid).ok_or_else(|| Error::new(Code::InvArgs))
+    }
+
+    fn with_chan<F, R>(&mut self, is: &mut GateIStream<'_>, func: F) -> Result<R, Error>
+    where
+        F: Fn(&mut Channel, &mut GateIStream<'_>) -> Result<R, Error>,
+    {
+        match &mut self.data {
+            SessionData::Meta => Err(Error::new(Code::InvArgs)),
+            SessionData::Chan(c) => func(c, is),
+        }
+    }
+
+    fn new_chan(
+        serv: ServerSession,
+        side: Side,
+        pair: Rc<RefCell<Pair>>,
+    ) -> Result<PtySession, Error> {
+        log!(
+            LogFlags::PtyReqs,
+            "[{}] pty::new_chan(side={:?})",
+            serv.id(),
+            side
+        );
+
+        Ok(PtySession {
+            data: SessionData::Chan(Channel::new(serv.id(), side, pair)?),
+            _serv: serv,
+        })
+    }
+
+    fn open_pair(
+        cli: &mut ClientManager<Self>,
+        crt: usize,
+        sid: SessId,
+        xchg: &mut CapExchange<'_>,
+    ) -> Result<(), Error> {
+        log!(LogFlags::PtyReqs, "[{}] pty::open_pair()", sid);
+
+        let (sel, _nsid) = cli.add_connected(crt, |cli, serv, _sgate| {
+            match &Self::get_sess(cli, sid)?.data {
+                SessionData::Meta => {
+                    let pair = Rc::new(RefCell::new(Pair::default()));
+                    Self::new_chan(serv, Side::Master, pair)
+                },
+                _ => Err(Error::new(Code::InvArgs)),
+            }
+        })?;
+
+        xchg.out_caps(kif::CapRngDesc::new(kif::CapType::Object, sel, 2));
+        Ok(())
+    }
+
+    fn open_slave(
+        cli: &mut ClientManager<Self>,
+        crt: usize,
+        sid: SessId,
+        xchg: &mut CapExchange<'_>,
+    ) -> Result<(), Error> {
+        log!(LogFlags::PtyReqs, "[{}] pty::open_slave()", sid);
+
+        let (sel, _nsid) = cli.add_connected(crt, |cli, serv, _sgate| {
+            match &Self::get_sess(cli, sid)?.data {
+                // slaves can only be opened via the master
+                SessionData::Chan(c) if c.side() == Side::Master => {
+                    Self::new_chan(serv, Side::Slave, c.pair().clone())
+                },
+                _ => Err(Error::new(Code::InvArgs)),
+            }
+        })?;
+
+        xchg.out_caps(kif::CapRngDesc::new(kif::CapType::Object, sel, 2));
+        Ok(())
+    }
+
+    fn clone(
+        cli: &mut ClientManager<Self>,
+        crt: usize,
+        sid: SessId,
+        xchg: &mut CapExchange<'_>,
+    ) -> Result<(), Error> {
+        log!(LogFlags::PtyReqs, "[{}] pty::clone(crt={})", sid, crt);
+
+        let (sel, _nsid) = cli.add_connected(crt, |cli, serv, _sgate| {
+            match &Self::get_sess(cli, sid)?.data {
+                SessionData::Chan(c) => Self::new_chan(serv, c.side(), c.pair().clone()),
+                _ => Err(Error::new(Code::InvArgs)),
+            }
+        })?;
+
+        xchg.out_caps(kif::CapRngDesc::new(kif::CapType::Object, sel, 2));
+        Ok(())
+    }
+
+    fn set_dest(
+        cli: &mut ClientManager<Self>,
+        _crt: usize,
+        sid: SessId,
+        xchg: &mut CapExchange<'_>,
+    ) -> Result<(), Error> {
+        let sess = Self::get_sess(cli, sid)?;
+        match &mut sess.data {
+            SessionData::Chan(c) => {
+                let sel = SelSpace::get().alloc_sel();
+                c.set_dest(sel);
+
+                log!(LogFlags::PtyReqs, "[{}] pty::set_dest(sel={})", sid, sel);
+
+                xchg.out_caps(kif::CapRngDesc::new(kif::CapType::Object, sel, 1));
+                Ok(())
+            },
+            _ => Err(Error::new(Code::InvArgs)),
+        }
+    }
+
+    fn enable_notify(
+        cli: &mut ClientManager<Self>,
+        _crt: usize,
+        sid: SessId,
+        xchg: &mut CapExchange<'_>,
+    ) -> Result<(), Error> {
+        let sess = Self::get_sess(cli, sid)?;
+        match &mut sess.data {
+            SessionData::Chan(c) => {
+                let sel = SelSpace::get().alloc_sel();
+                log!(
+                    LogFlags::PtyReqs,
+                    "[{}] pty::enable_notify(sel={})",
+                    sid,
+                    sel
+                );
+                c.enable_notify(sel)?;
+
+                xchg.out_caps(kif::CapRngDesc::new(kif::CapType::Object, sel, 1));
+                Ok(())
+            },
+            _ => Err(Error::new(Code::InvArgs)),
+        }
+    }
+}
+
+#[no_mangle]
+pub fn main() -> Result<(), Error> {
+    MEM.set(Rc::new(
+        MemGate::new((DEF_MAX_CLIENTS * chan::BUF_SIZE) as GlobOff, Perm::RW)
+            .expect("Unable to alloc memory"),
+    ));
+
+    let mut hdl = RequestHandler::new().expect("Unable to create request handler");
+    let mut srv = Server::new("pty", &mut hdl).expect("Unable to create service 'pty'");
+
+    use opcodes::Pty;
+    hdl.reg_cap_handler(Pty::OpenPair, ExcType::Obt(2), PtySession::open_pair);
+    hdl.reg_cap_handler(Pty::OpenSlave, ExcType::Obt(2), PtySession::open_slave);
+    hdl.reg_cap_handler(Pty::CloneFile, ExcType::Obt(2), PtySession::clone);
+    hdl.reg_cap_handler(Pty::SetDest, ExcType::Del(1), PtySession::set_dest);
+    hdl.reg_cap_handler(
+        Pty::EnableNotify,
+        ExcType::Del(1),
+        PtySession::enable_notify,
+    );
+
+    hdl.reg_msg_handler(Pty::NextIn, |sess, is| {
+        sess.with_chan(is, |c, is| c.next_in(is))
+    });
+    hdl.reg_msg_handler(Pty::NextOut, |sess, is| {
+        sess.with_chan(is, |c, is| c.next_out(is))
+    });
+    hdl.reg_msg_handler(Pty::Commit, |sess, is| {
+        sess.with_chan(is, |c, is| c.commit(is))
+    });
+    hdl.reg_msg_handler(Pty::FStat, |sess, is| {
+        sess.with_chan(is, |c, is| c.stat(is))
+    });
+    hdl.reg_msg_handler(Pty::Seek, |_sess, _is| Err(Error::new(Code::NotSup)));
+    hdl.reg_msg_handler(Pty::GetTMode, |sess, is| {
+        sess.with_chan(is, |c, is| c.get_tmode(is))
+    });
+    hdl.reg_msg_handler(Pty::SetTMode, |sess, is| {
+        sess.with_chan(is, |c, is| c.set_tmode(is))
+    });
+    hdl.reg_msg_handler(Pty::GetTermios, |sess, is| {
+        sess.with_chan(is, |c, is| c.get_termios(is))
+    });
+    hdl.reg_msg_handler(Pty::SetTermios, |sess, is| {
+        sess.with_chan(is, |c, is| c.set_termios(is))
+    });
+    hdl.reg_msg_handler(Pty::GetWinSize, |sess, is| {
+        sess.with_chan(is, |c, is| c.get_winsize(is))
+    });
+    hdl.reg_msg_handler(Pty::SetWinSize, |sess, is| {
+        sess.with_chan(is, |c, is| c.set_winsize(is))
+    });
+    hdl.reg_msg_handler(Pty::ReqNotify, |sess, is| {
+        sess.with_chan(is, |c, is| c.request_notify(is))
+    });
+
+    hdl.run(&mut srv).expect("Server loop failed");
+
+    Ok(())
+}
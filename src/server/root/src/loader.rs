@@ -17,21 +17,24 @@ use core::any::Any;
 use core::cmp;
 use core::fmt;
 
+use m3::boxed::Box;
 use m3::cap::Selector;
 use m3::cell::RefCell;
 use m3::cfg::PAGE_BITS;
 use m3::client::{HashInput, HashOutput, MapFlags, Pager};
 use m3::col::Vec;
-use m3::com::MemGate;
+use m3::com::{GateCap, MemGate};
 use m3::errors::{Code, Error};
 use m3::io::{Read, Write};
-use m3::kif::Perm;
+use m3::kif::{self, Perm};
 use m3::mem::{GlobOff, VirtAddr};
 use m3::rc::Rc;
 use m3::syscalls;
-use m3::tiles::Mapper;
+use m3::tiles::{Activity, Mapper};
 use m3::vfs;
 
+use resmng::subsys;
+
 use crate::memory;
 
 pub struct BootFile {
@@ -146,24 +149,24 @@ impl fmt::Debug for BootFile {
 
 pub struct BootMapper {
     act_sel: Selector,
-    mem_sel: Selector,
     has_virtmem: bool,
     mem_pool: Rc<RefCell<memory::MemPool>>,
+    bmods: Vec<kif::boot::Mod>,
     allocs: Vec<memory::Allocation>,
 }
 
 impl BootMapper {
     pub fn new(
         act_sel: Selector,
-        mem_sel: Selector,
         has_virtmem: bool,
         mem_pool: Rc<RefCell<memory::MemPool>>,
+        bmods: Vec<kif::boot::Mod>,
     ) -> Self {
         BootMapper {
             act_sel,
-            mem_sel,
             has_virtmem,
             mem_pool,
+            bmods,
             allocs: Vec::new(),
         }
     }
@@ -177,7 +180,7 @@ impl Mapper for BootMapper {
     fn map_file(
         &mut self,
         pager: Option<&Pager>,
-        _file: &mut vfs::BufReader<vfs::FileRef<dyn vfs::File>>,
+        file: &mut vfs::BufReader<vfs::FileRef<dyn vfs::File>>,
         foff: usize,
         virt: VirtAddr,
         len: usize,
@@ -189,11 +192,21 @@ impl Mapper for BootMapper {
             self.map_anon(pager, virt, len, perm, flags)
         }
         else if self.has_virtmem {
+            // the program and its shared objects are all stored in boot modules
+            let mem_sel = file
+                .get_ref()
+                .borrow()
+                .as_any()
+                .downcast_ref::<BootFile>()
+                .ok_or_else(|| Error::new(Code::InvArgs))?
+                .mgate
+                .sel();
+
             // map the memory of the boot module directly; therefore no initialization necessary
             syscalls::create_map(
                 virt,
                 self.act_sel,
-                self.mem_sel,
+                mem_sel,
                 (foff >> PAGE_BITS) as Selector,
                 (len >> PAGE_BITS) as Selector,
                 perm,
@@ -233,4 +246,17 @@ impl Mapper for BootMapper {
             Ok(true)
         }
     }
+
+    fn open_lib(&mut self, name: &str, _path: &str) -> Result<vfs::FileRef<dyn vfs::File>, Error> {
+        // shared objects are loaded from the boot module with the same name
+        let idx = self
+            .bmods
+            .iter()
+            .position(|m| m.name() == name)
+            .ok_or_else(|| Error::new(Code::NotFound))?;
+        let mgate = subsys::Subsystem::get_mod(idx).activate()?;
+        let bfile = BootFile::new(mgate, self.bmods[idx].size as usize);
+        let fd = Activity::own().files().add(Box::new(bfile))?;
+        Ok(vfs::FileRef::new_owned(fd))
+    }
 }
@@ -22,6 +22,7 @@ use m3::cap::Selector;
 use m3::cfg;
 use m3::col::{ToString, Vec};
 use m3::com::{GateCap, MemCap, MemGate, RGateArgs, RecvCap, RecvGate, SGateArgs, SendCap};
+use m3::env;
 use m3::errors::{Code, Error, VerboseError};
 use m3::format;
 use m3::io::LogFlags;
@@ -46,6 +47,8 @@ struct RootChildStarter {
     bmods: Vec<kif::boot::Mod>,
     loaded_bmods: u64,
     pmp_bmods: u64,
+    // the boot module that has been loaded for each child, which is reused on restarts
+    child_bmods: Vec<(childs::Id, usize)>,
 }
 
 impl RootChildStarter {
@@ -54,14 +57,20 @@ impl RootChildStarter {
             bmods,
             loaded_bmods: 0,
             pmp_bmods: 0,
+            child_bmods: Vec::new(),
         }
     }
 
     fn fetch_mod(&mut self, name: &str, pmp: bool) -> Option<(MemCap, GlobAddr, GlobOff)> {
+        self.fetch_mod_idx(name, pmp).map(|idx| self.get_mod(idx))
+    }
+
+    fn fetch_mod_idx(&mut self, name: &str, pmp: bool) -> Option<usize> {
         let RootChildStarter {
             bmods,
             loaded_bmods,
             pmp_bmods,
+            ..
         } = self;
 
         let mask = if pmp { pmp_bmods } else { loaded_bmods };
@@ -72,14 +81,36 @@ impl RootChildStarter {
             .position(|(idx, m)| (*mask & (1 << idx)) == 0 && m.name() == name)
             .map(|idx| {
                 *mask |= 1 << idx;
-                (
-                    subsys::Subsystem::get_mod(idx),
-                    GlobAddr::new(bmods[idx].addr),
-                    bmods[idx].size,
-                )
+                idx
             })
     }
 
+    fn get_mod(&self, idx: usize) -> (MemCap, GlobAddr, GlobOff) {
+        (
+            subsys::Subsystem::get_mod(idx),
+            GlobAddr::new(self.bmods[idx].addr),
+            self.bmods[idx].size,
+        )
+    }
+
+    fn child_mod(&mut self, child: &OwnChild) -> Option<(MemCap, GlobAddr, GlobOff)> {
+        // restarted childs keep their ID and get the same boot module again
+        let idx = match self.child_bmods.iter().find(|(id, _)| *id == child.id()) {
+            Some((_, idx)) => *idx,
+            None => {
+                // apps started at runtime may reuse a boot module that has been loaded before
+                let cfg = child.cfg();
+                let name = cfg.name();
+                let idx = self
+                    .fetch_mod_idx(name, false)
+                    .or_else(|| self.bmods.iter().position(|m| m.name() == name))?;
+                self.child_bmods.push((child.id(), idx));
+                idx
+            },
+        };
+        Some(self.get_mod(idx))
+    }
+
     fn modules_range(
         &mut self,
         domain: &config::Domain,
@@ -125,7 +156,7 @@ impl resmng::subsys::ChildStarter for RootChildStarter {
         // space and can thus load the program into the address space.
         let bmod = if tile.mux_type()? == MuxType::TileMux {
             Some(
-                self.fetch_mod(child.cfg().name(), false)
+                self.child_mod(child)
                     .ok_or_else(|| Error::new(Code::NotFound))?,
             )
         }
@@ -151,18 +182,48 @@ impl resmng::subsys::ChildStarter for RootChildStarter {
             act.add_mount("/", "/");
         }
 
+        for e in child.cfg().env() {
+            act.set_env(e.name(), e.value());
+        }
+        if let Some(path) = child.cfg().coredump() {
+            act.set_env(env::COREDUMP_VAR, path);
+        }
+        if let Some(conn) = child.cfg().gdbstub() {
+            act.set_env(env::GDBSTUB_VAR, conn);
+        }
+        if let Some(wd) = child.cfg().watchdog() {
+            let interval = wd.interval().as_micros().max(1);
+            act.set_env(env::WATCHDOG_VAR, format!("{}", interval));
+        }
+        for d in child.cfg().devices() {
+            let info = res.devices().resolve(d).map_err(|e| {
+                VerboseError::new(e.code(), format!("Unable to find device {}", d.name()))
+            })?;
+            act.set_env(
+                format!("{}{}", env::DEV_VAR_PREFIX, d.name()),
+                info.to_string(),
+            );
+        }
+
         let id = child.id();
         if let Some(sub) = child.subsys() {
             sub.finalize_async(res, id, &mut act)
                 .expect("Unable to finalize subsystem");
         }
 
+        // let the child join its gang so that it is started together with the other members
+        if let Some(gang) = child.cfg().gang() {
+            res.gangs().join(gang, &act).map_err(|e| {
+                VerboseError::new(e.code(), format!("Unable to join gang {}", gang))
+            })?;
+        }
+
         let run = if let Some(bmod) = bmod {
             let mut bmapper = loader::BootMapper::new(
                 act.sel(),
-                bmod.0.sel(),
                 act.tile_desc().has_virtmem(),
                 child.mem().pool().clone(),
+                self.bmods.clone(),
             );
             let bmod_gate = bmod.0.activate()?;
             let bfile = loader::BootFile::new(bmod_gate, bmod.2 as usize);
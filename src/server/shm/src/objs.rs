@@ -0,0 +1,99 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use m3::cap::Selector;
+use m3::col::{BTreeMap, String, Vec};
+use m3::com::{MemCap, Perm};
+use m3::errors::{Code, Error};
+use m3::mem::GlobOff;
+use m3::server::SessId;
+
+/// The maximum length of object names
+pub const MAX_NAME_LEN: usize = 32;
+
+/// A named shared memory object
+pub struct ShmObject {
+    mem: MemCap,
+    size: GlobOff,
+    owner: SessId,
+    // the capabilities handed out to clients; revoking them revokes the clients' copies as well
+    derived: Vec<(SessId, MemCap)>,
+}
+
+impl ShmObject {
+    pub fn new(mem: MemCap, size: GlobOff, owner: SessId) -> Self {
+        ShmObject {
+            mem,
+            size,
+            owner,
+            derived: Vec::new(),
+        }
+    }
+
+    pub fn size(&self) -> GlobOff {
+        self.size
+    }
+
+    pub fn owner(&self) -> SessId {
+        self.owner
+    }
+
+    /// Derives a capability for the whole object with permissions `perm` for session `sid`
+    pub fn derive(&mut self, sid: SessId, perm: Perm) -> Result<Selector, Error> {
+        let cap = self.mem.derive(0, self.size, perm)?;
+        let sel = cap.sel();
+        self.derived.push((sid, cap));
+        Ok(sel)
+    }
+}
+
+/// All shared memory objects, indexed by name
+pub struct Objects {
+    objs: BTreeMap<String, ShmObject>,
+}
+
+impl Objects {
+    pub const fn new() -> Self {
+        Objects {
+            objs: BTreeMap::new(),
+        }
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Result<&mut ShmObject, Error> {
+        self.objs
+            .get_mut(name)
+            .ok_or_else(|| Error::new(Code::NotFound))
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.objs.contains_key(name)
+    }
+
+    pub fn add(&mut self, name: &str, obj: ShmObject) {
+        self.objs.insert(String::from(name), obj);
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<ShmObject> {
+        self.objs.remove(name)
+    }
+
+    /// Removes all objects created by session `sid` and all capabilities handed out to it
+    pub fn remove_session(&mut self, sid: SessId) {
+        self.objs.retain(|_, o| o.owner != sid);
+        for o in self.objs.values_mut() {
+            o.derived.retain(|(s, _)| *s != sid);
+        }
+    }
+}
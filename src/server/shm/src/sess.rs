@@ -0,0 +1,195 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use m3::cell::StaticRefCell;
+use m3::cfg;
+use m3::col::Vec;
+use m3::com::{GateIStream, MemCap, Perm};
+use m3::errors::{Code, Error};
+use m3::io::LogFlags;
+use m3::kif;
+use m3::log;
+use m3::mem::GlobOff;
+use m3::reply_vmsg;
+use m3::server::{CapExchange, ClientManager, RequestSession, ServerSession, SessId};
+use m3::util::{math, parse};
+
+use crate::objs::{Objects, ShmObject, MAX_NAME_LEN};
+
+static OBJECTS: StaticRefCell<Objects> = StaticRefCell::new(Objects::new());
+
+pub struct ShmSession {
+    serv: ServerSession,
+    quota: GlobOff,
+    used: GlobOff,
+}
+
+fn parse_quota(arg: &str) -> Result<GlobOff, Error> {
+    let mut quota = crate::settings().quota;
+    for a in arg.split_whitespace() {
+        if let Some(q) = a.strip_prefix("quota=") {
+            quota = parse::size(q)? as GlobOff;
+        }
+        else {
+            return Err(Error::new(Code::InvArgs));
+        }
+    }
+    Ok(quota)
+}
+
+fn check_name(name: &str) -> Result<(), Error> {
+    if name.is_empty() || name.len() > MAX_NAME_LEN {
+        return Err(Error::new(Code::InvArgs));
+    }
+    Ok(())
+}
+
+fn parse_perm(bits: u32) -> Result<Perm, Error> {
+    match Perm::from_bits(bits) {
+        Some(perm) if !perm.is_empty() => Ok(perm),
+        _ => Err(Error::new(Code::InvArgs)),
+    }
+}
+
+impl RequestSession for ShmSession {
+    fn new(serv: ServerSession, arg: &str) -> Result<Self, Error>
+    where
+        Self: Sized,
+    {
+        let quota = parse_quota(arg)?;
+        log!(
+            LogFlags::ShmReqs,
+            "[{}] shm::open(quota={:#x})",
+            serv.id(),
+            quota
+        );
+
+        Ok(ShmSession {
+            serv,
+            quota,
+            used: 0,
+        })
+    }
+
+    fn close(&mut self, _cli: &mut ClientManager<Self>, sid: SessId, _sub_ids: &mut Vec<SessId>)
+    where
+        Self: Sized,
+    {
+        log!(LogFlags::ShmReqs, "[{}] shm::close()", sid);
+
+        // all objects of this session are destroyed, which revokes all capabilities for them
+        OBJECTS.borrow_mut().remove_session(sid);
+    }
+}
+
+impl ShmSession {
+    pub fn create(
+        cli: &mut ClientManager<Self>,
+        _crt: usize,
+        sid: SessId,
+        xchg: &mut CapExchange<'_>,
+    ) -> Result<(), Error> {
+        let name: &str = xchg.in_args().pop()?;
+        let size: GlobOff = xchg.in_args().pop()?;
+        let perm = parse_perm(xchg.in_args().pop()?)?;
+
+        log!(
+            LogFlags::ShmReqs,
+            "[{}] shm::create(name={}, size={:#x}, perm={:?})",
+            sid,
+            name,
+            size,
+            perm
+        );
+
+        check_name(name)?;
+        if size == 0 {
+            return Err(Error::new(Code::InvArgs));
+        }
+        let size = math::round_up(size, cfg::PAGE_SIZE as GlobOff);
+
+        let sess = cli.get_mut(sid).ok_or_else(|| Error::new(Code::InvArgs))?;
+        if sess.quota - sess.used < size {
+            return Err(Error::new(Code::NoSpace));
+        }
+
+        let mut objs = OBJECTS.borrow_mut();
+        if objs.contains(name) {
+            return Err(Error::new(Code::Exists));
+        }
+
+        let mem = MemCap::new(size, Perm::RWX)?;
+        let mut obj = ShmObject::new(mem, size, sid);
+        let sel = obj.derive(sid, perm)?;
+        objs.add(name, obj);
+        sess.used += size;
+
+        xchg.out_caps(kif::CapRngDesc::new(kif::CapType::Object, sel, 1));
+
+        Ok(())
+    }
+
+    pub fn open(
+        _cli: &mut ClientManager<Self>,
+        _crt: usize,
+        sid: SessId,
+        xchg: &mut CapExchange<'_>,
+    ) -> Result<(), Error> {
+        let name: &str = xchg.in_args().pop()?;
+        let perm = parse_perm(xchg.in_args().pop()?)?;
+
+        log!(
+            LogFlags::ShmReqs,
+            "[{}] shm::open(name={}, perm={:?})",
+            sid,
+            name,
+            perm
+        );
+
+        let sel = OBJECTS.borrow_mut().get_mut(name)?.derive(sid, perm)?;
+
+        xchg.out_caps(kif::CapRngDesc::new(kif::CapType::Object, sel, 1));
+
+        Ok(())
+    }
+
+    pub fn remove(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        let name: &str = is.pop()?;
+
+        log!(
+            LogFlags::ShmReqs,
+            "[{}] shm::remove(name={})",
+            self.serv.id(),
+            name
+        );
+
+        let mut objs = OBJECTS.borrow_mut();
+        if objs.get_mut(name)?.owner() != self.serv.id() {
+            return Err(Error::new(Code::NoPerm));
+        }
+
+        // dropping the object revokes all capabilities and frees the memory
+        let obj = objs.remove(name).unwrap();
+        self.used -= obj.size();
+
+        is.reply_error(Code::Success)
+    }
+
+    pub fn quota(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
+        log!(LogFlags::ShmReqs, "[{}] shm::quota()", self.serv.id());
+
+        reply_vmsg!(is, Code::Success, self.quota, self.used)
+    }
+}
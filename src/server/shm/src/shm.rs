@@ -0,0 +1,119 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+#![no_std]
+
+mod objs;
+mod sess;
+
+use m3::cell::LazyReadOnlyCell;
+use m3::col::{String, Vec};
+use m3::com::opcodes;
+use m3::env;
+use m3::errors::{Code, Error};
+use m3::mem::GlobOff;
+use m3::println;
+use m3::server::{ExcType, RequestHandler, Server, DEF_MAX_CLIENTS};
+use m3::tiles::OwnActivity;
+use m3::util::parse;
+
+use sess::ShmSession;
+
+// the message size for requests, which contain the object name
+const MSG_SIZE: usize = 128;
+
+static SETTINGS: LazyReadOnlyCell<ShmSettings> = LazyReadOnlyCell::default();
+
+#[derive(Clone, Debug)]
+pub struct ShmSettings {
+    max_clients: usize,
+    quota: GlobOff,
+}
+
+impl Default for ShmSettings {
+    fn default() -> Self {
+        ShmSettings {
+            max_clients: DEF_MAX_CLIENTS,
+            quota: 1024 * 1024,
+        }
+    }
+}
+
+fn settings() -> &'static ShmSettings {
+    SETTINGS.get()
+}
+
+fn usage() -> ! {
+    println!(
+        "Usage: {} [-m <clients>] [-q <quota>]",
+        env::args().next().unwrap()
+    );
+    println!();
+    println!("  -m: the maximum number of clients (receive slots)");
+    println!("  -q: the default quota per session (can be overwritten with \"quota=<size>\")");
+    OwnActivity::exit_with(Code::InvArgs);
+}
+
+fn parse_args() -> Result<ShmSettings, String> {
+    let mut settings = ShmSettings::default();
+
+    let args: Vec<&str> = env::args().collect();
+    let mut i = 1;
+    while i < args.len() {
+        match args[i] {
+            "-m" => {
+                settings.max_clients = args[i + 1]
+                    .parse::<usize>()
+                    .map_err(|_| String::from("Failed to parse client count"))?;
+                i += 1;
+            },
+            "-q" => {
+                settings.quota = parse::size(args[i + 1])
+                    .map_err(|_| String::from("Failed to parse quota"))?
+                    as GlobOff;
+                i += 1;
+            },
+            _ => break,
+        }
+        i += 1;
+    }
+    Ok(settings)
+}
+
+#[no_mangle]
+pub fn main() -> Result<(), Error> {
+    SETTINGS.set(parse_args().unwrap_or_else(|e| {
+        println!("Invalid arguments: {}", e);
+        usage();
+    }));
+
+    // create request handler and server
+    let mut hdl = RequestHandler::new_with(settings().max_clients, MSG_SIZE, 1)
+        .expect("Unable to create request handler");
+    let mut srv = Server::new("shm", &mut hdl).expect("Unable to create service 'shm'");
+
+    // register capability handler
+    use opcodes::Shm;
+    hdl.reg_cap_handler(Shm::Create, ExcType::Obt(1), ShmSession::create);
+    hdl.reg_cap_handler(Shm::Open, ExcType::Obt(1), ShmSession::open);
+
+    // register message handler
+    hdl.reg_msg_handler(Shm::Remove, ShmSession::remove);
+    hdl.reg_msg_handler(Shm::Quota, ShmSession::quota);
+
+    hdl.run(&mut srv).expect("Server loop failed");
+
+    Ok(())
+}
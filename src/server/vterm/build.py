@@ -1,2 +1,8 @@
+import os
+
+
 def build(gen, env):
-    env.m3_rust_exe(gen, out='vterm', dir='sbin')
+    features = []
+    if os.environ.get('M3_SRVTESTS', '0') == '1':
+        features = ['vterm/tests']
+    env.m3_rust_exe(gen, out='vterm', dir='sbin', features=features)
@@ -18,7 +18,7 @@ use m3::cell::{RefMut, StaticRefCell};
 use m3::col::Vec;
 use m3::com::{GateIStream, LazyGate, MemCap, MemGate, RecvGate, SendCap, EP};
 use m3::errors::{Code, Error};
-use m3::io::{LogFlags, Serial, Write};
+use m3::io::LogFlags;
 use m3::kif;
 use m3::log;
 use m3::mem::GlobOff;
@@ -29,6 +29,7 @@ use m3::tcu::Message;
 use m3::vfs::{FileEvent, FileInfo, FileMode, TMode};
 use m3::{build_vmsg, send_vmsg};
 
+use crate::console;
 use crate::input;
 
 pub const BUF_SIZE: usize = 256;
@@ -38,6 +39,7 @@ static TMP_BUF: StaticRefCell<[u8; BUF_SIZE]> = StaticRefCell::new([0u8; BUF_SIZ
 #[derive(Debug)]
 pub struct Channel {
     id: SessId,
+    console: usize,
     active: bool,
     writing: bool,
     ep: Option<Selector>,
@@ -57,11 +59,12 @@ fn mem_off(id: SessId) -> GlobOff {
 }
 
 impl Channel {
-    pub fn new(id: SessId, mem: Rc<MemGate>, writing: bool) -> Result<Self, Error> {
+    pub fn new(id: SessId, console: usize, mem: Rc<MemGate>, writing: bool) -> Result<Self, Error> {
         let cmem = mem.derive_cap(mem_off(id), BUF_SIZE as GlobOff, kif::Perm::RW)?;
 
         Ok(Channel {
             id,
+            console,
             active: false,
             writing,
             ep: None,
@@ -77,6 +80,10 @@ impl Channel {
         })
     }
 
+    pub fn console(&self) -> usize {
+        self.console
+    }
+
     pub fn is_writing(&self) -> bool {
         self.writing
     }
@@ -113,7 +120,7 @@ impl Channel {
 
         log!(LogFlags::VTReqs, "[{}] vterm::get_tmode()", self.id,);
 
-        reply_vmsg!(is, Code::Success, input::mode())
+        reply_vmsg!(is, Code::Success, input::mode(self.console))
     }
 
     pub fn set_tmode(&mut self, is: &mut GateIStream<'_>) -> Result<(), Error> {
@@ -126,7 +133,7 @@ impl Channel {
             self.id,
             mode
         );
-        input::set_mode(mode);
+        input::set_mode(self.console, mode);
 
         is.reply_error(Code::Success)
     }
@@ -147,8 +154,8 @@ impl Channel {
         if self.pos == self.len {
             assert!(self.pending_nextin.is_none());
 
-            let mut input = input::get();
-            if !input::eof() && input.is_empty() {
+            let mut input = input::get(self.console);
+            if !input::eof(self.console) && input.is_empty() {
                 // if we promised the client that input would be available, report WouldBlock
                 // instead of delaying the response.
                 if self.promised_events.contains(FileEvent::INPUT) {
@@ -185,7 +192,7 @@ impl Channel {
                 self.len - self.pos
             );
 
-            input::set_eof(false);
+            input::set_eof(self.console, false);
             input.clear();
 
             Ok(Some((msg, self.pos, self.len)))
@@ -253,7 +260,7 @@ impl Channel {
         if nbytes > 0 {
             self.our_mem
                 .read(&mut TMP_BUF.borrow_mut()[0..nbytes], mem_off(self.id))?;
-            Serial::new().write(&TMP_BUF.borrow()[0..nbytes])?;
+            console::write(self.console, &TMP_BUF.borrow()[0..nbytes])?;
         }
         self.len = 0;
         Ok(())
@@ -278,7 +285,7 @@ impl Channel {
         // remove from promised events, because we need to notify the client about them again first
         self.promised_events &= !events;
         // check whether input is available already
-        if events.contains(FileEvent::INPUT) && !input::get().is_empty() {
+        if events.contains(FileEvent::INPUT) && !input::get(self.console).is_empty() {
             self.pending_events |= FileEvent::INPUT;
         }
         // output is always possible
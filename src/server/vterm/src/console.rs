@@ -0,0 +1,94 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! Contains the virtual consoles that share the serial interface
+//!
+//! Only the active console is shown on the serial interface. The output of all other consoles is
+//! recorded in their screen, so that they can be redrawn when switching to them.
+
+use m3::cell::{StaticCell, StaticRefCell};
+use m3::col::Vec;
+use m3::errors::Error;
+use m3::io::{LogFlags, Serial, Write};
+use m3::log;
+
+use crate::screen::{Screen, ROWS};
+
+/// The number of virtual consoles
+pub const COUNT: usize = 4;
+
+const EMPTY_SCREEN: Screen = Screen::new();
+
+static SCREENS: StaticRefCell<[Screen; COUNT]> = StaticRefCell::new([EMPTY_SCREEN; COUNT]);
+static ACTIVE: StaticCell<usize> = StaticCell::new(0);
+// the number of lines the active console is scrolled back
+static SCROLL: StaticCell<usize> = StaticCell::new(0);
+
+/// Returns the id of the active console
+pub fn active() -> usize {
+    ACTIVE.get()
+}
+
+/// Writes the given bytes to the console with given id
+///
+/// The bytes are only written to the serial interface if the console is active and not scrolled
+/// back.
+pub fn write(id: usize, bytes: &[u8]) -> Result<(), Error> {
+    SCREENS.borrow_mut()[id].write(bytes);
+    if id == ACTIVE.get() && SCROLL.get() == 0 {
+        Serial::new().write(bytes)?;
+    }
+    Ok(())
+}
+
+/// Switches to the console with given id
+pub fn switch(id: usize) {
+    if id < COUNT && (id != ACTIVE.get() || SCROLL.get() != 0) {
+        log!(LogFlags::VTReqs, "vterm: switching to console {}", id);
+        ACTIVE.set(id);
+        SCROLL.set(0);
+        redraw();
+    }
+}
+
+/// Scrolls the active console back by one page
+pub fn scroll_up() {
+    let max = SCREENS.borrow()[ACTIVE.get()].history_len();
+    scroll_to((SCROLL.get() + ROWS - 1).min(max));
+}
+
+/// Scrolls the active console forward by one page
+pub fn scroll_down() {
+    scroll_to(SCROLL.get().saturating_sub(ROWS - 1));
+}
+
+/// Stops scrolling back in the active console, if necessary
+pub fn leave_scrollback() {
+    scroll_to(0);
+}
+
+fn scroll_to(lines: usize) {
+    if lines != SCROLL.get() {
+        SCROLL.set(lines);
+        redraw();
+    }
+}
+
+fn redraw() {
+    let mut out = Vec::new();
+    SCREENS.borrow()[ACTIVE.get()].render(SCROLL.get(), &mut out);
+    // ignore errors; there is nothing we can do about it
+    Serial::new().write(&out).ok();
+}
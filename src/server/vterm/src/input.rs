@@ -16,19 +16,31 @@
 use m3::cell::{RefMut, StaticCell, StaticRefCell};
 use m3::col::Vec;
 use m3::errors::Code;
-use m3::io::{LogFlags, Serial, Write};
+use m3::io::LogFlags;
 use m3::log;
 use m3::server::ClientManager;
 use m3::tcu::Message;
 use m3::vec;
 use m3::vfs::{FileEvent, TMode};
 
+use crate::console;
 use crate::{SessionData, VTermSession};
 
-static BUFFER: StaticRefCell<Vec<u8>> = StaticRefCell::new(Vec::new());
-static INPUT: StaticRefCell<Vec<u8>> = StaticRefCell::new(Vec::new());
-static EOF: StaticCell<bool> = StaticCell::new(false);
-static MODE: StaticCell<TMode> = StaticCell::new(TMode::Cooked);
+/// The key that starts a command for the vterm itself (^A)
+///
+/// It is followed by the number of the console to switch to, 'p' or 'n' to scroll the active
+/// console back or forward by one page, or another ^A to send ^A to the console.
+const CMD_KEY: u8 = 0x01;
+
+const EMPTY_BUF: Vec<u8> = Vec::new();
+
+static BUFFER: StaticRefCell<[Vec<u8>; console::COUNT]> =
+    StaticRefCell::new([EMPTY_BUF; console::COUNT]);
+static INPUT: StaticRefCell<[Vec<u8>; console::COUNT]> =
+    StaticRefCell::new([EMPTY_BUF; console::COUNT]);
+static EOF: StaticCell<[bool; console::COUNT]> = StaticCell::new([false; console::COUNT]);
+static MODE: StaticCell<[TMode; console::COUNT]> = StaticCell::new([TMode::Cooked; console::COUNT]);
+static CMD: StaticCell<bool> = StaticCell::new(false);
 
 macro_rules! reply_vmsg_late {
     ( $rgate:expr, $msg:expr, $( $args:expr ),* ) => ({
@@ -38,25 +50,29 @@ macro_rules! reply_vmsg_late {
     });
 }
 
-pub fn eof() -> bool {
-    EOF.get()
+pub fn eof(con: usize) -> bool {
+    EOF.get()[con]
 }
 
-pub fn set_eof(eof: bool) {
-    EOF.set(eof);
+pub fn set_eof(con: usize, eof: bool) {
+    let mut all = EOF.get();
+    all[con] = eof;
+    EOF.set(all);
 }
 
-pub fn mode() -> TMode {
-    MODE.get()
+pub fn mode(con: usize) -> TMode {
+    MODE.get()[con]
 }
 
-pub fn set_mode(mode: TMode) {
-    MODE.set(mode);
-    INPUT.borrow_mut().clear();
+pub fn set_mode(con: usize, mode: TMode) {
+    let mut all = MODE.get();
+    all[con] = mode;
+    MODE.set(all);
+    INPUT.borrow_mut()[con].clear();
 }
 
-pub fn get() -> RefMut<'static, Vec<u8>> {
-    INPUT.borrow_mut()
+pub fn get(con: usize) -> RefMut<'static, Vec<u8>> {
+    RefMut::map(INPUT.borrow_mut(), |i| &mut i[con])
 }
 
 pub fn receive_acks(cli: &mut ClientManager<VTermSession>) {
@@ -75,9 +91,6 @@ pub fn receive_acks(cli: &mut ClientManager<VTermSession>) {
 }
 
 pub fn handle_input(cli: &mut ClientManager<VTermSession>, msg: &'static Message) {
-    let mut input = INPUT.borrow_mut();
-    let mut buffer = BUFFER.borrow_mut();
-
     log!(
         LogFlags::VTInOut,
         "Got input message with {} bytes",
@@ -85,19 +98,68 @@ pub fn handle_input(cli: &mut ClientManager<VTermSession>, msg: &'static Message
     );
 
     let bytes = unsafe { core::slice::from_raw_parts(msg.data.as_ptr(), msg.header.length()) };
+
+    // filter out our commands and pass the remaining keys to the console they were typed in
+    let mut keys = Vec::with_capacity(bytes.len());
+    let mut con = console::active();
+    for b in bytes {
+        if let Some(k) = handle_cmd(*b) {
+            keys.push(k);
+        }
+        else if console::active() != con {
+            handle_keys(cli, con, &keys);
+            keys.clear();
+            con = console::active();
+        }
+    }
+
+    if !keys.is_empty() {
+        handle_keys(cli, con, &keys);
+    }
+}
+
+fn handle_cmd(b: u8) -> Option<u8> {
+    if CMD.get() {
+        CMD.set(false);
+        match b {
+            CMD_KEY => return Some(b),
+            b'1'..=b'9' => console::switch((b - b'1') as usize),
+            b'p' => console::scroll_up(),
+            b'n' => console::scroll_down(),
+            _ => {},
+        }
+        None
+    }
+    else if b == CMD_KEY {
+        CMD.set(true);
+        None
+    }
+    else {
+        // typing brings us back to the current screen contents
+        console::leave_scrollback();
+        Some(b)
+    }
+}
+
+fn handle_keys(cli: &mut ClientManager<VTermSession>, con: usize, keys: &[u8]) {
+    let mut input = get(con);
+    let mut buffer = RefMut::map(BUFFER.borrow_mut(), |b| &mut b[con]);
+
     let mut flush = false;
     let mut eof = false;
-    if MODE.get() == TMode::Raw {
-        input.extend_from_slice(bytes);
+    if mode(con) == TMode::Raw {
+        input.extend_from_slice(keys);
     }
     else {
         let mut output = vec![];
-        for b in bytes {
+        for b in keys {
             match b {
                 // ^D
                 0x04 => eof = true,
                 // ^C
-                0x03 => add_signal(cli),
+                0x03 => add_signal(cli, con, FileEvent::SIGNAL),
+                // ^Z
+                0x1a => add_signal(cli, con, FileEvent::SUSPEND),
                 // backspace
                 0x7f => {
                     output.push(0x08);
@@ -128,42 +190,46 @@ pub fn handle_input(cli: &mut ClientManager<VTermSession>, msg: &'static Message
             input.extend_from_slice(&buffer);
             buffer.clear();
         }
-        Serial::new().write(&output).unwrap();
+        console::write(con, &output).unwrap();
     }
 
-    add_input(cli, eof, eof || flush, &mut input);
+    add_input(cli, con, eof, eof || flush, &mut input);
 }
 
-fn add_signal(cli: &mut ClientManager<VTermSession>) {
+fn add_signal(cli: &mut ClientManager<VTermSession>, con: usize, event: FileEvent) {
     cli.for_each(|s| match &mut s.data {
-        SessionData::Chan(c) => {
-            c.add_event(FileEvent::SIGNAL);
+        SessionData::Chan(c) if c.console() == con => {
+            c.add_event(event);
         },
-        SessionData::Meta => {},
+        _ => {},
     });
 }
 
 fn add_input(
     cli: &mut ClientManager<VTermSession>,
+    con: usize,
     eof: bool,
     mut flush: bool,
     input: &mut RefMut<'_, Vec<u8>>,
 ) {
-    // pass to first session that wants input
-    EOF.set(eof);
+    // pass to first session of the console that wants input
+    set_eof(con, eof);
 
     let mut input_recv: Option<(&Message, usize, usize)> = None;
 
     cli.for_each(|s| {
         if flush || !input.is_empty() {
-            if let SessionData::Chan(c) = &mut s.data {
-                if let Some((msg, pos, len)) = c.fetch_input(input).unwrap() {
-                    input_recv = Some((msg, pos, len));
-                    flush = false;
-                }
-                else if c.add_event(FileEvent::INPUT) {
-                    flush = false;
-                }
+            match &mut s.data {
+                SessionData::Chan(c) if c.console() == con => {
+                    if let Some((msg, pos, len)) = c.fetch_input(input).unwrap() {
+                        input_recv = Some((msg, pos, len));
+                        flush = false;
+                    }
+                    else if c.add_event(FileEvent::INPUT) {
+                        flush = false;
+                    }
+                },
+                _ => {},
             }
         }
     });
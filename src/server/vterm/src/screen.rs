@@ -0,0 +1,484 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! Contains the screen of a virtual console, including its scrollback buffer
+//!
+//! The screen interprets the ANSI/VT100 escape sequences that are written to the console to keep
+//! track of its contents. This allows us to redraw the console after switching to it or to show
+//! lines that have been scrolled out of the screen. Only the characters are kept; attributes like
+//! colors are ignored.
+
+use m3::col::{Vec, VecDeque};
+use m3::format;
+
+/// The number of rows of the terminal
+pub const ROWS: usize = 24;
+/// The number of columns of the terminal
+pub const COLS: usize = 80;
+/// The maximum number of lines that are kept after they have been scrolled out of the screen
+pub const SCROLLBACK: usize = 500;
+
+const MAX_PARAMS: usize = 8;
+const TAB_WIDTH: usize = 8;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum State {
+    Normal,
+    // after ESC
+    Escape,
+    // after ESC and an intermediate byte (e.g., ESC ( B)
+    EscInter,
+    // control sequence (ESC [ ...)
+    Csi,
+    // operating system command (ESC ] ...), terminated by BEL or ESC \
+    Osc,
+}
+
+#[derive(Debug)]
+pub struct Screen {
+    // the lines that have been scrolled out of the screen, oldest first
+    history: VecDeque<Vec<u8>>,
+    // the lines on the screen; lines that do not exist (yet) are empty
+    lines: Vec<Vec<u8>>,
+    x: usize,
+    y: usize,
+    state: State,
+    params: [u16; MAX_PARAMS],
+    nparams: usize,
+    private: bool,
+}
+
+impl Screen {
+    pub const fn new() -> Self {
+        Screen {
+            history: VecDeque::new(),
+            lines: Vec::new(),
+            x: 0,
+            y: 0,
+            state: State::Normal,
+            params: [0; MAX_PARAMS],
+            nparams: 0,
+            private: false,
+        }
+    }
+
+    /// Returns the number of lines in the scrollback buffer
+    pub fn history_len(&self) -> usize {
+        self.history.len()
+    }
+
+    /// Returns the contents of the given row of the screen
+    pub fn line(&self, row: usize) -> &[u8] {
+        self.lines.get(row).map(|l| l.as_slice()).unwrap_or(&[])
+    }
+
+    /// Updates the screen according to the given output of the console
+    pub fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.process(*b);
+        }
+    }
+
+    /// Appends the bytes to redraw the screen to `out`
+    ///
+    /// `scroll` specifies how many lines the view should be scrolled back. If it is zero, the
+    /// cursor is placed at its current position.
+    pub fn render(&self, scroll: usize, out: &mut Vec<u8>) {
+        let scroll = scroll.min(self.history.len());
+        let first = self.history.len() - scroll;
+
+        // move cursor home and clear screen
+        out.extend_from_slice(b"\x1b[H\x1b[2J");
+        for row in 0..ROWS {
+            if row > 0 {
+                out.extend_from_slice(b"\r\n");
+            }
+            match self.history.get(first + row) {
+                Some(l) => out.extend_from_slice(l),
+                None => out.extend_from_slice(self.line(first + row - self.history.len())),
+            }
+        }
+
+        if scroll == 0 {
+            let pos = format!("\x1b[{};{}H", self.y + 1, self.x.min(COLS - 1) + 1);
+            out.extend_from_slice(pos.as_bytes());
+        }
+    }
+
+    fn process(&mut self, b: u8) {
+        match self.state {
+            State::Normal => match b {
+                0x1b => self.state = State::Escape,
+                // we assume that the terminal translates newlines into CR+LF
+                b'\n' => {
+                    self.x = 0;
+                    self.line_feed();
+                },
+                b'\r' => self.x = 0,
+                0x08 => self.x = self.x.min(COLS).saturating_sub(1),
+                b'\t' => self.x = ((self.x / TAB_WIDTH + 1) * TAB_WIDTH).min(COLS - 1),
+                b if b.is_ascii_control() => {},
+                b => self.put(b),
+            },
+
+            State::Escape => {
+                self.state = State::Normal;
+                match b {
+                    b'[' => {
+                        self.params = [0; MAX_PARAMS];
+                        self.nparams = 0;
+                        self.private = false;
+                        self.state = State::Csi;
+                    },
+                    b']' => self.state = State::Osc,
+                    b'c' => self.reset(),
+                    0x20..=0x2f => self.state = State::EscInter,
+                    // everything else (e.g., the string terminator) does not change the contents
+                    _ => {},
+                }
+            },
+
+            State::EscInter => {
+                if (0x30..=0x7e).contains(&b) {
+                    self.state = State::Normal;
+                }
+            },
+
+            State::Osc => match b {
+                0x07 => self.state = State::Normal,
+                0x1b => self.state = State::Escape,
+                _ => {},
+            },
+
+            State::Csi => match b {
+                b'0'..=b'9' => {
+                    if self.nparams == 0 {
+                        self.nparams = 1;
+                    }
+                    let p = &mut self.params[self.nparams - 1];
+                    *p = p.saturating_mul(10).saturating_add((b - b'0') as u16);
+                },
+                b';' => {
+                    if self.nparams == 0 {
+                        self.nparams = 1;
+                    }
+                    if self.nparams < MAX_PARAMS {
+                        self.nparams += 1;
+                    }
+                },
+                b'<'..=b'?' => self.private = true,
+                // intermediate bytes
+                0x20..=0x2f => {},
+                0x40..=0x7e => {
+                    self.state = State::Normal;
+                    self.execute(b);
+                },
+                // abort the sequence on invalid bytes
+                _ => self.state = State::Normal,
+            },
+        }
+    }
+
+    fn param(&self, idx: usize, def: usize) -> usize {
+        match self.params[idx] {
+            0 => def,
+            p if idx < self.nparams => p as usize,
+            _ => def,
+        }
+    }
+
+    fn execute(&mut self, cmd: u8) {
+        // private sequences (e.g., to hide the cursor) do not change the contents
+        if self.private {
+            return;
+        }
+
+        match cmd {
+            // cursor up/down/forward/backward
+            b'A' => self.y = self.y.saturating_sub(self.param(0, 1)),
+            b'B' => self.y = (self.y + self.param(0, 1)).min(ROWS - 1),
+            b'C' => self.x = (self.x + self.param(0, 1)).min(COLS - 1),
+            b'D' => self.x = self.x.min(COLS).saturating_sub(self.param(0, 1)),
+            // cursor to next/previous line
+            b'E' => {
+                self.y = (self.y + self.param(0, 1)).min(ROWS - 1);
+                self.x = 0;
+            },
+            b'F' => {
+                self.y = self.y.saturating_sub(self.param(0, 1));
+                self.x = 0;
+            },
+            // cursor to column/row
+            b'G' => self.x = (self.param(0, 1) - 1).min(COLS - 1),
+            b'd' => self.y = (self.param(0, 1) - 1).min(ROWS - 1),
+            // cursor position
+            b'H' | b'f' => {
+                self.y = (self.param(0, 1) - 1).min(ROWS - 1);
+                self.x = (self.param(1, 1) - 1).min(COLS - 1);
+            },
+            // erase in display
+            b'J' => match self.param(0, 0) {
+                0 => {
+                    self.lines.truncate(self.y + 1);
+                    self.erase_line(self.x, COLS);
+                },
+                1 => {
+                    for l in self.lines.iter_mut().take(self.y) {
+                        l.clear();
+                    }
+                    self.erase_line(0, self.x + 1);
+                },
+                2 => self.lines.clear(),
+                _ => {
+                    self.lines.clear();
+                    self.history.clear();
+                },
+            },
+            // erase in line
+            b'K' => match self.param(0, 0) {
+                0 => self.erase_line(self.x, COLS),
+                1 => self.erase_line(0, self.x + 1),
+                _ => self.erase_line(0, COLS),
+            },
+            // everything else (e.g., colors) does not change the contents
+            _ => {},
+        }
+    }
+
+    fn line_mut(&mut self, row: usize) -> &mut Vec<u8> {
+        if self.lines.len() <= row {
+            self.lines.resize(row + 1, Vec::new());
+        }
+        &mut self.lines[row]
+    }
+
+    fn put(&mut self, b: u8) {
+        // wrap around lazily so that the last column can be used
+        if self.x >= COLS {
+            self.x = 0;
+            self.line_feed();
+        }
+
+        let x = self.x;
+        let line = self.line_mut(self.y);
+        if line.len() <= x {
+            line.resize(x, b' ');
+            line.push(b);
+        }
+        else {
+            line[x] = b;
+        }
+        self.x += 1;
+    }
+
+    fn erase_line(&mut self, start: usize, end: usize) {
+        let line = self.line_mut(self.y);
+        if end >= line.len() {
+            line.truncate(start);
+        }
+        else {
+            line[start..end].fill(b' ');
+        }
+    }
+
+    fn line_feed(&mut self) {
+        if self.y + 1 < ROWS {
+            self.y += 1;
+            return;
+        }
+
+        // scroll the first line into the scrollback buffer
+        let first = if self.lines.is_empty() {
+            Vec::new()
+        }
+        else {
+            self.lines.remove(0)
+        };
+        if self.history.len() == SCROLLBACK {
+            self.history.pop_front();
+        }
+        self.history.push_back(first);
+    }
+
+    fn reset(&mut self) {
+        self.lines.clear();
+        self.x = 0;
+        self.y = 0;
+    }
+}
+
+#[cfg(feature = "tests")]
+pub mod tests {
+    use super::*;
+
+    use m3::test::WvTester;
+    use m3::{wv_assert, wv_assert_eq, wv_run_test};
+
+    pub fn run(t: &mut dyn WvTester) {
+        wv_run_test!(t, text);
+        wv_run_test!(t, wrap);
+        wv_run_test!(t, cursor);
+        wv_run_test!(t, erase);
+        wv_run_test!(t, ignored);
+        wv_run_test!(t, scrollback);
+        wv_run_test!(t, scrollback_limit);
+        wv_run_test!(t, render);
+    }
+
+    fn screen(bytes: &[u8]) -> Screen {
+        let mut s = Screen::new();
+        s.write(bytes);
+        s
+    }
+
+    fn text(t: &mut dyn WvTester) {
+        let s = screen(b"hello\nworld\r\tx\x08y");
+        wv_assert_eq!(t, s.line(0), b"hello");
+        wv_assert_eq!(t, s.line(1), b"world   y");
+        wv_assert_eq!(t, s.line(2), b"");
+        wv_assert_eq!(t, s.history_len(), 0);
+    }
+
+    fn wrap(t: &mut dyn WvTester) {
+        let mut s = Screen::new();
+        s.write(&[b'a'; COLS]);
+        // the last column is used without wrapping
+        wv_assert_eq!(t, s.line(0), &[b'a'; COLS][..]);
+        wv_assert_eq!(t, s.line(1), b"");
+
+        s.write(b"b");
+        wv_assert_eq!(t, s.line(1), b"b");
+    }
+
+    fn cursor(t: &mut dyn WvTester) {
+        // backward, forward, up and down
+        let s = screen(b"abcd\x1b[3DX\x1b[CY\nfoo\x1b[AZ\x1b[2BW");
+        wv_assert_eq!(t, s.line(0), b"aXcZ");
+        wv_assert_eq!(t, s.line(1), b"foo");
+        wv_assert_eq!(t, s.line(2), b"    W");
+
+        // absolute positions with default and out-of-range parameters
+        let s = screen(b"\x1b[3;5HA\x1b[HB\x1b[99;999HC\x1b[2;1H\x1b[4GD\x1b[5dE");
+        wv_assert_eq!(t, s.line(0), b"B");
+        wv_assert_eq!(t, s.line(1), b"   D");
+        wv_assert_eq!(t, s.line(2), b"    A");
+        wv_assert_eq!(t, s.line(4), b"    E");
+        wv_assert_eq!(t, s.line(ROWS - 1).len(), COLS);
+        wv_assert_eq!(t, s.line(ROWS - 1)[COLS - 1], b'C');
+
+        // next and previous line
+        let s = screen(b"abc\x1b[2Ed\x1b[Fe");
+        wv_assert_eq!(t, s.line(1), b"e");
+        wv_assert_eq!(t, s.line(2), b"d");
+    }
+
+    fn erase(t: &mut dyn WvTester) {
+        // to the end, to the start, and the whole line
+        let s = screen(b"hello\x1b[3D\x1b[K\nworld\x1b[3D\x1b[1K\nfoo\x1b[2K");
+        wv_assert_eq!(t, s.line(0), b"he");
+        wv_assert_eq!(t, s.line(1), b"   ld");
+        wv_assert_eq!(t, s.line(2), b"");
+
+        // to the end of the screen
+        let s = screen(b"one\ntwo\nthree\x1b[2;2H\x1b[J");
+        wv_assert_eq!(t, s.line(0), b"one");
+        wv_assert_eq!(t, s.line(1), b"t");
+        wv_assert_eq!(t, s.line(2), b"");
+
+        // to the start of the screen
+        let s = screen(b"one\ntwo\nthree\x1b[2;2H\x1b[1J");
+        wv_assert_eq!(t, s.line(0), b"");
+        wv_assert_eq!(t, s.line(1), b"  o");
+        wv_assert_eq!(t, s.line(2), b"three");
+
+        // the whole screen and the reset keep the scrollback buffer
+        let mut s = Screen::new();
+        for _ in 0..ROWS {
+            s.write(b"line\n");
+        }
+        s.write(b"\x1b[2J");
+        wv_assert_eq!(t, s.line(0), b"");
+        wv_assert_eq!(t, s.history_len(), 1);
+        s.write(b"x\x1bcy");
+        wv_assert_eq!(t, s.line(0), b"y");
+        wv_assert_eq!(t, s.history_len(), 1);
+
+        // unless the scrollback buffer is cleared explicitly
+        s.write(b"\x1b[3J");
+        wv_assert_eq!(t, s.history_len(), 0);
+    }
+
+    fn ignored(t: &mut dyn WvTester) {
+        // attributes, private sequences, OSC and character sets do not change the contents
+        let s = screen(
+            b"\x1b[1;31mred\x1b[0m \x1b[?25l\x1b[?1049h\x1b]0;title\x07a\x1b]2;x\x1b\\b\x1b(Bc",
+        );
+        wv_assert_eq!(t, s.line(0), b"red abc");
+
+        // unknown sequences are ignored and invalid ones are aborted
+        let s = screen(b"\x1b[5~x\x1b[1\x01y\x1bZz");
+        wv_assert_eq!(t, s.line(0), b"xyz");
+    }
+
+    fn scrollback(t: &mut dyn WvTester) {
+        let mut s = Screen::new();
+        for i in 0..ROWS + 2 {
+            s.write(format!("{}\n", i).as_bytes());
+        }
+
+        // the cursor stays in the last row, so that 3 lines have been scrolled out
+        wv_assert_eq!(t, s.history_len(), 3);
+        wv_assert_eq!(t, s.line(0), b"3");
+        wv_assert_eq!(t, s.line(ROWS - 2), format!("{}", ROWS + 1).as_bytes());
+        wv_assert_eq!(t, s.line(ROWS - 1), b"");
+    }
+
+    fn scrollback_limit(t: &mut dyn WvTester) {
+        let mut s = Screen::new();
+        for i in 0..SCROLLBACK + ROWS + 10 {
+            s.write(format!("{}\n", i).as_bytes());
+        }
+
+        // the oldest lines have been dropped
+        wv_assert_eq!(t, s.history_len(), SCROLLBACK);
+        let mut out = Vec::new();
+        s.render(SCROLLBACK, &mut out);
+        let expected = format!("\x1b[H\x1b[2J{}\r\n{}\r\n", 11, 12);
+        wv_assert!(t, out.starts_with(expected.as_bytes()));
+    }
+
+    fn render(t: &mut dyn WvTester) {
+        let mut s = Screen::new();
+        s.write(b"hello\nworld");
+
+        let mut out = Vec::new();
+        s.render(0, &mut out);
+        let mut expected = b"\x1b[H\x1b[2Jhello\r\nworld".to_vec();
+        expected.extend_from_slice(&b"\r\n".repeat(ROWS - 2));
+        expected.extend_from_slice(b"\x1b[2;6H");
+        wv_assert_eq!(t, out, expected);
+
+        // scroll back by more lines than there are; the cursor is not placed
+        for _ in 0..ROWS {
+            s.write(b"\n");
+        }
+        wv_assert_eq!(t, s.history_len(), 2);
+        out.clear();
+        s.render(10, &mut out);
+        wv_assert!(t, out.starts_with(b"\x1b[H\x1b[2Jhello\r\nworld\r\n"));
+        wv_assert!(t, !out.ends_with(b"H"));
+        wv_assert_eq!(t, out.iter().filter(|b| **b == b'\n').count(), ROWS - 1);
+    }
+}
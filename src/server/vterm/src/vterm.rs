@@ -16,7 +16,9 @@
 #![no_std]
 
 mod chan;
+mod console;
 mod input;
+mod screen;
 
 use m3::cap::SelSpace;
 use m3::cell::LazyStaticRefCell;
@@ -47,18 +49,45 @@ enum SessionData {
 pub struct VTermSession {
     _serv: ServerSession,
     data: SessionData,
+    console: usize,
     parent: Option<SessId>,
     childs: Vec<SessId>,
 }
 
+fn parse_console(arg: &str) -> Result<usize, Error> {
+    let mut id = 0;
+    for a in arg.split_whitespace() {
+        match a.strip_prefix("console=") {
+            Some(c) => {
+                id = c.parse::<usize>().map_err(|_| Error::new(Code::InvArgs))?;
+                if id >= console::COUNT {
+                    return Err(Error::new(Code::InvArgs));
+                }
+            },
+            None => return Err(Error::new(Code::InvArgs)),
+        }
+    }
+    Ok(id)
+}
+
 impl RequestSession for VTermSession {
-    fn new(serv: ServerSession, _arg: &str) -> Result<Self, Error>
+    fn new(serv: ServerSession, arg: &str) -> Result<Self, Error>
     where
         Self: Sized,
     {
+        let console = parse_console(arg)?;
+
+        log!(
+            LogFlags::VTReqs,
+            "[{}] vterm::open(console={})",
+            serv.id(),
+            console
+        );
+
         Ok(VTermSession {
             _serv: serv,
             data: SessionData::Meta,
+            console,
             parent: None,
             childs: Vec::new(),
         })
@@ -102,16 +131,28 @@ impl VTermSession {
         }
     }
 
-    fn new_chan(parent: SessId, serv: ServerSession, writing: bool) -> Result<VTermSession, Error> {
-        log!(LogFlags::VTReqs, "[{}] vterm::new_chan()", serv.id());
+    fn new_chan(
+        parent: SessId,
+        serv: ServerSession,
+        console: usize,
+        writing: bool,
+    ) -> Result<VTermSession, Error> {
+        log!(
+            LogFlags::VTReqs,
+            "[{}] vterm::new_chan(console={})",
+            serv.id(),
+            console
+        );
 
         Ok(VTermSession {
             data: SessionData::Chan(chan::Channel::new(
                 serv.id(),
+                console,
                 MEM.borrow().clone(),
                 writing,
             )?),
             _serv: serv,
+            console,
             parent: Some(parent),
             childs: Vec::new(),
         })
@@ -128,14 +169,15 @@ impl VTermSession {
         let (sel, _nsid) = cli.add_connected(crt, |cli, serv, _sgate| {
             let parent_sess = Self::get_sess(cli, sid)?;
             let nsid = serv.id();
+            let console = parent_sess.console;
 
             let child_sess = match &parent_sess.data {
                 SessionData::Meta => {
                     let writing = xchg.in_args().pop::<i32>()? == 1;
-                    Self::new_chan(sid, serv, writing)
+                    Self::new_chan(sid, serv, console, writing)
                 },
 
-                SessionData::Chan(c) => Self::new_chan(sid, serv, c.is_writing()),
+                SessionData::Chan(c) => Self::new_chan(sid, serv, console, c.is_writing()),
             }?;
 
             // remember that the new session is a child of the current one
@@ -189,8 +231,33 @@ impl VTermSession {
     }
 }
 
+/// Runs the tests of the screen emulation instead of the server if -T is given
+#[cfg(feature = "tests")]
+fn run_tests() {
+    use m3::test::{DefaultWvTester, WvTester};
+    use m3::tiles::OwnActivity;
+    use m3::{env, println, wv_run_suite};
+
+    if env::args().nth(1) != Some("-T") {
+        return;
+    }
+
+    let mut tester = DefaultWvTester::default();
+    wv_run_suite!(tester, screen::tests::run);
+    println!("{}", tester);
+    OwnActivity::exit_with(if tester.failures() == 0 {
+        Code::Success
+    }
+    else {
+        Code::InvState
+    });
+}
+
 #[no_mangle]
 pub fn main() -> Result<(), Error> {
+    #[cfg(feature = "tests")]
+    run_tests();
+
     MEM.set(Rc::new(
         MemGate::new((DEF_MAX_CLIENTS * chan::BUF_SIZE) as GlobOff, Perm::RW)
             .expect("Unable to alloc memory"),
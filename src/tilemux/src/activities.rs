@@ -14,7 +14,7 @@
  */
 
 use base::boxed::Box;
-use base::cell::{LazyStaticUnsafeCell, StaticCell, StaticRefCell, StaticUnsafeCell};
+use base::cell::{LazyStaticUnsafeCell, SpinLock, StaticCell, StaticRefCell, StaticUnsafeCell};
 use base::cfg;
 use base::col::{BoxList, Vec};
 use base::errors::{Code, Error};
@@ -27,15 +27,18 @@ use base::rc::Rc;
 use base::tcu;
 use base::time::{TimeDuration, TimeInstant};
 use base::tmif;
+use base::trace;
 use base::util::math;
 use core::cmp;
 use core::ops::{Deref, DerefMut};
 use core::ptr::NonNull;
 
 use crate::arch;
+use crate::futex;
 use crate::helper;
 use crate::irqs;
 use crate::pex_env;
+use crate::pmu;
 use crate::quota::{self, PTQuota, Quota, TimeQuota};
 use crate::sendqueue;
 use crate::timer;
@@ -47,6 +50,14 @@ use paging::{Allocator, ArchPaging, Paging};
 
 pub type Id = paging::ActId;
 
+/// The crash handler an activity has registered via [`tmif::Operation::RegCrash`]
+#[derive(Copy, Clone, Debug)]
+pub struct CrashHandler {
+    pub entry: usize,
+    pub stack: VirtAddr,
+    pub state: VirtAddr,
+}
+
 struct PTAllocator {
     act: Id,
     quota: Rc<PTQuota>,
@@ -129,6 +140,7 @@ pub enum Event {
     EpInvalid,
     Timeout,
     Start,
+    Futex,
 }
 
 pub struct Activity {
@@ -137,7 +149,7 @@ pub struct Activity {
     next: Option<NonNull<Activity>>,
     aspace: Option<paging::AddrSpace<PTAllocator>>,
     frames: Vec<PhysAddr>,
-    #[cfg(any(target_arch = "riscv64", target_arch = "x86_64"))]
+    #[cfg(not(target_arch = "arm"))]
     fpu_state: arch::FPUState,
     user_state: arch::State,
     user_state_addr: VirtAddr,
@@ -145,15 +157,25 @@ pub struct Activity {
     time_quota: Rc<TimeQuota>,
     cpu_time: TimeDuration,
     ctxsws: u64,
+    pmu: pmu::State,
     wait_timeout: bool,
     wait_irq: Option<tmif::IRQId>,
     wait_ep: Option<tcu::EpId>,
+    wait_futex: bool,
     irq_mask: u32,
+    // the external interrupts the activity may register or claim (given by the kernel)
+    irq_perms: u32,
     act_reg: tcu::Reg,
     eps_start: tcu::EpId,
     cmd: helper::TCUCmdState,
     pf_state: Option<PfState>,
     cont: Option<fn(&mut Activity) -> ContResult>,
+    crash_handler: Option<CrashHandler>,
+    in_crash_handler: bool,
+    // suspended activities are kept blocked until they are resumed
+    suspended: bool,
+    // whether a suspended activity should become ready as soon as it is resumed
+    wakeup: bool,
     has_refs: bool,
 }
 
@@ -204,8 +226,9 @@ static IDLE: LazyStaticUnsafeCell<Box<Activity>> = LazyStaticUnsafeCell::default
 static OUR: LazyStaticUnsafeCell<Box<Activity>> = LazyStaticUnsafeCell::default();
 static CUR: StaticUnsafeCell<Option<Box<Activity>>> = StaticUnsafeCell::new(None);
 
-static RDY: StaticRefCell<BoxList<Activity>> = StaticRefCell::new(BoxList::new());
-static BLK: StaticRefCell<BoxList<Activity>> = StaticRefCell::new(BoxList::new());
+// the ready and blocked lists are protected by spinlocks so that they can be shared between cores
+static RDY: SpinLock<BoxList<Activity>> = SpinLock::new(BoxList::new());
+static BLK: SpinLock<BoxList<Activity>> = SpinLock::new(BoxList::new());
 
 static BOOTSTRAP: StaticCell<bool> = StaticCell::new(true);
 static PTS: StaticRefCell<Vec<PhysAddr>> = StaticRefCell::new(Vec::new());
@@ -271,6 +294,7 @@ pub fn init() {
             idle_quota,
             quota::get_pt(quota::IDLE_ID).unwrap(),
             0,
+            0,
             root_pt,
         )));
         OUR.set(Box::new(Activity::new(
@@ -278,6 +302,7 @@ pub fn init() {
             our_quota,
             quota::get_pt(quota::IDLE_ID).unwrap(),
             0,
+            0,
             root_pt,
         )));
     }
@@ -304,6 +329,7 @@ pub fn add(
     time_quota: quota::Id,
     pt_quota: quota::Id,
     eps_start: tcu::EpId,
+    irq_perms: u32,
 ) -> Result<(), Error> {
     log!(LogFlags::MuxActs, "Created Activity {}", id);
 
@@ -329,7 +355,9 @@ pub fn add(
         (None, None)
     };
 
-    let mut act = Box::new(Activity::new(id, time_quota, pt_quota, eps_start, root_pt));
+    let mut act = Box::new(Activity::new(
+        id, time_quota, pt_quota, eps_start, irq_perms, root_pt,
+    ));
 
     if pex_env().tile_desc.has_virtmem() {
         act.frames.push(frame.unwrap());
@@ -381,7 +409,7 @@ pub fn cur() -> ActivityRef<'static> {
 }
 
 pub fn has_ready() -> bool {
-    !RDY.borrow().is_empty()
+    !RDY.lock().is_empty()
 }
 
 pub fn schedule(mut action: ScheduleAction) -> VirtAddr {
@@ -401,6 +429,13 @@ pub fn schedule(mut action: ScheduleAction) -> VirtAddr {
         }
         act.wait_ep = None;
         act.wait_irq = None;
+        if act.wait_futex {
+            // if we are still registered as a waiter, nobody woke us up
+            if futex::remove(act.id()) {
+                act.user_state().r[isr::TMC_ARG0] = Code::Timeout as usize;
+            }
+            act.wait_futex = false;
+        }
 
         break new_state;
     };
@@ -420,19 +455,16 @@ pub fn schedule(mut action: ScheduleAction) -> VirtAddr {
 
 fn do_schedule(mut action: ScheduleAction) -> VirtAddr {
     let now = TimeInstant::now();
+    quota::refill_all_times(now);
     let mut next = RDY
-        .borrow_mut()
+        .lock()
         .pop_front()
         // safety: we know that idle is stored in a Box
         .unwrap_or_else(|| unsafe { Box::from_raw(IDLE.get_mut().as_mut()) });
 
     let old_time = if let Some(mut old) = try_cur() {
         // reduce budget now in case we decide not to switch below
-        old.time_quota.set_left(
-            old.time_quota
-                .left()
-                .saturating_sub((now - old.scheduled).as_nanos() as u64),
-        );
+        quota::charge_time(&old.time_quota, (now - old.scheduled).as_nanos() as u64);
 
         // save TCU command registers; do that first while still running with that activity
         old.cmd.save();
@@ -443,11 +475,11 @@ fn do_schedule(mut action: ScheduleAction) -> VirtAddr {
         // are there messages left we care about?
         if action == ScheduleAction::Block && !old.can_block((old_id >> 16) as u16) {
             // if the activity has budget left (or there is no one else ready), continue with it
-            if old.time_quota.left() > 0 || next.id() == kif::tilemux::IDLE_ID {
+            if !old.budget_left().is_zero() || next.id() == kif::tilemux::IDLE_ID {
                 let next_id = tcu::TCU::xchg_activity(old_id).unwrap();
                 next.set_activity_reg(next_id);
                 if next.id() != kif::tilemux::IDLE_ID {
-                    let next_budget = TimeDuration::from_nanos(next.time_quota.left());
+                    let next_budget = next.budget_left();
                     make_ready(next, next_budget);
                 }
                 else {
@@ -468,7 +500,7 @@ fn do_schedule(mut action: ScheduleAction) -> VirtAddr {
         // pass the old budget from here to make_ready below, because we might share the budget with
         // the next activity (which prevented others from running, because we would just switch between
         // these two)
-        TimeDuration::from_nanos(old.time_quota.left())
+        old.budget_left()
     }
     else {
         let old_id = tcu::TCU::xchg_activity(next.activity_reg()).unwrap();
@@ -489,17 +521,15 @@ fn do_schedule(mut action: ScheduleAction) -> VirtAddr {
     ISR::set_entry_sp(new_state + size_of::<arch::State>());
     let next_id = next.id();
     next.state = ActState::Running;
+    trace!(Instant, trace::EventId::Schedule, next_id, next_id);
 
     next.scheduled = now;
+    next.pmu.start();
     // budget is immediately refilled but we prefer other activities while a budget is 0 (see make_ready)
-    if next.time_quota.left() == 0 {
-        // to keep it simple, we divide the time slice by the number of users to ensure that activities
-        // that share a time slice don't receive more than their share in total. the better approach
-        // might be to actually schedule quotas and not activities, but that seems like overkill here.
-        next.time_quota
-            .set_left(next.time_quota.total() / next.time_quota.users());
+    if quota::time_budget(&next.time_quota) == 0 {
+        quota::refill_time(&next.time_quota);
     }
-    let next_budget = next.time_quota.left();
+    let next_budget = quota::time_budget(&next.time_quota);
 
     // restore TCU command registers
     next.cmd.restore();
@@ -511,7 +541,7 @@ fn do_schedule(mut action: ScheduleAction) -> VirtAddr {
             LogFlags::MuxCtxSws,
             "Switching from {} (budget {}) to {} (budget {}): {:?} old Activity",
             old.id(),
-            old.time_quota.left(),
+            quota::time_budget(&old.time_quota),
             next_id,
             next_budget,
             action
@@ -519,6 +549,7 @@ fn do_schedule(mut action: ScheduleAction) -> VirtAddr {
 
         old.cpu_time += now - old.scheduled;
         old.ctxsws += 1;
+        old.pmu.stop();
 
         if old.id() != kif::tilemux::IDLE_ID {
             // block, preempt or kill activity
@@ -558,17 +589,17 @@ fn do_schedule(mut action: ScheduleAction) -> VirtAddr {
 
 fn make_blocked(mut act: Box<Activity>) {
     act.state = ActState::Blocked;
-    BLK.borrow_mut().push_back(act);
+    BLK.lock().push_back(act);
 }
 
 fn make_ready(mut act: Box<Activity>, budget: TimeDuration) {
     act.state = ActState::Ready;
     // prefer activities with budget
     if !budget.is_zero() {
-        RDY.borrow_mut().push_front(act);
+        RDY.lock().push_front(act);
     }
     else {
-        RDY.borrow_mut().push_back(act);
+        RDY.lock().push_back(act);
     }
 }
 
@@ -584,8 +615,8 @@ pub fn remove(id: Id, status: Code, notify: bool, sched: bool) {
         let old = match unsafe { &v.as_ref().state } {
             // safety: we don't access `v` afterwards
             ActState::Running => unsafe { CUR.set(None).unwrap() },
-            ActState::Ready => RDY.borrow_mut().remove_if(|v| v.id() == id).unwrap(),
-            ActState::Blocked => BLK.borrow_mut().remove_if(|v| v.id() == id).unwrap(),
+            ActState::Ready => RDY.lock().remove_if(|v| v.id() == id).unwrap(),
+            ActState::Blocked => BLK.lock().remove_if(|v| v.id() == id).unwrap(),
         };
         // we now can't access `v` anymore
 
@@ -638,6 +669,7 @@ impl Activity {
         time_quota: Rc<Quota<u64>>,
         pt_quota: Rc<PTQuota>,
         eps_start: tcu::EpId,
+        irq_perms: u32,
         root_pt: Option<GlobAddr>,
     ) -> Self {
         let aspace = root_pt.map(|r| {
@@ -654,22 +686,29 @@ impl Activity {
             frames: Vec::new(),
             act_reg: id,
             state: ActState::Blocked,
-            #[cfg(any(target_arch = "riscv64", target_arch = "x86_64"))]
+            #[cfg(not(target_arch = "arm"))]
             fpu_state: arch::FPUState::default(),
             user_state: arch::State::default(),
             user_state_addr: VirtAddr::null(),
             time_quota,
             cpu_time: TimeDuration::ZERO,
             ctxsws: 0,
+            pmu: pmu::State::default(),
             scheduled: TimeInstant::now(),
             wait_timeout: false,
             wait_irq: None,
             wait_ep: None,
+            wait_futex: false,
             irq_mask: 0,
+            irq_perms,
             eps_start,
             cmd: helper::TCUCmdState::new(),
             pf_state: None,
             cont: None,
+            crash_handler: None,
+            in_crash_handler: false,
+            suspended: false,
+            wakeup: false,
             has_refs: false,
         }
     }
@@ -707,11 +746,64 @@ impl Activity {
         self.act_reg = val;
     }
 
-    #[cfg(any(target_arch = "riscv64", target_arch = "x86_64"))]
+    #[cfg(not(target_arch = "arm"))]
     pub fn fpu_state(&mut self) -> &mut arch::FPUState {
         &mut self.fpu_state
     }
 
+    pub fn set_crash_handler(&mut self, handler: CrashHandler) {
+        self.crash_handler = Some(handler);
+    }
+
+    /// Returns the crash handler, unless the activity is already running in its crash handler
+    pub fn crash_handler(&self) -> Option<CrashHandler> {
+        match self.in_crash_handler {
+            true => None,
+            false => self.crash_handler,
+        }
+    }
+
+    pub fn in_crash_handler(&self) -> bool {
+        self.in_crash_handler
+    }
+
+    pub fn set_in_crash_handler(&mut self, handling: bool) {
+        self.in_crash_handler = handling;
+    }
+
+    /// Writes the given object to `virt` in the address space of this activity, which needs to be
+    /// the current one.
+    pub fn write_user<T: Copy>(&self, virt: VirtAddr, obj: &T) -> Result<(), Error> {
+        self.check_user::<T>(virt, kif::PageFlags::RW | kif::PageFlags::U)?;
+
+        // safety: the check above has ensured that the memory is mapped and writable
+        unsafe { virt.as_mut_ptr::<T>().write_unaligned(*obj) };
+        Ok(())
+    }
+
+    /// Reads an object from `virt` in the address space of this activity, which needs to be the
+    /// current one.
+    pub fn read_user<T: Copy>(&self, virt: VirtAddr) -> Result<T, Error> {
+        self.check_user::<T>(virt, kif::PageFlags::R | kif::PageFlags::U)?;
+
+        // safety: the check above has ensured that the memory is mapped and readable
+        Ok(unsafe { virt.as_ptr::<T>().read_unaligned() })
+    }
+
+    fn check_user<T>(&self, virt: VirtAddr, perm: kif::PageFlags) -> Result<(), Error> {
+        if pex_env().tile_desc.has_virtmem() {
+            // the object might span two pages, which both need to be mapped with `perm`
+            let last = virt + (size_of::<T>() - 1);
+            for page in [virt, last] {
+                let (_phys, flags) = self.translate(page, perm);
+                if !flags.contains(perm) {
+                    return Err(Error::new(Code::InvArgs));
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub fn eps_start(&self) -> tcu::EpId {
         self.eps_start
     }
@@ -734,21 +826,25 @@ impl Activity {
     }
 
     pub fn budget_left(&self) -> TimeDuration {
-        TimeDuration::from_nanos(self.time_quota.left())
+        TimeDuration::from_nanos(quota::time_budget(&self.time_quota))
     }
 
     pub fn user_state(&mut self) -> &mut arch::State {
         &mut self.user_state
     }
 
-    pub fn reset_stats(&mut self) -> TimeDuration {
-        let now = TimeInstant::now();
-        let old_time = if self.state == ActState::Running {
-            self.cpu_time + (now - self.scheduled)
+    pub fn cpu_time(&self) -> TimeDuration {
+        if self.state == ActState::Running {
+            self.cpu_time + (TimeInstant::now() - self.scheduled)
         }
         else {
             self.cpu_time
-        };
+        }
+    }
+
+    pub fn reset_stats(&mut self) -> TimeDuration {
+        let now = TimeInstant::now();
+        let old_time = self.cpu_time();
         log!(
             LogFlags::MuxActs,
             "Activity{} consumed {:?} CPU time and was suspended {} times",
@@ -762,6 +858,10 @@ impl Activity {
         old_time
     }
 
+    pub fn pmu(&mut self) -> &mut pmu::State {
+        &mut self.pmu
+    }
+
     pub fn irq_mask(&self) -> u32 {
         self.irq_mask
     }
@@ -770,9 +870,14 @@ impl Activity {
         self.irq_mask |= 1 << irq;
     }
 
+    /// Returns whether the activity is permitted to use the external interrupt `irq`
+    pub fn may_use_irq(&self, irq: tmif::IRQId) -> bool {
+        irq < u32::BITS && (self.irq_perms & (1 << irq)) != 0
+    }
+
     fn can_block(&self, msgs: u16) -> bool {
-        // always block activities when they are waiting for a PF response
-        if self.pf_state.is_some() {
+        // always block activities when they are suspended or waiting for a PF response
+        if self.suspended || self.pf_state.is_some() || self.wait_futex {
             true
         }
         else if let Some(wep) = self.wait_ep {
@@ -809,8 +914,15 @@ impl Activity {
         }
     }
 
+    pub fn block_futex(&mut self, timeout: Option<TimeDuration>) {
+        self.wait_futex = true;
+        self.block(None, None, None, timeout);
+    }
+
     fn should_unblock(&self, event: &Event) -> bool {
         match event {
+            // futex waiters are only interested in wakeups and timeouts
+            _ if self.wait_futex => matches!(event, Event::Futex | Event::Timeout | Event::Start),
             Event::Message(eep) => match self.wait_ep {
                 // if we wait for a specific EP, only unblock if this EP got a message
                 Some(wep) => *eep == wep,
@@ -827,6 +939,7 @@ impl Activity {
             Event::Timeout => true,
             Event::EpInvalid => true,
             Event::Start => true,
+            Event::Futex => false,
         }
     }
 
@@ -847,13 +960,19 @@ impl Activity {
             return false;
         }
 
+        // suspended activities are woken up as soon as they are resumed
+        if self.suspended {
+            self.wakeup = true;
+            return true;
+        }
+
         if self.state == ActState::Blocked {
-            let mut act = BLK.borrow_mut().remove_if(|v| v.id() == self.id()).unwrap();
+            let mut act = BLK.lock().remove_if(|v| v.id() == self.id()).unwrap();
             if !matches!(event, Event::Timeout) && act.wait_timeout {
                 timer::remove(act.id());
                 act.wait_timeout = false;
             }
-            let budget = TimeDuration::from_nanos(act.time_quota.left());
+            let budget = act.budget_left();
             make_ready(act, budget);
         }
         if self.state != ActState::Running {
@@ -862,15 +981,55 @@ impl Activity {
         true
     }
 
+    pub fn suspend(&mut self) {
+        if self.suspended {
+            return;
+        }
+
+        log!(LogFlags::MuxActs, "Suspending Activity {}", self.id());
+
+        self.suspended = true;
+        match self.state {
+            ActState::Ready => {
+                let act = RDY.lock().remove_if(|v| v.id() == self.id()).unwrap();
+                make_blocked(act);
+                self.wakeup = true;
+            },
+            ActState::Running => {
+                self.wakeup = true;
+                crate::reg_scheduling(ScheduleAction::Block);
+            },
+            // blocked activities stay blocked until the event they are waiting for arrives
+            ActState::Blocked => self.wakeup = false,
+        }
+    }
+
+    pub fn resume(&mut self) {
+        if !self.suspended {
+            return;
+        }
+
+        log!(LogFlags::MuxActs, "Resuming Activity {}", self.id());
+
+        self.suspended = false;
+        if self.wakeup && self.state == ActState::Blocked {
+            let act = BLK.lock().remove_if(|v| v.id() == self.id()).unwrap();
+            let budget = act.budget_left();
+            make_ready(act, budget);
+            crate::reg_scheduling(ScheduleAction::Yield);
+        }
+        self.wakeup = false;
+    }
+
     pub fn consume_time(&mut self) {
         let now = TimeInstant::now();
         let duration = now - self.scheduled;
-        self.time_quota.set_left(
-            self.time_quota
-                .left()
-                .saturating_sub(duration.as_nanos() as u64),
-        );
-        if self.time_quota.left() == 0 && has_ready() {
+        // account the time now and start a new period; otherwise we would charge the time again on
+        // the next timer interrupt or context switch
+        self.cpu_time += duration;
+        self.scheduled = now;
+        quota::charge_time(&self.time_quota, duration.as_nanos() as u64);
+        if self.budget_left().is_zero() && has_ready() {
             crate::reg_scheduling(ScheduleAction::Preempt);
         }
     }
@@ -1117,6 +1276,9 @@ impl Drop for Activity {
         if self.wait_timeout {
             timer::remove(self.id());
         }
+        if self.wait_futex {
+            futex::remove(self.id());
+        }
         irqs::remove(self);
         arch::forget_fpu(self.id());
     }
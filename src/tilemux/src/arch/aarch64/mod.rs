@@ -0,0 +1,154 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use base::cell::StaticCell;
+use base::cpu::{CPUOps, CPU};
+use base::errors::Code;
+use base::io::LogFlags;
+use base::kif::tilemux;
+use base::log;
+use base::tmif;
+use base::{read_csr, write_csr};
+
+use crate::activities;
+
+extern "C" {
+    fn save_fpu(state: *mut usize);
+    fn restore_fpu(state: *const usize);
+}
+
+// exception class for accesses to the FPU/SIMD unit if they are trapped via CPACR_EL1
+const EC_FP_ACCESS: usize = 0x07;
+
+// FPEN field in CPACR_EL1
+const CPACR_FPEN_TRAP_EL0: usize = 0b01 << 20;
+const CPACR_FPEN_NO_TRAP: usize = 0b11 << 20;
+
+// the condition flags (N, Z, C, V) are the only bits in PSTATE that can be changed by the user
+const PSTATE_NZCV: usize = 0xF << 28;
+
+pub type State = isr::State;
+
+#[derive(Default)]
+#[repr(C, align(16))]
+pub struct FPUState {
+    // all registers are zero on the first use of the FPU
+    q: [u128; 32],
+    fpcr: usize,
+    fpsr: usize,
+}
+
+static FPU_OWNER: StaticCell<activities::Id> = StaticCell::new(tilemux::ACT_ID);
+
+pub fn init_state(state: &mut State, entry: usize, sp: usize) {
+    state.r[0] = 0xDEAD_BEEF; // don't set the stackpointer in crt0
+    state.pc = entry;
+    state.sp = sp;
+    state.pstate = 0x0; // EL0t with interrupts enabled
+    state.r[30] = 0; // lr
+}
+
+/// Converts the given state into the crash state for the crash handler of the activity
+pub fn crash_state(state: &State) -> tmif::CrashState {
+    // x0 to x30 followed by sp, pc, and pstate
+    let mut regs = [0; tmif::CRASH_REGS];
+    regs[0..31].copy_from_slice(&state.r);
+    regs[31] = state.sp;
+    regs[32] = state.pc;
+    regs[33] = state.pstate;
+    tmif::CrashState {
+        vector: state.vec,
+        addr: read_csr!("far_el1"),
+        regs,
+    }
+}
+
+/// Takes the registers from the given crash state, which the crash handler of the activity passed
+/// to TileMux to continue
+pub fn apply_crash_state(state: &mut State, crash: &tmif::CrashState) {
+    state.r.copy_from_slice(&crash.regs[0..31]);
+    state.sp = crash.regs[31];
+    state.pc = crash.regs[32];
+    // stay in EL0 with interrupts enabled
+    state.pstate = crash.regs[33] & PSTATE_NZCV;
+}
+
+/// Lets the activity continue at `entry` with stack pointer `sp` and `arg` as the first argument
+pub fn enter_handler(state: &mut State, entry: usize, sp: usize, arg: usize) {
+    state.pc = entry;
+    state.sp = sp & !0xF;
+    state.r[0] = arg; // x0
+    state.r[29] = 0; // fp
+    state.r[30] = 0; // lr
+}
+
+pub fn forget_fpu(act_id: activities::Id) {
+    if FPU_OWNER.get() == act_id {
+        FPU_OWNER.set(tilemux::ACT_ID);
+    }
+}
+
+pub fn disable_fpu() {
+    if activities::cur().id() != FPU_OWNER.get() {
+        write_csr!("cpacr_el1", CPACR_FPEN_TRAP_EL0);
+    }
+}
+
+pub fn handle_fpu_ex(state: &mut State) {
+    // we also receive undefined instructions here, which are fatal
+    if (state.esr >> 26) != EC_FP_ACCESS {
+        log!(
+            LogFlags::Error,
+            "Illegal instruction with user state:\n{:?}",
+            state
+        );
+        crate::crash(state, Code::Unspecified);
+        return;
+    }
+
+    let mut cur = activities::cur();
+
+    write_csr!("cpacr_el1", CPACR_FPEN_NO_TRAP);
+
+    let old_id = FPU_OWNER.get() & 0xFFFF;
+    if old_id != cur.id() {
+        // need to save old state?
+        if old_id != tilemux::ACT_ID {
+            let mut old_act = activities::get_mut(old_id).unwrap();
+            unsafe { save_fpu(old_act.fpu_state() as *mut FPUState as *mut usize) };
+        }
+
+        // restore new state
+        unsafe { restore_fpu(cur.fpu_state() as *const FPUState as *const usize) };
+
+        // we are owner now
+        FPU_OWNER.set(cur.id());
+    }
+}
+
+pub fn pmu_supported(counter: tmif::PmuCounter) -> bool {
+    counter == tmif::PmuCounter::Cycles
+}
+
+pub fn pmu_enable(_counter: tmif::PmuCounter) {
+    // the counter is always enabled
+}
+
+pub fn pmu_read(counter: tmif::PmuCounter) -> u64 {
+    match counter {
+        tmif::PmuCounter::Cycles => CPU::elapsed_cycles(),
+        _ => unreachable!(),
+    }
+}
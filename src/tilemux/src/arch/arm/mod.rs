@@ -16,6 +16,9 @@
  * General Public License version 2 for more details.
  */
 
+use base::cpu::{CPUOps, CPU};
+use base::tmif;
+
 use crate::activities;
 
 pub type State = isr::State;
@@ -35,3 +38,18 @@ pub fn forget_fpu(_act_id: activities::Id) {
 pub fn disable_fpu() {
     // no FPU support
 }
+
+pub fn pmu_supported(counter: tmif::PmuCounter) -> bool {
+    counter == tmif::PmuCounter::Cycles
+}
+
+pub fn pmu_enable(_counter: tmif::PmuCounter) {
+    // the cycle counter is always enabled
+}
+
+pub fn pmu_read(counter: tmif::PmuCounter) -> u64 {
+    match counter {
+        tmif::PmuCounter::Cycles => CPU::elapsed_cycles(),
+        _ => unreachable!(),
+    }
+}
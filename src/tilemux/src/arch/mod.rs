@@ -24,6 +24,10 @@ mod isa;
 #[path = "arm/mod.rs"]
 mod isa;
 
+#[cfg(target_arch = "aarch64")]
+#[path = "aarch64/mod.rs"]
+mod isa;
+
 #[cfg(target_arch = "riscv64")]
 #[path = "riscv/mod.rs"]
 mod isa;
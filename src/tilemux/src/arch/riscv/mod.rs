@@ -13,23 +13,30 @@
  * General Public License version 2 for more details.
  */
 
-use base::cell::StaticCell;
+use base::cell::{LazyStaticCell, StaticCell};
+use base::col::Vec;
 use base::errors::Code;
 use base::io::LogFlags;
 use base::kif::tilemux;
 use base::libc;
 use base::mem::MaybeUninit;
-use base::{log, read_csr, write_csr};
+use base::tmif;
+use base::{log, read_csr, vec, write_csr};
 
 use num_enum::{FromPrimitive, IntoPrimitive};
 
 use crate::activities;
 
 extern "C" {
-    fn save_fpu(state: &mut FPUState);
-    fn restore_fpu(state: &FPUState);
+    fn save_fpu(state: *mut usize);
+    fn restore_fpu(state: *const usize);
+    fn save_vec(state: *mut u64);
+    fn restore_vec(state: *const u64);
 }
 
+// vl, vtype, vstart, and vcsr in front of the vector registers
+const VEC_HEADER_WORDS: usize = 4;
+
 pub type State = isr::State;
 
 #[repr(C, align(8))]
@@ -37,6 +44,8 @@ pub struct FPUState {
     r: [MaybeUninit<usize>; 32],
     fcsr: usize,
     init: bool,
+    // allocated on the first use of the vector unit
+    vec: Option<Vec<u64>>,
 }
 
 impl Default for FPUState {
@@ -46,6 +55,7 @@ impl Default for FPUState {
             r: unsafe { MaybeUninit::uninit().assume_init() },
             fcsr: 0,
             init: false,
+            vec: None,
         }
     }
 }
@@ -61,6 +71,9 @@ enum FSMode {
 }
 
 static FPU_OWNER: StaticCell<activities::Id> = StaticCell::new(tilemux::ACT_ID);
+static VEC_OWNER: StaticCell<activities::Id> = StaticCell::new(tilemux::ACT_ID);
+// the length of a vector register in bytes or 0 if there is no vector extension
+static VLENB: LazyStaticCell<usize> = LazyStaticCell::default();
 
 fn get_fpu_mode(status: usize) -> FSMode {
     FSMode::from((status >> 13) & 0x3)
@@ -71,6 +84,33 @@ fn set_fpu_mode(mut status: usize, mode: FSMode) -> usize {
     status | (mode as usize) << 13
 }
 
+fn get_vec_mode(status: usize) -> FSMode {
+    FSMode::from((status >> 9) & 0x3)
+}
+
+fn set_vec_mode(mut status: usize, mode: FSMode) -> usize {
+    status &= !(0x3 << 9);
+    status | (mode as usize) << 9
+}
+
+/// Returns the length of a vector register in bytes or 0 if the vector extension is not supported
+fn vlenb() -> usize {
+    if !VLENB.is_some() {
+        // the VS field in sstatus is hardwired to zero without vector extension
+        let old = read_csr!("sstatus");
+        write_csr!("sstatus", set_vec_mode(old, FSMode::INITIAL));
+        let len = if get_vec_mode(read_csr!("sstatus")) != FSMode::OFF {
+            read_csr!("0xc22") // vlenb
+        }
+        else {
+            0
+        };
+        write_csr!("sstatus", old);
+        VLENB.set(len);
+    }
+    VLENB.get()
+}
+
 pub fn init_state(state: &mut State, entry: usize, sp: usize) {
     state.r[9] = 0xDEAD_BEEF; // a0; don't set the stackpointer in crt0
     state.epc = entry;
@@ -79,12 +119,45 @@ pub fn init_state(state: &mut State, entry: usize, sp: usize) {
     state.status &= !(1 << 8); // user mode
     state.status |= 1 << 5; // interrupts enabled
     state.status = set_fpu_mode(state.status, FSMode::OFF);
+    state.status = set_vec_mode(state.status, FSMode::OFF);
+}
+
+/// Converts the given state into the crash state for the crash handler of the activity
+pub fn crash_state(state: &State) -> tmif::CrashState {
+    // pc followed by x1 to x31
+    let mut regs = [0; tmif::CRASH_REGS];
+    regs[0] = state.epc;
+    regs[1..].copy_from_slice(&state.r);
+    tmif::CrashState {
+        vector: state.cause,
+        addr: read_csr!("stval"),
+        regs,
+    }
+}
+
+/// Takes the registers from the given crash state, which the crash handler of the activity passed
+/// to TileMux to continue
+pub fn apply_crash_state(state: &mut State, crash: &tmif::CrashState) {
+    state.epc = crash.regs[0];
+    state.r.copy_from_slice(&crash.regs[1..]);
+}
+
+/// Lets the activity continue at `entry` with stack pointer `sp` and `arg` as the first argument
+pub fn enter_handler(state: &mut State, entry: usize, sp: usize, arg: usize) {
+    state.epc = entry;
+    state.r[0] = 0; // ra
+    state.r[1] = sp & !0xF; // sp
+    state.r[7] = 0; // fp
+    state.r[9] = arg; // a0
 }
 
 pub fn forget_fpu(act_id: activities::Id) {
     if FPU_OWNER.get() == act_id {
         FPU_OWNER.set(tilemux::ACT_ID);
     }
+    if VEC_OWNER.get() == act_id {
+        VEC_OWNER.set(tilemux::ACT_ID);
+    }
 }
 
 pub fn disable_fpu() {
@@ -92,19 +165,34 @@ pub fn disable_fpu() {
     if cur.id() != FPU_OWNER.get() {
         cur.user_state().status = set_fpu_mode(cur.user_state().status, FSMode::OFF);
     }
+    if cur.id() != VEC_OWNER.get() {
+        cur.user_state().status = set_vec_mode(cur.user_state().status, FSMode::OFF);
+    }
 }
 
 pub fn handle_fpu_ex(state: &mut State) {
     let mut cur = activities::cur();
 
-    // if the FPU is enabled and we receive an illegal instruction exception, kill activity
+    // if the FPU is enabled, the instruction might need the vector unit. note that vector
+    // floating-point instructions need both units, so that we might enable them one after another.
+    if get_fpu_mode(state.status) != FSMode::OFF
+        && get_vec_mode(state.status) == FSMode::OFF
+        && vlenb() > 0
+    {
+        handle_vec_ex(state, &mut cur);
+        return;
+    }
+
+    // if FPU and vector unit are enabled and we receive an illegal instruction exception, kill
+    // activity
     if get_fpu_mode(state.status) != FSMode::OFF {
         log!(
             LogFlags::Error,
             "Illegal instruction with user state:\n{:?}",
             state
         );
-        activities::remove_cur(Code::Unspecified);
+        drop(cur);
+        crate::crash(state, Code::Unspecified);
         return;
     }
 
@@ -119,13 +207,13 @@ pub fn handle_fpu_ex(state: &mut State) {
         // need to save old state?
         if old_id != tilemux::ACT_ID {
             let mut old_act = activities::get_mut(old_id).unwrap();
-            unsafe { save_fpu(old_act.fpu_state()) };
+            unsafe { save_fpu(old_act.fpu_state() as *mut FPUState as *mut usize) };
         }
 
         // restore new state
         let fpu_state = cur.fpu_state();
         if fpu_state.init {
-            unsafe { restore_fpu(fpu_state) };
+            unsafe { restore_fpu(fpu_state as *const FPUState as *const usize) };
         }
         else {
             unsafe { libc::memset(fpu_state as *mut _ as *mut libc::c_void, 0, 8 * 33) };
@@ -136,3 +224,46 @@ pub fn handle_fpu_ex(state: &mut State) {
         FPU_OWNER.set(cur.id());
     }
 }
+
+fn handle_vec_ex(state: &mut State, cur: &mut activities::ActivityRef<'_>) {
+    // enable vector unit
+    state.status = set_vec_mode(state.status, FSMode::CLEAN);
+
+    let old_id = VEC_OWNER.get();
+    if old_id != cur.id() {
+        // enable vector unit so that we can save/restore the vector registers
+        write_csr!("sstatus", set_vec_mode(read_csr!("sstatus"), FSMode::CLEAN));
+
+        // need to save old state?
+        if old_id != tilemux::ACT_ID {
+            let mut old_act = activities::get_mut(old_id).unwrap();
+            let old_state = old_act.fpu_state().vec.as_mut().unwrap();
+            unsafe { save_vec(old_state.as_mut_ptr()) };
+        }
+
+        // restore new state, which starts with all registers zeroed
+        let words = VEC_HEADER_WORDS + (32 * vlenb()) / 8;
+        let vec_state = cur.fpu_state().vec.get_or_insert_with(|| vec![0; words]);
+        unsafe { restore_vec(vec_state.as_ptr()) };
+
+        // we are owner now
+        VEC_OWNER.set(cur.id());
+    }
+}
+
+pub fn pmu_supported(counter: tmif::PmuCounter) -> bool {
+    // the other counters can only be configured in machine mode
+    matches!(counter, tmif::PmuCounter::Cycles | tmif::PmuCounter::Instrs)
+}
+
+pub fn pmu_enable(_counter: tmif::PmuCounter) {
+    // cycle and instret are always enabled
+}
+
+pub fn pmu_read(counter: tmif::PmuCounter) -> u64 {
+    match counter {
+        tmif::PmuCounter::Cycles => read_csr!("cycle") as u64,
+        tmif::PmuCounter::Instrs => read_csr!("instret") as u64,
+        _ => unreachable!(),
+    }
+}
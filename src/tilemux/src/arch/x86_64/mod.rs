@@ -17,8 +17,10 @@
  */
 
 use base::cell::StaticCell;
+use base::cpu::{CPUOps, CPU};
 use base::kif::tilemux;
 use base::mem::MaybeUninit;
+use base::tmif;
 use base::{read_csr, write_csr};
 
 use core::arch::asm;
@@ -29,6 +31,25 @@ pub type State = isr::State;
 
 const CR0_TASK_SWITCHED: usize = 1 << 3;
 
+const RFLAGS_TF: usize = 1 << 8;
+const RFLAGS_IF: usize = 1 << 9;
+// CF, PF, AF, ZF, SF, TF, DF, and OF
+const USER_FLAGS: usize = 0xDD5;
+
+// the indices in `State::r` for r15, r14, r13, r12, rbp, rbx, r11, r10, r9, r8, rax, rcx, rdx, rsi,
+// and rdi, which is the order used in core files and in `tmif::CrashState`
+const CRASH_GPRS: [usize; 15] = [0, 1, 2, 3, 8, 13, 4, 5, 6, 7, 14, 12, 11, 9, 10];
+
+// the architectural performance monitoring MSRs (see Intel SDM, Vol. 3, chapter 20)
+const MSR_PMC0: u32 = 0xC1;
+const MSR_PERFEVTSEL0: u32 = 0x186;
+const MSR_FIXED_CTR0: u32 = 0x309;
+const MSR_FIXED_CTR_CTRL: u32 = 0x38D;
+const MSR_PERF_GLOBAL_CTRL: u32 = 0x38F;
+
+// the architectural event "LLC misses" (event 0x2E, umask 0x41), counted in user and kernel mode
+const PERFEVTSEL_LLC_MISSES: u64 = 0x412E | (1 << 16) | (1 << 17) | (1 << 22);
+
 static FPU_OWNER: StaticCell<activities::Id> = StaticCell::new(tilemux::ACT_ID);
 
 #[repr(C, packed)]
@@ -53,13 +74,58 @@ pub fn init_state(state: &mut State, entry: usize, sp: usize) {
     state.r[8] = 0; // rbp
     state.r[14] = 0xDEAD_BEEF; // set rax to tell crt0 that we've set the SP
 
-    state.rflags = 0x200; // enable interrupts
+    state.rflags = RFLAGS_IF; // enable interrupts
 
     // run in user mode
     state.cs = ((isr::Segment::UCode as usize) << 3) | isr::DPL::User as usize;
     state.ss = ((isr::Segment::UData as usize) << 3) | isr::DPL::User as usize;
 }
 
+/// Converts the given state into the crash state for the crash handler of the activity
+pub fn crash_state(state: &State) -> tmif::CrashState {
+    let mut regs = [0; tmif::CRASH_REGS];
+    for (i, r) in CRASH_GPRS.iter().enumerate() {
+        regs[i] = state.r[*r];
+    }
+    regs[15] = usize::MAX; // orig_rax: not in a system call
+    regs[16] = state.rip;
+    regs[17] = state.cs;
+    regs[18] = state.rflags;
+    regs[19] = state.rsp;
+    regs[20] = state.ss;
+    // fs_base and gs_base are not saved; ds, es, fs, and gs are equal to ss
+    regs[23..27].fill(state.ss);
+
+    tmif::CrashState {
+        vector: state.irq,
+        addr: read_csr!("cr2"),
+        regs,
+    }
+}
+
+/// Takes the registers from the given crash state, which the crash handler of the activity passed
+/// to TileMux to continue
+pub fn apply_crash_state(state: &mut State, crash: &tmif::CrashState) {
+    for (i, r) in CRASH_GPRS.iter().enumerate() {
+        state.r[*r] = crash.regs[i];
+    }
+    state.rip = crash.regs[16];
+    state.rsp = crash.regs[19];
+    // only allow the status flags, the trap flag, and the direction flag to be changed
+    state.rflags = (crash.regs[18] & USER_FLAGS) | RFLAGS_IF;
+}
+
+/// Lets the activity continue at `entry` with stack pointer `sp` and `arg` as the first argument
+pub fn enter_handler(state: &mut State, entry: usize, sp: usize, arg: usize) {
+    state.rip = entry;
+    // don't single step through the handler
+    state.rflags &= !RFLAGS_TF;
+    // behave as if the handler has been called (16-byte aligned before the call)
+    state.rsp = (sp & !0xF) - 8;
+    state.r[8] = 0; // rbp
+    state.r[10] = arg; // rdi
+}
+
 pub fn forget_fpu(act_id: activities::Id) {
     if FPU_OWNER.get() == act_id {
         FPU_OWNER.set(tilemux::ACT_ID);
@@ -112,3 +178,78 @@ pub fn handle_fpu_ex(_state: &mut State) {
         FPU_OWNER.set(cur.id());
     }
 }
+
+fn rdmsr(msr: u32) -> u64 {
+    let (lo, hi): (u32, u32);
+    unsafe {
+        asm!(
+            "rdmsr",
+            in("ecx") msr,
+            out("eax") lo,
+            out("edx") hi,
+            options(nomem, nostack),
+        )
+    };
+    (hi as u64) << 32 | lo as u64
+}
+
+fn wrmsr(msr: u32, val: u64) {
+    unsafe {
+        asm!(
+            "wrmsr",
+            in("ecx") msr,
+            in("eax") val as u32,
+            in("edx") (val >> 32) as u32,
+            options(nomem, nostack),
+        )
+    };
+}
+
+/// Returns the version of the architectural performance monitoring, the number of general-purpose
+/// counters, and the number of fixed-function counters
+fn pmu_info() -> (u32, u32, u32) {
+    use core::arch::x86_64::__cpuid;
+
+    // safety: cpuid is available on all x86_64 CPUs
+    if unsafe { __cpuid(0) }.eax < 0xA {
+        return (0, 0, 0);
+    }
+    let res = unsafe { __cpuid(0xA) };
+    (res.eax & 0xFF, (res.eax >> 8) & 0xFF, res.edx & 0x1F)
+}
+
+pub fn pmu_supported(counter: tmif::PmuCounter) -> bool {
+    let (version, gp_ctrs, fixed_ctrs) = pmu_info();
+    match counter {
+        tmif::PmuCounter::Cycles => true,
+        tmif::PmuCounter::Instrs => version >= 2 && fixed_ctrs >= 1,
+        tmif::PmuCounter::CacheMisses => version >= 2 && gp_ctrs >= 1,
+    }
+}
+
+pub fn pmu_enable(counter: tmif::PmuCounter) {
+    match counter {
+        // we use the TSC, which is always running
+        tmif::PmuCounter::Cycles => {},
+        tmif::PmuCounter::Instrs => {
+            // count in user and kernel mode
+            wrmsr(MSR_FIXED_CTR_CTRL, rdmsr(MSR_FIXED_CTR_CTRL) | 0x3);
+            wrmsr(
+                MSR_PERF_GLOBAL_CTRL,
+                rdmsr(MSR_PERF_GLOBAL_CTRL) | (1 << 32),
+            );
+        },
+        tmif::PmuCounter::CacheMisses => {
+            wrmsr(MSR_PERFEVTSEL0, PERFEVTSEL_LLC_MISSES);
+            wrmsr(MSR_PERF_GLOBAL_CTRL, rdmsr(MSR_PERF_GLOBAL_CTRL) | 1);
+        },
+    }
+}
+
+pub fn pmu_read(counter: tmif::PmuCounter) -> u64 {
+    match counter {
+        tmif::PmuCounter::Cycles => CPU::elapsed_cycles(),
+        tmif::PmuCounter::Instrs => rdmsr(MSR_FIXED_CTR0),
+        tmif::PmuCounter::CacheMisses => rdmsr(MSR_PMC0),
+    }
+}
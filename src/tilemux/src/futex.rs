@@ -0,0 +1,126 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+use base::cell::StaticRefCell;
+use base::col::Vec;
+use base::errors::{Code, Error};
+use base::io::LogFlags;
+use base::kif;
+use base::log;
+use base::mem::{size_of, PhysAddr, PhysAddrRaw, VirtAddr};
+use base::time::TimeDuration;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::activities;
+use crate::timer;
+
+struct Waiter {
+    addr: PhysAddr,
+    act: activities::Id,
+}
+
+// the waiting activities in FIFO order
+static WAITERS: StaticRefCell<Vec<Waiter>> = StaticRefCell::new(Vec::new());
+
+/// Determines the key for the futex at `virt` in the address space of `act`.
+///
+/// We use the physical address as the key so that activities that share memory use the same key
+/// even if they map the memory at different virtual addresses.
+fn key(act: &activities::Activity, virt: VirtAddr) -> Result<PhysAddr, Error> {
+    if virt.is_null() || virt.as_local() % size_of::<u32>() != 0 {
+        return Err(Error::new(Code::InvArgs));
+    }
+
+    if !crate::pex_env().tile_desc.has_virtmem() {
+        return Ok(PhysAddr::new_raw(virt.as_local() as PhysAddrRaw));
+    }
+
+    let perm = kif::PageFlags::R | kif::PageFlags::U;
+    let (phys, flags) = act.translate(virt, perm);
+    if !flags.contains(perm) {
+        return Err(Error::new(Code::InvArgs));
+    }
+    Ok(phys + (virt.as_local() & base::cfg::PAGE_MASK) as PhysAddrRaw)
+}
+
+pub fn wait(
+    cur: &mut activities::ActivityRef<'_>,
+    virt: VirtAddr,
+    expected: u32,
+    timeout: Option<TimeDuration>,
+) -> Result<(), Error> {
+    let addr = key(cur, virt)?;
+
+    // TileMux runs with interrupts disabled, so that nobody can change the word between the check
+    // and blocking the activity (except for other tiles, which are not supported).
+    // safety: the translation above has ensured that the word is mapped and readable
+    let val = unsafe { (*virt.as_ptr::<AtomicU32>()).load(Ordering::SeqCst) };
+    if val != expected {
+        return Err(Error::new(Code::WouldBlock));
+    }
+
+    log!(
+        LogFlags::MuxCalls,
+        "futex: Activity {} waits for {}",
+        cur.id(),
+        addr
+    );
+
+    WAITERS.borrow_mut().push(Waiter {
+        addr,
+        act: cur.id(),
+    });
+    if let Some(t) = timeout {
+        timer::add(cur.id(), t);
+    }
+    cur.block_futex(timeout);
+    Ok(())
+}
+
+pub fn wake(cur: &activities::Activity, virt: VirtAddr, count: usize) -> Result<(), Error> {
+    let addr = key(cur, virt)?;
+
+    let mut woken = Vec::new();
+    WAITERS.borrow_mut().retain(|w| {
+        if woken.len() < count && w.addr == addr {
+            woken.push(w.act);
+            false
+        }
+        else {
+            true
+        }
+    });
+
+    for id in woken {
+        log!(
+            LogFlags::MuxCalls,
+            "futex: waking up Activity {} for {}",
+            id,
+            addr
+        );
+        activities::get_mut(id)
+            .unwrap()
+            .unblock(activities::Event::Futex);
+    }
+    Ok(())
+}
+
+/// Removes activity `act` from the waiters, if it is waiting. Returns true if it was waiting.
+pub fn remove(act: activities::Id) -> bool {
+    let mut waiters = WAITERS.borrow_mut();
+    let old_len = waiters.len();
+    waiters.retain(|w| w.act != act);
+    waiters.len() != old_len
+}
@@ -14,11 +14,15 @@
  */
 
 use base::cell::StaticRefCell;
+use base::errors::{Code, Error};
 use base::io::LogFlags;
 use base::log;
+use base::mem::MsgBuf;
+use base::tcu::{self, EpId};
 use base::tmif;
 
 use crate::activities;
+use crate::helper;
 
 use isr::{ISRArch, ISR};
 
@@ -26,21 +30,120 @@ use isr::{ISRArch, ISR};
 struct IRQCounter {
     act: activities::Id,
     counter: u64,
+    // the send EP for claimed IRQs, which are delivered as messages instead of events
+    ep: Option<EpId>,
+    masked: bool,
 }
 
 const MAX_IRQS: usize = 6;
 
 static IRQS: StaticRefCell<[Option<IRQCounter>; MAX_IRQS]> = StaticRefCell::new([None; MAX_IRQS]);
 
-pub fn register(act: &mut activities::ActivityRef<'_>, irq: tmif::IRQId) {
+pub fn register(act: &mut activities::ActivityRef<'_>, irq: tmif::IRQId) -> Result<(), Error> {
     let mut irqs = IRQS.borrow_mut();
-    assert!(irqs[irq as usize].is_none());
-    irqs[irq as usize] = Some(IRQCounter {
+    let entry = irqs
+        .get_mut(irq as usize)
+        .ok_or_else(|| Error::new(Code::InvArgs))?;
+    if entry.is_some() {
+        return Err(Error::new(Code::Exists));
+    }
+
+    *entry = Some(IRQCounter {
         act: act.id(),
         counter: 0,
+        ep: None,
+        masked: false,
     });
     ISR::register_ext_irq(irq);
     act.add_irq(irq);
+    Ok(())
+}
+
+pub fn claim(act: &activities::ActivityRef<'_>, irq: tmif::IRQId, ep: EpId) -> Result<(), Error> {
+    let mut irqs = IRQS.borrow_mut();
+    let entry = irqs
+        .get_mut(irq as usize)
+        .ok_or_else(|| Error::new(Code::InvArgs))?;
+    if entry.is_some() {
+        return Err(Error::new(Code::Exists));
+    }
+
+    *entry = Some(IRQCounter {
+        act: act.id(),
+        counter: 0,
+        ep: Some(ep),
+        masked: false,
+    });
+    ISR::register_ext_irq(irq);
+    log!(LogFlags::MuxIRQs, "irqmask[{:#x}] enable", 1 << irq);
+    ISR::enable_ext_irqs(1 << irq);
+    Ok(())
+}
+
+pub fn set_masked(
+    act: &mut activities::ActivityRef<'_>,
+    irq: tmif::IRQId,
+    masked: bool,
+) -> Result<(), Error> {
+    let mut irqs = IRQS.borrow_mut();
+    let cnt = match irqs.get_mut(irq as usize) {
+        Some(Some(cnt)) if cnt.act == act.id() && cnt.ep.is_some() => cnt,
+        _ => return Err(Error::new(Code::NoPerm)),
+    };
+
+    cnt.masked = masked;
+    if masked {
+        log!(LogFlags::MuxIRQs, "irqmask[{:#x}] disable", 1 << irq);
+        ISR::disable_ext_irqs(1 << irq);
+    }
+    // deliver the occurrences while the IRQ was masked, if any; the IRQ stays masked then
+    else if cnt.counter == 0 || !deliver(act, irq, cnt) {
+        log!(LogFlags::MuxIRQs, "irqmask[{:#x}] enable", 1 << irq);
+        ISR::enable_ext_irqs(1 << irq);
+    }
+    Ok(())
+}
+
+/// Sends a message for the occurrences of `irq` to `act`, which owns the IRQ
+///
+/// The caller passes in the reference to the owner, because it might be the current activity, of
+/// which the caller might already hold a reference.
+fn deliver(act: &mut activities::ActivityRef<'_>, irq: tmif::IRQId, cnt: &mut IRQCounter) -> bool {
+    // save command registers to be able to send a message
+    let _cmd_saved = helper::TCUGuard::new();
+
+    // change to the activity, if required, because the EP belongs to it
+    if act.state() != activities::ActState::Running {
+        let mut cur = activities::cur();
+        let old_act = tcu::TCU::xchg_activity(act.activity_reg()).unwrap();
+        cur.set_activity_reg(old_act);
+    }
+
+    let mut msg_buf = MsgBuf::borrow_def();
+    msg_buf.set(tmif::IRQMsg {
+        irq: irq as u64,
+        count: cnt.counter,
+    });
+    let res = tcu::TCU::send(cnt.ep.unwrap(), &msg_buf, 0, tcu::NO_REPLIES);
+
+    if act.state() != activities::ActState::Running {
+        let cur = activities::cur();
+        act.set_activity_reg(tcu::TCU::xchg_activity(cur.activity_reg()).unwrap());
+    }
+
+    match res {
+        Ok(_) => {
+            log!(LogFlags::MuxIRQs, "irqs[{}] sent {}", irq, cnt.counter);
+            // keep the IRQ masked until the activity has handled the message
+            cnt.counter = 0;
+            cnt.masked = true;
+            true
+        },
+        Err(e) => {
+            log!(LogFlags::MuxIRQs, "irqs[{}] unable to send: {}", irq, e);
+            false
+        },
+    }
 }
 
 pub fn wait(
@@ -57,7 +160,7 @@ pub fn wait(
     }
     else {
         for (i, cnt) in irqs.iter_mut().flatten().enumerate() {
-            if cnt.act == cur.id() && cnt.counter > 0 {
+            if cnt.act == cur.id() && cnt.ep.is_none() && cnt.counter > 0 {
                 cnt.counter -= 1;
                 return Some(activities::Event::Interrupt(i as tmif::IRQId));
             }
@@ -72,6 +175,21 @@ pub fn wait(
 pub fn signal(irq: tmif::IRQId) {
     let mut irqs = IRQS.borrow_mut();
     if let Some(ref mut cnt) = irqs[irq as usize] {
+        if cnt.ep.is_some() {
+            cnt.counter += 1;
+            log!(LogFlags::MuxIRQs, "irqs[{}] signal -> {}", irq, cnt.counter);
+            log!(LogFlags::MuxIRQs, "irqmask[{:#x}] disable", 1 << irq);
+            ISR::disable_ext_irqs(1 << irq);
+            if !cnt.masked {
+                let mut act = activities::get_mut(cnt.act).unwrap();
+                if !deliver(&mut act, irq, cnt) {
+                    // try again when the activity unmasks the IRQ
+                    cnt.masked = true;
+                }
+            }
+            return;
+        }
+
         let mut act = activities::get_mut(cnt.act).unwrap();
         if !act.unblock(activities::Event::Interrupt(irq)) {
             cnt.counter += 1;
@@ -84,18 +202,19 @@ pub fn signal(irq: tmif::IRQId) {
 }
 
 pub fn remove(act: &activities::Activity) {
-    if act.irq_mask() != 0 {
-        let mut irqs = IRQS.borrow_mut();
-        for i in 0..MAX_IRQS {
-            let irq = &mut irqs[i];
-            if let Some(ref cnt) = irq {
-                if cnt.act == act.id() {
-                    *irq = None;
-                }
+    let mut irqs = IRQS.borrow_mut();
+    let mut mask = 0;
+    for (i, irq) in irqs.iter_mut().enumerate() {
+        if let Some(ref cnt) = irq {
+            if cnt.act == act.id() {
+                *irq = None;
+                mask |= 1 << i;
             }
         }
+    }
 
-        log!(LogFlags::MuxIRQs, "irqmask[{:#x}] disable", act.irq_mask());
-        ISR::disable_ext_irqs(act.irq_mask());
+    if mask != 0 {
+        log!(LogFlags::MuxIRQs, "irqmask[{:#x}] disable", mask);
+        ISR::disable_ext_irqs(mask);
     }
 }
@@ -0,0 +1,95 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! Virtualizes the performance counters per activity
+//!
+//! The hardware counters are free running once enabled. Each activity remembers the counter values
+//! when it was scheduled and accumulates the difference whenever it is descheduled. Thus, an
+//! activity only sees the events that occurred while it was running (including the time TileMux
+//! spent on its behalf).
+
+use base::errors::{Code, Error};
+use base::tmif::{PmuCounter, PmuValues, PMU_COUNTERS};
+
+use crate::arch;
+
+const ALL_COUNTERS: [PmuCounter; PMU_COUNTERS] = [
+    PmuCounter::Cycles,
+    PmuCounter::Instrs,
+    PmuCounter::CacheMisses,
+];
+
+#[derive(Default)]
+pub struct State {
+    enabled: usize,
+    // the counter values at the time the activity was scheduled
+    start: [u64; PMU_COUNTERS],
+    // the accumulated counter values of all previous time slices
+    total: [u64; PMU_COUNTERS],
+}
+
+impl State {
+    /// Enables the counters in `mask` and resets them. The activity has to be running.
+    pub fn configure(&mut self, mask: usize) -> Result<(), Error> {
+        if mask >> PMU_COUNTERS != 0 {
+            return Err(Error::new(Code::InvArgs));
+        }
+        if Self::counters(mask).any(|c| !arch::pmu_supported(c)) {
+            return Err(Error::new(Code::NotSup));
+        }
+
+        for c in Self::counters(mask) {
+            arch::pmu_enable(c);
+        }
+        self.enabled = mask;
+        self.total = [0; PMU_COUNTERS];
+        self.start();
+        Ok(())
+    }
+
+    /// Starts counting; called whenever the activity is scheduled
+    pub fn start(&mut self) {
+        for c in Self::counters(self.enabled) {
+            self.start[c as usize] = arch::pmu_read(c);
+        }
+    }
+
+    /// Stops counting; called whenever the activity is descheduled
+    pub fn stop(&mut self) {
+        for c in Self::counters(self.enabled) {
+            let elapsed = arch::pmu_read(c).wrapping_sub(self.start[c as usize]);
+            self.total[c as usize] += elapsed;
+        }
+    }
+
+    /// Returns the current values. The activity has to be running.
+    pub fn values(&self) -> PmuValues {
+        let mut vals = PmuValues {
+            enabled: self.enabled,
+            ..Default::default()
+        };
+        for c in Self::counters(self.enabled) {
+            let elapsed = arch::pmu_read(c).wrapping_sub(self.start[c as usize]);
+            vals.values[c as usize] = self.total[c as usize] + elapsed;
+        }
+        vals
+    }
+
+    fn counters(mask: usize) -> impl Iterator<Item = PmuCounter> {
+        ALL_COUNTERS
+            .into_iter()
+            .filter(move |c| (mask & PmuCounter::mask(&[*c])) != 0)
+    }
+}
@@ -22,7 +22,7 @@ use base::io::LogFlags;
 use base::kif;
 use base::log;
 use base::rc::Rc;
-use base::time::TimeDuration;
+use base::time::{TimeDuration, TimeInstant};
 
 use core::fmt;
 
@@ -109,6 +109,7 @@ pub type TimeQuota = Quota<u64>;
 pub type PTQuota = Quota<usize>;
 
 static NEXT_ID: StaticCell<Id> = StaticCell::new(0);
+static NEXT_REFILL: StaticCell<Option<TimeInstant>> = StaticCell::new(None);
 static TIME_QUOTAS: StaticRefCell<Vec<Rc<TimeQuota>>> = StaticRefCell::new(Vec::new());
 static PT_QUOTAS: StaticRefCell<Vec<Rc<PTQuota>>> = StaticRefCell::new(Vec::new());
 
@@ -142,6 +143,76 @@ pub fn add_def(time: TimeDuration, pts: usize) {
     NEXT_ID.set(2);
 }
 
+/// Calls `func` for the given time quota and all its ancestors
+fn for_each_time<F: FnMut(&TimeQuota)>(quota: &TimeQuota, mut func: F) {
+    func(quota);
+    let mut parent = quota.parent.and_then(get_time);
+    while let Some(p) = parent {
+        func(&p);
+        parent = p.parent.and_then(get_time);
+    }
+}
+
+/// Returns the time budget that is left for the given quota in nanoseconds
+///
+/// Time quotas are hierarchical: a derived quota shares the budget of its parent. Therefore, the
+/// budget is the minimum of the budgets left in the quota and all its ancestors.
+pub fn time_budget(quota: &TimeQuota) -> u64 {
+    let mut budget = u64::MAX;
+    for_each_time(quota, |q| budget = budget.min(q.left()));
+    budget
+}
+
+/// Charges `time` nanoseconds to the given quota and all its ancestors
+pub fn charge_time(quota: &TimeQuota, time: u64) {
+    for_each_time(quota, |q| q.set_left(q.left().saturating_sub(time)));
+}
+
+/// Refills the given quota and all exhausted ancestors
+///
+/// This is called if an activity without budget is scheduled, that is, if no activity with budget
+/// is ready.
+pub fn refill_time(quota: &TimeQuota) {
+    // to keep it simple, we divide the time slice by the number of users to ensure that activities
+    // that share a time slice don't receive more than their share in total. the better approach
+    // might be to actually schedule quotas and not activities, but that seems like overkill here.
+    if quota.left() == 0 {
+        quota.set_left(quota.total() / quota.users().max(1));
+    }
+    for_each_time(quota, |q| {
+        if q.left() == 0 {
+            q.set_left(q.total());
+        }
+    });
+}
+
+/// Refills all time quotas if the current period is over
+///
+/// A period lasts as long as the budget of the root quota, which covers all activities on this
+/// tile. Thus, a subtree of activities that exhausted the budget of its parent quota cannot run
+/// with budget again before the other activities had the chance to consume their budget.
+pub fn refill_all_times(now: TimeInstant) {
+    if NEXT_REFILL.get().map_or(false, |next| now < next) {
+        return;
+    }
+
+    let period = match get_time(kif::tilemux::DEF_QUOTA_ID) {
+        Some(root) => TimeDuration::from_nanos(root.total()),
+        // not initialized yet
+        None => return,
+    };
+
+    for q in TIME_QUOTAS.borrow().iter() {
+        q.set_left(q.total());
+    }
+    NEXT_REFILL.set(Some(now + period));
+    log!(
+        LogFlags::MuxQuotas,
+        "time-quota: refilled all (period {:?})",
+        period
+    );
+}
+
 pub fn get(time: Id, pts: Id) -> Result<(u64, u64, usize, usize), Error> {
     let ptime = get_time(time).ok_or_else(|| Error::new(Code::InvArgs))?;
     let ppt = get_pt(pts).ok_or_else(|| Error::new(Code::InvArgs))?;
@@ -196,16 +267,11 @@ pub fn derive(
     );
 
     let time_id = if let Some(t) = time {
-        let total = TimeDuration::from_nanos(ptime.total());
-        if total < t {
+        // the child shares the budget of the parent (see `time_budget`) and therefore does not
+        // take the time away from the parent, but can also never get more than the parent.
+        if TimeDuration::from_nanos(ptime.total()) < t {
             return Err(Error::new(Code::NoSpace));
         }
-        if ptime.users() > 0 && total == t {
-            return Err(Error::new(Code::InvArgs));
-        }
-
-        ptime.set_total((total - t).as_nanos() as u64);
-        ptime.set_left(ptime.left().saturating_sub(t.as_nanos() as u64));
 
         let ctime = ptime.derive(t.as_nanos() as u64)?;
         log!(
@@ -258,11 +324,7 @@ pub fn remove(time: Option<Id>, pts: Option<Id>) -> Result<(), Error> {
         assert!(id > kif::tilemux::DEF_QUOTA_ID);
         let time = get_time(id).ok_or_else(|| Error::new(Code::InvArgs))?;
         log!(LogFlags::MuxQuotas, "time-quota: removing {:?}", time);
-        // give quota back to parent object
-        if let Some(parent) = time.parent {
-            let ptime = get_time(parent).unwrap();
-            ptime.set_total(ptime.total() + time.total());
-        }
+        // nothing to give back to the parent, because the child only shared its budget
         TIME_QUOTAS.borrow_mut().retain(|q| q.id != id);
     }
 
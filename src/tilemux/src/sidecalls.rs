@@ -22,6 +22,7 @@ use base::mem::{GlobAddr, MsgBuf, VirtAddr, VirtAddrRaw};
 use base::serialize::{Deserialize, M3Deserializer};
 use base::tcu;
 use base::time::TimeDuration;
+use base::trace;
 
 use crate::activities;
 use crate::helper;
@@ -53,14 +54,15 @@ fn activity_init(msg: &'static tcu::Message) -> Result<(), Error> {
 
     log!(
         LogFlags::MuxSideCalls,
-        "sidecall::activity_init(act={}, time={}, pt={}, eps_start={})",
+        "sidecall::activity_init(act={}, time={}, pt={}, eps_start={}, irqs={:#x})",
         r.act_id,
         r.time_quota,
         r.pt_quota,
-        r.eps_start
+        r.eps_start,
+        r.irqs
     );
 
-    activities::add(r.act_id, r.time_quota, r.pt_quota, r.eps_start)
+    activities::add(r.act_id, r.time_quota, r.pt_quota, r.eps_start, r.irqs)
 }
 
 fn activity_ctrl(msg: &'static tcu::Message) -> Result<(), Error> {
@@ -87,7 +89,21 @@ fn activity_ctrl(msg: &'static tcu::Message) -> Result<(), Error> {
             Ok(())
         },
 
-        _ => {
+        kif::tilemux::ActivityOp::Suspend => {
+            activities::get_mut(r.act_id)
+                .ok_or_else(|| Error::new(Code::NotFound))?
+                .suspend();
+            Ok(())
+        },
+
+        kif::tilemux::ActivityOp::Resume => {
+            activities::get_mut(r.act_id)
+                .ok_or_else(|| Error::new(Code::NotFound))?
+                .resume();
+            Ok(())
+        },
+
+        kif::tilemux::ActivityOp::Stop => {
             // we cannot remove the current activity here; remove it via scheduling
             match activities::try_cur() {
                 Some(cur) if cur.id() == r.act_id => {
@@ -100,6 +116,20 @@ fn activity_ctrl(msg: &'static tcu::Message) -> Result<(), Error> {
     }
 }
 
+fn activity_time(msg: &'static tcu::Message) -> Result<TimeDuration, Error> {
+    let r: kif::tilemux::ActTime = get_request(msg)?;
+
+    log!(
+        LogFlags::MuxSideCalls,
+        "sidecall::activity_time(act={})",
+        r.act_id
+    );
+
+    activities::get_mut(r.act_id)
+        .map(|act| act.cpu_time())
+        .ok_or_else(|| Error::new(Code::NotFound))
+}
+
 fn map(msg: &'static tcu::Message) -> Result<(), Error> {
     let r: kif::tilemux::Map = get_request(msg)?;
 
@@ -272,6 +302,7 @@ fn reset_stats(_msg: &'static tcu::Message) -> Result<(), Error> {
 fn shutdown(msg: &'static tcu::Message) -> Result<(), Error> {
     log!(LogFlags::MuxSideCalls, "sidecall::shutdown()",);
 
+    base::trace::flush();
     base::machine::write_coverage(0);
 
     let mut reply_buf = MsgBuf::borrow_def();
@@ -300,12 +331,16 @@ fn handle_sidecall(msg: &'static tcu::Message) {
     let mut val1 = 0;
     let mut val2 = 0;
     let op: kif::tilemux::Sidecalls = de.pop().unwrap();
+    trace!(Begin, trace::EventId::Sidecall, 0, msg.as_words()[0]);
     let res = match op {
         kif::tilemux::Sidecalls::Info => info(msg).map(|t| {
             val1 = t.into();
         }),
         kif::tilemux::Sidecalls::ActInit => activity_init(msg),
         kif::tilemux::Sidecalls::ActCtrl => activity_ctrl(msg),
+        kif::tilemux::Sidecalls::ActTime => activity_time(msg).map(|t| {
+            val1 = t.as_nanos() as u64;
+        }),
         kif::tilemux::Sidecalls::Map => map(msg),
         kif::tilemux::Sidecalls::Translate => translate(msg).map(|pte| val1 = pte),
         kif::tilemux::Sidecalls::RemMsgs => rem_msgs(msg),
@@ -339,6 +374,7 @@ fn handle_sidecall(msg: &'static tcu::Message) {
         kif::tilemux::Response { val1, val2 }
     );
     reply_msg(msg, &reply_buf);
+    trace!(End, trace::EventId::Sidecall, 0);
 }
 
 #[inline(never)]
@@ -21,8 +21,10 @@ extern crate heap;
 mod activities;
 mod arch;
 mod cureq;
+mod futex;
 mod helper;
 mod irqs;
+mod pmu;
 mod quota;
 mod sendqueue;
 mod sidecalls;
@@ -41,6 +43,7 @@ use base::log;
 use base::machine;
 use base::mem;
 use base::tcu;
+use base::trace;
 
 use core::ptr;
 
@@ -128,18 +131,63 @@ pub fn reg_timer_reprogram() {
     NEED_TIMER.set(true);
 }
 
+/// Handles a crash of the current activity
+///
+/// If the activity has registered a crash handler, the activity continues in its handler, which
+/// receives the state at the time of the crash. Otherwise or if the activity crashed within its
+/// handler, the activity is removed with given exit code.
+#[cfg(not(target_arch = "arm"))]
+pub fn crash(state: &mut arch::State, code: Code) {
+    let mut cur = activities::cur();
+    if let Some(handler) = cur.crash_handler() {
+        match cur.write_user(handler.state, &arch::crash_state(state)) {
+            Ok(_) => {
+                log!(
+                    LogFlags::MuxActs,
+                    "Activity {} crashed; calling handler {:#x}",
+                    cur.id(),
+                    handler.entry
+                );
+                cur.set_in_crash_handler(true);
+                arch::enter_handler(
+                    state,
+                    handler.entry,
+                    handler.stack.as_local(),
+                    handler.state.as_local(),
+                );
+                return;
+            },
+            Err(e) => log!(
+                LogFlags::Error,
+                "Unable to pass crash state to Activity {}: {:?}",
+                cur.id(),
+                e
+            ),
+        }
+    }
+    drop(cur);
+
+    activities::remove_cur(code);
+}
+
+#[cfg(target_arch = "arm")]
+pub fn crash(_state: &mut arch::State, code: Code) {
+    activities::remove_cur(code);
+}
+
 pub extern "C" fn unexpected_irq(state: &mut arch::State) -> *mut libc::c_void {
-    log!(
-        LogFlags::Error,
-        "Unexpected IRQ with user state:\n{:?}",
-        state
-    );
-    activities::remove_cur(Code::Unspecified);
+    // breakpoints and single steps are expected if the crash handler is a debugger
+    let flags = match activities::cur().crash_handler() {
+        Some(_) => LogFlags::MuxActs,
+        None => LogFlags::Error,
+    };
+    log!(flags, "Unexpected IRQ with user state:\n{:?}", state);
+    crash(state, Code::Unspecified);
 
     leave(state)
 }
 
-#[cfg(any(target_arch = "riscv64", target_arch = "x86_64"))]
+#[cfg(not(target_arch = "arm"))]
 pub extern "C" fn fpu_ex(state: &mut arch::State) -> *mut libc::c_void {
     arch::handle_fpu_ex(state);
     leave(state)
@@ -147,8 +195,19 @@ pub extern "C" fn fpu_ex(state: &mut arch::State) -> *mut libc::c_void {
 
 pub extern "C" fn mmu_pf(state: &mut arch::State) -> *mut libc::c_void {
     let (virt, perm) = ISR::get_pf_info(state);
-    if vma::handle_pf(state, virt, perm).is_err() {
-        activities::remove_cur(Code::Unspecified);
+    trace!(
+        Instant,
+        trace::EventId::PageFault,
+        activities::cur().id(),
+        virt.as_local(),
+        perm.bits()
+    );
+    if let Err(e) = vma::handle_pf(state, virt, perm) {
+        let code = match e.code() {
+            Code::StackOverflow => Code::StackOverflow,
+            _ => Code::Unspecified,
+        };
+        crash(state, code);
     }
 
     leave(state)
@@ -221,7 +280,7 @@ pub extern "C" fn init() -> usize {
     isr::reg_all(unexpected_irq);
     ISR::reg_tm_calls(tmcall);
     ISR::reg_page_faults(mmu_pf);
-    #[cfg(any(target_arch = "riscv64", target_arch = "x86_64"))]
+    #[cfg(not(target_arch = "arm"))]
     ISR::reg_illegal_instr(fpu_ex);
     ISR::reg_cu_reqs(ext_irq);
     ISR::reg_timer(ext_irq);
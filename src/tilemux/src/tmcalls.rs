@@ -20,13 +20,15 @@ use base::io::LogFlags;
 use base::kif;
 use base::log;
 use base::mem::{GlobAddr, GlobAddrRaw, VirtAddr};
-use base::tcu::{EpId, INVALID_EP, IRQ};
+use base::tcu::{self, EpId, INVALID_EP, IRQ};
 use base::time::TimeDuration;
 use base::tmif;
+use base::trace;
 
 use isr::{ISRArch, ISR};
 
 use crate::activities;
+use crate::futex;
 use crate::irqs;
 use crate::timer;
 use crate::vma;
@@ -118,11 +120,12 @@ fn tmcall_reg_irq(state: &mut arch::State) -> Result<(), Error> {
 
     log!(LogFlags::MuxCalls, "tmcall::reg_irq(irq={:?})", irq);
 
-    // TODO validate whether the activity is allowed to use these IRQs
-
-    irqs::register(&mut activities::cur(), irq);
+    let mut cur = activities::cur();
+    if !cur.may_use_irq(irq) {
+        return Err(Error::new(Code::NoPerm));
+    }
 
-    Ok(())
+    irqs::register(&mut cur, irq)
 }
 
 fn tmcall_transl_fault(state: &mut arch::State) -> Result<(), Error> {
@@ -160,6 +163,147 @@ fn tmcall_flush_inv(_state: &mut arch::State) -> Result<(), Error> {
     Ok(())
 }
 
+fn tmcall_futex_wait(state: &mut arch::State) -> Result<(), Error> {
+    let virt = VirtAddr::from(state.r[isr::TMC_ARG1]);
+    let expected = state.r[isr::TMC_ARG2] as u32;
+    let timeout = match state.r[isr::TMC_ARG3] {
+        usize::MAX => None,
+        t => Some(TimeDuration::from_nanos(t as u64)),
+    };
+
+    log!(
+        LogFlags::MuxCalls,
+        "tmcall::futex_wait(virt={}, expected={:#x}, timeout={:?})",
+        virt,
+        expected,
+        timeout,
+    );
+
+    futex::wait(&mut activities::cur(), virt, expected, timeout)
+}
+
+fn tmcall_futex_wake(state: &mut arch::State) -> Result<(), Error> {
+    let virt = VirtAddr::from(state.r[isr::TMC_ARG1]);
+    let count = state.r[isr::TMC_ARG2];
+
+    log!(
+        LogFlags::MuxCalls,
+        "tmcall::futex_wake(virt={}, count={})",
+        virt,
+        count,
+    );
+
+    futex::wake(&activities::cur(), virt, count)
+}
+
+#[cfg(not(target_arch = "arm"))]
+fn tmcall_reg_crash(state: &mut arch::State) -> Result<(), Error> {
+    let entry = state.r[isr::TMC_ARG1];
+    let stack = VirtAddr::from(state.r[isr::TMC_ARG2]);
+    let crash_state = VirtAddr::from(state.r[isr::TMC_ARG3]);
+
+    log!(
+        LogFlags::MuxCalls,
+        "tmcall::reg_crash(entry={:#x}, stack={}, state={})",
+        entry,
+        stack,
+        crash_state,
+    );
+
+    if entry == 0 || stack.is_null() || crash_state.is_null() {
+        return Err(Error::new(Code::InvArgs));
+    }
+
+    activities::cur().set_crash_handler(activities::CrashHandler {
+        entry,
+        stack,
+        state: crash_state,
+    });
+    Ok(())
+}
+
+#[cfg(target_arch = "arm")]
+fn tmcall_reg_crash(_state: &mut arch::State) -> Result<(), Error> {
+    Err(Error::new(Code::NotSup))
+}
+
+#[cfg(not(target_arch = "arm"))]
+fn tmcall_resume(state: &mut arch::State) -> Result<(), Error> {
+    let crash_state = VirtAddr::from(state.r[isr::TMC_ARG1]);
+
+    log!(LogFlags::MuxCalls, "tmcall::resume(state={})", crash_state);
+
+    let mut cur = activities::cur();
+    if !cur.in_crash_handler() {
+        return Err(Error::new(Code::InvState));
+    }
+
+    let crash_state = cur.read_user::<tmif::CrashState>(crash_state)?;
+    arch::apply_crash_state(state, &crash_state);
+    cur.set_in_crash_handler(false);
+    Ok(())
+}
+
+#[cfg(target_arch = "arm")]
+fn tmcall_resume(_state: &mut arch::State) -> Result<(), Error> {
+    Err(Error::new(Code::NotSup))
+}
+
+fn tmcall_pmu_config(state: &mut arch::State) -> Result<(), Error> {
+    let mask = state.r[isr::TMC_ARG1];
+
+    log!(LogFlags::MuxCalls, "tmcall::pmu_config(mask={:#x})", mask);
+
+    activities::cur().pmu().configure(mask)
+}
+
+fn tmcall_pmu_read(state: &mut arch::State) -> Result<(), Error> {
+    let vals = VirtAddr::from(state.r[isr::TMC_ARG1]);
+
+    log!(LogFlags::MuxCalls, "tmcall::pmu_read(vals={})", vals);
+
+    let mut cur = activities::cur();
+    let values = cur.pmu().values();
+    cur.write_user(vals, &values)
+}
+
+fn tmcall_claim_irq(state: &mut arch::State) -> Result<(), Error> {
+    let irq = state.r[isr::TMC_ARG1] as tmif::IRQId;
+    let ep = state.r[isr::TMC_ARG2] as EpId;
+
+    log!(
+        LogFlags::MuxCalls,
+        "tmcall::claim_irq(irq={}, ep={})",
+        irq,
+        ep
+    );
+
+    if ep < tcu::FIRST_USER_EP || ep == INVALID_EP {
+        return Err(Error::new(Code::InvArgs));
+    }
+
+    let cur = activities::cur();
+    if !cur.may_use_irq(irq) {
+        return Err(Error::new(Code::NoPerm));
+    }
+
+    irqs::claim(&cur, irq, ep)
+}
+
+fn tmcall_mask_irq(state: &mut arch::State) -> Result<(), Error> {
+    let irq = state.r[isr::TMC_ARG1] as tmif::IRQId;
+    let masked = state.r[isr::TMC_ARG2] != 0;
+
+    log!(
+        LogFlags::MuxCalls,
+        "tmcall::mask_irq(irq={}, masked={})",
+        irq,
+        masked
+    );
+
+    irqs::set_masked(&mut activities::cur(), irq, masked)
+}
+
 fn tmcall_noop(_state: &mut arch::State) -> Result<(), Error> {
     log!(LogFlags::MuxCalls, "tmcall::noop()");
 
@@ -168,6 +312,14 @@ fn tmcall_noop(_state: &mut arch::State) -> Result<(), Error> {
 
 pub fn handle_call(state: &mut arch::State) {
     let opcode = state.r[isr::TMC_ARG0];
+    // remember the id, because the call might remove the current activity
+    let act_id = if trace::ENABLED {
+        activities::cur().id()
+    }
+    else {
+        0
+    };
+    trace!(Begin, trace::EventId::TMCall, act_id, opcode);
 
     let res = match opcode {
         o if o == tmif::Operation::Wait.into() => tmcall_wait(state),
@@ -179,8 +331,22 @@ pub fn handle_call(state: &mut arch::State) {
         o if o == tmif::Operation::InitTLS.into() => tmcall_init_tls(state),
         o if o == tmif::Operation::FlushInv.into() => tmcall_flush_inv(state),
         o if o == tmif::Operation::Noop.into() => tmcall_noop(state),
+        o if o == tmif::Operation::FutexWait.into() => tmcall_futex_wait(state),
+        o if o == tmif::Operation::FutexWake.into() => tmcall_futex_wake(state),
+        o if o == tmif::Operation::RegCrash.into() => tmcall_reg_crash(state),
+        o if o == tmif::Operation::Resume.into() => tmcall_resume(state),
+        o if o == tmif::Operation::PmuConfig.into() => tmcall_pmu_config(state),
+        o if o == tmif::Operation::PmuRead.into() => tmcall_pmu_read(state),
+        o if o == tmif::Operation::ClaimIRQ.into() => tmcall_claim_irq(state),
+        o if o == tmif::Operation::MaskIRQ.into() => tmcall_mask_irq(state),
         _ => Err(Error::new(Code::InvArgs)),
     };
+    trace!(End, trace::EventId::TMCall, act_id);
+
+    // on a successful resume, the state has been replaced completely; keep the result register
+    if opcode == tmif::Operation::Resume.into() && res.is_ok() {
+        return;
+    }
 
     if let Err(e) = &res {
         log!(
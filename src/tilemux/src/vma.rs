@@ -148,6 +148,21 @@ pub fn handle_pf(state: &crate::arch::State, virt: VirtAddr, perm: PageFlags) ->
         panic!("pagefault for {} at {}", virt, state.instr_pointer());
     }
 
+    // accesses to the guard area below the stack are never resolved by the pager, but are most
+    // likely caused by a stack overflow, so that we report them as such
+    if let Some((guard, guard_size)) = crate::pex_env().tile_desc.stack_guard() {
+        if virt >= guard && virt < guard + guard_size {
+            log!(
+                LogFlags::Error,
+                "Stack overflow at {} (perm: {:?}) with user state:\n{:?}",
+                virt,
+                perm,
+                state
+            );
+            return Err(Error::new(Code::StackOverflow));
+        }
+    }
+
     if let Err(e) = send_pf(activities::cur(), virt, perm) {
         log!(
             LogFlags::Error,
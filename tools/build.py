@@ -10,6 +10,7 @@ dirs = [
     'netdbg',
     'setpgrp',
     'shm3fs',
+    'tracedec',
 ]
 
 
@@ -18,6 +18,7 @@
 #include <stdarg.h>
 #include <stdio.h>
 #include <stdlib.h>
+#include <string.h>
 #include <sys/stat.h>
 #include <sys/types.h>
 #include <time.h>
@@ -65,7 +66,12 @@ static void export_rec(const char *dest, m3::inodeno_t dirno, const char *src) {
         size_t blockcount = (inode.size + sb.blocksize - 1) / sb.blocksize;
         size_t count = 0;
         for(uint32_t i = 0; i < blockcount; ++i) {
-            read_from_block(buffer, sb.blocksize, get_block_no(inode, i));
+            m3::blockno_t block = get_block_no(inode, i);
+            // holes read as zeros
+            if(block == 0)
+                memset(buffer, 0, sb.blocksize);
+            else
+                read_from_block(buffer, sb.blocksize, block);
 
             size_t amount = i < blockcount - 1 ? sb.blocksize : inode.size - count;
             if(fwrite(buffer, 1, amount, f) != amount)
@@ -94,6 +100,8 @@ int main(int argc, char **argv) {
 
     if(fread(&sb, sizeof(sb), 1, file) != 1)
         err(1, "Unable to read superblock");
+    if(!sb.migrate())
+        errx(1, "Unsupported file system version %u (expected %u)", sb.version, m3::FS_VERSION);
     if(sb.checksum != sb.get_checksum()) {
         errx(1, "Superblock checksum is invalid (is %#010x, should be %#010x)", sb.checksum,
              sb.get_checksum());
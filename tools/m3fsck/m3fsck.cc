@@ -77,13 +77,15 @@ static void collect_blocks_and_inodes(m3::inodeno_t ino, m3::Bitmap &blocks, m3:
     }
     else {
         for(uint32_t i = 0; i < block_count; ++i) {
-            m3::blockno_t block = get_block_no(inode, i);
-            if(block == 0) {
+            m3::blockno_t block;
+            if(!find_block_no(inode, i, block)) {
                 errx(1, "Inode %u has %u blocks, but %u can't be found in extents", ino,
                      block_count, i);
                 break;
             }
-            set_block(blocks, block);
+            // files can have holes, which do not occupy blocks
+            if(block != 0)
+                set_block(blocks, block);
         }
     }
 
@@ -179,6 +181,8 @@ int main(int argc, char **argv) {
     if(fread(&sb, sizeof(sb), 1, file) != 1)
         err(1, "Unable to read superblock");
 
+    if(!sb.migrate())
+        errx(1, "Unsupported file system version %u (expected %u)", sb.version, m3::FS_VERSION);
     if(sb.checksum != sb.get_checksum()) {
         errx(1, "Superblock checksum is invalid (is %#010x, should be %#010x)", sb.checksum,
              sb.get_checksum());
@@ -48,6 +48,8 @@ static constexpr T round_up(T value, T align) {
 enum {
     MAX_BLOCKS = 1024 * 1024,
     MAX_INODES = 4096,
+    // header and commit record + the complete meta buffer of m3fs
+    JOURNAL_BLOCKS = 2 + 128,
 };
 
 m3::SuperBlock sb;
@@ -210,8 +212,12 @@ static m3::inodeno_t copy(const char *path, m3::inodeno_t parent, int level) {
     // TODO don't copy the number of links
     ino.links = st.st_nlink;
     ino.mode = st.st_mode;
+    // all files initially belong to the superuser
+    ino.uid = 0;
+    ino.gid = 0;
     ino.lastaccess = static_cast<m3::time_t>(st.st_atime);
     ino.lastmod = static_cast<m3::time_t>(st.st_mtime);
+    ino.lastchange = static_cast<m3::time_t>(st.st_ctime);
     ino.size = 0;
     for(int i = 0; i < m3::INODE_DIR_COUNT; ++i)
         ino.direct[i].start = ino.direct[i].length = 0;
@@ -306,6 +312,8 @@ int main(int argc, char **argv) {
     sb.total_inodes = strtoul(argv[4], nullptr, 0);
     sb.free_blocks = sb.total_blocks;
     sb.free_inodes = sb.total_inodes;
+    sb.version = m3::FS_VERSION;
+    sb.journal_blocks = JOURNAL_BLOCKS;
     blks_per_extent = strtoul(argv[5], nullptr, 0);
     use_rand = argc == 7 && strcmp(argv[6], "-rand");
     last_block = sb.first_data_block() - 1;
@@ -328,7 +336,7 @@ int main(int argc, char **argv) {
     if(ftruncate(fileno(file), static_cast<off_t>(sb.blocksize * sb.total_blocks)) != 0)
         err(1, "Unable to truncate the FS image");
 
-    // mark superblock, inode and block bitmap and inode blocks as occupied
+    // mark superblock, inode and block bitmap, inode blocks, and journal as occupied
     for(m3::blockno_t i = 0; i < sb.first_data_block(); ++i)
         block_bitmap->set(i);
     sb.free_blocks -= sb.first_data_block();
@@ -351,6 +359,10 @@ int main(int argc, char **argv) {
           sb.first_blockbm_block() + sb.blockbm_blocks());
     write_to_block(block_bitmap->bytes(), (sb.total_blocks + 7) / 8, sb.first_blockbm_block());
 
+    // the journal is already empty, because the image has been initialized with zeros
+    PRINT("Reserved journal in blocks %u..%u\n", sb.first_journal_block(),
+          sb.first_journal_block() + sb.journal_blocks);
+
     fclose(file);
     return 0;
 }
@@ -38,6 +38,8 @@ static void print_sb() {
     printf("  free_blocks: %u\n", sb.free_blocks);
     printf("  first_free_inode: %u\n", sb.first_free_inode);
     printf("  first_free_block: %u\n", sb.first_free_block);
+    printf("  version: %u\n", sb.version);
+    printf("  journal_blocks: %u\n", sb.journal_blocks);
 }
 
 static void print_bitmap(uint32_t total, const m3::Bitmap &bitmap) {
@@ -102,10 +104,13 @@ static void print_inode(m3::inodeno_t ino, bool all) {
     printf("  devno: %u\n", inode.devno);
     printf("  inode: %u\n", inode.inode);
     printf("  mode: %#04o\n", inode.mode);
+    printf("  uid: %u\n", inode.uid);
+    printf("  gid: %u\n", inode.gid);
     printf("  links: %u\n", inode.links);
     printf("  size: %" PRIu64 "\n", inode.size);
     print_time(inode.lastaccess, "lastaccess");
     print_time(inode.lastmod, "lastmod");
+    print_time(inode.lastchange, "lastchange");
     printf("  extents: %u\n", inode.extents);
     for(int i = 0; i < m3::INODE_DIR_COUNT; ++i) {
         printf("  direct[%d]: %4u .. %4u (%u)\n", i, inode.direct[i].start,
@@ -208,8 +213,13 @@ static void print_ino_bytes(m3::inodeno_t ino) {
     printf("Printing bytes of inode %d:\n", ino);
     m3::INode inode = read_inode(ino);
     size_t blockcount = (inode.size + sb.blocksize - 1) / sb.blocksize;
-    for(uint32_t i = 0; i < blockcount; ++i)
-        print_block_bytes(i * sb.blocksize, get_block_no(inode, i));
+    for(uint32_t i = 0; i < blockcount; ++i) {
+        m3::blockno_t block = get_block_no(inode, i);
+        if(block == 0)
+            printf("Hole at %08zx\n", static_cast<size_t>(i) * sb.blocksize);
+        else
+            print_block_bytes(i * sb.blocksize, block);
+    }
 }
 
 static void print_ino_text(m3::inodeno_t ino) {
@@ -221,7 +231,13 @@ static void print_ino_text(m3::inodeno_t ino) {
     size_t count = 0;
     for(uint32_t i = 0; i < blockcount; ++i) {
         size_t amount = i < blockcount - 1 ? sb.blocksize : inode.size - count;
-        print_block_text(get_block_no(inode, i), amount);
+        m3::blockno_t block = get_block_no(inode, i);
+        if(block == 0) {
+            for(size_t j = 0; j < amount; ++j)
+                putchar('\0');
+        }
+        else
+            print_block_text(block, amount);
         count += sb.blocksize;
     }
     putchar('\n');
@@ -295,6 +311,8 @@ int main(int argc, char **argv) {
 
     if(fread(&sb, sizeof(sb), 1, file) != 1)
         err(1, "Unable to read superblock");
+    if(!sb.migrate())
+        errx(1, "Unsupported file system version %u (expected %u)", sb.version, m3::FS_VERSION);
     if(sb.checksum != sb.get_checksum()) {
         errx(1, "Superblock checksum is invalid (is %#010x, should be %#010x)", sb.checksum,
              sb.get_checksum());
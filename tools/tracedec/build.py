@@ -0,0 +1,3 @@
+def build(gen, env):
+    bin = env.rust_exe(gen, out='tracedec')
+    env.install(gen, env['TOOLDIR'], bin)
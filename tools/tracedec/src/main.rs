@@ -0,0 +1,258 @@
+/*
+ * Copyright (C) 2024 Nils Asmussen, Barkhausen Institut
+ *
+ * This file is part of M3 (Microkernel-based SysteM for Heterogeneous Manycores).
+ *
+ * M3 is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License version 2 as
+ * published by the Free Software Foundation.
+ *
+ * M3 is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+ * General Public License version 2 for more details.
+ */
+
+//! Converts the events recorded via `base::trace` into the Chrome trace format
+//!
+//! The events are extracted from the log lines `TRACE:...` of all components, merged, and written
+//! as JSON to stdout, which can be loaded by `chrome://tracing` or https://ui.perfetto.dev.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::num::ParseIntError;
+use std::process::exit;
+
+// has to be kept in sync with base::trace::EventKind
+const KIND_BEGIN: u8 = 0;
+const KIND_END: u8 = 1;
+const KIND_INSTANT: u8 = 2;
+const KIND_COUNTER: u8 = 3;
+
+// the names of base::trace::EventId and their arguments
+const EVENTS: &[(&str, &[&str])] = &[
+    ("syscall", &["op"]),
+    ("tmcall", &["op"]),
+    ("sidecall", &["op"]),
+    ("pagefault", &["virt", "perm"]),
+    ("schedule", &["act"]),
+    ("msgsend", &["ep", "size"]),
+    ("msgrecv", &["ep", "offset"]),
+];
+const USER_EVENTS: u16 = 0x100;
+
+struct Event {
+    comp: usize,
+    time: u64,
+    kind: u8,
+    id: u16,
+    ctx: u32,
+    args: [u64; 2],
+}
+
+fn usage(prog: &str) -> ! {
+    eprintln!("Usage: {} [<log>...]", prog);
+    eprintln!();
+    eprintln!(concat!(
+        "Reads the traced events from the given logs (or stdin) and writes them in the Chrome",
+        " trace format to stdout. M3 needs to be built with M3_TRACE=1 to record events."
+    ));
+    exit(1)
+}
+
+/// Removes the escape sequences for colors from the given line
+fn strip_colors(line: &str) -> String {
+    let mut res = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1B' {
+            // skip until the final character of the escape sequence
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        }
+        else {
+            res.push(c);
+        }
+    }
+    res
+}
+
+/// Splits a log line of the form `[<tile>:<name>@<time>] <msg>` into component and message
+fn split_line(line: &str) -> Option<(&str, &str)> {
+    let start = line.find('[')?;
+    let at = start + line[start..].find('@')?;
+    let end = at + line[at..].find("] ")?;
+    Some((line[start + 1..at].trim(), &line[end + 2..]))
+}
+
+fn parse_event(comp: usize, msg: &str) -> Result<Option<Event>, ParseIntError> {
+    let fields = match msg.strip_prefix("TRACE:") {
+        Some(f) => f.trim().split(':').collect::<Vec<_>>(),
+        None => return Ok(None),
+    };
+    if fields.len() != 6 {
+        return Ok(None);
+    }
+
+    Ok(Some(Event {
+        comp,
+        time: u64::from_str_radix(fields[0], 16)?,
+        kind: u8::from_str_radix(fields[1], 16)?,
+        id: u16::from_str_radix(fields[2], 16)?,
+        ctx: u32::from_str_radix(fields[3], 16)?,
+        args: [
+            u64::from_str_radix(fields[4], 16)?,
+            u64::from_str_radix(fields[5], 16)?,
+        ],
+    }))
+}
+
+fn read_log(
+    input: Box<dyn BufRead>,
+    comps: &mut Vec<String>,
+    events: &mut Vec<Event>,
+) -> Result<(), io::Error> {
+    let mut lost = BTreeMap::new();
+
+    for line in input.lines() {
+        let line = strip_colors(&line?);
+        let (comp_name, msg) = match split_line(&line) {
+            Some(parts) => parts,
+            None => continue,
+        };
+
+        if let Some(count) = msg.strip_prefix("TRACE-LOST:") {
+            *lost.entry(comp_name.to_string()).or_insert(0) +=
+                u64::from_str_radix(count.trim(), 16).unwrap_or(0);
+            continue;
+        }
+        if !msg.starts_with("TRACE:") {
+            continue;
+        }
+
+        let comp = match comps.iter().position(|c| c == comp_name) {
+            Some(idx) => idx,
+            None => {
+                comps.push(comp_name.to_string());
+                comps.len() - 1
+            },
+        };
+
+        match parse_event(comp, msg) {
+            Ok(Some(ev)) => events.push(ev),
+            Ok(None) => {},
+            Err(e) => eprintln!("Ignoring invalid event '{}': {}", msg, e),
+        }
+    }
+
+    for (comp, count) in lost {
+        eprintln!(
+            "Warning: {} lost {} events due to a full buffer",
+            comp, count
+        );
+    }
+    Ok(())
+}
+
+fn event_name(id: u16) -> (String, &'static [&'static str]) {
+    match EVENTS.get(id as usize) {
+        Some((name, args)) => (name.to_string(), args),
+        None if id >= USER_EVENTS => (format!("user{}", id - USER_EVENTS), &["arg0", "arg1"]),
+        None => (format!("unknown{}", id), &["arg0", "arg1"]),
+    }
+}
+
+fn json_str(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn event_json(ev: &Event) -> Option<String> {
+    let (name, arg_names) = event_name(ev.id);
+    let ph = match ev.kind {
+        KIND_BEGIN => "B",
+        KIND_END => "E",
+        KIND_INSTANT => "i",
+        KIND_COUNTER => "C",
+        _ => return None,
+    };
+
+    let args = match ev.kind {
+        KIND_END => String::new(),
+        KIND_COUNTER => format!("\"{}\":{}", json_str(&name), ev.args[0]),
+        _ => arg_names
+            .iter()
+            .zip(ev.args.iter())
+            .map(|(n, v)| format!("\"{}\":\"{:#x}\"", n, v))
+            .collect::<Vec<_>>()
+            .join(","),
+    };
+
+    Some(format!(
+        "{{\"name\":\"{}\",\"ph\":\"{}\",\"ts\":{}.{:03},\"pid\":{},\"tid\":{},\"args\":{{{}}}{}}}",
+        json_str(&name),
+        ph,
+        ev.time / 1000,
+        ev.time % 1000,
+        ev.comp,
+        ev.ctx,
+        args,
+        // instant events are shown for the context only
+        if ev.kind == KIND_INSTANT {
+            ",\"s\":\"t\""
+        }
+        else {
+            ""
+        }
+    ))
+}
+
+fn main() -> Result<(), io::Error> {
+    let args: Vec<String> = env::args().collect();
+    if args.iter().skip(1).any(|a| a == "-h" || a == "--help") {
+        usage(&args[0]);
+    }
+
+    let mut comps = Vec::new();
+    let mut events = Vec::new();
+    if args.len() == 1 {
+        read_log(
+            Box::new(BufReader::new(io::stdin())),
+            &mut comps,
+            &mut events,
+        )?;
+    }
+    else {
+        for path in &args[1..] {
+            let file = File::open(path)?;
+            read_log(Box::new(BufReader::new(file)), &mut comps, &mut events)?;
+        }
+    }
+
+    // the sort is stable, so that events with the same timestamp keep their order
+    events.sort_by_key(|ev| ev.time);
+
+    // name the processes after the components
+    let entries = comps
+        .iter()
+        .enumerate()
+        .map(|(i, comp)| {
+            format!(
+                "{{\"name\":\"process_name\",\"ph\":\"M\",\"pid\":{},\"args\":{{\"name\":\"{}\"}}}}",
+                i,
+                json_str(comp)
+            )
+        })
+        .chain(events.iter().filter_map(event_json))
+        .collect::<Vec<_>>();
+
+    let stdout = io::stdout();
+    let mut out = io::BufWriter::new(stdout.lock());
+    writeln!(out, "{{\"displayTimeUnit\":\"ns\",\"traceEvents\":[")?;
+    writeln!(out, "{}", entries.join(",\n"))?;
+    writeln!(out, "]}}")
+}